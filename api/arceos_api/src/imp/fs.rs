@@ -50,6 +50,14 @@ pub fn ax_flush_file(file: &AxFileHandle) -> AxResult {
     file.0.flush()
 }
 
+pub fn ax_sync_file(file: &AxFileHandle, data_only: bool) -> AxResult {
+    if data_only {
+        file.0.sync_data()
+    } else {
+        file.0.sync_all()
+    }
+}
+
 pub fn ax_seek_file(file: &mut AxFileHandle, pos: AxSeekFrom) -> AxResult<u64> {
     file.0.seek(pos)
 }
@@ -16,6 +16,11 @@ cfg_display! {
     pub use display::*;
 }
 
+cfg_power! {
+    mod power;
+    pub use power::*;
+}
+
 mod stdio {
     use core::fmt;
 
@@ -43,6 +48,9 @@ mod time {
     pub use axhal::time::{
         TimeValue as AxTimeValue, monotonic_time as ax_monotonic_time, wall_time as ax_wall_time,
     };
+
+    #[cfg(feature = "vtime")]
+    pub use axhal::time::advance as ax_advance_time;
 }
 
 pub use self::mem::*;
@@ -0,0 +1,13 @@
+use alloc::{string::String, vec::Vec};
+
+pub use axpower::Properties as AxPowerProperties;
+
+/// Returns the names of all power supplies.
+pub fn ax_power_supplies() -> Vec<String> {
+    axpower::supplies()
+}
+
+/// Reads the properties of the power supply `name`.
+pub fn ax_power_properties(name: &str) -> crate::AxResult<AxPowerProperties> {
+    axpower::properties(name)
+}
@@ -12,6 +12,7 @@
     feature = "fs",
     feature = "net",
     feature = "multitask",
+    feature = "power",
     feature = "dummy-if-not-enabled"
 ))]
 extern crate alloc;
@@ -47,6 +48,13 @@ pub mod time {
         /// Returns the time elapsed since epoch, also known as realtime.
         pub fn ax_wall_time() -> AxTimeValue;
     }
+
+    define_api! {
+        @cfg "vtime";
+        /// Advances the virtual clock by the given duration, firing the
+        /// timers expired on the way.
+        pub fn ax_advance_time(dur: core::time::Duration);
+    }
 }
 
 /// Memory management.
@@ -231,6 +239,9 @@ pub mod fs {
         pub fn ax_truncate_file(file: &AxFileHandle, size: u64) -> AxResult;
         /// Flushes the file, writes all buffered data to the underlying device.
         pub fn ax_flush_file(file: &AxFileHandle) -> AxResult;
+        /// Writes the data of the file to the disk, and the metadata unless
+        /// `data_only`, like `fsync(2)` and `fdatasync(2)`.
+        pub fn ax_sync_file(file: &AxFileHandle, data_only: bool) -> AxResult;
         /// Sets the cursor of the file to the specified offset. Returns the new
         /// position after the seek.
         pub fn ax_seek_file(file: &mut AxFileHandle, pos: AxSeekFrom) -> AxResult<u64>;
@@ -374,6 +385,24 @@ pub mod display {
     }
 }
 
+/// Power supply operations.
+pub mod power {
+    define_api_type! {
+        @cfg "power";
+        pub type AxPowerProperties;
+    }
+
+    define_api! {
+        @cfg "power";
+        /// Returns the names of all power supplies, e.g. the batteries and the
+        /// AC adapters.
+        pub fn ax_power_supplies() -> alloc::vec::Vec<alloc::string::String>;
+        /// Reads the properties of the power supply `name`, e.g. the capacity
+        /// and the charging status of a battery.
+        pub fn ax_power_properties(name: &str) -> crate::AxResult<AxPowerProperties>;
+    }
+}
+
 /// Input/output operations.
 pub mod io {
     define_api_type! {
@@ -406,6 +435,8 @@ pub mod modules {
     pub use axmm;
     #[cfg(feature = "net")]
     pub use axnet;
+    #[cfg(feature = "power")]
+    pub use axpower;
     #[cfg(feature = "multitask")]
     pub use axtask;
 }
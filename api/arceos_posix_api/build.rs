@@ -37,6 +37,7 @@ typedef struct {{
 
         let allow_types = [
             "stat",
+            "statfs",
             "size_t",
             "ssize_t",
             "off_t",
@@ -50,24 +51,93 @@ typedef struct {{
             "pthread_mutexattr_t",
             "epoll_event",
             "iovec",
+            "msghdr",
+            "ip_mreq",
+            "linger",
             "clockid_t",
             "rlimit",
+            "rusage",
             "aibuf",
+            "mq_attr",
+            "sigevent",
+            "key_t",
+            "ipc_perm",
+            "semid_ds",
+            "sembuf",
+            "msqid_ds",
+            "shmid_ds",
+            "sigaction",
+            "sigset_t",
+            "siginfo_t",
+            "fb_.*",
+            "input_event",
+            "flock",
+            "inotify_event",
+            "cpu_set_t",
+            "timer_t",
+            "itimerspec",
+            "itimerval",
+            "dqblk",
+            "dqinfo",
         ];
         let allow_vars = [
             "CLOCK_.*",
             "O_.*",
+            "AT_.*",
             "AF_.*",
             "SOCK_.*",
             "IPPROTO_.*",
+            "IP_.*",
+            "SOL_.*",
+            "SO_.*",
+            "TCP_.*",
             "FD_.*",
             "F_.*",
+            "LOCK_.*",
+            "SPLICE_F_.*",
             "_SC_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
             "RLIMIT_.*",
+            "PRIO_.*",
+            "PR_.*",
+            "MQ_.*",
+            "PROT_.*",
+            "MAP_.*",
+            "SIG[A-Z0-9]+",
+            "SIG_.*",
+            "SIGEV_.*",
+            "SA_.*",
+            "SI_.*",
+            "IPC_.*",
+            "SEM_.*",
+            "GET(PID|VAL|ALL|NCNT|ZCNT)",
+            "SET(VAL|ALL)",
+            "MSG_.*",
+            "SHM_.*",
+            "SHMLBA",
+            "SHUT_.*",
             "EAI_.*",
+            "AI_.*",
+            "NI_.*",
             "MAXADDRS",
+            "FBIO.*",
+            "FB_.*",
+            "GRND_.*",
+            "EV_.*",
+            "EVIOC.*",
+            "SYN_.*",
+            "LED_.*",
+            "REP_.*",
+            "IN_.*",
+            "ITIMER_.*",
+            "TIMER_ABSTIME",
+            "TFD_.*",
+            "Q_.*",
+            "QIF_.*",
+            "IIF_.*",
+            "(USR|GRP|PRJ)QUOTA",
+            "SUBCMD(MASK|SHIFT)",
         ];
 
         #[derive(Debug)]
@@ -0,0 +1,75 @@
+//! The device files in `/dev`, which are opened as the files of their
+//! drivers, instead of the nodes in the filesystem.
+//!
+//! The character devices registered in axfs, e.g. `/dev/null` and
+//! `/dev/zero`, are read and written through their nodes.
+
+use alloc::sync::Arc;
+use core::ffi::c_int;
+
+use axerrno::LinuxResult;
+use axio::PollState;
+
+use crate::ctypes;
+use crate::imp::fd_ops::{FD_TABLE, FileLike};
+
+/// An opened character device registered in axfs.
+struct CharDevFile(axfs::CharDevice);
+
+impl FileLike for CharDevFile {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        Ok(self.0.read(buf)?)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        Ok(self.0.write(buf)?)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o20000 | 0o666; // S_IFCHR
+        let (major, minor) = self.0.rdev();
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_rdev: ((major << 8) | minor) as _,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// Opens the device file at the absolute `path`, called by `sys_open`,
+/// returns `None` if it's not one.
+pub(crate) fn open_device(path: &str, flags: c_int) -> LinuxResult<Option<c_int>> {
+    let nonblocking = flags as u32 & ctypes::O_NONBLOCK != 0;
+    let cloexec = flags as u32 & ctypes::O_CLOEXEC != 0;
+    let file: Arc<dyn FileLike> = match path {
+        "/dev/tty" => Arc::new(super::stdio::Tty::new(nonblocking)),
+        "/dev/random" | "/dev/urandom" => return super::random::open_random(path, cloexec),
+        #[cfg(feature = "fb")]
+        super::fb::FB_PATH => return super::fb::open_fb(cloexec).map(Some),
+        #[cfg(feature = "input")]
+        super::input::INPUT_PATH => return super::input::open_input(flags).map(Some),
+        _ => match axfs::char_device(path) {
+            Some(dev) => Arc::new(CharDevFile(dev)),
+            None => return Ok(None),
+        },
+    };
+    FD_TABLE.write().add(file, cloexec).map(Some)
+}
@@ -0,0 +1,196 @@
+//! The framebuffer device `/dev/fb0`, like the fbdev of Linux.
+//!
+//! The framebuffer of the main display is mapped by `mmap` of the device, so
+//! the applications render into it directly. Its format is got by the
+//! `FBIOGET_VSCREENINFO` and `FBIOGET_FSCREENINFO` ioctls, and the mode can
+//! not be changed. The display is refreshed by `FBIOPAN_DISPLAY`, and by a
+//! task every [`REFRESH_INTERVAL`] while the device is open, for applications
+//! that never pan.
+
+use alloc::sync::Arc;
+use core::ffi::c_int;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axdisplay::DisplayInfo;
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axtask::WaitQueue;
+
+use super::fd_ops::{FD_TABLE, FileLike, get_file_like};
+use crate::ctypes;
+use crate::uaccess::{get_user, put_user};
+
+/// The path of the device.
+pub(crate) const FB_PATH: &str = "/dev/fb0";
+/// The interval of refreshing the display while the device is open.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(16);
+/// The pixel format of the framebuffer, 32-bit BGRA.
+const BITS_PER_PIXEL: u32 = 32;
+
+/// The number of opened devices.
+static NUM_OPENED: AtomicUsize = AtomicUsize::new(0);
+static OPENED_WAIT: WaitQueue = WaitQueue::new();
+static REFRESHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// An opened framebuffer device.
+pub struct FbFile {
+    info: DisplayInfo,
+}
+
+impl FbFile {
+    pub(crate) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    /// Returns the physical address and the size of the framebuffer.
+    ///
+    /// The framebuffer is allocated from the physically contiguous pages.
+    pub(crate) fn map_region(&self) -> (usize, usize) {
+        let paddr = axhal::mem::virt_to_phys(self.info.fb_base_vaddr.into());
+        (paddr.as_usize(), self.info.fb_size)
+    }
+
+    fn var_screeninfo(&self) -> ctypes::fb_var_screeninfo {
+        let bitfield = |offset| ctypes::fb_bitfield {
+            offset,
+            length: 8,
+            msb_right: 0,
+        };
+        ctypes::fb_var_screeninfo {
+            xres: self.info.width,
+            yres: self.info.height,
+            xres_virtual: self.info.width,
+            yres_virtual: self.info.height,
+            bits_per_pixel: BITS_PER_PIXEL,
+            red: bitfield(16),
+            green: bitfield(8),
+            blue: bitfield(0),
+            transp: bitfield(24),
+            activate: ctypes::FB_ACTIVATE_NOW,
+            // the physical size is unknown
+            height: u32::MAX,
+            width: u32::MAX,
+            ..Default::default()
+        }
+    }
+
+    fn fix_screeninfo(&self) -> ctypes::fb_fix_screeninfo {
+        let mut id = [0; 16];
+        for (dst, &src) in id.iter_mut().zip(b"arceos-fb") {
+            *dst = src as _;
+        }
+        let (paddr, size) = self.map_region();
+        ctypes::fb_fix_screeninfo {
+            id,
+            smem_start: paddr as _,
+            smem_len: size as _,
+            type_: ctypes::FB_TYPE_PACKED_PIXELS,
+            visual: ctypes::FB_VISUAL_TRUECOLOR,
+            line_length: self.info.width * BITS_PER_PIXEL / 8,
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for FbFile {
+    fn drop(&mut self) {
+        NUM_OPENED.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl FileLike for FbFile {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o20000 | 0o600; // S_IFCHR
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_rdev: 29 << 8, // major number of fbdev
+            st_size: self.info.fb_size as _,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: false,
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+
+    fn ioctl(&self, cmd: u32, arg: usize) -> LinuxResult<c_int> {
+        match cmd {
+            ctypes::FBIOGET_VSCREENINFO => {
+                put_user(arg as *mut ctypes::fb_var_screeninfo, self.var_screeninfo())?;
+            }
+            ctypes::FBIOPUT_VSCREENINFO => {
+                let var = arg as *mut ctypes::fb_var_screeninfo;
+                // only the current mode is accepted
+                let current = self.var_screeninfo();
+                let requested = get_user(var)?;
+                if (requested.xres, requested.yres) != (current.xres, current.yres)
+                    || requested.xoffset != 0
+                    || requested.yoffset != 0
+                    || !matches!(requested.bits_per_pixel, 0 | BITS_PER_PIXEL)
+                {
+                    return Err(LinuxError::EINVAL);
+                }
+                put_user(var, current)?;
+            }
+            ctypes::FBIOGET_FSCREENINFO => {
+                put_user(arg as *mut ctypes::fb_fix_screeninfo, self.fix_screeninfo())?;
+            }
+            ctypes::FBIOPAN_DISPLAY => {
+                let var = get_user(arg as *const ctypes::fb_var_screeninfo)?;
+                if var.xoffset != 0 || var.yoffset != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                axdisplay::framebuffer_flush();
+            }
+            _ => return Err(LinuxError::ENOTTY),
+        }
+        Ok(0)
+    }
+}
+
+/// Refreshes the display while any device is open.
+fn refresher() {
+    loop {
+        OPENED_WAIT.wait_until(|| NUM_OPENED.load(Ordering::Acquire) > 0);
+        axdisplay::framebuffer_flush();
+        axtask::sleep(REFRESH_INTERVAL);
+    }
+}
+
+/// Opens the framebuffer device, called by `sys_open` on [`FB_PATH`].
+pub(crate) fn open_fb(cloexec: bool) -> LinuxResult<c_int> {
+    let info = axdisplay::framebuffer_info();
+    NUM_OPENED.fetch_add(1, Ordering::AcqRel);
+    let fd = FD_TABLE.write().add(Arc::new(FbFile { info }), cloexec)?;
+    if !REFRESHER_STARTED.swap(true, Ordering::AcqRel) {
+        axtask::spawn_raw(refresher, "fb-refresh".into(), axconfig::TASK_STACK_SIZE);
+    }
+    OPENED_WAIT.notify_one(false);
+    Ok(fd)
+}
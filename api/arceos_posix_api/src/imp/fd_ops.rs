@@ -1,24 +1,34 @@
-use crate::ctypes::{FD_CLOEXEC, O_NONBLOCK, O_RDWR, timespec};
+use crate::ctypes;
+use crate::ctypes::{FD_CLOEXEC, O_NONBLOCK, O_RDONLY, O_RDWR, O_WRONLY, timespec};
 use crate::imp::fd_ops::poll_flags::*;
 use crate::imp::pipe::Pipe;
 use crate::imp::stdio::{stdin, stdout};
-use crate::{File, ctypes};
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use axerrno::{LinuxError, LinuxResult};
 use axhal::time::{NANOS_PER_MICROS, NANOS_PER_SEC};
 use axio::PollState;
 use axns::{ResArc, def_resource};
-use axtask::yield_now;
+use bitmaps::Bitmap;
 use core::ffi::{c_int, c_void};
 use core::mem::replace;
 use core::ops::Deref;
 use core::ptr::drop_in_place;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
 use flatten_objects::FlattenObjects;
 use spin::RwLock;
 
 pub const AX_FILE_LIMIT: usize = 1024;
 
+/// Returns the maximum file descriptor plus one that can be allocated, i.e.
+/// the soft limit of `RLIMIT_NOFILE` of the current process.
+pub fn fd_limit() -> usize {
+    let limit = super::resources::current_limit(ctypes::RLIMIT_NOFILE).rlim_cur;
+    limit.min(AX_FILE_LIMIT as _) as usize
+}
+
 #[allow(dead_code)]
 pub trait FileLike: Send + Sync {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize>;
@@ -27,58 +37,282 @@ pub trait FileLike: Send + Sync {
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync>;
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
+    fn is_nonblocking(&self) -> bool {
+        false
+    }
+    /// Performs the device-specific request `cmd` with the argument `arg`.
+    fn ioctl(&self, _cmd: u32, _arg: usize) -> LinuxResult<c_int> {
+        Err(LinuxError::ENOTTY)
+    }
+    /// Returns the target of the link `/proc/self/fd/<fd>` to the file, e.g.
+    /// its path.
+    fn link_target(&self) -> String {
+        String::from("anon_inode:[unknown]")
+    }
+}
+
+/// An entry in the file descriptor table.
+#[derive(Clone)]
+pub struct FileDescriptor {
+    /// The opened file.
+    pub file: Arc<dyn FileLike>,
+    /// Whether the file descriptor is closed on `execve` (`FD_CLOEXEC`).
+    pub cloexec: bool,
+}
+
+/// The file descriptor table of a process.
+///
+/// All threads in a process share the same table, and a forked process gets
+/// a copy of its parent's table (see [`FdTable::fork`]).
+pub struct FdTable {
+    files: FlattenObjects<FileDescriptor, AX_FILE_LIMIT>,
+    /// The assigned file descriptors, the same as the IDs in `files`, to
+    /// find the first free one from a given descriptor.
+    used: Bitmap<AX_FILE_LIMIT>,
+}
+
+impl FdTable {
+    /// Creates an empty file descriptor table.
+    pub fn new() -> Self {
+        Self {
+            files: FlattenObjects::new(),
+            used: Bitmap::new(),
+        }
+    }
+
+    /// Returns the entry of the given file descriptor.
+    pub fn get(&self, fd: c_int) -> Option<&FileDescriptor> {
+        self.files.get(fd as usize)
+    }
+
+    /// Returns the file of the given file descriptor.
+    pub fn get_file(&self, fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
+        self.get(fd)
+            .map(|desc| desc.file.clone())
+            .ok_or(LinuxError::EBADF)
+    }
+
+    /// Adds a file to the table with the lowest available file descriptor.
+    ///
+    /// Returns `EMFILE` if none is available below [`fd_limit`].
+    pub fn add(&mut self, file: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+        let fd = self
+            .files
+            .add(FileDescriptor { file, cloexec })
+            .map_err(|_| LinuxError::EMFILE)?;
+        if fd >= fd_limit() {
+            drop(self.files.remove(fd));
+            return Err(LinuxError::EMFILE);
+        }
+        self.used.set(fd, true);
+        Ok(fd as c_int)
+    }
+
+    /// Adds a file to the table with the lowest available file descriptor
+    /// greater than or equal to `min_fd`.
+    pub fn add_from(
+        &mut self,
+        min_fd: c_int,
+        file: Arc<dyn FileLike>,
+        cloexec: bool,
+    ) -> LinuxResult<c_int> {
+        let min_fd = min_fd.max(0) as usize;
+        if min_fd >= fd_limit() {
+            return Err(LinuxError::EMFILE);
+        }
+        let fd = match min_fd {
+            0 => self.used.first_false_index(),
+            _ => self.used.next_false_index(min_fd - 1),
+        }
+        .filter(|&fd| fd < fd_limit())
+        .ok_or(LinuxError::EMFILE)?;
+        self.add_at(fd as c_int, file, cloexec)?;
+        Ok(fd as c_int)
+    }
+
+    /// Adds a file to the table with the given file descriptor, replacing the
+    /// file with the same descriptor if any.
+    ///
+    /// Returns the replaced entry, to be dropped after the table is unlocked,
+    /// as closing the file may block. Returns `EBADF` if `fd` is not below
+    /// [`fd_limit`].
+    pub fn add_at(
+        &mut self,
+        fd: c_int,
+        file: Arc<dyn FileLike>,
+        cloexec: bool,
+    ) -> LinuxResult<Option<FileDescriptor>> {
+        if fd < 0 || fd as usize >= fd_limit() {
+            return Err(LinuxError::EBADF);
+        }
+        let old = self.take(fd as usize);
+        self.files
+            .add_at(fd as usize, FileDescriptor { file, cloexec })
+            .map_err(|_| LinuxError::EMFILE)?;
+        self.used.set(fd as usize, true);
+        Ok(old)
+    }
+
+    /// Removes a file descriptor from the table.
+    pub fn remove(&mut self, fd: c_int) -> Option<FileDescriptor> {
+        if fd < 0 {
+            return None;
+        }
+        self.take(fd as usize)
+    }
+
+    /// Removes a file descriptor and marks it free.
+    fn take(&mut self, fd: usize) -> Option<FileDescriptor> {
+        let desc = self.files.remove(fd)?;
+        self.used.set(fd, false);
+        Some(desc)
+    }
+
+    /// Returns the close-on-exec flag of a file descriptor.
+    pub fn cloexec(&self, fd: c_int) -> LinuxResult<bool> {
+        self.get(fd)
+            .map(|desc| desc.cloexec)
+            .ok_or(LinuxError::EBADF)
+    }
+
+    /// Sets the close-on-exec flag of a file descriptor.
+    pub fn set_cloexec(&mut self, fd: c_int, cloexec: bool) -> LinuxResult {
+        if fd < 0 {
+            return Err(LinuxError::EBADF);
+        }
+        self.files
+            .get_mut(fd as usize)
+            .ok_or(LinuxError::EBADF)?
+            .cloexec = cloexec;
+        Ok(())
+    }
+
+    /// Returns a copy of the table, for the child process of `fork`.
+    ///
+    /// The files are shared between the two tables.
+    pub fn fork(&self) -> Self {
+        let mut new_table = FlattenObjects::new();
+        for id in self.files.ids() {
+            let _ = new_table.add_at(id, self.files.get(id).unwrap().clone());
+        }
+        Self {
+            files: new_table,
+            used: self.used,
+        }
+    }
+
+    /// Closes all file descriptors with the close-on-exec flag set, called
+    /// on `execve`.
+    pub fn close_on_exec(&mut self) {
+        let cloexec_fds: Vec<_> = self
+            .files
+            .ids()
+            .filter(|&id| self.files.get(id).unwrap().cloexec)
+            .collect();
+        for fd in cloexec_fds {
+            let desc = self.take(fd);
+            #[cfg(feature = "fs")]
+            if let Some(desc) = &desc {
+                super::fs::release_record_locks(self.lock_owner(), &desc.file);
+            }
+            drop(desc);
+        }
+    }
+
+    /// Closes all file descriptors.
+    pub fn close_all(&mut self) {
+        let all_ids: Vec<_> = self.files.ids().collect();
+        for id in all_ids {
+            let desc = self.take(id);
+            #[cfg(feature = "fs")]
+            if let Some(desc) = &desc {
+                super::fs::release_record_locks(self.lock_owner(), &desc.file);
+            }
+            drop(desc);
+        }
+    }
+
+    /// Returns the owner of the record locks placed through the table.
+    #[cfg(feature = "fs")]
+    fn lock_owner(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+impl Default for FdTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 def_resource! {
-    pub static FD_TABLE: ResArc<RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>>> = ResArc::new();
+    /// The file descriptor table of the current process.
+    ///
+    /// Threads created in the same namespace share the table, and each new
+    /// namespace (e.g., a forked process) can initialize its own copy with
+    /// [`FD_TABLE::copy_inner`].
+    pub static FD_TABLE: ResArc<RwLock<FdTable>> = ResArc::new();
 }
 
 impl FD_TABLE {
     /// Return a copy of the inner table.
-    pub fn copy_inner(&self) -> RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> {
-        let table = self.read();
-        let mut new_table = FlattenObjects::new();
-        for id in table.ids() {
-            let _ = new_table.add_at(id, table.get(id).unwrap().clone());
-        }
-        RwLock::new(new_table)
+    pub fn copy_inner(&self) -> RwLock<FdTable> {
+        RwLock::new(self.read().fork())
     }
 }
 
+/// Lists the open files of the current process as the links in
+/// `/proc/self/fd`.
+#[cfg(feature = "fs")]
+fn proc_fds() -> Vec<(u32, String)> {
+    let table = FD_TABLE.read();
+    table
+        .files
+        .ids()
+        .map(|fd| (fd as u32, table.files.get(fd).unwrap().file.link_target()))
+        .collect()
+}
+
+#[cfg(feature = "fs")]
+#[ctor_bare::register_ctor]
+fn init_proc_fds() {
+    axfs::set_proc_fds(proc_fds);
+}
+
 /// Get a file by `fd`.
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
-    FD_TABLE
-        .read()
-        .get(fd as usize)
-        .cloned()
-        .ok_or(LinuxError::EBADF)
+    FD_TABLE.read().get_file(fd)
 }
 
 /// Add a file to the file descriptor table.
 pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).map_err(|_| LinuxError::EMFILE)? as c_int)
+    FD_TABLE.write().add(f, false)
 }
 
 /// Close a file by `fd`.
 pub fn close_file_like(fd: c_int) -> LinuxResult {
-    let f = FD_TABLE
-        .write()
-        .remove(fd as usize)
-        .ok_or(LinuxError::EBADF)?;
+    let mut table = FD_TABLE.write();
+    let f = table.remove(fd).ok_or(LinuxError::EBADF)?;
+    #[cfg(feature = "fs")]
+    super::fs::release_record_locks(table.lock_owner(), &f.file);
+    drop(table);
     drop(f);
     Ok(())
 }
 
+/// Returns the owner of the record locks of the current process, as the
+/// address of its fd table, like `current->files` on Linux.
+#[cfg(feature = "fs")]
+pub(crate) fn lock_owner() -> usize {
+    FD_TABLE.read().lock_owner()
+}
+
 pub fn close_all_file_like() {
     let ref_count = FD_TABLE.ref_count();
     debug!("ref count for FD_TABLE is {}", ref_count);
 
     if ref_count == 1 {
-        let mut fd_table = FD_TABLE.write();
-        let all_ids: Vec<_> = fd_table.ids().collect();
-        for id in all_ids {
-            drop(fd_table.remove(id));
-        }
+        FD_TABLE.write().close_all();
     }
 
     let res = FD_TABLE.deref();
@@ -94,76 +328,117 @@ pub fn sys_close(fd: c_int) -> c_int {
     syscall_body!(sys_close, close_file_like(fd).map(|_| 0))
 }
 
-fn dup_fd(old_fd: c_int) -> LinuxResult<c_int> {
-    let f = get_file_like(old_fd)?;
-    let new_fd = add_file_like(f)?;
+/// Duplicate `old_fd` to the lowest available file descriptor greater than or
+/// equal to `min_fd`.
+fn dup_fd(old_fd: c_int, min_fd: c_int, cloexec: bool) -> LinuxResult<c_int> {
+    if min_fd < 0 || min_fd as usize >= fd_limit() {
+        return Err(LinuxError::EINVAL);
+    }
+    let mut fd_table = FD_TABLE.write();
+    let f = fd_table.get_file(old_fd)?;
+    fd_table.add_from(min_fd, f, cloexec)
+}
+
+/// Duplicate `old_fd` to `new_fd`, `new_fd` is closed first if it is open.
+pub(crate) fn dup_fd_to(old_fd: c_int, new_fd: c_int, cloexec: bool) -> LinuxResult<c_int> {
+    if new_fd < 0 || new_fd as usize >= fd_limit() {
+        return Err(LinuxError::EBADF);
+    }
+    let mut fd_table = FD_TABLE.write();
+    let f = fd_table.get_file(old_fd)?;
+    let old = fd_table.add_at(new_fd, f, cloexec)?;
+    #[cfg(feature = "fs")]
+    if let Some(old) = &old {
+        super::fs::release_record_locks(fd_table.lock_owner(), &old.file);
+    }
+    drop(fd_table);
+    drop(old);
     Ok(new_fd)
 }
 
 /// Duplicate a file descriptor.
 pub fn sys_dup(old_fd: c_int) -> c_int {
     debug!("sys_dup <= {}", old_fd);
-    syscall_body!(sys_dup, dup_fd(old_fd))
+    syscall_body!(sys_dup, dup_fd(old_fd, 0, false))
 }
 
 /// Duplicate a file descriptor, but it uses the file descriptor number specified in `new_fd`.
+///
+/// The close-on-exec flag of `new_fd` is cleared.
 pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
     debug!("sys_dup2 <= old_fd: {}, new_fd: {}", old_fd, new_fd);
     syscall_body!(sys_dup2, {
         if old_fd == new_fd {
-            let r = sys_fcntl(old_fd, ctypes::F_GETFD as _, 0);
-            return if r >= 0 { Ok(old_fd) } else { Ok(r) };
-        }
-        if new_fd as usize >= AX_FILE_LIMIT {
-            return Err(LinuxError::EBADF);
+            // do nothing, but check if the old fd is open
+            get_file_like(old_fd)?;
+            return Ok(new_fd);
         }
+        dup_fd_to(old_fd, new_fd, false)
+    })
+}
 
-        // check if the old fd is open
-        let f = get_file_like(old_fd)?;
-        // close the new_fd if it is already opened
-        // ignore any error during the close
-        close_file_like(new_fd).unwrap_or(());
-        FD_TABLE
-            .write()
-            .add_at(new_fd as usize, f)
-            .map_err(|_| LinuxError::EMFILE)?;
-
-        Ok(new_fd)
+/// Duplicate a file descriptor, like [`sys_dup2`], but the close-on-exec
+/// flag of `new_fd` can be set by specifying `O_CLOEXEC` in `flags`.
+///
+/// Unlike [`sys_dup2`], it fails with `EINVAL` if `old_fd` equals `new_fd`.
+pub fn sys_dup3(old_fd: c_int, new_fd: c_int, flags: c_int) -> c_int {
+    debug!(
+        "sys_dup3 <= old_fd: {}, new_fd: {}, flags: {:#o}",
+        old_fd, new_fd, flags
+    );
+    syscall_body!(sys_dup3, {
+        if old_fd == new_fd || flags as u32 & !ctypes::O_CLOEXEC != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        dup_fd_to(old_fd, new_fd, flags as u32 & ctypes::O_CLOEXEC != 0)
     })
 }
 
 /// Manipulate file descriptor.
 ///
-/// TODO: `SET/GET` command is ignored, hard-code stdin/stdout
+/// Supported commands:
+///
+/// - `F_DUPFD`/`F_DUPFD_CLOEXEC`: duplicate `fd` to the lowest available file
+///   descriptor greater than or equal to `arg`.
+/// - `F_GETFD`/`F_SETFD`: get or set the close-on-exec flag.
+/// - `F_GETFL`/`F_SETFL`: get or set the file status flags, only `O_NONBLOCK`
+///   can be changed.
+/// - `F_GETLK`/`F_SETLK`/`F_SETLKW`: test, place or remove a record lock of the
+///   process on the file, described by the `struct flock` at `arg`.
 pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
     debug!("sys_fcntl <= fd: {} cmd: {} arg: {}", fd, cmd, arg);
     syscall_body!(sys_fcntl, {
         match cmd as u32 {
-            ctypes::F_DUPFD => dup_fd(fd),
-            ctypes::F_DUPFD_CLOEXEC => {
-                // TODO: Change fd flags
-                dup_fd(fd)
+            ctypes::F_DUPFD => dup_fd(fd, arg as c_int, false),
+            ctypes::F_DUPFD_CLOEXEC => dup_fd(fd, arg as c_int, true),
+            ctypes::F_GETFD => {
+                let cloexec = FD_TABLE.read().cloexec(fd)?;
+                Ok(if cloexec { FD_CLOEXEC as _ } else { 0 })
             }
-            ctypes::F_SETFL => {
-                if fd == 0 || fd == 1 || fd == 2 {
-                    return Ok(0);
-                }
-                get_file_like(fd)?.set_nonblocking(arg & (ctypes::O_NONBLOCK as usize) > 0)?;
+            ctypes::F_SETFD => {
+                FD_TABLE
+                    .write()
+                    .set_cloexec(fd, arg & FD_CLOEXEC as usize != 0)?;
                 Ok(0)
             }
-            ctypes::F_GETFD => {
-                warn!("unsupported fcntl parameters: F_GETFD, returning FD_CLOEXEC");
-                Ok(FD_CLOEXEC as _)
-            }
             ctypes::F_GETFL => {
-                let file = get_file_like(fd)?.into_any();
-                if let Some(_) = file.downcast_ref::<File>() {
-                    warn!("unsupported fcntl parameters: F_GETFL, returning O_RDWR");
-                    Ok(O_RDWR as _)
+                let file = get_file_like(fd)?;
+                let nonblocking = if file.is_nonblocking() { O_NONBLOCK } else { 0 };
+                let file = file.into_any();
+                let access_mode = if let Some(pipe) = file.downcast_ref::<Pipe>() {
+                    if pipe.readable() { O_RDONLY } else { O_WRONLY }
                 } else {
-                    warn!("unsupported fcntl parameters: F_GETFL, returning O_NONBLOCK");
-                    Ok(O_NONBLOCK as _)
-                }
+                    O_RDWR
+                };
+                Ok((access_mode | nonblocking) as _)
+            }
+            ctypes::F_SETFL => {
+                get_file_like(fd)?.set_nonblocking(arg & (O_NONBLOCK as usize) != 0)?;
+                Ok(0)
+            }
+            #[cfg(feature = "fs")]
+            ctypes::F_GETLK | ctypes::F_SETLK | ctypes::F_SETLKW => {
+                super::fs::fcntl_lock(fd, cmd as u32, arg as *mut ctypes::flock)
             }
             _ => {
                 warn!("unsupported fcntl parameters: cmd {}", cmd);
@@ -173,21 +448,84 @@ pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
     })
 }
 
+/// Performs a device-specific request on `fd`.
+///
+/// Returns `ENOTTY` if the file does not support the request.
+pub fn sys_ioctl(fd: c_int, request: c_int, arg: usize) -> c_int {
+    debug!(
+        "sys_ioctl <= fd: {} request: {:#x} arg: {:#x}",
+        fd, request, arg
+    );
+    syscall_body!(sys_ioctl, get_file_like(fd)?.ioctl(request as u32, arg))
+}
+
 #[ctor_bare::register_ctor]
 fn init_stdio() {
-    let mut fd_table = flatten_objects::FlattenObjects::new();
-    fd_table
-        .add_at(0, Arc::new(stdin()) as _)
-        .unwrap_or_else(|_| panic!()); // stdin
-    fd_table
-        .add_at(1, Arc::new(stdout()) as _)
-        .unwrap_or_else(|_| panic!()); // stdout
-    fd_table
-        .add_at(2, Arc::new(stdout()) as _)
-        .unwrap_or_else(|_| panic!()); // stderr
+    let mut fd_table = FdTable::new();
+    fd_table.add_at(0, Arc::new(stdin()), false).unwrap(); // stdin
+    fd_table.add_at(1, Arc::new(stdout()), false).unwrap(); // stdout
+    fd_table.add_at(2, Arc::new(stdout()), false).unwrap(); // stderr
     FD_TABLE.init_new(spin::RwLock::new(fd_table));
 }
 
+/// Tasks blocked in `poll`, `select` and `epoll_wait`, woken up by the files
+/// that may have become ready.
+#[cfg(feature = "multitask")]
+static POLL_WAIT_QUEUE: axtask::WaitQueue = axtask::WaitQueue::new();
+
+/// Incremented when any file may have become ready.
+static POLL_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The maximum time to sleep before polling the files again, as not all the
+/// files notify the pollers when they become ready.
+#[cfg(feature = "multitask")]
+const MAX_POLL_IDLE: Duration = Duration::from_millis(10);
+
+/// Wakes up the tasks in `poll`, `select` and `epoll_wait` to poll their
+/// files again, as some files may have become ready.
+pub(crate) fn notify_pollers() {
+    POLL_GENERATION.fetch_add(1, Ordering::AcqRel);
+    #[cfg(feature = "multitask")]
+    POLL_WAIT_QUEUE.notify_all(false);
+}
+
+/// Returns the current generation of the poll events, to be passed to
+/// [`wait_for_poll_events`] after polling the files.
+pub(crate) fn poll_generation() -> usize {
+    POLL_GENERATION.load(Ordering::Acquire)
+}
+
+/// Waits before polling the files again, until [`notify_pollers`] is called
+/// after `generation` is taken, or the `deadline` (in wall time) is reached.
+///
+/// It sleeps for at most [`MAX_POLL_IDLE`], for the files that do not notify
+/// the pollers, and only yields if the network interfaces are not
+/// interrupt-driven and must be polled.
+pub(crate) fn wait_for_poll_events(generation: usize, deadline: Option<Duration>) {
+    #[cfg(feature = "net")]
+    if !axnet::interrupt_driven() {
+        crate::sys_sched_yield();
+        return;
+    }
+    #[cfg(feature = "multitask")]
+    {
+        let idle = axhal::time::wall_time() + MAX_POLL_IDLE;
+        let deadline = deadline.map_or(idle, |ddl| ddl.min(idle));
+        POLL_WAIT_QUEUE.wait_until_deadline(Some(deadline), || poll_generation() != generation);
+    }
+    #[cfg(not(feature = "multitask"))]
+    {
+        let _ = (generation, deadline);
+        crate::sys_sched_yield();
+    }
+}
+
+#[cfg(feature = "net")]
+#[ctor_bare::register_ctor]
+fn init_net_poll_notifier() {
+    axnet::set_event_notifier(notify_pollers);
+}
+
 pub fn sys_poll(fds: &mut [PollFd], timeout: i32) -> i32 {
     debug!("sys_poll <= fds: {:?}, timeout: {}", fds, timeout);
     syscall_body!(sys_poll, {
@@ -201,26 +539,36 @@ pub fn sys_poll(fds: &mut [PollFd], timeout: i32) -> i32 {
     })
 }
 
-pub fn sys_ppoll(fds: &mut [PollFd], timeout: *const timespec, _sigmask: *const c_void) -> i32 {
-    debug!("sys_ppoll <= fds: {:?}, timeout: {:?}", fds, timeout);
+/// Like [`sys_poll`], but the timeout is a `timespec`, and the signal mask is
+/// replaced by `sigmask` during the call if it is not NULL.
+///
+/// Only the first 64 signals of `sigmask` are used.
+pub fn sys_ppoll(fds: &mut [PollFd], timeout: *const timespec, sigmask: *const c_void) -> i32 {
+    debug!(
+        "sys_ppoll <= fds: {:?}, timeout: {:?}, sigmask: {:?}",
+        fds, timeout, sigmask
+    );
     syscall_body!(sys_poll, {
         let mut block = false;
         let mut timeout_nanos: u64 = 0;
         if timeout.is_null() {
             block = true;
         } else {
-            let secs;
-            let nsecs;
-            unsafe {
-                secs = (*timeout).tv_sec;
-                nsecs = (*timeout).tv_nsec;
-            }
+            let ts = crate::uaccess::get_user(timeout)?;
+            let (secs, nsecs) = (ts.tv_sec, ts.tv_nsec);
             if secs < 0 || nsecs < 0 || nsecs > 999_999_999 {
                 return Err(LinuxError::EINVAL);
             }
             timeout_nanos = secs as u64 * NANOS_PER_SEC + nsecs as u64;
         }
-        sys_poll_impl(fds, timeout_nanos, block)
+        #[cfg(feature = "signal")]
+        let old_mask = super::signal::replace_mask(sigmask.cast())?;
+        #[cfg(not(feature = "signal"))]
+        let _ = sigmask;
+        let res = sys_poll_impl(fds, timeout_nanos, block);
+        #[cfg(feature = "signal")]
+        super::signal::restore_mask(old_mask);
+        res
     })
 }
 
@@ -242,8 +590,9 @@ pub fn sys_poll_impl(fds: &mut [PollFd], timeout: u64, block: bool) -> LinuxResu
     for fd in fds.iter_mut() {
         fd.revents = 0;
     }
-    let now = axhal::time::monotonic_time_nanos();
+    let deadline = (!block).then(|| axhal::time::wall_time() + Duration::from_nanos(timeout));
     loop {
+        let generation = poll_generation();
         let mut updated = false;
         for fd in fds.iter_mut() {
             if fd.fd < 0 {
@@ -285,18 +634,15 @@ pub fn sys_poll_impl(fds: &mut [PollFd], timeout: u64, block: bool) -> LinuxResu
             // if any fd is updated, break
             break;
         }
-        if !block {
-            if timeout == 0 {
-                // timeout == 0 means no wait
-                break;
-            } else {
-                let elapsed = axhal::time::monotonic_time_nanos() - now;
-                if elapsed >= timeout {
-                    break;
-                }
-            }
+        if deadline.is_some_and(|ddl| axhal::time::wall_time() >= ddl) {
+            // a zero timeout means no wait
+            break;
         }
-        yield_now();
+        #[cfg(feature = "signal")]
+        if super::signal::has_pending_signal() {
+            return Err(LinuxError::EINTR);
+        }
+        wait_for_poll_events(generation, deadline);
     }
     let mut updated_count = 0;
     for fd in fds.iter() {
@@ -358,3 +704,128 @@ pub mod poll_flags {
     /// Invalid request: fd not open.
     pub const POLLNVAL: i16 = 0x0020;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyFile;
+
+    impl FileLike for DummyFile {
+        fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+            Ok(0)
+        }
+
+        fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+            Ok(buf.len())
+        }
+
+        fn stat(&self) -> LinuxResult<ctypes::stat> {
+            Err(LinuxError::EINVAL)
+        }
+
+        fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+            self
+        }
+
+        fn poll(&self) -> LinuxResult<PollState> {
+            Ok(PollState {
+                readable: true,
+                writable: true,
+            })
+        }
+
+        fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+            Ok(())
+        }
+    }
+
+    fn same_file(a: &Arc<dyn FileLike>, b: &Arc<DummyFile>) -> bool {
+        Arc::as_ptr(a) as *const u8 == Arc::as_ptr(b) as *const u8
+    }
+
+    #[test]
+    fn test_lowest_fd() {
+        let mut table = FdTable::new();
+        let file = Arc::new(DummyFile);
+        for fd in 0..3 {
+            assert_eq!(table.add(file.clone(), false), Ok(fd));
+        }
+        assert!(table.remove(1).is_some());
+        assert!(table.remove(1).is_none());
+        assert_eq!(table.add(file.clone(), false), Ok(1));
+        assert_eq!(table.add_from(5, file.clone(), false), Ok(5));
+        assert_eq!(table.add_from(0, file.clone(), false), Ok(3));
+        assert_eq!(table.add_from(5, file.clone(), false), Ok(6));
+        assert_eq!(table.add(file.clone(), false), Ok(4));
+        assert_eq!(table.add(file.clone(), false), Ok(7));
+        assert_eq!(
+            table.add_from(AX_FILE_LIMIT as _, file.clone(), false),
+            Err(LinuxError::EMFILE)
+        );
+        assert_eq!(table.get_file(8).err(), Some(LinuxError::EBADF));
+        assert_eq!(table.get_file(-1).err(), Some(LinuxError::EBADF));
+    }
+
+    #[test]
+    fn test_add_at() {
+        let mut table = FdTable::new();
+        let (a, b) = (Arc::new(DummyFile), Arc::new(DummyFile));
+        assert!(table.add_at(7, a.clone(), false).unwrap().is_none());
+        let old = table.add_at(7, b.clone(), true).unwrap().unwrap();
+        assert!(same_file(&old.file, &a) && !old.cloexec);
+        assert!(same_file(&table.get_file(7).unwrap(), &b));
+        assert_eq!(table.cloexec(7), Ok(true));
+        assert!(table.add_at(-1, a.clone(), false).is_err());
+        assert!(table.add_at(AX_FILE_LIMIT as _, a.clone(), false).is_err());
+        // the descriptors around the one placed explicitly are still free
+        assert_eq!(table.add(a.clone(), false), Ok(0));
+        assert_eq!(table.add_from(6, a.clone(), false), Ok(6));
+        assert_eq!(table.add_from(7, a.clone(), false), Ok(8));
+    }
+
+    #[test]
+    fn test_cloexec() {
+        let mut table = FdTable::new();
+        let (kept, closed) = (Arc::new(DummyFile), Arc::new(DummyFile));
+        assert_eq!(table.add(kept.clone(), false), Ok(0));
+        assert_eq!(table.add(closed.clone(), true), Ok(1));
+        assert_eq!(table.add(kept.clone(), true), Ok(2));
+        assert_eq!(table.set_cloexec(2, false), Ok(()));
+        assert_eq!(table.set_cloexec(3, true), Err(LinuxError::EBADF));
+        assert_eq!(table.cloexec(3), Err(LinuxError::EBADF));
+
+        table.close_on_exec();
+        assert!(table.get(0).is_some() && table.get(2).is_some());
+        assert!(table.get(1).is_none());
+        assert_eq!(Arc::strong_count(&closed), 1);
+        assert_eq!(Arc::strong_count(&kept), 3);
+        assert_eq!(table.add(closed.clone(), false), Ok(1));
+
+        table.close_all();
+        assert!(table.get(0).is_none());
+        assert_eq!(Arc::strong_count(&kept), 1);
+        assert_eq!(Arc::strong_count(&closed), 1);
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut parent = FdTable::new();
+        let file = Arc::new(DummyFile);
+        assert_eq!(parent.add(file.clone(), false), Ok(0));
+        assert_eq!(parent.add(file.clone(), true), Ok(1));
+        assert!(parent.add_at(3, file.clone(), false).unwrap().is_none());
+
+        let mut child = parent.fork();
+        assert_eq!(Arc::strong_count(&file), 7);
+        assert_eq!(child.cloexec(1), Ok(true));
+        assert!(child.remove(0).is_some());
+        assert!(parent.get(0).is_some());
+        assert_eq!(child.add_from(1, file.clone(), false), Ok(2));
+        assert_eq!(child.add(file.clone(), false), Ok(0));
+        assert_eq!(child.add(file.clone(), false), Ok(4));
+        assert_eq!(parent.add(file.clone(), false), Ok(2));
+        drop(child);
+        assert_eq!(Arc::strong_count(&file), 5);
+    }
+}
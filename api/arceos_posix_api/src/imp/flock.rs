@@ -0,0 +1,477 @@
+//! Advisory file locks, by `flock` and the record locks of `fcntl`.
+//!
+//! The locks of a file are kept in a list of the file, found by its
+//! [`FileId`], i.e. the filesystem and the inode holding it, so all the paths
+//! and the opened objects of a file share the locks. A `flock` lock is owned
+//! by an open file description, and released when the description is closed
+//! at last. A record lock is owned by a process, told by its fd table as on
+//! Linux, and released when the process closes any descriptor of the file.
+//!
+//! A blocking request of a record lock fails with `EDEADLK` if the owners of
+//! the locks blocking it are waiting, directly or not, for the locks of the
+//! requesting process, which would never be released.
+
+use alloc::collections::BTreeMap;
+#[cfg(feature = "multitask")]
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+#[cfg(feature = "multitask")]
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "multitask")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::fops::FileId;
+use axsync::spin::SpinNoIrq;
+#[cfg(feature = "multitask")]
+use axtask::WaitQueue;
+
+/// The type of a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// A shared (read) lock, which can be held by several owners.
+    Shared,
+    /// An exclusive (write) lock.
+    Exclusive,
+}
+
+impl LockKind {
+    fn conflicts(self, other: Self) -> bool {
+        self == Self::Exclusive || other == Self::Exclusive
+    }
+}
+
+/// A record lock on the bytes `start..end` of a file, where `end` is
+/// `u64::MAX` if the lock extends to the end of the file however it grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLock {
+    /// The process holding the lock, by the address of its fd table.
+    pub owner: usize,
+    /// The ID of the process holding the lock, reported by `F_GETLK`.
+    pub pid: i32,
+    pub kind: LockKind,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    /// Returns whether the lock blocks a lock of `kind` on the bytes
+    /// `start..end` for the process `owner`.
+    fn blocks(&self, owner: usize, kind: LockKind, start: u64, end: u64) -> bool {
+        self.owner != owner && self.overlaps(start, end) && self.kind.conflicts(kind)
+    }
+}
+
+#[derive(Default)]
+struct LockState {
+    /// The `flock` locks, by the addresses of the open file descriptions.
+    flocks: Vec<(usize, LockKind)>,
+    records: Vec<RecordLock>,
+    /// Increased on each change, for the waiters to know when to try again.
+    generation: u64,
+}
+
+impl LockState {
+    fn is_empty(&self) -> bool {
+        self.flocks.is_empty() && self.records.is_empty()
+    }
+
+    /// Places a record lock of `kind` on the bytes `start..end` for the
+    /// process `owner`, or removes its locks on the range if `kind` is
+    /// `None`. Returns `false` if it's blocked by the locks of the others.
+    ///
+    /// The locks of the process on the range are replaced, and the adjacent
+    /// ones of the same type are merged.
+    fn set_record(
+        &mut self,
+        owner: usize,
+        pid: i32,
+        kind: Option<LockKind>,
+        start: u64,
+        end: u64,
+    ) -> bool {
+        if let Some(kind) = kind {
+            if self
+                .records
+                .iter()
+                .any(|r| r.blocks(owner, kind, start, end))
+            {
+                return false;
+            }
+        }
+        // cut the range out of the locks of the owner
+        let mut rest = Vec::new();
+        self.records.retain_mut(|r| {
+            if r.owner != owner || !r.overlaps(start, end) {
+                return true;
+            }
+            if r.end > end {
+                rest.push(RecordLock { start: end, ..*r });
+            }
+            r.end = start;
+            r.start < start
+        });
+        self.records.extend(rest);
+        if let Some(kind) = kind {
+            let (mut start, mut end) = (start, end);
+            self.records.retain(|r| {
+                let adjacent = r.end == start || r.start == end;
+                if r.owner == owner && r.kind == kind && adjacent {
+                    start = start.min(r.start);
+                    end = end.max(r.end);
+                    return false;
+                }
+                true
+            });
+            self.records.push(RecordLock {
+                owner,
+                pid,
+                kind,
+                start,
+                end,
+            });
+        }
+        true
+    }
+}
+
+/// The locks of a file.
+struct FileLocks {
+    state: SpinNoIrq<LockState>,
+    #[cfg(feature = "multitask")]
+    waiters: WaitQueue,
+}
+
+impl FileLocks {
+    fn new() -> Self {
+        Self {
+            state: SpinNoIrq::new(LockState::default()),
+            #[cfg(feature = "multitask")]
+            waiters: WaitQueue::new(),
+        }
+    }
+}
+
+/// The locks of the files, with an entry only while a file has locks or
+/// waiters.
+static LOCKS: SpinNoIrq<BTreeMap<FileId, Arc<FileLocks>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// A blocking request of a record lock, waiting for the locks of the others
+/// on a file to be released.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "multitask"), allow(dead_code))]
+struct Waiter {
+    id: FileId,
+    owner: usize,
+    kind: LockKind,
+    start: u64,
+    end: u64,
+}
+
+/// The record lock requests being waited for, by the sequence numbers of the
+/// waits, as the threads of a process may wait at the same time.
+///
+/// It's locked before [`LOCKS`].
+#[cfg(feature = "multitask")]
+static WAITERS: SpinNoIrq<BTreeMap<u64, Waiter>> = SpinNoIrq::new(BTreeMap::new());
+
+#[cfg(feature = "multitask")]
+static NEXT_WAIT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// A record lock request registered in [`WAITERS`] while it's waited for.
+#[cfg(feature = "multitask")]
+struct WaitGuard(u64);
+
+#[cfg(feature = "multitask")]
+impl WaitGuard {
+    /// Registers the request `waiter`, or fails with `EDEADLK` if the owners
+    /// of the locks blocking it are waiting for the locks of its owner, in a
+    /// chain of any length.
+    fn new(waiter: Waiter) -> LinuxResult<Self> {
+        let mut waiters = WAITERS.lock();
+        {
+            let table = LOCKS.lock();
+            let mut pending = vec![waiter];
+            let mut visited = BTreeSet::new();
+            while let Some(w) = pending.pop() {
+                let Some(locks) = table.get(&w.id) else {
+                    continue;
+                };
+                let state = locks.state.lock();
+                for r in state.records.iter() {
+                    if !r.blocks(w.owner, w.kind, w.start, w.end) {
+                        continue;
+                    }
+                    if r.owner == waiter.owner {
+                        return Err(LinuxError::EDEADLK);
+                    }
+                    if visited.insert(r.owner) {
+                        pending.extend(waiters.values().filter(|w| w.owner == r.owner).copied());
+                    }
+                }
+            }
+        }
+        let seq = NEXT_WAIT_SEQ.fetch_add(1, Ordering::Relaxed);
+        waiters.insert(seq, waiter);
+        Ok(Self(seq))
+    }
+}
+
+#[cfg(feature = "multitask")]
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        WAITERS.lock().remove(&self.0);
+    }
+}
+
+fn file_locks(id: FileId) -> Arc<FileLocks> {
+    LOCKS
+        .lock()
+        .entry(id)
+        .or_insert_with(|| Arc::new(FileLocks::new()))
+        .clone()
+}
+
+/// Drops the reference to the locks of a file, and removes the entry of it
+/// if it has no more locks or waiters.
+fn put_file_locks(id: FileId, locks: Arc<FileLocks>) {
+    let mut table = LOCKS.lock();
+    // the references are only taken with the table locked
+    if Arc::strong_count(&locks) == 2 && locks.state.lock().is_empty() {
+        table.remove(&id);
+    }
+}
+
+/// Changes the locks of a file by `apply`, which returns `false` if the
+/// change conflicts with the locks of the others. It's tried again when the
+/// locks change if `block` is true, or fails with `EAGAIN` otherwise.
+///
+/// The record lock request `waiter`, if any, is checked for deadlocks before
+/// each wait.
+fn update<F>(id: FileId, block: bool, waiter: Option<Waiter>, mut apply: F) -> LinuxResult
+where
+    F: FnMut(&mut LockState) -> bool,
+{
+    let locks = file_locks(id);
+    let res = loop {
+        let mut state = locks.state.lock();
+        if apply(&mut state) {
+            state.generation += 1;
+            drop(state);
+            #[cfg(feature = "multitask")]
+            locks.waiters.notify_all(false);
+            break Ok(());
+        }
+        if !block {
+            break Err(LinuxError::EAGAIN);
+        }
+        #[cfg(feature = "multitask")]
+        {
+            let seen = state.generation;
+            drop(state);
+            let guard = match waiter.map(WaitGuard::new).transpose() {
+                Ok(guard) => guard,
+                Err(e) => break Err(e),
+            };
+            locks
+                .waiters
+                .wait_until(|| locks.state.lock().generation != seen);
+            drop(guard);
+        }
+        // no one else can release the lock
+        #[cfg(not(feature = "multitask"))]
+        {
+            let _ = waiter;
+            break Err(LinuxError::EDEADLK);
+        }
+    };
+    put_file_locks(id, locks);
+    res
+}
+
+/// Places a `flock` lock of `kind` on the file `id` for the open file
+/// description `owner`, converting the lock it holds if any.
+///
+/// As on Linux, the conversion is not atomic: a lock of the other type is
+/// released before waiting for the new one.
+pub fn flock(owner: usize, id: FileId, kind: LockKind, block: bool) -> LinuxResult {
+    let _ = update(id, false, None, |state| {
+        state.flocks.retain(|&(o, k)| o != owner || k == kind);
+        true
+    });
+    update(id, block, None, |state| {
+        if state.flocks.iter().any(|&(o, _)| o == owner) {
+            return true;
+        }
+        let conflict = state.flocks.iter().any(|&(_, k)| k.conflicts(kind));
+        if !conflict {
+            state.flocks.push((owner, kind));
+        }
+        !conflict
+    })
+}
+
+/// Removes the `flock` lock of the open file description `owner` on the
+/// file `id`, if any.
+pub fn funlock(owner: usize, id: FileId) {
+    let _ = update(id, false, None, |state| {
+        state.flocks.retain(|&(o, _)| o != owner);
+        true
+    });
+}
+
+/// Returns the first record lock of the others conflicting with a lock of
+/// `kind` on the bytes `start..end` for the process `owner`.
+pub fn test_record(
+    owner: usize,
+    id: FileId,
+    kind: LockKind,
+    start: u64,
+    end: u64,
+) -> Option<RecordLock> {
+    let locks = file_locks(id);
+    let conflict = locks
+        .state
+        .lock()
+        .records
+        .iter()
+        .find(|r| r.blocks(owner, kind, start, end))
+        .copied();
+    put_file_locks(id, locks);
+    conflict
+}
+
+/// Places a record lock of `kind` on the bytes `start..end` of the file `id`
+/// for the process `owner` with the ID `pid`, or removes its locks on the
+/// range if `kind` is `None`.
+///
+/// The locks of the process on the range are replaced, and the adjacent
+/// ones of the same type are merged. A blocking request fails with
+/// `EDEADLK` if it would wait for the process itself.
+pub fn set_record(
+    owner: usize,
+    pid: i32,
+    id: FileId,
+    kind: Option<LockKind>,
+    start: u64,
+    end: u64,
+    block: bool,
+) -> LinuxResult {
+    let waiter = kind.map(|kind| Waiter {
+        id,
+        owner,
+        kind,
+        start,
+        end,
+    });
+    update(id, block, waiter, |state| {
+        state.set_record(owner, pid, kind, start, end)
+    })
+}
+
+/// Removes all the record locks of the process `owner` on the file `id`.
+pub fn release_records(owner: usize, id: FileId) {
+    let Some(locks) = LOCKS.lock().get(&id).cloned() else {
+        return;
+    };
+    let mut state = locks.state.lock();
+    let len = state.records.len();
+    state.records.retain(|r| r.owner != owner);
+    if state.records.len() != len {
+        state.generation += 1;
+        drop(state);
+        #[cfg(feature = "multitask")]
+        locks.waiters.notify_all(false);
+    } else {
+        drop(state);
+    }
+    put_file_locks(id, locks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use LockKind::{Exclusive, Shared};
+
+    const A: usize = 1;
+    const B: usize = 2;
+
+    /// Returns the record locks of `owner`, ordered by their starts.
+    fn records(state: &LockState, owner: usize) -> Vec<(LockKind, u64, u64)> {
+        let mut records = state
+            .records
+            .iter()
+            .filter(|r| r.owner == owner)
+            .map(|r| (r.kind, r.start, r.end))
+            .collect::<Vec<_>>();
+        records.sort_by_key(|&(_, start, _)| start);
+        records
+    }
+
+    #[test]
+    fn test_split() {
+        let mut state = LockState::default();
+        assert!(state.set_record(A, 1, Some(Exclusive), 0, 100));
+        // unlocking the middle of a lock splits it
+        assert!(state.set_record(A, 1, None, 40, 60));
+        assert_eq!(
+            records(&state, A),
+            [(Exclusive, 0, 40), (Exclusive, 60, 100)]
+        );
+        // so does a lock of the other type
+        assert!(state.set_record(A, 1, Some(Shared), 10, 20));
+        assert_eq!(
+            records(&state, A),
+            [
+                (Exclusive, 0, 10),
+                (Shared, 10, 20),
+                (Exclusive, 20, 40),
+                (Exclusive, 60, 100)
+            ]
+        );
+        assert!(state.set_record(A, 1, None, 0, u64::MAX));
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut state = LockState::default();
+        assert!(state.set_record(A, 1, Some(Shared), 0, 10));
+        assert!(state.set_record(A, 1, Some(Shared), 20, 30));
+        // the adjacent locks of the same type are merged on both sides
+        assert!(state.set_record(A, 1, Some(Shared), 10, 20));
+        assert_eq!(records(&state, A), [(Shared, 0, 30)]);
+        // and the overlapping ones
+        assert!(state.set_record(A, 1, Some(Shared), 25, 40));
+        assert_eq!(records(&state, A), [(Shared, 0, 40)]);
+        // to the end of the file
+        assert!(state.set_record(A, 1, Some(Exclusive), 40, u64::MAX));
+        assert!(state.set_record(A, 1, Some(Exclusive), 0, 40));
+        assert_eq!(records(&state, A), [(Exclusive, 0, u64::MAX)]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut state = LockState::default();
+        assert!(state.set_record(A, 1, Some(Shared), 0, 100));
+        assert!(state.set_record(B, 2, Some(Shared), 50, 150));
+        // blocked by the shared lock of the other owner
+        assert!(!state.set_record(B, 2, Some(Exclusive), 0, 10));
+        assert_eq!(records(&state, B), [(Shared, 50, 150)]);
+        assert!(state.set_record(A, 1, Some(Exclusive), 0, 50));
+        assert!(!state.set_record(A, 1, Some(Exclusive), 0, 51));
+        assert_eq!(records(&state, A), [(Exclusive, 0, 50), (Shared, 50, 100)]);
+
+        // unlocking is never blocked, and keeps the locks of the others
+        assert!(state.set_record(B, 2, None, 0, u64::MAX));
+        assert!(records(&state, B).is_empty());
+        assert!(state.set_record(A, 1, Some(Exclusive), 0, 200));
+        assert_eq!(records(&state, A), [(Exclusive, 0, 200)]);
+    }
+}
@@ -1,13 +1,17 @@
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
-use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use alloc::vec;
+use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
+use axfs::fops::{FileAttr, FileId, OpenOptions};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_uint};
 
 use super::fd_ops::{FileLike, get_file_like};
+use super::flock::{self, LockKind};
 use crate::AT_FDCWD;
+use crate::uaccess::{copy_to_user, get_user, put_user};
 use crate::{ctypes, utils::char_ptr_to_str};
 
 // TODO: remove it to `utils`
@@ -32,10 +36,15 @@ pub fn hash_string(s: &str) -> u64 {
     hasher.finish()
 }
 
+/// The owner reported for the files on the filesystems not storing one.
+const DEFAULT_OWNER: (u32, u32) = (1000, 1000);
+
 /// File wrapper for `axfs::fops::File`.
 pub struct File {
     inner: Mutex<axfs::fops::File>,
     path: String,
+    /// The identity of the file, which its locks are kept by.
+    id: FileId,
     pub atime: Mutex<timespec>,
     pub mtime: Mutex<timespec>,
 }
@@ -43,6 +52,7 @@ pub struct File {
 impl File {
     pub fn new(inner: axfs::fops::File, path: &str) -> Self {
         Self {
+            id: inner.file_id(path),
             inner: Mutex::new(inner),
             path: path.to_string(),
             atime: Mutex::new(timespec::default()),
@@ -50,8 +60,8 @@ impl File {
         }
     }
 
-    fn add_to_fd_table(self) -> LinuxResult<c_int> {
-        super::fd_ops::add_file_like(Arc::new(self))
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::FD_TABLE.write().add(Arc::new(self), cloexec)
     }
 
     pub fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
@@ -82,7 +92,10 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
+        let inner = self.inner.lock();
+        let metadata = inner.get_attr()?;
+        let (st_uid, st_gid) = inner.owner().unwrap_or(DEFAULT_OWNER);
+        drop(inner);
         let ty = metadata.file_type() as u8;
         let perm = metadata.perm().bits() as u32;
         let st_mode = ((ty as u32) << 12) | perm;
@@ -92,8 +105,8 @@ impl FileLike for File {
             st_ino: fake_inode,
             st_nlink: 1,
             st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
+            st_uid,
+            st_gid,
             st_size: metadata.size() as _,
             st_blocks: metadata.blocks() as _,
             st_blksize: 512,
@@ -103,6 +116,10 @@ impl FileLike for File {
         })
     }
 
+    fn link_target(&self) -> String {
+        self.path.clone()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -119,6 +136,12 @@ impl FileLike for File {
     }
 }
 
+impl Drop for File {
+    fn drop(&mut self) {
+        flock::funlock(self as *const Self as usize, self.id);
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -160,82 +183,174 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
     let filename = char_ptr_to_str(filename);
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
-    syscall_body!(sys_open, {
-        add_file_or_directory_fd(
-            axfs::fops::File::open,
-            axfs::fops::Directory::open_dir,
-            filename?,
-            &flags_to_options(flags, mode),
-        )
-    })
+    syscall_body!(sys_open, open_path(&filename?, flags, mode))
+}
+
+/// Opens the file at `filename` like [`sys_open`], and returns its `fd`.
+pub(crate) fn open_path(filename: &str, flags: c_int, mode: ctypes::mode_t) -> LinuxResult<c_int> {
+    let path = axfs::api::canonicalize(filename)?;
+    if let Some(fd) = super::dev::open_device(&path, flags)? {
+        return Ok(fd);
+    }
+    if flags as u32 & ctypes::O_NOFOLLOW != 0
+        && axfs::api::symlink_metadata(filename).is_ok_and(|m| m.is_symlink())
+    {
+        return Err(LinuxError::ELOOP);
+    }
+    add_file_or_directory_fd(
+        axfs::fops::File::open,
+        axfs::fops::Directory::open_dir,
+        filename,
+        &path,
+        &flags_to_options(flags, mode),
+        flags as u32 & ctypes::O_CLOEXEC != 0,
+    )
 }
 
-/// Open or create a file.
-/// fd: file descriptor
-/// filename: file path to be opened or created
-/// flags: open flags
-/// mode: see man 7 inode
-/// return new file descriptor if success, or return -1.
+/// Open or create a file like [`sys_open`], where a relative `filename` is
+/// relative to the directory `dirfd`, or to the current directory if it's
+/// `AT_FDCWD`.
+///
+/// Return the new file descriptor if success.
 pub fn sys_openat(
     dirfd: c_int,
     filename: *const c_char,
     flags: c_int,
     mode: ctypes::mode_t,
 ) -> c_int {
-    let filename = match char_ptr_to_str(filename) {
-        Ok(s) => s,
-        Err(_) => return -1,
-    };
-
+    let path = char_ptr_to_str(filename);
     debug!(
         "sys_openat <= {} {:?} {:#o} {:#o}",
-        dirfd, filename, flags, mode
+        dirfd, path, flags, mode
     );
-
-    if filename.starts_with('/') || dirfd == AT_FDCWD as _ {
-        return sys_open(filename.as_ptr() as _, flags, mode);
+    if path.as_ref().is_ok_and(|p| p.starts_with('/')) || dirfd == AT_FDCWD as c_int {
+        return sys_open(filename, flags, mode);
     }
 
-    match Directory::from_fd(dirfd).and_then(|dir| {
+    syscall_body!(sys_openat, {
+        let filename = &path?;
+        let dir = dir_of_fd(dirfd)?;
+        if flags as u32 & ctypes::O_NOFOLLOW != 0
+            && dir
+                .inner
+                .lock()
+                .get_attr_at(filename, false)
+                .is_ok_and(|attr| attr.file_type().is_symlink())
+        {
+            return Err(LinuxError::ELOOP);
+        }
         add_file_or_directory_fd(
             |filename, options| dir.inner.lock().open_file_at(filename, options),
             |filename, options| dir.inner.lock().open_dir_at(filename, options),
             filename,
+            &absolute_path_at(dirfd, filename)?,
             &flags_to_options(flags, mode),
+            flags as u32 & ctypes::O_CLOEXEC != 0,
         )
-    }) {
-        Ok(fd) => fd,
-        Err(e) => {
-            debug!("sys_openat => {}", e);
-            -1
-        }
-    }
+    })
 }
 
 /// Use the function to open file or directory, then add into file descriptor table.
 /// First try opening files, if fails, try directory.
-fn add_file_or_directory_fd<F, D, E>(
+///
+/// The opened file is recorded with its absolute `path`, to resolve the paths
+/// relative to it later, and added with the close-on-exec flag `cloexec`.
+fn add_file_or_directory_fd<F, D>(
     open_file: F,
     open_dir: D,
     filename: &str,
+    path: &str,
     options: &OpenOptions,
+    cloexec: bool,
 ) -> LinuxResult<c_int>
 where
-    E: Into<LinuxError>,
-    F: FnOnce(&str, &OpenOptions) -> Result<axfs::fops::File, E>,
-    D: FnOnce(&str, &OpenOptions) -> Result<axfs::fops::Directory, E>,
+    F: FnOnce(&str, &OpenOptions) -> AxResult<axfs::fops::File>,
+    D: FnOnce(&str, &OpenOptions) -> AxResult<axfs::fops::Directory>,
 {
     if !options.has_directory() {
         match open_file(filename, options)
-            .map_err(Into::into)
-            .and_then(|f| File::new(f, filename.into()).add_to_fd_table())
+            .map_err(path_err)
+            .and_then(|f| File::new(f, path).add_to_fd_table(cloexec))
         {
             Err(LinuxError::EISDIR) => {}
             r => return r,
         }
     }
 
-    Directory::new(open_dir(filename, options).map_err(Into::into)?, filename).add_to_fd_table()
+    Directory::new(open_dir(filename, options).map_err(path_err)?, path).add_to_fd_table(cloexec)
+}
+
+/// Convert the error of a path lookup, where [`AxError::InvalidData`] means
+/// too many levels of symbolic links.
+fn path_err(e: AxError) -> LinuxError {
+    match e {
+        AxError::InvalidData => LinuxError::ELOOP,
+        e => e.into(),
+    }
+}
+
+/// Run `f` with the directory `dirfd` if `path` is relative to it, or with
+/// `None` if `path` is absolute or `dirfd` is `AT_FDCWD`.
+fn with_dir_at<T>(
+    dirfd: c_int,
+    path: &str,
+    f: impl FnOnce(Option<&axfs::fops::Directory>) -> AxResult<T>,
+) -> LinuxResult<T> {
+    if path.starts_with('/') || dirfd == AT_FDCWD as c_int {
+        f(None).map_err(path_err)
+    } else {
+        let dir = dir_of_fd(dirfd)?;
+        let dir = dir.inner.lock();
+        f(Some(&dir)).map_err(path_err)
+    }
+}
+
+/// Get the directory `dirfd` that the relative paths are relative to.
+fn dir_of_fd(dirfd: c_int) -> LinuxResult<Arc<Directory>> {
+    match Directory::from_fd(dirfd) {
+        Err(LinuxError::EINVAL) => Err(LinuxError::ENOTDIR),
+        res => res,
+    }
+}
+
+/// Get the absolute path of `path`, where a relative `path` is relative to
+/// the directory `dirfd`, or to the current directory if it's `AT_FDCWD`.
+fn absolute_path_at(dirfd: c_int, path: &str) -> LinuxResult<String> {
+    if path.is_empty() {
+        return Err(LinuxError::ENOENT);
+    }
+    if path.starts_with('/') || dirfd == AT_FDCWD as c_int {
+        Ok(axfs::api::canonicalize(path)?)
+    } else {
+        let dir = dir_of_fd(dirfd)?;
+        Ok(axfs::api::canonicalize(&format!(
+            "{}/{}",
+            dir.path(),
+            path
+        ))?)
+    }
+}
+
+/// Follow the symbolic link at the last component of the absolute `path`,
+/// until it's not a symbolic link.
+fn follow_last_link(mut path: String) -> LinuxResult<String> {
+    for _ in 0..axfs::MAX_SYMLINKS {
+        if !axfs::api::symlink_metadata(&path)
+            .map_err(path_err)?
+            .is_symlink()
+        {
+            return Ok(path);
+        }
+        let target = axfs::api::read_link(&path)?;
+        if !target.starts_with('/') {
+            let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+            path = format!("{}/{}", parent, target);
+        } else {
+            path = target;
+        }
+        path = axfs::api::canonicalize(&path)?;
+    }
+    Err(LinuxError::ELOOP)
 }
 
 /// Set the position of the file indicated by `fd`.
@@ -255,16 +370,692 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
     })
 }
 
+/// The size of the bounce buffer used by `sendfile`.
+const SENDFILE_CHUNK_SIZE: usize = 0x1_0000;
+
+/// Copy up to `count` bytes from the file `in_fd` to `out_fd`, e.g. a
+/// socket, inside the kernel.
+///
+/// Reads from `*offset` and advances it if `offset` is not NULL, leaving the
+/// file offset of `in_fd` unchanged. Otherwise, reads from the file offset
+/// and advances it.
+///
+/// Return the number of bytes written to `out_fd`.
+pub fn sys_sendfile(
+    out_fd: c_int,
+    in_fd: c_int,
+    offset: *mut ctypes::off_t,
+    count: usize,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_sendfile <= out_fd: {}, in_fd: {}, count: {}",
+        out_fd, in_fd, count
+    );
+    syscall_body!(sys_sendfile, {
+        let file_in = File::from_fd(in_fd)?;
+        let file_out = get_file_like(out_fd)?;
+        let mut pos = if offset.is_null() {
+            file_in.inner.lock().seek(SeekFrom::Current(0))?
+        } else {
+            let off = get_user(offset)?;
+            if off < 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            off as u64
+        };
+
+        let mut buf = vec![0u8; count.min(SENDFILE_CHUNK_SIZE)];
+        let mut total = 0;
+        while total < count {
+            let len = (count - total).min(buf.len());
+            let n = file_in.inner.lock().read_at(pos, &mut buf[..len])?;
+            if n == 0 {
+                break;
+            }
+            let written = match file_out.write(&buf[..n]) {
+                Ok(written) => written,
+                // Report the bytes sent before, e.g. to a nonblocking socket.
+                Err(_) if total > 0 => break,
+                Err(e) => return Err(e),
+            };
+            pos += written as u64;
+            total += written;
+            if written < n {
+                break;
+            }
+        }
+
+        if offset.is_null() {
+            file_in.inner.lock().seek(SeekFrom::Start(pos))?;
+        } else {
+            put_user(offset, pos as ctypes::off_t)?;
+        }
+        Ok(total as ctypes::ssize_t)
+    })
+}
+
+/// Apply or remove an advisory lock on the open file `fd`.
+///
+/// `operation` is one of `LOCK_SH`, `LOCK_EX` and `LOCK_UN`, optionally
+/// with `LOCK_NB` not to block but fail with `EWOULDBLOCK` if the lock is
+/// held by others. The lock is shared by the duplicates of `fd`, and released
+/// when all of them are closed.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let f = get_file_like(fd)?.into_any();
+        // the open file description owns the lock
+        let owner = Arc::as_ptr(&f) as *const () as usize;
+        let id = if let Some(file) = f.downcast_ref::<File>() {
+            file.id
+        } else if let Some(dir) = f.downcast_ref::<Directory>() {
+            dir.id
+        } else {
+            return Err(LinuxError::EINVAL);
+        };
+        let operation = operation as u32;
+        let block = operation & ctypes::LOCK_NB == 0;
+        match operation & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => flock::flock(owner, id, LockKind::Shared, block)?,
+            ctypes::LOCK_EX => flock::flock(owner, id, LockKind::Exclusive, block)?,
+            ctypes::LOCK_UN => flock::funlock(owner, id),
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
+
+/// Handle the record lock commands `F_GETLK`, `F_SETLK` and `F_SETLKW` of
+/// `fcntl` on the file `fd`, where the locks are owned by the process.
+pub(crate) fn fcntl_lock(fd: c_int, cmd: u32, lock: *mut ctypes::flock) -> LinuxResult<c_int> {
+    let file = File::from_fd(fd)?;
+    let lock_ptr = lock;
+    let mut lock = get_user(lock_ptr)?;
+    let kind = match lock.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockKind::Shared),
+        ctypes::F_WRLCK => Some(LockKind::Exclusive),
+        ctypes::F_UNLCK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let base = match lock.l_whence {
+        0 => 0,
+        1 => file.inner.lock().seek(SeekFrom::Current(0))? as i64,
+        2 => file.inner.lock().get_attr()?.size() as i64,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let start = base
+        .checked_add(lock.l_start)
+        .ok_or(LinuxError::EOVERFLOW)?;
+    // a negative length locks the bytes before `start`, and zero to the end
+    // of the file however it grows
+    let end = start.checked_add(lock.l_len).ok_or(LinuxError::EOVERFLOW)?;
+    let (start, end) = if lock.l_len < 0 {
+        (end, start)
+    } else {
+        (start, end)
+    };
+    if start < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let end = if lock.l_len == 0 {
+        u64::MAX
+    } else {
+        end as u64
+    };
+    let block = cmd == ctypes::F_SETLKW;
+    let owner = super::fd_ops::lock_owner();
+    match cmd {
+        ctypes::F_GETLK => {
+            let kind = kind.ok_or(LinuxError::EINVAL)?;
+            match flock::test_record(owner, file.id, kind, start as u64, end) {
+                Some(conflict) => {
+                    lock.l_type = match conflict.kind {
+                        LockKind::Shared => ctypes::F_RDLCK,
+                        LockKind::Exclusive => ctypes::F_WRLCK,
+                    } as _;
+                    lock.l_whence = 0;
+                    lock.l_start = conflict.start as _;
+                    lock.l_len = match conflict.end {
+                        u64::MAX => 0,
+                        end => (end - conflict.start) as _,
+                    };
+                    lock.l_pid = conflict.pid;
+                }
+                None => lock.l_type = ctypes::F_UNLCK as _,
+            }
+            put_user(lock_ptr, lock)?;
+        }
+        _ => {
+            let pid = crate::sys_getpid();
+            flock::set_record(owner, pid, file.id, kind, start as u64, end, block)?
+        }
+    }
+    Ok(0)
+}
+
+/// Release the record locks of the process `owner` on `file`, when one of its
+/// descriptors is closed.
+pub(crate) fn release_record_locks(owner: usize, file: &Arc<dyn FileLike>) {
+    if let Some(file) = file.clone().into_any().downcast_ref::<File>() {
+        flock::release_records(owner, file.id);
+    }
+}
+
 /// Rename `old` to `new`
 /// If new exists, it is first removed.
 ///
 /// Return 0 if the operation succeeds, otherwise return -1.
 pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
-    syscall_body!(sys_rename, {
-        let old_path = char_ptr_to_str(old)?;
-        let new_path = char_ptr_to_str(new)?;
-        debug!("sys_rename <= old: {:?}, new: {:?}", old_path, new_path);
-        axfs::api::rename(old_path, new_path)?;
+    sys_renameat2(AT_FDCWD as _, old, AT_FDCWD as _, new, 0)
+}
+
+/// Rename `old` to `new` like [`sys_rename`], where they are relative to the
+/// directories `olddirfd` and `newdirfd` respectively if relative.
+pub fn sys_renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    sys_renameat2(olddirfd, old, newdirfd, new, 0)
+}
+
+/// Don't overwrite `new` of [`sys_renameat2`], fail with `EEXIST` instead.
+const RENAME_NOREPLACE: c_uint = 1 << 0;
+
+/// Rename `old` to `new` like [`sys_renameat`].
+///
+/// `flags` can be `RENAME_NOREPLACE` only, exchanging the two files and
+/// the whiteouts are not supported by the filesystems, for which it fails
+/// with `EINVAL`.
+pub fn sys_renameat2(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_uint,
+) -> c_int {
+    syscall_body!(sys_renameat2, {
+        let old = &char_ptr_to_str(old)?;
+        let new = &char_ptr_to_str(new)?;
+        debug!(
+            "sys_renameat2 <= {} {:?} {} {:?} {:#x}",
+            olddirfd, old, newdirfd, new, flags
+        );
+        let old_path = absolute_path_at(olddirfd, old)?;
+        let new_path = absolute_path_at(newdirfd, new)?;
+        match flags {
+            0 => {}
+            RENAME_NOREPLACE => {
+                if axfs::api::symlink_metadata(&new_path).is_ok() {
+                    return Err(LinuxError::EEXIST);
+                }
+            }
+            _ => return Err(LinuxError::EINVAL),
+        }
+        axfs::api::rename(&old_path, &new_path).map_err(path_err)?;
+        Ok(0)
+    })
+}
+
+/// Create a directory `path`.
+///
+/// `mode` is ignored like that of [`sys_open`]. Return 0 if success.
+pub fn sys_mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    sys_mkdirat(AT_FDCWD as _, path, mode)
+}
+
+/// Create a directory `path` like [`sys_mkdir`], where a relative `path` is
+/// relative to the directory `dirfd`.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_mkdirat, {
+        let path = &char_ptr_to_str(path)?;
+        debug!("sys_mkdirat <= {} {:?} {:#o}", dirfd, path, mode);
+        with_dir_at(dirfd, path, |dir| match dir {
+            Some(dir) => dir.create_dir(path),
+            None => axfs::api::create_dir(path),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Remove the file `path`, or the symbolic link itself if it's one.
+///
+/// Return 0 if success.
+pub fn sys_unlink(path: *const c_char) -> c_int {
+    sys_unlinkat(AT_FDCWD as _, path, 0)
+}
+
+/// Remove the empty directory `path`.
+///
+/// Return 0 if success.
+pub fn sys_rmdir(path: *const c_char) -> c_int {
+    sys_unlinkat(AT_FDCWD as _, path, ctypes::AT_REMOVEDIR as _)
+}
+
+/// Remove the file `path` like [`sys_unlink`], or the directory like
+/// [`sys_rmdir`] if `flags` has `AT_REMOVEDIR`, where a relative `path` is
+/// relative to the directory `dirfd`.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_unlinkat, {
+        let path = &char_ptr_to_str(path)?;
+        debug!("sys_unlinkat <= {} {:?} {:#x}", dirfd, path, flags);
+        let flags = flags as u32;
+        if flags & !ctypes::AT_REMOVEDIR != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let remove_dir = flags & ctypes::AT_REMOVEDIR != 0;
+        with_dir_at(dirfd, path, |dir| match dir {
+            Some(dir) if remove_dir => dir.remove_dir(path),
+            Some(dir) => dir.remove_file(path),
+            None if remove_dir => axfs::api::remove_dir(path),
+            None => axfs::api::remove_file(path),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Create a hard link `new` to the file `old`.
+///
+/// Hard links are supported in tmpfs only, otherwise it fails with `EPERM`.
+/// Return 0 if success.
+pub fn sys_link(old: *const c_char, new: *const c_char) -> c_int {
+    sys_linkat(AT_FDCWD as _, old, AT_FDCWD as _, new, 0)
+}
+
+/// Create a hard link `new` to the file `old` like [`sys_link`], where they
+/// are relative to the directories `olddirfd` and `newdirfd` respectively if
+/// relative.
+///
+/// A symbolic link `old` is followed if `flags` has `AT_SYMLINK_FOLLOW`, and
+/// the file `olddirfd` is linked if `old` is empty and `flags` has
+/// `AT_EMPTY_PATH`.
+pub fn sys_linkat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_linkat, {
+        let old = &char_ptr_to_str(old)?;
+        let new = &char_ptr_to_str(new)?;
+        debug!(
+            "sys_linkat <= {} {:?} {} {:?} {:#x}",
+            olddirfd, old, newdirfd, new, flags
+        );
+        let flags = flags as u32;
+        if flags & !(ctypes::AT_SYMLINK_FOLLOW | ctypes::AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut old_path = if old.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 {
+            File::from_fd(olddirfd)?.path().to_string()
+        } else {
+            absolute_path_at(olddirfd, old)?
+        };
+        if flags & ctypes::AT_SYMLINK_FOLLOW != 0 {
+            old_path = follow_last_link(old_path)?;
+        }
+        let new_path = absolute_path_at(newdirfd, new)?;
+        axfs::api::hard_link(&old_path, &new_path).map_err(|e| match e {
+            AxError::Unsupported => LinuxError::EPERM,
+            e => path_err(e),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Create a symbolic link `linkpath` whose content is `target`.
+///
+/// Return 0 if success.
+pub fn sys_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    sys_symlinkat(target, AT_FDCWD as _, linkpath)
+}
+
+/// Create a symbolic link `linkpath` whose content is `target`, which does not
+/// have to exist. A relative `linkpath` is relative to the directory
+/// `newdirfd`, or to the current directory if it's `AT_FDCWD`.
+///
+/// Return 0 if success.
+pub fn sys_symlinkat(target: *const c_char, newdirfd: c_int, linkpath: *const c_char) -> c_int {
+    syscall_body!(sys_symlinkat, {
+        let target = &char_ptr_to_str(target)?;
+        let linkpath = &char_ptr_to_str(linkpath)?;
+        debug!("sys_symlinkat <= {:?} {} {:?}", target, newdirfd, linkpath);
+        with_dir_at(newdirfd, linkpath, |dir| match dir {
+            Some(dir) => dir.create_symlink(target, linkpath),
+            None => axfs::api::symlink(target, linkpath),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Read the content of the symbolic link `path` into `buf`, truncated to
+/// `bufsiz` bytes and without a terminating null byte.
+///
+/// Return the number of bytes placed in `buf`.
+pub fn sys_readlink(path: *const c_char, buf: *mut c_char, bufsiz: usize) -> ctypes::ssize_t {
+    sys_readlinkat(AT_FDCWD as _, path, buf, bufsiz)
+}
+
+/// Read the content of the symbolic link `path` like [`sys_readlink`], where a
+/// relative `path` is relative to the directory `dirfd`.
+///
+/// Return `EINVAL` if `path` is not a symbolic link.
+pub fn sys_readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_readlinkat, {
+        let path = &char_ptr_to_str(path)?;
+        debug!(
+            "sys_readlinkat <= {} {:?} {:#x} {}",
+            dirfd, path, buf as usize, bufsiz
+        );
+        crate::utils::check_null_mut_ptr(buf)?;
+        if bufsiz == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let target = with_dir_at(dirfd, path, |dir| match dir {
+            Some(dir) => dir.read_link(path),
+            None => axfs::api::read_link(path),
+        })?;
+        let len = target.len().min(bufsiz);
+        copy_to_user(buf as *mut u8, &target.as_bytes()[..len])?;
+        Ok(len)
+    })
+}
+
+/// Get the metadata of the file `path` and write into `buf`, following the
+/// symbolic links.
+///
+/// Return 0 if success.
+pub fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
+    sys_fstatat(AT_FDCWD as _, path, buf, 0)
+}
+
+/// Get the metadata of the file `path` like [`sys_stat`], but of the symbolic
+/// link itself if `path` is one.
+///
+/// Return 0 if success.
+pub fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
+    sys_fstatat(AT_FDCWD as _, path, buf, ctypes::AT_SYMLINK_NOFOLLOW as _)
+}
+
+/// Get the metadata of the file `fd` and write into `buf`.
+///
+/// Return 0 if success.
+pub fn sys_fstat(fd: c_int, buf: *mut ctypes::stat) -> c_int {
+    debug!("sys_fstat <= {} {:#x}", fd, buf as usize);
+    syscall_body!(sys_fstat, {
+        put_user(buf, get_file_like(fd)?.stat()?)?;
+        Ok(0)
+    })
+}
+
+/// Get the metadata of the file `path` and write into `buf`, where a relative
+/// `path` is relative to the directory `dirfd`.
+///
+/// The symbolic link itself is queried if `flags` has `AT_SYMLINK_NOFOLLOW`,
+/// and the file `dirfd` if `path` is empty and `flags` has `AT_EMPTY_PATH`.
+///
+/// Return 0 if success.
+pub fn sys_fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_fstatat, {
+        let path = &char_ptr_to_str(path)?;
+        debug!(
+            "sys_fstatat <= {} {:?} {:#x} {:#x}",
+            dirfd, path, buf as usize, flags
+        );
+        crate::utils::check_null_mut_ptr(buf)?;
+        let flags = flags as u32;
+        if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 && dirfd != AT_FDCWD as c_int {
+            put_user(buf, get_file_like(dirfd)?.stat()?)?;
+            return Ok(0);
+        }
+        let follow = flags & ctypes::AT_SYMLINK_NOFOLLOW == 0;
+        let (attr, owner) = with_dir_at(dirfd, path, |dir| match dir {
+            Some(dir) => Ok((dir.get_attr_at(path, follow)?, dir.owner_at(path, follow)?)),
+            None => {
+                let metadata = if follow {
+                    axfs::api::metadata(path)?
+                } else {
+                    axfs::api::symlink_metadata(path)?
+                };
+                let attr = FileAttr::new(
+                    metadata.permissions(),
+                    metadata.file_type(),
+                    metadata.size(),
+                    metadata.blocks(),
+                );
+                Ok((attr, axfs::api::owner(path, follow)?))
+            }
+        })?;
+        let (st_uid, st_gid) = owner.unwrap_or(DEFAULT_OWNER);
+        let st_mode = ((attr.file_type() as u32) << 12) | attr.perm().bits() as u32;
+        // TODO: true inode
+        let fake_inode = hash_string(path);
+        let st = ctypes::stat {
+            st_ino: fake_inode,
+            st_nlink: if attr.is_dir() { 2 } else { 1 },
+            st_mode,
+            st_uid,
+            st_gid,
+            st_size: attr.size() as _,
+            st_blocks: attr.blocks() as _,
+            st_blksize: 512,
+            ..Default::default()
+        };
+        put_user(buf, st)?;
+        Ok(0)
+    })
+}
+
+/// Write the data and the metadata of the file `fd` to the disk.
+///
+/// The dirty blocks of the other files on the same disk are also written
+/// back. Return `EINVAL` if `fd` is not a file or a directory.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, {
+        sync_fd(fd, false)?;
+        Ok(0)
+    })
+}
+
+/// Write the data of the file `fd` to the disk, like [`sys_fsync`] but
+/// without the metadata not needed to read the data.
+pub fn sys_fdatasync(fd: c_int) -> c_int {
+    debug!("sys_fdatasync <= {}", fd);
+    syscall_body!(sys_fdatasync, {
+        sync_fd(fd, true)?;
+        Ok(0)
+    })
+}
+
+fn sync_fd(fd: c_int, data_only: bool) -> LinuxResult {
+    let f = get_file_like(fd)?.into_any();
+    if let Ok(file) = f.clone().downcast::<File>() {
+        let file = file.inner.lock();
+        if data_only {
+            file.sync_data()?;
+        } else {
+            file.sync_all()?;
+        }
+    } else if f.downcast::<Directory>().is_ok() {
+        axfs::api::sync()?;
+    } else {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(())
+}
+
+/// Manipulate disk quotas.
+///
+/// Only tmpfs has quotas, of the users and the groups owning the files, so
+/// `special` is any path in a tmpfs instead of its block device. Supports
+/// `Q_QUOTAON`, `Q_QUOTAOFF`, `Q_GETQUOTA`, `Q_SETQUOTA`, `Q_GETINFO` and
+/// `Q_SYNC`. Only the hard limits are enforced, without the grace periods,
+/// and exceeding them fails with `ENOSPC`.
+#[cfg(feature = "quota")]
+pub fn sys_quotactl(cmd: c_int, special: *const c_char, id: c_int, addr: *mut c_char) -> c_int {
+    use axfs::tmpfs::QuotaType;
+
+    /// The size of the blocks in the limits of `dqblk`.
+    const QIF_DQBLKSIZE: u64 = 1024;
+
+    syscall_body!(sys_quotactl, {
+        let path = &char_ptr_to_str(special)?;
+        debug!(
+            "sys_quotactl <= cmd: {:#x}, special: {:?}, id: {}, addr: {:#x}",
+            cmd, path, id, addr as usize
+        );
+        let ty = match (cmd as u32) & ctypes::SUBCMDMASK {
+            ctypes::USRQUOTA => QuotaType::User,
+            ctypes::GRPQUOTA => QuotaType::Group,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let node = axfs::api::tmpfs_node(path).map_err(|e| match e {
+            AxError::Unsupported => LinuxError::ENOTBLK,
+            e => e.into(),
+        })?;
+        let enabled = node.quota_enabled(ty);
+        match (cmd as u32) >> ctypes::SUBCMDSHIFT {
+            ctypes::Q_QUOTAON if enabled => return Err(LinuxError::EBUSY),
+            ctypes::Q_QUOTAON => node.set_quota_enabled(ty, true),
+            ctypes::Q_QUOTAOFF => node.set_quota_enabled(ty, false),
+            ctypes::Q_SYNC => {}
+            _ if !enabled => return Err(LinuxError::ESRCH),
+            ctypes::Q_GETQUOTA => {
+                let dq = node.quota(ty, id as u32);
+                let dqblk = ctypes::dqblk {
+                    dqb_bhardlimit: dq.limits.space_hard / QIF_DQBLKSIZE,
+                    dqb_bsoftlimit: dq.limits.space_soft / QIF_DQBLKSIZE,
+                    dqb_curspace: dq.space,
+                    dqb_ihardlimit: dq.limits.inodes_hard,
+                    dqb_isoftlimit: dq.limits.inodes_soft,
+                    dqb_curinodes: dq.inodes,
+                    dqb_btime: 0,
+                    dqb_itime: 0,
+                    dqb_valid: ctypes::QIF_LIMITS | ctypes::QIF_USAGE,
+                };
+                put_user(addr as *mut ctypes::dqblk, dqblk)?;
+            }
+            ctypes::Q_SETQUOTA => {
+                let dqblk = get_user(addr as *const ctypes::dqblk)?;
+                let mut limits = node.quota(ty, id as u32).limits;
+                if dqblk.dqb_valid & ctypes::QIF_BLIMITS != 0 {
+                    limits.space_hard = dqblk.dqb_bhardlimit.saturating_mul(QIF_DQBLKSIZE);
+                    limits.space_soft = dqblk.dqb_bsoftlimit.saturating_mul(QIF_DQBLKSIZE);
+                }
+                if dqblk.dqb_valid & ctypes::QIF_ILIMITS != 0 {
+                    limits.inodes_hard = dqblk.dqb_ihardlimit;
+                    limits.inodes_soft = dqblk.dqb_isoftlimit;
+                }
+                node.set_quota_limits(ty, id as u32, limits);
+            }
+            ctypes::Q_GETINFO => {
+                let info = ctypes::dqinfo {
+                    dqi_bgrace: 0,
+                    dqi_igrace: 0,
+                    dqi_flags: 0,
+                    dqi_valid: ctypes::IIF_ALL,
+                };
+                put_user(addr as *mut ctypes::dqinfo, info)?;
+            }
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
+
+/// Manipulate disk quotas.
+///
+/// Quotas are not supported without the `quota` feature. Like Linux built
+/// without quota support, it always fails with `ENOSYS`.
+#[cfg(not(feature = "quota"))]
+pub fn sys_quotactl(cmd: c_int, special: *const c_char, id: c_int, addr: *mut c_char) -> c_int {
+    syscall_body!(sys_quotactl, {
+        debug!(
+            "sys_quotactl <= cmd: {:#x}, special: {:?}, id: {}, addr: {:#x}",
+            cmd,
+            char_ptr_to_str(special),
+            id,
+            addr as usize
+        );
+        Err::<c_int, _>(LinuxError::ENOSYS)
+    })
+}
+
+/// The `f_type` of `statfs` for the type of a filesystem in `/proc/mounts`,
+/// or 0 if Linux has none for it.
+fn fs_magic(fstype: &str) -> u64 {
+    match fstype {
+        "ext2" | "ext4" => 0xef53,
+        "vfat" => 0x4d44,
+        "tmpfs" | "devtmpfs" => 0x0102_1994,
+        "ramfs" => 0x8584_58f6,
+        "proc" => 0x9fa0,
+        "sysfs" => 0x6265_6572,
+        "hugetlbfs" => 0x9584_58f6,
+        "9p" => 0x0102_1997,
+        "nfs" => 0x6969,
+        _ => 0,
+    }
+}
+
+fn write_statfs(buf: *mut ctypes::statfs, stat: axfs::FileSystemStat) -> LinuxResult {
+    let statfs = ctypes::statfs {
+        f_type: fs_magic(stat.fstype) as _,
+        f_bsize: stat.block_size as _,
+        f_blocks: stat.blocks,
+        f_bfree: stat.blocks_free,
+        f_bavail: stat.blocks_avail,
+        f_files: stat.files,
+        f_ffree: stat.files_free,
+        f_namelen: stat.name_max as _,
+        f_frsize: stat.block_size as _,
+        ..Default::default()
+    };
+    put_user(buf, statfs)
+}
+
+/// Get the statistics of the filesystem holding the file `path`, following
+/// the symbolic links, and write into `buf`.
+///
+/// Return 0 if success.
+pub fn sys_statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    syscall_body!(sys_statfs, {
+        let path = &char_ptr_to_str(path)?;
+        debug!("sys_statfs <= {:?} {:#x}", path, buf as usize);
+        write_statfs(buf, axfs::api::statfs(path)?)?;
+        Ok(0)
+    })
+}
+
+/// Get the statistics of the filesystem holding the file `fd` like
+/// [`sys_statfs`].
+///
+/// Return 0 if success.
+pub fn sys_fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    debug!("sys_fstatfs <= {} {:#x}", fd, buf as usize);
+    syscall_body!(sys_fstatfs, {
+        let f = get_file_like(fd)?.into_any();
+        let stat = if let Some(file) = f.downcast_ref::<File>() {
+            axfs::api::statfs(file.path())?
+        } else if let Some(dir) = f.downcast_ref::<Directory>() {
+            axfs::api::statfs(dir.path())?
+        } else {
+            return Err(LinuxError::EINVAL);
+        };
+        write_statfs(buf, stat)?;
         Ok(0)
     })
 }
@@ -273,18 +1064,21 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
 pub struct Directory {
     inner: Mutex<axfs::fops::Directory>,
     path: String,
+    /// The identity of the directory, which its locks are kept by.
+    id: FileId,
 }
 
 impl Directory {
     pub fn new(inner: axfs::fops::Directory, path: &str) -> Self {
         Self {
+            id: inner.file_id(path),
             inner: Mutex::new(inner),
             path: path.to_string(),
         }
     }
 
-    fn add_to_fd_table(self) -> LinuxResult<c_int> {
-        super::fd_ops::add_file_like(Arc::new(self))
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::FD_TABLE.write().add(Arc::new(self), cloexec)
     }
 
     /// Open a directory by `fd`.
@@ -316,7 +1110,10 @@ impl FileLike for Directory {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
+        let inner = self.inner.lock();
+        let metadata = inner.get_attr()?;
+        let (st_uid, st_gid) = inner.owner().unwrap_or(DEFAULT_OWNER);
+        drop(inner);
         let ty = metadata.file_type() as u8;
         let perm = metadata.perm().bits() as u32;
         let st_mode = ((ty as u32) << 12) | perm;
@@ -325,8 +1122,8 @@ impl FileLike for Directory {
             st_ino: fake_inode,
             st_nlink: 2,
             st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
+            st_uid,
+            st_gid,
             st_size: metadata.size() as _,
             st_blocks: metadata.blocks() as _,
             st_blksize: 4096,
@@ -334,6 +1131,10 @@ impl FileLike for Directory {
         })
     }
 
+    fn link_target(&self) -> String {
+        self.path.clone()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -349,3 +1150,9 @@ impl FileLike for Directory {
         Ok(())
     }
 }
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        flock::funlock(self as *const Self as usize, self.id);
+    }
+}
@@ -0,0 +1,615 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+use core::ffi::c_int;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::spin::SpinNoIrq;
+use axtask::{AxTaskRef, WaitQueue};
+
+use super::task::wait_until_deadline;
+use super::time::realtime_now;
+use crate::ctypes;
+use crate::uaccess::{get_user, with_user_atomic};
+
+/// Wait if the futex word still contains the expected value.
+const FUTEX_WAIT: c_int = 0;
+/// Wake up waiters on the futex word.
+const FUTEX_WAKE: c_int = 1;
+/// Lock the priority-inheritance futex, with an absolute `CLOCK_REALTIME`
+/// timeout.
+const FUTEX_LOCK_PI: c_int = 6;
+/// Unlock the priority-inheritance futex, handing it off to the waiter with
+/// the highest priority.
+const FUTEX_UNLOCK_PI: c_int = 7;
+/// Like [`FUTEX_LOCK_PI`], without waiting.
+const FUTEX_TRYLOCK_PI: c_int = 8;
+/// Like [`FUTEX_WAIT`], with an absolute timeout and a bitset of the waiter.
+const FUTEX_WAIT_BITSET: c_int = 9;
+/// Like [`FUTEX_WAKE`], only waking up waiters whose bitsets intersect.
+const FUTEX_WAKE_BITSET: c_int = 10;
+/// Like [`FUTEX_LOCK_PI`], with the timeout measured by `CLOCK_MONOTONIC`
+/// unless [`FUTEX_CLOCK_REALTIME`] is set.
+const FUTEX_LOCK_PI2: c_int = 13;
+
+/// The futex is only used by one process, so it is looked up by its address
+/// in the process, instead of the memory the word is in (see [`FutexKey`]).
+const FUTEX_PRIVATE_FLAG: c_int = 128;
+/// The timeout of [`FUTEX_WAIT_BITSET`] or [`FUTEX_LOCK_PI2`] is measured by
+/// `CLOCK_REALTIME` instead of `CLOCK_MONOTONIC`.
+const FUTEX_CLOCK_REALTIME: c_int = 256;
+
+/// There are waiters on the PI futex, so its owner must unlock it by
+/// [`FUTEX_UNLOCK_PI`].
+const FUTEX_WAITERS: u32 = 0x8000_0000;
+/// The owner of the PI futex exited without unlocking it.
+const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+/// The thread ID of the owner of the PI futex.
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+/// The bitset matching all waiters.
+pub(crate) const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+
+/// The number of buckets in the futex hash table.
+const FUTEX_HASH_SIZE: usize = 256;
+
+/// Identifies a futex word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum FutexKey {
+    /// A futex private to a process, by the process ID, or 0 for the kernel,
+    /// and the user address of the word.
+    Private { pid: u64, addr: usize },
+    /// A futex possibly shared between processes, by the kernel address of
+    /// the word, i.e. in the linear mapping of its physical frame, which is
+    /// the same in all address spaces mapping the frame.
+    Shared { kaddr: usize },
+}
+
+/// Calls `f` with the key of the futex word at `uaddr`, and the word as an
+/// atomic. With `write`, the word must be writable.
+fn with_futex<R>(
+    uaddr: *mut u32,
+    private: bool,
+    write: bool,
+    f: impl FnOnce(FutexKey, &AtomicU32) -> LinuxResult<R>,
+) -> LinuxResult<R> {
+    #[cfg(feature = "uspace")]
+    let pid = match private {
+        true => super::process::current_pid().unwrap_or(0),
+        false => 0,
+    };
+    #[cfg(not(feature = "uspace"))]
+    let pid = 0;
+    with_user_atomic(uaddr, write, |word| {
+        let key = if private {
+            FutexKey::Private {
+                pid,
+                addr: uaddr as usize,
+            }
+        } else {
+            FutexKey::Shared {
+                kaddr: word.as_ptr() as usize,
+            }
+        };
+        f(key, word)
+    })
+}
+
+struct FutexWaiter {
+    key: FutexKey,
+    bitset: u32,
+    /// The thread ID of the waiter on a PI futex, or 0 for the other waiters.
+    pi_tid: u32,
+    /// The priority of the waiter on a PI futex.
+    prio: isize,
+    woken: AtomicBool,
+}
+
+impl FutexWaiter {
+    fn is_pi_on(&self, key: FutexKey) -> bool {
+        self.key == key && self.pi_tid != 0
+    }
+}
+
+/// Waiters on the futex words hashed to the same bucket.
+///
+/// All the waiters sleep on the same wait queue, and those not woken go back
+/// to sleep after a wakeup.
+struct FutexBucket {
+    waiters: SpinNoIrq<VecDeque<Arc<FutexWaiter>>>,
+    wq: WaitQueue,
+}
+
+impl FutexBucket {
+    const fn new() -> Self {
+        Self {
+            waiters: SpinNoIrq::new(VecDeque::new()),
+            wq: WaitQueue::new(),
+        }
+    }
+}
+
+static FUTEX_BUCKETS: [FutexBucket; FUTEX_HASH_SIZE] =
+    [const { FutexBucket::new() }; FUTEX_HASH_SIZE];
+
+fn futex_bucket(key: FutexKey) -> &'static FutexBucket {
+    let (addr, pid) = match key {
+        FutexKey::Private { pid, addr } => (addr, pid as usize),
+        FutexKey::Shared { kaddr } => (kaddr, 0),
+    };
+    // Futex words are 4-byte aligned, use the Fibonacci hashing on the rest.
+    let hash = ((addr >> 2) ^ pid).wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as usize);
+    &FUTEX_BUCKETS[(hash >> (usize::BITS - FUTEX_HASH_SIZE.trailing_zeros())) as usize]
+}
+
+fn futex_wait(
+    uaddr: *mut u32,
+    private: bool,
+    val: u32,
+    deadline: Option<Duration>,
+    bitset: u32,
+) -> LinuxResult {
+    // Check the value with the bucket locked, so that a wakeup after the
+    // value has changed cannot be missed.
+    let (bucket, waiter) = with_futex(uaddr, private, false, |key, word| {
+        let bucket = futex_bucket(key);
+        let mut waiters = bucket.waiters.lock();
+        if word.load(Ordering::SeqCst) != val {
+            return Err(LinuxError::EAGAIN);
+        }
+        let waiter = Arc::new(FutexWaiter {
+            key,
+            bitset,
+            pi_tid: 0,
+            prio: 0,
+            woken: AtomicBool::new(false),
+        });
+        waiters.push_back(waiter.clone());
+        Ok((bucket, waiter))
+    })?;
+
+    let res = wait_until_deadline(&bucket.wq, deadline, || {
+        waiter.woken.load(Ordering::Acquire)
+    });
+    if res.is_err() {
+        let mut waiters = bucket.waiters.lock();
+        // Woken up just after timed out, consume the wakeup.
+        if waiter.woken.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        waiters.retain(|w| !Arc::ptr_eq(w, &waiter));
+    }
+    res
+}
+
+pub(crate) fn futex_wake(
+    uaddr: *mut u32,
+    private: bool,
+    count: u32,
+    bitset: u32,
+) -> LinuxResult<usize> {
+    let key = with_futex(uaddr, private, false, |key, _| Ok(key))?;
+    let bucket = futex_bucket(key);
+    let mut woken = 0;
+    bucket.waiters.lock().retain(|w| {
+        if woken < count as usize && w.key == key && w.pi_tid == 0 && w.bitset & bitset != 0 {
+            w.woken.store(true, Ordering::Release);
+            woken += 1;
+            false
+        } else {
+            true
+        }
+    });
+    if woken > 0 {
+        bucket.wq.notify_all(false);
+    }
+    Ok(woken)
+}
+
+/// The owner of a PI futex, boosted to the highest priority of its waiters.
+///
+/// The priorities are compared as the nice values of the CFS scheduler, where
+/// the lower values are the higher priorities. Only the owner is boosted, not
+/// the owners of the futexes it waits for in turn.
+struct PiBoost {
+    owner: AxTaskRef,
+    /// The priority of the owner before it was boosted.
+    base_prio: isize,
+}
+
+impl PiBoost {
+    fn restore(&self) {
+        if self.owner.priority() != self.base_prio {
+            axtask::set_task_priority(&self.owner, self.base_prio);
+        }
+    }
+}
+
+/// The boosted owners of the PI futexes by the keys, locked after the
+/// buckets.
+static PI_BOOSTS: SpinNoIrq<BTreeMap<FutexKey, PiBoost>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Boosts the owner of the PI futex `key` to the highest priority of the
+/// waiters on it, or restores its priority if there are no waiters.
+fn pi_boost(key: FutexKey, owner: &AxTaskRef, waiters: &VecDeque<Arc<FutexWaiter>>) {
+    let top = waiters
+        .iter()
+        .filter(|w| w.is_pi_on(key))
+        .map(|w| w.prio)
+        .min();
+    let mut boosts = PI_BOOSTS.lock();
+    let base_prio = match boosts.remove(&key) {
+        Some(boost) if Arc::ptr_eq(&boost.owner, owner) => boost.base_prio,
+        Some(boost) => {
+            boost.restore();
+            owner.priority()
+        }
+        None => owner.priority(),
+    };
+    let prio = top.map_or(base_prio, |top| top.min(base_prio));
+    if owner.priority() != prio {
+        axtask::set_task_priority(owner, prio);
+    }
+    if top.is_some() {
+        boosts.insert(
+            key,
+            PiBoost {
+                owner: owner.clone(),
+                base_prio,
+            },
+        );
+    }
+}
+
+/// Locks the PI futex at `uaddr`, whose word is the thread ID of the owner,
+/// or 0 if it's unlocked.
+fn futex_lock_pi(
+    uaddr: *mut u32,
+    private: bool,
+    deadline: Option<Duration>,
+    trylock: bool,
+) -> LinuxResult {
+    let curr = axtask::current();
+    let tid = curr.id().as_u64() as u32;
+    let waiter = with_futex(uaddr, private, true, |key, word| {
+        let bucket = futex_bucket(key);
+        let mut waiters = bucket.waiters.lock();
+        loop {
+            let val = word.load(Ordering::SeqCst);
+            let owner_tid = val & FUTEX_TID_MASK;
+            if owner_tid == 0 {
+                // Unlocked, or its owner died, keep the waiters bit for the others.
+                let mut new = tid | (val & FUTEX_OWNER_DIED);
+                if waiters.iter().any(|w| w.is_pi_on(key)) {
+                    new |= FUTEX_WAITERS;
+                }
+                match word.compare_exchange(val, new, Ordering::SeqCst, Ordering::SeqCst) {
+                    Ok(_) => return Ok(None),
+                    Err(_) => continue,
+                }
+            }
+            if owner_tid == tid {
+                return Err(LinuxError::EDEADLK);
+            }
+            if trylock {
+                return Err(LinuxError::EAGAIN);
+            }
+            let Some(owner) = axtask::init_pid_ns().find_task(owner_tid as u64) else {
+                return Err(LinuxError::ESRCH);
+            };
+            // Make the owner unlock it by `FUTEX_UNLOCK_PI` to hand it off.
+            if val & FUTEX_WAITERS == 0
+                && word
+                    .compare_exchange(val, val | FUTEX_WAITERS, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err()
+            {
+                continue;
+            }
+            let waiter = Arc::new(FutexWaiter {
+                key,
+                bitset: FUTEX_BITSET_MATCH_ANY,
+                pi_tid: tid,
+                prio: curr.priority(),
+                woken: AtomicBool::new(false),
+            });
+            waiters.push_back(waiter.clone());
+            pi_boost(key, &owner, &waiters);
+            break Ok(Some(waiter));
+        }
+    })?;
+    let Some(waiter) = waiter else {
+        return Ok(());
+    };
+    let bucket = futex_bucket(waiter.key);
+
+    let res = wait_until_deadline(&bucket.wq, deadline, || {
+        waiter.woken.load(Ordering::Acquire)
+    });
+    if res.is_err() {
+        // The word may have been unmapped in the meantime, then only the
+        // waiter is removed.
+        let handed_off = with_user_atomic(uaddr, true, |word| {
+            Ok(cancel_lock_pi(bucket, &waiter, Some(word)))
+        })
+        .unwrap_or_else(|_| cancel_lock_pi(bucket, &waiter, None));
+        // Handed off just after timed out, keep the lock.
+        if handed_off {
+            return Ok(());
+        }
+    }
+    res
+}
+
+/// Removes the timed out `waiter` on a PI futex, and clears the waiters bit
+/// of its `word` if it was the last one.
+///
+/// Returns `true` if the futex has been handed off to it in the meantime.
+fn cancel_lock_pi(
+    bucket: &FutexBucket,
+    waiter: &Arc<FutexWaiter>,
+    word: Option<&AtomicU32>,
+) -> bool {
+    let key = waiter.key;
+    let mut waiters = bucket.waiters.lock();
+    if waiter.woken.load(Ordering::Acquire) {
+        return true;
+    }
+    waiters.retain(|w| !Arc::ptr_eq(w, waiter));
+    let owner = PI_BOOSTS.lock().get(&key).map(|b| b.owner.clone());
+    if let Some(owner) = owner {
+        pi_boost(key, &owner, &waiters);
+    }
+    if let Some(word) = word {
+        if !waiters.iter().any(|w| w.is_pi_on(key)) {
+            word.fetch_and(!FUTEX_WAITERS, Ordering::SeqCst);
+        }
+    }
+    false
+}
+
+/// Unlocks the PI futex at `uaddr` owned by the current thread, and hands it
+/// off to the first waiter with the highest priority.
+fn futex_unlock_pi(uaddr: *mut u32, private: bool) -> LinuxResult {
+    let tid = axtask::current().id().as_u64() as u32;
+    with_futex(uaddr, private, true, |key, word| {
+        futex_hand_off(key, tid, word)
+    })
+}
+
+/// Hands off the PI futex `key` owned by `tid`, whose word is `word`.
+fn futex_hand_off(key: FutexKey, tid: u32, word: &AtomicU32) -> LinuxResult {
+    let bucket = futex_bucket(key);
+    let mut waiters = bucket.waiters.lock();
+    if word.load(Ordering::SeqCst) & FUTEX_TID_MASK != tid {
+        return Err(LinuxError::EPERM);
+    }
+    if let Some(boost) = PI_BOOSTS.lock().remove(&key) {
+        boost.restore();
+    }
+    let next = waiters
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| w.is_pi_on(key))
+        .min_by_key(|(_, w)| w.prio)
+        .map(|(i, _)| i);
+    let Some(next) = next.and_then(|i| waiters.remove(i)) else {
+        word.store(0, Ordering::SeqCst);
+        return Ok(());
+    };
+    let has_waiters = waiters.iter().any(|w| w.is_pi_on(key));
+    let new = if has_waiters {
+        next.pi_tid | FUTEX_WAITERS
+    } else {
+        next.pi_tid
+    };
+    word.store(new, Ordering::SeqCst);
+    next.woken.store(true, Ordering::Release);
+    if has_waiters {
+        if let Some(owner) = axtask::init_pid_ns().find_task(next.pi_tid as u64) {
+            pi_boost(key, &owner, &waiters);
+        }
+    }
+    drop(waiters);
+    bucket.wq.notify_all(false);
+    Ok(())
+}
+
+/// Converts the timeout of the futex operation `op` to a deadline of the
+/// wall time.
+///
+/// `FUTEX_WAIT` takes a relative timeout, and the others take absolute ones
+/// measured by `CLOCK_MONOTONIC`, or by `CLOCK_REALTIME` for `FUTEX_LOCK_PI`
+/// and with `FUTEX_CLOCK_REALTIME`.
+fn futex_deadline(op: c_int, timeout: *const ctypes::timespec) -> LinuxResult<Option<Duration>> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    let ts = get_user(timeout)?;
+    if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec >= 1_000_000_000 {
+        return Err(LinuxError::EINVAL);
+    }
+    let timeout = Duration::from(ts);
+    let cmd = op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+    let remaining = if cmd == FUTEX_WAIT {
+        timeout
+    } else if op & FUTEX_CLOCK_REALTIME != 0 || cmd == FUTEX_LOCK_PI {
+        timeout.saturating_sub(realtime_now())
+    } else {
+        timeout.saturating_sub(axhal::time::monotonic_time())
+    };
+    Ok(Some(axhal::time::wall_time() + remaining))
+}
+
+/// Fast user-space locking.
+///
+/// Supports `FUTEX_WAIT`, `FUTEX_WAKE`, `FUTEX_WAIT_BITSET`,
+/// `FUTEX_WAKE_BITSET`, and the priority-inheritance `FUTEX_LOCK_PI`,
+/// `FUTEX_LOCK_PI2`, `FUTEX_TRYLOCK_PI` and `FUTEX_UNLOCK_PI`. `uaddr2` is
+/// unused by these operations. Without `FUTEX_PRIVATE_FLAG`, the futexes are
+/// shared with the other processes mapping the same memory.
+///
+/// Return the number of woken waiters for wakeups, or 0 for waits.
+pub unsafe fn sys_futex(
+    uaddr: *mut u32,
+    op: c_int,
+    val: u32,
+    timeout: *const ctypes::timespec,
+    _uaddr2: *mut u32,
+    val3: u32,
+) -> c_int {
+    debug!(
+        "sys_futex <= uaddr: {:#x}, op: {}, val: {}",
+        uaddr as usize, op, val
+    );
+    syscall_body!(sys_futex, {
+        if uaddr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if uaddr as usize % 4 != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let private = op & FUTEX_PRIVATE_FLAG != 0;
+        let cmd = op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+        if op & FUTEX_CLOCK_REALTIME != 0
+            && !matches!(cmd, FUTEX_WAIT | FUTEX_WAIT_BITSET | FUTEX_LOCK_PI2)
+        {
+            return Err(LinuxError::ENOSYS);
+        }
+        let bitset = match cmd {
+            FUTEX_WAIT | FUTEX_WAKE => FUTEX_BITSET_MATCH_ANY,
+            FUTEX_LOCK_PI | FUTEX_LOCK_PI2 | FUTEX_TRYLOCK_PI | FUTEX_UNLOCK_PI => {
+                FUTEX_BITSET_MATCH_ANY
+            }
+            FUTEX_WAIT_BITSET | FUTEX_WAKE_BITSET if val3 == 0 => {
+                return Err(LinuxError::EINVAL);
+            }
+            FUTEX_WAIT_BITSET | FUTEX_WAKE_BITSET => val3,
+            _ => return Err(LinuxError::ENOSYS),
+        };
+
+        match cmd {
+            FUTEX_WAIT | FUTEX_WAIT_BITSET => {
+                futex_wait(uaddr, private, val, futex_deadline(op, timeout)?, bitset)?;
+                Ok(0)
+            }
+            FUTEX_LOCK_PI | FUTEX_LOCK_PI2 => {
+                futex_lock_pi(uaddr, private, futex_deadline(op, timeout)?, false)?;
+                Ok(0)
+            }
+            FUTEX_TRYLOCK_PI => {
+                futex_lock_pi(uaddr, private, None, true)?;
+                Ok(0)
+            }
+            FUTEX_UNLOCK_PI => {
+                futex_unlock_pi(uaddr, private)?;
+                Ok(0)
+            }
+            _ => Ok(futex_wake(uaddr, private, val, bitset)? as c_int),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+    use std::sync::{Mutex, Once};
+
+    use super::*;
+
+    static INIT: Once = Once::new();
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn addr(word: &AtomicU32) -> *mut u32 {
+        word.as_ptr()
+    }
+
+    /// Returns the number of waiters on the private futex `word`.
+    fn num_waiters(word: &AtomicU32) -> usize {
+        let key = FutexKey::Private {
+            pid: 0,
+            addr: addr(word) as usize,
+        };
+        let waiters = futex_bucket(key).waiters.lock();
+        waiters.iter().filter(|w| w.key == key).count()
+    }
+
+    #[test]
+    fn test_wait_wake() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(axtask::init_scheduler);
+
+        static WORD: AtomicU32 = AtomicU32::new(0);
+        static WOKEN: AtomicUsize = AtomicUsize::new(0);
+
+        for (i, bitset) in [0b01, 0b10, 0b10].into_iter().enumerate() {
+            axtask::spawn(move || {
+                futex_wait(addr(&WORD), true, 0, None, bitset).unwrap();
+                WOKEN.fetch_or(1 << i, Ordering::SeqCst);
+            });
+        }
+        while num_waiters(&WORD) < 3 {
+            axtask::yield_now();
+        }
+        assert_eq!(
+            futex_wait(addr(&WORD), true, 1, None, FUTEX_BITSET_MATCH_ANY),
+            Err(LinuxError::EAGAIN)
+        );
+
+        // only the first waiter whose bitset matches
+        assert_eq!(futex_wake(addr(&WORD), true, 1, 0b10), Ok(1));
+        while WOKEN.load(Ordering::SeqCst) != 0b010 {
+            axtask::yield_now();
+        }
+        // a shared futex is another one than the private futex at the address
+        assert_eq!(
+            futex_wake(addr(&WORD), false, u32::MAX, FUTEX_BITSET_MATCH_ANY),
+            Ok(0)
+        );
+        assert_eq!(
+            futex_wake(addr(&WORD), true, u32::MAX, FUTEX_BITSET_MATCH_ANY),
+            Ok(2)
+        );
+        while WOKEN.load(Ordering::SeqCst) != 0b111 {
+            axtask::yield_now();
+        }
+        assert_eq!(num_waiters(&WORD), 0);
+        assert_eq!(
+            futex_wake(addr(&WORD), true, u32::MAX, FUTEX_BITSET_MATCH_ANY),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_trylock_pi() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(axtask::init_scheduler);
+
+        let word = AtomicU32::new(0);
+        let tid = axtask::current().id().as_u64() as u32;
+
+        assert_eq!(futex_lock_pi(addr(&word), true, None, true), Ok(()));
+        assert_eq!(word.load(Ordering::SeqCst), tid);
+        assert_eq!(
+            futex_lock_pi(addr(&word), true, None, true),
+            Err(LinuxError::EDEADLK)
+        );
+        assert_eq!(futex_unlock_pi(addr(&word), true), Ok(()));
+        assert_eq!(word.load(Ordering::SeqCst), 0);
+        assert_eq!(futex_unlock_pi(addr(&word), true), Err(LinuxError::EPERM));
+
+        word.store(tid + 1, Ordering::SeqCst);
+        assert_eq!(
+            futex_lock_pi(addr(&word), true, None, true),
+            Err(LinuxError::EAGAIN)
+        );
+        assert_eq!(futex_unlock_pi(addr(&word), true), Err(LinuxError::EPERM));
+
+        // the lock of a dead owner can be taken over
+        word.store(FUTEX_OWNER_DIED, Ordering::SeqCst);
+        assert_eq!(futex_lock_pi(addr(&word), true, None, true), Ok(()));
+        assert_eq!(word.load(Ordering::SeqCst), tid | FUTEX_OWNER_DIED);
+    }
+}
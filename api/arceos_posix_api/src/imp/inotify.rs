@@ -0,0 +1,251 @@
+//! Watching the changes of the files, like `inotify(7)`.
+//!
+//! An inotify instance is a file descriptor, which reads the events of its
+//! watches as `struct inotify_event` and can be monitored with
+//! `poll`/`select`/`epoll`. The events are generated by the VFS (see
+//! [`axfs::notify`]), where the accesses, the opens and the changes of the
+//! attributes are not reported.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::ffi::{c_char, c_int};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{AxError, LinuxError, LinuxResult};
+use axfs::notify::Watcher;
+use axio::PollState;
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+use super::fd_ops::{FD_TABLE, FileLike, get_file_like};
+use crate::ctypes;
+use crate::uaccess::put_user;
+use crate::utils::char_ptr_to_str;
+
+/// The maximum number of the events queued in an instance, after which the
+/// events are dropped and `IN_Q_OVERFLOW` is queued.
+const MAX_QUEUED_EVENTS: usize = 16384;
+/// Gets the number of the bytes that can be read.
+const FIONREAD: u32 = 0x541b;
+
+const EVENT_SIZE: usize = size_of::<ctypes::inotify_event>();
+
+#[derive(PartialEq, Eq)]
+struct Event {
+    wd: c_int,
+    mask: u32,
+    cookie: u32,
+    name: String,
+}
+
+impl Event {
+    /// The length of the name, with the terminating NUL and the padding to
+    /// align the next event, or zero if there is no name.
+    fn name_len(&self) -> usize {
+        if self.name.is_empty() {
+            0
+        } else {
+            (self.name.len() + 1).next_multiple_of(EVENT_SIZE)
+        }
+    }
+
+    fn size(&self) -> usize {
+        EVENT_SIZE + self.name_len()
+    }
+}
+
+/// An inotify instance.
+pub struct Inotify {
+    events: SpinNoIrq<VecDeque<Event>>,
+    wq: WaitQueue,
+    nonblocking: AtomicBool,
+}
+
+impl Inotify {
+    fn new(nonblocking: bool) -> Self {
+        Self {
+            events: SpinNoIrq::new(VecDeque::new()),
+            wq: WaitQueue::new(),
+            nonblocking: AtomicBool::new(nonblocking),
+        }
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    fn push(&self, event: Event) {
+        let mut events = self.events.lock();
+        // the same event as the last one is merged into it
+        if events.back() == Some(&event) {
+            return;
+        }
+        if events.len() >= MAX_QUEUED_EVENTS {
+            let overflow = Event {
+                wd: -1,
+                mask: ctypes::IN_Q_OVERFLOW,
+                cookie: 0,
+                name: String::new(),
+            };
+            if events.back() != Some(&overflow) {
+                events.push_back(overflow);
+            }
+            return;
+        }
+        events.push_back(event);
+        drop(events);
+        self.wq.notify_all(true);
+    }
+}
+
+impl Watcher for Inotify {
+    fn notify(&self, wd: i32, mask: u32, cookie: u32, name: &str) {
+        self.push(Event {
+            wd,
+            mask,
+            cookie,
+            name: String::from(name),
+        });
+    }
+}
+
+impl FileLike for Inotify {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let mut events = loop {
+            let events = self.events.lock();
+            if !events.is_empty() {
+                break events;
+            }
+            drop(events);
+            if self.is_nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            self.wq.wait_until(|| !self.events.lock().is_empty());
+        };
+
+        let mut len = 0;
+        while let Some(event) = events.front() {
+            let size = event.size();
+            if len + size > buf.len() {
+                break;
+            }
+            let header = ctypes::inotify_event {
+                wd: event.wd,
+                mask: event.mask,
+                cookie: event.cookie,
+                len: event.name_len() as u32,
+                ..Default::default()
+            };
+            let ptr = buf[len..].as_mut_ptr() as *mut ctypes::inotify_event;
+            unsafe { ptr.write_unaligned(header) };
+            let name = &mut buf[len + EVENT_SIZE..len + size];
+            name.fill(0);
+            name[..event.name.len()].copy_from_slice(event.name.as_bytes());
+            len += size;
+            events.pop_front();
+        }
+        if len == 0 {
+            // the buffer is too small for the first event
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(len)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o600u32; // rw-------
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: !self.events.lock().is_empty(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn ioctl(&self, cmd: u32, arg: usize) -> LinuxResult<c_int> {
+        match cmd {
+            FIONREAD => {
+                let len: usize = self.events.lock().iter().map(Event::size).sum();
+                put_user(arg as *mut c_int, len as c_int)?;
+                Ok(0)
+            }
+            _ => Err(LinuxError::ENOTTY),
+        }
+    }
+
+    fn link_target(&self) -> String {
+        String::from("anon_inode:inotify")
+    }
+}
+
+/// Creates an inotify instance, and returns its file descriptor.
+///
+/// `flags` may contain `IN_NONBLOCK` and `IN_CLOEXEC`.
+pub fn sys_inotify_init1(flags: c_int) -> c_int {
+    debug!("sys_inotify_init1 <= flags: {:#x}", flags);
+    syscall_body!(sys_inotify_init1, {
+        let flags = flags as u32;
+        if flags & !(ctypes::IN_NONBLOCK | ctypes::IN_CLOEXEC) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let inotify = Arc::new(Inotify::new(flags & ctypes::IN_NONBLOCK != 0));
+        FD_TABLE
+            .write()
+            .add(inotify, flags & ctypes::IN_CLOEXEC != 0)
+    })
+}
+
+/// Watches the events in `mask` of the file at `path` by the inotify
+/// instance `fd`, and returns the watch descriptor.
+pub fn sys_inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_inotify_add_watch <= fd: {}, path: {:?}, mask: {:#x}",
+        fd, path, mask
+    );
+    syscall_body!(sys_inotify_add_watch, {
+        let watcher: Arc<dyn Watcher> = Inotify::from_fd(fd)?;
+        axfs::notify::add_watch(&path?, mask, &watcher).map_err(|e| match e {
+            // too many levels of symbolic links
+            AxError::InvalidData => LinuxError::ELOOP,
+            e => e.into(),
+        })
+    })
+}
+
+/// Removes the watch `wd` from the inotify instance `fd`.
+pub fn sys_inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    debug!("sys_inotify_rm_watch <= fd: {}, wd: {}", fd, wd);
+    syscall_body!(sys_inotify_rm_watch, {
+        let watcher: Arc<dyn Watcher> = Inotify::from_fd(fd)?;
+        axfs::notify::remove_watch(&watcher, wd)?;
+        Ok(0)
+    })
+}
@@ -0,0 +1,189 @@
+//! The input device `/dev/input/event0`, like the evdev of Linux.
+//!
+//! The events of the main input device are read as `struct input_event`,
+//! always timestamped by the monotonic clock. The autorepeat of the keys held
+//! down is got and set by `EVIOCGREP` and `EVIOCSREP`, or set by writing
+//! `EV_REP` events, and the LEDs are got by `EVIOCGLED` and set by writing
+//! `EV_LED` events.
+
+use alloc::sync::Arc;
+use core::ffi::{c_int, c_uint};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axinput::Event;
+use axio::PollState;
+
+use super::fd_ops::{FD_TABLE, FileLike};
+use crate::ctypes;
+use crate::uaccess::{copy_to_user, get_user, put_user};
+
+/// The path of the device.
+pub(crate) const INPUT_PATH: &str = "/dev/input/event0";
+/// The interval of polling the device in a blocking read.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// The maximum number of events read at once.
+const MAX_READ_EVENTS: usize = 16;
+/// The mask of the length in the ioctl commands.
+const IOC_SIZE_MASK: u32 = 0x3fff << 16;
+
+const EVENT_SIZE: usize = size_of::<ctypes::input_event>();
+
+/// An opened input device.
+pub struct InputFile {
+    nonblocking: AtomicBool,
+}
+
+fn to_input_event(event: &Event) -> ctypes::input_event {
+    ctypes::input_event {
+        time: ctypes::timeval {
+            tv_sec: event.time.as_secs() as _,
+            tv_usec: event.time.subsec_micros() as _,
+        },
+        type_: event.event_type,
+        code: event.code,
+        value: event.value,
+    }
+}
+
+impl FileLike for InputFile {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let max_events = (buf.len() / EVENT_SIZE).min(MAX_READ_EVENTS);
+        if max_events == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut events = [Event {
+            time: Duration::ZERO,
+            event_type: 0,
+            code: 0,
+            value: 0,
+        }; MAX_READ_EVENTS];
+        let count = loop {
+            let count = axinput::read_events(&mut events[..max_events]);
+            if count > 0 {
+                break count;
+            }
+            if self.is_nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            // wake up by the next repeat, or poll the device later
+            let now = axhal::time::monotonic_time();
+            let timeout = axinput::next_repeat()
+                .map_or(POLL_INTERVAL, |at| at.saturating_sub(now))
+                .min(POLL_INTERVAL);
+            axtask::sleep(timeout);
+        };
+        for (chunk, event) in buf.chunks_exact_mut(EVENT_SIZE).zip(&events[..count]) {
+            let event = to_input_event(event);
+            let ptr = chunk.as_mut_ptr() as *mut ctypes::input_event;
+            unsafe { ptr.write_unaligned(event) };
+        }
+        Ok(count * EVENT_SIZE)
+    }
+
+    /// Sets the LEDs by `EV_LED` events and the autorepeat by `EV_REP`
+    /// events, the other events are ignored.
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        if buf.len() < EVENT_SIZE {
+            return Err(LinuxError::EINVAL);
+        }
+        let (mut delay, mut period) = axinput::repeat();
+        let mut repeat_changed = false;
+        for chunk in buf.chunks_exact(EVENT_SIZE) {
+            let ptr = chunk.as_ptr() as *const ctypes::input_event;
+            let event = unsafe { ptr.read_unaligned() };
+            let value = Duration::from_millis(event.value.max(0) as u64);
+            match (event.type_ as u32, event.code as u32) {
+                (ctypes::EV_LED, led) if led <= ctypes::LED_MAX => {
+                    axinput::set_led(led as u16, event.value != 0);
+                }
+                (ctypes::EV_REP, ctypes::REP_DELAY) => {
+                    delay = value;
+                    repeat_changed = true;
+                }
+                (ctypes::EV_REP, ctypes::REP_PERIOD) => {
+                    period = value;
+                    repeat_changed = true;
+                }
+                _ => {}
+            }
+        }
+        if repeat_changed {
+            axinput::set_repeat(delay, period);
+        }
+        Ok(buf.len() / EVENT_SIZE * EVENT_SIZE)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o20000 | 0o660; // S_IFCHR
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_rdev: (13 << 8) | 64, // major number of the input devices
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: axinput::has_events(),
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn ioctl(&self, cmd: u32, arg: usize) -> LinuxResult<c_int> {
+        match cmd {
+            ctypes::EVIOCGVERSION => {
+                put_user(arg as *mut c_int, ctypes::EV_VERSION as _)?;
+            }
+            ctypes::EVIOCGREP => {
+                let (delay, period) = axinput::repeat();
+                let rep: [c_uint; 2] = [delay.as_millis() as _, period.as_millis() as _];
+                put_user(arg as *mut [c_uint; 2], rep)?;
+            }
+            ctypes::EVIOCSREP => {
+                let [delay, period] = get_user(arg as *const [c_uint; 2])?;
+                axinput::set_repeat(
+                    Duration::from_millis(delay as u64),
+                    Duration::from_millis(period as u64),
+                );
+            }
+            cmd if cmd & !IOC_SIZE_MASK == ctypes::EVIOCGLED_BASE => {
+                let leds = (axinput::leds() as u16).to_le_bytes();
+                let len = ((cmd & IOC_SIZE_MASK) >> 16) as usize;
+                let len = len.min(leds.len());
+                copy_to_user(arg as *mut u8, &leds[..len])?;
+                return Ok(len as c_int);
+            }
+            _ => return Err(LinuxError::ENOTTY),
+        }
+        Ok(0)
+    }
+}
+
+/// Opens the input device, called by `sys_open` on [`INPUT_PATH`]. Only
+/// `O_NONBLOCK` and `O_CLOEXEC` in `flags` are supported.
+pub(crate) fn open_input(flags: c_int) -> LinuxResult<c_int> {
+    let file = Arc::new(InputFile {
+        nonblocking: AtomicBool::new(flags as u32 & ctypes::O_NONBLOCK != 0),
+    });
+    FD_TABLE
+        .write()
+        .add(file, flags as u32 & ctypes::O_CLOEXEC != 0)
+}
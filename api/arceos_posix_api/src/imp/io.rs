@@ -1,5 +1,6 @@
 #[cfg(feature = "fd")]
 use crate::imp::fd_ops::get_file_like;
+use crate::uaccess::{get_user, with_user_buf, with_user_buf_mut};
 use crate::{File, ctypes};
 use axerrno::{LinuxError, LinuxResult};
 use axio::SeekFrom;
@@ -13,38 +14,36 @@ use core::ffi::{c_int, c_void};
 pub fn sys_read(fd: c_int, buf: *mut c_void, count: usize) -> ctypes::ssize_t {
     debug!("sys_read <= {} {:#x} {}", fd, buf as usize, count);
     syscall_body!(sys_read, {
-        if buf.is_null() {
-            return Err(LinuxError::EFAULT);
-        }
-        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
+        let len = with_user_buf_mut(buf as *mut u8, count, |dst| {
+            #[cfg(feature = "fd")]
+            {
+                Ok(get_file_like(fd)?.read(dst)?)
+            }
+            #[cfg(not(feature = "fd"))]
+            match fd {
+                0 => Ok(super::stdio::stdin().read(dst)?),
+                1 | 2 => Err(LinuxError::EPERM),
+                _ => Err(LinuxError::EBADF),
+            }
+        })?;
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+fn write_impl(fd: c_int, buf: *const c_void, count: usize) -> LinuxResult<ctypes::ssize_t> {
+    let len = with_user_buf(buf as *const u8, count, |src| {
         #[cfg(feature = "fd")]
         {
-            Ok(get_file_like(fd)?.read(dst)? as ctypes::ssize_t)
+            Ok(get_file_like(fd)?.write(src)?)
         }
         #[cfg(not(feature = "fd"))]
         match fd {
-            0 => Ok(super::stdio::stdin().read(dst)? as ctypes::ssize_t),
-            1 | 2 => Err(LinuxError::EPERM),
+            0 => Err(LinuxError::EPERM),
+            1 | 2 => Ok(super::stdio::stdout().write(src)?),
             _ => Err(LinuxError::EBADF),
         }
-    })
-}
-
-fn write_impl(fd: c_int, buf: *const c_void, count: usize) -> LinuxResult<ctypes::ssize_t> {
-    if buf.is_null() {
-        return Err(LinuxError::EFAULT);
-    }
-    let src = unsafe { core::slice::from_raw_parts(buf as *const u8, count) };
-    #[cfg(feature = "fd")]
-    {
-        Ok(get_file_like(fd)?.write(src)? as ctypes::ssize_t)
-    }
-    #[cfg(not(feature = "fd"))]
-    match fd {
-        0 => Err(LinuxError::EPERM),
-        1 | 2 => Ok(super::stdio::stdout().write(src)? as ctypes::ssize_t),
-        _ => Err(LinuxError::EBADF),
-    }
+    })?;
+    Ok(len as ctypes::ssize_t)
 }
 
 /// Write data to the file indicated by `fd`.
@@ -63,9 +62,9 @@ pub unsafe fn sys_writev(fd: c_int, iov: *const ctypes::iovec, iocnt: c_int) ->
             return Err(LinuxError::EINVAL);
         }
 
-        let iovs = unsafe { core::slice::from_raw_parts(iov, iocnt as usize) };
         let mut ret = 0;
-        for iov in iovs.iter() {
+        for i in 0..iocnt as usize {
+            let iov = get_user(iov.wrapping_add(i))?;
             // TODO: if the `unwrap_or(0)` is correct?
             let result = write_impl(fd, iov.iov_base, iov.iov_len).unwrap_or(0);
             ret += result;
@@ -87,9 +86,9 @@ pub unsafe fn sys_readv(fd: c_int, iov: *const ctypes::iovec, iocnt: c_int) -> c
             return Err(LinuxError::EINVAL);
         }
 
-        let iovs = unsafe { core::slice::from_raw_parts(iov, iocnt as usize) };
         let mut ret = 0;
-        for iov in iovs.iter() {
+        for i in 0..iocnt as usize {
+            let iov = get_user(iov.wrapping_add(i))?;
             let result = sys_read(fd, iov.iov_base, iov.iov_len as usize);
             ret += result;
 
@@ -155,9 +154,7 @@ pub fn sys_sendfile(
                         // save origin offset
                         origin_offset = file.lock().seek(SeekFrom::Current(0))?;
                         // seek to the offset
-                        unsafe {
-                            file.lock().seek(SeekFrom::Start(*offset as _))?;
-                        }
+                        file.lock().seek(SeekFrom::Start(get_user(offset)? as _))?;
                     }
                     None => {
                         // The in_file must be seekable
@@ -190,9 +187,8 @@ pub fn sys_sendfile(
                     Some(file) => {
                         let file = file.inner();
                         // save current offset
-                        unsafe {
-                            *offset = file.lock().seek(SeekFrom::Current(0))? as _;
-                        }
+                        let pos = file.lock().seek(SeekFrom::Current(0))?;
+                        crate::uaccess::put_user(offset, pos as _)?;
                         // restore the origin offset
                         file.lock().seek(SeekFrom::Start(origin_offset))?;
                     }
@@ -5,6 +5,7 @@
 use alloc::collections::BTreeMap;
 use alloc::collections::btree_map::Entry;
 use alloc::sync::Arc;
+use alloc::vec;
 use core::{ffi::c_int, time::Duration};
 
 use axerrno::{LinuxError, LinuxResult};
@@ -12,7 +13,13 @@ use axhal::time::wall_time;
 use axsync::Mutex;
 
 use crate::ctypes;
-use crate::imp::fd_ops::{FileLike, add_file_like, get_file_like};
+use crate::imp::fd_ops::{
+    FileLike, add_file_like, get_file_like, poll_generation, wait_for_poll_events,
+};
+use crate::uaccess::{get_user, put_user_slice};
+
+/// The maximum number of events returned by an `epoll_wait` call.
+const MAX_EVENTS: usize = 1024;
 
 pub struct EpollInstance {
     events: Mutex<BTreeMap<usize, ctypes::epoll_event>>,
@@ -78,6 +85,9 @@ impl EpollInstance {
         let mut events_num = 0;
 
         for (infd, ev) in ready_list.iter() {
+            if events_num == events.len() {
+                break;
+            }
             match get_file_like(*infd as c_int)?.poll() {
                 Err(_) => {
                     if (ev.events & ctypes::EPOLLERR) != 0 {
@@ -93,7 +103,10 @@ impl EpollInstance {
                         events_num += 1;
                     }
 
-                    if state.writable && (ev.events & ctypes::EPOLLOUT != 0) {
+                    if state.writable
+                        && (ev.events & ctypes::EPOLLOUT != 0)
+                        && events_num < events.len()
+                    {
                         events[events_num].events = ctypes::EPOLLOUT;
                         events[events_num].data = ev.data;
                         events_num += 1;
@@ -124,6 +137,10 @@ impl FileLike for EpollInstance {
         })
     }
 
+    fn link_target(&self) -> alloc::string::String {
+        "anon_inode:[eventpoll]".into()
+    }
+
     fn into_any(self: Arc<Self>) -> alloc::sync::Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -160,10 +177,14 @@ pub unsafe fn sys_epoll_ctl(
 ) -> c_int {
     debug!("sys_epoll_ctl <= epfd: {} op: {} fd: {}", epfd, op, fd);
     syscall_body!(sys_epoll_ctl, {
-        let ret = unsafe {
-            EpollInstance::from_fd(epfd)?.control(op as usize, fd as usize, &(*event))? as c_int
+        // The event is ignored for `EPOLL_CTL_DEL`, and may be NULL.
+        let event = if op as u32 == ctypes::EPOLL_CTL_DEL {
+            ctypes::epoll_event::default()
+        } else {
+            get_user(event)?
         };
-        Ok(ret)
+        let ret = EpollInstance::from_fd(epfd)?.control(op as usize, fd as usize, &event)?;
+        Ok(ret as c_int)
     })
 }
 
@@ -183,15 +204,22 @@ pub unsafe fn sys_epoll_wait(
         if maxevents <= 0 {
             return Err(LinuxError::EINVAL);
         }
-        let events = unsafe { core::slice::from_raw_parts_mut(events, maxevents as usize) };
+        if events.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        // The events are collected in the kernel, the others are left for
+        // the next call.
+        let mut buf = vec![ctypes::epoll_event::default(); (maxevents as usize).min(MAX_EVENTS)];
         let deadline =
             (!timeout.is_negative()).then(|| wall_time() + Duration::from_millis(timeout as u64));
         let epoll_instance = EpollInstance::from_fd(epfd)?;
         loop {
+            let generation = poll_generation();
             #[cfg(feature = "net")]
             axnet::poll_interfaces();
-            let events_num = epoll_instance.poll_all(events)?;
+            let events_num = epoll_instance.poll_all(&mut buf)?;
             if events_num > 0 {
+                put_user_slice(events, &buf[..events_num])?;
                 return Ok(events_num as c_int);
             }
 
@@ -199,7 +227,7 @@ pub unsafe fn sys_epoll_wait(
                 debug!("    timeout!");
                 return Ok(0);
             }
-            crate::sys_sched_yield();
+            wait_for_poll_events(generation, deadline);
         }
     })
 }
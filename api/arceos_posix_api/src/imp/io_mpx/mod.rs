@@ -1,6 +1,7 @@
 //! I/O multiplexing:
 //!
 //! * [`select`](select::sys_select)
+//! * [`pselect6`](select::sys_pselect6)
 //! * [`epoll_create`](epoll::sys_epoll_create)
 //! * [`epoll_ctl`](epoll::sys_epoll_ctl)
 //! * [`epoll_wait`](epoll::sys_epoll_wait)
@@ -13,4 +14,4 @@ mod select;
 #[cfg(feature = "epoll")]
 pub use self::epoll::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
 #[cfg(feature = "select")]
-pub use self::select::sys_select;
+pub use self::select::{sys_pselect6, sys_select};
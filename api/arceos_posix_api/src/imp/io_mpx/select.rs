@@ -1,9 +1,12 @@
-use core::ffi::c_int;
+use core::ffi::{c_int, c_void};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
 use axhal::time::wall_time;
 
-use crate::{ctypes, imp::fd_ops::get_file_like};
+use crate::ctypes;
+use crate::imp::fd_ops::{get_file_like, poll_generation, wait_for_poll_events};
+use crate::uaccess::{get_user, get_user_slice, put_user, put_user_slice};
 
 const FD_SETSIZE: usize = 1024;
 const BITS_PER_USIZE: usize = usize::BITS as usize;
@@ -20,52 +23,29 @@ impl FdSets {
         read_fds: *const ctypes::fd_set,
         write_fds: *const ctypes::fd_set,
         except_fds: *const ctypes::fd_set,
-    ) -> Self {
+    ) -> LinuxResult<Self> {
         let nfds = nfds.min(FD_SETSIZE);
         let nfds_usizes = nfds.div_ceil(BITS_PER_USIZE);
-        let mut bits = core::mem::MaybeUninit::<[usize; FD_SETSIZE_USIZES * 3]>::uninit();
-        let bits_ptr: *mut usize = unsafe { core::mem::transmute(bits.as_mut_ptr()) };
-
-        let copy_from_fd_set = |bits_ptr: *mut usize, fds: *const ctypes::fd_set| unsafe {
-            let dst = core::slice::from_raw_parts_mut(bits_ptr, nfds_usizes);
-            if fds.is_null() {
-                dst.fill(0);
-            } else {
-                let fds_ptr = (*fds).fds_bits.as_ptr() as *const usize;
-                let src = core::slice::from_raw_parts(fds_ptr, nfds_usizes);
-                dst.copy_from_slice(src);
+        let mut bits = [0; FD_SETSIZE_USIZES * 3];
+        for (k, fds) in [read_fds, write_fds, except_fds].into_iter().enumerate() {
+            if !fds.is_null() {
+                let dst = &mut bits[k * FD_SETSIZE_USIZES..][..nfds_usizes];
+                get_user_slice(dst, fds as *const usize)?;
             }
-        };
-
-        let bits = unsafe {
-            copy_from_fd_set(bits_ptr, read_fds);
-            copy_from_fd_set(bits_ptr.add(FD_SETSIZE_USIZES), write_fds);
-            copy_from_fd_set(bits_ptr.add(FD_SETSIZE_USIZES * 2), except_fds);
-            bits.assume_init()
-        };
-        Self { nfds, bits }
+        }
+        Ok(Self { nfds, bits })
     }
 
-    fn poll_all(
-        &self,
-        res_read_fds: *mut ctypes::fd_set,
-        res_write_fds: *mut ctypes::fd_set,
-        res_except_fds: *mut ctypes::fd_set,
-    ) -> LinuxResult<usize> {
-        let mut read_bits_ptr = self.bits.as_ptr();
-        let mut write_bits_ptr = unsafe { read_bits_ptr.add(FD_SETSIZE_USIZES) };
-        let mut execpt_bits_ptr = unsafe { read_bits_ptr.add(FD_SETSIZE_USIZES * 2) };
+    /// Polls the files in the sets, and sets the bits of the ready ones in
+    /// `res`, laid out like `self.bits`.
+    fn poll_all(&self, res: &mut [usize; FD_SETSIZE_USIZES * 3]) -> LinuxResult<usize> {
         let mut i = 0;
         let mut res_num = 0;
         while i < self.nfds {
-            let read_bits = unsafe { *read_bits_ptr };
-            let write_bits = unsafe { *write_bits_ptr };
-            let except_bits = unsafe { *execpt_bits_ptr };
-            unsafe {
-                read_bits_ptr = read_bits_ptr.add(1);
-                write_bits_ptr = write_bits_ptr.add(1);
-                execpt_bits_ptr = execpt_bits_ptr.add(1);
-            }
+            let k = i / BITS_PER_USIZE;
+            let read_bits = self.bits[k];
+            let write_bits = self.bits[FD_SETSIZE_USIZES + k];
+            let except_bits = self.bits[FD_SETSIZE_USIZES * 2 + k];
 
             let all_bits = read_bits | write_bits | except_bits;
             if all_bits == 0 {
@@ -83,18 +63,18 @@ impl FdSets {
                 match get_file_like(fd as _)?.poll() {
                     Ok(state) => {
                         if state.readable && read_bits & bit != 0 {
-                            unsafe { set_fd_set(res_read_fds, fd) };
+                            res[k] |= bit;
                             res_num += 1;
                         }
                         if state.writable && write_bits & bit != 0 {
-                            unsafe { set_fd_set(res_write_fds, fd) };
+                            res[FD_SETSIZE_USIZES + k] |= bit;
                             res_num += 1;
                         }
                     }
                     Err(e) => {
                         debug!("    except: {} {:?}", fd, e);
                         if except_bits & bit != 0 {
-                            unsafe { set_fd_set(res_except_fds, fd) };
+                            res[FD_SETSIZE_USIZES * 2 + k] |= bit;
                             res_num += 1;
                         }
                     }
@@ -107,6 +87,45 @@ impl FdSets {
     }
 }
 
+/// Waits until one or more of the file descriptors in the sets become ready,
+/// or the `deadline` (in wall time) is reached.
+fn select_impl(
+    nfds: c_int,
+    readfds: *mut ctypes::fd_set,
+    writefds: *mut ctypes::fd_set,
+    exceptfds: *mut ctypes::fd_set,
+    deadline: Option<Duration>,
+) -> LinuxResult<usize> {
+    if nfds < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let nfds = (nfds as usize).min(FD_SETSIZE);
+    let fd_sets = FdSets::from(nfds, readfds, writefds, exceptfds)?;
+    let mut res_bits = [0; FD_SETSIZE_USIZES * 3];
+
+    loop {
+        let generation = poll_generation();
+        #[cfg(feature = "net")]
+        axnet::poll_interfaces();
+        let res = fd_sets.poll_all(&mut res_bits)?;
+        if res > 0 {
+            write_fd_sets([readfds, writefds, exceptfds], &res_bits, nfds)?;
+            return Ok(res);
+        }
+
+        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+            debug!("    timeout!");
+            write_fd_sets([readfds, writefds, exceptfds], &res_bits, nfds)?;
+            return Ok(0);
+        }
+        #[cfg(feature = "signal")]
+        if crate::imp::signal::has_pending_signal() {
+            return Err(LinuxError::EINTR);
+        }
+        wait_for_poll_events(generation, deadline);
+    }
+}
+
 /// Monitor multiple file descriptors, waiting until one or more of the file descriptors become "ready" for some class of I/O operation
 pub unsafe fn sys_select(
     nfds: c_int,
@@ -120,46 +139,84 @@ pub unsafe fn sys_select(
         nfds, readfds as usize, writefds as usize, exceptfds as usize
     );
     syscall_body!(sys_select, {
-        if nfds < 0 {
-            return Err(LinuxError::EINVAL);
-        }
-        let nfds = (nfds as usize).min(FD_SETSIZE);
-        let deadline = unsafe { timeout.as_ref().map(|t| wall_time() + (*t).into()) };
-        let fd_sets = FdSets::from(nfds, readfds, writefds, exceptfds);
-
-        unsafe {
-            zero_fd_set(readfds, nfds);
-            zero_fd_set(writefds, nfds);
-            zero_fd_set(exceptfds, nfds);
-        }
-
-        loop {
-            #[cfg(feature = "net")]
-            axnet::poll_interfaces();
-            let res = fd_sets.poll_all(readfds, writefds, exceptfds)?;
-            if res > 0 {
-                return Ok(res);
-            }
-
-            if deadline.is_some_and(|ddl| wall_time() >= ddl) {
-                debug!("    timeout!");
-                return Ok(0);
+        let t = (!timeout.is_null())
+            .then(|| get_user(timeout))
+            .transpose()?;
+        let deadline = match t {
+            Some(t) if t.tv_sec < 0 || t.tv_usec < 0 || t.tv_usec >= 1_000_000 => {
+                return Err(LinuxError::EINVAL);
             }
-            crate::sys_sched_yield();
+            Some(t) => Some(wall_time() + t.into()),
+            None => None,
+        };
+        let res = select_impl(nfds, readfds, writefds, exceptfds, deadline)?;
+        // Like Linux, update the timeout to the remaining time.
+        if let Some(ddl) = deadline {
+            let remaining = ddl.saturating_sub(wall_time());
+            let t = ctypes::timeval {
+                tv_sec: remaining.as_secs() as _,
+                tv_usec: remaining.subsec_micros() as _,
+            };
+            put_user(timeout, t)?;
         }
+        Ok(res)
     })
 }
 
-unsafe fn zero_fd_set(fds: *mut ctypes::fd_set, nfds: usize) {
-    if !fds.is_null() {
-        let nfds_usizes = nfds.div_ceil(BITS_PER_USIZE);
-        let dst = &mut unsafe { *fds }.fds_bits[..nfds_usizes];
-        dst.fill(0);
-    }
+/// Like [`sys_select`], but the timeout is a `timespec` which is not updated,
+/// and the signal mask is replaced by `sigmask` during the call if it is not
+/// NULL.
+///
+/// Only the first 64 signals of `sigmask` are used.
+pub unsafe fn sys_pselect6(
+    nfds: c_int,
+    readfds: *mut ctypes::fd_set,
+    writefds: *mut ctypes::fd_set,
+    exceptfds: *mut ctypes::fd_set,
+    timeout: *const ctypes::timespec,
+    sigmask: *const c_void,
+) -> c_int {
+    debug!(
+        "sys_pselect6 <= {} {:#x} {:#x} {:#x} {:#x}",
+        nfds, readfds as usize, writefds as usize, exceptfds as usize, sigmask as usize
+    );
+    syscall_body!(sys_pselect6, {
+        let t = (!timeout.is_null())
+            .then(|| get_user(timeout))
+            .transpose()?;
+        let deadline = match t {
+            Some(t) if t.tv_sec < 0 || t.tv_nsec < 0 || t.tv_nsec >= 1_000_000_000 => {
+                return Err(LinuxError::EINVAL);
+            }
+            Some(t) => Some(wall_time() + t.into()),
+            None => None,
+        };
+        #[cfg(feature = "signal")]
+        let old_mask = crate::imp::signal::replace_mask(sigmask.cast())?;
+        #[cfg(not(feature = "signal"))]
+        let _ = sigmask;
+        let res = select_impl(nfds, readfds, writefds, exceptfds, deadline);
+        #[cfg(feature = "signal")]
+        crate::imp::signal::restore_mask(old_mask);
+        res
+    })
 }
 
-unsafe fn set_fd_set(fds: *mut ctypes::fd_set, fd: usize) {
-    if !fds.is_null() {
-        unsafe { *fds }.fds_bits[fd / BITS_PER_USIZE] |= 1 << (fd % BITS_PER_USIZE);
+/// Writes the ready sets in `res` back to the non-null sets of the caller,
+/// with the bits of the other files of the first `nfds` cleared.
+fn write_fd_sets(
+    fds: [*mut ctypes::fd_set; 3],
+    res: &[usize; FD_SETSIZE_USIZES * 3],
+    nfds: usize,
+) -> LinuxResult {
+    let nfds_usizes = nfds.div_ceil(BITS_PER_USIZE);
+    for (k, fds) in fds.into_iter().enumerate() {
+        if !fds.is_null() {
+            put_user_slice(
+                fds as *mut usize,
+                &res[k * FD_SETSIZE_USIZES..][..nfds_usizes],
+            )?;
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,169 @@
+//! System V IPC: semaphore sets, message queues, and shared memory segments
+//! with the `shm` feature.
+//!
+//! IPC objects live in a global namespace, and are identified by their IDs.
+//! A snapshot of the objects is exported to `/proc/sysvipc/{sem,msg,shm}` if
+//! the file system is enabled.
+
+mod msg;
+mod sem;
+#[cfg(feature = "shm")]
+mod shm;
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::ffi::c_int;
+
+use axerrno::{LinuxError, LinuxResult};
+
+use crate::ctypes;
+
+pub use self::msg::{sys_msgctl, sys_msgget, sys_msgrcv, sys_msgsnd};
+pub use self::sem::{sys_semctl, sys_semget, sys_semop, sys_semtimedop};
+#[cfg(all(feature = "shm", feature = "uspace"))]
+pub(crate) use self::shm::{fork_attachments, release_attachments};
+#[cfg(feature = "shm")]
+pub use self::shm::{sys_shmat, sys_shmctl, sys_shmdt, sys_shmget};
+
+/// The key to always create a new IPC object.
+const IPC_PRIVATE: ctypes::key_t = 0;
+
+/// Permissions of an IPC object.
+#[derive(Clone, Copy)]
+struct IpcPerm {
+    key: ctypes::key_t,
+    uid: ctypes::uid_t,
+    gid: ctypes::gid_t,
+    cuid: ctypes::uid_t,
+    cgid: ctypes::gid_t,
+    mode: ctypes::mode_t,
+}
+
+impl IpcPerm {
+    fn new(key: ctypes::key_t, flags: c_int) -> Self {
+        Self {
+            key,
+            uid: 0,
+            gid: 0,
+            cuid: 0,
+            cgid: 0,
+            mode: flags as ctypes::mode_t & 0o777,
+        }
+    }
+
+    /// Updates the fields that can be changed by `IPC_SET`.
+    fn set(&mut self, perm: &ctypes::ipc_perm) {
+        self.uid = perm.uid;
+        self.gid = perm.gid;
+        self.mode = perm.mode & 0o777;
+    }
+
+    fn to_ctype(self) -> ctypes::ipc_perm {
+        ctypes::ipc_perm {
+            __key: self.key,
+            uid: self.uid,
+            gid: self.gid,
+            cuid: self.cuid,
+            cgid: self.cgid,
+            mode: self.mode,
+            ..Default::default()
+        }
+    }
+}
+
+/// A collection of IPC objects of the same type, indexed by ID and by key.
+struct IpcNamespace<T> {
+    keys: BTreeMap<ctypes::key_t, c_int>,
+    objs: BTreeMap<c_int, Arc<T>>,
+    next_id: c_int,
+}
+
+impl<T> IpcNamespace<T> {
+    const fn new() -> Self {
+        Self {
+            keys: BTreeMap::new(),
+            objs: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn get(&self, id: c_int) -> LinuxResult<Arc<T>> {
+        self.objs.get(&id).cloned().ok_or(LinuxError::EINVAL)
+    }
+
+    /// Looks up the object associated with `key`, or creates a new one with
+    /// `create` according to `flags` (`IPC_CREAT` and `IPC_EXCL`).
+    ///
+    /// Returns the object ID and the object.
+    fn get_or_create<F>(
+        &mut self,
+        key: ctypes::key_t,
+        flags: c_int,
+        create: F,
+    ) -> LinuxResult<(c_int, Arc<T>)>
+    where
+        F: FnOnce() -> LinuxResult<T>,
+    {
+        let flags = flags as u32;
+        if key != IPC_PRIVATE {
+            if let Some(&id) = self.keys.get(&key) {
+                if flags & ctypes::IPC_CREAT != 0 && flags & ctypes::IPC_EXCL != 0 {
+                    return Err(LinuxError::EEXIST);
+                }
+                return Ok((id, self.objs[&id].clone()));
+            }
+            if flags & ctypes::IPC_CREAT == 0 {
+                return Err(LinuxError::ENOENT);
+            }
+        }
+        let id = self.alloc_id()?;
+        let obj = Arc::new(create()?);
+        if key != IPC_PRIVATE {
+            self.keys.insert(key, id);
+        }
+        self.objs.insert(id, obj.clone());
+        Ok((id, obj))
+    }
+
+    fn alloc_id(&mut self) -> LinuxResult<c_int> {
+        for _ in 0..c_int::MAX {
+            let id = self.next_id;
+            self.next_id = self.next_id.checked_add(1).unwrap_or(0);
+            if !self.objs.contains_key(&id) {
+                return Ok(id);
+            }
+        }
+        Err(LinuxError::ENOSPC)
+    }
+
+    fn remove(&mut self, id: c_int, key: ctypes::key_t) -> Option<Arc<T>> {
+        if self.keys.get(&key) == Some(&id) {
+            self.keys.remove(&key);
+        }
+        self.objs.remove(&id)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&c_int, &Arc<T>)> {
+        self.objs.iter()
+    }
+}
+
+fn current_pid() -> ctypes::pid_t {
+    axtask::current().id().as_u64() as ctypes::pid_t
+}
+
+fn current_time() -> ctypes::time_t {
+    axhal::time::wall_time().as_secs() as ctypes::time_t
+}
+
+/// Writes a snapshot of IPC objects to the file under `/proc/sysvipc`.
+#[cfg(feature = "fs")]
+fn update_proc_file(name: &str, content: &str) {
+    let path = alloc::format!("/proc/sysvipc/{}", name);
+    if let Err(e) = axfs::api::write(&path, content) {
+        debug!("failed to update {}: {:?}", path, e);
+    }
+}
+
+#[cfg(not(feature = "fs"))]
+fn update_proc_file(_name: &str, _content: &str) {}
@@ -0,0 +1,330 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::{c_int, c_long, c_void};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Mutex;
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+use super::{IpcNamespace, IpcPerm, current_pid, current_time, update_proc_file};
+use crate::ctypes;
+use crate::uaccess::{copy_from_user, copy_to_user, get_user, put_user};
+
+/// The maximum size of a message.
+const MSGMAX: usize = 8192;
+/// The default maximum number of bytes in a queue.
+const MSGMNB: usize = 16384;
+
+struct Message {
+    mtype: c_long,
+    data: Vec<u8>,
+}
+
+struct MsgQueueInner {
+    perm: IpcPerm,
+    msgs: VecDeque<Message>,
+    /// The current number of bytes in the queue.
+    cbytes: usize,
+    /// The maximum number of bytes allowed in the queue.
+    qbytes: usize,
+    lspid: ctypes::pid_t,
+    lrpid: ctypes::pid_t,
+    stime: ctypes::time_t,
+    rtime: ctypes::time_t,
+    ctime: ctypes::time_t,
+}
+
+impl MsgQueueInner {
+    /// Finds the message to be received according to `msgtyp`:
+    ///
+    /// - `msgtyp == 0`: the first message in the queue.
+    /// - `msgtyp > 0`: the first message of type `msgtyp`, or the first
+    ///   message not of type `msgtyp` if `MSG_EXCEPT` is specified.
+    /// - `msgtyp < 0`: the first message with the lowest type less than or
+    ///   equal to the absolute value of `msgtyp`.
+    fn find(&self, msgtyp: c_long, except: bool) -> Option<usize> {
+        match msgtyp {
+            0 => (!self.msgs.is_empty()).then_some(0),
+            t if t > 0 => self.msgs.iter().position(|m| (m.mtype == t) != except),
+            t => self
+                .msgs
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.mtype <= -t)
+                .min_by_key(|(_, m)| m.mtype)
+                .map(|(i, _)| i),
+        }
+    }
+}
+
+/// A System V message queue.
+struct MsgQueue {
+    inner: SpinNoIrq<MsgQueueInner>,
+    /// Tasks waiting for free space in the queue.
+    send_wq: WaitQueue,
+    /// Tasks waiting for messages to arrive.
+    recv_wq: WaitQueue,
+    removed: AtomicBool,
+}
+
+impl MsgQueue {
+    fn new(perm: IpcPerm) -> Self {
+        Self {
+            inner: SpinNoIrq::new(MsgQueueInner {
+                perm,
+                msgs: VecDeque::new(),
+                cbytes: 0,
+                qbytes: MSGMNB,
+                lspid: 0,
+                lrpid: 0,
+                stime: 0,
+                rtime: 0,
+                ctime: current_time(),
+            }),
+            send_wq: WaitQueue::new(),
+            recv_wq: WaitQueue::new(),
+            removed: AtomicBool::new(false),
+        }
+    }
+
+    fn check_removed(&self) -> LinuxResult {
+        if self.removed.load(Ordering::Acquire) {
+            Err(LinuxError::EIDRM)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn send(&self, mtype: c_long, data: &[u8], nowait: bool) -> LinuxResult {
+        loop {
+            self.check_removed()?;
+            {
+                let mut inner = self.inner.lock();
+                if inner.cbytes + data.len() <= inner.qbytes {
+                    inner.cbytes += data.len();
+                    inner.msgs.push_back(Message {
+                        mtype,
+                        data: data.to_vec(),
+                    });
+                    inner.lspid = current_pid();
+                    inner.stime = current_time();
+                    drop(inner);
+                    self.recv_wq.notify_all(true);
+                    return Ok(());
+                }
+            }
+            if nowait {
+                return Err(LinuxError::EAGAIN);
+            }
+            self.send_wq.wait_until(|| {
+                let inner = self.inner.lock();
+                inner.cbytes + data.len() <= inner.qbytes || self.removed.load(Ordering::Acquire)
+            });
+        }
+    }
+
+    fn receive(&self, buf: &mut [u8], msgtyp: c_long, flags: u32) -> LinuxResult<(c_long, usize)> {
+        let except = flags & ctypes::MSG_EXCEPT != 0;
+        loop {
+            self.check_removed()?;
+            {
+                let mut inner = self.inner.lock();
+                if let Some(idx) = inner.find(msgtyp, except) {
+                    let len = inner.msgs[idx].data.len();
+                    if len > buf.len() && flags & ctypes::MSG_NOERROR == 0 {
+                        return Err(LinuxError::E2BIG);
+                    }
+                    let msg = inner.msgs.remove(idx).unwrap();
+                    inner.cbytes -= len;
+                    inner.lrpid = current_pid();
+                    inner.rtime = current_time();
+                    drop(inner);
+                    // The message is truncated if `MSG_NOERROR` is specified.
+                    let len = len.min(buf.len());
+                    buf[..len].copy_from_slice(&msg.data[..len]);
+                    self.send_wq.notify_all(true);
+                    return Ok((msg.mtype, len));
+                }
+            }
+            if flags & ctypes::IPC_NOWAIT != 0 {
+                return Err(LinuxError::ENOMSG);
+            }
+            self.recv_wq.wait_until(|| {
+                self.inner.lock().find(msgtyp, except).is_some()
+                    || self.removed.load(Ordering::Acquire)
+            });
+        }
+    }
+
+    fn stat(&self) -> ctypes::msqid_ds {
+        let inner = self.inner.lock();
+        ctypes::msqid_ds {
+            msg_perm: inner.perm.to_ctype(),
+            msg_stime: inner.stime,
+            msg_rtime: inner.rtime,
+            msg_ctime: inner.ctime,
+            __msg_cbytes: inner.cbytes as _,
+            msg_qnum: inner.msgs.len() as _,
+            msg_qbytes: inner.qbytes as _,
+            msg_lspid: inner.lspid,
+            msg_lrpid: inner.lrpid,
+            ..Default::default()
+        }
+    }
+}
+
+static MSG_NAMESPACE: Mutex<IpcNamespace<MsgQueue>> = Mutex::new(IpcNamespace::new());
+
+fn msg_queue(msqid: c_int) -> LinuxResult<Arc<MsgQueue>> {
+    MSG_NAMESPACE.lock().get(msqid)
+}
+
+fn update_proc_msg(ns: &IpcNamespace<MsgQueue>) {
+    let mut content = String::from(
+        "       key      msqid perms      cbytes       qnum lspid lrpid   uid   gid  cuid  cgid      stime      rtime      ctime\n",
+    );
+    for (id, queue) in ns.iter() {
+        let inner = queue.inner.lock();
+        let perm = &inner.perm;
+        let _ = writeln!(
+            content,
+            "{:>10} {:>10}  {:>4o}  {:>10} {:>10} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>10} {:>10} {:>10}",
+            perm.key,
+            id,
+            perm.mode,
+            inner.cbytes,
+            inner.msgs.len(),
+            inner.lspid,
+            inner.lrpid,
+            perm.uid,
+            perm.gid,
+            perm.cuid,
+            perm.cgid,
+            inner.stime,
+            inner.rtime,
+            inner.ctime
+        );
+    }
+    update_proc_file("msg", &content);
+}
+
+/// Get a System V message queue identifier.
+///
+/// A new queue is created if `key` is `IPC_PRIVATE`, or no queue is
+/// associated with `key` and `IPC_CREAT` is specified.
+pub fn sys_msgget(key: ctypes::key_t, msgflg: c_int) -> c_int {
+    debug!("sys_msgget <= key: {}, msgflg: {:#o}", key, msgflg);
+    syscall_body!(sys_msgget, {
+        let mut ns = MSG_NAMESPACE.lock();
+        let (id, _) =
+            ns.get_or_create(key, msgflg, || Ok(MsgQueue::new(IpcPerm::new(key, msgflg))))?;
+        update_proc_msg(&ns);
+        Ok(id)
+    })
+}
+
+/// Send a message to a System V message queue.
+///
+/// `msgp` points to a `struct msgbuf`, which begins with a `long` message
+/// type, followed by `msgsz` bytes of message text.
+pub fn sys_msgsnd(msqid: c_int, msgp: *const c_void, msgsz: usize, msgflg: c_int) -> c_int {
+    debug!(
+        "sys_msgsnd <= msqid: {}, msgsz: {}, msgflg: {:#o}",
+        msqid, msgsz, msgflg
+    );
+    syscall_body!(sys_msgsnd, {
+        if msgsz > MSGMAX {
+            return Err(LinuxError::EINVAL);
+        }
+        let mtype = get_user(msgp as *const c_long)?;
+        if mtype < 1 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut data = vec![0; msgsz];
+        copy_from_user(
+            &mut data,
+            (msgp as *const c_long).wrapping_add(1) as *const u8,
+        )?;
+        let queue = msg_queue(msqid)?;
+        queue.send(mtype, &data, msgflg as u32 & ctypes::IPC_NOWAIT != 0)?;
+        Ok(0)
+    })
+}
+
+/// Receive a message from a System V message queue.
+///
+/// Returns the number of bytes copied into the message text of `msgp`.
+pub fn sys_msgrcv(
+    msqid: c_int,
+    msgp: *mut c_void,
+    msgsz: usize,
+    msgtyp: c_long,
+    msgflg: c_int,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_msgrcv <= msqid: {}, msgsz: {}, msgtyp: {}, msgflg: {:#o}",
+        msqid, msgsz, msgtyp, msgflg
+    );
+    syscall_body!(sys_msgrcv, {
+        if (msgsz as isize) < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if msgp.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        // No message is longer than `MSGMAX`.
+        let mut buf = vec![0; msgsz.min(MSGMAX)];
+        let queue = msg_queue(msqid)?;
+        let (mtype, len) = queue.receive(&mut buf, msgtyp, msgflg as u32)?;
+        put_user(msgp as *mut c_long, mtype)?;
+        copy_to_user(
+            (msgp as *mut c_long).wrapping_add(1) as *mut u8,
+            &buf[..len],
+        )?;
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// System V message queue control operations.
+pub fn sys_msgctl(msqid: c_int, cmd: c_int, buf: *mut ctypes::msqid_ds) -> c_int {
+    debug!("sys_msgctl <= msqid: {}, cmd: {}", msqid, cmd);
+    syscall_body!(sys_msgctl, {
+        let queue = msg_queue(msqid)?;
+        match cmd as u32 {
+            ctypes::IPC_RMID => {
+                let mut ns = MSG_NAMESPACE.lock();
+                let key = queue.inner.lock().perm.key;
+                ns.remove(msqid, key);
+                queue.removed.store(true, Ordering::Release);
+                queue.send_wq.notify_all(true);
+                queue.recv_wq.notify_all(true);
+                update_proc_msg(&ns);
+                Ok(0)
+            }
+            ctypes::IPC_STAT => {
+                put_user(buf, queue.stat())?;
+                Ok(0)
+            }
+            ctypes::IPC_SET => {
+                let ds = get_user(buf)?;
+                {
+                    let mut inner = queue.inner.lock();
+                    inner.perm.set(&ds.msg_perm);
+                    inner.qbytes = ds.msg_qbytes as usize;
+                    inner.ctime = current_time();
+                }
+                // More space may be available now.
+                queue.send_wq.notify_all(true);
+                update_proc_msg(&MSG_NAMESPACE.lock());
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
@@ -0,0 +1,390 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::{c_int, c_ushort};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Mutex;
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+use super::{IpcNamespace, IpcPerm, current_pid, current_time, update_proc_file};
+use crate::ctypes;
+use crate::imp::task::wait_until_deadline;
+use crate::uaccess::{get_user, get_user_slice, put_user, put_user_slice};
+
+/// The maximum number of semaphores in a set.
+const SEMMSL: usize = 32000;
+/// The maximum value of a semaphore.
+const SEMVMX: i32 = 32767;
+/// The maximum number of operations in a single `semop` call.
+const SEMOPM: usize = 500;
+
+#[derive(Default, Clone, Copy)]
+struct Semaphore {
+    val: i32,
+    /// The PID of the last task that operated on the semaphore.
+    pid: ctypes::pid_t,
+    /// The number of tasks waiting for the value to increase.
+    ncnt: usize,
+    /// The number of tasks waiting for the value to become zero.
+    zcnt: usize,
+}
+
+struct SemSetInner {
+    perm: IpcPerm,
+    sems: Vec<Semaphore>,
+    otime: ctypes::time_t,
+    ctime: ctypes::time_t,
+}
+
+/// A System V semaphore set.
+struct SemSet {
+    inner: SpinNoIrq<SemSetInner>,
+    /// Tasks blocked in `semop`.
+    wq: WaitQueue,
+    /// Incremented on every change of semaphore values, to wake up waiters.
+    seq: AtomicUsize,
+    removed: AtomicBool,
+}
+
+/// The result of trying to apply semaphore operations.
+enum SemOpResult {
+    Done,
+    /// The operation on the given semaphore would block. `true` if it waits
+    /// for zero, `false` if it waits for the value to increase.
+    WouldBlock(usize, bool),
+}
+
+impl SemSet {
+    fn new(perm: IpcPerm, nsems: usize) -> Self {
+        let now = current_time();
+        Self {
+            inner: SpinNoIrq::new(SemSetInner {
+                perm,
+                sems: alloc::vec![Semaphore::default(); nsems],
+                otime: 0,
+                ctime: now,
+            }),
+            wq: WaitQueue::new(),
+            seq: AtomicUsize::new(0),
+            removed: AtomicBool::new(false),
+        }
+    }
+
+    fn nsems(&self) -> usize {
+        self.inner.lock().sems.len()
+    }
+
+    fn check_removed(&self) -> LinuxResult {
+        if self.removed.load(Ordering::Acquire) {
+            Err(LinuxError::EIDRM)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Wakes up all the tasks blocked in `semop`, to let them retry.
+    fn wake_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.wq.notify_all(true);
+    }
+
+    /// Applies all operations atomically: either all of them are performed,
+    /// or none of them.
+    fn try_apply(&self, sops: &[ctypes::sembuf]) -> LinuxResult<SemOpResult> {
+        let mut inner = self.inner.lock();
+        let mut vals: Vec<i32> = inner.sems.iter().map(|s| s.val).collect();
+        for op in sops {
+            let num = op.sem_num as usize;
+            let val = &mut vals[num];
+            let sem_op = op.sem_op as i32;
+            if sem_op == 0 {
+                if *val != 0 {
+                    return Ok(SemOpResult::WouldBlock(num, true));
+                }
+            } else if *val + sem_op < 0 {
+                return Ok(SemOpResult::WouldBlock(num, false));
+            } else if *val + sem_op > SEMVMX {
+                return Err(LinuxError::ERANGE);
+            } else {
+                *val += sem_op;
+            }
+        }
+        let pid = current_pid();
+        for op in sops {
+            inner.sems[op.sem_num as usize].pid = pid;
+        }
+        for (sem, val) in inner.sems.iter_mut().zip(vals) {
+            sem.val = val;
+        }
+        inner.otime = current_time();
+        Ok(SemOpResult::Done)
+    }
+
+    fn semop(&self, sops: &[ctypes::sembuf], deadline: Option<Duration>) -> LinuxResult {
+        loop {
+            self.check_removed()?;
+            let seq = self.seq.load(Ordering::Acquire);
+            let (num, wait_zero) = match self.try_apply(sops)? {
+                SemOpResult::Done => {
+                    if sops.iter().any(|op| op.sem_op != 0) {
+                        self.wake_all();
+                    }
+                    return Ok(());
+                }
+                SemOpResult::WouldBlock(num, wait_zero) => (num, wait_zero),
+            };
+            let flags = sops
+                .iter()
+                .find(|op| op.sem_num as usize == num)
+                .map_or(0, |op| op.sem_flg as u32);
+            if flags & ctypes::IPC_NOWAIT != 0 {
+                return Err(LinuxError::EAGAIN);
+            }
+
+            let update_cnt = |inc: bool| {
+                let mut inner = self.inner.lock();
+                let sem = &mut inner.sems[num];
+                let cnt = if wait_zero {
+                    &mut sem.zcnt
+                } else {
+                    &mut sem.ncnt
+                };
+                if inc {
+                    *cnt += 1;
+                } else {
+                    *cnt -= 1;
+                }
+            };
+            update_cnt(true);
+            let res = wait_until_deadline(&self.wq, deadline, || {
+                self.seq.load(Ordering::Acquire) != seq || self.removed.load(Ordering::Acquire)
+            });
+            update_cnt(false);
+            res.map_err(|e| match e {
+                LinuxError::ETIMEDOUT => LinuxError::EAGAIN,
+                e => e,
+            })?;
+        }
+    }
+
+    fn stat(&self) -> ctypes::semid_ds {
+        let inner = self.inner.lock();
+        ctypes::semid_ds {
+            sem_perm: inner.perm.to_ctype(),
+            sem_otime: inner.otime,
+            sem_ctime: inner.ctime,
+            sem_nsems: inner.sems.len() as _,
+            ..Default::default()
+        }
+    }
+}
+
+static SEM_NAMESPACE: Mutex<IpcNamespace<SemSet>> = Mutex::new(IpcNamespace::new());
+
+fn sem_set(semid: c_int) -> LinuxResult<Arc<SemSet>> {
+    SEM_NAMESPACE.lock().get(semid)
+}
+
+fn update_proc_sem(ns: &IpcNamespace<SemSet>) {
+    let mut content = String::from(
+        "       key      semid perms      nsems   uid   gid  cuid  cgid      otime      ctime\n",
+    );
+    for (id, set) in ns.iter() {
+        let inner = set.inner.lock();
+        let perm = &inner.perm;
+        let _ = writeln!(
+            content,
+            "{:>10} {:>10}  {:>4o} {:>10} {:>5} {:>5} {:>5} {:>5} {:>10} {:>10}",
+            perm.key,
+            id,
+            perm.mode,
+            inner.sems.len(),
+            perm.uid,
+            perm.gid,
+            perm.cuid,
+            perm.cgid,
+            inner.otime,
+            inner.ctime
+        );
+    }
+    update_proc_file("sem", &content);
+}
+
+/// Get a System V semaphore set identifier.
+///
+/// A new set of `nsems` semaphores is created if `key` is `IPC_PRIVATE`, or
+/// no set is associated with `key` and `IPC_CREAT` is specified.
+pub fn sys_semget(key: ctypes::key_t, nsems: c_int, semflg: c_int) -> c_int {
+    debug!(
+        "sys_semget <= key: {}, nsems: {}, semflg: {:#o}",
+        key, nsems, semflg
+    );
+    syscall_body!(sys_semget, {
+        if nsems < 0 || nsems as usize > SEMMSL {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut ns = SEM_NAMESPACE.lock();
+        let (id, set) = ns.get_or_create(key, semflg, || {
+            if nsems == 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            Ok(SemSet::new(IpcPerm::new(key, semflg), nsems as usize))
+        })?;
+        if set.nsems() < nsems as usize {
+            return Err(LinuxError::EINVAL);
+        }
+        update_proc_sem(&ns);
+        Ok(id)
+    })
+}
+
+/// Perform operations on selected semaphores in a System V semaphore set.
+pub fn sys_semop(semid: c_int, sops: *mut ctypes::sembuf, nsops: usize) -> c_int {
+    sys_semtimedop(semid, sops, nsops, core::ptr::null())
+}
+
+/// Perform operations on selected semaphores in a System V semaphore set,
+/// with a relative `timeout` if it is not NULL.
+///
+/// The operations are performed atomically. If any of them can not be
+/// performed immediately, the calling task is blocked (unless `IPC_NOWAIT`
+/// is specified) until all of them can be performed.
+pub fn sys_semtimedop(
+    semid: c_int,
+    sops: *mut ctypes::sembuf,
+    nsops: usize,
+    timeout: *const ctypes::timespec,
+) -> c_int {
+    debug!("sys_semtimedop <= semid: {}, nsops: {}", semid, nsops);
+    syscall_body!(sys_semtimedop, {
+        if nsops == 0 || nsops > SEMOPM {
+            return Err(LinuxError::E2BIG);
+        }
+        let mut ops = vec![ctypes::sembuf::default(); nsops];
+        get_user_slice(&mut ops, sops)?;
+        let deadline = if timeout.is_null() {
+            None
+        } else {
+            let ts = get_user(timeout)?;
+            if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec >= 1_000_000_000 {
+                return Err(LinuxError::EINVAL);
+            }
+            Some(axhal::time::wall_time() + Duration::from(ts))
+        };
+        let set = sem_set(semid)?;
+        let nsems = set.nsems();
+        if ops.iter().any(|op| op.sem_num as usize >= nsems) {
+            return Err(LinuxError::EFBIG);
+        }
+        if ops
+            .iter()
+            .any(|op| op.sem_flg as u32 & ctypes::SEM_UNDO != 0)
+        {
+            warn!("sys_semtimedop: SEM_UNDO is not supported, ignored");
+        }
+        set.semop(&ops, deadline)?;
+        Ok(0)
+    })
+}
+
+/// System V semaphore control operations.
+///
+/// `arg` is the value of `union semun`, which is interpreted according to
+/// `cmd`.
+pub fn sys_semctl(semid: c_int, semnum: c_int, cmd: c_int, arg: usize) -> c_int {
+    debug!(
+        "sys_semctl <= semid: {}, semnum: {}, cmd: {}, arg: {:#x}",
+        semid, semnum, cmd, arg
+    );
+    syscall_body!(sys_semctl, {
+        let set = sem_set(semid)?;
+        let nsems = set.nsems();
+        let check_semnum = || {
+            if semnum < 0 || semnum as usize >= nsems {
+                Err(LinuxError::EINVAL)
+            } else {
+                Ok(semnum as usize)
+            }
+        };
+        match cmd as u32 {
+            ctypes::IPC_RMID => {
+                let mut ns = SEM_NAMESPACE.lock();
+                let key = set.inner.lock().perm.key;
+                ns.remove(semid, key);
+                set.removed.store(true, Ordering::Release);
+                set.wake_all();
+                update_proc_sem(&ns);
+                Ok(0)
+            }
+            ctypes::IPC_STAT => {
+                put_user(arg as *mut ctypes::semid_ds, set.stat())?;
+                Ok(0)
+            }
+            ctypes::IPC_SET => {
+                let perm = get_user(arg as *const ctypes::semid_ds)?.sem_perm;
+                {
+                    let mut inner = set.inner.lock();
+                    inner.perm.set(&perm);
+                    inner.ctime = current_time();
+                }
+                update_proc_sem(&SEM_NAMESPACE.lock());
+                Ok(0)
+            }
+            ctypes::GETVAL => Ok(set.inner.lock().sems[check_semnum()?].val),
+            ctypes::GETPID => Ok(set.inner.lock().sems[check_semnum()?].pid),
+            ctypes::GETNCNT => Ok(set.inner.lock().sems[check_semnum()?].ncnt as c_int),
+            ctypes::GETZCNT => Ok(set.inner.lock().sems[check_semnum()?].zcnt as c_int),
+            ctypes::GETALL => {
+                let vals: Vec<c_ushort> = set
+                    .inner
+                    .lock()
+                    .sems
+                    .iter()
+                    .map(|sem| sem.val as c_ushort)
+                    .collect();
+                put_user_slice(arg as *mut c_ushort, &vals)?;
+                Ok(0)
+            }
+            ctypes::SETVAL => {
+                let num = check_semnum()?;
+                let val = arg as c_int;
+                if !(0..=SEMVMX).contains(&val) {
+                    return Err(LinuxError::ERANGE);
+                }
+                {
+                    let mut inner = set.inner.lock();
+                    inner.sems[num].val = val;
+                    inner.sems[num].pid = current_pid();
+                    inner.ctime = current_time();
+                }
+                set.wake_all();
+                Ok(0)
+            }
+            ctypes::SETALL => {
+                let mut vals = vec![0 as c_ushort; nsems];
+                get_user_slice(&mut vals, arg as *const c_ushort)?;
+                if vals.iter().any(|&v| v as i32 > SEMVMX) {
+                    return Err(LinuxError::ERANGE);
+                }
+                {
+                    let mut inner = set.inner.lock();
+                    let pid = current_pid();
+                    for (sem, &val) in inner.sems.iter_mut().zip(&vals) {
+                        sem.val = val as i32;
+                        sem.pid = pid;
+                    }
+                    inner.ctime = current_time();
+                }
+                set.wake_all();
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
@@ -0,0 +1,308 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+#[cfg(feature = "uspace")]
+use alloc::vec::Vec;
+use core::ffi::{c_int, c_void};
+use core::fmt::Write;
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::tmpfs::TmpNode;
+use axhal::mem::PAGE_SIZE_4K;
+use axhal::paging::MappingFlags;
+use axsync::Mutex;
+use axsync::spin::SpinNoIrq;
+
+use super::{IpcNamespace, IpcPerm, current_pid, current_time, update_proc_file};
+use crate::ctypes;
+use crate::imp::mmap::{current_aspace_id, shmem, unmap_range};
+use crate::uaccess::{get_user, put_user};
+
+/// The minimum size of a segment.
+const SHMMIN: usize = 1;
+/// The maximum size of a segment.
+const SHMMAX: usize = 1 << 30;
+
+struct ShmSegmentInner {
+    perm: IpcPerm,
+    /// The number of the attachments.
+    nattch: usize,
+    cpid: ctypes::pid_t,
+    lpid: ctypes::pid_t,
+    atime: ctypes::time_t,
+    dtime: ctypes::time_t,
+    ctime: ctypes::time_t,
+}
+
+/// A System V shared memory segment, backed by an unlinked tmpfs file.
+///
+/// A removed segment stays mapped until detached, as the attachments hold
+/// references to it.
+struct ShmSegment {
+    size: usize,
+    file: Arc<TmpNode>,
+    inner: SpinNoIrq<ShmSegmentInner>,
+}
+
+impl ShmSegment {
+    fn new(perm: IpcPerm, size: usize) -> Self {
+        let file = TmpNode::new_unlinked(perm.mode as u16, size as u64);
+        Self {
+            size,
+            file,
+            inner: SpinNoIrq::new(ShmSegmentInner {
+                perm,
+                nattch: 0,
+                cpid: current_pid(),
+                lpid: 0,
+                atime: 0,
+                dtime: 0,
+                ctime: current_time(),
+            }),
+        }
+    }
+
+    fn stat(&self) -> ctypes::shmid_ds {
+        let inner = self.inner.lock();
+        ctypes::shmid_ds {
+            shm_perm: inner.perm.to_ctype(),
+            shm_segsz: self.size as _,
+            shm_atime: inner.atime,
+            shm_dtime: inner.dtime,
+            shm_ctime: inner.ctime,
+            shm_cpid: inner.cpid,
+            shm_lpid: inner.lpid,
+            shm_nattch: inner.nattch as _,
+            ..Default::default()
+        }
+    }
+}
+
+static SHM_NAMESPACE: Mutex<IpcNamespace<ShmSegment>> = Mutex::new(IpcNamespace::new());
+
+/// The attached segments, indexed by the address space ID and the start
+/// address, with the length of the mappings.
+static ATTACHMENTS: Mutex<BTreeMap<(usize, usize), (Arc<ShmSegment>, usize)>> =
+    Mutex::new(BTreeMap::new());
+
+fn shm_segment(shmid: c_int) -> LinuxResult<Arc<ShmSegment>> {
+    SHM_NAMESPACE.lock().get(shmid)
+}
+
+fn update_proc_shm(ns: &IpcNamespace<ShmSegment>) {
+    let mut content = String::from(
+        "       key      shmid perms                  size  cpid  lpid nattch   uid   gid  cuid  cgid      atime      dtime      ctime\n",
+    );
+    for (id, seg) in ns.iter() {
+        let inner = seg.inner.lock();
+        let perm = &inner.perm;
+        let _ = writeln!(
+            content,
+            "{:>10} {:>10}  {:>4o} {:>21} {:>5} {:>5} {:>6} {:>5} {:>5} {:>5} {:>5} {:>10} {:>10} {:>10}",
+            perm.key,
+            id,
+            perm.mode,
+            seg.size,
+            inner.cpid,
+            inner.lpid,
+            inner.nattch,
+            perm.uid,
+            perm.gid,
+            perm.cuid,
+            perm.cgid,
+            inner.atime,
+            inner.dtime,
+            inner.ctime
+        );
+    }
+    update_proc_file("shm", &content);
+}
+
+/// Get a System V shared memory segment identifier.
+///
+/// A new segment of `size` bytes, initialized to zeros, is created if `key`
+/// is `IPC_PRIVATE`, or no segment is associated with `key` and `IPC_CREAT`
+/// is specified.
+pub fn sys_shmget(key: ctypes::key_t, size: usize, shmflg: c_int) -> c_int {
+    debug!(
+        "sys_shmget <= key: {}, size: {}, shmflg: {:#o}",
+        key, size, shmflg
+    );
+    syscall_body!(sys_shmget, {
+        let mut ns = SHM_NAMESPACE.lock();
+        let (id, seg) = ns.get_or_create(key, shmflg, || {
+            if !(SHMMIN..=SHMMAX).contains(&size) {
+                return Err(LinuxError::EINVAL);
+            }
+            Ok(ShmSegment::new(IpcPerm::new(key, shmflg), size))
+        })?;
+        if size > seg.size {
+            return Err(LinuxError::EINVAL);
+        }
+        update_proc_shm(&ns);
+        Ok(id)
+    })
+}
+
+/// Attach the System V shared memory segment `shmid`.
+///
+/// The segment is attached at `shmaddr`, which must be aligned to `SHMLBA`
+/// unless `SHM_RND` is specified to round it down, or at a free address if
+/// it is NULL. An existing mapping at `shmaddr` is replaced only with
+/// `SHM_REMAP`. Returns the address of the attached segment.
+pub fn sys_shmat(shmid: c_int, shmaddr: *const c_void, shmflg: c_int) -> *mut c_void {
+    debug!(
+        "sys_shmat <= shmid: {}, shmaddr: {:#x?}, shmflg: {:#o}",
+        shmid, shmaddr, shmflg
+    );
+    syscall_body!(sys_shmat, {
+        let seg = shm_segment(shmid)?;
+        let flags = shmflg as u32;
+        let mut prot = MappingFlags::READ;
+        if flags & ctypes::SHM_RDONLY == 0 {
+            prot |= MappingFlags::WRITE;
+        }
+        if flags & ctypes::SHM_EXEC != 0 {
+            prot |= MappingFlags::EXECUTE;
+        }
+
+        let shmlba = ctypes::SHMLBA as usize;
+        let mut addr = shmaddr as usize;
+        let map_flags = if addr == 0 {
+            if flags & ctypes::SHM_REMAP != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            0
+        } else {
+            if flags & ctypes::SHM_RND != 0 {
+                addr &= !(shmlba - 1);
+            }
+            if addr % shmlba != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            if flags & ctypes::SHM_REMAP != 0 {
+                ctypes::MAP_FIXED
+            } else {
+                ctypes::MAP_FIXED_NOREPLACE
+            }
+        };
+
+        let len = seg.size.next_multiple_of(PAGE_SIZE_4K);
+        let start = match shmem::mmap(seg.file.clone(), addr, len, map_flags, prot, 0) {
+            Err(LinuxError::EEXIST) => return Err(LinuxError::EINVAL),
+            res => res?,
+        };
+        {
+            let mut inner = seg.inner.lock();
+            inner.nattch += 1;
+            inner.lpid = current_pid();
+            inner.atime = current_time();
+        }
+        ATTACHMENTS
+            .lock()
+            .insert((current_aspace_id(), start), (seg, len));
+        update_proc_shm(&SHM_NAMESPACE.lock());
+        Ok(start)
+    })
+}
+
+/// Detach the System V shared memory segment attached at `shmaddr`.
+pub fn sys_shmdt(shmaddr: *const c_void) -> c_int {
+    debug!("sys_shmdt <= shmaddr: {:#x?}", shmaddr);
+    syscall_body!(sys_shmdt, {
+        let (seg, len) = ATTACHMENTS
+            .lock()
+            .remove(&(current_aspace_id(), shmaddr as usize))
+            .ok_or(LinuxError::EINVAL)?;
+        unmap_range((shmaddr as usize).into(), len)?;
+        {
+            let mut inner = seg.inner.lock();
+            inner.nattch -= 1;
+            inner.lpid = current_pid();
+            inner.dtime = current_time();
+        }
+        update_proc_shm(&SHM_NAMESPACE.lock());
+        Ok(0)
+    })
+}
+
+/// Detaches the segments attached to the user address space `aspace_id`,
+/// which is destroyed or cleared by `execve`, along with its mappings.
+#[cfg(feature = "uspace")]
+pub(crate) fn release_attachments(aspace_id: usize) {
+    let detached: Vec<_> = {
+        let mut attachments = ATTACHMENTS.lock();
+        let starts: Vec<usize> = attachments
+            .range((aspace_id, 0)..=(aspace_id, usize::MAX))
+            .map(|(&(_, start), _)| start)
+            .collect();
+        starts
+            .into_iter()
+            .map(|start| attachments.remove(&(aspace_id, start)).unwrap().0)
+            .collect()
+    };
+    if detached.is_empty() {
+        return;
+    }
+    for seg in detached {
+        let mut inner = seg.inner.lock();
+        inner.nattch -= 1;
+        inner.dtime = current_time();
+    }
+    update_proc_shm(&SHM_NAMESPACE.lock());
+}
+
+/// Attaches the segments attached to `parent` to `child` as well, which has
+/// copied the mappings of `parent` by `fork`.
+#[cfg(feature = "uspace")]
+pub(crate) fn fork_attachments(parent: usize, child: usize) {
+    let mut attachments = ATTACHMENTS.lock();
+    let copies: Vec<_> = attachments
+        .range((parent, 0)..=(parent, usize::MAX))
+        .map(|(&(_, start), (seg, len))| ((child, start), (seg.clone(), *len)))
+        .collect();
+    if copies.is_empty() {
+        return;
+    }
+    for (_, (seg, _)) in &copies {
+        seg.inner.lock().nattch += 1;
+    }
+    attachments.extend(copies);
+    drop(attachments);
+    update_proc_shm(&SHM_NAMESPACE.lock());
+}
+
+/// System V shared memory control operations.
+///
+/// A segment removed by `IPC_RMID` can not be attached anymore, and its
+/// memory is released after it is detached by all.
+pub fn sys_shmctl(shmid: c_int, cmd: c_int, buf: *mut ctypes::shmid_ds) -> c_int {
+    debug!("sys_shmctl <= shmid: {}, cmd: {}", shmid, cmd);
+    syscall_body!(sys_shmctl, {
+        let seg = shm_segment(shmid)?;
+        match cmd as u32 {
+            ctypes::IPC_RMID => {
+                let mut ns = SHM_NAMESPACE.lock();
+                let key = seg.inner.lock().perm.key;
+                ns.remove(shmid, key);
+                update_proc_shm(&ns);
+                Ok(0)
+            }
+            ctypes::IPC_STAT => {
+                put_user(buf, seg.stat())?;
+                Ok(0)
+            }
+            ctypes::IPC_SET => {
+                let ds = get_user(buf)?;
+                {
+                    let mut inner = seg.inner.lock();
+                    inner.perm.set(&ds.shm_perm);
+                    inner.ctime = current_time();
+                }
+                update_proc_shm(&SHM_NAMESPACE.lock());
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
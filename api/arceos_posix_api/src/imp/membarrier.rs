@@ -0,0 +1,142 @@
+use core::ffi::{c_int, c_uint};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use axerrno::LinuxError;
+use axsync::barrier::smp_mb;
+
+/// Query the set of supported commands.
+const MEMBARRIER_CMD_QUERY: c_int = 0;
+/// Ensure all running threads on the system have passed a memory barrier.
+const MEMBARRIER_CMD_GLOBAL: c_int = 1 << 0;
+/// Like [`MEMBARRIER_CMD_GLOBAL`], but only for registered processes.
+const MEMBARRIER_CMD_GLOBAL_EXPEDITED: c_int = 1 << 1;
+/// Register the intention to receive [`MEMBARRIER_CMD_GLOBAL_EXPEDITED`].
+const MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED: c_int = 1 << 2;
+/// Ensure all running threads of the calling process have passed a memory barrier.
+const MEMBARRIER_CMD_PRIVATE_EXPEDITED: c_int = 1 << 3;
+/// Register the intention to use [`MEMBARRIER_CMD_PRIVATE_EXPEDITED`].
+const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: c_int = 1 << 4;
+/// Like [`MEMBARRIER_CMD_PRIVATE_EXPEDITED`], and also serialize the instruction stream.
+const MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE: c_int = 1 << 5;
+/// Register the intention to use [`MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE`].
+const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE: c_int = 1 << 6;
+
+const MEMBARRIER_SUPPORTED_CMDS: c_int = MEMBARRIER_CMD_GLOBAL
+    | MEMBARRIER_CMD_GLOBAL_EXPEDITED
+    | MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+    | MEMBARRIER_CMD_PRIVATE_EXPEDITED
+    | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+    | MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE
+    | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE;
+
+/// The registered commands of the kernel tasks, and of the application if it
+/// is not run in user processes, as a bitmask of the
+/// `MEMBARRIER_CMD_REGISTER_*`.
+static GLOBAL_REGISTERED_CMDS: AtomicU32 = AtomicU32::new(0);
+
+/// Runs `f` with the registered commands of the current process.
+fn with_registered_cmds<R>(f: impl FnOnce(&AtomicU32) -> R) -> R {
+    #[cfg(feature = "uspace")]
+    if let Some(process) = super::process::current_process() {
+        return f(&process.membarrier_cmds);
+    }
+    f(&GLOBAL_REGISTERED_CMDS)
+}
+
+#[cfg(all(feature = "smp", feature = "irq"))]
+mod ipi {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use axsync::barrier::smp_mb;
+
+    /// The sequence number of the last barrier request.
+    static BARRIER_SEQ: AtomicU64 = AtomicU64::new(0);
+    /// The last barrier request acknowledged by each CPU, indexed by CPU ID.
+    ///
+    /// A CPU acknowledges all the requests made before its barrier, so an IPI
+    /// sent for another request or by another user is never lost.
+    static ACKED_SEQ: [AtomicU64; axconfig::SMP] = [const { AtomicU64::new(0) }; axconfig::SMP];
+
+    fn handle_barrier_ipi() {
+        let seq = BARRIER_SEQ.load(Ordering::Acquire);
+        smp_mb();
+        ACKED_SEQ[axhal::cpu::this_cpu_id()].fetch_max(seq, Ordering::Release);
+    }
+
+    #[ctor_bare::register_ctor]
+    fn init_barrier_ipi() {
+        axhal::irq::register_handler(axhal::irq::IPI_IRQ_NUM, handle_barrier_ipi);
+    }
+
+    /// Executes a full memory barrier on all other online CPUs, and waits
+    /// for them to finish.
+    pub fn barrier_all_cpus() {
+        let seq = BARRIER_SEQ.fetch_add(1, Ordering::AcqRel) + 1;
+        smp_mb();
+        let this_cpu = axhal::cpu::this_cpu_id();
+        let is_target = |&id: &usize| id != this_cpu && axhal::cpu::is_online(id);
+        for cpu_id in (0..axconfig::SMP).filter(is_target) {
+            axhal::irq::send_ipi(cpu_id);
+        }
+        // The IPIs are answered here if migrated to one of the targets.
+        for cpu_id in (0..axconfig::SMP).filter(is_target) {
+            while ACKED_SEQ[cpu_id].load(Ordering::Acquire) < seq {
+                axhal::arch::cpu_relax();
+            }
+        }
+        smp_mb();
+    }
+}
+
+/// Issues memory barriers on all CPUs that may run user threads.
+///
+/// In a single-core configuration, a local full barrier is enough, since
+/// context switches already imply a memory barrier.
+fn membarrier_all_cpus() {
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    ipi::barrier_all_cpus();
+    #[cfg(not(all(feature = "smp", feature = "irq")))]
+    smp_mb();
+}
+
+/// Issue memory barriers on a set of threads.
+///
+/// Supports `MEMBARRIER_CMD_GLOBAL` and `MEMBARRIER_CMD_PRIVATE_EXPEDITED` (and
+/// their expedited/registration variants). The threads of the calling process
+/// may run on any CPU, so the private and global commands have the same
+/// effect: an IPI is sent to every other online CPU, which executes a full
+/// memory barrier.
+pub fn sys_membarrier(cmd: c_int, flags: c_uint, _cpu_id: c_int) -> c_int {
+    debug!("sys_membarrier <= cmd: {}, flags: {}", cmd, flags);
+    syscall_body!(sys_membarrier, {
+        // `MEMBARRIER_CMD_FLAG_CPU` is only valid for the RSEQ commands, which
+        // are not supported.
+        if flags != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let registered = with_registered_cmds(|cmds| cmds.load(Ordering::Acquire)) as c_int;
+        match cmd {
+            MEMBARRIER_CMD_QUERY => return Ok(MEMBARRIER_SUPPORTED_CMDS),
+            MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+            | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+            | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE => {
+                with_registered_cmds(|cmds| cmds.fetch_or(cmd as u32, Ordering::AcqRel));
+                return Ok(0);
+            }
+            MEMBARRIER_CMD_GLOBAL | MEMBARRIER_CMD_GLOBAL_EXPEDITED => {}
+            MEMBARRIER_CMD_PRIVATE_EXPEDITED => {
+                if registered & MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED == 0 {
+                    return Err(LinuxError::EPERM);
+                }
+            }
+            MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE => {
+                if registered & MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE == 0 {
+                    return Err(LinuxError::EPERM);
+                }
+            }
+            _ => return Err(LinuxError::EINVAL),
+        }
+        membarrier_all_cpus();
+        Ok(0)
+    })
+}
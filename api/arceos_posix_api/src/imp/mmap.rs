@@ -0,0 +1,990 @@
+//! Memory mappings (`mmap`, `munmap` and `mprotect`) and the program break
+//! (`brk` and `sbrk`).
+//!
+//! Mappings are placed in a window at the top of the address space of the
+//! caller, the one of the current process for a user task, or the kernel one
+//! otherwise, and the heap managed by `brk` is right below the window.
+//! Anonymous mappings are backed by frames allocated lazily by the page fault
+//! handler, unless `MAP_POPULATE` is given. File mappings are filled with the
+//! file content when created, and `MAP_SHARED` ones are written back to the
+//! file when unmapped. Files on hugetlbfs are mapped to their huge pages
+//! directly, which are shared even by `MAP_PRIVATE` mappings. So are the
+//! memory regions of devices opened by `sys_uio_open`, and the framebuffer of
+//! `/dev/fb0`. `MAP_SHARED` mappings of tmpfs files, e.g. the POSIX shared
+//! memory objects, are mapped to the pages of the files directly as well, so
+//! the changes are seen by all the mappings and the file at once.
+
+#[cfg(feature = "uspace")]
+use alloc::collections::BTreeMap;
+use core::ffi::{c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{MemoryAddr, VirtAddr};
+use axhal::paging::MappingFlags;
+use axhal::trap::{PAGE_FAULT, register_trap_handler};
+use axmm::{AddrSpace, kernel_aspace};
+use axsync::Mutex;
+use memory_addr::{VirtAddrRange, align_up_4k};
+
+use crate::ctypes;
+
+/// Size of the window for mappings, at the top of the address space.
+const MMAP_AREA_SIZE: usize = 0x10_0000_0000; // 64 GiB
+
+/// The maximum size of the heap managed by `brk`.
+const MAX_HEAP_SIZE: usize = 0x1_0000_0000; // 4 GiB
+
+/// The program break of the kernel, or 0 before the first `brk` or `sbrk`
+/// call. User processes have their own ones.
+static BRK: Mutex<usize> = Mutex::new(0);
+
+/// Calls `f` with the address space of the caller: the one of the current
+/// process if it is a user task, or the kernel one.
+fn with_aspace<R>(f: impl FnOnce(&mut AddrSpace) -> R) -> R {
+    #[cfg(feature = "uspace")]
+    if let Some(aspace) = super::process::current_aspace() {
+        return f(&mut aspace.lock());
+    }
+    f(&mut kernel_aspace().lock())
+}
+
+/// Calls `f` with the program break of the caller: the one of the current
+/// process if it is a user task, or the kernel one.
+fn with_brk<R>(f: impl FnOnce(&mut usize) -> R) -> R {
+    #[cfg(feature = "uspace")]
+    if let Some(process) = super::process::current_process() {
+        return f(&mut process.brk.lock());
+    }
+    f(&mut BRK.lock())
+}
+
+/// Returns the ID of `aspace`, which indexes the mappings tracked along with
+/// the virtual address.
+fn aspace_id(aspace: &AddrSpace) -> usize {
+    aspace.page_table_root().as_usize()
+}
+
+/// Returns the ID of the address space of the caller.
+#[cfg(all(feature = "sysvipc", feature = "shm"))]
+pub(crate) fn current_aspace_id() -> usize {
+    with_aspace(|aspace| aspace_id(aspace))
+}
+
+fn mmap_area(aspace: &AddrSpace) -> VirtAddrRange {
+    VirtAddrRange::from_start_size(aspace.end() - MMAP_AREA_SIZE, MMAP_AREA_SIZE)
+}
+
+fn heap_area(aspace: &AddrSpace) -> VirtAddrRange {
+    let end = aspace.end() - MMAP_AREA_SIZE;
+    VirtAddrRange::new(end - MAX_HEAP_SIZE, end)
+}
+
+/// Checks that `[addr, addr + len)` is page aligned and lies in the mapping
+/// window, returns the start address and the length rounded up to pages.
+fn check_range(addr: *mut c_void, len: ctypes::size_t) -> LinuxResult<(VirtAddr, usize)> {
+    let start = VirtAddr::from(addr as usize);
+    if !start.is_aligned_4k() || len == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let len = (len as usize)
+        .checked_next_multiple_of(axhal::mem::PAGE_SIZE_4K)
+        .ok_or(LinuxError::ENOMEM)?;
+    let area = with_aspace(|aspace| mmap_area(aspace));
+    match start.checked_add(len) {
+        Some(end) if area.contains_range(VirtAddrRange::new(start, end)) => Ok((start, len)),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+fn prot_to_flags(prot: c_int) -> LinuxResult<MappingFlags> {
+    let prot = prot as u32;
+    if prot & !(ctypes::PROT_READ | ctypes::PROT_WRITE | ctypes::PROT_EXEC) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let mut flags = MappingFlags::empty();
+    // Write-only pages are not supported by most architectures.
+    if prot & (ctypes::PROT_READ | ctypes::PROT_WRITE) != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    Ok(flags)
+}
+
+#[cfg(feature = "fs")]
+mod file {
+    use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+    use core::ffi::c_int;
+
+    use axerrno::{LinuxError, LinuxResult};
+    use axhal::mem::{PAGE_SIZE_4K, VirtAddr};
+    #[cfg(feature = "uspace")]
+    use axmm::AddrSpace;
+    use axsync::Mutex;
+
+    use super::{aspace_id, with_aspace};
+    use crate::imp::fs::File;
+
+    /// A `MAP_SHARED` file mapping, whose changes are written back to the file.
+    #[derive(Clone)]
+    struct SharedRegion {
+        len: usize,
+        file: Arc<File>,
+        /// File offset of the start of the region.
+        offset: u64,
+        /// Bytes at the start of the region that are backed by the file, the
+        /// rest is beyond the end of the file and is not written back.
+        data_len: usize,
+    }
+
+    impl SharedRegion {
+        /// Writes `[rel_start, rel_start + len)` of the region at `start` back
+        /// to the file, where the memory is read by `read`.
+        fn write_back(
+            &self,
+            read: impl Fn(VirtAddr, &mut [u8]) -> LinuxResult,
+            start: VirtAddr,
+            rel_start: usize,
+            len: usize,
+        ) -> LinuxResult {
+            let end = (rel_start + len).min(self.data_len);
+            let mut buf = [0u8; PAGE_SIZE_4K];
+            let mut pos = rel_start;
+            while pos < end {
+                let n = (end - pos).min(PAGE_SIZE_4K);
+                // Read through the page table, the pages may be not readable.
+                read(start + pos, &mut buf[..n])?;
+                let mut file = self.file.inner().lock();
+                file.write_at(self.offset + pos as u64, &buf[..n])?;
+                pos += n;
+            }
+            Ok(())
+        }
+    }
+
+    /// Shared file mappings, indexed by the address space ID and the start
+    /// address.
+    static SHARED_REGIONS: Mutex<BTreeMap<(usize, usize), SharedRegion>> =
+        Mutex::new(BTreeMap::new());
+
+    pub fn get_file(fd: c_int) -> LinuxResult<Arc<File>> {
+        match File::from_fd(fd) {
+            Err(LinuxError::EINVAL) => Err(LinuxError::ENODEV),
+            res => res,
+        }
+    }
+
+    /// Fills the mapping at `start` with the file content from `offset`.
+    ///
+    /// Returns the number of bytes read, the rest of the mapping is left zeroed.
+    pub fn fill(file: &File, start: VirtAddr, len: usize, offset: u64) -> LinuxResult<usize> {
+        let mut buf = [0u8; PAGE_SIZE_4K];
+        let mut pos = 0;
+        while pos < len {
+            let n = (len - pos).min(PAGE_SIZE_4K);
+            let n = file
+                .inner()
+                .lock()
+                .read_at(offset + pos as u64, &mut buf[..n])?;
+            if n == 0 {
+                break;
+            }
+            // Write through the page table, the range is just mapped and
+            // populated.
+            with_aspace(|aspace| aspace.write(start + pos, &buf[..n]))?;
+            pos += n;
+        }
+        Ok(pos)
+    }
+
+    pub fn add_shared(start: VirtAddr, len: usize, file: Arc<File>, offset: u64, data_len: usize) {
+        let region = SharedRegion {
+            len,
+            file,
+            offset,
+            data_len,
+        };
+        let id = with_aspace(|aspace| aspace_id(aspace));
+        SHARED_REGIONS.lock().insert((id, start.as_usize()), region);
+    }
+
+    /// Writes back the shared mappings of the caller overlapping
+    /// `[start, start + len)`, and stops tracking the range.
+    pub fn release_shared(start: VirtAddr, len: usize) {
+        let id = with_aspace(|aspace| aspace_id(aspace));
+        let (start, end) = (start.as_usize(), start.as_usize() + len);
+        let mut regions = SHARED_REGIONS.lock();
+        let overlapping: Vec<usize> = regions
+            .range((id, 0)..(id, end))
+            .filter(|(&(_, s), r)| s + r.len > start)
+            .map(|(&(_, s), _)| s)
+            .collect();
+        let mut released = Vec::new();
+        for s in overlapping {
+            let r = regions.remove(&(id, s)).unwrap();
+            let (ov_start, ov_end) = (s.max(start), (s + r.len).min(end));
+            if s < ov_start {
+                let left = SharedRegion {
+                    len: ov_start - s,
+                    file: r.file.clone(),
+                    offset: r.offset,
+                    data_len: r.data_len.min(ov_start - s),
+                };
+                regions.insert((id, s), left);
+            }
+            if ov_end < s + r.len {
+                let right = SharedRegion {
+                    len: s + r.len - ov_end,
+                    file: r.file.clone(),
+                    offset: r.offset + (ov_end - s) as u64,
+                    data_len: r.data_len.saturating_sub(ov_end - s),
+                };
+                regions.insert((id, ov_end), right);
+            }
+            released.push((s, ov_start - s, ov_end - ov_start, r));
+        }
+        // The address space is locked before the regions by `fork_mappings`.
+        drop(regions);
+        for (s, rel_start, len, r) in released {
+            let read = |vaddr, buf: &mut [u8]| -> LinuxResult {
+                Ok(with_aspace(|aspace| aspace.read(vaddr, buf))?)
+            };
+            if let Err(e) = r.write_back(read, s.into(), rel_start, len) {
+                warn!("failed to write back mapping at {:#x}: {:?}", s, e);
+            }
+        }
+    }
+
+    /// Writes back all the shared mappings of `aspace`, and stops tracking
+    /// them.
+    #[cfg(feature = "uspace")]
+    pub fn release_all(aspace: &AddrSpace) {
+        let released = super::take_aspace(&mut SHARED_REGIONS.lock(), aspace_id(aspace));
+        for ((_, s), r) in released {
+            let read = |vaddr, buf: &mut [u8]| -> LinuxResult { Ok(aspace.read(vaddr, buf)?) };
+            if let Err(e) = r.write_back(read, s.into(), 0, r.len) {
+                warn!("failed to write back mapping at {:#x}: {:?}", s, e);
+            }
+        }
+    }
+
+    /// Tracks the copies of the shared mappings of `parent` in `child`, which
+    /// are written back as well.
+    #[cfg(feature = "uspace")]
+    pub fn fork(parent: &AddrSpace, child: &AddrSpace) {
+        let (parent, child) = (aspace_id(parent), aspace_id(child));
+        let mut regions = SHARED_REGIONS.lock();
+        let copies: Vec<_> = regions
+            .range((parent, 0)..=(parent, usize::MAX))
+            .map(|(&(_, s), r)| ((child, s), r.clone()))
+            .collect();
+        regions.extend(copies);
+    }
+}
+
+#[cfg(feature = "hugetlbfs")]
+mod hugetlb {
+    use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+    use axerrno::{LinuxError, LinuxResult};
+    use axfs::hugetlbfs::{HUGE_PAGE_SIZE, HugeFile};
+    use axhal::mem::{MemoryAddr, VirtAddr, virt_to_phys};
+    use axhal::paging::MappingFlags;
+    #[cfg(feature = "uspace")]
+    use axmm::AddrSpace;
+    use axsync::Mutex;
+    use memory_addr::VirtAddrRange;
+
+    use super::{aspace_id, check_range, mmap_area, unmap_range, with_aspace};
+    use crate::{ctypes, imp::fs::File};
+
+    /// Mapped huge pages of hugetlbfs files and their offsets in the files,
+    /// indexed by the address space ID and the virtual address.
+    static HUGE_MAPPINGS: Mutex<BTreeMap<(usize, usize), (Arc<HugeFile>, usize)>> =
+        Mutex::new(BTreeMap::new());
+
+    /// Returns the hugetlbfs file of `file`, if it is one.
+    pub fn get_file(file: &File) -> Option<Arc<HugeFile>> {
+        axfs::hugetlbfs::hugetlb_file(file.inner().lock().get_node())
+    }
+
+    /// Maps `[offset, offset + len)` of the hugetlbfs file `file`.
+    ///
+    /// `addr`, `offset` and the mapping flags are checked like in `sys_mmap`,
+    /// except that huge page alignment is required, and `len` is rounded up
+    /// to huge pages.
+    pub fn mmap(
+        file: Arc<HugeFile>,
+        addr: usize,
+        len: usize,
+        flags: u32,
+        prot_flags: MappingFlags,
+        offset: usize,
+    ) -> LinuxResult<usize> {
+        let len = len
+            .checked_next_multiple_of(HUGE_PAGE_SIZE)
+            .ok_or(LinuxError::ENOMEM)?;
+        let fixed = flags & (ctypes::MAP_FIXED | ctypes::MAP_FIXED_NOREPLACE) != 0;
+        if offset % HUGE_PAGE_SIZE != 0 || (fixed && addr % HUGE_PAGE_SIZE != 0) {
+            return Err(LinuxError::EINVAL);
+        }
+        if fixed && flags & ctypes::MAP_FIXED_NOREPLACE == 0 {
+            let (start, len) = check_range(addr as _, len)?;
+            unmap_range(start, len)?;
+        }
+
+        // The file must not be locked with the address space locked.
+        let pages = file.map_pages(offset, len)?;
+        match map_at(&pages, addr, len, fixed, prot_flags) {
+            Ok(start) => {
+                let id = with_aspace(|aspace| aspace_id(aspace));
+                let mut mappings = HUGE_MAPPINGS.lock();
+                for i in 0..pages.len() {
+                    let vaddr = start.as_usize() + i * HUGE_PAGE_SIZE;
+                    mappings.insert((id, vaddr), (file.clone(), offset + i * HUGE_PAGE_SIZE));
+                }
+                Ok(start.as_usize())
+            }
+            Err(e) => {
+                file.unmap_pages(pages.len());
+                Err(e)
+            }
+        }
+    }
+
+    /// Maps `pages` at `addr`, or a free area if not `fixed`.
+    fn map_at(
+        pages: &[usize],
+        addr: usize,
+        len: usize,
+        fixed: bool,
+        prot_flags: MappingFlags,
+    ) -> LinuxResult<VirtAddr> {
+        with_aspace(|aspace| {
+            let area = mmap_area(aspace);
+            let start = if fixed {
+                let start = VirtAddr::from(addr);
+                let range = VirtAddrRange::from_start_size(start, len);
+                if !area.contains_range(range) {
+                    return Err(LinuxError::EINVAL);
+                }
+                if aspace.find_free_area(start, len, range) != Some(start) {
+                    return Err(LinuxError::EEXIST);
+                }
+                start
+            } else {
+                let hint = VirtAddr::from(addr).align_down(HUGE_PAGE_SIZE);
+                let hint = if area.contains(hint) {
+                    hint
+                } else {
+                    area.start
+                };
+                // Leave room for aligning the start to huge pages.
+                aspace
+                    .find_free_area(hint, len + HUGE_PAGE_SIZE, area)
+                    .ok_or(LinuxError::ENOMEM)?
+                    .align_up(HUGE_PAGE_SIZE)
+            };
+            for (i, &page) in pages.iter().enumerate() {
+                let vaddr = start + i * HUGE_PAGE_SIZE;
+                let paddr = virt_to_phys(page.into());
+                if let Err(e) = aspace.map_linear_huge(vaddr, paddr, HUGE_PAGE_SIZE, prot_flags) {
+                    aspace.unmap(start, i * HUGE_PAGE_SIZE)?;
+                    return Err(e.into());
+                }
+            }
+            Ok(start)
+        })
+    }
+
+    /// Stops tracking the huge pages in `[start, start + len)`, which are going
+    /// to be unmapped, returns their files.
+    ///
+    /// Returns `EINVAL` if a huge page is partially in the range.
+    pub fn take_pages(start: VirtAddr, len: usize) -> LinuxResult<Vec<Arc<HugeFile>>> {
+        let id = with_aspace(|aspace| aspace_id(aspace));
+        let (start, end) = (start.as_usize(), start.as_usize() + len);
+        let mut mappings = HUGE_MAPPINGS.lock();
+        let overlapping: Vec<usize> = mappings
+            .range((id, start.saturating_sub(HUGE_PAGE_SIZE - 1))..(id, end))
+            .map(|(&(_, vaddr), _)| vaddr)
+            .collect();
+        if overlapping
+            .iter()
+            .any(|&vaddr| vaddr < start || vaddr + HUGE_PAGE_SIZE > end)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(overlapping
+            .iter()
+            .map(|&vaddr| mappings.remove(&(id, vaddr)).unwrap().0)
+            .collect())
+    }
+
+    /// Stops tracking all the huge pages mapped in `aspace`.
+    #[cfg(feature = "uspace")]
+    pub fn release_all(aspace: &AddrSpace) {
+        let released = super::take_aspace(&mut HUGE_MAPPINGS.lock(), aspace_id(aspace));
+        for (file, _) in released.into_values() {
+            file.unmap_pages(1);
+        }
+    }
+
+    /// Tracks the huge pages of `parent` mapped in `child` as well, which are
+    /// shared by them.
+    #[cfg(feature = "uspace")]
+    pub fn fork(parent: &AddrSpace, child: &AddrSpace) {
+        let (parent, child) = (aspace_id(parent), aspace_id(child));
+        let copies: Vec<_> = HUGE_MAPPINGS
+            .lock()
+            .range((parent, 0)..=(parent, usize::MAX))
+            .map(|(&(_, vaddr), (file, offset))| (vaddr, file.clone(), *offset))
+            .collect();
+        // Count the pages mapped once more, they are present in the file.
+        let copies: Vec<_> = copies
+            .into_iter()
+            .filter(|(_, file, offset)| file.map_pages(*offset, HUGE_PAGE_SIZE).is_ok())
+            .collect();
+        let mut mappings = HUGE_MAPPINGS.lock();
+        for (vaddr, file, offset) in copies {
+            mappings.insert((child, vaddr), (file, offset));
+        }
+    }
+}
+
+#[cfg(feature = "shm")]
+pub(crate) mod shmem {
+    use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+    use axerrno::{LinuxError, LinuxResult};
+    use axfs::tmpfs::TmpNode;
+    use axhal::mem::{MemoryAddr, PAGE_SIZE_4K, VirtAddr, virt_to_phys};
+    use axhal::paging::MappingFlags;
+    #[cfg(feature = "uspace")]
+    use axmm::AddrSpace;
+    use axsync::Mutex;
+    use memory_addr::VirtAddrRange;
+
+    use super::{aspace_id, check_range, mmap_area, unmap_range, with_aspace};
+    use crate::{ctypes, imp::fs::File};
+
+    /// Mapped pages of tmpfs files and their offsets in the files, indexed by
+    /// the address space ID and the virtual address.
+    ///
+    /// Each address space maps the pages of the files by itself, so they are
+    /// shared by all the processes mapping them.
+    static SHM_MAPPINGS: Mutex<BTreeMap<(usize, usize), (Arc<TmpNode>, u64)>> =
+        Mutex::new(BTreeMap::new());
+
+    /// Returns the tmpfs node of `file`, if it is one.
+    pub fn get_file(file: &File) -> Option<Arc<TmpNode>> {
+        axfs::tmpfs::tmpfs_node(file.inner().lock().get_node())
+    }
+
+    /// Maps `[offset, offset + len)` of the tmpfs file `node` to its pages.
+    ///
+    /// `addr` and the mapping flags are checked like in `sys_mmap`. The range
+    /// must be in the file rounded up to pages, as there are no pages to
+    /// map beyond the end.
+    pub fn mmap(
+        node: Arc<TmpNode>,
+        addr: usize,
+        len: usize,
+        flags: u32,
+        prot_flags: MappingFlags,
+        offset: u64,
+    ) -> LinuxResult<usize> {
+        let fixed = flags & (ctypes::MAP_FIXED | ctypes::MAP_FIXED_NOREPLACE) != 0;
+        if fixed && flags & ctypes::MAP_FIXED_NOREPLACE == 0 {
+            let (start, len) = check_range(addr as _, len)?;
+            unmap_range(start, len)?;
+        }
+
+        // The file must not be locked with the address space locked.
+        let pages = node.map_pages(offset, len)?;
+        match map_at(&pages, addr, len, fixed, prot_flags) {
+            Ok(start) => {
+                let id = with_aspace(|aspace| aspace_id(aspace));
+                let mut mappings = SHM_MAPPINGS.lock();
+                for i in 0..pages.len() {
+                    let vaddr = start.as_usize() + i * PAGE_SIZE_4K;
+                    let page_offset = offset + (i * PAGE_SIZE_4K) as u64;
+                    mappings.insert((id, vaddr), (node.clone(), page_offset));
+                }
+                Ok(start.as_usize())
+            }
+            Err(e) => {
+                node.unmap_pages(pages.len());
+                Err(e)
+            }
+        }
+    }
+
+    /// Maps `pages` at `addr`, or a free area if not `fixed`.
+    fn map_at(
+        pages: &[usize],
+        addr: usize,
+        len: usize,
+        fixed: bool,
+        prot_flags: MappingFlags,
+    ) -> LinuxResult<VirtAddr> {
+        with_aspace(|aspace| {
+            let area = mmap_area(aspace);
+            let start = if fixed {
+                let start = VirtAddr::from(addr);
+                let range = VirtAddrRange::from_start_size(start, len);
+                if !start.is_aligned_4k() || !area.contains_range(range) {
+                    return Err(LinuxError::EINVAL);
+                }
+                if aspace.find_free_area(start, len, range) != Some(start) {
+                    return Err(LinuxError::EEXIST);
+                }
+                start
+            } else {
+                let hint = VirtAddr::from(addr).align_down_4k();
+                let hint = if area.contains(hint) {
+                    hint
+                } else {
+                    area.start
+                };
+                aspace
+                    .find_free_area(hint, len, area)
+                    .ok_or(LinuxError::ENOMEM)?
+            };
+            for (i, &page) in pages.iter().enumerate() {
+                let vaddr = start + i * PAGE_SIZE_4K;
+                let paddr = virt_to_phys(page.into());
+                if let Err(e) = aspace.map_linear(vaddr, paddr, PAGE_SIZE_4K, prot_flags) {
+                    aspace.unmap(start, i * PAGE_SIZE_4K)?;
+                    return Err(e.into());
+                }
+            }
+            Ok(start)
+        })
+    }
+
+    /// Stops tracking the pages in `[start, start + len)`, which are going to
+    /// be unmapped, returns their files.
+    pub fn take_pages(start: VirtAddr, len: usize) -> Vec<Arc<TmpNode>> {
+        let id = with_aspace(|aspace| aspace_id(aspace));
+        let (start, end) = (start.as_usize(), start.as_usize() + len);
+        let mut mappings = SHM_MAPPINGS.lock();
+        let overlapping: Vec<usize> = mappings
+            .range((id, start)..(id, end))
+            .map(|(&(_, v), _)| v)
+            .collect();
+        overlapping
+            .iter()
+            .map(|&vaddr| mappings.remove(&(id, vaddr)).unwrap().0)
+            .collect()
+    }
+
+    /// Stops tracking all the pages mapped in `aspace`.
+    #[cfg(feature = "uspace")]
+    pub fn release_all(aspace: &AddrSpace) {
+        let released = super::take_aspace(&mut SHM_MAPPINGS.lock(), aspace_id(aspace));
+        for (node, _) in released.into_values() {
+            node.unmap_pages(1);
+        }
+    }
+
+    /// Tracks the pages of `parent` mapped in `child` as well, which are
+    /// shared by them.
+    #[cfg(feature = "uspace")]
+    pub fn fork(parent: &AddrSpace, child: &AddrSpace) {
+        let (parent, child) = (aspace_id(parent), aspace_id(child));
+        let copies: Vec<_> = SHM_MAPPINGS
+            .lock()
+            .range((parent, 0)..=(parent, usize::MAX))
+            .map(|(&(_, vaddr), (node, offset))| (vaddr, node.clone(), *offset))
+            .collect();
+        // Count the pages mapped once more, they are present in the file.
+        let copies: Vec<_> = copies
+            .into_iter()
+            .filter(|(_, node, offset)| node.map_pages(*offset, PAGE_SIZE_4K).is_ok())
+            .collect();
+        let mut mappings = SHM_MAPPINGS.lock();
+        for (vaddr, node, offset) in copies {
+            mappings.insert((child, vaddr), (node, offset));
+        }
+    }
+}
+
+#[cfg(any(feature = "uio", feature = "fb"))]
+mod device {
+    use axerrno::{LinuxError, LinuxResult};
+    use axhal::mem::{MemoryAddr, PAGE_SIZE_4K, PhysAddr, VirtAddr};
+    use axhal::paging::MappingFlags;
+    use memory_addr::VirtAddrRange;
+
+    use super::{check_range, mmap_area, unmap_range, with_aspace};
+    use crate::ctypes;
+
+    /// Maps the memory region of a device at `paddr` with `map_flags`.
+    ///
+    /// A region not page aligned is mapped from the start of its page, and
+    /// `len` must not exceed the pages of the region. The mapping is always
+    /// shared, and stays valid after the device is closed.
+    pub fn mmap(
+        paddr: usize,
+        size: usize,
+        addr: usize,
+        len: usize,
+        flags: u32,
+        map_flags: MappingFlags,
+    ) -> LinuxResult<usize> {
+        let paddr = PhysAddr::from(paddr);
+        if len > (paddr.align_offset_4k() + size).next_multiple_of(PAGE_SIZE_4K) {
+            return Err(LinuxError::EINVAL);
+        }
+        let fixed = flags & (ctypes::MAP_FIXED | ctypes::MAP_FIXED_NOREPLACE) != 0;
+        if fixed && flags & ctypes::MAP_FIXED_NOREPLACE == 0 {
+            let (start, len) = check_range(addr as _, len)?;
+            unmap_range(start, len)?;
+        }
+
+        with_aspace(|aspace| {
+            let area = mmap_area(aspace);
+            let start = if fixed {
+                let start = VirtAddr::from(addr);
+                let range = VirtAddrRange::from_start_size(start, len);
+                if !start.is_aligned_4k() || !area.contains_range(range) {
+                    return Err(LinuxError::EINVAL);
+                }
+                if aspace.find_free_area(start, len, range) != Some(start) {
+                    return Err(LinuxError::EEXIST);
+                }
+                start
+            } else {
+                let hint = VirtAddr::from(addr).align_down_4k();
+                let hint = if area.contains(hint) {
+                    hint
+                } else {
+                    area.start
+                };
+                aspace
+                    .find_free_area(hint, len, area)
+                    .ok_or(LinuxError::ENOMEM)?
+            };
+            aspace.map_linear(start, paddr.align_down_4k(), len, map_flags)?;
+            Ok(start.as_usize())
+        })
+    }
+}
+
+/// Removes the mappings in the range, writing back shared file mappings.
+pub(crate) fn unmap_range(start: VirtAddr, len: usize) -> LinuxResult {
+    #[cfg(feature = "hugetlbfs")]
+    let huge_files = hugetlb::take_pages(start, len)?;
+    #[cfg(feature = "fs")]
+    file::release_shared(start, len);
+    #[cfg(feature = "shm")]
+    let shm_files = shmem::take_pages(start, len);
+    with_aspace(|aspace| aspace.unmap(start, len))?;
+    #[cfg(feature = "hugetlbfs")]
+    for file in huge_files {
+        file.unmap_pages(1);
+    }
+    #[cfg(feature = "shm")]
+    for node in shm_files {
+        node.unmap_pages(1);
+    }
+    Ok(())
+}
+
+/// Removes the entries of the address space `id` from `map`, indexed by the
+/// address space ID and the virtual address, and returns them.
+#[cfg(feature = "uspace")]
+fn take_aspace<V>(map: &mut BTreeMap<(usize, usize), V>, id: usize) -> BTreeMap<(usize, usize), V> {
+    let mut taken = map.split_off(&(id, 0));
+    map.append(&mut taken.split_off(&(id + 1, 0)));
+    taken
+}
+
+/// Stops tracking the mappings of the user address space `aspace`, which is
+/// destroyed or cleared by `execve`, writing back shared file mappings.
+#[cfg(feature = "uspace")]
+pub(crate) fn release_mappings(aspace: &AddrSpace) {
+    #[cfg(feature = "fs")]
+    file::release_all(aspace);
+    #[cfg(feature = "hugetlbfs")]
+    hugetlb::release_all(aspace);
+    #[cfg(feature = "shm")]
+    shmem::release_all(aspace);
+    #[cfg(all(feature = "sysvipc", feature = "shm"))]
+    super::ipc::release_attachments(aspace_id(aspace));
+    #[cfg(not(feature = "fs"))]
+    let _ = aspace;
+}
+
+/// Tracks the mappings of `parent` copied to `child` by `fork`.
+#[cfg(feature = "uspace")]
+pub(crate) fn fork_mappings(parent: &AddrSpace, child: &AddrSpace) {
+    #[cfg(feature = "fs")]
+    file::fork(parent, child);
+    #[cfg(feature = "hugetlbfs")]
+    hugetlb::fork(parent, child);
+    #[cfg(feature = "shm")]
+    shmem::fork(parent, child);
+    #[cfg(all(feature = "sysvipc", feature = "shm"))]
+    super::ipc::fork_attachments(aspace_id(parent), aspace_id(child));
+    #[cfg(not(feature = "fs"))]
+    let _ = (parent, child);
+}
+
+/// Creates a new mapping.
+///
+/// Only page aligned `offset` is supported. On success, returns the start
+/// address of the mapping; on failure, returns a negative error code, which
+/// is in `[-4095, -1]` when interpreted as a signed integer.
+pub fn sys_mmap(
+    addr: *mut c_void,
+    len: ctypes::size_t,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    offset: ctypes::off_t,
+) -> *mut c_void {
+    debug!(
+        "sys_mmap <= addr: {:#x?}, len: {}, prot: {:#x}, flags: {:#x}, fd: {}, offset: {}",
+        addr, len, prot, flags, fd, offset
+    );
+    syscall_body!(sys_mmap, {
+        let flags = flags as u32;
+        let shared = match flags & ctypes::MAP_TYPE {
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE => true,
+            ctypes::MAP_PRIVATE => false,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if len == 0 || offset < 0 || offset as usize % axhal::mem::PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let len = (len as usize)
+            .checked_next_multiple_of(axhal::mem::PAGE_SIZE_4K)
+            .ok_or(LinuxError::ENOMEM)?;
+        let prot_flags = prot_to_flags(prot)?;
+
+        let anonymous = flags & ctypes::MAP_ANONYMOUS != 0;
+        // `offset` selects the memory region of the device in pages
+        #[cfg(feature = "uio")]
+        if !anonymous {
+            if let Ok(file) = super::uio::UioFile::from_fd(fd) {
+                let index = offset as usize / axhal::mem::PAGE_SIZE_4K;
+                let (paddr, size) = file.map_region(index)?;
+                let map_flags = prot_flags | MappingFlags::DEVICE;
+                return device::mmap(paddr, size, addr as usize, len, flags, map_flags);
+            }
+        }
+        // the framebuffer is in the memory, mapped as cacheable like the kernel
+        #[cfg(feature = "fb")]
+        if !anonymous {
+            if let Ok(file) = super::fb::FbFile::from_fd(fd) {
+                let (paddr, size) = file.map_region();
+                let offset = offset as usize;
+                if offset >= size {
+                    return Err(LinuxError::EINVAL);
+                }
+                let (paddr, size) = (paddr + offset, size - offset);
+                return device::mmap(paddr, size, addr as usize, len, flags, prot_flags);
+            }
+        }
+        #[cfg(feature = "fs")]
+        let file = if anonymous {
+            None
+        } else {
+            Some(file::get_file(fd)?)
+        };
+        #[cfg(not(feature = "fs"))]
+        if !anonymous {
+            return Err(LinuxError::ENODEV);
+        }
+        #[cfg(feature = "hugetlbfs")]
+        if let Some(huge_file) = file.as_deref().and_then(hugetlb::get_file) {
+            let addr = addr as usize;
+            return hugetlb::mmap(huge_file, addr, len, flags, prot_flags, offset as usize);
+        }
+        #[cfg(feature = "shm")]
+        if shared {
+            if let Some(node) = file.as_deref().and_then(shmem::get_file) {
+                let addr = addr as usize;
+                return shmem::mmap(node, addr, len, flags, prot_flags, offset as u64);
+            }
+        }
+
+        let fixed = flags & (ctypes::MAP_FIXED | ctypes::MAP_FIXED_NOREPLACE) != 0;
+        if fixed && flags & ctypes::MAP_FIXED_NOREPLACE == 0 {
+            let (start, len) = check_range(addr, len)?;
+            unmap_range(start, len)?;
+        }
+
+        let start = with_aspace(|aspace| {
+            let area = mmap_area(aspace);
+            let start = if fixed {
+                let start = VirtAddr::from(addr as usize);
+                let range = VirtAddrRange::from_start_size(start, len);
+                if !start.is_aligned_4k() || !area.contains_range(range) {
+                    return Err(LinuxError::EINVAL);
+                }
+                if aspace.find_free_area(start, len, range) != Some(start) {
+                    return Err(LinuxError::EEXIST);
+                }
+                start
+            } else {
+                let hint = VirtAddr::from(addr as usize).align_down_4k();
+                let hint = if area.contains(hint) {
+                    hint
+                } else {
+                    area.start
+                };
+                aspace
+                    .find_free_area(hint, len, area)
+                    .ok_or(LinuxError::ENOMEM)?
+            };
+            // File mappings are populated and made writable to copy the file
+            // content in, the requested protection is applied afterwards.
+            let (map_flags, populate) = if anonymous {
+                (prot_flags, flags & ctypes::MAP_POPULATE != 0)
+            } else {
+                (MappingFlags::READ | MappingFlags::WRITE, true)
+            };
+            aspace.map_alloc(start, len, map_flags, populate)?;
+            Ok(start)
+        })?;
+
+        #[cfg(feature = "fs")]
+        if let Some(file) = file {
+            let data_len = match file::fill(&file, start, len, offset as u64) {
+                Ok(n) => n,
+                Err(e) => {
+                    with_aspace(|aspace| aspace.unmap(start, len))?;
+                    return Err(e);
+                }
+            };
+            with_aspace(|aspace| {
+                aspace.protect(start, len, prot_flags)?;
+                axhal::arch::flush_tlb(None);
+                LinuxResult::Ok(())
+            })?;
+            if shared {
+                file::add_shared(start, len, file, offset as u64, data_len);
+            }
+        }
+        #[cfg(not(feature = "fs"))]
+        let _ = shared;
+
+        Ok(start.as_usize())
+    })
+}
+
+/// Removes the mappings in `[addr, addr + len)`.
+///
+/// Changes to `MAP_SHARED` file mappings in the range are written back.
+pub fn sys_munmap(addr: *mut c_void, len: ctypes::size_t) -> c_int {
+    debug!("sys_munmap <= addr: {:#x?}, len: {}", addr, len);
+    syscall_body!(sys_munmap, {
+        let (start, len) = check_range(addr, len)?;
+        unmap_range(start, len)?;
+        Ok(0)
+    })
+}
+
+/// Changes the access protection of the mappings in `[addr, addr + len)`.
+pub fn sys_mprotect(addr: *mut c_void, len: ctypes::size_t, prot: c_int) -> c_int {
+    debug!(
+        "sys_mprotect <= addr: {:#x?}, len: {}, prot: {:#x}",
+        addr, len, prot
+    );
+    syscall_body!(sys_mprotect, {
+        let (start, len) = check_range(addr, len).map_err(|_| LinuxError::ENOMEM)?;
+        let flags = prot_to_flags(prot)?;
+        with_aspace(|aspace| aspace.protect(start, len, flags))?;
+        axhal::arch::flush_tlb(None);
+        Ok(0)
+    })
+}
+
+/// Moves the program break from `old` to `new`, mapping or unmapping the
+/// pages in between. Pages of the heap are allocated on the first access.
+fn resize_heap(aspace: &mut AddrSpace, old: usize, new: usize) -> LinuxResult {
+    let (old_end, new_end) = (align_up_4k(old), align_up_4k(new));
+    if new_end > old_end {
+        let flags = MappingFlags::READ | MappingFlags::WRITE;
+        aspace.map_alloc(old_end.into(), new_end - old_end, flags, false)?;
+    } else if new_end < old_end {
+        aspace.unmap(new_end.into(), old_end - new_end)?;
+    }
+    Ok(())
+}
+
+/// Sets the program break to `addr`.
+///
+/// Returns the new program break on success, or the current one if `addr`
+/// is null, out of the heap range, or the memory is not enough, the same as
+/// the Linux syscall.
+pub fn sys_brk(addr: *mut c_void) -> *mut c_void {
+    debug!("sys_brk <= addr: {:#x?}", addr);
+    syscall_body!(sys_brk, {
+        with_brk(|brk| {
+            with_aspace(|aspace| {
+                let heap = heap_area(aspace);
+                if *brk == 0 {
+                    *brk = heap.start.as_usize();
+                }
+                let new = addr as usize;
+                if heap.contains(new.into()) && resize_heap(aspace, *brk, new).is_ok() {
+                    *brk = new;
+                }
+                Ok(*brk)
+            })
+        })
+    })
+}
+
+/// Moves the program break by `increment` bytes.
+///
+/// Returns the previous program break on success.
+pub fn sys_sbrk(increment: isize) -> *mut c_void {
+    debug!("sys_sbrk <= increment: {}", increment);
+    syscall_body!(sys_sbrk, {
+        with_brk(|brk| {
+            with_aspace(|aspace| {
+                let heap = heap_area(aspace);
+                if *brk == 0 {
+                    *brk = heap.start.as_usize();
+                }
+                let old = *brk;
+                let new = old
+                    .checked_add_signed(increment)
+                    .filter(|&new| heap.contains(new.into()))
+                    .ok_or(LinuxError::ENOMEM)?;
+                resize_heap(aspace, old, new)?;
+                *brk = new;
+                Ok(old)
+            })
+        })
+    })
+}
+
+/// Allocates frames for the faulting page in the mapping window or the heap of
+/// the kernel, or in any area of the current process for a fault of the user
+/// task, or of the kernel on a user address.
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_mmap_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    #[cfg(feature = "uspace")]
+    if let Some(aspace) = super::process::current_aspace() {
+        let mut aspace = aspace.lock();
+        if is_user || aspace.contains_range(vaddr, 1) {
+            return aspace.handle_page_fault(vaddr, access_flags);
+        }
+    }
+    if is_user {
+        return false;
+    }
+    let mut aspace = kernel_aspace().lock();
+    (mmap_area(&aspace).contains(vaddr) || heap_area(&aspace).contains(vaddr))
+        && aspace.handle_page_fault(vaddr, access_flags)
+}
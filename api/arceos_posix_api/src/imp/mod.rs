@@ -1,22 +1,59 @@
 mod stdio;
 
 pub mod io;
+pub mod membarrier;
+pub mod prctl;
+pub mod random;
 pub mod resources;
 pub mod sys;
 pub mod task;
 pub mod time;
 
+#[cfg(feature = "fs")]
+mod dev;
+#[cfg(feature = "fb")]
+pub mod fb;
 #[cfg(feature = "fd")]
 pub mod fd_ops;
 #[cfg(feature = "fs")]
+mod flock;
+#[cfg(feature = "fs")]
 pub mod fs;
+#[cfg(feature = "multitask")]
+pub mod futex;
+#[cfg(feature = "inotify")]
+pub mod inotify;
+#[cfg(feature = "input")]
+pub mod input;
 #[cfg(any(feature = "select", feature = "epoll"))]
 pub mod io_mpx;
+#[cfg(feature = "sysvipc")]
+pub mod ipc;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "mqueue")]
+pub mod mqueue;
 #[cfg(feature = "net")]
 pub mod net;
 #[cfg(feature = "fs")]
 pub mod path_link;
 #[cfg(feature = "pipe")]
 pub mod pipe;
+#[cfg(feature = "uspace")]
+pub mod process;
 #[cfg(feature = "multitask")]
 pub mod pthread;
+#[cfg(feature = "net")]
+mod resolv;
+#[cfg(feature = "shm")]
+pub mod shm;
+#[cfg(feature = "signal")]
+pub mod signal;
+#[cfg(feature = "uspace")]
+mod syscall;
+#[cfg(all(feature = "signal", feature = "irq"))]
+pub mod timer;
+#[cfg(feature = "timerfd")]
+pub mod timerfd;
+#[cfg(feature = "uio")]
+pub mod uio;
@@ -0,0 +1,509 @@
+//! POSIX message queues.
+//!
+//! Message queue descriptors are file descriptors, so they can be closed with
+//! `close` and monitored with `poll`/`select`/`epoll`.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::{c_char, c_int, c_long, c_uint};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axsync::Mutex;
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+use super::fd_ops::{FileLike, add_file_like, get_file_like};
+use super::task::wait_until_deadline;
+use crate::ctypes;
+use crate::uaccess::{copy_from_user, copy_to_user, get_user, put_user};
+use crate::utils::char_ptr_to_str;
+
+/// The default maximum number of messages in a queue.
+const MQ_DEFAULT_MAXMSG: usize = 10;
+/// The default maximum size of a message.
+const MQ_DEFAULT_MSGSIZE: usize = 8192;
+/// The upper limit of `mq_maxmsg` that can be specified in `mq_open`.
+const MQ_MAXMSG_LIMIT: usize = 65536;
+/// The upper limit of `mq_msgsize` that can be specified in `mq_open`.
+const MQ_MSGSIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+struct Message {
+    prio: c_uint,
+    data: Vec<u8>,
+}
+
+/// A notification registered by `mq_notify`.
+struct Notification {
+    /// The ID of the task that registered the notification.
+    owner: u64,
+    event: ctypes::sigevent,
+}
+
+// SAFETY: the pointers in `sigevent` are never dereferenced by the kernel.
+unsafe impl Send for Notification {}
+
+struct MqInner {
+    /// Messages in decreasing order of priority, and FIFO order for messages
+    /// with the same priority.
+    msgs: VecDeque<Message>,
+    notification: Option<Notification>,
+}
+
+/// A POSIX message queue.
+pub struct MessageQueue {
+    maxmsg: usize,
+    msgsize: usize,
+    mode: ctypes::mode_t,
+    inner: SpinNoIrq<MqInner>,
+    /// Tasks waiting for messages to arrive.
+    recv_wq: WaitQueue,
+    /// Tasks waiting for free space in the queue.
+    send_wq: WaitQueue,
+}
+
+impl MessageQueue {
+    fn new(maxmsg: usize, msgsize: usize, mode: ctypes::mode_t) -> Self {
+        Self {
+            maxmsg,
+            msgsize,
+            mode,
+            inner: SpinNoIrq::new(MqInner {
+                msgs: VecDeque::with_capacity(maxmsg),
+                notification: None,
+            }),
+            recv_wq: WaitQueue::new(),
+            send_wq: WaitQueue::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.lock().msgs.is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.inner.lock().msgs.len() >= self.maxmsg
+    }
+
+    fn try_send(&self, data: &[u8], prio: c_uint) -> Option<()> {
+        let mut inner = self.inner.lock();
+        if inner.msgs.len() >= self.maxmsg {
+            return None;
+        }
+        let was_empty = inner.msgs.is_empty();
+        let pos = inner
+            .msgs
+            .iter()
+            .position(|m| m.prio < prio)
+            .unwrap_or(inner.msgs.len());
+        inner.msgs.insert(
+            pos,
+            Message {
+                prio,
+                data: data.to_vec(),
+            },
+        );
+        // The notification is only triggered when a message arrives on an
+        // empty queue and nobody is blocked in `mq_receive`.
+        if was_empty && self.recv_wq.is_empty() {
+            if let Some(n) = inner.notification.take() {
+                drop(inner);
+                deliver_notification(&n);
+            }
+        }
+        Some(())
+    }
+
+    fn try_receive(&self, buf: &mut [u8]) -> Option<(usize, c_uint)> {
+        let msg = self.inner.lock().msgs.pop_front()?;
+        let len = msg.data.len();
+        buf[..len].copy_from_slice(&msg.data);
+        Some((len, msg.prio))
+    }
+
+    fn send(
+        &self,
+        data: &[u8],
+        prio: c_uint,
+        nonblocking: bool,
+        deadline: Option<Duration>,
+    ) -> LinuxResult {
+        loop {
+            if self.try_send(data, prio).is_some() {
+                self.recv_wq.notify_one(true);
+                return Ok(());
+            }
+            if nonblocking {
+                return Err(LinuxError::EAGAIN);
+            }
+            wait_until_deadline(&self.send_wq, deadline, || !self.is_full())?;
+        }
+    }
+
+    fn receive(
+        &self,
+        buf: &mut [u8],
+        nonblocking: bool,
+        deadline: Option<Duration>,
+    ) -> LinuxResult<(usize, c_uint)> {
+        loop {
+            if let Some(res) = self.try_receive(buf) {
+                self.send_wq.notify_one(true);
+                return Ok(res);
+            }
+            if nonblocking {
+                return Err(LinuxError::EAGAIN);
+            }
+            wait_until_deadline(&self.recv_wq, deadline, || !self.is_empty())?;
+        }
+    }
+}
+
+/// Delivers the notification registered by `mq_notify`.
+fn deliver_notification(n: &Notification) {
+    if n.event.sigev_notify as u32 == ctypes::SIGEV_SIGNAL {
+        debug!(
+            "mqueue notification: signal {} to task {}",
+            n.event.sigev_signo, n.owner
+        );
+        #[cfg(feature = "signal")]
+        if let Err(e) = crate::imp::signal::send_signal_to_task(
+            n.owner,
+            n.event.sigev_signo,
+            ctypes::SI_MESGQ,
+            unsafe { n.event.sigev_value.sival_ptr } as usize,
+        ) {
+            warn!("failed to deliver mqueue notification: {:?}", e);
+        }
+    }
+}
+
+/// A descriptor of an opened message queue.
+pub struct MqDescriptor {
+    queue: Arc<MessageQueue>,
+    readable: bool,
+    writable: bool,
+    nonblocking: AtomicBool,
+}
+
+impl MqDescriptor {
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<MqDescriptor>()
+            .map_err(|_| LinuxError::EBADF)
+    }
+
+    fn attr(&self) -> ctypes::mq_attr {
+        ctypes::mq_attr {
+            mq_flags: if self.nonblocking.load(Ordering::Acquire) {
+                ctypes::O_NONBLOCK as c_long
+            } else {
+                0
+            },
+            mq_maxmsg: self.queue.maxmsg as c_long,
+            mq_msgsize: self.queue.msgsize as c_long,
+            mq_curmsgs: self.queue.inner.lock().msgs.len() as c_long,
+            ..Default::default()
+        }
+    }
+}
+
+impl FileLike for MqDescriptor {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o100000 | (self.queue.mode & 0o777); // S_IFREG
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_uid: 1000,
+            st_gid: 1000,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        let len = self.queue.inner.lock().msgs.len();
+        Ok(PollState {
+            readable: self.readable && len > 0,
+            writable: self.writable && len < self.queue.maxmsg,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+}
+
+/// The namespace of all named message queues.
+static MQ_NAMESPACE: Mutex<BTreeMap<String, Arc<MessageQueue>>> = Mutex::new(BTreeMap::new());
+
+fn mq_name(name: *const c_char) -> LinuxResult<String> {
+    let name = char_ptr_to_str(name)?;
+    match name.strip_prefix('/') {
+        Some(n) if !n.is_empty() && !n.contains('/') => {
+            if n.len() > 255 {
+                Err(LinuxError::ENAMETOOLONG)
+            } else {
+                Ok(String::from(n))
+            }
+        }
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+fn abs_timeout(abs_timeout: *const ctypes::timespec) -> LinuxResult<Option<Duration>> {
+    if abs_timeout.is_null() {
+        return Ok(None);
+    }
+    let ts = get_user(abs_timeout)?;
+    if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec >= 1_000_000_000 {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Some(ts.into()))
+}
+
+/// Open a message queue.
+///
+/// If `O_CREAT` is specified in `oflag` and the queue does not exist, it is
+/// created with the given `mode` and `attr`. If `attr` is NULL, the default
+/// attributes are used.
+pub fn sys_mq_open(
+    name: *const c_char,
+    oflag: c_int,
+    mode: ctypes::mode_t,
+    attr: *const ctypes::mq_attr,
+) -> c_int {
+    debug!(
+        "sys_mq_open <= name: {:?}, oflag: {:#o}, mode: {:#o}",
+        char_ptr_to_str(name),
+        oflag,
+        mode
+    );
+    syscall_body!(sys_mq_open, {
+        let name = mq_name(name)?;
+        let oflag = oflag as u32;
+        let (readable, writable) = match oflag & 0b11 {
+            ctypes::O_RDONLY => (true, false),
+            ctypes::O_WRONLY => (false, true),
+            ctypes::O_RDWR => (true, true),
+            _ => return Err(LinuxError::EINVAL),
+        };
+
+        let mut namespace = MQ_NAMESPACE.lock();
+        let queue = if let Some(queue) = namespace.get(&name) {
+            if oflag & ctypes::O_CREAT != 0 && oflag & ctypes::O_EXCL != 0 {
+                return Err(LinuxError::EEXIST);
+            }
+            queue.clone()
+        } else if oflag & ctypes::O_CREAT != 0 {
+            let (maxmsg, msgsize) = if attr.is_null() {
+                (MQ_DEFAULT_MAXMSG, MQ_DEFAULT_MSGSIZE)
+            } else {
+                let attr = get_user(attr)?;
+                if attr.mq_maxmsg <= 0
+                    || attr.mq_msgsize <= 0
+                    || attr.mq_maxmsg as usize > MQ_MAXMSG_LIMIT
+                    || attr.mq_msgsize as usize > MQ_MSGSIZE_LIMIT
+                {
+                    return Err(LinuxError::EINVAL);
+                }
+                (attr.mq_maxmsg as usize, attr.mq_msgsize as usize)
+            };
+            let queue = Arc::new(MessageQueue::new(maxmsg, msgsize, mode));
+            namespace.insert(name, queue.clone());
+            queue
+        } else {
+            return Err(LinuxError::ENOENT);
+        };
+        drop(namespace);
+
+        add_file_like(Arc::new(MqDescriptor {
+            queue,
+            readable,
+            writable,
+            nonblocking: AtomicBool::new(oflag & ctypes::O_NONBLOCK != 0),
+        }))
+    })
+}
+
+/// Remove a message queue.
+///
+/// The queue is destroyed once all descriptors referring to it are closed.
+pub fn sys_mq_unlink(name: *const c_char) -> c_int {
+    debug!("sys_mq_unlink <= name: {:?}", char_ptr_to_str(name));
+    syscall_body!(sys_mq_unlink, {
+        let name = mq_name(name)?;
+        MQ_NAMESPACE
+            .lock()
+            .remove(&name)
+            .ok_or(LinuxError::ENOENT)?;
+        Ok(0)
+    })
+}
+
+/// Send a message to a message queue.
+///
+/// If the queue is full, it blocks until there is free space, or the absolute
+/// timeout `abs_timeout` (if not NULL) expires.
+pub fn sys_mq_timedsend(
+    mqdes: c_int,
+    msg_ptr: *const c_char,
+    msg_len: usize,
+    msg_prio: c_uint,
+    abs_timeout: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_mq_timedsend <= mqdes: {}, msg_len: {}, msg_prio: {}",
+        mqdes, msg_len, msg_prio
+    );
+    syscall_body!(sys_mq_timedsend, {
+        let mqd = MqDescriptor::from_fd(mqdes)?;
+        if !mqd.writable {
+            return Err(LinuxError::EBADF);
+        }
+        if msg_len > mqd.queue.msgsize {
+            return Err(LinuxError::EMSGSIZE);
+        }
+        if msg_prio >= ctypes::MQ_PRIO_MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut data = vec![0; msg_len];
+        if msg_len > 0 {
+            copy_from_user(&mut data, msg_ptr as *const u8)?;
+        }
+        let nonblocking = mqd.nonblocking.load(Ordering::Acquire);
+        mqd.queue
+            .send(&data, msg_prio, nonblocking, abs_timeout(abs_timeout)?)?;
+        Ok(0)
+    })
+}
+
+/// Receive the oldest message with the highest priority from a message queue.
+///
+/// If the queue is empty, it blocks until a message arrives, or the absolute
+/// timeout `abs_timeout` (if not NULL) expires.
+pub fn sys_mq_timedreceive(
+    mqdes: c_int,
+    msg_ptr: *mut c_char,
+    msg_len: usize,
+    msg_prio: *mut c_uint,
+    abs_timeout: *const ctypes::timespec,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_mq_timedreceive <= mqdes: {}, msg_len: {}",
+        mqdes, msg_len
+    );
+    syscall_body!(sys_mq_timedreceive, {
+        let mqd = MqDescriptor::from_fd(mqdes)?;
+        if !mqd.readable {
+            return Err(LinuxError::EBADF);
+        }
+        if msg_len < mqd.queue.msgsize {
+            return Err(LinuxError::EMSGSIZE);
+        }
+        if msg_ptr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let mut buf = vec![0; mqd.queue.msgsize];
+        let nonblocking = mqd.nonblocking.load(Ordering::Acquire);
+        let (len, prio) = mqd
+            .queue
+            .receive(&mut buf, nonblocking, abs_timeout(abs_timeout)?)?;
+        copy_to_user(msg_ptr as *mut u8, &buf[..len])?;
+        if !msg_prio.is_null() {
+            put_user(msg_prio, prio)?;
+        }
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Register or unregister for notification when a message arrives on an
+/// empty message queue.
+///
+/// Only one task can be registered for a queue at a time. If `sevp` is NULL,
+/// the registration of the calling task is removed.
+pub fn sys_mq_notify(mqdes: c_int, sevp: *const ctypes::sigevent) -> c_int {
+    debug!("sys_mq_notify <= mqdes: {}", mqdes);
+    syscall_body!(sys_mq_notify, {
+        let mqd = MqDescriptor::from_fd(mqdes)?;
+        let curr_id = axtask::current().id().as_u64();
+        // Read before locking the queue, which may fault in the page.
+        let event = if sevp.is_null() {
+            None
+        } else {
+            Some(get_user(sevp)?)
+        };
+        let mut inner = mqd.queue.inner.lock();
+        let Some(event) = event else {
+            if inner
+                .notification
+                .as_ref()
+                .is_some_and(|n| n.owner == curr_id)
+            {
+                inner.notification = None;
+            }
+            return Ok(0);
+        };
+        if inner.notification.is_some() {
+            return Err(LinuxError::EBUSY);
+        }
+        match event.sigev_notify as u32 {
+            ctypes::SIGEV_NONE | ctypes::SIGEV_SIGNAL => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
+        inner.notification = Some(Notification {
+            owner: curr_id,
+            event,
+        });
+        Ok(0)
+    })
+}
+
+/// Get and/or set the attributes of a message queue.
+///
+/// Only the `O_NONBLOCK` flag in `mq_flags` can be changed.
+pub fn sys_mq_getsetattr(
+    mqdes: c_int,
+    newattr: *const ctypes::mq_attr,
+    oldattr: *mut ctypes::mq_attr,
+) -> c_int {
+    debug!("sys_mq_getsetattr <= mqdes: {}", mqdes);
+    syscall_body!(sys_mq_getsetattr, {
+        let mqd = MqDescriptor::from_fd(mqdes)?;
+        if !oldattr.is_null() {
+            put_user(oldattr, mqd.attr())?;
+        }
+        if !newattr.is_null() {
+            let flags = get_user(newattr)?.mq_flags;
+            if flags & !(ctypes::O_NONBLOCK as c_long) != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            mqd.set_nonblocking(flags != 0)?;
+        }
+        Ok(0)
+    })
+}
@@ -1,5 +1,461 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::ffi::{c_char, c_int, c_void};
+use core::mem::size_of;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::time::Duration;
+
+use axerrno::{AxError, LinuxError, LinuxResult};
+use axio::PollState;
+use axnet::{ConnectFailure, TcpSocket, UdpSocket};
+use axsync::Mutex;
+
+use super::fd_ops::FileLike;
+use crate::ctypes;
 use crate::ctypes::{AF_INET, in_addr, sockaddr_in};
-use core::net::{Ipv4Addr, SocketAddrV4};
+use crate::uaccess::{copy_from_user, copy_to_user, get_user, put_user};
+use crate::uaccess::{with_user_buf, with_user_buf_mut};
+use crate::utils::char_ptr_to_str;
+
+/// The time after which a connection attempt is aborted with `ETIMEDOUT`, as
+/// Linux after the default 6 retransmissions of SYN.
+///
+/// A blocking `connect` waits for `SO_SNDTIMEO` at most, and then fails with
+/// `EINPROGRESS`, while the attempt goes on in the background.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(127);
+
+pub enum Socket {
+    Udp(Mutex<UdpSocket>),
+    Tcp(Mutex<TcpSocket>),
+}
+
+impl Socket {
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::FD_TABLE.write().add(Arc::new(self), cloexec)
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    /// Sends data on a connected socket.
+    ///
+    /// Sending on a TCP socket that cannot send any more fails with `EPIPE`,
+    /// and raises `SIGPIPE` unless `MSG_NOSIGNAL` is in `flags`. There is no
+    /// out-of-band data, so `MSG_OOB` fails with `EOPNOTSUPP`.
+    fn send(&self, buf: &[u8], flags: c_int) -> LinuxResult<usize> {
+        if flags as u32 & ctypes::MSG_OOB != 0 {
+            return Err(LinuxError::EOPNOTSUPP);
+        }
+        match self {
+            Socket::Udp(udpsocket) => with_flags(udpsocket, flags, |s| s.send(buf)),
+            Socket::Tcp(tcpsocket) => match with_flags(tcpsocket, flags, |s| s.send(buf)) {
+                // Shut down for writing, or closed by the peer.
+                Err(LinuxError::ECONNRESET) => {
+                    #[cfg(feature = "signal")]
+                    if flags as u32 & ctypes::MSG_NOSIGNAL == 0 {
+                        super::signal::raise_sigpipe();
+                    }
+                    Err(LinuxError::EPIPE)
+                }
+                res => res,
+            },
+        }
+    }
+
+    fn recv(&self, buf: &mut [u8], flags: c_int) -> LinuxResult<usize> {
+        self.recvfrom(buf, flags).map(|(len, _)| len)
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        match self {
+            Socket::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
+        }
+    }
+
+    fn local_addr(&self) -> LinuxResult<SocketAddr> {
+        match self {
+            Socket::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
+        }
+    }
+
+    fn peer_addr(&self) -> LinuxResult<SocketAddr> {
+        match self {
+            Socket::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
+        }
+    }
+
+    fn bind(&self, addr: SocketAddr) -> LinuxResult {
+        match self {
+            Socket::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
+        }
+    }
+
+    fn connect(&self, addr: SocketAddr) -> LinuxResult {
+        match self {
+            Socket::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
+            Socket::Tcp(tcpsocket) => {
+                let tcpsocket = tcpsocket.lock();
+                if tcpsocket.is_connecting() {
+                    // Updates the state of the pending connection.
+                    tcpsocket.poll()?;
+                    if tcpsocket.is_connecting() {
+                        return Err(LinuxError::EALREADY);
+                    }
+                    if let Some(failure) = tcpsocket.take_error() {
+                        return Err(connect_errno(failure));
+                    }
+                }
+                match tcpsocket.connect(addr) {
+                    // Timed out by the connect timeout if failed, otherwise
+                    // completed later, found by `poll` and `SO_ERROR`.
+                    Err(AxError::WouldBlock) => match tcpsocket.take_error() {
+                        Some(failure) => Err(connect_errno(failure)),
+                        None => Err(LinuxError::EINPROGRESS),
+                    },
+                    Err(AxError::AlreadyExists) => Err(LinuxError::EISCONN),
+                    res => Ok(res?),
+                }
+            }
+        }
+    }
+
+    fn sendto(&self, buf: &[u8], addr: SocketAddr, flags: c_int) -> LinuxResult<usize> {
+        if flags as u32 & ctypes::MSG_OOB != 0 {
+            return Err(LinuxError::EOPNOTSUPP);
+        }
+        match self {
+            // diff: must bind before sendto
+            Socket::Udp(udpsocket) => with_flags(udpsocket, flags, |s| s.send_to(buf, addr)),
+            Socket::Tcp(_) => Err(LinuxError::EISCONN),
+        }
+    }
+
+    /// Receives data, and the source address for UDP sockets.
+    ///
+    /// With `MSG_PEEK` in `flags`, the data is left in the receive queue. With
+    /// `MSG_WAITALL`, a TCP socket receives until `buf` is full, unless EOF,
+    /// an error or timeout comes first. There is no out-of-band data, so
+    /// `MSG_OOB` fails with `EINVAL`.
+    fn recvfrom(&self, buf: &mut [u8], flags: c_int) -> LinuxResult<(usize, Option<SocketAddr>)> {
+        let flags_u32 = flags as u32;
+        if flags_u32 & ctypes::MSG_OOB != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let peek = flags_u32 & ctypes::MSG_PEEK != 0;
+        let wait_all = flags_u32 & ctypes::MSG_WAITALL != 0;
+        match self {
+            // diff: must bind before recvfrom
+            Socket::Udp(udpsocket) => with_flags(udpsocket, flags, |s| {
+                let (len, addr) = if peek {
+                    s.peek_from(buf)?
+                } else {
+                    s.recv_from(buf)?
+                };
+                Ok((len, Some(addr)))
+            }),
+            Socket::Tcp(tcpsocket) => with_flags(tcpsocket, flags, |s| {
+                if peek {
+                    return Ok((s.peek(buf)?, None));
+                } else if !wait_all {
+                    return Ok((s.recv(buf)?, None));
+                }
+                let mut len = 0;
+                while len < buf.len() {
+                    match s.recv(&mut buf[len..]) {
+                        Ok(0) => break,
+                        Ok(n) => len += n,
+                        Err(e) if len == 0 => return Err(e),
+                        // Returns the data received before the error.
+                        Err(_) => break,
+                    }
+                }
+                Ok((len, None))
+            }),
+        }
+    }
+
+    fn listen(&self) -> LinuxResult {
+        match self {
+            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+        }
+    }
+
+    fn accept(&self) -> LinuxResult<TcpSocket> {
+        match self {
+            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
+            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+        }
+    }
+
+    fn setsockopt(
+        &self,
+        level: u32,
+        optname: u32,
+        optval: *const c_void,
+        optlen: ctypes::socklen_t,
+    ) -> LinuxResult {
+        match (level, optname) {
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEADDR) => {
+                let reuse = read_opt::<c_int>(optval, optlen)? != 0;
+                match self {
+                    Socket::Udp(udpsocket) => udpsocket.lock().set_reuse_addr(reuse),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().set_reuse_addr(reuse),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEPORT) => {
+                // The connections are balanced across the TCP listeners, while
+                // the UDP sockets only share the port.
+                let reuse = read_opt::<c_int>(optval, optlen)? != 0;
+                match self {
+                    Socket::Udp(udpsocket) => udpsocket.lock().set_reuse_addr(reuse),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().set_reuse_port(reuse),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_LINGER) => {
+                let Socket::Tcp(tcpsocket) = self else {
+                    return Err(LinuxError::EOPNOTSUPP);
+                };
+                let linger = read_opt::<ctypes::linger>(optval, optlen)?;
+                let timeout = (linger.l_onoff != 0)
+                    .then(|| Duration::from_secs(linger.l_linger.max(0) as u64));
+                tcpsocket.lock().set_linger(timeout);
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVTIMEO | ctypes::SO_SNDTIMEO) => {
+                let tv = read_opt::<ctypes::timeval>(optval, optlen)?;
+                if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+                    return Err(LinuxError::EDOM);
+                }
+                // A zero timeout means blocking forever.
+                let timeout = Some(Duration::from(tv)).filter(|t| !t.is_zero());
+                let recv = optname == ctypes::SO_RCVTIMEO;
+                match self {
+                    Socket::Udp(udpsocket) if recv => udpsocket.lock().set_read_timeout(timeout),
+                    Socket::Udp(udpsocket) => udpsocket.lock().set_write_timeout(timeout),
+                    Socket::Tcp(tcpsocket) if recv => tcpsocket.lock().set_read_timeout(timeout),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().set_write_timeout(timeout),
+                }
+            }
+            (ctypes::IPPROTO_IP, ctypes::IP_ADD_MEMBERSHIP | ctypes::IP_DROP_MEMBERSHIP) => {
+                let Socket::Udp(_) = self else {
+                    return Err(LinuxError::EOPNOTSUPP);
+                };
+                let mreq = read_opt::<ctypes::ip_mreq>(optval, optlen)?;
+                let into_ip_addr = |addr: in_addr| {
+                    axnet::IpAddr::Ipv4(axnet::Ipv4Addr::from_bytes(&addr.s_addr.to_ne_bytes()))
+                };
+                let group = into_ip_addr(mreq.imr_multiaddr);
+                let interface = into_ip_addr(mreq.imr_interface);
+                if optname == ctypes::IP_ADD_MEMBERSHIP {
+                    axnet::add_membership(group, interface)?;
+                } else {
+                    axnet::drop_membership(group, interface)?;
+                }
+            }
+            _ => return Err(LinuxError::ENOPROTOOPT),
+        }
+        Ok(())
+    }
+
+    fn getsockopt(
+        &self,
+        level: u32,
+        optname: u32,
+        optval: *mut c_void,
+        optlen: *mut ctypes::socklen_t,
+    ) -> LinuxResult {
+        match (level, optname) {
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEADDR) => {
+                let reuse = match self {
+                    Socket::Udp(udpsocket) => udpsocket.lock().is_reuse_addr(),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().is_reuse_addr(),
+                };
+                write_opt(optval, optlen, reuse as c_int)
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEPORT) => {
+                let reuse = match self {
+                    Socket::Udp(udpsocket) => udpsocket.lock().is_reuse_addr(),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().is_reuse_port(),
+                };
+                write_opt(optval, optlen, reuse as c_int)
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_LINGER) => {
+                let Socket::Tcp(tcpsocket) = self else {
+                    return Err(LinuxError::EOPNOTSUPP);
+                };
+                let timeout = tcpsocket.lock().linger();
+                let linger = ctypes::linger {
+                    l_onoff: timeout.is_some() as c_int,
+                    l_linger: timeout.map_or(0, |t| t.as_secs() as c_int),
+                };
+                write_opt(optval, optlen, linger)
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVTIMEO | ctypes::SO_SNDTIMEO) => {
+                let recv = optname == ctypes::SO_RCVTIMEO;
+                let timeout = match self {
+                    Socket::Udp(udpsocket) if recv => udpsocket.lock().read_timeout(),
+                    Socket::Udp(udpsocket) => udpsocket.lock().write_timeout(),
+                    Socket::Tcp(tcpsocket) if recv => tcpsocket.lock().read_timeout(),
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().write_timeout(),
+                };
+                let tv = ctypes::timeval::from(timeout.unwrap_or_default());
+                write_opt(optval, optlen, tv)
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_ERROR) => {
+                let failure = match self {
+                    Socket::Udp(_) => None,
+                    Socket::Tcp(tcpsocket) => tcpsocket.lock().take_error(),
+                };
+                let errno = failure.map_or(0, |f| connect_errno(f).code());
+                write_opt(optval, optlen, errno)
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_TYPE) => {
+                let socktype = match self {
+                    Socket::Udp(_) => ctypes::SOCK_DGRAM,
+                    Socket::Tcp(_) => ctypes::SOCK_STREAM,
+                };
+                write_opt(optval, optlen, socktype as c_int)
+            }
+            _ => Err(LinuxError::ENOPROTOOPT),
+        }
+    }
+
+    /// Shuts down the receive half, the transmit half or both of them, by
+    /// `SHUT_RD`, `SHUT_WR` or `SHUT_RDWR` in `how`.
+    fn shutdown(&self, how: u32) -> LinuxResult {
+        if !matches!(how, ctypes::SHUT_RD | ctypes::SHUT_WR | ctypes::SHUT_RDWR) {
+            return Err(LinuxError::EINVAL);
+        }
+        match self {
+            Socket::Udp(udpsocket) => {
+                let udpsocket = udpsocket.lock();
+                udpsocket.peer_addr()?;
+                udpsocket.shutdown()?;
+                Ok(())
+            }
+            Socket::Tcp(tcpsocket) => {
+                let tcpsocket = tcpsocket.lock();
+                tcpsocket.peer_addr()?;
+                if how != ctypes::SHUT_WR {
+                    tcpsocket.shutdown_read();
+                }
+                if how != ctypes::SHUT_RD {
+                    // FIN is sent after the data queued before.
+                    tcpsocket.close();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The socket operations that depend on the blocking mode.
+trait NonBlocking {
+    fn is_nonblocking(&self) -> bool;
+    fn set_nonblocking(&self, nonblocking: bool);
+}
+
+impl NonBlocking for UdpSocket {
+    fn is_nonblocking(&self) -> bool {
+        UdpSocket::is_nonblocking(self)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        UdpSocket::set_nonblocking(self, nonblocking)
+    }
+}
+
+impl NonBlocking for TcpSocket {
+    fn is_nonblocking(&self) -> bool {
+        TcpSocket::is_nonblocking(self)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        TcpSocket::set_nonblocking(self, nonblocking)
+    }
+}
+
+/// Runs `f` on the locked socket, in nonblocking mode if `MSG_DONTWAIT` is in
+/// `flags`.
+fn with_flags<S: NonBlocking, T>(
+    socket: &Mutex<S>,
+    flags: c_int,
+    f: impl FnOnce(&S) -> axerrno::AxResult<T>,
+) -> LinuxResult<T> {
+    let socket = socket.lock();
+    if flags as u32 & ctypes::MSG_DONTWAIT == 0 || socket.is_nonblocking() {
+        return Ok(f(&socket)?);
+    }
+    // The socket is locked, so the mode cannot be changed by others meanwhile.
+    socket.set_nonblocking(true);
+    let res = f(&socket);
+    socket.set_nonblocking(false);
+    Ok(res?)
+}
+
+impl FileLike for Socket {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        self.recv(buf, 0)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        self.send(buf, 0)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        // not really implemented
+        let st_mode = 0o140000 | 0o777u32; // S_IFSOCK | rwxrwxrwx
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_uid: 1000,
+            st_gid: 1000,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn link_target(&self) -> String {
+        format!("socket:[{}]", self as *const Self as usize)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        self.poll()
+    }
+
+    fn set_nonblocking(&self, nonblock: bool) -> LinuxResult {
+        match self {
+            Socket::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
+            Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+        }
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        match self {
+            Socket::Udp(udpsocket) => udpsocket.lock().is_nonblocking(),
+            Socket::Tcp(tcpsocket) => tcpsocket.lock().is_nonblocking(),
+        }
+    }
+}
 
 impl From<SocketAddrV4> for sockaddr_in {
     fn from(addr: SocketAddrV4) -> sockaddr_in {
@@ -24,3 +480,667 @@ impl From<sockaddr_in> for SocketAddrV4 {
         )
     }
 }
+
+fn into_sockaddr(addr: SocketAddr) -> (ctypes::sockaddr, ctypes::socklen_t) {
+    debug!("    Sockaddr: {}", addr);
+    match addr {
+        SocketAddr::V4(addr) => (
+            unsafe { *(&sockaddr_in::from(addr) as *const _ as *const ctypes::sockaddr) },
+            size_of::<ctypes::sockaddr>() as _,
+        ),
+        SocketAddr::V6(_) => panic!("IPv6 is not supported"),
+    }
+}
+
+fn from_sockaddr(
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> LinuxResult<SocketAddr> {
+    if addr.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if addrlen != size_of::<ctypes::sockaddr>() as _ {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let mid = get_user(addr as *const sockaddr_in)?;
+    if mid.sin_family != AF_INET as u16 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let res = SocketAddr::V4(mid.into());
+    debug!("    load sockaddr:{:#x} => {:?}", addr as usize, res);
+    Ok(res)
+}
+
+/// Converts the failure of a TCP connection attempt to the error number.
+fn connect_errno(failure: ConnectFailure) -> LinuxError {
+    match failure {
+        ConnectFailure::Refused => LinuxError::ECONNREFUSED,
+        ConnectFailure::TimedOut => LinuxError::ETIMEDOUT,
+    }
+}
+
+/// Reads a socket option value of type `T`.
+fn read_opt<T: Copy>(optval: *const c_void, optlen: ctypes::socklen_t) -> LinuxResult<T> {
+    if optval.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if (optlen as usize) < size_of::<T>() {
+        return Err(LinuxError::EINVAL);
+    }
+    get_user(optval as *const T)
+}
+
+/// Writes a socket option value, truncated to the buffer length in `optlen`.
+fn write_opt<T>(optval: *mut c_void, optlen: *mut ctypes::socklen_t, val: T) -> LinuxResult {
+    if optval.is_null() || optlen.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let len = (get_user(optlen)? as usize).min(size_of::<T>());
+    let bytes = unsafe { core::slice::from_raw_parts(&val as *const T as *const u8, len) };
+    copy_to_user(optval as *mut u8, bytes)?;
+    put_user(optlen, len as _)
+}
+
+/// Create an socket for communication.
+///
+/// `SOCK_NONBLOCK` and `SOCK_CLOEXEC` can be set in `socktype`.
+///
+/// Return the socket file descriptor.
+pub fn sys_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
+    debug!("sys_socket <= {} {} {}", domain, socktype, protocol);
+    let (domain, socktype, protocol) = (domain as u32, socktype as u32, protocol as u32);
+    let flags = socktype & (ctypes::SOCK_NONBLOCK | ctypes::SOCK_CLOEXEC);
+    syscall_body!(sys_socket, {
+        let socket = match (domain, socktype & !flags, protocol) {
+            (ctypes::AF_INET, ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
+            | (ctypes::AF_INET, ctypes::SOCK_STREAM, 0) => {
+                let tcpsocket = TcpSocket::new();
+                tcpsocket.set_connect_timeout(Some(CONNECT_TIMEOUT));
+                Socket::Tcp(Mutex::new(tcpsocket))
+            }
+            (ctypes::AF_INET, ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
+            | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => Socket::Udp(Mutex::new(UdpSocket::new())),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if flags & ctypes::SOCK_NONBLOCK != 0 {
+            FileLike::set_nonblocking(&socket, true)?;
+        }
+        socket.add_to_fd_table(flags & ctypes::SOCK_CLOEXEC != 0)
+    })
+}
+
+/// Bind a address to a socket.
+///
+/// Return 0 if success.
+pub fn sys_bind(
+    socket_fd: c_int,
+    socket_addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_bind <= {} {:#x} {}",
+        socket_fd, socket_addr as usize, addrlen
+    );
+    syscall_body!(sys_bind, {
+        let addr = from_sockaddr(socket_addr, addrlen)?;
+        Socket::from_fd(socket_fd)?.bind(addr)?;
+        Ok(0)
+    })
+}
+
+/// Connects the socket to the address specified.
+///
+/// Return 0 if success.
+pub fn sys_connect(
+    socket_fd: c_int,
+    socket_addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_connect <= {} {:#x} {}",
+        socket_fd, socket_addr as usize, addrlen
+    );
+    syscall_body!(sys_connect, {
+        let addr = from_sockaddr(socket_addr, addrlen)?;
+        Socket::from_fd(socket_fd)?.connect(addr)?;
+        Ok(0)
+    })
+}
+
+/// Send a message on a socket to the address specified.
+///
+/// Return the number of bytes sent if success.
+pub fn sys_sendto(
+    socket_fd: c_int,
+    buf_ptr: *const c_void,
+    len: ctypes::size_t,
+    flag: c_int,
+    socket_addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_sendto <= {} {:#x} {} {} {:#x} {}",
+        socket_fd, buf_ptr as usize, len, flag, socket_addr as usize, addrlen
+    );
+    syscall_body!(sys_sendto, {
+        if buf_ptr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let addr = from_sockaddr(socket_addr, addrlen)?;
+
+        let socket = Socket::from_fd(socket_fd)?;
+        with_user_buf(buf_ptr as *const u8, len, |buf| {
+            socket.sendto(buf, addr, flag)
+        })
+    })
+}
+
+/// Send a message on a socket to the address connected.
+///
+/// Supported flags are `MSG_DONTWAIT` and `MSG_NOSIGNAL`.
+///
+/// Return the number of bytes sent if success.
+pub fn sys_send(
+    socket_fd: c_int,
+    buf_ptr: *const c_void,
+    len: ctypes::size_t,
+    flag: c_int,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_sendto <= {} {:#x} {} {}",
+        socket_fd, buf_ptr as usize, len, flag
+    );
+    syscall_body!(sys_send, {
+        let socket = Socket::from_fd(socket_fd)?;
+        with_user_buf(buf_ptr as *const u8, len, |buf| socket.send(buf, flag))
+    })
+}
+
+/// Receive a message on a socket and get its source address.
+///
+/// Return the number of bytes received if success.
+pub unsafe fn sys_recvfrom(
+    socket_fd: c_int,
+    buf_ptr: *mut c_void,
+    len: ctypes::size_t,
+    flag: c_int,
+    socket_addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_recvfrom <= {} {:#x} {} {} {:#x} {:#x}",
+        socket_fd, buf_ptr as usize, len, flag, socket_addr as usize, addrlen as usize
+    );
+    syscall_body!(sys_recvfrom, {
+        if buf_ptr.is_null() || socket_addr.is_null() || addrlen.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let socket = Socket::from_fd(socket_fd)?;
+        let mut src = None;
+        let len = with_user_buf_mut(buf_ptr as *mut u8, len, |buf| {
+            let (len, addr) = socket.recvfrom(buf, flag)?;
+            src = addr;
+            Ok(len)
+        })?;
+        if let Some(addr) = src {
+            let (addr, len) = into_sockaddr(addr);
+            put_user(socket_addr, addr)?;
+            put_user(addrlen, len)?;
+        }
+        Ok(len)
+    })
+}
+
+/// Receive a message on a socket.
+///
+/// Supported flags are `MSG_PEEK`, `MSG_WAITALL` and `MSG_DONTWAIT`.
+///
+/// Return the number of bytes received if success.
+pub fn sys_recv(
+    socket_fd: c_int,
+    buf_ptr: *mut c_void,
+    len: ctypes::size_t,
+    flag: c_int,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_recv <= {} {:#x} {} {}",
+        socket_fd, buf_ptr as usize, len, flag
+    );
+    syscall_body!(sys_recv, {
+        let socket = Socket::from_fd(socket_fd)?;
+        with_user_buf_mut(buf_ptr as *mut u8, len, |buf| socket.recv(buf, flag))
+    })
+}
+
+/// Returns the buffers described by the `iovlen` entries at `iov`.
+fn iov_bufs(iov: *const ctypes::iovec, iovlen: c_int) -> LinuxResult<Vec<ctypes::iovec>> {
+    if !(0..=1024).contains(&iovlen) {
+        return Err(LinuxError::EMSGSIZE);
+    }
+    let iovs = (0..iovlen as usize)
+        .map(|i| get_user(iov.wrapping_add(i)))
+        .collect::<LinuxResult<Vec<_>>>()?;
+    if iovs
+        .iter()
+        .any(|iov| iov.iov_base.is_null() && iov.iov_len > 0)
+    {
+        return Err(LinuxError::EFAULT);
+    }
+    Ok(iovs)
+}
+
+/// Send a message gathered from the buffers in `msg`, to `msg_name` if it is
+/// given.
+///
+/// `MSG_DONTWAIT` is supported in `flags`. Ancillary data is ignored.
+///
+/// Return the number of bytes sent if success.
+pub unsafe fn sys_sendmsg(
+    socket_fd: c_int,
+    msg: *const ctypes::msghdr,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    debug!("sys_sendmsg <= {} {:#x} {}", socket_fd, msg as usize, flags);
+    syscall_body!(sys_sendmsg, {
+        let msg = get_user(msg)?;
+        let socket = Socket::from_fd(socket_fd)?;
+        let iovs = iov_bufs(msg.msg_iov, msg.msg_iovlen)?;
+        // The message is sent as a whole, as a datagram cannot be split.
+        let mut buf = vec![0; iovs.iter().map(|iov| iov.iov_len as usize).sum()];
+        let mut pos = 0;
+        for iov in iovs.iter().filter(|iov| iov.iov_len > 0) {
+            let n = iov.iov_len as usize;
+            copy_from_user(&mut buf[pos..pos + n], iov.iov_base as *const u8)?;
+            pos += n;
+        }
+        if msg.msg_name.is_null() {
+            socket.send(&buf, flags)
+        } else {
+            let addr = from_sockaddr(msg.msg_name as *const ctypes::sockaddr, msg.msg_namelen)?;
+            socket.sendto(&buf, addr, flags)
+        }
+    })
+}
+
+/// Receive a message into the buffers in `msg`, and get its source address
+/// in `msg_name` if it is given.
+///
+/// `MSG_PEEK` and `MSG_DONTWAIT` are supported in `flags`. No ancillary data
+/// is received.
+///
+/// Return the number of bytes received if success.
+pub unsafe fn sys_recvmsg(
+    socket_fd: c_int,
+    msg: *mut ctypes::msghdr,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    debug!("sys_recvmsg <= {} {:#x} {}", socket_fd, msg as usize, flags);
+    syscall_body!(sys_recvmsg, {
+        let msg_ptr = msg;
+        let mut msg = get_user(msg_ptr)?;
+        let socket = Socket::from_fd(socket_fd)?;
+        let iovs = iov_bufs(msg.msg_iov, msg.msg_iovlen)?;
+        // Receive at once then scatter, as a datagram is consumed by one read.
+        let mut buf = vec![0; iovs.iter().map(|iov| iov.iov_len as usize).sum()];
+        let (len, addr) = socket.recvfrom(&mut buf, flags)?;
+
+        let mut pos = 0;
+        for iov in iovs.iter().filter(|iov| iov.iov_len > 0) {
+            if pos == len {
+                break;
+            }
+            let n = (iov.iov_len as usize).min(len - pos);
+            copy_to_user(iov.iov_base as *mut u8, &buf[pos..pos + n])?;
+            pos += n;
+        }
+
+        if !msg.msg_name.is_null() {
+            match addr {
+                Some(addr) if msg.msg_namelen as usize >= size_of::<ctypes::sockaddr>() => {
+                    let name;
+                    (name, msg.msg_namelen) = into_sockaddr(addr);
+                    put_user(msg.msg_name as *mut ctypes::sockaddr, name)?;
+                }
+                Some(_) => return Err(LinuxError::EINVAL),
+                None => msg.msg_namelen = 0,
+            }
+        }
+        msg.msg_controllen = 0;
+        msg.msg_flags = 0;
+        put_user(msg_ptr, msg)?;
+        Ok(len)
+    })
+}
+
+/// Listen for connections on a socket
+///
+/// Return 0 if success.
+pub fn sys_listen(
+    socket_fd: c_int,
+    backlog: c_int, // currently not used
+) -> c_int {
+    debug!("sys_listen <= {} {}", socket_fd, backlog);
+    syscall_body!(sys_listen, {
+        Socket::from_fd(socket_fd)?.listen()?;
+        Ok(0)
+    })
+}
+
+/// Accept for connections on a socket
+///
+/// Return file descriptor for the accepted socket if success.
+pub unsafe fn sys_accept(
+    socket_fd: c_int,
+    socket_addr: *mut ctypes::sockaddr,
+    socket_len: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_accept <= {} {:#x} {:#x}",
+        socket_fd, socket_addr as usize, socket_len as usize
+    );
+    syscall_body!(sys_accept, {
+        if socket_addr.is_null() || socket_len.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let socket = Socket::from_fd(socket_fd)?;
+        let new_socket = socket.accept()?;
+        let addr = new_socket.peer_addr()?;
+        let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)), false)?;
+        let (addr, len) = into_sockaddr(addr);
+        put_user(socket_addr, addr)?;
+        put_user(socket_len, len)?;
+        Ok(new_fd)
+    })
+}
+
+/// Shut down part or all of a full-duplex connection.
+///
+/// After `SHUT_RD`, reading returns the data already received, then EOF.
+/// After `SHUT_WR`, FIN is sent to the peer, and writing fails with `EPIPE`.
+/// The socket is still to be closed.
+///
+/// Return 0 if success.
+pub fn sys_shutdown(socket_fd: c_int, how: c_int) -> c_int {
+    debug!("sys_shutdown <= {} {}", socket_fd, how);
+    syscall_body!(sys_shutdown, {
+        Socket::from_fd(socket_fd)?.shutdown(how as u32)?;
+        Ok(0)
+    })
+}
+
+/// Set an option of a socket.
+///
+/// Supported options are `SO_REUSEADDR`, `SO_REUSEPORT`, `SO_RCVTIMEO`,
+/// `SO_SNDTIMEO`, and `SO_LINGER` of TCP sockets at `SOL_SOCKET`, and
+/// `IP_ADD_MEMBERSHIP` and `IP_DROP_MEMBERSHIP` of UDP sockets at
+/// `IPPROTO_IP`. `SO_SNDTIMEO` also bounds the wait of a blocking `connect`.
+///
+/// Return 0 if success.
+pub fn sys_setsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_setsockopt <= {} {} {} {:#x} {}",
+        socket_fd, level, optname, optval as usize, optlen
+    );
+    syscall_body!(sys_setsockopt, {
+        Socket::from_fd(socket_fd)?.setsockopt(level as u32, optname as u32, optval, optlen)?;
+        Ok(0)
+    })
+}
+
+/// Get an option of a socket.
+///
+/// Besides the options supported by [`sys_setsockopt`] at `SOL_SOCKET`,
+/// `SO_ERROR` and `SO_TYPE` are supported.
+///
+/// Return 0 if success.
+pub unsafe fn sys_getsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_getsockopt <= {} {} {} {:#x} {:#x}",
+        socket_fd, level, optname, optval as usize, optlen as usize
+    );
+    syscall_body!(sys_getsockopt, {
+        Socket::from_fd(socket_fd)?.getsockopt(level as u32, optname as u32, optval, optlen)?;
+        Ok(0)
+    })
+}
+
+/// Query addresses for a domain name.
+///
+/// The name is an IPv4 address, a name in `/etc/hosts`, or a name queried
+/// from the DNS. Only IPv4. `servname` must be a port number. The `ai_flags`,
+/// `ai_family` and `ai_socktype` of hints are supported.
+/// Results' ai_flags and ai_canonname are 0 or NULL.
+///
+/// Return address number if success.
+pub unsafe fn sys_getaddrinfo(
+    nodename: *const c_char,
+    servname: *const c_char,
+    hints: *const ctypes::addrinfo,
+    res: *mut *mut ctypes::addrinfo,
+) -> c_int {
+    let name = char_ptr_to_str(nodename);
+    let port = char_ptr_to_str(servname);
+    debug!("sys_getaddrinfo <= {:?} {:?}", name, port);
+    syscall_body!(sys_getaddrinfo, {
+        if nodename.is_null() && servname.is_null() {
+            return Ok(0);
+        }
+        if res.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let (flags, family, socktype) = if hints.is_null() {
+            (0, ctypes::AF_UNSPEC as _, 0)
+        } else {
+            let hints = get_user(hints)?;
+            (hints.ai_flags, hints.ai_family, hints.ai_socktype)
+        };
+        if family != ctypes::AF_UNSPEC as c_int && family != AF_INET as c_int {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+        let (socktype, protocol) = match socktype as u32 {
+            0 | ctypes::SOCK_STREAM => (ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP),
+            ctypes::SOCK_DGRAM => (ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP),
+            _ => return Err(LinuxError::ESOCKTNOSUPPORT),
+        };
+
+        let port = port.map_or(0, |p| p.parse::<u16>().unwrap_or(0));
+        let ip_addrs = if let Ok(domain) = name {
+            if flags & ctypes::AI_NUMERICHOST as c_int != 0 {
+                vec![domain.parse::<IpAddr>().map_err(|_| LinuxError::ENOENT)?]
+            } else {
+                super::resolv::lookup_host(&domain)?
+            }
+        } else if flags & ctypes::AI_PASSIVE as c_int != 0 {
+            vec![Ipv4Addr::UNSPECIFIED.into()]
+        } else {
+            vec![Ipv4Addr::LOCALHOST.into()]
+        };
+        let ip_addrs: Vec<Ipv4Addr> = ip_addrs
+            .into_iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+            .collect();
+
+        let len = ip_addrs.len().min(ctypes::MAXADDRS as usize);
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let mut out: Vec<ctypes::aibuf> = Vec::with_capacity(len);
+        for (i, &ip) in ip_addrs.iter().enumerate().take(len) {
+            let buf = ctypes::aibuf {
+                ai: ctypes::addrinfo {
+                    ai_family: AF_INET as _,
+                    ai_socktype: socktype as _,
+                    ai_protocol: protocol as _,
+                    ai_addrlen: size_of::<sockaddr_in>() as _,
+                    ai_addr: core::ptr::null_mut(),
+                    ai_canonname: core::ptr::null_mut(),
+                    ai_next: core::ptr::null_mut(),
+                    ai_flags: 0,
+                },
+                sa: ctypes::aibuf_sa {
+                    sin: SocketAddrV4::new(ip, port).into(),
+                },
+                slot: i as i16,
+                lock: [0],
+                ref_: 0,
+            };
+            out.push(buf);
+            out[i].ai.ai_addr =
+                unsafe { core::ptr::addr_of_mut!(out[i].sa.sin) as *mut ctypes::sockaddr };
+            if i > 0 {
+                out[i - 1].ai.ai_next = core::ptr::addr_of_mut!(out[i].ai);
+            }
+        }
+
+        out[0].ref_ = len as i16;
+        put_user(res, core::ptr::addr_of_mut!(out[0].ai))?;
+        core::mem::forget(out); // drop in `sys_freeaddrinfo`
+        Ok(len)
+    })
+}
+
+/// Free queried `addrinfo` struct
+pub unsafe fn sys_freeaddrinfo(res: *mut ctypes::addrinfo) {
+    if res.is_null() {
+        return;
+    }
+    let aibuf_ptr = res as *mut ctypes::aibuf;
+    let len = unsafe { (*aibuf_ptr).ref_ } as usize;
+    assert!(unsafe { (*aibuf_ptr).slot } == 0);
+    assert!(len > 0);
+    let vec = unsafe { Vec::from_raw_parts(aibuf_ptr, len, len) }; // TODO: lock
+    drop(vec);
+}
+
+/// Get the name of a host and a service from a socket address.
+///
+/// Only IPv4. The host name is looked up in `/etc/hosts`, and the service is
+/// always the port number. `NI_NUMERICHOST`, `NI_NAMEREQD` and `NI_NOFQDN` of
+/// flags are supported.
+///
+/// Return 0 if success.
+pub unsafe fn sys_getnameinfo(
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+    host: *mut c_char,
+    hostlen: ctypes::socklen_t,
+    serv: *mut c_char,
+    servlen: ctypes::socklen_t,
+    flags: c_int,
+) -> c_int {
+    debug!(
+        "sys_getnameinfo <= {:#x} {} {:#x}",
+        addr as usize, addrlen, flags
+    );
+    syscall_body!(sys_getnameinfo, {
+        if addr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if addrlen < size_of::<sockaddr_in>() as _ {
+            return Err(LinuxError::EINVAL);
+        }
+        let sin = get_user(addr as *const sockaddr_in)?;
+        if sin.sin_family != AF_INET as u16 {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+        let addr = SocketAddrV4::from(sin);
+
+        if !host.is_null() && hostlen > 0 {
+            let name = if flags & ctypes::NI_NUMERICHOST as c_int != 0 {
+                None
+            } else {
+                super::resolv::lookup_addr((*addr.ip()).into())
+            };
+            let name = match name {
+                Some(name) if flags & ctypes::NI_NOFQDN as c_int != 0 => {
+                    name.split('.').next().unwrap_or_default().into()
+                }
+                Some(name) => name,
+                None if flags & ctypes::NI_NAMEREQD as c_int != 0 => {
+                    return Err(LinuxError::ENOENT);
+                }
+                None => addr.ip().to_string(),
+            };
+            copy_c_str(&name, host, hostlen)?;
+        }
+        if !serv.is_null() && servlen > 0 {
+            copy_c_str(&addr.port().to_string(), serv, servlen)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Copies a string to a C buffer with the terminating NUL.
+fn copy_c_str(s: &str, buf: *mut c_char, len: ctypes::socklen_t) -> LinuxResult {
+    if s.len() >= len as usize {
+        return Err(LinuxError::EOVERFLOW);
+    }
+    copy_to_user(buf as *mut u8, s.as_bytes())?;
+    put_user(buf.wrapping_add(s.len()), 0)
+}
+
+/// Get current address to which the socket sockfd is bound.
+pub unsafe fn sys_getsockname(
+    sock_fd: c_int,
+    addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_getsockname <= {} {:#x} {:#x}",
+        sock_fd, addr as usize, addrlen as usize
+    );
+    syscall_body!(sys_getsockname, {
+        if addr.is_null() || addrlen.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if get_user(addrlen)? < size_of::<ctypes::sockaddr>() as u32 {
+            return Err(LinuxError::EINVAL);
+        }
+        let (name, len) = into_sockaddr(Socket::from_fd(sock_fd)?.local_addr()?);
+        put_user(addr, name)?;
+        put_user(addrlen, len)?;
+        Ok(0)
+    })
+}
+
+/// Get peer address to which the socket sockfd is connected.
+pub unsafe fn sys_getpeername(
+    sock_fd: c_int,
+    addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_getpeername <= {} {:#x} {:#x}",
+        sock_fd, addr as usize, addrlen as usize
+    );
+    syscall_body!(sys_getpeername, {
+        if addr.is_null() || addrlen.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if get_user(addrlen)? < size_of::<ctypes::sockaddr>() as u32 {
+            return Err(LinuxError::EINVAL);
+        }
+        let (name, len) = into_sockaddr(Socket::from_fd(sock_fd)?.peer_addr()?);
+        put_user(addr, name)?;
+        put_user(addrlen, len)?;
+        Ok(0)
+    })
+}
@@ -305,9 +305,7 @@ pub fn handle_file_path(
                 axlog::warn!("路径地址为空");
                 return Err(AxError::BadAddress);
             }
-            crate::utils::char_ptr_to_str(addr as *const _)
-                .map_err(|_| AxError::NotFound)?
-                .to_string()
+            crate::utils::char_ptr_to_str(addr as *const _).map_err(|_| AxError::NotFound)?
         }
         None => String::new(),
     };
@@ -349,8 +347,7 @@ fn handle_empty_path(dir_fd: isize) -> AxResult<String> {
 fn handle_relative_path(dir_fd: isize, path: &str) -> AxResult<String> {
     match super::fs::Directory::from_fd(dir_fd as i32) {
         Ok(dir) => {
-            // 假设目录路径以 '/' 结尾，无需手动添加
-            let combined_path = format!("{}{}", dir.path(), path);
+            let combined_path = format!("{}/{}", dir.path().trim_end_matches('/'), path);
             axlog::info!("处理后的路径: {} (目录: {})", combined_path, dir.path());
             Ok(combined_path)
         }
@@ -1,12 +1,20 @@
+use alloc::format;
+use alloc::string::String;
 use alloc::sync::Arc;
-use core::ffi::c_int;
+use core::ffi::{c_int, c_uint};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
-use axsync::Mutex;
+use axsync::{Mutex, MutexGuard};
+#[cfg(feature = "multitask")]
+use axtask::WaitQueue;
 
-use super::fd_ops::{FileLike, add_file_like, close_file_like};
+use super::fd_ops::{FD_TABLE, FileLike, close_file_like, get_file_like};
 use crate::ctypes;
+#[cfg(feature = "fs")]
+use crate::uaccess::put_user;
+use crate::uaccess::{get_user, with_user_buf, with_user_buf_mut};
 
 #[derive(Copy, Clone, PartialEq)]
 enum RingBufferStatus {
@@ -43,6 +51,11 @@ impl PipeRingBuffer {
         }
     }
 
+    /// Get the `i`-th byte to be read, without consuming it.
+    pub fn peek_byte(&self, i: usize) -> u8 {
+        self.arr[(self.head + i) % RING_BUFFER_SIZE]
+    }
+
     pub fn read_byte(&mut self) -> u8 {
         self.status = RingBufferStatus::Normal;
         let c = self.arr[self.head];
@@ -74,21 +87,80 @@ impl PipeRingBuffer {
     }
 }
 
+/// The tasks blocked on the ends of a pipe.
+struct PipeWaitQueue {
+    /// Incremented when the data or the space in the buffer changes, or an
+    /// end is closed.
+    generation: AtomicUsize,
+    #[cfg(feature = "multitask")]
+    wq: WaitQueue,
+}
+
+/// The reference to the [`PipeWaitQueue`] of each end, which wakes up the
+/// other end when dropped.
+struct PipeNotifier(Arc<PipeWaitQueue>);
+
+impl PipeNotifier {
+    /// Returns the current generation, to be passed to [`PipeNotifier::wait`]
+    /// after checking the buffer.
+    fn generation(&self) -> usize {
+        self.0.generation.load(Ordering::Acquire)
+    }
+
+    /// Wakes up the tasks blocked on both ends, and the pollers.
+    fn notify(&self) {
+        self.0.generation.fetch_add(1, Ordering::AcqRel);
+        #[cfg(feature = "multitask")]
+        self.0.wq.notify_all(false);
+        super::fd_ops::notify_pollers();
+    }
+
+    /// Blocks the current task until [`PipeNotifier::notify`] is called after
+    /// `generation` is taken.
+    fn wait(&self, generation: usize) {
+        #[cfg(feature = "multitask")]
+        self.0.wq.wait_until(|| self.generation() != generation);
+        #[cfg(not(feature = "multitask"))]
+        while self.generation() == generation {
+            crate::sys_sched_yield();
+        }
+    }
+}
+
+impl Drop for PipeNotifier {
+    fn drop(&mut self) {
+        self.notify();
+    }
+}
+
 pub struct Pipe {
     readable: bool,
     buffer: Arc<Mutex<PipeRingBuffer>>,
+    nonblocking: AtomicBool,
+    // Declared after `buffer` to be dropped after it, so the other end sees
+    // this end closed when woken up.
+    notifier: PipeNotifier,
 }
 
 impl Pipe {
     pub fn new() -> (Pipe, Pipe) {
         let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
+        let wait_queue = Arc::new(PipeWaitQueue {
+            generation: AtomicUsize::new(0),
+            #[cfg(feature = "multitask")]
+            wq: WaitQueue::new(),
+        });
         let read_end = Pipe {
             readable: true,
             buffer: buffer.clone(),
+            nonblocking: AtomicBool::new(false),
+            notifier: PipeNotifier(wait_queue.clone()),
         };
         let write_end = Pipe {
             readable: false,
             buffer,
+            nonblocking: AtomicBool::new(false),
+            notifier: PipeNotifier(wait_queue),
         };
         (read_end, write_end)
     }
@@ -104,6 +176,86 @@ impl Pipe {
     pub fn write_end_close(&self) -> bool {
         Arc::strong_count(&self.buffer) == 1
     }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    /// Locks the ring buffers of `self` and `other` in a fixed order, to avoid
+    /// deadlocks between two tasks locking the same pair.
+    fn lock_pair<'a>(
+        &'a self,
+        other: &'a Pipe,
+    ) -> LinuxResult<(
+        MutexGuard<'a, PipeRingBuffer>,
+        MutexGuard<'a, PipeRingBuffer>,
+    )> {
+        if Arc::ptr_eq(&self.buffer, &other.buffer) {
+            return Err(LinuxError::EINVAL);
+        }
+        if Arc::as_ptr(&self.buffer) < Arc::as_ptr(&other.buffer) {
+            let src = self.buffer.lock();
+            Ok((src, other.buffer.lock()))
+        } else {
+            let dst = other.buffer.lock();
+            Ok((self.buffer.lock(), dst))
+        }
+    }
+
+    /// Moves (or copies if `consume` is false) up to `len` bytes from this
+    /// pipe to the `dst` pipe, directly between their ring buffers.
+    fn transfer_to(
+        &self,
+        dst: &Pipe,
+        len: usize,
+        consume: bool,
+        nonblocking: bool,
+    ) -> LinuxResult<usize> {
+        if !self.readable() || !dst.writable() {
+            return Err(LinuxError::EBADF);
+        }
+        loop {
+            let src_generation = self.notifier.generation();
+            let dst_generation = dst.notifier.generation();
+            let avail_read = {
+                let (mut src_buf, mut dst_buf) = self.lock_pair(dst)?;
+                let avail_read = src_buf.available_read();
+                let n = len.min(avail_read).min(dst_buf.available_write());
+                if n > 0 {
+                    for i in 0..n {
+                        let byte = if consume {
+                            src_buf.read_byte()
+                        } else {
+                            src_buf.peek_byte(i)
+                        };
+                        dst_buf.write_byte(byte);
+                    }
+                    drop((src_buf, dst_buf));
+                    if consume {
+                        self.notifier.notify();
+                    }
+                    dst.notifier.notify();
+                    return Ok(n);
+                }
+                if len == 0 || (avail_read == 0 && self.write_end_close()) {
+                    return Ok(0);
+                }
+                avail_read
+            };
+            if nonblocking {
+                return Err(LinuxError::EAGAIN);
+            }
+            // wait for the data in this pipe, or the space in `dst`
+            if avail_read == 0 {
+                self.notifier.wait(src_generation);
+            } else {
+                dst.notifier.wait(dst_generation);
+            }
+        }
+    }
 }
 
 impl FileLike for Pipe {
@@ -114,26 +266,34 @@ impl FileLike for Pipe {
         let mut read_size = 0usize;
         let max_len = buf.len();
         loop {
+            let generation = self.notifier.generation();
             let mut ring_buffer = self.buffer.lock();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
-                if self.write_end_close() || read_size > 0
-                /* || non_block */
-                {
+                if self.write_end_close() || read_size > 0 {
                     return Ok(read_size);
                 }
+                if self.is_nonblocking() {
+                    return Err(LinuxError::EAGAIN);
+                }
                 drop(ring_buffer);
                 // Data not ready, wait for write end
-                crate::sys_sched_yield(); // TODO: use synconize primitive
+                self.notifier.wait(generation);
                 continue;
             }
-            for _ in 0..loop_read {
-                if read_size == max_len {
-                    return Ok(read_size);
-                }
+            let n = loop_read.min(max_len - read_size);
+            for _ in 0..n {
                 buf[read_size] = ring_buffer.read_byte();
                 read_size += 1;
             }
+            drop(ring_buffer);
+            if n > 0 {
+                // wake up the writers waiting for the space
+                self.notifier.notify();
+            }
+            if read_size == max_len {
+                return Ok(read_size);
+            }
         }
     }
 
@@ -144,21 +304,35 @@ impl FileLike for Pipe {
         let mut write_size = 0usize;
         let max_len = buf.len();
         loop {
+            let generation = self.notifier.generation();
             let mut ring_buffer = self.buffer.lock();
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
+                if self.is_nonblocking() {
+                    return if write_size > 0 {
+                        Ok(write_size)
+                    } else {
+                        Err(LinuxError::EAGAIN)
+                    };
+                }
                 drop(ring_buffer);
                 // Buffer is full, wait for read end to consume
-                crate::sys_sched_yield(); // TODO: use synconize primitive
+                self.notifier.wait(generation);
                 continue;
             }
-            for _ in 0..loop_write {
-                if write_size == max_len {
-                    return Ok(write_size);
-                }
+            let n = loop_write.min(max_len - write_size);
+            for _ in 0..n {
                 ring_buffer.write_byte(buf[write_size]);
                 write_size += 1;
             }
+            drop(ring_buffer);
+            if n > 0 {
+                // wake up the readers waiting for the data
+                self.notifier.notify();
+            }
+            if write_size == max_len {
+                return Ok(write_size);
+            }
         }
     }
 
@@ -175,6 +349,11 @@ impl FileLike for Pipe {
         })
     }
 
+    fn link_target(&self) -> String {
+        // both ends show the same pipe
+        format!("pipe:[{}]", Arc::as_ptr(&self.buffer) as usize)
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -187,9 +366,14 @@ impl FileLike for Pipe {
         })
     }
 
-    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
         Ok(())
     }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
 }
 
 /// Create a pipe
@@ -197,16 +381,37 @@ impl FileLike for Pipe {
 /// Return 0 if succeed
 pub fn sys_pipe(fds: &mut [c_int]) -> c_int {
     debug!("sys_pipe <= {:#x}", fds.as_ptr() as usize);
-    syscall_body!(sys_pipe, {
+    sys_pipe2(fds, 0)
+}
+
+/// Create a pipe, like [`sys_pipe`], with `flags` set on both of its ends.
+///
+/// Only `O_CLOEXEC` and `O_NONBLOCK` are supported, and `EINVAL` is returned
+/// for the other flags.
+pub fn sys_pipe2(fds: &mut [c_int], flags: c_int) -> c_int {
+    debug!("sys_pipe2 <= {:#x} {:#o}", fds.as_ptr() as usize, flags);
+    syscall_body!(sys_pipe2, {
+        let flags = flags as u32;
+        if flags & !(ctypes::O_CLOEXEC | ctypes::O_NONBLOCK) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
         if fds.len() != 2 {
             return Err(LinuxError::EFAULT);
         }
 
         let (read_end, write_end) = Pipe::new();
-        let read_fd = add_file_like(Arc::new(read_end))?;
-        let write_fd = add_file_like(Arc::new(write_end)).inspect_err(|_| {
-            close_file_like(read_fd).ok();
-        })?;
+        if flags & ctypes::O_NONBLOCK != 0 {
+            read_end.set_nonblocking(true)?;
+            write_end.set_nonblocking(true)?;
+        }
+        let cloexec = flags & ctypes::O_CLOEXEC != 0;
+        let read_fd = FD_TABLE.write().add(Arc::new(read_end), cloexec)?;
+        let write_fd = FD_TABLE
+            .write()
+            .add(Arc::new(write_end), cloexec)
+            .inspect_err(|_| {
+                close_file_like(read_fd).ok();
+            })?;
 
         fds[0] = read_fd as c_int;
         fds[1] = write_fd as c_int;
@@ -214,3 +419,222 @@ pub fn sys_pipe(fds: &mut [c_int]) -> c_int {
         Ok(0)
     })
 }
+
+/// The size of the bounce buffer used when splicing between a pipe and
+/// another file.
+const SPLICE_CHUNK_SIZE: usize = RING_BUFFER_SIZE;
+
+/// Reads from `file` at `*off` if `off` is not NULL, and advances `*off`.
+/// Otherwise, reads from the current file offset.
+fn read_file_at(
+    file: &Arc<dyn FileLike>,
+    off: *mut ctypes::off_t,
+    buf: &mut [u8],
+) -> LinuxResult<usize> {
+    if off.is_null() {
+        return file.read(buf);
+    }
+    #[cfg(feature = "fs")]
+    if let Some(f) = file.clone().into_any().downcast_ref::<crate::File>() {
+        let pos = get_user(off)?;
+        if pos < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let n = f.inner().lock().read_at(pos as u64, buf)?;
+        put_user(off, pos + n as ctypes::off_t)?;
+        return Ok(n);
+    }
+    Err(LinuxError::ESPIPE)
+}
+
+/// Writes to `file` at `*off` if `off` is not NULL, and advances `*off`.
+/// Otherwise, writes at the current file offset.
+fn write_file_at(
+    file: &Arc<dyn FileLike>,
+    off: *mut ctypes::off_t,
+    buf: &[u8],
+) -> LinuxResult<usize> {
+    if off.is_null() {
+        return file.write(buf);
+    }
+    #[cfg(feature = "fs")]
+    if let Some(f) = file.clone().into_any().downcast_ref::<crate::File>() {
+        let pos = get_user(off)?;
+        if pos < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let n = f.inner().lock().write_at(pos as u64, buf)?;
+        put_user(off, pos + n as ctypes::off_t)?;
+        return Ok(n);
+    }
+    Err(LinuxError::ESPIPE)
+}
+
+/// Splices data from a file into a pipe, without reading more than the free
+/// space in the pipe, so no data is lost.
+fn splice_file_to_pipe(
+    file: &Arc<dyn FileLike>,
+    off: *mut ctypes::off_t,
+    pipe: &Pipe,
+    len: usize,
+    nonblocking: bool,
+) -> LinuxResult<usize> {
+    if !pipe.writable() {
+        return Err(LinuxError::EBADF);
+    }
+    let space = loop {
+        let generation = pipe.notifier.generation();
+        let space = pipe.buffer.lock().available_write();
+        if space > 0 || len == 0 {
+            break space;
+        }
+        if nonblocking {
+            return Err(LinuxError::EAGAIN);
+        }
+        pipe.notifier.wait(generation);
+    };
+    let mut buf = [0u8; SPLICE_CHUNK_SIZE];
+    let n = read_file_at(file, off, &mut buf[..len.min(space)])?;
+    pipe.write(&buf[..n])
+}
+
+/// Splices data from a pipe into a file.
+fn splice_pipe_to_file(
+    pipe: &Pipe,
+    file: &Arc<dyn FileLike>,
+    off: *mut ctypes::off_t,
+    len: usize,
+    nonblocking: bool,
+) -> LinuxResult<usize> {
+    if !pipe.readable() {
+        return Err(LinuxError::EBADF);
+    }
+    if nonblocking && pipe.buffer.lock().available_read() == 0 && !pipe.write_end_close() {
+        return Err(LinuxError::EAGAIN);
+    }
+    let mut buf = [0u8; SPLICE_CHUNK_SIZE];
+    let n = pipe.read(&mut buf[..len.min(SPLICE_CHUNK_SIZE)])?;
+    write_file_at(file, off, &buf[..n])
+}
+
+/// Splice data to/from a pipe.
+///
+/// Moves up to `len` bytes between `fd_in` and `fd_out`, one of which must
+/// be a pipe. Between two pipes, data is moved directly from one ring buffer
+/// to another. The offset of a pipe end must be NULL.
+pub fn sys_splice(
+    fd_in: c_int,
+    off_in: *mut ctypes::off_t,
+    fd_out: c_int,
+    off_out: *mut ctypes::off_t,
+    len: usize,
+    flags: c_uint,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_splice <= fd_in: {}, fd_out: {}, len: {}, flags: {:#x}",
+        fd_in, fd_out, len, flags
+    );
+    syscall_body!(sys_splice, {
+        let nonblocking = flags & ctypes::SPLICE_F_NONBLOCK != 0;
+        let pipe_in = Pipe::from_fd(fd_in).ok();
+        let pipe_out = Pipe::from_fd(fd_out).ok();
+        if (pipe_in.is_some() && !off_in.is_null()) || (pipe_out.is_some() && !off_out.is_null()) {
+            return Err(LinuxError::ESPIPE);
+        }
+        let n = match (pipe_in, pipe_out) {
+            (Some(pipe_in), Some(pipe_out)) => {
+                pipe_in.transfer_to(&pipe_out, len, true, nonblocking)?
+            }
+            (Some(pipe_in), None) => {
+                let file_out = get_file_like(fd_out)?;
+                splice_pipe_to_file(&pipe_in, &file_out, off_out, len, nonblocking)?
+            }
+            (None, Some(pipe_out)) => {
+                let file_in = get_file_like(fd_in)?;
+                splice_file_to_pipe(&file_in, off_in, &pipe_out, len, nonblocking)?
+            }
+            (None, None) => {
+                get_file_like(fd_in)?;
+                get_file_like(fd_out)?;
+                return Err(LinuxError::EINVAL);
+            }
+        };
+        Ok(n as ctypes::ssize_t)
+    })
+}
+
+/// Duplicate pipe content.
+///
+/// Copies up to `len` bytes from the pipe `fd_in` to the pipe `fd_out`,
+/// without consuming them from `fd_in`.
+pub fn sys_tee(fd_in: c_int, fd_out: c_int, len: usize, flags: c_uint) -> ctypes::ssize_t {
+    debug!(
+        "sys_tee <= fd_in: {}, fd_out: {}, len: {}, flags: {:#x}",
+        fd_in, fd_out, len, flags
+    );
+    syscall_body!(sys_tee, {
+        let nonblocking = flags & ctypes::SPLICE_F_NONBLOCK != 0;
+        let pipe_in = Pipe::from_fd(fd_in)?;
+        let pipe_out = Pipe::from_fd(fd_out)?;
+        Ok(pipe_in.transfer_to(&pipe_out, len, false, nonblocking)? as ctypes::ssize_t)
+    })
+}
+
+/// Splice user pages to/from a pipe.
+///
+/// If `fd` is the write end of a pipe, the data in `iov` is written into
+/// the pipe. If it is the read end, data is read from the pipe into `iov`.
+pub fn sys_vmsplice(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    nr_segs: usize,
+    flags: c_uint,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_vmsplice <= fd: {}, nr_segs: {}, flags: {:#x}",
+        fd, nr_segs, flags
+    );
+    syscall_body!(sys_vmsplice, {
+        if nr_segs > 1024 {
+            return Err(LinuxError::EINVAL);
+        }
+        if iov.is_null() && nr_segs > 0 {
+            return Err(LinuxError::EFAULT);
+        }
+        let nonblocking = flags & ctypes::SPLICE_F_NONBLOCK != 0;
+        let pipe = Pipe::from_fd(fd).map_err(|_| LinuxError::EBADF)?;
+        let mut total = 0;
+        for i in 0..nr_segs {
+            let iov = get_user(iov.wrapping_add(i))?;
+            if iov.iov_len == 0 {
+                continue;
+            }
+            let n = if pipe.writable() {
+                if nonblocking && pipe.buffer.lock().available_write() == 0 {
+                    break;
+                }
+                with_user_buf(iov.iov_base as *const u8, iov.iov_len as usize, |src| {
+                    pipe.write(src)
+                })?
+            } else {
+                if nonblocking
+                    && pipe.buffer.lock().available_read() == 0
+                    && !pipe.write_end_close()
+                {
+                    break;
+                }
+                with_user_buf_mut(iov.iov_base as *mut u8, iov.iov_len as usize, |dst| {
+                    pipe.read(dst)
+                })?
+            };
+            total += n;
+            if n < iov.iov_len as usize {
+                break;
+            }
+        }
+        if total == 0 && nonblocking && nr_segs > 0 {
+            return Err(LinuxError::EAGAIN);
+        }
+        Ok(total as ctypes::ssize_t)
+    })
+}
@@ -0,0 +1,192 @@
+use core::ffi::{c_char, c_int, c_ulong};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+
+use crate::ctypes;
+use crate::uaccess::{copy_from_user, put_user, strnlen_user};
+
+/// The default persona of Linux.
+const PER_LINUX: c_ulong = 0;
+/// The mask of the persona type, other bits are flags.
+const PER_MASK: c_ulong = 0xff;
+/// Passed to `personality` to query the current persona without changing it.
+const PER_QUERY: c_ulong = 0xffff_ffff;
+
+/// The `SUID_DUMP_*` values accepted by `PR_SET_DUMPABLE`.
+const SUID_DUMP_DISABLE: c_ulong = 0;
+const SUID_DUMP_USER: c_ulong = 1;
+
+/// The `arch_prctl` codes to set or get the base of the `fs` segment.
+#[cfg(all(feature = "uspace", target_arch = "x86_64"))]
+const ARCH_SET_FS: c_int = 0x1002;
+#[cfg(all(feature = "uspace", target_arch = "x86_64"))]
+const ARCH_GET_FS: c_int = 0x1003;
+
+/// The end of the user space on x86_64, where the canonical addresses of the
+/// lower half end.
+#[cfg(all(feature = "uspace", target_arch = "x86_64"))]
+const USER_SPACE_END: usize = 0x0000_8000_0000_0000;
+
+static PERSONALITY: AtomicU32 = AtomicU32::new(PER_LINUX as u32);
+static DUMPABLE: AtomicBool = AtomicBool::new(true);
+static NO_NEW_PRIVS: AtomicBool = AtomicBool::new(false);
+
+#[cfg(not(feature = "multitask"))]
+static TASK_NAME: axsync::spin::SpinNoIrq<[u8; ctypes::PR_NAME_LEN as usize]> =
+    axsync::spin::SpinNoIrq::new(*b"main\0\0\0\0\0\0\0\0\0\0\0\0");
+
+fn set_task_name(name: &str) {
+    // Like Linux, silently truncate the name to fit `PR_NAME_LEN`.
+    let mut len = name.len().min(ctypes::PR_NAME_LEN as usize - 1);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    #[cfg(feature = "multitask")]
+    axtask::current().set_name(&name[..len]);
+    #[cfg(not(feature = "multitask"))]
+    {
+        let mut buf = TASK_NAME.lock();
+        buf.fill(0);
+        buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+    }
+}
+
+/// Copies the name at `src` for `PR_SET_NAME` into `buf`, silently truncated
+/// to fit `PR_NAME_LEN` like [`set_task_name`].
+fn read_task_name(
+    src: *const c_char,
+    buf: &mut [u8; ctypes::PR_NAME_LEN as usize],
+) -> LinuxResult<&str> {
+    let len = match strnlen_user(src, buf.len()) {
+        Err(LinuxError::ENAMETOOLONG) => buf.len() - 1,
+        res => res?,
+    };
+    copy_from_user(&mut buf[..len], src as *const u8)?;
+    match core::str::from_utf8(&buf[..len]) {
+        Ok(name) => Ok(name),
+        // The last character is cut by the truncation.
+        Err(e) if e.error_len().is_none() => {
+            Ok(core::str::from_utf8(&buf[..e.valid_up_to()]).unwrap())
+        }
+        Err(_) => Err(LinuxError::EINVAL),
+    }
+}
+
+fn get_task_name(buf: &mut [u8; ctypes::PR_NAME_LEN as usize]) {
+    #[cfg(feature = "multitask")]
+    {
+        let curr = axtask::current();
+        let name = curr.name().as_bytes();
+        let len = name.len().min(buf.len() - 1);
+        buf[..len].copy_from_slice(&name[..len]);
+        buf[len] = 0;
+    }
+    #[cfg(not(feature = "multitask"))]
+    buf.copy_from_slice(&*TASK_NAME.lock());
+}
+
+/// Operations on a process or thread.
+///
+/// Currently supported options:
+///
+/// - `PR_SET_NAME`/`PR_GET_NAME`: set or get the name of the calling thread.
+/// - `PR_SET_DUMPABLE`/`PR_GET_DUMPABLE`: set or get the "dumpable" flag.
+/// - `PR_SET_NO_NEW_PRIVS`/`PR_GET_NO_NEW_PRIVS`: set or get the
+///   "no_new_privs" flag. Once set, it can not be unset.
+pub fn sys_prctl(
+    option: c_int,
+    arg2: c_ulong,
+    arg3: c_ulong,
+    arg4: c_ulong,
+    arg5: c_ulong,
+) -> c_int {
+    debug!(
+        "sys_prctl <= option: {}, args: [{:#x}, {:#x}, {:#x}, {:#x}]",
+        option, arg2, arg3, arg4, arg5
+    );
+    syscall_body!(sys_prctl, {
+        match option as u32 {
+            ctypes::PR_SET_NAME => {
+                let mut buf = [0; ctypes::PR_NAME_LEN as usize];
+                set_task_name(read_task_name(arg2 as *const c_char, &mut buf)?);
+                Ok(0)
+            }
+            ctypes::PR_GET_NAME => {
+                let mut buf = [0; ctypes::PR_NAME_LEN as usize];
+                get_task_name(&mut buf);
+                put_user(arg2 as *mut [u8; ctypes::PR_NAME_LEN as usize], buf)?;
+                Ok(0)
+            }
+            ctypes::PR_SET_DUMPABLE => match arg2 {
+                SUID_DUMP_DISABLE | SUID_DUMP_USER => {
+                    DUMPABLE.store(arg2 == SUID_DUMP_USER, Ordering::Release);
+                    Ok(0)
+                }
+                _ => Err(LinuxError::EINVAL),
+            },
+            ctypes::PR_GET_DUMPABLE => Ok(DUMPABLE.load(Ordering::Acquire) as c_int),
+            ctypes::PR_SET_NO_NEW_PRIVS => {
+                if arg2 != 1 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                NO_NEW_PRIVS.store(true, Ordering::Release);
+                Ok(0)
+            }
+            ctypes::PR_GET_NO_NEW_PRIVS => {
+                if arg2 != 0 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                Ok(NO_NEW_PRIVS.load(Ordering::Acquire) as c_int)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
+
+/// Set the process execution domain.
+///
+/// Only the Linux persona (`PER_LINUX`, possibly with some flags) is
+/// accepted. Returns the previous persona, or the current one if `persona` is
+/// `0xffffffff`.
+pub fn sys_personality(persona: c_ulong) -> c_int {
+    debug!("sys_personality <= {:#x}", persona);
+    syscall_body!(sys_personality, {
+        if persona == PER_QUERY {
+            return Ok(PERSONALITY.load(Ordering::Acquire) as c_int);
+        }
+        if persona & PER_MASK != PER_LINUX || persona > u32::MAX as c_ulong {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(PERSONALITY.swap(persona as u32, Ordering::AcqRel) as c_int)
+    })
+}
+
+/// Set or get the architecture-specific thread state of the calling user
+/// thread, with the trap frame `tf` of the syscall.
+///
+/// Only the base of the `fs` segment, i.e. the thread pointer of the user
+/// TLS, is supported: `ARCH_SET_FS` sets it to `addr`, and `ARCH_GET_FS`
+/// stores it to the `unsigned long` at `addr`. It is saved in the trap frame,
+/// and loaded when returning to the user space. The `gs` segment is used by
+/// the kernel, and the other codes fail with `EINVAL`.
+#[cfg(all(feature = "uspace", target_arch = "x86_64"))]
+pub fn sys_arch_prctl(tf: &mut axhal::arch::TrapFrame, code: c_int, addr: usize) -> c_int {
+    debug!("sys_arch_prctl <= code: {:#x}, addr: {:#x}", code, addr);
+    syscall_body!(sys_arch_prctl, {
+        match code {
+            ARCH_SET_FS => {
+                if addr >= USER_SPACE_END {
+                    return Err(LinuxError::EPERM);
+                }
+                tf.set_tls(addr);
+                Ok(0)
+            }
+            ARCH_GET_FS => {
+                put_user(addr as *mut c_ulong, tf.tls() as c_ulong)?;
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
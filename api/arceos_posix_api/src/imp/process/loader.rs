@@ -0,0 +1,341 @@
+//! Loading static ELF executables into user address spaces.
+
+use alloc::{string::String, vec, vec::Vec};
+use core::mem::size_of;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::paging::MappingFlags;
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, VirtAddr, va};
+
+/// The load address of position-independent executables (`ET_DYN`).
+const PIE_BASE: usize = 0x1000_0000;
+
+/// The base address of user address spaces.
+pub const USER_SPACE_BASE: usize = 0x1000;
+/// The size of user address spaces.
+pub const USER_SPACE_SIZE: usize = 0x3f_ffff_f000 - USER_SPACE_BASE;
+
+/// The top of the user stack, at the end of the user address space.
+const USER_STACK_TOP: usize = USER_SPACE_BASE + USER_SPACE_SIZE;
+/// The default size of the user stack, if `RLIMIT_STACK` is unlimited.
+pub const USER_STACK_SIZE: usize = 0x4_0000;
+
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+
+const R_NONE: u32 = 0;
+#[cfg(target_arch = "x86_64")]
+const R_RELATIVE: u32 = 8;
+#[cfg(target_arch = "aarch64")]
+const R_RELATIVE: u32 = 1027;
+#[cfg(any(
+    target_arch = "riscv32",
+    target_arch = "riscv64",
+    target_arch = "loongarch64"
+))]
+const R_RELATIVE: u32 = 3;
+
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_ENTRY: usize = 9;
+const AT_RANDOM: usize = 25;
+
+#[cfg(target_arch = "x86_64")]
+const EM_CURRENT: u16 = 62;
+#[cfg(target_arch = "aarch64")]
+const EM_CURRENT: u16 = 183;
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+const EM_CURRENT: u16 = 243;
+#[cfg(target_arch = "loongarch64")]
+const EM_CURRENT: u16 = 258;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Dyn {
+    d_tag: u64,
+    d_val: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Rela {
+    r_offset: u64,
+    r_info: u64,
+    r_addend: i64,
+}
+
+/// Reads a `T` at `offset` of `data`, returns `ENOEXEC` if out of bounds.
+fn read_at<T: Copy>(data: &[u8], offset: usize) -> LinuxResult<T> {
+    match offset.checked_add(size_of::<T>()) {
+        Some(end) if end <= data.len() => {
+            Ok(unsafe { core::ptr::read_unaligned(data.as_ptr().add(offset) as *const T) })
+        }
+        _ => Err(LinuxError::ENOEXEC),
+    }
+}
+
+fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if p_flags & PF_W != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if p_flags & PF_X != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// Applies the relocations of a position-independent executable loaded at
+/// `base`, with the dynamic section `dynamic`.
+///
+/// Only relative relocations are supported, which are the only ones in static
+/// PIEs. Applying them is idempotent, so it is fine if the program relocates
+/// itself again.
+fn relocate(aspace: &mut AddrSpace, data: &[u8], dynamic: &Elf64Phdr, base: usize) -> LinuxResult {
+    let (mut rela, mut relasz, mut relaent) = (0, 0, size_of::<Elf64Rela>());
+    let offset = dynamic.p_offset as usize;
+    for i in 0..dynamic.p_filesz as usize / size_of::<Elf64Dyn>() {
+        let dyn_entry: Elf64Dyn = read_at(data, offset + i * size_of::<Elf64Dyn>())?;
+        match dyn_entry.d_tag {
+            DT_NULL => break,
+            DT_RELA => rela = dyn_entry.d_val as usize,
+            DT_RELASZ => relasz = dyn_entry.d_val as usize,
+            DT_RELAENT => relaent = dyn_entry.d_val as usize,
+            _ => {}
+        }
+    }
+    if relasz == 0 {
+        return Ok(());
+    }
+    if relaent != size_of::<Elf64Rela>() {
+        return Err(LinuxError::ENOEXEC);
+    }
+
+    let mut table = vec![0; relasz];
+    aspace.read(va!(base + rela), &mut table)?;
+    for i in 0..relasz / relaent {
+        let entry: Elf64Rela = read_at(&table, i * relaent)?;
+        match entry.r_info as u32 {
+            R_NONE => {}
+            R_RELATIVE => {
+                let value = base.wrapping_add_signed(entry.r_addend as isize);
+                aspace.write(va!(base + entry.r_offset as usize), &value.to_ne_bytes())?;
+            }
+            ty => {
+                warn!("load_elf: unsupported relocation type {}", ty);
+                return Err(LinuxError::ENOEXEC);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Information of a loaded ELF image, passed to the program in the auxiliary
+/// vector.
+pub struct ElfImage {
+    /// The entry point.
+    pub entry: usize,
+    /// The address of the program headers in memory.
+    pub phdr: usize,
+    /// The number of program headers.
+    pub phnum: usize,
+}
+
+/// Maps the segments of the ELF executable `data` into `aspace`.
+///
+/// Only statically linked executables are supported, position-independent
+/// ones (static PIEs) are loaded at [`PIE_BASE`] and relocated. The BSS is
+/// zeroed, as the segments are mapped to zeroed frames.
+pub fn load_elf(aspace: &mut AddrSpace, data: &[u8]) -> LinuxResult<ElfImage> {
+    let ehdr: Elf64Ehdr = read_at(data, 0)?;
+    // 64-bit, little-endian.
+    if ehdr.e_ident[..6] != *b"\x7fELF\x02\x01" || ehdr.e_machine != EM_CURRENT {
+        return Err(LinuxError::ENOEXEC);
+    }
+    let base = match ehdr.e_type {
+        ET_EXEC => 0,
+        ET_DYN => PIE_BASE,
+        _ => return Err(LinuxError::ENOEXEC),
+    };
+    if ehdr.e_phentsize as usize != size_of::<Elf64Phdr>() {
+        return Err(LinuxError::ENOEXEC);
+    }
+
+    let phdrs = (0..ehdr.e_phnum as usize)
+        .map(|i| read_at::<Elf64Phdr>(data, ehdr.e_phoff as usize + i * size_of::<Elf64Phdr>()))
+        .collect::<LinuxResult<Vec<_>>>()?;
+    if phdrs.iter().any(|ph| ph.p_type == PT_INTERP) {
+        warn!("load_elf: dynamically linked executables are not supported");
+        return Err(LinuxError::ENOEXEC);
+    }
+
+    let mut phdr = 0;
+    // Segments may share a page at the boundary, which is mapped once.
+    let mut mapped_end = va!(0);
+    for ph in phdrs.iter().filter(|ph| ph.p_type == PT_LOAD) {
+        let (offset, filesz) = (ph.p_offset as usize, ph.p_filesz as usize);
+        if filesz > ph.p_memsz as usize || offset.saturating_add(filesz) > data.len() {
+            return Err(LinuxError::ENOEXEC);
+        }
+        let vaddr = va!(base + ph.p_vaddr as usize);
+        let start = vaddr.align_down_4k().max(mapped_end);
+        let end = (vaddr + ph.p_memsz as usize).align_up_4k();
+        if start < end {
+            aspace.map_alloc(start, end - start, segment_flags(ph.p_flags), true)?;
+            mapped_end = end;
+        }
+        aspace.write(vaddr, &data[offset..offset + filesz])?;
+
+        let phoff = ehdr.e_phoff as usize;
+        if (offset..offset + filesz).contains(&phoff) {
+            phdr = vaddr.as_usize() + phoff - offset;
+        }
+    }
+
+    if ehdr.e_type == ET_DYN {
+        if let Some(dynamic) = phdrs.iter().find(|ph| ph.p_type == PT_DYNAMIC) {
+            relocate(aspace, data, dynamic, base)?;
+        }
+    }
+
+    Ok(ElfImage {
+        entry: base + ehdr.e_entry as usize,
+        phdr,
+        phnum: phdrs.len(),
+    })
+}
+
+/// Maps the user stack of `stack_size` bytes into `aspace`, and pushes the
+/// arguments, environment variables and the auxiliary vector on it.
+///
+/// Returns the initial stack pointer, which points to `argc`.
+pub fn init_stack(
+    aspace: &mut AddrSpace,
+    image: &ElfImage,
+    stack_size: usize,
+    args: &[String],
+    envs: &[String],
+) -> LinuxResult<VirtAddr> {
+    let top = va!(USER_STACK_TOP);
+    if stack_size > USER_SPACE_SIZE {
+        return Err(LinuxError::ENOMEM);
+    }
+    aspace.map_alloc(
+        top - stack_size,
+        stack_size,
+        MappingFlags::USER | MappingFlags::READ | MappingFlags::WRITE,
+        true,
+    )?;
+
+    let mut sp = top.as_usize();
+    let mut push = |bytes: &[u8]| -> LinuxResult<usize> {
+        sp -= bytes.len();
+        aspace.write(va!(sp), bytes)?;
+        Ok(sp)
+    };
+    let mut push_str = |s: &str| -> LinuxResult<usize> {
+        push(&[0])?;
+        push(s.as_bytes())
+    };
+    let env_ptrs = envs
+        .iter()
+        .map(|s| push_str(s))
+        .collect::<LinuxResult<Vec<_>>>()?;
+    let arg_ptrs = args
+        .iter()
+        .map(|s| push_str(s))
+        .collect::<LinuxResult<Vec<_>>>()?;
+
+    // There is no entropy source, use the boot time as the random bytes.
+    let now = axhal::time::monotonic_time_nanos();
+    let mut random = [0; 16];
+    random[..8].copy_from_slice(&now.to_ne_bytes());
+    random[8..].copy_from_slice(&now.rotate_left(32).to_ne_bytes());
+    let random_ptr = push(&random)?;
+
+    let mut words = Vec::new();
+    words.push(args.len());
+    words.extend(&arg_ptrs);
+    words.push(0);
+    words.extend(&env_ptrs);
+    words.push(0);
+    words.extend([
+        AT_PHDR,
+        image.phdr,
+        AT_PHENT,
+        size_of::<Elf64Phdr>(),
+        AT_PHNUM,
+        image.phnum,
+        AT_PAGESZ,
+        memory_addr::PAGE_SIZE_4K,
+        AT_ENTRY,
+        image.entry,
+        AT_RANDOM,
+        random_ptr,
+        AT_NULL,
+        0,
+    ]);
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_ne_bytes()).collect();
+
+    // `argc` must be 16-byte aligned.
+    let sp = (random_ptr - bytes.len()) & !0xf;
+    if sp < top.as_usize() - stack_size {
+        return Err(LinuxError::E2BIG);
+    }
+    aspace.write(va!(sp), &bytes)?;
+    Ok(va!(sp))
+}
@@ -0,0 +1,1330 @@
+//! User processes.
+//!
+//! A process is a group of user tasks (threads) sharing an address space and
+//! a namespace of resources (e.g., the file descriptor table). A process is
+//! created by [`sys_clone`] (or [`sys_clone3`], [`sys_fork`]) from an existing
+//! one, by [`sys_posix_spawn`] from a program, or by [`spawn_process`] from a
+//! kernel task. The process ID is the task ID of its first thread.
+//!
+//! When the last thread of a process exits, the process becomes a zombie
+//! until its parent waits for it by [`sys_wait4`], and the exit signal (e.g.,
+//! `SIGCHLD`) is sent to the parent. Processes spawned from kernel tasks have
+//! no parent, and are reaped on exit, as are orphaned processes.
+
+#[cfg(feature = "fs")]
+mod loader;
+
+#[cfg(feature = "fs")]
+use alloc::string::String;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+#[cfg(feature = "fs")]
+use core::ffi::{c_char, c_void};
+use core::ffi::{c_int, c_ulong};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::{TrapFrame, UspaceContext};
+use axns::{AxNamespace, AxNamespaceIf, ResArc};
+use axsync::{Completion, Mutex, MutexGuard, spin::SpinNoIrq};
+use axtask::{AxTaskRef, TaskInner, WaitQueue};
+use memory_addr::va;
+use spin::RwLock;
+
+use super::fd_ops::{FD_TABLE, FdTable};
+use super::futex::{FUTEX_BITSET_MATCH_ANY, futex_wake};
+use super::resources::{ResourceLimits, current_limits};
+use crate::ctypes;
+#[cfg(feature = "fs")]
+use crate::uaccess::strncpy_from_user;
+use crate::uaccess::{copy_from_user, get_user, put_user};
+
+/// The exit signal sent to the parent, in the lowest byte of the flags.
+const CSIGNAL: c_ulong = 0xff;
+/// Share the address space.
+const CLONE_VM: c_ulong = 0x100;
+/// Share the file system information.
+const CLONE_FS: c_ulong = 0x200;
+/// Share the file descriptor table.
+const CLONE_FILES: c_ulong = 0x400;
+/// Share the signal handlers.
+const CLONE_SIGHAND: c_ulong = 0x800;
+/// Suspend the parent until the child execs or exits.
+const CLONE_VFORK: c_ulong = 0x4000;
+/// Create a thread in the same process.
+const CLONE_THREAD: c_ulong = 0x10000;
+/// Set the thread pointer of the child.
+const CLONE_SETTLS: c_ulong = 0x80000;
+/// Store the child's thread ID at `ptid` in the parent's memory.
+const CLONE_PARENT_SETTID: c_ulong = 0x100000;
+/// Clear the child's thread ID at `ctid` and wake up the futex on exit.
+const CLONE_CHILD_CLEARTID: c_ulong = 0x200000;
+/// Store the child's thread ID at `ctid` in the child's memory.
+const CLONE_CHILD_SETTID: c_ulong = 0x1000000;
+
+const CLONE_SUPPORTED: c_ulong = CSIGNAL
+    | CLONE_VM
+    | CLONE_FS
+    | CLONE_FILES
+    | CLONE_SIGHAND
+    | CLONE_VFORK
+    | CLONE_THREAD
+    | CLONE_SETTLS
+    | CLONE_PARENT_SETTID
+    | CLONE_CHILD_CLEARTID
+    | CLONE_CHILD_SETTID;
+
+/// The size of the first version of `struct clone_args`.
+const CLONE_ARGS_SIZE_VER0: usize = 64;
+
+/// Return immediately if no child has exited.
+const WNOHANG: c_int = 1;
+/// Also report stopped children. Ignored, as stopping is not supported.
+const WUNTRACED: c_int = 2;
+/// Also report continued children. Ignored, as stopping is not supported.
+const WCONTINUED: c_int = 8;
+
+/// The maximum length of a path, with the terminating NUL.
+#[cfg(feature = "fs")]
+const PATH_MAX: usize = 4096;
+/// The maximum length of an argument or environment string of `execve`,
+/// with the terminating NUL.
+#[cfg(feature = "fs")]
+const MAX_ARG_STRLEN: usize = 32 * memory_addr::PAGE_SIZE_4K;
+
+/// The `si_code` of the exit signal, for a child that has exited.
+#[cfg(feature = "signal")]
+const CLD_EXITED: c_int = 1;
+
+/// A user address space, which shares the kernel portion of the page table.
+pub(crate) struct UserAspace(Mutex<axmm::AddrSpace>);
+
+impl UserAspace {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, axmm::AddrSpace> {
+        self.0.lock()
+    }
+}
+
+impl Drop for UserAspace {
+    fn drop(&mut self) {
+        let aspace = self.0.get_mut();
+        crate::imp::mmap::release_mappings(aspace);
+        axmm::clear_kernel_mappings(aspace);
+    }
+}
+
+/// The namespace of a process, where the resources owned by the process are
+/// dropped with it.
+struct ProcessNamespace(AxNamespace);
+
+impl ProcessNamespace {
+    /// Creates a namespace with the given file descriptor table, and the
+    /// current working directory shared with the current task if `share_fs`
+    /// is true, or a copy of it.
+    fn new(files: Arc<RwLock<FdTable>>, share_fs: bool) -> Self {
+        let ns = AxNamespace::new_thread_local();
+        // The resources are bitwise copies of the global ones, which are
+        // overwritten without being dropped.
+        unsafe {
+            init_resource(FD_TABLE::as_ptr(FD_TABLE.deref_from(&ns)), files);
+            #[cfg(feature = "fs")]
+            {
+                use axfs::{CURRENT_DIR, CURRENT_DIR_PATH};
+                let (dir, path) = if share_fs {
+                    (CURRENT_DIR.share(), CURRENT_DIR_PATH.share())
+                } else {
+                    (
+                        Arc::new(CURRENT_DIR.copy_inner()),
+                        Arc::new(CURRENT_DIR_PATH.copy_inner()),
+                    )
+                };
+                init_resource(CURRENT_DIR::as_ptr(CURRENT_DIR.deref_from(&ns)), dir);
+                init_resource(
+                    CURRENT_DIR_PATH::as_ptr(CURRENT_DIR_PATH.deref_from(&ns)),
+                    path,
+                );
+            }
+        }
+        Self(ns)
+    }
+}
+
+impl Drop for ProcessNamespace {
+    fn drop(&mut self) {
+        use core::ptr::drop_in_place;
+        let ns = &self.0;
+        unsafe {
+            drop_in_place(FD_TABLE::as_ptr(FD_TABLE.deref_from(ns)));
+            #[cfg(feature = "fs")]
+            {
+                use axfs::{CURRENT_DIR, CURRENT_DIR_PATH};
+                drop_in_place(CURRENT_DIR::as_ptr(CURRENT_DIR.deref_from(ns)));
+                drop_in_place(CURRENT_DIR_PATH::as_ptr(CURRENT_DIR_PATH.deref_from(ns)));
+            }
+        }
+    }
+}
+
+/// Overwrites the resource at `res` with a new one initialized with `data`.
+unsafe fn init_resource<T>(res: *mut ResArc<T>, data: Arc<T>) {
+    let new_res = ResArc::new();
+    new_res.init_shared(data);
+    unsafe { res.write(new_res) };
+}
+
+pub(crate) struct Process {
+    pid: u64,
+    /// The address space, which is replaced by `execve` if it is shared with
+    /// other processes.
+    aspace: Mutex<Arc<UserAspace>>,
+    /// The program break, or 0 before the first `brk` or `sbrk` call or after
+    /// `execve`. It is copied by `fork`.
+    pub(crate) brk: Mutex<usize>,
+    ns: ProcessNamespace,
+    /// The resource limits, inherited from the creator of the process.
+    rlimits: Arc<SpinNoIrq<ResourceLimits>>,
+    /// The commands registered by `membarrier`, as a bitmask of the
+    /// `MEMBARRIER_CMD_REGISTER_*`. They are not inherited, and are cleared by
+    /// `execve`.
+    pub(crate) membarrier_cmds: AtomicU32,
+    /// The number of threads that have been spawned and not exited, the
+    /// process exits with its last thread.
+    live_threads: AtomicUsize,
+}
+
+struct Thread {
+    process: Arc<Process>,
+    /// The address to clear on exit, set by `CLONE_CHILD_CLEARTID` or
+    /// [`sys_set_tid_address`].
+    clear_child_tid: AtomicUsize,
+    /// Completed on exec or exit, to resume the parent suspended by
+    /// `CLONE_VFORK`.
+    vfork_done: Option<Arc<Completion>>,
+}
+
+lazy_static::lazy_static! {
+    /// The user tasks, indexed by task ID.
+    static ref THREADS: RwLock<BTreeMap<u64, Arc<Thread>>> = RwLock::new(BTreeMap::new());
+}
+
+/// The bookkeeping of a process for its parent to wait for it.
+struct ProcessEntry {
+    /// The parent process ID, or 0 if there is no parent to wait for it.
+    ppid: u64,
+    /// The signal sent to the parent on exit, or 0 for none.
+    exit_signal: c_int,
+    /// The exit code, set when the process has exited (is a zombie).
+    exit_code: Option<c_int>,
+}
+
+/// All child processes, including zombies, indexed by process ID.
+static PROCESS_TABLE: SpinNoIrq<BTreeMap<u64, ProcessEntry>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Parents waiting for their children to exit.
+static CHILD_EXIT_WQ: WaitQueue = WaitQueue::new();
+
+fn current_thread() -> Option<Arc<Thread>> {
+    let curr = axtask::current_may_uninit()?;
+    THREADS.read().get(&curr.id().as_u64()).cloned()
+}
+
+/// Returns the process ID of the current task, if it is a user task.
+pub(crate) fn current_pid() -> Option<u64> {
+    current_thread().map(|thread| thread.process.pid)
+}
+
+/// Returns the current process, if the current task is a user task.
+pub(crate) fn current_process() -> Option<Arc<Process>> {
+    current_thread().map(|thread| thread.process.clone())
+}
+
+/// Returns the address space of the current process, if the current task is
+/// a user task.
+pub(crate) fn current_aspace() -> Option<Arc<UserAspace>> {
+    Some(current_thread()?.process.aspace.lock().clone())
+}
+
+/// Calls `f` with the address space of the current process, or returns
+/// `None` if the current task is not a user task.
+pub(crate) fn with_current_aspace<R>(f: impl FnOnce(&mut axmm::AddrSpace) -> R) -> Option<R> {
+    Some(f(&mut current_aspace()?.lock()))
+}
+
+/// Returns the resource limits of the user process `pid`, or of the current
+/// process if `pid` is 0 and the current task is a user task.
+pub(crate) fn process_rlimits(pid: u64) -> Option<Arc<SpinNoIrq<ResourceLimits>>> {
+    let thread = if pid == 0 {
+        current_thread()?
+    } else {
+        THREADS
+            .read()
+            .values()
+            .find(|t| t.process.pid == pid)
+            .cloned()?
+    };
+    Some(thread.process.rlimits.clone())
+}
+
+struct AxNamespaceIfImpl;
+
+#[crate_interface::impl_interface]
+impl AxNamespaceIf for AxNamespaceIfImpl {
+    fn current_namespace_base() -> *mut u8 {
+        // Kernel tasks use the global namespace.
+        let Some(curr) = axtask::current_may_uninit() else {
+            return AxNamespace::global().base();
+        };
+        match THREADS.read().get(&curr.id().as_u64()) {
+            Some(thread) => thread.process.ns.0.base(),
+            None => AxNamespace::global().base(),
+        }
+    }
+}
+
+/// Creates a task entering user space with `uctx` in the address space.
+fn new_user_task(name: &str, uctx: UspaceContext, aspace: &UserAspace) -> LinuxResult<TaskInner> {
+    new_user_task_with(name, uctx, aspace, || {})
+}
+
+/// Creates a task entering user space with `uctx` in the address space,
+/// after running `init` in the kernel.
+///
+/// Returns `EAGAIN` if all task IDs are in use.
+fn new_user_task_with<F>(
+    name: &str,
+    uctx: UspaceContext,
+    aspace: &UserAspace,
+    init: F,
+) -> LinuxResult<TaskInner>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let mut task = TaskInner::try_new(
+        move || {
+            init();
+            let kstack_top = axtask::current().get_kernel_stack_top().unwrap();
+            unsafe { uctx.enter_uspace(va!(kstack_top)) }
+        },
+        name.into(),
+        axconfig::TASK_STACK_SIZE,
+    )
+    .ok_or(LinuxError::EAGAIN)?;
+    task.ctx_mut()
+        .set_page_table_root(aspace.0.lock().page_table_root());
+    Ok(task)
+}
+
+/// Inserts the process `pid` to `PROCESS_TABLE`, which keeps its ID until
+/// it is reaped by [`reap_process`].
+fn add_process(task: &TaskInner, entry: ProcessEntry) {
+    task.keep_id_until_reaped();
+    PROCESS_TABLE.lock().insert(task.id().as_u64(), entry);
+}
+
+/// Removes the exited process `pid` from `PROCESS_TABLE`, and releases its ID.
+fn reap_process(table: &mut BTreeMap<u64, ProcessEntry>, pid: u64) -> Option<ProcessEntry> {
+    let entry = table.remove(&pid)?;
+    axtask::init_pid_ns().free_id(pid);
+    Some(entry)
+}
+
+fn spawn_user_task(task: TaskInner, thread: Thread) -> AxTaskRef {
+    thread.process.live_threads.fetch_add(1, Ordering::AcqRel);
+    THREADS.write().insert(task.id().as_u64(), Arc::new(thread));
+    axtask::spawn_task(task)
+}
+
+/// Records the exit of the process `pid` in `table`, where it becomes a
+/// zombie if its parent may wait for it, or is reaped otherwise.
+///
+/// Returns the parent ID and the exit signal to notify the parent with, or
+/// `None` if there is no parent.
+fn record_exit(
+    table: &mut BTreeMap<u64, ProcessEntry>,
+    pid: u64,
+    exit_code: c_int,
+) -> Option<(u64, c_int)> {
+    // Nobody will wait for the children now, reap the zombies among them.
+    let zombies: Vec<u64> = table
+        .iter_mut()
+        .filter(|(_, p)| p.ppid == pid)
+        .filter_map(|(&cpid, p)| {
+            p.ppid = 0;
+            p.exit_code.is_some().then_some(cpid)
+        })
+        .collect();
+    for cpid in zombies {
+        reap_process(table, cpid);
+    }
+    let entry = table.get_mut(&pid)?;
+    let (ppid, exit_signal) = (entry.ppid, entry.exit_signal);
+    if ppid == 0 {
+        reap_process(table, pid);
+        return None;
+    }
+    entry.exit_code = Some(exit_code);
+    Some((ppid, exit_signal))
+}
+
+/// Records the exit of the process `pid`, and notifies its parent.
+fn process_exited(pid: u64, exit_code: c_int) {
+    let Some((ppid, exit_signal)) = record_exit(&mut PROCESS_TABLE.lock(), pid, exit_code) else {
+        return;
+    };
+
+    #[cfg(feature = "signal")]
+    if exit_signal != 0 {
+        // Send to any thread of the parent, as its first thread may have exited.
+        let parent_tid = THREADS
+            .read()
+            .iter()
+            .find(|(_, t)| t.process.pid == ppid)
+            .map(|(&tid, _)| tid);
+        if let Some(tid) = parent_tid {
+            let _ = super::signal::send_signal_to_task(
+                tid,
+                exit_signal,
+                CLD_EXITED,
+                exit_code as usize,
+            );
+        }
+    }
+    #[cfg(not(feature = "signal"))]
+    let _ = exit_signal;
+    CHILD_EXIT_WQ.notify_all(false);
+}
+
+/// Cleans up the current task if it is a user task, called on exit.
+///
+/// If it is the last thread of the process, the process exits with
+/// `exit_code`.
+pub(crate) fn exit_current(exit_code: c_int) {
+    let tid = axtask::current().id().as_u64();
+    // Cleared while the thread is still found as the current one, which
+    // writes through its address space.
+    if let Some(thread) = current_thread() {
+        let clear_child_tid = thread.clear_child_tid.load(Ordering::Acquire);
+        if clear_child_tid != 0 {
+            let addr = clear_child_tid as *mut u32;
+            // Like Linux, a bad address is ignored.
+            let _ = put_user(addr, 0);
+            let _ = futex_wake(addr, false, 1, FUTEX_BITSET_MATCH_ANY);
+        }
+    }
+    let Some(thread) = THREADS.write().remove(&tid) else {
+        return;
+    };
+    #[cfg(feature = "signal")]
+    super::signal::set_thread_sighand(tid, None);
+    if let Some(done) = &thread.vfork_done {
+        done.complete_all();
+    }
+
+    let process = thread.process.clone();
+    drop(thread);
+    if process.live_threads.fetch_sub(1, Ordering::AcqRel) != 1 {
+        return;
+    }
+    // The last thread, switch away from the page table of the process, as
+    // the address space may be freed with the process.
+    #[cfg(not(any(target_arch = "aarch64", target_arch = "loongarch64")))]
+    unsafe {
+        axhal::arch::write_page_table_root(axmm::kernel_page_table_root())
+    };
+    process_exited(process.pid, exit_code);
+}
+
+/// The arguments to create a child, shared by [`sys_clone`] and
+/// [`sys_clone3`].
+struct CloneArgs {
+    flags: c_ulong,
+    /// The signal sent to the parent when the child process exits.
+    exit_signal: c_int,
+    /// The stack pointer of the child, or 0 to use the one of the parent.
+    stack: usize,
+    tls: usize,
+    ptid: *mut c_int,
+    ctid: *mut c_int,
+}
+
+/// Creates a child task sharing the resources selected by `args.flags` with
+/// the current one, and returns its task ID.
+fn clone_task(tf: &TrapFrame, args: CloneArgs) -> LinuxResult<c_int> {
+    let flags = args.flags;
+    let curr = current_thread().ok_or(LinuxError::EPERM)?;
+    if flags & CLONE_SIGHAND != 0 && flags & CLONE_VM == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    // The file descriptor table is in the namespace of the process, so it is
+    // always shared by the threads.
+    if flags & CLONE_THREAD != 0
+        && flags & (CLONE_SIGHAND | CLONE_FILES) != CLONE_SIGHAND | CLONE_FILES
+    {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let mut child_tf = *tf;
+    child_tf.set_retval(0);
+    if args.stack != 0 {
+        child_tf.set_sp(args.stack);
+    }
+    if flags & CLONE_SETTLS != 0 {
+        child_tf.set_tls(args.tls);
+    }
+
+    let aspace = curr.process.aspace.lock().clone();
+    let aspace = if flags & CLONE_VM != 0 {
+        aspace
+    } else {
+        let mut parent = aspace.lock();
+        let mut aspace = parent.clone_or_err()?;
+        axmm::copy_kernel_mappings(&mut aspace)?;
+        crate::imp::mmap::fork_mappings(&parent, &aspace);
+        Arc::new(UserAspace(Mutex::new(aspace)))
+    };
+    let task = new_user_task(
+        axtask::current().name(),
+        UspaceContext::from(&child_tf),
+        &aspace,
+    )?;
+    let tid = task.id().as_u64();
+
+    if flags & CLONE_CHILD_SETTID != 0 {
+        let bytes = (tid as c_int).to_ne_bytes();
+        aspace.0.lock().write(va!(args.ctid as usize), &bytes)?;
+    }
+    if flags & CLONE_PARENT_SETTID != 0 {
+        put_user(args.ptid, tid as c_int)?;
+    }
+
+    let process = if flags & CLONE_THREAD != 0 {
+        curr.process.clone()
+    } else {
+        let files = if flags & CLONE_FILES != 0 {
+            FD_TABLE.share()
+        } else {
+            Arc::new(FD_TABLE.copy_inner())
+        };
+        add_process(
+            &task,
+            ProcessEntry {
+                ppid: curr.process.pid,
+                exit_signal: args.exit_signal,
+                exit_code: None,
+            },
+        );
+        Arc::new(Process {
+            pid: tid,
+            aspace: Mutex::new(aspace),
+            brk: Mutex::new(*curr.process.brk.lock()),
+            ns: ProcessNamespace::new(files, flags & CLONE_FS != 0),
+            rlimits: Arc::new(SpinNoIrq::new(current_limits())),
+            membarrier_cmds: AtomicU32::new(0),
+            live_threads: AtomicUsize::new(0),
+        })
+    };
+    let clear_child_tid = if flags & CLONE_CHILD_CLEARTID != 0 {
+        args.ctid as usize
+    } else {
+        0
+    };
+    let vfork_done = (flags & CLONE_VFORK != 0).then(|| Arc::new(Completion::new()));
+    #[cfg(feature = "signal")]
+    super::signal::set_thread_sighand(
+        tid,
+        Some(super::signal::clone_sighand(flags & CLONE_SIGHAND != 0)),
+    );
+    spawn_user_task(
+        task,
+        Thread {
+            process,
+            clear_child_tid: AtomicUsize::new(clear_child_tid),
+            vfork_done: vfork_done.clone(),
+        },
+    );
+    drop(curr);
+    if let Some(done) = vfork_done {
+        done.wait();
+    }
+    Ok(tid as c_int)
+}
+
+/// Create a child process or thread.
+///
+/// The child starts from the return of the syscall with the trap frame `tf`
+/// of the caller, with the return value set to 0, and the stack pointer set
+/// to `newsp` if it is not 0. The arguments are in the order of most
+/// architectures, callers on x86_64 should swap `tls` and `ctid`.
+///
+/// Each resource is shared with the child by its flag, or copied:
+///
+/// - `CLONE_VM`: the address space.
+/// - `CLONE_FILES`: the file descriptor table.
+/// - `CLONE_FS`: the current working directory.
+/// - `CLONE_SIGHAND`: the signal handlers, which requires `CLONE_VM`.
+///
+/// `CLONE_THREAD` creates a thread in the same process, which shares all the
+/// resources above, and requires `CLONE_SIGHAND` and `CLONE_FILES`.
+/// `CLONE_VFORK` suspends the parent until the child calls [`sys_execve`] or
+/// exits, so the child can borrow the address space with `CLONE_VM` instead
+/// of copying it. The exit signal in the lowest byte of `flags` is sent to
+/// the parent when the child process exits.
+///
+/// Return the task ID of the child.
+pub fn sys_clone(
+    tf: &TrapFrame,
+    flags: c_ulong,
+    newsp: usize,
+    ptid: *mut c_int,
+    tls: usize,
+    ctid: *mut c_int,
+) -> c_int {
+    debug!("sys_clone <= flags: {:#x}, newsp: {:#x}", flags, newsp);
+    syscall_body!(sys_clone, {
+        if flags & !CLONE_SUPPORTED != 0 {
+            warn!(
+                "sys_clone: unsupported flags {:#x}, ignored",
+                flags & !CLONE_SUPPORTED
+            );
+        }
+        clone_task(
+            tf,
+            CloneArgs {
+                flags: flags & !CSIGNAL,
+                exit_signal: (flags & CSIGNAL) as c_int,
+                stack: newsp,
+                tls,
+                ptid,
+                ctid,
+            },
+        )
+    })
+}
+
+/// The arguments of [`sys_clone3`], as `struct clone_args` of Linux.
+#[repr(C)]
+#[derive(Default)]
+struct RawCloneArgs {
+    flags: u64,
+    pidfd: u64,
+    child_tid: u64,
+    parent_tid: u64,
+    exit_signal: u64,
+    stack: u64,
+    stack_size: u64,
+    tls: u64,
+    set_tid: u64,
+    set_tid_size: u64,
+    cgroup: u64,
+}
+
+/// Create a child process or thread, with the arguments in `struct
+/// clone_args` of `size` bytes at `args`.
+///
+/// The child stack is given by its lowest address and size, instead of the
+/// stack pointer, and the exit signal is given separately from the flags. See
+/// [`sys_clone`] for the flags, where unsupported ones are rejected instead of
+/// ignored. Choosing the thread IDs (`set_tid`), `CLONE_PIDFD` and
+/// `CLONE_INTO_CGROUP` are not supported.
+///
+/// Return the task ID of the child.
+pub unsafe fn sys_clone3(tf: &TrapFrame, args: *const u8, size: usize) -> c_int {
+    debug!("sys_clone3 <= args: {:#x}, size: {}", args as usize, size);
+    syscall_body!(sys_clone3, {
+        if args.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if size < CLONE_ARGS_SIZE_VER0 {
+            return Err(LinuxError::EINVAL);
+        }
+        // Newer versions of the struct are accepted if the unknown fields
+        // are all zeros.
+        let known = size_of::<RawCloneArgs>();
+        if size > memory_addr::PAGE_SIZE_4K {
+            return Err(LinuxError::E2BIG);
+        }
+        if size > known {
+            let mut extra = [0; memory_addr::PAGE_SIZE_4K];
+            let extra = &mut extra[..size - known];
+            copy_from_user(extra, args.wrapping_add(known))?;
+            if extra.iter().any(|&b| b != 0) {
+                return Err(LinuxError::E2BIG);
+            }
+        }
+        let mut raw = RawCloneArgs::default();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(&mut raw as *mut RawCloneArgs as *mut u8, known)
+        };
+        copy_from_user(&mut bytes[..size.min(known)], args)?;
+        debug!("sys_clone3 <= flags: {:#x}", raw.flags);
+
+        let flags = raw.flags as c_ulong;
+        if flags & (!CLONE_SUPPORTED | CSIGNAL) != 0
+            || raw.exit_signal & !(CSIGNAL as u64) != 0
+            || raw.set_tid != 0
+            || raw.set_tid_size != 0
+            || raw.cgroup != 0
+            || (raw.stack == 0) != (raw.stack_size == 0)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        // The stack grows down from the end of the given area.
+        let stack = raw
+            .stack
+            .checked_add(raw.stack_size)
+            .ok_or(LinuxError::EINVAL)? as usize;
+        clone_task(
+            tf,
+            CloneArgs {
+                flags,
+                exit_signal: raw.exit_signal as c_int,
+                stack,
+                tls: raw.tls as usize,
+                ptid: raw.parent_tid as *mut c_int,
+                ctid: raw.child_tid as *mut c_int,
+            },
+        )
+    })
+}
+
+/// Set the address to clear on exit of the calling thread to `tidptr`, as
+/// `CLONE_CHILD_CLEARTID` of [`sys_clone`].
+///
+/// When the thread exits, 0 is written to the address, and one waiter of the
+/// futex at it is woken up, which is how `pthread_join` of musl waits for
+/// the thread. A NULL `tidptr` clears nothing.
+///
+/// Return the thread ID of the caller.
+pub fn sys_set_tid_address(tidptr: *mut c_int) -> c_int {
+    debug!("sys_set_tid_address <= tidptr: {:#x}", tidptr as usize);
+    syscall_body!(sys_set_tid_address, {
+        let curr = current_thread().ok_or(LinuxError::EPERM)?;
+        curr.clear_child_tid
+            .store(tidptr as usize, Ordering::Release);
+        Ok(axtask::current().id().as_u64() as c_int)
+    })
+}
+
+/// Create a child process, with a copy of the address space and the file
+/// descriptor table. `SIGCHLD` is sent to the parent when the child exits.
+///
+/// Return the process ID of the child.
+pub fn sys_fork(tf: &TrapFrame) -> c_int {
+    let flags = ctypes::SIGCHLD as c_ulong;
+    sys_clone(
+        tf,
+        flags,
+        0,
+        core::ptr::null_mut(),
+        0,
+        core::ptr::null_mut(),
+    )
+}
+
+/// Create a child process sharing the address space, and suspend the parent
+/// until the child calls [`sys_execve`] or exits. `SIGCHLD` is sent to the
+/// parent when the child exits.
+///
+/// Return the process ID of the child.
+pub fn sys_vfork(tf: &TrapFrame) -> c_int {
+    let flags = CLONE_VM | CLONE_VFORK | ctypes::SIGCHLD as c_ulong;
+    sys_clone(
+        tf,
+        flags,
+        0,
+        core::ptr::null_mut(),
+        0,
+        core::ptr::null_mut(),
+    )
+}
+
+/// Finds a child of the process `ppid` matching `pid` (-1 for any child).
+///
+/// Returns the ID of an exited child, or `None` if the matching children are
+/// all running.
+fn find_exited_child(
+    table: &BTreeMap<u64, ProcessEntry>,
+    ppid: u64,
+    pid: c_int,
+) -> LinuxResult<Option<u64>> {
+    let mut found = false;
+    let exited = table
+        .iter()
+        .filter(|&(&cpid, p)| p.ppid == ppid && (pid == -1 || cpid == pid as u64))
+        .inspect(|_| found = true)
+        .find(|(_, p)| p.exit_code.is_some())
+        .map(|(&cpid, _)| cpid);
+    if found {
+        Ok(exited)
+    } else {
+        Err(LinuxError::ECHILD)
+    }
+}
+
+/// Wait for a child process to exit, and reap it.
+///
+/// `pid` is the ID of the child to wait for, or -1 for any child. Process
+/// groups are not supported, 0 waits for any child as -1, and other negative
+/// values are invalid. Only `WNOHANG` is supported in `options`, as children
+/// can not be stopped. The resource usage is not tracked, and is reported as
+/// all zeros.
+///
+/// Return the ID of the reaped child, or 0 if `WNOHANG` is specified and no
+/// child has exited.
+pub unsafe fn sys_wait4(
+    pid: c_int,
+    wstatus: *mut c_int,
+    options: c_int,
+    rusage: *mut ctypes::rusage,
+) -> c_int {
+    debug!("sys_wait4 <= pid: {}, options: {:#x}", pid, options);
+    syscall_body!(sys_wait4, {
+        let ppid = current_pid().ok_or(LinuxError::ECHILD)?;
+        if options & !(WNOHANG | WUNTRACED | WCONTINUED) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let pid = match pid {
+            0 | -1 => -1,
+            pid if pid > 0 => pid,
+            _ => return Err(LinuxError::EINVAL),
+        };
+
+        let (cpid, exit_code) = loop {
+            {
+                let mut table = PROCESS_TABLE.lock();
+                if let Some(cpid) = find_exited_child(&table, ppid, pid)? {
+                    let entry = reap_process(&mut table, cpid).unwrap();
+                    break (cpid, entry.exit_code.unwrap());
+                }
+            }
+            if options & WNOHANG != 0 {
+                return Ok(0);
+            }
+            // Another thread of the process may reap the child first, check
+            // again after woken up.
+            CHILD_EXIT_WQ.wait_until(|| {
+                !matches!(
+                    find_exited_child(&PROCESS_TABLE.lock(), ppid, pid),
+                    Ok(None)
+                )
+            });
+        };
+
+        if !wstatus.is_null() {
+            put_user(wstatus, (exit_code & 0xff) << 8)?;
+        }
+        if !rusage.is_null() {
+            put_user(rusage, Default::default())?;
+        }
+        Ok(cpid as c_int)
+    })
+}
+
+/// Wait for a child process to exit, and reap it.
+///
+/// See [`sys_wait4`] for the supported arguments.
+pub unsafe fn sys_waitpid(pid: c_int, wstatus: *mut c_int, options: c_int) -> c_int {
+    unsafe { sys_wait4(pid, wstatus, options, core::ptr::null_mut()) }
+}
+
+/// Reads a NULL-terminated array of C strings.
+#[cfg(feature = "fs")]
+fn read_str_array(arr: *const *const c_char) -> LinuxResult<Vec<String>> {
+    let mut strs = Vec::new();
+    if arr.is_null() {
+        return Ok(strs);
+    }
+    loop {
+        let ptr = get_user(arr.wrapping_add(strs.len()))?;
+        if ptr.is_null() {
+            return Ok(strs);
+        }
+        strs.push(strncpy_from_user(ptr, MAX_ARG_STRLEN)?);
+    }
+}
+
+/// Loads the executable at `path` into `aspace`, returns the context to enter
+/// it in user space.
+#[cfg(feature = "fs")]
+fn load_user_app(
+    aspace: &mut axmm::AddrSpace,
+    path: &str,
+    args: &[String],
+    envs: &[String],
+) -> LinuxResult<UspaceContext> {
+    let data = axfs::api::read(path)?;
+    let image = loader::load_elf(aspace, &data)?;
+    let stack_size = super::resources::stack_size(loader::USER_STACK_SIZE);
+    let sp = loader::init_stack(aspace, &image, stack_size, args, envs)?;
+    Ok(UspaceContext::new(image.entry, sp, 0))
+}
+
+/// Execute the program at `path` in the current process.
+///
+/// The user mappings of the process are replaced by the program, and the
+/// file descriptors with the close-on-exec flag are closed. Only statically
+/// linked ELF executables are supported. Other threads of the process are not
+/// terminated.
+///
+/// If the address space is shared with other processes, e.g. by
+/// `CLONE_VFORK`, the program is loaded into a new one instead, and the
+/// parent suspended by `CLONE_VFORK` is resumed.
+///
+/// On success, the trap frame `tf` is set to enter the program, and 0 is
+/// returned. If the program fails to load after the old mappings are removed,
+/// the current task exits.
+#[cfg(feature = "fs")]
+pub fn sys_execve(
+    tf: &mut TrapFrame,
+    path: *const c_char,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    syscall_body!(sys_execve, {
+        let path = &crate::utils::char_ptr_to_str(path)?;
+        let args = read_str_array(argv)?;
+        let envs = read_str_array(envp)?;
+        debug!("sys_execve <= path: {:?}, args: {:?}", path, args);
+        let curr = current_thread().ok_or(LinuxError::EPERM)?;
+        if !axfs::api::metadata(path)?.is_file() {
+            return Err(LinuxError::EACCES);
+        }
+
+        let mut process_aspace = curr.process.aspace.lock();
+        let uctx = if Arc::strong_count(&process_aspace) > 1 {
+            // The address space is shared with other processes, e.g. borrowed
+            // from the parent by `CLONE_VFORK`, load the program into a new one.
+            let mut aspace =
+                axmm::new_user_aspace(va!(loader::USER_SPACE_BASE), loader::USER_SPACE_SIZE)?;
+            let uctx = match load_user_app(&mut aspace, path, &args, &envs) {
+                Ok(uctx) => uctx,
+                Err(e) => {
+                    axmm::clear_kernel_mappings(&mut aspace);
+                    return Err(e);
+                }
+            };
+            let root = aspace.page_table_root();
+            *process_aspace = Arc::new(UserAspace(Mutex::new(aspace)));
+            // Switch to the new page table, which is also set in the task
+            // context first to be restored if preempted in between.
+            unsafe {
+                (*axtask::current().ctx_mut_ptr()).set_page_table_root(root);
+                #[cfg(any(target_arch = "aarch64", target_arch = "loongarch64"))]
+                axhal::arch::write_page_table_root0(root);
+                #[cfg(not(any(target_arch = "aarch64", target_arch = "loongarch64")))]
+                axhal::arch::write_page_table_root(root);
+            }
+            uctx
+        } else {
+            let mut aspace = process_aspace.lock();
+            crate::imp::mmap::release_mappings(&aspace);
+            aspace.unmap_user_areas()?;
+            let uctx = match load_user_app(&mut aspace, path, &args, &envs) {
+                Ok(uctx) => uctx,
+                Err(e) => {
+                    warn!("sys_execve: failed to load {:?}: {:?}", path, e);
+                    drop(aspace);
+                    drop(process_aspace);
+                    drop(curr);
+                    super::task::sys_exit(-1);
+                }
+            };
+            drop(aspace);
+            axhal::arch::flush_tlb(None);
+            uctx
+        };
+        drop(process_aspace);
+        *curr.process.brk.lock() = 0;
+        curr.process.membarrier_cmds.store(0, Ordering::Release);
+        if let Some(done) = &curr.vfork_done {
+            done.complete_all();
+        }
+        // The address is in the old program.
+        curr.clear_child_tid.store(0, Ordering::Release);
+
+        FD_TABLE.write().close_on_exec();
+        #[cfg(feature = "signal")]
+        super::signal::set_thread_sighand(
+            axtask::current().id().as_u64(),
+            Some(super::signal::exec_sighand()),
+        );
+        axtask::current().set_name(path);
+        *tf = *uctx;
+        Ok(0)
+    })
+}
+
+/// Reset the user and group IDs, which is a no-op as there is only one user.
+#[cfg(feature = "fs")]
+const POSIX_SPAWN_RESETIDS: c_int = 0x01;
+/// Use `vfork` instead of `fork`, which is a no-op as neither is used.
+#[cfg(feature = "fs")]
+const POSIX_SPAWN_USEVFORK: c_int = 0x40;
+
+/// The commands of the file actions in `posix_spawn_file_actions_t` of musl.
+#[cfg(feature = "fs")]
+const FDOP_CLOSE: c_int = 1;
+#[cfg(feature = "fs")]
+const FDOP_DUP2: c_int = 2;
+#[cfg(feature = "fs")]
+const FDOP_OPEN: c_int = 3;
+#[cfg(feature = "fs")]
+const FDOP_CHDIR: c_int = 4;
+#[cfg(feature = "fs")]
+const FDOP_FCHDIR: c_int = 5;
+
+/// `posix_spawn_file_actions_t` of musl, a list of the file actions.
+#[cfg(feature = "fs")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RawSpawnFileActions {
+    _pad0: [c_int; 2],
+    /// The last added action.
+    actions: *const RawSpawnFileAction,
+    _pad: [c_int; 16],
+}
+
+/// A file action of musl, followed by the NUL-terminated path.
+#[cfg(feature = "fs")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RawSpawnFileAction {
+    /// The previously added action.
+    next: *const RawSpawnFileAction,
+    /// The next added action.
+    prev: *const RawSpawnFileAction,
+    cmd: c_int,
+    fd: c_int,
+    srcfd: c_int,
+    oflag: c_int,
+    mode: ctypes::mode_t,
+    path: [c_char; 0],
+}
+
+/// A file action of [`sys_posix_spawn`], performed by the child before it
+/// enters user space.
+#[cfg(feature = "fs")]
+enum SpawnFileAction {
+    Close(c_int),
+    Dup2 {
+        srcfd: c_int,
+        fd: c_int,
+    },
+    Open {
+        fd: c_int,
+        path: String,
+        oflag: c_int,
+        mode: ctypes::mode_t,
+    },
+    Chdir(String),
+    Fchdir(c_int),
+}
+
+/// Reads the file actions of musl in the order they were added.
+#[cfg(feature = "fs")]
+fn read_file_actions(actions: *const RawSpawnFileActions) -> LinuxResult<Vec<SpawnFileAction>> {
+    let mut ops = Vec::new();
+    if actions.is_null() {
+        return Ok(ops);
+    }
+    let mut op = get_user(actions)?.actions;
+    if op.is_null() {
+        return Ok(ops);
+    }
+    loop {
+        let next = get_user(op)?.next;
+        if next.is_null() {
+            break;
+        }
+        op = next;
+    }
+    while !op.is_null() {
+        let raw = get_user(op)?;
+        let path_ptr = op.wrapping_byte_add(core::mem::offset_of!(RawSpawnFileAction, path));
+        let path = || strncpy_from_user(path_ptr as *const c_char, PATH_MAX);
+        ops.push(match raw.cmd {
+            FDOP_CLOSE => SpawnFileAction::Close(raw.fd),
+            FDOP_DUP2 => SpawnFileAction::Dup2 {
+                srcfd: raw.srcfd,
+                fd: raw.fd,
+            },
+            FDOP_OPEN => SpawnFileAction::Open {
+                fd: raw.fd,
+                path: path()?,
+                oflag: raw.oflag,
+                mode: raw.mode,
+            },
+            FDOP_CHDIR => SpawnFileAction::Chdir(path()?),
+            FDOP_FCHDIR => SpawnFileAction::Fchdir(raw.fd),
+            _ => return Err(LinuxError::EINVAL),
+        });
+        op = raw.prev;
+    }
+    Ok(ops)
+}
+
+/// Performs the file actions on the file descriptor table and the current
+/// directory of the current process.
+#[cfg(feature = "fs")]
+fn perform_file_actions(actions: &[SpawnFileAction]) -> LinuxResult {
+    use super::fd_ops::{close_file_like, dup_fd_to};
+
+    for action in actions {
+        match action {
+            SpawnFileAction::Close(fd) => close_file_like(*fd)?,
+            SpawnFileAction::Dup2 { srcfd, fd } => {
+                if srcfd == fd {
+                    // Inherit the same file descriptor across the exec.
+                    FD_TABLE.write().set_cloexec(*fd, false)?;
+                } else {
+                    dup_fd_to(*srcfd, *fd, false)?;
+                }
+            }
+            SpawnFileAction::Open {
+                fd,
+                path,
+                oflag,
+                mode,
+            } => {
+                let new_fd = super::fs::open_path(path, *oflag, *mode)?;
+                if new_fd != *fd {
+                    let cloexec = FD_TABLE.read().cloexec(new_fd)?;
+                    dup_fd_to(new_fd, *fd, cloexec)?;
+                    close_file_like(new_fd)?;
+                }
+            }
+            SpawnFileAction::Chdir(path) => axfs::api::set_current_dir(path)?,
+            SpawnFileAction::Fchdir(fd) => {
+                let dir = super::fs::Directory::from_fd(*fd).map_err(|e| match e {
+                    LinuxError::EINVAL => LinuxError::ENOTDIR,
+                    e => e,
+                })?;
+                axfs::api::set_current_dir(dir.path())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The result of the file actions of a child of [`sys_posix_spawn`].
+#[cfg(feature = "fs")]
+struct SpawnStatus {
+    done: Completion,
+    error: SpinNoIrq<Option<LinuxError>>,
+}
+
+/// Spawn a child process of the current one, running the program at `path`.
+///
+/// Unlike `fork` and `execve`, the address space of the child is created
+/// from the program directly, without copying the one of the parent. The
+/// child gets a copy of the file descriptor table and the current working
+/// directory, where the file actions in `file_actions`, in the layout of
+/// `posix_spawn_file_actions_t` of musl, are performed before the
+/// close-on-exec file descriptors are closed. Of the flags in `attrp`, only
+/// `POSIX_SPAWN_RESETIDS` and `POSIX_SPAWN_USEVFORK` are supported, which
+/// have no effect. `SIGCHLD` is sent to the parent when the child exits. See
+/// [`sys_execve`] for the supported programs.
+///
+/// On success, the process ID of the child is stored at `pid` if it is not
+/// null, and 0 is returned. If a file action fails, the child exits and is
+/// reaped without notifying the parent, and the error is returned.
+#[cfg(feature = "fs")]
+pub unsafe fn sys_posix_spawn(
+    pid: *mut c_int,
+    path: *const c_char,
+    file_actions: *const c_void,
+    attrp: *const c_void,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    syscall_body!(sys_posix_spawn, {
+        let path = &crate::utils::char_ptr_to_str(path)?;
+        let args = read_str_array(argv)?;
+        let envs = read_str_array(envp)?;
+        debug!("sys_posix_spawn <= path: {:?}, args: {:?}", path, args);
+        let curr = current_thread().ok_or(LinuxError::EPERM)?;
+        if !attrp.is_null() {
+            // The flags are the first field of `posix_spawnattr_t`.
+            let flags = get_user(attrp as *const c_int)?;
+            if flags & !(POSIX_SPAWN_RESETIDS | POSIX_SPAWN_USEVFORK) != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+        }
+        let actions = read_file_actions(file_actions as *const RawSpawnFileActions)?;
+        if !axfs::api::metadata(path)?.is_file() {
+            return Err(LinuxError::EACCES);
+        }
+
+        let mut aspace =
+            axmm::new_user_aspace(va!(loader::USER_SPACE_BASE), loader::USER_SPACE_SIZE)?;
+        let uctx = match load_user_app(&mut aspace, path, &args, &envs) {
+            Ok(uctx) => uctx,
+            Err(e) => {
+                axmm::clear_kernel_mappings(&mut aspace);
+                return Err(e);
+            }
+        };
+        let aspace = Arc::new(UserAspace(Mutex::new(aspace)));
+
+        // Without file actions, the child can not fail, and the parent need
+        // not wait for it.
+        let status = (!actions.is_empty()).then(|| {
+            Arc::new(SpawnStatus {
+                done: Completion::new(),
+                error: SpinNoIrq::new(None),
+            })
+        });
+        let child_status = status.clone();
+        let task = new_user_task_with(path, uctx, &aspace, move || {
+            let result = perform_file_actions(&actions);
+            drop(actions);
+            if let Some(status) = child_status {
+                if let Err(e) = result {
+                    // Nobody waits for the failed child.
+                    let pid = axtask::current().id().as_u64();
+                    if let Some(entry) = PROCESS_TABLE.lock().get_mut(&pid) {
+                        entry.ppid = 0;
+                    }
+                    *status.error.lock() = Some(e);
+                    status.done.complete_all();
+                    drop(status);
+                    super::task::sys_exit(127);
+                }
+                status.done.complete_all();
+            }
+            FD_TABLE.write().close_on_exec();
+        })?;
+        let tid = task.id().as_u64();
+        add_process(
+            &task,
+            ProcessEntry {
+                ppid: curr.process.pid,
+                exit_signal: ctypes::SIGCHLD as c_int,
+                exit_code: None,
+            },
+        );
+        let process = Arc::new(Process {
+            pid: tid,
+            aspace: Mutex::new(aspace),
+            brk: Mutex::new(0),
+            ns: ProcessNamespace::new(Arc::new(FD_TABLE.copy_inner()), false),
+            rlimits: Arc::new(SpinNoIrq::new(current_limits())),
+            membarrier_cmds: AtomicU32::new(0),
+            live_threads: AtomicUsize::new(0),
+        });
+        #[cfg(feature = "signal")]
+        super::signal::set_thread_sighand(tid, Some(super::signal::exec_sighand()));
+        spawn_user_task(
+            task,
+            Thread {
+                process,
+                clear_child_tid: AtomicUsize::new(0),
+                vfork_done: None,
+            },
+        );
+        drop(curr);
+
+        if let Some(status) = status {
+            status.done.wait();
+            if let Some(e) = *status.error.lock() {
+                return Err(e);
+            }
+        }
+        if !pid.is_null() {
+            put_user(pid, tid as c_int)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Spawns a new process running the program at `path`, from a kernel task.
+///
+/// The process gets a copy of the file descriptor table of the current task.
+/// See [`sys_execve`] for the supported programs.
+#[cfg(feature = "fs")]
+pub fn spawn_process(path: &str, args: &[String], envs: &[String]) -> LinuxResult<AxTaskRef> {
+    let mut aspace = axmm::new_user_aspace(va!(loader::USER_SPACE_BASE), loader::USER_SPACE_SIZE)?;
+    let uctx = match load_user_app(&mut aspace, path, args, envs) {
+        Ok(uctx) => uctx,
+        Err(e) => {
+            axmm::clear_kernel_mappings(&mut aspace);
+            return Err(e);
+        }
+    };
+    let aspace = Arc::new(UserAspace(Mutex::new(aspace)));
+    // The spawning kernel task joins the task instead of waiting for the
+    // process, so it has no parent and is not in `PROCESS_TABLE`.
+    let task = new_user_task(path, uctx, &aspace)?;
+    let process = Arc::new(Process {
+        pid: task.id().as_u64(),
+        aspace: Mutex::new(aspace),
+        brk: Mutex::new(0),
+        ns: ProcessNamespace::new(Arc::new(FD_TABLE.copy_inner()), false),
+        rlimits: Arc::new(SpinNoIrq::new(current_limits())),
+        membarrier_cmds: AtomicU32::new(0),
+        live_threads: AtomicUsize::new(0),
+    });
+    #[cfg(feature = "signal")]
+    super::signal::set_thread_sighand(process.pid, Some(super::signal::exec_sighand()));
+    Ok(spawn_user_task(
+        task,
+        Thread {
+            process,
+            clear_child_tid: AtomicUsize::new(0),
+            vfork_done: None,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ppid: u64, exit_code: Option<c_int>) -> ProcessEntry {
+        ProcessEntry {
+            ppid,
+            exit_signal: 17,
+            exit_code,
+        }
+    }
+
+    #[test]
+    fn test_find_exited_child() {
+        let mut table = BTreeMap::new();
+        assert_eq!(find_exited_child(&table, 1, -1), Err(LinuxError::ECHILD));
+        table.insert(2, entry(1, None));
+        table.insert(3, entry(1, Some(0)));
+        table.insert(4, entry(2, Some(0)));
+        assert_eq!(find_exited_child(&table, 1, -1), Ok(Some(3)));
+        assert_eq!(find_exited_child(&table, 1, 2), Ok(None));
+        assert_eq!(find_exited_child(&table, 1, 3), Ok(Some(3)));
+        // not a child of the caller
+        assert_eq!(find_exited_child(&table, 1, 4), Err(LinuxError::ECHILD));
+        assert_eq!(find_exited_child(&table, 3, -1), Err(LinuxError::ECHILD));
+    }
+
+    #[test]
+    fn test_record_exit() {
+        let mut table = BTreeMap::new();
+        table.insert(2, entry(1, None));
+        table.insert(3, entry(2, None));
+        table.insert(4, entry(2, None));
+        table.insert(5, entry(0, None));
+
+        assert_eq!(record_exit(&mut table, 3, 42), Some((2, 17)));
+        assert_eq!(table[&3].exit_code, Some(42));
+        assert_eq!(find_exited_child(&table, 2, -1), Ok(Some(3)));
+
+        // the zombie child is reaped, and the running one is orphaned
+        assert_eq!(record_exit(&mut table, 2, 0), Some((1, 17)));
+        assert!(!table.contains_key(&3));
+        assert_eq!(table[&4].ppid, 0);
+        assert_eq!(find_exited_child(&table, 1, 2), Ok(Some(2)));
+
+        // the processes without a parent are reaped on exit
+        assert_eq!(record_exit(&mut table, 4, 0), None);
+        assert_eq!(record_exit(&mut table, 5, 0), None);
+        assert!(!table.contains_key(&4) && !table.contains_key(&5));
+        assert_eq!(record_exit(&mut table, 6, 0), None);
+        assert_eq!(table.len(), 1);
+    }
+}
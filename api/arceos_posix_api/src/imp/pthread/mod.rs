@@ -59,7 +59,10 @@ impl Pthread {
             drop(their_packet);
         };
 
-        let task_inner = axtask::spawn(main);
+        // Like glibc, the default stack size is the soft limit of `RLIMIT_STACK`.
+        let stack_size = crate::imp::resources::stack_size(axconfig::TASK_STACK_SIZE);
+        let task_inner =
+            axtask::try_spawn_raw(main, "".into(), stack_size).ok_or(LinuxError::EAGAIN)?;
         let tid = task_inner.id().as_u64();
         let thread = Pthread {
             inner: task_inner,
@@ -147,6 +150,22 @@ pub unsafe fn sys_pthread_join(thread: ctypes::pthread_t, retval: *mut *mut c_vo
     })
 }
 
+/// Sends a signal to the given thread.
+#[cfg(feature = "signal")]
+pub unsafe fn sys_pthread_kill(thread: ctypes::pthread_t, sig: c_int) -> c_int {
+    debug!("sys_pthread_kill <= {:#x}, {}", thread as usize, sig);
+    syscall_body!(sys_pthread_kill, {
+        if thread.is_null() {
+            return Err(LinuxError::ESRCH);
+        }
+        let tid = unsafe { &*(thread as *const Pthread) }.inner.id().as_u64();
+        if sig != 0 {
+            crate::imp::signal::send_signal_to_task(tid, sig, ctypes::SI_TKILL, 0)?;
+        }
+        Ok(0)
+    })
+}
+
 #[derive(Clone, Copy)]
 struct ForceSendSync<T>(T);
 
@@ -0,0 +1,138 @@
+//! Random bytes from the kernel entropy pool, by `getrandom` and the devices
+//! `/dev/random` and `/dev/urandom`.
+//!
+//! The pool never blocks, as it's either seeded by the hardware random number
+//! generator at boot, or never seeded, in which case a warning is printed at
+//! the first use instead of blocking forever.
+
+use core::ffi::{c_uint, c_void};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::LinuxError;
+
+use crate::ctypes;
+
+/// The maximum number of bytes returned by a `getrandom` with `GRND_RANDOM`.
+const RANDOM_READ_MAX: usize = 512;
+/// The maximum number of bytes returned by a `getrandom` without
+/// `GRND_RANDOM`.
+const URANDOM_READ_MAX: usize = (i32::MAX as usize) >> 6;
+
+static WARNED_UNSEEDED: AtomicBool = AtomicBool::new(false);
+
+fn fill_bytes(buf: &mut [u8]) {
+    if !axhal::entropy::is_seeded() && !WARNED_UNSEEDED.swap(true, Ordering::Relaxed) {
+        warn!("random bytes requested before the entropy pool is seeded");
+    }
+    axhal::entropy::fill_bytes(buf);
+}
+
+/// Fills `buf` with up to `buflen` random bytes, returns the number of bytes
+/// filled.
+///
+/// It never blocks, so `GRND_NONBLOCK` and `GRND_INSECURE` have no effect.
+pub fn sys_getrandom(buf: *mut c_void, buflen: usize, flags: c_uint) -> ctypes::ssize_t {
+    debug!(
+        "sys_getrandom <= {:#x} {} {:#x}",
+        buf as usize, buflen, flags
+    );
+    syscall_body!(sys_getrandom, {
+        let all_flags = ctypes::GRND_NONBLOCK | ctypes::GRND_RANDOM | ctypes::GRND_INSECURE;
+        if flags & !all_flags != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let random = flags & ctypes::GRND_RANDOM != 0;
+        if random && flags & ctypes::GRND_INSECURE != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if buflen == 0 {
+            return Ok(0);
+        }
+        let max_len = if random {
+            RANDOM_READ_MAX
+        } else {
+            URANDOM_READ_MAX
+        };
+        crate::uaccess::with_user_buf_mut(buf as *mut u8, buflen.min(max_len), |buf| {
+            fill_bytes(buf);
+            Ok(buf.len())
+        })
+    })
+}
+
+#[cfg(feature = "fs")]
+pub(crate) use self::dev::open_random;
+
+#[cfg(feature = "fs")]
+mod dev {
+    use alloc::sync::Arc;
+    use core::ffi::c_int;
+
+    use axerrno::LinuxResult;
+    use axio::PollState;
+
+    use super::fill_bytes;
+    use crate::ctypes;
+    use crate::imp::fd_ops::{FD_TABLE, FileLike};
+
+    /// An opened `/dev/random` or `/dev/urandom`, which are the same.
+    struct RandomFile {
+        /// The minor number of the device.
+        minor: u32,
+    }
+
+    impl FileLike for RandomFile {
+        fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+            fill_bytes(buf);
+            Ok(buf.len())
+        }
+
+        /// Mixes the bytes written into the pool, but they are not counted as
+        /// entropy.
+        fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+            axhal::entropy::add_entropy(buf);
+            Ok(buf.len())
+        }
+
+        fn stat(&self) -> LinuxResult<ctypes::stat> {
+            let st_mode = 0o20000 | 0o666; // S_IFCHR
+            Ok(ctypes::stat {
+                st_ino: 1,
+                st_nlink: 1,
+                st_mode,
+                st_rdev: ((1 << 8) | self.minor) as _, // major number of the memory devices
+                st_blksize: 4096,
+                ..Default::default()
+            })
+        }
+
+        fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+            self
+        }
+
+        fn poll(&self) -> LinuxResult<PollState> {
+            Ok(PollState {
+                readable: true,
+                writable: true,
+            })
+        }
+
+        fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+            Ok(())
+        }
+    }
+
+    /// Opens the device if `path` is `/dev/random` or `/dev/urandom`, called
+    /// by `open_device`, returns `None` for the other paths.
+    pub(crate) fn open_random(path: &str, cloexec: bool) -> LinuxResult<Option<c_int>> {
+        let minor = match path {
+            "/dev/random" => 8,
+            "/dev/urandom" => 9,
+            _ => return Ok(None),
+        };
+        FD_TABLE
+            .write()
+            .add(Arc::new(RandomFile { minor }), cloexec)
+            .map(Some)
+    }
+}
@@ -0,0 +1,79 @@
+//! Name resolution for `getaddrinfo` and `getnameinfo`.
+//!
+//! The names in `/etc/hosts` override the DNS, so the hosts without records
+//! can be reached by name. Others are queried from the DNS servers.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr};
+
+use axerrno::LinuxResult;
+
+/// The static table of host names.
+#[cfg(feature = "fs")]
+const HOSTS_PATH: &str = "/etc/hosts";
+
+/// Calls `f` with the address and the names of each entry in `/etc/hosts`,
+/// until it returns `Some`.
+///
+/// The lines are `<address> <name> [<alias>...]`, and the text after `#` is
+/// ignored. It's an empty table if the file doesn't exist.
+#[cfg(feature = "fs")]
+fn find_in_hosts<T>(
+    mut f: impl FnMut(IpAddr, &mut dyn Iterator<Item = &str>) -> Option<T>,
+) -> Option<T> {
+    let hosts = axfs::api::read_to_string(HOSTS_PATH).ok()?;
+    hosts.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_ascii_whitespace();
+        let addr = fields.next()?.parse::<IpAddr>().ok()?;
+        f(addr, &mut fields)
+    })
+}
+
+#[cfg(not(feature = "fs"))]
+fn find_in_hosts<T>(
+    _f: impl FnMut(IpAddr, &mut dyn Iterator<Item = &str>) -> Option<T>,
+) -> Option<T> {
+    None
+}
+
+/// Returns the addresses of a host, which is an address itself, a name in
+/// `/etc/hosts`, or a name queried from the DNS.
+///
+/// It fails with `ENOENT` if the name doesn't exist, or `EAGAIN` if the DNS
+/// servers don't respond.
+pub fn lookup_host(name: &str) -> LinuxResult<Vec<IpAddr>> {
+    if let Ok(addr) = name.parse::<IpAddr>() {
+        return Ok(alloc::vec![addr]);
+    }
+    let mut addrs = Vec::new();
+    find_in_hosts(|addr, names| {
+        if names.any(|n| n.eq_ignore_ascii_case(name)) {
+            addrs.push(addr);
+        }
+        None::<()>
+    });
+    if !addrs.is_empty() {
+        return Ok(addrs);
+    }
+    if name.eq_ignore_ascii_case("localhost") {
+        return Ok(alloc::vec![Ipv4Addr::LOCALHOST.into()]);
+    }
+    Ok(axnet::dns_query(name)?)
+}
+
+/// Returns the name of an address in `/etc/hosts`.
+///
+/// The DNS has no reverse lookup, so only the loopback address is named
+/// besides them.
+pub fn lookup_addr(addr: IpAddr) -> Option<String> {
+    find_in_hosts(|a, names| {
+        if a == addr {
+            names.next().map(str::to_string)
+        } else {
+            None
+        }
+    })
+    .or_else(|| addr.is_loopback().then(|| "localhost".to_string()))
+}
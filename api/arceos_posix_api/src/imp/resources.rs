@@ -1,51 +1,183 @@
-use crate::ctypes;
-use axerrno::LinuxError;
+//! Resource limits of processes, like `getrlimit(2)`.
+//!
+//! Each user process has its own table of the limits, which is inherited by
+//! its children, while the kernel tasks (and the threads of the application
+//! without the `uspace` feature) share a global one. All the resources can be
+//! queried and set, but only the following ones are enforced:
+//!
+//! - `RLIMIT_NOFILE`: the file descriptors are allocated below the soft
+//!   limit, and the hard limit can not exceed the size of the table.
+//! - `RLIMIT_STACK`: the soft limit is the size of the stacks of the new
+//!   threads created by `pthread_create`, and of the programs loaded by
+//!   `execve` and `posix_spawn`.
+//!
+//! There is only one user, who is privileged to raise the hard limits.
+
 use core::ffi::c_int;
 
-/// Get resource limitations
-///
-/// TODO: support more resource types
-pub unsafe fn sys_getrlimit(resource: c_int, rlimits: *mut ctypes::rlimit) -> c_int {
-    debug!("sys_getrlimit <= {} {:#x}", resource, rlimits as usize);
-    syscall_body!(sys_getrlimit, {
-        match resource as u32 {
-            ctypes::RLIMIT_DATA => {}
-            ctypes::RLIMIT_STACK => {}
-            ctypes::RLIMIT_NOFILE => {}
-            _ => return Err(LinuxError::EINVAL),
+use axerrno::{LinuxError, LinuxResult};
+use axsync::spin::SpinNoIrq;
+
+use crate::ctypes;
+use crate::uaccess::{get_user, put_user};
+
+/// The value of an unlimited resource, `RLIM_INFINITY`.
+const RLIM_INFINITY: ctypes::rlim_t = ctypes::rlim_t::MAX;
+
+/// The maximum number of open files, i.e. the size of the file descriptor
+/// table.
+#[cfg(feature = "fd")]
+const NOFILE_MAX: ctypes::rlim_t = super::fd_ops::AX_FILE_LIMIT as _;
+#[cfg(not(feature = "fd"))]
+const NOFILE_MAX: ctypes::rlim_t = 0;
+
+const RLIM_NLIMITS: usize = ctypes::RLIMIT_NLIMITS as usize;
+
+/// The resource limits of a process, indexed by the `RLIMIT_*` resources.
+#[derive(Clone)]
+pub struct ResourceLimits([ctypes::rlimit; RLIM_NLIMITS]);
+
+impl ResourceLimits {
+    /// Creates the default limits, where the resources other than the open
+    /// files and the stack are unlimited.
+    pub const fn new() -> Self {
+        const UNLIMITED: ctypes::rlimit = ctypes::rlimit {
+            rlim_cur: RLIM_INFINITY,
+            rlim_max: RLIM_INFINITY,
+        };
+        let mut limits = [UNLIMITED; RLIM_NLIMITS];
+        limits[ctypes::RLIMIT_NOFILE as usize] = ctypes::rlimit {
+            rlim_cur: NOFILE_MAX,
+            rlim_max: NOFILE_MAX,
+        };
+        limits[ctypes::RLIMIT_STACK as usize].rlim_cur = axconfig::TASK_STACK_SIZE as _;
+        Self(limits)
+    }
+
+    /// Returns the limit of `resource`.
+    pub fn get(&self, resource: u32) -> ctypes::rlimit {
+        self.0[resource as usize]
+    }
+
+    /// Sets the limit of `resource`.
+    fn set(&mut self, resource: u32, limit: ctypes::rlimit) -> LinuxResult {
+        if limit.rlim_cur > limit.rlim_max {
+            return Err(LinuxError::EINVAL);
         }
-        if rlimits.is_null() {
-            return Ok(0);
+        if resource == ctypes::RLIMIT_NOFILE && limit.rlim_max > NOFILE_MAX {
+            return Err(LinuxError::EPERM);
+        }
+        self.0[resource as usize] = limit;
+        Ok(())
+    }
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The limits of the kernel tasks, and of the application if it is not run
+/// in user processes.
+static GLOBAL_LIMITS: SpinNoIrq<ResourceLimits> = SpinNoIrq::new(ResourceLimits::new());
+
+/// Runs `f` with the limits of the process `pid`, or of the current process
+/// if `pid` is 0.
+fn with_limits<R>(pid: ctypes::pid_t, f: impl FnOnce(&mut ResourceLimits) -> R) -> LinuxResult<R> {
+    if pid < 0 {
+        return Err(LinuxError::ESRCH);
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(limits) = super::process::process_rlimits(pid as u64) {
+        return Ok(f(&mut limits.lock()));
+    }
+    if pid != 0 && pid != super::task::sys_getpid() {
+        return Err(LinuxError::ESRCH);
+    }
+    Ok(f(&mut GLOBAL_LIMITS.lock()))
+}
+
+/// Returns a copy of the limits of the current process, for a new process
+/// to inherit.
+pub(crate) fn current_limits() -> ResourceLimits {
+    with_limits(0, |limits| limits.clone()).unwrap()
+}
+
+/// Returns the limit of `resource` of the current process.
+pub(crate) fn current_limit(resource: u32) -> ctypes::rlimit {
+    with_limits(0, |limits| limits.get(resource)).unwrap()
+}
+
+/// Returns the size of the stacks of the new threads and programs, i.e. the
+/// soft limit of `RLIMIT_STACK` rounded up to pages, or `default` if it is
+/// unlimited.
+pub(crate) fn stack_size(default: usize) -> usize {
+    const PAGE_SIZE_4K: usize = 4096;
+    match current_limit(ctypes::RLIMIT_STACK).rlim_cur {
+        RLIM_INFINITY => default,
+        size => (size.min(isize::MAX as _) as usize)
+            .max(PAGE_SIZE_4K)
+            .next_multiple_of(PAGE_SIZE_4K),
+    }
+}
+
+/// Gets and sets the limit of `resource` of the process `pid`, or of the
+/// current process if `pid` is 0.
+///
+/// The old limit is stored in `old_limit` if it is not NULL, and then the
+/// limit is set to `new_limit` if it is not NULL.
+pub unsafe fn sys_prlimit64(
+    pid: ctypes::pid_t,
+    resource: c_int,
+    new_limit: *const ctypes::rlimit,
+    old_limit: *mut ctypes::rlimit,
+) -> c_int {
+    debug!(
+        "sys_prlimit64 <= pid: {}, resource: {}, new_limit: {:#x}, old_limit: {:#x}",
+        pid, resource, new_limit as usize, old_limit as usize
+    );
+    syscall_body!(sys_prlimit64, {
+        if resource < 0 || resource as usize >= RLIM_NLIMITS {
+            return Err(LinuxError::EINVAL);
         }
-        match resource as u32 {
-            ctypes::RLIMIT_STACK => unsafe {
-                (*rlimits).rlim_cur = axconfig::TASK_STACK_SIZE as _;
-                (*rlimits).rlim_max = axconfig::TASK_STACK_SIZE as _;
-            },
-            #[cfg(feature = "fd")]
-            ctypes::RLIMIT_NOFILE => unsafe {
-                (*rlimits).rlim_cur = super::fd_ops::AX_FILE_LIMIT as _;
-                (*rlimits).rlim_max = super::fd_ops::AX_FILE_LIMIT as _;
-            },
-            _ => {}
+        let resource = resource as u32;
+        let new_limit = if new_limit.is_null() {
+            None
+        } else {
+            Some(get_user(new_limit)?)
+        };
+        let old = with_limits(pid, |limits| -> LinuxResult<_> {
+            let old = limits.get(resource);
+            if let Some(new_limit) = new_limit {
+                limits.set(resource, new_limit)?;
+            }
+            Ok(old)
+        })??;
+        if !old_limit.is_null() {
+            put_user(old_limit, old)?;
         }
         Ok(0)
     })
 }
 
-/// Set resource limitations
+/// Gets the limit of `resource` of the current process.
+pub unsafe fn sys_getrlimit(resource: c_int, rlimits: *mut ctypes::rlimit) -> c_int {
+    debug!("sys_getrlimit <= {} {:#x}", resource, rlimits as usize);
+    if rlimits.is_null() {
+        return -LinuxError::EFAULT.code();
+    }
+    unsafe { sys_prlimit64(0, resource, core::ptr::null(), rlimits) }
+}
+
+/// Sets the limit of `resource` of the current process.
 ///
-/// TODO: support more resource types
-pub unsafe fn sys_setrlimit(resource: c_int, rlimits: *mut crate::ctypes::rlimit) -> c_int {
+/// The soft limit can not exceed the hard limit, and the hard limit of
+/// `RLIMIT_NOFILE` can not exceed the size of the file descriptor table.
+pub unsafe fn sys_setrlimit(resource: c_int, rlimits: *mut ctypes::rlimit) -> c_int {
     debug!("sys_setrlimit <= {} {:#x}", resource, rlimits as usize);
-    syscall_body!(sys_setrlimit, {
-        match resource as u32 {
-            crate::ctypes::RLIMIT_DATA => {}
-            crate::ctypes::RLIMIT_STACK => {}
-            crate::ctypes::RLIMIT_NOFILE => {}
-            _ => return Err(LinuxError::EINVAL),
-        }
-        // Currently do not support set resources
-        Ok(0)
-    })
+    if rlimits.is_null() {
+        return -LinuxError::EFAULT.code();
+    }
+    unsafe { sys_prlimit64(0, resource, rlimits, core::ptr::null_mut()) }
 }
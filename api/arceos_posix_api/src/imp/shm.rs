@@ -0,0 +1,70 @@
+//! POSIX shared memory objects, like `shm_open(3)`.
+//!
+//! The objects are the files in `/dev/shm`, where a tmpfs is mounted, so they
+//! are resized by `ftruncate` and mapped by `mmap` with `MAP_SHARED` to the
+//! pages of the files, which are shared by all the tasks mapping the same
+//! object. An object is released when it is unlinked, and no longer opened
+//! or mapped.
+
+use alloc::format;
+use alloc::string::String;
+use core::ffi::{c_char, c_int};
+
+use axerrno::{LinuxError, LinuxResult};
+
+use crate::{ctypes, utils::char_ptr_to_str};
+
+/// The directory of the shared memory objects.
+const SHM_DIR: &str = "/dev/shm";
+
+/// Returns the path of the shared memory object `name`, which must be like
+/// `/somename`.
+fn shm_path(name: *const c_char) -> LinuxResult<String> {
+    let name = char_ptr_to_str(name)?;
+    match name.strip_prefix('/') {
+        Some(n) if !n.is_empty() && !n.contains('/') && !matches!(n, "." | "..") => {
+            if n.len() > 255 {
+                Err(LinuxError::ENAMETOOLONG)
+            } else {
+                Ok(format!("{SHM_DIR}/{n}"))
+            }
+        }
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Creates or opens the shared memory object `name`, and returns its file
+/// descriptor.
+///
+/// `oflag` is the access mode (`O_RDONLY` or `O_RDWR`) with `O_CREAT`,
+/// `O_EXCL` and `O_TRUNC`. The file descriptor is opened with `FD_CLOEXEC`.
+pub fn sys_shm_open(name: *const c_char, oflag: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!(
+        "sys_shm_open <= name: {:?}, oflag: {:#o}, mode: {:#o}",
+        char_ptr_to_str(name),
+        oflag,
+        mode
+    );
+    syscall_body!(sys_shm_open, {
+        let path = shm_path(name)?;
+        let flags = oflag as u32;
+        let allowed = 0b11 | ctypes::O_CREAT | ctypes::O_EXCL | ctypes::O_TRUNC;
+        if flags & !allowed != 0 || !matches!(flags & 0b11, ctypes::O_RDONLY | ctypes::O_RDWR) {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = flags | ctypes::O_CLOEXEC | ctypes::O_NOFOLLOW;
+        super::fs::open_path(&path, flags as c_int, mode)
+    })
+}
+
+/// Removes the shared memory object `name`.
+///
+/// The object is released after the file descriptors and the mappings of it
+/// are closed.
+pub fn sys_shm_unlink(name: *const c_char) -> c_int {
+    debug!("sys_shm_unlink <= name: {:?}", char_ptr_to_str(name));
+    syscall_body!(sys_shm_unlink, {
+        axfs::api::remove_file(&shm_path(name)?)?;
+        Ok(0)
+    })
+}
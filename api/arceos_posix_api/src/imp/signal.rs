@@ -0,0 +1,942 @@
+//! POSIX signals.
+//!
+//! The signal actions are shared by the tasks of a sighand group, i.e. the
+//! tasks cloned with `CLONE_SIGHAND`, and the kernel tasks outside the user
+//! processes. The process-directed pending signals are shared by all tasks,
+//! while each task has its own signal mask and thread-directed pending
+//! signals.
+//!
+//! Pending signals are delivered to the current task when it returns from a
+//! syscall (at the end of `syscall_body!`). If the `uspace` feature is
+//! enabled, they are also delivered when returning from a trap to user space,
+//! by setting up a signal frame on the user stack, which is restored by
+//! [`sys_rt_sigreturn`].
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::ffi::{c_int, c_uint, c_void};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::spin::SpinNoIrq;
+use axtask::{AxTaskRef, WeakAxTaskRef};
+
+use crate::ctypes;
+use crate::uaccess::{get_user, put_user};
+
+/// The number of signals, valid signal numbers are `1..NSIG`.
+const NSIG: usize = 65;
+
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
+
+/// Signals that can not be caught, blocked or ignored.
+const UNBLOCKABLE: u64 = sig_bit(ctypes::SIGKILL as usize) | sig_bit(ctypes::SIGSTOP as usize);
+
+const fn sig_bit(sig: usize) -> u64 {
+    1 << (sig - 1)
+}
+
+pub(crate) fn check_signo(sig: c_int) -> LinuxResult<usize> {
+    if sig <= 0 || sig as usize >= NSIG {
+        Err(LinuxError::EINVAL)
+    } else {
+        Ok(sig as usize)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SigAction {
+    handler: usize,
+    flags: u32,
+    mask: u64,
+    restorer: usize,
+}
+
+impl SigAction {
+    const DEFAULT: Self = Self {
+        handler: SIG_DFL,
+        flags: 0,
+        mask: 0,
+        restorer: 0,
+    };
+
+    fn from_ctype(act: &ctypes::sigaction) -> Self {
+        Self {
+            handler: unsafe { act.__sa_handler.sa_handler }.map_or(SIG_DFL, |f| f as usize),
+            flags: act.sa_flags as u32,
+            mask: act.sa_mask.__bits[0] as u64 & !UNBLOCKABLE,
+            restorer: act.sa_restorer.map_or(0, |f| f as usize),
+        }
+    }
+
+    fn to_ctype(self) -> ctypes::sigaction {
+        let mut act = ctypes::sigaction::default();
+        act.__sa_handler.sa_handler = unsafe { core::mem::transmute::<usize, _>(self.handler) };
+        act.sa_flags = self.flags as c_int;
+        act.sa_mask.__bits[0] = self.mask as _;
+        act.sa_restorer = unsafe { core::mem::transmute::<usize, _>(self.restorer) };
+        act
+    }
+
+    /// Whether the signal is discarded, either explicitly ignored or ignored
+    /// by default.
+    fn is_ignored(&self, sig: usize) -> bool {
+        match self.handler {
+            SIG_IGN => true,
+            SIG_DFL => is_ignored_by_default(sig),
+            _ => false,
+        }
+    }
+}
+
+fn is_ignored_by_default(sig: usize) -> bool {
+    matches!(
+        sig as u32,
+        ctypes::SIGCHLD | ctypes::SIGCONT | ctypes::SIGURG | ctypes::SIGWINCH
+    )
+}
+
+/// The signal information passed to `SA_SIGINFO` handlers, with the same
+/// layout as `siginfo_t`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SigInfo {
+    signo: c_int,
+    errno: c_int,
+    code: c_int,
+    _pad: c_int,
+    pid: ctypes::pid_t,
+    uid: c_uint,
+    value: usize,
+    _rest: [u64; 12],
+}
+
+static_assertions::const_assert_eq!(
+    core::mem::size_of::<SigInfo>(),
+    core::mem::size_of::<ctypes::siginfo_t>()
+);
+
+/// The information recorded for a pending signal.
+#[derive(Clone, Copy)]
+struct PendingInfo {
+    code: c_int,
+    pid: ctypes::pid_t,
+    value: usize,
+}
+
+impl PendingInfo {
+    const EMPTY: Self = Self {
+        code: 0,
+        pid: 0,
+        value: 0,
+    };
+
+    fn to_siginfo(self, sig: usize) -> SigInfo {
+        SigInfo {
+            signo: sig as c_int,
+            errno: 0,
+            code: self.code,
+            _pad: 0,
+            pid: self.pid,
+            uid: 0,
+            value: self.value,
+            _rest: [0; 12],
+        }
+    }
+}
+
+/// A set of pending signals.
+///
+/// Signals are not queued: if a signal is already pending, sending it again
+/// has no effect, the information of the first one is kept.
+struct PendingSignals {
+    set: u64,
+    info: [PendingInfo; NSIG],
+}
+
+impl PendingSignals {
+    const fn new() -> Self {
+        Self {
+            set: 0,
+            info: [PendingInfo::EMPTY; NSIG],
+        }
+    }
+
+    fn add(&mut self, sig: usize, info: PendingInfo) {
+        if self.set & sig_bit(sig) == 0 {
+            self.set |= sig_bit(sig);
+            self.info[sig] = info;
+            PENDING_COUNT.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    fn take(&mut self, sig: usize) -> PendingInfo {
+        self.set &= !sig_bit(sig);
+        PENDING_COUNT.fetch_sub(1, Ordering::Release);
+        self.info[sig]
+    }
+
+    fn discard(&mut self, sig: usize) {
+        if self.set & sig_bit(sig) != 0 {
+            self.take(sig);
+        }
+    }
+}
+
+struct ThreadSignals {
+    task: WeakAxTaskRef,
+    mask: u64,
+    /// The mask to be restored after the pending signals are delivered, which
+    /// is replaced during a syscall by [`replace_mask`].
+    saved_mask: Option<u64>,
+    pending: PendingSignals,
+}
+
+impl ThreadSignals {
+    /// Returns the set of the pending signals that are not blocked.
+    fn deliverable(&self, process: &PendingSignals) -> u64 {
+        (self.pending.set | process.set) & !(self.mask & !UNBLOCKABLE)
+    }
+
+    fn save_mask(&mut self, mask: u64) {
+        if self.saved_mask.replace(mask).is_none() {
+            PENDING_COUNT.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    fn take_saved_mask(&mut self) -> Option<u64> {
+        let mask = self.saved_mask.take();
+        if mask.is_some() {
+            PENDING_COUNT.fetch_sub(1, Ordering::Release);
+        }
+        mask
+    }
+
+    fn restore_saved_mask(&mut self) {
+        if let Some(mask) = self.take_saved_mask() {
+            self.mask = mask;
+        }
+    }
+
+    /// Discards the pending signals and the saved mask of an exited task.
+    fn clear(&mut self) {
+        for sig in 1..NSIG {
+            self.pending.discard(sig);
+        }
+        self.take_saved_mask();
+    }
+}
+
+/// The total number of pending signals and saved masks, to quickly skip the
+/// delivery if there are none.
+static PENDING_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The signal actions of a sighand group.
+pub(crate) struct SigHand(SpinNoIrq<[SigAction; NSIG]>);
+
+impl SigHand {
+    /// Creates the actions of a new group, all of which are the default.
+    pub(crate) const fn new() -> Self {
+        Self(SpinNoIrq::new([SigAction::DEFAULT; NSIG]))
+    }
+
+    /// Returns a copy of the actions, where the caught signals are reset to
+    /// the default if `exec`, as their handlers are gone with the program.
+    #[cfg(feature = "uspace")]
+    fn copy(&self, exec: bool) -> Arc<Self> {
+        let mut actions = *self.0.lock();
+        if exec {
+            for action in actions.iter_mut().filter(|a| a.handler != SIG_IGN) {
+                *action = SigAction::DEFAULT;
+            }
+        }
+        Arc::new(Self(SpinNoIrq::new(actions)))
+    }
+}
+
+/// The signal actions of the tasks outside the user processes.
+static KERNEL_SIGHAND: SigHand = SigHand::new();
+
+/// The sighand groups of the user threads, by their task IDs.
+///
+/// It's kept apart from the threads of the processes as signals are sent in
+/// the timer IRQ handler too.
+#[cfg(feature = "uspace")]
+static SIGHANDS: SpinNoIrq<BTreeMap<u64, Arc<SigHand>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Returns the sighand group of the user thread `tid`.
+#[cfg(feature = "uspace")]
+pub(crate) fn thread_sighand(tid: u64) -> Option<Arc<SigHand>> {
+    SIGHANDS.lock().get(&tid).cloned()
+}
+
+/// Puts the user thread `tid` into the sighand group `sighand`, or removes
+/// it from its group if `sighand` is `None`.
+#[cfg(feature = "uspace")]
+pub(crate) fn set_thread_sighand(tid: u64, sighand: Option<Arc<SigHand>>) {
+    let mut sighands = SIGHANDS.lock();
+    let old = match sighand {
+        Some(sighand) => sighands.insert(tid, sighand),
+        None => sighands.remove(&tid),
+    };
+    drop(sighands);
+    drop(old);
+}
+
+/// Returns the sighand group of a task cloned from the current one, which
+/// is shared if `share` (`CLONE_SIGHAND`), or a copy otherwise.
+#[cfg(feature = "uspace")]
+pub(crate) fn clone_sighand(share: bool) -> Arc<SigHand> {
+    match thread_sighand(axtask::current().id().as_u64()) {
+        Some(sighand) if share => sighand,
+        Some(sighand) => sighand.copy(false),
+        None => KERNEL_SIGHAND.copy(false),
+    }
+}
+
+/// Returns the sighand group of a new program run by the current task, a
+/// copy of its actions where only the ignored signals are kept.
+#[cfg(feature = "uspace")]
+pub(crate) fn exec_sighand() -> Arc<SigHand> {
+    match thread_sighand(axtask::current().id().as_u64()) {
+        Some(sighand) => sighand.copy(true),
+        None => KERNEL_SIGHAND.copy(true),
+    }
+}
+static PROCESS_PENDING: SpinNoIrq<PendingSignals> = SpinNoIrq::new(PendingSignals::new());
+static THREAD_SIGNALS: SpinNoIrq<BTreeMap<u64, ThreadSignals>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Runs `f` with the signal state of `task`, which is created on the first
+/// access.
+///
+/// Task IDs may be reused, so the state is recreated if the recorded task is
+/// not `task`.
+fn with_thread<R>(task: &AxTaskRef, f: impl FnOnce(&mut ThreadSignals) -> R) -> R {
+    let mut threads = THREAD_SIGNALS.lock();
+    let id = task.id().as_u64();
+    let valid = threads
+        .get(&id)
+        .is_some_and(|t| core::ptr::eq(t.task.as_ptr(), Arc::as_ptr(task)));
+    if !valid {
+        // Drop the states of exited tasks.
+        threads.retain(|_, t| {
+            if t.task.strong_count() > 0 {
+                return true;
+            }
+            t.clear();
+            false
+        });
+        if let Some(mut t) = threads.remove(&id) {
+            t.clear();
+        }
+        threads.insert(
+            id,
+            ThreadSignals {
+                task: Arc::downgrade(task),
+                mask: 0,
+                saved_mask: None,
+                pending: PendingSignals::new(),
+            },
+        );
+    }
+    f(threads.get_mut(&id).unwrap())
+}
+
+fn with_current<R>(f: impl FnOnce(&mut ThreadSignals) -> R) -> R {
+    with_thread(axtask::current().as_task_ref(), f)
+}
+
+/// Runs `f` with the signal actions of the task `tid`.
+fn with_actions<R>(tid: u64, f: impl FnOnce(&mut [SigAction; NSIG]) -> R) -> R {
+    #[cfg(feature = "uspace")]
+    if let Some(sighand) = thread_sighand(tid) {
+        return f(&mut sighand.0.lock());
+    }
+    #[cfg(not(feature = "uspace"))]
+    let _ = tid;
+    f(&mut KERNEL_SIGHAND.0.lock())
+}
+
+fn with_current_actions<R>(f: impl FnOnce(&mut [SigAction; NSIG]) -> R) -> R {
+    with_actions(axtask::current().id().as_u64(), f)
+}
+
+fn current_pid() -> ctypes::pid_t {
+    axtask::current().id().as_u64() as ctypes::pid_t
+}
+
+/// Sends a signal to the thread `target`, or to the process if `target` is
+/// [`None`].
+///
+/// It's discarded if ignored by the actions of `target`, or of the current
+/// task for the process, which all tasks are in.
+fn send_signal(target: Option<&AxTaskRef>, sig: usize, info: PendingInfo) {
+    let tid = target.map_or_else(|| axtask::current().id().as_u64(), |t| t.id().as_u64());
+    if with_actions(tid, |actions| actions[sig].is_ignored(sig)) {
+        debug!("signal {} is ignored", sig);
+        return;
+    }
+    match target {
+        Some(task) => with_thread(task, |t| t.pending.add(sig, info)),
+        None => PROCESS_PENDING.lock().add(sig, info),
+    }
+}
+
+/// Sends a signal to the task with the given ID.
+pub(crate) fn send_signal_to_task(tid: u64, sig: c_int, code: c_int, value: usize) -> LinuxResult {
+    let sig = check_signo(sig)?;
+    let task = axtask::init_pid_ns()
+        .find_task(tid)
+        .ok_or(LinuxError::ESRCH)?;
+    let info = PendingInfo {
+        code,
+        pid: current_pid(),
+        value,
+    };
+    send_signal(Some(&task), sig, info);
+    Ok(())
+}
+
+/// Sends a signal on the expiration of a timer to the task with the given
+/// ID, or to the process if `tid` is [`None`].
+///
+/// The ID of the timer is reported in place of the sender's PID, like
+/// `si_timerid` of Linux.
+pub(crate) fn send_timer_signal(
+    tid: Option<u64>,
+    sig: c_int,
+    code: c_int,
+    timer_id: c_int,
+    value: usize,
+) -> LinuxResult {
+    let sig = check_signo(sig)?;
+    let task = match tid {
+        Some(tid) => Some(
+            axtask::init_pid_ns()
+                .find_task(tid)
+                .ok_or(LinuxError::ESRCH)?,
+        ),
+        None => None,
+    };
+    let info = PendingInfo {
+        code,
+        pid: timer_id,
+        value,
+    };
+    send_signal(task.as_ref(), sig, info);
+    Ok(())
+}
+
+/// Sends `SIGPIPE` to the current task, on writing to a socket that cannot be
+/// written any more.
+pub(crate) fn raise_sigpipe() {
+    let info = PendingInfo {
+        code: ctypes::SI_USER as _,
+        pid: current_pid(),
+        value: 0,
+    };
+    send_signal(
+        Some(axtask::current().as_task_ref()),
+        ctypes::SIGPIPE as usize,
+        info,
+    );
+}
+
+/// Removes the lowest-numbered deliverable signal of the current task from
+/// the pending sets, thread-directed signals first.
+fn dequeue_signal() -> Option<(usize, PendingInfo)> {
+    with_current(|t| {
+        let mut process = PROCESS_PENDING.lock();
+        let deliverable = t.deliverable(&process);
+        if deliverable == 0 {
+            return None;
+        }
+        let sig = deliverable.trailing_zeros() as usize + 1;
+        let info = if t.pending.set & sig_bit(sig) != 0 {
+            t.pending.take(sig)
+        } else {
+            process.take(sig)
+        };
+        Some((sig, info))
+    })
+}
+
+/// Returns whether the current task has any deliverable signals, used by
+/// blocking syscalls to return `EINTR`.
+pub(crate) fn has_pending_signal() -> bool {
+    if PENDING_COUNT.load(Ordering::Acquire) == 0 {
+        return false;
+    }
+    with_current(|t| t.deliverable(&PROCESS_PENDING.lock()) != 0)
+}
+
+/// Sets the signal mask of the current task, returns the old one.
+fn set_current_mask(mask: u64) -> u64 {
+    with_current(|t| core::mem::replace(&mut t.mask, mask & !UNBLOCKABLE))
+}
+
+/// Replaces the signal mask of the current task with the 64-bit set at
+/// `sigmask` during a syscall like `pselect6` and `ppoll`, or keeps it if
+/// `sigmask` is NULL.
+///
+/// Returns the old mask, to be passed to [`restore_mask`] before the syscall
+/// returns.
+#[cfg_attr(not(feature = "fd"), allow(dead_code))]
+pub(crate) fn replace_mask(sigmask: *const u64) -> LinuxResult<Option<u64>> {
+    if sigmask.is_null() {
+        return Ok(None);
+    }
+    let mask = get_user(sigmask)?;
+    Ok(Some(set_current_mask(mask)))
+}
+
+/// Restores the signal mask replaced by [`replace_mask`].
+///
+/// If any signals are deliverable with the replaced mask, e.g. the ones that
+/// interrupted the syscall, the old mask is restored only after they are
+/// delivered, like `set_restore_sigmask` of Linux. Otherwise they would be
+/// blocked again on the return of the syscall.
+#[cfg_attr(not(feature = "fd"), allow(dead_code))]
+pub(crate) fn restore_mask(old_mask: Option<u64>) {
+    let Some(old_mask) = old_mask else {
+        return;
+    };
+    with_current(|t| {
+        if t.deliverable(&PROCESS_PENDING.lock()) != 0 {
+            t.save_mask(old_mask);
+        } else {
+            t.mask = old_mask;
+        }
+    });
+}
+
+/// Gets the action of `sig` to be taken on delivery, and resets it to the
+/// default if `SA_RESETHAND` is specified.
+fn take_action(sig: usize) -> SigAction {
+    with_current_actions(|actions| {
+        let action = actions[sig];
+        if action.flags & ctypes::SA_RESETHAND != 0 {
+            actions[sig] = SigAction::DEFAULT;
+        }
+        action
+    })
+}
+
+/// Blocks the signals specified by the action while running its handler,
+/// returns the old signal mask.
+fn block_for_handler(sig: usize, action: &SigAction) -> u64 {
+    let mut mask = action.mask;
+    if action.flags & ctypes::SA_NODEFER == 0 {
+        mask |= sig_bit(sig);
+    }
+    with_current(|t| {
+        let old_mask = t.mask;
+        t.mask |= mask & !UNBLOCKABLE;
+        old_mask
+    })
+}
+
+/// Performs the default action of a signal.
+fn default_action(sig: usize) {
+    match sig as u32 {
+        _ if is_ignored_by_default(sig) => {}
+        ctypes::SIGSTOP | ctypes::SIGTSTP | ctypes::SIGTTIN | ctypes::SIGTTOU => {
+            warn!("signal {}: stopping is not supported, ignored", sig);
+        }
+        _ => {
+            warn!("terminated by signal {}", sig);
+            axhal::misc::terminate();
+        }
+    }
+}
+
+/// Delivers the pending signals of the current task, by running their
+/// handlers or performing the default actions.
+pub(crate) fn handle_pending_signals() {
+    if PENDING_COUNT.load(Ordering::Acquire) == 0 || axtask::current_may_uninit().is_none() {
+        return;
+    }
+    // The signals of user threads are delivered on the return to user space,
+    // where their handlers are.
+    #[cfg(feature = "uspace")]
+    if thread_sighand(axtask::current().id().as_u64()).is_some() {
+        return;
+    }
+    while let Some((sig, info)) = dequeue_signal() {
+        debug!("delivering signal {}", sig);
+        let action = take_action(sig);
+        match action.handler {
+            SIG_DFL => default_action(sig),
+            SIG_IGN => {}
+            handler => {
+                let old_mask = block_for_handler(sig, &action);
+                if action.flags & ctypes::SA_SIGINFO != 0 {
+                    let f: unsafe extern "C" fn(c_int, *mut SigInfo, *mut c_void) =
+                        unsafe { core::mem::transmute(handler) };
+                    let mut info = info.to_siginfo(sig);
+                    unsafe { f(sig as c_int, &mut info, core::ptr::null_mut()) };
+                } else {
+                    let f: unsafe extern "C" fn(c_int) = unsafe { core::mem::transmute(handler) };
+                    unsafe { f(sig as c_int) };
+                }
+                set_current_mask(old_mask);
+            }
+        }
+    }
+    with_current(|t| t.restore_saved_mask());
+}
+
+/// Examine and change a signal action.
+///
+/// The actions of `SIGKILL` and `SIGSTOP` can not be changed.
+pub fn sys_rt_sigaction(
+    signum: c_int,
+    act: *const ctypes::sigaction,
+    oldact: *mut ctypes::sigaction,
+) -> c_int {
+    debug!(
+        "sys_rt_sigaction <= signum: {}, act: {:#x}, oldact: {:#x}",
+        signum, act as usize, oldact as usize
+    );
+    syscall_body!(sys_rt_sigaction, {
+        let sig = check_signo(signum)?;
+        let new = if act.is_null() {
+            None
+        } else {
+            Some(SigAction::from_ctype(&get_user(act)?))
+        };
+        let old = change_action(sig, new)?;
+        if !oldact.is_null() {
+            put_user(oldact, old.to_ctype())?;
+        }
+        Ok(0)
+    })
+}
+
+/// Sets the action of `sig` to `new` if it is not `None`, returns the old one.
+fn change_action(sig: usize, new: Option<SigAction>) -> LinuxResult<SigAction> {
+    if new.is_some() && sig_bit(sig) & UNBLOCKABLE != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let old = with_current_actions(|actions| {
+        let old = actions[sig];
+        if let Some(new) = new {
+            actions[sig] = new;
+        }
+        old
+    });
+    // Setting the action to be ignored discards the pending signal.
+    if new.is_some_and(|new| new.is_ignored(sig)) {
+        PROCESS_PENDING.lock().discard(sig);
+        for t in THREAD_SIGNALS.lock().values_mut() {
+            t.pending.discard(sig);
+        }
+    }
+    Ok(old)
+}
+
+/// Examine and change blocked signals of the current thread.
+///
+/// Attempts to block `SIGKILL` or `SIGSTOP` are silently ignored.
+pub fn sys_rt_sigprocmask(
+    how: c_int,
+    set: *const ctypes::sigset_t,
+    oldset: *mut ctypes::sigset_t,
+) -> c_int {
+    debug!(
+        "sys_rt_sigprocmask <= how: {}, set: {:#x}, oldset: {:#x}",
+        how, set as usize, oldset as usize
+    );
+    syscall_body!(sys_rt_sigprocmask, {
+        let set = if set.is_null() {
+            None
+        } else {
+            Some(get_user(set)?.__bits[0] as u64)
+        };
+        let old_mask = change_mask(how, set)?;
+        if !oldset.is_null() {
+            let mut old = ctypes::sigset_t::default();
+            old.__bits[0] = old_mask as _;
+            put_user(oldset, old)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Changes the signal mask of the current thread by `set` as `how` if it is
+/// not `None`, returns the old mask.
+fn change_mask(how: c_int, set: Option<u64>) -> LinuxResult<u64> {
+    let Some(set) = set else {
+        return Ok(with_current(|t| t.mask));
+    };
+    with_current(|t| {
+        let old_mask = t.mask;
+        t.mask = match how as u32 {
+            ctypes::SIG_BLOCK => old_mask | set,
+            ctypes::SIG_UNBLOCK => old_mask & !set,
+            ctypes::SIG_SETMASK => set,
+            _ => return Err(LinuxError::EINVAL),
+        } & !UNBLOCKABLE;
+        Ok(old_mask)
+    })
+}
+
+/// Send a signal to a process.
+///
+/// As all tasks are in the same process, the signal is sent to the process
+/// if `pid` is the ID of any existing task, or `pid` is 0 or negative (the
+/// process group of the caller, or all processes). If `sig` is 0, only the
+/// existence of the target is checked.
+pub fn sys_kill(pid: ctypes::pid_t, sig: c_int) -> c_int {
+    debug!("sys_kill <= pid: {}, sig: {}", pid, sig);
+    syscall_body!(sys_kill, {
+        if pid > 0 && axtask::init_pid_ns().find_task(pid as u64).is_none() {
+            return Err(LinuxError::ESRCH);
+        }
+        if sig == 0 {
+            return Ok(0);
+        }
+        let sig = check_signo(sig)?;
+        let info = PendingInfo {
+            code: ctypes::SI_USER as _,
+            pid: current_pid(),
+            value: 0,
+        };
+        send_signal(None, sig, info);
+        Ok(0)
+    })
+}
+
+/// Send a signal to a thread.
+pub fn sys_tgkill(tgid: ctypes::pid_t, tid: ctypes::pid_t, sig: c_int) -> c_int {
+    debug!("sys_tgkill <= tgid: {}, tid: {}, sig: {}", tgid, tid, sig);
+    syscall_body!(sys_tgkill, {
+        if tgid <= 0 || tid <= 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let task = axtask::init_pid_ns()
+            .find_task(tid as u64)
+            .ok_or(LinuxError::ESRCH)?;
+        if sig == 0 {
+            return Ok(0);
+        }
+        let sig = check_signo(sig)?;
+        let info = PendingInfo {
+            code: ctypes::SI_TKILL,
+            pid: current_pid(),
+            value: 0,
+        };
+        send_signal(Some(&task), sig, info);
+        Ok(0)
+    })
+}
+
+#[cfg(feature = "uspace")]
+pub use self::uspace::sys_rt_sigreturn;
+#[cfg(feature = "uspace")]
+pub(crate) use self::uspace::{user_rt_sigaction, user_rt_sigprocmask};
+
+#[cfg(feature = "uspace")]
+mod uspace {
+    use core::ffi::c_ulong;
+
+    use axhal::arch::TrapFrame;
+    use axhal::trap::{POST_TRAP, register_trap_handler};
+
+    use super::*;
+
+    /// `struct sigaction` of the syscall, which differs from the one of libc.
+    ///
+    /// The signal mask is only of the 64 signals, and the architectures
+    /// without `SA_RESTORER` have no restorer.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub(crate) struct KernelSigAction {
+        handler: usize,
+        flags: c_ulong,
+        #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+        restorer: usize,
+        mask: u64,
+    }
+
+    impl KernelSigAction {
+        fn to_action(self) -> SigAction {
+            SigAction {
+                handler: self.handler,
+                flags: self.flags as u32,
+                mask: self.mask & !UNBLOCKABLE,
+                #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+                restorer: self.restorer,
+                #[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+                restorer: 0,
+            }
+        }
+
+        fn from_action(action: SigAction) -> Self {
+            Self {
+                handler: action.handler,
+                flags: action.flags as c_ulong,
+                #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+                restorer: action.restorer,
+                mask: action.mask,
+            }
+        }
+    }
+
+    /// The size of the area below the user stack pointer that must not be
+    /// touched (the red zone of x86_64).
+    const RED_ZONE_SIZE: usize = 128;
+
+    /// The frame pushed onto the user stack when delivering a signal, which
+    /// is restored by [`sys_rt_sigreturn`].
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct SignalFrame {
+        info: SigInfo,
+        tf: TrapFrame,
+        mask: u64,
+    }
+
+    /// Sets up the frame of the handler of `sig` on the user stack, or
+    /// returns `EFAULT` if the stack is not writable.
+    fn setup_frame(
+        tf: &mut TrapFrame,
+        sig: usize,
+        info: PendingInfo,
+        action: &SigAction,
+    ) -> LinuxResult {
+        let sp = (tf.sp() - RED_ZONE_SIZE - core::mem::size_of::<SignalFrame>()) & !0xf;
+        let old_mask = block_for_handler(sig, action);
+        // The mask replaced during the syscall is restored on the return of
+        // the handler instead.
+        let saved_mask = with_current(|t| t.take_saved_mask());
+        let frame = SignalFrame {
+            info: info.to_siginfo(sig),
+            tf: *tf,
+            mask: saved_mask.unwrap_or(old_mask),
+        };
+        if let Err(e) = put_user(sp as *mut SignalFrame, frame) {
+            set_current_mask(frame.mask);
+            return Err(e);
+        }
+
+        let restorer = if action.flags & ctypes::SA_RESTORER != 0 {
+            action.restorer
+        } else {
+            0
+        };
+        tf.set_ip(action.handler);
+        tf.set_sp(sp);
+        tf.set_arg0(sig);
+        tf.set_arg1(sp + core::mem::offset_of!(SignalFrame, info));
+        tf.set_arg2(sp);
+        #[cfg(target_arch = "x86_64")]
+        tf.push_ra(restorer);
+        #[cfg(not(target_arch = "x86_64"))]
+        tf.set_ra(restorer);
+        Ok(())
+    }
+
+    #[register_trap_handler(POST_TRAP)]
+    fn deliver_signal_to_user(tf: &mut TrapFrame, from_user: bool) {
+        if !from_user || PENDING_COUNT.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        while let Some((sig, info)) = dequeue_signal() {
+            debug!("delivering signal {} to user space", sig);
+            let action = take_action(sig);
+            match action.handler {
+                SIG_DFL => default_action(sig),
+                SIG_IGN => {}
+                _ => {
+                    // Only one handler frame is set up at a time, the other
+                    // signals are delivered on the next return to user space.
+                    if setup_frame(tf, sig, info, &action).is_err() {
+                        warn!("bad user stack {:#x} for signal {}", tf.sp(), sig);
+                        default_action(ctypes::SIGSEGV as usize);
+                    }
+                    return;
+                }
+            }
+        }
+        with_current(|t| t.restore_saved_mask());
+    }
+
+    /// Return from a signal handler in user space.
+    ///
+    /// Restores the trap frame and the signal mask saved in the signal frame,
+    /// which is at the user stack pointer after the handler returns. Returns
+    /// the restored value of the return value register, so that it is kept
+    /// unchanged when written back by the syscall handler.
+    pub fn sys_rt_sigreturn(tf: &mut TrapFrame) -> isize {
+        debug!("sys_rt_sigreturn <= sp: {:#x}", tf.sp());
+        let Ok(frame) = get_user(tf.sp() as *const SignalFrame) else {
+            warn!("bad signal frame at {:#x}", tf.sp());
+            default_action(ctypes::SIGSEGV as usize);
+            return -(LinuxError::EFAULT.code() as isize);
+        };
+        *tf = frame.tf;
+        set_current_mask(frame.mask);
+        tf.retval() as isize
+    }
+    /// Examine and change a signal action, with the `struct sigaction` of
+    /// the syscall for user processes.
+    ///
+    /// `sigsetsize` must be the size of the 64-bit signal mask.
+    pub(crate) fn user_rt_sigaction(
+        signum: c_int,
+        act: *const KernelSigAction,
+        oldact: *mut KernelSigAction,
+        sigsetsize: usize,
+    ) -> c_int {
+        debug!(
+            "sys_rt_sigaction <= signum: {}, act: {:#x}, oldact: {:#x}",
+            signum, act as usize, oldact as usize
+        );
+        syscall_body!(sys_rt_sigaction, {
+            if sigsetsize != size_of::<u64>() {
+                return Err(LinuxError::EINVAL);
+            }
+            let sig = check_signo(signum)?;
+            let new = if act.is_null() {
+                None
+            } else {
+                Some(get_user(act)?.to_action())
+            };
+            let old = change_action(sig, new)?;
+            if !oldact.is_null() {
+                put_user(oldact, KernelSigAction::from_action(old))?;
+            }
+            Ok(0)
+        })
+    }
+
+    /// Examine and change blocked signals of the current thread, with the
+    /// 64-bit signal sets of the syscall for user processes.
+    pub(crate) fn user_rt_sigprocmask(
+        how: c_int,
+        set: *const u64,
+        oldset: *mut u64,
+        sigsetsize: usize,
+    ) -> c_int {
+        debug!(
+            "sys_rt_sigprocmask <= how: {}, set: {:#x}, oldset: {:#x}",
+            how, set as usize, oldset as usize
+        );
+        syscall_body!(sys_rt_sigprocmask, {
+            if sigsetsize != size_of::<u64>() {
+                return Err(LinuxError::EINVAL);
+            }
+            let set = if set.is_null() {
+                None
+            } else {
+                Some(get_user(set)?)
+            };
+            let old_mask = change_mask(how, set)?;
+            if !oldset.is_null() {
+                put_user(oldset, old_mask)?;
+            }
+            Ok(0)
+        })
+    }
+}
@@ -1,4 +1,3 @@
-use alloc::vec;
 use axerrno::AxResult;
 use axio::{BufReader, prelude::*};
 use axsync::Mutex;
@@ -6,11 +5,40 @@ use axsync::Mutex;
 #[cfg(feature = "fd")]
 use {alloc::sync::Arc, axerrno::LinuxError, axerrno::LinuxResult, axio::PollState};
 
+#[cfg(feature = "fs")]
+use super::fd_ops::FileLike;
+
+/// Tasks blocked on reading the console, woken up by the UART RX IRQ.
+#[cfg(all(feature = "console-irq", feature = "multitask"))]
+static STDIN_WAIT_QUEUE: axtask::WaitQueue = axtask::WaitQueue::new();
+
+#[cfg(all(feature = "console-irq", feature = "multitask"))]
+#[ctor_bare::register_ctor]
+fn init_stdin_notifier() {
+    axhal::console::set_input_notifier(|| {
+        STDIN_WAIT_QUEUE.notify_all(false);
+        #[cfg(feature = "fd")]
+        super::fd_ops::notify_pollers();
+    });
+}
+
+/// Waits until new console input may be available.
+///
+/// If console input is interrupt-driven, the current task is blocked until
+/// the UART IRQ handler receives some bytes, otherwise it just yields.
+fn wait_for_console_input() {
+    #[cfg(all(feature = "console-irq", feature = "multitask"))]
+    if axhal::console::input_irq_enabled() {
+        STDIN_WAIT_QUEUE.wait_until(axhal::console::has_pending_input);
+        return;
+    }
+    crate::sys_sched_yield();
+}
+
+/// Reads bytes from the console into `buf`, which is always in the kernel
+/// memory, as the buffers of the user processes are bounced by `uaccess`.
 fn console_read_bytes(buf: &mut [u8]) -> AxResult<usize> {
-    // we must make sure the buffer is in kernel memory
-    let mut kernel_buf = vec![0u8; buf.len()];
-    let len = axhal::console::read_bytes(&mut kernel_buf);
-    buf.copy_from_slice(&kernel_buf);
+    let len = axhal::console::read_bytes(buf);
     for c in &mut buf[..len] {
         if *c == b'\r' {
             *c = b'\n';
@@ -91,7 +119,7 @@ impl Stdin {
             if read_len > 0 {
                 return Ok(read_len);
             }
-            crate::sys_sched_yield();
+            wait_for_console_input();
         }
     }
 }
@@ -151,6 +179,10 @@ impl super::fd_ops::FileLike for Stdin {
         })
     }
 
+    fn link_target(&self) -> alloc::string::String {
+        "/dev/console".into()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -197,6 +229,10 @@ impl super::fd_ops::FileLike for Stdout {
         })
     }
 
+    fn link_target(&self) -> alloc::string::String {
+        "/dev/console".into()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -212,3 +248,70 @@ impl super::fd_ops::FileLike for Stdout {
         Ok(())
     }
 }
+
+/// `/dev/tty`, the console, read like the standard input and written like the
+/// standard output.
+#[cfg(feature = "fs")]
+pub(crate) struct Tty {
+    stdin: Stdin,
+    stdout: Stdout,
+    nonblocking: core::sync::atomic::AtomicBool,
+}
+
+#[cfg(feature = "fs")]
+impl Tty {
+    pub(crate) fn new(nonblocking: bool) -> Self {
+        Self {
+            stdin: stdin(),
+            stdout: stdout(),
+            nonblocking: core::sync::atomic::AtomicBool::new(nonblocking),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl FileLike for Tty {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if self.is_nonblocking() && !FileLike::poll(&self.stdin)?.readable {
+            return Err(LinuxError::EAGAIN);
+        }
+        FileLike::read(&self.stdin, buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        FileLike::write(&self.stdout, buf)
+    }
+
+    fn stat(&self) -> LinuxResult<crate::ctypes::stat> {
+        let st_mode = 0o20000 | 0o666u32; // S_IFCHR | rw-rw-rw-
+        Ok(crate::ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_rdev: 5 << 8, // major number of the tty devices
+            ..Default::default()
+        })
+    }
+
+    fn link_target(&self) -> alloc::string::String {
+        "/dev/tty".into()
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        FileLike::poll(&self.stdin)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking
+            .store(nonblocking, core::sync::atomic::Ordering::Release);
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(core::sync::atomic::Ordering::Acquire)
+    }
+}
@@ -0,0 +1,401 @@
+//! The syscall handler of user tasks, which dispatches the Linux syscalls to
+//! the POSIX API by their numbers.
+//!
+//! The numbers are the ones of the Linux ABI of the target: x86_64 has its
+//! own table, and the other architectures share the generic one. Syscalls
+//! not listed here, or whose features are disabled, return `ENOSYS`, and are
+//! passed on to the other registered handlers.
+//!
+//! Pointers in the arguments are passed to the POSIX API as they are, which
+//! checks them against the address space of the process.
+
+#[cfg(target_arch = "x86_64")]
+#[path = "x86_64.rs"]
+mod sysno;
+
+#[cfg(not(target_arch = "x86_64"))]
+#[path = "generic.rs"]
+mod sysno;
+
+#[cfg(feature = "pipe")]
+use core::ffi::c_int;
+
+use axerrno::LinuxError;
+use axhal::arch::TrapFrame;
+use axhal::trap::{SYSCALL, register_trap_handler};
+
+use self::sysno::*;
+#[cfg(feature = "fd")]
+use crate::uaccess::{get_user, put_user};
+use crate::*;
+
+/// The number of the private `posix_spawn` syscall, next to
+/// [`axhal::trap::SYSCALL_BATCH`], with the arguments of [`sys_posix_spawn`].
+#[cfg(feature = "fs")]
+pub const SYS_POSIX_SPAWN: usize = axhal::trap::SYSCALL_BATCH + 1;
+
+/// Reads the `nfds` entries at `fds` of `poll`, runs `f` on them, and writes
+/// the returned events back.
+#[cfg(feature = "fd")]
+fn with_user_pollfds(fds: *mut PollFd, nfds: usize, f: impl FnOnce(&mut [PollFd]) -> i32) -> isize {
+    if nfds > AX_FILE_LIMIT {
+        return -LinuxError::EINVAL.code() as isize;
+    }
+    let mut buf = alloc::vec::Vec::with_capacity(nfds);
+    for i in 0..nfds {
+        match get_user(fds.wrapping_add(i)) {
+            Ok(fd) => buf.push(fd),
+            Err(e) => return -e.code() as isize,
+        }
+    }
+    let ret = f(&mut buf);
+    if ret >= 0 {
+        for (i, fd) in buf.into_iter().enumerate() {
+            if let Err(e) = put_user(fds.wrapping_add(i), fd) {
+                return -e.code() as isize;
+            }
+        }
+    }
+    ret as isize
+}
+
+/// Creates a pipe and writes its file descriptors to `fds`.
+///
+/// Only the flags `O_CLOEXEC` and `O_NONBLOCK` are supported.
+#[cfg(feature = "pipe")]
+fn user_pipe2(fds: *mut [c_int; 2], flags: c_int) -> isize {
+    let mut pipe = [0; 2];
+    let ret = sys_pipe2(&mut pipe, flags);
+    if ret < 0 {
+        return ret as isize;
+    }
+    if let Err(e) = put_user(fds, pipe) {
+        sys_close(pipe[0]);
+        sys_close(pipe[1]);
+        return -e.code() as isize;
+    }
+    0
+}
+
+/// Monitors the file descriptors like [`sys_pselect6`], where the signal mask
+/// is passed by a pointer to the pair of the mask and its size.
+///
+/// The mask is kept if the pair or the mask is NULL.
+#[cfg(feature = "select")]
+unsafe fn user_pselect6(
+    nfds: c_int,
+    readfds: *mut ctypes::fd_set,
+    writefds: *mut ctypes::fd_set,
+    exceptfds: *mut ctypes::fd_set,
+    timeout: *const ctypes::timespec,
+    sigmask: *const [usize; 2],
+) -> isize {
+    let mask = if sigmask.is_null() {
+        0
+    } else {
+        match get_user(sigmask) {
+            Ok([mask, size]) if mask == 0 || size == size_of::<u64>() => mask,
+            Ok(_) => return -LinuxError::EINVAL.code() as isize,
+            Err(e) => return -e.code() as isize,
+        }
+    };
+    unsafe { sys_pselect6(nfds, readfds, writefds, exceptfds, timeout, mask as _) as isize }
+}
+
+#[register_trap_handler(SYSCALL)]
+fn handle_syscall(tf: &mut TrapFrame, sysno: usize) -> isize {
+    let (a0, a1, a2, a3, a4, a5) = (
+        tf.arg0(),
+        tf.arg1(),
+        tf.arg2(),
+        tf.arg3(),
+        tf.arg4(),
+        tf.arg5(),
+    );
+    unsafe {
+        match sysno {
+            // Files and I/O.
+            SYS_READ => sys_read(a0 as _, a1 as _, a2) as isize,
+            SYS_WRITE => sys_write(a0 as _, a1 as _, a2) as isize,
+            SYS_READV => sys_readv(a0 as _, a1 as _, a2 as _) as isize,
+            SYS_WRITEV => sys_writev(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "fd")]
+            SYS_CLOSE => sys_close(a0 as _) as isize,
+            #[cfg(feature = "fd")]
+            SYS_DUP => sys_dup(a0 as _) as isize,
+            #[cfg(all(feature = "fd", target_arch = "x86_64"))]
+            SYS_DUP2 => sys_dup2(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "fd")]
+            SYS_DUP3 => sys_dup3(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "fd")]
+            SYS_FCNTL => sys_fcntl(a0 as _, a1 as _, a2) as isize,
+            #[cfg(feature = "fd")]
+            SYS_IOCTL => sys_ioctl(a0 as _, a1 as _, a2) as isize,
+            #[cfg(all(feature = "fd", target_arch = "x86_64"))]
+            SYS_POLL => with_user_pollfds(a0 as _, a1, |fds| sys_poll(fds, a2 as _)),
+            // Only the 64-bit signal masks are supported.
+            #[cfg(feature = "fd")]
+            SYS_PPOLL if a3 != 0 && a4 != size_of::<u64>() => -LinuxError::EINVAL.code() as isize,
+            #[cfg(feature = "fd")]
+            SYS_PPOLL => with_user_pollfds(a0 as _, a1, |fds| sys_ppoll(fds, a2 as _, a3 as _)),
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_OPEN => sys_open(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_OPENAT => sys_openat(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_LSEEK => sys_lseek(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_STAT => sys_stat(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_LSTAT => sys_lstat(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "fs", not(target_arch = "loongarch64")))]
+            SYS_FSTAT => sys_fstat(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "fs", not(target_arch = "loongarch64")))]
+            SYS_NEWFSTATAT => sys_fstatat(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_STATFS => sys_statfs(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_FSTATFS => sys_fstatfs(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_FSYNC => sys_fsync(a0 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_FDATASYNC => sys_fdatasync(a0 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_FLOCK => sys_flock(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_MKDIR => sys_mkdir(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_MKDIRAT => sys_mkdirat(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_RMDIR => sys_rmdir(a0 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_UNLINK => sys_unlink(a0 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_UNLINKAT => sys_unlinkat(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_LINK => sys_link(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_LINKAT => sys_linkat(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_SYMLINK => sys_symlink(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_SYMLINKAT => sys_symlinkat(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_READLINK => sys_readlink(a0 as _, a1 as _, a2) as isize,
+            #[cfg(feature = "fs")]
+            SYS_READLINKAT => sys_readlinkat(a0 as _, a1 as _, a2 as _, a3) as isize,
+            #[cfg(all(feature = "fs", target_arch = "x86_64"))]
+            SYS_RENAME => sys_rename(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "fs", not(target_arch = "loongarch64")))]
+            SYS_RENAMEAT => sys_renameat(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_RENAMEAT2 => sys_renameat2(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_SENDFILE => sys_sendfile(a0 as _, a1 as _, a2 as _, a3) as isize,
+            #[cfg(feature = "fs")]
+            SYS_QUOTACTL => sys_quotactl(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(all(feature = "pipe", target_arch = "x86_64"))]
+            SYS_PIPE => user_pipe2(a0 as _, 0),
+            #[cfg(feature = "pipe")]
+            SYS_PIPE2 => user_pipe2(a0 as _, a1 as _),
+            #[cfg(feature = "pipe")]
+            SYS_SPLICE => sys_splice(a0 as _, a1 as _, a2 as _, a3 as _, a4, a5 as _) as isize,
+            #[cfg(feature = "pipe")]
+            SYS_TEE => sys_tee(a0 as _, a1 as _, a2, a3 as _) as isize,
+            #[cfg(feature = "pipe")]
+            SYS_VMSPLICE => sys_vmsplice(a0 as _, a1 as _, a2, a3 as _) as isize,
+            #[cfg(all(feature = "select", target_arch = "x86_64"))]
+            SYS_SELECT => sys_select(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _) as isize,
+            #[cfg(feature = "select")]
+            SYS_PSELECT6 => user_pselect6(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _, a5 as _),
+            #[cfg(all(feature = "epoll", target_arch = "x86_64"))]
+            SYS_EPOLL_CREATE => sys_epoll_create(a0 as _) as isize,
+            // No flags are supported.
+            #[cfg(feature = "epoll")]
+            SYS_EPOLL_CREATE1 if a0 != 0 => -LinuxError::EINVAL.code() as isize,
+            #[cfg(feature = "epoll")]
+            SYS_EPOLL_CREATE1 => sys_epoll_create(1) as isize,
+            #[cfg(feature = "epoll")]
+            SYS_EPOLL_CTL => sys_epoll_ctl(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(all(feature = "epoll", target_arch = "x86_64"))]
+            SYS_EPOLL_WAIT => sys_epoll_wait(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            // The signal mask is ignored.
+            #[cfg(feature = "epoll")]
+            SYS_EPOLL_PWAIT => sys_epoll_wait(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(feature = "inotify")]
+            SYS_INOTIFY_INIT1 => sys_inotify_init1(a0 as _) as isize,
+            #[cfg(feature = "inotify")]
+            SYS_INOTIFY_ADD_WATCH => sys_inotify_add_watch(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "inotify")]
+            SYS_INOTIFY_RM_WATCH => sys_inotify_rm_watch(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "timerfd")]
+            SYS_TIMERFD_CREATE => sys_timerfd_create(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "timerfd")]
+            SYS_TIMERFD_SETTIME => sys_timerfd_settime(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(feature = "timerfd")]
+            SYS_TIMERFD_GETTIME => sys_timerfd_gettime(a0 as _, a1 as _) as isize,
+
+            // Memory.
+            #[cfg(feature = "mmap")]
+            SYS_BRK => sys_brk(a0 as _) as isize,
+            #[cfg(feature = "mmap")]
+            SYS_MMAP => sys_mmap(a0 as _, a1, a2 as _, a3 as _, a4 as _, a5 as _) as isize,
+            #[cfg(feature = "mmap")]
+            SYS_MUNMAP => sys_munmap(a0 as _, a1) as isize,
+            #[cfg(feature = "mmap")]
+            SYS_MPROTECT => sys_mprotect(a0 as _, a1, a2 as _) as isize,
+            SYS_MEMBARRIER => sys_membarrier(a0 as _, a1 as _, a2 as _) as isize,
+
+            // Processes.
+            #[cfg(target_arch = "x86_64")]
+            SYS_CLONE => sys_clone(tf, a0 as _, a1, a2 as _, a4, a3 as _) as isize,
+            #[cfg(not(target_arch = "x86_64"))]
+            SYS_CLONE => sys_clone(tf, a0 as _, a1, a2 as _, a3, a4 as _) as isize,
+            SYS_CLONE3 => sys_clone3(tf, a0 as _, a1) as isize,
+            #[cfg(target_arch = "x86_64")]
+            SYS_FORK => sys_fork(tf) as isize,
+            #[cfg(target_arch = "x86_64")]
+            SYS_VFORK => sys_vfork(tf) as isize,
+            #[cfg(feature = "fs")]
+            SYS_EXECVE => sys_execve(tf, a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "fs")]
+            SYS_POSIX_SPAWN => {
+                sys_posix_spawn(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _, a5 as _) as isize
+            }
+            SYS_WAIT4 => sys_wait4(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            SYS_SET_TID_ADDRESS => sys_set_tid_address(a0 as _) as isize,
+            #[cfg(target_arch = "x86_64")]
+            SYS_ARCH_PRCTL => sys_arch_prctl(tf, a0 as _, a1) as isize,
+            // Other threads of the process are not terminated.
+            SYS_EXIT | SYS_EXIT_GROUP => sys_exit(a0 as _),
+
+            // Tasks.
+            SYS_GETPID => sys_getpid() as isize,
+            SYS_GETTID => axtask::current().id().as_u64() as isize,
+            SYS_SCHED_YIELD => sys_sched_yield() as isize,
+            SYS_SCHED_SETAFFINITY => sys_sched_setaffinity(a0 as _, a1, a2 as _) as isize,
+            SYS_SCHED_GETAFFINITY => sys_sched_getaffinity(a0 as _, a1, a2 as _) as isize,
+            SYS_GETPRIORITY => sys_getpriority(a0 as _, a1 as _) as isize,
+            SYS_SETPRIORITY => sys_setpriority(a0 as _, a1 as _, a2 as _) as isize,
+            SYS_FUTEX => sys_futex(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _, a5 as _) as isize,
+            SYS_PRCTL => sys_prctl(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _) as isize,
+            SYS_PERSONALITY => sys_personality(a0 as _) as isize,
+            #[cfg(not(target_arch = "loongarch64"))]
+            SYS_GETRLIMIT => sys_getrlimit(a0 as _, a1 as _) as isize,
+            #[cfg(not(target_arch = "loongarch64"))]
+            SYS_SETRLIMIT => sys_setrlimit(a0 as _, a1 as _) as isize,
+            SYS_PRLIMIT64 => sys_prlimit64(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            SYS_GETRANDOM => sys_getrandom(a0 as _, a1, a2 as _) as isize,
+
+            // Signals.
+            #[cfg(feature = "signal")]
+            SYS_KILL => sys_kill(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "signal")]
+            SYS_TGKILL => sys_tgkill(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "signal")]
+            SYS_RT_SIGACTION => {
+                super::signal::user_rt_sigaction(a0 as _, a1 as _, a2 as _, a3) as isize
+            }
+            #[cfg(feature = "signal")]
+            SYS_RT_SIGPROCMASK => {
+                super::signal::user_rt_sigprocmask(a0 as _, a1 as _, a2 as _, a3) as isize
+            }
+            #[cfg(feature = "signal")]
+            SYS_RT_SIGRETURN => sys_rt_sigreturn(tf),
+
+            // Time.
+            SYS_NANOSLEEP => sys_nanosleep(a0 as _, a1 as _) as isize,
+            SYS_CLOCK_GETTIME => sys_clock_gettime(a0 as _, a1 as _) as isize,
+            SYS_CLOCK_GETRES => sys_clock_getres(a0 as _, a1 as _) as isize,
+            SYS_CLOCK_SETTIME => sys_clock_settime(a0 as _, a1 as _) as isize,
+            SYS_CLOCK_NANOSLEEP => sys_clock_nanosleep(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            SYS_GETTIMEOFDAY => sys_get_time_of_day(a0 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_GETITIMER => sys_getitimer(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_SETITIMER => sys_setitimer(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_TIMER_CREATE => sys_timer_create(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_TIMER_SETTIME => sys_timer_settime(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_TIMER_GETTIME => sys_timer_gettime(a0 as _, a1 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_TIMER_GETOVERRUN => sys_timer_getoverrun(a0 as _) as isize,
+            #[cfg(all(feature = "signal", feature = "irq"))]
+            SYS_TIMER_DELETE => sys_timer_delete(a0 as _) as isize,
+
+            // IPC.
+            #[cfg(feature = "mqueue")]
+            SYS_MQ_OPEN => sys_mq_open(a0 as _, a1 as _, a2 as _, a3 as _) as isize,
+            #[cfg(feature = "mqueue")]
+            SYS_MQ_UNLINK => sys_mq_unlink(a0 as _) as isize,
+            #[cfg(feature = "mqueue")]
+            SYS_MQ_TIMEDSEND => sys_mq_timedsend(a0 as _, a1 as _, a2, a3 as _, a4 as _) as isize,
+            #[cfg(feature = "mqueue")]
+            SYS_MQ_TIMEDRECEIVE => {
+                sys_mq_timedreceive(a0 as _, a1 as _, a2, a3 as _, a4 as _) as isize
+            }
+            #[cfg(feature = "mqueue")]
+            SYS_MQ_NOTIFY => sys_mq_notify(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "mqueue")]
+            SYS_MQ_GETSETATTR => sys_mq_getsetattr(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_MSGGET => sys_msgget(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_MSGSND => sys_msgsnd(a0 as _, a1 as _, a2, a3 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_MSGRCV => sys_msgrcv(a0 as _, a1 as _, a2, a3 as _, a4 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_MSGCTL => sys_msgctl(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_SEMGET => sys_semget(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_SEMOP => sys_semop(a0 as _, a1 as _, a2) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_SEMTIMEDOP => sys_semtimedop(a0 as _, a1 as _, a2, a3 as _) as isize,
+            #[cfg(feature = "sysvipc")]
+            SYS_SEMCTL => sys_semctl(a0 as _, a1 as _, a2 as _, a3) as isize,
+            #[cfg(all(feature = "sysvipc", feature = "shm"))]
+            SYS_SHMGET => sys_shmget(a0 as _, a1, a2 as _) as isize,
+            #[cfg(all(feature = "sysvipc", feature = "shm"))]
+            SYS_SHMAT => sys_shmat(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(all(feature = "sysvipc", feature = "shm"))]
+            SYS_SHMDT => sys_shmdt(a0 as _) as isize,
+            #[cfg(all(feature = "sysvipc", feature = "shm"))]
+            SYS_SHMCTL => sys_shmctl(a0 as _, a1 as _, a2 as _) as isize,
+
+            // Sockets.
+            #[cfg(feature = "net")]
+            SYS_SOCKET => sys_socket(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_BIND => sys_bind(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_LISTEN => sys_listen(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_ACCEPT => sys_accept(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_CONNECT => sys_connect(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_GETSOCKNAME => sys_getsockname(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_GETPEERNAME => sys_getpeername(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_SENDTO => sys_sendto(a0 as _, a1 as _, a2, a3 as _, a4 as _, a5 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_RECVFROM => sys_recvfrom(a0 as _, a1 as _, a2, a3 as _, a4 as _, a5 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_SENDMSG => sys_sendmsg(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_RECVMSG => sys_recvmsg(a0 as _, a1 as _, a2 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_SHUTDOWN => sys_shutdown(a0 as _, a1 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_SETSOCKOPT => sys_setsockopt(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _) as isize,
+            #[cfg(feature = "net")]
+            SYS_GETSOCKOPT => sys_getsockopt(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _) as isize,
+
+            _ => -LinuxError::ENOSYS.code() as isize,
+        }
+    }
+}
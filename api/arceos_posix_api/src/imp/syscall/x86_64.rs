@@ -0,0 +1,139 @@
+//! The syscall numbers of x86_64.
+
+pub const SYS_READ: usize = 0;
+pub const SYS_WRITE: usize = 1;
+pub const SYS_OPEN: usize = 2;
+pub const SYS_CLOSE: usize = 3;
+pub const SYS_STAT: usize = 4;
+pub const SYS_FSTAT: usize = 5;
+pub const SYS_LSTAT: usize = 6;
+pub const SYS_POLL: usize = 7;
+pub const SYS_LSEEK: usize = 8;
+pub const SYS_MMAP: usize = 9;
+pub const SYS_MPROTECT: usize = 10;
+pub const SYS_MUNMAP: usize = 11;
+pub const SYS_BRK: usize = 12;
+pub const SYS_RT_SIGACTION: usize = 13;
+pub const SYS_RT_SIGPROCMASK: usize = 14;
+pub const SYS_RT_SIGRETURN: usize = 15;
+pub const SYS_IOCTL: usize = 16;
+pub const SYS_READV: usize = 19;
+pub const SYS_WRITEV: usize = 20;
+pub const SYS_PIPE: usize = 22;
+pub const SYS_SELECT: usize = 23;
+pub const SYS_SCHED_YIELD: usize = 24;
+pub const SYS_SHMGET: usize = 29;
+pub const SYS_SHMAT: usize = 30;
+pub const SYS_SHMCTL: usize = 31;
+pub const SYS_DUP: usize = 32;
+pub const SYS_DUP2: usize = 33;
+pub const SYS_NANOSLEEP: usize = 35;
+pub const SYS_GETITIMER: usize = 36;
+pub const SYS_SETITIMER: usize = 38;
+pub const SYS_GETPID: usize = 39;
+pub const SYS_SENDFILE: usize = 40;
+pub const SYS_SOCKET: usize = 41;
+pub const SYS_CONNECT: usize = 42;
+pub const SYS_ACCEPT: usize = 43;
+pub const SYS_SENDTO: usize = 44;
+pub const SYS_RECVFROM: usize = 45;
+pub const SYS_SENDMSG: usize = 46;
+pub const SYS_RECVMSG: usize = 47;
+pub const SYS_SHUTDOWN: usize = 48;
+pub const SYS_BIND: usize = 49;
+pub const SYS_LISTEN: usize = 50;
+pub const SYS_GETSOCKNAME: usize = 51;
+pub const SYS_GETPEERNAME: usize = 52;
+pub const SYS_SETSOCKOPT: usize = 54;
+pub const SYS_GETSOCKOPT: usize = 55;
+pub const SYS_CLONE: usize = 56;
+pub const SYS_FORK: usize = 57;
+pub const SYS_VFORK: usize = 58;
+pub const SYS_EXECVE: usize = 59;
+pub const SYS_EXIT: usize = 60;
+pub const SYS_WAIT4: usize = 61;
+pub const SYS_KILL: usize = 62;
+pub const SYS_SEMGET: usize = 64;
+pub const SYS_SEMOP: usize = 65;
+pub const SYS_SEMCTL: usize = 66;
+pub const SYS_SHMDT: usize = 67;
+pub const SYS_MSGGET: usize = 68;
+pub const SYS_MSGSND: usize = 69;
+pub const SYS_MSGRCV: usize = 70;
+pub const SYS_MSGCTL: usize = 71;
+pub const SYS_FCNTL: usize = 72;
+pub const SYS_FLOCK: usize = 73;
+pub const SYS_FSYNC: usize = 74;
+pub const SYS_FDATASYNC: usize = 75;
+pub const SYS_RENAME: usize = 82;
+pub const SYS_MKDIR: usize = 83;
+pub const SYS_RMDIR: usize = 84;
+pub const SYS_LINK: usize = 86;
+pub const SYS_UNLINK: usize = 87;
+pub const SYS_SYMLINK: usize = 88;
+pub const SYS_READLINK: usize = 89;
+pub const SYS_GETTIMEOFDAY: usize = 96;
+pub const SYS_GETRLIMIT: usize = 97;
+pub const SYS_PERSONALITY: usize = 135;
+pub const SYS_STATFS: usize = 137;
+pub const SYS_FSTATFS: usize = 138;
+pub const SYS_GETPRIORITY: usize = 140;
+pub const SYS_SETPRIORITY: usize = 141;
+pub const SYS_PRCTL: usize = 157;
+pub const SYS_ARCH_PRCTL: usize = 158;
+pub const SYS_SETRLIMIT: usize = 160;
+pub const SYS_QUOTACTL: usize = 179;
+pub const SYS_GETTID: usize = 186;
+pub const SYS_FUTEX: usize = 202;
+pub const SYS_SCHED_SETAFFINITY: usize = 203;
+pub const SYS_SCHED_GETAFFINITY: usize = 204;
+pub const SYS_EPOLL_CREATE: usize = 213;
+pub const SYS_SET_TID_ADDRESS: usize = 218;
+pub const SYS_SEMTIMEDOP: usize = 220;
+pub const SYS_TIMER_CREATE: usize = 222;
+pub const SYS_TIMER_SETTIME: usize = 223;
+pub const SYS_TIMER_GETTIME: usize = 224;
+pub const SYS_TIMER_GETOVERRUN: usize = 225;
+pub const SYS_TIMER_DELETE: usize = 226;
+pub const SYS_CLOCK_SETTIME: usize = 227;
+pub const SYS_CLOCK_GETTIME: usize = 228;
+pub const SYS_CLOCK_GETRES: usize = 229;
+pub const SYS_CLOCK_NANOSLEEP: usize = 230;
+pub const SYS_EXIT_GROUP: usize = 231;
+pub const SYS_EPOLL_WAIT: usize = 232;
+pub const SYS_EPOLL_CTL: usize = 233;
+pub const SYS_TGKILL: usize = 234;
+pub const SYS_MQ_OPEN: usize = 240;
+pub const SYS_MQ_UNLINK: usize = 241;
+pub const SYS_MQ_TIMEDSEND: usize = 242;
+pub const SYS_MQ_TIMEDRECEIVE: usize = 243;
+pub const SYS_MQ_NOTIFY: usize = 244;
+pub const SYS_MQ_GETSETATTR: usize = 245;
+pub const SYS_INOTIFY_ADD_WATCH: usize = 254;
+pub const SYS_INOTIFY_RM_WATCH: usize = 255;
+pub const SYS_OPENAT: usize = 257;
+pub const SYS_MKDIRAT: usize = 258;
+pub const SYS_NEWFSTATAT: usize = 262;
+pub const SYS_UNLINKAT: usize = 263;
+pub const SYS_RENAMEAT: usize = 264;
+pub const SYS_LINKAT: usize = 265;
+pub const SYS_SYMLINKAT: usize = 266;
+pub const SYS_READLINKAT: usize = 267;
+pub const SYS_PSELECT6: usize = 270;
+pub const SYS_PPOLL: usize = 271;
+pub const SYS_SPLICE: usize = 275;
+pub const SYS_TEE: usize = 276;
+pub const SYS_VMSPLICE: usize = 278;
+pub const SYS_EPOLL_PWAIT: usize = 281;
+pub const SYS_TIMERFD_CREATE: usize = 283;
+pub const SYS_TIMERFD_SETTIME: usize = 286;
+pub const SYS_TIMERFD_GETTIME: usize = 287;
+pub const SYS_EPOLL_CREATE1: usize = 291;
+pub const SYS_DUP3: usize = 292;
+pub const SYS_PIPE2: usize = 293;
+pub const SYS_INOTIFY_INIT1: usize = 294;
+pub const SYS_PRLIMIT64: usize = 302;
+pub const SYS_RENAMEAT2: usize = 316;
+pub const SYS_GETRANDOM: usize = 318;
+pub const SYS_MEMBARRIER: usize = 324;
+pub const SYS_CLONE3: usize = 435;
@@ -1,4 +1,18 @@
+#[cfg(feature = "multitask")]
+use alloc::{vec, vec::Vec};
 use core::ffi::c_int;
+#[cfg(feature = "multitask")]
+use core::{ffi::c_ulong, time::Duration};
+
+#[cfg(feature = "multitask")]
+use axerrno::{LinuxError, LinuxResult};
+#[cfg(feature = "multitask")]
+use axtask::{AxCpuMask, AxTaskRef};
+
+#[cfg(feature = "multitask")]
+use crate::ctypes;
+#[cfg(feature = "multitask")]
+use crate::uaccess::{copy_to_user, with_user_buf};
 
 /// Relinquish the CPU, and switches to another task.
 ///
@@ -11,7 +25,7 @@ pub fn sys_sched_yield() -> c_int {
     if cfg!(feature = "irq") {
         axhal::arch::wait_for_irqs();
     } else {
-        core::hint::spin_loop();
+        axhal::arch::cpu_relax();
     }
     0
 }
@@ -19,6 +33,10 @@ pub fn sys_sched_yield() -> c_int {
 /// Get current thread ID.
 pub fn sys_getpid() -> c_int {
     syscall_body!(sys_getpid,
+        #[cfg(feature = "uspace")]
+        if let Some(pid) = super::process::current_pid() {
+            return Ok(pid as c_int);
+        }
         #[cfg(feature = "multitask")]
         {
             Ok(axtask::current().id().as_u64() as c_int)
@@ -33,8 +51,214 @@ pub fn sys_getpid() -> c_int {
 /// Exit current task
 pub fn sys_exit(exit_code: c_int) -> ! {
     debug!("sys_exit <= {}", exit_code);
+    #[cfg(feature = "fs")]
+    axfs::api::sync().ok();
+    #[cfg(feature = "uspace")]
+    super::process::exit_current(exit_code);
     #[cfg(feature = "multitask")]
     axtask::exit(exit_code);
     #[cfg(not(feature = "multitask"))]
     axhal::misc::terminate();
 }
+
+/// Blocks the current task on `wq` until `condition` becomes true, or the
+/// absolute (`CLOCK_REALTIME`) `deadline` has passed.
+#[cfg(feature = "multitask")]
+pub(crate) fn wait_until_deadline<F>(
+    wq: &axtask::WaitQueue,
+    deadline: Option<Duration>,
+    condition: F,
+) -> LinuxResult
+where
+    F: Fn() -> bool,
+{
+    if wq.wait_until_deadline(deadline, condition) {
+        Err(LinuxError::ETIMEDOUT)
+    } else {
+        Ok(())
+    }
+}
+
+/// The lowest nice value, i.e. the highest priority.
+#[cfg(feature = "multitask")]
+const NICE_MIN: c_int = -20;
+/// The highest nice value, i.e. the lowest priority.
+#[cfg(feature = "multitask")]
+const NICE_MAX: c_int = 19;
+
+/// Returns the tasks selected by `which` and `who` of `getpriority` and
+/// `setpriority`.
+///
+/// There is only one process group and one user, which are selected by a
+/// `who` of 0 and have all the tasks.
+#[cfg(feature = "multitask")]
+fn priority_tasks(which: c_int, who: c_int) -> LinuxResult<Vec<AxTaskRef>> {
+    if who < 0 {
+        return Err(LinuxError::ESRCH);
+    }
+    match which as u32 {
+        ctypes::PRIO_PROCESS if who == 0 => Ok(vec![axtask::current().as_task_ref().clone()]),
+        ctypes::PRIO_PROCESS => axtask::init_pid_ns()
+            .find_task(who as u64)
+            .map(|task| vec![task])
+            .ok_or(LinuxError::ESRCH),
+        ctypes::PRIO_PGRP | ctypes::PRIO_USER if who == 0 => {
+            let pid_ns = axtask::init_pid_ns();
+            Ok(pid_ns
+                .task_ids()
+                .into_iter()
+                .filter_map(|id| pid_ns.find_task(id))
+                .filter(|task| !task.is_idle())
+                .collect())
+        }
+        ctypes::PRIO_PGRP | ctypes::PRIO_USER => Err(LinuxError::ESRCH),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Get the scheduling priority of the tasks selected by `which` and `who`,
+/// the highest one if there are several.
+///
+/// Return `20 - nice` like the Linux syscall, in the range of 1 to 40, to
+/// avoid negative values.
+#[cfg(feature = "multitask")]
+pub fn sys_getpriority(which: c_int, who: c_int) -> c_int {
+    debug!("sys_getpriority <= which: {}, who: {}", which, who);
+    syscall_body!(sys_getpriority, {
+        let nice = priority_tasks(which, who)?
+            .iter()
+            .map(|task| task.priority() as c_int)
+            .min()
+            .ok_or(LinuxError::ESRCH)?;
+        Ok(20 - nice)
+    })
+}
+
+/// Set the scheduling priority, i.e. the nice value, of the tasks selected
+/// by `which` and `who`. The nice value is clamped to -20 to 19, where the
+/// lower values get the larger shares of the CPU.
+///
+/// Return `EINVAL` if the scheduler has no priorities, only the CFS
+/// scheduler (`sched_cfs`) has.
+#[cfg(feature = "multitask")]
+pub fn sys_setpriority(which: c_int, who: c_int, prio: c_int) -> c_int {
+    debug!(
+        "sys_setpriority <= which: {}, who: {}, prio: {}",
+        which, who, prio
+    );
+    syscall_body!(sys_setpriority, {
+        let nice = prio.clamp(NICE_MIN, NICE_MAX) as isize;
+        let tasks = priority_tasks(which, who)?;
+        if tasks.is_empty() {
+            return Err(LinuxError::ESRCH);
+        }
+        for task in &tasks {
+            if !axtask::set_task_priority(task, nice) {
+                return Err(LinuxError::EINVAL);
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Add `inc` to the nice value of the current task, see [`sys_setpriority`].
+#[cfg(feature = "multitask")]
+pub fn sys_nice(inc: c_int) -> c_int {
+    debug!("sys_nice <= inc: {}", inc);
+    syscall_body!(sys_nice, {
+        let curr = axtask::current();
+        let nice = (curr.priority() as c_int).saturating_add(inc);
+        let nice = nice.clamp(NICE_MIN, NICE_MAX) as isize;
+        if !axtask::set_task_priority(curr.as_task_ref(), nice) {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// The size in bytes of the CPU masks of the tasks, rounded up to `c_ulong`
+/// like Linux.
+#[cfg(feature = "multitask")]
+const CPU_MASK_SIZE: usize = axconfig::SMP.div_ceil(c_ulong::BITS as usize) * size_of::<c_ulong>();
+
+/// Returns the task `pid` for `sched_setaffinity` and `sched_getaffinity`,
+/// or the current task if `pid` is 0.
+#[cfg(feature = "multitask")]
+fn affinity_task(pid: c_int) -> LinuxResult<AxTaskRef> {
+    match pid {
+        0 => Ok(axtask::current().as_task_ref().clone()),
+        pid if pid > 0 => axtask::init_pid_ns()
+            .find_task(pid as u64)
+            .ok_or(LinuxError::ESRCH),
+        _ => Err(LinuxError::ESRCH),
+    }
+}
+
+/// Set the CPU affinity of the task `pid`, or the current task if `pid` is
+/// 0, to the CPUs in the first `cpusetsize` bytes of `mask`.
+///
+/// The CPUs beyond the ones of the system are ignored, and `EINVAL` is
+/// returned if no CPU of the system is in `mask`. See
+/// [`axtask::set_task_affinity`] for when the task moves to the CPUs.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    mask: *const ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_setaffinity <= pid: {}, cpusetsize: {}",
+        pid, cpusetsize
+    );
+    syscall_body!(sys_sched_setaffinity, {
+        // The bits are indexed by bytes, which is the same as by `c_ulong`s on
+        // the little-endian architectures.
+        let cpumask = with_user_buf(mask as *const u8, cpusetsize, |bytes| {
+            let mut cpumask = AxCpuMask::new();
+            for cpu in 0..axconfig::SMP.min(bytes.len() * 8) {
+                if bytes[cpu / 8] & (1 << (cpu % 8)) != 0 {
+                    cpumask.set(cpu, true);
+                }
+            }
+            Ok(cpumask)
+        })?;
+        let task = affinity_task(pid)?;
+        if !axtask::set_task_affinity(&task, cpumask) {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Get the CPU affinity of the task `pid`, or the current task if `pid` is
+/// 0, into `mask` of `cpusetsize` bytes.
+///
+/// `cpusetsize` must be a multiple of `sizeof(unsigned long)` large enough
+/// for the CPUs of the system. Like the Linux syscall, only the bytes for
+/// the CPUs of the system are written, and their size is returned.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_getaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    mask: *mut ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_getaffinity <= pid: {}, cpusetsize: {}",
+        pid, cpusetsize
+    );
+    syscall_body!(sys_sched_getaffinity, {
+        if cpusetsize < CPU_MASK_SIZE || cpusetsize % size_of::<c_ulong>() != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if mask.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let cpumask = affinity_task(pid)?.cpumask();
+        let mut bytes = [0u8; CPU_MASK_SIZE];
+        for cpu in (0..axconfig::SMP).filter(|&cpu| cpumask.get(cpu)) {
+            bytes[cpu / 8] |= 1 << (cpu % 8);
+        }
+        copy_to_user(mask as *mut u8, &bytes)?;
+        Ok(CPU_MASK_SIZE as c_int)
+    })
+}
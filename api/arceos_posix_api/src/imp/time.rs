@@ -1,9 +1,15 @@
-use axerrno::LinuxError;
+use axerrno::{LinuxError, LinuxResult};
+use axsync::SeqLock;
 use core::ffi::{c_int, c_long};
 use core::time::Duration;
 
 use crate::ctypes;
-use crate::ctypes::{CLOCK_MONOTONIC, CLOCK_REALTIME};
+use crate::ctypes::{
+    CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE, CLOCK_MONOTONIC_RAW,
+    CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_REALTIME_COARSE, CLOCK_THREAD_CPUTIME_ID,
+    TIMER_ABSTIME,
+};
+use crate::uaccess::{get_user, put_user};
 
 impl From<ctypes::timespec> for Duration {
     fn from(ts: ctypes::timespec) -> Self {
@@ -35,57 +41,195 @@ impl From<Duration> for ctypes::timeval {
     }
 }
 
-/// Get clock time since booting
+/// A reference point of `CLOCK_REALTIME` set by `clock_settime`: the wall time
+/// in nanoseconds at the given monotonic time.
+#[derive(Clone, Copy)]
+struct RealtimeBase {
+    monotonic_nanos: u64,
+    wall_nanos: u64,
+}
+
+/// The reference point of `CLOCK_REALTIME`, or `None` to use the RTC time.
+///
+/// It is read on every `clock_gettime(CLOCK_REALTIME)`, so a sequence lock is
+/// used to keep readers lock-free.
+static REALTIME_BASE: SeqLock<Option<RealtimeBase>> = SeqLock::new(None);
+
+pub(crate) fn realtime_now() -> Duration {
+    match REALTIME_BASE.read() {
+        Some(base) => {
+            let elapsed = axhal::time::monotonic_time_nanos() - base.monotonic_nanos;
+            Duration::from_nanos(base.wall_nanos + elapsed)
+        }
+        None => axhal::time::wall_time(),
+    }
+}
+
+/// Returns the CPU time of the process, which is all the tasks.
+fn process_cpu_time() -> Duration {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::tasks_cpu_time()
+    }
+    // The only task has been running since booting.
+    #[cfg(not(feature = "multitask"))]
+    axhal::time::monotonic_time()
+}
+
+/// Returns the CPU time of the current thread.
+fn thread_cpu_time() -> Duration {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::current().cpu_time()
+    }
+    #[cfg(not(feature = "multitask"))]
+    axhal::time::monotonic_time()
+}
+
+/// Returns the current time of the clock `clk`.
+///
+/// The raw, coarse and boot time variants are the same as the plain clocks,
+/// as the time is never adjusted gradually, and the system never suspends.
+fn clock_now(clk: ctypes::clockid_t) -> LinuxResult<Duration> {
+    Ok(match clk as u32 {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => realtime_now(),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            axhal::time::monotonic_time()
+        }
+        CLOCK_PROCESS_CPUTIME_ID => process_cpu_time(),
+        CLOCK_THREAD_CPUTIME_ID => thread_cpu_time(),
+        _ => {
+            warn!("unsupported clock {}", clk);
+            return Err(LinuxError::EINVAL);
+        }
+    })
+}
+
+pub(crate) fn timespec_to_duration(ts: &ctypes::timespec) -> LinuxResult<Duration> {
+    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*ts))
+}
+
+/// Sleeps for `dur`, returns the remaining time if it is interrupted.
+fn sleep(dur: Duration) -> Option<Duration> {
+    let deadline = axhal::time::wall_time() + dur;
+    axtask::sleep_until(deadline);
+    deadline
+        .checked_sub(axhal::time::wall_time())
+        .filter(|diff| !diff.is_zero())
+}
+
+/// Get the time of the clock `clk`
+///
+/// `CLOCK_PROCESS_CPUTIME_ID` counts the CPU time of all tasks, and
+/// `CLOCK_THREAD_CPUTIME_ID` counts the CPU time of the current task.
 pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
     syscall_body!(sys_clock_gettime, {
-        if ts.is_null() {
-            return Err(LinuxError::EFAULT);
-        }
-        let now = match clk as u32 {
-            CLOCK_REALTIME => axhal::time::wall_time().into(),
-            CLOCK_MONOTONIC => axhal::time::monotonic_time().into(),
-            _ => {
-                warn!("Called sys_clock_gettime for unsupported clock {}", clk);
-                return Err(LinuxError::EINVAL);
-            }
-        };
-        unsafe { *ts = now };
+        let now: ctypes::timespec = clock_now(clk)?.into();
+        put_user(ts, now)?;
         debug!("sys_clock_gettime: {}.{:09}s", now.tv_sec, now.tv_nsec);
         Ok(0)
     })
 }
 
+/// Get the resolution of the clock `clk`
+pub unsafe fn sys_clock_getres(clk: ctypes::clockid_t, res: *mut ctypes::timespec) -> c_int {
+    syscall_body!(sys_clock_getres, {
+        clock_now(clk)?;
+        // All clocks are counted by the hardware timer.
+        let nanos = axhal::time::ticks_to_nanos(1).max(1);
+        if !res.is_null() {
+            put_user(res, Duration::from_nanos(nanos).into())?;
+        }
+        Ok(0)
+    })
+}
+
+/// Set the time of the clock
+///
+/// Only `CLOCK_REALTIME` can be set.
+pub unsafe fn sys_clock_settime(clk: ctypes::clockid_t, ts: *const ctypes::timespec) -> c_int {
+    syscall_body!(sys_clock_settime, {
+        let ts = get_user(ts)?;
+        debug!(
+            "sys_clock_settime <= {} {}.{:09}s",
+            clk, ts.tv_sec, ts.tv_nsec
+        );
+        if clk as u32 != CLOCK_REALTIME
+            || ts.tv_sec < 0
+            || !(0..1_000_000_000).contains(&ts.tv_nsec)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        REALTIME_BASE.set(Some(RealtimeBase {
+            monotonic_nanos: axhal::time::monotonic_time_nanos(),
+            wall_nanos: Duration::from(ts).as_nanos() as u64,
+        }));
+        Ok(0)
+    })
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
 pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::timespec) -> c_int {
     syscall_body!(sys_nanosleep, {
-        unsafe {
-            if req.is_null() || (*req).tv_nsec < 0 || (*req).tv_nsec > 999999999 {
-                return Err(LinuxError::EINVAL);
-            }
+        if req.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let req = get_user(req)?;
+        if req.tv_nsec < 0 || req.tv_nsec > 999999999 {
+            return Err(LinuxError::EINVAL);
         }
 
-        let dur = unsafe {
-            debug!("sys_nanosleep <= {}.{:09}s", (*req).tv_sec, (*req).tv_nsec);
-            Duration::from(*req)
-        };
-
-        let now = axhal::time::monotonic_time();
-
-        // FIXME: this blocks for single-core, probably irq is disabled by a spin lock
-        // so we use busy wait instead of axtask::sleep to prevent deadlock
-        // #[cfg(feature = "multitask")]
-        // axtask::sleep(dur);
-        // #[cfg(not(feature = "multitask"))]
-        axhal::time::busy_wait(dur);
-
-        let after = axhal::time::monotonic_time();
-        let actual = after - now;
+        debug!("sys_nanosleep <= {}.{:09}s", req.tv_sec, req.tv_nsec);
+        let dur = Duration::from(req);
 
-        if let Some(diff) = dur.checked_sub(actual) {
+        if let Some(diff) = sleep(dur) {
             if !rem.is_null() {
-                unsafe { (*rem) = diff.into() };
+                put_user(rem, diff.into())?;
+            }
+            return Err(LinuxError::EINTR);
+        }
+        Ok(0)
+    })
+}
+
+/// Sleep on the clock `clk`, for the duration `req`, or until `req` if
+/// `flags` contains `TIMER_ABSTIME`
+///
+/// The remaining time is stored in `rem` if it is interrupted, and the sleep
+/// is relative. Sleeping on the CPU time clocks is not supported.
+pub unsafe fn sys_clock_nanosleep(
+    clk: ctypes::clockid_t,
+    flags: c_int,
+    req: *const ctypes::timespec,
+    rem: *mut ctypes::timespec,
+) -> c_int {
+    syscall_body!(sys_clock_nanosleep, {
+        let req = get_user(req)?;
+        debug!(
+            "sys_clock_nanosleep <= clk: {}, flags: {:#x}, {}.{:09}s",
+            clk, flags, req.tv_sec, req.tv_nsec
+        );
+        let req = timespec_to_duration(&req)?;
+        match clk as u32 {
+            CLOCK_THREAD_CPUTIME_ID => return Err(LinuxError::EINVAL),
+            CLOCK_PROCESS_CPUTIME_ID => return Err(LinuxError::EOPNOTSUPP),
+            _ => {}
+        }
+        let now = clock_now(clk)?;
+        let absolute = flags as u32 & TIMER_ABSTIME != 0;
+        let dur = if absolute {
+            req.saturating_sub(now)
+        } else {
+            req
+        };
+        if let Some(diff) = sleep(dur) {
+            if !absolute && !rem.is_null() {
+                put_user(rem, diff.into())?;
             }
             return Err(LinuxError::EINTR);
         }
@@ -96,13 +240,12 @@ pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::time
 /// Get current system time and store in specific struct
 pub unsafe fn sys_get_time_of_day(ts: *mut ctypes::timeval) -> c_int {
     syscall_body!(sys_get_time_of_day, {
-        let current_us = axhal::time::monotonic_time_nanos() as usize / 1000;
-        unsafe {
-            *ts = ctypes::timeval {
-                tv_sec: (current_us / 1_000_000) as i64,
-                tv_usec: (current_us % 1_000_000) as i64,
-            }
-        }
+        let current_us = realtime_now().as_micros() as usize;
+        let tv = ctypes::timeval {
+            tv_sec: (current_us / 1_000_000) as i64,
+            tv_usec: (current_us % 1_000_000) as i64,
+        };
+        put_user(ts, tv)?;
         Ok(0)
     })
 }
@@ -0,0 +1,423 @@
+//! POSIX per-process timers (`timer_create`) and the interval timer of
+//! `setitimer`.
+//!
+//! The timers are set on the timer wheel of [`axtask`], whose callbacks send
+//! the signals in the timer interrupt handler. Only the `ITIMER_REAL` interval
+//! timer is supported, which sends `SIGALRM` to the process.
+//!
+//! The timers are counted in the monotonic time, so the absolute deadlines of
+//! `CLOCK_REALTIME` timers are not changed by later `clock_settime` calls.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use core::ffi::c_int;
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::wall_time;
+use axsync::spin::SpinNoIrq;
+use axtask::TimerHandle;
+use lazy_static::lazy_static;
+
+use super::signal::{check_signo, send_timer_signal};
+use super::time::{realtime_now, timespec_to_duration};
+use crate::ctypes;
+use crate::uaccess::{get_user, put_user};
+
+/// Notify the thread `sigev_notify_thread_id` instead of the process, which
+/// is Linux-specific.
+const SIGEV_THREAD_ID: u32 = 4;
+/// The maximum of the overrun count.
+const DELAYTIMER_MAX: u64 = c_int::MAX as u64;
+
+/// How the expiration of a timer is notified.
+#[derive(Clone, Copy)]
+enum Notify {
+    None,
+    Signal {
+        sig: c_int,
+        code: c_int,
+        tid: Option<u64>,
+        value: usize,
+    },
+}
+
+struct TimerState {
+    /// Increased on each arming, to ignore the callbacks of the previous
+    /// settings that are running concurrently.
+    generation: u64,
+    /// The next expiration in the wall time of `axhal`, or [`None`] if the
+    /// timer is disarmed.
+    deadline: Option<Duration>,
+    interval: Duration,
+    /// The number of the extra expirations missed at the last expiration.
+    overrun: c_int,
+    handle: Option<TimerHandle>,
+}
+
+struct PosixTimer {
+    id: c_int,
+    clock: u32,
+    notify: Notify,
+    state: SpinNoIrq<TimerState>,
+}
+
+impl PosixTimer {
+    fn new(id: c_int, clock: u32, notify: Notify) -> Self {
+        Self {
+            id,
+            clock,
+            notify,
+            state: SpinNoIrq::new(TimerState {
+                generation: 0,
+                deadline: None,
+                interval: Duration::ZERO,
+                overrun: 0,
+                handle: None,
+            }),
+        }
+    }
+
+    fn clock_now(&self) -> Duration {
+        match self.clock {
+            ctypes::CLOCK_REALTIME => realtime_now(),
+            _ => axhal::time::monotonic_time(),
+        }
+    }
+
+    /// Returns the time until the next expiration and the interval.
+    fn get(&self) -> (Duration, Duration) {
+        let state = self.state.lock();
+        let remaining = state.deadline.map_or(Duration::ZERO, |deadline| {
+            // An expired timer that is not handled yet is reported as
+            // expiring soon, as zero means disarmed.
+            deadline
+                .saturating_sub(wall_time())
+                .max(Duration::from_nanos(1))
+        });
+        (remaining, state.interval)
+    }
+
+    /// Arms the timer to expire after `value` (or at `value` of its clock if
+    /// `absolute` is true) and then every `interval`, or disarms it if
+    /// `value` is zero. Returns the old setting as [`PosixTimer::get`].
+    fn set(
+        self: &Arc<Self>,
+        value: Duration,
+        interval: Duration,
+        absolute: bool,
+    ) -> (Duration, Duration) {
+        let old = self.get();
+        let mut state = self.state.lock();
+        state.interval = interval;
+        state.overrun = 0;
+        if value.is_zero() {
+            disarm(&mut state);
+        } else {
+            let value = if absolute {
+                value.saturating_sub(self.clock_now())
+            } else {
+                value
+            };
+            arm(self, &mut state, wall_time() + value);
+        }
+        old
+    }
+
+    fn notify(&self) {
+        if let Notify::Signal {
+            sig,
+            code,
+            tid,
+            value,
+        } = self.notify
+        {
+            if let Err(e) = send_timer_signal(tid, sig, code, self.id, value) {
+                warn!("timer {}: failed to send signal {}: {:?}", self.id, sig, e);
+            }
+        }
+    }
+}
+
+fn disarm(state: &mut TimerState) {
+    if let Some(handle) = state.handle.take() {
+        handle.cancel();
+    }
+    state.generation += 1;
+    state.deadline = None;
+}
+
+fn arm(timer: &Arc<PosixTimer>, state: &mut TimerState, deadline: Duration) {
+    disarm(state);
+    state.deadline = Some(deadline);
+    let generation = state.generation;
+    // The timer wheel keeps a weak reference, as a deleted timer may still
+    // be in it until the deadline.
+    let timer = Arc::downgrade(timer);
+    state.handle = Some(axtask::set_timer(deadline, move |now| {
+        expire(timer, generation, now)
+    }));
+}
+
+/// Called on the expiration of a timer, in the timer interrupt handler.
+fn expire(timer: Weak<PosixTimer>, generation: u64, now: Duration) {
+    let Some(timer) = timer.upgrade() else {
+        return;
+    };
+    let mut state = timer.state.lock();
+    if state.generation != generation {
+        return;
+    }
+    let Some(deadline) = state.deadline else {
+        return;
+    };
+    if state.interval.is_zero() {
+        state.deadline = None;
+        state.handle = None;
+    } else {
+        // Count the periods missed, and schedule the next expiration after
+        // the current time.
+        let interval = state.interval.as_nanos();
+        let missed = (now.saturating_sub(deadline).as_nanos() / interval) as u64;
+        state.overrun = missed.min(DELAYTIMER_MAX) as c_int;
+        let next = deadline.as_nanos() + interval * (missed as u128 + 1);
+        let next = Duration::from_nanos(next.min(u64::MAX as u128) as u64);
+        arm(&timer, &mut state, next);
+    }
+    drop(state);
+    timer.notify();
+}
+
+static TIMERS: SpinNoIrq<BTreeMap<c_int, Arc<PosixTimer>>> = SpinNoIrq::new(BTreeMap::new());
+
+lazy_static! {
+    /// The `ITIMER_REAL` interval timer.
+    static ref REAL_TIMER: Arc<PosixTimer> = Arc::new(PosixTimer::new(
+        0,
+        ctypes::CLOCK_REALTIME,
+        Notify::Signal {
+            sig: ctypes::SIGALRM as c_int,
+            code: ctypes::SI_KERNEL as c_int,
+            tid: None,
+            value: 0,
+        },
+    ));
+}
+
+fn find_timer(timerid: ctypes::timer_t) -> LinuxResult<Arc<PosixTimer>> {
+    let id = c_int::try_from(timerid as usize).map_err(|_| LinuxError::EINVAL)?;
+    TIMERS.lock().get(&id).cloned().ok_or(LinuxError::EINVAL)
+}
+
+fn timeval_to_duration(tv: &ctypes::timeval) -> LinuxResult<Duration> {
+    if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*tv))
+}
+
+/// Create a POSIX per-process timer on the clock `clockid`, and store its ID
+/// in `timerid`.
+///
+/// `sevp` may specify `SIGEV_NONE`, `SIGEV_SIGNAL` or `SIGEV_THREAD_ID`
+/// notification. If it is NULL, `SIGALRM` is sent to the process with the
+/// timer ID as the value.
+pub unsafe fn sys_timer_create(
+    clockid: ctypes::clockid_t,
+    sevp: *mut ctypes::sigevent,
+    timerid: *mut ctypes::timer_t,
+) -> c_int {
+    debug!(
+        "sys_timer_create <= clockid: {}, sevp: {:#x}",
+        clockid, sevp as usize
+    );
+    syscall_body!(sys_timer_create, {
+        if timerid.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let clock = clockid as u32;
+        if clock != ctypes::CLOCK_REALTIME && clock != ctypes::CLOCK_MONOTONIC {
+            return Err(LinuxError::EINVAL);
+        }
+        let event = if sevp.is_null() {
+            None
+        } else {
+            Some(get_user(sevp)?)
+        };
+        let mut timers = TIMERS.lock();
+        let id = (0..c_int::MAX)
+            .find(|id| !timers.contains_key(id))
+            .ok_or(LinuxError::EAGAIN)?;
+        let notify = match event {
+            None => Notify::Signal {
+                sig: ctypes::SIGALRM as c_int,
+                code: ctypes::SI_TIMER,
+                tid: None,
+                value: id as usize,
+            },
+            Some(event) => {
+                let sig = event.sigev_signo;
+                let value = unsafe { event.sigev_value.sival_ptr } as usize;
+                match event.sigev_notify as u32 {
+                    ctypes::SIGEV_NONE => Notify::None,
+                    ctypes::SIGEV_SIGNAL | SIGEV_THREAD_ID => {
+                        check_signo(sig)?;
+                        let tid = if event.sigev_notify as u32 == SIGEV_THREAD_ID {
+                            let tid = unsafe { event.__sev_fields.sigev_notify_thread_id };
+                            if tid <= 0 || axtask::init_pid_ns().find_task(tid as u64).is_none() {
+                                return Err(LinuxError::EINVAL);
+                            }
+                            Some(tid as u64)
+                        } else {
+                            None
+                        };
+                        Notify::Signal {
+                            sig,
+                            code: ctypes::SI_TIMER,
+                            tid,
+                            value,
+                        }
+                    }
+                    // `SIGEV_THREAD` is implemented by the C library.
+                    _ => return Err(LinuxError::EINVAL),
+                }
+            }
+        };
+        timers.insert(id, Arc::new(PosixTimer::new(id, clock, notify)));
+        drop(timers);
+        if let Err(e) = put_user(timerid, id as usize as ctypes::timer_t) {
+            TIMERS.lock().remove(&id);
+            return Err(e);
+        }
+        Ok(0)
+    })
+}
+
+/// Arm or disarm a POSIX per-process timer.
+///
+/// The timer expires after `it_value` of `new_value`, or at `it_value` of
+/// its clock if `flags` contains `TIMER_ABSTIME`, and then every
+/// `it_interval`. A zero `it_value` disarms it. The old setting is stored in
+/// `old_value` if it is not NULL.
+pub unsafe fn sys_timer_settime(
+    timerid: ctypes::timer_t,
+    flags: c_int,
+    new_value: *const ctypes::itimerspec,
+    old_value: *mut ctypes::itimerspec,
+) -> c_int {
+    debug!(
+        "sys_timer_settime <= timerid: {:#x}, flags: {:#x}",
+        timerid as usize, flags
+    );
+    syscall_body!(sys_timer_settime, {
+        let timer = find_timer(timerid)?;
+        let new_value = get_user(new_value)?;
+        if flags as u32 & !ctypes::TIMER_ABSTIME != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let value = timespec_to_duration(&new_value.it_value)?;
+        let interval = timespec_to_duration(&new_value.it_interval)?;
+        let absolute = flags as u32 & ctypes::TIMER_ABSTIME != 0;
+        let (old_remaining, old_interval) = timer.set(value, interval, absolute);
+        if !old_value.is_null() {
+            let old = ctypes::itimerspec {
+                it_interval: old_interval.into(),
+                it_value: old_remaining.into(),
+            };
+            put_user(old_value, old)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Get the time until the next expiration and the interval of a POSIX
+/// per-process timer.
+pub unsafe fn sys_timer_gettime(
+    timerid: ctypes::timer_t,
+    curr_value: *mut ctypes::itimerspec,
+) -> c_int {
+    syscall_body!(sys_timer_gettime, {
+        let timer = find_timer(timerid)?;
+        let (remaining, interval) = timer.get();
+        let curr = ctypes::itimerspec {
+            it_interval: interval.into(),
+            it_value: remaining.into(),
+        };
+        put_user(curr_value, curr)?;
+        Ok(0)
+    })
+}
+
+/// Get the number of the extra expirations of a POSIX per-process timer,
+/// that happened at its last expiration.
+pub fn sys_timer_getoverrun(timerid: ctypes::timer_t) -> c_int {
+    syscall_body!(sys_timer_getoverrun, {
+        let timer = find_timer(timerid)?;
+        let overrun = timer.state.lock().overrun;
+        Ok(overrun)
+    })
+}
+
+/// Delete a POSIX per-process timer, which is disarmed.
+pub fn sys_timer_delete(timerid: ctypes::timer_t) -> c_int {
+    debug!("sys_timer_delete <= timerid: {:#x}", timerid as usize);
+    syscall_body!(sys_timer_delete, {
+        let timer = find_timer(timerid)?;
+        TIMERS.lock().remove(&timer.id);
+        disarm(&mut timer.state.lock());
+        Ok(0)
+    })
+}
+
+fn check_itimer(which: c_int) -> LinuxResult {
+    match which as u32 {
+        ctypes::ITIMER_REAL => Ok(()),
+        ctypes::ITIMER_VIRTUAL | ctypes::ITIMER_PROF => {
+            warn!("setitimer: unsupported timer {}", which);
+            Err(LinuxError::EINVAL)
+        }
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Set the interval timer `which`, only `ITIMER_REAL` is supported.
+///
+/// The timer sends `SIGALRM` to the process after `it_value` of
+/// `new_value`, and then every `it_interval`. A zero `it_value` disarms it.
+/// The old setting is stored in `old_value` if it is not NULL.
+pub unsafe fn sys_setitimer(
+    which: c_int,
+    new_value: *const ctypes::itimerval,
+    old_value: *mut ctypes::itimerval,
+) -> c_int {
+    debug!("sys_setitimer <= which: {}", which);
+    syscall_body!(sys_setitimer, {
+        check_itimer(which)?;
+        let new_value = get_user(new_value)?;
+        let value = timeval_to_duration(&new_value.it_value)?;
+        let interval = timeval_to_duration(&new_value.it_interval)?;
+        let (old_remaining, old_interval) = REAL_TIMER.set(value, interval, false);
+        if !old_value.is_null() {
+            let old = ctypes::itimerval {
+                it_interval: old_interval.into(),
+                it_value: old_remaining.into(),
+            };
+            put_user(old_value, old)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Get the interval timer `which`, only `ITIMER_REAL` is supported.
+pub unsafe fn sys_getitimer(which: c_int, curr_value: *mut ctypes::itimerval) -> c_int {
+    syscall_body!(sys_getitimer, {
+        check_itimer(which)?;
+        let (remaining, interval) = REAL_TIMER.get();
+        let curr = ctypes::itimerval {
+            it_interval: interval.into(),
+            it_value: remaining.into(),
+        };
+        put_user(curr_value, curr)?;
+        Ok(0)
+    })
+}
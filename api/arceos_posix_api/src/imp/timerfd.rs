@@ -0,0 +1,309 @@
+//! Timers notified through file descriptors, like `timerfd_create(2)`.
+//!
+//! A timerfd is readable when the timer has expired, and reads the number of
+//! the expirations since the last read as a `u64`, so it can be monitored
+//! with `poll`/`select`/`epoll` together with other files. The timers are set
+//! on the timer wheel of [`axtask`], as the POSIX timers.
+//!
+//! The timers are counted in the monotonic time, so the absolute deadlines of
+//! `CLOCK_REALTIME` timers are not changed by later `clock_settime` calls,
+//! and `TFD_TIMER_CANCEL_ON_SET` has no effect.
+
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use core::ffi::c_int;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::wall_time;
+use axio::PollState;
+use axsync::spin::SpinNoIrq;
+use axtask::{TimerHandle, WaitQueue};
+
+use super::fd_ops::{FD_TABLE, FileLike, get_file_like};
+use super::time::{realtime_now, timespec_to_duration};
+use crate::ctypes;
+use crate::uaccess::{get_user, put_user};
+
+struct TimerState {
+    /// Increased on each arming, to ignore the callbacks of the previous
+    /// settings that are running concurrently.
+    generation: u64,
+    /// The next expiration in the wall time of `axhal`, or [`None`] if the
+    /// timer is disarmed.
+    deadline: Option<Duration>,
+    interval: Duration,
+    /// The number of the expirations since the last read.
+    expirations: u64,
+    handle: Option<TimerHandle>,
+}
+
+/// A timer file descriptor.
+pub struct TimerFd {
+    clock: u32,
+    state: SpinNoIrq<TimerState>,
+    wq: WaitQueue,
+    nonblocking: AtomicBool,
+}
+
+impl TimerFd {
+    fn new(clock: u32, nonblocking: bool) -> Self {
+        Self {
+            clock,
+            state: SpinNoIrq::new(TimerState {
+                generation: 0,
+                deadline: None,
+                interval: Duration::ZERO,
+                expirations: 0,
+                handle: None,
+            }),
+            wq: WaitQueue::new(),
+            nonblocking: AtomicBool::new(nonblocking),
+        }
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    fn clock_now(&self) -> Duration {
+        match self.clock {
+            ctypes::CLOCK_REALTIME => realtime_now(),
+            _ => axhal::time::monotonic_time(),
+        }
+    }
+
+    /// Returns the time until the next expiration and the interval.
+    fn get(&self) -> (Duration, Duration) {
+        let state = self.state.lock();
+        let remaining = state.deadline.map_or(Duration::ZERO, |deadline| {
+            // An expired timer that is not handled yet is reported as
+            // expiring soon, as zero means disarmed.
+            deadline
+                .saturating_sub(wall_time())
+                .max(Duration::from_nanos(1))
+        });
+        (remaining, state.interval)
+    }
+
+    /// Arms the timer to expire after `value` (or at `value` of its clock if
+    /// `absolute` is true) and then every `interval`, or disarms it if
+    /// `value` is zero. The expirations not read yet are discarded.
+    ///
+    /// Returns the old setting as [`TimerFd::get`].
+    fn set(
+        self: &Arc<Self>,
+        value: Duration,
+        interval: Duration,
+        absolute: bool,
+    ) -> (Duration, Duration) {
+        let old = self.get();
+        let mut state = self.state.lock();
+        state.interval = interval;
+        state.expirations = 0;
+        if value.is_zero() {
+            disarm(&mut state);
+        } else {
+            let value = if absolute {
+                value.saturating_sub(self.clock_now())
+            } else {
+                value
+            };
+            arm(self, &mut state, wall_time() + value);
+        }
+        old
+    }
+}
+
+fn disarm(state: &mut TimerState) {
+    if let Some(handle) = state.handle.take() {
+        handle.cancel();
+    }
+    state.generation += 1;
+    state.deadline = None;
+}
+
+fn arm(timer: &Arc<TimerFd>, state: &mut TimerState, deadline: Duration) {
+    disarm(state);
+    state.deadline = Some(deadline);
+    let generation = state.generation;
+    // The timer wheel keeps a weak reference, as a closed timerfd may still
+    // be in it until the deadline.
+    let timer = Arc::downgrade(timer);
+    state.handle = Some(axtask::set_timer(deadline, move |now| {
+        expire(timer, generation, now)
+    }));
+}
+
+/// Called on the expiration of a timer, in the timer interrupt handler.
+fn expire(timer: Weak<TimerFd>, generation: u64, now: Duration) {
+    let Some(timer) = timer.upgrade() else {
+        return;
+    };
+    let mut state = timer.state.lock();
+    if state.generation != generation {
+        return;
+    }
+    let Some(deadline) = state.deadline else {
+        return;
+    };
+    if state.interval.is_zero() {
+        state.deadline = None;
+        state.handle = None;
+        state.expirations += 1;
+    } else {
+        // Count the periods missed, and schedule the next expiration after
+        // the current time.
+        let interval = state.interval.as_nanos();
+        let missed = (now.saturating_sub(deadline).as_nanos() / interval) as u64;
+        state.expirations = state.expirations.saturating_add(missed + 1);
+        let next = deadline.as_nanos() + interval * (missed as u128 + 1);
+        let next = Duration::from_nanos(next.min(u64::MAX as u128) as u64);
+        arm(&timer, &mut state, next);
+    }
+    drop(state);
+    timer.wq.notify_all(true);
+    super::fd_ops::notify_pollers();
+}
+
+impl FileLike for TimerFd {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(LinuxError::EINVAL);
+        }
+        let expirations = loop {
+            let mut state = self.state.lock();
+            if state.expirations > 0 {
+                break core::mem::take(&mut state.expirations);
+            }
+            drop(state);
+            if self.is_nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            self.wq.wait_until(|| self.state.lock().expirations > 0);
+        };
+        buf[..size_of::<u64>()].copy_from_slice(&expirations.to_ne_bytes());
+        Ok(size_of::<u64>())
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o600u32; // rw-------
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self.state.lock().expirations > 0,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn link_target(&self) -> String {
+        String::from("anon_inode:[timerfd]")
+    }
+}
+
+/// Creates a timerfd on the clock `clockid`, and returns its file
+/// descriptor.
+///
+/// `clockid` may be `CLOCK_REALTIME`, `CLOCK_MONOTONIC` or `CLOCK_BOOTTIME`,
+/// and `flags` may contain `TFD_NONBLOCK` and `TFD_CLOEXEC`.
+pub fn sys_timerfd_create(clockid: ctypes::clockid_t, flags: c_int) -> c_int {
+    debug!(
+        "sys_timerfd_create <= clockid: {}, flags: {:#x}",
+        clockid, flags
+    );
+    syscall_body!(sys_timerfd_create, {
+        let clock = clockid as u32;
+        if !matches!(
+            clock,
+            ctypes::CLOCK_REALTIME | ctypes::CLOCK_MONOTONIC | ctypes::CLOCK_BOOTTIME
+        ) {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = flags as u32;
+        if flags & !(ctypes::TFD_NONBLOCK | ctypes::TFD_CLOEXEC) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let timerfd = Arc::new(TimerFd::new(clock, flags & ctypes::TFD_NONBLOCK != 0));
+        FD_TABLE
+            .write()
+            .add(timerfd, flags & ctypes::TFD_CLOEXEC != 0)
+    })
+}
+
+/// Arms or disarms the timerfd `fd`.
+///
+/// The timer expires after `it_value` of `new_value`, or at `it_value` of
+/// its clock if `flags` contains `TFD_TIMER_ABSTIME`, and then every
+/// `it_interval`. A zero `it_value` disarms it. The old setting is stored in
+/// `old_value` if it is not NULL.
+pub unsafe fn sys_timerfd_settime(
+    fd: c_int,
+    flags: c_int,
+    new_value: *const ctypes::itimerspec,
+    old_value: *mut ctypes::itimerspec,
+) -> c_int {
+    debug!("sys_timerfd_settime <= fd: {}, flags: {:#x}", fd, flags);
+    syscall_body!(sys_timerfd_settime, {
+        let timerfd = TimerFd::from_fd(fd)?;
+        let new_value = get_user(new_value)?;
+        let flags = flags as u32;
+        if flags & !(ctypes::TFD_TIMER_ABSTIME | ctypes::TFD_TIMER_CANCEL_ON_SET) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let value = timespec_to_duration(&new_value.it_value)?;
+        let interval = timespec_to_duration(&new_value.it_interval)?;
+        let absolute = flags & ctypes::TFD_TIMER_ABSTIME != 0;
+        let (old_remaining, old_interval) = timerfd.set(value, interval, absolute);
+        if !old_value.is_null() {
+            let old = ctypes::itimerspec {
+                it_interval: old_interval.into(),
+                it_value: old_remaining.into(),
+            };
+            put_user(old_value, old)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Gets the time until the next expiration and the interval of the timerfd
+/// `fd`.
+pub unsafe fn sys_timerfd_gettime(fd: c_int, curr_value: *mut ctypes::itimerspec) -> c_int {
+    syscall_body!(sys_timerfd_gettime, {
+        let timerfd = TimerFd::from_fd(fd)?;
+        let (remaining, interval) = timerfd.get();
+        let curr = ctypes::itimerspec {
+            it_interval: interval.into(),
+            it_value: remaining.into(),
+        };
+        put_user(curr_value, curr)?;
+        Ok(0)
+    })
+}
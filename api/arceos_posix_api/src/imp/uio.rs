@@ -0,0 +1,122 @@
+//! User-space drivers of devices not claimed by the kernel, like the UIO
+//! devices of Linux.
+//!
+//! A device opened by [`sys_uio_open`] is accessed through the returned file
+//! descriptor: `read` of 4 bytes waits for an interrupt and returns the total
+//! number of interrupts, `write` of a non-zero 4-byte value unmasks the IRQ
+//! after an interrupt is handled, and `mmap` with `offset` of `N` pages maps
+//! the `N`-th memory region of the device. The devices and their regions are
+//! listed in `/proc/uio`.
+
+use alloc::sync::Arc;
+use core::ffi::c_int;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axuio::UioHandle;
+
+use super::fd_ops::{FileLike, add_file_like, get_file_like};
+use crate::ctypes;
+
+/// An opened device.
+pub struct UioFile {
+    handle: UioHandle,
+    nonblocking: AtomicBool,
+}
+
+impl UioFile {
+    pub(crate) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    /// Returns the physical address and the size of the `index`-th memory
+    /// region.
+    pub(crate) fn map_region(&self, index: usize) -> LinuxResult<(usize, usize)> {
+        let map = self
+            .handle
+            .device()
+            .maps
+            .get(index)
+            .ok_or(LinuxError::EINVAL)?;
+        Ok((map.paddr, map.size))
+    }
+}
+
+impl FileLike for UioFile {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if buf.len() != 4 {
+            return Err(LinuxError::EINVAL);
+        }
+        let count = self.handle.wait_irq(self.is_nonblocking())?;
+        buf.copy_from_slice(&count.to_ne_bytes());
+        Ok(4)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        let value: [u8; 4] = buf.try_into().map_err(|_| LinuxError::EINVAL)?;
+        self.handle.enable_irq(u32::from_ne_bytes(value) != 0)?;
+        Ok(4)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o20000 | 0o600; // S_IFCHR
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_rdev: self.handle.index() as _,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self.handle.irq_pending(),
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+}
+
+/// Open the `index`-th device in `/proc/uio` exclusively, and deliver the
+/// interrupts of `irq` to it if `irq` is not negative.
+///
+/// There is no IOMMU, so the device can access any memory by DMA. Only
+/// `O_NONBLOCK` in `flags` is supported.
+pub fn sys_uio_open(index: c_int, irq: c_int, flags: c_int) -> c_int {
+    debug!(
+        "sys_uio_open <= index: {}, irq: {}, flags: {:#o}",
+        index, irq, flags
+    );
+    syscall_body!(sys_uio_open, {
+        let index = usize::try_from(index).map_err(|_| LinuxError::ENODEV)?;
+        let handle = axuio::open(index).map_err(|e| match e {
+            axerrno::AxError::NotFound => LinuxError::ENODEV,
+            e => e.into(),
+        })?;
+        if irq >= 0 {
+            handle.bind_irq(irq as usize)?;
+        }
+        add_file_like(Arc::new(UioFile {
+            handle,
+            nonblocking: AtomicBool::new(flags as u32 & ctypes::O_NONBLOCK != 0),
+        }))
+    })
+}
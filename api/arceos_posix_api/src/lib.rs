@@ -17,6 +17,8 @@ extern crate axruntime;
 mod utils;
 
 mod imp;
+mod uaccess;
+#[cfg(feature = "alloc")]
 pub use utils::char_ptr_to_str;
 
 /// Platform-specific constants and parameters.
@@ -31,28 +33,88 @@ pub mod config {
 pub mod ctypes;
 
 pub use imp::io::*;
+pub use imp::membarrier::sys_membarrier;
 #[cfg(feature = "fs")]
 pub use imp::path_link::{AT_FDCWD, FilePath, HARDLINK_MANAGER, handle_file_path};
-pub use imp::resources::{sys_getrlimit, sys_setrlimit};
+pub use imp::prctl::{sys_personality, sys_prctl};
+pub use imp::random::sys_getrandom;
+pub use imp::resources::{sys_getrlimit, sys_prlimit64, sys_setrlimit};
 pub use imp::sys::sys_sysconf;
 pub use imp::task::{sys_exit, sys_getpid, sys_sched_yield};
-pub use imp::time::{sys_clock_gettime, sys_get_time_of_day, sys_nanosleep};
+pub use imp::time::{
+    sys_clock_getres, sys_clock_gettime, sys_clock_nanosleep, sys_clock_settime,
+    sys_get_time_of_day, sys_nanosleep,
+};
 
 #[cfg(feature = "fd")]
 pub use imp::fd_ops::*;
 #[cfg(feature = "fs")]
-pub use imp::fs::{Directory, File, sys_lseek, sys_open, sys_openat, sys_rename};
-#[cfg(feature = "select")]
-pub use imp::io_mpx::sys_select;
+pub use imp::fs::{
+    Directory, File, sys_fdatasync, sys_flock, sys_fstat, sys_fstatat, sys_fstatfs, sys_fsync,
+    sys_link, sys_linkat, sys_lseek, sys_lstat, sys_mkdir, sys_mkdirat, sys_open, sys_openat,
+    sys_quotactl, sys_readlink, sys_readlinkat, sys_rename, sys_renameat, sys_renameat2, sys_rmdir,
+    sys_sendfile, sys_stat, sys_statfs, sys_symlink, sys_symlinkat, sys_unlink, sys_unlinkat,
+};
+#[cfg(feature = "multitask")]
+pub use imp::futex::sys_futex;
+#[cfg(feature = "inotify")]
+pub use imp::inotify::{sys_inotify_add_watch, sys_inotify_init1, sys_inotify_rm_watch};
 #[cfg(feature = "epoll")]
 pub use imp::io_mpx::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
+#[cfg(feature = "select")]
+pub use imp::io_mpx::{sys_pselect6, sys_select};
+#[cfg(feature = "sysvipc")]
+pub use imp::ipc::{
+    sys_msgctl, sys_msgget, sys_msgrcv, sys_msgsnd, sys_semctl, sys_semget, sys_semop,
+    sys_semtimedop,
+};
+#[cfg(all(feature = "sysvipc", feature = "shm"))]
+pub use imp::ipc::{sys_shmat, sys_shmctl, sys_shmdt, sys_shmget};
+#[cfg(feature = "mmap")]
+pub use imp::mmap::{sys_brk, sys_mmap, sys_mprotect, sys_munmap, sys_sbrk};
+#[cfg(feature = "mqueue")]
+pub use imp::mqueue::{
+    sys_mq_getsetattr, sys_mq_notify, sys_mq_open, sys_mq_timedreceive, sys_mq_timedsend,
+    sys_mq_unlink,
+};
 #[cfg(feature = "net")]
 pub use imp::net::*;
 #[cfg(feature = "pipe")]
 pub use imp::pipe::*;
+#[cfg(all(feature = "uspace", target_arch = "x86_64"))]
+pub use imp::prctl::sys_arch_prctl;
+#[cfg(all(feature = "uspace", feature = "fs"))]
+pub use imp::process::{spawn_process, sys_execve, sys_posix_spawn};
+#[cfg(feature = "uspace")]
+pub use imp::process::{
+    sys_clone, sys_clone3, sys_fork, sys_set_tid_address, sys_vfork, sys_wait4, sys_waitpid,
+};
 #[cfg(feature = "multitask")]
 pub use imp::pthread::mutex::{
     sys_pthread_mutex_init, sys_pthread_mutex_lock, sys_pthread_mutex_unlock,
 };
+#[cfg(feature = "signal")]
+pub use imp::pthread::sys_pthread_kill;
 #[cfg(feature = "multitask")]
 pub use imp::pthread::{sys_pthread_create, sys_pthread_exit, sys_pthread_join, sys_pthread_self};
+#[cfg(feature = "shm")]
+pub use imp::shm::{sys_shm_open, sys_shm_unlink};
+#[cfg(all(feature = "signal", feature = "uspace"))]
+pub use imp::signal::sys_rt_sigreturn;
+#[cfg(feature = "signal")]
+pub use imp::signal::{sys_kill, sys_rt_sigaction, sys_rt_sigprocmask, sys_tgkill};
+#[cfg(all(feature = "uspace", feature = "fs"))]
+pub use imp::syscall::SYS_POSIX_SPAWN;
+#[cfg(feature = "multitask")]
+pub use imp::task::{
+    sys_getpriority, sys_nice, sys_sched_getaffinity, sys_sched_setaffinity, sys_setpriority,
+};
+#[cfg(all(feature = "signal", feature = "irq"))]
+pub use imp::timer::{
+    sys_getitimer, sys_setitimer, sys_timer_create, sys_timer_delete, sys_timer_getoverrun,
+    sys_timer_gettime, sys_timer_settime,
+};
+#[cfg(feature = "timerfd")]
+pub use imp::timerfd::{sys_timerfd_create, sys_timerfd_gettime, sys_timerfd_settime};
+#[cfg(feature = "uio")]
+pub use imp::uio::sys_uio_open;
@@ -0,0 +1,328 @@
+//! Access to the memory passed to the syscalls by their callers.
+//!
+//! With the `uspace` feature, the pointers from a user process are checked
+//! against its address space: the whole range must lie in the user areas
+//! mapped with the required permissions, whose pages are populated in
+//! advance. The data are then copied through the page table of the process
+//! rather than dereferenced, so a bad pointer fails the syscall with
+//! `EFAULT` instead of faulting in the kernel.
+//!
+//! The pointers from the kernel (the applications linked with it, or all the
+//! callers without `uspace`) are trusted, and only checked for NULL.
+
+use core::ffi::{CStr, c_char};
+use core::mem::{MaybeUninit, size_of};
+#[cfg(feature = "multitask")]
+use core::sync::atomic::AtomicU32;
+
+use axerrno::{LinuxError, LinuxResult};
+
+#[cfg(feature = "uspace")]
+mod user {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::mem::size_of;
+    use core::sync::atomic::AtomicU32;
+
+    use axerrno::{LinuxError, LinuxResult};
+    use axhal::mem::VirtAddr;
+    use axhal::paging::MappingFlags;
+    use axmm::AddrSpace;
+    use memory_addr::{PAGE_SIZE_4K, VirtAddrRange, align_down_4k, align_up_4k};
+
+    use crate::imp::process::with_current_aspace;
+
+    /// The maximum size of a buffer bounced through the kernel at once.
+    pub const BUF_MAX: usize = 1 << 20;
+
+    /// Checks that `len` bytes at `start` lie in the user areas of `aspace`
+    /// with the `access` permissions, and populates their pages.
+    fn check_range(
+        aspace: &mut AddrSpace,
+        start: usize,
+        len: usize,
+        access: MappingFlags,
+    ) -> LinuxResult<VirtAddr> {
+        let end = start.checked_add(len).ok_or(LinuxError::EFAULT)?;
+        let range = VirtAddrRange::new(start.into(), end.into());
+        if !aspace.contains_range(range.start, len)
+            || !aspace.check_region_access(range, access | MappingFlags::USER)
+        {
+            return Err(LinuxError::EFAULT);
+        }
+        let (page_start, page_end) = (align_down_4k(start), align_up_4k(end));
+        aspace.populate_area(page_start.into(), page_end - page_start)?;
+        Ok(range.start)
+    }
+
+    /// Calls `f` with the address space of the current process and the
+    /// checked range, or returns `None` if the caller is the kernel.
+    pub fn access(
+        start: usize,
+        len: usize,
+        flags: MappingFlags,
+        f: impl FnOnce(&AddrSpace, VirtAddr) -> LinuxResult,
+    ) -> Option<LinuxResult> {
+        with_current_aspace(|aspace| {
+            let start = check_range(aspace, start, len, flags)?;
+            f(aspace, start)
+        })
+    }
+
+    /// Returns the length of the string at `start`, or `None` if the caller
+    /// is the kernel.
+    ///
+    /// It is read in chunks, so that the string may end right before an
+    /// unmapped page.
+    pub fn strnlen(start: usize, max_len: usize) -> Option<LinuxResult<usize>> {
+        with_current_aspace(|aspace| {
+            let mut chunk = [0u8; 256];
+            let mut len = 0;
+            while len < max_len {
+                let addr = start.checked_add(len).ok_or(LinuxError::EFAULT)?;
+                let size = chunk
+                    .len()
+                    .min(PAGE_SIZE_4K - (addr % PAGE_SIZE_4K))
+                    .min(max_len - len);
+                let addr = check_range(aspace, addr, size, MappingFlags::READ)?;
+                aspace.read(addr, &mut chunk[..size])?;
+                if let Some(pos) = chunk[..size].iter().position(|&c| c == 0) {
+                    return Ok(len + pos);
+                }
+                len += size;
+            }
+            Err(LinuxError::ENAMETOOLONG)
+        })
+    }
+
+    /// Calls `f` with the word at `addr` of the locked `aspace`, through the
+    /// linear mapping of its frame.
+    pub fn atomic<R>(
+        aspace: &mut AddrSpace,
+        addr: usize,
+        access: MappingFlags,
+        f: impl FnOnce(&AtomicU32) -> LinuxResult<R>,
+    ) -> LinuxResult<R> {
+        let start = check_range(aspace, addr, size_of::<u32>(), access)?;
+        let (paddr, ..) = aspace
+            .page_table()
+            .query(start.align_down_4k())
+            .map_err(|_| LinuxError::EFAULT)?;
+        let ptr = axhal::mem::phys_to_virt(paddr + start.align_offset_4k()).as_mut_ptr();
+        f(unsafe { AtomicU32::from_ptr(ptr as *mut u32) })
+    }
+
+    /// Copies at most [`BUF_MAX`] bytes at `src` into a buffer of the
+    /// kernel, or returns `None` if the caller is the kernel.
+    pub fn bounce_in(src: usize, len: usize) -> Option<LinuxResult<Vec<u8>>> {
+        let mut buf = vec![0; len.min(BUF_MAX)];
+        access(src, buf.len(), MappingFlags::READ, |aspace, start| {
+            Ok(aspace.read(start, &mut buf)?)
+        })
+        .map(|res| res.map(|_| buf))
+    }
+
+    /// Checks that at most [`BUF_MAX`] bytes at `dst` are writable, and
+    /// returns a buffer of the kernel of that size to be copied out, or
+    /// `None` if the caller is the kernel.
+    pub fn bounce_out(dst: usize, len: usize) -> Option<LinuxResult<Vec<u8>>> {
+        let len = len.min(BUF_MAX);
+        access(dst, len, MappingFlags::WRITE, |_, _| Ok(())).map(|res| res.map(|_| vec![0; len]))
+    }
+}
+
+/// Copies `dst.len()` bytes at `src` of the caller into `dst`.
+pub fn copy_from_user(dst: &mut [u8], src: *const u8) -> LinuxResult {
+    if src.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if dst.is_empty() {
+        return Ok(());
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(res) = user::access(
+        src as usize,
+        dst.len(),
+        axhal::paging::MappingFlags::READ,
+        |aspace, start| Ok(aspace.read(start, dst)?),
+    ) {
+        return res;
+    }
+    unsafe { core::ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), dst.len()) };
+    Ok(())
+}
+
+/// Copies `src` to `dst` of the caller.
+pub fn copy_to_user(dst: *mut u8, src: &[u8]) -> LinuxResult {
+    if dst.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if src.is_empty() {
+        return Ok(());
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(res) = user::access(
+        dst as usize,
+        src.len(),
+        axhal::paging::MappingFlags::WRITE,
+        |aspace, start| Ok(aspace.write(start, src)?),
+    ) {
+        return res;
+    }
+    unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), dst, src.len()) };
+    Ok(())
+}
+
+/// Reads a value at `src` of the caller, which may be unaligned.
+///
+/// `T` must be a plain C type, valid for any bytes.
+pub fn get_user<T: Copy>(src: *const T) -> LinuxResult<T> {
+    let mut val = MaybeUninit::<T>::zeroed();
+    let bytes =
+        unsafe { core::slice::from_raw_parts_mut(val.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    copy_from_user(bytes, src as *const u8)?;
+    Ok(unsafe { val.assume_init() })
+}
+
+/// Writes `val` to `dst` of the caller, which may be unaligned.
+pub fn put_user<T: Copy>(dst: *mut T, val: T) -> LinuxResult {
+    let bytes =
+        unsafe { core::slice::from_raw_parts(&val as *const T as *const u8, size_of::<T>()) };
+    copy_to_user(dst as *mut u8, bytes)
+}
+
+/// Copies `dst.len()` values at `src` of the caller into `dst`.
+///
+/// `T` must be a plain C type, valid for any bytes.
+#[cfg(any(feature = "select", feature = "sysvipc"))]
+pub fn get_user_slice<T: Copy>(dst: &mut [T], src: *const T) -> LinuxResult {
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(dst.as_mut_ptr() as *mut u8, core::mem::size_of_val(dst))
+    };
+    copy_from_user(bytes, src as *const u8)
+}
+
+/// Writes the values of `src` to `dst` of the caller.
+#[cfg(any(feature = "select", feature = "epoll", feature = "sysvipc"))]
+pub fn put_user_slice<T: Copy>(dst: *mut T, src: &[T]) -> LinuxResult {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(src.as_ptr() as *const u8, core::mem::size_of_val(src))
+    };
+    copy_to_user(dst as *mut u8, bytes)
+}
+
+/// Returns the length of the NUL-terminated string at `src` of the caller,
+/// which must end within `max_len` bytes, otherwise `ENAMETOOLONG` is
+/// returned.
+pub fn strnlen_user(src: *const c_char, max_len: usize) -> LinuxResult<usize> {
+    if src.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(res) = user::strnlen(src as usize, max_len) {
+        return res;
+    }
+    let len = unsafe { CStr::from_ptr(src) }.count_bytes();
+    if len >= max_len {
+        return Err(LinuxError::ENAMETOOLONG);
+    }
+    Ok(len)
+}
+
+/// Copies the NUL-terminated UTF-8 string at `src` of the caller, which must
+/// end within `max_len` bytes.
+#[cfg(feature = "alloc")]
+pub fn strncpy_from_user(src: *const c_char, max_len: usize) -> LinuxResult<alloc::string::String> {
+    let mut buf = alloc::vec![0; strnlen_user(src, max_len)?];
+    copy_from_user(&mut buf, src as *const u8)?;
+    alloc::string::String::from_utf8(buf).map_err(|_| LinuxError::EINVAL)
+}
+
+/// Calls `f` with the 32-bit word at `uaddr` of the caller as an atomic,
+/// which must be aligned, for the futexes. With `write`, the word must be
+/// writable.
+///
+/// The address space of a user process stays locked during `f`, so that the
+/// word cannot be unmapped under it.
+#[cfg(feature = "multitask")]
+pub fn with_user_atomic<R>(
+    uaddr: *mut u32,
+    write: bool,
+    f: impl FnOnce(&AtomicU32) -> LinuxResult<R>,
+) -> LinuxResult<R> {
+    if uaddr.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if uaddr as usize % core::mem::align_of::<u32>() != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(aspace) = crate::imp::process::current_aspace() {
+        use axhal::paging::MappingFlags;
+        let access = if write {
+            MappingFlags::READ | MappingFlags::WRITE
+        } else {
+            MappingFlags::READ
+        };
+        return user::atomic(&mut aspace.lock(), uaddr as usize, access, f);
+    }
+    #[cfg(not(feature = "uspace"))]
+    let _ = write;
+    f(unsafe { AtomicU32::from_ptr(uaddr) })
+}
+
+/// Calls `f` with the `len` bytes at `src` of the caller.
+///
+/// The bytes of a user process are copied into the kernel first, at most
+/// 1 MiB of them, so `f` may see fewer bytes than `len`, as a short write.
+pub fn with_user_buf<R>(
+    src: *const u8,
+    len: usize,
+    f: impl FnOnce(&[u8]) -> LinuxResult<R>,
+) -> LinuxResult<R> {
+    if src.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(buf) = user::bounce_in(src as usize, len) {
+        return f(&buf?);
+    }
+    f(unsafe { core::slice::from_raw_parts(src, len) })
+}
+
+/// Calls `f` to fill a buffer for the `len` bytes at `dst` of the caller,
+/// where `f` returns the number of bytes filled.
+///
+/// The range of a user process is checked before calling `f`, which fills a
+/// buffer of the kernel of at most 1 MiB instead, copied out afterwards.
+pub fn with_user_buf_mut(
+    dst: *mut u8,
+    len: usize,
+    f: impl FnOnce(&mut [u8]) -> LinuxResult<usize>,
+) -> LinuxResult<usize> {
+    if dst.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    #[cfg(feature = "uspace")]
+    if let Some(buf) = user::bounce_out(dst as usize, len) {
+        let mut buf = buf?;
+        let n = f(&mut buf)?.min(buf.len());
+        copy_to_user(dst, &buf[..n])?;
+        return Ok(n);
+    }
+    f(unsafe { core::slice::from_raw_parts_mut(dst, len) })
+}
+
+/// Copies the user memory for the syscalls handled by the HAL, i.e. the
+/// batched syscalls.
+#[cfg(feature = "uspace")]
+#[axhal::trap::register_trap_handler(axhal::trap::COPY_FROM_USER)]
+fn hal_copy_from_user(dst: &mut [u8], src: usize) -> bool {
+    copy_from_user(dst, src as *const u8).is_ok()
+}
+
+#[cfg(feature = "uspace")]
+#[axhal::trap::register_trap_handler(axhal::trap::COPY_TO_USER)]
+fn hal_copy_to_user(dst: usize, src: &[u8]) -> bool {
+    copy_to_user(dst as *mut u8, src).is_ok()
+}
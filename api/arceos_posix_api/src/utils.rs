@@ -2,18 +2,15 @@
 #![allow(unused_macros)]
 
 use axerrno::{LinuxError, LinuxResult};
-use core::ffi::{CStr, c_char};
+use core::ffi::c_char;
 
 /// Convert a C string to a Rust string
-pub fn char_ptr_to_str<'a>(str: *const c_char) -> LinuxResult<&'a str> {
-    if str.is_null() {
-        Err(LinuxError::EFAULT)
-    } else {
-        let str = str as *const _;
-        unsafe { CStr::from_ptr(str) }
-            .to_str()
-            .map_err(|_| LinuxError::EINVAL)
-    }
+///
+/// The string is copied from the caller, so it is not changed by the caller
+/// (e.g., another thread of a user process) while in use.
+#[cfg(feature = "alloc")]
+pub fn char_ptr_to_str(str: *const c_char) -> LinuxResult<alloc::string::String> {
+    crate::uaccess::strncpy_from_user(str, usize::MAX)
 }
 
 pub fn check_null_ptr<T>(ptr: *const T) -> LinuxResult {
@@ -40,6 +37,8 @@ macro_rules! syscall_body {
             Ok(_) | Err(axerrno::LinuxError::EAGAIN) => debug!(concat!(stringify!($fn), " => {:?}"),  res),
             Err(_) => info!(concat!(stringify!($fn), " => {:?}"), res),
         }
+        #[cfg(feature = "signal")]
+        $crate::imp::signal::handle_pending_signals();
         match res {
             Ok(v) => v as _,
             Err(e) => {
@@ -14,22 +14,76 @@
 //!     - `alloc-buddy`: Use the buddy system allocator.
 //!     - `paging`: Enable page table manipulation.
 //!     - `tls`: Enable thread-local storage.
+//!     - `kaslr`: Randomize the virtual base of the kernel image at boot (RISC-V and AArch64 only).
 //! - Task management
 //!     - `multitask`: Enable multi-threading support.
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `sched_global_rq`: Share a single run queue between all CPUs instead of stealing tasks
+//!       between the run queues of the CPUs, for small SMP systems.
+//!     - `sched_trace`: Record scheduler events, exported to `/proc/sched_trace` in the
+//!       Chrome trace event format.
+//!     - `lockdep`: Detect potential deadlocks of mutexes, for debugging.
+//!     - `qos`: Partition the caches and the memory bandwidth between the classes of service
+//!       of the tasks, by Intel RDT or Arm MPAM.
 //! - Upperlayer stacks (fs, net, display)
 //!     - `fs`: Enable file system support.
+//!     - `fs-irq`: Wait for the requests of the disk by its interrupt, instead of polling the
+//!       device.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext2`: Use ext2 as the root filesystem instead of FAT.
+//!     - `tmpfs`: Mount tmpfs on `/tmp`, and use it as the root filesystem if there is no
+//!       block device.
+//!     - `hugetlbfs`: Mount a filesystem of files backed by huge pages on `/dev/hugepages`.
+//!     - `inotify`: Report the changes of the watched files, like `inotify(7)`.
+//!     - `ninep`: Mount the folders shared by the host through virtio-9p on `/mnt/<tag>`.
 //!     - `net`: Enable networking support.
+//!     - `dhcp`: Configure the network interface by DHCP, instead of `AX_IP` and `AX_GW`.
+//!     - `net-irq`: Receive by the interrupt of the NIC, instead of polling in the blocking
+//!       operations.
+//!     - `cluster`: Enable the node discovery, the failure detection and the RPCs between
+//!       the ArceOS instances on the network.
+//!     - `nbd`: Use the export of the NBD server set by `AX_NBD` as the root disk if there is
+//!       no block device.
+//!     - `nfs`: Mount the export of the NFSv3 server set by `AX_NFS` on `/mnt/nfs`.
+//!     - `httpfs`: Mount the files served under the URL set by `AX_HTTPFS` on `/mnt/http`.
+//!     - `oci`: Unpack the OCI image set by `AX_OCI` to `/` as the root filesystem.
 //!     - `display`: Enable graphics support.
+//!     - `input`: Enable input devices support, with key autorepeat and the lock key LEDs.
+//!     - `hvc`: Use the virtio-console devices as the hvc ports, and the first one as the
+//!       console instead of the UART.
+//!     - `rng`: Seed the kernel entropy pool by the virtio-rng device.
+//!     - `power`: Enable the power supplies, e.g. the goldfish battery, exported to
+//!       `/sys/class/power_supply`.
+//!     - `led`: Enable the LEDs of the platform, driven by the heartbeat, disk
+//!       and network triggers, exported to `/sys/class/leds`.
+//!     - `clk`: Enable the clock and reset controllers in the device tree.
+//!     - `pinctrl`: Route the pins of the devices by the pin controllers in the device tree.
+//!     - `mbox`: Enable the mailboxes in the device tree, and the RPMsg devices to talk to the
+//!       firmware of the co-processors.
+//!     - `uio`: Allow the PCI devices not claimed by any driver to be driven by the
+//!       application, there is no IOMMU to confine their DMA.
+//!     - `ivshmem`: Use the inter-VM shared memory devices as byte streams to the host or other
+//!       VMs, also registered as `/dev/ivshmem<N>` if `fs` is enabled.
+//! - Host
+//!     - `host`: Run the kernel in a user-space process of the Linux host, e.g. for the unit
+//!       tests and fuzzing without an emulator, see `axruntime::init_host`.
+//!     - `vtime`: Replace the hardware clock with a virtual one, advanced only by the tests
+//!       (or the idle task to the next timer), for the deterministic timeouts.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
 //!     - `bus-pci`: Use PCI bus to probe all PCI devices.
+//!     - `bus-both`: Probe both the MMIO devices and the PCI devices.
 //!     - `driver-ramdisk`: Use the RAM disk to emulate the block device.
 //!     - `driver-ixgbe`: Enable the Intel 82599 10Gbit NIC driver.
+//!     - `driver-tap`: Use the TAP device of the Linux host as the NIC, with `host`.
 //!     - `driver-bcm2835-sdhci`: Enable the BCM2835 SDHCI driver (Raspberry Pi SD card).
+//! - Profiling
+//!     - `mcount`: Record the call graph of the modules built with `MCOUNT=y`, exported to
+//!       `/proc/mcount`.
+//!     - `ksyms`: Embed the symbol table in the kernel image, to show the backtraces of the
+//!       panics, the faulting code of the exceptions and the call graph by the function names.
 //! - Logging
 //!     - `log-level-off`: Disable all logging.
 //!     - `log-level-error`, `log-level-warn`, `log-level-info`, `log-level-debug`,
@@ -0,0 +1,32 @@
+//! Builds the user programs with the musl toolchain of the target, which are
+//! embedded into the kernel.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+const PROGRAMS: &[&str] = &["forkexec"];
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    for prog in PROGRAMS {
+        let src = format!("user/{prog}.c");
+        println!("cargo:rerun-if-changed={src}");
+        let out = out_dir.join(prog);
+        // Nothing runs the programs without ArceOS, e.g. in a host build.
+        if env::var_os("CARGO_FEATURE_AXSTD").is_none() {
+            std::fs::write(&out, []).unwrap();
+            continue;
+        }
+        let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+        let cc = env::var("USER_CC").unwrap_or_else(|_| format!("{arch}-linux-musl-gcc"));
+        let status = Command::new(&cc)
+            .args(["-static", "-O2", "-o"])
+            .arg(&out)
+            .arg(&src)
+            .status()
+            .unwrap_or_else(|e| panic!("failed to run {cc}: {e}"));
+        assert!(status.success(), "failed to build {src}");
+    }
+    println!("cargo:rerun-if-env-changed=USER_CC");
+}
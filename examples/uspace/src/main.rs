@@ -0,0 +1,41 @@
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[cfg(feature = "axstd")]
+extern crate alloc;
+
+#[cfg(feature = "axstd")]
+use axstd::println;
+
+/// The user programs built by `build.rs`, with their expected exit codes.
+#[cfg(feature = "axstd")]
+const PROGRAMS: &[(&str, &[u8], i32)] = &[(
+    "forkexec",
+    include_bytes!(concat!(env!("OUT_DIR"), "/forkexec")),
+    0,
+)];
+
+#[cfg(feature = "axstd")]
+fn run_program(name: &str, elf: &[u8]) -> i32 {
+    use alloc::{format, string::String};
+
+    let path = format!("/{name}");
+    axstd::fs::write(&path, elf).expect("failed to write the program");
+    let args = [path.clone()];
+    let task = arceos_posix_api::spawn_process(&path, &args, &[] as &[String])
+        .unwrap_or_else(|e| panic!("failed to spawn {path}: {e:?}"));
+    let code = task.join().unwrap();
+    axstd::fs::remove_file(&path).ok();
+    code
+}
+
+#[cfg_attr(feature = "axstd", unsafe(no_mangle))]
+fn main() {
+    #[cfg(feature = "axstd")]
+    for &(name, elf, expected) in PROGRAMS {
+        println!("Running {name} ...");
+        let code = run_program(name, elf);
+        assert_eq!(code, expected, "{name} exited with {code}");
+    }
+    println!("All user space tests passed!");
+}
@@ -0,0 +1,41 @@
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define CHILD_EXIT_CODE 42
+
+int main(int argc, char **argv)
+{
+    if (argc > 1 && strcmp(argv[1], "child") == 0)
+        return CHILD_EXIT_CODE;
+
+    pid_t pid = fork();
+    if (pid < 0) {
+        perror("fork");
+        return 1;
+    }
+    if (pid == 0) {
+        char *args[] = {argv[0], "child", NULL};
+        char *envs[] = {NULL};
+        execve(argv[0], args, envs);
+        perror("execve");
+        _exit(127);
+    }
+
+    int status;
+    if (waitpid(pid, &status, 0) != pid) {
+        perror("waitpid");
+        return 1;
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != CHILD_EXIT_CODE) {
+        printf("child %d exited with status %#x\n", pid, status);
+        return 1;
+    }
+    if (waitpid(-1, &status, WNOHANG) != -1) {
+        puts("waitpid succeeded without children");
+        return 1;
+    }
+    printf("fork, execve and waitpid OK\n");
+    return 0;
+}
@@ -0,0 +1,214 @@
+//! The basic clocks: the fixed-rate and the fixed-factor clocks of the device
+//! tree, and the gates, the dividers and the muxes of the bits in a register,
+//! which the platforms combine into their clock trees.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axerrno::{AxError, AxResult};
+use axhal::dtb::Node;
+
+use crate::{Clk, ClkHw, Regs};
+
+/// Returns the name of the clock of `node`, its first `clock-output-names`
+/// or the name of the node.
+fn output_name(node: &Node) -> &'static str {
+    node.strings("clock-output-names")
+        .next()
+        .unwrap_or(node.name())
+}
+
+/// A clock of a fixed rate, e.g. an oscillator.
+pub struct FixedRate {
+    rate: u64,
+}
+
+impl FixedRate {
+    /// Creates the clock of `rate` Hz.
+    pub const fn new(rate: u64) -> Self {
+        Self { rate }
+    }
+}
+
+impl ClkHw for FixedRate {
+    fn recalc_rate(&self, _parent_rate: u64) -> u64 {
+        self.rate
+    }
+}
+
+/// A clock whose rate is the one of its parent multiplied by `mult` and
+/// divided by `div`.
+pub struct FixedFactor {
+    mult: u64,
+    div: u64,
+}
+
+impl FixedFactor {
+    /// Creates the clock of the factor `mult / div`.
+    pub const fn new(mult: u64, div: u64) -> Self {
+        Self { mult, div }
+    }
+}
+
+impl ClkHw for FixedFactor {
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        (parent_rate as u128 * self.mult as u128)
+            .checked_div(self.div as u128)
+            .unwrap_or(0) as u64
+    }
+}
+
+/// A gate enabled by setting a bit, or clearing it if `set_to_disable`.
+pub struct Gate {
+    regs: Regs,
+    offset: usize,
+    bit: u32,
+    set_to_disable: bool,
+}
+
+impl Gate {
+    /// Creates the gate of the bit `bit` of the register at `offset`.
+    pub const fn new(regs: Regs, offset: usize, bit: u32, set_to_disable: bool) -> Self {
+        Self {
+            regs,
+            offset,
+            bit,
+            set_to_disable,
+        }
+    }
+
+    fn set(&self, enabled: bool) {
+        let mask = 1 << self.bit;
+        if enabled != self.set_to_disable {
+            self.regs.modify(self.offset, 0, mask);
+        } else {
+            self.regs.modify(self.offset, mask, 0);
+        }
+    }
+}
+
+impl ClkHw for Gate {
+    fn enable(&self) -> AxResult {
+        self.set(true);
+        Ok(())
+    }
+
+    fn disable(&self) {
+        self.set(false);
+    }
+}
+
+/// A divider of the bits `[shift, shift + width)` of a register, whose value
+/// is the divisor minus one, or the divisor if `one_based`.
+pub struct Divider {
+    regs: Regs,
+    offset: usize,
+    shift: u32,
+    width: u32,
+    one_based: bool,
+}
+
+impl Divider {
+    /// Creates the divider of the bits of the register at `offset`.
+    pub const fn new(regs: Regs, offset: usize, shift: u32, width: u32, one_based: bool) -> Self {
+        Self {
+            regs,
+            offset,
+            shift,
+            width,
+            one_based,
+        }
+    }
+
+    const fn mask(&self) -> u32 {
+        ((1u64 << self.width) - 1) as u32
+    }
+
+    fn max_div(&self) -> u64 {
+        self.mask() as u64 + !self.one_based as u64
+    }
+}
+
+impl ClkHw for Divider {
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        let value = (self.regs.read(self.offset) >> self.shift) & self.mask();
+        let div = value as u64 + !self.one_based as u64;
+        parent_rate.checked_div(div).unwrap_or(0)
+    }
+
+    fn set_rate(&self, rate: u64, parent_rate: u64) -> AxResult {
+        if rate == 0 {
+            return Err(AxError::InvalidInput);
+        }
+        let div = parent_rate.div_ceil(rate).clamp(1, self.max_div());
+        let value = (div - !self.one_based as u64) as u32;
+        self.regs
+            .modify(self.offset, self.mask() << self.shift, value << self.shift);
+        Ok(())
+    }
+}
+
+/// A mux of the bits `[shift, shift + width)` of a register, whose value is
+/// the index of the parent.
+pub struct Mux {
+    regs: Regs,
+    offset: usize,
+    shift: u32,
+    width: u32,
+}
+
+impl Mux {
+    /// Creates the mux of the bits of the register at `offset`.
+    pub const fn new(regs: Regs, offset: usize, shift: u32, width: u32) -> Self {
+        Self {
+            regs,
+            offset,
+            shift,
+            width,
+        }
+    }
+
+    const fn mask(&self) -> u32 {
+        ((1u64 << self.width) - 1) as u32
+    }
+}
+
+impl ClkHw for Mux {
+    fn parent_index(&self) -> usize {
+        ((self.regs.read(self.offset) >> self.shift) & self.mask()) as usize
+    }
+
+    fn set_parent(&self, index: usize) -> AxResult {
+        if index > self.mask() as usize {
+            return Err(AxError::InvalidInput);
+        }
+        self.regs.modify(
+            self.offset,
+            self.mask() << self.shift,
+            (index as u32) << self.shift,
+        );
+        Ok(())
+    }
+}
+
+/// Probes a `fixed-clock` node, of the rate in `clock-frequency`.
+pub(crate) fn probe_fixed_rate(node: &Node) -> AxResult<Vec<Option<Clk>>> {
+    let rate = node
+        .property_u64("clock-frequency")
+        .ok_or(AxError::InvalidData)?;
+    let clk = Clk::new(output_name(node), FixedRate::new(rate), Vec::new());
+    Ok(vec![Some(clk)])
+}
+
+/// Probes a `fixed-factor-clock` node, of the factor in `clock-mult` and
+/// `clock-div`.
+pub(crate) fn probe_fixed_factor(node: &Node) -> AxResult<Vec<Option<Clk>>> {
+    let parent = crate::get(node, None)?;
+    let mult = node
+        .property_u32("clock-mult")
+        .ok_or(AxError::InvalidData)?;
+    let div = node.property_u32("clock-div").ok_or(AxError::InvalidData)?;
+    let hw = FixedFactor::new(mult as u64, div as u64);
+    let clk = Clk::new(output_name(node), hw, vec![Some(parent)]);
+    Ok(vec![Some(clk)])
+}
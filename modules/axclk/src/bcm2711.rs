@@ -0,0 +1,258 @@
+//! The clock manager (CPRMAN) of BCM2711, the SoC of Raspberry Pi 4.
+//!
+//! The PLLs and their channels are set up by the firmware, so only their
+//! rates are read from the `A2W_*` registers. The peripheral clocks, e.g. the
+//! ones of the UART and the eMMC controllers, are enabled, muxed and divided
+//! by their `CM_*CTL` and `CM_*DIV` registers.
+//!
+//! The clocks are indexed by the IDs of the device tree bindings of Linux
+//! (`dt-bindings/clock/bcm2835.h`), and fed by the oscillator of the first
+//! clock of the node.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult};
+use axhal::dtb::Node;
+
+use crate::{Clk, ClkHw, Regs};
+
+/// The password in the top byte of the values written to the registers.
+const CM_PASSWORD: u32 = 0x5a00_0000;
+const CM_SRC_MASK: u32 = 0xf;
+const CM_ENABLE: u32 = 1 << 4;
+const CM_BUSY: u32 = 1 << 7;
+/// The number of the fraction bits of the divisors in `CM_*DIV`.
+const CM_DIV_FRAC_BITS: u32 = 12;
+
+const A2W_PLL_CTRL_NDIV_MASK: u32 = 0x3ff;
+const A2W_PLL_CTRL_PDIV_SHIFT: u32 = 12;
+const A2W_PLL_CTRL_PDIV_MASK: u32 = 0x7;
+/// The number of the bits of the fraction of the feedback divisor.
+const A2W_PLL_FRAC_BITS: u32 = 20;
+const A2W_PLL_CHANNEL_DISABLE: u32 = 1 << 8;
+const A2W_PLL_DIV_MASK: u32 = 0xff;
+
+/// The number of the clock IDs, up to `BCM2711_CLOCK_EMMC2`.
+const NUM_CLOCKS: usize = 52;
+
+/// The PLLs: the ID, the name, the `A2W_*_CTRL` and the `A2W_*_FRAC`
+/// registers. BCM2711 has no PLLH.
+const PLLS: &[(usize, &str, usize, usize)] = &[
+    (0, "plla", 0x1100, 0x1200),
+    (1, "pllb", 0x11e0, 0x12e0),
+    (2, "pllc", 0x1120, 0x1220),
+    (3, "plld", 0x1140, 0x1240),
+];
+
+/// The channels of the PLLs: the ID, the name, the ID of the PLL and the
+/// `A2W_*` register of the divider.
+const CHANNELS: &[(usize, &str, usize, usize)] = &[
+    (5, "plla_core", 0, 0x1400),
+    (6, "plla_per", 0, 0x1500),
+    (7, "pllb_arm", 1, 0x13e0),
+    (8, "pllc_core0", 2, 0x1620),
+    (9, "pllc_core1", 2, 0x1420),
+    (10, "pllc_core2", 2, 0x1320),
+    (11, "pllc_per", 2, 0x1520),
+    (12, "plld_core", 3, 0x1440),
+    (13, "plld_per", 3, 0x1540),
+    (32, "plla_dsi0", 0, 0x1300),
+    (33, "plla_ccp2", 0, 0x1600),
+    (34, "plld_dsi0", 3, 0x1340),
+    (35, "plld_dsi1", 3, 0x1640),
+];
+
+/// The parents of the VPU clocks by the source index, as the IDs of the
+/// channels, `None` for the ground and the test inputs, and `Some(usize::MAX)`
+/// for the oscillator.
+const VPU_PARENTS: &[Option<usize>] = &[
+    None,
+    Some(usize::MAX),
+    None,
+    None,
+    Some(5),
+    Some(8),
+    Some(12),
+    None,
+    Some(9),
+    Some(10),
+];
+
+/// The parents of the other peripheral clocks, see [`VPU_PARENTS`].
+const PERIPH_PARENTS: &[Option<usize>] = &[
+    None,
+    Some(usize::MAX),
+    None,
+    None,
+    Some(6),
+    Some(11),
+    Some(13),
+    None,
+];
+
+/// The peripheral clocks: the ID, the name, the `CM_*CTL` register (followed
+/// by `CM_*DIV`), the integer and the fraction bits of the divisor, and
+/// whether it's fed by the VPU parents.
+const PERIPHS: &[(usize, &str, usize, u32, u32, bool)] = &[
+    (20, "vpu", 0x008, 12, 8, true),
+    (19, "uart", 0x0f0, 10, 12, false),
+    (28, "emmc", 0x1c0, 4, 8, false),
+    (51, "emmc2", 0x1d0, 4, 8, false),
+    (30, "pwm", 0x0a0, 12, 12, false),
+    (31, "pcm", 0x098, 12, 12, false),
+    (38, "gp0", 0x070, 12, 12, false),
+    (39, "gp1", 0x078, 12, 12, false),
+    (40, "gp2", 0x080, 12, 12, false),
+];
+
+/// A PLL, whose rate is the one of the oscillator multiplied by the
+/// fractional `NDIV` and divided by `PDIV`.
+struct Pll {
+    regs: Regs,
+    ctrl: usize,
+    frac: usize,
+}
+
+impl ClkHw for Pll {
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        let ctrl = self.regs.read(self.ctrl);
+        let ndiv = (ctrl & A2W_PLL_CTRL_NDIV_MASK) as u128;
+        let pdiv = ((ctrl >> A2W_PLL_CTRL_PDIV_SHIFT) & A2W_PLL_CTRL_PDIV_MASK) as u128;
+        let frac = (self.regs.read(self.frac) & ((1 << A2W_PLL_FRAC_BITS) - 1)) as u128;
+        let mult = (ndiv << A2W_PLL_FRAC_BITS) + frac;
+        ((parent_rate as u128 * mult) >> A2W_PLL_FRAC_BITS)
+            .checked_div(pdiv)
+            .unwrap_or(0) as u64
+    }
+}
+
+/// A channel of a PLL, divided by an integer.
+struct PllChannel {
+    regs: Regs,
+    reg: usize,
+}
+
+impl ClkHw for PllChannel {
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        let value = self.regs.read(self.reg);
+        if value & A2W_PLL_CHANNEL_DISABLE != 0 {
+            return 0;
+        }
+        // 0 is the divisor of 256
+        let div = match value & A2W_PLL_DIV_MASK {
+            0 => 256,
+            div => div as u64,
+        };
+        parent_rate / div
+    }
+}
+
+/// A peripheral clock, with a gate, a mux and a fractional divider.
+struct Periph {
+    regs: Regs,
+    ctl: usize,
+    /// The mask of the divisor in `CM_*DIV`.
+    div_mask: u32,
+}
+
+impl Periph {
+    fn write_ctl(&self, value: u32) {
+        self.regs
+            .write(self.ctl, CM_PASSWORD | (value & !0xff00_0000));
+    }
+
+    fn wait_idle(&self) {
+        for _ in 0..1000 {
+            if self.regs.read(self.ctl) & CM_BUSY == 0 {
+                return;
+            }
+            axhal::time::busy_wait(Duration::from_micros(1));
+        }
+        warn!("clock at {:#x} is still busy", self.ctl);
+    }
+}
+
+impl ClkHw for Periph {
+    fn enable(&self) -> AxResult {
+        self.write_ctl(self.regs.read(self.ctl) | CM_ENABLE);
+        Ok(())
+    }
+
+    fn disable(&self) {
+        self.write_ctl(self.regs.read(self.ctl) & !CM_ENABLE);
+        self.wait_idle();
+    }
+
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        let div = (self.regs.read(self.ctl + 4) & self.div_mask) as u64;
+        (parent_rate << CM_DIV_FRAC_BITS)
+            .checked_div(div)
+            .unwrap_or(0)
+    }
+
+    fn set_rate(&self, rate: u64, parent_rate: u64) -> AxResult {
+        if rate == 0 {
+            return Err(AxError::InvalidInput);
+        }
+        // round the divisor up, so the rate is not above the one requested
+        let div = (parent_rate << CM_DIV_FRAC_BITS).div_ceil(rate);
+        let div = div.clamp(1 << CM_DIV_FRAC_BITS, self.div_mask as u64) as u32;
+        self.regs
+            .write(self.ctl + 4, CM_PASSWORD | (div & self.div_mask));
+        Ok(())
+    }
+
+    fn parent_index(&self) -> usize {
+        (self.regs.read(self.ctl) & CM_SRC_MASK) as usize
+    }
+
+    fn set_parent(&self, index: usize) -> AxResult {
+        if index > CM_SRC_MASK as usize {
+            return Err(AxError::InvalidInput);
+        }
+        let ctl = self.regs.read(self.ctl);
+        // the source must not be changed while the clock is running
+        if ctl & CM_ENABLE != 0 {
+            self.write_ctl(ctl & !CM_ENABLE);
+            self.wait_idle();
+        }
+        self.write_ctl((ctl & !CM_SRC_MASK) | index as u32);
+        Ok(())
+    }
+}
+
+/// Probes a `brcm,bcm2711-cprman` node.
+pub(crate) fn probe(node: &Node) -> AxResult<Vec<Option<Clk>>> {
+    let osc = crate::get(node, None)?;
+    let regs = Regs::from_node(node, 0)?;
+    let mut clocks = vec![None; NUM_CLOCKS];
+    for &(id, name, ctrl, frac) in PLLS {
+        let pll = Pll { regs, ctrl, frac };
+        clocks[id] = Some(Clk::new(name, pll, vec![Some(osc.clone())]));
+    }
+    for &(id, name, pll, reg) in CHANNELS {
+        let parent = clocks[pll].clone();
+        clocks[id] = Some(Clk::new(name, PllChannel { regs, reg }, vec![parent]));
+    }
+    for &(id, name, ctl, int_bits, frac_bits, vpu) in PERIPHS {
+        let parents = if vpu { VPU_PARENTS } else { PERIPH_PARENTS };
+        let parents = parents
+            .iter()
+            .map(|parent| match *parent {
+                Some(usize::MAX) => Some(osc.clone()),
+                Some(channel) => clocks[channel].clone(),
+                None => None,
+            })
+            .collect();
+        let div_mask = ((1 << (int_bits + frac_bits)) - 1) << (CM_DIV_FRAC_BITS - frac_bits);
+        let periph = Periph {
+            regs,
+            ctl,
+            div_mask,
+        };
+        clocks[id] = Some(Clk::new(name, periph, parents));
+    }
+    Ok(clocks)
+}
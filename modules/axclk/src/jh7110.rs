@@ -0,0 +1,230 @@
+//! The clocks and the reset lines of StarFive JH7110, the SoC of VisionFive 2.
+//!
+//! The three PLLs are in the SYS syscon, whose rates are read from its
+//! registers, indexed by the IDs of `dt-bindings/clock/starfive,jh7110-crg.h`
+//! of Linux. The other clocks are in the clock and reset generators (CRGs),
+//! where each clock is a register at the offset of 4 times its ID, with a
+//! gate, a mux and a divider, see [`Jh7110Clk`]. As the parents of the clocks
+//! differ in each CRG, the tables of them are not included, and the platform
+//! builds the clocks it uses. The reset lines of the CRGs are probed from the
+//! device tree.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::{AxError, AxResult};
+use axhal::dtb::Node;
+
+use crate::reset::{RegisterReset, ResetOps};
+use crate::{Clk, ClkHw, Regs};
+
+const CLK_ENABLE: u32 = 1 << 31;
+const CLK_MUX_SHIFT: u32 = 24;
+const CLK_MUX_MASK: u32 = 0x3f;
+const CLK_DIV_MASK: u32 = 0xff_ffff;
+
+/// The number of the bits of the fraction of the feedback divisor.
+const PLL_FRAC_BITS: u32 = 24;
+
+/// The fields of a PLL in the SYS syscon.
+struct PllRegs {
+    name: &'static str,
+    /// The register and the bits of `DACPD` and `DSMPD`, both set in the
+    /// integer mode and cleared in the fraction mode.
+    pd: usize,
+    dacpd_shift: u32,
+    dsmpd_shift: u32,
+    /// The register and the shift of the 12-bit feedback divisor.
+    fbdiv: usize,
+    fbdiv_shift: u32,
+    /// The register of the 24-bit fraction and the 2-bit `POSTDIV1` at bit 28.
+    frac: usize,
+    /// The register of the 6-bit reference divisor.
+    prediv: usize,
+}
+
+const PLLS: [PllRegs; 3] = [
+    PllRegs {
+        name: "pll0_out",
+        pd: 0x18,
+        dacpd_shift: 24,
+        dsmpd_shift: 25,
+        fbdiv: 0x1c,
+        fbdiv_shift: 0,
+        frac: 0x20,
+        prediv: 0x24,
+    },
+    PllRegs {
+        name: "pll1_out",
+        pd: 0x24,
+        dacpd_shift: 15,
+        dsmpd_shift: 16,
+        fbdiv: 0x24,
+        fbdiv_shift: 17,
+        frac: 0x28,
+        prediv: 0x2c,
+    },
+    PllRegs {
+        name: "pll2_out",
+        pd: 0x2c,
+        dacpd_shift: 15,
+        dsmpd_shift: 16,
+        fbdiv: 0x2c,
+        fbdiv_shift: 17,
+        frac: 0x30,
+        prediv: 0x34,
+    },
+];
+
+/// A PLL, whose rate is the one of the oscillator multiplied by the feedback
+/// divisor, and divided by the reference divisor and `2 ^ POSTDIV1`.
+struct Pll {
+    regs: Regs,
+    pll: &'static PllRegs,
+}
+
+impl ClkHw for Pll {
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        let pll = self.pll;
+        let pd = self.regs.read(pll.pd);
+        let dacpd = (pd >> pll.dacpd_shift) & 1;
+        let dsmpd = (pd >> pll.dsmpd_shift) & 1;
+        let fbdiv = ((self.regs.read(pll.fbdiv) >> pll.fbdiv_shift) & 0xfff) as u128;
+        let frac_reg = self.regs.read(pll.frac);
+        let postdiv1 = (frac_reg >> 28) & 0x3;
+        let prediv = (self.regs.read(pll.prediv) & 0x3f) as u128;
+        let mult = match (dacpd, dsmpd) {
+            // the fraction mode
+            (0, 0) => (fbdiv << PLL_FRAC_BITS) + (frac_reg & 0xff_ffff) as u128,
+            // the integer mode
+            (1, 1) => fbdiv << PLL_FRAC_BITS,
+            _ => return 0,
+        };
+        (((parent_rate as u128 * mult) >> PLL_FRAC_BITS) >> postdiv1)
+            .checked_div(prediv)
+            .unwrap_or(0) as u64
+    }
+}
+
+/// A clock of a CRG, in the register at `4 * id`.
+///
+/// The bit 31 is the gate, the bits 24 to 29 select the parent, and the bits
+/// 0 to 23 are the divisor, each of which is used only if the clock has it.
+pub struct Jh7110Clk {
+    regs: Regs,
+    offset: usize,
+    gate: bool,
+    mux: bool,
+    max_div: u32,
+}
+
+impl Jh7110Clk {
+    /// Creates the clock `id` of the CRG, with a gate if `gate`, a mux if
+    /// `mux`, and a divider up to `max_div` if it's not 0.
+    pub const fn new(regs: Regs, id: usize, gate: bool, mux: bool, max_div: u32) -> Self {
+        Self {
+            regs,
+            offset: id * 4,
+            gate,
+            mux,
+            max_div,
+        }
+    }
+}
+
+impl ClkHw for Jh7110Clk {
+    fn enable(&self) -> AxResult {
+        if self.gate {
+            self.regs.modify(self.offset, 0, CLK_ENABLE);
+        }
+        Ok(())
+    }
+
+    fn disable(&self) {
+        if self.gate {
+            self.regs.modify(self.offset, CLK_ENABLE, 0);
+        }
+    }
+
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        if self.max_div == 0 {
+            return parent_rate;
+        }
+        let div = self.regs.read(self.offset) & CLK_DIV_MASK;
+        parent_rate.checked_div(div as u64).unwrap_or(0)
+    }
+
+    fn set_rate(&self, rate: u64, parent_rate: u64) -> AxResult {
+        if self.max_div == 0 || rate == 0 {
+            return Err(AxError::Unsupported);
+        }
+        let div = parent_rate.div_ceil(rate).clamp(1, self.max_div as u64) as u32;
+        self.regs.modify(self.offset, CLK_DIV_MASK, div);
+        Ok(())
+    }
+
+    fn parent_index(&self) -> usize {
+        if !self.mux {
+            return 0;
+        }
+        ((self.regs.read(self.offset) >> CLK_MUX_SHIFT) & CLK_MUX_MASK) as usize
+    }
+
+    fn set_parent(&self, index: usize) -> AxResult {
+        if !self.mux || index > CLK_MUX_MASK as usize {
+            return Err(AxError::InvalidInput);
+        }
+        self.regs.modify(
+            self.offset,
+            CLK_MUX_MASK << CLK_MUX_SHIFT,
+            (index as u32) << CLK_MUX_SHIFT,
+        );
+        Ok(())
+    }
+}
+
+/// Probes a `starfive,jh7110-pll` node, in the SYS syscon of its parent.
+pub(crate) fn probe_pll(node: &Node) -> AxResult<Vec<Option<Clk>>> {
+    let osc = crate::get(node, None)?;
+    let syscon = node.parent().ok_or(AxError::InvalidData)?;
+    let regs = Regs::from_node(&syscon, 0)?;
+    Ok(PLLS
+        .iter()
+        .map(|pll| {
+            let hw = Pll { regs, pll };
+            Some(Clk::new(pll.name, hw, alloc::vec![Some(osc.clone())]))
+        })
+        .collect())
+}
+
+/// Probes the reset lines of a CRG, in `count` bits of the registers at
+/// `assert`, whose status are in the registers at `status`.
+fn probe_reset(
+    node: &Node,
+    assert: usize,
+    status: usize,
+    count: u32,
+) -> AxResult<Arc<dyn ResetOps>> {
+    let regs = Regs::from_node(node, 0)?;
+    Ok(Arc::new(RegisterReset::new(
+        regs,
+        assert,
+        Some(status),
+        count,
+    )))
+}
+
+/// Probes the reset lines of the SYS CRG.
+pub(crate) fn probe_syscrg_reset(node: &Node) -> AxResult<Arc<dyn ResetOps>> {
+    probe_reset(node, 0x2f8, 0x308, 126)
+}
+
+/// Probes the reset lines of the AON (always-on) CRG.
+pub(crate) fn probe_aoncrg_reset(node: &Node) -> AxResult<Arc<dyn ResetOps>> {
+    probe_reset(node, 0x38, 0x3c, 8)
+}
+
+/// Probes the reset lines of the STG (system-top-group) CRG.
+pub(crate) fn probe_stgcrg_reset(node: &Node) -> AxResult<Arc<dyn ResetOps>> {
+    probe_reset(node, 0x74, 0x78, 23)
+}
@@ -0,0 +1,420 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) clock and reset controller
+//! module.
+//!
+//! The clocks form a tree, where each [`Clk`] is driven by the operations of
+//! [`ClkHw`], e.g. a gate or a divider, and is fed by one of its parents. A
+//! clock is enabled after its parent, and is reference-counted, so a clock
+//! shared by several devices is disabled only when all of them disable it.
+//!
+//! The clock controllers are probed from the device tree by [`init`], and
+//! registered by the phandles of their nodes. Then the drivers get the clocks
+//! of their devices by the `clocks` and `clock-names` properties of their
+//! nodes by [`get`], and the reset lines by [`reset::get`].
+//!
+//! The providers of the clocks are:
+//!
+//! - [`basic`]: the fixed-rate and the fixed-factor clocks of the device tree,
+//!   and the gates, the dividers and the muxes in the registers.
+//! - [`bcm2711`]: the PLLs and the peripheral clocks of the clock manager of
+//!   BCM2711 (Raspberry Pi 4).
+//! - [`jh7110`]: the PLLs of StarFive JH7110 (VisionFive 2), and the clocks
+//!   and the reset lines of its clock and reset generators.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+pub mod basic;
+pub mod bcm2711;
+pub mod jh7110;
+pub mod reset;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ptr::NonNull;
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+use axsync::Mutex;
+
+/// The hardware operations of a clock.
+///
+/// The default ones are of a clock always enabled, with the same rate as its
+/// only parent.
+pub trait ClkHw: Send + Sync {
+    /// Enables the clock, after its parent is enabled.
+    fn enable(&self) -> AxResult {
+        Ok(())
+    }
+
+    /// Disables the clock, before its parent is disabled.
+    fn disable(&self) {}
+
+    /// Returns the rate of the clock in Hz, from the rate of its parent.
+    fn recalc_rate(&self, parent_rate: u64) -> u64 {
+        parent_rate
+    }
+
+    /// Sets the rate of the clock to the closest one not above `rate`.
+    fn set_rate(&self, rate: u64, parent_rate: u64) -> AxResult {
+        let _ = (rate, parent_rate);
+        ax_err!(Unsupported)
+    }
+
+    /// Returns the index of the current parent in the parents of the clock.
+    fn parent_index(&self) -> usize {
+        0
+    }
+
+    /// Selects the parent of the index `index`.
+    fn set_parent(&self, index: usize) -> AxResult {
+        let _ = index;
+        ax_err!(Unsupported)
+    }
+}
+
+struct ClkCore {
+    name: String,
+    hw: Box<dyn ClkHw>,
+    /// The possible parents, `None` for the ones not provided, e.g. the test
+    /// inputs of a mux.
+    parents: Vec<Option<Clk>>,
+    enable_count: Mutex<usize>,
+}
+
+/// A clock in the clock tree, shared by its consumers.
+#[derive(Clone)]
+pub struct Clk(Arc<ClkCore>);
+
+impl Clk {
+    /// Creates the clock `name` driven by `hw`, with the possible parents
+    /// `parents` in the order of [`ClkHw::parent_index`].
+    pub fn new(
+        name: impl Into<String>,
+        hw: impl ClkHw + 'static,
+        parents: Vec<Option<Clk>>,
+    ) -> Self {
+        Self(Arc::new(ClkCore {
+            name: name.into(),
+            hw: Box::new(hw),
+            parents,
+            enable_count: Mutex::new(0),
+        }))
+    }
+
+    /// The name of the clock.
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Returns the current parent, or `None` for a root clock.
+    pub fn parent(&self) -> Option<Clk> {
+        self.0.parents.get(self.0.hw.parent_index())?.clone()
+    }
+
+    /// Returns the rate in Hz, 0 if unknown.
+    pub fn rate(&self) -> u64 {
+        let parent_rate = self.parent().map_or(0, |parent| parent.rate());
+        self.0.hw.recalc_rate(parent_rate)
+    }
+
+    /// Returns whether the clock is enabled by any consumer.
+    pub fn is_enabled(&self) -> bool {
+        *self.0.enable_count.lock() > 0
+    }
+
+    /// Enables the clock and its parents.
+    pub fn enable(&self) -> AxResult {
+        let mut count = self.0.enable_count.lock();
+        if *count == 0 {
+            let parent = self.parent();
+            if let Some(parent) = &parent {
+                parent.enable()?;
+            }
+            if let Err(e) = self.0.hw.enable() {
+                if let Some(parent) = &parent {
+                    parent.disable();
+                }
+                return Err(e);
+            }
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Disables the clock, and its parents not used by other clocks, if
+    /// this is the last consumer enabling it.
+    pub fn disable(&self) {
+        let mut count = self.0.enable_count.lock();
+        match *count {
+            0 => warn!("clock {} is disabled more times than enabled", self.name()),
+            1 => {
+                self.0.hw.disable();
+                if let Some(parent) = self.parent() {
+                    parent.disable();
+                }
+                *count = 0;
+            }
+            _ => *count -= 1,
+        }
+    }
+
+    /// Sets the rate in Hz, to the closest one not above `rate` the hardware
+    /// supports.
+    pub fn set_rate(&self, rate: u64) -> AxResult {
+        let parent_rate = self.parent().map_or(0, |parent| parent.rate());
+        self.0.hw.set_rate(rate, parent_rate)
+    }
+
+    /// Switches to the parent `parent`, which must be one of the possible
+    /// parents of the clock.
+    ///
+    /// If the clock is enabled, the new parent is enabled before switching,
+    /// and the old one is disabled after it.
+    pub fn set_parent(&self, parent: &Clk) -> AxResult {
+        let index = self
+            .0
+            .parents
+            .iter()
+            .position(|p| p.as_ref().is_some_and(|p| Arc::ptr_eq(&p.0, &parent.0)))
+            .ok_or(AxError::InvalidInput)?;
+        let count = self.0.enable_count.lock();
+        let old = self.parent();
+        if *count > 0 {
+            parent.enable()?;
+        }
+        if let Err(e) = self.0.hw.set_parent(index) {
+            if *count > 0 {
+                parent.disable();
+            }
+            return Err(e);
+        }
+        if *count > 0 {
+            if let Some(old) = old {
+                old.disable();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Clk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Clk")
+            .field("name", &self.name())
+            .field("rate", &self.rate())
+            .finish()
+    }
+}
+
+/// The 32-bit registers of a controller.
+#[derive(Debug, Clone, Copy)]
+pub struct Regs {
+    base: NonNull<u8>,
+}
+
+unsafe impl Send for Regs {}
+unsafe impl Sync for Regs {}
+
+impl Regs {
+    /// Creates the registers mapped at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the virtual address of the registers of the controller,
+    /// which are mapped as device memory.
+    pub const unsafe fn new(base: NonNull<u8>) -> Self {
+        Self { base }
+    }
+
+    /// Maps the `idx`-th region in `reg` of the device tree node `node`.
+    pub fn from_node(node: &Node, idx: usize) -> AxResult<Self> {
+        let vaddr = node.mmio(idx).ok_or(AxError::BadAddress)?;
+        // the region is checked to be in the MMIO regions mapped at boot
+        Ok(unsafe { Self::new(NonNull::new(vaddr.as_mut_ptr()).unwrap()) })
+    }
+
+    /// Reads the register at `offset`.
+    pub fn read(&self, offset: usize) -> u32 {
+        unsafe { self.base.as_ptr().add(offset).cast::<u32>().read_volatile() }
+    }
+
+    /// Writes `value` to the register at `offset`.
+    pub fn write(&self, offset: usize, value: u32) {
+        unsafe {
+            self.base
+                .as_ptr()
+                .add(offset)
+                .cast::<u32>()
+                .write_volatile(value)
+        }
+    }
+
+    /// Clears the bits `clear` and sets the bits `set` of the register at
+    /// `offset`.
+    pub fn modify(&self, offset: usize, clear: u32, set: u32) {
+        self.write(offset, (self.read(offset) & !clear) | set);
+    }
+}
+
+/// The clocks of each provider by its phandle, indexed by the first cell of
+/// the specifiers, or the only one if `#clock-cells` is 0.
+static PROVIDERS: Mutex<BTreeMap<u32, Vec<Option<Clk>>>> = Mutex::new(BTreeMap::new());
+
+/// Registers the clocks of the controller of the device tree node with the
+/// phandle `phandle`, indexed by the IDs of the clocks in the specifiers.
+pub fn register_provider(phandle: u32, clocks: Vec<Option<Clk>>) {
+    PROVIDERS.lock().insert(phandle, clocks);
+}
+
+/// Returns the registered clock named `name`.
+pub fn get_by_name(name: &str) -> Option<Clk> {
+    let providers = PROVIDERS.lock();
+    providers
+        .values()
+        .flatten()
+        .flatten()
+        .find(|clk| clk.name() == name)
+        .cloned()
+}
+
+/// Returns the phandle and the arguments of the `index`-th specifier in the
+/// property `prop` of `node`, whose number of arguments is the property
+/// `cells_prop` of the provider, e.g. `clocks` and `#clock-cells`.
+///
+/// Returns `None` if there is no such specifier, or [`AxError::NotFound`] if
+/// the provider is not in the device tree.
+pub(crate) fn specifier(
+    node: &Node,
+    prop: &str,
+    cells_prop: &str,
+    index: usize,
+) -> AxResult<Option<(u32, Vec<u32>)>> {
+    let mut cells = node.cells(prop);
+    for i in 0.. {
+        let Some(phandle) = cells.next() else {
+            return Ok(None);
+        };
+        let provider = axhal::dtb::find_by_phandle(phandle).ok_or(AxError::NotFound)?;
+        let num_args = provider.property_u32(cells_prop).unwrap_or(0) as usize;
+        let args = cells.by_ref().take(num_args).collect::<Vec<_>>();
+        if args.len() < num_args {
+            return ax_err!(InvalidData);
+        }
+        if i == index {
+            return Ok(Some((phandle, args)));
+        }
+    }
+    unreachable!()
+}
+
+/// Returns the index of the name `name` in the property `names_prop` of
+/// `node`, or 0 if `name` is `None`.
+pub(crate) fn name_index(node: &Node, names_prop: &str, name: Option<&str>) -> AxResult<usize> {
+    match name {
+        Some(name) => node
+            .strings(names_prop)
+            .position(|n| n == name)
+            .ok_or(AxError::NotFound),
+        None => Ok(0),
+    }
+}
+
+/// Returns the registered clock of the specifier `spec`.
+fn provided_clock((phandle, args): (u32, Vec<u32>)) -> AxResult<Clk> {
+    let providers = PROVIDERS.lock();
+    let clocks = providers.get(&phandle).ok_or(AxError::NotFound)?;
+    let id = args.first().copied().unwrap_or(0) as usize;
+    clocks.get(id).cloned().flatten().ok_or(AxError::NotFound)
+}
+
+/// Returns the clock of the device tree node `node` named `name` in its
+/// `clock-names`, or the first one if `name` is `None`.
+///
+/// Returns [`AxError::NotFound`] if the clock or its provider is not found.
+pub fn get(node: &Node, name: Option<&str>) -> AxResult<Clk> {
+    let index = name_index(node, "clock-names", name)?;
+    let spec = specifier(node, "clocks", "#clock-cells", index)?.ok_or(AxError::NotFound)?;
+    provided_clock(spec)
+}
+
+/// Returns all the clocks of the device tree node `node`, in the order of its
+/// `clocks`.
+pub fn get_all(node: &Node) -> AxResult<Vec<Clk>> {
+    let mut clocks = Vec::new();
+    while let Some(spec) = specifier(node, "clocks", "#clock-cells", clocks.len())? {
+        clocks.push(provided_clock(spec)?);
+    }
+    Ok(clocks)
+}
+
+/// Probes the clock controller of a device tree node, and returns its clocks
+/// indexed by the IDs in the specifiers.
+type ProbeFn = fn(&Node) -> AxResult<Vec<Option<Clk>>>;
+
+/// The clock controllers by their compatible strings.
+const DRIVERS: &[(&str, ProbeFn)] = &[
+    ("fixed-clock", basic::probe_fixed_rate),
+    ("fixed-factor-clock", basic::probe_fixed_factor),
+    ("brcm,bcm2711-cprman", bcm2711::probe),
+    ("starfive,jh7110-pll", jh7110::probe_pll),
+];
+
+/// Probes the clock and reset controllers in the device tree.
+///
+/// The controllers whose parent clocks are not registered yet are probed
+/// again after the others, as the parents may be after the children in the
+/// device tree.
+pub fn init() {
+    info!("Initialize clock and reset controllers...");
+    let mut failed = BTreeSet::new();
+    loop {
+        let mut deferred = 0;
+        let mut probed = 0;
+        axhal::dtb::for_each_node(|node| {
+            let Some(phandle) = node.phandle() else {
+                return;
+            };
+            if !node.is_enabled()
+                || failed.contains(&phandle)
+                || PROVIDERS.lock().contains_key(&phandle)
+            {
+                return;
+            }
+            let Some(&(compatible, probe)) = DRIVERS.iter().find(|(c, _)| node.is_compatible(c))
+            else {
+                return;
+            };
+            match probe(&node) {
+                Ok(clocks) => {
+                    debug!(
+                        "  {} ({}): {} clocks",
+                        node.name(),
+                        compatible,
+                        clocks.len()
+                    );
+                    register_provider(phandle, clocks);
+                    probed += 1;
+                }
+                Err(AxError::NotFound) => deferred += 1,
+                Err(e) => {
+                    warn!("failed to probe the clocks of {}: {:?}", node.name(), e);
+                    failed.insert(phandle);
+                }
+            }
+        });
+        if probed == 0 {
+            if deferred > 0 {
+                warn!("{} clock controllers miss their parents", deferred);
+            }
+            break;
+        }
+    }
+    reset::probe();
+}
@@ -0,0 +1,190 @@
+//! Reset controllers, which hold the devices in reset until their clocks are
+//! enabled.
+//!
+//! The reset controllers are probed from the device tree with the clock
+//! controllers by [`crate::init`], and the reset lines of the devices are got
+//! by the `resets` and `reset-names` properties of their nodes by [`get`].
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+use axsync::Mutex;
+
+use crate::Regs;
+
+/// The maximum number of times the status of a reset line is polled.
+const MAX_POLLS: usize = 1000;
+
+/// The operations of a reset controller, on its reset lines by the IDs.
+pub trait ResetOps: Send + Sync {
+    /// Puts the device of the reset line `id` in reset.
+    fn assert(&self, id: u32) -> AxResult;
+
+    /// Takes the device of the reset line `id` out of reset.
+    fn deassert(&self, id: u32) -> AxResult;
+
+    /// Returns whether the reset line `id` is asserted.
+    fn status(&self, id: u32) -> AxResult<bool> {
+        let _ = id;
+        ax_err!(Unsupported)
+    }
+}
+
+/// A reset line of a device.
+#[derive(Clone)]
+pub struct ResetControl {
+    ops: Arc<dyn ResetOps>,
+    id: u32,
+}
+
+impl ResetControl {
+    /// Puts the device in reset.
+    pub fn assert(&self) -> AxResult {
+        self.ops.assert(self.id)
+    }
+
+    /// Takes the device out of reset.
+    pub fn deassert(&self) -> AxResult {
+        self.ops.deassert(self.id)
+    }
+
+    /// Resets the device, by asserting the reset line for a microsecond.
+    pub fn reset(&self) -> AxResult {
+        self.assert()?;
+        axhal::time::busy_wait(Duration::from_micros(1));
+        self.deassert()
+    }
+
+    /// Returns whether the device is in reset.
+    pub fn is_asserted(&self) -> AxResult<bool> {
+        self.ops.status(self.id)
+    }
+}
+
+/// The reset controllers by the phandles of their nodes.
+static PROVIDERS: Mutex<BTreeMap<u32, Arc<dyn ResetOps>>> = Mutex::new(BTreeMap::new());
+
+/// Registers the reset controller of the device tree node with the phandle
+/// `phandle`.
+pub fn register_provider(phandle: u32, ops: Arc<dyn ResetOps>) {
+    PROVIDERS.lock().insert(phandle, ops);
+}
+
+/// Returns the reset line of the device tree node `node` named `name` in its
+/// `reset-names`, or the first one if `name` is `None`.
+///
+/// The first cell of the specifier is the ID of the reset line.
+pub fn get(node: &Node, name: Option<&str>) -> AxResult<ResetControl> {
+    let index = crate::name_index(node, "reset-names", name)?;
+    let (phandle, args) =
+        crate::specifier(node, "resets", "#reset-cells", index)?.ok_or(AxError::NotFound)?;
+    let ops = PROVIDERS
+        .lock()
+        .get(&phandle)
+        .cloned()
+        .ok_or(AxError::NotFound)?;
+    let id = args.first().copied().unwrap_or(0);
+    Ok(ResetControl { ops, id })
+}
+
+/// The reset lines of the bits of consecutive registers, asserted by setting
+/// the bits.
+///
+/// The status of the lines are the bits of the registers at `status`, which
+/// are set when the lines are deasserted, polled until the lines change.
+pub struct RegisterReset {
+    regs: Regs,
+    assert: usize,
+    status: Option<usize>,
+    count: u32,
+}
+
+impl RegisterReset {
+    /// Creates the `count` reset lines of the registers at `assert`.
+    pub const fn new(regs: Regs, assert: usize, status: Option<usize>, count: u32) -> Self {
+        Self {
+            regs,
+            assert,
+            status,
+            count,
+        }
+    }
+
+    /// Returns the offset of the register and the mask of the line `id`.
+    fn line(&self, id: u32) -> AxResult<(usize, u32)> {
+        if id >= self.count {
+            return ax_err!(InvalidInput, "invalid reset line");
+        }
+        Ok(((id / 32) as usize * 4, 1 << (id % 32)))
+    }
+
+    fn update(&self, id: u32, assert: bool) -> AxResult {
+        let (offset, mask) = self.line(id)?;
+        if assert {
+            self.regs.modify(self.assert + offset, 0, mask);
+        } else {
+            self.regs.modify(self.assert + offset, mask, 0);
+        }
+        let Some(status) = self.status else {
+            return Ok(());
+        };
+        let done = if assert { 0 } else { mask };
+        for _ in 0..MAX_POLLS {
+            if self.regs.read(status + offset) & mask == done {
+                return Ok(());
+            }
+            axhal::time::busy_wait(Duration::from_micros(1));
+        }
+        ax_err!(ResourceBusy, "reset line timed out")
+    }
+}
+
+impl ResetOps for RegisterReset {
+    fn assert(&self, id: u32) -> AxResult {
+        self.update(id, true)
+    }
+
+    fn deassert(&self, id: u32) -> AxResult {
+        self.update(id, false)
+    }
+
+    fn status(&self, id: u32) -> AxResult<bool> {
+        let (offset, mask) = self.line(id)?;
+        Ok(match self.status {
+            Some(status) => self.regs.read(status + offset) & mask == 0,
+            None => self.regs.read(self.assert + offset) & mask != 0,
+        })
+    }
+}
+
+/// Probes a reset controller of a device tree node.
+type ProbeFn = fn(&Node) -> AxResult<Arc<dyn ResetOps>>;
+
+/// The reset controllers by their compatible strings.
+const DRIVERS: &[(&str, ProbeFn)] = &[
+    ("starfive,jh7110-syscrg", crate::jh7110::probe_syscrg_reset),
+    ("starfive,jh7110-aoncrg", crate::jh7110::probe_aoncrg_reset),
+    ("starfive,jh7110-stgcrg", crate::jh7110::probe_stgcrg_reset),
+];
+
+/// Probes the reset controllers in the device tree.
+pub(crate) fn probe() {
+    axhal::dtb::for_each_node(|node| {
+        let Some(phandle) = node.phandle() else {
+            return;
+        };
+        if !node.is_enabled() || node.property("#reset-cells").is_none() {
+            return;
+        }
+        let Some((_, probe)) = DRIVERS.iter().find(|(c, _)| node.is_compatible(c)) else {
+            return;
+        };
+        match probe(&node) {
+            Ok(ops) => register_provider(phandle, ops),
+            Err(e) => warn!("failed to probe the resets of {}: {:?}", node.name(), e),
+        }
+    });
+}
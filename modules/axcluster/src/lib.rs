@@ -0,0 +1,156 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) clustering module.
+//!
+//! It lets several ArceOS instances on the same network coordinate, e.g. the
+//! primary and the backup of an appliance, by three primitives:
+//!
+//! - Node discovery: each node sends heartbeats by UDP, to a multicast group
+//!   or to a static list of the peers, see [`Discovery`]. The nodes heard of
+//!   are the [`members`] of the cluster.
+//! - Failure detection: a member is [`NodeState::Suspect`] if no heartbeat is
+//!   heard of it for half of the failure timeout, and [`NodeState::Failed`]
+//!   after the whole timeout. The changes are told to the handler set by
+//!   [`set_event_handler`].
+//! - RPC: the requests to the handlers registered by [`register_handler`] on
+//!   the other nodes are sent by [`call`], over a TCP connection to each peer.
+//!
+//! The cluster is joined by [`start`], after the network is initialized.
+//!
+//! # Wire formats
+//!
+//! All the integers are little-endian. A heartbeat is a UDP datagram of:
+//!
+//! | Offset | Content                                 |
+//! |--------|-----------------------------------------|
+//! | 0      | `magic: u32`, `b"AXCL"`                 |
+//! | 4      | `node: u64`, the ID of the sender       |
+//! | 12     | `rpc_port: u16`, the RPC port of it     |
+//!
+//! And each RPC request or response is a header followed by the payload:
+//!
+//! | Offset | Content                                                      |
+//! |--------|--------------------------------------------------------------|
+//! | 0      | `node: u64`, the ID of the caller                            |
+//! | 8      | `id: u32`, the ID of the request, echoed in its response     |
+//! | 12     | `method: u16`                                                |
+//! | 14     | `status: u16`, 0 for the requests and the successful replies |
+//! | 16     | `len: u32`, the size of the payload                          |
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod membership;
+mod rpc;
+
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr};
+use core::time::Duration;
+
+use axerrno::{AxResult, ax_err};
+use lazyinit::LazyInit;
+
+pub use self::membership::{Member, NodeState, members, set_event_handler};
+pub use self::rpc::{RpcHandler, call, register_handler};
+
+/// The default UDP port of the heartbeats.
+pub const DEFAULT_HEARTBEAT_PORT: u16 = 7400;
+/// The default TCP port of the RPCs.
+pub const DEFAULT_RPC_PORT: u16 = 7401;
+
+/// How the nodes find each other.
+#[derive(Debug, Clone)]
+pub enum Discovery {
+    /// Send the heartbeats to a multicast group, which all the nodes join.
+    Multicast(Ipv4Addr),
+    /// Send the heartbeats to each of the addresses, e.g. when the network
+    /// does not route multicast.
+    Static(Vec<IpAddr>),
+}
+
+/// The configuration of this node.
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    /// The ID of this node, unique in the cluster.
+    pub node_id: u64,
+    /// How the peers are found.
+    pub discovery: Discovery,
+    /// The UDP port of the heartbeats, the same on all the nodes.
+    pub heartbeat_port: u16,
+    /// The TCP port to serve the RPCs.
+    pub rpc_port: u16,
+    /// The interval between two heartbeats.
+    pub heartbeat_interval: Duration,
+    /// How long without a heartbeat before a member is failed.
+    pub failure_timeout: Duration,
+    /// How long to wait for the response of an RPC.
+    pub rpc_timeout: Duration,
+    /// How many times an RPC is retried on a new connection, if the old one
+    /// is broken.
+    pub rpc_retries: u32,
+}
+
+impl ClusterConfig {
+    /// Creates the configuration of the node `node_id`, with the default
+    /// ports and timeouts.
+    pub fn new(node_id: u64, discovery: Discovery) -> Self {
+        Self {
+            node_id,
+            discovery,
+            heartbeat_port: DEFAULT_HEARTBEAT_PORT,
+            rpc_port: DEFAULT_RPC_PORT,
+            heartbeat_interval: Duration::from_millis(500),
+            failure_timeout: Duration::from_secs(3),
+            rpc_timeout: Duration::from_secs(2),
+            rpc_retries: 1,
+        }
+    }
+}
+
+static CONFIG: LazyInit<ClusterConfig> = LazyInit::new();
+
+fn config() -> &'static ClusterConfig {
+    &CONFIG
+}
+
+/// Joins the cluster: starts the heartbeats, the failure detector and the
+/// RPC server in their own tasks.
+///
+/// It can be called only once.
+pub fn start(config: ClusterConfig) -> AxResult {
+    if CONFIG.is_inited() {
+        return ax_err!(AlreadyExists, "cluster already started");
+    }
+    if let Discovery::Multicast(group) = config.discovery {
+        if !group.is_multicast() {
+            return ax_err!(InvalidInput, "cluster: not a multicast group");
+        }
+    }
+    if config.heartbeat_interval.is_zero() || config.failure_timeout <= config.heartbeat_interval {
+        return ax_err!(InvalidInput, "cluster: failure timeout too short");
+    }
+    info!(
+        "Start cluster node {}: heartbeats on port {}, RPCs on port {}",
+        config.node_id, config.heartbeat_port, config.rpc_port
+    );
+    let heartbeats = membership::bind(&config)?;
+    let listener = rpc::bind(config.rpc_port)?;
+    CONFIG.init_once(config);
+    axtask::spawn_raw(
+        move || membership::run(heartbeats),
+        "cluster-heartbeat".into(),
+        axconfig::TASK_STACK_SIZE,
+    );
+    axtask::spawn_raw(
+        move || rpc::serve(listener),
+        "cluster-rpc".into(),
+        axconfig::TASK_STACK_SIZE,
+    );
+    Ok(())
+}
+
+/// Returns the ID of this node, or [`None`] if the cluster is not started.
+pub fn node_id() -> Option<u64> {
+    CONFIG.is_inited().then(|| config().node_id)
+}
@@ -0,0 +1,205 @@
+//! Node discovery by the heartbeats, and the failure detector.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use axerrno::{AxError, AxResult};
+use axhal::time::{TimeValue, monotonic_time};
+use axnet::UdpSocket;
+use axsync::Mutex;
+
+use crate::{ClusterConfig, Discovery, config};
+
+/// The magic number of the heartbeats, `b"AXCL"`.
+const MAGIC: u32 = u32::from_le_bytes(*b"AXCL");
+/// The size of a heartbeat.
+const HEARTBEAT_LEN: usize = 14;
+
+/// The state of a member, by the heartbeats heard of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeState {
+    /// A heartbeat is heard recently.
+    Alive,
+    /// No heartbeat is heard for half of the failure timeout.
+    Suspect,
+    /// No heartbeat is heard for the failure timeout. It's alive again if a
+    /// heartbeat is heard later, e.g. after a restart.
+    Failed,
+}
+
+/// A node of the cluster, other than this node.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The ID of the node.
+    pub id: u64,
+    /// The address its heartbeats are from.
+    pub addr: IpAddr,
+    /// The TCP port it serves the RPCs on.
+    pub rpc_port: u16,
+    /// The state of the node.
+    pub state: NodeState,
+    /// When the last heartbeat is heard.
+    pub last_seen: TimeValue,
+}
+
+impl Member {
+    /// The address of the RPC server of the node.
+    pub fn rpc_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.addr, self.rpc_port)
+    }
+}
+
+/// The members by their IDs.
+static MEMBERS: Mutex<BTreeMap<u64, Member>> = Mutex::new(BTreeMap::new());
+
+/// The handler of the changes of the states of the members.
+static EVENT_HANDLER: Mutex<Option<fn(&Member)>> = Mutex::new(None);
+
+/// Returns the members heard of, including the failed ones, in the order of
+/// their IDs.
+pub fn members() -> Vec<Member> {
+    MEMBERS.lock().values().cloned().collect()
+}
+
+/// Returns the member `id`, if it's heard of.
+pub(crate) fn member(id: u64) -> Option<Member> {
+    MEMBERS.lock().get(&id).cloned()
+}
+
+/// Sets the handler called with a member when it joins, or its state
+/// changes.
+///
+/// It's called in the task of the heartbeats, so it should not block.
+pub fn set_event_handler(handler: fn(&Member)) {
+    *EVENT_HANDLER.lock() = Some(handler);
+}
+
+fn notify(changed: &[Member]) {
+    let handler = *EVENT_HANDLER.lock();
+    if let Some(handler) = handler {
+        changed.iter().for_each(handler);
+    }
+}
+
+/// Binds the socket of the heartbeats, and joins the multicast group if any.
+pub(crate) fn bind(config: &ClusterConfig) -> AxResult<UdpSocket> {
+    let socket = UdpSocket::new();
+    socket.set_reuse_addr(true);
+    socket.bind(SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        config.heartbeat_port,
+    ))?;
+    if let Discovery::Multicast(group) = config.discovery {
+        let group = axnet::IpAddr::Ipv4(axnet::Ipv4Addr(group.octets()));
+        let interface = axnet::IpAddr::Ipv4(axnet::Ipv4Addr::UNSPECIFIED);
+        axnet::add_membership(group, interface)?;
+    }
+    // wake up in time to send the next heartbeat
+    socket.set_read_timeout(Some(config.heartbeat_interval));
+    Ok(socket)
+}
+
+/// Sends the heartbeats, receives those of the peers, and detects the
+/// failures, until the network fails.
+pub(crate) fn run(socket: UdpSocket) {
+    let config = config();
+    let mut next_beat = monotonic_time();
+    let mut buf = [0; 64];
+    loop {
+        let now = monotonic_time();
+        if now >= next_beat {
+            send_heartbeat(&socket, config);
+            detect_failures(config, now);
+            next_beat = now + config.heartbeat_interval;
+        }
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) => recv_heartbeat(config, &buf[..len], from.ip()),
+            Err(AxError::WouldBlock) => {}
+            Err(e) => {
+                error!("cluster: heartbeats stopped: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn send_heartbeat(socket: &UdpSocket, config: &ClusterConfig) {
+    let mut beat = [0; HEARTBEAT_LEN];
+    beat[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+    beat[4..12].copy_from_slice(&config.node_id.to_le_bytes());
+    beat[12..14].copy_from_slice(&config.rpc_port.to_le_bytes());
+    let send = |addr: IpAddr| {
+        let dest = SocketAddr::new(addr, config.heartbeat_port);
+        if let Err(e) = socket.send_to(&beat, dest) {
+            // the peer may be unreachable for now
+            debug!("cluster: failed to send heartbeat to {}: {:?}", dest, e);
+        }
+    };
+    match &config.discovery {
+        Discovery::Multicast(group) => send(IpAddr::V4(*group)),
+        Discovery::Static(peers) => peers.iter().copied().for_each(send),
+    }
+}
+
+fn recv_heartbeat(config: &ClusterConfig, beat: &[u8], addr: IpAddr) {
+    if beat.len() < HEARTBEAT_LEN || beat[0..4] != MAGIC.to_le_bytes() {
+        return;
+    }
+    let id = u64::from_le_bytes(beat[4..12].try_into().unwrap());
+    let rpc_port = u16::from_le_bytes(beat[12..14].try_into().unwrap());
+    // our own multicast heartbeats may be looped back
+    if id == config.node_id {
+        return;
+    }
+    let now = monotonic_time();
+    let mut members = MEMBERS.lock();
+    let member = members.entry(id).or_insert_with(|| Member {
+        id,
+        addr,
+        rpc_port,
+        state: NodeState::Failed,
+        last_seen: now,
+    });
+    member.addr = addr;
+    member.rpc_port = rpc_port;
+    member.last_seen = now;
+    if member.state != NodeState::Alive {
+        member.state = NodeState::Alive;
+        info!("cluster: node {} at {} is alive", id, addr);
+        let changed = [member.clone()];
+        drop(members);
+        notify(&changed);
+    }
+}
+
+fn detect_failures(config: &ClusterConfig, now: TimeValue) {
+    let changed: Vec<_> = MEMBERS
+        .lock()
+        .values_mut()
+        .filter_map(|member| {
+            let silence = now.saturating_sub(member.last_seen);
+            let state = if silence >= config.failure_timeout {
+                NodeState::Failed
+            } else if silence >= config.failure_timeout / 2 {
+                NodeState::Suspect
+            } else {
+                NodeState::Alive
+            };
+            // only heartbeats bring a member back to life
+            if state == member.state || state == NodeState::Alive {
+                return None;
+            }
+            member.state = state;
+            match state {
+                NodeState::Failed => warn!("cluster: node {} failed", member.id),
+                _ => debug!("cluster: node {} is suspected", member.id),
+            }
+            Some(member.clone())
+        })
+        .collect();
+    for member in changed.iter().filter(|m| m.state == NodeState::Failed) {
+        crate::rpc::disconnect(member.id);
+    }
+    notify(&changed);
+}
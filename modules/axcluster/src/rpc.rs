@@ -0,0 +1,301 @@
+//! A small reliable RPC layer over TCP.
+//!
+//! The calls to a node are serialized on one connection to it, which is
+//! made when needed, and dropped if broken or if the node fails. A call is
+//! retried on a new connection with the same request ID, and the server
+//! answers a retried request by the response cached for its caller, so a
+//! handler runs at most once for each call, unless the retry comes while the
+//! handler is still running.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use axerrno::{AxError, AxResult, ax_err};
+use axnet::TcpSocket;
+use axsync::Mutex;
+
+use crate::config;
+use crate::membership::{self, NodeState};
+
+/// The size of the header of the requests and the responses.
+const HEADER_LEN: usize = 20;
+/// The maximum size of a payload.
+const MAX_PAYLOAD: usize = 1 << 20;
+
+/// The status of the successful responses.
+const STATUS_OK: u16 = 0;
+/// The status of the responses to the methods not registered.
+const STATUS_NO_METHOD: u16 = 1;
+/// The status of the responses whose handlers return an error.
+const STATUS_FAILED: u16 = 2;
+
+/// The handler of the requests of a method, called with the ID of the
+/// calling node and the payload of the request, returning that of the
+/// response.
+pub type RpcHandler = fn(u64, &[u8]) -> AxResult<Vec<u8>>;
+
+static HANDLERS: Mutex<BTreeMap<u16, RpcHandler>> = Mutex::new(BTreeMap::new());
+
+/// Registers the handler of `method`, replacing the old one if any.
+pub fn register_handler(method: u16, handler: RpcHandler) {
+    HANDLERS.lock().insert(method, handler);
+}
+
+struct Header {
+    node: u64,
+    id: u32,
+    method: u16,
+    status: u16,
+    len: u32,
+}
+
+impl Header {
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut buf = [0; HEADER_LEN];
+        buf[0..8].copy_from_slice(&self.node.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.id.to_le_bytes());
+        buf[12..14].copy_from_slice(&self.method.to_le_bytes());
+        buf[14..16].copy_from_slice(&self.status.to_le_bytes());
+        buf[16..20].copy_from_slice(&self.len.to_le_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8; HEADER_LEN]) -> Self {
+        Self {
+            node: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            id: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            method: u16::from_le_bytes(buf[12..14].try_into().unwrap()),
+            status: u16::from_le_bytes(buf[14..16].try_into().unwrap()),
+            len: u32::from_le_bytes(buf[16..20].try_into().unwrap()),
+        }
+    }
+}
+
+fn send_all(socket: &TcpSocket, mut buf: &[u8]) -> AxResult {
+    while !buf.is_empty() {
+        match socket.send(buf)? {
+            0 => return Err(AxError::WriteZero),
+            n => buf = &buf[n..],
+        }
+    }
+    Ok(())
+}
+
+fn recv_exact(socket: &TcpSocket, mut buf: &mut [u8]) -> AxResult {
+    while !buf.is_empty() {
+        match socket.recv(buf)? {
+            0 => return Err(AxError::UnexpectedEof),
+            n => buf = &mut buf[n..],
+        }
+    }
+    Ok(())
+}
+
+fn send_frame(socket: &TcpSocket, header: Header, payload: &[u8]) -> AxResult {
+    send_all(socket, &header.encode())?;
+    send_all(socket, payload)
+}
+
+fn recv_frame(socket: &TcpSocket) -> AxResult<(Header, Vec<u8>)> {
+    let mut buf = [0; HEADER_LEN];
+    recv_exact(socket, &mut buf)?;
+    let header = Header::decode(&buf);
+    if header.len as usize > MAX_PAYLOAD {
+        return ax_err!(InvalidData, "cluster: RPC payload too large");
+    }
+    let mut payload = vec![0; header.len as usize];
+    recv_exact(socket, &mut payload)?;
+    Ok((header, payload))
+}
+
+/// Binds and listens on the RPC port.
+pub(crate) fn bind(port: u16) -> AxResult<TcpSocket> {
+    // not to reuse the IDs of the requests before a restart, which may be
+    // cached by the peers
+    NEXT_ID.store(axhal::time::wall_time_nanos() as u32, Ordering::Relaxed);
+    let listener = TcpSocket::new();
+    listener.bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))?;
+    listener.listen()?;
+    Ok(listener)
+}
+
+/// The last response to each node, to answer its retried request.
+static LAST_RESPONSES: Mutex<BTreeMap<u64, (u32, u16, Arc<[u8]>)>> = Mutex::new(BTreeMap::new());
+
+/// Accepts the connections of the peers, and serves each of them in its own
+/// task.
+pub(crate) fn serve(listener: TcpSocket) {
+    loop {
+        match listener.accept() {
+            Ok(conn) => {
+                axtask::spawn_raw(
+                    move || {
+                        if let Err(e) = serve_conn(&conn) {
+                            debug!("cluster: RPC connection closed: {:?}", e);
+                        }
+                    },
+                    "cluster-rpc-conn".into(),
+                    axconfig::TASK_STACK_SIZE,
+                );
+            }
+            Err(e) => {
+                error!("cluster: RPC server stopped: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn serve_conn(conn: &TcpSocket) -> AxResult {
+    loop {
+        let (req, payload) = recv_frame(conn)?;
+        let cached = LAST_RESPONSES
+            .lock()
+            .get(&req.node)
+            .filter(|(id, ..)| *id == req.id)
+            .map(|(_, status, resp)| (*status, resp.clone()));
+        let (status, resp) = match cached {
+            Some(cached) => cached,
+            None => {
+                let (status, resp) = handle(&req, &payload);
+                let resp: Arc<[u8]> = resp.into();
+                LAST_RESPONSES
+                    .lock()
+                    .insert(req.node, (req.id, status, resp.clone()));
+                (status, resp)
+            }
+        };
+        let header = Header {
+            node: config().node_id,
+            id: req.id,
+            method: req.method,
+            status,
+            len: resp.len() as u32,
+        };
+        send_frame(conn, header, &resp)?;
+    }
+}
+
+fn handle(req: &Header, payload: &[u8]) -> (u16, Vec<u8>) {
+    let handler = HANDLERS.lock().get(&req.method).copied();
+    let Some(handler) = handler else {
+        warn!(
+            "cluster: unknown RPC method {} from node {}",
+            req.method, req.node
+        );
+        return (STATUS_NO_METHOD, Vec::new());
+    };
+    match handler(req.node, payload) {
+        Ok(resp) if resp.len() <= MAX_PAYLOAD => (STATUS_OK, resp),
+        Ok(_) => {
+            warn!("cluster: RPC method {} replied too much", req.method);
+            (STATUS_FAILED, Vec::new())
+        }
+        Err(e) => {
+            debug!("cluster: RPC method {} failed: {:?}", req.method, e);
+            (STATUS_FAILED, Vec::new())
+        }
+    }
+}
+
+/// The connection to each node, made by the first call to it.
+static CONNECTIONS: Mutex<BTreeMap<u64, Arc<Mutex<Option<TcpSocket>>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// The ID of the next request, shared by all the nodes called.
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Drops the connection to the node, e.g. when it fails.
+pub(crate) fn disconnect(node: u64) {
+    let Some(conn) = CONNECTIONS.lock().remove(&node) else {
+        return;
+    };
+    // a call in progress drops the connection when it's done
+    if let Some(mut conn) = conn.try_lock() {
+        conn.take();
+    }
+}
+
+fn connect(node: u64) -> AxResult<TcpSocket> {
+    let Some(member) = membership::member(node) else {
+        return ax_err!(NotFound, "cluster: unknown node");
+    };
+    if member.state == NodeState::Failed {
+        return ax_err!(ConnectionRefused, "cluster: node failed");
+    }
+    let config = config();
+    let socket = TcpSocket::new();
+    socket.set_connect_timeout(Some(config.rpc_timeout));
+    socket.set_read_timeout(Some(config.rpc_timeout));
+    socket.set_write_timeout(Some(config.rpc_timeout));
+    socket.connect(member.rpc_addr())?;
+    Ok(socket)
+}
+
+/// Calls `method` on the node `node` with the payload `req`, and returns the
+/// payload of the response.
+///
+/// The call is retried on a new connection if the connection is broken, or
+/// there is no response in time. It returns [`AxError::NotFound`] if the node
+/// is not heard of, [`AxError::ConnectionRefused`] if it's failed,
+/// [`AxError::WouldBlock`] if it times out at last, [`AxError::Unsupported`]
+/// if the method is not registered on the node, and [`AxError::Io`] if the
+/// handler fails.
+pub fn call(node: u64, method: u16, req: &[u8]) -> AxResult<Vec<u8>> {
+    if crate::node_id().is_none() {
+        return ax_err!(BadState, "cluster not started");
+    }
+    if req.len() > MAX_PAYLOAD {
+        return ax_err!(InvalidInput, "cluster: RPC payload too large");
+    }
+    let config = config();
+    let conn = CONNECTIONS.lock().entry(node).or_default().clone();
+    let mut conn = conn.lock();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut attempts = 0;
+    loop {
+        let res = match conn.as_ref() {
+            Some(socket) => call_on(socket, id, method, req),
+            None => connect(node).and_then(|socket| call_on(conn.insert(socket), id, method, req)),
+        };
+        match res {
+            Ok((STATUS_OK, resp)) => return Ok(resp),
+            Ok((STATUS_NO_METHOD, _)) => {
+                return ax_err!(Unsupported, "cluster: no such RPC method");
+            }
+            Ok(_) => return ax_err!(Io, "cluster: RPC failed"),
+            Err(e) => {
+                conn.take();
+                let retry = !matches!(e, AxError::NotFound | AxError::ConnectionRefused);
+                if !retry || attempts >= config.rpc_retries {
+                    return Err(e);
+                }
+                attempts += 1;
+                debug!("cluster: retrying RPC {} to node {}: {:?}", id, node, e);
+            }
+        }
+    }
+}
+
+/// Sends the request `id` on the connection, and waits for its response.
+fn call_on(socket: &TcpSocket, id: u32, method: u16, req: &[u8]) -> AxResult<(u16, Vec<u8>)> {
+    let header = Header {
+        node: config().node_id,
+        id,
+        method,
+        status: STATUS_OK,
+        len: req.len() as u32,
+    };
+    send_frame(socket, header, req)?;
+    loop {
+        let (resp, payload) = recv_frame(socket)?;
+        // skip the stale responses, e.g. of a misbehaving peer
+        if resp.id == id {
+            return Ok((resp.status, payload));
+        }
+    }
+}
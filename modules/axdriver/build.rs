@@ -1,6 +1,10 @@
-const NET_DEV_FEATURES: &[&str] = &["fxmac", "ixgbe", "virtio-net"];
+const NET_DEV_FEATURES: &[&str] = &["fxmac", "ixgbe", "tap", "virtio-net"];
 const BLOCK_DEV_FEATURES: &[&str] = &["ramdisk", "bcm2835-sdhci", "virtio-blk"];
 const DISPLAY_DEV_FEATURES: &[&str] = &["virtio-gpu"];
+const CHAR_DEV_FEATURES: &[&str] = &["virtio-console"];
+const RNG_DEV_FEATURES: &[&str] = &["virtio-rng"];
+const INPUT_DEV_FEATURES: &[&str] = &["virtio-input"];
+const NINEP_DEV_FEATURES: &[&str] = &["virtio-9p"];
 
 fn make_cfg_values(str_list: &[&str]) -> String {
     str_list
@@ -23,7 +27,12 @@ fn enable_cfg(key: &str, value: &str) {
 }
 
 fn main() {
-    if has_feature("bus-mmio") {
+    // `bus-pci` is enabled by default, so it's overridden by `bus-mmio` unless
+    // both buses are required by `bus-both`.
+    if has_feature("bus-both") {
+        enable_cfg("bus", "mmio");
+        enable_cfg("bus", "pci");
+    } else if has_feature("bus-mmio") {
         enable_cfg("bus", "mmio");
     } else {
         enable_cfg("bus", "pci");
@@ -36,6 +45,10 @@ fn main() {
         ("net", NET_DEV_FEATURES),
         ("block", BLOCK_DEV_FEATURES),
         ("display", DISPLAY_DEV_FEATURES),
+        ("char", CHAR_DEV_FEATURES),
+        ("rng", RNG_DEV_FEATURES),
+        ("input", INPUT_DEV_FEATURES),
+        ("ninep", NINEP_DEV_FEATURES),
     ] {
         if !has_feature(dev_kind) {
             continue;
@@ -72,4 +85,20 @@ fn main() {
         "cargo::rustc-check-cfg=cfg(display_dev, values({}, \"dummy\"))",
         make_cfg_values(DISPLAY_DEV_FEATURES)
     );
+    println!(
+        "cargo::rustc-check-cfg=cfg(char_dev, values({}, \"dummy\"))",
+        make_cfg_values(CHAR_DEV_FEATURES)
+    );
+    println!(
+        "cargo::rustc-check-cfg=cfg(rng_dev, values({}, \"dummy\"))",
+        make_cfg_values(RNG_DEV_FEATURES)
+    );
+    println!(
+        "cargo::rustc-check-cfg=cfg(input_dev, values({}, \"dummy\"))",
+        make_cfg_values(INPUT_DEV_FEATURES)
+    );
+    println!(
+        "cargo::rustc-check-cfg=cfg(ninep_dev, values({}, \"dummy\"))",
+        make_cfg_values(NINEP_DEV_FEATURES)
+    );
 }
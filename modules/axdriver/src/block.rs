@@ -0,0 +1,242 @@
+//! Request queues of the block storage devices.
+
+use core::ptr::NonNull;
+
+#[cfg(feature = "virtio-blk")]
+pub use self::virtio_blk::VirtIoBlkDev;
+
+use axdriver_base::{DevError, DevResult};
+use axdriver_block::BlockDriverOps;
+
+/// The operation of a block request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOp {
+    /// Reads the blocks into the buffer.
+    Read,
+    /// Writes the buffer to the blocks.
+    Write,
+}
+
+/// Operations of the block devices that process several requests at a time,
+/// completed asynchronously.
+///
+/// The devices without a request queue use the default implementations, whose
+/// [`queue_depth`] is 0, so they are accessed by the synchronous
+/// [`BlockDriverOps::read_block`] and [`BlockDriverOps::write_block`] only.
+/// The synchronous operations must not be used while any request is in
+/// flight.
+///
+/// [`queue_depth`]: BlockQueueOps::queue_depth
+pub trait BlockQueueOps: BlockDriverOps {
+    /// The maximum number of the requests in flight, 0 if the requests can
+    /// not be submitted asynchronously.
+    fn queue_depth(&self) -> usize {
+        0
+    }
+
+    /// Submits a request of `op` on the blocks from `block_id`, whose size is
+    /// the length of `buf`, and returns its token, without waiting for it.
+    ///
+    /// Returns [`DevError::Again`] if the queue is full, then the completed
+    /// requests must be taken by [`poll_completion`] before submitting more.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be valid, and must not be accessed by others until the
+    /// request is completed and returned by [`poll_completion`].
+    ///
+    /// [`poll_completion`]: BlockQueueOps::poll_completion
+    unsafe fn submit(&mut self, op: BlockOp, block_id: u64, buf: NonNull<[u8]>) -> DevResult<u16> {
+        let _ = (op, block_id, buf);
+        Err(DevError::Unsupported)
+    }
+
+    /// Takes a completed request, returns its token and result, or `None` if
+    /// no request is completed.
+    fn poll_completion(&mut self) -> Option<(u16, DevResult)> {
+        None
+    }
+}
+
+#[cfg(feature = "ramdisk")]
+impl BlockQueueOps for axdriver_block::ramdisk::RamDisk {}
+
+#[cfg(feature = "bcm2835-sdhci")]
+impl BlockQueueOps for axdriver_block::bcm2835sdhci::SDHCIDriver {}
+
+#[cfg(feature = "virtio-blk")]
+mod virtio_blk {
+    use alloc::{vec, vec::Vec};
+    use core::ptr::NonNull;
+
+    use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+    use axdriver_block::BlockDriverOps;
+    use virtio_drivers::{
+        Error, Hal,
+        device::blk::{BlkReq, BlkResp, RespStatus, SECTOR_SIZE, VirtIOBlk},
+        transport::Transport,
+    };
+
+    use super::{BlockOp, BlockQueueOps};
+
+    const fn as_dev_err(e: Error) -> DevError {
+        match e {
+            Error::QueueFull => DevError::Again,
+            Error::NotReady => DevError::Again,
+            Error::WrongToken => DevError::BadState,
+            Error::AlreadyUsed => DevError::AlreadyExists,
+            Error::InvalidParam => DevError::InvalidParam,
+            Error::DmaError => DevError::NoMemory,
+            Error::IoError => DevError::Io,
+            Error::Unsupported => DevError::Unsupported,
+            _ => DevError::BadState,
+        }
+    }
+
+    /// The header and the status of a request in flight, which are read and
+    /// written by the device.
+    #[derive(Default)]
+    struct Slot {
+        req: BlkReq,
+        resp: BlkResp,
+        op: Option<BlockOp>,
+        buf: Option<NonNull<[u8]>>,
+    }
+
+    /// The VirtIO block device driver, with the requests submitted to the
+    /// virtqueue without waiting for each other.
+    ///
+    /// The headers of the requests are kept in the slots allocated on the
+    /// heap, so they are not moved while the device accesses them.
+    pub struct VirtIoBlkDev<H: Hal, T: Transport> {
+        inner: VirtIOBlk<H, T>,
+        slots: Vec<Slot>,
+        /// The slot of each token in flight, indexed by the token.
+        tokens: Vec<Option<usize>>,
+    }
+
+    unsafe impl<H: Hal, T: Transport> Send for VirtIoBlkDev<H, T> {}
+    unsafe impl<H: Hal, T: Transport> Sync for VirtIoBlkDev<H, T> {}
+
+    impl<H: Hal, T: Transport> VirtIoBlkDev<H, T> {
+        /// Creates a new driver instance and initializes the device, or returns
+        /// an error if any step fails.
+        pub fn try_new(transport: T) -> DevResult<Self> {
+            let inner = VirtIOBlk::new(transport).map_err(as_dev_err)?;
+            let queue_size = inner.virt_queue_size() as usize;
+            Ok(Self {
+                inner,
+                slots: (0..queue_size).map(|_| Slot::default()).collect(),
+                tokens: vec![None; queue_size],
+            })
+        }
+    }
+
+    impl<H: Hal, T: Transport> BaseDriverOps for VirtIoBlkDev<H, T> {
+        fn device_name(&self) -> &str {
+            "virtio-blk"
+        }
+
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Block
+        }
+    }
+
+    impl<H: Hal, T: Transport> BlockDriverOps for VirtIoBlkDev<H, T> {
+        fn num_blocks(&self) -> u64 {
+            self.inner.capacity()
+        }
+
+        fn block_size(&self) -> usize {
+            SECTOR_SIZE
+        }
+
+        fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+            self.inner
+                .read_blocks(block_id as usize, buf)
+                .map_err(as_dev_err)
+        }
+
+        fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+            self.inner
+                .write_blocks(block_id as usize, buf)
+                .map_err(as_dev_err)
+        }
+
+        fn flush(&mut self) -> DevResult {
+            Ok(())
+        }
+    }
+
+    impl<H: Hal, T: Transport> BlockQueueOps for VirtIoBlkDev<H, T> {
+        fn queue_depth(&self) -> usize {
+            self.slots.len()
+        }
+
+        unsafe fn submit(
+            &mut self,
+            op: BlockOp,
+            block_id: u64,
+            buf: NonNull<[u8]>,
+        ) -> DevResult<u16> {
+            let Some(idx) = self.slots.iter().position(|s| s.op.is_none()) else {
+                return Err(DevError::Again);
+            };
+            let slot = &mut self.slots[idx];
+            let block_id = block_id as usize;
+            // SAFETY: the buffer is valid until completed, guaranteed by the
+            // caller, and the slot is not moved or reused until then.
+            let token = unsafe {
+                match op {
+                    BlockOp::Read => self.inner.read_blocks_nb(
+                        block_id,
+                        &mut slot.req,
+                        &mut *buf.as_ptr(),
+                        &mut slot.resp,
+                    ),
+                    BlockOp::Write => self.inner.write_blocks_nb(
+                        block_id,
+                        &mut slot.req,
+                        &*buf.as_ptr(),
+                        &mut slot.resp,
+                    ),
+                }
+            }
+            .map_err(as_dev_err)?;
+            slot.op = Some(op);
+            slot.buf = Some(buf);
+            self.tokens[token as usize] = Some(idx);
+            Ok(token)
+        }
+
+        fn poll_completion(&mut self) -> Option<(u16, DevResult)> {
+            let token = self.inner.peek_used()?;
+            let idx = self.tokens[token as usize].take()?;
+            let slot = &mut self.slots[idx];
+            let (op, buf) = (slot.op.take()?, slot.buf.take()?);
+            // SAFETY: the same buffer and slot as the ones submitted.
+            let res = unsafe {
+                match op {
+                    BlockOp::Read => self.inner.complete_read_blocks(
+                        token,
+                        &slot.req,
+                        &mut *buf.as_ptr(),
+                        &mut slot.resp,
+                    ),
+                    BlockOp::Write => self.inner.complete_write_blocks(
+                        token,
+                        &slot.req,
+                        &*buf.as_ptr(),
+                        &mut slot.resp,
+                    ),
+                }
+            };
+            let res = match res {
+                Ok(()) if slot.resp.status() == RespStatus::OK => Ok(()),
+                Ok(()) => Err(DevError::Io),
+                Err(e) => Err(as_dev_err(e)),
+            };
+            Some((token, res))
+        }
+    }
+}
@@ -2,18 +2,33 @@
 use crate::{AllDevices, prelude::*};
 
 impl AllDevices {
-    pub(crate) fn probe_bus_devices(&mut self) {
-        // TODO: parse device tree
+    pub(crate) fn probe_mmio_devices(&mut self) {
         #[cfg(feature = "virtio")]
-        for reg in axconfig::devices::VIRTIO_MMIO_REGIONS {
+        for (paddr, size, irq) in virtio_mmio_regions() {
             for_each_drivers!(type Driver, {
-                if let Some(dev) = Driver::probe_mmio(reg.0, reg.1) {
+                if let Some(dev) = Driver::probe_mmio(paddr, size) {
                     info!(
                         "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
                         dev.device_type(),
-                        reg.0, reg.0 + reg.1,
+                        paddr, paddr + size,
                         dev.device_name(),
                     );
+                    #[cfg(feature = "net")]
+                    if let (DeviceType::Net, Some(irq)) = (dev.device_type(), irq) {
+                        // axnet uses the first NIC
+                        if self.net.is_empty() {
+                            let base = axhal::mem::phys_to_virt(paddr.into()).as_usize();
+                            self.net_irq = Some(crate::DeviceIrq::new_virtio_mmio(irq, base));
+                        }
+                    }
+                    #[cfg(feature = "block")]
+                    if let (DeviceType::Block, Some(irq)) = (dev.device_type(), irq) {
+                        // axfs uses the first disk
+                        if self.block.is_empty() {
+                            let base = axhal::mem::phys_to_virt(paddr.into()).as_usize();
+                            self.block_irq = Some(crate::DeviceIrq::new_virtio_mmio(irq, base));
+                        }
+                    }
                     self.add_device(dev);
                     continue; // skip to the next device
                 }
@@ -21,3 +36,26 @@ impl AllDevices {
         }
     }
 }
+
+/// Returns the VirtIO MMIO regions and their IRQs in the device tree, or the
+/// regions in the platform configuration if there is no device tree.
+#[cfg(feature = "virtio")]
+fn virtio_mmio_regions() -> impl Iterator<Item = (usize, usize, Option<usize>)> {
+    let from_dtb = axhal::dtb::virtio_mmio_devices().map(|devs| devs.iter());
+    if from_dtb.is_none() {
+        debug!("no device tree, use the VirtIO MMIO regions in the config");
+    }
+    let from_config = from_dtb
+        .is_none()
+        .then(|| axconfig::devices::VIRTIO_MMIO_REGIONS.iter());
+    from_dtb
+        .into_iter()
+        .flatten()
+        .map(|dev| (dev.paddr, dev.size, dev.irq))
+        .chain(
+            from_config
+                .into_iter()
+                .flatten()
+                .map(|&(paddr, size)| (paddr, size, None)),
+        )
+}
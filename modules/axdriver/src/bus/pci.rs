@@ -6,6 +6,10 @@ use axhal::mem::phys_to_virt;
 
 const PCI_BAR_NUM: u8 = 6;
 
+/// The class code of bridge devices, which are never left to user space.
+#[cfg(feature = "uio")]
+const PCI_CLASS_BRIDGE: u8 = 0x06;
+
 fn config_pci_device(
     root: &mut PciRoot,
     bdf: DeviceFunction,
@@ -82,8 +86,67 @@ fn config_pci_device(
     Ok(())
 }
 
+/// Describes an enabled device that no driver claims, for user-space drivers.
+#[cfg(feature = "uio")]
+fn uio_device(
+    root: &mut PciRoot,
+    bdf: DeviceFunction,
+    dev_info: &axdriver_pci::DeviceFunctionInfo,
+) -> crate::UioDevice {
+    let mut maps = alloc::vec::Vec::new();
+    let mut bar = 0;
+    while bar < PCI_BAR_NUM {
+        let info = root.bar_info(bdf, bar).unwrap();
+        if let BarInfo::Memory { address, size, .. } = info {
+            if address > 0 && size > 0 {
+                maps.push(crate::UioMap {
+                    bar,
+                    paddr: address as usize,
+                    size: size as usize,
+                });
+            }
+        }
+        bar += 1;
+        if info.takes_two_entries() {
+            bar += 1;
+        }
+    }
+    crate::UioDevice {
+        bus: bdf.bus,
+        device: bdf.device,
+        function: bdf.function,
+        vendor_id: dev_info.vendor_id,
+        device_id: dev_info.device_id,
+        class: dev_info.class,
+        subclass: dev_info.subclass,
+        maps,
+    }
+}
+
+/// Describes an ivshmem device, or returns `None` if its registers or its
+/// shared memory are not assigned an address.
+#[cfg(feature = "ivshmem")]
+fn ivshmem_device(root: &mut PciRoot, bdf: DeviceFunction) -> Option<crate::IvshmemDevice> {
+    let mem_bar = |root: &mut PciRoot, bar| match root.bar_info(bdf, bar).ok()? {
+        BarInfo::Memory { address, size, .. } if address > 0 && size > 0 => {
+            Some((address as usize, size as usize))
+        }
+        _ => None,
+    };
+    let (regs_paddr, _) = mem_bar(root, 0)?;
+    let (shmem_paddr, shmem_size) = mem_bar(root, 2)?;
+    Some(crate::IvshmemDevice {
+        bus: bdf.bus,
+        device: bdf.device,
+        function: bdf.function,
+        regs_paddr,
+        shmem_paddr,
+        shmem_size,
+    })
+}
+
 impl AllDevices {
-    pub(crate) fn probe_bus_devices(&mut self) {
+    pub(crate) fn probe_pci_devices(&mut self) {
         let base_vaddr = phys_to_virt(axconfig::devices::PCI_ECAM_BASE.into());
         let mut root = unsafe { PciRoot::new(base_vaddr.as_mut_ptr(), Cam::Ecam) };
 
@@ -99,18 +162,38 @@ impl AllDevices {
                     continue;
                 }
                 match config_pci_device(&mut root, bdf, &mut allocator) {
-                    Ok(_) => for_each_drivers!(type Driver, {
-                        if let Some(dev) = Driver::probe_pci(&mut root, bdf, &dev_info) {
-                            info!(
-                                "registered a new {:?} device at {}: {:?}",
-                                dev.device_type(),
-                                bdf,
-                                dev.device_name(),
-                            );
-                            self.add_device(dev);
-                            continue; // skip to the next device
+                    Ok(_) => {
+                        #[cfg(feature = "ivshmem")]
+                        if dev_info.vendor_id == crate::ivshmem::IVSHMEM_VENDOR_ID
+                            && dev_info.device_id == crate::ivshmem::IVSHMEM_DEVICE_ID
+                        {
+                            match ivshmem_device(&mut root, bdf) {
+                                Some(dev) => {
+                                    info!("found an ivshmem device at {}", bdf);
+                                    self.ivshmem.push(dev);
+                                }
+                                None => warn!("no shared memory of ivshmem device at {}", bdf),
+                            }
+                            continue;
+                        }
+                        for_each_drivers!(type Driver, {
+                            if let Some(dev) = Driver::probe_pci(&mut root, bdf, &dev_info) {
+                                info!(
+                                    "registered a new {:?} device at {}: {:?}",
+                                    dev.device_type(),
+                                    bdf,
+                                    dev.device_name(),
+                                );
+                                self.add_device(dev);
+                                continue; // skip to the next device
+                            }
+                        });
+                        #[cfg(feature = "uio")]
+                        if dev_info.class != PCI_CLASS_BRIDGE {
+                            info!("PCI device at {} is left to user space", bdf);
+                            self.uio.push(uio_device(&mut root, bdf, &dev_info));
                         }
-                    }),
+                    }
                     Err(e) => warn!(
                         "failed to enable PCI device at {}({}): {:?}",
                         bdf, dev_info, e
@@ -0,0 +1,88 @@
+//! Character devices, e.g. the consoles.
+
+#[cfg(feature = "virtio-console")]
+pub use self::virtio_console::VirtIoConsoleDev;
+
+use axdriver_base::{BaseDriverOps, DevResult};
+
+/// Operations that require a character device driver to implement.
+pub trait CharDriverOps: BaseDriverOps {
+    /// Writes all the bytes to the device.
+    fn write_bytes(&mut self, bytes: &[u8]) -> DevResult;
+
+    /// Reads the bytes received into `buf` without blocking, returns the
+    /// number of bytes read.
+    fn read_bytes(&mut self, buf: &mut [u8]) -> DevResult<usize>;
+}
+
+#[cfg(feature = "virtio-console")]
+mod virtio_console {
+    use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+    use virtio_drivers::{Error, Hal, device::console::VirtIOConsole, transport::Transport};
+
+    use super::CharDriverOps;
+
+    const fn as_dev_err(e: Error) -> DevError {
+        match e {
+            Error::NotReady => DevError::Again,
+            Error::InvalidParam => DevError::InvalidParam,
+            Error::DmaError => DevError::NoMemory,
+            Error::IoError => DevError::Io,
+            Error::Unsupported => DevError::Unsupported,
+            _ => DevError::BadState,
+        }
+    }
+
+    /// The VirtIO console device driver.
+    ///
+    /// Only the first port of the device is used, as the multiport feature is
+    /// not negotiated. More ports are added by more devices, e.g. by QEMU
+    /// `-device virtio-serial-device -device virtconsole` pairs.
+    pub struct VirtIoConsoleDev<H: Hal, T: Transport> {
+        inner: VirtIOConsole<H, T>,
+    }
+
+    unsafe impl<H: Hal, T: Transport> Send for VirtIoConsoleDev<H, T> {}
+    unsafe impl<H: Hal, T: Transport> Sync for VirtIoConsoleDev<H, T> {}
+
+    impl<H: Hal, T: Transport> VirtIoConsoleDev<H, T> {
+        /// Creates a new driver instance and initializes the device, or returns
+        /// an error if any step fails.
+        pub fn try_new(transport: T) -> DevResult<Self> {
+            Ok(Self {
+                inner: VirtIOConsole::new(transport).map_err(as_dev_err)?,
+            })
+        }
+    }
+
+    impl<H: Hal, T: Transport> BaseDriverOps for VirtIoConsoleDev<H, T> {
+        fn device_name(&self) -> &str {
+            "virtio-console"
+        }
+
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Char
+        }
+    }
+
+    impl<H: Hal, T: Transport> CharDriverOps for VirtIoConsoleDev<H, T> {
+        fn write_bytes(&mut self, bytes: &[u8]) -> DevResult {
+            for &b in bytes {
+                self.inner.send(b).map_err(as_dev_err)?;
+            }
+            Ok(())
+        }
+
+        fn read_bytes(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+            let mut read_len = 0;
+            while read_len < buf.len() {
+                match self.inner.recv(true).map_err(as_dev_err)? {
+                    Some(b) => buf[read_len] = b,
+                    None => break,
+                }
+                read_len += 1;
+            }
+            Ok(read_len)
+        }
+    }
+}
@@ -51,6 +51,30 @@ register_display_driver!(
     <virtio::VirtIoGpu as VirtIoDevMeta>::Device
 );
 
+#[cfg(char_dev = "virtio-console")]
+register_char_driver!(
+    <virtio::VirtIoConsole as VirtIoDevMeta>::Driver,
+    <virtio::VirtIoConsole as VirtIoDevMeta>::Device
+);
+
+#[cfg(rng_dev = "virtio-rng")]
+register_rng_driver!(
+    <virtio::VirtIoRng as VirtIoDevMeta>::Driver,
+    <virtio::VirtIoRng as VirtIoDevMeta>::Device
+);
+
+#[cfg(input_dev = "virtio-input")]
+register_input_driver!(
+    <virtio::VirtIoInput as VirtIoDevMeta>::Driver,
+    <virtio::VirtIoInput as VirtIoDevMeta>::Device
+);
+
+#[cfg(ninep_dev = "virtio-9p")]
+register_ninep_driver!(
+    <virtio::VirtIo9p as VirtIoDevMeta>::Driver,
+    <virtio::VirtIo9p as VirtIoDevMeta>::Device
+);
+
 cfg_if::cfg_if! {
     if #[cfg(block_dev = "ramdisk")] {
         pub struct RamDiskDriver;
@@ -174,3 +198,22 @@ cfg_if::cfg_if! {
         }
     }
 }
+
+cfg_if::cfg_if! {
+    if #[cfg(net_dev = "tap")] {
+        pub struct TapDriver;
+        register_net_driver!(TapDriver, crate::tap::TapDev);
+
+        impl DriverProbe for TapDriver {
+            fn probe_global() -> Option<AxDeviceEnum> {
+                match crate::tap::TapDev::open() {
+                    Ok(dev) => Some(AxDeviceEnum::from_net(dev)),
+                    Err(e) => {
+                        warn!("failed to open the TAP device: {:?}", e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
@@ -66,6 +66,8 @@ cfg_if! {
                 Err(DevError::Unsupported)
             }
         }
+
+        impl BlockQueueOps for DummyBlockDev {}
     }
 }
 
@@ -100,3 +102,107 @@ cfg_if! {
         }
     }
 }
+
+cfg_if! {
+    if #[cfg(char_dev = "dummy")] {
+        pub struct DummyCharDev;
+        pub struct DummyCharDriver;
+        register_char_driver!(DummyCharDriver, DummyCharDev);
+
+        impl BaseDriverOps for DummyCharDev {
+            fn device_type(&self) -> DeviceType {
+                DeviceType::Char
+            }
+            fn device_name(&self) -> &str {
+                "dummy-char"
+            }
+        }
+
+        impl CharDriverOps for DummyCharDev {
+            fn write_bytes(&mut self, _: &[u8]) -> DevResult {
+                Err(DevError::Unsupported)
+            }
+            fn read_bytes(&mut self, _: &mut [u8]) -> DevResult<usize> {
+                Err(DevError::Unsupported)
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(rng_dev = "dummy")] {
+        pub struct DummyRngDev;
+        pub struct DummyRngDriver;
+        register_rng_driver!(DummyRngDriver, DummyRngDev);
+
+        impl BaseDriverOps for DummyRngDev {
+            fn device_type(&self) -> DeviceType {
+                DeviceType::Char
+            }
+            fn device_name(&self) -> &str {
+                "dummy-rng"
+            }
+        }
+
+        impl RngDriverOps for DummyRngDev {
+            fn read_entropy(&mut self, _: &mut [u8]) -> DevResult<usize> {
+                Err(DevError::Unsupported)
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(input_dev = "dummy")] {
+        pub struct DummyInputDev;
+        pub struct DummyInputDriver;
+        register_input_driver!(DummyInputDriver, DummyInputDev);
+
+        impl BaseDriverOps for DummyInputDev {
+            fn device_type(&self) -> DeviceType {
+                DeviceType::Char
+            }
+            fn device_name(&self) -> &str {
+                "dummy-input"
+            }
+        }
+
+        impl InputDriverOps for DummyInputDev {
+            fn read_event(&mut self) -> DevResult<Option<InputEvent>> {
+                Err(DevError::Unsupported)
+            }
+            fn set_led(&mut self, _: u16, _: bool) -> DevResult {
+                Err(DevError::Unsupported)
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(ninep_dev = "dummy")] {
+        pub struct DummyNinePDev;
+        pub struct DummyNinePDriver;
+        register_ninep_driver!(DummyNinePDriver, DummyNinePDev);
+
+        impl BaseDriverOps for DummyNinePDev {
+            fn device_type(&self) -> DeviceType {
+                DeviceType::Char
+            }
+            fn device_name(&self) -> &str {
+                "dummy-9p"
+            }
+        }
+
+        impl NinePDriverOps for DummyNinePDev {
+            fn mount_tag(&self) -> &str {
+                ""
+            }
+            fn max_message_size(&self) -> usize {
+                0
+            }
+            fn request(&mut self, _: &[u8], _: &mut [u8]) -> DevResult<usize> {
+                Err(DevError::Unsupported)
+            }
+        }
+    }
+}
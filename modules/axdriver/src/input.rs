@@ -0,0 +1,81 @@
+//! Input devices, e.g. the keyboards and the mice.
+
+#[cfg(feature = "virtio-input")]
+pub use self::virtio_input::VirtIoInputDev;
+
+use axdriver_base::{BaseDriverOps, DevResult};
+
+/// An event reported by an input device, in the format of the Linux evdev
+/// events without the timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// The type of the event, e.g. `EV_KEY`.
+    pub event_type: u16,
+    /// The code of the event, e.g. the key code.
+    pub code: u16,
+    /// The value of the event, e.g. 1 for a key pressed and 0 for released.
+    pub value: i32,
+}
+
+/// Operations that require an input device driver to implement.
+pub trait InputDriverOps: BaseDriverOps {
+    /// Pops an event reported by the device without blocking, or returns
+    /// `None` if there is none.
+    fn read_event(&mut self) -> DevResult<Option<InputEvent>>;
+
+    /// Turns the LED of the code, e.g. `LED_CAPSL`, on or off.
+    fn set_led(&mut self, led: u16, on: bool) -> DevResult;
+}
+
+#[cfg(feature = "virtio-input")]
+mod virtio_input {
+    use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+    use virtio_drivers::{Hal, device::input::VirtIOInput, transport::Transport};
+
+    use super::{InputDriverOps, InputEvent};
+
+    /// The VirtIO input device driver.
+    pub struct VirtIoInputDev<H: Hal, T: Transport> {
+        inner: VirtIOInput<H, T>,
+    }
+
+    unsafe impl<H: Hal, T: Transport> Send for VirtIoInputDev<H, T> {}
+    unsafe impl<H: Hal, T: Transport> Sync for VirtIoInputDev<H, T> {}
+
+    impl<H: Hal, T: Transport> VirtIoInputDev<H, T> {
+        /// Creates a new driver instance and initializes the device, or returns
+        /// an error if any step fails.
+        pub fn try_new(transport: T) -> DevResult<Self> {
+            Ok(Self {
+                inner: VirtIOInput::new(transport).map_err(|_| DevError::BadState)?,
+            })
+        }
+    }
+
+    impl<H: Hal, T: Transport> BaseDriverOps for VirtIoInputDev<H, T> {
+        fn device_name(&self) -> &str {
+            "virtio-input"
+        }
+
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Char
+        }
+    }
+
+    impl<H: Hal, T: Transport> InputDriverOps for VirtIoInputDev<H, T> {
+        fn read_event(&mut self) -> DevResult<Option<InputEvent>> {
+            self.inner.ack_interrupt();
+            Ok(self.inner.pop_pending_event().map(|e| InputEvent {
+                event_type: e.event_type,
+                code: e.code,
+                value: e.value as i32,
+            }))
+        }
+
+        /// The status queue of the device is not driven by `virtio_drivers`,
+        /// so the LEDs are only kept by the input subsystem.
+        fn set_led(&mut self, _led: u16, _on: bool) -> DevResult {
+            Err(DevError::Unsupported)
+        }
+    }
+}
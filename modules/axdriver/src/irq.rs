@@ -0,0 +1,38 @@
+//! Interrupts of the devices.
+
+/// The interrupt of a device, and how to acknowledge it.
+///
+/// Only the VirtIO MMIO devices described by the device tree are supported, as
+/// the routing of PCI interrupts is platform-specific.
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceIrq {
+    /// The IRQ number.
+    pub irq_num: usize,
+    /// The virtual address of the VirtIO MMIO registers.
+    mmio_base: usize,
+}
+
+impl DeviceIrq {
+    /// The offset of the `InterruptStatus` register.
+    const INTERRUPT_STATUS: usize = 0x60;
+    /// The offset of the `InterruptACK` register.
+    const INTERRUPT_ACK: usize = 0x64;
+
+    #[allow(dead_code)]
+    pub(crate) const fn new_virtio_mmio(irq_num: usize, mmio_base: usize) -> Self {
+        Self { irq_num, mmio_base }
+    }
+
+    /// Acknowledges the interrupt, returns `false` if the device did not
+    /// raise it.
+    pub fn ack(&self) -> bool {
+        // SAFETY: the registers are mapped, as the device is probed there.
+        unsafe {
+            let status = ((self.mmio_base + Self::INTERRUPT_STATUS) as *const u32).read_volatile();
+            if status != 0 {
+                ((self.mmio_base + Self::INTERRUPT_ACK) as *mut u32).write_volatile(status);
+            }
+            status != 0
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Inter-VM shared memory devices of QEMU (`ivshmem-plain` and
+//! `ivshmem-doorbell`).
+
+/// The vendor ID of the ivshmem devices (Red Hat).
+pub(crate) const IVSHMEM_VENDOR_ID: u16 = 0x1af4;
+/// The device ID of the ivshmem devices.
+pub(crate) const IVSHMEM_DEVICE_ID: u16 = 0x1110;
+
+/// An inter-VM shared memory device.
+#[derive(Debug, Clone)]
+pub struct IvshmemDevice {
+    /// The bus number.
+    pub bus: u8,
+    /// The device number on the bus.
+    pub device: u8,
+    /// The function number of the device.
+    pub function: u8,
+    /// The physical address of the registers in BAR 0.
+    pub regs_paddr: usize,
+    /// The physical address of the shared memory in BAR 2.
+    pub shmem_paddr: usize,
+    /// The size of the shared memory in bytes.
+    pub shmem_size: usize,
+}
@@ -8,8 +8,10 @@
 //! driver they want.
 //!
 //! For each device category (i.e., net, block, display, etc.), an unified type
-//! is used to represent all devices in that category. Currently, there are 3
-//! categories: [`AxNetDevice`], [`AxBlockDevice`], and [`AxDisplayDevice`].
+//! is used to represent all devices in that category. Currently, there are 7
+//! categories: [`AxNetDevice`], [`AxBlockDevice`], [`AxDisplayDevice`],
+//! [`AxCharDevice`], [`AxRngDevice`], [`AxInputDevice`], and
+//! [`AxNinePDevice`].
 //!
 //! # Concepts
 //!
@@ -31,9 +33,14 @@
 //! | Device Category | Cargo Feature | Description |
 //! |-|-|-|
 //! | Block | `ramdisk` | A RAM disk that stores data in a vector |
-//! | Block | `virtio-blk` | VirtIO block device |
+//! | Block | `virtio-blk` | VirtIO block device, with several requests in flight |
 //! | Network | `virtio-net` | VirtIO network device |
+//! | Network | `tap` | TAP device of the Linux host, for the `linux-host` platform |
 //! | Display | `virtio-gpu` | VirtIO graphics device |
+//! | Char | `virtio-console` | VirtIO console device |
+//! | Rng | `virtio-rng` | VirtIO entropy device |
+//! | Input | `virtio-input` | VirtIO input device, e.g. a keyboard |
+//! | 9P | `virtio-9p` | VirtIO 9P transport, to share folders with the host |
 //!
 //! # Other Cargo Features
 //!
@@ -41,13 +48,31 @@
 //! - `bus-mmio`: use device tree to probe all MMIO devices.
 //! - `bus-pci`: use PCI bus to probe all PCI devices. This feature is
 //!    enabeld by default.
+//! - `bus-both`: probe both the MMIO devices and the PCI bus, e.g., for the
+//!    PCIe NICs on MMIO platforms. It enables the `dyn` feature, as the VirtIO
+//!    devices of a category may use different transports.
 //! - `virtio`: use VirtIO devices. This is enabled if any of `virtio-blk`,
-//!   `virtio-net` or `virtio-gpu` is enabled.
+//!   `virtio-net`, `virtio-gpu`, `virtio-console`, `virtio-rng`,
+//!   `virtio-input` or `virtio-9p` is enabled.
 //! - `net`: use network devices. This is enabled if any feature of network
 //!    devices is selected. If this feature is enabled without any network device
 //!    features, a dummy struct is used for [`AxNetDevice`].
-//! - `block`: use block storage devices. Similar to the `net` feature.
+//! - `block`: use block storage devices. Similar to the `net` feature. The
+//!    requests are submitted asynchronously by [`BlockQueueOps`] if the
+//!    device supports it.
 //! - `display`: use graphics display devices. Similar to the `net` feature.
+//! - `char`: use character devices, e.g. the consoles. Similar to the `net`
+//!   feature.
+//! - `rng`: use hardware random number generators. Similar to the `net`
+//!   feature.
+//! - `input`: use input devices, e.g. the keyboards. Similar to the `net`
+//!   feature.
+//! - `ninep`: use 9P transports, e.g. the folders shared by the host. Similar
+//!   to the `net` feature.
+//! - `uio`: collect the PCI devices not claimed by any driver into
+//!   [`AllDevices::uio`], so that they can be driven in user space.
+//! - `ivshmem`: collect the inter-VM shared memory devices of QEMU into
+//!   [`AllDevices::ivshmem`], before any other driver probes them.
 //!
 //! [`VirtioNetDev`]: axdriver_virtio::VirtIoNetDev
 //! [`Box<dyn NetDriverOps>`]: axdriver_net::NetDriverOps
@@ -61,15 +86,33 @@
 #[macro_use]
 extern crate log;
 
-#[cfg(feature = "dyn")]
+#[cfg(any(
+    feature = "dyn",
+    feature = "uio",
+    feature = "ivshmem",
+    feature = "virtio-blk",
+    feature = "tap"
+))]
 extern crate alloc;
 
 #[macro_use]
 mod macros;
 
+#[cfg(feature = "block")]
+mod block;
 mod bus;
+#[cfg(feature = "char")]
+mod chardev;
 mod drivers;
 mod dummy;
+#[cfg(feature = "input")]
+mod input;
+#[cfg(any(feature = "net", feature = "block"))]
+mod irq;
+#[cfg(feature = "ninep")]
+mod ninep;
+#[cfg(feature = "rng")]
+mod rng;
 mod structs;
 
 #[cfg(feature = "virtio")]
@@ -78,18 +121,41 @@ mod virtio;
 #[cfg(feature = "ixgbe")]
 mod ixgbe;
 
+#[cfg(feature = "tap")]
+mod tap;
+
+#[cfg(feature = "uio")]
+mod uio;
+
+#[cfg(feature = "ivshmem")]
+mod ivshmem;
+
 pub mod prelude;
 
 #[allow(unused_imports)]
 use self::prelude::*;
 pub use self::structs::{AxDeviceContainer, AxDeviceEnum};
 
+#[cfg(any(feature = "net", feature = "block"))]
+pub use self::irq::DeviceIrq;
+#[cfg(feature = "ivshmem")]
+pub use self::ivshmem::IvshmemDevice;
 #[cfg(feature = "block")]
 pub use self::structs::AxBlockDevice;
+#[cfg(feature = "char")]
+pub use self::structs::AxCharDevice;
 #[cfg(feature = "display")]
 pub use self::structs::AxDisplayDevice;
+#[cfg(feature = "input")]
+pub use self::structs::AxInputDevice;
 #[cfg(feature = "net")]
 pub use self::structs::AxNetDevice;
+#[cfg(feature = "ninep")]
+pub use self::structs::AxNinePDevice;
+#[cfg(feature = "rng")]
+pub use self::structs::AxRngDevice;
+#[cfg(feature = "uio")]
+pub use self::uio::{UioDevice, UioMap};
 
 /// A structure that contains all device drivers, organized by their category.
 #[derive(Default)]
@@ -97,12 +163,36 @@ pub struct AllDevices {
     /// All network device drivers.
     #[cfg(feature = "net")]
     pub net: AxDeviceContainer<AxNetDevice>,
+    /// The interrupt of the first network device, if known.
+    #[cfg(feature = "net")]
+    pub net_irq: Option<DeviceIrq>,
     /// All block device drivers.
     #[cfg(feature = "block")]
     pub block: AxDeviceContainer<AxBlockDevice>,
+    /// The interrupt of the first block device, if known.
+    #[cfg(feature = "block")]
+    pub block_irq: Option<DeviceIrq>,
     /// All graphics device drivers.
     #[cfg(feature = "display")]
     pub display: AxDeviceContainer<AxDisplayDevice>,
+    /// All character device drivers.
+    #[cfg(feature = "char")]
+    pub char: AxDeviceContainer<AxCharDevice>,
+    /// All random number generator drivers.
+    #[cfg(feature = "rng")]
+    pub rng: AxDeviceContainer<AxRngDevice>,
+    /// All input device drivers.
+    #[cfg(feature = "input")]
+    pub input: AxDeviceContainer<AxInputDevice>,
+    /// All 9P transport drivers.
+    #[cfg(feature = "ninep")]
+    pub ninep: AxDeviceContainer<AxNinePDevice>,
+    /// All PCI devices not claimed by any driver.
+    #[cfg(feature = "uio")]
+    pub uio: alloc::vec::Vec<UioDevice>,
+    /// All inter-VM shared memory devices.
+    #[cfg(feature = "ivshmem")]
+    pub ivshmem: alloc::vec::Vec<IvshmemDevice>,
 }
 
 impl AllDevices {
@@ -130,7 +220,10 @@ impl AllDevices {
             }
         });
 
-        self.probe_bus_devices();
+        #[cfg(bus = "mmio")]
+        self.probe_mmio_devices();
+        #[cfg(bus = "pci")]
+        self.probe_pci_devices();
     }
 
     /// Adds one device into the corresponding container, according to its device category.
@@ -143,6 +236,14 @@ impl AllDevices {
             AxDeviceEnum::Block(dev) => self.block.push(dev),
             #[cfg(feature = "display")]
             AxDeviceEnum::Display(dev) => self.display.push(dev),
+            #[cfg(feature = "char")]
+            AxDeviceEnum::Char(dev) => self.char.push(dev),
+            #[cfg(feature = "rng")]
+            AxDeviceEnum::Rng(dev) => self.rng.push(dev),
+            #[cfg(feature = "input")]
+            AxDeviceEnum::Input(dev) => self.input.push(dev),
+            #[cfg(feature = "ninep")]
+            AxDeviceEnum::NineP(dev) => self.ninep.push(dev),
         }
     }
 }
@@ -179,6 +280,55 @@ pub fn init_drivers() -> AllDevices {
             debug!("  graphics device {}: {:?}", i, dev.device_name());
         }
     }
+    #[cfg(feature = "char")]
+    {
+        debug!("number of character devices: {}", all_devs.char.len());
+        for (i, dev) in all_devs.char.iter().enumerate() {
+            assert_eq!(dev.device_type(), DeviceType::Char);
+            debug!("  character device {}: {:?}", i, dev.device_name());
+        }
+    }
+    #[cfg(feature = "rng")]
+    {
+        debug!("number of random number generators: {}", all_devs.rng.len());
+        for (i, dev) in all_devs.rng.iter().enumerate() {
+            debug!("  random number generator {}: {:?}", i, dev.device_name());
+        }
+    }
+    #[cfg(feature = "input")]
+    {
+        debug!("number of input devices: {}", all_devs.input.len());
+        for (i, dev) in all_devs.input.iter().enumerate() {
+            debug!("  input device {}: {:?}", i, dev.device_name());
+        }
+    }
+    #[cfg(feature = "ninep")]
+    {
+        debug!("number of 9P transports: {}", all_devs.ninep.len());
+        for (i, dev) in all_devs.ninep.iter().enumerate() {
+            debug!("  9P transport {}: {:?}", i, dev.device_name());
+        }
+    }
+    #[cfg(feature = "uio")]
+    {
+        debug!("number of unclaimed devices: {}", all_devs.uio.len());
+        for (i, dev) in all_devs.uio.iter().enumerate() {
+            debug!(
+                "  unclaimed device {}: {:04x}:{:04x}",
+                i, dev.vendor_id, dev.device_id
+            );
+        }
+    }
+    #[cfg(feature = "ivshmem")]
+    {
+        debug!("number of ivshmem devices: {}", all_devs.ivshmem.len());
+        for (i, dev) in all_devs.ivshmem.iter().enumerate() {
+            debug!(
+                "  ivshmem device {}: {:#x} bytes at {:#x}",
+                i, dev.shmem_size, dev.shmem_paddr
+            );
+        }
+    }
 
     all_devs
 }
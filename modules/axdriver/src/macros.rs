@@ -26,6 +26,38 @@ macro_rules! register_display_driver {
     };
 }
 
+macro_rules! register_char_driver {
+    ($driver_type:ty, $device_type:ty) => {
+        /// The unified type of the character devices.
+        #[cfg(not(feature = "dyn"))]
+        pub type AxCharDevice = $device_type;
+    };
+}
+
+macro_rules! register_rng_driver {
+    ($driver_type:ty, $device_type:ty) => {
+        /// The unified type of the random number generators.
+        #[cfg(not(feature = "dyn"))]
+        pub type AxRngDevice = $device_type;
+    };
+}
+
+macro_rules! register_input_driver {
+    ($driver_type:ty, $device_type:ty) => {
+        /// The unified type of the input devices.
+        #[cfg(not(feature = "dyn"))]
+        pub type AxInputDevice = $device_type;
+    };
+}
+
+macro_rules! register_ninep_driver {
+    ($driver_type:ty, $device_type:ty) => {
+        /// The unified type of the 9P transports.
+        #[cfg(not(feature = "dyn"))]
+        pub type AxNinePDevice = $device_type;
+    };
+}
+
 macro_rules! for_each_drivers {
     (type $drv_type:ident, $code:block) => {{
         #[allow(unused_imports)]
@@ -49,6 +81,26 @@ macro_rules! for_each_drivers {
             type $drv_type = <virtio::VirtIoGpu as VirtIoDevMeta>::Driver;
             $code
         }
+        #[cfg(char_dev = "virtio-console")]
+        {
+            type $drv_type = <virtio::VirtIoConsole as VirtIoDevMeta>::Driver;
+            $code
+        }
+        #[cfg(rng_dev = "virtio-rng")]
+        {
+            type $drv_type = <virtio::VirtIoRng as VirtIoDevMeta>::Driver;
+            $code
+        }
+        #[cfg(input_dev = "virtio-input")]
+        {
+            type $drv_type = <virtio::VirtIoInput as VirtIoDevMeta>::Driver;
+            $code
+        }
+        #[cfg(ninep_dev = "virtio-9p")]
+        {
+            type $drv_type = <virtio::VirtIo9p as VirtIoDevMeta>::Driver;
+            $code
+        }
         #[cfg(block_dev = "ramdisk")]
         {
             type $drv_type = crate::drivers::RamDiskDriver;
@@ -69,5 +121,10 @@ macro_rules! for_each_drivers {
             type $drv_type = crate::drivers::FXmacDriver;
             $code
         }
+        #[cfg(net_dev = "tap")]
+        {
+            type $drv_type = crate::drivers::TapDriver;
+            $code
+        }
     }};
 }
@@ -0,0 +1,292 @@
+//! 9P transports, to access the filesystems exported by the host.
+
+#[cfg(feature = "virtio-9p")]
+pub use self::virtio_9p::VirtIo9pDev;
+
+use axdriver_base::{BaseDriverOps, DevResult};
+
+/// Operations that require a 9P transport driver to implement.
+pub trait NinePDriverOps: BaseDriverOps {
+    /// The tag of the exported filesystem, used to choose the one to mount.
+    fn mount_tag(&self) -> &str;
+
+    /// The maximum size of a message, either a request or a response.
+    fn max_message_size(&self) -> usize;
+
+    /// Sends the request message `req` and waits for its response, which is
+    /// written into `resp`. Returns the size of the response.
+    fn request(&mut self, req: &[u8], resp: &mut [u8]) -> DevResult<usize>;
+}
+
+#[cfg(feature = "virtio-9p")]
+mod virtio_9p {
+    use core::ptr::{NonNull, addr_of_mut};
+    use core::sync::atomic::{Ordering, fence};
+
+    use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+    use virtio_drivers::{
+        BufferDirection, Hal, PAGE_SIZE, PhysAddr,
+        transport::{DeviceStatus, Transport},
+    };
+
+    use super::NinePDriverOps;
+
+    /// The feature bit of a device compliant with the VirtIO 1.0 spec.
+    const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+    /// The feature bit of a device with the mount tag in its config space.
+    const VIRTIO_9P_MOUNT_TAG: u64 = 1 << 0;
+    /// The descriptor flag of a buffer continued by the next descriptor.
+    const VIRTQ_DESC_F_NEXT: u16 = 1;
+    /// The descriptor flag of a buffer written by the device.
+    const VIRTQ_DESC_F_WRITE: u16 = 2;
+
+    /// The maximum size of the request queue, only one request is in flight.
+    const QUEUE_SIZE: u16 = 8;
+    /// The maximum length of the mount tag.
+    const MAX_TAG_LEN: usize = 64;
+    /// The number of pages of a message buffer.
+    const MSG_PAGES: usize = 8;
+    /// The maximum size of a message.
+    const MSG_SIZE: usize = MSG_PAGES * PAGE_SIZE;
+    /// The offset of the request buffer, after the pages of the virtqueue.
+    const REQ_OFFSET: usize = 2 * PAGE_SIZE;
+    /// The offset of the response buffer.
+    const RESP_OFFSET: usize = REQ_OFFSET + MSG_SIZE;
+    /// The number of DMA pages.
+    const DMA_PAGES: usize = 2 + 2 * MSG_PAGES;
+
+    // The layouts of the virtqueue shared with the device, whose fields are
+    // mostly accessed by the device.
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct Descriptor {
+        addr: u64,
+        len: u32,
+        flags: u16,
+        next: u16,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct AvailRing {
+        flags: u16,
+        idx: u16,
+        ring: [u16; QUEUE_SIZE as usize],
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct UsedElem {
+        id: u32,
+        len: u32,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct UsedRing {
+        flags: u16,
+        idx: u16,
+        ring: [UsedElem; QUEUE_SIZE as usize],
+    }
+
+    /// The VirtIO 9P transport driver.
+    ///
+    /// The `virtio_drivers` crate has no driver of the device, so its only
+    /// virtqueue is set up here like the one of the virtio-rng driver,
+    /// followed by the request and the response buffers. Each request is a
+    /// chain of the request buffer and the response buffer, and is polled
+    /// until the device completes it.
+    pub struct VirtIo9pDev<H: Hal, T: Transport> {
+        transport: T,
+        queue_size: u16,
+        paddr: PhysAddr,
+        vaddr: NonNull<u8>,
+        avail_idx: u16,
+        last_used_idx: u16,
+        tag: [u8; MAX_TAG_LEN],
+        tag_len: usize,
+        _hal: core::marker::PhantomData<H>,
+    }
+
+    unsafe impl<H: Hal, T: Transport> Send for VirtIo9pDev<H, T> {}
+    unsafe impl<H: Hal, T: Transport> Sync for VirtIo9pDev<H, T> {}
+
+    impl<H: Hal, T: Transport> VirtIo9pDev<H, T> {
+        /// Creates a new driver instance and initializes the device, or returns
+        /// an error if any step fails.
+        pub fn try_new(mut transport: T) -> DevResult<Self> {
+            transport.set_status(DeviceStatus::empty());
+            transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+            let features = transport.read_device_features();
+            if features & VIRTIO_9P_MOUNT_TAG == 0 {
+                return Err(DevError::Unsupported);
+            }
+            transport.write_driver_features(features & (VIRTIO_F_VERSION_1 | VIRTIO_9P_MOUNT_TAG));
+            transport.set_status(
+                DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK,
+            );
+            if transport.requires_legacy_layout() {
+                transport.set_guest_page_size(PAGE_SIZE as u32);
+            }
+
+            // The config space is the length of the tag and the tag.
+            let config = transport
+                .config_space::<u16>()
+                .map_err(|_| DevError::BadState)?;
+            let tag_len = u16::from_le(unsafe { config.as_ptr().read_volatile() }) as usize;
+            if tag_len == 0 || tag_len > MAX_TAG_LEN {
+                return Err(DevError::InvalidParam);
+            }
+            let mut tag = [0; MAX_TAG_LEN];
+            let tag_ptr = unsafe { config.as_ptr().cast::<u8>().add(2) };
+            for (i, b) in tag[..tag_len].iter_mut().enumerate() {
+                *b = unsafe { tag_ptr.add(i).read_volatile() };
+            }
+            if core::str::from_utf8(&tag[..tag_len]).is_err() {
+                return Err(DevError::InvalidParam);
+            }
+
+            let max_size = transport.max_queue_size(0);
+            if max_size < 2 || transport.queue_used(0) {
+                return Err(DevError::BadState);
+            }
+            let queue_size = QUEUE_SIZE.min(max_size as u16);
+            let (paddr, vaddr) = H::dma_alloc(DMA_PAGES, BufferDirection::Both);
+            if paddr == 0 {
+                return Err(DevError::NoMemory);
+            }
+            unsafe { vaddr.as_ptr().write_bytes(0, 2 * PAGE_SIZE) };
+            let desc_size = size_of::<Descriptor>() * queue_size as usize;
+            transport.queue_set(
+                0,
+                queue_size as u32,
+                paddr,
+                paddr + desc_size,
+                paddr + PAGE_SIZE,
+            );
+            transport.set_status(
+                DeviceStatus::ACKNOWLEDGE
+                    | DeviceStatus::DRIVER
+                    | DeviceStatus::FEATURES_OK
+                    | DeviceStatus::DRIVER_OK,
+            );
+
+            Ok(Self {
+                transport,
+                queue_size,
+                paddr,
+                vaddr,
+                avail_idx: 0,
+                last_used_idx: 0,
+                tag,
+                tag_len,
+                _hal: core::marker::PhantomData,
+            })
+        }
+
+        fn desc(&self) -> *mut Descriptor {
+            self.vaddr.as_ptr().cast()
+        }
+
+        fn avail(&self) -> *mut AvailRing {
+            let desc_size = size_of::<Descriptor>() * self.queue_size as usize;
+            unsafe { self.vaddr.as_ptr().add(desc_size).cast() }
+        }
+
+        fn used(&self) -> *mut UsedRing {
+            unsafe { self.vaddr.as_ptr().add(PAGE_SIZE).cast() }
+        }
+
+        /// Posts the request of `len` bytes in the request buffer, with the
+        /// first two descriptors.
+        fn post(&mut self, len: usize) {
+            let req = Descriptor {
+                addr: (self.paddr + REQ_OFFSET) as u64,
+                len: len as u32,
+                flags: VIRTQ_DESC_F_NEXT,
+                next: 1,
+            };
+            let resp = Descriptor {
+                addr: (self.paddr + RESP_OFFSET) as u64,
+                len: MSG_SIZE as u32,
+                flags: VIRTQ_DESC_F_WRITE,
+                next: 0,
+            };
+            let slot = (self.avail_idx % self.queue_size) as usize;
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            unsafe {
+                self.desc().write_volatile(req);
+                self.desc().add(1).write_volatile(resp);
+                addr_of_mut!((*self.avail()).ring[slot]).write_volatile(0);
+                fence(Ordering::SeqCst);
+                addr_of_mut!((*self.avail()).idx).write_volatile(self.avail_idx);
+            }
+            fence(Ordering::SeqCst);
+            self.transport.notify(0);
+        }
+
+        /// Waits for the request posted, returns the number of bytes written
+        /// by the device.
+        fn wait(&mut self) -> usize {
+            loop {
+                fence(Ordering::SeqCst);
+                let used_idx = unsafe { addr_of_mut!((*self.used()).idx).read_volatile() };
+                if used_idx != self.last_used_idx {
+                    break;
+                }
+                axhal::arch::cpu_relax();
+            }
+            let slot = (self.last_used_idx % self.queue_size) as usize;
+            let len = unsafe { addr_of_mut!((*self.used()).ring[slot].len).read_volatile() };
+            self.last_used_idx = self.last_used_idx.wrapping_add(1);
+            self.transport.ack_interrupt();
+            (len as usize).min(MSG_SIZE)
+        }
+    }
+
+    impl<H: Hal, T: Transport> Drop for VirtIo9pDev<H, T> {
+        fn drop(&mut self) {
+            self.transport.queue_unset(0);
+            self.transport.set_status(DeviceStatus::empty());
+            unsafe { H::dma_dealloc(self.paddr, self.vaddr, DMA_PAGES) };
+        }
+    }
+
+    impl<H: Hal, T: Transport> BaseDriverOps for VirtIo9pDev<H, T> {
+        fn device_name(&self) -> &str {
+            "virtio-9p"
+        }
+
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Char
+        }
+    }
+
+    impl<H: Hal, T: Transport> NinePDriverOps for VirtIo9pDev<H, T> {
+        fn mount_tag(&self) -> &str {
+            // checked to be UTF-8 in `try_new`
+            core::str::from_utf8(&self.tag[..self.tag_len]).unwrap_or_default()
+        }
+
+        fn max_message_size(&self) -> usize {
+            MSG_SIZE
+        }
+
+        fn request(&mut self, req: &[u8], resp: &mut [u8]) -> DevResult<usize> {
+            if req.len() > MSG_SIZE {
+                return Err(DevError::InvalidParam);
+            }
+            let base = self.vaddr.as_ptr();
+            unsafe {
+                core::ptr::copy_nonoverlapping(req.as_ptr(), base.add(REQ_OFFSET), req.len())
+            };
+            self.post(req.len());
+            let len = self.wait().min(resp.len());
+            unsafe {
+                core::ptr::copy_nonoverlapping(base.add(RESP_OFFSET), resp.as_mut_ptr(), len)
+            };
+            Ok(len)
+        }
+    }
+}
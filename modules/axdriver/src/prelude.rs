@@ -3,7 +3,22 @@
 pub use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
 
 #[cfg(feature = "block")]
-pub use {crate::structs::AxBlockDevice, axdriver_block::BlockDriverOps};
+pub use {
+    crate::block::{BlockOp, BlockQueueOps},
+    crate::structs::AxBlockDevice,
+    axdriver_block::BlockDriverOps,
+};
+#[cfg(feature = "char")]
+pub use {crate::chardev::CharDriverOps, crate::structs::AxCharDevice};
+#[cfg(feature = "input")]
+pub use {
+    crate::input::{InputDriverOps, InputEvent},
+    crate::structs::AxInputDevice,
+};
+#[cfg(feature = "ninep")]
+pub use {crate::ninep::NinePDriverOps, crate::structs::AxNinePDevice};
+#[cfg(feature = "rng")]
+pub use {crate::rng::RngDriverOps, crate::structs::AxRngDevice};
 #[cfg(feature = "display")]
 pub use {crate::structs::AxDisplayDevice, axdriver_display::DisplayDriverOps};
 #[cfg(feature = "net")]
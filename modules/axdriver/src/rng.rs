@@ -0,0 +1,242 @@
+//! Hardware random number generators.
+
+#[cfg(feature = "virtio-rng")]
+pub use self::virtio_rng::VirtIoRngDev;
+
+use axdriver_base::{BaseDriverOps, DevResult};
+
+/// Operations that require a random number generator driver to implement.
+pub trait RngDriverOps: BaseDriverOps {
+    /// Fills `buf` with the random bytes from the hardware, returns the number
+    /// of bytes filled, which may be less than the length of `buf`.
+    fn read_entropy(&mut self, buf: &mut [u8]) -> DevResult<usize>;
+}
+
+#[cfg(feature = "virtio-rng")]
+mod virtio_rng {
+    use core::ptr::{NonNull, addr_of_mut};
+    use core::sync::atomic::{Ordering, fence};
+
+    use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+    use virtio_drivers::{
+        BufferDirection, Hal, PAGE_SIZE, PhysAddr,
+        transport::{DeviceStatus, Transport},
+    };
+
+    use super::RngDriverOps;
+
+    /// The feature bit of a device compliant with the VirtIO 1.0 spec.
+    const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+    /// The descriptor flag of a buffer written by the device.
+    const VIRTQ_DESC_F_WRITE: u16 = 2;
+
+    /// The maximum size of the request queue, only one request is in flight.
+    const QUEUE_SIZE: u16 = 8;
+    /// The size of the buffer of a request.
+    const BUF_SIZE: usize = 256;
+    /// The offset of the buffer, after the used ring in the second page.
+    const BUF_OFFSET: usize = PAGE_SIZE + 1024;
+    /// The number of polls of a request before it's left in flight.
+    const MAX_POLLS: usize = 1 << 20;
+
+    // The layouts of the virtqueue shared with the device, whose fields are
+    // mostly accessed by the device.
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct Descriptor {
+        addr: u64,
+        len: u32,
+        flags: u16,
+        next: u16,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct AvailRing {
+        flags: u16,
+        idx: u16,
+        ring: [u16; QUEUE_SIZE as usize],
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct UsedElem {
+        id: u32,
+        len: u32,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct UsedRing {
+        flags: u16,
+        idx: u16,
+        ring: [UsedElem; QUEUE_SIZE as usize],
+    }
+
+    /// The VirtIO entropy device driver.
+    ///
+    /// The `virtio_drivers` crate has no driver of the device, so its only
+    /// virtqueue is set up here, in the legacy layout which is also accepted by
+    /// the modern transports: the descriptors and the available ring in the
+    /// first DMA page, and the used ring and the buffer in the second one.
+    pub struct VirtIoRngDev<H: Hal, T: Transport> {
+        transport: T,
+        queue_size: u16,
+        paddr: PhysAddr,
+        vaddr: NonNull<u8>,
+        avail_idx: u16,
+        last_used_idx: u16,
+        /// Whether a request is posted but not completed.
+        in_flight: bool,
+        _hal: core::marker::PhantomData<H>,
+    }
+
+    unsafe impl<H: Hal, T: Transport> Send for VirtIoRngDev<H, T> {}
+    unsafe impl<H: Hal, T: Transport> Sync for VirtIoRngDev<H, T> {}
+
+    impl<H: Hal, T: Transport> VirtIoRngDev<H, T> {
+        /// Creates a new driver instance and initializes the device, or returns
+        /// an error if any step fails.
+        pub fn try_new(mut transport: T) -> DevResult<Self> {
+            transport.set_status(DeviceStatus::empty());
+            transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+            let features = transport.read_device_features();
+            transport.write_driver_features(features & VIRTIO_F_VERSION_1);
+            transport.set_status(
+                DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK,
+            );
+            if transport.requires_legacy_layout() {
+                transport.set_guest_page_size(PAGE_SIZE as u32);
+            }
+
+            let max_size = transport.max_queue_size(0);
+            if max_size == 0 || transport.queue_used(0) {
+                return Err(DevError::BadState);
+            }
+            let queue_size = QUEUE_SIZE.min(max_size as u16);
+            let (paddr, vaddr) = H::dma_alloc(2, BufferDirection::DeviceToDriver);
+            if paddr == 0 {
+                return Err(DevError::NoMemory);
+            }
+            unsafe { vaddr.as_ptr().write_bytes(0, 2 * PAGE_SIZE) };
+            let desc_size = size_of::<Descriptor>() * queue_size as usize;
+            transport.queue_set(
+                0,
+                queue_size as u32,
+                paddr,
+                paddr + desc_size,
+                paddr + PAGE_SIZE,
+            );
+            transport.set_status(
+                DeviceStatus::ACKNOWLEDGE
+                    | DeviceStatus::DRIVER
+                    | DeviceStatus::FEATURES_OK
+                    | DeviceStatus::DRIVER_OK,
+            );
+
+            Ok(Self {
+                transport,
+                queue_size,
+                paddr,
+                vaddr,
+                avail_idx: 0,
+                last_used_idx: 0,
+                in_flight: false,
+                _hal: core::marker::PhantomData,
+            })
+        }
+
+        fn desc(&self) -> *mut Descriptor {
+            self.vaddr.as_ptr().cast()
+        }
+
+        fn avail(&self) -> *mut AvailRing {
+            let desc_size = size_of::<Descriptor>() * self.queue_size as usize;
+            unsafe { self.vaddr.as_ptr().add(desc_size).cast() }
+        }
+
+        fn used(&self) -> *mut UsedRing {
+            unsafe { self.vaddr.as_ptr().add(PAGE_SIZE).cast() }
+        }
+
+        /// Posts a request of [`BUF_SIZE`] bytes with the only descriptor.
+        fn post(&mut self) {
+            let desc = Descriptor {
+                addr: (self.paddr + BUF_OFFSET) as u64,
+                len: BUF_SIZE as u32,
+                flags: VIRTQ_DESC_F_WRITE,
+                next: 0,
+            };
+            let slot = (self.avail_idx % self.queue_size) as usize;
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            unsafe {
+                self.desc().write_volatile(desc);
+                addr_of_mut!((*self.avail()).ring[slot]).write_volatile(0);
+                fence(Ordering::SeqCst);
+                addr_of_mut!((*self.avail()).idx).write_volatile(self.avail_idx);
+            }
+            fence(Ordering::SeqCst);
+            self.transport.notify(0);
+            self.in_flight = true;
+        }
+
+        /// Polls the request in flight, returns the number of bytes written by
+        /// the device if it's completed.
+        fn poll(&mut self) -> Option<usize> {
+            fence(Ordering::SeqCst);
+            let used_idx = unsafe { addr_of_mut!((*self.used()).idx).read_volatile() };
+            if used_idx == self.last_used_idx {
+                return None;
+            }
+            let slot = (self.last_used_idx % self.queue_size) as usize;
+            let len = unsafe { addr_of_mut!((*self.used()).ring[slot].len).read_volatile() };
+            self.last_used_idx = self.last_used_idx.wrapping_add(1);
+            self.in_flight = false;
+            Some((len as usize).min(BUF_SIZE))
+        }
+    }
+
+    impl<H: Hal, T: Transport> Drop for VirtIoRngDev<H, T> {
+        fn drop(&mut self) {
+            self.transport.queue_unset(0);
+            self.transport.set_status(DeviceStatus::empty());
+            unsafe { H::dma_dealloc(self.paddr, self.vaddr, 2) };
+        }
+    }
+
+    impl<H: Hal, T: Transport> BaseDriverOps for VirtIoRngDev<H, T> {
+        fn device_name(&self) -> &str {
+            "virtio-rng"
+        }
+
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Char
+        }
+    }
+
+    impl<H: Hal, T: Transport> RngDriverOps for VirtIoRngDev<H, T> {
+        fn read_entropy(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+            if !self.in_flight {
+                self.post();
+            }
+            let mut polls = 0;
+            let len = loop {
+                if let Some(len) = self.poll() {
+                    break len;
+                }
+                // the device may be rate-limited, keep the request in flight
+                // and complete it by the next read
+                polls += 1;
+                if polls >= MAX_POLLS {
+                    return Err(DevError::Again);
+                }
+                axhal::arch::cpu_relax();
+            };
+            let len = len.min(buf.len());
+            let data = unsafe { self.vaddr.as_ptr().add(BUF_OFFSET) };
+            unsafe { core::ptr::copy_nonoverlapping(data, buf.as_mut_ptr(), len) };
+            Ok(len)
+        }
+    }
+}
@@ -8,10 +8,22 @@ use alloc::{boxed::Box, vec, vec::Vec};
 pub type AxNetDevice = Box<dyn NetDriverOps>;
 /// The unified type of the block storage devices.
 #[cfg(feature = "block")]
-pub type AxBlockDevice = Box<dyn BlockDriverOps>;
+pub type AxBlockDevice = Box<dyn BlockQueueOps>;
 /// The unified type of the graphics display devices.
 #[cfg(feature = "display")]
 pub type AxDisplayDevice = Box<dyn DisplayDriverOps>;
+/// The unified type of the character devices.
+#[cfg(feature = "char")]
+pub type AxCharDevice = Box<dyn CharDriverOps>;
+/// The unified type of the random number generators.
+#[cfg(feature = "rng")]
+pub type AxRngDevice = Box<dyn RngDriverOps>;
+/// The unified type of the input devices.
+#[cfg(feature = "input")]
+pub type AxInputDevice = Box<dyn InputDriverOps>;
+/// The unified type of the 9P transports.
+#[cfg(feature = "ninep")]
+pub type AxNinePDevice = Box<dyn NinePDriverOps>;
 
 impl super::AxDeviceEnum {
     /// Constructs a network device.
@@ -22,7 +34,7 @@ impl super::AxDeviceEnum {
 
     /// Constructs a block device.
     #[cfg(feature = "block")]
-    pub fn from_block(dev: impl BlockDriverOps + 'static) -> Self {
+    pub fn from_block(dev: impl BlockQueueOps + 'static) -> Self {
         Self::Block(Box::new(dev))
     }
 
@@ -31,6 +43,30 @@ impl super::AxDeviceEnum {
     pub fn from_display(dev: impl DisplayDriverOps + 'static) -> Self {
         Self::Display(Box::new(dev))
     }
+
+    /// Constructs a character device.
+    #[cfg(feature = "char")]
+    pub fn from_char(dev: impl CharDriverOps + 'static) -> Self {
+        Self::Char(Box::new(dev))
+    }
+
+    /// Constructs a random number generator.
+    #[cfg(feature = "rng")]
+    pub fn from_rng(dev: impl RngDriverOps + 'static) -> Self {
+        Self::Rng(Box::new(dev))
+    }
+
+    /// Constructs an input device.
+    #[cfg(feature = "input")]
+    pub fn from_input(dev: impl InputDriverOps + 'static) -> Self {
+        Self::Input(Box::new(dev))
+    }
+
+    /// Constructs a 9P transport.
+    #[cfg(feature = "ninep")]
+    pub fn from_ninep(dev: impl NinePDriverOps + 'static) -> Self {
+        Self::NineP(Box::new(dev))
+    }
 }
 
 /// A structure that contains all device drivers of a certain category.
@@ -0,0 +1,184 @@
+//! The TAP device of the Linux host, as a NIC of the kernel run in a user-space
+//! process (see the `linux-host` platform of `axhal`).
+//!
+//! The interface is given by the `AX_TAP_IFNAME` environment variable, `tap0`
+//! by default, which must be created and brought up in advance, e.g. by
+//! `ip tuntap add tap0 mode tap user $USER`. The frames are read and written
+//! without blocking, one by one.
+
+use alloc::boxed::Box;
+use core::ffi::{CStr, c_int, c_void};
+use core::ptr::NonNull;
+
+use axdriver_base::{BaseDriverOps, DevError, DevResult, DeviceType};
+use axdriver_net::{EthernetAddress, NetBufPtr, NetDriverOps};
+
+/// The size of the buffers, for a frame of the MTU 1500 with its header.
+const BUF_SIZE: usize = 1536;
+
+/// The MAC address of the NIC, which differs from the one of the TAP
+/// interface on the host side.
+const MAC_ADDRESS: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+const TUNSETIFF: u32 = 0x4004_54ca;
+const IFF_TAP: i16 = 0x0002;
+const IFF_NO_PI: i16 = 0x1000;
+
+/// `struct ifreq` with the `ifr_flags` member.
+#[repr(C)]
+struct IfReq {
+    name: [u8; 16],
+    flags: i16,
+    _pad: [u8; 22],
+}
+
+type Buf = [u8; BUF_SIZE];
+
+fn alloc_buf(len: usize) -> NetBufPtr {
+    let buf = NonNull::from(Box::leak(Box::new([0u8; BUF_SIZE]))).cast::<u8>();
+    NetBufPtr::new(buf, buf, len)
+}
+
+fn dealloc_buf(buf: NetBufPtr) {
+    drop(unsafe { Box::from_raw(buf.raw_ptr::<Buf>()) });
+}
+
+fn last_error() -> DevError {
+    match unsafe { *libc::__errno_location() } {
+        libc::EAGAIN => DevError::Again,
+        libc::ENOMEM | libc::ENOBUFS => DevError::NoMemory,
+        _ => DevError::Io,
+    }
+}
+
+/// A TAP device of the Linux host.
+pub struct TapDev {
+    fd: c_int,
+}
+
+impl TapDev {
+    /// Opens the TAP interface given by `AX_TAP_IFNAME`.
+    pub fn open() -> DevResult<Self> {
+        let ifname = unsafe {
+            let name = libc::getenv(c"AX_TAP_IFNAME".as_ptr());
+            if name.is_null() {
+                c"tap0"
+            } else {
+                CStr::from_ptr(name)
+            }
+        };
+        let ifname = ifname.to_bytes();
+        let mut req = IfReq {
+            name: [0; 16],
+            flags: IFF_TAP | IFF_NO_PI,
+            _pad: [0; 22],
+        };
+        if ifname.is_empty() || ifname.len() >= req.name.len() {
+            return Err(DevError::InvalidParam);
+        }
+        req.name[..ifname.len()].copy_from_slice(ifname);
+
+        let fd = unsafe {
+            libc::open(
+                c"/dev/net/tun".as_ptr(),
+                libc::O_RDWR | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            let err = last_error();
+            warn!("tap: failed to open /dev/net/tun");
+            return Err(err);
+        }
+        if unsafe { libc::ioctl(fd, TUNSETIFF as _, &mut req as *mut IfReq) } < 0 {
+            let err = last_error();
+            warn!(
+                "tap: failed to attach to {:?}",
+                core::str::from_utf8(ifname)
+            );
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+        Ok(Self { fd })
+    }
+}
+
+impl Drop for TapDev {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+impl BaseDriverOps for TapDev {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+
+    fn device_name(&self) -> &str {
+        "tap"
+    }
+}
+
+impl NetDriverOps for TapDev {
+    fn mac_address(&self) -> EthernetAddress {
+        EthernetAddress(MAC_ADDRESS)
+    }
+
+    fn can_transmit(&self) -> bool {
+        true
+    }
+
+    fn can_receive(&self) -> bool {
+        true
+    }
+
+    fn rx_queue_size(&self) -> usize {
+        1
+    }
+
+    fn tx_queue_size(&self) -> usize {
+        1
+    }
+
+    fn recycle_rx_buffer(&mut self, rx_buf: NetBufPtr) -> DevResult {
+        dealloc_buf(rx_buf);
+        Ok(())
+    }
+
+    fn recycle_tx_buffers(&mut self) -> DevResult {
+        Ok(())
+    }
+
+    /// Writes the frame synchronously, which is dropped if the queue of the
+    /// interface is full.
+    fn transmit(&mut self, tx_buf: NetBufPtr) -> DevResult {
+        let packet = tx_buf.packet();
+        let ret = unsafe { libc::write(self.fd, packet.as_ptr() as *const c_void, packet.len()) };
+        let res = if ret < 0 { Err(last_error()) } else { Ok(()) };
+        dealloc_buf(tx_buf);
+        res
+    }
+
+    fn receive(&mut self) -> DevResult<NetBufPtr> {
+        let mut rx_buf = alloc_buf(BUF_SIZE);
+        let packet = rx_buf.packet_mut();
+        let ret = unsafe { libc::read(self.fd, packet.as_mut_ptr() as *mut c_void, packet.len()) };
+        if ret <= 0 {
+            let err = if ret < 0 {
+                last_error()
+            } else {
+                DevError::Again
+            };
+            dealloc_buf(rx_buf);
+            return Err(err);
+        }
+        let buf = NonNull::new(rx_buf.raw_ptr::<u8>()).unwrap();
+        Ok(NetBufPtr::new(buf, buf, ret as usize))
+    }
+
+    fn alloc_tx_buffer(&mut self, size: usize) -> DevResult<NetBufPtr> {
+        if size > BUF_SIZE {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(alloc_buf(size))
+    }
+}
@@ -0,0 +1,37 @@
+//! Devices left to user-space drivers.
+
+use alloc::vec::Vec;
+
+/// A memory region of a [`UioDevice`], which can be mapped by its user-space
+/// driver.
+#[derive(Debug, Clone, Copy)]
+pub struct UioMap {
+    /// The index of the BAR that the region is decoded by.
+    pub bar: u8,
+    /// The physical address of the region.
+    pub paddr: usize,
+    /// The size of the region in bytes.
+    pub size: usize,
+}
+
+/// A PCI device that is not claimed by any kernel driver.
+#[derive(Debug, Clone)]
+pub struct UioDevice {
+    /// The bus number.
+    pub bus: u8,
+    /// The device number on the bus.
+    pub device: u8,
+    /// The function number of the device.
+    pub function: u8,
+    /// The vendor ID.
+    pub vendor_id: u16,
+    /// The device ID.
+    pub device_id: u16,
+    /// The class code.
+    pub class: u8,
+    /// The subclass code.
+    pub subclass: u8,
+    /// The memory BARs that are assigned an address, in the order of their
+    /// indices.
+    pub maps: Vec<UioMap>,
+}
@@ -6,12 +6,15 @@ use axdriver_base::{BaseDriverOps, DevResult, DeviceType};
 use axdriver_virtio::{BufferDirection, PhysAddr, VirtIoHal};
 use axhal::mem::{phys_to_virt, virt_to_phys};
 use cfg_if::cfg_if;
+use virtio_drivers::transport::DeviceType as VirtIoDevType;
 
 use crate::{AxDeviceEnum, drivers::DriverProbe};
 
+#[cfg(bus = "pci")]
+use axdriver_pci::{DeviceFunction, DeviceFunctionInfo, PciRoot};
+
 cfg_if! {
     if #[cfg(bus = "pci")] {
-        use axdriver_pci::{PciRoot, DeviceFunction, DeviceFunctionInfo};
         type VirtIoTransport = axdriver_virtio::PciTransport;
     } else if #[cfg(bus =  "mmio")] {
         type VirtIoTransport = axdriver_virtio::MmioTransport;
@@ -21,11 +24,18 @@ cfg_if! {
 /// A trait for VirtIO device meta information.
 pub trait VirtIoDevMeta {
     const DEVICE_TYPE: DeviceType;
+    /// The VirtIO device type, for the devices not known by `axdriver_virtio`,
+    /// which are probed by `virtio_drivers` directly.
+    const VIRTIO_TYPE: Option<VirtIoDevType> = None;
 
     type Device: BaseDriverOps;
     type Driver = VirtIoDriver<Self>;
 
     fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum>;
+
+    /// Creates a device on the MMIO transport, if the PCI one is also used.
+    #[cfg(all(bus = "mmio", bus = "pci"))]
+    fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum>;
 }
 
 cfg_if! {
@@ -39,6 +49,12 @@ cfg_if! {
             fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
                 Ok(AxDeviceEnum::from_net(Self::Device::try_new(transport)?))
             }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = axdriver_virtio::VirtIoNetDev::<VirtIoHalImpl, _, 64>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_net(dev))
+            }
         }
     }
 }
@@ -49,11 +65,17 @@ cfg_if! {
 
         impl VirtIoDevMeta for VirtIoBlk {
             const DEVICE_TYPE: DeviceType = DeviceType::Block;
-            type Device = axdriver_virtio::VirtIoBlkDev<VirtIoHalImpl, VirtIoTransport>;
+            type Device = crate::block::VirtIoBlkDev<VirtIoHalImpl, VirtIoTransport>;
 
             fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
                 Ok(AxDeviceEnum::from_block(Self::Device::try_new(transport)?))
             }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = crate::block::VirtIoBlkDev::<VirtIoHalImpl, _>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_block(dev))
+            }
         }
     }
 }
@@ -69,8 +91,137 @@ cfg_if! {
             fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
                 Ok(AxDeviceEnum::from_display(Self::Device::try_new(transport)?))
             }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = axdriver_virtio::VirtIoGpuDev::<VirtIoHalImpl, _>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_display(dev))
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(char_dev = "virtio-console")] {
+        pub struct VirtIoConsole;
+
+        impl VirtIoDevMeta for VirtIoConsole {
+            const DEVICE_TYPE: DeviceType = DeviceType::Char;
+            const VIRTIO_TYPE: Option<VirtIoDevType> = Some(VirtIoDevType::Console);
+            type Device = crate::chardev::VirtIoConsoleDev<VirtIoHalImpl, VirtIoTransport>;
+
+            fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
+                Ok(AxDeviceEnum::from_char(Self::Device::try_new(transport)?))
+            }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = crate::chardev::VirtIoConsoleDev::<VirtIoHalImpl, _>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_char(dev))
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(rng_dev = "virtio-rng")] {
+        pub struct VirtIoRng;
+
+        impl VirtIoDevMeta for VirtIoRng {
+            const DEVICE_TYPE: DeviceType = DeviceType::Char;
+            const VIRTIO_TYPE: Option<VirtIoDevType> = Some(VirtIoDevType::EntropySource);
+            type Device = crate::rng::VirtIoRngDev<VirtIoHalImpl, VirtIoTransport>;
+
+            fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
+                Ok(AxDeviceEnum::from_rng(Self::Device::try_new(transport)?))
+            }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = crate::rng::VirtIoRngDev::<VirtIoHalImpl, _>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_rng(dev))
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(input_dev = "virtio-input")] {
+        pub struct VirtIoInput;
+
+        impl VirtIoDevMeta for VirtIoInput {
+            const DEVICE_TYPE: DeviceType = DeviceType::Char;
+            const VIRTIO_TYPE: Option<VirtIoDevType> = Some(VirtIoDevType::Input);
+            type Device = crate::input::VirtIoInputDev<VirtIoHalImpl, VirtIoTransport>;
+
+            fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
+                Ok(AxDeviceEnum::from_input(Self::Device::try_new(transport)?))
+            }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = crate::input::VirtIoInputDev::<VirtIoHalImpl, _>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_input(dev))
+            }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(ninep_dev = "virtio-9p")] {
+        pub struct VirtIo9p;
+
+        impl VirtIoDevMeta for VirtIo9p {
+            const DEVICE_TYPE: DeviceType = DeviceType::Char;
+            const VIRTIO_TYPE: Option<VirtIoDevType> = Some(VirtIoDevType::_9P);
+            type Device = crate::ninep::VirtIo9pDev<VirtIoHalImpl, VirtIoTransport>;
+
+            fn try_new(transport: VirtIoTransport) -> DevResult<AxDeviceEnum> {
+                Ok(AxDeviceEnum::from_ninep(Self::Device::try_new(transport)?))
+            }
+
+            #[cfg(all(bus = "mmio", bus = "pci"))]
+            fn try_new_mmio(transport: axdriver_virtio::MmioTransport) -> DevResult<AxDeviceEnum> {
+                let dev = crate::ninep::VirtIo9pDev::<VirtIoHalImpl, _>::try_new(transport)?;
+                Ok(AxDeviceEnum::from_ninep(dev))
+            }
+        }
+    }
+}
+
+/// Probes a VirtIO MMIO device, returns its transport if it's of the type of
+/// `D`.
+#[cfg(bus = "mmio")]
+fn probe_mmio_device<D: VirtIoDevMeta>(
+    reg_base: *mut u8,
+    reg_size: usize,
+) -> Option<axdriver_virtio::MmioTransport> {
+    if let Some(virtio_type) = D::VIRTIO_TYPE {
+        use virtio_drivers::transport::{Transport, mmio::VirtIOHeader};
+        let header = NonNull::new(reg_base as *mut VirtIOHeader)?;
+        let transport = unsafe { axdriver_virtio::MmioTransport::new(header) }.ok()?;
+        return (transport.device_type() == virtio_type).then_some(transport);
+    }
+    let (ty, transport) = axdriver_virtio::probe_mmio_device(reg_base, reg_size)?;
+    (ty == D::DEVICE_TYPE).then_some(transport)
+}
+
+/// Probes a VirtIO PCI device, returns its transport if it's of the type of
+/// `D`.
+#[cfg(bus = "pci")]
+fn probe_pci_device<D: VirtIoDevMeta>(
+    root: &mut PciRoot,
+    bdf: DeviceFunction,
+    dev_info: &DeviceFunctionInfo,
+) -> Option<axdriver_virtio::PciTransport> {
+    if let Some(virtio_type) = D::VIRTIO_TYPE {
+        if virtio_drivers::transport::pci::virtio_device_type(dev_info)? != virtio_type {
+            return None;
         }
+        return axdriver_virtio::PciTransport::new::<VirtIoHalImpl>(root, bdf).ok();
     }
+    let (ty, transport) = axdriver_virtio::probe_pci_device::<VirtIoHalImpl>(root, bdf, dev_info)?;
+    (ty == D::DEVICE_TYPE).then_some(transport)
 }
 
 /// A common driver for all VirtIO devices that implements [`DriverProbe`].
@@ -80,21 +231,21 @@ impl<D: VirtIoDevMeta> DriverProbe for VirtIoDriver<D> {
     #[cfg(bus = "mmio")]
     fn probe_mmio(mmio_base: usize, mmio_size: usize) -> Option<AxDeviceEnum> {
         let base_vaddr = phys_to_virt(mmio_base.into());
-        if let Some((ty, transport)) =
-            axdriver_virtio::probe_mmio_device(base_vaddr.as_mut_ptr(), mmio_size)
-        {
-            if ty == D::DEVICE_TYPE {
-                match D::try_new(transport) {
-                    Ok(dev) => return Some(dev),
-                    Err(e) => {
-                        warn!(
-                            "failed to initialize MMIO device at [PA:{:#x}, PA:{:#x}): {:?}",
-                            mmio_base,
-                            mmio_base + mmio_size,
-                            e
-                        );
-                        return None;
-                    }
+        if let Some(transport) = probe_mmio_device::<D>(base_vaddr.as_mut_ptr(), mmio_size) {
+            #[cfg(not(bus = "pci"))]
+            let dev = D::try_new(transport);
+            #[cfg(bus = "pci")]
+            let dev = D::try_new_mmio(transport);
+            match dev {
+                Ok(dev) => return Some(dev),
+                Err(e) => {
+                    warn!(
+                        "failed to initialize MMIO device at [PA:{:#x}, PA:{:#x}): {:?}",
+                        mmio_base,
+                        mmio_base + mmio_size,
+                        e
+                    );
+                    return None;
                 }
             }
         }
@@ -114,22 +265,22 @@ impl<D: VirtIoDevMeta> DriverProbe for VirtIoDriver<D> {
             (DeviceType::Net, 0x1000) | (DeviceType::Net, 0x1041) => {}
             (DeviceType::Block, 0x1001) | (DeviceType::Block, 0x1042) => {}
             (DeviceType::Display, 0x1050) => {}
+            (DeviceType::Char, 0x1003) | (DeviceType::Char, 0x1043) => {}
+            (DeviceType::Char, 0x1005) | (DeviceType::Char, 0x1044) => {}
+            (DeviceType::Char, 0x1052) => {}
+            (DeviceType::Char, 0x1009) | (DeviceType::Char, 0x1049) => {}
             _ => return None,
         }
 
-        if let Some((ty, transport)) =
-            axdriver_virtio::probe_pci_device::<VirtIoHalImpl>(root, bdf, dev_info)
-        {
-            if ty == D::DEVICE_TYPE {
-                match D::try_new(transport) {
-                    Ok(dev) => return Some(dev),
-                    Err(e) => {
-                        warn!(
-                            "failed to initialize PCI device at {}({}): {:?}",
-                            bdf, dev_info, e
-                        );
-                        return None;
-                    }
+        if let Some(transport) = probe_pci_device::<D>(root, bdf, dev_info) {
+            match D::try_new(transport) {
+                Ok(dev) => return Some(dev),
+                Err(e) => {
+                    warn!(
+                        "failed to initialize PCI device at {}({}): {:?}",
+                        bdf, dev_info, e
+                    );
+                    return None;
                 }
             }
         }
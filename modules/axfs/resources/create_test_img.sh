@@ -26,5 +26,32 @@ create_test_img() {
 	sudo umount mnt
 }
 
+# Made from a directory by `mke2fs -d`, without mounting it, and the owners of
+# some files are changed by `debugfs` for the tests of the ownership.
+create_ext2_img() {
+	local name=$1
+	local blkcount=$2
+	local src=$(mktemp -d)
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$src/long.txt"
+	done
+	echo "Rust is cool!" >>"$src/short.txt"
+	mkdir -p "$src/very/long/path"
+	echo "Rust is cool!" >>"$src/very/long/path/test.txt"
+	mkdir -p "$src/very-long-dir-name"
+	echo "Rust is cool!" >>"$src/very-long-dir-name/very-long-file-name.txt"
+	ln -s very/long/path/test.txt "$src/link"
+	rm -f "$name"
+	mkfs.ext2 -q -b 1024 -L "Test!" -E root_owner=0:0 -d "$src" "$name" $blkcount
+	debugfs -w "$name" <<-EOF
+		sif /short.txt uid 1000
+		sif /short.txt gid 1000
+		sif /very/long/path/test.txt uid 100000
+		sif /very/long/path/test.txt gid 100001
+	EOF
+	rm -rf "$src"
+}
+
 create_test_img "$CUR_DIR/fat16.img" 2500 16
 create_test_img "$CUR_DIR/fat32.img" 34000 32
+create_ext2_img "$CUR_DIR/ext2.img" 512
@@ -91,6 +91,12 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symlink, which is only got by
+    /// [`symlink_metadata`](super::symlink_metadata).
+    pub fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -163,6 +169,17 @@ impl File {
     pub fn metadata(&self) -> Result<Metadata> {
         self.inner.get_attr().map(Metadata)
     }
+
+    /// Attempts to sync all data and metadata of the file to the disk.
+    pub fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+
+    /// Attempts to sync the data of the file to the disk, but not
+    /// necessarily the metadata.
+    pub fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
 }
 
 impl Read for File {
@@ -20,6 +20,15 @@ pub fn canonicalize(path: &str) -> io::Result<String> {
     crate::root::absolute_path(path)
 }
 
+/// Writes back the cached data of all filesystems to the disks, like
+/// `sync(2)`.
+///
+/// It should be called before the system terminates, as the blocks written
+/// are kept in the cache for a while.
+pub fn sync() -> io::Result<()> {
+    crate::cache::sync().map_err(|_| io::Error::Io)
+}
+
 /// Returns the current working directory as a [`String`].
 pub fn current_dir() -> io::Result<String> {
     crate::root::current_dir()
@@ -30,6 +39,50 @@ pub fn set_current_dir(path: &str) -> io::Result<()> {
     crate::root::set_current_dir(path)
 }
 
+/// Sets whether the names in the filesystem mounted at `path` are looked up
+/// case-insensitively, which also matches the names in different Unicode
+/// normalization forms.
+///
+/// It's enabled on `/` if the main filesystem is FAT.
+pub fn set_case_insensitive(path: &str, enabled: bool) -> io::Result<()> {
+    crate::root::set_case_insensitive(path, enabled)
+}
+
+/// Mounts the folder shared by the host with the mount tag `tag` on `path`,
+/// creating the directories of `path` if they do not exist.
+///
+/// A folder can be mounted on several paths, all of which share the same
+/// files.
+#[cfg(feature = "ninep")]
+pub fn mount_shared_folder(tag: &str, path: &str) -> io::Result<()> {
+    crate::root::mount(path, crate::fs::ninep::new_mount(tag)?, "9p")
+}
+
+/// Mounts the directory `export` of the NFSv3 server `server` on `path` by
+/// `transport`, creating the directories of `path` if they do not exist.
+///
+/// The network must be initialized before.
+#[cfg(feature = "nfs")]
+pub fn mount_nfs(
+    server: core::net::IpAddr,
+    export: &str,
+    path: &str,
+    transport: crate::NfsTransport,
+) -> io::Result<()> {
+    let fs = crate::fs::nfs::new_mount(server, export, transport)?;
+    crate::root::mount(path, fs, "nfs")
+}
+
+/// Mounts the files served under `url` read-only on `path`, creating the
+/// directories of `path` if they do not exist.
+///
+/// The URL is of the form `http://<host>[:<port>][/<path>]`, where the files
+/// are listed by `<url>/.index`. The network must be initialized before.
+#[cfg(feature = "httpfs")]
+pub fn mount_http(url: &str, path: &str) -> io::Result<()> {
+    crate::root::mount(path, crate::fs::httpfs::new_mount(url)?, "httpfs")
+}
+
 /// Read the entire contents of a file into a bytes vector.
 pub fn read(path: &str) -> io::Result<Vec<u8>> {
     let mut file = File::open(path)?;
@@ -59,6 +112,41 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     crate::root::lookup(None, path)?.get_attr().map(Metadata)
 }
 
+/// Queries the metadata of `path` like [`metadata`], but of the symlink itself
+/// if it's a symlink.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    crate::root::lookup_at(None, path, false)?
+        .get_attr()
+        .map(Metadata)
+}
+
+/// Returns the statistics of the filesystem holding the file at `path`, like
+/// `statfs(2)`, following the symlinks.
+///
+/// It fails with [`io::Error::Unsupported`] if the filesystem can't report
+/// them.
+pub fn statfs(path: &str) -> io::Result<crate::FileSystemStat> {
+    crate::root::statfs(path)
+}
+
+/// Creates a symlink `link` whose content is `original`, which does not have
+/// to exist.
+///
+/// The symlinks are followed in the lookups of the paths, at most
+/// [`MAX_SYMLINKS`](crate::MAX_SYMLINKS) times, beyond which the lookups
+/// fail with [`io::Error::InvalidData`] as a symlink loop. They are supported
+/// by e.g. ext2 and tmpfs.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::create_symlink(None, original, link)
+}
+
+/// Reads the content of the symlink at `path`.
+///
+/// Returns [`io::Error::InvalidInput`] if it's not a symlink.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(None, path)
+}
+
 /// Creates a new, empty directory at the provided path.
 pub fn create_dir(path: &str) -> io::Result<()> {
     DirBuilder::new().create(path)
@@ -88,6 +176,48 @@ pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
 
+/// Creates a new hard link `link` to the file `original`, both of which must
+/// be in the same tmpfs.
+///
+/// Returns [`io::Error::Unsupported`] if tmpfs is not enabled.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::hard_link(original, link)
+}
+
+/// Changes the permissions of the file or directory at `path` in tmpfs.
+#[cfg(feature = "tmpfs")]
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    tmpfs_node(path)?.set_perm(perm);
+    Ok(())
+}
+
+/// Returns the user and the group owning the file or directory at `path`, or
+/// of the symlink itself if `follow` is not set.
+///
+/// Returns `None` if its filesystem does not store them, i.e. it's not tmpfs
+/// or ext2.
+pub fn owner(path: &str, follow: bool) -> io::Result<Option<(u32, u32)>> {
+    let node = crate::root::lookup_at(None, path, follow)?;
+    Ok(crate::root::node_owner(&node))
+}
+
+/// Changes the user and the group owning the file or directory at `path` in
+/// tmpfs, moving it to their quotas.
+#[cfg(feature = "tmpfs")]
+pub fn set_owner(path: &str, uid: u32, gid: u32) -> io::Result<()> {
+    Ok(tmpfs_node(path)?.set_owner(uid, gid)?)
+}
+
+/// Returns the file or directory at `path` in tmpfs, e.g. to manage the
+/// quotas of its filesystem.
+///
+/// Returns [`io::Error::Unsupported`] if it is not in tmpfs.
+#[cfg(feature = "tmpfs")]
+pub fn tmpfs_node(path: &str) -> io::Result<alloc::sync::Arc<crate::fs::tmpfs::TmpNode>> {
+    let node = crate::root::lookup(None, path)?;
+    crate::fs::tmpfs::tmpfs_node(&node).ok_or(io::Error::Unsupported)
+}
+
 /// check whether absolute path exists.
 pub fn absolute_path_exists(path: &str) -> bool {
     crate::root::lookup(None, path).is_ok()
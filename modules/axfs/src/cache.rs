@@ -0,0 +1,399 @@
+//! Cache of the disk blocks, with readahead and write-behind.
+//!
+//! The blocks read sequentially are detected, and the following blocks are
+//! read ahead in one request. The readahead window starts small and doubles
+//! while the reads stay sequential, up to `/proc/sys/vm/read_ahead_kb`.
+//!
+//! The blocks written are kept dirty in the cache, and written back in a batch,
+//! where the contiguous blocks are merged by the request queue of the disk, see
+//! [`crate::iosched`]. When the dirty blocks exceed
+//! `/proc/sys/vm/dirty_background_ratio` percent of the cache, the oldest half
+//! of them are written back, and all of them when exceeding
+//! `/proc/sys/vm/dirty_ratio`, by the writer crossing the thresholds.
+//!
+//! With the `writeback` feature, a flusher task wakes up every
+//! `/proc/sys/vm/dirty_writeback_centisecs`, and writes back the blocks dirty
+//! for longer than `/proc/sys/vm/dirty_expire_centisecs`. The age of a dirty
+//! block is counted in the wakeups of the flusher, so it is rounded up to the
+//! interval. The dirty blocks are also written back by [`sync`], e.g. when a
+//! file is synced by `fsync`.
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use axdriver::prelude::*;
+use axsync::Mutex;
+
+use crate::iosched::{Request, RequestQueue};
+
+/// The size of a block.
+pub(crate) const BLOCK_SIZE: usize = 512;
+/// The maximum number of blocks cached, 4 MiB.
+const CACHE_BLOCKS: usize = 8192;
+/// The readahead window of the first sequential read, in blocks.
+const INITIAL_READAHEAD: usize = 8;
+
+/// The maximum readahead window, in KiB.
+static READ_AHEAD_KB: AtomicUsize = AtomicUsize::new(128);
+/// The percentage of the cache that can be dirty before all the dirty blocks
+/// are written back.
+static DIRTY_RATIO: AtomicUsize = AtomicUsize::new(20);
+/// The percentage of the cache that can be dirty before the oldest dirty
+/// blocks are written back.
+static DIRTY_BACKGROUND_RATIO: AtomicUsize = AtomicUsize::new(10);
+/// The interval between the wakeups of the flusher, in centiseconds, 0 to
+/// disable the periodic writeback.
+static DIRTY_WRITEBACK_CENTISECS: AtomicUsize = AtomicUsize::new(500);
+/// The age of the dirty blocks written back by the flusher, in centiseconds.
+static DIRTY_EXPIRE_CENTISECS: AtomicUsize = AtomicUsize::new(3000);
+
+/// The number of the wakeups of the flusher, the clock of the dirty blocks.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// The caches of all disks, written back by [`sync`].
+static CACHES: Mutex<Vec<Arc<Mutex<BlockCache>>>> = Mutex::new(Vec::new());
+
+struct Block {
+    data: Box<[u8; BLOCK_SIZE]>,
+    dirty: bool,
+    last_used: u64,
+    /// The [`EPOCH`] when the block became dirty.
+    dirtied: u64,
+}
+
+/// The cached blocks of a disk.
+pub(crate) struct BlockCache {
+    dev: RequestQueue,
+    blocks: BTreeMap<u64, Block>,
+    num_dirty: usize,
+    clock: u64,
+    /// The block expected by a sequential read.
+    next_seq: u64,
+    /// The current readahead window, in blocks.
+    readahead: usize,
+}
+
+fn max_dirty(ratio: &AtomicUsize) -> usize {
+    CACHE_BLOCKS * ratio.load(Ordering::Relaxed) / 100
+}
+
+impl BlockCache {
+    /// Creates the cache of `dev`, which is written back by [`sync`].
+    pub fn new(dev: AxBlockDevice) -> Arc<Mutex<Self>> {
+        let cache = Arc::new(Mutex::new(Self {
+            dev: RequestQueue::new(dev),
+            blocks: BTreeMap::new(),
+            num_dirty: 0,
+            clock: 0,
+            next_seq: 0,
+            readahead: 0,
+        }));
+        CACHES.lock().push(cache.clone());
+        cache
+    }
+
+    pub fn num_blocks(&self) -> u64 {
+        self.dev.num_blocks()
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Reads a block, and the following ones if read sequentially.
+    pub fn read_block(&mut self, block_id: u64, buf: &mut [u8; BLOCK_SIZE]) -> DevResult {
+        let max_readahead = READ_AHEAD_KB.load(Ordering::Relaxed) * 1024 / BLOCK_SIZE;
+        self.readahead = if block_id == self.next_seq {
+            (self.readahead * 2)
+                .max(INITIAL_READAHEAD)
+                .min(max_readahead)
+        } else {
+            0
+        };
+        self.next_seq = block_id + 1;
+
+        let now = self.tick();
+        if let Some(block) = self.blocks.get_mut(&block_id) {
+            block.last_used = now;
+            buf.copy_from_slice(&block.data[..]);
+            // keep reading ahead of a sequential reader
+            let ahead = block_id + self.readahead as u64 / 2;
+            if self.readahead > 0 && !self.blocks.contains_key(&ahead) {
+                self.fill(ahead, self.readahead / 2)?;
+            }
+            return Ok(());
+        }
+        self.fill(block_id, self.readahead.max(1))?;
+        buf.copy_from_slice(&self.blocks[&block_id].data[..]);
+        Ok(())
+    }
+
+    /// Reads `count` blocks from `start` in one request, until a cached block
+    /// or the end of the disk.
+    fn fill(&mut self, start: u64, count: usize) -> DevResult {
+        let end = self.dev.num_blocks().min(start + count as u64);
+        let end = (start..end)
+            .find(|id| self.blocks.contains_key(id))
+            .unwrap_or(end);
+        if start >= end {
+            return Ok(());
+        }
+        let count = (end - start) as usize;
+        self.reserve(count)?;
+        let mut data = vec![0u8; count * BLOCK_SIZE];
+        self.dev.read(start, &mut data)?;
+        let now = self.tick();
+        for (id, chunk) in (start..end).zip(data.chunks_exact(BLOCK_SIZE)) {
+            let data = Box::new(chunk.try_into().unwrap());
+            let block = Block {
+                data,
+                dirty: false,
+                last_used: now,
+                dirtied: 0,
+            };
+            self.blocks.insert(id, block);
+        }
+        Ok(())
+    }
+
+    /// Writes a block to the cache, which is written back later.
+    pub fn write_block(&mut self, block_id: u64, buf: &[u8; BLOCK_SIZE]) -> DevResult {
+        let now = self.tick();
+        match self.blocks.get_mut(&block_id) {
+            Some(block) => {
+                block.data.copy_from_slice(buf);
+                block.last_used = now;
+                if !block.dirty {
+                    block.dirty = true;
+                    block.dirtied = EPOCH.load(Ordering::Relaxed);
+                    self.num_dirty += 1;
+                }
+            }
+            None => {
+                self.reserve(1)?;
+                let block = Block {
+                    data: Box::new(*buf),
+                    dirty: true,
+                    last_used: now,
+                    dirtied: EPOCH.load(Ordering::Relaxed),
+                };
+                self.blocks.insert(block_id, block);
+                self.num_dirty += 1;
+            }
+        }
+        if self.num_dirty > max_dirty(&DIRTY_RATIO) {
+            self.write_back(usize::MAX)?;
+        } else if self.num_dirty > max_dirty(&DIRTY_BACKGROUND_RATIO) {
+            self.write_back(self.num_dirty / 2)?;
+        }
+        Ok(())
+    }
+
+    /// Writes back the `count` least recently used dirty blocks at once.
+    fn write_back(&mut self, count: usize) -> DevResult {
+        let mut ids = self
+            .blocks
+            .iter()
+            .filter(|(_, b)| b.dirty)
+            .map(|(&id, b)| (b.last_used, id))
+            .collect::<Vec<_>>();
+        if count < ids.len() {
+            ids.select_nth_unstable(count);
+            ids.truncate(count);
+        }
+        self.write_ids(&ids)
+    }
+
+    /// Writes back the blocks dirtied at or before the [`EPOCH`] `epoch`.
+    fn write_back_expired(&mut self, epoch: u64) -> DevResult {
+        let ids = self
+            .blocks
+            .iter()
+            .filter(|(_, b)| b.dirty && b.dirtied <= epoch)
+            .map(|(&id, b)| (b.last_used, id))
+            .collect::<Vec<_>>();
+        self.write_ids(&ids)
+    }
+
+    /// Writes back the dirty blocks `ids`, as pairs of the last used time and
+    /// the block ID.
+    fn write_ids(&mut self, ids: &[(u64, u64)]) -> DevResult {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let reqs = ids
+            .iter()
+            .map(|&(_, id)| Request::Write(id, &self.blocks[&id].data[..]))
+            .collect();
+        self.dev.submit(reqs)?;
+        for (_, id) in ids {
+            self.blocks.get_mut(id).unwrap().dirty = false;
+        }
+        self.num_dirty -= ids.len();
+        Ok(())
+    }
+
+    /// Makes room for `count` blocks, by dropping the least recently used
+    /// quarter of the clean blocks.
+    fn reserve(&mut self, count: usize) -> DevResult {
+        if self.blocks.len() + count <= CACHE_BLOCKS {
+            return Ok(());
+        }
+        if self.num_dirty * 2 > CACHE_BLOCKS {
+            self.write_back(usize::MAX)?;
+        }
+        let mut ages = self
+            .blocks
+            .values()
+            .filter(|b| !b.dirty)
+            .map(|b| b.last_used)
+            .collect::<Vec<_>>();
+        if ages.is_empty() {
+            return Ok(());
+        }
+        let idx = (ages.len() / 4).max(count).min(ages.len() - 1);
+        let (_, &mut threshold, _) = ages.select_nth_unstable(idx);
+        self.blocks
+            .retain(|_, b| b.dirty || b.last_used > threshold);
+        Ok(())
+    }
+
+    /// Writes back all dirty blocks, and drops the clean ones if `drop_clean`.
+    pub fn sync(&mut self, drop_clean: bool) -> DevResult {
+        self.write_back(usize::MAX)?;
+        if drop_clean {
+            self.blocks.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Writes back the dirty blocks of all disks.
+pub fn sync() -> DevResult {
+    for cache in CACHES.lock().iter() {
+        cache.lock().sync(false)?;
+    }
+    Ok(())
+}
+
+/// Writes back and drops the cached blocks of all disks.
+pub(crate) fn drop_caches() -> DevResult {
+    for cache in CACHES.lock().iter() {
+        cache.lock().sync(true)?;
+    }
+    Ok(())
+}
+
+/// Starts the flusher task, which writes back the expired dirty blocks
+/// periodically.
+#[cfg(feature = "writeback")]
+pub(crate) fn start_flusher() {
+    axtask::spawn(flusher);
+}
+
+#[cfg(feature = "writeback")]
+fn flusher() {
+    use core::time::Duration;
+
+    loop {
+        let interval = DIRTY_WRITEBACK_CENTISECS.load(Ordering::Relaxed);
+        if interval == 0 {
+            // disabled, check it again later like the default interval
+            axtask::sleep(Duration::from_secs(5));
+            continue;
+        }
+        axtask::sleep(Duration::from_millis(interval as u64 * 10));
+
+        let epoch = EPOCH.fetch_add(1, Ordering::Relaxed) + 1;
+        let expire = DIRTY_EXPIRE_CENTISECS.load(Ordering::Relaxed);
+        let age = expire.div_ceil(interval) as u64;
+        let Some(dirtied) = epoch.checked_sub(age) else {
+            continue;
+        };
+        for cache in CACHES.lock().iter() {
+            if let Err(e) = cache.lock().write_back_expired(dirtied) {
+                warn!("failed to write back the dirty blocks: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "procfs")]
+pub(crate) mod sysctl {
+    //! The sysctls in `/proc/sys/vm` of the cache.
+
+    use alloc::{format, string::String};
+    use core::sync::atomic::Ordering;
+
+    use axfs_vfs::{VfsError, VfsResult};
+
+    fn parse(value: &str) -> VfsResult<usize> {
+        value.trim().parse().map_err(|_| VfsError::InvalidInput)
+    }
+
+    fn parse_ratio(value: &str) -> VfsResult<usize> {
+        parse(value).and_then(|v| {
+            if v <= 100 {
+                Ok(v)
+            } else {
+                Err(VfsError::InvalidInput)
+            }
+        })
+    }
+
+    pub fn read_read_ahead_kb() -> String {
+        format!("{}\n", super::READ_AHEAD_KB.load(Ordering::Relaxed))
+    }
+
+    pub fn write_read_ahead_kb(value: &str) -> VfsResult {
+        super::READ_AHEAD_KB.store(parse(value)?, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn read_dirty_ratio() -> String {
+        format!("{}\n", super::DIRTY_RATIO.load(Ordering::Relaxed))
+    }
+
+    pub fn write_dirty_ratio(value: &str) -> VfsResult {
+        super::DIRTY_RATIO.store(parse_ratio(value)?, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn read_dirty_background_ratio() -> String {
+        format!(
+            "{}\n",
+            super::DIRTY_BACKGROUND_RATIO.load(Ordering::Relaxed)
+        )
+    }
+
+    pub fn write_dirty_background_ratio(value: &str) -> VfsResult {
+        super::DIRTY_BACKGROUND_RATIO.store(parse_ratio(value)?, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn read_dirty_writeback_centisecs() -> String {
+        format!(
+            "{}\n",
+            super::DIRTY_WRITEBACK_CENTISECS.load(Ordering::Relaxed)
+        )
+    }
+
+    pub fn write_dirty_writeback_centisecs(value: &str) -> VfsResult {
+        super::DIRTY_WRITEBACK_CENTISECS.store(parse(value)?, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn read_dirty_expire_centisecs() -> String {
+        format!(
+            "{}\n",
+            super::DIRTY_EXPIRE_CENTISECS.load(Ordering::Relaxed)
+        )
+    }
+
+    pub fn write_dirty_expire_centisecs(value: &str) -> VfsResult {
+        super::DIRTY_EXPIRE_CENTISECS.store(parse(value)?, Ordering::Relaxed);
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Case-insensitive lookup of the names in a mounted filesystem.
+//!
+//! The names are compared after folding: they are converted to lowercase and
+//! normalized to NFC, so `README` and `readme`, or the composed and decomposed
+//! forms of `café`, refer to the same file. The names are stored as created.
+//!
+//! A component without an exact match is found by scanning its directory, so
+//! the resolved directories are cached. The cache is keyed by the folded path,
+//! so all the spellings of a directory share one entry.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeRef, VfsResult};
+use axsync::Mutex;
+use unicode_normalization::UnicodeNormalization;
+
+/// Returns the form of a name that is compared.
+pub(crate) fn fold_name(name: &str) -> String {
+    name.chars().flat_map(char::to_lowercase).nfc().collect()
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.into()
+    } else {
+        alloc::format!("{}/{}", dir, name)
+    }
+}
+
+/// Returns the stored name of the entry `name` in `dir`, or `None` if there
+/// is no such entry.
+fn find_entry(dir: &VfsNodeRef, name: &str) -> VfsResult<Option<String>> {
+    match dir.clone().lookup(name) {
+        Ok(_) => return Ok(Some(name.into())),
+        Err(VfsError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+    let folded = fold_name(name);
+    const EMPTY: VfsDirEntry = VfsDirEntry::default();
+    let mut dirents = [EMPTY; 16];
+    let mut start_idx = 0;
+    loop {
+        let n = dir.read_dir(start_idx, &mut dirents)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        for entry in &dirents[..n] {
+            let Ok(entry_name) = core::str::from_utf8(entry.name_as_bytes()) else {
+                continue;
+            };
+            if entry_name != "." && entry_name != ".." && fold_name(entry_name) == folded {
+                return Ok(Some(entry_name.into()));
+            }
+        }
+        start_idx += n;
+    }
+}
+
+/// The case-insensitive lookup state of a mounted filesystem.
+pub(crate) struct CaseFolder {
+    /// The stored paths of the directories, by their folded paths.
+    dirs: Mutex<BTreeMap<String, String>>,
+}
+
+impl CaseFolder {
+    pub const fn new() -> Self {
+        Self {
+            dirs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the stored path of `path`, which is relative to `root`.
+    ///
+    /// The components from the first one not found are kept as given, so
+    /// the path can be created.
+    pub fn resolve(&self, root: &VfsNodeRef, path: &str) -> VfsResult<String> {
+        let path = axfs_vfs::path::canonicalize(path);
+        let mut comps = path.split('/').filter(|c| !c.is_empty());
+        let mut real = String::new();
+        let mut folded = String::new();
+        let mut dir = root.clone();
+        while let Some(comp) = comps.next() {
+            let key = join(&folded, &fold_name(comp));
+            let cached = self.dirs.lock().get(&key).cloned();
+            // the cached path is gone if renamed or removed out of the root
+            let hit = cached.and_then(|p| root.clone().lookup(&p).ok().map(|node| (p, node)));
+            let (next, node) = match hit {
+                Some(hit) => hit,
+                None => {
+                    let Some(name) = find_entry(&dir, comp)? else {
+                        real = join(&real, comp);
+                        comps.by_ref().for_each(|c| real = join(&real, c));
+                        return Ok(real);
+                    };
+                    let next = join(&real, &name);
+                    let node = root.clone().lookup(&next)?;
+                    (next, node)
+                }
+            };
+            if node.get_attr()?.is_dir() {
+                self.dirs.lock().insert(key.clone(), next.clone());
+            }
+            real = next;
+            folded = key;
+            dir = node;
+        }
+        Ok(real)
+    }
+
+    /// Drops the cached directories, after they are renamed or removed.
+    pub fn invalidate(&self) {
+        self.dirs.lock().clear();
+    }
+}
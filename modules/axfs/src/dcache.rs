@@ -0,0 +1,123 @@
+//! Cache of the path lookups (dcache).
+//!
+//! Looking up a path walks its components in the backing filesystem, so the
+//! results of the lookups by absolute paths are cached: the nodes found, which
+//! are kept alive by the cache as an inode cache, and the paths not found as
+//! negative entries.
+//!
+//! The operations in [`crate::root`] that change the directory entries
+//! invalidate the entries of the paths changed, or all entries if the paths
+//! are relative to a directory node. When the cache is full, the least
+//! recently used quarter of the entries is dropped. All entries are dropped by
+//! writing 2 to `/proc/sys/vm/drop_caches`.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::ops::Bound;
+
+use axfs_vfs::VfsNodeRef;
+use axsync::Mutex;
+
+/// The maximum number of entries.
+const DCACHE_CAPACITY: usize = 1024;
+
+struct Entry {
+    /// The node found, or `None` for a negative entry.
+    node: Option<VfsNodeRef>,
+    last_used: u64,
+}
+
+struct DCache {
+    entries: BTreeMap<String, Entry>,
+    clock: u64,
+}
+
+static DCACHE: Mutex<DCache> = Mutex::new(DCache {
+    entries: BTreeMap::new(),
+    clock: 0,
+});
+
+impl DCache {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Drops the least recently used quarter of the entries.
+    fn shrink(&mut self) {
+        let mut ages = self
+            .entries
+            .values()
+            .map(|e| e.last_used)
+            .collect::<Vec<_>>();
+        let idx = ages.len() / 4;
+        let (_, &mut threshold, _) = ages.select_nth_unstable(idx);
+        self.entries.retain(|_, e| e.last_used > threshold);
+    }
+}
+
+/// Returns the cached lookup result of `path`: `Some(None)` if it's known not
+/// to exist, or `None` if it's not cached.
+pub(crate) fn get(path: &str) -> Option<Option<VfsNodeRef>> {
+    let mut dcache = DCACHE.lock();
+    let now = dcache.tick();
+    let entry = dcache.entries.get_mut(path)?;
+    entry.last_used = now;
+    Some(entry.node.clone())
+}
+
+/// Caches the lookup result of `path`.
+pub(crate) fn insert(path: String, node: Option<VfsNodeRef>) {
+    let mut dcache = DCACHE.lock();
+    if dcache.entries.len() >= DCACHE_CAPACITY {
+        dcache.shrink();
+    }
+    let last_used = dcache.tick();
+    dcache.entries.insert(path, Entry { node, last_used });
+}
+
+/// Drops the entries of `path` and the paths under it.
+pub(crate) fn invalidate(path: &str) {
+    let mut dcache = DCACHE.lock();
+    let stale = dcache
+        .entries
+        .range::<str, _>((Bound::Included(path), Bound::Unbounded))
+        .map(|(key, _)| key)
+        .take_while(|key| key.starts_with(path))
+        .filter(|key| {
+            let rest = &key[path.len()..];
+            rest.is_empty() || rest.starts_with('/') || path.ends_with('/')
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for key in stale {
+        dcache.entries.remove(&key);
+    }
+}
+
+/// Drops all entries.
+pub(crate) fn invalidate_all() {
+    DCACHE.lock().entries.clear();
+}
+
+/// Reads `/proc/sys/vm/drop_caches`, which is always 0 like Linux.
+#[cfg(feature = "procfs")]
+pub(crate) fn read_drop_caches() -> String {
+    "0\n".into()
+}
+
+/// Sets `/proc/sys/vm/drop_caches`, where 1 drops the cached disk blocks, 2
+/// drops the dentries and the inodes, and 3 drops both.
+#[cfg(feature = "procfs")]
+pub(crate) fn write_drop_caches(value: &str) -> axfs_vfs::VfsResult {
+    let value = value.trim().parse::<u8>();
+    if !matches!(value, Ok(1..=3)) {
+        return Err(axfs_vfs::VfsError::InvalidInput);
+    }
+    if matches!(value, Ok(1 | 3)) {
+        crate::cache::drop_caches().map_err(|_| axfs_vfs::VfsError::Io)?;
+    }
+    if matches!(value, Ok(2 | 3)) {
+        invalidate_all();
+    }
+    Ok(())
+}
@@ -1,12 +1,16 @@
+use alloc::sync::Arc;
 use axdriver::prelude::*;
+use axsync::Mutex;
 
-const BLOCK_SIZE: usize = 512;
+use crate::cache::{BLOCK_SIZE, BlockCache};
 
 /// A disk device with a cursor.
+///
+/// The blocks are accessed through a cache, see [`crate::cache`].
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    cache: Arc<Mutex<BlockCache>>,
 }
 
 impl Disk {
@@ -16,13 +20,13 @@ impl Disk {
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            cache: BlockCache::new(dev),
         }
     }
 
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.cache.lock().num_blocks() * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -40,11 +44,8 @@ impl Disk {
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
         let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
-            let mut data = [0u8; BLOCK_SIZE];
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[0..BLOCK_SIZE].copy_from_slice(&data);
-            // self.dev
-            //     .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
+            let data = (&mut buf[0..BLOCK_SIZE]).try_into().unwrap();
+            self.cache.lock().read_block(self.block_id, data)?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
@@ -53,7 +54,7 @@ impl Disk {
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.cache.lock().read_block(self.block_id, &mut data)?;
             buf[..count].copy_from_slice(&data[start..start + count]);
 
             self.offset += count;
@@ -69,12 +70,9 @@ impl Disk {
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
         let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            // copy data to kernel address space
-            // Because underlying driver assumes a linear mapping between virtual address and
-            // physical address when converting them, which is only present in kernel address space.
-            let data = buf[0..BLOCK_SIZE].to_vec();
-            self.dev.write_block(self.block_id, &data)?;
+            // whole block, copied to the cache in the kernel address space
+            let data = buf[0..BLOCK_SIZE].try_into().unwrap();
+            self.cache.lock().write_block(self.block_id, data)?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
@@ -83,9 +81,11 @@ impl Disk {
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            let mut cache = self.cache.lock();
+            cache.read_block(self.block_id, &mut data)?;
             data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+            cache.write_block(self.block_id, &data)?;
+            drop(cache);
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
@@ -102,7 +102,8 @@ impl Disk {
     pub fn read_offset(&mut self, offset: usize) -> [u8; BLOCK_SIZE] {
         let block_id = offset / BLOCK_SIZE;
         let mut block_data = [0u8; BLOCK_SIZE];
-        self.dev
+        self.cache
+            .lock()
             .read_block(block_id as u64, &mut block_data)
             .unwrap();
         block_data
@@ -117,7 +118,15 @@ impl Disk {
         );
         assert!(offset % BLOCK_SIZE == 0);
         let block_id = offset / BLOCK_SIZE;
-        self.dev.write_block(block_id as u64, buf).unwrap();
+        self.cache
+            .lock()
+            .write_block(block_id as u64, buf.try_into().unwrap())
+            .unwrap();
         Ok(buf.len())
     }
+
+    /// Writes back the dirty blocks of the disk.
+    pub fn sync(&mut self) -> DevResult {
+        self.cache.lock().sync(false)
+    }
 }
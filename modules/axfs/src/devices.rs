@@ -0,0 +1,209 @@
+//! The registry of the character devices in `/dev`.
+//!
+//! The drivers and the subsystems add their device nodes by
+//! [`register_char_device`], with the major and the minor numbers of Linux,
+//! and the layers above find them by [`char_device`] to open them. The memory
+//! devices and the console are registered at first:
+//!
+//! | Path           | Device numbers |
+//! |----------------|----------------|
+//! | `/dev/null`    | 1, 3           |
+//! | `/dev/zero`    | 1, 5           |
+//! | `/dev/random`  | 1, 8           |
+//! | `/dev/urandom` | 1, 9           |
+//! | `/dev/tty`     | 5, 0           |
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+use axsync::Mutex;
+use spin::Once;
+
+use crate::fs::devfs::{DeviceFileSystem, DirNode, NullDev, ZeroDev};
+
+/// A character device registered in `/dev`.
+#[derive(Clone)]
+pub struct CharDevice {
+    node: VfsNodeRef,
+    major: u32,
+    minor: u32,
+}
+
+impl CharDevice {
+    /// Returns the node of the device.
+    pub fn node(&self) -> &VfsNodeRef {
+        &self.node
+    }
+
+    /// Returns the major and the minor numbers of the device.
+    pub fn rdev(&self) -> (u32, u32) {
+        (self.major, self.minor)
+    }
+
+    /// Reads from the device.
+    pub fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.node.read_at(0, buf)
+    }
+
+    /// Writes to the device.
+    pub fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        self.node.write_at(0, buf)
+    }
+}
+
+/// `/dev/random` and `/dev/urandom`, which are the same and never block.
+struct RandomDev;
+
+impl VfsNodeOps for RandomDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o666),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        axhal::entropy::fill_bytes(buf);
+        Ok(buf.len())
+    }
+
+    /// Mixes the bytes written into the pool, but they are not counted as
+    /// entropy.
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        axhal::entropy::add_entropy(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// `/dev/tty`, the console, whose reads return 0 if there is no input.
+struct TtyDev;
+
+impl VfsNodeOps for TtyDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o666),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        Ok(axhal::console::read_bytes(buf))
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        axhal::console::write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+struct Registry {
+    devfs: Arc<DeviceFileSystem>,
+    /// The subdirectories of `/dev` by their paths.
+    dirs: BTreeMap<&'static str, Arc<DirNode>>,
+    /// The devices by their paths in `/dev`.
+    devices: BTreeMap<&'static str, CharDevice>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        let mut registry = Self {
+            devfs: Arc::new(DeviceFileSystem::new()),
+            dirs: BTreeMap::new(),
+            devices: BTreeMap::new(),
+        };
+        let builtins: [(&str, u32, u32, VfsNodeRef); 7] = [
+            ("null", 1, 3, Arc::new(NullDev)),
+            ("zero", 1, 5, Arc::new(ZeroDev)),
+            ("random", 1, 8, Arc::new(RandomDev)),
+            ("urandom", 1, 9, Arc::new(RandomDev)),
+            ("tty", 5, 0, Arc::new(TtyDev)),
+            // hwclock
+            ("misc/rtc", 10, 135, Arc::new(ZeroDev)),
+            ("foo/bar", 1, 5, Arc::new(ZeroDev)),
+        ];
+        for (path, major, minor, node) in builtins {
+            registry.add(path, major, minor, node).unwrap();
+        }
+        registry
+    }
+
+    /// Returns the subdirectory `path`, creating it and its parents if they
+    /// do not exist.
+    fn dir(&mut self, path: &'static str) -> Arc<DirNode> {
+        if let Some(dir) = self.dirs.get(path) {
+            return dir.clone();
+        }
+        let dir = match path.rsplit_once('/') {
+            Some((parent, name)) => self.dir(parent).mkdir(name),
+            None => self.devfs.mkdir(path),
+        };
+        self.dirs.insert(path, dir.clone());
+        dir
+    }
+
+    fn add(&mut self, path: &'static str, major: u32, minor: u32, node: VfsNodeRef) -> AxResult {
+        if path.is_empty() || self.devices.contains_key(path) || self.dirs.contains_key(path) {
+            return ax_err!(AlreadyExists);
+        }
+        match path.rsplit_once('/') {
+            Some((dir, name)) => self.dir(dir).add(name, node.clone()),
+            None => self.devfs.add(path, node.clone()),
+        }
+        let dev = CharDevice { node, major, minor };
+        self.devices.insert(path, dev);
+        Ok(())
+    }
+}
+
+static REGISTRY: Once<Mutex<Registry>> = Once::new();
+
+fn registry() -> &'static Mutex<Registry> {
+    REGISTRY.call_once(|| Mutex::new(Registry::new()))
+}
+
+/// Returns the devfs mounted on `/dev`.
+pub(crate) fn devfs() -> Arc<DeviceFileSystem> {
+    registry().lock().devfs.clone()
+}
+
+/// Registers the character device `node` at `path` in `/dev`, e.g.
+/// `input/event0`, with its major and minor numbers.
+///
+/// The directories of `path` are created if they do not exist. Returns
+/// [`AxError::AlreadyExists`](axerrno::AxError::AlreadyExists) if `path` is
+/// used.
+pub fn register_char_device(
+    path: &'static str,
+    major: u32,
+    minor: u32,
+    node: VfsNodeRef,
+) -> AxResult {
+    registry()
+        .lock()
+        .add(path.trim_matches('/'), major, minor, node)?;
+    crate::dcache::invalidate_all();
+    Ok(())
+}
+
+/// Returns the character device at the absolute `path`, e.g. `/dev/null`.
+pub fn char_device(path: &str) -> Option<CharDevice> {
+    let path = path.strip_prefix("/dev/")?;
+    registry().lock().devices.get(path).cloned()
+}
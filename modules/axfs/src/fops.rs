@@ -1,5 +1,6 @@
 //! Low-level filesystem operations.
 
+use alloc::string::String;
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
@@ -20,11 +21,26 @@ pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
+/// The identity of a file, as the filesystem holding it and the inode in the
+/// filesystem, shared by all the paths and the opened objects of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileId {
+    /// The address of the filesystem.
+    pub fs: usize,
+    /// The number of the inode, or another number telling the file in the
+    /// filesystem if it has no inode numbers.
+    pub ino: u64,
+}
+
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    /// The absolute path of the file for the events of its watches, if it's
+    /// known.
+    #[cfg(feature = "notify")]
+    path: Option<String>,
 }
 
 /// An opened directory object, with open permissions and a cursor for
@@ -155,13 +171,22 @@ impl File {
         unsafe { self.node.access_unchecked() }
     }
 
+    /// Reports the event `mask` of the file to its watches.
+    #[cfg(feature = "notify")]
+    fn notify(&self, mask: u32) {
+        if let Some(path) = &self.path {
+            crate::notify::notify(path, mask, 0, false);
+        }
+    }
+
     fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
 
-        let node_option = crate::root::lookup(dir, path);
+        // an exclusive creation fails on any symlink, even a dangling one
+        let node_option = crate::root::lookup_at(dir, path, !opts.create_new);
         let node = if opts.create || opts.create_new {
             match node_option {
                 Ok(node) => {
@@ -193,11 +218,22 @@ impl File {
         if opts.truncate {
             node.truncate(0)?;
         }
-        Ok(Self {
+        let file = Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
-        })
+            #[cfg(feature = "notify")]
+            path: if dir.is_none() || path.starts_with('/') {
+                crate::root::resolve_symlinks(path, true).ok()
+            } else {
+                None
+            },
+        };
+        #[cfg(feature = "notify")]
+        if opts.truncate {
+            file.notify(crate::notify::IN_MODIFY);
+        }
+        Ok(file)
     }
 
     /// Opens a file at the path relative to the current directory. Returns a
@@ -209,6 +245,8 @@ impl File {
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
         self.access_node(Cap::WRITE)?.truncate(size)?;
+        #[cfg(feature = "notify")]
+        self.notify(crate::notify::IN_MODIFY);
         Ok(())
     }
 
@@ -246,6 +284,10 @@ impl File {
         let node = self.access_node(Cap::WRITE)?;
         let write_len = node.write_at(offset, buf)?;
         self.offset = offset + write_len as u64;
+        #[cfg(feature = "notify")]
+        if write_len > 0 {
+            self.notify(crate::notify::IN_MODIFY);
+        }
         Ok(write_len)
     }
 
@@ -256,13 +298,36 @@ impl File {
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::WRITE)?;
         let write_len = node.write_at(offset, buf)?;
+        #[cfg(feature = "notify")]
+        if write_len > 0 {
+            self.notify(crate::notify::IN_MODIFY);
+        }
         Ok(write_len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
     pub fn flush(&self) -> AxResult {
         self.access_node(Cap::WRITE)?.fsync()?;
-        Ok(())
+        crate::cache::sync().map_err(|_| AxError::Io)
+    }
+
+    /// Writes the data and the metadata of the file to the underlying device,
+    /// like `fsync(2)`. The file needs not be opened for writing.
+    ///
+    /// The dirty blocks of the other files on the same disks are also written
+    /// back, as the cache does not know which file a block belongs to.
+    pub fn sync_all(&self) -> AxResult {
+        self.get_node().fsync()?;
+        crate::cache::sync().map_err(|_| AxError::Io)
+    }
+
+    /// Writes the data of the file to the underlying device, like
+    /// `fdatasync(2)`.
+    ///
+    /// The metadata is written back as well, the same as [`File::sync_all`],
+    /// since the blocks of them can not be told apart by the cache.
+    pub fn sync_data(&self) -> AxResult {
+        self.sync_all()
     }
 
     /// Sets the cursor of the file to the specified offset. Returns the new
@@ -283,6 +348,18 @@ impl File {
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.access_node(Cap::empty())?.get_attr()
     }
+
+    /// Gets the identity of the file, which was opened at the absolute
+    /// `path`.
+    pub fn file_id(&self, path: &str) -> FileId {
+        crate::root::file_id(self.get_node(), path)
+    }
+
+    /// Gets the user and the group owning the file, or `None` if its
+    /// filesystem does not store them.
+    pub fn owner(&self) -> Option<(u32, u32)> {
+        crate::root::node_owner(self.get_node())
+    }
 }
 
 impl Directory {
@@ -366,6 +443,32 @@ impl Directory {
         crate::root::remove_dir(self.access_at(path)?, path)
     }
 
+    /// Creates a symlink at the path relative to this directory, whose content
+    /// is `target`.
+    pub fn create_symlink(&self, target: &str, path: &str) -> AxResult {
+        crate::root::create_symlink(self.access_at(path)?, target, path)
+    }
+
+    /// Reads the content of the symlink at the path relative to this
+    /// directory.
+    pub fn read_link(&self, path: &str) -> AxResult<String> {
+        crate::root::read_link(self.access_at(path)?, path)
+    }
+
+    /// Gets the attributes of the file at the path relative to this
+    /// directory, or of the symlink itself if `follow` is not set.
+    pub fn get_attr_at(&self, path: &str, follow: bool) -> AxResult<FileAttr> {
+        crate::root::lookup_at(self.access_at(path)?, path, follow)?.get_attr()
+    }
+
+    /// Gets the user and the group owning the file at the path relative to
+    /// this directory like [`Directory::get_attr_at`], or `None` if its
+    /// filesystem does not store them.
+    pub fn owner_at(&self, path: &str, follow: bool) -> AxResult<Option<(u32, u32)>> {
+        let node = crate::root::lookup_at(self.access_at(path)?, path, follow)?;
+        Ok(crate::root::node_owner(&node))
+    }
+
     /// Reads directory entries starts from the current position into the
     /// given buffer. Returns the number of entries read.
     ///
@@ -407,11 +510,27 @@ impl Directory {
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.access_node(Cap::empty())?.get_attr()
     }
+
+    /// Gets the identity of the directory, which was opened at the absolute
+    /// `path`.
+    pub fn file_id(&self, path: &str) -> FileId {
+        crate::root::file_id(unsafe { self.node.access_unchecked() }, path)
+    }
+
+    /// Gets the user and the group owning the directory, or `None` if its
+    /// filesystem does not store them.
+    pub fn owner(&self) -> Option<(u32, u32)> {
+        crate::root::node_owner(unsafe { self.node.access_unchecked() })
+    }
 }
 
 impl Drop for File {
     fn drop(&mut self) {
         unsafe { self.node.access_unchecked().release().ok() };
+        #[cfg(feature = "notify")]
+        if self.node.can_access(Cap::WRITE) {
+            self.notify(crate::notify::IN_CLOSE_WRITE);
+        }
     }
 }
 
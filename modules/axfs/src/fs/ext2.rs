@@ -0,0 +1,1447 @@
+//! A native ext2 filesystem, with the files, the directories, the hard links
+//! and the symlinks, and their timestamps.
+//!
+//! It supports the revisions 0 and 1 of ext2, with the blocks of 1 to 64 KiB,
+//! and the features `filetype`, `sparse_super` and `large_file`. An ext3 or
+//! ext4 filesystem is supported if it has no other incompatible features,
+//! e.g. made by `mkfs.ext4 -O ^extent,^64bit`, and its journal is not used.
+//! It's mounted read-only if it has the other read-only compatible features,
+//! e.g. the checksums of the metadata, which are not updated.
+//!
+//! The directories are lists of the entries, and the hashed index of a
+//! directory is dropped when it's changed, as the one of Linux does. The
+//! timestamps are the seconds of the wall time, and the access time is
+//! updated like `relatime`.
+//!
+//! All the metadata is accessed through the block cache by one lock of the
+//! filesystem. An inode is freed once its last link is removed, even if it's
+//! still open.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+
+use crate::dev::Disk;
+use crate::statfs::{FileSystemStat, StatFs};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xef53;
+const ROOT_INO: u32 = 2;
+/// The first non-reserved inode of the revision 0.
+const GOOD_OLD_FIRST_INO: u32 = 11;
+const GOOD_OLD_INODE_SIZE: usize = 128;
+/// The size of the fields of an inode accessed, the ones of the revision 0.
+const INODE_SIZE: usize = 128;
+const GROUP_DESC_SIZE: usize = 32;
+
+const FEATURE_INCOMPAT_FILETYPE: u32 = 0x2;
+const FEATURE_INCOMPAT_FLEX_BG: u32 = 0x200;
+const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x2;
+const SUPPORTED_INCOMPAT: u32 = FEATURE_INCOMPAT_FILETYPE | FEATURE_INCOMPAT_FLEX_BG;
+const SUPPORTED_RO_COMPAT: u32 = FEATURE_RO_COMPAT_SPARSE_SUPER | FEATURE_RO_COMPAT_LARGE_FILE;
+
+/// The flag of a directory with the hashed index.
+const INDEX_FL: u32 = 0x1000;
+
+const S_IFMT: u16 = 0xf000;
+const S_IFSOCK: u16 = 0xc000;
+const S_IFLNK: u16 = 0xa000;
+const S_IFREG: u16 = 0x8000;
+const S_IFBLK: u16 = 0x6000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFCHR: u16 = 0x2000;
+const S_IFIFO: u16 = 0x1000;
+
+/// The number of the direct blocks of an inode.
+const NDIR_BLOCKS: usize = 12;
+/// The number of the block pointers of an inode.
+const N_BLOCKS: usize = 15;
+/// The maximum length of a symlink stored in the block pointers.
+const FAST_SYMLINK_MAX: usize = N_BLOCKS * 4;
+const MAX_NAME_LEN: usize = 255;
+/// The size of the header of a directory entry.
+const DIRENT_HEADER_SIZE: usize = 8;
+/// The interval to update the access time of an inode, if it's after the
+/// modification.
+const ATIME_INTERVAL: u32 = 24 * 60 * 60;
+
+fn get_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes(buf[off..off + 2].try_into().unwrap())
+}
+
+fn get_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn set_u16(buf: &mut [u8], off: usize, value: u16) {
+    buf[off..off + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn set_u32(buf: &mut [u8], off: usize, value: u32) {
+    buf[off..off + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Returns the current time in seconds.
+fn now() -> u32 {
+    axhal::time::wall_time().as_secs() as u32
+}
+
+fn node_type(mode: u16) -> VfsNodeType {
+    match mode & S_IFMT {
+        S_IFSOCK => VfsNodeType::Socket,
+        S_IFLNK => VfsNodeType::SymLink,
+        S_IFBLK => VfsNodeType::BlockDevice,
+        S_IFDIR => VfsNodeType::Dir,
+        S_IFCHR => VfsNodeType::CharDevice,
+        S_IFIFO => VfsNodeType::Fifo,
+        _ => VfsNodeType::File,
+    }
+}
+
+/// Returns the type in the mode and the default permissions of a new inode.
+fn new_mode(ty: VfsNodeType) -> u16 {
+    match ty {
+        VfsNodeType::Fifo => S_IFIFO | 0o644,
+        VfsNodeType::CharDevice => S_IFCHR | 0o644,
+        VfsNodeType::Dir => S_IFDIR | 0o755,
+        VfsNodeType::BlockDevice => S_IFBLK | 0o644,
+        VfsNodeType::File => S_IFREG | 0o644,
+        VfsNodeType::SymLink => S_IFLNK | 0o777,
+        VfsNodeType::Socket => S_IFSOCK | 0o644,
+    }
+}
+
+/// Returns the type of a directory entry, of the feature `filetype`.
+fn dirent_type(mode: u16) -> u8 {
+    match mode & S_IFMT {
+        S_IFREG => 1,
+        S_IFDIR => 2,
+        S_IFCHR => 3,
+        S_IFBLK => 4,
+        S_IFIFO => 5,
+        S_IFSOCK => 6,
+        S_IFLNK => 7,
+        _ => 0,
+    }
+}
+
+/// Returns the size of a directory entry of the name of `name_len` bytes.
+const fn dirent_size(name_len: usize) -> usize {
+    (DIRENT_HEADER_SIZE + name_len + 3) & !3
+}
+
+/// An inode, of its first 128 bytes, the other ones are kept as they are.
+struct Inode {
+    ino: u32,
+    raw: [u8; INODE_SIZE],
+}
+
+impl Inode {
+    fn mode(&self) -> u16 {
+        get_u16(&self.raw, 0)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.mode() & S_IFMT == S_IFDIR
+    }
+
+    fn is_file(&self) -> bool {
+        self.mode() & S_IFMT == S_IFREG
+    }
+
+    fn size(&self) -> u64 {
+        let high = if self.is_file() {
+            get_u32(&self.raw, 108)
+        } else {
+            0
+        };
+        ((high as u64) << 32) | get_u32(&self.raw, 4) as u64
+    }
+
+    fn set_size(&mut self, size: u64) {
+        set_u32(&mut self.raw, 4, size as u32);
+        if self.is_file() {
+            set_u32(&mut self.raw, 108, (size >> 32) as u32);
+        }
+    }
+
+    /// Returns the owner user, of the low 16 bits and the high ones in the
+    /// Linux-specific field.
+    fn uid(&self) -> u32 {
+        get_u16(&self.raw, 2) as u32 | (get_u16(&self.raw, 120) as u32) << 16
+    }
+
+    /// Returns the owner group, like [`Inode::uid`].
+    fn gid(&self) -> u32 {
+        get_u16(&self.raw, 24) as u32 | (get_u16(&self.raw, 122) as u32) << 16
+    }
+
+    fn atime(&self) -> u32 {
+        get_u32(&self.raw, 8)
+    }
+
+    fn ctime(&self) -> u32 {
+        get_u32(&self.raw, 12)
+    }
+
+    fn mtime(&self) -> u32 {
+        get_u32(&self.raw, 16)
+    }
+
+    fn set_atime(&mut self, time: u32) {
+        set_u32(&mut self.raw, 8, time);
+    }
+
+    fn set_ctime(&mut self, time: u32) {
+        set_u32(&mut self.raw, 12, time);
+    }
+
+    /// Sets the modification and the change time, when the data is changed.
+    fn touch(&mut self) {
+        let time = now();
+        set_u32(&mut self.raw, 12, time);
+        set_u32(&mut self.raw, 16, time);
+    }
+
+    fn links(&self) -> u16 {
+        get_u16(&self.raw, 26)
+    }
+
+    fn set_links(&mut self, links: u16) {
+        set_u16(&mut self.raw, 26, links);
+    }
+
+    /// The number of the 512-byte sectors of the blocks.
+    fn sectors(&self) -> u32 {
+        get_u32(&self.raw, 28)
+    }
+
+    fn set_sectors(&mut self, sectors: u32) {
+        set_u32(&mut self.raw, 28, sectors);
+    }
+
+    fn flags(&self) -> u32 {
+        get_u32(&self.raw, 32)
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        set_u32(&mut self.raw, 32, flags);
+    }
+
+    fn block(&self, idx: usize) -> u32 {
+        get_u32(&self.raw, 40 + idx * 4)
+    }
+
+    fn set_block(&mut self, idx: usize, block: u32) {
+        set_u32(&mut self.raw, 40 + idx * 4, block);
+    }
+
+    /// The block of the extended attributes.
+    fn file_acl(&self) -> u32 {
+        get_u32(&self.raw, 104)
+    }
+
+    /// Returns whether it's a symlink of the target stored in the block
+    /// pointers.
+    fn is_fast_symlink(&self, block_size: usize) -> bool {
+        let acl_sectors = if self.file_acl() != 0 {
+            block_size as u32 / 512
+        } else {
+            0
+        };
+        self.mode() & S_IFMT == S_IFLNK && self.sectors() == acl_sectors
+    }
+
+    /// The data stored in the block pointers.
+    fn inline_data(&mut self) -> &mut [u8] {
+        &mut self.raw[40..40 + FAST_SYMLINK_MAX]
+    }
+}
+
+/// An entry of a directory.
+struct DirEntry<'a> {
+    ino: u32,
+    rec_len: usize,
+    ty: u8,
+    name: &'a [u8],
+}
+
+/// The position of an entry in a directory.
+struct EntryPos {
+    /// The index of the block in the directory.
+    idx: u64,
+    offset: usize,
+    /// The offset of the previous entry in the block.
+    prev: Option<usize>,
+    ino: u32,
+}
+
+/// The state of a mounted ext2 filesystem.
+struct Ext2 {
+    disk: Disk,
+    sb: Vec<u8>,
+    gdt: Vec<u8>,
+    block_size: usize,
+    inode_size: usize,
+    first_ino: u32,
+    blocks_count: u32,
+    first_data_block: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    groups: u32,
+    /// Whether the directory entries have the types of the inodes.
+    filetype: bool,
+    read_only: bool,
+}
+
+impl Ext2 {
+    fn open(mut disk: Disk) -> VfsResult<Self> {
+        let mut sb = vec![0; SUPERBLOCK_SIZE];
+        read_exact(&mut disk, SUPERBLOCK_OFFSET, &mut sb)?;
+        if get_u16(&sb, 56) != EXT2_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let log_block_size = get_u32(&sb, 24);
+        if log_block_size > 6 {
+            return Err(VfsError::InvalidData);
+        }
+        let block_size = 1024 << log_block_size;
+        let rev_level = get_u32(&sb, 76);
+        let (first_ino, inode_size, incompat, ro_compat) = if rev_level == 0 {
+            (GOOD_OLD_FIRST_INO, GOOD_OLD_INODE_SIZE, 0, 0)
+        } else {
+            (
+                get_u32(&sb, 84),
+                get_u16(&sb, 88) as usize,
+                get_u32(&sb, 96),
+                get_u32(&sb, 100),
+            )
+        };
+        if incompat & !SUPPORTED_INCOMPAT != 0 {
+            warn!(
+                "ext2: unsupported incompatible features {:#x}",
+                incompat & !SUPPORTED_INCOMPAT
+            );
+            return Err(VfsError::Unsupported);
+        }
+        let read_only = ro_compat & !SUPPORTED_RO_COMPAT != 0;
+        if read_only {
+            warn!(
+                "ext2: unsupported read-only compatible features {:#x}, mount read-only",
+                ro_compat & !SUPPORTED_RO_COMPAT
+            );
+        }
+        if get_u16(&sb, 58) & 0x2 != 0 {
+            warn!("ext2: the filesystem has errors, run fsck");
+        }
+
+        let blocks_count = get_u32(&sb, 4);
+        let first_data_block = get_u32(&sb, 20);
+        let blocks_per_group = get_u32(&sb, 32);
+        let inodes_per_group = get_u32(&sb, 40);
+        if blocks_per_group == 0
+            || inodes_per_group == 0
+            || inode_size < INODE_SIZE
+            || blocks_count <= first_data_block
+        {
+            return Err(VfsError::InvalidData);
+        }
+        let groups = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+        let mut gdt = vec![0; groups as usize * GROUP_DESC_SIZE];
+        let gdt_pos = (first_data_block as u64 + 1) * block_size as u64;
+        read_exact(&mut disk, gdt_pos, &mut gdt)?;
+
+        let mut fs = Self {
+            disk,
+            sb,
+            gdt,
+            block_size,
+            inode_size,
+            first_ino,
+            blocks_count,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            groups,
+            filetype: incompat & FEATURE_INCOMPAT_FILETYPE != 0,
+            read_only,
+        };
+        if !fs.read_only {
+            // the mount time and count
+            let time = now();
+            set_u32(&mut fs.sb, 44, time);
+            let count = get_u16(&fs.sb, 52);
+            set_u16(&mut fs.sb, 52, count.wrapping_add(1));
+            fs.write_super()?;
+        }
+        info!(
+            "ext2: {} blocks of {} bytes, {} groups",
+            blocks_count, block_size, groups
+        );
+        Ok(fs)
+    }
+
+    /// Returns the statistics in the superblock, where the blocks reserved
+    /// for root are not available to the others.
+    fn stat(&self) -> FileSystemStat {
+        let free_blocks = get_u32(&self.sb, 12) as u64;
+        let reserved = get_u32(&self.sb, 8) as u64;
+        FileSystemStat {
+            block_size: self.block_size as u64,
+            blocks: self.blocks_count as u64,
+            blocks_free: free_blocks,
+            blocks_avail: free_blocks.saturating_sub(reserved),
+            files: get_u32(&self.sb, 0) as u64,
+            files_free: get_u32(&self.sb, 16) as u64,
+            ..Default::default()
+        }
+    }
+
+    fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            Err(VfsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_bytes(&mut self, pos: u64, buf: &mut [u8]) -> VfsResult {
+        read_exact(&mut self.disk, pos, buf)
+    }
+
+    fn write_bytes(&mut self, pos: u64, buf: &[u8]) -> VfsResult {
+        self.disk.set_position(pos);
+        let mut done = 0;
+        while done < buf.len() {
+            match self.disk.write_one(&buf[done..]) {
+                Ok(0) => return Err(VfsError::WriteZero),
+                Ok(n) => done += n,
+                Err(_) => return Err(VfsError::Io),
+            }
+        }
+        Ok(())
+    }
+
+    fn block_pos(&self, block: u32) -> u64 {
+        block as u64 * self.block_size as u64
+    }
+
+    fn read_block(&mut self, block: u32) -> VfsResult<Vec<u8>> {
+        let mut buf = vec![0; self.block_size];
+        self.read_bytes(self.block_pos(block), &mut buf)?;
+        Ok(buf)
+    }
+
+    fn write_block(&mut self, block: u32, buf: &[u8]) -> VfsResult {
+        self.write_bytes(self.block_pos(block), buf)
+    }
+
+    fn read_u32(&mut self, pos: u64) -> VfsResult<u32> {
+        let mut buf = [0; 4];
+        self.read_bytes(pos, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn write_u32(&mut self, pos: u64, value: u32) -> VfsResult {
+        self.write_bytes(pos, &value.to_le_bytes())
+    }
+
+    fn write_super(&mut self) -> VfsResult {
+        set_u32(&mut self.sb, 48, now());
+        let sb = core::mem::take(&mut self.sb);
+        let res = self.write_bytes(SUPERBLOCK_OFFSET, &sb);
+        self.sb = sb;
+        res
+    }
+
+    /// Returns the field at `off` of the descriptor of the group `group`.
+    fn group_field(&self, group: u32, off: usize) -> usize {
+        group as usize * GROUP_DESC_SIZE + off
+    }
+
+    fn block_bitmap(&self, group: u32) -> u32 {
+        get_u32(&self.gdt, self.group_field(group, 0))
+    }
+
+    fn inode_bitmap(&self, group: u32) -> u32 {
+        get_u32(&self.gdt, self.group_field(group, 4))
+    }
+
+    fn inode_table(&self, group: u32) -> u32 {
+        get_u32(&self.gdt, self.group_field(group, 8))
+    }
+
+    /// Adds `delta` to the 16-bit counter at `off` of the descriptor of the
+    /// group, and the 32-bit one at `sb_off` of the superblock if any, and
+    /// writes them back.
+    fn update_counts(
+        &mut self,
+        group: u32,
+        off: usize,
+        sb_off: Option<usize>,
+        delta: i32,
+    ) -> VfsResult {
+        let field = self.group_field(group, off);
+        let count = get_u16(&self.gdt, field) as i32 + delta;
+        set_u16(&mut self.gdt, field, count as u16);
+        let desc = group as usize * GROUP_DESC_SIZE;
+        let pos = self.block_pos(self.first_data_block + 1) + desc as u64;
+        let buf: [u8; GROUP_DESC_SIZE] = self.gdt[desc..desc + GROUP_DESC_SIZE].try_into().unwrap();
+        self.write_bytes(pos, &buf)?;
+        if let Some(sb_off) = sb_off {
+            let count = get_u32(&self.sb, sb_off) as i64 + delta as i64;
+            set_u32(&mut self.sb, sb_off, count as u32);
+            self.write_super()?;
+        }
+        Ok(())
+    }
+
+    fn inode_pos(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.groups * self.inodes_per_group {
+            return Err(VfsError::InvalidData);
+        }
+        let group = (ino - 1) / self.inodes_per_group;
+        let index = (ino - 1) % self.inodes_per_group;
+        Ok(self.block_pos(self.inode_table(group)) + index as u64 * self.inode_size as u64)
+    }
+
+    fn read_inode(&mut self, ino: u32) -> VfsResult<Inode> {
+        let mut inode = Inode {
+            ino,
+            raw: [0; INODE_SIZE],
+        };
+        self.read_bytes(self.inode_pos(ino)?, &mut inode.raw)?;
+        Ok(inode)
+    }
+
+    fn write_inode(&mut self, inode: &Inode) -> VfsResult {
+        self.write_bytes(self.inode_pos(inode.ino)?, &inode.raw)
+    }
+
+    /// Returns the index of the first clear bit below `limit` in `bitmap`.
+    fn find_clear_bit(bitmap: &[u8], limit: u32) -> Option<u32> {
+        (0..limit).find(|&bit| bitmap[bit as usize / 8] & (1 << (bit % 8)) == 0)
+    }
+
+    /// Allocates a zeroed block for `inode`, preferably in the group of the
+    /// inode.
+    fn alloc_block(&mut self, inode: &mut Inode) -> VfsResult<u32> {
+        let start = (inode.ino - 1) / self.inodes_per_group;
+        for i in 0..self.groups {
+            let group = (start + i) % self.groups;
+            if get_u16(&self.gdt, self.group_field(group, 12)) == 0 {
+                continue;
+            }
+            let first = self.first_data_block + group * self.blocks_per_group;
+            let limit = self.blocks_per_group.min(self.blocks_count - first);
+            let bitmap_block = self.block_bitmap(group);
+            let mut bitmap = self.read_block(bitmap_block)?;
+            let Some(bit) = Self::find_clear_bit(&bitmap, limit) else {
+                continue;
+            };
+            bitmap[bit as usize / 8] |= 1 << (bit % 8);
+            self.write_block(bitmap_block, &bitmap)?;
+            self.update_counts(group, 12, Some(12), -1)?;
+
+            let block = first + bit;
+            self.write_block(block, &vec![0; self.block_size])?;
+            inode.set_sectors(inode.sectors() + self.block_size as u32 / 512);
+            return Ok(block);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_block(&mut self, inode: &mut Inode, block: u32) -> VfsResult {
+        if block < self.first_data_block || block >= self.blocks_count {
+            return Err(VfsError::InvalidData);
+        }
+        let group = (block - self.first_data_block) / self.blocks_per_group;
+        let bit = (block - self.first_data_block) % self.blocks_per_group;
+        let bitmap_block = self.block_bitmap(group);
+        let mut bitmap = self.read_block(bitmap_block)?;
+        bitmap[bit as usize / 8] &= !(1 << (bit % 8));
+        self.write_block(bitmap_block, &bitmap)?;
+        self.update_counts(group, 12, Some(12), 1)?;
+        let sectors = self.block_size as u32 / 512;
+        inode.set_sectors(inode.sectors().saturating_sub(sectors));
+        Ok(())
+    }
+
+    /// Allocates an inode, preferably in the group of `parent`.
+    fn alloc_inode(&mut self, parent: u32, dir: bool) -> VfsResult<u32> {
+        let start = (parent - 1) / self.inodes_per_group;
+        for i in 0..self.groups {
+            let group = (start + i) % self.groups;
+            if get_u16(&self.gdt, self.group_field(group, 14)) == 0 {
+                continue;
+            }
+            let bitmap_block = self.inode_bitmap(group);
+            let mut bitmap = self.read_block(bitmap_block)?;
+            // skip the reserved inodes
+            let Some(bit) = (0..self.inodes_per_group).find(|&bit| {
+                group * self.inodes_per_group + bit + 1 >= self.first_ino
+                    && bitmap[bit as usize / 8] & (1 << (bit % 8)) == 0
+            }) else {
+                continue;
+            };
+            bitmap[bit as usize / 8] |= 1 << (bit % 8);
+            self.write_block(bitmap_block, &bitmap)?;
+            self.update_counts(group, 14, Some(16), -1)?;
+            if dir {
+                self.update_counts(group, 16, None, 1)?;
+            }
+            return Ok(group * self.inodes_per_group + bit + 1);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_inode(&mut self, ino: u32, dir: bool) -> VfsResult {
+        let group = (ino - 1) / self.inodes_per_group;
+        let bit = (ino - 1) % self.inodes_per_group;
+        let bitmap_block = self.inode_bitmap(group);
+        let mut bitmap = self.read_block(bitmap_block)?;
+        bitmap[bit as usize / 8] &= !(1 << (bit % 8));
+        self.write_block(bitmap_block, &bitmap)?;
+        self.update_counts(group, 14, Some(16), 1)?;
+        if dir {
+            self.update_counts(group, 16, None, -1)?;
+        }
+        Ok(())
+    }
+
+    /// The number of the block pointers in a block.
+    fn ptrs_per_block(&self) -> u64 {
+        self.block_size as u64 / 4
+    }
+
+    /// Returns the block of the index `idx` in the data of `inode`, 0 for a
+    /// hole. The block and the indirect ones are allocated if `create`.
+    fn bmap(&mut self, inode: &mut Inode, idx: u64, create: bool) -> VfsResult<u32> {
+        let ptrs = self.ptrs_per_block();
+        let mut path = [0u64; 3];
+        let (slot, depth) = if idx < NDIR_BLOCKS as u64 {
+            (idx as usize, 0)
+        } else if idx - (NDIR_BLOCKS as u64) < ptrs {
+            path[0] = idx - NDIR_BLOCKS as u64;
+            (NDIR_BLOCKS, 1)
+        } else if idx - (NDIR_BLOCKS as u64) - ptrs < ptrs * ptrs {
+            let idx = idx - NDIR_BLOCKS as u64 - ptrs;
+            path[..2].copy_from_slice(&[idx / ptrs, idx % ptrs]);
+            (NDIR_BLOCKS + 1, 2)
+        } else if idx - (NDIR_BLOCKS as u64) - ptrs - ptrs * ptrs < ptrs * ptrs * ptrs {
+            let idx = idx - NDIR_BLOCKS as u64 - ptrs - ptrs * ptrs;
+            path = [idx / (ptrs * ptrs), idx / ptrs % ptrs, idx % ptrs];
+            (NDIR_BLOCKS + 2, 3)
+        } else {
+            return Err(VfsError::InvalidInput);
+        };
+
+        let mut block = inode.block(slot);
+        if block == 0 {
+            if !create {
+                return Ok(0);
+            }
+            block = self.alloc_block(inode)?;
+            inode.set_block(slot, block);
+        }
+        for &index in &path[..depth] {
+            let pos = self.block_pos(block) + index * 4;
+            let mut next = self.read_u32(pos)?;
+            if next == 0 {
+                if !create {
+                    return Ok(0);
+                }
+                next = self.alloc_block(inode)?;
+                self.write_u32(pos, next)?;
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+
+    /// Frees the blocks of the indices from `keep` in the tree of `block`,
+    /// which covers the indices from `base` with `level` levels of the
+    /// indirect blocks. Returns whether `block` itself is freed.
+    fn free_tree(
+        &mut self,
+        inode: &mut Inode,
+        block: u32,
+        level: u32,
+        base: u64,
+        keep: u64,
+    ) -> VfsResult<bool> {
+        if level > 0 {
+            let ptrs = self.ptrs_per_block();
+            let span = ptrs.pow(level - 1);
+            let mut buf = self.read_block(block)?;
+            let mut changed = false;
+            for i in 0..ptrs as usize {
+                let child_base = base + i as u64 * span;
+                let child = get_u32(&buf, i * 4);
+                if child == 0 || child_base + span <= keep {
+                    continue;
+                }
+                if self.free_tree(inode, child, level - 1, child_base, keep)? {
+                    set_u32(&mut buf, i * 4, 0);
+                    changed = true;
+                }
+            }
+            if keep > base {
+                if changed {
+                    self.write_block(block, &buf)?;
+                }
+                return Ok(false);
+            }
+        } else if keep > base {
+            return Ok(false);
+        }
+        self.free_block(inode, block)?;
+        Ok(true)
+    }
+
+    /// Frees the data blocks of the indices from `keep` of `inode`.
+    fn free_blocks_from(&mut self, inode: &mut Inode, keep: u64) -> VfsResult {
+        let ptrs = self.ptrs_per_block();
+        let mut base = 0;
+        for slot in 0..N_BLOCKS {
+            let level = slot.saturating_sub(NDIR_BLOCKS - 1) as u32;
+            let span = ptrs.pow(level);
+            let block = inode.block(slot);
+            if block != 0
+                && base + span > keep
+                && self.free_tree(inode, block, level, base, keep)?
+            {
+                inode.set_block(slot, 0);
+            }
+            base += span;
+        }
+        Ok(())
+    }
+
+    fn read_at(&mut self, ino: u32, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut inode = self.read_inode(ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = (buf.len() as u64).min(size - offset) as usize;
+        if inode.is_fast_symlink(self.block_size) {
+            let offset = offset as usize;
+            buf[..len].copy_from_slice(&inode.inline_data()[offset..offset + len]);
+        } else {
+            let bs = self.block_size as u64;
+            let mut done = 0;
+            while done < len {
+                let pos = offset + done as u64;
+                let off = (pos % bs) as usize;
+                let n = (self.block_size - off).min(len - done);
+                let block = self.bmap(&mut inode, pos / bs, false)?;
+                if block == 0 {
+                    buf[done..done + n].fill(0);
+                } else {
+                    self.read_bytes(self.block_pos(block) + off as u64, &mut buf[done..done + n])?;
+                }
+                done += n;
+            }
+        }
+
+        let time = now();
+        let atime = inode.atime();
+        if !self.read_only
+            && (atime <= inode.mtime()
+                || atime <= inode.ctime()
+                || time.wrapping_sub(atime) >= ATIME_INTERVAL)
+        {
+            inode.set_atime(time);
+            self.write_inode(&inode)?;
+        }
+        Ok(len)
+    }
+
+    fn write_at(&mut self, ino: u32, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(VfsError::InvalidInput)?;
+        if inode.is_fast_symlink(self.block_size) {
+            if end as usize <= FAST_SYMLINK_MAX {
+                let offset = offset as usize;
+                inode.inline_data()[offset..end as usize].copy_from_slice(buf);
+                inode.set_size(inode.size().max(end));
+                inode.touch();
+                self.write_inode(&inode)?;
+                return Ok(buf.len());
+            }
+            // move the target to a block
+            let size = inode.size() as usize;
+            let data: Vec<u8> = inode.inline_data()[..size].to_vec();
+            inode.inline_data().fill(0);
+            let block = self.bmap(&mut inode, 0, true)?;
+            self.write_bytes(self.block_pos(block), &data)?;
+        }
+
+        let bs = self.block_size as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let off = (pos % bs) as usize;
+            let n = (self.block_size - off).min(buf.len() - done);
+            let block = match self.bmap(&mut inode, pos / bs, true) {
+                Ok(block) => block,
+                Err(e) if done == 0 => {
+                    self.write_inode(&inode)?;
+                    return Err(e);
+                }
+                Err(_) => break,
+            };
+            self.write_bytes(self.block_pos(block) + off as u64, &buf[done..done + n])?;
+            done += n;
+        }
+
+        let end = offset + done as u64;
+        if end > inode.size() {
+            inode.set_size(end);
+            if end > i32::MAX as u64 {
+                self.set_large_file()?;
+            }
+        }
+        inode.touch();
+        self.write_inode(&inode)?;
+        Ok(done)
+    }
+
+    /// Sets the feature `large_file`, once a file is larger than 2 GiB.
+    fn set_large_file(&mut self) -> VfsResult {
+        let ro_compat = get_u32(&self.sb, 100);
+        if get_u32(&self.sb, 76) == 0 || ro_compat & FEATURE_RO_COMPAT_LARGE_FILE != 0 {
+            return Ok(());
+        }
+        set_u32(&mut self.sb, 100, ro_compat | FEATURE_RO_COMPAT_LARGE_FILE);
+        self.write_super()
+    }
+
+    fn truncate(&mut self, ino: u32, size: u64) -> VfsResult {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        let old_size = inode.size();
+        if inode.is_fast_symlink(self.block_size) {
+            if size as usize > FAST_SYMLINK_MAX {
+                return Err(VfsError::InvalidInput);
+            }
+            inode.inline_data()[size as usize..].fill(0);
+        } else if size < old_size {
+            let bs = self.block_size as u64;
+            // zero the tail of the last block kept
+            if size % bs != 0 {
+                let block = self.bmap(&mut inode, size / bs, false)?;
+                if block != 0 {
+                    let off = (size % bs) as usize;
+                    let zeros = vec![0; self.block_size - off];
+                    self.write_bytes(self.block_pos(block) + off as u64, &zeros)?;
+                }
+            }
+            self.free_blocks_from(&mut inode, size.div_ceil(bs))?;
+        }
+        inode.set_size(size);
+        if size > i32::MAX as u64 {
+            self.set_large_file()?;
+        }
+        inode.touch();
+        self.write_inode(&inode)
+    }
+
+    /// Parses the entry at `offset` of the block of a directory.
+    fn parse_entry<'a>(&self, block: &'a [u8], offset: usize) -> VfsResult<DirEntry<'a>> {
+        if offset + DIRENT_HEADER_SIZE > block.len() {
+            return Err(VfsError::InvalidData);
+        }
+        let rec_len = get_u16(block, offset + 4) as usize;
+        let name_len = block[offset + 6] as usize;
+        if rec_len < DIRENT_HEADER_SIZE + name_len
+            || rec_len % 4 != 0
+            || offset + rec_len > block.len()
+        {
+            return Err(VfsError::InvalidData);
+        }
+        Ok(DirEntry {
+            ino: get_u32(block, offset),
+            rec_len,
+            ty: if self.filetype { block[offset + 7] } else { 0 },
+            name: &block[offset + DIRENT_HEADER_SIZE..offset + DIRENT_HEADER_SIZE + name_len],
+        })
+    }
+
+    /// Writes an entry at `offset` of the block of a directory.
+    fn set_entry(
+        &self,
+        block: &mut [u8],
+        offset: usize,
+        ino: u32,
+        rec_len: usize,
+        name: &[u8],
+        ty: u8,
+    ) {
+        set_u32(block, offset, ino);
+        set_u16(block, offset + 4, rec_len as u16);
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = if self.filetype { ty } else { 0 };
+        block[offset + DIRENT_HEADER_SIZE..offset + DIRENT_HEADER_SIZE + name.len()]
+            .copy_from_slice(name);
+    }
+
+    /// Returns the number of the blocks of the directory and the block of the
+    /// index `idx` of it.
+    fn dir_block(&mut self, dir: &mut Inode, idx: u64) -> VfsResult<Option<(u32, Vec<u8>)>> {
+        let block = self.bmap(dir, idx, false)?;
+        if block == 0 {
+            return Ok(None);
+        }
+        Ok(Some((block, self.read_block(block)?)))
+    }
+
+    /// Calls `f` on each entry of the directory in order, until it returns
+    /// `true`.
+    fn for_each_entry(
+        &mut self,
+        dir: &mut Inode,
+        mut f: impl FnMut(&DirEntry<'_>, EntryPos) -> bool,
+    ) -> VfsResult {
+        if !dir.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let nblocks = dir.size().div_ceil(self.block_size as u64);
+        for idx in 0..nblocks {
+            let Some((_, block)) = self.dir_block(dir, idx)? else {
+                continue;
+            };
+            let mut offset = 0;
+            let mut prev = None;
+            while offset < block.len() {
+                let entry = self.parse_entry(&block, offset)?;
+                let pos = EntryPos {
+                    idx,
+                    offset,
+                    prev,
+                    ino: entry.ino,
+                };
+                if entry.ino != 0 && f(&entry, pos) {
+                    return Ok(());
+                }
+                prev = Some(offset);
+                offset += entry.rec_len;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_entry(&mut self, dir: &mut Inode, name: &str) -> VfsResult<Option<EntryPos>> {
+        let mut found = None;
+        self.for_each_entry(dir, |entry, pos| {
+            if entry.name == name.as_bytes() {
+                found = Some(pos);
+                true
+            } else {
+                false
+            }
+        })?;
+        Ok(found)
+    }
+
+    fn lookup_entry(&mut self, dir: u32, name: &str) -> VfsResult<u32> {
+        let mut dir = self.read_inode(dir)?;
+        self.find_entry(&mut dir, name)?
+            .map(|pos| pos.ino)
+            .ok_or(VfsError::NotFound)
+    }
+
+    /// Adds the entry `name` of the inode `ino` to the directory `dir`.
+    fn add_entry(&mut self, dir: u32, name: &str, ino: u32, ty: u8) -> VfsResult {
+        let name = name.as_bytes();
+        if name.len() > MAX_NAME_LEN {
+            return Err(VfsError::InvalidInput);
+        }
+        let need = dirent_size(name.len());
+        let mut dir = self.read_inode(dir)?;
+        let nblocks = dir.size().div_ceil(self.block_size as u64);
+        for idx in 0..nblocks {
+            let Some((block_no, mut block)) = self.dir_block(&mut dir, idx)? else {
+                continue;
+            };
+            let mut offset = 0;
+            while offset < block.len() {
+                let entry = self.parse_entry(&block, offset)?;
+                let rec_len = entry.rec_len;
+                let used = if entry.ino == 0 {
+                    0
+                } else {
+                    dirent_size(entry.name.len())
+                };
+                if rec_len - used >= need {
+                    if used == 0 {
+                        self.set_entry(&mut block, offset, ino, rec_len, name, ty);
+                    } else {
+                        set_u16(&mut block, offset + 4, used as u16);
+                        self.set_entry(&mut block, offset + used, ino, rec_len - used, name, ty);
+                    }
+                    self.write_block(block_no, &block)?;
+                    return self.dir_changed(&mut dir);
+                }
+                offset += rec_len;
+            }
+        }
+
+        // append a block of the entry
+        let block_no = self.bmap(&mut dir, nblocks, true)?;
+        let mut block = vec![0; self.block_size];
+        self.set_entry(&mut block, 0, ino, self.block_size, name, ty);
+        self.write_block(block_no, &block)?;
+        dir.set_size((nblocks + 1) * self.block_size as u64);
+        self.dir_changed(&mut dir)
+    }
+
+    /// Removes the entry `name` of the directory `dir`, returns its inode.
+    fn remove_entry(&mut self, dir: u32, name: &str) -> VfsResult<u32> {
+        let mut dir = self.read_inode(dir)?;
+        let pos = self.find_entry(&mut dir, name)?.ok_or(VfsError::NotFound)?;
+        let (block_no, mut block) = self
+            .dir_block(&mut dir, pos.idx)?
+            .ok_or(VfsError::InvalidData)?;
+        match pos.prev {
+            // merge it into the previous entry
+            Some(prev) => {
+                let len = get_u16(&block, prev + 4) + get_u16(&block, pos.offset + 4);
+                set_u16(&mut block, prev + 4, len);
+            }
+            None => set_u32(&mut block, pos.offset, 0),
+        }
+        self.write_block(block_no, &block)?;
+        self.dir_changed(&mut dir)?;
+        Ok(pos.ino)
+    }
+
+    /// Updates the directory after its entries are changed, and drops its
+    /// hashed index.
+    fn dir_changed(&mut self, dir: &mut Inode) -> VfsResult {
+        dir.set_flags(dir.flags() & !INDEX_FL);
+        dir.touch();
+        self.write_inode(dir)
+    }
+
+    /// Sets the inode of the entry `name` of the directory `dir`, and its type
+    /// if `ty` is given.
+    fn set_entry_ino(&mut self, dir: u32, name: &str, ino: u32, ty: Option<u8>) -> VfsResult {
+        let mut dir = self.read_inode(dir)?;
+        let pos = self.find_entry(&mut dir, name)?.ok_or(VfsError::NotFound)?;
+        let (block_no, mut block) = self
+            .dir_block(&mut dir, pos.idx)?
+            .ok_or(VfsError::InvalidData)?;
+        set_u32(&mut block, pos.offset, ino);
+        if let Some(ty) = ty.filter(|_| self.filetype) {
+            block[pos.offset + 7] = ty;
+        }
+        self.write_block(block_no, &block)?;
+        self.dir_changed(&mut dir)
+    }
+
+    fn add_links(&mut self, ino: u32, delta: i16) -> VfsResult {
+        let mut inode = self.read_inode(ino)?;
+        inode.set_links(inode.links().saturating_add_signed(delta));
+        inode.set_ctime(now());
+        self.write_inode(&inode)
+    }
+
+    fn is_empty_dir(&mut self, dir: &mut Inode) -> VfsResult<bool> {
+        let mut empty = true;
+        self.for_each_entry(dir, |entry, _| {
+            empty = entry.name == b"." || entry.name == b"..";
+            !empty
+        })?;
+        Ok(empty)
+    }
+
+    /// Resolves `path` from the directory `ino`, or the root if it's absolute.
+    fn resolve(&mut self, mut ino: u32, path: &str) -> VfsResult<u32> {
+        if path.starts_with('/') {
+            ino = ROOT_INO;
+        }
+        for name in path.split('/') {
+            match name {
+                "" | "." => {}
+                _ => ino = self.lookup_entry(ino, name)?,
+            }
+        }
+        Ok(ino)
+    }
+
+    /// Resolves the directory of the last component of `path` from the
+    /// directory `ino`, returns the directory and the name.
+    fn resolve_parent<'a>(&mut self, ino: u32, path: &'a str) -> VfsResult<(u32, &'a str)> {
+        let trimmed = path.trim_end_matches('/');
+        let (dir, name) = match trimmed.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((dir, name)) => (dir, name),
+            None => ("", trimmed),
+        };
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::InvalidInput);
+        }
+        let dir = if path.starts_with('/') && dir.is_empty() {
+            ROOT_INO
+        } else {
+            self.resolve(ino, dir)?
+        };
+        Ok((dir, name))
+    }
+
+    fn create(&mut self, ino: u32, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.check_writable()?;
+        let (dir, name) = self.resolve_parent(ino, path)?;
+        let mut parent = self.read_inode(dir)?;
+        if self.find_entry(&mut parent, name)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let is_dir = ty == VfsNodeType::Dir;
+        let ino = self.alloc_inode(dir, is_dir)?;
+
+        let mut inode = Inode {
+            ino,
+            raw: [0; INODE_SIZE],
+        };
+        let mode = new_mode(ty);
+        set_u16(&mut inode.raw, 0, mode);
+        let time = now();
+        inode.set_atime(time);
+        inode.touch();
+        inode.set_links(1);
+        if is_dir {
+            let block_no = self.alloc_block(&mut inode)?;
+            inode.set_block(0, block_no);
+            let mut block = vec![0; self.block_size];
+            let dot_len = dirent_size(1);
+            self.set_entry(&mut block, 0, ino, dot_len, b".", 2);
+            self.set_entry(
+                &mut block,
+                dot_len,
+                dir,
+                self.block_size - dot_len,
+                b"..",
+                2,
+            );
+            self.write_block(block_no, &block)?;
+            inode.set_size(self.block_size as u64);
+            inode.set_links(2);
+        }
+        // clear the rest of the inode, e.g. the extended fields
+        let zeros = vec![0; self.inode_size];
+        self.write_bytes(self.inode_pos(ino)?, &zeros)?;
+        self.write_inode(&inode)?;
+
+        self.add_entry(dir, name, ino, dirent_type(mode))?;
+        if is_dir {
+            self.add_links(dir, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Drops a link of the inode, and frees it with its blocks if it's the
+    /// last one.
+    fn unlink_inode(&mut self, ino: u32) -> VfsResult {
+        let mut inode = self.read_inode(ino)?;
+        let is_dir = inode.is_dir();
+        let links = if is_dir {
+            0
+        } else {
+            inode.links().saturating_sub(1)
+        };
+        inode.set_links(links);
+        inode.set_ctime(now());
+        if links == 0 {
+            if !inode.is_fast_symlink(self.block_size) {
+                self.free_blocks_from(&mut inode, 0)?;
+            }
+            inode.set_size(0);
+            set_u32(&mut inode.raw, 20, now()); // the deletion time
+            self.write_inode(&inode)?;
+            self.free_inode(ino, is_dir)
+        } else {
+            self.write_inode(&inode)
+        }
+    }
+
+    fn remove(&mut self, ino: u32, path: &str) -> VfsResult {
+        self.check_writable()?;
+        let (dir, name) = self.resolve_parent(ino, path)?;
+        let target = self.lookup_entry(dir, name)?;
+        let mut inode = self.read_inode(target)?;
+        let is_dir = inode.is_dir();
+        if is_dir && !self.is_empty_dir(&mut inode)? {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        self.remove_entry(dir, name)?;
+        if is_dir {
+            self.add_links(dir, -1)?;
+        }
+        self.unlink_inode(target)
+    }
+
+    /// Returns whether `ino` is the directory `dir` or one of its ancestors.
+    fn is_ancestor(&mut self, ino: u32, mut dir: u32) -> VfsResult<bool> {
+        loop {
+            if dir == ino {
+                return Ok(true);
+            }
+            if dir == ROOT_INO {
+                return Ok(false);
+            }
+            dir = self.lookup_entry(dir, "..")?;
+        }
+    }
+
+    fn rename(&mut self, ino: u32, src_path: &str, dst_path: &str) -> VfsResult {
+        self.check_writable()?;
+        let (src_dir, src_name) = self.resolve_parent(ino, src_path)?;
+        let (dst_dir, dst_name) = self.resolve_parent(ino, dst_path)?;
+        let target = self.lookup_entry(src_dir, src_name)?;
+        let mut inode = self.read_inode(target)?;
+        let is_dir = inode.is_dir();
+        if is_dir && self.is_ancestor(target, dst_dir)? {
+            return Err(VfsError::InvalidInput);
+        }
+
+        // replace the destination
+        match self.lookup_entry(dst_dir, dst_name) {
+            Ok(old) if old == target => return Ok(()),
+            Ok(old) => {
+                let mut old_inode = self.read_inode(old)?;
+                if old_inode.is_dir() {
+                    if !is_dir {
+                        return Err(VfsError::IsADirectory);
+                    }
+                    if !self.is_empty_dir(&mut old_inode)? {
+                        return Err(VfsError::DirectoryNotEmpty);
+                    }
+                    self.add_links(dst_dir, -1)?;
+                } else if is_dir {
+                    return Err(VfsError::NotADirectory);
+                }
+                self.set_entry_ino(dst_dir, dst_name, target, Some(dirent_type(inode.mode())))?;
+                self.unlink_inode(old)?;
+            }
+            Err(VfsError::NotFound) => {
+                self.add_entry(dst_dir, dst_name, target, dirent_type(inode.mode()))?;
+            }
+            Err(e) => return Err(e),
+        }
+        self.remove_entry(src_dir, src_name)?;
+
+        if is_dir && src_dir != dst_dir {
+            self.set_entry_ino(target, "..", dst_dir, None)?;
+            self.add_links(src_dir, -1)?;
+            self.add_links(dst_dir, 1)?;
+        }
+        let mut inode = self.read_inode(target)?;
+        inode.set_ctime(now());
+        self.write_inode(&inode)
+    }
+
+    fn read_dir(
+        &mut self,
+        ino: u32,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+    ) -> VfsResult<usize> {
+        let max = dirents.len();
+        if max == 0 {
+            return Ok(0);
+        }
+        let mut dir = self.read_inode(ino)?;
+        let mut entries = Vec::new();
+        let mut idx = 0;
+        self.for_each_entry(&mut dir, |entry, _| {
+            if idx >= start_idx {
+                let name = String::from_utf8_lossy(entry.name).into_owned();
+                entries.push((name, entry.ino, entry.ty));
+            }
+            idx += 1;
+            entries.len() == max
+        })?;
+        for (out, (name, ino, ty)) in dirents.iter_mut().zip(&entries) {
+            let ty = match ty {
+                1 => VfsNodeType::File,
+                2 => VfsNodeType::Dir,
+                3 => VfsNodeType::CharDevice,
+                4 => VfsNodeType::BlockDevice,
+                5 => VfsNodeType::Fifo,
+                6 => VfsNodeType::Socket,
+                7 => VfsNodeType::SymLink,
+                _ => node_type(self.read_inode(*ino)?.mode()),
+            };
+            *out = VfsDirEntry::new(name, ty);
+        }
+        Ok(entries.len())
+    }
+}
+
+fn read_exact(disk: &mut Disk, pos: u64, buf: &mut [u8]) -> VfsResult {
+    disk.set_position(pos);
+    let mut done = 0;
+    while done < buf.len() {
+        match disk.read_one(&mut buf[done..]) {
+            Ok(0) => return Err(VfsError::UnexpectedEof),
+            Ok(n) => done += n,
+            Err(_) => return Err(VfsError::Io),
+        }
+    }
+    Ok(())
+}
+
+/// A file, a directory or another inode in an ext2 filesystem.
+pub struct Ext2Node {
+    fs: Arc<Mutex<Ext2>>,
+    ino: u32,
+}
+
+impl Ext2Node {
+    fn new(fs: &Arc<Mutex<Ext2>>, ino: u32) -> Arc<Self> {
+        Arc::new(Self {
+            fs: fs.clone(),
+            ino,
+        })
+    }
+
+    /// Returns the number of the inode.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Returns the access, the modification and the change time of the inode,
+    /// in seconds since the epoch.
+    pub fn times(&self) -> VfsResult<(u32, u32, u32)> {
+        let inode = self.fs.lock().read_inode(self.ino)?;
+        Ok((inode.atime(), inode.mtime(), inode.ctime()))
+    }
+
+    /// Returns the user and the group owning the inode.
+    pub fn owner(&self) -> VfsResult<(u32, u32)> {
+        let inode = self.fs.lock().read_inode(self.ino)?;
+        Ok((inode.uid(), inode.gid()))
+    }
+}
+
+impl VfsNodeOps for Ext2Node {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let inode = self.fs.lock().read_inode(self.ino)?;
+        let mode = inode.mode();
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(mode & 0o777),
+            node_type(mode),
+            inode.size(),
+            inode.sectors() as u64,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.fs.lock().read_at(self.ino, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.fs.lock().write_at(self.ino, offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.fs.lock().disk.sync().map_err(|_| VfsError::Io)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.fs.lock().truncate(self.ino, size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let ino = self.fs.lock().lookup_entry(self.ino, "..").ok()?;
+        Some(Self::new(&self.fs, ino))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at ext2: {}", path);
+        let ino = self.fs.lock().resolve(self.ino, path)?;
+        if ino == self.ino {
+            return Ok(self);
+        }
+        Ok(Self::new(&self.fs, ino))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext2: {}", ty, path);
+        self.fs.lock().create(self.ino, path, ty)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext2: {}", path);
+        self.fs.lock().remove(self.ino, path)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.fs.lock().read_dir(self.ino, start_idx, dirents)
+    }
+
+    /// Renames `src_path` to `dst_path` in the same filesystem, where
+    /// `dst_path` is either absolute or relative to this node.
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!(
+            "rename at ext2, src_path: {}, dst_path: {}",
+            src_path, dst_path
+        );
+        self.fs.lock().rename(self.ino, src_path, dst_path)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// An ext2 filesystem on a disk.
+pub struct Ext2FileSystem {
+    root: Arc<Ext2Node>,
+}
+
+impl Ext2FileSystem {
+    /// Mounts the ext2 filesystem on `disk`.
+    pub fn new(disk: Disk) -> Self {
+        let fs = Ext2::open(disk).expect("failed to initialize ext2 filesystem");
+        let fs = Arc::new(Mutex::new(fs));
+        Self {
+            root: Ext2Node::new(&fs, ROOT_INO),
+        }
+    }
+}
+
+impl VfsOps for Ext2FileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl StatFs for Ext2FileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        Ok(self.root.fs.lock().stat())
+    }
+}
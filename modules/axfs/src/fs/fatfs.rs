@@ -7,6 +7,7 @@ use axsync::Mutex;
 use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
 
 use crate::dev::Disk;
+use crate::statfs::{FileSystemStat, StatFs};
 
 const BLOCK_SIZE: usize = 512;
 
@@ -222,6 +223,20 @@ impl VfsOps for FatFileSystem {
     }
 }
 
+impl StatFs for FatFileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        let stats = self.inner.stats().map_err(as_vfs_err)?;
+        Ok(FileSystemStat {
+            block_size: stats.cluster_size() as u64,
+            blocks: stats.total_clusters() as u64,
+            blocks_free: stats.free_clusters() as u64,
+            blocks_avail: stats.free_clusters() as u64,
+            // the number of files is not limited
+            ..Default::default()
+        })
+    }
+}
+
 impl fatfs::IoBase for Disk {
     type Error = ();
 }
@@ -262,7 +277,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        self.sync().map_err(|_| ())
     }
 }
 
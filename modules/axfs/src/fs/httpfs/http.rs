@@ -0,0 +1,271 @@
+//! A minimal HTTP/1.1 client, for the `GET` requests of whole files or of
+//! byte ranges, on a persistent connection.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::SocketAddr;
+use core::time::Duration;
+
+use axerrno::{AxError, ax_err};
+use axfs_vfs::{VfsError, VfsResult};
+use axnet::TcpSocket;
+
+/// The timeout of the connections and of the responses.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// The maximum size of the status line and of the headers.
+const MAX_HEADER_LEN: usize = 8192;
+
+/// A connection to the server, with the bytes received but not parsed yet.
+struct Conn {
+    socket: TcpSocket,
+    buf: Vec<u8>,
+}
+
+impl Conn {
+    fn connect(addr: SocketAddr) -> VfsResult<Self> {
+        let socket = TcpSocket::new();
+        socket.set_connect_timeout(Some(TIMEOUT));
+        socket.set_read_timeout(Some(TIMEOUT));
+        socket.set_write_timeout(Some(TIMEOUT));
+        socket.connect(addr)?;
+        Ok(Self {
+            socket,
+            buf: Vec::new(),
+        })
+    }
+
+    fn send_all(&self, mut buf: &[u8]) -> VfsResult {
+        while !buf.is_empty() {
+            match self.socket.send(buf)? {
+                0 => return Err(AxError::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Receives more bytes into the buffer.
+    fn fill(&mut self) -> VfsResult {
+        let mut chunk = [0; 4096];
+        match self.socket.recv(&mut chunk)? {
+            0 => Err(AxError::UnexpectedEof),
+            n => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads a line ended by CRLF, without the ending.
+    fn read_line(&mut self) -> VfsResult<String> {
+        loop {
+            if let Some(end) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                let line = String::from_utf8(self.buf[..end].to_vec())
+                    .map_err(|_| VfsError::InvalidData)?;
+                self.buf.drain(..end + 2);
+                return Ok(line);
+            }
+            if self.buf.len() > MAX_HEADER_LEN {
+                return ax_err!(InvalidData, "httpfs: header too long");
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Reads exactly `len` bytes, appended to `out`.
+    fn read_exact(&mut self, len: usize, out: &mut Vec<u8>) -> VfsResult {
+        while self.buf.len() < len {
+            self.fill()?;
+        }
+        out.extend(self.buf.drain(..len));
+        Ok(())
+    }
+}
+
+/// The body of a response.
+pub(super) struct Response {
+    /// The status code, 200 or 206.
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// A client of a server, reconnecting when the connection is closed.
+pub(super) struct HttpClient {
+    addr: SocketAddr,
+    /// The value of the `Host` header.
+    host: String,
+    conn: Option<Conn>,
+}
+
+impl HttpClient {
+    pub fn new(addr: SocketAddr, host: String) -> Self {
+        Self {
+            addr,
+            host,
+            conn: None,
+        }
+    }
+
+    /// Gets the resource `path`, or the bytes `start..end` of it if `range`
+    /// is set. The responses other than 200 and 206 are errors, e.g.
+    /// [`VfsError::NotFound`] for 404.
+    ///
+    /// The request is sent again on a new connection if the persistent one
+    /// is closed by the server.
+    pub fn get(&mut self, path: &str, range: Option<(u64, u64)>) -> VfsResult<Response> {
+        let mut req = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAccept-Encoding: identity\r\n",
+            encode_path(path),
+            self.host
+        );
+        if let Some((start, end)) = range {
+            req += &format!("Range: bytes={}-{}\r\n", start, end - 1);
+        }
+        req += "\r\n";
+        let reused = self.conn.is_some();
+        match self.request(req.as_bytes()) {
+            Err(e) if reused => {
+                debug!("httpfs: retrying on a new connection: {:?}", e);
+                self.request(req.as_bytes())
+            }
+            res => res,
+        }
+    }
+
+    fn request(&mut self, req: &[u8]) -> VfsResult<Response> {
+        if self.conn.is_none() {
+            self.conn = Some(Conn::connect(self.addr)?);
+        }
+        let conn = self.conn.as_mut().unwrap();
+        let res = send_and_recv(conn, req);
+        match &res {
+            Ok((_, keep_alive)) if *keep_alive => {}
+            _ => self.conn = None,
+        }
+        let (resp, _) = res?;
+        match resp.status {
+            200 | 206 => Ok(resp),
+            404 | 410 => ax_err!(NotFound),
+            401 | 403 => ax_err!(PermissionDenied),
+            // beyond the end of the file
+            416 => Ok(Response {
+                status: 206,
+                body: Vec::new(),
+            }),
+            status => {
+                warn!("httpfs: unexpected HTTP status {}", status);
+                ax_err!(Io)
+            }
+        }
+    }
+}
+
+/// Sends the request, and receives the response. Returns whether the
+/// connection can be used again.
+fn send_and_recv(conn: &mut Conn, req: &[u8]) -> VfsResult<(Response, bool)> {
+    conn.send_all(req)?;
+    let status_line = conn.read_line()?;
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    let status: u16 = parts
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or(VfsError::InvalidData)?;
+    let mut keep_alive = version == "HTTP/1.1";
+    let mut content_len = None;
+    let mut chunked = false;
+    loop {
+        let line = conn.read_line()?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_len = Some(value.parse::<usize>().map_err(|_| VfsError::InvalidData)?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        } else if name.eq_ignore_ascii_case("connection") {
+            keep_alive = !value.eq_ignore_ascii_case("close");
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let line = conn.read_line()?;
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| VfsError::InvalidData)?;
+            if size == 0 {
+                // skip the trailers
+                while !conn.read_line()?.is_empty() {}
+                break;
+            }
+            conn.read_exact(size, &mut body)?;
+            conn.read_line()?;
+        }
+    } else if let Some(len) = content_len {
+        conn.read_exact(len, &mut body)?;
+    } else {
+        // the body ends with the connection
+        loop {
+            match conn.fill() {
+                Ok(()) => body.append(&mut conn.buf),
+                Err(AxError::UnexpectedEof) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        body.append(&mut conn.buf);
+        keep_alive = false;
+    }
+    Ok((Response { status, body }, keep_alive))
+}
+
+/// Percent-encodes the bytes of `path` other than the unreserved ones and
+/// the slashes.
+fn encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for &b in path.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            _ => out += &format!("%{:02X}", b),
+        }
+    }
+    out
+}
+
+/// Splits an `http://<host>[:<port>][/<prefix>]` URL into the host, the port
+/// and the prefix of the paths, without the trailing slash.
+pub(super) fn parse_url(url: &str) -> VfsResult<(&str, u16, &str)> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        // no TLS
+        return ax_err!(Unsupported, "httpfs: only http:// URLs are supported");
+    };
+    let (authority, prefix) = match rest.find('/') {
+        Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+        None => (rest, ""),
+    };
+    // the IPv6 addresses are in brackets, as they contain colons
+    let port_sep = match authority.rfind(']') {
+        Some(i) => authority[i..].find(':').map(|j| i + j),
+        None => authority.rfind(':'),
+    };
+    let (host, port) = match port_sep {
+        Some(i) => {
+            let port = authority[i + 1..]
+                .parse()
+                .map_err(|_| VfsError::InvalidInput)?;
+            (&authority[..i], port)
+        }
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(VfsError::InvalidInput);
+    }
+    Ok((host, port, prefix))
+}
@@ -0,0 +1,382 @@
+//! A read-only filesystem of the files served by an HTTP server, e.g. the
+//! large assets of an application kept in an object storage, fetched on
+//! demand instead of being built into the image.
+//!
+//! The files are listed by the index `<url>/.index`, with a line of
+//! `<size> <path>` for each file, where the path is relative to the URL, and
+//! the directories are implied by the paths. It can be generated by
+//! `find . -type f -printf '%s %P\n' > .index` in the served directory.
+//!
+//! The files are read by blocks of [`BLOCK_SIZE`], each fetched by an HTTP
+//! range request on a persistent connection when it's first read, and kept
+//! in a cache of [`CACHE_BLOCKS`] blocks shared by the files of the mount,
+//! the least recently used of which is dropped first. Only plain HTTP is
+//! supported, without TLS, and the files are assumed not to be changed on
+//! the server while mounted.
+
+mod http;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::net::{IpAddr, SocketAddr};
+
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use spin::RwLock;
+
+use self::http::HttpClient;
+use crate::statfs::{FileSystemStat, StatFs};
+
+/// The size of the blocks fetched by a request.
+pub const BLOCK_SIZE: usize = 0x10000;
+/// The number of blocks in the cache of a mount, 4 MiB in total.
+pub const CACHE_BLOCKS: usize = 64;
+/// The name of the index of the files.
+const INDEX_NAME: &str = ".index";
+
+/// The blocks cached, by the IDs of the files and the indices of the blocks.
+struct BlockCache {
+    blocks: BTreeMap<(usize, u64), (Arc<[u8]>, u64)>,
+    /// Increased on each access, for the blocks to be stamped with.
+    clock: u64,
+}
+
+impl BlockCache {
+    fn get(&mut self, key: (usize, u64)) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let (data, stamp) = self.blocks.get_mut(&key)?;
+        *stamp = self.clock;
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, key: (usize, u64), data: Arc<[u8]>) {
+        if self.blocks.len() >= CACHE_BLOCKS && !self.blocks.contains_key(&key) {
+            let lru = self.blocks.iter().min_by_key(|(_, (_, stamp))| *stamp);
+            if let Some((&lru, _)) = lru {
+                self.blocks.remove(&lru);
+            }
+        }
+        self.clock += 1;
+        self.blocks.insert(key, (data, self.clock));
+    }
+}
+
+/// The server of a mount.
+struct Remote {
+    client: Mutex<HttpClient>,
+    cache: Mutex<BlockCache>,
+}
+
+impl Remote {
+    /// Reads the block `idx` of `file`, from the cache or from the server.
+    fn block(&self, file: &HttpFile, idx: u64) -> VfsResult<Arc<[u8]>> {
+        if let Some(data) = self.cache.lock().get((file.id, idx)) {
+            return Ok(data);
+        }
+        let start = idx * BLOCK_SIZE as u64;
+        let end = (start + BLOCK_SIZE as u64).min(file.size);
+        let resp = self.client.lock().get(&file.url, Some((start, end)))?;
+        let data: Arc<[u8]> = if resp.status == 206 {
+            resp.body.into()
+        } else {
+            // the server ignored the range, and sent the whole file
+            let body = &resp.body;
+            body[(start as usize).min(body.len())..(end as usize).min(body.len())].into()
+        };
+        self.cache.lock().insert((file.id, idx), data.clone());
+        Ok(data)
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let path = path.trim_start_matches('/');
+    match path.find('/') {
+        Some(n) => (
+            &path[..n],
+            Some(&path[n + 1..]).filter(|rest| !rest.is_empty()),
+        ),
+        None => (path, None),
+    }
+}
+
+/// A file on the server.
+struct HttpFile {
+    remote: Arc<Remote>,
+    /// The ID of the file in the cache.
+    id: usize,
+    /// The path of the file in the URLs.
+    url: String,
+    size: u64,
+}
+
+impl VfsNodeOps for HttpFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            self.size,
+            self.size.div_ceil(512),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let end = (offset + buf.len() as u64).min(self.size);
+        let mut pos = offset;
+        while pos < end {
+            let data = self.remote.block(self, pos / BLOCK_SIZE as u64)?;
+            let start = (pos % BLOCK_SIZE as u64) as usize;
+            let len = data.len().saturating_sub(start).min((end - pos) as usize);
+            if len == 0 {
+                // the file is shorter on the server than in the index
+                break;
+            }
+            let at = (pos - offset) as usize;
+            buf[at..at + len].copy_from_slice(&data[start..start + len]);
+            pos += len as u64;
+        }
+        Ok((pos - offset) as usize)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// An entry of a directory.
+#[derive(Clone)]
+enum Entry {
+    Dir(Arc<HttpDir>),
+    File(Arc<HttpFile>),
+}
+
+impl Entry {
+    fn node(&self) -> VfsNodeRef {
+        match self {
+            Self::Dir(dir) => dir.clone(),
+            Self::File(file) => file.clone(),
+        }
+    }
+}
+
+/// A directory, implied by the paths in the index.
+struct HttpDir {
+    parent: RwLock<Option<Weak<dyn VfsNodeOps>>>,
+    entries: RwLock<BTreeMap<String, Entry>>,
+}
+
+impl HttpDir {
+    fn new(parent: Option<Weak<dyn VfsNodeOps>>) -> Arc<Self> {
+        Arc::new(Self {
+            parent: RwLock::new(parent),
+            entries: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Adds the file at `path` to the tree under this directory, with the
+    /// directories of the path.
+    fn add_file(self: &Arc<Self>, path: &str, make_file: impl FnOnce() -> HttpFile) -> VfsResult {
+        let (name, rest) = split_path(path);
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut entries = self.entries.write();
+        match (entries.get(name).cloned(), rest) {
+            (Some(Entry::Dir(dir)), Some(rest)) => {
+                drop(entries);
+                dir.add_file(rest, make_file)
+            }
+            (Some(_), _) => Err(VfsError::AlreadyExists),
+            (None, Some(rest)) => {
+                let parent: Arc<dyn VfsNodeOps> = self.clone();
+                let dir = HttpDir::new(Some(Arc::downgrade(&parent)));
+                entries.insert(name.into(), Entry::Dir(dir.clone()));
+                drop(entries);
+                dir.add_file(rest, make_file)
+            }
+            (None, None) => {
+                entries.insert(name.into(), Entry::File(Arc::new(make_file())));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl VfsNodeOps for HttpDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o555),
+            VfsNodeType::Dir,
+            4096,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.read().as_ref().and_then(Weak::upgrade)
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node: VfsNodeRef = match name {
+            "" | "." => self.clone(),
+            ".." => self.parent().ok_or(VfsError::NotFound)?,
+            _ => self
+                .entries
+                .read()
+                .get(name)
+                .map(Entry::node)
+                .ok_or(VfsError::NotFound)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries.read();
+        let mut iter = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match iter.next() {
+                    Some((name, Entry::Dir(_))) => *ent = VfsDirEntry::new(name, VfsNodeType::Dir),
+                    Some((name, Entry::File(_))) => {
+                        *ent = VfsDirEntry::new(name, VfsNodeType::File)
+                    }
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+}
+
+/// A mount of the files served under a URL.
+pub struct HttpFileSystem {
+    root: Arc<HttpDir>,
+    /// The number of the files, and the sum of their sizes.
+    files: u64,
+    bytes: u64,
+}
+
+impl VfsOps for HttpFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        *self.root.parent.write() = mount_point.parent().map(|p| Arc::downgrade(&p));
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl StatFs for HttpFileSystem {
+    /// Returns the files in the index, where no space is free.
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        Ok(FileSystemStat {
+            block_size: BLOCK_SIZE as u64,
+            blocks: self.bytes.div_ceil(BLOCK_SIZE as u64),
+            files: self.files,
+            ..Default::default()
+        })
+    }
+}
+
+/// Resolves the host of a URL, which is either an IP address or a domain
+/// name.
+fn resolve_host(host: &str) -> VfsResult<IpAddr> {
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = literal.parse() {
+        return Ok(ip);
+    }
+    axnet::dns_query(host)?
+        .into_iter()
+        .next()
+        .ok_or(VfsError::NotFound)
+}
+
+/// Mounts the files served under `url`, of the form
+/// `http://<host>[:<port>][/<path>]`, as listed by the index there.
+pub(crate) fn new_mount(url: &str) -> VfsResult<Arc<HttpFileSystem>> {
+    let (host, port, prefix) = http::parse_url(url)?;
+    let addr = SocketAddr::new(resolve_host(host)?, port);
+    let host_header = match port {
+        80 => host.into(),
+        _ => format!("{}:{}", host, port),
+    };
+    let mut client = HttpClient::new(addr, host_header);
+    let index = client.get(&format!("{}/{}", prefix, INDEX_NAME), None)?;
+    let index = String::from_utf8(index.body).map_err(|_| VfsError::InvalidData)?;
+
+    let remote = Arc::new(Remote {
+        client: Mutex::new(client),
+        cache: Mutex::new(BlockCache {
+            blocks: BTreeMap::new(),
+            clock: 0,
+        }),
+    });
+    let root = HttpDir::new(None);
+    let (mut count, mut bytes) = (0, 0);
+    for line in index.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = line
+            .split_once(' ')
+            .and_then(|(size, path)| Some((size.parse::<u64>().ok()?, path.trim())));
+        let Some((size, path)) = parsed else {
+            warn!("httpfs: invalid line in the index: {:?}", line);
+            continue;
+        };
+        let names: Vec<&str> = path
+            .split('/')
+            .filter(|s| !s.is_empty() && *s != ".")
+            .collect();
+        let path = names.join("/");
+        let make_file = || HttpFile {
+            remote: remote.clone(),
+            id: count,
+            url: format!("{}/{}", prefix, path),
+            size,
+        };
+        match root.add_file(&path, make_file) {
+            Ok(()) => {
+                count += 1;
+                bytes += size;
+            }
+            Err(e) => warn!("httpfs: cannot add {:?}: {:?}", path, e),
+        }
+    }
+    info!("httpfs: {} files under {}", count, url);
+    Ok(Arc::new(HttpFileSystem {
+        root,
+        files: count as u64,
+        bytes,
+    }))
+}
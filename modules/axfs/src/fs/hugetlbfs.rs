@@ -0,0 +1,448 @@
+//! A filesystem whose files are backed by huge pages, to be mapped with
+//! `mmap` explicitly, like hugetlbfs on Linux.
+//!
+//! Huge pages come from a pool, whose size is set by writing
+//! `/proc/sys/vm/nr_hugepages`. Growing a file by `truncate` reserves pages in
+//! the pool for it, so that mapping the file does not run out of huge pages
+//! later. The pages are allocated on the first mapping, and returned to the
+//! pool when the file is shrunk or removed. Files can be mapped and read, but
+//! not written.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use axalloc::global_allocator;
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use spin::RwLock;
+
+use crate::statfs::{FileSystemStat, StatFs};
+
+/// The size of huge pages.
+pub const HUGE_PAGE_SIZE: usize = 0x20_0000;
+
+struct HugePagePool {
+    /// Addresses of the free pages.
+    free: Vec<usize>,
+    /// The number of pages in the pool, including those in use.
+    total: usize,
+    /// The number of free pages reserved by files.
+    reserved: usize,
+}
+
+impl HugePagePool {
+    fn reserve(&mut self, count: usize) -> VfsResult {
+        if self.free.len() - self.reserved < count {
+            return Err(VfsError::NoMemory);
+        }
+        self.reserved += count;
+        Ok(())
+    }
+
+    fn unreserve(&mut self, count: usize) {
+        self.reserved -= count;
+    }
+
+    /// Takes a reserved page, which is zeroed.
+    fn take_reserved(&mut self) -> usize {
+        self.reserved -= 1;
+        let page = self.free.pop().unwrap();
+        unsafe { core::ptr::write_bytes(page as *mut u8, 0, HUGE_PAGE_SIZE) };
+        page
+    }
+
+    fn put(&mut self, page: usize) {
+        self.free.push(page);
+    }
+
+    /// Grows or shrinks the pool to `count` pages, as far as possible.
+    fn resize(&mut self, count: usize) {
+        const PAGE_SIZE: usize = 0x1000;
+        while self.total < count {
+            match global_allocator().alloc_pages(HUGE_PAGE_SIZE / PAGE_SIZE, HUGE_PAGE_SIZE) {
+                Ok(page) => {
+                    self.free.push(page);
+                    self.total += 1;
+                }
+                Err(_) => {
+                    warn!("hugetlbfs: only {} huge pages are allocated", self.total);
+                    break;
+                }
+            }
+        }
+        // Pages in use or reserved can not be released.
+        while self.total > count && self.free.len() > self.reserved {
+            let page = self.free.pop().unwrap();
+            global_allocator().dealloc_pages(page, HUGE_PAGE_SIZE / PAGE_SIZE);
+            self.total -= 1;
+        }
+    }
+}
+
+static POOL: Mutex<HugePagePool> = Mutex::new(HugePagePool {
+    free: Vec::new(),
+    total: 0,
+    reserved: 0,
+});
+
+/// All existing files, indexed by their addresses, to find the file of a
+/// node in [`hugetlb_file`].
+static FILES: Mutex<BTreeMap<usize, Weak<HugeFile>>> = Mutex::new(BTreeMap::new());
+
+/// Returns the number of huge pages in the pool.
+pub fn nr_hugepages() -> usize {
+    POOL.lock().total
+}
+
+/// Sets the number of huge pages in the pool, returns the new number.
+///
+/// The pool may end up larger than `count` if the pages in use or reserved
+/// are more than that, or smaller if the memory is not enough.
+pub fn set_nr_hugepages(count: usize) -> usize {
+    let mut pool = POOL.lock();
+    pool.resize(count);
+    pool.total
+}
+
+/// Returns the hugetlbfs file of `node`, or `None` if it is not one.
+pub fn hugetlb_file(node: &VfsNodeRef) -> Option<Arc<HugeFile>> {
+    let addr = Arc::as_ptr(node) as *const u8 as usize;
+    FILES.lock().get(&addr).and_then(Weak::upgrade)
+}
+
+struct HugeFileInner {
+    /// The pages of the file, `None` for the reserved ones not allocated yet.
+    pages: Vec<Option<usize>>,
+    /// The number of mapped pages of the file.
+    mapped: usize,
+}
+
+impl HugeFileInner {
+    /// Resizes the file to `count` pages, reserving or releasing pages.
+    ///
+    /// The file can not be shrunk while mapped.
+    fn resize(&mut self, count: usize) -> VfsResult {
+        let mut pool = POOL.lock();
+        if count > self.pages.len() {
+            pool.reserve(count - self.pages.len())?;
+            self.pages.resize(count, None);
+        } else if count < self.pages.len() {
+            if self.mapped > 0 {
+                return Err(VfsError::ResourceBusy);
+            }
+            for page in self.pages.drain(count..) {
+                match page {
+                    Some(page) => pool.put(page),
+                    None => pool.unreserve(1),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A file backed by huge pages.
+pub struct HugeFile {
+    inner: Mutex<HugeFileInner>,
+}
+
+impl HugeFile {
+    fn new() -> Arc<Self> {
+        let file = Arc::new(Self {
+            inner: Mutex::new(HugeFileInner {
+                pages: Vec::new(),
+                mapped: 0,
+            }),
+        });
+        FILES
+            .lock()
+            .insert(Arc::as_ptr(&file) as usize, Arc::downgrade(&file));
+        file
+    }
+
+    /// Gets the pages in `[offset, offset + len)` of the file to map them,
+    /// extending the file if the range is beyond the end.
+    ///
+    /// Returns the virtual addresses of the pages, which are counted as mapped
+    /// until [`HugeFile::unmap_pages`].
+    pub fn map_pages(&self, offset: usize, len: usize) -> VfsResult<Vec<usize>> {
+        if offset % HUGE_PAGE_SIZE != 0 || len % HUGE_PAGE_SIZE != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let (start, end) = (offset / HUGE_PAGE_SIZE, (offset + len) / HUGE_PAGE_SIZE);
+        let mut inner = self.inner.lock();
+        if end > inner.pages.len() {
+            inner.resize(end)?;
+        }
+        let mut pool = POOL.lock();
+        let pages = inner.pages[start..end]
+            .iter_mut()
+            .map(|page| *page.get_or_insert_with(|| pool.take_reserved()))
+            .collect();
+        inner.mapped += end - start;
+        Ok(pages)
+    }
+
+    /// Releases `count` pages got by [`HugeFile::map_pages`].
+    pub fn unmap_pages(&self, count: usize) {
+        self.inner.lock().mapped -= count;
+    }
+}
+
+impl Drop for HugeFile {
+    fn drop(&mut self) {
+        FILES.lock().remove(&(self as *const Self as usize));
+        // Mappings hold references to the file, it is not mapped now.
+        self.inner.get_mut().resize(0).unwrap();
+    }
+}
+
+impl VfsNodeOps for HugeFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = (self.inner.lock().pages.len() * HUGE_PAGE_SIZE) as u64;
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o644),
+            VfsNodeType::File,
+            size,
+            size / 512,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let inner = self.inner.lock();
+        let size = inner.pages.len() * HUGE_PAGE_SIZE;
+        let start = size.min(offset as usize);
+        let end = size.min(start + buf.len());
+        let mut pos = start;
+        while pos < end {
+            let n = (end - pos).min(HUGE_PAGE_SIZE - pos % HUGE_PAGE_SIZE);
+            let dst = &mut buf[pos - start..pos - start + n];
+            match inner.pages[pos / HUGE_PAGE_SIZE] {
+                Some(page) => {
+                    let src = (page + pos % HUGE_PAGE_SIZE) as *const u8;
+                    dst.copy_from_slice(unsafe { core::slice::from_raw_parts(src, n) });
+                }
+                None => dst.fill(0),
+            }
+            pos += n;
+        }
+        Ok(end - start)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidInput)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        if size % HUGE_PAGE_SIZE as u64 != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        self.inner
+            .lock()
+            .resize((size / HUGE_PAGE_SIZE as u64) as usize)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Splits the first component from `path`.
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let path = path.trim_start_matches('/');
+    match path.find('/') {
+        Some(n) => (
+            &path[..n],
+            Some(&path[n + 1..]).filter(|rest| !rest.is_empty()),
+        ),
+        None => (path, None),
+    }
+}
+
+/// The root directory of hugetlbfs. Subdirectories are not supported.
+struct HugeDir {
+    parent: RwLock<Option<Weak<dyn VfsNodeOps>>>,
+    files: RwLock<BTreeMap<String, Arc<HugeFile>>>,
+}
+
+impl VfsNodeOps for HugeDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new_dir(4096, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.read().as_ref().and_then(Weak::upgrade)
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node: VfsNodeRef = match name {
+            "" | "." => self.clone(),
+            ".." => self.parent().ok_or(VfsError::NotFound)?,
+            _ => self
+                .files
+                .read()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            return match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ if self.files.read().contains_key(name) => Err(VfsError::NotADirectory),
+                _ => Err(VfsError::NotFound),
+            };
+        }
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::AlreadyExists);
+        }
+        if ty != VfsNodeType::File {
+            return Err(VfsError::Unsupported);
+        }
+        let mut files = self.files.write();
+        if files.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        files.insert(name.to_string(), HugeFile::new());
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            return match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ if self.files.read().contains_key(name) => Err(VfsError::NotADirectory),
+                _ => Err(VfsError::NotFound),
+            };
+        }
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::InvalidInput);
+        }
+        // The pages are released when the file is no longer opened or mapped.
+        self.files.write().remove(name).ok_or(VfsError::NotFound)?;
+        Ok(())
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let files = self.files.read();
+        let mut names = files.keys().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match names.next() {
+                    Some(name) => *ent = VfsDirEntry::new(name, VfsNodeType::File),
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// A hugetlbfs instance.
+pub struct HugetlbFileSystem {
+    root: Arc<HugeDir>,
+}
+
+impl HugetlbFileSystem {
+    /// Creates a new hugetlbfs instance, sharing the global huge page pool.
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(HugeDir {
+                parent: RwLock::new(None),
+                files: RwLock::new(BTreeMap::new()),
+            }),
+        }
+    }
+}
+
+impl Default for HugetlbFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for HugetlbFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        *self.root.parent.write() = mount_point.parent().map(|p| Arc::downgrade(&p));
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl StatFs for HugetlbFileSystem {
+    /// Returns the huge pages in the pool, where the reserved ones are not
+    /// free.
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        let pool = POOL.lock();
+        let free = (pool.free.len() - pool.reserved) as u64;
+        Ok(FileSystemStat {
+            block_size: HUGE_PAGE_SIZE as u64,
+            blocks: pool.total as u64,
+            blocks_free: free,
+            blocks_avail: free,
+            ..Default::default()
+        })
+    }
+}
+
+/// `/proc/sys/vm/nr_hugepages`, the number of huge pages in the pool.
+#[cfg(feature = "procfs")]
+pub(crate) struct NrHugePagesNode;
+
+#[cfg(feature = "procfs")]
+impl VfsNodeOps for NrHugePagesNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o644),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = alloc::format!("{}\n", nr_hugepages());
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&content[start..end]);
+        Ok(end - start)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let count = core::str::from_utf8(buf)
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .ok_or(VfsError::InvalidInput)?;
+        set_nr_hugepages(count);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // Opened with `O_TRUNC` before written.
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -12,6 +12,7 @@ use lwext4_rust::bindings::{
 use lwext4_rust::{Ext4BlockWrapper, Ext4File, InodeTypes, KernelDevOp};
 
 use crate::dev::Disk;
+use crate::statfs::{FileSystemStat, StatFs};
 pub const BLOCK_SIZE: usize = 512;
 
 #[allow(dead_code)]
@@ -54,6 +55,13 @@ impl VfsOps for Ext4FileSystem {
     }
 }
 
+impl StatFs for Ext4FileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        // not provided by lwext4_rust yet
+        Err(VfsError::Unsupported)
+    }
+}
+
 pub struct FileWrapper(Mutex<Ext4File>);
 
 unsafe impl Send for FileWrapper {}
@@ -381,7 +389,8 @@ impl KernelDevOp for Disk {
         trace!("WRITE rt len={}", write_len);
         Ok(write_len)
     }
-    fn flush(_dev: &mut Self::DevType) -> Result<usize, i32> {
+    fn flush(dev: &mut Self::DevType) -> Result<usize, i32> {
+        dev.sync().map_err(|_| -1)?;
         Ok(0)
     }
     fn seek(dev: &mut Disk, off: i64, whence: i32) -> Result<i64, i32> {
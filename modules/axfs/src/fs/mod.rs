@@ -3,14 +3,37 @@ cfg_if::cfg_if! {
         pub mod myfs;
     } else if #[cfg(feature = "lwext4_rs")] {
         pub mod lwext4_rust;
+    } else if #[cfg(feature = "ext2")] {
+        pub mod ext2;
     } else if #[cfg(feature = "fatfs")] {
         pub mod fatfs;
     }
 
 }
 
-#[cfg(feature = "devfs")]
+#[cfg(any(feature = "devfs", feature = "procfs", feature = "sysfs"))]
 pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
+
+#[cfg(feature = "procfs")]
+pub mod procfs;
+
+#[cfg(feature = "sysfs")]
+pub mod sysfs;
+
+#[cfg(feature = "tmpfs")]
+pub mod tmpfs;
+
+#[cfg(feature = "hugetlbfs")]
+pub mod hugetlbfs;
+
+#[cfg(feature = "ninep")]
+pub mod ninep;
+
+#[cfg(feature = "nfs")]
+pub mod nfs;
+
+#[cfg(feature = "httpfs")]
+pub mod httpfs;
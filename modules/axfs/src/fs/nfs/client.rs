@@ -0,0 +1,395 @@
+//! The procedures of the MOUNT protocol and of NFSv3 (RFC 1813).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use super::NfsTransport;
+use super::rpc::{RpcClient, XdrReader, XdrWriter};
+use crate::statfs::FileSystemStat;
+
+const MOUNT_PROG: u32 = 100005;
+const MOUNT_VERS: u32 = 3;
+const MOUNTPROC_MNT: u32 = 1;
+const MOUNTPROC_UMNT: u32 = 3;
+
+const NFS_PROG: u32 = 100003;
+const NFS_VERS: u32 = 3;
+/// The port of NFS if the portmapper does not know it.
+const NFS_PORT: u16 = 2049;
+
+const NFSPROC_GETATTR: u32 = 1;
+const NFSPROC_SETATTR: u32 = 2;
+const NFSPROC_LOOKUP: u32 = 3;
+const NFSPROC_READLINK: u32 = 5;
+const NFSPROC_READ: u32 = 6;
+const NFSPROC_WRITE: u32 = 7;
+const NFSPROC_CREATE: u32 = 8;
+const NFSPROC_MKDIR: u32 = 9;
+const NFSPROC_REMOVE: u32 = 12;
+const NFSPROC_RMDIR: u32 = 13;
+const NFSPROC_RENAME: u32 = 14;
+const NFSPROC_READDIRPLUS: u32 = 17;
+const NFSPROC_FSSTAT: u32 = 18;
+const NFSPROC_FSINFO: u32 = 19;
+
+/// The maximum size of a file handle.
+const FHSIZE: usize = 64;
+/// The maximum size of a name or a path.
+const MAXPATHLEN: usize = 4096;
+/// The size of `fattr3`.
+const FATTR_SIZE: usize = 84;
+/// The size of `wcc_attr`.
+const WCC_ATTR_SIZE: usize = 24;
+/// `GUARDED` of `createhow3`, failing if the file exists.
+const GUARDED: u32 = 1;
+/// `FILE_SYNC` of `stable_how`, so that no `COMMIT` is needed.
+const FILE_SYNC: u32 = 2;
+/// `SET_TO_SERVER_TIME` of `time_how`.
+const SET_TO_SERVER_TIME: u32 = 1;
+
+/// The maximum size of the data of a `READ` or a `WRITE` over UDP, to fit in
+/// a datagram, as there is no IP fragmentation.
+const UDP_MAX_DATA: u32 = 1024;
+/// The maximum size of the data of a `READ` or a `WRITE` over TCP.
+const TCP_MAX_DATA: u32 = 64 * 1024;
+
+/// A file handle.
+pub(super) type FileHandle = Vec<u8>;
+
+/// Converts an `nfsstat3` or a `mountstat3`.
+fn from_status(status: u32) -> VfsError {
+    match status {
+        1 | 13 | 30 => VfsError::PermissionDenied, // PERM, ACCES, ROFS
+        2 | 70 => VfsError::NotFound,              // NOENT, STALE
+        17 => VfsError::AlreadyExists,
+        18 => VfsError::Unsupported, // XDEV
+        20 => VfsError::NotADirectory,
+        21 => VfsError::IsADirectory,
+        22 | 63 | 10001 => VfsError::InvalidInput, // INVAL, NAMETOOLONG, BADHANDLE
+        27 | 28 | 69 => VfsError::StorageFull,     // FBIG, NOSPC, DQUOT
+        66 => VfsError::DirectoryNotEmpty,
+        10004 | 10007 => VfsError::Unsupported, // NOTSUPP, BADTYPE
+        10008 => VfsError::WouldBlock,          // JUKEBOX
+        _ => VfsError::Io,
+    }
+}
+
+/// Reads the status of a result, returns the error if it's not `NFS3_OK`.
+fn check_status(r: &mut XdrReader) -> VfsResult {
+    match r.u32()? {
+        0 => Ok(()),
+        status => Err(from_status(status)),
+    }
+}
+
+/// The attributes of a file.
+pub(super) struct Attr {
+    pub ty: VfsNodeType,
+    pub mode: u32,
+    pub size: u64,
+    /// The bytes used on the disk.
+    pub used: u64,
+}
+
+fn node_type(ty: u32) -> VfsNodeType {
+    match ty {
+        2 => VfsNodeType::Dir,
+        3 => VfsNodeType::BlockDevice,
+        4 => VfsNodeType::CharDevice,
+        5 => VfsNodeType::SymLink,
+        6 => VfsNodeType::Socket,
+        7 => VfsNodeType::Fifo,
+        _ => VfsNodeType::File,
+    }
+}
+
+fn fattr(r: &mut XdrReader) -> VfsResult<Attr> {
+    let ty = node_type(r.u32()?);
+    let mode = r.u32()?;
+    r.skip(12)?; // nlink, uid, gid
+    let size = r.u64()?;
+    let used = r.u64()?;
+    r.skip(FATTR_SIZE - 36)?; // rdev, fsid, fileid and the times
+    Ok(Attr {
+        ty,
+        mode,
+        size,
+        used,
+    })
+}
+
+fn post_op_attr(r: &mut XdrReader) -> VfsResult<Option<Attr>> {
+    if r.bool()? {
+        fattr(r).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn post_op_fh(r: &mut XdrReader) -> VfsResult<Option<FileHandle>> {
+    if r.bool()? {
+        Ok(Some(r.opaque(FHSIZE)?.to_vec()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// An entry of a directory.
+pub(super) struct DirEntry {
+    pub name: String,
+    pub ty: VfsNodeType,
+    /// The cookie to read the entries after it.
+    pub cookie: u64,
+}
+
+/// The position to read a directory from, by `READDIRPLUS`.
+#[derive(Clone, Copy, Default)]
+pub(super) struct DirCookie {
+    pub cookie: u64,
+    pub verf: [u8; 8],
+}
+
+/// `sattr3` setting the mode only.
+fn sattr_mode(args: XdrWriter, mode: u32) -> XdrWriter {
+    args.bool(true)
+        .u32(mode)
+        .bool(false) // uid
+        .bool(false) // gid
+        .bool(false) // size
+        .u32(0) // atime
+        .u32(0) // mtime
+}
+
+/// A client of an NFSv3 server.
+pub(super) struct NfsClient {
+    nfs: RpcClient,
+    /// The maximum sizes of the data of a `READ` and a `WRITE`.
+    rsize: u32,
+    wsize: u32,
+}
+
+impl NfsClient {
+    /// Mounts `export` on `server`, returns the client and the root handle.
+    pub fn mount(
+        server: IpAddr,
+        export: &str,
+        transport: NfsTransport,
+    ) -> VfsResult<(Self, FileHandle)> {
+        let mut mount = RpcClient::new(server, None, transport, MOUNT_PROG, MOUNT_VERS)?;
+        let res = mount.call(MOUNTPROC_MNT, &XdrWriter::new().str(export).0)?;
+        let mut r = XdrReader(&res);
+        match r.u32()? {
+            0 => {}
+            status => return Err(from_status(status)),
+        }
+        let root = r.opaque(FHSIZE)?.to_vec();
+        // the authentication flavors accepted, AUTH_UNIX is assumed
+
+        let nfs = RpcClient::new(server, None, transport, NFS_PROG, NFS_VERS).or_else(|e| {
+            debug!("nfs: portmapper failed: {:?}, use port {}", e, NFS_PORT);
+            RpcClient::new(server, Some(NFS_PORT), transport, NFS_PROG, NFS_VERS)
+        })?;
+        let max_data = match transport {
+            NfsTransport::Udp => UDP_MAX_DATA,
+            NfsTransport::Tcp => TCP_MAX_DATA,
+        };
+        let mut client = Self {
+            nfs,
+            rsize: max_data,
+            wsize: max_data,
+        };
+        let (rtmax, wtmax) = client.fsinfo(&root)?;
+        client.rsize = client.rsize.min(rtmax).max(1);
+        client.wsize = client.wsize.min(wtmax).max(1);
+        // the server keeps a list of the clients mounted, which is only
+        // informative, so it's cleared at once not to leave stale entries
+        if let Err(e) = mount.call(MOUNTPROC_UMNT, &XdrWriter::new().str(export).0) {
+            debug!("nfs: failed to unmount {:?}: {:?}", export, e);
+        }
+        Ok((client, root))
+    }
+
+    fn call(&mut self, proc: u32, args: XdrWriter) -> VfsResult<Vec<u8>> {
+        self.nfs.call(proc, &args.0)
+    }
+
+    /// Returns the maximum sizes of the data of a `READ` and a `WRITE`.
+    fn fsinfo(&mut self, fh: &[u8]) -> VfsResult<(u32, u32)> {
+        let res = self.call(NFSPROC_FSINFO, XdrWriter::new().opaque(fh))?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        post_op_attr(&mut r)?;
+        let rtmax = r.u32()?;
+        r.skip(8)?; // rtpref, rtmult
+        let wtmax = r.u32()?;
+        Ok((rtmax, wtmax))
+    }
+
+    /// Returns the space and the files of the filesystem of `fh`, where the
+    /// bytes are counted in blocks of the default size.
+    pub fn fsstat(&mut self, fh: &[u8]) -> VfsResult<FileSystemStat> {
+        let res = self.call(NFSPROC_FSSTAT, XdrWriter::new().opaque(fh))?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        post_op_attr(&mut r)?;
+        let (tbytes, fbytes, abytes) = (r.u64()?, r.u64()?, r.u64()?);
+        let (tfiles, ffiles) = (r.u64()?, r.u64()?);
+        let stat = FileSystemStat::default();
+        Ok(FileSystemStat {
+            blocks: tbytes / stat.block_size,
+            blocks_free: fbytes / stat.block_size,
+            blocks_avail: abytes / stat.block_size,
+            files: tfiles,
+            files_free: ffiles,
+            ..stat
+        })
+    }
+
+    pub fn getattr(&mut self, fh: &[u8]) -> VfsResult<Attr> {
+        let res = self.call(NFSPROC_GETATTR, XdrWriter::new().opaque(fh))?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        fattr(&mut r)
+    }
+
+    pub fn set_size(&mut self, fh: &[u8], size: u64) -> VfsResult {
+        let args = XdrWriter::new()
+            .opaque(fh)
+            .bool(false) // mode
+            .bool(false) // uid
+            .bool(false) // gid
+            .bool(true)
+            .u64(size)
+            .u32(0) // atime
+            .u32(SET_TO_SERVER_TIME) // mtime
+            .bool(false); // guard
+        let res = self.call(NFSPROC_SETATTR, args)?;
+        check_status(&mut XdrReader(&res))
+    }
+
+    /// Looks up `name` in the directory `dir`, returns the handle and the
+    /// type of the file.
+    pub fn lookup(&mut self, dir: &[u8], name: &str) -> VfsResult<(FileHandle, VfsNodeType)> {
+        let res = self.call(NFSPROC_LOOKUP, XdrWriter::new().opaque(dir).str(name))?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        let fh = r.opaque(FHSIZE)?.to_vec();
+        let ty = match post_op_attr(&mut r)? {
+            Some(attr) => attr.ty,
+            None => self.getattr(&fh)?.ty,
+        };
+        Ok((fh, ty))
+    }
+
+    pub fn readlink(&mut self, fh: &[u8]) -> VfsResult<String> {
+        let res = self.call(NFSPROC_READLINK, XdrWriter::new().opaque(fh))?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        post_op_attr(&mut r)?;
+        r.str(MAXPATHLEN)
+    }
+
+    /// Reads up to one `READ` of data at `offset`, returns the size read.
+    pub fn read(&mut self, fh: &[u8], offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let count = (buf.len() as u32).min(self.rsize);
+        let args = XdrWriter::new().opaque(fh).u64(offset).u32(count);
+        let res = self.call(NFSPROC_READ, args)?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        post_op_attr(&mut r)?;
+        r.u32()?; // count
+        r.bool()?; // eof
+        let data = r.opaque(count as usize)?;
+        buf[..data.len()].copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    /// Writes up to one `WRITE` of data at `offset`, returns the size
+    /// written.
+    pub fn write(&mut self, fh: &[u8], offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let count = (buf.len() as u32).min(self.wsize);
+        let args = XdrWriter::new()
+            .opaque(fh)
+            .u64(offset)
+            .u32(count)
+            .u32(FILE_SYNC)
+            .opaque(&buf[..count as usize]);
+        let res = self.call(NFSPROC_WRITE, args)?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        // wcc_data
+        if r.bool()? {
+            r.skip(WCC_ATTR_SIZE)?;
+        }
+        post_op_attr(&mut r)?;
+        Ok((r.u32()? as usize).min(count as usize))
+    }
+
+    /// Creates the file `name` in the directory `dir`, failing if it exists.
+    pub fn create(&mut self, dir: &[u8], name: &str, mode: u32) -> VfsResult {
+        let args = XdrWriter::new().opaque(dir).str(name).u32(GUARDED);
+        let res = self.call(NFSPROC_CREATE, sattr_mode(args, mode))?;
+        check_status(&mut XdrReader(&res))
+    }
+
+    pub fn mkdir(&mut self, dir: &[u8], name: &str, mode: u32) -> VfsResult {
+        let args = XdrWriter::new().opaque(dir).str(name);
+        let res = self.call(NFSPROC_MKDIR, sattr_mode(args, mode))?;
+        check_status(&mut XdrReader(&res))
+    }
+
+    /// Removes the file `name` in the directory `dir`, or the empty
+    /// directory if `is_dir` is set.
+    pub fn remove(&mut self, dir: &[u8], name: &str, is_dir: bool) -> VfsResult {
+        let proc = if is_dir {
+            NFSPROC_RMDIR
+        } else {
+            NFSPROC_REMOVE
+        };
+        let res = self.call(proc, XdrWriter::new().opaque(dir).str(name))?;
+        check_status(&mut XdrReader(&res))
+    }
+
+    pub fn rename(&mut self, from: &[u8], from_name: &str, to: &[u8], to_name: &str) -> VfsResult {
+        let args = XdrWriter::new()
+            .opaque(from)
+            .str(from_name)
+            .opaque(to)
+            .str(to_name);
+        let res = self.call(NFSPROC_RENAME, args)?;
+        check_status(&mut XdrReader(&res))
+    }
+
+    /// Reads the entries of the directory `dir` from `pos`, up to one
+    /// `READDIRPLUS`. Returns the entries and whether the end is reached.
+    pub fn readdir(&mut self, dir: &[u8], pos: &mut DirCookie) -> VfsResult<(Vec<DirEntry>, bool)> {
+        let args = XdrWriter::new()
+            .opaque(dir)
+            .u64(pos.cookie)
+            .fixed(&pos.verf)
+            .u32(self.rsize) // dircount
+            .u32(self.rsize); // maxcount
+        let res = self.call(NFSPROC_READDIRPLUS, args)?;
+        let mut r = XdrReader(&res);
+        check_status(&mut r)?;
+        post_op_attr(&mut r)?;
+        pos.verf.copy_from_slice(r.fixed(8)?);
+        let mut entries = Vec::new();
+        while r.bool()? {
+            r.u64()?; // fileid
+            let name = r.str(MAXPATHLEN)?;
+            let cookie = r.u64()?;
+            let ty = post_op_attr(&mut r)?.map_or(VfsNodeType::File, |attr| attr.ty);
+            post_op_fh(&mut r)?;
+            entries.push(DirEntry { name, ty, cookie });
+        }
+        let eof = r.bool()?;
+        if let Some(last) = entries.last() {
+            pos.cookie = last.cookie;
+        }
+        Ok((entries, eof))
+    }
+}
@@ -0,0 +1,365 @@
+//! An NFSv3 client, to mount the directories exported by a server over the
+//! network, e.g. shared by the instances of a cluster.
+//!
+//! The export is mounted by the MOUNT protocol, and its files are accessed by
+//! NFSv3, both over UDP or TCP by our own ONC RPC layer. The ports of them
+//! are asked of the portmapper of the server. The requests come from a
+//! reserved port, and with the `AUTH_UNIX` credentials of root, so the
+//! export may need the `no_root_squash` option to be written.
+//!
+//! A node holds the file handle got by looking up its path from the root of
+//! the mount. Like the 9P client, the attributes are always got from the
+//! server, as the files may be changed by the other clients. The data is
+//! written synchronously, so there is nothing to do to sync a file. Symbolic
+//! links can be read, but not created.
+
+mod client;
+mod rpc;
+
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use spin::RwLock;
+
+use self::client::{DirCookie, FileHandle, NfsClient};
+use crate::statfs::{FileSystemStat, StatFs};
+
+/// The transport of the RPCs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NfsTransport {
+    /// UDP, where a `READ` or a `WRITE` transfers at most 1 KiB to fit in a
+    /// datagram.
+    Udp,
+    /// TCP, which is preferred, and the only one supported by some servers.
+    Tcp,
+}
+
+/// A mount of an export.
+struct Session {
+    client: Mutex<NfsClient>,
+    root: FileHandle,
+    /// The path of the mount point, to resolve the absolute paths of renames.
+    mount_path: RwLock<String>,
+    /// The parent of the mount point, for `..` of the root.
+    parent: RwLock<Option<Weak<dyn VfsNodeOps>>>,
+}
+
+impl Session {
+    /// Looks up `names` from the directory `fh`, returns the handle and the
+    /// type of the file reached.
+    fn walk(&self, fh: &[u8], names: &[&str]) -> VfsResult<(FileHandle, VfsNodeType)> {
+        let mut client = self.client.lock();
+        let mut found = (fh.to_vec(), VfsNodeType::Dir);
+        for name in names {
+            if found.1 != VfsNodeType::Dir {
+                return Err(VfsError::NotADirectory);
+            }
+            found = client.lookup(&found.0, name)?;
+        }
+        Ok(found)
+    }
+
+    /// Looks up the directory of the entry `path` relative to `base`, returns
+    /// the handle of the directory and the name of the entry.
+    fn walk_dir_of<'a>(&self, base: &'a str, path: &'a str) -> VfsResult<(FileHandle, &'a str)> {
+        let Resolved::Inside(names) = resolve(base, path) else {
+            return Err(VfsError::Unsupported);
+        };
+        let Some((name, dir)) = names.split_last() else {
+            return Err(VfsError::InvalidInput);
+        };
+        match self.walk(&self.root, dir)? {
+            (fh, VfsNodeType::Dir) => Ok((fh, name)),
+            _ => Err(VfsError::NotADirectory),
+        }
+    }
+}
+
+/// A path resolved from a node.
+enum Resolved<'a> {
+    /// The names from the root of the mount.
+    Inside(Vec<&'a str>),
+    /// The rest of the path after it leaves the root by `..`, to be resolved
+    /// from the parent of the mount point.
+    Outside(&'a str),
+}
+
+/// Resolves `path` relative to `base`, which is a path from the root of the
+/// mount.
+fn resolve<'a>(base: &'a str, path: &'a str) -> Resolved<'a> {
+    let mut names: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (name, next) = rest.split_once('/').unwrap_or((rest, ""));
+        rest = next;
+        match name {
+            "" | "." => {}
+            ".." => {
+                if names.pop().is_none() {
+                    return Resolved::Outside(rest);
+                }
+            }
+            _ => names.push(name),
+        }
+    }
+    Resolved::Inside(names)
+}
+
+/// A file or a directory in an export.
+struct NfsNode {
+    session: Arc<Session>,
+    /// The path from the root of the mount, empty for the root.
+    path: String,
+    fh: FileHandle,
+    ty: VfsNodeType,
+    /// The index and the cookie of the next entry, to continue reading the
+    /// directory without reading the entries before again.
+    dir_pos: Mutex<(usize, DirCookie)>,
+}
+
+impl NfsNode {
+    fn new(session: &Arc<Session>, names: &[&str], fh: FileHandle, ty: VfsNodeType) -> Arc<Self> {
+        Arc::new(Self {
+            session: session.clone(),
+            path: names.join("/"),
+            fh,
+            ty,
+            dir_pos: Mutex::new((0, DirCookie::default())),
+        })
+    }
+
+    fn mount_parent(&self) -> VfsResult<VfsNodeRef> {
+        let parent = self.session.parent.read();
+        parent
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .ok_or(VfsError::NotFound)
+    }
+
+    fn resolve<'a>(&'a self, path: &'a str) -> Resolved<'a> {
+        resolve(&self.path, path)
+    }
+
+    /// Reads the target of the symlink, and caches nothing, as it may be
+    /// replaced by another client.
+    fn read_link(&self) -> VfsResult<String> {
+        self.session.client.lock().readlink(&self.fh)
+    }
+}
+
+impl VfsNodeOps for NfsNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = self.session.client.lock().getattr(&self.fh)?;
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(attr.mode as u16 & 0o777),
+            attr.ty,
+            attr.size,
+            attr.used.div_ceil(512),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.ty == VfsNodeType::SymLink {
+            let target = self.read_link()?;
+            let data = target.as_bytes().get(offset as usize..).unwrap_or(&[]);
+            let len = data.len().min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            return Ok(len);
+        }
+        let mut client = self.session.client.lock();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let len = client.read(&self.fh, offset + pos as u64, &mut buf[pos..])?;
+            if len == 0 {
+                break;
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if self.ty == VfsNodeType::SymLink {
+            return Err(VfsError::Unsupported);
+        }
+        let mut client = self.session.client.lock();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let len = client.write(&self.fh, offset + pos as u64, &buf[pos..])?;
+            if len == 0 {
+                break;
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.session.client.lock().set_size(&self.fh, size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.path.is_empty() {
+            return self.mount_parent().ok();
+        }
+        let parent = self.path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        let names: Vec<&str> = parent.split('/').filter(|s| !s.is_empty()).collect();
+        let (fh, ty) = self.session.walk(&self.session.root, &names).ok()?;
+        Some(NfsNode::new(&self.session, &names, fh, ty))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        match self.resolve(path) {
+            Resolved::Inside(names) => {
+                // look up from this node if the path stays under it
+                let depth = self.path.split('/').filter(|s| !s.is_empty()).count();
+                let below = names.len() >= depth && names[..depth].join("/") == self.path;
+                let (fh, ty) = if below {
+                    self.session.walk(&self.fh, &names[depth..])?
+                } else {
+                    self.session.walk(&self.session.root, &names)?
+                };
+                Ok(NfsNode::new(&self.session, &names, fh, ty))
+            }
+            Resolved::Outside("") => self.mount_parent(),
+            Resolved::Outside(rest) => self.mount_parent()?.lookup(rest),
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        if let Resolved::Outside(rest) = self.resolve(path) {
+            return self.mount_parent()?.create(rest, ty);
+        }
+        let (dir, name) = self
+            .session
+            .walk_dir_of(&self.path, path)
+            .map_err(|e| match e {
+                VfsError::InvalidInput => VfsError::AlreadyExists,
+                e => e,
+            })?;
+        let mut client = self.session.client.lock();
+        match ty {
+            VfsNodeType::Dir => client.mkdir(&dir, name, 0o755),
+            VfsNodeType::File => client.create(&dir, name, 0o644),
+            _ => Err(VfsError::Unsupported),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        if let Resolved::Outside(rest) = self.resolve(path) {
+            return self.mount_parent()?.remove(rest);
+        }
+        let (dir, name) = self.session.walk_dir_of(&self.path, path)?;
+        let mut client = self.session.client.lock();
+        let (_, ty) = client.lookup(&dir, name)?;
+        client.remove(&dir, name, ty == VfsNodeType::Dir)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut pos = self.dir_pos.lock();
+        if pos.0 > start_idx {
+            *pos = (0, DirCookie::default());
+        }
+        let mut client = self.session.client.lock();
+        let mut count = 0;
+        'read: while count < dirents.len() {
+            let mut cookie = pos.1;
+            let (entries, eof) = client.readdir(&self.fh, &mut cookie)?;
+            let done = eof || entries.is_empty();
+            for entry in entries {
+                if pos.0 >= start_idx {
+                    if count == dirents.len() {
+                        break 'read;
+                    }
+                    dirents[count] = VfsDirEntry::new(&entry.name, entry.ty);
+                    count += 1;
+                }
+                pos.0 += 1;
+                pos.1 = DirCookie {
+                    cookie: entry.cookie,
+                    verf: cookie.verf,
+                };
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Renames `src_path` to `dst_path` in the same export, where `dst_path`
+    /// is either absolute or relative to this node.
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let session = &self.session;
+        let (dst_base, dst_path) = if dst_path.starts_with('/') {
+            let mount_path = session.mount_path.read();
+            let rest = dst_path
+                .strip_prefix(mount_path.as_str())
+                .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+                .ok_or(VfsError::Unsupported)?;
+            ("", rest)
+        } else {
+            (self.path.as_str(), dst_path)
+        };
+        let (src_dir, src_name) = session.walk_dir_of(&self.path, src_path)?;
+        let (dst_dir, dst_name) = session.walk_dir_of(dst_base, dst_path)?;
+        session
+            .client
+            .lock()
+            .rename(&src_dir, src_name, &dst_dir, dst_name)
+    }
+}
+
+/// A mount of a directory exported by an NFS server.
+pub struct NfsFileSystem {
+    root: Arc<NfsNode>,
+}
+
+impl VfsOps for NfsFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        let session = &self.root.session;
+        *session.mount_path.write() = path.trim_end_matches('/').into();
+        *session.parent.write() = mount_point.parent().map(|p| Arc::downgrade(&p));
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl StatFs for NfsFileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        let session = &self.root.session;
+        session.client.lock().fsstat(&session.root)
+    }
+}
+
+/// Mounts `export` on the NFS server `server` by `transport`.
+pub(crate) fn new_mount(
+    server: IpAddr,
+    export: &str,
+    transport: NfsTransport,
+) -> VfsResult<Arc<NfsFileSystem>> {
+    let (client, root) = NfsClient::mount(server, export, transport)?;
+    let session = Arc::new(Session {
+        client: Mutex::new(client),
+        root: root.clone(),
+        mount_path: RwLock::new(String::new()),
+        parent: RwLock::new(None),
+    });
+    Ok(Arc::new(NfsFileSystem {
+        root: NfsNode::new(&session, &[], root, VfsNodeType::Dir),
+    }))
+}
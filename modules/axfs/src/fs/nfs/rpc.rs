@@ -0,0 +1,382 @@
+//! ONC RPC (RFC 5531) over UDP or TCP, with the XDR encoding (RFC 4506) of
+//! the arguments and the results.
+//!
+//! The calls of a client are serialized. A call over UDP is retransmitted
+//! with the same XID until it's answered, with a growing timeout. A call
+//! over TCP is made again on a new connection if the connection breaks.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::time::Duration;
+
+use axerrno::{AxError, ax_err};
+use axfs_vfs::{VfsError, VfsResult};
+use axnet::{TcpSocket, UdpSocket};
+
+use super::NfsTransport;
+
+/// The portmapper, to find the ports of the programs.
+const PMAP_PROG: u32 = 100000;
+const PMAP_VERS: u32 = 2;
+const PMAP_PORT: u16 = 111;
+const PMAPPROC_GETPORT: u32 = 3;
+const IPPROTO_TCP: u32 = 6;
+const IPPROTO_UDP: u32 = 17;
+
+const RPC_VERSION: u32 = 2;
+const MSG_CALL: u32 = 0;
+const MSG_REPLY: u32 = 1;
+const MSG_ACCEPTED: u32 = 0;
+const AUTH_NONE: u32 = 0;
+const AUTH_UNIX: u32 = 1;
+
+const SUCCESS: u32 = 0;
+const PROG_UNAVAIL: u32 = 1;
+const PROG_MISMATCH: u32 = 2;
+const PROC_UNAVAIL: u32 = 3;
+
+/// The machine name in the credentials.
+const MACHINE_NAME: &str = "arceos";
+/// The last fragment bit of the record marks over TCP.
+const LAST_FRAGMENT: u32 = 1 << 31;
+/// The maximum size of a reply.
+const MAX_REPLY: usize = 1 << 20;
+/// The lowest reserved port to bind, as the servers only accept the
+/// requests from the reserved ports by default.
+const MIN_RESERVED_PORT: u16 = 600;
+
+/// The timeout of the first transmission of a call over UDP, doubled on
+/// each retransmission.
+const UDP_TIMEOUT: Duration = Duration::from_secs(1);
+/// How many times a call over UDP is retransmitted before it fails.
+const UDP_RETRIES: u32 = 5;
+/// The timeout of the calls over TCP.
+const TCP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An encoder of the arguments of a call.
+pub(super) struct XdrWriter(pub Vec<u8>);
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn u32(mut self, v: u32) -> Self {
+        self.0.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn u64(mut self, v: u64) -> Self {
+        self.0.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn bool(self, v: bool) -> Self {
+        self.u32(v as u32)
+    }
+
+    /// Appends fixed-length opaque data, padded to 4 bytes.
+    pub fn fixed(mut self, data: &[u8]) -> Self {
+        self.0.extend_from_slice(data);
+        self.0.resize(self.0.len().next_multiple_of(4), 0);
+        self
+    }
+
+    /// Appends variable-length opaque data, prefixed by its length.
+    pub fn opaque(self, data: &[u8]) -> Self {
+        self.u32(data.len() as u32).fixed(data)
+    }
+
+    pub fn str(self, s: &str) -> Self {
+        self.opaque(s.as_bytes())
+    }
+}
+
+/// A decoder of the results of a call.
+pub(super) struct XdrReader<'a>(pub &'a [u8]);
+
+impl<'a> XdrReader<'a> {
+    fn take(&mut self, len: usize) -> VfsResult<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(VfsError::InvalidData);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    pub fn u32(&mut self) -> VfsResult<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> VfsResult<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bool(&mut self) -> VfsResult<bool> {
+        Ok(self.u32()? != 0)
+    }
+
+    /// Takes fixed-length opaque data, skipping the padding.
+    pub fn fixed(&mut self, len: usize) -> VfsResult<&'a [u8]> {
+        let data = self.take(len)?;
+        self.take(len.next_multiple_of(4) - len)?;
+        Ok(data)
+    }
+
+    /// Takes variable-length opaque data of at most `max` bytes.
+    pub fn opaque(&mut self, max: usize) -> VfsResult<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if len > max {
+            return Err(VfsError::InvalidData);
+        }
+        self.fixed(len)
+    }
+
+    pub fn str(&mut self, max: usize) -> VfsResult<String> {
+        let s = core::str::from_utf8(self.opaque(max)?).map_err(|_| VfsError::InvalidData)?;
+        Ok(s.into())
+    }
+
+    /// Skips `len` bytes, e.g. of the fields not used.
+    pub fn skip(&mut self, len: usize) -> VfsResult {
+        self.take(len).map(|_| ())
+    }
+}
+
+/// Binds a socket to a reserved port by `bind`, trying the ports from the
+/// highest one.
+fn bind_reserved<T>(bind: impl Fn(SocketAddr) -> VfsResult<T>) -> VfsResult<T> {
+    for port in (MIN_RESERVED_PORT..1024).rev() {
+        match bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)) {
+            Err(AxError::AddrInUse) => continue,
+            res => return res,
+        }
+    }
+    ax_err!(AddrInUse, "nfs: no reserved port available")
+}
+
+enum Socket {
+    Udp(UdpSocket),
+    Tcp(TcpSocket),
+}
+
+impl Socket {
+    fn connect(transport: NfsTransport, server: SocketAddr) -> VfsResult<Self> {
+        match transport {
+            NfsTransport::Udp => bind_reserved(|addr| {
+                let socket = UdpSocket::new();
+                socket.bind(addr)?;
+                Ok(Self::Udp(socket))
+            }),
+            NfsTransport::Tcp => {
+                let socket = bind_reserved(|addr| {
+                    // a socket failed to bind is not usable any more
+                    let socket = TcpSocket::new();
+                    socket.bind(addr)?;
+                    Ok(socket)
+                })?;
+                socket.set_connect_timeout(Some(TCP_TIMEOUT));
+                socket.set_read_timeout(Some(TCP_TIMEOUT));
+                socket.set_write_timeout(Some(TCP_TIMEOUT));
+                socket.connect(server)?;
+                Ok(Self::Tcp(socket))
+            }
+        }
+    }
+}
+
+fn send_all(socket: &TcpSocket, mut buf: &[u8]) -> VfsResult {
+    while !buf.is_empty() {
+        match socket.send(buf)? {
+            0 => return Err(AxError::WriteZero),
+            n => buf = &buf[n..],
+        }
+    }
+    Ok(())
+}
+
+fn recv_exact(socket: &TcpSocket, mut buf: &mut [u8]) -> VfsResult {
+    while !buf.is_empty() {
+        match socket.recv(buf)? {
+            0 => return Err(AxError::UnexpectedEof),
+            n => buf = &mut buf[n..],
+        }
+    }
+    Ok(())
+}
+
+/// A client of a program on a server.
+pub(super) struct RpcClient {
+    server: SocketAddr,
+    transport: NfsTransport,
+    prog: u32,
+    vers: u32,
+    socket: Option<Socket>,
+    next_xid: u32,
+    /// The buffer of the datagrams received.
+    buf: Vec<u8>,
+}
+
+impl RpcClient {
+    /// Creates a client of the version `vers` of the program `prog` on
+    /// `server`, whose port is asked of the portmapper if `port` is `None`.
+    pub fn new(
+        server: IpAddr,
+        port: Option<u16>,
+        transport: NfsTransport,
+        prog: u32,
+        vers: u32,
+    ) -> VfsResult<Self> {
+        let port = match port {
+            Some(port) => port,
+            None => {
+                let mut pmap = Self::new(server, Some(PMAP_PORT), transport, PMAP_PROG, PMAP_VERS)?;
+                let proto = match transport {
+                    NfsTransport::Udp => IPPROTO_UDP,
+                    NfsTransport::Tcp => IPPROTO_TCP,
+                };
+                let args = XdrWriter::new().u32(prog).u32(vers).u32(proto).u32(0);
+                let res = pmap.call(PMAPPROC_GETPORT, &args.0)?;
+                match XdrReader(&res).u32()? {
+                    0 => return ax_err!(NotFound, "nfs: program not registered"),
+                    port => port as u16,
+                }
+            }
+        };
+        Ok(Self {
+            server: SocketAddr::new(server, port),
+            transport,
+            prog,
+            vers,
+            socket: None,
+            // not to reuse the XIDs of the calls before a restart, which may
+            // be cached by the server
+            next_xid: axhal::time::wall_time_nanos() as u32,
+            buf: vec![0; 64 * 1024],
+        })
+    }
+
+    /// Calls the procedure `proc` with the encoded `args`, returns the
+    /// encoded results.
+    pub fn call(&mut self, proc: u32, args: &[u8]) -> VfsResult<Vec<u8>> {
+        let xid = self.next_xid;
+        self.next_xid = self.next_xid.wrapping_add(1);
+        let mut msg = XdrWriter::new()
+            .u32(xid)
+            .u32(MSG_CALL)
+            .u32(RPC_VERSION)
+            .u32(self.prog)
+            .u32(self.vers)
+            .u32(proc);
+        // AUTH_UNIX credentials of root, with no auxiliary groups
+        let cred = XdrWriter::new()
+            .u32(0) // stamp
+            .str(MACHINE_NAME)
+            .u32(0) // uid
+            .u32(0) // gid
+            .u32(0);
+        msg = msg.u32(AUTH_UNIX).opaque(&cred.0).u32(AUTH_NONE).u32(0);
+        msg.0.extend_from_slice(args);
+
+        let reply = match self.transport {
+            NfsTransport::Udp => self.call_udp(xid, &msg.0)?,
+            NfsTransport::Tcp => match self.call_tcp(xid, &msg.0) {
+                Ok(reply) => reply,
+                Err(e) => {
+                    debug!("nfs: retrying RPC {} on a new connection: {:?}", xid, e);
+                    self.socket = None;
+                    self.call_tcp(xid, &msg.0)
+                        .inspect_err(|_| self.socket = None)?
+                }
+            },
+        };
+        let mut r = XdrReader(&reply);
+        r.skip(8)?; // xid, message type
+        if r.u32()? != MSG_ACCEPTED {
+            // RPC_MISMATCH or AUTH_ERROR
+            return ax_err!(PermissionDenied, "nfs: RPC denied");
+        }
+        r.u32()?; // the flavor of the verifier
+        r.opaque(400)?;
+        match r.u32()? {
+            SUCCESS => Ok(r.0.to_vec()),
+            PROG_UNAVAIL | PROG_MISMATCH | PROC_UNAVAIL => {
+                ax_err!(Unsupported, "nfs: RPC program unavailable")
+            }
+            _ => ax_err!(Io, "nfs: RPC failed"),
+        }
+    }
+
+    fn call_udp(&mut self, xid: u32, msg: &[u8]) -> VfsResult<Vec<u8>> {
+        if self.socket.is_none() {
+            self.socket = Some(Socket::connect(self.transport, self.server)?);
+        }
+        let Some(Socket::Udp(socket)) = &self.socket else {
+            unreachable!()
+        };
+        let mut timeout = UDP_TIMEOUT;
+        for _ in 0..=UDP_RETRIES {
+            socket.send_to(msg, self.server)?;
+            socket.set_read_timeout(Some(timeout));
+            loop {
+                let len = match socket.recv_from(&mut self.buf) {
+                    Ok((len, from)) if from == self.server => len,
+                    Ok(_) => continue,
+                    Err(AxError::WouldBlock) => break,
+                    Err(e) => return Err(e),
+                };
+                // skip the replies to the calls retransmitted before
+                let reply = &self.buf[..len];
+                if reply.len() >= 8 && reply_header(reply) == (xid, MSG_REPLY) {
+                    return Ok(reply.to_vec());
+                }
+            }
+            timeout *= 2;
+        }
+        ax_err!(Io, "nfs: server not responding")
+    }
+
+    fn call_tcp(&mut self, xid: u32, msg: &[u8]) -> VfsResult<Vec<u8>> {
+        if self.socket.is_none() {
+            self.socket = Some(Socket::connect(self.transport, self.server)?);
+        }
+        let Some(Socket::Tcp(socket)) = &self.socket else {
+            unreachable!()
+        };
+        let mark = LAST_FRAGMENT | msg.len() as u32;
+        send_all(socket, &mark.to_be_bytes())?;
+        send_all(socket, msg)?;
+        loop {
+            let mut reply = Vec::new();
+            loop {
+                let mut mark = [0; 4];
+                recv_exact(socket, &mut mark)?;
+                let mark = u32::from_be_bytes(mark);
+                let len = (mark & !LAST_FRAGMENT) as usize;
+                if reply.len() + len > MAX_REPLY {
+                    return ax_err!(InvalidData, "nfs: RPC reply too large");
+                }
+                let start = reply.len();
+                reply.resize(start + len, 0);
+                recv_exact(socket, &mut reply[start..])?;
+                if mark & LAST_FRAGMENT != 0 {
+                    break;
+                }
+            }
+            // skip the stale replies, e.g. of a call timed out before
+            if reply.len() >= 8 && reply_header(&reply) == (xid, MSG_REPLY) {
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+/// Returns the XID and the message type of a message.
+fn reply_header(msg: &[u8]) -> (u32, u32) {
+    let xid = u32::from_be_bytes(msg[0..4].try_into().unwrap());
+    let ty = u32::from_be_bytes(msg[4..8].try_into().unwrap());
+    (xid, ty)
+}
@@ -0,0 +1,822 @@
+//! A 9P2000.L client, to mount the folders shared by the host, e.g. by the
+//! `-virtfs` option of QEMU.
+//!
+//! Each 9P transport exports a folder identified by its mount tag. A mount of
+//! the folder attaches a new session to the transport, so that a folder can be
+//! mounted at several paths. A node holds a fid walked from the root of the
+//! session, and another fid opened on its first read or write. The nodes are
+//! looked up by walking from the root each time, and the attributes are
+//! always got from the host, as the folder may be changed by the host.
+
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axdriver::prelude::*;
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use spin::RwLock;
+
+use crate::statfs::{FileSystemStat, StatFs};
+
+const RLERROR: u8 = 7;
+const TSTATFS: u8 = 8;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+/// The version of the protocol.
+const VERSION: &str = "9P2000.L";
+/// The tag of `Tversion`, the other requests use tag 0 as they are serialized.
+const NOTAG: u16 = !0;
+/// No fid, for the authentication fid of `Tattach`.
+const NOFID: u32 = !0;
+/// The maximum number of names walked by one `Twalk`.
+const MAX_WALK_NAMES: usize = 16;
+/// The size of the header of `Rread` and `Rreaddir`.
+const READ_HEADER_SIZE: usize = 11;
+/// The size of the header of `Twrite`.
+const WRITE_HEADER_SIZE: usize = 23;
+
+/// The basic fields requested by `Tgetattr`.
+const GETATTR_BASIC: u64 = 0x7ff;
+/// The field of the size set by `Tsetattr`.
+const SETATTR_SIZE: u32 = 0x8;
+/// The flag of `Tunlinkat` to remove a directory.
+const AT_REMOVEDIR: u32 = 0x200;
+const O_RDONLY: u32 = 0;
+const O_RDWR: u32 = 2;
+
+/// Converts a Linux error number returned by the host.
+fn from_errno(errno: u32) -> VfsError {
+    match errno {
+        1 | 13 | 30 => VfsError::PermissionDenied, // EPERM, EACCES, EROFS
+        2 => VfsError::NotFound,
+        11 => VfsError::WouldBlock,
+        12 => VfsError::NoMemory,
+        16 => VfsError::ResourceBusy,
+        17 => VfsError::AlreadyExists,
+        20 => VfsError::NotADirectory,
+        21 => VfsError::IsADirectory,
+        22 | 36 => VfsError::InvalidInput, // EINVAL, ENAMETOOLONG
+        28 => VfsError::StorageFull,
+        38 | 95 => VfsError::Unsupported, // ENOSYS, EOPNOTSUPP
+        39 => VfsError::DirectoryNotEmpty,
+        _ => VfsError::Io,
+    }
+}
+
+/// Converts the file type in a mode.
+fn node_type(mode: u32) -> VfsNodeType {
+    match mode & 0o170000 {
+        0o010000 => VfsNodeType::Fifo,
+        0o020000 => VfsNodeType::CharDevice,
+        0o040000 => VfsNodeType::Dir,
+        0o060000 => VfsNodeType::BlockDevice,
+        0o120000 => VfsNodeType::SymLink,
+        0o140000 => VfsNodeType::Socket,
+        _ => VfsNodeType::File,
+    }
+}
+
+/// Converts the file type of a directory entry.
+fn dirent_type(ty: u8) -> VfsNodeType {
+    match ty {
+        1 => VfsNodeType::Fifo,
+        2 => VfsNodeType::CharDevice,
+        4 => VfsNodeType::Dir,
+        6 => VfsNodeType::BlockDevice,
+        10 => VfsNodeType::SymLink,
+        12 => VfsNodeType::Socket,
+        _ => VfsNodeType::File,
+    }
+}
+
+/// A request message being built.
+struct Msg(Vec<u8>);
+
+impl Msg {
+    fn new(ty: u8) -> Self {
+        let tag = if ty == TVERSION { NOTAG } else { 0 };
+        let mut buf = vec![0; 4]; // the size, filled when sent
+        buf.push(ty);
+        buf.extend_from_slice(&tag.to_le_bytes());
+        Self(buf)
+    }
+
+    fn u16(mut self, v: u16) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, v: u32) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, v: u64) -> Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn str(self, s: &str) -> Self {
+        let mut msg = self.u16(s.len() as u16);
+        msg.0.extend_from_slice(s.as_bytes());
+        msg
+    }
+
+    fn bytes(mut self, data: &[u8]) -> Self {
+        self.0.extend_from_slice(data);
+        self
+    }
+}
+
+/// A reader of the body of a response message.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> VfsResult<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(VfsError::InvalidData);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> VfsResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> VfsResult<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> VfsResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> VfsResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> VfsResult<&'a str> {
+        let len = self.u16()? as usize;
+        core::str::from_utf8(self.take(len)?).map_err(|_| VfsError::InvalidData)
+    }
+
+    /// Skips a qid, which is not used as the nodes are identified by paths.
+    fn qid(&mut self) -> VfsResult {
+        self.take(13).map(|_| ())
+    }
+}
+
+struct DirEntry {
+    name: String,
+    ty: VfsNodeType,
+    /// The offset of the next entry.
+    next: u64,
+}
+
+struct Attr {
+    mode: u32,
+    size: u64,
+    blocks: u64,
+}
+
+/// A connection to the host through a 9P transport, whose requests are
+/// serialized.
+struct Client {
+    dev: AxNinePDevice,
+    msize: usize,
+    resp: Vec<u8>,
+    next_fid: u32,
+    free_fids: Vec<u32>,
+}
+
+impl Client {
+    /// Negotiates the version and the maximum message size with the host.
+    fn new(dev: AxNinePDevice) -> VfsResult<Self> {
+        let msize = dev.max_message_size();
+        let mut client = Self {
+            dev,
+            msize,
+            resp: vec![0; msize],
+            next_fid: 0,
+            free_fids: Vec::new(),
+        };
+        let msg = Msg::new(TVERSION).u32(msize as u32).str(VERSION);
+        let mut r = client.rpc(msg)?;
+        let msize = r.u32()? as usize;
+        if r.str()? != VERSION {
+            return Err(VfsError::Unsupported);
+        }
+        client.msize = client.msize.min(msize);
+        Ok(client)
+    }
+
+    /// Sends the request `msg`, returns the body of the response.
+    fn rpc(&mut self, msg: Msg) -> VfsResult<Reader<'_>> {
+        let mut req = msg.0;
+        if req.len() > self.msize {
+            return Err(VfsError::InvalidInput);
+        }
+        let size = req.len() as u32;
+        req[..4].copy_from_slice(&size.to_le_bytes());
+        let ty = req[4];
+        let len = self
+            .dev
+            .request(&req, &mut self.resp)
+            .map_err(|_| VfsError::Io)?;
+        let mut r = Reader(&self.resp[..len]);
+        let size = r.u32()? as usize;
+        let resp_ty = r.u8()?;
+        r.u16()?; // the tag
+        if size < 7 || size > len {
+            return Err(VfsError::InvalidData);
+        }
+        let mut body = Reader(&self.resp[7..size]);
+        if resp_ty == RLERROR {
+            Err(from_errno(body.u32()?))
+        } else if resp_ty != ty + 1 {
+            Err(VfsError::InvalidData)
+        } else {
+            Ok(body)
+        }
+    }
+
+    fn alloc_fid(&mut self) -> u32 {
+        self.free_fids.pop().unwrap_or_else(|| {
+            self.next_fid += 1;
+            self.next_fid - 1
+        })
+    }
+
+    /// Releases `fid`, which is freed even if the request fails.
+    fn clunk(&mut self, fid: u32) {
+        if let Err(e) = self.rpc(Msg::new(TCLUNK).u32(fid)) {
+            warn!("9p: failed to clunk fid {}: {:?}", fid, e);
+        }
+        self.free_fids.push(fid);
+    }
+
+    /// Attaches a new session, returns the fid of the root.
+    fn attach(&mut self) -> VfsResult<u32> {
+        let fid = self.alloc_fid();
+        let msg = Msg::new(TATTACH)
+            .u32(fid)
+            .u32(NOFID)
+            .str("root")
+            .str("")
+            .u32(0);
+        match self.rpc(msg).and_then(|mut r| r.qid()) {
+            Ok(()) => Ok(fid),
+            Err(e) => {
+                self.free_fids.push(fid);
+                Err(e)
+            }
+        }
+    }
+
+    /// Walks from `fid` by `names`, returns a new fid of the file reached.
+    fn walk(&mut self, fid: u32, names: &[&str]) -> VfsResult<u32> {
+        let new_fid = self.alloc_fid();
+        let mut from = fid;
+        let mut walked = 0;
+        loop {
+            let chunk = &names[walked..names.len().min(walked + MAX_WALK_NAMES)];
+            let msg = Msg::new(TWALK)
+                .u32(from)
+                .u32(new_fid)
+                .u16(chunk.len() as u16);
+            let msg = chunk.iter().fold(msg, |msg, name| msg.str(name));
+            let res = self.rpc(msg).and_then(|mut r| match r.u16()? as usize {
+                n if n < chunk.len() => Err(VfsError::NotFound),
+                _ => Ok(()),
+            });
+            if let Err(e) = res {
+                // the new fid is created by the first walk only if it succeeds
+                if from == new_fid {
+                    self.clunk(new_fid);
+                } else {
+                    self.free_fids.push(new_fid);
+                }
+                return Err(e);
+            }
+            from = new_fid;
+            walked += chunk.len();
+            if walked == names.len() {
+                return Ok(new_fid);
+            }
+        }
+    }
+
+    fn lopen(&mut self, fid: u32, flags: u32) -> VfsResult {
+        self.rpc(Msg::new(TLOPEN).u32(fid).u32(flags)).map(|_| ())
+    }
+
+    /// Creates the file `name` in the directory of `fid`, which is then
+    /// opened on the new file.
+    fn lcreate(&mut self, fid: u32, name: &str, mode: u32) -> VfsResult {
+        let msg = Msg::new(TLCREATE)
+            .u32(fid)
+            .str(name)
+            .u32(O_RDONLY)
+            .u32(mode)
+            .u32(0);
+        self.rpc(msg).map(|_| ())
+    }
+
+    fn mkdir(&mut self, fid: u32, name: &str, mode: u32) -> VfsResult {
+        let msg = Msg::new(TMKDIR).u32(fid).str(name).u32(mode).u32(0);
+        self.rpc(msg).map(|_| ())
+    }
+
+    fn unlinkat(&mut self, fid: u32, name: &str, flags: u32) -> VfsResult {
+        let msg = Msg::new(TUNLINKAT).u32(fid).str(name).u32(flags);
+        self.rpc(msg).map(|_| ())
+    }
+
+    fn renameat(&mut self, old_fid: u32, old: &str, new_fid: u32, new: &str) -> VfsResult {
+        let msg = Msg::new(TRENAMEAT)
+            .u32(old_fid)
+            .str(old)
+            .u32(new_fid)
+            .str(new);
+        self.rpc(msg).map(|_| ())
+    }
+
+    fn getattr(&mut self, fid: u32) -> VfsResult<Attr> {
+        let mut r = self.rpc(Msg::new(TGETATTR).u32(fid).u64(GETATTR_BASIC))?;
+        r.u64()?; // valid
+        r.qid()?;
+        let mode = r.u32()?;
+        r.take(4 + 4 + 8 + 8)?; // uid, gid, nlink, rdev
+        let size = r.u64()?;
+        r.u64()?; // blksize
+        let blocks = r.u64()?;
+        Ok(Attr { mode, size, blocks })
+    }
+
+    fn statfs(&mut self, fid: u32) -> VfsResult<FileSystemStat> {
+        let mut r = self.rpc(Msg::new(TSTATFS).u32(fid))?;
+        r.u32()?; // type
+        let block_size = r.u32()? as u64;
+        let blocks = r.u64()?;
+        let blocks_free = r.u64()?;
+        let blocks_avail = r.u64()?;
+        let files = r.u64()?;
+        let files_free = r.u64()?;
+        r.u64()?; // fsid
+        let name_max = r.u32()? as u64;
+        Ok(FileSystemStat {
+            block_size,
+            blocks,
+            blocks_free,
+            blocks_avail,
+            files,
+            files_free,
+            name_max,
+            ..Default::default()
+        })
+    }
+
+    fn set_size(&mut self, fid: u32, size: u64) -> VfsResult {
+        let msg = Msg::new(TSETATTR)
+            .u32(fid)
+            .u32(SETATTR_SIZE)
+            .u32(0) // mode
+            .u32(0) // uid
+            .u32(0) // gid
+            .u64(size)
+            .u64(0) // atime
+            .u64(0)
+            .u64(0) // mtime
+            .u64(0);
+        self.rpc(msg).map(|_| ())
+    }
+
+    fn fsync(&mut self, fid: u32) -> VfsResult {
+        self.rpc(Msg::new(TFSYNC).u32(fid).u32(0)).map(|_| ())
+    }
+
+    /// Reads up to one message of data at `offset` of the opened `fid`.
+    fn read(&mut self, fid: u32, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let count = buf.len().min(self.msize - READ_HEADER_SIZE);
+        let msg = Msg::new(TREAD).u32(fid).u64(offset).u32(count as u32);
+        let mut r = self.rpc(msg)?;
+        let len = (r.u32()? as usize).min(count);
+        buf[..len].copy_from_slice(r.take(len)?);
+        Ok(len)
+    }
+
+    /// Writes up to one message of data at `offset` of the opened `fid`.
+    fn write(&mut self, fid: u32, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let count = buf.len().min(self.msize - WRITE_HEADER_SIZE);
+        let msg = Msg::new(TWRITE)
+            .u32(fid)
+            .u64(offset)
+            .u32(count as u32)
+            .bytes(&buf[..count]);
+        let mut r = self.rpc(msg)?;
+        Ok((r.u32()? as usize).min(count))
+    }
+
+    /// Reads the entries from `offset` of the opened directory `fid`, up to
+    /// one message.
+    fn readdir(&mut self, fid: u32, offset: u64) -> VfsResult<Vec<DirEntry>> {
+        let count = self.msize - READ_HEADER_SIZE;
+        let msg = Msg::new(TREADDIR).u32(fid).u64(offset).u32(count as u32);
+        let mut r = self.rpc(msg)?;
+        let len = r.u32()? as usize;
+        let mut r = Reader(r.take(len)?);
+        let mut entries = Vec::new();
+        while !r.0.is_empty() {
+            r.qid()?;
+            let next = r.u64()?;
+            let ty = dirent_type(r.u8()?);
+            let name = r.str()?.to_string();
+            entries.push(DirEntry { name, ty, next });
+        }
+        Ok(entries)
+    }
+}
+
+/// A mount of a shared folder.
+struct Session {
+    client: Arc<Mutex<Client>>,
+    root_fid: u32,
+    /// The path of the mount point, to resolve the absolute paths of renames.
+    mount_path: RwLock<String>,
+    /// The parent of the mount point, for `..` of the root.
+    parent: RwLock<Option<Weak<dyn VfsNodeOps>>>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.client.lock().clunk(self.root_fid);
+    }
+}
+
+impl Session {
+    /// Walks to the directory of the existing entry `path` relative to
+    /// `base`, returns the fid of the directory and the name of the entry.
+    fn walk_dir_of<'a>(&self, base: &'a str, path: &'a str) -> VfsResult<(u32, &'a str)> {
+        let Resolved::Inside(names) = resolve(base, path) else {
+            return Err(VfsError::Unsupported);
+        };
+        let Some((name, dir)) = names.split_last() else {
+            return Err(VfsError::InvalidInput);
+        };
+        let fid = self.client.lock().walk(self.root_fid, dir)?;
+        Ok((fid, name))
+    }
+}
+
+/// A path resolved from a node.
+enum Resolved<'a> {
+    /// The names from the root of the session.
+    Inside(Vec<&'a str>),
+    /// The rest of the path after it leaves the root by `..`, to be resolved
+    /// from the parent of the mount point.
+    Outside(&'a str),
+}
+
+/// Resolves `path` relative to `base`, which is a path from the root of the
+/// session.
+fn resolve<'a>(base: &'a str, path: &'a str) -> Resolved<'a> {
+    let mut names: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (name, next) = rest.split_once('/').unwrap_or((rest, ""));
+        rest = next;
+        match name {
+            "" | "." => {}
+            ".." => {
+                if names.pop().is_none() {
+                    return Resolved::Outside(rest);
+                }
+            }
+            _ => names.push(name),
+        }
+    }
+    Resolved::Inside(names)
+}
+
+/// A file or a directory in a shared folder.
+struct NinePNode {
+    session: Arc<Session>,
+    /// The path from the root of the session, empty for the root.
+    path: String,
+    fid: u32,
+    /// The fid opened for I/O and whether it's writable.
+    io_fid: Mutex<Option<(u32, bool)>>,
+    /// The index and the offset of the next entry, to continue reading the
+    /// directory without reading the entries before again.
+    dir_pos: Mutex<(usize, u64)>,
+}
+
+impl NinePNode {
+    /// Opens the node of the names from the root of `session`.
+    fn open(session: &Arc<Session>, names: &[&str]) -> VfsResult<Arc<Self>> {
+        let fid = session.client.lock().walk(session.root_fid, names)?;
+        Ok(Arc::new(Self {
+            session: session.clone(),
+            path: names.join("/"),
+            fid,
+            io_fid: Mutex::new(None),
+            dir_pos: Mutex::new((0, 0)),
+        }))
+    }
+
+    fn mount_parent(&self) -> VfsResult<VfsNodeRef> {
+        let parent = self.session.parent.read();
+        parent
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .ok_or(VfsError::NotFound)
+    }
+
+    fn resolve<'a>(&'a self, path: &'a str) -> Resolved<'a> {
+        resolve(&self.path, path)
+    }
+
+    /// Returns the fid opened for I/O, which is opened on the first use. It's
+    /// opened read-write if possible, read-only otherwise.
+    fn io_fid(&self, write: bool) -> VfsResult<u32> {
+        let mut io_fid = self.io_fid.lock();
+        if let Some((fid, writable)) = *io_fid {
+            return if writable || !write {
+                Ok(fid)
+            } else {
+                Err(VfsError::PermissionDenied)
+            };
+        }
+        let mut client = self.session.client.lock();
+        let fid = client.walk(self.fid, &[])?;
+        let writable = match client.lopen(fid, O_RDWR) {
+            Ok(()) => true,
+            Err(VfsError::PermissionDenied | VfsError::IsADirectory) if !write => {
+                client
+                    .lopen(fid, O_RDONLY)
+                    .inspect_err(|_| client.clunk(fid))?;
+                false
+            }
+            Err(e) => {
+                client.clunk(fid);
+                return Err(e);
+            }
+        };
+        *io_fid = Some((fid, writable));
+        Ok(fid)
+    }
+}
+
+impl Drop for NinePNode {
+    fn drop(&mut self) {
+        let mut client = self.session.client.lock();
+        if let Some((fid, _)) = *self.io_fid.get_mut() {
+            client.clunk(fid);
+        }
+        client.clunk(self.fid);
+    }
+}
+
+impl VfsNodeOps for NinePNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = self.session.client.lock().getattr(self.fid)?;
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(attr.mode as u16 & 0o777),
+            node_type(attr.mode),
+            attr.size,
+            attr.blocks,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let fid = self.io_fid(false)?;
+        let mut client = self.session.client.lock();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let len = client.read(fid, offset + pos as u64, &mut buf[pos..])?;
+            if len == 0 {
+                break;
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let fid = self.io_fid(true)?;
+        let mut client = self.session.client.lock();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let len = client.write(fid, offset + pos as u64, &buf[pos..])?;
+            if len == 0 {
+                break;
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        match *self.io_fid.lock() {
+            Some((fid, _)) => self.session.client.lock().fsync(fid),
+            None => Ok(()),
+        }
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.session.client.lock().set_size(self.fid, size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.path.is_empty() {
+            return self.mount_parent().ok();
+        }
+        let parent = self.path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        let names: Vec<&str> = parent.split('/').filter(|s| !s.is_empty()).collect();
+        Some(Self::open(&self.session, &names).ok()?)
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        match self.resolve(path) {
+            Resolved::Inside(names) => Ok(Self::open(&self.session, &names)?),
+            Resolved::Outside("") => self.mount_parent(),
+            Resolved::Outside(rest) => self.mount_parent()?.lookup(rest),
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        if let Resolved::Outside(rest) = self.resolve(path) {
+            return self.mount_parent()?.create(rest, ty);
+        }
+        let (fid, name) = self
+            .session
+            .walk_dir_of(&self.path, path)
+            .map_err(|e| match e {
+                VfsError::InvalidInput => VfsError::AlreadyExists,
+                e => e,
+            })?;
+        let mut client = self.session.client.lock();
+        let res = match ty {
+            VfsNodeType::Dir => client.mkdir(fid, name, 0o755),
+            VfsNodeType::File => client.lcreate(fid, name, 0o644),
+            _ => Err(VfsError::Unsupported),
+        };
+        client.clunk(fid);
+        res
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        if let Resolved::Outside(rest) = self.resolve(path) {
+            return self.mount_parent()?.remove(rest);
+        }
+        let (fid, name) = self.session.walk_dir_of(&self.path, path)?;
+        let mut client = self.session.client.lock();
+        let res = match client.unlinkat(fid, name, 0) {
+            Err(VfsError::IsADirectory) => client.unlinkat(fid, name, AT_REMOVEDIR),
+            res => res,
+        };
+        client.clunk(fid);
+        res
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let fid = self.io_fid(false)?;
+        let mut pos = self.dir_pos.lock();
+        if pos.0 > start_idx {
+            *pos = (0, 0);
+        }
+        let mut client = self.session.client.lock();
+        let mut count = 0;
+        'read: while count < dirents.len() {
+            let entries = client.readdir(fid, pos.1)?;
+            if entries.is_empty() {
+                break;
+            }
+            for entry in entries {
+                if pos.0 >= start_idx {
+                    if count == dirents.len() {
+                        break 'read;
+                    }
+                    dirents[count] = VfsDirEntry::new(&entry.name, entry.ty);
+                    count += 1;
+                }
+                *pos = (pos.0 + 1, entry.next);
+            }
+        }
+        Ok(count)
+    }
+
+    /// Renames `src_path` to `dst_path` in the same shared folder, where
+    /// `dst_path` is either absolute or relative to this node.
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let session = &self.session;
+        let (dst_base, dst_path) = if dst_path.starts_with('/') {
+            let mount_path = session.mount_path.read();
+            let rest = dst_path
+                .strip_prefix(mount_path.as_str())
+                .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+                .ok_or(VfsError::Unsupported)?;
+            ("", rest)
+        } else {
+            (self.path.as_str(), dst_path)
+        };
+        let (src_fid, src_name) = session.walk_dir_of(&self.path, src_path)?;
+        let (dst_fid, dst_name) = match session.walk_dir_of(dst_base, dst_path) {
+            Ok(dst) => dst,
+            Err(e) => {
+                session.client.lock().clunk(src_fid);
+                return Err(e);
+            }
+        };
+        let mut client = session.client.lock();
+        let res = client.renameat(src_fid, src_name, dst_fid, dst_name);
+        client.clunk(src_fid);
+        client.clunk(dst_fid);
+        res
+    }
+}
+
+/// A mount of a folder shared by the host.
+pub struct NinePFileSystem {
+    root: Arc<NinePNode>,
+}
+
+impl NinePFileSystem {
+    fn new(client: Arc<Mutex<Client>>) -> VfsResult<Self> {
+        let root_fid = client.lock().attach()?;
+        let session = Arc::new(Session {
+            client,
+            root_fid,
+            mount_path: RwLock::new(String::new()),
+            parent: RwLock::new(None),
+        });
+        Ok(Self {
+            root: NinePNode::open(&session, &[])?,
+        })
+    }
+}
+
+impl VfsOps for NinePFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        let session = &self.root.session;
+        *session.mount_path.write() = path.trim_end_matches('/').into();
+        *session.parent.write() = mount_point.parent().map(|p| Arc::downgrade(&p));
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl StatFs for NinePFileSystem {
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        let session = &self.root.session;
+        session.client.lock().statfs(session.root_fid)
+    }
+}
+
+/// The connections to the shared folders, by their mount tags.
+static CLIENTS: Mutex<Vec<(String, Arc<Mutex<Client>>)>> = Mutex::new(Vec::new());
+
+/// Connects to the folder shared by `dev`, returns its mount tag.
+pub(crate) fn add_device(dev: AxNinePDevice) -> VfsResult<String> {
+    let tag = dev.mount_tag().to_string();
+    let client = Client::new(dev)?;
+    CLIENTS
+        .lock()
+        .push((tag.clone(), Arc::new(Mutex::new(client))));
+    Ok(tag)
+}
+
+/// Creates a new mount of the shared folder of `tag`.
+pub(crate) fn new_mount(tag: &str) -> VfsResult<Arc<NinePFileSystem>> {
+    let client = CLIENTS
+        .lock()
+        .iter()
+        .find(|(t, _)| t == tag)
+        .map(|(_, client)| client.clone())
+        .ok_or(VfsError::NotFound)?;
+    Ok(Arc::new(NinePFileSystem::new(client)?))
+}
@@ -0,0 +1,499 @@
+//! Files in `/proc` whose content is generated each time they are read,
+//! sysctls in `/proc/sys` backed by the kernel state, and the directories of
+//! the tasks, `/proc/<pid>` and `/proc/self`, generated on lookup.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axfs_devfs::{DeviceFileSystem, DirNode};
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use lazyinit::LazyInit;
+use spin::{Mutex, Once};
+
+/// The root directory of procfs, set when it is mounted.
+pub(crate) static PROC_ROOT: LazyInit<Arc<DeviceFileSystem>> = LazyInit::new();
+
+/// The directories in `/proc/sys` by their paths relative to it, where
+/// sysctls are added. `/proc/sys` itself is the empty path.
+pub(crate) static SYSCTL_DIRS: Mutex<BTreeMap<&'static str, Arc<DirNode>>> =
+    Mutex::new(BTreeMap::new());
+
+/// A read-only file whose content is produced by a generator function.
+pub struct ProcFileNode {
+    generate: fn() -> String,
+}
+
+impl ProcFileNode {
+    /// Creates a new file with the given content generator.
+    pub const fn new(generate: fn() -> String) -> Self {
+        Self { generate }
+    }
+}
+
+impl VfsNodeOps for ProcFileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        // Like Linux, the size is reported as 0 as it is unknown until read.
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.generate)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        let src = &content[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Adds a read-only file `/proc/<name>`, whose content is generated by
+/// `generate` each time the file is read.
+///
+/// It must be called after the filesystems are initialized.
+pub fn add_proc_file(name: &'static str, generate: fn() -> String) {
+    PROC_ROOT.add(name, Arc::new(ProcFileNode::new(generate)));
+    crate::dcache::invalidate_all();
+}
+
+/// A sysctl file, read and set as text by the given functions.
+pub struct SysctlNode {
+    read: fn() -> String,
+    write: fn(&str) -> VfsResult,
+}
+
+impl VfsNodeOps for SysctlNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o644),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        let src = &content[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        // Each write sets the whole value, like Linux.
+        let value = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        (self.write)(value)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // Opened with `O_TRUNC` before written.
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+fn sysctl_dir(dirs: &mut BTreeMap<&'static str, Arc<DirNode>>, path: &'static str) -> Arc<DirNode> {
+    if let Some(dir) = dirs.get(path) {
+        return dir.clone();
+    }
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let dir = sysctl_dir(dirs, parent).mkdir(name);
+    dirs.insert(path, dir.clone());
+    dir
+}
+
+/// Adds a sysctl file `/proc/sys/<path>`, whose content is generated by
+/// `read` each time the file is read, and is set by `write` with the text
+/// written to the file.
+///
+/// The missing parent directories are created. It must be called after the
+/// filesystems are initialized.
+pub fn add_sysctl(path: &'static str, read: fn() -> String, write: fn(&str) -> VfsResult) {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let dir = sysctl_dir(&mut SYSCTL_DIRS.lock(), dir);
+    dir.add(name, Arc::new(SysctlNode { read, write }));
+    crate::dcache::invalidate_all();
+}
+
+/// The tasks shown in `/proc/<pid>`, provided by the runtime.
+pub trait ProcTasks: Send + Sync {
+    /// Returns the ID of the current task, which `/proc/self` refers to.
+    fn current(&self) -> u64;
+
+    /// Returns the IDs of the tasks, in ascending order.
+    fn pids(&self) -> Vec<u64>;
+
+    /// Generates the file `name` of [`TASK_FILES`] in `/proc/<pid>`, or
+    /// returns `None` if there is no such task.
+    fn generate(&self, pid: u64, name: &str) -> Option<String>;
+}
+
+/// The files in each `/proc/<pid>`, besides the directory `fd`.
+pub const TASK_FILES: [&str; 3] = ["comm", "stat", "status"];
+
+static PROC_TASKS: Once<&'static dyn ProcTasks> = Once::new();
+
+/// Lists the open files as `(fd, target)`, see [`set_proc_fds`].
+static PROC_FDS: Once<fn() -> Vec<(u32, String)>> = Once::new();
+
+/// Sets the tasks shown in `/proc/<pid>`, which can only be set once.
+pub fn set_proc_tasks(tasks: &'static dyn ProcTasks) {
+    PROC_TASKS.call_once(|| tasks);
+}
+
+/// Sets the function listing the open files, shown as the links in
+/// `/proc/<pid>/fd` to their targets, e.g. the paths of the files.
+///
+/// The files are those of the reader, as the tasks in a namespace share them.
+pub fn set_proc_fds(list: fn() -> Vec<(u32, String)>) {
+    PROC_FDS.call_once(|| list);
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let path = path.trim_start_matches('/');
+    match path.find('/') {
+        Some(n) => (
+            &path[..n],
+            Some(&path[n + 1..]).filter(|rest| !rest.is_empty()),
+        ),
+        None => (path, None),
+    }
+}
+
+/// Copies `content` from `offset` to `buf`.
+fn read_content(content: &[u8], offset: u64, buf: &mut [u8]) -> usize {
+    let start = content.len().min(offset as usize);
+    let end = content.len().min(start + buf.len());
+    buf[..end - start].copy_from_slice(&content[start..end]);
+    end - start
+}
+
+/// Fills `dirents` from `start_idx` by `.`, `..` and the entries `names`.
+fn fill_dirents(
+    start_idx: usize,
+    dirents: &mut [VfsDirEntry],
+    names: &[(String, VfsNodeType)],
+) -> usize {
+    for (i, ent) in dirents.iter_mut().enumerate() {
+        *ent = match i + start_idx {
+            0 => VfsDirEntry::new(".", VfsNodeType::Dir),
+            1 => VfsDirEntry::new("..", VfsNodeType::Dir),
+            idx => match names.get(idx - 2) {
+                Some((name, ty)) => VfsDirEntry::new(name, *ty),
+                None => return i,
+            },
+        };
+    }
+    dirents.len()
+}
+
+/// The root of procfs, whose static entries are in [`PROC_ROOT`], followed by
+/// `self` and the directories of the tasks.
+struct ProcRootDir {
+    fixed: VfsNodeRef,
+}
+
+impl ProcRootDir {
+    fn task_dir(self: Arc<Self>, pid: u64) -> VfsResult<VfsNodeRef> {
+        let tasks = PROC_TASKS.get().ok_or(VfsError::NotFound)?;
+        if !tasks.pids().contains(&pid) {
+            return Err(VfsError::NotFound);
+        }
+        Ok(Arc::new(TaskDir { pid, root: self }))
+    }
+
+    fn num_fixed_entries(&self) -> VfsResult<usize> {
+        const EMPTY: VfsDirEntry = VfsDirEntry::default();
+        let mut dirents = [EMPTY; 16];
+        let mut count = 0;
+        loop {
+            match self.fixed.read_dir(count, &mut dirents)? {
+                0 => return Ok(count),
+                n => count += n,
+            }
+        }
+    }
+}
+
+impl VfsNodeOps for ProcRootDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.fixed.get_attr()
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.fixed.parent()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node: VfsNodeRef = match name {
+            "" | "." => self.clone(),
+            "self" => {
+                let tasks = PROC_TASKS.get().ok_or(VfsError::NotFound)?;
+                self.task_dir(tasks.current())?
+            }
+            _ => match name.parse() {
+                Ok(pid) => self.task_dir(pid)?,
+                Err(_) => return self.fixed.clone().lookup(path),
+            },
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let num_fixed = self.num_fixed_entries()?;
+        let n = if start_idx < num_fixed {
+            self.fixed.read_dir(start_idx, dirents)?
+        } else {
+            0
+        };
+        let Some(tasks) = PROC_TASKS.get().filter(|_| n < dirents.len()) else {
+            return Ok(n);
+        };
+        let mut names = Vec::from([String::from("self")]);
+        names.extend(tasks.pids().iter().map(u64::to_string));
+        let skip = (start_idx + n) - num_fixed;
+        for (i, ent) in dirents[n..].iter_mut().enumerate() {
+            match names.get(skip + i) {
+                Some(name) => *ent = VfsDirEntry::new(name, VfsNodeType::Dir),
+                None => return Ok(n + i),
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// `/proc/<pid>`.
+struct TaskDir {
+    pid: u64,
+    root: Arc<ProcRootDir>,
+}
+
+impl VfsNodeOps for TaskDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o555),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        Some(self.root.clone())
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node: VfsNodeRef = match name {
+            "" | "." => self.clone(),
+            ".." => self.root.clone(),
+            "fd" => Arc::new(FdDir { task: self.clone() }),
+            _ => {
+                let name = TASK_FILES
+                    .into_iter()
+                    .find(|&f| f == name)
+                    .ok_or(VfsError::NotFound)?;
+                Arc::new(TaskFileNode {
+                    pid: self.pid,
+                    name,
+                })
+            }
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut names = Vec::from([(String::from("fd"), VfsNodeType::Dir)]);
+        names.extend(TASK_FILES.map(|name| (String::from(name), VfsNodeType::File)));
+        Ok(fill_dirents(start_idx, dirents, &names))
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// A file in `/proc/<pid>` generated by [`ProcTasks::generate`].
+struct TaskFileNode {
+    pid: u64,
+    name: &'static str,
+}
+
+impl VfsNodeOps for TaskFileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        // the task may have exited after it's opened
+        let content = PROC_TASKS
+            .get()
+            .and_then(|tasks| tasks.generate(self.pid, self.name))
+            .ok_or(VfsError::NotFound)?;
+        Ok(read_content(content.as_bytes(), offset, buf))
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// `/proc/<pid>/fd`, the links to the open files.
+struct FdDir {
+    task: Arc<TaskDir>,
+}
+
+impl FdDir {
+    fn fds(&self) -> Vec<(u32, String)> {
+        PROC_FDS.get().map_or_else(Vec::new, |list| list())
+    }
+}
+
+impl VfsNodeOps for FdDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o500),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        Some(self.task.clone())
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node: VfsNodeRef = match name {
+            "" | "." => self.clone(),
+            ".." => self.task.clone(),
+            _ => {
+                let fd = name.parse().map_err(|_| VfsError::NotFound)?;
+                let (_, target) = self
+                    .fds()
+                    .into_iter()
+                    .find(|&(n, _)| n == fd)
+                    .ok_or(VfsError::NotFound)?;
+                Arc::new(FdLinkNode { target })
+            }
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let names = self
+            .fds()
+            .into_iter()
+            .map(|(fd, _)| (fd.to_string(), VfsNodeType::SymLink))
+            .collect::<Vec<_>>();
+        Ok(fill_dirents(start_idx, dirents, &names))
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// `/proc/<pid>/fd/<fd>`, a symlink whose content is the target.
+struct FdLinkNode {
+    target: String,
+}
+
+impl VfsNodeOps for FdLinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o777),
+            VfsNodeType::SymLink,
+            self.target.len() as u64,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        Ok(read_content(self.target.as_bytes(), offset, buf))
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// procfs, the static entries in [`PROC_ROOT`] and the directories of the
+/// tasks.
+pub(crate) struct ProcFileSystem {
+    fixed: Arc<DeviceFileSystem>,
+    root: Arc<ProcRootDir>,
+}
+
+impl ProcFileSystem {
+    pub(crate) fn new(fixed: Arc<DeviceFileSystem>) -> Self {
+        let root = Arc::new(ProcRootDir {
+            fixed: fixed.root_dir(),
+        });
+        Self { fixed, root }
+    }
+}
+
+impl VfsOps for ProcFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.fixed.mount(path, mount_point)
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl crate::statfs::StatFs for ProcFileSystem {}
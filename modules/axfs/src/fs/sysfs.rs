@@ -0,0 +1,122 @@
+//! Files in `/sys` whose content is generated each time they are read, like
+//! the attributes of the devices, some of which can also be set by writing.
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+};
+use axfs_devfs::{DeviceFileSystem, DirNode};
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use lazyinit::LazyInit;
+use spin::Mutex;
+
+/// The root directory of sysfs, set when it is mounted.
+pub(crate) static SYS_ROOT: LazyInit<Arc<DeviceFileSystem>> = LazyInit::new();
+
+/// The directories created by `add_sys_attr` by their paths relative to
+/// `/sys`.
+static SYS_DIRS: Mutex<BTreeMap<String, Arc<DirNode>>> = Mutex::new(BTreeMap::new());
+
+type ReadFn = Box<dyn Fn() -> String + Send + Sync>;
+type WriteFn = Box<dyn Fn(&str) -> VfsResult + Send + Sync>;
+
+/// A file whose content is produced by a generator closure, and is set by
+/// another closure if writable.
+pub struct SysFileNode {
+    read: ReadFn,
+    write: Option<WriteFn>,
+}
+
+impl VfsNodeOps for SysFileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        // Like Linux, the size is reported as 4096 whatever the content is.
+        let mode = if self.write.is_some() { 0o644 } else { 0o444 };
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(mode),
+            VfsNodeType::File,
+            4096,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        let src = &content[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        // Each write sets the whole value, like Linux.
+        let value = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        write(value)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // Opened with `O_TRUNC` before written.
+        match self.write {
+            Some(_) => Ok(()),
+            None => Err(VfsError::PermissionDenied),
+        }
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+fn sys_dir(dirs: &mut BTreeMap<String, Arc<DirNode>>, path: &str) -> Option<Arc<DirNode>> {
+    if path.is_empty() {
+        return None;
+    }
+    if let Some(dir) = dirs.get(path) {
+        return Some(dir.clone());
+    }
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    // The names of the entries of devfs directories live forever.
+    let name: &'static str = name.to_string().leak();
+    let dir = match sys_dir(dirs, parent) {
+        Some(parent) => parent.mkdir(name),
+        None => SYS_ROOT.mkdir(name),
+    };
+    dirs.insert(path.into(), dir.clone());
+    Some(dir)
+}
+
+/// Adds a read-only file `/sys/<path>`, whose content is generated by `read`
+/// each time the file is read.
+///
+/// The missing parent directories are created, which must not be the ones
+/// of the initial sysfs, e.g. `/sys/kernel`. It must be called after the
+/// filesystems are initialized.
+pub fn add_sys_file(path: &str, read: impl Fn() -> String + Send + Sync + 'static) {
+    add_node(path, Box::new(read), None);
+}
+
+/// Adds a file `/sys/<path>`, whose content is generated by `read` each time
+/// the file is read, and is set by `write` with the text written to the file.
+///
+/// Like [`add_sys_file`], the missing parent directories are created.
+pub fn add_sys_attr(
+    path: &str,
+    read: impl Fn() -> String + Send + Sync + 'static,
+    write: impl Fn(&str) -> VfsResult + Send + Sync + 'static,
+) {
+    add_node(path, Box::new(read), Some(Box::new(write)));
+}
+
+fn add_node(path: &str, read: ReadFn, write: Option<WriteFn>) {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let name: &'static str = name.to_string().leak();
+    let node = Arc::new(SysFileNode { read, write });
+    match sys_dir(&mut SYS_DIRS.lock(), dir) {
+        Some(dir) => dir.add(name, node),
+        None => SYS_ROOT.add(name, node),
+    }
+    crate::dcache::invalidate_all();
+}
@@ -0,0 +1,834 @@
+//! A filesystem in memory, like tmpfs on Linux.
+//!
+//! Unlike ramfs, it has the directory tree with the files renamed across the
+//! directories, the hard links counted by the link counts, the sparse files
+//! whose holes take no memory, and the permissions and the owners of the
+//! files. The data of a file is released when it has no links and is no
+//! longer opened.
+//!
+//! The pages of the files are aligned, so they can be mapped into the address
+//! spaces directly by [`TmpNode::map_pages`], to share the files with
+//! `MAP_SHARED`, as the POSIX shared memory objects in `/dev/shm`.
+//!
+//! The blocks and the nodes owned by each user and group are accounted, and
+//! limited by the quotas once enforced by [`TmpNode::set_quota_enabled`].
+//!
+//! It's mounted on `/tmp` and `/dev/shm`, and used as the root filesystem if
+//! there is no block device.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use spin::RwLock;
+
+use crate::statfs::{FileSystemStat, StatFs};
+
+/// The size of the pages of the files, in which the memory is allocated.
+const PAGE_SIZE: usize = 0x1000;
+
+/// A page of a file, aligned to be mapped.
+#[repr(C, align(4096))]
+struct Page([u8; PAGE_SIZE]);
+
+impl Page {
+    fn new_zeroed() -> Box<Self> {
+        Box::new(Self([0; PAGE_SIZE]))
+    }
+}
+
+/// All existing nodes, indexed by their addresses, to find the node of a
+/// [`VfsNodeRef`] in [`tmpfs_node`].
+static NODES: Mutex<BTreeMap<usize, Weak<TmpNode>>> = Mutex::new(BTreeMap::new());
+
+/// Returns the tmpfs node of `node`, or `None` if it is not one.
+pub fn tmpfs_node(node: &VfsNodeRef) -> Option<Arc<TmpNode>> {
+    let addr = Arc::as_ptr(node) as *const u8 as usize;
+    NODES.lock().get(&addr).and_then(Weak::upgrade)
+}
+
+/// Links `node` into the directory `dir` as `name`, if both of them are in
+/// the same tmpfs.
+pub(crate) fn link(dir: &VfsNodeRef, name: &str, node: &VfsNodeRef) -> VfsResult {
+    match (tmpfs_node(dir), tmpfs_node(node)) {
+        (Some(dir), Some(node)) => dir.link(name, &node),
+        _ => Err(VfsError::Unsupported),
+    }
+}
+
+/// The kind of the owners of the files limited by a quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaType {
+    /// Limits the users, by the `uid`s of the files.
+    User = 0,
+    /// Limits the groups, by the `gid`s of the files.
+    Group = 1,
+}
+
+/// The limits of a user or a group, where 0 is no limit.
+///
+/// Only the hard limits are enforced, the soft limits are kept for
+/// `quotactl` without the grace periods.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotaLimits {
+    /// The hard limit of the space in bytes.
+    pub space_hard: u64,
+    /// The soft limit of the space in bytes.
+    pub space_soft: u64,
+    /// The hard limit of the number of nodes.
+    pub inodes_hard: u64,
+    /// The soft limit of the number of nodes.
+    pub inodes_soft: u64,
+}
+
+/// The usage and the limits of a user or a group.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dquot {
+    /// The limits set by [`TmpNode::set_quota_limits`].
+    pub limits: QuotaLimits,
+    /// The space used by the pages of the files, in bytes.
+    pub space: u64,
+    /// The number of nodes owned.
+    pub inodes: u64,
+}
+
+impl Dquot {
+    /// Returns whether `space` more bytes and `inodes` more nodes stay in the
+    /// hard limits.
+    fn fits(&self, space: u64, inodes: u64) -> bool {
+        let fits = |cur: u64, more: u64, limit: u64| limit == 0 || cur + more <= limit;
+        fits(self.space, space, self.limits.space_hard)
+            && fits(self.inodes, inodes, self.limits.inodes_hard)
+    }
+}
+
+/// The usage of all users and groups of a filesystem, and whether their
+/// quotas are enforced.
+#[derive(Default)]
+struct Quotas {
+    enabled: [bool; 2],
+    dquots: [BTreeMap<u32, Dquot>; 2],
+}
+
+impl Quotas {
+    /// Moves `space` bytes and `inodes` nodes from the owner `from` to the
+    /// owner `to`, both of which are `(uid, gid)`, or `None` to charge or
+    /// release them.
+    ///
+    /// Returns [`VfsError::StorageFull`] without moving anything if a hard
+    /// limit of an enforced quota of `to` would be exceeded.
+    fn transfer(
+        &mut self,
+        from: Option<(u32, u32)>,
+        to: Option<(u32, u32)>,
+        space: u64,
+        inodes: u64,
+    ) -> VfsResult {
+        let ids = |owner: Option<(u32, u32)>| owner.map(|(uid, gid)| [uid, gid]);
+        let (from, to) = (ids(from), ids(to));
+        let moved = |ty: usize| from.map(|ids| ids[ty]) != to.map(|ids| ids[ty]);
+        for ty in 0..2 {
+            let Some(to) = to.filter(|_| moved(ty) && self.enabled[ty]) else {
+                continue;
+            };
+            if !self.dquots[ty]
+                .get(&to[ty])
+                .is_none_or(|dq| dq.fits(space, inodes))
+            {
+                return Err(VfsError::StorageFull);
+            }
+        }
+        for ty in (0..2).filter(|&ty| moved(ty)) {
+            if let Some(dq) = from.and_then(|from| self.dquots[ty].get_mut(&from[ty])) {
+                dq.space = dq.space.saturating_sub(space);
+                dq.inodes = dq.inodes.saturating_sub(inodes);
+            }
+            if let Some(to) = to {
+                let dq = self.dquots[ty].entry(to[ty]).or_default();
+                dq.space += space;
+                dq.inodes += inodes;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state shared by all the nodes of a filesystem.
+struct Shared {
+    /// Serializes the changes of the directory tree.
+    tree: Mutex<()>,
+    quotas: Mutex<Quotas>,
+}
+
+impl Shared {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            tree: Mutex::new(()),
+            quotas: Mutex::new(Quotas::default()),
+        })
+    }
+}
+
+struct Meta {
+    perm: VfsNodePerm,
+    uid: u32,
+    gid: u32,
+    /// The number of the directory entries of the node, plus the `.` and the
+    /// `..` entries of the subdirectories for a directory.
+    nlink: u32,
+}
+
+/// The contents of a regular file or a symlink.
+struct FileData {
+    size: u64,
+    /// The pages written or mapped, where the others are holes and read as
+    /// zeros.
+    pages: BTreeMap<u64, Box<Page>>,
+    /// The number of mapped pages of the file.
+    mapped: usize,
+}
+
+struct DirData {
+    /// The parent directory, which is the parent of the mount point for the
+    /// root directory.
+    parent: Option<Weak<dyn VfsNodeOps>>,
+    entries: BTreeMap<String, Arc<TmpNode>>,
+}
+
+enum Content {
+    File(FileData),
+    Dir(DirData),
+}
+
+/// A node of tmpfs, which is a regular file, a directory or a symlink.
+pub struct TmpNode {
+    ty: VfsNodeType,
+    this: Weak<TmpNode>,
+    fs: Arc<Shared>,
+    meta: Mutex<Meta>,
+    content: RwLock<Content>,
+}
+
+impl TmpNode {
+    /// Creates a node owned by root, charging it to the quotas of root.
+    fn new(
+        fs: &Arc<Shared>,
+        ty: VfsNodeType,
+        perm: u16,
+        parent: Option<Weak<dyn VfsNodeOps>>,
+    ) -> VfsResult<Arc<Self>> {
+        fs.quotas.lock().transfer(None, Some((0, 0)), 0, 1)?;
+        let (content, nlink) = if ty == VfsNodeType::Dir {
+            let dir = DirData {
+                parent,
+                entries: BTreeMap::new(),
+            };
+            (Content::Dir(dir), 2)
+        } else {
+            let file = FileData {
+                size: 0,
+                pages: BTreeMap::new(),
+                mapped: 0,
+            };
+            (Content::File(file), 1)
+        };
+        let node = Arc::new_cyclic(|this| Self {
+            ty,
+            this: this.clone(),
+            fs: fs.clone(),
+            meta: Mutex::new(Meta {
+                perm: VfsNodePerm::from_bits_truncate(perm),
+                uid: 0,
+                gid: 0,
+                nlink,
+            }),
+            content: RwLock::new(content),
+        });
+        NODES
+            .lock()
+            .insert(Arc::as_ptr(&node) as usize, Arc::downgrade(&node));
+        Ok(node)
+    }
+
+    /// Creates a regular file of `size` bytes that is not linked in any
+    /// directory, e.g. the System V shared memory segments. Its data is
+    /// released when it is no longer referenced.
+    pub fn new_unlinked(perm: u16, size: u64) -> Arc<Self> {
+        // the only node of its own filesystem, where no quota is enforced
+        let node = Self::new(&Shared::new(), VfsNodeType::File, perm, None)
+            .expect("no quota on a new filesystem");
+        node.meta.lock().nlink = 0;
+        if let Content::File(file) = &mut *node.content.write() {
+            file.size = size;
+        }
+        node
+    }
+
+    /// Returns the permissions of the node.
+    pub fn perm(&self) -> VfsNodePerm {
+        self.meta.lock().perm
+    }
+
+    /// Sets the permissions of the node.
+    pub fn set_perm(&self, perm: VfsNodePerm) {
+        self.meta.lock().perm = perm;
+    }
+
+    /// Returns the user and the group owning the node.
+    pub fn owner(&self) -> (u32, u32) {
+        let meta = self.meta.lock();
+        (meta.uid, meta.gid)
+    }
+
+    /// Sets the user and the group owning the node, moving its space and
+    /// itself to their quotas.
+    ///
+    /// Returns [`VfsError::StorageFull`] if it exceeds their quotas.
+    pub fn set_owner(&self, uid: u32, gid: u32) -> VfsResult {
+        let content = self.content.read();
+        let mut meta = self.meta.lock();
+        self.fs.quotas.lock().transfer(
+            Some((meta.uid, meta.gid)),
+            Some((uid, gid)),
+            content.space() as u64,
+            1,
+        )?;
+        meta.uid = uid;
+        meta.gid = gid;
+        Ok(())
+    }
+
+    /// Returns whether the quotas of the users or the groups are enforced in
+    /// the filesystem of the node.
+    pub fn quota_enabled(&self, ty: QuotaType) -> bool {
+        self.fs.quotas.lock().enabled[ty as usize]
+    }
+
+    /// Enforces or stops enforcing the quotas of the users or the groups in
+    /// the filesystem of the node.
+    ///
+    /// The usage is accounted in either case, so it is up to date once the
+    /// quotas are enforced.
+    pub fn set_quota_enabled(&self, ty: QuotaType, enabled: bool) {
+        self.fs.quotas.lock().enabled[ty as usize] = enabled;
+    }
+
+    /// Returns the usage and the limits of the user or the group `id` in the
+    /// filesystem of the node.
+    pub fn quota(&self, ty: QuotaType, id: u32) -> Dquot {
+        let quotas = self.fs.quotas.lock();
+        quotas.dquots[ty as usize]
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Sets the limits of the user or the group `id` in the filesystem of
+    /// the node. The current usage may exceed the new limits.
+    pub fn set_quota_limits(&self, ty: QuotaType, id: u32, limits: QuotaLimits) {
+        let mut quotas = self.fs.quotas.lock();
+        quotas.dquots[ty as usize].entry(id).or_default().limits = limits;
+    }
+
+    /// Charges `pages` more pages to the owner of the node, or releases them
+    /// with `release`.
+    fn charge_pages(&self, pages: usize, release: bool) -> VfsResult {
+        let owner = Some(self.owner());
+        let (from, to) = if release {
+            (owner, None)
+        } else {
+            (None, owner)
+        };
+        let space = (pages * PAGE_SIZE) as u64;
+        self.fs.quotas.lock().transfer(from, to, space, 0)
+    }
+
+    /// Returns the number of hard links to the node.
+    pub fn nlink(&self) -> u32 {
+        self.meta.lock().nlink
+    }
+
+    /// Creates a hard link `name` in this directory to `node`, which must not
+    /// be a directory.
+    pub fn link(&self, name: &str, node: &Arc<TmpNode>) -> VfsResult {
+        if !Arc::ptr_eq(&self.fs, &node.fs) {
+            return Err(VfsError::InvalidInput);
+        }
+        if node.ty == VfsNodeType::Dir {
+            return Err(VfsError::PermissionDenied);
+        }
+        if matches!(name, "" | "." | "..") || name.contains('/') {
+            return Err(VfsError::AlreadyExists);
+        }
+        let _tree = self.fs.tree.lock();
+        if node.nlink() == 0 {
+            // removed after looked up
+            return Err(VfsError::NotFound);
+        }
+        self.with_dir_mut(|dir| {
+            if dir.entries.contains_key(name) {
+                return Err(VfsError::AlreadyExists);
+            }
+            dir.entries.insert(name.to_string(), node.clone());
+            Ok(())
+        })?;
+        node.meta.lock().nlink += 1;
+        Ok(())
+    }
+
+    /// Gets the pages in `[offset, offset + len)` of the file to map them,
+    /// allocating the holes. The range must be in the file, rounded up to
+    /// pages.
+    ///
+    /// Returns the virtual addresses of the pages, which are counted as mapped
+    /// until [`TmpNode::unmap_pages`].
+    pub fn map_pages(&self, offset: u64, len: usize) -> VfsResult<Vec<usize>> {
+        if offset % PAGE_SIZE as u64 != 0 || len % PAGE_SIZE != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        self.with_file_mut(|file| {
+            let start = offset / PAGE_SIZE as u64;
+            let end = start + (len / PAGE_SIZE) as u64;
+            if end > file.size.div_ceil(PAGE_SIZE as u64) {
+                return Err(VfsError::InvalidInput);
+            }
+            let holes = (start..end)
+                .filter(|index| !file.pages.contains_key(index))
+                .count();
+            self.charge_pages(holes, false)?;
+            let pages = (start..end)
+                .map(|index| {
+                    let page = file.pages.entry(index).or_insert_with(Page::new_zeroed);
+                    page.0.as_ptr() as usize
+                })
+                .collect();
+            file.mapped += len / PAGE_SIZE;
+            Ok(pages)
+        })
+    }
+
+    /// Releases `count` pages got by [`TmpNode::map_pages`].
+    pub fn unmap_pages(&self, count: usize) {
+        let _ = self.with_file_mut(|file| {
+            file.mapped -= count;
+            Ok(())
+        });
+    }
+
+    fn add_nlink(&self, delta: i32) {
+        let mut meta = self.meta.lock();
+        meta.nlink = meta.nlink.saturating_add_signed(delta);
+    }
+
+    fn with_file<T>(&self, f: impl FnOnce(&FileData) -> VfsResult<T>) -> VfsResult<T> {
+        match &*self.content.read() {
+            Content::File(file) => f(file),
+            Content::Dir(_) => Err(VfsError::IsADirectory),
+        }
+    }
+
+    fn with_file_mut<T>(&self, f: impl FnOnce(&mut FileData) -> VfsResult<T>) -> VfsResult<T> {
+        match &mut *self.content.write() {
+            Content::File(file) => f(file),
+            Content::Dir(_) => Err(VfsError::IsADirectory),
+        }
+    }
+
+    fn with_dir<T>(&self, f: impl FnOnce(&DirData) -> VfsResult<T>) -> VfsResult<T> {
+        match &*self.content.read() {
+            Content::Dir(dir) => f(dir),
+            Content::File(_) => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn with_dir_mut<T>(&self, f: impl FnOnce(&mut DirData) -> VfsResult<T>) -> VfsResult<T> {
+        match &mut *self.content.write() {
+            Content::Dir(dir) => f(dir),
+            Content::File(_) => Err(VfsError::NotADirectory),
+        }
+    }
+
+    fn entry(&self, name: &str) -> VfsResult<Arc<TmpNode>> {
+        self.with_dir(|dir| dir.entries.get(name).cloned().ok_or(VfsError::NotFound))
+    }
+
+    fn is_empty_dir(&self) -> bool {
+        self.with_dir(|dir| Ok(dir.entries.is_empty()))
+            .unwrap_or(false)
+    }
+
+    /// Looks up the parent directory of `path`, returns it with the last
+    /// component of `path`.
+    fn parent_of<'a>(&self, path: &'a str) -> VfsResult<(VfsNodeRef, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (dir, name) = match path.rfind('/') {
+            Some(n) => (&path[..n], &path[n + 1..]),
+            None => ("", path),
+        };
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        Ok((this.lookup(dir)?, name))
+    }
+
+    /// Returns the node of `node` if it's in the same filesystem.
+    fn same_fs(&self, node: &VfsNodeRef) -> Option<Arc<TmpNode>> {
+        tmpfs_node(node).filter(|node| Arc::ptr_eq(&node.fs, &self.fs))
+    }
+
+    /// Returns whether this directory is `dir` or one of its ancestors.
+    fn is_ancestor_of(&self, dir: &Arc<TmpNode>) -> bool {
+        let mut cur = dir.clone();
+        loop {
+            if core::ptr::eq(Arc::as_ptr(&cur), self) {
+                return true;
+            }
+            match cur.parent().and_then(|parent| self.same_fs(&parent)) {
+                Some(parent) => cur = parent,
+                None => return false,
+            }
+        }
+    }
+
+    fn create_child(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::AlreadyExists);
+        }
+        let perm = match ty {
+            VfsNodeType::File => 0o644,
+            VfsNodeType::Dir => 0o755,
+            VfsNodeType::SymLink => 0o777,
+            _ => return Err(VfsError::Unsupported),
+        };
+        let _tree = self.fs.tree.lock();
+        let parent = self.this.clone() as Weak<dyn VfsNodeOps>;
+        self.with_dir_mut(|dir| {
+            if dir.entries.contains_key(name) {
+                return Err(VfsError::AlreadyExists);
+            }
+            let node = TmpNode::new(&self.fs, ty, perm, Some(parent))?;
+            dir.entries.insert(name.to_string(), node);
+            Ok(())
+        })?;
+        if ty == VfsNodeType::Dir {
+            self.add_nlink(1);
+        }
+        Ok(())
+    }
+
+    /// Unlinks the entry `node` of this directory, which must be empty if
+    /// it's a directory. The entry itself is removed by the caller.
+    fn unlink(&self, node: &TmpNode) -> VfsResult {
+        if node.ty == VfsNodeType::Dir {
+            if !node.is_empty_dir() {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+            node.meta.lock().nlink = 0;
+            self.add_nlink(-1);
+        } else {
+            node.add_nlink(-1);
+        }
+        Ok(())
+    }
+
+    fn remove_child(&self, name: &str) -> VfsResult {
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::InvalidInput);
+        }
+        let _tree = self.fs.tree.lock();
+        let node = self.entry(name)?;
+        self.unlink(&node)?;
+        self.with_dir_mut(|dir| {
+            dir.entries.remove(name);
+            Ok(())
+        })
+    }
+}
+
+impl Drop for TmpNode {
+    fn drop(&mut self) {
+        NODES.lock().remove(&(self as *const Self as usize));
+        let space = self.content.get_mut().space() as u64;
+        let meta = self.meta.get_mut();
+        let owner = Some((meta.uid, meta.gid));
+        let _ = self.fs.quotas.lock().transfer(owner, None, space, 1);
+    }
+}
+
+impl Content {
+    /// Returns the space used by the pages of a file.
+    fn space(&self) -> usize {
+        match self {
+            Content::File(file) => file.pages.len() * PAGE_SIZE,
+            Content::Dir(_) => 0,
+        }
+    }
+}
+
+impl VfsNodeOps for TmpNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = self.perm();
+        match &*self.content.read() {
+            Content::File(file) => {
+                let blocks = (file.pages.len() * PAGE_SIZE / 512) as u64;
+                Ok(VfsNodeAttr::new(perm, self.ty, file.size, blocks))
+            }
+            Content::Dir(_) => Ok(VfsNodeAttr::new(perm, self.ty, 4096, 0)),
+        }
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.with_dir(|dir| Ok(dir.parent.as_ref().and_then(Weak::upgrade)))
+            .ok()
+            .flatten()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node: VfsNodeRef = match name {
+            "" | "." => self.clone(),
+            ".." => self.parent().ok_or(VfsError::NotFound)?,
+            _ => self.entry(name)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let (dir, name) = self.parent_of(path)?;
+        match self.same_fs(&dir) {
+            Some(dir) => dir.create_child(name, ty),
+            None => dir.create(name, ty),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let (dir, name) = self.parent_of(path)?;
+        match self.same_fs(&dir) {
+            Some(dir) => dir.remove_child(name),
+            None => dir.remove(name),
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let (src_dir, src_name) = self.parent_of(src_path)?;
+        let (dst_dir, dst_name) = self.parent_of(dst_path)?;
+        // can not be renamed across filesystems
+        let src_dir = self.same_fs(&src_dir).ok_or(VfsError::InvalidInput)?;
+        let dst_dir = self.same_fs(&dst_dir).ok_or(VfsError::InvalidInput)?;
+        if matches!(src_name, "" | "." | "..") || matches!(dst_name, "" | "." | "..") {
+            return Err(VfsError::InvalidInput);
+        }
+        if dst_dir.ty != VfsNodeType::Dir {
+            return Err(VfsError::NotADirectory);
+        }
+
+        let _tree = self.fs.tree.lock();
+        let node = src_dir.entry(src_name)?;
+        let is_dir = node.ty == VfsNodeType::Dir;
+        if is_dir && node.is_ancestor_of(&dst_dir) {
+            return Err(VfsError::InvalidInput);
+        }
+
+        // replace the destination
+        match dst_dir.entry(dst_name) {
+            Ok(old) if Arc::ptr_eq(&old, &node) => return Ok(()),
+            Ok(old) => {
+                if old.ty == VfsNodeType::Dir && !is_dir {
+                    return Err(VfsError::IsADirectory);
+                } else if old.ty != VfsNodeType::Dir && is_dir {
+                    return Err(VfsError::NotADirectory);
+                }
+                dst_dir.unlink(&old)?;
+            }
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        src_dir.with_dir_mut(|dir| {
+            dir.entries.remove(src_name);
+            Ok(())
+        })?;
+        dst_dir.with_dir_mut(|dir| {
+            dir.entries.insert(dst_name.to_string(), node.clone());
+            Ok(())
+        })?;
+
+        if is_dir && !Arc::ptr_eq(&src_dir, &dst_dir) {
+            let parent = Arc::downgrade(&dst_dir) as Weak<dyn VfsNodeOps>;
+            node.with_dir_mut(|dir| {
+                dir.parent = Some(parent);
+                Ok(())
+            })?;
+            src_dir.add_nlink(-1);
+            dst_dir.add_nlink(1);
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.with_dir(|dir| {
+            let mut entries = dir.entries.iter().skip(start_idx.max(2) - 2);
+            for (i, ent) in dirents.iter_mut().enumerate() {
+                match i + start_idx {
+                    0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                    1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                    _ => match entries.next() {
+                        Some((name, node)) => *ent = VfsDirEntry::new(name, node.ty),
+                        None => return Ok(i),
+                    },
+                }
+            }
+            Ok(dirents.len())
+        })
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.with_file(|file| {
+            let start = file.size.min(offset);
+            let end = file.size.min(start + buf.len() as u64);
+            let mut pos = start;
+            while pos < end {
+                let off = pos as usize % PAGE_SIZE;
+                let n = ((end - pos) as usize).min(PAGE_SIZE - off);
+                let dst = &mut buf[(pos - start) as usize..][..n];
+                match file.pages.get(&(pos / PAGE_SIZE as u64)) {
+                    Some(page) => dst.copy_from_slice(&page.0[off..off + n]),
+                    None => dst.fill(0),
+                }
+                pos += n as u64;
+            }
+            Ok((end - start) as usize)
+        })
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.with_file_mut(|file| {
+            let mut pos = offset;
+            let end = offset + buf.len() as u64;
+            let holes = (offset / PAGE_SIZE as u64..end.div_ceil(PAGE_SIZE as u64))
+                .filter(|index| !file.pages.contains_key(index))
+                .count();
+            self.charge_pages(holes, false)?;
+            while pos < end {
+                let off = pos as usize % PAGE_SIZE;
+                let n = ((end - pos) as usize).min(PAGE_SIZE - off);
+                let page = file
+                    .pages
+                    .entry(pos / PAGE_SIZE as u64)
+                    .or_insert_with(Page::new_zeroed);
+                page.0[off..off + n].copy_from_slice(&buf[(pos - offset) as usize..][..n]);
+                pos += n as u64;
+            }
+            file.size = file.size.max(end);
+            Ok(buf.len())
+        })
+    }
+
+    /// The file can not be shrunk while mapped.
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.with_file_mut(|file| {
+            if size < file.size {
+                if file.mapped > 0 {
+                    return Err(VfsError::ResourceBusy);
+                }
+                // release the pages beyond the end, and clear the tail of the
+                // last page, which is read as zeros if extended later
+                let removed = file.pages.split_off(&size.div_ceil(PAGE_SIZE as u64));
+                self.charge_pages(removed.len(), true)?;
+                let off = size as usize % PAGE_SIZE;
+                if off != 0 {
+                    if let Some(page) = file.pages.get_mut(&(size / PAGE_SIZE as u64)) {
+                        page.0[off..].fill(0);
+                    }
+                }
+            }
+            // extended with a hole
+            file.size = size;
+            Ok(())
+        })
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(())
+    }
+}
+
+/// Splits the first component from `path`.
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let path = path.trim_start_matches('/');
+    match path.find('/') {
+        Some(n) => (
+            &path[..n],
+            Some(&path[n + 1..]).filter(|rest| !rest.is_empty()),
+        ),
+        None => (path, None),
+    }
+}
+
+/// A tmpfs instance.
+pub struct TmpFileSystem {
+    root: Arc<TmpNode>,
+}
+
+impl TmpFileSystem {
+    /// Creates a new empty tmpfs instance.
+    pub fn new() -> Self {
+        let root = TmpNode::new(&Shared::new(), VfsNodeType::Dir, 0o777, None);
+        Self {
+            root: root.expect("no quota on a new filesystem"),
+        }
+    }
+}
+
+impl Default for TmpFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for TmpFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        let parent = mount_point.parent().map(|p| Arc::downgrade(&p));
+        self.root.with_dir_mut(|dir| {
+            dir.parent = parent;
+            Ok(())
+        })
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl StatFs for TmpFileSystem {
+    /// Returns the pages used by the files, and the free memory, from which
+    /// both the pages and the nodes are allocated without a limit.
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        // dropped after `NODES` is unlocked, as they may be the last ones
+        let nodes: Vec<Arc<TmpNode>> = NODES.lock().values().filter_map(Weak::upgrade).collect();
+        let (mut files, mut pages) = (0, 0);
+        for node in nodes.iter().filter(|n| Arc::ptr_eq(&n.fs, &self.root.fs)) {
+            files += 1;
+            if let Content::File(file) = &*node.content.read() {
+                pages += file.pages.len() as u64;
+            }
+        }
+        let free = axalloc::global_allocator().available_pages() as u64;
+        Ok(FileSystemStat {
+            block_size: PAGE_SIZE as u64,
+            blocks: pages + free,
+            blocks_free: free,
+            blocks_avail: free,
+            files: files + free,
+            files_free: free,
+            ..Default::default()
+        })
+    }
+}
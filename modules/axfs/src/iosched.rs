@@ -0,0 +1,300 @@
+//! The request queue of a disk, which merges and submits the requests of the
+//! block cache.
+//!
+//! The requests of a batch are sorted, and the adjacent ones of the same
+//! operation are merged into one request, up to [`MAX_MERGE_BLOCKS`] blocks.
+//! If the device supports it, the merged requests are submitted at once, up
+//! to the depth of its queue, and completed in any order. The task waits for
+//! the interrupt of the device to take the completed ones if the `irq`
+//! feature is enabled, or polls the device otherwise.
+//!
+//! The devices without a request queue process the merged requests one by
+//! one.
+
+use alloc::{vec, vec::Vec};
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axdriver::prelude::*;
+
+use crate::cache::BLOCK_SIZE;
+
+/// The maximum number of blocks of a merged request, 128 KiB.
+const MAX_MERGE_BLOCKS: usize = 256;
+
+/// The number of the merged requests submitted to all disks.
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of the requests submitted to the disks so far, after
+/// merged.
+pub fn io_count() -> u64 {
+    REQUESTS.load(Ordering::Relaxed)
+}
+
+/// A request of the blocks from `block_id`, whose size is the length of the
+/// buffer.
+pub(crate) enum Request<'a> {
+    Read(u64, &'a mut [u8]),
+    Write(u64, &'a [u8]),
+}
+
+impl Request<'_> {
+    fn op(&self) -> BlockOp {
+        match self {
+            Self::Read(..) => BlockOp::Read,
+            Self::Write(..) => BlockOp::Write,
+        }
+    }
+
+    fn block_id(&self) -> u64 {
+        match self {
+            Self::Read(id, _) | Self::Write(id, _) => *id,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Read(_, buf) => buf.len(),
+            Self::Write(_, buf) => buf.len(),
+        }
+    }
+
+    fn buf_ptr(&mut self) -> NonNull<[u8]> {
+        match self {
+            Self::Read(_, buf) => NonNull::from(&mut **buf),
+            Self::Write(_, buf) => NonNull::from(&**buf),
+        }
+    }
+}
+
+/// The requests merged into one.
+struct Merged {
+    op: BlockOp,
+    block_id: u64,
+    len: usize,
+    /// The indices of the requests merged, in the order of the blocks.
+    parts: Vec<usize>,
+    /// The buffer of the merged blocks, if more than one request is merged.
+    bounce: Option<Vec<u8>>,
+}
+
+/// The request queue of a disk.
+pub(crate) struct RequestQueue {
+    dev: AxBlockDevice,
+}
+
+impl RequestQueue {
+    pub fn new(dev: AxBlockDevice) -> Self {
+        Self { dev }
+    }
+
+    pub fn num_blocks(&self) -> u64 {
+        self.dev.num_blocks()
+    }
+
+    /// Reads the blocks from `block_id` into `buf`.
+    pub fn read(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.submit(vec![Request::Read(block_id, buf)])
+    }
+
+    /// Submits the requests, and waits for all of them to complete. Returns
+    /// the first error if any request fails.
+    ///
+    /// The requests must not overlap each other, as they are not processed in
+    /// order.
+    pub fn submit(&mut self, mut reqs: Vec<Request>) -> DevResult {
+        let mut merged = merge(&reqs);
+        REQUESTS.fetch_add(merged.len() as u64, Ordering::Relaxed);
+        let res = if self.dev.queue_depth() == 0 {
+            self.process_sync(&mut reqs, &mut merged)
+        } else {
+            self.process_async(&mut reqs, &mut merged)
+        };
+        // scatter the blocks read into the buffers of the requests
+        for m in merged.iter().filter(|m| m.op == BlockOp::Read) {
+            if let Some(bounce) = &m.bounce {
+                let mut chunks = bounce.as_slice();
+                for &i in &m.parts {
+                    let Request::Read(_, buf) = &mut reqs[i] else {
+                        unreachable!()
+                    };
+                    let (chunk, rest) = chunks.split_at(buf.len());
+                    buf.copy_from_slice(chunk);
+                    chunks = rest;
+                }
+            }
+        }
+        res
+    }
+
+    fn process_sync(&mut self, reqs: &mut [Request], merged: &mut [Merged]) -> DevResult {
+        for m in merged.iter_mut() {
+            let buf = match &mut m.bounce {
+                Some(bounce) => NonNull::from(bounce.as_mut_slice()),
+                None => reqs[m.parts[0]].buf_ptr(),
+            };
+            // SAFETY: the buffer is borrowed by the request or the bounce
+            // buffer, not accessed by others during the call.
+            match m.op {
+                BlockOp::Read => self
+                    .dev
+                    .read_block(m.block_id, unsafe { &mut *buf.as_ptr() })?,
+                BlockOp::Write => self
+                    .dev
+                    .write_block(m.block_id, unsafe { &*buf.as_ptr() })?,
+            }
+        }
+        Ok(())
+    }
+
+    fn process_async(&mut self, reqs: &mut [Request], merged: &mut [Merged]) -> DevResult {
+        let depth = self.dev.queue_depth();
+        let mut next = 0;
+        let mut in_flight = 0;
+        let mut res = Ok(());
+        while next < merged.len() || in_flight > 0 {
+            // fill the queue of the device
+            while next < merged.len() && in_flight < depth && res.is_ok() {
+                let m = &mut merged[next];
+                let buf = match &mut m.bounce {
+                    Some(bounce) => NonNull::from(bounce.as_mut_slice()),
+                    None => reqs[m.parts[0]].buf_ptr(),
+                };
+                // SAFETY: the buffers of the requests and the bounce buffers
+                // are not accessed until all the requests are completed.
+                match unsafe { self.dev.submit(m.op, m.block_id, buf) } {
+                    Ok(_) => {
+                        next += 1;
+                        in_flight += 1;
+                    }
+                    Err(DevError::Again) if in_flight > 0 => break,
+                    Err(e) => res = Err(e),
+                }
+            }
+            if in_flight == 0 {
+                break;
+            }
+            let events = irq::events();
+            let mut completed = false;
+            while let Some((_, r)) = self.dev.poll_completion() {
+                in_flight -= 1;
+                completed = true;
+                if res.is_ok() {
+                    res = r;
+                }
+            }
+            if !completed {
+                irq::wait(events);
+            }
+        }
+        res
+    }
+}
+
+/// Sorts the requests, and merges the adjacent ones of the same operation.
+fn merge(reqs: &[Request]) -> Vec<Merged> {
+    let mut order = (0..reqs.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&i| (reqs[i].op() == BlockOp::Write, reqs[i].block_id()));
+
+    let mut merged = Vec::<Merged>::new();
+    for i in order {
+        let (op, block_id, len) = (reqs[i].op(), reqs[i].block_id(), reqs[i].len());
+        if let Some(last) = merged.last_mut() {
+            let end = last.block_id + (last.len / BLOCK_SIZE) as u64;
+            if last.op == op && end == block_id && last.len + len <= MAX_MERGE_BLOCKS * BLOCK_SIZE {
+                last.len += len;
+                last.parts.push(i);
+                continue;
+            }
+        }
+        merged.push(Merged {
+            op,
+            block_id,
+            len,
+            parts: vec![i],
+            bounce: None,
+        });
+    }
+
+    // gather the blocks to write into the bounce buffers
+    for m in merged.iter_mut().filter(|m| m.parts.len() > 1) {
+        let mut bounce = Vec::with_capacity(m.len);
+        for &i in &m.parts {
+            match &reqs[i] {
+                Request::Write(_, buf) => bounce.extend_from_slice(buf),
+                Request::Read(_, buf) => bounce.resize(bounce.len() + buf.len(), 0),
+            }
+        }
+        m.bounce = Some(bounce);
+    }
+    merged
+}
+
+/// Initializes the interrupt of the disk, to wake up the tasks waiting for
+/// the requests.
+#[cfg(feature = "irq")]
+pub(crate) fn init_irq(irq: axdriver::DeviceIrq) {
+    irq::init(irq);
+}
+
+#[cfg(feature = "irq")]
+mod irq {
+    //! Wakes up the tasks waiting for the requests by the interrupt of the
+    //! disk.
+
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+
+    use axdriver::DeviceIrq;
+    use axtask::WaitQueue;
+    use lazyinit::LazyInit;
+
+    /// The maximum time to sleep, in case of a missed interrupt.
+    const MAX_IDLE_WAIT: Duration = Duration::from_millis(10);
+
+    static IRQ: LazyInit<DeviceIrq> = LazyInit::new();
+    /// Incremented when requests are completed.
+    static EVENTS: AtomicUsize = AtomicUsize::new(0);
+    static EVENT_WAIT: WaitQueue = WaitQueue::new();
+
+    pub(super) fn init(irq: DeviceIrq) {
+        IRQ.init_once(irq);
+        if axhal::irq::register_handler(irq.irq_num, handle_irq) {
+            info!("  block irq: {}", irq.irq_num);
+        }
+    }
+
+    fn handle_irq() {
+        if IRQ.ack() {
+            EVENTS.fetch_add(1, Ordering::AcqRel);
+            EVENT_WAIT.notify_all(false);
+        }
+    }
+
+    pub(super) fn events() -> usize {
+        EVENTS.load(Ordering::Acquire)
+    }
+
+    /// Waits for the interrupt after `events` was read, or yields if there
+    /// is no interrupt.
+    pub(super) fn wait(events: usize) {
+        if !IRQ.is_inited() {
+            axtask::yield_now();
+            return;
+        }
+        EVENT_WAIT.wait_timeout_until(MAX_IDLE_WAIT, || EVENTS.load(Ordering::Acquire) != events);
+    }
+}
+
+#[cfg(not(feature = "irq"))]
+mod irq {
+    //! Polls the device without interrupts.
+
+    pub(super) fn events() -> usize {
+        0
+    }
+
+    pub(super) fn wait(_events: usize) {
+        core::hint::spin_loop();
+    }
+}
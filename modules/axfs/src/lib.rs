@@ -6,10 +6,52 @@
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!    is **enabled** by default.
-//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
+//! - `ext2`: Use [ext2] as the main filesystem and mount it on `/`, instead of
+//!    FAT, with the permissions, the symlinks and the timestamps. This feature
+//!    is **disabled** by default.
+//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`, with the
+//!    character devices registered by [`register_char_device`], including
+//!    `/dev/null`, `/dev/zero`, `/dev/urandom` and `/dev/tty`. This feature is
 //!    **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `tmpfs`: Mount [`tmpfs`] on `/tmp` instead of ramfs, with hard links,
+//!    sparse files and the owners of the files, and use it as the root
+//!    filesystem if there is no block device. It's also mounted on `/dev/shm`
+//!    for the POSIX shared memory. This feature is **disabled** by default.
+//! - `procfs`: Mount a procfs on `/proc`, with `/proc/mounts`. Files generated
+//!    on read can be added by [`add_proc_file`], and sysctls in `/proc/sys` by
+//!    [`add_sysctl`]. The directories of the tasks, `/proc/<pid>` and
+//!    `/proc/self`, are shown after [`set_proc_tasks`] is called. This feature
+//!    is **enabled** by default.
+//! - `sysfs`: Mount a sysfs on `/sys`. Files generated on read can be added by
+//!    [`add_sys_file`], and the writable ones by [`add_sys_attr`]. This feature
+//!    is **enabled** by default.
+//! - `hugetlbfs`: Mount a filesystem of files backed by huge pages on
+//!    `/dev/hugepages`, see [`hugetlbfs`]. The size of the huge page pool is
+//!    set by `/proc/sys/vm/nr_hugepages`. This feature is **disabled** by
+//!    default.
+//! - `ninep`: Mount the folders shared by the host through 9P transports on
+//!    `/mnt/<tag>`, see [`init_shared_folders`]. They can be mounted on other
+//!    paths by [`api::mount_shared_folder`]. This feature is **disabled** by
+//!    default.
+//! - `nfs`: Mount the directories exported by NFSv3 servers by
+//!    [`api::mount_nfs`], over UDP or TCP, after the network is initialized.
+//!    This feature is **disabled** by default.
+//! - `httpfs`: Mount the files served by HTTP servers read-only by
+//!    [`api::mount_http`], fetching the blocks of them by range requests on
+//!    demand and caching them, see [`httpfs`]. This feature is **disabled**
+//!    by default.
+//! - `irq`: Wait for the requests of the disk by its interrupt, instead of
+//!    polling the device, see [`init_block_irq`]. This feature is **disabled**
+//!    by default.
+//! - `writeback`: Write back the dirty blocks of the cache periodically by a
+//!    flusher task, as configured by `/proc/sys/vm/dirty_writeback_centisecs`
+//!    and `/proc/sys/vm/dirty_expire_centisecs`. This feature is **disabled**
+//!    by default.
+//! - `notify`: Report the changes of the files to the watchers registered by
+//!    [`notify::add_watch`], e.g. the inotify instances. This feature is
+//!    **disabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
@@ -17,6 +59,7 @@
 //!    both are enabled.
 //!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [ext2]: https://en.wikipedia.org/wiki/Ext2
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
@@ -26,22 +69,93 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
+mod casefold;
+mod dcache;
 mod dev;
+#[cfg(feature = "devfs")]
+mod devices;
 mod fs;
+mod iosched;
 mod mounts;
 mod root;
+mod statfs;
 
 pub mod api;
 pub mod fops;
-pub use root::{CURRENT_DIR, CURRENT_DIR_PATH};
+#[cfg(feature = "notify")]
+pub mod notify;
+pub use iosched::io_count;
+pub use root::{CURRENT_DIR, CURRENT_DIR_PATH, MAX_SYMLINKS};
+pub use statfs::{FileSystemStat, StatFs};
+
+#[cfg(feature = "procfs")]
+pub use fs::procfs::{
+    ProcTasks, TASK_FILES, add_proc_file, add_sysctl, set_proc_fds, set_proc_tasks,
+};
+
+#[cfg(feature = "sysfs")]
+pub use fs::sysfs::{add_sys_attr, add_sys_file};
+
+#[cfg(feature = "devfs")]
+pub use devices::{CharDevice, char_device, register_char_device};
+
+#[cfg(feature = "hugetlbfs")]
+pub use fs::hugetlbfs;
+
+#[cfg(feature = "tmpfs")]
+pub use fs::tmpfs;
+
+#[cfg(feature = "nfs")]
+pub use fs::nfs::NfsTransport;
+
+#[cfg(feature = "httpfs")]
+pub use fs::httpfs;
 
 use axdriver::{AxDeviceContainer, prelude::*};
 
 /// Initializes filesystems by block devices.
+///
+/// If there is no block device, tmpfs is used as the root filesystem when the
+/// `tmpfs` feature is enabled.
 pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     info!("Initialize filesystems...");
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    let disk = blk_devs.take_one().map(|dev| {
+        info!("  use block device 0: {:?}", dev.device_name());
+        self::dev::Disk::new(dev)
+    });
+    self::root::init_rootfs(disk);
+    #[cfg(feature = "writeback")]
+    self::cache::start_flusher();
+}
+
+/// Completes the requests of the disk by its interrupt, instead of polling.
+///
+/// It must be called after [`init_filesystems`], with the interrupt of the
+/// block device used.
+#[cfg(feature = "irq")]
+pub fn init_block_irq(irq: axdriver::DeviceIrq) {
+    self::iosched::init_irq(irq);
+}
+
+/// Connects to the folders shared by the host through 9P transports, and
+/// mounts each of them on `/mnt/<tag>`, where `<tag>` is its mount tag.
+///
+/// It must be called after [`init_filesystems`].
+#[cfg(feature = "ninep")]
+pub fn init_shared_folders(mut ninep_devs: AxDeviceContainer<AxNinePDevice>) {
+    info!("Initialize shared folders...");
+
+    while let Some(dev) = ninep_devs.take_one() {
+        info!("  use 9P transport: {:?}", dev.device_name());
+        let res = fs::ninep::add_device(dev).and_then(|tag| {
+            let path = alloc::format!("/mnt/{}", tag);
+            info!("  mount shared folder {:?} on {}", tag, path);
+            root::mount(&path, fs::ninep::new_mount(&tag)?, "9p")
+        });
+        if let Err(e) = res {
+            warn!("failed to mount the shared folder: {:?}", e);
+        }
+    }
 }
@@ -5,22 +5,15 @@ use crate::fs;
 
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
-    let null = fs::devfs::NullDev;
-    let zero = fs::devfs::ZeroDev;
-    let bar = fs::devfs::ZeroDev;
-    let devfs = fs::devfs::DeviceFileSystem::new();
-    let foo_dir = devfs.mkdir("foo");
-    devfs.add("null", Arc::new(null));
-    devfs.add("zero", Arc::new(zero));
-    foo_dir.add("bar", Arc::new(bar));
-    // hwclock: /dev/misc/rtc
-    let rtc = fs::devfs::ZeroDev;
-    let misc = devfs.mkdir("misc");
-    misc.add("rtc", Arc::new(rtc));
-    // shm
-    let shm = fs::ramfs::RamFileSystem::new();
-    devfs.add("shm", shm.root_dir_node());
-    Arc::new(devfs)
+    // the character devices are added by the registry
+    let devfs = crate::devices::devfs();
+    // shm, where a tmpfs is mounted instead if enabled
+    #[cfg(not(feature = "tmpfs"))]
+    {
+        let shm = fs::ramfs::RamFileSystem::new();
+        devfs.add("shm", shm.root_dir_node());
+    }
+    devfs
 }
 
 #[cfg(feature = "ramfs")]
@@ -28,8 +21,13 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
     Arc::new(fs::ramfs::RamFileSystem::new())
 }
 
+#[cfg(feature = "tmpfs")]
+pub(crate) fn tmpfs() -> Arc<fs::tmpfs::TmpFileSystem> {
+    Arc::new(fs::tmpfs::TmpFileSystem::new())
+}
+
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
+pub(crate) fn procfs() -> VfsResult<Arc<fs::procfs::ProcFileSystem>> {
     let procfs = fs::ramfs::RamFileSystem::new();
     let proc_root = procfs.root_dir();
 
@@ -47,23 +45,74 @@ pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
     let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
     file_over.write_at(0, b"0\n")?;
 
-    // Create /proc/meminfo
-    // TODO: stub
-    proc_root.create("meminfo", VfsNodeType::File)?;
+    // Create /proc/sysvipc/{sem,msg,shm}, updated by the System V IPC syscalls
+    proc_root.create("sysvipc", VfsNodeType::Dir)?;
+    proc_root.create("sysvipc/sem", VfsNodeType::File)?;
+    proc_root.create("sysvipc/msg", VfsNodeType::File)?;
+    proc_root.create("sysvipc/shm", VfsNodeType::File)?;
 
-    // Create /proc/mounts
-    // TODO: stub
-    proc_root.create("mounts", VfsNodeType::File)?;
-
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
+    // The root is a devfs directory holding the entries above, so that files
+    // generated on read can be added later by `add_proc_file`.
+    let procfs_root = fs::devfs::DeviceFileSystem::new();
+    procfs_root.add("sysvipc", proc_root.clone().lookup("sysvipc")?);
+    procfs_root.add(
+        "mounts",
+        Arc::new(fs::procfs::ProcFileNode::new(crate::root::gen_mounts)),
+    );
+    // So are `/proc/sys`, `/proc/sys/net` and `/proc/sys/vm`, for the sysctls
+    // backed by the kernel state.
+    let sys = procfs_root.mkdir("sys");
+    let net = sys.mkdir("net");
+    net.add("core", proc_root.clone().lookup("sys/net/core")?);
+    let vm = sys.mkdir("vm");
+    vm.add(
+        "overcommit_memory",
+        proc_root.clone().lookup("sys/vm/overcommit_memory")?,
+    );
+    #[cfg(feature = "hugetlbfs")]
+    vm.add("nr_hugepages", Arc::new(fs::hugetlbfs::NrHugePagesNode));
+    // Other sysctls are added later by `add_sysctl`.
+    fs::procfs::SYSCTL_DIRS
+        .lock()
+        .extend([("", sys), ("net", net), ("vm", vm)]);
+    fs::procfs::add_sysctl(
+        "vm/drop_caches",
+        crate::dcache::read_drop_caches,
+        crate::dcache::write_drop_caches,
+    );
+    {
+        use crate::cache::sysctl::*;
+        fs::procfs::add_sysctl("vm/read_ahead_kb", read_read_ahead_kb, write_read_ahead_kb);
+        fs::procfs::add_sysctl("vm/dirty_ratio", read_dirty_ratio, write_dirty_ratio);
+        fs::procfs::add_sysctl(
+            "vm/dirty_background_ratio",
+            read_dirty_background_ratio,
+            write_dirty_background_ratio,
+        );
+        fs::procfs::add_sysctl(
+            "vm/dirty_writeback_centisecs",
+            read_dirty_writeback_centisecs,
+            write_dirty_writeback_centisecs,
+        );
+        fs::procfs::add_sysctl(
+            "vm/dirty_expire_centisecs",
+            read_dirty_expire_centisecs,
+            write_dirty_expire_centisecs,
+        );
+    }
+    let procfs_root = Arc::new(procfs_root);
+    fs::procfs::PROC_ROOT.init_once(procfs_root.clone());
+    // `/proc/self` and `/proc/<pid>` are generated on lookup
+    Ok(Arc::new(fs::procfs::ProcFileSystem::new(procfs_root)))
+}
 
-    Ok(Arc::new(procfs))
+#[cfg(feature = "hugetlbfs")]
+pub(crate) fn hugetlbfs() -> Arc<fs::hugetlbfs::HugetlbFileSystem> {
+    Arc::new(fs::hugetlbfs::HugetlbFileSystem::new())
 }
 
 #[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
+pub(crate) fn sysfs() -> VfsResult<Arc<fs::devfs::DeviceFileSystem>> {
     let sysfs = fs::ramfs::RamFileSystem::new();
     let sys_root = sysfs.root_dir();
 
@@ -91,5 +140,13 @@ pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
         .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
     file_cc.write_at(0, b"tsc\n")?;
 
-    Ok(Arc::new(sysfs))
+    // The root is a devfs directory holding the entries above, so that files
+    // generated on read can be added later by `add_sys_file`.
+    let sysfs_root = fs::devfs::DeviceFileSystem::new();
+    for name in ["kernel", "devices"] {
+        sysfs_root.add(name, sys_root.clone().lookup(name)?);
+    }
+    let sysfs_root = Arc::new(sysfs_root);
+    fs::sysfs::SYS_ROOT.init_once(sysfs_root.clone());
+    Ok(sysfs_root)
 }
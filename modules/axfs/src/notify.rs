@@ -0,0 +1,246 @@
+//! Notifications of the changes of the files, like `inotify(7)`.
+//!
+//! The watches are keyed by the absolute paths of the watched files, without
+//! the symlinks, and follow them when they are renamed. The events are
+//! generated by the operations on the paths, and by the writes and the
+//! closes of the opened files. The changes by the paths relative to the
+//! opened directories, e.g. by `unlinkat(2)`, are not reported, as their
+//! absolute paths are unknown.
+
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
+
+use axerrno::{AxResult, ax_err};
+use spin::RwLock;
+
+// The events and the flags of the watches, with the values of `inotify(7)`.
+
+/// The file is written, or truncated.
+pub const IN_MODIFY: u32 = 0x0000_0002;
+/// The file opened for writing is closed.
+pub const IN_CLOSE_WRITE: u32 = 0x0000_0008;
+/// An entry is renamed from the watched directory.
+pub const IN_MOVED_FROM: u32 = 0x0000_0040;
+/// An entry is renamed to the watched directory.
+pub const IN_MOVED_TO: u32 = 0x0000_0080;
+/// An entry is created in the watched directory.
+pub const IN_CREATE: u32 = 0x0000_0100;
+/// An entry is removed from the watched directory.
+pub const IN_DELETE: u32 = 0x0000_0200;
+/// The watched file itself is removed.
+pub const IN_DELETE_SELF: u32 = 0x0000_0400;
+/// The watched file itself is renamed.
+pub const IN_MOVE_SELF: u32 = 0x0000_0800;
+/// The watch is removed, explicitly or as the file is removed.
+pub const IN_IGNORED: u32 = 0x0000_8000;
+/// Only watches the path if it's a directory.
+pub const IN_ONLYDIR: u32 = 0x0100_0000;
+/// Does not follow the symlink of the path.
+pub const IN_DONT_FOLLOW: u32 = 0x0200_0000;
+/// Adds the events to the mask of an existing watch, instead of replacing it.
+pub const IN_MASK_ADD: u32 = 0x2000_0000;
+/// The entry of the event is a directory.
+pub const IN_ISDIR: u32 = 0x4000_0000;
+/// Removes the watch after the first event.
+pub const IN_ONESHOT: u32 = 0x8000_0000;
+/// Fails if the path is already watched.
+pub const IN_MASK_CREATE: u32 = 0x1000_0000;
+
+/// The events reported to the watchers.
+const IN_ALL_EVENTS: u32 = IN_MODIFY
+    | IN_CLOSE_WRITE
+    | IN_MOVED_FROM
+    | IN_MOVED_TO
+    | IN_CREATE
+    | IN_DELETE
+    | IN_DELETE_SELF
+    | IN_MOVE_SELF;
+
+/// A receiver of the events of the watched files, e.g. an inotify instance.
+pub trait Watcher: Send + Sync {
+    /// Receives the event `mask` of the watch `wd`, about the entry `name` in
+    /// the watched directory, or about the watched file itself if `name` is
+    /// empty. The two events of a rename have the same nonzero `cookie`.
+    fn notify(&self, wd: i32, mask: u32, cookie: u32, name: &str);
+}
+
+struct Watch {
+    watcher: Weak<dyn Watcher>,
+    wd: i32,
+    mask: u32,
+}
+
+impl Watch {
+    fn is_of(&self, watcher: &Arc<dyn Watcher>) -> bool {
+        Weak::as_ptr(&self.watcher) as *const () == Arc::as_ptr(watcher) as *const ()
+    }
+}
+
+/// The watches of each path.
+static WATCHES: RwLock<BTreeMap<String, Vec<Watch>>> = RwLock::new(BTreeMap::new());
+/// The number of the watches, to skip the events if there are none.
+static NUM_WATCHES: AtomicUsize = AtomicUsize::new(0);
+static NEXT_WD: AtomicI32 = AtomicI32::new(1);
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// Watches the events in `mask` of the file or the directory at `path`,
+/// returning the watch descriptor.
+///
+/// If `watcher` already watches the file, the mask of the watch is replaced,
+/// or added to with [`IN_MASK_ADD`], and its descriptor is returned.
+pub fn add_watch(path: &str, mask: u32, watcher: &Arc<dyn Watcher>) -> AxResult<i32> {
+    if mask & IN_ALL_EVENTS == 0 || mask & IN_MASK_ADD != 0 && mask & IN_MASK_CREATE != 0 {
+        return ax_err!(InvalidInput);
+    }
+    let follow = mask & IN_DONT_FOLLOW == 0;
+    let node = crate::root::lookup_at(None, path, follow)?;
+    if mask & IN_ONLYDIR != 0 && !node.get_attr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    let path = crate::root::resolve_symlinks(path, follow)?;
+    let path = match path.trim_end_matches('/') {
+        "" => String::from("/"),
+        path => String::from(path),
+    };
+
+    let events = mask & (IN_ALL_EVENTS | IN_ONESHOT);
+    let mut watches = WATCHES.write();
+    let entries = watches.entry(path).or_default();
+    if let Some(watch) = entries.iter_mut().find(|w| w.is_of(watcher)) {
+        if mask & IN_MASK_CREATE != 0 {
+            return ax_err!(AlreadyExists);
+        }
+        if mask & IN_MASK_ADD != 0 {
+            watch.mask |= events;
+        } else {
+            watch.mask = events;
+        }
+        return Ok(watch.wd);
+    }
+    let wd = NEXT_WD.fetch_add(1, Ordering::Relaxed);
+    entries.push(Watch {
+        watcher: Arc::downgrade(watcher),
+        wd,
+        mask: events,
+    });
+    NUM_WATCHES.fetch_add(1, Ordering::Relaxed);
+    Ok(wd)
+}
+
+/// Removes the watch `wd` of `watcher`, which receives [`IN_IGNORED`].
+pub fn remove_watch(watcher: &Arc<dyn Watcher>, wd: i32) -> AxResult {
+    let mut watches = WATCHES.write();
+    let removed = watches.values_mut().any(|entries| {
+        let len = entries.len();
+        entries.retain(|w| !(w.wd == wd && w.is_of(watcher)));
+        entries.len() != len
+    });
+    watches.retain(|_, entries| !entries.is_empty());
+    drop(watches);
+    if !removed {
+        return ax_err!(InvalidInput);
+    }
+    NUM_WATCHES.fetch_sub(1, Ordering::Relaxed);
+    watcher.notify(wd, IN_IGNORED, 0, "");
+    Ok(())
+}
+
+/// Returns a new cookie relating the two events of a rename.
+pub(crate) fn next_cookie() -> u32 {
+    NEXT_COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Reports the event `mask` of the file at the absolute `path` to the
+/// watches of it, and to those of its directory.
+pub(crate) fn notify(path: &str, mask: u32, cookie: u32, is_dir: bool) {
+    if NUM_WATCHES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let path = match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    };
+    let (dir, name) = match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((dir, name)) => (dir, name),
+        None => return,
+    };
+    let self_mask = match mask {
+        IN_DELETE => IN_DELETE_SELF,
+        IN_MOVED_FROM => IN_MOVE_SELF,
+        IN_CREATE | IN_MOVED_TO => 0,
+        mask => mask,
+    };
+    let dir_mask = if is_dir { mask | IN_ISDIR } else { mask };
+
+    let mut events = Vec::new();
+    let mut ignored = Vec::new();
+    let mut watches = WATCHES.write();
+    // the watches of the file itself get no name, and are removed with it
+    let mut deliver = |key: &str, event: u32, to_dir: bool, remove: bool| {
+        let Some(entries) = watches.get_mut(key) else {
+            return;
+        };
+        entries.retain(|w| {
+            let Some(watcher) = w.watcher.upgrade() else {
+                return false;
+            };
+            if w.mask & event & IN_ALL_EVENTS != 0 {
+                let name = if to_dir { name } else { "" };
+                events.push((watcher.clone(), w.wd, event, name));
+            } else if !remove {
+                return true;
+            }
+            let keep = !remove && w.mask & IN_ONESHOT == 0;
+            if !keep {
+                ignored.push((watcher, w.wd));
+            }
+            keep
+        });
+    };
+    if self_mask != 0 {
+        deliver(path, self_mask, false, self_mask == IN_DELETE_SELF);
+    }
+    if dir != path {
+        deliver(dir, dir_mask, true, false);
+    }
+    watches.retain(|_, entries| !entries.is_empty());
+    let count = watches.values().map(Vec::len).sum();
+    NUM_WATCHES.store(count, Ordering::Relaxed);
+    drop(watches);
+
+    for (watcher, wd, event, name) in events {
+        watcher.notify(wd, event, cookie, name);
+    }
+    for (watcher, wd) in ignored {
+        watcher.notify(wd, IN_IGNORED, 0, "");
+    }
+}
+
+/// Moves the watches of the file at `old`, and of the files under it, to
+/// `new` after the file is renamed.
+pub(crate) fn rename(old: &str, new: &str) {
+    if NUM_WATCHES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let (old, new) = (old.trim_end_matches('/'), new.trim_end_matches('/'));
+    let mut watches = WATCHES.write();
+    let moved = watches
+        .keys()
+        .filter(|key| {
+            key.strip_prefix(old)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for key in moved {
+        let entries = watches.remove(&key).unwrap();
+        let key = String::from(new) + &key[old.len()..];
+        watches.entry(key).or_default().extend(entries);
+    }
+}
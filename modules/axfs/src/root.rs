@@ -3,7 +3,7 @@
 //! TODO: it doesn't work very well if the mount points have containment relationships.
 
 use alloc::string::ToString;
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{AxError, AxResult, ax_err};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axns::{ResArc, def_resource};
@@ -13,10 +13,20 @@ use spin::RwLock;
 
 use crate::{
     api::FileType,
+    casefold::{self, CaseFolder},
+    dcache,
+    fops::FileId,
     fs::{self},
     mounts,
+    statfs::{FileSystemStat, StatFs},
 };
 
+/// The maximum number of symlinks followed in a lookup, `MAXSYMLINKS` of
+/// Linux.
+pub const MAX_SYMLINKS: usize = 40;
+/// The maximum size of the target of a symlink, `PATH_MAX` of Linux.
+const MAX_LINK_LEN: usize = 4096;
+
 def_resource! {
     pub static CURRENT_DIR_PATH: ResArc<Mutex<String>> = ResArc::new();
     pub static CURRENT_DIR: ResArc<Mutex<VfsNodeRef>> = ResArc::new();
@@ -39,18 +49,38 @@ impl CURRENT_DIR {
 struct MountPoint {
     path: &'static str,
     fs: Arc<dyn VfsOps>,
+    stat: Arc<dyn StatFs>,
+    /// The type of the filesystem shown in `/proc/mounts`, e.g. `tmpfs`.
+    fstype: &'static str,
+    /// Set if the names are looked up case-insensitively.
+    folder: Option<Arc<CaseFolder>>,
 }
 
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
+    main_stat: Arc<dyn StatFs>,
+    /// The source and the type of the main filesystem in `/proc/mounts`.
+    main_source: &'static str,
+    main_fstype: &'static str,
+    main_folder: RwLock<Option<Arc<CaseFolder>>>,
     mounts: RwLock<Vec<MountPoint>>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new<T: VfsOps + StatFs + 'static>(
+        path: &'static str,
+        fs: Arc<T>,
+        fstype: &'static str,
+    ) -> Self {
+        Self {
+            path,
+            fs: fs.clone(),
+            stat: fs,
+            fstype,
+            folder: None,
+        }
     }
 }
 
@@ -61,14 +91,28 @@ impl Drop for MountPoint {
 }
 
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub const fn new(
+        main_fs: Arc<dyn VfsOps>,
+        main_stat: Arc<dyn StatFs>,
+        main_source: &'static str,
+        main_fstype: &'static str,
+    ) -> Self {
         Self {
             main_fs,
+            main_stat,
+            main_source,
+            main_fstype,
+            main_folder: RwLock::new(None),
             mounts: RwLock::new(Vec::new()),
         }
     }
 
-    pub fn mount(&self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    pub fn mount<T: VfsOps + StatFs + 'static>(
+        &self,
+        path: &'static str,
+        fs: Arc<T>,
+        fstype: &'static str,
+    ) -> AxResult {
         if path == "/" {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
@@ -81,7 +125,8 @@ impl RootDirectory {
         // create the mount point in the main filesystem if it does not exist
         self.main_fs.root_dir().create(path, FileType::Dir)?;
         fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.write().push(MountPoint::new(path, fs));
+        self.mounts.write().push(MountPoint::new(path, fs, fstype));
+        dcache::invalidate_all();
         Ok(())
     }
 
@@ -93,9 +138,88 @@ impl RootDirectory {
         self.mounts.read().iter().any(|mp| mp.path == path)
     }
 
+    /// Sets whether the names in the filesystem mounted at `path` are looked
+    /// up case-insensitively, where `/` is the main filesystem.
+    pub fn set_case_insensitive(&self, path: &str, enabled: bool) -> AxResult {
+        let folder = enabled.then(|| Arc::new(CaseFolder::new()));
+        if path.trim_end_matches('/').is_empty() {
+            *self.main_folder.write() = folder;
+            dcache::invalidate_all();
+            return Ok(());
+        }
+        let path = path.trim_end_matches('/');
+        let mut mounts = self.mounts.write();
+        let Some(mp) = mounts.iter_mut().find(|mp| mp.path == path) else {
+            return ax_err!(InvalidInput, "not a mount point");
+        };
+        mp.folder = folder;
+        dcache::invalidate_all();
+        Ok(())
+    }
+
+    /// Returns the key of the absolute `path` in the dcache, where the names
+    /// in a case-insensitive filesystem are folded, so that all the spellings
+    /// share one entry.
+    fn dcache_key(&self, path: &str) -> String {
+        let mounts = self.mounts.read();
+        let mp = mounts
+            .iter()
+            .filter(|mp| {
+                path.strip_prefix(mp.path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|mp| mp.path.len());
+        let (prefix_len, folded) = match mp {
+            Some(mp) => (mp.path.len(), mp.folder.is_some()),
+            None => (0, self.main_folder.read().is_some()),
+        };
+        if folded {
+            path[..prefix_len].to_string() + &casefold::fold_name(&path[prefix_len..])
+        } else {
+            path.into()
+        }
+    }
+
+    /// Returns the statistics of the filesystem holding the absolute `path`.
+    fn statfs(&self, path: &str) -> AxResult<FileSystemStat> {
+        let mounts = self.mounts.read();
+        let mp = mounts
+            .iter()
+            .filter(|mp| {
+                path.strip_prefix(mp.path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|mp| mp.path.len());
+        let (stat, fstype) = match mp {
+            Some(mp) => (mp.stat.statfs()?, mp.fstype),
+            None => (self.main_stat.statfs()?, self.main_fstype),
+        };
+        Ok(FileSystemStat { fstype, ..stat })
+    }
+
+    /// Returns the identity of the file `node` at the absolute `path`.
+    fn file_id(&self, node: &VfsNodeRef, path: &str) -> FileId {
+        let mounts = self.mounts.read();
+        let mp = mounts
+            .iter()
+            .filter(|mp| {
+                path.strip_prefix(mp.path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|mp| mp.path.len());
+        let (fs, fstype, rest) = match mp {
+            Some(mp) => (&mp.fs, mp.fstype, &path[mp.path.len()..]),
+            None => (&self.main_fs, self.main_fstype, path),
+        };
+        FileId {
+            fs: Arc::as_ptr(fs) as *const u8 as usize,
+            ino: node_ino(node, fstype, rest),
+        }
+    }
+
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
     where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
+        F: FnOnce(Arc<dyn VfsOps>, &str, Option<&CaseFolder>) -> AxResult<T>,
     {
         debug!("lookup at root: {}", path);
         let path = path.trim_matches('/');
@@ -118,10 +242,24 @@ impl RootDirectory {
             }
         }
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), &path) // not matched any mount point
+        let (fs, rest_path, folder) = if max_len == 0 {
+            // not matched any mount point
+            (self.main_fs.clone(), path, self.main_folder.read().clone())
         } else {
-            f(self.mounts.read()[idx].fs.clone(), &path[max_len..]) // matched at `idx`
+            // matched at `idx`
+            let mounts = self.mounts.read();
+            (
+                mounts[idx].fs.clone(),
+                &path[max_len..],
+                mounts[idx].folder.clone(),
+            )
+        };
+        match folder {
+            Some(folder) => {
+                let rest_path = folder.resolve(&fs.root_dir(), rest_path)?;
+                f(fs, &rest_path, Some(&*folder))
+            }
+            None => f(fs, rest_path, None),
         }
     }
 }
@@ -134,11 +272,11 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        self.lookup_mounted_fs(path, |fs, rest_path| fs.root_dir().lookup(rest_path))
+        self.lookup_mounted_fs(path, |fs, rest_path, _| fs.root_dir().lookup(rest_path))
     }
 
     fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
+        self.lookup_mounted_fs(path, |fs, rest_path, _| {
             if rest_path.is_empty() {
                 Ok(()) // already exists
             } else {
@@ -148,64 +286,133 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn remove(&self, path: &str) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
+        self.lookup_mounted_fs(path, |fs, rest_path, folder| {
             if rest_path.is_empty() {
                 ax_err!(PermissionDenied) // cannot remove mount points
             } else {
+                if let Some(folder) = folder {
+                    folder.invalidate();
+                }
                 fs.root_dir().remove(rest_path)
             }
         })
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
+        // the destination relative to the root of its filesystem
+        let (dst_fs, dst_path) = self.lookup_mounted_fs(dst_path, |fs, rest_path, _| {
+            Ok((fs, String::from("/") + rest_path.trim_start_matches('/')))
+        })?;
+        self.lookup_mounted_fs(src_path, |fs, rest_path, folder| {
             if rest_path.is_empty() {
                 ax_err!(PermissionDenied) // cannot rename mount points
+            } else if !Arc::ptr_eq(&fs, &dst_fs) {
+                ax_err!(Unsupported, "cannot rename across filesystems")
             } else {
-                fs.root_dir().rename(rest_path, dst_path)
+                if let Some(folder) = folder {
+                    folder.invalidate();
+                }
+                fs.root_dir().rename(rest_path, &dst_path)
             }
         })
     }
 }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+/// Opens the main filesystem on `disk`, and returns it with its statistics
+/// and its type.
+fn disk_fs(disk: crate::dev::Disk) -> (Arc<dyn VfsOps>, Arc<dyn StatFs>, &'static str) {
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
+            let main_fs: (Arc<dyn VfsOps>, Arc<dyn StatFs>, _) =
+                (fs::myfs::new_myfs(disk), Arc::new(crate::statfs::UnknownStat), "myfs");
         } else if #[cfg(feature = "lwext4_rs")] {
             static EXT4_FS: LazyInit<Arc<fs::lwext4_rust::Ext4FileSystem>> = LazyInit::new();
             EXT4_FS.init_once(Arc::new(fs::lwext4_rust::Ext4FileSystem::new(disk)));
-            let main_fs = EXT4_FS.clone();
+            let main_fs: (Arc<dyn VfsOps>, Arc<dyn StatFs>, _) =
+                (EXT4_FS.clone(), EXT4_FS.clone(), "ext4");
+        } else if #[cfg(feature = "ext2")] {
+            static EXT2_FS: LazyInit<Arc<fs::ext2::Ext2FileSystem>> = LazyInit::new();
+            EXT2_FS.init_once(Arc::new(fs::ext2::Ext2FileSystem::new(disk)));
+            let main_fs: (Arc<dyn VfsOps>, Arc<dyn StatFs>, _) =
+                (EXT2_FS.clone(), EXT2_FS.clone(), "ext2");
         } else if #[cfg(feature = "fatfs")] {
             static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
             FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
             FAT_FS.init();
-            let main_fs = FAT_FS.clone();
+            let main_fs: (Arc<dyn VfsOps>, Arc<dyn StatFs>, _) =
+                (FAT_FS.clone(), FAT_FS.clone(), "vfat");
         }
     }
+    main_fs
+}
 
-    let root_dir = RootDirectory::new(main_fs);
+pub(crate) fn init_rootfs(disk: Option<crate::dev::Disk>) {
+    #[cfg(all(
+        feature = "fatfs",
+        not(any(feature = "myfs", feature = "lwext4_rs", feature = "ext2"))
+    ))]
+    let on_fat = disk.is_some();
+    let (main_fs, main_stat, main_source, main_fstype): (Arc<dyn VfsOps>, Arc<dyn StatFs>, _, _) =
+        match disk {
+            Some(disk) => {
+                let (main_fs, main_stat, fstype) = disk_fs(disk);
+                (main_fs, main_stat, "/dev/root", fstype)
+            }
+            #[cfg(feature = "tmpfs")]
+            None => {
+                info!("  no block device, use tmpfs as the root filesystem");
+                let tmpfs = mounts::tmpfs();
+                (tmpfs.clone(), tmpfs, "rootfs", "tmpfs")
+            }
+            #[cfg(not(feature = "tmpfs"))]
+            None => panic!("No block device found!"),
+        };
+
+    let root_dir = RootDirectory::new(main_fs, main_stat, main_source, main_fstype);
+
+    // FAT is case-insensitive, make the other spellings normalized as well
+    #[cfg(all(
+        feature = "fatfs",
+        not(any(feature = "myfs", feature = "lwext4_rs", feature = "ext2"))
+    ))]
+    if on_fat {
+        root_dir.set_case_insensitive("/", true).unwrap();
+    }
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount("/dev", mounts::devfs(), "devtmpfs")
         .expect("failed to mount devfs at /dev");
 
-    #[cfg(feature = "ramfs")]
+    #[cfg(feature = "hugetlbfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
+        .mount("/dev/hugepages", mounts::hugetlbfs(), "hugetlbfs")
+        .expect("failed to mount hugetlbfs at /dev/hugepages");
+
+    #[cfg(feature = "tmpfs")]
+    root_dir
+        .mount("/tmp", mounts::tmpfs(), "tmpfs")
+        .expect("failed to mount tmpfs at /tmp");
+    #[cfg(all(feature = "devfs", feature = "tmpfs"))]
+    root_dir
+        .mount("/dev/shm", mounts::tmpfs(), "tmpfs")
+        .expect("failed to mount tmpfs at /dev/shm");
+
+    #[cfg(all(feature = "ramfs", not(feature = "tmpfs")))]
+    root_dir
+        .mount("/tmp", mounts::ramfs(), "ramfs")
         .expect("failed to mount ramfs at /tmp");
 
     // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
     root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+        .mount("/proc", mounts::procfs().unwrap(), "proc")
         .expect("fail to mount procfs at /proc");
 
     // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount("/sys", mounts::sysfs().unwrap(), "sysfs")
         .expect("fail to mount sysfs at /sys");
 
     ROOT_DIR.init_once(Arc::new(root_dir));
@@ -215,6 +422,43 @@ pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
     CURRENT_DIR_PATH.init_new(Mutex::new("/".into()));
 }
 
+/// Mounts `fs` of the type `fstype` on `path` after the root filesystem is
+/// initialized, creating the directories of `path` in the main filesystem if
+/// they do not exist.
+#[cfg(any(feature = "ninep", feature = "nfs", feature = "httpfs"))]
+pub(crate) fn mount<T: VfsOps + StatFs + 'static>(
+    path: &str,
+    fs: Arc<T>,
+    fstype: &'static str,
+) -> AxResult {
+    let path = absolute_path(path)?;
+    let path = path.trim_end_matches('/');
+    let main_root = ROOT_DIR.main_fs.root_dir();
+    for (i, _) in path.match_indices('/').skip(1) {
+        match main_root.create(&path[..i], VfsNodeType::Dir) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    // mounts are never dropped, as unmounting is not supported
+    ROOT_DIR.mount(path.to_string().leak(), fs, fstype)
+}
+
+/// Generates `/proc/mounts`, the main filesystem and the ones mounted on it,
+/// in the order they are mounted.
+#[cfg(feature = "procfs")]
+pub(crate) fn gen_mounts() -> String {
+    use core::fmt::Write;
+
+    let mut out = String::new();
+    let (source, fstype) = (ROOT_DIR.main_source, ROOT_DIR.main_fstype);
+    writeln!(out, "{source} / {fstype} rw 0 0").ok();
+    for mp in ROOT_DIR.mounts.read().iter() {
+        writeln!(out, "{} {} {} rw 0 0", mp.fstype, mp.path, mp.fstype).ok();
+    }
+    out
+}
+
 fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
     if path.starts_with('/') {
         ROOT_DIR.clone()
@@ -232,11 +476,158 @@ pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     }
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Returns the dcache key of `path`, or `None` if it's relative to `dir`,
+/// whose path is unknown.
+fn dcache_key(dir: Option<&VfsNodeRef>, path: &str) -> Option<String> {
+    if dir.is_some() && !path.starts_with('/') {
+        return None;
+    }
+    let path = absolute_path(path).ok()?;
+    let path = match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    };
+    // the entries of procfs are generated on lookup, e.g. `/proc/self`
+    #[cfg(feature = "procfs")]
+    if path.starts_with("/proc/") {
+        return None;
+    }
+    Some(ROOT_DIR.dcache_key(path))
+}
+
+/// Drops the dcache entries of `path` after it's changed.
+fn invalidate_dcache(dir: Option<&VfsNodeRef>, path: &str) {
+    match dcache_key(dir, path) {
+        Some(key) => dcache::invalidate(&key),
+        None => dcache::invalidate_all(),
+    }
+}
+
+fn lookup_cached(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    let Some(key) = dcache_key(dir, path) else {
+        return parent_node_of(dir, path).lookup(path);
+    };
+    if let Some(node) = dcache::get(&key) {
+        return node.ok_or(AxError::NotFound);
+    }
+    let res = parent_node_of(dir, path).lookup(path);
+    match &res {
+        Ok(node) => dcache::insert(key, Some(node.clone())),
+        Err(AxError::NotFound) => dcache::insert(key, None),
+        Err(_) => {}
+    }
+    res
+}
+
+/// Reports the event `mask` of the file at `path` to its watches, unless the
+/// path is relative to `dir`, whose path is unknown.
+#[cfg(feature = "notify")]
+fn notify_at(dir: Option<&VfsNodeRef>, path: &str, mask: u32, is_dir: bool) {
+    if dir.is_none() {
+        if let Ok(path) = absolute_path(path) {
+            crate::notify::notify(&path, mask, 0, is_dir);
+        }
+    }
+}
+
+/// Reads the target of the symlink `node`.
+pub(crate) fn read_link_node(node: &VfsNodeRef) -> AxResult<String> {
+    let size = node.get_attr()?.size() as usize;
+    if size > MAX_LINK_LEN {
+        return ax_err!(InvalidData, "symlink target too long");
+    }
+    let mut buf = vec![0; size];
+    let len = node.read_at(0, &mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// Resolves the symlinks in the directories of `path`, and in its last
+/// component if `follow` is set, or if it ends with a slash.
+///
+/// Returns the path without the symlinks, which is relative to the directory
+/// returned if it's still relative. If a component is not found, the rest of
+/// the path is kept as is, e.g. to create the last component. It fails with
+/// [`AxError::InvalidData`] if more than [`MAX_SYMLINKS`] symlinks are met,
+/// as there is no error kind for the loops.
+fn resolve(
+    dir: Option<&VfsNodeRef>,
+    path: &str,
+    follow: bool,
+) -> AxResult<(Option<VfsNodeRef>, String)> {
+    // keep the trailing slash dropped by the canonicalization
+    let canonicalize = |path: String| {
+        let mut canonical = absolute_path(&path)?;
+        if path.ends_with('/') && !canonical.ends_with('/') {
+            canonical.push('/');
+        }
+        AxResult::Ok(canonical)
+    };
+    let mut dir = dir.filter(|_| !path.starts_with('/')).cloned();
+    let mut path = match dir {
+        Some(_) => String::from(path),
+        None => canonicalize(String::from(path))?,
+    };
+    let mut links = 0;
+    'walk: loop {
+        let mut end = 0;
+        loop {
+            let Some(skip) = path[end..].find(|c| c != '/') else {
+                break 'walk;
+            };
+            let begin = end + skip;
+            end = path[begin..].find('/').map_or(path.len(), |i| begin + i);
+            if matches!(&path[begin..end], "." | "..") {
+                continue;
+            }
+            let rest = &path[end..];
+            let is_last = rest.trim_start_matches('/').is_empty();
+            if is_last && !follow && rest.is_empty() {
+                break 'walk;
+            }
+            let node = match lookup_cached(dir.as_ref(), &path[..end]) {
+                Ok(node) => node,
+                Err(AxError::NotFound) if is_last => break 'walk,
+                Err(e) => return Err(e),
+            };
+            if !node.get_attr()?.file_type().is_symlink() {
+                continue;
+            }
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return ax_err!(InvalidData, "too many levels of symbolic links");
+            }
+            let target = read_link_node(&node)?;
+            if target.is_empty() {
+                return ax_err!(NotFound);
+            }
+            // the target replaces the symlink, then walk the new path again
+            path = if target.starts_with('/') {
+                dir = None;
+                canonicalize(target + rest)?
+            } else if dir.is_some() {
+                path[..begin].to_string() + &target + rest
+            } else {
+                canonicalize(path[..begin].to_string() + &target + rest)?
+            };
+            continue 'walk;
+        }
+    }
+    Ok((dir, path))
+}
+
+/// Looks up `path`, following the symlink of the last component if `follow`
+/// is set.
+pub(crate) fn lookup_at(
+    dir: Option<&VfsNodeRef>,
+    path: &str,
+    follow: bool,
+) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
+    let (dir, path) = resolve(dir, path, follow)?;
+    let node = lookup_cached(dir.as_ref(), &path)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
@@ -244,34 +635,159 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
+pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(dir, path, true)
+}
+
+/// Creates the file at `path`, or the one a dangling symlink at `path` points
+/// to.
 pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
+    let (dir, path) = resolve(dir, path, true)?;
+    let (dir, path) = (dir.as_ref(), path.as_str());
     let parent = parent_node_of(dir, path);
     parent.create(path, VfsNodeType::File)?;
+    invalidate_dcache(dir, path);
+    #[cfg(feature = "notify")]
+    notify_at(dir, path, crate::notify::IN_CREATE, false);
     parent.lookup(path)
 }
 
 pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+    match lookup_at(dir, path, false) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let (dir, path) = resolve(dir, path, false)?;
+            let (dir, path) = (dir.as_ref(), path.as_str());
+            parent_node_of(dir, path).create(path, VfsNodeType::Dir)?;
+            invalidate_dcache(dir, path);
+            #[cfg(feature = "notify")]
+            notify_at(dir, path, crate::notify::IN_CREATE, true);
+            Ok(())
+        }
         Err(e) => Err(e),
     }
 }
 
+/// Creates a symlink at `path` whose content is `target`, which is not
+/// checked to exist.
+pub(crate) fn create_symlink(dir: Option<&VfsNodeRef>, target: &str, path: &str) -> AxResult {
+    if target.is_empty() || path.is_empty() || path.ends_with('/') {
+        return ax_err!(NotFound);
+    } else if target.len() > MAX_LINK_LEN {
+        return ax_err!(InvalidInput, "symlink target too long");
+    }
+    let (dir, path) = resolve(dir, path, false)?;
+    let (dir, path) = (dir.as_ref(), path.as_str());
+    match lookup_cached(dir, path) {
+        Ok(_) => return ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+    let parent = parent_node_of(dir, path);
+    parent.create(path, VfsNodeType::SymLink)?;
+    invalidate_dcache(dir, path);
+    let res = parent.lookup(path).and_then(|node| {
+        node.write_at(0, target.as_bytes())?;
+        Ok(())
+    });
+    if res.is_err() {
+        parent.remove(path).ok();
+        invalidate_dcache(dir, path);
+    }
+    #[cfg(feature = "notify")]
+    if res.is_ok() {
+        notify_at(dir, path, crate::notify::IN_CREATE, false);
+    }
+    res
+}
+
+/// Returns the target of the symlink at `path`.
+pub(crate) fn read_link(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<String> {
+    let node = lookup_at(dir, path, false)?;
+    if !node.get_attr()?.file_type().is_symlink() {
+        return ax_err!(InvalidInput, "not a symlink");
+    }
+    read_link_node(&node)
+}
+
+/// Resolves the symlinks in `path` like [`resolve`], where `path` is relative
+/// to the current directory.
+pub(crate) fn resolve_symlinks(path: &str, follow: bool) -> AxResult<String> {
+    resolve(None, path, follow).map(|(_, path)| path)
+}
+
+/// Returns the statistics of the filesystem holding the file at `path`.
+pub(crate) fn statfs(path: &str) -> AxResult<FileSystemStat> {
+    let path = resolve_symlinks(path, true)?;
+    lookup(None, &path)?;
+    ROOT_DIR.statfs(&path)
+}
+
+/// Returns the number telling the file `node` at `path` in its filesystem of
+/// the type `fstype`.
+///
+/// It's the inode number if the filesystem has one, or the address of the
+/// node in tmpfs, whose nodes live as long as the files. The files of the
+/// other filesystems are told by their paths, as they have no hard links.
+#[allow(unused_variables)]
+fn node_ino(node: &VfsNodeRef, fstype: &str, path: &str) -> u64 {
+    #[cfg(feature = "tmpfs")]
+    if let Some(node) = fs::tmpfs::tmpfs_node(node) {
+        return Arc::as_ptr(&node) as usize as u64;
+    }
+    #[cfg(all(feature = "ext2", not(any(feature = "myfs", feature = "lwext4_rs"))))]
+    if fstype == "ext2" {
+        if let Some(node) = node.as_any().downcast_ref::<fs::ext2::Ext2Node>() {
+            return node.ino() as u64;
+        }
+    }
+    // FNV-1a
+    path.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Returns the user and the group owning the file `node`, or `None` if its
+/// filesystem does not store them, i.e. it's not tmpfs or ext2.
+pub(crate) fn node_owner(node: &VfsNodeRef) -> Option<(u32, u32)> {
+    #[cfg(feature = "tmpfs")]
+    if let Some(node) = fs::tmpfs::tmpfs_node(node) {
+        return Some(node.owner());
+    }
+    #[cfg(all(feature = "ext2", not(any(feature = "myfs", feature = "lwext4_rs"))))]
+    if let Some(node) = node.as_any().downcast_ref::<fs::ext2::Ext2Node>() {
+        return node.owner().ok();
+    }
+    let _ = node;
+    None
+}
+
+/// Returns the identity of the file `node` opened at the absolute `path`.
+pub(crate) fn file_id(node: &VfsNodeRef, path: &str) -> FileId {
+    ROOT_DIR.file_id(node, path)
+}
+
+/// Removes the file at `path`, or the symlink itself if it's a symlink.
 pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
+    let node = lookup_at(dir, path, false)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let (dir, path) = resolve(dir, path, false)?;
+        let (dir, path) = (dir.as_ref(), path.as_str());
+        parent_node_of(dir, path).remove(path)?;
+        invalidate_dcache(dir, path);
+        #[cfg(feature = "notify")]
+        notify_at(dir, path, crate::notify::IN_DELETE, false);
+        Ok(())
     }
 }
 
@@ -289,21 +805,31 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
+    let (dir, path) = resolve(dir, path.trim_end_matches('/'), false)?;
+    let (dir, path) = (dir.as_ref(), path.as_str());
     if ROOT_DIR.contains(&absolute_path(path)?) {
         return ax_err!(PermissionDenied);
     }
 
-    let node = lookup(dir, path)?;
+    let node = lookup_cached(dir, path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        parent_node_of(dir, path).remove(path)?;
+        invalidate_dcache(dir, path);
+        #[cfg(feature = "notify")]
+        notify_at(dir, path, crate::notify::IN_DELETE, true);
+        Ok(())
     }
 }
 
+pub(crate) fn set_case_insensitive(path: &str, enabled: bool) -> AxResult {
+    ROOT_DIR.set_case_insensitive(&absolute_path(path)?, enabled)
+}
+
 pub(crate) fn current_dir() -> AxResult<String> {
     Ok(CURRENT_DIR_PATH.lock().clone())
 }
@@ -333,9 +859,46 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
+    // the symlinks themselves are renamed or replaced
+    let old = &resolve_symlinks(old, false)?;
+    let new = &resolve_symlinks(new, false)?;
     if parent_node_of(None, new).lookup(new).is_ok() {
         warn!("dst file already exist, now remove it");
         remove_file(None, new)?;
     }
-    parent_node_of(None, old).rename(old, new)
+    parent_node_of(None, old).rename(old, new)?;
+    invalidate_dcache(None, old);
+    invalidate_dcache(None, new);
+    #[cfg(feature = "notify")]
+    {
+        use crate::notify::{IN_MOVED_FROM, IN_MOVED_TO};
+        let is_dir = lookup_at(None, new, false).and_then(|node| node.get_attr());
+        let is_dir = is_dir.is_ok_and(|attr| attr.is_dir());
+        let cookie = crate::notify::next_cookie();
+        let (old, new) = (absolute_path(old)?, absolute_path(new)?);
+        crate::notify::notify(&old, IN_MOVED_FROM, cookie, is_dir);
+        crate::notify::notify(&new, IN_MOVED_TO, cookie, is_dir);
+        crate::notify::rename(&old, &new);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tmpfs")]
+pub(crate) fn hard_link(old: &str, new: &str) -> AxResult {
+    let node = lookup_at(None, old, false)?;
+    let new = resolve_symlinks(new, false)?;
+    let Some((dir, name)) = new.trim_end_matches('/').rsplit_once('/') else {
+        return ax_err!(InvalidInput);
+    };
+    let dir = lookup(None, if dir.is_empty() { "/" } else { dir })?;
+    fs::tmpfs::link(&dir, name, &node)?;
+    invalidate_dcache(None, &new);
+    #[cfg(feature = "notify")]
+    crate::notify::notify(&new, crate::notify::IN_CREATE, 0, false);
+    Ok(())
+}
+
+#[cfg(not(feature = "tmpfs"))]
+pub(crate) fn hard_link(_old: &str, _new: &str) -> AxResult {
+    ax_err!(Unsupported, "hard links are supported in tmpfs only")
 }
@@ -0,0 +1,71 @@
+//! The statistics of the mounted filesystems, as reported by `statfs`.
+//!
+//! [`axfs_vfs::VfsOps::statfs`] can't carry them, so each filesystem reports
+//! them by [`StatFs`] instead, which is required to mount it.
+
+use axfs_vfs::VfsResult;
+
+/// The statistics of a filesystem.
+#[derive(Debug, Clone, Copy)]
+pub struct FileSystemStat {
+    /// The type of the filesystem, as in `/proc/mounts`, set by the VFS.
+    pub fstype: &'static str,
+    /// The size of the blocks counted below.
+    pub block_size: u64,
+    /// The number of the blocks.
+    pub blocks: u64,
+    /// The number of the free blocks.
+    pub blocks_free: u64,
+    /// The number of the free blocks available to the unprivileged users.
+    pub blocks_avail: u64,
+    /// The number of the inodes, or 0 if it is not limited.
+    pub files: u64,
+    /// The number of the free inodes.
+    pub files_free: u64,
+    /// The maximum length of the names.
+    pub name_max: u64,
+}
+
+impl Default for FileSystemStat {
+    /// Returns the statistics of a filesystem without any storage, e.g.
+    /// procfs, where all the counts are 0.
+    fn default() -> Self {
+        Self {
+            fstype: "",
+            block_size: 4096,
+            blocks: 0,
+            blocks_free: 0,
+            blocks_avail: 0,
+            files: 0,
+            files_free: 0,
+            name_max: 255,
+        }
+    }
+}
+
+/// Reports the statistics of a filesystem.
+pub trait StatFs: Send + Sync {
+    /// Returns the statistics of the filesystem, which are all 0 by default
+    /// for the filesystems without any storage.
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        Ok(FileSystemStat::default())
+    }
+}
+
+/// The statistics of a filesystem provided by `MyFileSystemIf`, which can't
+/// report them.
+#[cfg(feature = "myfs")]
+pub(crate) struct UnknownStat;
+
+#[cfg(feature = "myfs")]
+impl StatFs for UnknownStat {
+    fn statfs(&self) -> VfsResult<FileSystemStat> {
+        Err(axfs_vfs::VfsError::Unsupported)
+    }
+}
+
+#[cfg(any(feature = "devfs", feature = "procfs", feature = "sysfs"))]
+impl StatFs for crate::fs::devfs::DeviceFileSystem {}
+
+#[cfg(feature = "ramfs")]
+impl StatFs for crate::fs::ramfs::RamFileSystem {}
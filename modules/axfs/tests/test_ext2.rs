@@ -0,0 +1,82 @@
+#![cfg(all(feature = "ext2", not(any(feature = "myfs", feature = "lwext4_rs"))))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, FileType};
+use axio::Result;
+
+const IMG_PATH: &str = "resources/ext2.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_owner() -> Result<()> {
+    println!("test owners of the inodes:");
+
+    assert_eq!(fs::owner("/short.txt", true)?, Some((1000, 1000)));
+    assert_eq!(fs::owner("/long.txt", true)?, Some((0, 0)));
+    // the high 16 bits are in the Linux-specific fields
+    let fname = "/very/long/path/test.txt";
+    assert_eq!(fs::owner(fname, true)?, Some((100000, 100001)));
+    // the symlink itself is owned by root
+    assert_eq!(fs::owner("/link", false)?, Some((0, 0)));
+    assert_eq!(fs::owner("/link", true)?, Some((100000, 100001)));
+    // not in ext2
+    assert_eq!(fs::owner("/dev/null", true)?, None);
+
+    println!("test_owner() OK!");
+    Ok(())
+}
+
+fn test_symlink() -> Result<()> {
+    println!("test symlinks:");
+
+    assert_eq!(fs::read_link("/link")?, "very/long/path/test.txt");
+    assert_eq!(
+        fs::symlink_metadata("/link")?.file_type(),
+        FileType::SymLink
+    );
+    assert_eq!(
+        fs::read_to_string("/link")?,
+        fs::read_to_string("/very/long/path/test.txt")?
+    );
+
+    // a long target is stored in a block instead of the inode
+    let target = "very/".repeat(20) + "long";
+    fs::symlink(&target, "/long-link")?;
+    assert_eq!(fs::read_link("/long-link")?, target);
+    fs::symlink("short.txt", "/short-link")?;
+    assert_eq!(
+        fs::read_to_string("/short-link")?,
+        fs::read_to_string("/short.txt")?
+    );
+    fs::remove_file("/long-link")?;
+    fs::remove_file("/short-link")?;
+    assert_eq!(
+        fs::read_link("/short-link").err(),
+        Some(axio::Error::NotFound)
+    );
+
+    println!("test_symlink() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext2() {
+    println!("Testing ext2 with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_owner().expect("test_owner() failed");
+    test_symlink().expect("test_symlink() failed");
+    test_common::test_all();
+}
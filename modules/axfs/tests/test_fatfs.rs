@@ -1,4 +1,4 @@
-#![cfg(not(feature = "myfs"))]
+#![cfg(not(any(feature = "myfs", feature = "ext2", feature = "lwext4_rs")))]
 
 mod test_common;
 
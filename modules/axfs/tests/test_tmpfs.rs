@@ -0,0 +1,121 @@
+#![cfg(all(feature = "tmpfs", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api::{self as fs, File};
+use axfs::tmpfs::{QuotaLimits, QuotaType};
+use axio::{Error, Result, Write};
+
+fn create_init_files() -> Result<()> {
+    fs::write("./short.txt", "Rust is cool!\n")?;
+    let mut file = File::create_new("/long.txt")?;
+    for _ in 0..100 {
+        file.write_fmt(format_args!("Rust is cool!\n"))?;
+    }
+
+    fs::create_dir("very-long-dir-name")?;
+    fs::write(
+        "very-long-dir-name/very-long-file-name.txt",
+        "Rust is cool!\n",
+    )?;
+
+    fs::create_dir("very")?;
+    fs::create_dir("//very/long")?;
+    fs::create_dir("/./very/long/path")?;
+    fs::write(".//very/long/path/test.txt", "Rust is cool!\n")?;
+    Ok(())
+}
+
+fn test_hard_link() -> Result<()> {
+    let fname = "/tmp/a.txt";
+    println!("test hard links to {:?}:", fname);
+
+    fs::write(fname, "Rust is cool!\n")?;
+    fs::hard_link(fname, "/tmp/b.txt")?;
+    assert_eq!(fs::tmpfs_node(fname)?.nlink(), 2);
+    fs::write("/tmp/b.txt", "linked\n")?;
+    assert_eq!(fs::read_to_string(fname)?, "linked\n");
+
+    // the data is kept until the last link is removed
+    fs::remove_file(fname)?;
+    assert_eq!(fs::tmpfs_node("/tmp/b.txt")?.nlink(), 1);
+    assert_eq!(fs::read_to_string("/tmp/b.txt")?, "linked\n");
+
+    // error cases
+    assert_eq!(
+        fs::hard_link("/tmp/b.txt", "/tmp/b.txt"),
+        Err(Error::AlreadyExists)
+    );
+    assert_eq!(
+        fs::hard_link("/tmp", "/tmp/dir"),
+        Err(Error::PermissionDenied)
+    );
+    assert_eq!(
+        fs::hard_link("/tmp/b.txt", "/b.txt"),
+        Err(Error::InvalidInput)
+    );
+    assert_eq!(
+        fs::hard_link("/dev/null", "/tmp/null"),
+        Err(Error::Unsupported)
+    );
+    fs::remove_file("/tmp/b.txt")?;
+
+    println!("test_hard_link() OK!");
+    Ok(())
+}
+
+fn test_owner_quota() -> Result<()> {
+    let fname = "/tmp/owned.txt";
+    println!("test owners and quotas of {:?}:", fname);
+
+    fs::write(fname, "Rust is cool!\n")?;
+    fs::symlink(fname, "/tmp/link")?;
+    assert_eq!(fs::owner(fname, true)?, Some((0, 0)));
+    fs::set_owner(fname, 1000, 100)?;
+    assert_eq!(fs::owner(fname, true)?, Some((1000, 100)));
+    assert_eq!(fs::owner("/tmp/link", true)?, Some((1000, 100)));
+    assert_eq!(fs::owner("/tmp/link", false)?, Some((0, 0)));
+
+    // the node and its page are moved to the quotas of the new owner
+    let tmp = fs::tmpfs_node("/tmp")?;
+    let dq = tmp.quota(QuotaType::User, 1000);
+    assert_eq!((dq.inodes, dq.space), (1, 4096));
+    assert_eq!(tmp.quota(QuotaType::Group, 100).inodes, 1);
+
+    // the limits are only enforced once the quota is enabled
+    let limits = QuotaLimits {
+        inodes_hard: 1,
+        ..Default::default()
+    };
+    tmp.set_quota_limits(QuotaType::User, 1000, limits);
+    fs::set_owner("/tmp/link", 1000, 0)?;
+    fs::set_owner("/tmp/link", 0, 0)?;
+    tmp.set_quota_enabled(QuotaType::User, true);
+    assert_eq!(fs::set_owner("/tmp/link", 1000, 0), Err(Error::StorageFull));
+    assert_eq!(fs::owner("/tmp/link", false)?, Some((0, 0)));
+    tmp.set_quota_enabled(QuotaType::User, false);
+
+    fs::remove_file("/tmp/link")?;
+    fs::remove_file(fname)?;
+    assert_eq!(tmp.quota(QuotaType::User, 1000).inodes, 0);
+
+    println!("test_owner_quota() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_tmpfs() {
+    println!("Testing tmpfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::default()); // no disk, tmpfs is the root.
+
+    if let Err(e) = create_init_files() {
+        log::warn!("failed to create init files: {:?}", e);
+    }
+
+    test_common::test_all();
+    test_hard_link().expect("test_hard_link() failed");
+    test_owner_quota().expect("test_owner_quota() failed");
+}
@@ -21,6 +21,17 @@ const BUILTIN_PLATFORM_FAMILIES: &[&str] = &[
     "x86-pc",
 ];
 
+/// The platform families taking the console input on the UART IRQ with the
+/// `console-irq` feature. The others always poll the UART.
+const UART_IRQ_PLATFORM_FAMILIES: &[&str] = &[
+    "aarch64-bsta1000b",
+    "aarch64-phytium-pi",
+    "aarch64-qemu-virt",
+    "aarch64-raspi",
+    "riscv64-qemu-virt",
+    "x86-pc",
+];
+
 fn make_cfg_values(str_list: &[&str]) -> String {
     str_list
         .iter()
@@ -33,8 +44,12 @@ fn main() {
     let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
     let platform = axconfig::PLATFORM;
     if platform != "dummy" {
+        check_memory_layout(&arch);
         gen_linker_script(&arch, platform).unwrap();
     }
+    if std::env::var("CARGO_FEATURE_KASLR").is_ok() {
+        gen_krelocs().unwrap();
+    }
 
     println!("cargo:rustc-cfg=platform=\"{}\"", platform);
     println!(
@@ -49,6 +64,167 @@ fn main() {
         "cargo::rustc-check-cfg=cfg(platform_family, values({}))",
         make_cfg_values(BUILTIN_PLATFORM_FAMILIES)
     );
+    if UART_IRQ_PLATFORM_FAMILIES.contains(&axconfig::plat::FAMILY) {
+        println!("cargo:rustc-cfg=uart_irq");
+    }
+    println!("cargo::rustc-check-cfg=cfg(uart_irq)");
+}
+
+/// Checks the memory layout in the platform config, so that the mistakes in
+/// it fail the build, instead of the boot.
+fn check_memory_layout(arch: &str) {
+    use axconfig::plat::*;
+
+    const BOOT_BLOCK_SIZE: usize = 0x4000_0000;
+
+    let check = |ok: bool, msg: String| {
+        if !ok {
+            panic!(
+                "invalid memory layout of platform {:?}: {}",
+                axconfig::PLATFORM,
+                msg
+            );
+        }
+    };
+    let aligned = |addr: usize| addr % 0x1000 == 0;
+
+    let ram_end = PHYS_MEMORY_BASE.checked_add(PHYS_MEMORY_SIZE);
+    check(
+        PHYS_MEMORY_SIZE > 0 && ram_end.is_some(),
+        format!(
+            "physical memory {:#x} + {:#x} is empty or overflows",
+            PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE
+        ),
+    );
+    let ram_end = ram_end.unwrap();
+    for (name, addr) in [
+        ("phys-memory-base", PHYS_MEMORY_BASE),
+        ("phys-memory-size", PHYS_MEMORY_SIZE),
+        ("kernel-base-paddr", KERNEL_BASE_PADDR),
+        ("kernel-base-vaddr", KERNEL_BASE_VADDR),
+        ("phys-virt-offset", PHYS_VIRT_OFFSET),
+    ] {
+        check(
+            aligned(addr),
+            format!("{} {:#x} is not 4K aligned", name, addr),
+        );
+    }
+    check(
+        (PHYS_MEMORY_BASE..ram_end).contains(&KERNEL_BASE_PADDR),
+        format!(
+            "kernel-base-paddr {:#x} is not in the physical memory {:#x}..{:#x}",
+            KERNEL_BASE_PADDR, PHYS_MEMORY_BASE, ram_end
+        ),
+    );
+    check(
+        KERNEL_BASE_VADDR.wrapping_sub(KERNEL_BASE_PADDR) == PHYS_VIRT_OFFSET,
+        format!(
+            "kernel-base-vaddr {:#x} - kernel-base-paddr {:#x} != phys-virt-offset {:#x}",
+            KERNEL_BASE_VADDR, KERNEL_BASE_PADDR, PHYS_VIRT_OFFSET
+        ),
+    );
+    let aspace_end = KERNEL_ASPACE_BASE.wrapping_add(KERNEL_ASPACE_SIZE);
+    check(
+        KERNEL_ASPACE_BASE <= PHYS_VIRT_OFFSET.wrapping_add(PHYS_MEMORY_BASE)
+            && PHYS_VIRT_OFFSET.wrapping_add(ram_end) <= aspace_end,
+        format!(
+            "the linear mapping of the physical memory is not in the kernel address space {:#x}..{:#x}",
+            KERNEL_ASPACE_BASE, aspace_end
+        ),
+    );
+    for &(base, size) in axconfig::devices::MMIO_REGIONS {
+        check(
+            aligned(base) && aligned(size),
+            format!("MMIO region {:#x} + {:#x} is not 4K aligned", base, size),
+        );
+        check(
+            base.checked_add(size)
+                .is_some_and(|end| end <= PHYS_MEMORY_BASE)
+                || base >= ram_end,
+            format!(
+                "MMIO region {:#x} + {:#x} overlaps the physical memory {:#x}..{:#x}",
+                base, size, PHYS_MEMORY_BASE, ram_end
+            ),
+        );
+    }
+    // The boot page tables map the physical memory in 1G blocks: the first
+    // 512G by a table on most architectures, the first 4G on x86_64.
+    let boot_limit = if arch == "x86_64" {
+        4 * BOOT_BLOCK_SIZE
+    } else {
+        512 * BOOT_BLOCK_SIZE
+    };
+    check(
+        ram_end <= boot_limit,
+        format!(
+            "physical memory end {:#x} is beyond the boot mapping limit {:#x}",
+            ram_end, boot_limit
+        ),
+    );
+}
+
+/// Packs the link-time addresses `addrs`, sorted and aligned to 8 bytes, in
+/// the format of `SHT_RELR`, see the `kaslr` module.
+fn pack_relocs(addrs: &[u64]) -> Vec<u64> {
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while i < addrs.len() {
+        entries.push(addrs[i]);
+        let mut next = addrs[i] + 8;
+        i += 1;
+        loop {
+            let mut bitmap = 0u64;
+            while i < addrs.len() && addrs[i] < next + 63 * 8 {
+                bitmap |= 1 << ((addrs[i] - next) / 8);
+                i += 1;
+            }
+            if bitmap == 0 {
+                break;
+            }
+            entries.push((bitmap << 1) | 1);
+            next += 63 * 8;
+        }
+    }
+    entries
+}
+
+/// Generates the table of the locations of the absolute addresses in the
+/// kernel image for the `kaslr` feature, from the file `AX_KRELOCS_PATH`
+/// written by the build scripts after linking, with one location per line as
+/// the link-time address in hex. The table is empty if the file is absent,
+/// in the first build.
+fn gen_krelocs() -> Result<()> {
+    println!("cargo:rerun-if-changed=linker.lds.S");
+    println!("cargo:rerun-if-env-changed=AX_KRELOCS_PATH");
+    let mut addrs = Vec::new();
+    if let Ok(path) = std::env::var("AX_KRELOCS_PATH") {
+        println!("cargo:rerun-if-changed={}", path);
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            for line in text.lines() {
+                let addr = u64::from_str_radix(line.trim(), 16)
+                    .unwrap_or_else(|_| panic!("invalid relocation {:?} in {}", line, path));
+                if addr % 8 != 0 {
+                    panic!("unaligned relocation at {:#x} in {}", addr, path);
+                }
+                addrs.push(addr);
+            }
+        }
+    }
+    addrs.sort_unstable();
+    addrs.dedup();
+    let entries = pack_relocs(&addrs);
+    let table = entries
+        .iter()
+        .map(|e| format!("{:#x}", e))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let content = format!(
+        "#[unsafe(link_section = \".krelocs\")]\nstatic KRELOCS: [u64; {}] = [{}];\n",
+        entries.len(),
+        table
+    );
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("krelocs.rs"), content)
 }
 
 fn gen_linker_script(arch: &str, platform: &str) -> Result<()> {
@@ -66,6 +242,17 @@ fn gen_linker_script(arch: &str, platform: &str) -> Result<()> {
         "%KERNEL_BASE%",
         &format!("{:#x}", axconfig::plat::KERNEL_BASE_VADDR),
     );
+    let ld_content = ld_content.replace(
+        "%KERNEL_BASE_PADDR%",
+        &format!("{:#x}", axconfig::plat::KERNEL_BASE_PADDR),
+    );
+    let ld_content = ld_content.replace(
+        "%PHYS_MEMORY_END%",
+        &format!(
+            "{:#x}",
+            axconfig::plat::PHYS_MEMORY_BASE + axconfig::plat::PHYS_MEMORY_SIZE
+        ),
+    );
     let ld_content = ld_content.replace("%SMP%", &format!("{}", axconfig::SMP));
 
     // target/<target_triple>/<mode>/build/axhal-xxxx/out
@@ -18,6 +18,11 @@ pub struct TrapFrame {
 }
 
 impl TrapFrame {
+    /// Gets the syscall number.
+    pub const fn sysno(&self) -> usize {
+        self.r[8] as _
+    }
+
     /// Gets the 0th syscall argument.
     pub const fn arg0(&self) -> usize {
         self.r[0] as _
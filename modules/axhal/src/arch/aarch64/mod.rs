@@ -4,6 +4,7 @@ mod context;
 mod trap;
 
 use core::arch::asm;
+use core::sync::atomic::AtomicUsize;
 
 use aarch64_cpu::registers::{DAIF, TPIDR_EL0, TTBR0_EL1, TTBR1_EL1, VBAR_EL1};
 use memory_addr::{PhysAddr, VirtAddr};
@@ -46,6 +47,30 @@ pub fn halt() {
     aarch64_cpu::asm::wfi(); // should never return
 }
 
+/// Relaxes the current CPU in a busy-waiting loop, with `YIELD` to let the
+/// other hardware threads run.
+#[inline]
+pub fn cpu_relax() {
+    unsafe { asm!("yield") };
+}
+
+/// Waits until the value at `addr` may be changed from `old`, or any other
+/// event happens, such as an interrupt.
+///
+/// The exclusive load arms the exclusive monitor, so a store to `addr` by
+/// other CPUs wakes up the `WFE`. It may return spuriously, so the callers
+/// should check the value again.
+#[inline]
+pub fn wait_for_change(addr: &AtomicUsize, old: usize) {
+    let value: usize;
+    unsafe {
+        asm!("ldaxr {0}, [{1}]", out(reg) value, in(reg) addr.as_ptr());
+        if value == old {
+            aarch64_cpu::asm::wfe();
+        }
+    }
+}
+
 /// Reads the register that stores the current page table root.
 ///
 /// Returns the physical address of the page table root.
@@ -47,7 +47,7 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
 
 #[unsafe(no_mangle)]
 fn handle_irq_exception(tf: &mut TrapFrame, source: TrapSource) {
-    handle_trap!(IRQ, 0);
+    crate::trap::handle_irq(0);
     crate::trap::post_trap_callback(tf, source.is_from_user());
 }
 
@@ -63,9 +63,9 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
         panic!(
-            "Unhandled {} Instruction Abort @ {:#x}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
+            "Unhandled {} Instruction Abort @ {}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
-            tf.elr,
+            crate::ksyms::symbolize(tf.elr as usize),
             vaddr,
             iss,
             access_flags,
@@ -92,9 +92,9 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
         panic!(
-            "Unhandled {} Data Abort @ {:#x}, fault_vaddr={:#x}, ISS=0b{:08b} ({:?}):\n{:#x?}",
+            "Unhandled {} Data Abort @ {}, fault_vaddr={:#x}, ISS=0b{:08b} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
-            tf.elr,
+            crate::ksyms::symbolize(tf.elr as usize),
             vaddr,
             iss,
             access_flags,
@@ -113,7 +113,7 @@ fn handle_sync_exception(tf: &mut TrapFrame, source: TrapSource) {
     match esr.read_as_enum(ESR_EL1::EC) {
         #[cfg(feature = "uspace")]
         Some(ESR_EL1::EC::Value::SVC64) => {
-            tf.r[0] = crate::trap::handle_syscall(tf, tf.r[8] as usize) as u64;
+            tf.r[0] = crate::trap::handle_syscall(tf, tf.sysno()) as u64;
         }
         Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => handle_instruction_abort(tf, iss, true),
         Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_instruction_abort(tf, iss, false),
@@ -125,8 +125,8 @@ fn handle_sync_exception(tf: &mut TrapFrame, source: TrapSource) {
         }
         _ => {
             panic!(
-                "Unhandled synchronous exception @ {:#x}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
-                tf.elr,
+                "Unhandled synchronous exception @ {}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
+                crate::ksyms::symbolize(tf.elr as usize),
                 esr.get(),
                 esr.read(ESR_EL1::EC),
                 esr.read(ESR_EL1::ISS),
@@ -68,6 +68,11 @@ pub struct TrapFrame {
 }
 
 impl TrapFrame {
+    /// Gets the syscall number.
+    pub const fn sysno(&self) -> usize {
+        self.regs.a7
+    }
+
     /// Gets the 0th syscall argument.
     pub const fn arg0(&self) -> usize {
         self.regs.a0
@@ -5,6 +5,7 @@ mod context;
 mod trap;
 
 use core::arch::asm;
+use core::sync::atomic::AtomicUsize;
 use loongArch64::register::{crmd, ecfg, eentry, pgdh, pgdl, stlbps, tlbidx, tlbrehi, tlbrentry};
 use memory_addr::{PhysAddr, VirtAddr};
 use page_table_multiarch::loongarch64::LA64MetaData;
@@ -47,6 +48,22 @@ pub fn halt() {
     unsafe { loongArch64::asm::idle() }
 }
 
+/// Relaxes the current CPU in a busy-waiting loop.
+#[inline]
+pub fn cpu_relax() {
+    core::hint::spin_loop()
+}
+
+/// Waits until the value at `addr` may be changed from `old`.
+///
+/// There is no way to wait for the stores of other CPUs, so it only relaxes
+/// the CPU once. The callers should check the value again.
+#[inline]
+pub fn wait_for_change(addr: &AtomicUsize, old: usize) {
+    let _ = (addr, old);
+    cpu_relax();
+}
+
 /// Reads the register that stores the current kernel page table root.
 ///
 /// Returns the physical address of the kernel page table root.
@@ -23,9 +23,9 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
     let vaddr = va!(badv::read().raw());
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
         panic!(
-            "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
+            "Unhandled {} Page Fault @ {}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
             if is_user { "PLV3" } else { "PLV0" },
-            tf.era,
+            crate::ksyms::symbolize(tf.era),
             vaddr,
             access_flags,
             tf,
@@ -46,7 +46,7 @@ fn loongarch64_trap_handler(tf: &mut TrapFrame, from_user: bool) {
         #[cfg(feature = "uspace")]
         Trap::Exception(Exception::Syscall) => {
             tf.era += 4;
-            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
+            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.sysno()) as usize;
         }
         Trap::Exception(Exception::LoadPageFault)
         | Trap::Exception(Exception::PageNonReadableFault) => {
@@ -63,13 +63,13 @@ fn loongarch64_trap_handler(tf: &mut TrapFrame, from_user: bool) {
         Trap::Exception(Exception::Breakpoint) => handle_breakpoint(&mut tf.era),
         Trap::Interrupt(_) => {
             let irq_num: usize = estat.is().trailing_zeros() as usize;
-            handle_trap!(IRQ, irq_num);
+            crate::trap::handle_irq(irq_num);
         }
         _ => {
             panic!(
-                "Unhandled trap {:?} @ {:#x}:\n{:#x?}",
+                "Unhandled trap {:?} @ {}:\n{:#x?}",
                 estat.cause(),
-                tf.era,
+                crate::ksyms::symbolize(tf.era),
                 tf
             );
         }
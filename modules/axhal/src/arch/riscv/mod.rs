@@ -4,6 +4,8 @@ mod macros;
 mod context;
 mod trap;
 
+use core::sync::atomic::AtomicUsize;
+
 use memory_addr::{PhysAddr, VirtAddr};
 use riscv::asm;
 use riscv::register::{satp, sstatus, stvec};
@@ -45,6 +47,26 @@ pub fn halt() {
     riscv::asm::wfi() // should never return
 }
 
+/// Relaxes the current CPU in a busy-waiting loop, with `PAUSE` of the
+/// `Zihintpause` extension, which is a no-op on the CPUs without it.
+#[inline]
+pub fn cpu_relax() {
+    // `pause` is encoded as `fence w, 0`, for the assemblers without the
+    // extension.
+    unsafe { core::arch::asm!(".insn i 0x0F, 0, x0, x0, 0x010") };
+}
+
+/// Waits until the value at `addr` may be changed from `old`.
+///
+/// There is no way to wait for the stores of other CPUs without the `Zawrs`
+/// extension, so it only relaxes the CPU once. The callers should check the
+/// value again.
+#[inline]
+pub fn wait_for_change(addr: &AtomicUsize, old: usize) {
+    let _ = (addr, old);
+    cpu_relax();
+}
+
 /// Reads the register that stores the current page table root.
 ///
 /// Returns the physical address of the page table root.
@@ -28,9 +28,9 @@ fn handle_page_fault(
     }
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
         panic!(
-            "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
+            "Unhandled {} Page Fault @ {}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
             if is_user { "User" } else { "Supervisor" },
-            tf.sepc,
+            crate::ksyms::symbolize(tf.sepc),
             vaddr,
             access_flags,
             tf,
@@ -52,7 +52,7 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             #[cfg(feature = "uspace")]
             Trap::Exception(E::UserEnvCall) => {
                 tf.sepc += 4;
-                tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
+                tf.regs.a0 = crate::trap::handle_syscall(tf, tf.sysno()) as usize;
             }
             Trap::Exception(E::LoadPageFault) => {
                 handle_page_fault(tf, vaddr, MappingFlags::READ, from_user)
@@ -65,19 +65,24 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             }
             Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
             Trap::Interrupt(_) => {
-                handle_trap!(IRQ, scause.bits());
+                crate::trap::handle_irq(scause.bits());
             }
             _ => {
-                panic!("Unhandled trap {:?} @ {:#x}:\n{:#x?}", cause, tf.sepc, tf);
+                panic!(
+                    "Unhandled trap {:?} @ {}:\n{:#x?}",
+                    cause,
+                    crate::ksyms::symbolize(tf.sepc),
+                    tf
+                );
             }
         }
         crate::trap::post_trap_callback(tf, from_user);
         mask_irqs();
     } else {
         panic!(
-            "Unknown trap {:?} @ {:#x}:\n{:#x?}",
+            "Unknown trap {:?} @ {}:\n{:#x?}",
             scause.cause(),
-            tf.sepc,
+            crate::ksyms::symbolize(tf.sepc),
             tf
         );
     }
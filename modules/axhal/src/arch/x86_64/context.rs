@@ -38,6 +38,11 @@ pub struct TrapFrame {
 }
 
 impl TrapFrame {
+    /// Gets the syscall number.
+    pub const fn sysno(&self) -> usize {
+        self.rax as _
+    }
+
     /// Gets the 0th syscall argument.
     pub const fn arg0(&self) -> usize {
         self.rdi as _
@@ -12,6 +12,7 @@ mod tls;
 mod trap;
 
 use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
 use x86::{controlregs, msr, tlb};
@@ -71,6 +72,37 @@ pub fn halt() {
     wait_for_irqs(); // should never return
 }
 
+/// Whether `MONITOR`/`MWAIT` are supported, detected by [`cpu_init`].
+static HAS_MWAIT: AtomicBool = AtomicBool::new(false);
+
+/// Relaxes the current CPU in a busy-waiting loop, with `PAUSE` to save power
+/// and yield the resources to the sibling hyper-thread.
+#[inline]
+pub fn cpu_relax() {
+    unsafe { asm!("pause") };
+}
+
+/// Waits until the value at `addr` may be changed from `old`, or any other
+/// event happens, such as an interrupt.
+///
+/// It uses `MONITOR`/`MWAIT` if they are supported, so a store to `addr` by
+/// other CPUs wakes up the CPU, or only relaxes the CPU once otherwise. It may
+/// return spuriously, so the callers should check the value again.
+#[inline]
+pub fn wait_for_change(addr: &AtomicUsize, old: usize) {
+    if cfg!(target_os = "none") && HAS_MWAIT.load(Ordering::Relaxed) {
+        unsafe {
+            asm!("monitor", in("rax") addr.as_ptr(), in("ecx") 0, in("edx") 0);
+            // Check again after arming the monitor, not to miss the store.
+            if addr.load(Ordering::Acquire) == old {
+                asm!("mwait", in("eax") 0, in("ecx") 0);
+            }
+        }
+    } else {
+        cpu_relax();
+    }
+}
+
 /// Reads the register that stores the current page table root.
 ///
 /// Returns the physical address of the page table root.
@@ -127,12 +159,17 @@ pub unsafe fn write_thread_pointer(fs_base: usize) {
 
 /// Initializes CPU states on the current CPU.
 ///
-/// In detail, it initializes the GDT, IDT on x86_64 platforms. If the `uspace`
+/// In detail, it initializes the GDT, IDT on x86_64 platforms, and detects the
+/// support of `MONITOR`/`MWAIT` for [`wait_for_change`]. If the `uspace`
 /// feature is enabled, it also initializes relevant model-specific registers
 /// to enable the `syscall` instruction.
 pub fn cpu_init() {
     init_gdt();
     init_idt();
+    let has_mwait = raw_cpuid::CpuId::new()
+        .get_feature_info()
+        .is_some_and(|info| info.has_monitor_mwait());
+    HAS_MWAIT.store(has_mwait, Ordering::Relaxed);
     #[cfg(feature = "uspace")]
     init_syscall();
 }
@@ -16,7 +16,7 @@ core::arch::global_asm!(
 );
 
 pub(super) fn handle_syscall(tf: &mut TrapFrame) {
-    tf.rax = crate::trap::handle_syscall(tf, tf.rax as usize) as u64;
+    tf.rax = crate::trap::handle_syscall(tf, tf.sysno()) as u64;
 }
 
 #[unsafe(no_mangle)]
@@ -19,9 +19,9 @@ fn handle_page_fault(tf: &TrapFrame) {
     let vaddr = va!(unsafe { cr2() });
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, tf.is_user()) {
         panic!(
-            "Unhandled {} #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
+            "Unhandled {} #PF @ {}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
             if tf.is_user() { "user" } else { "kernel" },
-            tf.rip,
+            crate::ksyms::symbolize(tf.rip as usize),
             vaddr,
             tf.error_code,
             access_flags,
@@ -46,22 +46,24 @@ fn x86_trap_handler(tf: &mut TrapFrame) {
             let cr4 = unsafe { cr4() };
             debug!("cr4: {:?}({:#b})", cr4, cr4.bits());
             panic!(
-                "#GP @ {:#x}, error_code={:#x}:\n{:#x?}",
-                tf.rip, tf.error_code, tf
+                "#GP @ {}, error_code={:#x}:\n{:#x?}",
+                crate::ksyms::symbolize(tf.rip as usize),
+                tf.error_code,
+                tf
             );
         }
         #[cfg(feature = "uspace")]
         LEGACY_SYSCALL_VECTOR => super::syscall::handle_syscall(tf),
         IRQ_VECTOR_START..=IRQ_VECTOR_END => {
-            handle_trap!(IRQ, tf.vector as _);
+            crate::trap::handle_irq(tf.vector as _);
         }
         _ => {
             panic!(
-                "Unhandled exception {} ({}, error_code={:#x}) @ {:#x}:\n{:#x?}",
+                "Unhandled exception {} ({}, error_code={:#x}) @ {}:\n{:#x?}",
                 tf.vector,
                 vec_to_str(tf.vector),
                 tf.error_code,
-                tf.rip,
+                crate::ksyms::symbolize(tf.rip as usize),
                 tf
             );
         }
@@ -0,0 +1,259 @@
+//! Console input and output.
+//!
+//! When the `console-irq` feature is enabled and the platform UART supports
+//! receive interrupts (the `uart_irq` cfg set by the build script), received
+//! bytes are pushed into a kernel ring buffer by the UART IRQ handler, and a
+//! registered notifier is invoked to wake up blocked readers. Otherwise, [`read_bytes`] polls the UART directly, even
+//! if the other IRQs are enabled by the `irq` feature.
+//!
+//! The console can also be backed by an hvc port, e.g. a virtio-console device
+//! registered by [`register_hvc_port`] once the drivers are probed. The backend
+//! is selected at runtime by [`set_backend`]. The input of an hvc port is
+//! always polled.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+pub use crate::platform::console::*;
+
+#[cfg(feature = "console-irq")]
+pub use self::irq_input::{has_pending_input, input_irq_enabled, read_bytes, set_input_notifier};
+
+/// The maximum number of hvc ports.
+pub const MAX_HVC_PORTS: usize = 8;
+
+/// A console port driven outside of this crate, e.g. a virtio-console port.
+pub trait HvcPort: Send {
+    /// Writes all the bytes to the port.
+    fn write_bytes(&mut self, bytes: &[u8]);
+
+    /// Reads the bytes received into `bytes` without blocking, returns the
+    /// number of bytes read.
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> usize;
+}
+
+/// The backends of the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleBackend {
+    /// The platform UART, or the SBI console on RISC-V.
+    Uart,
+    /// The hvc port of the index.
+    Hvc(usize),
+}
+
+type HvcSlot = SpinNoIrq<Option<&'static mut dyn HvcPort>>;
+
+static HVC_PORTS: [HvcSlot; MAX_HVC_PORTS] = [const { SpinNoIrq::new(None) }; MAX_HVC_PORTS];
+static NUM_HVC_PORTS: AtomicUsize = AtomicUsize::new(0);
+/// 0 for the UART, or the index of the hvc port plus 1.
+static BACKEND: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers an hvc port, returns its index, or `None` if there are already
+/// [`MAX_HVC_PORTS`] ports.
+pub fn register_hvc_port(port: &'static mut dyn HvcPort) -> Option<usize> {
+    let idx = NUM_HVC_PORTS.load(Ordering::Acquire);
+    if idx >= MAX_HVC_PORTS {
+        return None;
+    }
+    *HVC_PORTS[idx].lock() = Some(port);
+    NUM_HVC_PORTS.store(idx + 1, Ordering::Release);
+    Some(idx)
+}
+
+/// Returns the number of hvc ports registered.
+pub fn num_hvc_ports() -> usize {
+    NUM_HVC_PORTS.load(Ordering::Acquire)
+}
+
+/// Returns the current backend of the console.
+pub fn backend() -> ConsoleBackend {
+    match BACKEND.load(Ordering::Acquire) {
+        0 => ConsoleBackend::Uart,
+        n => ConsoleBackend::Hvc(n - 1),
+    }
+}
+
+/// Selects the backend of the console, returns `false` if it's an hvc port
+/// not registered.
+pub fn set_backend(backend: ConsoleBackend) -> bool {
+    let value = match backend {
+        ConsoleBackend::Uart => 0,
+        ConsoleBackend::Hvc(idx) if idx < num_hvc_ports() => idx + 1,
+        ConsoleBackend::Hvc(_) => return false,
+    };
+    BACKEND.store(value, Ordering::Release);
+    true
+}
+
+/// Writes bytes to the hvc port `idx`, returns `false` if it's not registered
+/// or busy.
+///
+/// The port is busy if it's re-entered, e.g. by a log in its driver.
+pub fn hvc_write_bytes(idx: usize, bytes: &[u8]) -> bool {
+    let Some(mut slot) = HVC_PORTS.get(idx).and_then(|slot| slot.try_lock()) else {
+        return false;
+    };
+    match slot.as_mut() {
+        Some(port) => {
+            port.write_bytes(bytes);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Reads bytes from the hvc port `idx` without blocking, returns the number
+/// of bytes read, or `None` if it's not registered or busy.
+pub fn hvc_read_bytes(idx: usize, bytes: &mut [u8]) -> Option<usize> {
+    let mut slot = HVC_PORTS.get(idx)?.try_lock()?;
+    Some(slot.as_mut()?.read_bytes(bytes))
+}
+
+/// Writes bytes to the console from input u8 slice.
+///
+/// It falls back to the UART if the hvc port selected is busy.
+pub fn write_bytes(bytes: &[u8]) {
+    match backend() {
+        ConsoleBackend::Hvc(idx) if hvc_write_bytes(idx, bytes) => {}
+        _ => crate::platform::console::write_bytes(bytes),
+    }
+}
+
+/// Reads bytes from the console backend without the IRQ ring buffer.
+fn read_backend(bytes: &mut [u8]) -> usize {
+    match backend() {
+        ConsoleBackend::Uart => crate::platform::console::read_bytes(bytes),
+        ConsoleBackend::Hvc(idx) => hvc_read_bytes(idx, bytes).unwrap_or(0),
+    }
+}
+
+/// Reads bytes from the console into the given mutable slice.
+/// Returns the number of bytes read.
+#[cfg(not(feature = "console-irq"))]
+pub fn read_bytes(bytes: &mut [u8]) -> usize {
+    read_backend(bytes)
+}
+
+#[cfg(all(feature = "console-irq", uart_irq))]
+pub(crate) use self::irq_input::{enable_input_irq, handle_input_irq};
+
+#[cfg(feature = "console-irq")]
+mod irq_input {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use kspin::SpinNoIrq;
+
+    /// The capacity of the console input ring buffer.
+    const RX_BUF_SIZE: usize = 1024;
+
+    struct RxRingBuffer {
+        buf: [u8; RX_BUF_SIZE],
+        head: usize,
+        len: usize,
+    }
+
+    impl RxRingBuffer {
+        const fn new() -> Self {
+            Self {
+                buf: [0; RX_BUF_SIZE],
+                head: 0,
+                len: 0,
+            }
+        }
+
+        fn push(&mut self, c: u8) -> bool {
+            if self.len == RX_BUF_SIZE {
+                return false;
+            }
+            self.buf[(self.head + self.len) % RX_BUF_SIZE] = c;
+            self.len += 1;
+            true
+        }
+
+        fn pop(&mut self) -> Option<u8> {
+            if self.len == 0 {
+                return None;
+            }
+            let c = self.buf[self.head];
+            self.head = (self.head + 1) % RX_BUF_SIZE;
+            self.len -= 1;
+            Some(c)
+        }
+    }
+
+    static RX_BUF: SpinNoIrq<RxRingBuffer> = SpinNoIrq::new(RxRingBuffer::new());
+    static INPUT_NOTIFIER: SpinNoIrq<Option<fn()>> = SpinNoIrq::new(None);
+    static INPUT_IRQ_ENABLED: AtomicBool = AtomicBool::new(false);
+
+    /// Reads bytes from the console into the given mutable slice.
+    /// Returns the number of bytes read.
+    ///
+    /// Bytes buffered by the UART IRQ handler are consumed first, then the
+    /// console backend is polled for the remaining space.
+    pub fn read_bytes(bytes: &mut [u8]) -> usize {
+        let mut rx_buf = RX_BUF.lock();
+        let mut read_len = 0;
+        while read_len < bytes.len() {
+            if let Some(c) = rx_buf.pop() {
+                bytes[read_len] = c;
+                read_len += 1;
+            } else {
+                break;
+            }
+        }
+        // Hold the buffer lock while polling, so the IRQ handler can not
+        // reorder bytes between the buffer and the UART FIFO.
+        read_len + super::read_backend(&mut bytes[read_len..])
+    }
+
+    /// Returns whether there are bytes buffered by the UART IRQ handler.
+    pub fn has_pending_input() -> bool {
+        RX_BUF.lock().len > 0
+    }
+
+    /// Returns whether console input is interrupt-driven on this platform.
+    ///
+    /// If it returns `false`, readers must poll [`read_bytes`] since the
+    /// input notifier is never invoked. It's always `false` if the console is
+    /// backed by an hvc port.
+    pub fn input_irq_enabled() -> bool {
+        INPUT_IRQ_ENABLED.load(Ordering::Acquire) && super::backend() == super::ConsoleBackend::Uart
+    }
+
+    /// Sets the callback to be invoked in the IRQ context when new console
+    /// input arrives.
+    ///
+    /// It is usually used to wake up tasks blocked on reading the console.
+    pub fn set_input_notifier(notifier: fn()) {
+        *INPUT_NOTIFIER.lock() = Some(notifier);
+    }
+
+    /// Marks console input as interrupt-driven, called by the platform after
+    /// the UART RX IRQ handler is registered.
+    #[cfg(uart_irq)]
+    pub(crate) fn enable_input_irq() {
+        INPUT_IRQ_ENABLED.store(true, Ordering::Release);
+    }
+
+    /// Drains all available bytes with `getchar` into the ring buffer, and
+    /// notifies the blocked readers. Called by the UART RX IRQ handler.
+    #[cfg(uart_irq)]
+    pub(crate) fn handle_input_irq(mut getchar: impl FnMut() -> Option<u8>) {
+        let mut received = false;
+        {
+            let mut rx_buf = RX_BUF.lock();
+            while let Some(c) = getchar() {
+                if !rx_buf.push(c) {
+                    warn!("console input buffer overflow, byte dropped");
+                }
+                received = true;
+            }
+        }
+        if received {
+            if let Some(notifier) = *INPUT_NOTIFIER.lock() {
+                notifier();
+            }
+        }
+    }
+}
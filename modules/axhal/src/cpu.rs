@@ -1,5 +1,7 @@
 //! CPU-related operations.
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 #[percpu::def_percpu]
 static CPU_ID: usize = 0;
 
@@ -9,6 +11,9 @@ static IS_BSP: bool = false;
 #[percpu::def_percpu]
 static CURRENT_TASK_PTR: usize = 0;
 
+/// Whether each CPU is online, indexed by CPU ID.
+static ONLINE_CPUS: [AtomicBool; axconfig::SMP] = [const { AtomicBool::new(false) }; axconfig::SMP];
+
 /// Returns the ID of the current CPU.
 #[inline]
 pub fn this_cpu_id() -> usize {
@@ -22,6 +27,20 @@ pub fn this_cpu_is_bsp() -> bool {
     IS_BSP.read_current()
 }
 
+/// Marks the current CPU online, once its platform devices including the
+/// interrupt controller are initialized, so it can take the IPIs.
+pub fn mark_online() {
+    ONLINE_CPUS[this_cpu_id()].store(true, Ordering::Release);
+}
+
+/// Returns whether the CPU `cpu_id` is online, i.e. it has been brought up
+/// and can take the IPIs.
+pub fn is_online(cpu_id: usize) -> bool {
+    ONLINE_CPUS
+        .get(cpu_id)
+        .is_some_and(|online| online.load(Ordering::Acquire))
+}
+
 /// Stores the pointer to the current task in the SP_EL0 register.
 ///
 /// In aarch64 architecture, we use `SP_EL0` as the read cache for
@@ -101,6 +120,8 @@ pub(crate) fn init_primary(cpu_id: usize) {
         CPU_ID.write_current_raw(cpu_id);
         IS_BSP.write_current_raw(true);
     }
+    // The CPU states are set up by the host if run in a user-space process.
+    #[cfg(target_os = "none")]
     crate::arch::cpu_init();
 }
 
@@ -0,0 +1,487 @@
+//! Device discovery from the flattened device tree (FDT) passed by the
+//! bootloader.
+//!
+//! The device tree is checked once at boot by [`init`], and the VirtIO MMIO
+//! devices found are got by [`virtio_mmio_devices`]. The blob is excluded from
+//! the free memory, so the drivers can look up their nodes later by
+//! [`for_each_node`], [`find_node`] and [`find_by_phandle`].
+
+use core::ops::ControlFlow;
+
+use axconfig::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE};
+use lazyinit::LazyInit;
+
+use crate::mem::{PhysAddr, VirtAddr, phys_to_virt};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// The maximum depth of the nodes parsed.
+const MAX_DEPTH: usize = 16;
+/// The maximum number of VirtIO MMIO devices recorded.
+const MAX_VIRTIO_MMIO: usize = 32;
+
+/// A VirtIO MMIO device described by a `virtio,mmio` node.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VirtioMmioDevice {
+    /// The physical address of the register region.
+    pub paddr: usize,
+    /// The size of the register region.
+    pub size: usize,
+    /// The IRQ number, if the node has `interrupts`.
+    pub irq: Option<usize>,
+}
+
+struct VirtioMmioDevices {
+    devices: [VirtioMmioDevice; MAX_VIRTIO_MMIO],
+    len: usize,
+}
+
+static VIRTIO_MMIO_DEVICES: LazyInit<VirtioMmioDevices> = LazyInit::new();
+
+/// The physical address of the device tree blob, and the blob.
+static BLOB: LazyInit<(usize, &'static [u8])> = LazyInit::new();
+
+/// A big-endian reader of the FDT blob.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.data.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos = (self.pos + len + 3) & !3;
+        Some(bytes)
+    }
+
+    fn c_str(&mut self) -> Option<&'a [u8]> {
+        let len = self.data.get(self.pos..)?.iter().position(|&b| b == 0)?;
+        let s = self.bytes(len + 1)?;
+        Some(&s[..len])
+    }
+}
+
+fn be_u32(bytes: &[u8], idx: usize) -> Option<u32> {
+    let bytes = bytes.get(idx * 4..idx * 4 + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a number of `cells` cells at cell `idx`.
+fn read_cells(bytes: &[u8], idx: usize, cells: usize) -> Option<usize> {
+    (idx..idx + cells).try_fold(0usize, |acc, i| {
+        Some((((acc as u64) << 32) | be_u32(bytes, i)? as u64) as usize)
+    })
+}
+
+fn c_str_at(data: &[u8], offset: usize) -> Option<&[u8]> {
+    let data = data.get(offset..)?;
+    Some(&data[..data.iter().position(|&b| b == 0)?])
+}
+
+/// Returns the IRQ number of an `interrupts` property.
+///
+/// The GIC uses 3 cells of the type, the number and the flags, where the
+/// number is relative to the first SPI or PPI. Other controllers (PLIC,
+/// PCH-PIC) use the IRQ number in the first cell.
+fn decode_irq(interrupts: &[u8]) -> Option<usize> {
+    if interrupts.len() == 12 {
+        let base = if be_u32(interrupts, 0)? == 0 { 32 } else { 16 };
+        Some(base + be_u32(interrupts, 1)? as usize)
+    } else {
+        Some(be_u32(interrupts, 0)? as usize)
+    }
+}
+
+/// Returns whether the region is in the MMIO regions mapped at boot.
+fn is_mapped_mmio(paddr: usize, size: usize) -> bool {
+    axconfig::devices::MMIO_REGIONS
+        .iter()
+        .any(|&(base, len)| paddr >= base && paddr + size <= base + len)
+}
+
+/// Parses the FDT and calls `f` on each enabled `virtio,mmio` node.
+fn parse_virtio_mmio(fdt: &[u8], mut f: impl FnMut(VirtioMmioDevice)) -> Option<()> {
+    let mut header = Reader { data: fdt, pos: 0 };
+    if header.u32()? != FDT_MAGIC {
+        return None;
+    }
+    let _total_size = header.u32()?;
+    let off_struct = header.u32()? as usize;
+    let off_strings = header.u32()? as usize;
+    let strings = fdt.get(off_strings..)?;
+    let mut reader = Reader {
+        data: fdt,
+        pos: off_struct,
+    };
+
+    // `#address-cells` and `#size-cells` of the nodes in the current path
+    let mut cells = [(2, 1); MAX_DEPTH];
+    let mut depth = 0;
+    let mut is_virtio = false;
+    let mut is_disabled = false;
+    let mut reg: &[u8] = &[];
+    let mut irq = None;
+    loop {
+        match reader.u32()? {
+            FDT_BEGIN_NODE => {
+                reader.c_str()?;
+                depth += 1;
+                if depth >= MAX_DEPTH {
+                    return None;
+                }
+                cells[depth] = (2, 1);
+                (is_virtio, is_disabled, reg, irq) = (false, false, &[], None);
+            }
+            FDT_END_NODE => {
+                if is_virtio && !is_disabled {
+                    let (addr_cells, size_cells) = cells[depth - 1];
+                    if let (Some(paddr), Some(size)) = (
+                        read_cells(reg, 0, addr_cells),
+                        read_cells(reg, addr_cells, size_cells),
+                    ) {
+                        f(VirtioMmioDevice { paddr, size, irq });
+                    }
+                }
+                // only the leaves are devices
+                is_virtio = false;
+                depth = depth.checked_sub(1)?;
+            }
+            FDT_PROP => {
+                let len = reader.u32()? as usize;
+                let name = c_str_at(strings, reader.u32()? as usize)?;
+                let value = reader.bytes(len)?;
+                match name {
+                    b"#address-cells" => cells[depth].0 = be_u32(value, 0)? as usize,
+                    b"#size-cells" => cells[depth].1 = be_u32(value, 0)? as usize,
+                    b"compatible" => {
+                        is_virtio = value.split(|&b| b == 0).any(|s| s == b"virtio,mmio")
+                    }
+                    b"status" => is_disabled = !value.starts_with(b"ok"),
+                    b"reg" => reg = value,
+                    b"interrupts" => irq = decode_irq(value),
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => return Some(()),
+            _ => return None,
+        }
+    }
+}
+
+/// Parses the device tree at the physical address `dtb`.
+///
+/// It's ignored if `dtb` is 0 or not in the physical memory, or the blob is
+/// invalid, then [`virtio_mmio_devices`] returns `None`.
+pub fn init(dtb: usize) {
+    if dtb < PHYS_MEMORY_BASE || dtb + 8 > PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE {
+        return;
+    }
+    let vaddr = phys_to_virt(PhysAddr::from(dtb)).as_usize();
+    // SAFETY: the header is in the physical memory, which is mapped linearly.
+    let header = unsafe { core::slice::from_raw_parts(vaddr as *const u8, 8) };
+    let total_size = be_u32(header, 1).unwrap() as usize;
+    if be_u32(header, 0) != Some(FDT_MAGIC)
+        || dtb + total_size > PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE
+    {
+        warn!("invalid device tree at {:#x}", dtb);
+        return;
+    }
+    // SAFETY: the whole blob is checked to be in the physical memory.
+    let fdt = unsafe { core::slice::from_raw_parts(vaddr as *const u8, total_size) };
+
+    let mut devices = VirtioMmioDevices {
+        devices: [VirtioMmioDevice::default(); MAX_VIRTIO_MMIO],
+        len: 0,
+    };
+    let parsed = parse_virtio_mmio(fdt, |dev| {
+        if !is_mapped_mmio(dev.paddr, dev.size) {
+            warn!("VirtIO MMIO region {:#x?} is not mapped, skipped", dev);
+        } else if devices.len < MAX_VIRTIO_MMIO {
+            devices.devices[devices.len] = dev;
+            devices.len += 1;
+        }
+    });
+    if parsed.is_none() {
+        warn!("failed to parse the device tree at {:#x}", dtb);
+        return;
+    }
+    // the nodes are listed from the highest address on QEMU, probe in order
+    devices.devices[..devices.len].sort_unstable_by_key(|dev| dev.paddr);
+    info!(
+        "found {} VirtIO MMIO devices in the device tree",
+        devices.len
+    );
+    VIRTIO_MMIO_DEVICES.init_once(devices);
+    BLOB.init_once((dtb, fdt));
+}
+
+/// Returns the physical address range of the device tree blob, which is
+/// reserved from the free memory.
+pub(crate) fn blob_range() -> Option<(usize, usize)> {
+    BLOB.get().map(|&(paddr, fdt)| (paddr, paddr + fdt.len()))
+}
+
+/// Returns the structure block and the strings block of the blob.
+fn blocks() -> Option<(Reader<'static>, &'static [u8])> {
+    let &(_, fdt) = BLOB.get()?;
+    let off_struct = be_u32(fdt, 2)? as usize;
+    let off_strings = be_u32(fdt, 3)? as usize;
+    Some((
+        Reader {
+            data: fdt,
+            pos: off_struct,
+        },
+        fdt.get(off_strings..)?,
+    ))
+}
+
+/// Returns the VirtIO MMIO devices in the device tree, or `None` if there is
+/// no valid device tree.
+pub fn virtio_mmio_devices() -> Option<&'static [VirtioMmioDevice]> {
+    let devices = VIRTIO_MMIO_DEVICES.get()?;
+    Some(&devices.devices[..devices.len])
+}
+
+/// A node of the device tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Node {
+    name: &'static str,
+    /// The offset of the tokens after the name in the blob.
+    body: usize,
+    /// The body of the parent node, `None` for the root.
+    parent: Option<usize>,
+}
+
+/// An iterator over the properties of a node.
+struct Properties {
+    reader: Reader<'static>,
+    strings: &'static [u8],
+}
+
+impl Iterator for Properties {
+    type Item = (&'static [u8], &'static [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.reader.u32()? {
+                FDT_PROP => {
+                    let len = self.reader.u32()? as usize;
+                    let name = c_str_at(self.strings, self.reader.u32()? as usize)?;
+                    return Some((name, self.reader.bytes(len)?));
+                }
+                FDT_NOP => {}
+                // the properties are followed by the children
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Node {
+    /// The name of the node, with the unit address, e.g. `uart@fe201000`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn properties(&self) -> Properties {
+        let (mut reader, strings) = blocks().unwrap();
+        reader.pos = self.body;
+        Properties { reader, strings }
+    }
+
+    /// Returns the value of the property `name`.
+    pub fn property(&self, name: &str) -> Option<&'static [u8]> {
+        self.properties()
+            .find(|(n, _)| *n == name.as_bytes())
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value of the property `name` of one cell.
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        be_u32(self.property(name)?, 0)
+    }
+
+    /// Returns the value of the property `name` of one or two cells.
+    pub fn property_u64(&self, name: &str) -> Option<u64> {
+        let value = self.property(name)?;
+        read_cells(value, 0, (value.len() / 4).clamp(1, 2)).map(|v| v as u64)
+    }
+
+    /// Returns the cells of the property `name`.
+    pub fn cells(&self, name: &str) -> impl Iterator<Item = u32> {
+        let value = self.property(name).unwrap_or_default();
+        (0..value.len() / 4).filter_map(move |i| be_u32(value, i))
+    }
+
+    /// Returns the strings of the property `name` of a string list.
+    pub fn strings(&self, name: &str) -> impl Iterator<Item = &'static str> {
+        let value = self.property(name).unwrap_or_default();
+        value
+            .strip_suffix(&[0])
+            .unwrap_or(value)
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    /// Returns whether the node is compatible with `compatible`.
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.strings("compatible").any(|c| c == compatible)
+    }
+
+    /// Returns whether the node is enabled by its `status`.
+    pub fn is_enabled(&self) -> bool {
+        self.property("status")
+            .is_none_or(|status| status.starts_with(b"ok"))
+    }
+
+    /// The phandle of the node, by which other nodes refer to it.
+    pub fn phandle(&self) -> Option<u32> {
+        self.property_u32("phandle")
+            .or_else(|| self.property_u32("linux,phandle"))
+    }
+
+    /// Returns the parent node, or `None` for the root.
+    pub fn parent(&self) -> Option<Node> {
+        let body = self.parent?;
+        find_node(|node| node.body == body)
+    }
+
+    /// Calls `f` on each child node, in the order of the blob.
+    pub fn for_each_child(&self, mut f: impl FnMut(Node)) {
+        for_each_node(|node| {
+            if node.parent == Some(self.body) {
+                f(node);
+            }
+        });
+    }
+
+    /// The `#address-cells` and `#size-cells` of the children.
+    fn child_cells(&self) -> (usize, usize) {
+        let addr_cells = self.property_u32("#address-cells").unwrap_or(2);
+        let size_cells = self.property_u32("#size-cells").unwrap_or(1);
+        (addr_cells as usize, size_cells as usize)
+    }
+
+    /// Returns the physical address and the size of the `idx`-th region in
+    /// `reg`, translated by the `ranges` of the parent buses.
+    pub fn reg(&self, idx: usize) -> Option<(usize, usize)> {
+        let mut bus = self.parent()?;
+        let (addr_cells, size_cells) = bus.child_cells();
+        let reg = self.property("reg")?;
+        let entry = idx * (addr_cells + size_cells);
+        let mut addr = read_cells(reg, entry, addr_cells)?;
+        let size = read_cells(reg, entry + addr_cells, size_cells)?;
+
+        // translate the address to the one of the parent bus, up to the root
+        while let Some(parent) = bus.parent() {
+            let ranges = bus.property("ranges").unwrap_or_default();
+            let (child_cells, len_cells) = bus.child_cells();
+            let parent_cells = parent.child_cells().0;
+            let entry_cells = child_cells + parent_cells + len_cells;
+            // an empty or missing `ranges` is taken as the identity mapping
+            if !ranges.is_empty() {
+                addr = (0..ranges.len() / 4 / entry_cells).find_map(|i| {
+                    let child = read_cells(ranges, i * entry_cells, child_cells)?;
+                    let to = read_cells(ranges, i * entry_cells + child_cells, parent_cells)?;
+                    let len = read_cells(
+                        ranges,
+                        i * entry_cells + child_cells + parent_cells,
+                        len_cells,
+                    )?;
+                    (addr >= child && addr - child < len).then(|| to + (addr - child))
+                })?;
+            }
+            bus = parent;
+        }
+        Some((addr, size))
+    }
+
+    /// Returns the virtual address of the `idx`-th region in `reg`, or `None`
+    /// if it's not in the MMIO regions mapped at boot.
+    pub fn mmio(&self, idx: usize) -> Option<VirtAddr> {
+        let (paddr, size) = self.reg(idx)?;
+        if !is_mapped_mmio(paddr, size) {
+            warn!("MMIO region {:#x} of {} is not mapped", paddr, self.name);
+            return None;
+        }
+        Some(phys_to_virt(PhysAddr::from(paddr)))
+    }
+}
+
+/// Calls `f` on each node of the device tree in order, until it breaks.
+fn walk(mut f: impl FnMut(Node) -> ControlFlow<()>) -> Option<()> {
+    let (mut reader, _) = blocks()?;
+    let mut stack = [0; MAX_DEPTH];
+    let mut depth = 0;
+    loop {
+        match reader.u32()? {
+            FDT_BEGIN_NODE => {
+                let name = core::str::from_utf8(reader.c_str()?).ok()?;
+                if depth >= MAX_DEPTH {
+                    return None;
+                }
+                let node = Node {
+                    name,
+                    body: reader.pos,
+                    parent: depth.checked_sub(1).map(|d| stack[d]),
+                };
+                stack[depth] = reader.pos;
+                depth += 1;
+                if f(node).is_break() {
+                    return Some(());
+                }
+            }
+            FDT_END_NODE => depth = depth.checked_sub(1)?,
+            FDT_PROP => {
+                let len = reader.u32()? as usize;
+                reader.u32()?;
+                reader.bytes(len)?;
+            }
+            FDT_NOP => {}
+            FDT_END => return Some(()),
+            _ => return None,
+        }
+    }
+}
+
+/// Calls `f` on each node of the device tree, in the order of the blob.
+///
+/// Nothing is called if there is no valid device tree.
+pub fn for_each_node(mut f: impl FnMut(Node)) {
+    walk(|node| {
+        f(node);
+        ControlFlow::Continue(())
+    });
+}
+
+/// Returns the first node that satisfies `pred`.
+pub fn find_node(mut pred: impl FnMut(&Node) -> bool) -> Option<Node> {
+    let mut found = None;
+    walk(|node| {
+        if pred(&node) {
+            found = Some(node);
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    found
+}
+
+/// Returns the node of the phandle `phandle`.
+pub fn find_by_phandle(phandle: u32) -> Option<Node> {
+    find_node(|node| node.phandle() == Some(phandle))
+}
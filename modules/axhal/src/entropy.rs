@@ -0,0 +1,212 @@
+//! Kernel entropy pool.
+//!
+//! The pool is a ChaCha20 key. The entropy added by [`add_entropy`] is mixed
+//! into the key, and the random bytes of [`fill_bytes`] are the keystream of
+//! the key, which is replaced after each request, so that the bytes returned
+//! can not be recovered from a later state of the pool.
+//!
+//! A hardware random number generator registered by [`set_source`], e.g. a
+//! virtio-rng device, seeds the pool, and reseeds it every
+//! [`RESEED_INTERVAL`] when the pool is used. Until then, the pool is only
+//! seeded by the time and its bytes are predictable, which is reported by
+//! [`is_seeded`].
+
+use core::time::Duration;
+
+use kspin::SpinNoIrq;
+
+/// The interval of reseeding the pool from the source.
+pub const RESEED_INTERVAL: Duration = Duration::from_secs(60);
+/// The interval of retrying the source, if it fails to seed the pool.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The size of the key, and of a seed from the source.
+const KEY_SIZE: usize = 32;
+/// The size of a ChaCha20 block.
+const BLOCK_SIZE: usize = 64;
+/// The maximum number of bytes generated with the pool locked.
+const MAX_CHUNK_SIZE: usize = 256;
+/// The block counter of rekeying, never used by the output.
+const REKEY_COUNTER: u64 = u64::MAX;
+
+/// A hardware random number generator driven outside of this crate.
+pub trait EntropySource: Send {
+    /// Fills `buf` with random bytes, returns the number of bytes filled.
+    fn fill(&mut self, buf: &mut [u8]) -> usize;
+}
+
+struct Pool {
+    key: [u32; 8],
+    /// Whether the time has been mixed in.
+    initialized: bool,
+    /// Whether a full seed from the source has been mixed in.
+    seeded: bool,
+    /// The time of the last seed from the source, or of the last try.
+    last_reseed: Option<Duration>,
+    source: Option<&'static mut dyn EntropySource>,
+}
+
+static POOL: SpinNoIrq<Pool> = SpinNoIrq::new(Pool::new());
+
+#[inline]
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// Generates the ChaCha20 block of `counter`, with a zero nonce.
+fn chacha20_block(key: &[u32; 8], counter: u64) -> [u32; 16] {
+    let mut init = [0u32; 16];
+    init[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    init[4..12].copy_from_slice(key);
+    init[12] = counter as u32;
+    init[13] = (counter >> 32) as u32;
+
+    let mut state = init;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    for (s, i) in state.iter_mut().zip(init) {
+        *s = s.wrapping_add(i);
+    }
+    state
+}
+
+impl Pool {
+    const fn new() -> Self {
+        Self {
+            key: [0; 8],
+            initialized: false,
+            seeded: false,
+            last_reseed: None,
+            source: None,
+        }
+    }
+
+    /// Replaces the key by a block of itself.
+    fn rekey(&mut self) {
+        let block = chacha20_block(&self.key, REKEY_COUNTER);
+        self.key.copy_from_slice(&block[..8]);
+    }
+
+    fn mix(&mut self, data: &[u8]) {
+        for chunk in data.chunks(KEY_SIZE) {
+            for (i, &b) in chunk.iter().enumerate() {
+                self.key[i / 4] ^= (b as u32) << (8 * (i % 4));
+            }
+            self.rekey();
+        }
+    }
+
+    fn generate(&mut self, buf: &mut [u8]) {
+        for (counter, chunk) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            let block = chacha20_block(&self.key, counter as u64);
+            let bytes = block.iter().flat_map(|w| w.to_le_bytes());
+            for (dst, src) in chunk.iter_mut().zip(bytes) {
+                *dst = src;
+            }
+        }
+        self.rekey();
+    }
+
+    /// Mixes in the time at the first use, and a seed from the source if the
+    /// pool is not seeded or the seed is too old.
+    fn prepare(&mut self) {
+        let now = crate::time::monotonic_time();
+        if !self.initialized {
+            self.initialized = true;
+            self.mix(&crate::time::wall_time_nanos().to_le_bytes());
+            self.mix(&(now.as_nanos() as u64).to_le_bytes());
+        }
+        let interval = if self.seeded {
+            RESEED_INTERVAL
+        } else {
+            RETRY_INTERVAL
+        };
+        if self.last_reseed.is_some_and(|last| now < last + interval) {
+            return;
+        }
+        let Some(source) = self.source.as_mut() else {
+            return;
+        };
+        let mut seed = [0; KEY_SIZE];
+        let len = source.fill(&mut seed);
+        self.mix(&seed[..len]);
+        self.seeded |= len == KEY_SIZE;
+        self.last_reseed = Some(now);
+    }
+}
+
+/// A pool on the stack, seeded only by the entropy mixed in by its user.
+///
+/// It's for the boot code running before the `.bss` section is cleared and
+/// the time is set up, where the global pool can not be used yet, i.e. the
+/// relocation of the kernel image by the `kaslr` feature.
+#[cfg(feature = "kaslr")]
+pub(crate) struct BootPool(Pool);
+
+#[cfg(feature = "kaslr")]
+impl BootPool {
+    pub(crate) const fn new() -> Self {
+        Self(Pool::new())
+    }
+
+    /// Mixes `data` into the pool.
+    pub(crate) fn add_entropy(&mut self, data: &[u8]) {
+        self.0.mix(data);
+    }
+
+    /// Generates a random `u64` from the pool.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut buf = [0; 8];
+        self.0.generate(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+}
+
+/// Registers the hardware random number generator, and seeds the pool from it.
+pub fn set_source(source: &'static mut dyn EntropySource) {
+    let mut pool = POOL.lock();
+    pool.source = Some(source);
+    pool.seeded = false;
+    pool.last_reseed = None;
+    pool.prepare();
+}
+
+/// Mixes `data` into the pool, e.g. the bytes written to `/dev/random`.
+///
+/// It's not counted as entropy, so the pool is not seeded by it.
+pub fn add_entropy(data: &[u8]) {
+    POOL.lock().mix(data);
+}
+
+/// Returns whether the pool has been seeded by a hardware random number
+/// generator.
+pub fn is_seeded() -> bool {
+    POOL.lock().seeded
+}
+
+/// Fills `buf` with random bytes from the pool.
+///
+/// It never blocks, and the bytes are predictable if the pool is not seeded
+/// yet, see [`is_seeded`].
+pub fn fill_bytes(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(MAX_CHUNK_SIZE) {
+        let mut pool = POOL.lock();
+        pool.prepare();
+        pool.generate(chunk);
+    }
+}
@@ -1,21 +1,70 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use axconfig::SMP;
 use handler_table::HandlerTable;
 
-use crate::platform::irq::{MAX_IRQ_COUNT, dispatch_irq};
-use crate::trap::{IRQ, register_trap_handler};
+use crate::platform::irq::dispatch_irq;
 
-pub use crate::platform::irq::{register_handler, set_enable};
+#[cfg(not(feature = "vtime"))]
+pub use crate::platform::irq::register_handler;
+pub use crate::platform::irq::{IPI_IRQ_NUM, MAX_IRQ_COUNT, send_ipi, set_enable};
 
 /// The type if an IRQ handler.
 pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// Number of times each IRQ has been handled on each CPU.
+static IRQ_COUNTS: [[AtomicUsize; SMP]; MAX_IRQ_COUNT] =
+    [const { [const { AtomicUsize::new(0) }; SMP] }; MAX_IRQ_COUNT];
+
+/// Time spent in IRQ handlers on each CPU, in nanoseconds.
+static IRQ_TIME_NANOS: [AtomicU64; SMP] = [const { AtomicU64::new(0) }; SMP];
+
+/// Records an occurrence of the IRQ on the current CPU.
+pub(crate) fn record_irq(irq_num: usize) {
+    if irq_num < MAX_IRQ_COUNT {
+        IRQ_COUNTS[irq_num][crate::cpu::this_cpu_id()].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of times the IRQ has been handled on the given CPU.
+pub fn irq_count(irq_num: usize, cpu_id: usize) -> usize {
+    if irq_num < MAX_IRQ_COUNT && cpu_id < SMP {
+        IRQ_COUNTS[irq_num][cpu_id].load(Ordering::Relaxed)
+    } else {
+        0
+    }
+}
+
+/// Returns the total time spent in IRQ handlers on the given CPU, in
+/// nanoseconds.
+pub fn irq_time_nanos(cpu_id: usize) -> u64 {
+    IRQ_TIME_NANOS
+        .get(cpu_id)
+        .map_or(0, |t| t.load(Ordering::Relaxed))
+}
+
+/// Registers an IRQ handler.
+///
+/// The handler of the timer IRQ is kept by the virtual clock, and the IRQ is
+/// left disabled. It returns `false` if the registration failed.
+#[cfg(feature = "vtime")]
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+    if irq_num == crate::time::TIMER_IRQ_NUM {
+        crate::time::register_timer_handler(handler)
+    } else {
+        crate::platform::irq::register_handler(irq_num, handler)
+    }
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
+    record_irq(irq_num);
     if !IRQ_HANDLER_TABLE.handle(irq_num) {
         warn!("Unhandled IRQ {}", irq_num);
     }
@@ -35,10 +84,13 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
     false
 }
 
-#[register_trap_handler(IRQ)]
-fn handler_irq(irq_num: usize) -> bool {
+/// Handles an IRQ, after invoking the observers in [`crate::trap::IRQ`].
+pub(crate) fn handler_irq(irq_num: usize) {
     let guard = kernel_guard::NoPreempt::new();
+    let start = crate::time::monotonic_time_nanos();
+    crate::trap::observe_irq(irq_num);
     dispatch_irq(irq_num);
+    let elapsed = crate::time::monotonic_time_nanos() - start;
+    IRQ_TIME_NANOS[crate::cpu::this_cpu_id()].fetch_add(elapsed, Ordering::Relaxed);
     drop(guard); // rescheduling may occur when preemption is re-enabled.
-    true
 }
@@ -0,0 +1,206 @@
+//! Randomization of the virtual base of the kernel image (KASLR).
+//!
+//! The code is position-independent (`medany` on riscv64, `adrp` on
+//! aarch64), only the absolute addresses stored in the image, e.g. the
+//! function pointers and the vtables, depend on where the image is. The
+//! image is linked with `--emit-relocs`, and the build scripts collect the
+//! locations of the absolute relocations to the image (`R_RISCV_64` and
+//! `R_AARCH64_ABS64`), then build the image again with them embedded in
+//! [`KRELOCS`] by the build script of this crate, in the packed format of
+//! `SHT_RELR`: an even entry is the link-time address of a location, and an
+//! odd entry is a bitmap of the locations in the next 63 words after the last
+//! one. The table is the last section of the loaded data, so embedding it
+//! moves nothing else.
+//!
+//! At boot, the image is moved to a random 1G block of the kernel address
+//! space that is not used by the linear mapping, chosen by a boot-time
+//! [entropy pool](crate::entropy::BootPool) seeded by the clock and the seeds
+//! passed in the `/chosen` node of the device tree (`kaslr-seed` and
+//! `rng-seed`), and each of the addresses is fixed up by the slide. This runs
+//! before the MMU is enabled, at the physical addresses, so it must not follow
+//! any pointer stored in the image, nor panic, and it only makes aligned
+//! accesses, as the memory is of the device type on aarch64 there.
+
+use axconfig::plat::{
+    KERNEL_ASPACE_BASE, KERNEL_ASPACE_SIZE, KERNEL_BASE_PADDR, KERNEL_BASE_VADDR, PHYS_VIRT_OFFSET,
+};
+
+use crate::entropy::BootPool;
+use crate::mem::{BOOT_BLOCK_SIZE, boot_blocks};
+
+#[cfg(not(any(target_arch = "riscv64", target_arch = "aarch64")))]
+compile_error!("the `kaslr` feature is only supported on riscv64 and aarch64");
+
+// `static KRELOCS: [u64; _]`, placed in the `.krelocs` section.
+include!(concat!(env!("OUT_DIR"), "/krelocs.rs"));
+
+/// The number of 1G blocks mapped by a table at boot, in which the image is
+/// placed.
+const WINDOW_BLOCKS: usize = 512;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+unsafe fn read_be32(addr: usize) -> u32 {
+    let bytes = [0, 1, 2, 3].map(|i| unsafe { ((addr + i) as *const u8).read() });
+    u32::from_be_bytes(bytes)
+}
+
+/// Returns whether the NUL-terminated string at `addr` is `name`.
+unsafe fn str_eq(addr: usize, name: &[u8]) -> bool {
+    let s = addr as *const u8;
+    for (i, &b) in name.iter().enumerate() {
+        if unsafe { s.add(i).read() } != b {
+            return false;
+        }
+    }
+    unsafe { s.add(name.len()).read() == 0 }
+}
+
+/// Calls `f` with each byte of the `kaslr-seed` and `rng-seed` properties in
+/// the `/chosen` node of the device tree at `dtb`.
+unsafe fn for_each_seed_byte(dtb: usize, mut f: impl FnMut(u8)) {
+    unsafe {
+        if dtb == 0 || read_be32(dtb) != FDT_MAGIC {
+            return;
+        }
+        let end = dtb + read_be32(dtb + 4) as usize;
+        let strings = dtb + read_be32(dtb + 12) as usize;
+        let mut pos = dtb + read_be32(dtb + 8) as usize;
+        let mut depth = 0usize;
+        let mut in_chosen = false;
+        while pos + 4 <= end {
+            let token = read_be32(pos);
+            pos += 4;
+            if token == FDT_BEGIN_NODE {
+                depth += 1;
+                if depth == 2 {
+                    in_chosen = str_eq(pos, b"chosen");
+                }
+                while pos < end && (pos as *const u8).read() != 0 {
+                    pos += 1;
+                }
+                pos = (pos + 4) & !3;
+            } else if token == FDT_END_NODE {
+                if depth == 2 {
+                    in_chosen = false;
+                }
+                depth = depth.saturating_sub(1);
+            } else if token == FDT_PROP {
+                let len = read_be32(pos) as usize;
+                let name = strings + read_be32(pos + 4) as usize;
+                let value = pos + 8;
+                if value + len > end {
+                    return;
+                }
+                if depth == 2
+                    && in_chosen
+                    && (str_eq(name, b"kaslr-seed") || str_eq(name, b"rng-seed"))
+                {
+                    for i in 0..len {
+                        f(((value + i) as *const u8).read());
+                    }
+                }
+                pos = (value + len + 3) & !3;
+            } else if token != FDT_NOP {
+                return;
+            }
+        }
+    }
+}
+
+/// Returns the start of the `index`-th 1G block of the kernel address space
+/// that can hold the image, and the number of such blocks.
+///
+/// The blocks are in the window of [`WINDOW_BLOCKS`] containing the linear
+/// mapping, which is mapped by the same table at boot. The blocks mapped by
+/// the linear mapping at boot (see [`boot_blocks`]) are excluded, so the
+/// image is never in the linear mapping.
+fn image_block(index: usize) -> (Option<usize>, usize) {
+    let window = PHYS_VIRT_OFFSET & !(WINDOW_BLOCKS * BOOT_BLOCK_SIZE - 1);
+    let block_index = |vaddr: usize| (vaddr.wrapping_sub(window) / BOOT_BLOCK_SIZE) % WINDOW_BLOCKS;
+    let mut used = [false; WINDOW_BLOCKS];
+    for (paddr, _) in boot_blocks() {
+        used[block_index(paddr.as_usize().wrapping_add(PHYS_VIRT_OFFSET))] = true;
+    }
+    let aspace_last = KERNEL_ASPACE_BASE + (KERNEL_ASPACE_SIZE - 1);
+    let mut found = None;
+    let mut count = 0;
+    for (i, &used) in used.iter().enumerate() {
+        let vaddr = window + i * BOOT_BLOCK_SIZE;
+        if used || vaddr < KERNEL_ASPACE_BASE || vaddr + (BOOT_BLOCK_SIZE - 1) > aspace_last {
+            continue;
+        }
+        if count == index {
+            found = Some(vaddr);
+        }
+        count += 1;
+    }
+    (found, count)
+}
+
+/// Adds `slide` to the address stored at `addr`.
+unsafe fn relocate(addr: usize, slide: usize) {
+    let ptr = addr as *mut usize;
+    unsafe { ptr.write(ptr.read().wrapping_add(slide)) };
+}
+
+/// Fixes up the addresses in the image loaded at the physical address
+/// `base`, by adding `slide` to each of them.
+unsafe fn apply_relocations(base: usize, slide: usize) {
+    // the address of the word after the last location of an even entry
+    let mut next = 0;
+    for &entry in KRELOCS.iter() {
+        if entry & 1 == 0 {
+            let addr = base + (entry as usize).wrapping_sub(KERNEL_BASE_VADDR);
+            unsafe { relocate(addr, slide) };
+            next = addr + 8;
+        } else {
+            let mut bitmap = entry >> 1;
+            let mut addr = next;
+            while bitmap != 0 {
+                if bitmap & 1 != 0 {
+                    unsafe { relocate(addr, slide) };
+                }
+                bitmap >>= 1;
+                addr += 8;
+            }
+            next += 63 * 8;
+        }
+    }
+}
+
+/// Moves the kernel image to a random virtual base, and returns its offset
+/// to the physical addresses, i.e. the new [`crate::mem::kernel_image_offset`].
+///
+/// It is called by the primary CPU before the MMU is enabled and the `.bss`
+/// section is cleared, with the physical address of the device tree `dtb`.
+/// The image is kept in the linear mapping if no block is available.
+pub(crate) unsafe extern "C" fn relocate_image(dtb: usize) -> usize {
+    let mut pool = BootPool::new();
+    pool.add_entropy(&crate::platform::time::current_ticks().to_le_bytes());
+    unsafe { for_each_seed_byte(dtb, |b| pool.add_entropy(&[b])) };
+    let (_, count) = image_block(0);
+    if count == 0 {
+        return PHYS_VIRT_OFFSET;
+    }
+    let Some(vaddr) = image_block((pool.next_u64() % count as u64) as usize).0 else {
+        return PHYS_VIRT_OFFSET;
+    };
+    let image_offset = vaddr.wrapping_sub(KERNEL_BASE_PADDR & !(BOOT_BLOCK_SIZE - 1));
+    unsafe {
+        apply_relocations(
+            _skernel as usize,
+            image_offset.wrapping_sub(PHYS_VIRT_OFFSET),
+        )
+    };
+    crate::mem::set_kernel_image_offset(image_offset);
+    image_offset
+}
+
+unsafe extern "C" {
+    fn _skernel();
+}
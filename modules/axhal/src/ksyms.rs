@@ -0,0 +1,196 @@
+//! The symbol table of the kernel image, to show the addresses of the code
+//! by the function names on the target, and the backtraces of the panics.
+//!
+//! With the `ksyms` feature, a `.ksyms` section of [`KSYMS_CAPACITY`] bytes
+//! is reserved in the image, and filled after linking by the build scripts
+//! with the text symbols in the output of `nm -n`, one per line as
+//! `<address in hex> <demangled name>`, sorted by the addresses and padded
+//! with zeros. Without the feature, no symbols can be found, and the
+//! addresses are shown as is.
+//!
+//! The backtraces are walked by the frame pointers, which are kept by
+//! `-C force-frame-pointers=yes` when the feature is enabled.
+
+use core::fmt;
+
+use axconfig::plat::PHYS_VIRT_OFFSET;
+
+/// The size in bytes reserved for the symbol table.
+#[cfg(feature = "ksyms")]
+pub const KSYMS_CAPACITY: usize = 1 << 20;
+
+/// The maximum number of frames in a backtrace.
+#[cfg(feature = "ksyms")]
+pub const MAX_FRAMES: usize = 64;
+
+#[cfg(feature = "ksyms")]
+#[used]
+#[unsafe(link_section = ".ksyms")]
+static KSYMS: [u8; KSYMS_CAPACITY] = [0; KSYMS_CAPACITY];
+
+/// Returns the symbol table, without the padding.
+#[cfg(feature = "ksyms")]
+fn table() -> &'static [u8] {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static LEN: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+    // The table is filled after compiling, so it must not be read as the
+    // zeros it is initialized to.
+    let table: &'static [u8; KSYMS_CAPACITY] = core::hint::black_box(&KSYMS);
+    let mut len = LEN.load(Ordering::Relaxed);
+    if len == usize::MAX {
+        len = table.iter().position(|&b| b == 0).unwrap_or(KSYMS_CAPACITY);
+        LEN.store(len, Ordering::Relaxed);
+    }
+    &table[..len]
+}
+
+#[cfg(not(feature = "ksyms"))]
+fn table() -> &'static [u8] {
+    &[]
+}
+
+/// Returns the start of the line in `table` that contains `pos`.
+fn line_start(table: &[u8], pos: usize) -> usize {
+    table[..pos]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1)
+}
+
+/// Parses the line in `table` at `start`, returns the address and the name of
+/// the symbol, and the start of the next line.
+fn parse_line(table: &[u8], start: usize) -> Option<(usize, &str, usize)> {
+    let end = table[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(table.len(), |i| start + i);
+    let line = core::str::from_utf8(&table[start..end]).ok()?;
+    let (addr, name) = line.split_once(' ')?;
+    let addr = usize::from_str_radix(addr, 16).ok()?;
+    Some((addr, name, end + 1))
+}
+
+/// Looks up the function that contains `addr`, returns its name and the
+/// offset of `addr` in it.
+///
+/// Returns [`None`] if `addr` is not in the code of the kernel, or the symbol
+/// table is not embedded.
+///
+/// The table has the link-time addresses, so `addr` is moved back by the
+/// slide of the kernel image first, if it is relocated at boot (see
+/// [`crate::mem::kernel_image_offset`]).
+pub fn lookup(addr: usize) -> Option<(&'static str, usize)> {
+    if !(_stext as usize.._etext as usize).contains(&addr) {
+        return None;
+    }
+    let slide = crate::mem::kernel_image_offset().wrapping_sub(PHYS_VIRT_OFFSET);
+    let addr = addr.wrapping_sub(slide);
+    let table = table();
+    // Binary search for the last symbol not after `addr`, by the positions
+    // in the table, as the lines are sorted but not of the same length.
+    let (mut lo, mut hi) = (0, table.len());
+    let mut found = None;
+    while lo < hi {
+        let mid = line_start(table, lo + (hi - lo) / 2);
+        let (sym_addr, name, next) = parse_line(table, mid)?;
+        if sym_addr <= addr {
+            found = Some((name, addr - sym_addr));
+            lo = next;
+        } else {
+            hi = mid;
+        }
+    }
+    found
+}
+
+/// An address that is shown with the function it is in, if found in the
+/// symbol table, e.g. `0xffffffc080201234 <axruntime::rust_main+0x34>`.
+#[derive(Clone, Copy)]
+pub struct Symbolized(pub usize);
+
+impl fmt::Display for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)?;
+        if let Some((name, offset)) = lookup(self.0) {
+            write!(f, " <{name}+{offset:#x}>")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Wraps `addr` to be shown with the function it is in.
+pub fn symbolize(addr: usize) -> Symbolized {
+    Symbolized(addr)
+}
+
+/// Returns the frame pointer of the caller.
+#[cfg(feature = "ksyms")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        #[cfg(target_arch = "x86_64")]
+        core::arch::asm!("mov {}, rbp", out(reg) fp);
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("mov {}, x29", out(reg) fp);
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("mv {}, s0", out(reg) fp);
+        #[cfg(target_arch = "loongarch64")]
+        core::arch::asm!("move {}, $fp", out(reg) fp);
+    }
+    fp
+}
+
+/// Returns the frame pointer and the return address saved in the frame at
+/// `fp`.
+///
+/// On x86_64 and AArch64, they are saved at where the frame pointer points
+/// to, while on RISC-V and LoongArch, they are saved right below it.
+#[cfg(feature = "ksyms")]
+unsafe fn read_frame(fp: usize) -> (usize, usize) {
+    let frame = if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+        fp
+    } else {
+        fp - 2 * size_of::<usize>()
+    } as *const usize;
+    unsafe { (frame.read(), frame.add(1).read()) }
+}
+
+/// Calls `f` with the return addresses in the call stack of the caller, from
+/// the innermost one, up to [`MAX_FRAMES`] frames.
+///
+/// The frames are walked by the frame pointers, until the return address is
+/// not in the code of the kernel, e.g. at the entry of the task.
+#[cfg(feature = "ksyms")]
+#[inline(never)]
+pub fn backtrace(mut f: impl FnMut(usize)) {
+    let mut fp = frame_pointer();
+    for _ in 0..MAX_FRAMES {
+        if fp == 0 || fp % size_of::<usize>() != 0 {
+            break;
+        }
+        let (next_fp, ret_addr) = unsafe { read_frame(fp) };
+        if !(_stext as usize.._etext as usize).contains(&ret_addr) {
+            break;
+        }
+        f(ret_addr);
+        // The stack grows downwards, so the frames of the callers are above.
+        if next_fp <= fp {
+            break;
+        }
+        fp = next_fp;
+    }
+}
+
+unsafe extern "C" {
+    fn _stext();
+    fn _etext();
+}
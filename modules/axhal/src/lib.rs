@@ -10,6 +10,9 @@
 //! - `riscv64-qemu-virt`: QEMU virt machine with RISC-V ISA.
 //! - `aarch64-qemu-virt`: QEMU virt machine with AArch64 ISA.
 //! - `aarch64-raspi`: Raspberry Pi with AArch64 ISA.
+//! - `linux-host`: A user-space process on Linux, with the `host` feature.
+//!    It runs the kernel logic without any emulator, e.g. for the unit tests
+//!    and fuzzing.
 //! - `dummy`: If none of the above platform is selected, the dummy platform
 //!    will be used. In this platform, most of the operations are no-op or
 //!    `unimplemented!()`. This platform is mainly used for [cargo test].
@@ -20,6 +23,15 @@
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
 //! - `irq`: Enable interrupt handling support.
+//! - `qos`: Enable the partitioning of the caches and the memory bandwidth.
+//! - `ksyms`: Embed the symbol table in the kernel image, to show the
+//!   addresses of the code by the function names, e.g. in the backtraces.
+//! - `kaslr`: Randomize the virtual base of the kernel image at boot, and fix
+//!   up the absolute addresses in it (only on riscv64 and aarch64).
+//! - `host`: Use the `linux-host` platform if the target OS is Linux, where
+//!   the `irq` and `smp` features are not supported.
+//! - `vtime`: Replace the hardware clock with a virtual one, which advances
+//!   only under the control of the tests (see [`time::advance`]).
 //!
 //! [ArceOS]: https://github.com/arceos-org/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
@@ -33,10 +45,15 @@
 #[macro_use]
 extern crate log;
 
+#[cfg(all(feature = "host", target_os = "linux"))]
+extern crate std;
+
 #[allow(unused_imports)]
 #[macro_use]
 extern crate memory_addr;
 
+// The clock of the platform is partially used with the `vtime` feature.
+#[cfg_attr(feature = "vtime", allow(dead_code))]
 mod platform;
 
 #[macro_use]
@@ -44,6 +61,8 @@ pub mod trap;
 
 pub mod arch;
 pub mod cpu;
+pub mod dtb;
+pub mod entropy;
 pub mod mem;
 pub mod time;
 
@@ -56,10 +75,14 @@ pub mod irq;
 #[cfg(feature = "paging")]
 pub mod paging;
 
-/// Console input and output.
-pub mod console {
-    pub use super::platform::console::*;
-}
+#[cfg(feature = "kaslr")]
+mod kaslr;
+
+#[cfg(feature = "qos")]
+pub mod qos;
+
+pub mod console;
+pub mod ksyms;
 
 /// Miscellaneous operation, e.g. terminate the system.
 pub mod misc {
@@ -74,5 +97,8 @@ pub mod mp {
 
 pub use self::platform::platform_init;
 
+#[cfg(all(feature = "host", target_os = "linux"))]
+pub use self::platform::init_early;
+
 #[cfg(feature = "smp")]
 pub use self::platform::platform_init_secondary;
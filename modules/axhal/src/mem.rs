@@ -1,8 +1,10 @@
 //! Physical memory management.
 
 use core::fmt;
+#[cfg(feature = "kaslr")]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use axconfig::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use axconfig::plat::{KERNEL_BASE_PADDR, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
 
 #[doc(no_inline)]
 pub use memory_addr::{MemoryAddr, PAGE_SIZE_4K, PhysAddr, VirtAddr};
@@ -46,12 +48,41 @@ pub struct MemRegion {
     pub name: &'static str,
 }
 
+/// The offset of the virtual addresses of the kernel image to its physical
+/// addresses, see [`kernel_image_offset`].
+#[cfg(feature = "kaslr")]
+static KERNEL_IMAGE_OFFSET: AtomicUsize = AtomicUsize::new(PHYS_VIRT_OFFSET);
+
+/// Returns the offset of the virtual addresses of the kernel image to its
+/// physical addresses, i.e. `vaddr - paddr` of the code and data of the
+/// kernel.
+///
+/// It is [`PHYS_VIRT_OFFSET`], the image is in the linear mapping, unless its
+/// virtual base is randomized at boot by the `kaslr` feature, then the image
+/// is mapped elsewhere, while its physical memory is still accessible in the
+/// linear mapping.
+#[inline]
+pub fn kernel_image_offset() -> usize {
+    #[cfg(feature = "kaslr")]
+    return KERNEL_IMAGE_OFFSET.load(Ordering::Relaxed);
+    #[cfg(not(feature = "kaslr"))]
+    PHYS_VIRT_OFFSET
+}
+
+/// Sets the offset of the virtual addresses of the kernel image, before the
+/// MMU is enabled.
+#[cfg(feature = "kaslr")]
+pub(crate) fn set_kernel_image_offset(offset: usize) {
+    KERNEL_IMAGE_OFFSET.store(offset, Ordering::Relaxed);
+}
+
 /// Converts a virtual address to a physical address.
 ///
 /// It assumes that there is a linear mapping with the offset
 /// [`PHYS_VIRT_OFFSET`], that maps all the physical memory to the virtual
 /// space at the address plus the offset. So we have
 /// `paddr = vaddr - PHYS_VIRT_OFFSET`.
+#[cfg(not(feature = "kaslr"))]
 #[inline]
 pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
     assert!(
@@ -61,12 +92,41 @@ pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
     pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
 }
 
+/// Converts a virtual address to a physical address.
+///
+/// It assumes that there is a linear mapping with the offset
+/// [`PHYS_VIRT_OFFSET`], that maps all the physical memory to the virtual
+/// space at the address plus the offset. So we have
+/// `paddr = vaddr - PHYS_VIRT_OFFSET`.
+///
+/// The addresses in the kernel image are converted by
+/// [`kernel_image_offset`] instead, which differs if the image is relocated.
+#[cfg(feature = "kaslr")]
+#[inline]
+pub fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+    let image_offset = kernel_image_offset();
+    if image_offset != PHYS_VIRT_OFFSET
+        && (_skernel as usize..=_ekernel as usize).contains(&vaddr.as_usize())
+    {
+        return pa!(vaddr.as_usize() - image_offset);
+    }
+    assert!(
+        vaddr.as_usize() >= PHYS_VIRT_OFFSET,
+        "Converted address is invalid, check if the virtual address is in kernel space"
+    );
+    pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
+}
+
 /// Converts a physical address to a virtual address.
 ///
 /// It assumes that there is a linear mapping with the offset
 /// [`PHYS_VIRT_OFFSET`], that maps all the physical memory to the virtual
 /// space at the address plus the offset. So we have
 /// `vaddr = paddr + PHYS_VIRT_OFFSET`.
+///
+/// The physical memory of the kernel image is converted to its alias in the
+/// linear mapping as well, even if the image is relocated, see
+/// [`kernel_image_offset`].
 #[inline]
 pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
@@ -78,7 +138,7 @@ pub fn memory_regions() -> impl Iterator<Item = MemRegion> {
 }
 
 /// Returns the memory regions of the kernel image (code and data sections).
-fn kernel_image_regions() -> impl Iterator<Item = MemRegion> {
+pub fn kernel_image_regions() -> impl Iterator<Item = MemRegion> {
     [
         MemRegion {
             paddr: virt_to_phys((_stext as usize).into()),
@@ -129,15 +189,72 @@ pub(crate) fn default_mmio_regions() -> impl Iterator<Item = MemRegion> {
 }
 
 /// Returns the default free memory regions (kernel image end to physical memory end).
+///
+/// The device tree blob is reserved, either in them or below the kernel image,
+/// see [`crate::dtb`].
 #[allow(dead_code)]
 pub(crate) fn default_free_regions() -> impl Iterator<Item = MemRegion> {
     let start = virt_to_phys((_ekernel as usize).into()).align_up_4k();
     let end = pa!(PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE).align_down_4k();
-    core::iter::once(MemRegion {
+    let dtb = crate::dtb::blob_range()
+        .map(|(dtb_start, dtb_end)| (pa!(dtb_start).align_down_4k(), pa!(dtb_end).align_up_4k()));
+    let (dtb_start, dtb_end) = match dtb {
+        Some((dtb_start, dtb_end)) if dtb_start < end && dtb_end > start => {
+            (dtb_start.max(start), dtb_end.min(end))
+        }
+        _ => (end, end),
+    };
+    let below_kernel = dtb.filter(|&(_, dtb_end)| dtb_end <= start);
+
+    let free = |start: PhysAddr, end: PhysAddr| MemRegion {
         paddr: start,
         size: end.as_usize() - start.as_usize(),
         flags: MemRegionFlags::FREE | MemRegionFlags::READ | MemRegionFlags::WRITE,
         name: "free memory",
+    };
+    let reserved = |(start, end): (PhysAddr, PhysAddr)| MemRegion {
+        paddr: start,
+        size: end.as_usize() - start.as_usize(),
+        flags: MemRegionFlags::RESERVED | MemRegionFlags::READ,
+        name: "device tree",
+    };
+    [
+        free(start, dtb_start),
+        reserved((dtb_start, dtb_end)),
+        free(dtb_end, end),
+    ]
+    .into_iter()
+    .chain(below_kernel.map(reserved))
+    .filter(|r| r.size > 0)
+}
+
+/// The size of the blocks mapped by the boot page tables (1G).
+#[allow(dead_code)]
+pub(crate) const BOOT_BLOCK_SIZE: usize = 0x4000_0000;
+
+/// Returns the blocks of [`BOOT_BLOCK_SIZE`] to be mapped by the boot page
+/// tables, as their start physical addresses and whether they are mapped as
+/// device memory, derived from the platform configuration.
+///
+/// The blocks that contain any MMIO region are mapped as device memory, and
+/// the other blocks that contain the physical memory are mapped as normal
+/// memory. The block of the kernel image is always normal memory. Only the
+/// first 512 blocks can be mapped, by a single table of the blocks.
+#[allow(dead_code)]
+pub(crate) fn boot_blocks() -> impl Iterator<Item = (PhysAddr, bool)> {
+    (0..512).filter_map(|i| {
+        let start = i * BOOT_BLOCK_SIZE;
+        let end = start + BOOT_BLOCK_SIZE;
+        let overlaps = |base: usize, size: usize| size > 0 && base < end && start < base + size;
+        let device = !(start..end).contains(&KERNEL_BASE_PADDR)
+            && axconfig::devices::MMIO_REGIONS
+                .iter()
+                .any(|reg| overlaps(reg.0, reg.1));
+        if device || overlaps(PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE) {
+            Some((pa!(start), device))
+        } else {
+            None
+        }
     })
 }
 
@@ -151,6 +268,7 @@ pub(crate) fn clear_bss() {
 }
 
 unsafe extern "C" {
+    fn _skernel();
     fn _stext();
     fn _etext();
     fn _srodata();
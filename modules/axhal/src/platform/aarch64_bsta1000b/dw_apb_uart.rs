@@ -54,13 +54,17 @@ pub fn init_early() {
 }
 
 /// Set UART IRQ Enable
-#[cfg(feature = "irq")]
+#[cfg(feature = "console-irq")]
 pub fn init_irq() {
     UART.lock().set_ier(true);
-    crate::irq::register_handler(crate::platform::irq::UART_IRQ_NUM, handle);
+    if crate::irq::register_handler(crate::platform::irq::UART_IRQ_NUM, handle) {
+        crate::console::enable_input_irq();
+    }
 }
 
 /// UART IRQ Handler
+#[cfg(feature = "console-irq")]
 pub fn handle() {
     trace!("Uart IRQ Handler");
+    crate::console::handle_input_irq(getchar);
 }
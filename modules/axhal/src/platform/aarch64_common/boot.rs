@@ -1,9 +1,12 @@
 use aarch64_cpu::{asm, asm::barrier, registers::*};
-use core::ptr::addr_of_mut;
 use page_table_entry::aarch64::{A64PTE, MemAttr};
+use page_table_entry::{GenericPTE, MappingFlags};
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 
-use axconfig::{TASK_STACK_SIZE, plat::PHYS_VIRT_OFFSET};
+use axconfig::{
+    TASK_STACK_SIZE,
+    plat::{KERNEL_BASE_PADDR, PHYS_VIRT_OFFSET},
+};
 
 #[unsafe(link_section = ".bss.stack")]
 static mut BOOT_STACK: [u8; TASK_STACK_SIZE] = [0; TASK_STACK_SIZE];
@@ -102,10 +105,44 @@ unsafe fn enable_fp() {
     }
 }
 
+/// Maps the physical memory and the MMIO regions in 1G blocks, for both the
+/// identity mapping (TTBR0) and the linear mapping (TTBR1), see
+/// [`crate::mem::boot_blocks`]. The block of the kernel image is also mapped
+/// at its virtual base, if it is relocated out of the linear mapping.
 unsafe fn init_boot_page_table() {
-    crate::platform::mem::init_boot_page_table(addr_of_mut!(BOOT_PT_L0), addr_of_mut!(BOOT_PT_L1));
+    let boot_pt_l0 = unsafe { &mut *(&raw mut BOOT_PT_L0) };
+    let boot_pt_l1 = unsafe { &mut *(&raw mut BOOT_PT_L1) };
+    // 0x0000_0000_0000 ~ 0x0080_0000_0000, table
+    boot_pt_l0[0] = A64PTE::new_table(pa!(boot_pt_l1.as_ptr() as usize));
+    for (paddr, device) in crate::mem::boot_blocks() {
+        let flags = if device {
+            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::DEVICE
+        } else {
+            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE
+        };
+        let idx = paddr.as_usize() / crate::mem::BOOT_BLOCK_SIZE;
+        boot_pt_l1[idx] = A64PTE::new_page(paddr, flags, true);
+    }
+    let image_offset = crate::mem::kernel_image_offset();
+    if image_offset != PHYS_VIRT_OFFSET {
+        let paddr = KERNEL_BASE_PADDR & !(crate::mem::BOOT_BLOCK_SIZE - 1);
+        let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE;
+        let idx = (paddr.wrapping_add(image_offset) / crate::mem::BOOT_BLOCK_SIZE) % 512;
+        boot_pt_l1[idx] = A64PTE::new_page(pa!(paddr), flags, true);
+    }
 }
 
+/// Returns the offset of the virtual addresses of the kernel image to the
+/// physical ones, after moving the image to a random virtual base with the
+/// `kaslr` feature.
+#[cfg(not(feature = "kaslr"))]
+unsafe extern "C" fn relocate_image(_dtb: usize) -> usize {
+    PHYS_VIRT_OFFSET
+}
+
+#[cfg(feature = "kaslr")]
+use crate::kaslr::relocate_image;
+
 /// Kernel entry point with Linux image header.
 ///
 /// Some bootloaders require this header to be present at the beginning of the
@@ -154,11 +191,15 @@ unsafe extern "C" fn _start_primary() -> ! {
 
             bl      {switch_to_el1}         // switch to EL1
             bl      {enable_fp}             // enable fp/neon
+
+            mov     x0, x20
+            bl      {relocate_image}        // get the virtual offset of the image
+            mov     x21, x0
+
             bl      {init_boot_page_table}
             bl      {init_mmu}              // setup MMU
 
-            mov     x8, {phys_virt_offset}  // set SP to the high address
-            add     sp, sp, x8
+            add     sp, sp, x21             // set SP to the high address
 
             mov     x0, x19                 // call rust_entry(cpu_id, dtb)
             mov     x1, x20
@@ -166,12 +207,12 @@ unsafe extern "C" fn _start_primary() -> ! {
             blr     x8
             b      .",
             switch_to_el1 = sym switch_to_el1,
+            relocate_image = sym relocate_image,
             init_boot_page_table = sym init_boot_page_table,
             init_mmu = sym init_mmu,
             enable_fp = sym enable_fp,
             boot_stack = sym BOOT_STACK,
             boot_stack_size = const TASK_STACK_SIZE,
-            phys_virt_offset = const PHYS_VIRT_OFFSET,
             entry = sym crate::platform::rust_entry,
         )
     }
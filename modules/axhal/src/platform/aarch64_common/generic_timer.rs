@@ -77,7 +77,7 @@ pub(crate) fn init_early() {
 }
 
 pub(crate) fn init_percpu() {
-    #[cfg(feature = "irq")]
+    #[cfg(all(feature = "irq", not(feature = "vtime")))]
     {
         CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET);
         CNTP_TVAL_EL0.set(0);
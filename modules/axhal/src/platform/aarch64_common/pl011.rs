@@ -57,17 +57,23 @@ pub fn init_early() {
 
 /// Set UART IRQ Enable
 pub fn init() {
-    #[cfg(feature = "irq")]
-    crate::irq::set_enable(crate::platform::irq::UART_IRQ_NUM, true);
+    #[cfg(feature = "console-irq")]
+    if crate::irq::register_handler(crate::platform::irq::UART_IRQ_NUM, handle) {
+        crate::console::enable_input_irq();
+    }
 }
 
 /// UART IRQ Handler
+#[cfg(feature = "console-irq")]
 pub fn handle() {
     let is_receive_interrupt = UART.lock().is_receive_interrupt();
     UART.lock().ack_interrupts();
     if is_receive_interrupt {
-        while let Some(c) = getchar() {
+        crate::console::handle_input_irq(|| {
+            let c = getchar()?;
+            // echo the input back
             putchar(c);
-        }
+            Some(c)
+        });
     }
 }
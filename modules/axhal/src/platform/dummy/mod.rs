@@ -69,6 +69,9 @@ pub mod irq {
     /// The timer IRQ number.
     pub const TIMER_IRQ_NUM: usize = 0;
 
+    /// The IPI IRQ number.
+    pub const IPI_IRQ_NUM: usize = 1;
+
     /// Enables or disables the given IRQ.
     pub fn set_enable(irq_num: usize, enabled: bool) {}
 
@@ -77,6 +80,9 @@ pub mod irq {
         false
     }
 
+    /// Sends an IPI to the given CPU.
+    pub fn send_ipi(cpu_id: usize) {}
+
     /// Dispatches the IRQ.
     ///
     /// This function is called by the common interrupt handler. It looks
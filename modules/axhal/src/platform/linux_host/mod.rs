@@ -0,0 +1,182 @@
+//! The platform of a Linux user-space process, to run the kernel logic on the
+//! host without any emulator, e.g. for the unit tests and fuzzing.
+//!
+//! The console is the standard input and output of the process, the clock is
+//! the monotonic clock of the host, and the physical memory is a block of the
+//! heap of the process, identically mapped, whose size is given by the
+//! `AX_HOST_MEMORY` environment variable in bytes (64 MiB by default). There
+//! are no interrupts, nor other CPUs, and the CPU states (e.g. the page
+//! tables) are left as the host sets up.
+
+use std::io::{Read, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "irq")]
+compile_error!("the `irq` feature is not supported on the Linux host platform");
+#[cfg(feature = "smp")]
+compile_error!("the `smp` feature is not supported on the Linux host platform");
+
+pub mod console {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// The bytes read from the standard input, but not yet by the kernel.
+    static INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+    /// Writes bytes to the console from input u8 slice.
+    pub fn write_bytes(bytes: &[u8]) {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytes).ok();
+        stdout.flush().ok();
+    }
+
+    /// Reads bytes from the console into the given mutable slice.
+    /// Returns the number of bytes read.
+    ///
+    /// It never blocks, the standard input is read by a thread of the host,
+    /// started on the first call.
+    pub fn read_bytes(bytes: &mut [u8]) -> usize {
+        static READER: OnceLock<()> = OnceLock::new();
+        READER.get_or_init(|| {
+            std::thread::spawn(|| {
+                let mut buf = [0; 256];
+                while let Ok(n @ 1..) = std::io::stdin().read(&mut buf) {
+                    INPUT.lock().unwrap().extend(&buf[..n]);
+                }
+            });
+        });
+        let mut input = INPUT.lock().unwrap();
+        let n = bytes.len().min(input.len());
+        for (b, c) in bytes.iter_mut().zip(input.drain(..n)) {
+            *b = c;
+        }
+        n
+    }
+}
+
+pub mod misc {
+    /// Shutdown the whole system, including all CPUs.
+    ///
+    /// The process exits.
+    pub fn terminate() -> ! {
+        info!("Shutting down...");
+        std::process::exit(0)
+    }
+}
+
+pub mod mem {
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::mem::{MemRegion, MemRegionFlags, PAGE_SIZE_4K};
+
+    /// The default size of the physical memory.
+    const DEFAULT_MEMORY_SIZE: usize = 64 << 20;
+
+    /// Returns the start address and the size of the physical memory,
+    /// allocated from the host on the first call.
+    fn memory() -> (usize, usize) {
+        static MEMORY: OnceLock<(usize, usize)> = OnceLock::new();
+        *MEMORY.get_or_init(|| {
+            let size = std::env::var("AX_HOST_MEMORY")
+                .ok()
+                .and_then(|s| s.trim().parse::<usize>().ok())
+                .unwrap_or(DEFAULT_MEMORY_SIZE)
+                .next_multiple_of(PAGE_SIZE_4K);
+            let layout = Layout::from_size_align(size, PAGE_SIZE_4K).unwrap();
+            let start = unsafe { std::alloc::alloc_zeroed(layout) };
+            if start.is_null() {
+                std::alloc::handle_alloc_error(layout);
+            }
+            (start as usize, size)
+        })
+    }
+
+    /// Returns platform-specific memory regions.
+    pub(crate) fn platform_regions() -> impl Iterator<Item = MemRegion> {
+        let (start, size) = memory();
+        core::iter::once(MemRegion {
+            paddr: pa!(start),
+            size,
+            flags: MemRegionFlags::FREE | MemRegionFlags::READ | MemRegionFlags::WRITE,
+            name: "free memory",
+        })
+    }
+}
+
+pub mod time {
+    use super::*;
+
+    /// Returns the instant of the boot, and the wall time of it in
+    /// nanoseconds.
+    fn boot_time() -> (Instant, u64) {
+        static BOOT_TIME: OnceLock<(Instant, u64)> = OnceLock::new();
+        *BOOT_TIME.get_or_init(|| {
+            let epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64);
+            (Instant::now(), epoch)
+        })
+    }
+
+    /// Returns the current clock time in hardware ticks.
+    ///
+    /// A tick is a nanosecond of the monotonic clock of the host.
+    pub fn current_ticks() -> u64 {
+        boot_time().0.elapsed().as_nanos() as u64
+    }
+
+    /// Converts hardware ticks to nanoseconds.
+    pub fn ticks_to_nanos(ticks: u64) -> u64 {
+        ticks
+    }
+
+    /// Converts nanoseconds to hardware ticks.
+    pub fn nanos_to_ticks(nanos: u64) -> u64 {
+        nanos
+    }
+
+    /// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
+    pub fn epochoffset_nanos() -> u64 {
+        boot_time().1
+    }
+}
+
+// The kernel image is the executable of the host, whose sections are not
+// known, so its regions are empty.
+core::arch::global_asm!(
+    ".pushsection .rodata.axhal_host_image, \"a\"",
+    ".balign 4096",
+    ".globl _skernel, _stext, _etext, _srodata, _erodata, _sdata, _edata",
+    ".globl _sbss, _ebss, _ekernel, boot_stack, boot_stack_top",
+    ".hidden _skernel, _stext, _etext, _srodata, _erodata, _sdata, _edata",
+    ".hidden _sbss, _ebss, _ekernel, boot_stack, boot_stack_top",
+    "_skernel:",
+    "_stext:",
+    "_etext:",
+    "_srodata:",
+    "_erodata:",
+    "_sdata:",
+    "_edata:",
+    "_sbss:",
+    "_ebss:",
+    "_ekernel:",
+    "boot_stack:",
+    "boot_stack_top:",
+    ".popsection",
+);
+
+/// Initializes the calling thread of the host as the primary CPU, before any
+/// other function of the kernel is called on it.
+///
+/// It does what the boot code does on the other platforms, and it must be
+/// called only once.
+pub fn init_early() {
+    crate::cpu::init_primary(0);
+    self::time::current_ticks();
+}
+
+/// Initializes the platform devices for the primary CPU.
+pub fn platform_init() {}
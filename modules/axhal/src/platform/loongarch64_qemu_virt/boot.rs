@@ -13,23 +13,22 @@ static mut BOOT_PT_L0: [LA64PTE; 512] = [LA64PTE::empty(); 512];
 #[unsafe(link_section = ".data.boot_page_table")]
 static mut BOOT_PT_L1: [LA64PTE; 512] = [LA64PTE::empty(); 512];
 
+/// Maps the physical memory and the MMIO regions in 1G blocks, see
+/// [`crate::mem::boot_blocks`].
 unsafe fn init_boot_page_table() {
     unsafe {
         let l1_va = va!(&raw const BOOT_PT_L1 as usize);
         // 0x0000_0000_0000 ~ 0x0080_0000_0000, table
         BOOT_PT_L0[0] = LA64PTE::new_table(crate::mem::virt_to_phys(l1_va));
-        // 0x0000_0000..0x4000_0000, VPWXGD, 1G block
-        BOOT_PT_L1[0] = LA64PTE::new_page(
-            pa!(0),
-            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::DEVICE,
-            true,
-        );
-        // 0x8000_0000..0xc000_0000, VPWXGD, 1G block
-        BOOT_PT_L1[2] = LA64PTE::new_page(
-            pa!(0x8000_0000),
-            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE,
-            true,
-        );
+        for (paddr, device) in crate::mem::boot_blocks() {
+            let flags = if device {
+                MappingFlags::READ | MappingFlags::WRITE | MappingFlags::DEVICE
+            } else {
+                MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE
+            };
+            let idx = paddr.as_usize() / crate::mem::BOOT_BLOCK_SIZE;
+            BOOT_PT_L1[idx] = LA64PTE::new_page(paddr, flags, true);
+        }
     }
 }
 
@@ -4,21 +4,33 @@ use loongArch64::register::{
 };
 
 /// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 12;
+pub const MAX_IRQ_COUNT: usize = 13;
 
 /// The timer IRQ number.
 pub const TIMER_IRQ_NUM: usize = estat::Interrupt::Timer as usize;
 
+/// The IPI IRQ number.
+pub const IPI_IRQ_NUM: usize = estat::Interrupt::IPI as usize;
+
+/// The IOCSR address of the IPI status clear register.
+const IOCSR_IPI_CLEAR: usize = 0x100c;
+
+/// The IPI action bit used for generic IPIs (bit 0 is used to boot CPUs).
+const ACTION_IPI: u32 = 1 << 1;
+
 /// Enables or disables the given IRQ.
 pub fn set_enable(irq_num: usize, enabled: bool) {
-    if irq_num == TIMER_IRQ_NUM {
-        let old_value = ecfg::read().lie();
-        let new_value = match enabled {
-            true => old_value | LineBasedInterrupt::TIMER,
-            false => old_value & !LineBasedInterrupt::TIMER,
-        };
-        ecfg::set_lie(new_value);
-    }
+    let line = match irq_num {
+        TIMER_IRQ_NUM => LineBasedInterrupt::TIMER,
+        IPI_IRQ_NUM => LineBasedInterrupt::IPI,
+        _ => return,
+    };
+    let old_value = ecfg::read().lie();
+    let new_value = match enabled {
+        true => old_value | line,
+        false => old_value & !line,
+    };
+    ecfg::set_lie(new_value);
 }
 
 /// Registers an IRQ handler for the given IRQ.
@@ -26,6 +38,11 @@ pub fn register_handler(irq_num: usize, handler: crate::irq::IrqHandler) -> bool
     crate::irq::register_handler_common(irq_num, handler)
 }
 
+/// Sends an IPI to the given CPU.
+pub fn send_ipi(cpu_id: usize) {
+    loongArch64::ipi::send_ipi_single(cpu_id, ACTION_IPI);
+}
+
 /// Dispatches the IRQ.
 ///
 /// This function is called by the common interrupt handler. It looks
@@ -34,6 +51,16 @@ pub fn register_handler(irq_num: usize, handler: crate::irq::IrqHandler) -> bool
 pub fn dispatch_irq(irq_num: usize) {
     if irq_num == TIMER_IRQ_NUM {
         ticlr::clear_timer_interrupt();
+    } else if irq_num == IPI_IRQ_NUM {
+        unsafe {
+            core::arch::asm!("iocsrwr.w {}, {}", in(reg) u32::MAX, in(reg) IOCSR_IPI_CLEAR);
+        }
     }
     crate::irq::dispatch_irq_common(irq_num)
 }
+
+/// Enables the IPI line on the current CPU, so that IPIs can be received
+/// regardless of which CPU registers the handler.
+pub(super) fn init_percpu() {
+    set_enable(IPI_IRQ_NUM, true);
+}
@@ -47,7 +47,7 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
 }
 
 pub(super) fn init_percpu() {
-    #[cfg(feature = "irq")]
+    #[cfg(all(feature = "irq", not(feature = "vtime")))]
     {
         use loongArch64::register::tcfg;
         tcfg::set_init_val(0);
@@ -28,6 +28,9 @@ cfg_if::cfg_if! {
     } else if #[cfg(all(target_arch = "loongarch64", platform_family = "loongarch64-qemu-virt"))] {
         mod loongarch64_qemu_virt;
         pub use self::loongarch64_qemu_virt::*;
+    } else if #[cfg(all(feature = "host", target_os = "linux"))] {
+        mod linux_host;
+        pub use self::linux_host::*;
     } else {
         mod dummy;
         pub use self::dummy::*;
@@ -1,6 +1,9 @@
 use riscv::register::satp;
 
-use axconfig::{TASK_STACK_SIZE, plat::PHYS_VIRT_OFFSET};
+use axconfig::{
+    TASK_STACK_SIZE,
+    plat::{KERNEL_BASE_PADDR, PHYS_VIRT_OFFSET},
+};
 
 #[unsafe(link_section = ".bss.stack")]
 static mut BOOT_STACK: [u8; TASK_STACK_SIZE] = [0; TASK_STACK_SIZE];
@@ -8,16 +11,46 @@ static mut BOOT_STACK: [u8; TASK_STACK_SIZE] = [0; TASK_STACK_SIZE];
 #[unsafe(link_section = ".data.boot_page_table")]
 static mut BOOT_PT_SV39: [u64; 512] = [0; 512];
 
-#[allow(clippy::identity_op)] // (0x0 << 10) here makes sense because it's an address
+/// Maps the physical memory and the MMIO regions in 1G blocks, at both the
+/// identity addresses and the linear mapping, see [`crate::mem::boot_blocks`].
+/// The block of the kernel image is also mapped at its virtual base, if it is
+/// relocated out of the linear mapping.
+///
+/// All the blocks are mapped as `VRWX_GAD`, as Sv39 has no memory types.
 unsafe fn init_boot_page_table() {
-    // 0x0000_0000..0x4000_0000, VRWX_GAD, 1G block
-    BOOT_PT_SV39[0] = (0x0 << 10) | 0xef;
-    // 0x8000_0000..0xc000_0000, VRWX_GAD, 1G block
-    BOOT_PT_SV39[2] = (0x80000 << 10) | 0xef;
-    // 0xffff_ffc0_0000_0000..0xffff_ffc0_4000_0000, VRWX_GAD, 1G block
-    BOOT_PT_SV39[0x100] = (0x0 << 10) | 0xef;
-    // 0xffff_ffc0_8000_0000..0xffff_ffc0_c000_0000, VRWX_GAD, 1G block
-    BOOT_PT_SV39[0x102] = (0x80000 << 10) | 0xef;
+    let block_index = |vaddr: usize| (vaddr / crate::mem::BOOT_BLOCK_SIZE) % 512;
+    for (paddr, _) in crate::mem::boot_blocks() {
+        let paddr = paddr.as_usize();
+        let pte = ((paddr >> 12) << 10) as u64 | 0xef;
+        // e.g. 0xffff_ffc0_8000_0000..0xffff_ffc0_c000_0000 for the block at
+        // 0x8000_0000, in entry 0x102
+        unsafe {
+            BOOT_PT_SV39[block_index(paddr)] = pte;
+            BOOT_PT_SV39[block_index(paddr.wrapping_add(PHYS_VIRT_OFFSET))] = pte;
+        }
+    }
+    let image_offset = crate::mem::kernel_image_offset();
+    if image_offset != PHYS_VIRT_OFFSET {
+        let paddr = KERNEL_BASE_PADDR & !(crate::mem::BOOT_BLOCK_SIZE - 1);
+        let pte = ((paddr >> 12) << 10) as u64 | 0xef;
+        unsafe { BOOT_PT_SV39[block_index(paddr.wrapping_add(image_offset))] = pte };
+    }
+}
+
+/// Returns the offset of the virtual addresses of the kernel image to the
+/// physical ones, after moving the image to a random virtual base with the
+/// `kaslr` feature.
+#[cfg(not(feature = "kaslr"))]
+unsafe extern "C" fn relocate_image(_dtb: usize) -> usize {
+    PHYS_VIRT_OFFSET
+}
+
+#[cfg(feature = "kaslr")]
+use crate::kaslr::relocate_image;
+
+#[cfg(feature = "smp")]
+extern "C" fn kernel_image_offset() -> usize {
+    crate::mem::kernel_image_offset()
 }
 
 unsafe fn init_mmu() {
@@ -41,11 +74,14 @@ unsafe extern "C" fn _start() -> ! {
         li      t0, {boot_stack_size}
         add     sp, sp, t0              // setup boot stack
 
+        mv      a0, s1
+        call    {relocate_image}        // get the virtual offset of the image
+        mv      s2, a0
+
         call    {init_boot_page_table}
         call    {init_mmu}              // setup boot page table and enabel MMU
 
-        li      s2, {phys_virt_offset}  // fix up virtual high address
-        add     sp, sp, s2
+        add     sp, sp, s2              // fix up virtual high address
 
         mv      a0, s0
         mv      a1, s1
@@ -53,9 +89,9 @@ unsafe extern "C" fn _start() -> ! {
         add     a2, a2, s2
         jalr    a2                      // call rust_entry(hartid, dtb)
         j       .",
-        phys_virt_offset = const PHYS_VIRT_OFFSET,
         boot_stack_size = const TASK_STACK_SIZE,
         boot_stack = sym BOOT_STACK,
+        relocate_image = sym relocate_image,
         init_boot_page_table = sym init_boot_page_table,
         init_mmu = sym init_mmu,
         entry = sym super::rust_entry,
@@ -75,18 +111,20 @@ unsafe extern "C" fn _start_secondary() -> ! {
         mv      sp, a1                  // set SP
 
         call    {init_mmu}              // setup boot page table and enabel MMU
+        call    {kernel_image_offset}   // get the virtual offset of the image
+        mv      s2, a0
 
         li      s1, {phys_virt_offset}  // fix up virtual high address
-        add     a1, a1, s1
         add     sp, sp, s1
 
         mv      a0, s0
         la      a1, {entry}
-        add     a1, a1, s1
+        add     a1, a1, s2
         jalr    a1                      // call rust_entry_secondary(hartid)
         j       .",
         phys_virt_offset = const PHYS_VIRT_OFFSET,
         init_mmu = sym init_mmu,
+        kernel_image_offset = sym kernel_image_offset,
         entry = sym super::rust_entry_secondary,
     )
 }
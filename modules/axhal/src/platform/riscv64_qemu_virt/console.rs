@@ -60,3 +60,57 @@ pub fn read_bytes(bytes: &mut [u8]) -> usize {
     ))
     .value
 }
+
+/// The ns16550 UART behind the SBI console, only accessed directly to take
+/// the input on its receive interrupt.
+#[cfg(feature = "console-irq")]
+mod uart {
+    use memory_addr::PhysAddr;
+
+    use crate::mem::phys_to_virt;
+
+    const UART_BASE: PhysAddr = pa!(axconfig::devices::UART_PADDR);
+    /// The receiver buffer register.
+    const RBR: usize = 0;
+    /// The interrupt enable register.
+    const IER: usize = 1;
+    /// The line status register.
+    const LSR: usize = 5;
+    /// The received data available interrupt in `IER`.
+    const IER_RX_AVAILABLE: u8 = 1;
+    /// The data ready bit in `LSR`.
+    const LSR_DATA_READY: u8 = 1;
+
+    fn reg(offset: usize) -> *mut u8 {
+        phys_to_virt(UART_BASE + offset).as_mut_ptr()
+    }
+
+    /// Reads a byte received, or returns [`None`] if there is none.
+    pub fn getchar() -> Option<u8> {
+        unsafe {
+            if reg(LSR).read_volatile() & LSR_DATA_READY != 0 {
+                Some(reg(RBR).read_volatile())
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn enable_rx_irq() {
+        unsafe { reg(IER).write_volatile(IER_RX_AVAILABLE) };
+    }
+}
+
+/// Takes the console input on the UART receive interrupt.
+#[cfg(feature = "console-irq")]
+pub(super) fn init_irq() {
+    if crate::irq::register_handler(crate::platform::irq::UART_IRQ_NUM, handle_irq) {
+        uart::enable_rx_irq();
+        crate::console::enable_input_irq();
+    }
+}
+
+#[cfg(feature = "console-irq")]
+fn handle_irq() {
+    crate::console::handle_input_irq(uart::getchar);
+}
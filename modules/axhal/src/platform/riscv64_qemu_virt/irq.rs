@@ -1,14 +1,18 @@
-//! TODO: PLIC
+//! The local interrupts in `scause`, and the external ones from the PLIC.
+//!
+//! An external IRQ is numbered by its interrupt source of the PLIC, and is
+//! routed to the supervisor context of the hart enabling it.
 
 use crate::irq::IrqHandler;
+use crate::mem::phys_to_virt;
 use lazyinit::LazyInit;
+use memory_addr::PhysAddr;
 use riscv::register::sie;
 
 /// `Interrupt` bit in `scause`
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
 
 /// Supervisor software interrupt in `scause`
-#[allow(unused)]
 pub(super) const S_SOFT: usize = INTC_IRQ_BASE + 1;
 
 /// Supervisor timer interrupt in `scause`
@@ -19,44 +23,107 @@ pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 
 static TIMER_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
 
+static IPI_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
+
 /// The maximum number of IRQs.
 pub const MAX_IRQ_COUNT: usize = 1024;
 
 /// The timer IRQ number (supervisor timer interrupt in `scause`).
 pub const TIMER_IRQ_NUM: usize = S_TIMER;
 
-macro_rules! with_cause {
-    ($cause: expr, @TIMER => $timer_op: expr, @EXT => $ext_op: expr $(,)?) => {
-        match $cause {
-            S_TIMER => $timer_op,
-            S_EXT => $ext_op,
-            _ => panic!("invalid trap cause: {:#x}", $cause),
+/// The IPI IRQ number (supervisor software interrupt in `scause`).
+pub const IPI_IRQ_NUM: usize = S_SOFT;
+
+/// The UART IRQ number (interrupt source of the PLIC).
+pub const UART_IRQ_NUM: usize = axconfig::devices::UART_IRQ;
+
+/// The PLIC, of the layout in the RISC-V PLIC specification.
+mod plic {
+    use super::*;
+
+    const PLIC_BASE: PhysAddr = pa!(axconfig::devices::PLIC_PADDR);
+    const PRIORITY_OFFSET: usize = 0;
+    const ENABLE_OFFSET: usize = 0x2000;
+    const ENABLE_STRIDE: usize = 0x80;
+    const CONTEXT_OFFSET: usize = 0x20_0000;
+    const CONTEXT_STRIDE: usize = 0x1000;
+    const THRESHOLD: usize = 0;
+    const CLAIM_COMPLETE: usize = 4;
+
+    fn reg(offset: usize) -> *mut u32 {
+        phys_to_virt(PLIC_BASE + offset).as_mut_ptr() as *mut u32
+    }
+
+    /// The supervisor context of the current hart, after its machine one.
+    fn context() -> usize {
+        2 * crate::cpu::this_cpu_id() + 1
+    }
+
+    fn context_reg(offset: usize) -> *mut u32 {
+        reg(CONTEXT_OFFSET + context() * CONTEXT_STRIDE + offset)
+    }
+
+    /// Enables or disables the source `irq` for the current hart.
+    pub fn set_enable(irq: usize, enabled: bool) {
+        let enable = reg(ENABLE_OFFSET + context() * ENABLE_STRIDE + irq / 32 * 4);
+        unsafe {
+            // Any non-zero priority is above the threshold 0.
+            reg(PRIORITY_OFFSET + irq * 4).write_volatile(1);
+            let bits = enable.read_volatile();
+            let bit = 1 << (irq % 32);
+            enable.write_volatile(if enabled { bits | bit } else { bits & !bit });
         }
-    };
+    }
+
+    /// Takes the pending source of the highest priority, if any.
+    pub fn claim() -> Option<usize> {
+        match unsafe { context_reg(CLAIM_COMPLETE).read_volatile() } {
+            0 => None,
+            irq => Some(irq as usize),
+        }
+    }
+
+    /// Completes the source `irq` taken by [`claim`].
+    pub fn complete(irq: usize) {
+        unsafe { context_reg(CLAIM_COMPLETE).write_volatile(irq as u32) };
+    }
+
+    /// Takes the sources of all priorities on the current hart.
+    pub fn init_percpu() {
+        unsafe { context_reg(THRESHOLD).write_volatile(0) };
+    }
 }
 
 /// Enables or disables the given IRQ.
-pub fn set_enable(scause: usize, _enabled: bool) {
-    if scause == S_EXT {
-        // TODO: set enable in PLIC
+///
+/// Only the external IRQs can be disabled, on the current hart.
+pub fn set_enable(irq: usize, enabled: bool) {
+    if irq < MAX_IRQ_COUNT {
+        plic::set_enable(irq, enabled);
     }
 }
 
-/// Registers an IRQ handler for the given IRQ.
+/// Registers an IRQ handler for the given IRQ, the timer or IPI one in
+/// `scause`, or an external one of the PLIC.
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
 /// the registration failed.
-pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
-    with_cause!(
-        scause,
-        @TIMER => if !TIMER_HANDLER.is_inited() {
-            TIMER_HANDLER.init_once(handler);
-            true
-        } else {
-            false
-        },
-        @EXT => crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler),
-    )
+pub fn register_handler(irq: usize, handler: IrqHandler) -> bool {
+    let slot = match irq {
+        S_TIMER => &TIMER_HANDLER,
+        S_SOFT => &IPI_HANDLER,
+        _ => return crate::irq::register_handler_common(irq, handler),
+    };
+    if slot.is_inited() {
+        return false;
+    }
+    slot.init_once(handler);
+    true
+}
+
+/// Sends an IPI to the given hart.
+pub fn send_ipi(hartid: usize) {
+    sbi_rt::send_ipi(sbi_rt::HartMask::from_mask_base(1, hartid));
 }
 
 /// Dispatches the IRQ.
@@ -65,17 +132,33 @@ pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
 /// up in the IRQ handler table and calls the corresponding handler. If
 /// necessary, it also acknowledges the interrupt controller after handling.
 pub fn dispatch_irq(scause: usize) {
-    with_cause!(
-        scause,
-        @TIMER => {
+    match scause {
+        S_TIMER => {
             trace!("IRQ: timer");
+            crate::irq::record_irq(S_TIMER - INTC_IRQ_BASE);
             TIMER_HANDLER();
-        },
-        @EXT => crate::irq::dispatch_irq_common(0), // TODO: get IRQ number from PLIC
-    );
+        }
+        S_SOFT => {
+            trace!("IRQ: IPI");
+            crate::irq::record_irq(S_SOFT - INTC_IRQ_BASE);
+            // Clear the pending supervisor software interrupt (`sip.SSIP`).
+            unsafe { core::arch::asm!("csrc sip, {}", in(reg) 1 << 1) };
+            if let Some(handler) = IPI_HANDLER.get() {
+                handler();
+            }
+        }
+        S_EXT => {
+            while let Some(irq) = plic::claim() {
+                crate::irq::dispatch_irq_common(irq);
+                plic::complete(irq);
+            }
+        }
+        _ => panic!("invalid trap cause: {:#x}", scause),
+    }
 }
 
 pub(super) fn init_percpu() {
+    plic::init_percpu();
     // enable soft interrupts, timer interrupts, and external interrupts
     unsafe {
         sie::set_ssoft();
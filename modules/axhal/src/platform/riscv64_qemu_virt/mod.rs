@@ -41,6 +41,8 @@ pub fn platform_init() {
     #[cfg(feature = "irq")]
     self::irq::init_percpu();
     self::time::init_percpu();
+    #[cfg(feature = "console-irq")]
+    self::console::init_irq();
 }
 
 /// Initializes the platform devices for secondary CPUs.
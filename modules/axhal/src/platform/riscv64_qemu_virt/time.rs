@@ -55,6 +55,6 @@ pub(super) fn init_early() {
 }
 
 pub(super) fn init_percpu() {
-    #[cfg(feature = "irq")]
+    #[cfg(all(feature = "irq", not(feature = "vtime")))]
     sbi_rt::set_timer(0);
 }
@@ -16,6 +16,7 @@ pub(super) mod vectors {
     pub const APIC_TIMER_VECTOR: u8 = 0xf0;
     pub const APIC_SPURIOUS_VECTOR: u8 = 0xf1;
     pub const APIC_ERROR_VECTOR: u8 = 0xf2;
+    pub const APIC_IPI_VECTOR: u8 = 0xf3;
 }
 
 /// The maximum number of IRQs.
@@ -24,23 +25,40 @@ pub const MAX_IRQ_COUNT: usize = 256;
 /// The timer IRQ number.
 pub const TIMER_IRQ_NUM: usize = APIC_TIMER_VECTOR as usize;
 
+/// The IPI (inter-processor interrupt) IRQ number.
+pub const IPI_IRQ_NUM: usize = APIC_IPI_VECTOR as usize;
+
+/// The IRQ number of the COM1 UART, on the pin of the ISA IRQ 4.
+pub const UART_IRQ_NUM: usize = IO_APIC_VECTOR_BASE as usize + 4;
+
 const IO_APIC_BASE: PhysAddr = pa!(0xFEC0_0000);
 
+/// The vector of the IO APIC pin 0, and the following pins are mapped to the
+/// vectors in order.
+const IO_APIC_VECTOR_BASE: u8 = 0x20;
+/// The number of the IO APIC pins.
+const IO_APIC_PINS: u8 = 24;
+
 static LOCAL_APIC: SyncUnsafeCell<MaybeUninit<LocalApic>> =
     SyncUnsafeCell::new(MaybeUninit::uninit());
 static mut IS_X2APIC: bool = false;
 static IO_APIC: LazyInit<SpinNoIrq<IoApic>> = LazyInit::new();
 
 /// Enables or disables the given IRQ.
+///
+/// Only the vectors of the IO APIC pins can be disabled.
 #[cfg(feature = "irq")]
 pub fn set_enable(vector: usize, enabled: bool) {
     // should not affect LAPIC interrupts
-    if vector < APIC_TIMER_VECTOR as _ {
+    let Some(pin) = vector.checked_sub(IO_APIC_VECTOR_BASE as usize) else {
+        return;
+    };
+    if pin < IO_APIC_PINS as usize {
         unsafe {
             if enabled {
-                IO_APIC.lock().enable_irq(vector as u8);
+                IO_APIC.lock().enable_irq(pin as u8);
             } else {
-                IO_APIC.lock().disable_irq(vector as u8);
+                IO_APIC.lock().disable_irq(pin as u8);
             }
         }
     }
@@ -66,6 +84,12 @@ pub fn dispatch_irq(vector: usize) {
     unsafe { local_apic().end_of_interrupt() };
 }
 
+/// Sends an IPI to the given CPU.
+#[cfg(feature = "irq")]
+pub fn send_ipi(cpu_id: usize) {
+    unsafe { local_apic().send_ipi(APIC_IPI_VECTOR, raw_apic_id(cpu_id as u8)) };
+}
+
 pub(super) fn local_apic<'a>() -> &'a mut LocalApic {
     // It's safe as `LOCAL_APIC` is initialized in `init_primary`.
     unsafe { LOCAL_APIC.get().as_mut().unwrap().assume_init_mut() }
@@ -117,7 +141,9 @@ pub(super) fn init_primary() {
     }
 
     info!("Initialize IO APIC...");
-    let io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
+    let mut io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
+    // All pins are masked and routed to the BSP.
+    unsafe { io_apic.init(IO_APIC_VECTOR_BASE) };
     IO_APIC.init_once(SpinNoIrq::new(io_apic));
 }
 
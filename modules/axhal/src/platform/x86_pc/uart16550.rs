@@ -65,6 +65,12 @@ impl Uart16550 {
         }
     }
 
+    #[cfg(feature = "console-irq")]
+    fn enable_rx_irq(&mut self) {
+        // Received data available interrupt
+        unsafe { self.int_en.write(0x01) };
+    }
+
     fn line_sts(&mut self) -> LineStsFlags {
         unsafe { LineStsFlags::from_bits_truncate(self.line_sts.read()) }
     }
@@ -125,3 +131,17 @@ pub fn read_bytes(bytes: &mut [u8]) -> usize {
 pub(super) fn init() {
     COM1.lock().init(115200);
 }
+
+/// Takes the console input on the UART receive interrupt.
+#[cfg(feature = "console-irq")]
+pub(super) fn init_irq() {
+    if crate::irq::register_handler(crate::platform::irq::UART_IRQ_NUM, handle_irq) {
+        COM1.lock().enable_rx_irq();
+        crate::console::enable_input_irq();
+    }
+}
+
+#[cfg(feature = "console-irq")]
+fn handle_irq() {
+    crate::console::handle_input_irq(getchar);
+}
@@ -0,0 +1,364 @@
+//! Partitioning of the shared caches and the memory bandwidth, to isolate
+//! the latency of some tasks from the others.
+//!
+//! The tasks are grouped into classes of service, and each class can be
+//! limited to a portion of the caches and of the memory bandwidth. The class
+//! of the current CPU is switched by [`switch_class`], e.g. on context
+//! switches, where class 0 is the default one that all CPUs start with.
+//!
+//! - On x86_64, Intel RDT (Resource Director Technology) is used for the L2
+//!   and L3 cache allocation and the memory bandwidth allocation. The limits
+//!   are kept per CPU package by the hardware, so they are written to each
+//!   CPU the next time it switches the class.
+//! - On AArch64, Arm MPAM (Memory System Resource Partitioning and
+//!   Monitoring) tags the memory accesses with the class as the partition
+//!   ID. The limits are kept in the memory system components discovered by
+//!   the ACPI MPAM table, which is not supported, so they must be set by the
+//!   firmware.
+//! - On other architectures, only the default class is available.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// The maximum number of the classes.
+pub const MAX_CLASSES: usize = 16;
+
+/// A resource shared by the CPUs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// The L2 cache, partitioned by ways.
+    L2Cache,
+    /// The L3 cache, partitioned by ways.
+    L3Cache,
+    /// The memory bandwidth, throttled by percentage.
+    MemoryBandwidth,
+}
+
+/// The errors of configuring the classes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QosError {
+    /// The resource can not be partitioned on this hardware.
+    Unsupported,
+    /// The class is not available for the resource.
+    InvalidClass,
+    /// The cache mask or the bandwidth is out of range, or the cache mask is
+    /// empty or not contiguous.
+    InvalidLimit,
+}
+
+/// The limits of the classes, whose changes are applied to each CPU lazily.
+struct Limits {
+    l2_masks: [u64; MAX_CLASSES],
+    l3_masks: [u64; MAX_CLASSES],
+    /// The percentages of the memory bandwidth.
+    bandwidths: [u32; MAX_CLASSES],
+}
+
+static LIMITS: SpinNoIrq<Limits> = SpinNoIrq::new(Limits {
+    l2_masks: [u64::MAX; MAX_CLASSES],
+    l3_masks: [u64::MAX; MAX_CLASSES],
+    bandwidths: [100; MAX_CLASSES],
+});
+
+/// Increased on each change of [`LIMITS`].
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The class of the current CPU.
+#[percpu::def_percpu]
+static CURRENT_CLASS: usize = 0;
+
+/// The generation of [`LIMITS`] applied to the current CPU.
+#[percpu::def_percpu]
+static APPLIED_GENERATION: usize = 0;
+
+/// Returns the number of the classes available for `res`, or 0 if it can not
+/// be partitioned.
+pub fn num_classes(res: Resource) -> usize {
+    arch::num_classes(res).min(MAX_CLASSES)
+}
+
+/// Returns the number of the bits of the cache masks of `res`, i.e. the
+/// number of the portions the cache can be partitioned into, or 0 if it can
+/// not be partitioned.
+pub fn cache_mask_bits(res: Resource) -> u32 {
+    match res {
+        Resource::L2Cache | Resource::L3Cache => arch::cache_mask_bits(res),
+        Resource::MemoryBandwidth => 0,
+    }
+}
+
+fn check_class(res: Resource, class: usize) -> Result<(), QosError> {
+    match num_classes(res) {
+        0 => Err(QosError::Unsupported),
+        n if class >= n => Err(QosError::InvalidClass),
+        _ => Ok(()),
+    }
+}
+
+/// Limits the class to the portions of the cache `res` in `mask`, which must
+/// be a nonempty contiguous range of bits. The masks of the classes may
+/// overlap.
+pub fn set_cache_mask(class: usize, res: Resource, mask: u64) -> Result<(), QosError> {
+    if res == Resource::MemoryBandwidth {
+        return Err(QosError::InvalidLimit);
+    }
+    check_class(res, class)?;
+    let bits = cache_mask_bits(res);
+    if mask == 0 || (bits < 64 && mask >> bits != 0) {
+        return Err(QosError::InvalidLimit);
+    }
+    // Contiguous bits become zero after adding the lowest bit.
+    if (mask + (mask & mask.wrapping_neg())) & mask != 0 {
+        return Err(QosError::InvalidLimit);
+    }
+    let mut limits = LIMITS.lock();
+    match res {
+        Resource::L2Cache => limits.l2_masks[class] = mask,
+        _ => limits.l3_masks[class] = mask,
+    }
+    GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
+}
+
+/// Limits the class to `percent` (1 to 100) of the memory bandwidth, which
+/// is rounded down to the granularity of the hardware.
+pub fn set_memory_bandwidth(class: usize, percent: u32) -> Result<(), QosError> {
+    check_class(Resource::MemoryBandwidth, class)?;
+    if !(1..=100).contains(&percent) {
+        return Err(QosError::InvalidLimit);
+    }
+    LIMITS.lock().bandwidths[class] = percent;
+    GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
+}
+
+/// Returns the class of the current CPU.
+pub fn current_class() -> usize {
+    CURRENT_CLASS.read_current()
+}
+
+/// Switches the current CPU to `class`, after applying the changes of the
+/// limits if there are any.
+///
+/// It is a no-op if `class` is not available for any resource.
+pub fn switch_class(class: usize) {
+    if class >= MAX_CLASSES {
+        return;
+    }
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    let generation = GENERATION.load(Ordering::Acquire);
+    if APPLIED_GENERATION.read_current() != generation {
+        arch::apply_limits(&LIMITS.lock());
+        APPLIED_GENERATION.write_current(generation);
+    }
+    if CURRENT_CLASS.read_current() != class {
+        arch::switch_class(class);
+        CURRENT_CLASS.write_current(class);
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "none"))]
+mod arch {
+    //! Intel RDT, see the Intel SDM, Volume 3, Section 19.19.
+
+    use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+    use lazyinit::LazyInit;
+    use x86::msr::{rdmsr, wrmsr};
+
+    use super::{Limits, MAX_CLASSES, Resource};
+
+    const IA32_PQR_ASSOC: u32 = 0xc8f;
+    const IA32_L3_MASK_0: u32 = 0xc90;
+    const IA32_L2_MASK_0: u32 = 0xd10;
+    const IA32_MBA_THRTL_0: u32 = 0xd50;
+
+    /// The capabilities enumerated by `CPUID` leaf 0x10.
+    #[derive(Default)]
+    struct Rdt {
+        l3_classes: usize,
+        l3_mask_bits: u32,
+        l2_classes: usize,
+        l2_mask_bits: u32,
+        mba_classes: usize,
+        mba_max_delay: u32,
+        mba_linear: bool,
+    }
+
+    static RDT: LazyInit<Rdt> = LazyInit::new();
+
+    fn rdt() -> &'static Rdt {
+        if !RDT.is_inited() {
+            RDT.call_once(detect);
+        }
+        &RDT
+    }
+
+    fn detect() -> Rdt {
+        let mut rdt = Rdt::default();
+        let max_leaf = unsafe { __cpuid(0) }.eax;
+        // RDT allocation is enumerated by `CPUID.(EAX=07H, ECX=0):EBX[15]`.
+        if max_leaf < 0x10 || unsafe { __cpuid_count(7, 0) }.ebx & (1 << 15) == 0 {
+            return rdt;
+        }
+        let res = unsafe { __cpuid_count(0x10, 0) }.ebx;
+        if res & (1 << 1) != 0 {
+            let l3 = unsafe { __cpuid_count(0x10, 1) };
+            rdt.l3_mask_bits = (l3.eax & 0x1f) + 1;
+            rdt.l3_classes = (l3.edx & 0xffff) as usize + 1;
+        }
+        if res & (1 << 2) != 0 {
+            let l2 = unsafe { __cpuid_count(0x10, 2) };
+            rdt.l2_mask_bits = (l2.eax & 0x1f) + 1;
+            rdt.l2_classes = (l2.edx & 0xffff) as usize + 1;
+        }
+        if res & (1 << 3) != 0 {
+            let mba = unsafe { __cpuid_count(0x10, 3) };
+            rdt.mba_max_delay = (mba.eax & 0xfff) + 1;
+            rdt.mba_linear = mba.ecx & (1 << 2) != 0;
+            rdt.mba_classes = (mba.edx & 0xffff) as usize + 1;
+        }
+        info!(
+            "Intel RDT: L3 CAT {} classes, L2 CAT {} classes, MBA {} classes",
+            rdt.l3_classes, rdt.l2_classes, rdt.mba_classes
+        );
+        rdt
+    }
+
+    pub fn num_classes(res: Resource) -> usize {
+        let rdt = rdt();
+        match res {
+            Resource::L2Cache => rdt.l2_classes,
+            Resource::L3Cache => rdt.l3_classes,
+            // Only the linear throttling of the delay values is supported.
+            Resource::MemoryBandwidth if rdt.mba_linear => rdt.mba_classes,
+            Resource::MemoryBandwidth => 0,
+        }
+    }
+
+    pub fn cache_mask_bits(res: Resource) -> u32 {
+        match res {
+            Resource::L2Cache => rdt().l2_mask_bits,
+            _ => rdt().l3_mask_bits,
+        }
+    }
+
+    /// Returns `mask` limited to the bits of the cache.
+    fn cache_mask(mask: u64, bits: u32) -> u64 {
+        if bits < 64 {
+            mask & ((1 << bits) - 1)
+        } else {
+            mask
+        }
+    }
+
+    pub fn apply_limits(limits: &Limits) {
+        let rdt = rdt();
+        for class in 0..rdt.l3_classes.min(MAX_CLASSES) {
+            let mask = cache_mask(limits.l3_masks[class], rdt.l3_mask_bits);
+            unsafe { wrmsr(IA32_L3_MASK_0 + class as u32, mask) };
+        }
+        for class in 0..rdt.l2_classes.min(MAX_CLASSES) {
+            let mask = cache_mask(limits.l2_masks[class], rdt.l2_mask_bits);
+            unsafe { wrmsr(IA32_L2_MASK_0 + class as u32, mask) };
+        }
+        if rdt.mba_linear {
+            for class in 0..rdt.mba_classes.min(MAX_CLASSES) {
+                // The delay is the percentage of the bandwidth throttled.
+                let delay = (100 - limits.bandwidths[class]).min(rdt.mba_max_delay - 1);
+                unsafe { wrmsr(IA32_MBA_THRTL_0 + class as u32, delay as u64) };
+            }
+        }
+    }
+
+    pub fn switch_class(class: usize) {
+        let rdt = rdt();
+        let classes = rdt.l3_classes.max(rdt.l2_classes).max(rdt.mba_classes);
+        if class >= classes {
+            return;
+        }
+        // The class is in the upper half, keep the monitoring ID in the lower.
+        unsafe {
+            let assoc = rdmsr(IA32_PQR_ASSOC) & 0xffff_ffff;
+            wrmsr(IA32_PQR_ASSOC, assoc | (class as u64) << 32);
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_os = "none"))]
+mod arch {
+    //! Arm MPAM, only tagging the memory accesses with the partition ID.
+
+    use core::arch::asm;
+
+    use lazyinit::LazyInit;
+
+    use super::{Limits, Resource};
+
+    /// The number of the partition IDs, or 0 if MPAM is not implemented.
+    static NUM_PARTIDS: LazyInit<usize> = LazyInit::new();
+
+    fn num_partids() -> usize {
+        if !NUM_PARTIDS.is_inited() {
+            NUM_PARTIDS.call_once(|| {
+                // `ID_AA64PFR0_EL1.MPAM`, bits [43:40].
+                let pfr0: u64;
+                unsafe { asm!("mrs {}, ID_AA64PFR0_EL1", out(reg) pfr0) };
+                if (pfr0 >> 40) & 0xf == 0 {
+                    return 0;
+                }
+                // `MPAMIDR_EL1.PARTID_MAX`, bits [15:0].
+                let mpamidr: u64;
+                unsafe { asm!("mrs {}, S3_0_C10_C4_4", out(reg) mpamidr) };
+                (mpamidr & 0xffff) as usize + 1
+            });
+        }
+        *NUM_PARTIDS
+    }
+
+    pub fn num_classes(res: Resource) -> usize {
+        let _ = res;
+        num_partids()
+    }
+
+    pub fn cache_mask_bits(_res: Resource) -> u32 {
+        0
+    }
+
+    pub fn apply_limits(_limits: &Limits) {}
+
+    pub fn switch_class(class: usize) {
+        if class >= num_partids() {
+            return;
+        }
+        // The same partition ID for the instruction and the data accesses, in
+        // `PARTID_I` and `PARTID_D`, with `MPAMEN` set.
+        let partid = class as u64;
+        let value = (1 << 63) | (partid << 16) | partid;
+        unsafe {
+            // `MPAM1_EL1` for the kernel, and `MPAM0_EL1` for the user space.
+            asm!("msr S3_0_C10_C5_0, {0}", "msr S3_0_C10_C5_1, {0}", "isb", in(reg) value);
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_os = "none"),
+    all(target_arch = "aarch64", target_os = "none")
+)))]
+mod arch {
+    use super::{Limits, Resource};
+
+    pub fn num_classes(_res: Resource) -> usize {
+        0
+    }
+
+    pub fn cache_mask_bits(_res: Resource) -> u32 {
+        0
+    }
+
+    pub fn apply_limits(_limits: &Limits) {}
+
+    pub fn switch_class(_class: usize) {}
+}
@@ -8,12 +8,26 @@ pub use core::time::Duration;
 /// represent a duration, but a clock time.
 pub type TimeValue = Duration;
 
+#[cfg(feature = "vtime")]
+mod vtime;
+
 #[cfg(feature = "irq")]
 pub use crate::platform::irq::TIMER_IRQ_NUM;
-#[cfg(feature = "irq")]
+#[cfg(all(feature = "irq", not(feature = "vtime")))]
 pub use crate::platform::time::set_oneshot_timer;
+#[cfg(not(feature = "vtime"))]
 pub use crate::platform::time::{current_ticks, epochoffset_nanos, nanos_to_ticks, ticks_to_nanos};
 
+#[cfg(all(feature = "irq", feature = "vtime"))]
+pub(crate) use self::vtime::register_timer_handler;
+#[cfg(feature = "vtime")]
+pub use self::vtime::{
+    advance, advance_to, busy_wait_until, current_ticks, epochoffset_nanos, nanos_to_ticks,
+    ticks_to_nanos,
+};
+#[cfg(all(feature = "irq", feature = "vtime"))]
+pub use self::vtime::{advance_to_next_timer, set_oneshot_timer};
+
 /// Number of milliseconds in a second.
 pub const MILLIS_PER_SEC: u64 = 1_000;
 /// Number of microseconds in a second.
@@ -51,8 +65,9 @@ pub fn busy_wait(dur: Duration) {
 }
 
 /// Busy waiting until reaching the given deadline.
+#[cfg(not(feature = "vtime"))]
 pub fn busy_wait_until(deadline: TimeValue) {
     while wall_time() < deadline {
-        core::hint::spin_loop();
+        crate::arch::cpu_relax();
     }
 }
@@ -0,0 +1,141 @@
+//! The virtual clock, with the `vtime` feature.
+//!
+//! It replaces the hardware clock for deterministic tests: the clock starts
+//! at 0 on boot, as well as the wall clock (the epoch), and only advances by
+//! [`advance`], [`advance_to_next_timer`] or the busy waits. The hardware
+//! timer is never armed, instead the one-shot timer is recorded, and the
+//! timer IRQ is dispatched in the caller of [`advance`] when the clock
+//! reaches its deadline, as long as IRQs are enabled there. The timers
+//! expired while IRQs are disabled stay pending until the next advance.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{Duration, TimeValue};
+
+#[cfg(feature = "smp")]
+compile_error!("the `vtime` feature is not supported with the `smp` feature");
+
+/// The current virtual time in nanoseconds.
+static NOW_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// The deadline of the one-shot timer in nanoseconds, `u64::MAX` if it is
+/// not armed.
+#[cfg(feature = "irq")]
+static DEADLINE_NANOS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// The handler of the timer IRQ, which is never registered to the interrupt
+/// controller.
+#[cfg(feature = "irq")]
+static TIMER_HANDLER: lazyinit::LazyInit<crate::irq::IrqHandler> = lazyinit::LazyInit::new();
+
+/// Returns the current clock time in hardware ticks.
+///
+/// A tick is a nanosecond of the virtual clock.
+pub fn current_ticks() -> u64 {
+    NOW_NANOS.load(Ordering::Acquire)
+}
+
+/// Converts hardware ticks to nanoseconds.
+pub fn ticks_to_nanos(ticks: u64) -> u64 {
+    ticks
+}
+
+/// Converts nanoseconds to hardware ticks.
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    nanos
+}
+
+/// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
+///
+/// The virtual clock starts at the epoch.
+pub fn epochoffset_nanos() -> u64 {
+    0
+}
+
+/// Set a one-shot timer.
+///
+/// A timer interrupt will be triggered at the specified monotonic time
+/// deadline (in nanoseconds), once the virtual clock reaches it.
+#[cfg(feature = "irq")]
+pub fn set_oneshot_timer(deadline_ns: u64) {
+    DEADLINE_NANOS.store(deadline_ns, Ordering::Release);
+}
+
+/// Registers the handler of the timer IRQ, called on the expiration of the
+/// one-shot timer.
+#[cfg(feature = "irq")]
+pub(crate) fn register_timer_handler(handler: crate::irq::IrqHandler) -> bool {
+    if TIMER_HANDLER.is_inited() {
+        warn!("register handler for the virtual timer IRQ failed");
+        return false;
+    }
+    TIMER_HANDLER.init_once(handler);
+    true
+}
+
+/// Fires the one-shot timer if it has expired at `now` and IRQs are enabled,
+/// returns whether it has been fired.
+#[cfg(feature = "irq")]
+fn fire_timer(now: u64) -> bool {
+    let deadline = DEADLINE_NANOS.load(Ordering::Acquire);
+    if deadline > now || !crate::arch::irqs_enabled() {
+        return false;
+    }
+    NOW_NANOS.fetch_max(deadline, Ordering::AcqRel);
+    DEADLINE_NANOS.store(u64::MAX, Ordering::Release);
+
+    // Run the handler like a real IRQ, rescheduling may occur when the guard
+    // is dropped.
+    let guard = kernel_guard::NoPreemptIrqSave::new();
+    crate::irq::record_irq(super::TIMER_IRQ_NUM);
+    match TIMER_HANDLER.get() {
+        Some(handler) => handler(),
+        None => warn!("Unhandled IRQ {}", super::TIMER_IRQ_NUM),
+    }
+    drop(guard);
+    true
+}
+
+/// Advances the virtual clock to the monotonic time `nanos`, firing the
+/// one-shot timer at its deadline on the way.
+///
+/// It never goes backwards.
+pub fn advance_to(nanos: u64) {
+    #[cfg(feature = "irq")]
+    while fire_timer(nanos) {}
+    NOW_NANOS.fetch_max(nanos, Ordering::AcqRel);
+}
+
+/// Advances the virtual clock by `dur`, firing the one-shot timer at its
+/// deadline on the way.
+pub fn advance(dur: Duration) {
+    advance_to(current_ticks().saturating_add(dur.as_nanos() as u64));
+}
+
+/// Advances the virtual clock to the deadline of the one-shot timer and
+/// fires it, for the idle task to skip the time in which nothing is runnable.
+///
+/// Returns `false` if the timer is not armed or IRQs are disabled.
+#[cfg(feature = "irq")]
+pub fn advance_to_next_timer() -> bool {
+    fire_timer(DEADLINE_NANOS.load(Ordering::Acquire))
+}
+
+/// Busy waiting until reaching the given deadline.
+///
+/// It spins for the same duration of the hardware clock, as the devices may
+/// wait for it, and then advances the virtual clock to the deadline.
+pub fn busy_wait_until(deadline: TimeValue) {
+    use crate::platform::time as hw;
+
+    let now = current_ticks();
+    let deadline = deadline.as_nanos() as u64;
+    if deadline <= now {
+        return;
+    }
+    let hw_deadline = hw::ticks_to_nanos(hw::current_ticks()) + (deadline - now);
+    while hw::ticks_to_nanos(hw::current_ticks()) < hw_deadline {
+        crate::arch::cpu_relax();
+    }
+    advance_to(deadline);
+}
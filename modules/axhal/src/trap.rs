@@ -8,39 +8,92 @@ pub use linkme::distributed_slice as register_trap_handler;
 
 use crate::arch::TrapFrame;
 
-/// A slice of IRQ handler functions.
+/// A slice of IRQ observer functions.
+///
+/// Multiple observers can be registered, e.g. a profiler sampling the
+/// interrupted code. Each of them is invoked with the IRQ number on every IRQ,
+/// with preemption disabled, before the IRQ is dispatched to the handler
+/// registered with [`crate::irq::register_handler`], which they can't prevent.
 #[def_trap_handler]
-pub static IRQ: [fn(usize) -> bool];
+pub static IRQ: [fn(usize)];
 
 /// A slice of page fault handler functions.
+///
+/// Multiple handlers can be registered. Each handler returns `true` if the page
+/// fault is resolved, or `false` to pass it to the next handler.
 #[def_trap_handler]
 pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 
 /// A slice of syscall handler functions.
+///
+/// A handler receives the trap frame of the user task and the syscall number,
+/// and returns the value to be written back to the return value register.
+/// Multiple handlers can be registered, and they are tried in turn until one
+/// returns a value other than `-ENOSYS`.
 #[cfg(feature = "uspace")]
 #[def_trap_handler]
 pub static SYSCALL: [fn(&mut TrapFrame, usize) -> isize];
 
+/// A slice of functions copying the memory of the current user task at the
+/// given address into a kernel buffer, for the syscalls handled by the HAL
+/// itself (i.e., [`SYSCALL_BATCH`]).
+///
+/// The first registered function is used, which returns `false` if the user
+/// memory is not readable. Without one, the user memory is never accessed.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static COPY_FROM_USER: [fn(&mut [u8], usize) -> bool];
+
+/// Like [`COPY_FROM_USER`], copies a kernel buffer to the memory of the
+/// current user task at the given address.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static COPY_TO_USER: [fn(usize, &[u8]) -> bool];
+
 /// A slice of callbacks to be invoked after a trap.
 #[linkme::distributed_slice]
 pub static POST_TRAP: [fn(&mut TrapFrame, bool)];
 
+/// Invokes the handlers registered for the given trap in turn, until one of
+/// them returns `true`.
+///
+/// Handlers are tried in link order, so each of them should return `false`
+/// for the traps it is not interested in, to let the following ones handle it.
+/// Evaluates to `false` if no handler accepted the trap.
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
-        let mut iter = $crate::trap::$trap.iter();
-        if let Some(func) = iter.next() {
-            if iter.next().is_some() {
-                warn!("Multiple handlers for trap {} are not currently supported", stringify!($trap));
-            }
-            func($($args)*)
-        } else {
+        let handlers = &$crate::trap::$trap;
+        if handlers.is_empty() {
             warn!("No registered handler for trap {}", stringify!($trap));
-            false
         }
+        handlers.iter().any(|func| func($($args)*))
     }}
 }
 
+/// Handles an IRQ trap.
+///
+/// The registered [`IRQ`] observers are invoked first, then the IRQ is passed
+/// to the platform IRQ dispatcher.
+pub(crate) fn handle_irq(irq_num: usize) {
+    #[cfg(feature = "irq")]
+    crate::irq::handler_irq(irq_num);
+    #[cfg(not(feature = "irq"))]
+    {
+        let guard = kernel_guard::NoPreempt::new();
+        observe_irq(irq_num);
+        drop(guard);
+        warn!("No registered handler for trap IRQ");
+    }
+}
+
+/// Invokes the registered [`IRQ`] observers, with preemption disabled.
+pub(crate) fn observe_irq(irq_num: usize) {
+    for func in IRQ.iter() {
+        func(irq_num);
+    }
+}
+
 #[unsafe(no_mangle)]
 pub(crate) fn post_trap_callback(tf: &mut TrapFrame, from_user: bool) {
     for cb in crate::trap::POST_TRAP.iter() {
@@ -48,8 +101,147 @@ pub(crate) fn post_trap_callback(tf: &mut TrapFrame, from_user: bool) {
     }
 }
 
-/// Call the external syscall handler.
+/// The error code returned for unhandled syscalls.
+#[cfg(feature = "uspace")]
+const ENOSYS: isize = 38;
+/// The error code returned for invalid batches.
+#[cfg(feature = "uspace")]
+const EINVAL: isize = 22;
+/// The error code returned for batches in inaccessible memory.
+#[cfg(feature = "uspace")]
+const EFAULT: isize = 14;
+
+/// The syscall number of the batch syscall, which is not used by Linux on
+/// any architecture.
+///
+/// It runs the syscalls in an array of [`SyscallBatchEntry`] in order, to
+/// save the trap overhead of frequent small syscalls (e.g., `clock_gettime`
+/// or futex wakeups). The arguments are:
+///
+/// - `arg0`: the address of the array.
+/// - `arg1`: the number of entries, at most [`SYSCALL_BATCH_MAX`].
+/// - `arg2`: flags, [`SYSCALL_BATCH_STOP_ON_ERROR`] or 0.
+///
+/// The result of each syscall is written to its entry, and the number of
+/// syscalls run is returned. Each syscall runs with a copy of the trap frame,
+/// so the ones changing the trap frame of the caller (e.g., `execve`) must not
+/// be batched. Batches can not be nested.
+#[cfg(feature = "uspace")]
+pub const SYSCALL_BATCH: usize = 0x400;
+
+/// The maximum number of syscalls in a batch.
+#[cfg(feature = "uspace")]
+pub const SYSCALL_BATCH_MAX: usize = 256;
+
+/// Stops the batch at the first syscall returning an error.
+#[cfg(feature = "uspace")]
+pub const SYSCALL_BATCH_STOP_ON_ERROR: usize = 1;
+
+/// A syscall in a batch, see [`SYSCALL_BATCH`].
+#[cfg(feature = "uspace")]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyscallBatchEntry {
+    /// The syscall number.
+    pub sysno: usize,
+    /// The syscall arguments.
+    pub args: [usize; 6],
+    /// The return value, written after the syscall.
+    pub ret: isize,
+}
+
+/// Copies the user memory at `src` into `dst` by [`COPY_FROM_USER`].
+#[cfg(feature = "uspace")]
+fn read_user(dst: &mut [u8], src: usize) -> bool {
+    COPY_FROM_USER.first().is_some_and(|func| func(dst, src))
+}
+
+/// Copies `src` to the user memory at `dst` by [`COPY_TO_USER`].
+#[cfg(feature = "uspace")]
+fn write_user(dst: usize, src: &[u8]) -> bool {
+    COPY_TO_USER.first().is_some_and(|func| func(dst, src))
+}
+
+/// Runs the syscalls in a batch, returns the number of syscalls run.
+///
+/// The entries are copied in and their results copied out one by one, as
+/// the user memory is checked by [`COPY_FROM_USER`] and [`COPY_TO_USER`].
+/// If an entry can not be accessed, the batch stops there, and fails with
+/// `EFAULT` if no syscall has run.
+#[cfg(feature = "uspace")]
+fn handle_syscall_batch(tf: &TrapFrame) -> isize {
+    use core::mem::{offset_of, size_of};
+
+    let (entries, count, flags) = (tf.arg0(), tf.arg1(), tf.arg2());
+    if count > SYSCALL_BATCH_MAX || flags & !SYSCALL_BATCH_STOP_ON_ERROR != 0 {
+        return -EINVAL;
+    }
+    if count == 0 {
+        return 0;
+    }
+    if entries == 0 || entries % core::mem::align_of::<SyscallBatchEntry>() != 0 {
+        return -EINVAL;
+    }
+    if entries
+        .checked_add(count * size_of::<SyscallBatchEntry>())
+        .is_none()
+    {
+        return -EFAULT;
+    }
+
+    let mut done = 0;
+    for i in 0..count {
+        let addr = entries + i * size_of::<SyscallBatchEntry>();
+        let mut entry = SyscallBatchEntry::default();
+        // SAFETY: the entry is plain integers, valid for any bytes.
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(
+                &mut entry as *mut SyscallBatchEntry as *mut u8,
+                size_of::<SyscallBatchEntry>(),
+            )
+        };
+        if !read_user(bytes, addr) {
+            break;
+        }
+        let ret = if entry.sysno == SYSCALL_BATCH {
+            -EINVAL
+        } else {
+            let mut entry_tf = *tf;
+            entry_tf.set_arg0(entry.args[0]);
+            entry_tf.set_arg1(entry.args[1]);
+            entry_tf.set_arg2(entry.args[2]);
+            entry_tf.set_arg3(entry.args[3]);
+            entry_tf.set_arg4(entry.args[4]);
+            entry_tf.set_arg5(entry.args[5]);
+            handle_syscall(&mut entry_tf, entry.sysno)
+        };
+        done += 1;
+        let ret_addr = addr + offset_of!(SyscallBatchEntry, ret);
+        if !write_user(ret_addr, &ret.to_ne_bytes()) {
+            break;
+        }
+        // Errors are in `-4095..0`, as in Linux.
+        if flags & SYSCALL_BATCH_STOP_ON_ERROR != 0 && (-4095..0).contains(&ret) {
+            break;
+        }
+    }
+    if done == 0 { -EFAULT } else { done }
+}
+
+/// Call the external syscall handlers.
+///
+/// Returns `-ENOSYS` if no handler accepts the syscall.
 #[cfg(feature = "uspace")]
 pub(crate) fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
-    SYSCALL[0](tf, syscall_num)
+    if syscall_num == SYSCALL_BATCH {
+        return handle_syscall_batch(tf);
+    }
+    for func in SYSCALL.iter() {
+        let ret = func(tf, syscall_num);
+        if ret != -ENOSYS {
+            return ret;
+        }
+    }
+    warn!("Unhandled syscall {}", syscall_num);
+    -ENOSYS
 }
@@ -0,0 +1,38 @@
+//! The event types and codes used by the input subsystem, the same as Linux.
+
+/// Synchronization events.
+pub const EV_SYN: u16 = 0x00;
+/// Key and button events.
+pub const EV_KEY: u16 = 0x01;
+/// LED events.
+pub const EV_LED: u16 = 0x11;
+/// Autorepeat events.
+pub const EV_REP: u16 = 0x14;
+
+/// The end of a frame of events.
+pub const SYN_REPORT: u16 = 0;
+/// Events are dropped as the queue is full.
+pub const SYN_DROPPED: u16 = 3;
+
+/// The Caps Lock key.
+pub const KEY_CAPSLOCK: u16 = 58;
+/// The Num Lock key.
+pub const KEY_NUMLOCK: u16 = 69;
+/// The Scroll Lock key.
+pub const KEY_SCROLLLOCK: u16 = 70;
+/// The first code of the buttons.
+pub const BTN_MISC: u16 = 0x100;
+
+/// The Num Lock LED.
+pub const LED_NUML: u16 = 0x00;
+/// The Caps Lock LED.
+pub const LED_CAPSL: u16 = 0x01;
+/// The Scroll Lock LED.
+pub const LED_SCROLLL: u16 = 0x02;
+/// The maximum code of the LEDs.
+pub const LED_MAX: u16 = 0x0f;
+
+/// The delay of autorepeat.
+pub const REP_DELAY: u16 = 0x00;
+/// The period of autorepeat.
+pub const REP_PERIOD: u16 = 0x01;
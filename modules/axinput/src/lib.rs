@@ -0,0 +1,242 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) input module.
+//!
+//! The events of the main input device are read by [`read_events`], in the
+//! format of the Linux evdev events with monotonic timestamps. The device is
+//! polled when the events are read, so an event is timestamped when it's
+//! polled, not when the key is pressed.
+//!
+//! Like the keyboard handler of Linux, a key held down is repeated as events
+//! of value 2 after a delay and then at a period, both set by [`set_repeat`].
+//! The repeats are generated when the events are read, with the timestamps
+//! of when they are due. The lock keys toggle their LEDs, which are reported
+//! as `EV_LED` events and by [`leds`].
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+pub mod codes;
+
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+use axdriver::{AxDeviceContainer, prelude::*};
+use axsync::Mutex;
+use lazyinit::LazyInit;
+
+use self::codes::*;
+
+/// The maximum number of events queued, the oldest ones are dropped when the
+/// queue is full.
+const MAX_EVENTS: usize = 256;
+/// The maximum number of repeats generated at once, if the events are not
+/// read for a long time.
+const MAX_REPEATS: usize = 32;
+
+/// The default delay before a key held down is repeated, the same as Linux.
+pub const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(250);
+/// The default period of repeating a key held down, the same as Linux.
+pub const DEFAULT_REPEAT_PERIOD: Duration = Duration::from_millis(33);
+
+/// An input event with the monotonic time it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// The monotonic time of the event.
+    pub time: Duration,
+    /// The type of the event, e.g. [`EV_KEY`].
+    pub event_type: u16,
+    /// The code of the event, e.g. the key code.
+    pub code: u16,
+    /// The value of the event, e.g. 1 for a key pressed, 0 for released, and
+    /// 2 for repeated.
+    pub value: i32,
+}
+
+struct InputState {
+    dev: AxInputDevice,
+    events: VecDeque<Event>,
+    /// The key held down and the time of its next repeat.
+    held: Option<(u16, Duration)>,
+    repeat_delay: Duration,
+    /// The period of repeating, or zero if the keys are not repeated.
+    repeat_period: Duration,
+    /// The bitmask of the LEDs on.
+    leds: u32,
+}
+
+static MAIN_INPUT: LazyInit<Mutex<InputState>> = LazyInit::new();
+
+/// Whether the key of `code` is repeated when held down, the buttons of the
+/// mice and the joysticks are not.
+const fn is_repeatable(code: u16) -> bool {
+    code < BTN_MISC
+}
+
+/// Returns the LED toggled by the lock key of `code`.
+const fn lock_key_led(code: u16) -> Option<u16> {
+    match code {
+        KEY_CAPSLOCK => Some(LED_CAPSL),
+        KEY_NUMLOCK => Some(LED_NUML),
+        KEY_SCROLLLOCK => Some(LED_SCROLLL),
+        _ => None,
+    }
+}
+
+impl InputState {
+    fn push(&mut self, time: Duration, event_type: u16, code: u16, value: i32) {
+        if self.events.len() >= MAX_EVENTS {
+            // the reader has to resync, like the evdev of Linux
+            self.events.clear();
+            self.events.push_back(Event {
+                time,
+                event_type: EV_SYN,
+                code: SYN_DROPPED,
+                value: 0,
+            });
+        }
+        self.events.push_back(Event {
+            time,
+            event_type,
+            code,
+            value,
+        });
+    }
+
+    fn set_led(&mut self, led: u16, on: bool) {
+        if on {
+            self.leds |= 1 << led;
+        } else {
+            self.leds &= !(1 << led);
+        }
+        match self.dev.set_led(led, on) {
+            Ok(()) | Err(DevError::Unsupported) => {}
+            Err(e) => warn!("failed to set LED {}: {:?}", led, e),
+        }
+    }
+
+    fn handle(&mut self, now: Duration, event: InputEvent) {
+        let InputEvent {
+            event_type,
+            code,
+            value,
+        } = event;
+        self.push(now, event_type, code, value);
+        if event_type != EV_KEY {
+            return;
+        }
+        match value {
+            1 => {
+                self.held = (is_repeatable(code) && !self.repeat_period.is_zero())
+                    .then_some((code, now + self.repeat_delay));
+                if let Some(led) = lock_key_led(code) {
+                    let on = self.leds & (1 << led) == 0;
+                    self.set_led(led, on);
+                    self.push(now, EV_LED, led, on as i32);
+                }
+            }
+            0 if self.held.is_some_and(|(held, _)| held == code) => self.held = None,
+            _ => {}
+        }
+    }
+
+    /// Generates the repeats of the key held down due by `now`.
+    fn repeat_until(&mut self, now: Duration) {
+        let Some((code, mut at)) = self.held else {
+            return;
+        };
+        let mut count = 0;
+        while at <= now && count < MAX_REPEATS {
+            self.push(at, EV_KEY, code, 2);
+            self.push(at, EV_SYN, SYN_REPORT, 0);
+            at += self.repeat_period;
+            count += 1;
+        }
+        if at <= now {
+            // too far behind, skip the missed repeats
+            at = now + self.repeat_period;
+        }
+        self.held = Some((code, at));
+    }
+
+    fn poll(&mut self) {
+        let now = axhal::time::monotonic_time();
+        while let Ok(Some(event)) = self.dev.read_event() {
+            self.handle(now, event);
+        }
+        self.repeat_until(now);
+    }
+}
+
+/// Initializes the input subsystem by underlayer devices.
+pub fn init_input(mut input_devs: AxDeviceContainer<AxInputDevice>) {
+    info!("Initialize input subsystem...");
+
+    let dev = input_devs.take_one().expect("No input device found!");
+    info!("  use input device 0: {:?}", dev.device_name());
+    MAIN_INPUT.init_once(Mutex::new(InputState {
+        dev,
+        events: VecDeque::new(),
+        held: None,
+        repeat_delay: DEFAULT_REPEAT_DELAY,
+        repeat_period: DEFAULT_REPEAT_PERIOD,
+        leds: 0,
+    }));
+}
+
+/// Reads the events into `buf` without blocking, returns the number of events
+/// read.
+pub fn read_events(buf: &mut [Event]) -> usize {
+    let mut input = MAIN_INPUT.lock();
+    input.poll();
+    let count = buf.len().min(input.events.len());
+    for (dst, src) in buf.iter_mut().zip(input.events.drain(..count)) {
+        *dst = src;
+    }
+    count
+}
+
+/// Returns whether there are events to read.
+pub fn has_events() -> bool {
+    let mut input = MAIN_INPUT.lock();
+    input.poll();
+    !input.events.is_empty()
+}
+
+/// Returns the monotonic time of the next repeat of the key held down, if
+/// any, so that a blocked reader can wake up by then.
+pub fn next_repeat() -> Option<Duration> {
+    MAIN_INPUT.lock().held.map(|(_, at)| at)
+}
+
+/// Returns the delay and the period of repeating a key held down.
+pub fn repeat() -> (Duration, Duration) {
+    let input = MAIN_INPUT.lock();
+    (input.repeat_delay, input.repeat_period)
+}
+
+/// Sets the delay and the period of repeating a key held down. The keys are
+/// not repeated if the period is zero.
+pub fn set_repeat(delay: Duration, period: Duration) {
+    let mut input = MAIN_INPUT.lock();
+    input.repeat_delay = delay;
+    input.repeat_period = period;
+    if period.is_zero() {
+        input.held = None;
+    }
+}
+
+/// Returns the bitmask of the LEDs on, e.g. `1 << LED_CAPSL` if Caps Lock is
+/// on.
+pub fn leds() -> u32 {
+    MAIN_INPUT.lock().leds
+}
+
+/// Turns the LED of `led` on or off, e.g. [`LED_CAPSL`].
+pub fn set_led(led: u16, on: bool) {
+    if led > LED_MAX {
+        return;
+    }
+    MAIN_INPUT.lock().set_led(led, on);
+}
@@ -0,0 +1,53 @@
+//! The character devices of the channels, `/dev/ivshmem<N>`.
+
+use alloc::format;
+use alloc::sync::Arc;
+
+use axerrno::AxResult;
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+use crate::Channel;
+
+/// The major number of the devices, in the range for local use of Linux.
+const IVSHMEM_MAJOR: u32 = 240;
+
+/// A channel as a character device, whose reads wait for at least one byte,
+/// and whose writes wait until all the bytes are sent.
+struct ChannelDev(Channel);
+
+impl VfsNodeOps for ChannelDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o600),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.0.recv(buf)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.0.send(buf)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Registers the channel of the device at `index` as `/dev/ivshmem<index>`.
+pub(crate) fn register(index: usize, channel: Channel) -> AxResult {
+    let path = format!("ivshmem{}", index).leak();
+    axfs::register_char_device(
+        path,
+        IVSHMEM_MAJOR,
+        index as u32,
+        Arc::new(ChannelDev(channel)),
+    )
+}
@@ -0,0 +1,329 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) shared-memory ring transport
+//! module.
+//!
+//! The inter-VM shared memory devices of QEMU (`ivshmem-plain` and
+//! `ivshmem-doorbell`) map the same memory into several VMs, or into a VM and
+//! the processes of the host. Each device found is a byte stream in both
+//! directions here, by two single-producer single-consumer rings in the
+//! shared memory, for the low-latency communication with a Linux host or
+//! another ArceOS instance. It's got by [`channel`], and also registered as
+//! `/dev/ivshmem<N>` if the `devfs` feature is enabled.
+//!
+//! # Layout
+//!
+//! The shared memory is split into two halves, the ring of the side 0 to the
+//! side 1 at offset 0, and the ring of the side 1 to the side 0 in the other
+//! half. Each ring is:
+//!
+//! | Offset | Content                                                  |
+//! |--------|----------------------------------------------------------|
+//! | 0      | `head: u32`, the number of bytes written by the producer |
+//! | 64     | `tail: u32`, the number of bytes read by the consumer    |
+//! | 128    | the data, whose size is the largest power of 2 that fits |
+//!
+//! The counters are little-endian and wrap around, and the memory is zeroed
+//! by the host at first. The side of a device is the lowest bit of its ID in
+//! `IVPosition`: the `ivshmem-plain` devices are always of ID 0, so the host
+//! uses the side 1, while the `ivshmem-doorbell` devices of ID 0 and 1 talk to
+//! each other.
+//!
+//! # Doorbells
+//!
+//! After writing to or reading from a ring, the doorbell of the peer, whose
+//! ID differs from ours in the lowest bit, is rung by the vector 0. As the
+//! routing of PCI interrupts is platform-specific, the doorbells of the peer
+//! are received only after the IRQ of the device is given by
+//! [`Channel::bind_irq`], and the channel is polled otherwise.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+#[cfg(feature = "devfs")]
+mod dev;
+mod ring;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[doc(no_inline)]
+pub use axdriver::IvshmemDevice;
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::irq::IrqHandler;
+use axhal::mem::phys_to_virt;
+use axsync::{Completion, Mutex};
+use lazyinit::LazyInit;
+
+use self::ring::Ring;
+
+/// The interrupt mask register.
+const INTR_MASK: usize = 0x00;
+/// The interrupt status register, which is cleared on read.
+const INTR_STATUS: usize = 0x04;
+/// The ID of this VM among the peers.
+const IV_POSITION: usize = 0x08;
+/// The doorbell register, written by the ID of the peer in the upper 16
+/// bits, and the vector in the lower 16 bits.
+const DOORBELL: usize = 0x0c;
+
+/// The maximum number of devices whose doorbells can be received, as each of
+/// them has its own IRQ handler.
+const MAX_IRQ_DEVICES: usize = 4;
+
+/// The IRQ number of a device which is not bound to any IRQ.
+const NO_IRQ: usize = usize::MAX;
+
+struct Shmem {
+    dev: IvshmemDevice,
+    regs: usize,
+    position: u32,
+    tx: Mutex<Ring>,
+    rx: Mutex<Ring>,
+}
+
+impl Shmem {
+    fn new(dev: IvshmemDevice) -> Self {
+        let regs = phys_to_virt(dev.regs_paddr.into()).as_usize();
+        let shmem = phys_to_virt(dev.shmem_paddr.into()).as_usize();
+        // SAFETY: the BARs are in the MMIO regions, which are mapped.
+        let position = unsafe { ((regs + IV_POSITION) as *const u32).read_volatile() };
+        let half = dev.shmem_size / 2;
+        // SAFETY: the halves do not overlap, and the memory is only accessed
+        // by the rings.
+        let (ring0, ring1) = unsafe { (Ring::new(shmem, half), Ring::new(shmem + half, half)) };
+        let (tx, rx) = if position & 1 == 0 {
+            (ring0, ring1)
+        } else {
+            (ring1, ring0)
+        };
+        Self {
+            dev,
+            regs,
+            position,
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx),
+        }
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        // SAFETY: the registers are mapped, see `new`.
+        unsafe { ((self.regs + offset) as *const u32).read_volatile() }
+    }
+
+    fn write_reg(&self, offset: usize, value: u32) {
+        // SAFETY: the registers are mapped, see `new`.
+        unsafe { ((self.regs + offset) as *mut u32).write_volatile(value) }
+    }
+
+    /// Rings the doorbell of the peer, which is ignored if there is no peer.
+    fn notify_peer(&self) {
+        let peer = (self.position ^ 1) & 0xffff;
+        self.write_reg(DOORBELL, peer << 16);
+    }
+}
+
+static DEVICES: LazyInit<Vec<Shmem>> = LazyInit::new();
+
+/// The interrupt state of a device.
+struct IrqState {
+    irq: AtomicUsize,
+    /// Completed by the doorbells, for the receivers.
+    readable: Completion,
+    /// Completed by the doorbells, for the senders.
+    writable: Completion,
+}
+
+impl IrqState {
+    const fn new() -> Self {
+        Self {
+            irq: AtomicUsize::new(NO_IRQ),
+            readable: Completion::new(),
+            writable: Completion::new(),
+        }
+    }
+}
+
+/// The interrupt states, indexed by the device index.
+static IRQ_STATES: [IrqState; MAX_IRQ_DEVICES] = [const { IrqState::new() }; MAX_IRQ_DEVICES];
+
+fn irq_handler<const INDEX: usize>() {
+    let Some(shm) = DEVICES.get(INDEX) else {
+        return;
+    };
+    // acknowledge the interrupt, as the status is cleared on read
+    if shm.read_reg(INTR_STATUS) == 0 {
+        return;
+    }
+    // the doorbells do not tell the direction, so both sides are woken up
+    let state = &IRQ_STATES[INDEX];
+    state.readable.complete();
+    state.writable.complete();
+}
+
+const IRQ_HANDLERS: [IrqHandler; MAX_IRQ_DEVICES] = [
+    irq_handler::<0>,
+    irq_handler::<1>,
+    irq_handler::<2>,
+    irq_handler::<3>,
+];
+
+/// Initializes the shared-memory rings by the ivshmem devices, and registers
+/// them in `/dev` if the `devfs` feature is enabled.
+pub fn init(ivshmem_devs: Vec<IvshmemDevice>) {
+    info!("Initialize shared-memory rings...");
+    DEVICES.init_once(ivshmem_devs.into_iter().map(Shmem::new).collect());
+    for (i, shm) in DEVICES.iter().enumerate() {
+        info!(
+            "  ivshmem{}: {:02x}:{:02x}.{}, {:#x} bytes, ID {}",
+            i, shm.dev.bus, shm.dev.device, shm.dev.function, shm.dev.shmem_size, shm.position
+        );
+        #[cfg(feature = "devfs")]
+        if let Err(e) = dev::register(i, Channel { index: i }) {
+            warn!("failed to register /dev/ivshmem{}: {:?}", i, e);
+        }
+    }
+}
+
+/// Returns the number of the channels.
+pub fn num_channels() -> usize {
+    DEVICES.len()
+}
+
+/// Returns the channel of the device at `index`.
+///
+/// The channel can be got several times, while the sends and the receives
+/// are serialized respectively.
+pub fn channel(index: usize) -> Option<Channel> {
+    (index < DEVICES.len()).then_some(Channel { index })
+}
+
+/// A byte stream in both directions by the shared memory of a device.
+#[derive(Debug, Clone, Copy)]
+pub struct Channel {
+    index: usize,
+}
+
+impl Channel {
+    fn shm(&self) -> &'static Shmem {
+        &DEVICES[self.index]
+    }
+
+    fn irq_state(&self) -> Option<&'static IrqState> {
+        IRQ_STATES
+            .get(self.index)
+            .filter(|state| state.irq.load(Ordering::Relaxed) != NO_IRQ)
+    }
+
+    /// Returns the index of the device.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the information of the device.
+    pub fn device(&self) -> &'static IvshmemDevice {
+        &self.shm().dev
+    }
+
+    /// Returns the ID of this VM among the peers of the device, whose lowest
+    /// bit is the side of the rings used.
+    pub fn position(&self) -> u32 {
+        self.shm().position
+    }
+
+    /// Receives the doorbells of the peer by `irq`, instead of polling the
+    /// rings.
+    ///
+    /// It's only supported by the first few devices, as each of them needs
+    /// its own IRQ handler.
+    pub fn bind_irq(&self, irq: usize) -> AxResult {
+        let state = IRQ_STATES.get(self.index).ok_or(AxError::Unsupported)?;
+        if state.irq.load(Ordering::Relaxed) != NO_IRQ {
+            return ax_err!(AlreadyExists, "ivshmem: device bound to an IRQ");
+        }
+        state.irq.store(irq, Ordering::Relaxed);
+        if !axhal::irq::register_handler(irq, IRQ_HANDLERS[self.index]) {
+            state.irq.store(NO_IRQ, Ordering::Relaxed);
+            return ax_err!(ResourceBusy, "ivshmem: IRQ already in use");
+        }
+        self.shm().write_reg(INTR_MASK, u32::MAX);
+        Ok(())
+    }
+
+    /// Returns `true` if there are bytes to receive.
+    pub fn readable(&self) -> bool {
+        !self.shm().rx.lock().is_empty()
+    }
+
+    /// Returns `true` if there is space to send.
+    pub fn writable(&self) -> bool {
+        !self.shm().tx.lock().is_full()
+    }
+
+    /// Sends as many bytes of `buf` as the ring can hold, and returns the
+    /// number of bytes sent.
+    ///
+    /// Returns [`AxError::WouldBlock`] if the ring is full.
+    pub fn try_send(&self, buf: &[u8]) -> AxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let shm = self.shm();
+        let n = shm.tx.lock().write(buf);
+        if n == 0 {
+            return Err(AxError::WouldBlock);
+        }
+        shm.notify_peer();
+        Ok(n)
+    }
+
+    /// Sends all the bytes of `buf`, waiting for the peer to make space.
+    pub fn send(&self, buf: &[u8]) -> AxResult {
+        let mut sent = 0;
+        while sent < buf.len() {
+            match self.try_send(&buf[sent..]) {
+                Ok(n) => sent += n,
+                Err(AxError::WouldBlock) => self.wait(|state| &state.writable),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Receives the bytes available into `buf`, and returns the number of
+    /// bytes received.
+    ///
+    /// Returns [`AxError::WouldBlock`] if the ring is empty.
+    pub fn try_recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let shm = self.shm();
+        let n = shm.rx.lock().read(buf);
+        if n == 0 {
+            return Err(AxError::WouldBlock);
+        }
+        shm.notify_peer();
+        Ok(n)
+    }
+
+    /// Receives into `buf`, waiting until there is at least one byte.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        loop {
+            match self.try_recv(buf) {
+                Err(AxError::WouldBlock) => self.wait(|state| &state.readable),
+                res => return res,
+            }
+        }
+    }
+
+    /// Waits for a doorbell of the peer if the IRQ is bound, or yields to
+    /// poll the ring again later.
+    fn wait(&self, event: impl FnOnce(&'static IrqState) -> &'static Completion) {
+        match self.irq_state() {
+            Some(state) => event(state).wait(),
+            None => axtask::yield_now(),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Single-producer single-consumer byte rings in the shared memory.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The offset of the counter of the producer.
+const HEAD: usize = 0;
+/// The offset of the counter of the consumer, in another cache line.
+const TAIL: usize = 64;
+/// The offset of the data.
+const DATA: usize = 128;
+
+/// A ring of bytes, whose counters are shared with the other side.
+pub(crate) struct Ring {
+    base: usize,
+    /// The size of the data, a power of 2.
+    capacity: u32,
+}
+
+impl Ring {
+    /// Creates a ring in the memory at the virtual address `base` of `size`
+    /// bytes.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be mapped, and must not be used by anything else.
+    pub(crate) unsafe fn new(base: usize, size: usize) -> Self {
+        let space = size.saturating_sub(DATA).min(1 << 31);
+        let capacity = match space {
+            0 => 0,
+            space => 1 << space.ilog2(),
+        };
+        Self { base, capacity }
+    }
+
+    fn counter(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: the counters are aligned, and in the memory of the ring.
+        unsafe { &*((self.base + offset) as *const AtomicU32) }
+    }
+
+    fn data(&self) -> *mut u8 {
+        (self.base + DATA) as *mut u8
+    }
+
+    /// The number of bytes written but not read yet.
+    fn len(&self) -> u32 {
+        let head = self.counter(HEAD).load(Ordering::Acquire);
+        let tail = self.counter(TAIL).load(Ordering::Acquire);
+        // never trust the other side
+        head.wrapping_sub(tail).min(self.capacity)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+
+    /// Writes as many bytes of `buf` as there is space for, as the producer.
+    pub(crate) fn write(&self, buf: &[u8]) -> usize {
+        let head = self.counter(HEAD).load(Ordering::Relaxed);
+        let n = buf.len().min((self.capacity - self.len()) as usize);
+        if n == 0 {
+            return 0;
+        }
+        let start = (head & (self.capacity - 1)) as usize;
+        let first = n.min(self.capacity as usize - start);
+        // SAFETY: the ranges are in the data, and not read by the consumer
+        // until the head is published.
+        unsafe {
+            core::ptr::copy_nonoverlapping(buf.as_ptr(), self.data().add(start), first);
+            core::ptr::copy_nonoverlapping(buf[first..].as_ptr(), self.data(), n - first);
+        }
+        self.counter(HEAD)
+            .store(head.wrapping_add(n as u32), Ordering::Release);
+        n
+    }
+
+    /// Reads as many bytes into `buf` as there are, as the consumer.
+    pub(crate) fn read(&self, buf: &mut [u8]) -> usize {
+        let tail = self.counter(TAIL).load(Ordering::Relaxed);
+        let n = buf.len().min(self.len() as usize);
+        if n == 0 {
+            return 0;
+        }
+        let start = (tail & (self.capacity - 1)) as usize;
+        let first = n.min(self.capacity as usize - start);
+        // SAFETY: the ranges are in the data, and not written by the
+        // producer until the tail is published.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.data().add(start), buf.as_mut_ptr(), first);
+            core::ptr::copy_nonoverlapping(self.data(), buf[first..].as_mut_ptr(), n - first);
+        }
+        self.counter(TAIL)
+            .store(tail.wrapping_add(n as u32), Ordering::Release);
+        n
+    }
+}
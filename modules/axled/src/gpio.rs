@@ -0,0 +1,100 @@
+//! The LEDs connected to GPIO pins.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::ptr::NonNull;
+
+use kspin::SpinNoIrq;
+
+use crate::Led;
+
+/// A GPIO controller, to be implemented by the platform.
+pub trait GpioOps: Send {
+    /// Sets the direction of the pin `pin` to output.
+    fn set_output(&mut self, pin: u32);
+
+    /// Drives the output pin `pin` high or low.
+    fn set_value(&mut self, pin: u32, high: bool);
+}
+
+/// An LED on a pin of a GPIO controller, which may be shared by the LEDs on
+/// the other pins.
+pub struct GpioLed<G: GpioOps> {
+    name: String,
+    gpio: Arc<SpinNoIrq<G>>,
+    pin: u32,
+    active_low: bool,
+}
+
+impl<G: GpioOps> GpioLed<G> {
+    /// Creates the LED `name` on the pin `pin` of `gpio`, which is lit when
+    /// the pin is low if `active_low`. The LED is turned off.
+    pub fn new(
+        name: impl Into<String>,
+        gpio: Arc<SpinNoIrq<G>>,
+        pin: u32,
+        active_low: bool,
+    ) -> Self {
+        {
+            let mut gpio = gpio.lock();
+            gpio.set_value(pin, active_low);
+            gpio.set_output(pin);
+        }
+        Self {
+            name: name.into(),
+            gpio,
+            pin,
+            active_low,
+        }
+    }
+}
+
+impl<G: GpioOps> Led for GpioLed<G> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_brightness(&mut self, brightness: u32) {
+        let on = brightness != 0;
+        self.gpio.lock().set_value(self.pin, on != self.active_low);
+    }
+}
+
+/// The ARM PrimeCell PL061 GPIO controller, e.g. the one of the QEMU `virt`
+/// machine of AArch64.
+pub struct Pl061 {
+    base: NonNull<u8>,
+}
+
+unsafe impl Send for Pl061 {}
+
+impl Pl061 {
+    /// The offset of the `GPIODIR` register.
+    const GPIODIR: usize = 0x400;
+
+    /// Creates the controller whose registers are mapped at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the virtual address of the registers of a PL061.
+    pub const unsafe fn new(base: NonNull<u8>) -> Self {
+        Self { base }
+    }
+
+    fn reg(&self, offset: usize) -> *mut u32 {
+        unsafe { self.base.as_ptr().add(offset).cast() }
+    }
+}
+
+impl GpioOps for Pl061 {
+    fn set_output(&mut self, pin: u32) {
+        let dir = self.reg(Self::GPIODIR);
+        unsafe { dir.write_volatile(dir.read_volatile() | 1 << pin) };
+    }
+
+    fn set_value(&mut self, pin: u32, high: bool) {
+        // The address bits [9:2] mask the bits of `GPIODATA` written.
+        let data = self.reg((1 << pin) << 2);
+        unsafe { data.write_volatile(if high { 1 << pin } else { 0 }) };
+    }
+}
@@ -0,0 +1,257 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) LED and status indicator
+//! module.
+//!
+//! The LEDs are registered by [`register`], each with a [`Trigger`] that
+//! drives it, like the LED class of Linux:
+//!
+//! - [`Trigger::None`]: set by [`set_brightness`] only.
+//! - [`Trigger::DefaultOn`]: always on.
+//! - [`Trigger::Heartbeat`]: blinks twice a second, as long as the kernel is
+//!   alive.
+//! - [`Trigger::Disk`]: blinks on the disk requests.
+//! - [`Trigger::Netdev`]: blinks on the frames received or transmitted.
+//!
+//! The triggers are driven by [`update`], which must be called periodically,
+//! e.g. by the timer interrupt. The activities of the disk and the network
+//! are counted by the sources set by [`set_activity_source`].
+//!
+//! The LEDs are driven by:
+//!
+//! - [`gpio`]: the GPIO pins, e.g. of an ARM PL061.
+//! - [`platform`]: the bits of the LED registers of the board.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+pub mod gpio;
+pub mod platform;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use axerrno::{AxResult, ax_err};
+use kspin::SpinNoIrq;
+
+/// The period of the heartbeat.
+const HEARTBEAT_PERIOD: Duration = Duration::from_millis(1000);
+/// The time of the second beat in a period of the heartbeat.
+const HEARTBEAT_SECOND_BEAT: Duration = Duration::from_millis(250);
+/// The time the LED is on for a beat, or an activity.
+const BLINK_ON: Duration = Duration::from_millis(70);
+/// The minimum time the LED is off after blinking for an activity.
+const BLINK_OFF: Duration = Duration::from_millis(50);
+
+/// An LED, or another status indicator.
+pub trait Led: Send {
+    /// The name of the LED, e.g. `led0` or `board:green:status`.
+    fn name(&self) -> &str;
+
+    /// The maximum brightness, 1 if the LED can only be turned on or off.
+    fn max_brightness(&self) -> u32 {
+        1
+    }
+
+    /// Sets the brightness, 0 to turn off the LED.
+    fn set_brightness(&mut self, brightness: u32);
+}
+
+/// The event driving an LED.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// No trigger, the brightness is set by [`set_brightness`].
+    None,
+    /// Always on.
+    DefaultOn,
+    /// Blinks twice a second.
+    Heartbeat,
+    /// Blinks on the disk requests.
+    Disk,
+    /// Blinks on the network frames.
+    Netdev,
+}
+
+impl Trigger {
+    /// All the triggers.
+    pub const ALL: [Self; 5] = [
+        Self::None,
+        Self::DefaultOn,
+        Self::Heartbeat,
+        Self::Disk,
+        Self::Netdev,
+    ];
+
+    /// Returns the name of the trigger, the same as Linux.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::DefaultOn => "default-on",
+            Self::Heartbeat => "heartbeat",
+            Self::Disk => "disk-activity",
+            Self::Netdev => "netdev",
+        }
+    }
+
+    /// Returns the trigger of the name `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == name)
+    }
+}
+
+/// The sources of activities, which blink the LEDs of [`Trigger::Disk`] and
+/// [`Trigger::Netdev`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    /// The disk requests.
+    Disk,
+    /// The network frames.
+    Netdev,
+}
+
+struct Entry {
+    led: Box<dyn Led>,
+    trigger: Trigger,
+    brightness: u32,
+    /// The count of the activity source when last updated.
+    last_count: u64,
+    /// The end of the current blink, and of the time off after it.
+    blink: Option<(Duration, Duration)>,
+}
+
+impl Entry {
+    fn set(&mut self, brightness: u32) {
+        if self.brightness != brightness {
+            self.brightness = brightness;
+            self.led.set_brightness(brightness);
+        }
+    }
+
+    fn update(&mut self, now: Duration, sources: &[Option<fn() -> u64>; 2]) {
+        let max = self.led.max_brightness();
+        let source = match self.trigger {
+            Trigger::None => return,
+            Trigger::DefaultOn => return self.set(max),
+            Trigger::Heartbeat => {
+                let phase =
+                    Duration::from_nanos((now.as_nanos() % HEARTBEAT_PERIOD.as_nanos()) as u64);
+                let on = phase < BLINK_ON
+                    || (phase >= HEARTBEAT_SECOND_BEAT && phase < HEARTBEAT_SECOND_BEAT + BLINK_ON);
+                return self.set(if on { max } else { 0 });
+            }
+            Trigger::Disk => sources[Activity::Disk as usize],
+            Trigger::Netdev => sources[Activity::Netdev as usize],
+        };
+        let count = source.map_or(0, |f| f());
+        match self.blink {
+            Some((on_until, _)) if now < on_until => {}
+            Some((_, off_until)) if now < off_until => self.set(0),
+            _ if count != self.last_count => {
+                self.last_count = count;
+                self.blink = Some((now + BLINK_ON, now + BLINK_ON + BLINK_OFF));
+                self.set(max);
+            }
+            _ => {
+                self.blink = None;
+                self.set(0);
+            }
+        }
+    }
+}
+
+static LEDS: SpinNoIrq<Vec<Entry>> = SpinNoIrq::new(Vec::new());
+static SOURCES: SpinNoIrq<[Option<fn() -> u64>; 2]> = SpinNoIrq::new([None; 2]);
+
+fn with_led<R>(name: &str, f: impl FnOnce(&mut Entry) -> R) -> AxResult<R> {
+    match LEDS.lock().iter_mut().find(|e| e.led.name() == name) {
+        Some(entry) => Ok(f(entry)),
+        None => ax_err!(NotFound),
+    }
+}
+
+/// Registers an LED driven by `trigger`, whose name must be unique. It's
+/// turned off until driven.
+pub fn register(mut led: Box<dyn Led>, trigger: Trigger) -> AxResult {
+    let mut leds = LEDS.lock();
+    if leds.iter().any(|e| e.led.name() == led.name()) {
+        return ax_err!(AlreadyExists, "LED already registered");
+    }
+    info!("LED {}: {}", led.name(), trigger.as_str());
+    led.set_brightness(0);
+    leds.push(Entry {
+        led,
+        trigger,
+        brightness: 0,
+        last_count: 0,
+        blink: None,
+    });
+    Ok(())
+}
+
+/// Returns the names of all LEDs, in the order they are registered.
+pub fn leds() -> Vec<String> {
+    LEDS.lock()
+        .iter()
+        .map(|e| e.led.name().to_string())
+        .collect()
+}
+
+/// Returns the current brightness of the LED `name`.
+pub fn brightness(name: &str) -> AxResult<u32> {
+    with_led(name, |e| e.brightness)
+}
+
+/// Returns the maximum brightness of the LED `name`.
+pub fn max_brightness(name: &str) -> AxResult<u32> {
+    with_led(name, |e| e.led.max_brightness())
+}
+
+/// Sets the brightness of the LED `name`, up to its maximum. Like Linux, its
+/// trigger is removed if `brightness` is 0.
+pub fn set_brightness(name: &str, brightness: u32) -> AxResult {
+    with_led(name, |e| {
+        if brightness == 0 {
+            e.trigger = Trigger::None;
+            e.blink = None;
+        }
+        e.set(brightness.min(e.led.max_brightness()));
+    })
+}
+
+/// Returns the trigger of the LED `name`.
+pub fn trigger(name: &str) -> AxResult<Trigger> {
+    with_led(name, |e| e.trigger)
+}
+
+/// Sets the trigger of the LED `name`, which takes effect from the next
+/// [`update`]. The LED is turned off if `trigger` is [`Trigger::None`].
+pub fn set_trigger(name: &str, trigger: Trigger) -> AxResult {
+    with_led(name, |e| {
+        e.trigger = trigger;
+        e.blink = None;
+        if trigger == Trigger::None {
+            e.set(0);
+        }
+    })
+}
+
+/// Sets the source of `activity`, which returns the count of the activities
+/// so far, e.g. the number of the disk requests. The LEDs blink when it
+/// changes.
+pub fn set_activity_source(activity: Activity, count: fn() -> u64) {
+    SOURCES.lock()[activity as usize] = Some(count);
+}
+
+/// Drives the LEDs by their triggers at the monotonic time `now`.
+///
+/// It's called periodically, at least every 50 ms, and can be called in the
+/// interrupt context.
+pub fn update(now: Duration) {
+    let sources = *SOURCES.lock();
+    for entry in LEDS.lock().iter_mut() {
+        entry.update(now, &sources);
+    }
+}
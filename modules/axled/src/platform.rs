@@ -0,0 +1,62 @@
+//! The LEDs of the platform LED controllers, driven by the bits of MMIO
+//! registers, e.g. the LED registers of the board controllers (CPLDs).
+
+use alloc::string::String;
+use core::ptr::NonNull;
+
+use crate::Led;
+
+/// An LED driven by the bits `mask` of a 32-bit MMIO register.
+///
+/// The other bits of the register are preserved, so it must not be written by
+/// others at the same time.
+pub struct RegisterLed {
+    name: String,
+    reg: NonNull<u32>,
+    mask: u32,
+    active_low: bool,
+}
+
+unsafe impl Send for RegisterLed {}
+
+impl RegisterLed {
+    /// Creates the LED `name` driven by the bits `mask` of the register at
+    /// `reg`, which is lit when the bits are cleared if `active_low`.
+    ///
+    /// # Safety
+    ///
+    /// `reg` must be the virtual address of the register.
+    pub unsafe fn new(
+        name: impl Into<String>,
+        reg: NonNull<u32>,
+        mask: u32,
+        active_low: bool,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            reg,
+            mask,
+            active_low,
+        }
+    }
+}
+
+impl Led for RegisterLed {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_brightness(&mut self, brightness: u32) {
+        let set = (brightness != 0) != self.active_low;
+        let ptr = self.reg.as_ptr();
+        unsafe {
+            let value = ptr.read_volatile();
+            let value = if set {
+                value | self.mask
+            } else {
+                value & !self.mask
+            };
+            ptr.write_volatile(value);
+        }
+    }
+}
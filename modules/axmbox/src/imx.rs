@@ -0,0 +1,145 @@
+//! The Messaging Unit (MU) of i.MX, between the application cores (side A)
+//! and the Cortex-M core (side B).
+//!
+//! Each side has 4 transmit and 4 receive registers of 32 bits, and 4 general
+//! purpose interrupts used as doorbells. A channel is given by 2 cells, the
+//! type and the index 0-3, where the types are:
+//!
+//! - 0: the transmit register.
+//! - 1: the receive register.
+//! - 2: the doorbell to the other side, whose messages are ignored.
+//! - 3: the doorbell from the other side, whose messages are always 0.
+
+use alloc::sync::Arc;
+
+use axclk::Regs;
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+
+use crate::MboxOps;
+
+const TYPE_TX: u32 = 0;
+const TYPE_RX: u32 = 1;
+const TYPE_TXDB: u32 = 2;
+const TYPE_RXDB: u32 = 3;
+
+/// The offsets of the registers of side A.
+struct Layout {
+    tr: usize,
+    rr: usize,
+    sr: usize,
+    cr: usize,
+}
+
+const IMX6SX: Layout = Layout {
+    tr: 0x00,
+    rr: 0x10,
+    sr: 0x20,
+    cr: 0x24,
+};
+
+const IMX7ULP: Layout = Layout {
+    tr: 0x20,
+    rr: 0x40,
+    sr: 0x60,
+    cr: 0x64,
+};
+
+/// The transmit register `n` is empty.
+const fn sr_te(n: u32) -> u32 {
+    1 << (23 - n)
+}
+
+/// The receive register `n` is full.
+const fn sr_rf(n: u32) -> u32 {
+    1 << (27 - n)
+}
+
+/// The general purpose interrupt `n` from the other side is pending.
+const fn sr_gip(n: u32) -> u32 {
+    1 << (31 - n)
+}
+
+/// Triggers the general purpose interrupt `n` of the other side.
+const fn cr_gir(n: u32) -> u32 {
+    1 << (19 - n)
+}
+
+/// The interrupt enables and the triggers in the control register.
+const CR_GIR_MASK: u32 = 0xf << 16;
+
+/// A Messaging Unit of i.MX.
+pub struct ImxMu {
+    regs: Regs,
+    layout: &'static Layout,
+}
+
+impl MboxOps for ImxMu {
+    fn check_channel(&self, args: &[u32]) -> AxResult {
+        match args {
+            [ty, idx] if *ty <= TYPE_RXDB && *idx < 4 => Ok(()),
+            _ => ax_err!(InvalidInput, "invalid MU channel"),
+        }
+    }
+
+    fn try_send(&self, args: &[u32], msg: u32) -> AxResult {
+        let (ty, idx) = (args[0], args[1]);
+        let layout = self.layout;
+        match ty {
+            TYPE_TX => {
+                if self.regs.read(layout.sr) & sr_te(idx) == 0 {
+                    return Err(AxError::WouldBlock);
+                }
+                self.regs.write(layout.tr + idx as usize * 4, msg);
+            }
+            TYPE_TXDB => {
+                // the trigger is cleared by the hardware when it's taken
+                let cr = self.regs.read(layout.cr);
+                if cr & cr_gir(idx) != 0 {
+                    return Err(AxError::WouldBlock);
+                }
+                self.regs
+                    .write(layout.cr, (cr & !CR_GIR_MASK) | cr_gir(idx));
+            }
+            _ => return ax_err!(Unsupported, "not a transmit channel"),
+        }
+        Ok(())
+    }
+
+    fn try_recv(&self, args: &[u32]) -> Option<u32> {
+        let (ty, idx) = (args[0], args[1]);
+        let layout = self.layout;
+        let sr = self.regs.read(layout.sr);
+        match ty {
+            TYPE_RX if sr & sr_rf(idx) != 0 => Some(self.regs.read(layout.rr + idx as usize * 4)),
+            TYPE_RXDB if sr & sr_gip(idx) != 0 => {
+                // write 1 to clear
+                self.regs.write(layout.sr, sr_gip(idx));
+                Some(0)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Probes a MU, and enables its clock if it's provided.
+fn probe(node: &Node, layout: &'static Layout) -> AxResult<Arc<dyn MboxOps>> {
+    let regs = Regs::from_node(node, 0)?;
+    if let Ok(clk) = axclk::get(node, None) {
+        clk.enable()?;
+    }
+    // mask all the interrupts, the channels are polled
+    regs.write(layout.cr, 0);
+    Ok(Arc::new(ImxMu { regs, layout }))
+}
+
+/// Probes a `fsl,imx6sx-mu` node, which is also compatible with the MUs of
+/// i.MX 7D and 8.
+pub(crate) fn probe_imx6sx(node: &Node) -> AxResult<Arc<dyn MboxOps>> {
+    probe(node, &IMX6SX)
+}
+
+/// Probes a `fsl,imx7ulp-mu` node.
+pub(crate) fn probe_imx7ulp(node: &Node) -> AxResult<Arc<dyn MboxOps>> {
+    probe(node, &IMX7ULP)
+}
@@ -0,0 +1,163 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) mailbox and RPMsg module.
+//!
+//! On the SoCs with heterogeneous cores, e.g. the Cortex-M cores of i.MX, the
+//! application cores talk to the firmware of the co-processors by the
+//! mailboxes, the hardware FIFOs or doorbells between the cores, and by the
+//! memory shared between them.
+//!
+//! The mailbox controllers are probed from the device tree by [`init`], and
+//! registered by the phandles of their nodes. The channels of a device are
+//! got by the `mboxes` and `mbox-names` properties of its node by [`get`].
+//! The controllers supported are:
+//!
+//! - [`imx`]: the Messaging Unit (MU) of i.MX 6SX, 7ULP and 8.
+//!
+//! On top of them, [`rpmsg`] is the transport of the messages of RPMsg-Lite,
+//! as the master, which allocates the buffers in the shared memory.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+pub mod imx;
+pub mod rpmsg;
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+use axsync::Mutex;
+
+/// The operations of a mailbox controller, on the channels given by the
+/// arguments of their specifiers in `mboxes`.
+pub trait MboxOps: Send + Sync {
+    /// Checks the arguments of a channel specifier.
+    fn check_channel(&self, args: &[u32]) -> AxResult;
+
+    /// Sends `msg` on the channel, or returns [`AxError::WouldBlock`] if the
+    /// last message is not taken by the other side yet.
+    fn try_send(&self, args: &[u32], msg: u32) -> AxResult;
+
+    /// Receives a message from the channel, if any.
+    fn try_recv(&self, args: &[u32]) -> Option<u32>;
+}
+
+/// A channel of a mailbox.
+#[derive(Clone)]
+pub struct Channel {
+    ops: Arc<dyn MboxOps>,
+    args: Vec<u32>,
+}
+
+impl Channel {
+    /// Sends `msg`, or returns [`AxError::WouldBlock`] if the channel is
+    /// busy.
+    pub fn try_send(&self, msg: u32) -> AxResult {
+        self.ops.try_send(&self.args, msg)
+    }
+
+    /// Sends `msg`, waiting until the channel is not busy.
+    pub fn send(&self, msg: u32) -> AxResult {
+        loop {
+            match self.try_send(msg) {
+                Err(AxError::WouldBlock) => axhal::arch::cpu_relax(),
+                res => return res,
+            }
+        }
+    }
+
+    /// Receives a message, if any.
+    pub fn try_recv(&self) -> Option<u32> {
+        self.ops.try_recv(&self.args)
+    }
+}
+
+/// The mailbox controllers by the phandles of their nodes.
+static PROVIDERS: Mutex<BTreeMap<u32, Arc<dyn MboxOps>>> = Mutex::new(BTreeMap::new());
+
+/// Registers the mailbox controller of the device tree node with the phandle
+/// `phandle`.
+pub fn register_provider(phandle: u32, ops: Arc<dyn MboxOps>) {
+    PROVIDERS.lock().insert(phandle, ops);
+}
+
+/// Returns the channel of the device tree node `node` named `name` in its
+/// `mbox-names`, or the first one if `name` is `None`.
+///
+/// Returns [`AxError::NotFound`] if the channel or its controller is not
+/// found.
+pub fn get(node: &Node, name: Option<&str>) -> AxResult<Channel> {
+    let index = match name {
+        Some(name) => node
+            .strings("mbox-names")
+            .position(|n| n == name)
+            .ok_or(AxError::NotFound)?,
+        None => 0,
+    };
+    let mut cells = node.cells("mboxes");
+    for i in 0.. {
+        let Some(phandle) = cells.next() else {
+            return ax_err!(NotFound);
+        };
+        let provider = axhal::dtb::find_by_phandle(phandle).ok_or(AxError::NotFound)?;
+        let num_args = provider.property_u32("#mbox-cells").unwrap_or(0) as usize;
+        let args = cells.by_ref().take(num_args).collect::<Vec<_>>();
+        if args.len() < num_args {
+            return ax_err!(InvalidData);
+        }
+        if i == index {
+            let ops = PROVIDERS
+                .lock()
+                .get(&phandle)
+                .cloned()
+                .ok_or(AxError::NotFound)?;
+            ops.check_channel(&args)?;
+            return Ok(Channel { ops, args });
+        }
+    }
+    unreachable!()
+}
+
+/// Probes a mailbox controller of a device tree node.
+type ProbeFn = fn(&Node) -> AxResult<Arc<dyn MboxOps>>;
+
+/// The mailbox controllers by their compatible strings.
+const DRIVERS: &[(&str, ProbeFn)] = &[
+    ("fsl,imx6sx-mu", imx::probe_imx6sx),
+    ("fsl,imx7ulp-mu", imx::probe_imx7ulp),
+];
+
+/// Probes the mailbox controllers in the device tree, and then the RPMsg
+/// devices on them.
+///
+/// It must be called after [`axclk::init`], as the controllers may need
+/// their clocks.
+pub fn init() {
+    info!("Initialize mailboxes...");
+    axhal::dtb::for_each_node(|node| {
+        let Some(phandle) = node.phandle() else {
+            return;
+        };
+        if !node.is_enabled() {
+            return;
+        }
+        let Some(&(compatible, probe)) = DRIVERS.iter().find(|(c, _)| node.is_compatible(c)) else {
+            return;
+        };
+        match probe(&node) {
+            Ok(ops) => {
+                debug!("  {} ({})", node.name(), compatible);
+                register_provider(phandle, ops);
+            }
+            Err(e) => warn!("failed to probe the mailbox {}: {:?}", node.name(), e),
+        }
+    });
+    if PROVIDERS.lock().is_empty() {
+        return;
+    }
+    rpmsg::init();
+}
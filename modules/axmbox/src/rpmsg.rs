@@ -0,0 +1,430 @@
+//! The transport of the messages of RPMsg-Lite, between the application
+//! cores as the master and the firmware of a co-processor as the remote.
+//!
+//! An RPMsg device is a node compatible with `fsl,imx7ulp-rpmsg`,
+//! `fsl,imx8mq-rpmsg` or `fsl,imx8qm-rpmsg`, whose `memory-region` is the
+//! memory shared with the remote, and whose `mboxes` named `tx` and `rx` kick
+//! the other side. The shared memory must be in the MMIO regions of the
+//! platform, so that it's not cached, and is laid out as:
+//!
+//! - `0x0000`: the vring of the messages from the remote, whose buffers are
+//!   given by the master.
+//! - `0x8000`: the vring of the messages to the remote.
+//! - `0x10000`: the buffers of the vrings, [`BUFFER_SIZE`] bytes each,
+//!   [`NUM_BUFFERS`] for each vring.
+//!
+//! Each message has a header with the source and the destination addresses,
+//! by which it's delivered to an [`Endpoint`]. The services of the remote are
+//! announced to the name service endpoint, see [`RpmsgDevice::services`].
+//!
+//! The devices are polled, there is no interrupt.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{Ordering, fence};
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+use axsync::Mutex;
+
+use crate::Channel;
+
+/// The number of the buffers of each vring.
+pub const NUM_BUFFERS: usize = 256;
+/// The size of each buffer, including the header of the message.
+pub const BUFFER_SIZE: usize = 512;
+/// The size of the header of a message.
+const HEADER_SIZE: usize = 16;
+/// The largest payload of a message.
+pub const MAX_PAYLOAD: usize = BUFFER_SIZE - HEADER_SIZE;
+
+const VRING_ALIGN: usize = 0x1000;
+/// The offsets of the vrings and the buffers in the shared memory.
+const RX_VRING: usize = 0x0;
+const TX_VRING: usize = 0x8000;
+const BUFFERS: usize = 0x10000;
+const SHM_SIZE: usize = BUFFERS + 2 * NUM_BUFFERS * BUFFER_SIZE;
+
+const VRING_DESC_F_WRITE: u16 = 2;
+
+/// The address of the name service endpoint.
+const NS_ADDR: u32 = 53;
+/// The size of the name in a name service message.
+const NS_NAME_SIZE: usize = 32;
+const NS_CREATE: u32 = 0;
+const NS_DESTROY: u32 = 1;
+/// The addresses below are reserved, the others are allocated to the
+/// endpoints created without an address.
+const FIRST_DYNAMIC_ADDR: u32 = 1024;
+
+/// A split virtqueue ring in the shared memory.
+struct Vring {
+    base: *mut u8,
+    last_used: u16,
+}
+
+impl Vring {
+    const AVAIL: usize = NUM_BUFFERS * 16;
+    const USED: usize = (Self::AVAIL + 2 * (3 + NUM_BUFFERS)).next_multiple_of(VRING_ALIGN);
+
+    unsafe fn read<T>(&self, offset: usize) -> T {
+        unsafe { self.base.add(offset).cast::<T>().read_volatile() }
+    }
+
+    unsafe fn write<T>(&self, offset: usize, value: T) {
+        unsafe { self.base.add(offset).cast::<T>().write_volatile(value) }
+    }
+
+    fn set_desc(&self, id: usize, addr: u64, len: u32, flags: u16) {
+        unsafe {
+            self.write(id * 16, addr);
+            self.write(id * 16 + 8, len);
+            self.write(id * 16 + 12, flags);
+            self.write(id * 16 + 14, 0u16);
+        }
+    }
+
+    fn set_desc_len(&self, id: usize, len: u32) {
+        unsafe { self.write(id * 16 + 8, len) }
+    }
+
+    /// Gives the buffer of the descriptor `id` to the remote.
+    fn push_avail(&self, id: usize) {
+        unsafe {
+            let idx: u16 = self.read(Self::AVAIL + 2);
+            self.write(
+                Self::AVAIL + 4 + (idx as usize % NUM_BUFFERS) * 2,
+                id as u16,
+            );
+            fence(Ordering::SeqCst);
+            self.write(Self::AVAIL + 2, idx.wrapping_add(1));
+        }
+    }
+
+    /// Takes a buffer returned by the remote, with its descriptor and the
+    /// length written.
+    fn pop_used(&mut self) -> Option<(usize, usize)> {
+        fence(Ordering::SeqCst);
+        let idx: u16 = unsafe { self.read(Self::USED + 2) };
+        if idx == self.last_used {
+            return None;
+        }
+        let elem = Self::USED + 4 + (self.last_used as usize % NUM_BUFFERS) * 8;
+        let (id, len): (u32, u32) = unsafe { (self.read(elem), self.read(elem + 4)) };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some((id as usize % NUM_BUFFERS, len as usize))
+    }
+}
+
+struct Transport {
+    rx: Vring,
+    tx: Vring,
+    /// The virtual address of the buffers.
+    buf_vaddr: *mut u8,
+    /// The descriptors of the free buffers of the TX vring.
+    tx_free: Vec<usize>,
+}
+
+unsafe impl Send for Transport {}
+
+impl Transport {
+    fn rx_buffer(&self, id: usize) -> *mut u8 {
+        unsafe { self.buf_vaddr.add(id * BUFFER_SIZE) }
+    }
+
+    fn tx_buffer(&self, id: usize) -> *mut u8 {
+        unsafe { self.buf_vaddr.add((NUM_BUFFERS + id) * BUFFER_SIZE) }
+    }
+}
+
+/// An endpoint of an RPMsg device, which receives the messages sent to its
+/// address.
+pub struct Endpoint {
+    addr: u32,
+    dev: Weak<RpmsgDevice>,
+    queue: Mutex<VecDeque<(u32, Vec<u8>)>>,
+}
+
+impl Endpoint {
+    /// Returns the local address of the endpoint.
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    fn dev(&self) -> AxResult<Arc<RpmsgDevice>> {
+        self.dev.upgrade().ok_or(AxError::NotConnected)
+    }
+
+    /// Sends `data` to the remote endpoint `dst`.
+    pub fn send_to(&self, dst: u32, data: &[u8]) -> AxResult {
+        self.dev()?.send(self.addr, dst, data)
+    }
+
+    /// Receives a message, returns its length and its source address, or
+    /// [`AxError::WouldBlock`] if there is none.
+    ///
+    /// The message is truncated if `buf` is too small.
+    pub fn try_recv(&self, buf: &mut [u8]) -> AxResult<(usize, u32)> {
+        self.dev()?.poll();
+        let Some((src, data)) = self.queue.lock().pop_front() else {
+            return Err(AxError::WouldBlock);
+        };
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok((len, src))
+    }
+
+    /// Receives a message, waiting until one arrives.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<(usize, u32)> {
+        loop {
+            match self.try_recv(buf) {
+                Err(AxError::WouldBlock) => axhal::arch::cpu_relax(),
+                res => return res,
+            }
+        }
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        if let Some(dev) = self.dev.upgrade() {
+            dev.endpoints.lock().remove(&self.addr);
+        }
+    }
+}
+
+/// An RPMsg device, the link to the firmware of a co-processor.
+pub struct RpmsgDevice {
+    name: &'static str,
+    tx_chan: Channel,
+    rx_chans: Vec<Channel>,
+    transport: Mutex<Transport>,
+    endpoints: Mutex<BTreeMap<u32, Weak<Endpoint>>>,
+    /// The services announced by the remote, by their names.
+    services: Mutex<BTreeMap<String, u32>>,
+}
+
+impl RpmsgDevice {
+    /// Returns the name of the device tree node of the device.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Kicks the remote to process the vring `vq`.
+    fn kick(&self, vq: u32) -> AxResult {
+        self.tx_chan.send(vq << 16)
+    }
+
+    /// Creates an endpoint at the address `addr`, or at a free one if it's
+    /// `None`.
+    pub fn create_endpoint(self: &Arc<Self>, addr: Option<u32>) -> AxResult<Arc<Endpoint>> {
+        let mut endpoints = self.endpoints.lock();
+        let addr = match addr {
+            Some(addr) if endpoints.contains_key(&addr) || addr == NS_ADDR => {
+                return ax_err!(AddrInUse);
+            }
+            Some(addr) => addr,
+            None => (FIRST_DYNAMIC_ADDR..)
+                .find(|addr| !endpoints.contains_key(addr))
+                .unwrap(),
+        };
+        let ept = Arc::new(Endpoint {
+            addr,
+            dev: Arc::downgrade(self),
+            queue: Mutex::new(VecDeque::new()),
+        });
+        endpoints.insert(addr, Arc::downgrade(&ept));
+        Ok(ept)
+    }
+
+    /// Announces the service `name` at the local address `addr` to the
+    /// remote, or that it's destroyed.
+    pub fn announce(&self, name: &str, addr: u32, create: bool) -> AxResult {
+        if name.len() >= NS_NAME_SIZE {
+            return ax_err!(InvalidInput, "service name too long");
+        }
+        let mut msg = [0; NS_NAME_SIZE + 8];
+        msg[..name.len()].copy_from_slice(name.as_bytes());
+        msg[NS_NAME_SIZE..NS_NAME_SIZE + 4].copy_from_slice(&addr.to_le_bytes());
+        let flags = if create { NS_CREATE } else { NS_DESTROY };
+        msg[NS_NAME_SIZE + 4..].copy_from_slice(&flags.to_le_bytes());
+        self.send(addr, NS_ADDR, &msg)
+    }
+
+    /// Returns the services announced by the remote, with their addresses.
+    pub fn services(&self) -> Vec<(String, u32)> {
+        let services = self.services.lock();
+        services.iter().map(|(n, &a)| (n.clone(), a)).collect()
+    }
+
+    /// Sends `data` from the local address `src` to the remote address
+    /// `dst`, or returns [`AxError::WouldBlock`] if all the buffers are in
+    /// use by the remote.
+    pub fn send(&self, src: u32, dst: u32, data: &[u8]) -> AxResult {
+        if data.len() > MAX_PAYLOAD {
+            return ax_err!(InvalidInput, "message too long");
+        }
+        let mut t = self.transport.lock();
+        while let Some((id, _)) = t.tx.pop_used() {
+            t.tx_free.push(id);
+        }
+        let Some(id) = t.tx_free.pop() else {
+            return Err(AxError::WouldBlock);
+        };
+        let buf = t.tx_buffer(id);
+        let mut header = [0; HEADER_SIZE];
+        header[0..4].copy_from_slice(&src.to_le_bytes());
+        header[4..8].copy_from_slice(&dst.to_le_bytes());
+        header[12..14].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        unsafe {
+            core::ptr::copy_nonoverlapping(header.as_ptr(), buf, HEADER_SIZE);
+            core::ptr::copy_nonoverlapping(data.as_ptr(), buf.add(HEADER_SIZE), data.len());
+        }
+        t.tx.set_desc_len(id, (HEADER_SIZE + data.len()) as u32);
+        t.tx.push_avail(id);
+        drop(t);
+        self.kick(1)
+    }
+
+    /// Processes the messages from the remote, delivering them to the
+    /// endpoints, and gives their buffers back.
+    pub fn poll(&self) {
+        // the kicks are not needed, as the vrings are polled
+        for chan in &self.rx_chans {
+            while chan.try_recv().is_some() {}
+        }
+        let mut t = self.transport.lock();
+        let mut received = false;
+        while let Some((id, len)) = t.rx.pop_used() {
+            let mut msg = [0; BUFFER_SIZE];
+            let len = len.clamp(HEADER_SIZE, BUFFER_SIZE);
+            unsafe { core::ptr::copy_nonoverlapping(t.rx_buffer(id), msg.as_mut_ptr(), len) };
+            t.rx.push_avail(id);
+            received = true;
+
+            let word = |i: usize| u32::from_le_bytes(msg[i..i + 4].try_into().unwrap());
+            let (src, dst) = (word(0), word(4));
+            let size = u16::from_le_bytes([msg[12], msg[13]]) as usize;
+            let payload = &msg[HEADER_SIZE..HEADER_SIZE + size.min(len - HEADER_SIZE)];
+            if dst == NS_ADDR {
+                self.handle_ns(payload);
+                continue;
+            }
+            let ept = self.endpoints.lock().get(&dst).and_then(Weak::upgrade);
+            match ept {
+                Some(ept) => ept.queue.lock().push_back((src, payload.to_vec())),
+                None => debug!("rpmsg: message from {} to no endpoint {}", src, dst),
+            }
+        }
+        drop(t);
+        if received {
+            self.kick(0).ok();
+        }
+    }
+
+    /// Handles a name service message from the remote.
+    fn handle_ns(&self, msg: &[u8]) {
+        if msg.len() < NS_NAME_SIZE + 8 {
+            return;
+        }
+        let name = &msg[..NS_NAME_SIZE];
+        let name = &name[..name.iter().position(|&c| c == 0).unwrap_or(NS_NAME_SIZE)];
+        let name = String::from_utf8_lossy(name).into_owned();
+        let word = |i: usize| u32::from_le_bytes(msg[i..i + 4].try_into().unwrap());
+        let (addr, flags) = (word(NS_NAME_SIZE), word(NS_NAME_SIZE + 4));
+        info!("rpmsg: {} service {:?} at {}", self.name, name, addr);
+        let mut services = self.services.lock();
+        if flags == NS_DESTROY {
+            services.remove(&name);
+        } else {
+            services.insert(name, addr);
+        }
+    }
+}
+
+/// The RPMsg devices probed.
+static DEVICES: Mutex<Vec<Arc<RpmsgDevice>>> = Mutex::new(Vec::new());
+
+/// Returns the `idx`-th RPMsg device.
+pub fn device(idx: usize) -> Option<Arc<RpmsgDevice>> {
+    DEVICES.lock().get(idx).cloned()
+}
+
+/// Returns the number of the RPMsg devices.
+pub fn num_devices() -> usize {
+    DEVICES.lock().len()
+}
+
+/// Probes an RPMsg device, and gives the buffers of the messages from the
+/// remote to it.
+fn probe(node: &Node) -> AxResult<Arc<RpmsgDevice>> {
+    let tx_chan = crate::get(node, Some("tx"))?;
+    let rx_chans = ["rx", "rxdb"]
+        .into_iter()
+        .filter_map(|name| crate::get(node, Some(name)).ok())
+        .collect();
+    let shm = node
+        .property_u32("memory-region")
+        .and_then(axhal::dtb::find_by_phandle)
+        .ok_or(AxError::NotFound)?;
+    let (paddr, size) = shm.reg(0).ok_or(AxError::InvalidData)?;
+    if size < SHM_SIZE {
+        return ax_err!(InvalidData, "shared memory too small");
+    }
+    // the region is checked to be in the MMIO regions mapped at boot
+    let base = shm.mmio(0).ok_or(AxError::BadAddress)?.as_mut_ptr();
+    unsafe { core::ptr::write_bytes(base, 0, BUFFERS) };
+
+    let rx = Vring {
+        base: unsafe { base.add(RX_VRING) },
+        last_used: 0,
+    };
+    let tx = Vring {
+        base: unsafe { base.add(TX_VRING) },
+        last_used: 0,
+    };
+    for id in 0..NUM_BUFFERS {
+        let rx_paddr = (paddr + BUFFERS + id * BUFFER_SIZE) as u64;
+        let tx_paddr = rx_paddr + (NUM_BUFFERS * BUFFER_SIZE) as u64;
+        rx.set_desc(id, rx_paddr, BUFFER_SIZE as u32, VRING_DESC_F_WRITE);
+        tx.set_desc(id, tx_paddr, BUFFER_SIZE as u32, 0);
+        rx.push_avail(id);
+    }
+    let dev = Arc::new(RpmsgDevice {
+        name: node.name(),
+        tx_chan,
+        rx_chans,
+        transport: Mutex::new(Transport {
+            rx,
+            tx,
+            buf_vaddr: unsafe { base.add(BUFFERS) },
+            tx_free: (0..NUM_BUFFERS).rev().collect(),
+        }),
+        endpoints: Mutex::new(BTreeMap::new()),
+        services: Mutex::new(BTreeMap::new()),
+    });
+    // the link is up when the remote sees the buffers
+    dev.kick(0)?;
+    Ok(dev)
+}
+
+/// The compatible strings of the RPMsg devices.
+const COMPATIBLES: &[&str] = &["fsl,imx7ulp-rpmsg", "fsl,imx8mq-rpmsg", "fsl,imx8qm-rpmsg"];
+
+/// Probes the RPMsg devices in the device tree.
+pub(crate) fn init() {
+    axhal::dtb::for_each_node(|node| {
+        if !node.is_enabled() || !COMPATIBLES.iter().any(|c| node.is_compatible(c)) {
+            return;
+        }
+        match probe(&node) {
+            Ok(dev) => {
+                info!("  rpmsg device {}", node.name());
+                DEVICES.lock().push(dev);
+            }
+            Err(e) => warn!("failed to probe the rpmsg device {}: {:?}", node.name(), e),
+        }
+    });
+}
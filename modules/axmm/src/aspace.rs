@@ -2,7 +2,7 @@ use core::fmt;
 
 use axerrno::{AxError, AxResult, ax_err};
 use axhal::mem::phys_to_virt;
-use axhal::paging::{MappingFlags, PageTable, PagingError};
+use axhal::paging::{MappingFlags, PageSize, PageTable, PagingError};
 use memory_addr::{
     MemoryAddr, PAGE_SIZE_4K, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, is_aligned_4k,
 };
@@ -137,6 +137,33 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new linear mapping with huge pages.
+    ///
+    /// It is the same as [`AddrSpace::map_linear`], except that the addresses
+    /// and the size must be aligned to 2MiB huge pages, which are used for the
+    /// mapping. The mapping can only be unmapped or protected in units of huge
+    /// pages.
+    pub fn map_linear_huge(
+        &mut self,
+        start_vaddr: VirtAddr,
+        start_paddr: PhysAddr,
+        size: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        self.validate_region(start_vaddr, size)?;
+        let aligned = |addr: usize| addr % PageSize::Size2M as usize == 0;
+        if !aligned(start_vaddr.as_usize()) || !aligned(start_paddr.as_usize()) || !aligned(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let offset = start_vaddr.as_usize().wrapping_sub(start_paddr.as_usize());
+        let area = MemoryArea::new(start_vaddr, size, flags, Backend::new_linear_huge(offset));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
     /// Add a new allocation mapping.
     ///
     /// See [`Backend`] for more details about the mapping backends.
@@ -6,7 +6,18 @@ use super::Backend;
 impl Backend {
     /// Creates a new linear mapping backend.
     pub const fn new_linear(pa_va_offset: usize) -> Self {
-        Self::Linear { pa_va_offset }
+        Self::Linear {
+            pa_va_offset,
+            huge: false,
+        }
+    }
+
+    /// Creates a new linear mapping backend using huge pages.
+    pub const fn new_linear_huge(pa_va_offset: usize) -> Self {
+        Self::Linear {
+            pa_va_offset,
+            huge: true,
+        }
     }
 
     pub(crate) fn map_linear(
@@ -15,6 +26,7 @@ impl Backend {
         flags: MappingFlags,
         pt: &mut PageTable,
         pa_va_offset: usize,
+        huge: bool,
     ) -> bool {
         let va_to_pa = |va: VirtAddr| PhysAddr::from(va.as_usize().wrapping_sub(pa_va_offset));
         debug!(
@@ -25,7 +37,7 @@ impl Backend {
             va_to_pa(start + size),
             flags
         );
-        pt.map_region(start, va_to_pa, size, flags, false, false)
+        pt.map_region(start, va_to_pa, size, flags, huge, false)
             .map(|tlb| tlb.ignore()) // TLB flush on map is unnecessary, as there are no outdated mappings.
             .is_ok()
     }
@@ -25,6 +25,9 @@ pub enum Backend {
     Linear {
         /// `vaddr - paddr`.
         pa_va_offset: usize,
+        /// Whether to map with huge pages where the addresses and the size
+        /// are aligned to them.
+        huge: bool,
     },
     /// Allocation mapping backend.
     ///
@@ -44,14 +47,16 @@ impl MappingBackend for Backend {
     type PageTable = PageTable;
     fn map(&self, start: VirtAddr, size: usize, flags: MappingFlags, pt: &mut PageTable) -> bool {
         match *self {
-            Self::Linear { pa_va_offset } => Self::map_linear(start, size, flags, pt, pa_va_offset),
+            Self::Linear { pa_va_offset, huge } => {
+                Self::map_linear(start, size, flags, pt, pa_va_offset, huge)
+            }
             Self::Alloc { populate } => Self::map_alloc(start, size, flags, pt, populate),
         }
     }
 
     fn unmap(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
         match *self {
-            Self::Linear { pa_va_offset } => Self::unmap_linear(start, size, pt, pa_va_offset),
+            Self::Linear { pa_va_offset, .. } => Self::unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate } => Self::unmap_alloc(start, size, pt, populate),
         }
     }
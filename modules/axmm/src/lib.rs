@@ -16,7 +16,7 @@ use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
-use memory_addr::{PhysAddr, va};
+use memory_addr::{PhysAddr, VirtAddr, VirtAddrRange, va};
 use memory_set::MappingError;
 
 static KERNEL_ASPACE: LazyInit<SpinNoIrq<AddrSpace>> = LazyInit::new();
@@ -39,9 +39,47 @@ pub fn new_kernel_aspace() -> AxResult<AddrSpace> {
     for r in axhal::mem::memory_regions() {
         aspace.map_linear(phys_to_virt(r.paddr), r.paddr, r.size, r.flags.into())?;
     }
+    // The kernel image relocated at boot is also mapped at its virtual base.
+    let image_offset = axhal::mem::kernel_image_offset();
+    if image_offset != axconfig::plat::PHYS_VIRT_OFFSET {
+        for r in axhal::mem::kernel_image_regions() {
+            let vaddr = va!(r.paddr.as_usize().wrapping_add(image_offset));
+            aspace.map_linear(vaddr, r.paddr, r.size, r.flags.into())?;
+        }
+    }
+    Ok(aspace)
+}
+
+/// Creates a new address space for user processes.
+///
+/// The kernel portion of the page table is copied by [`copy_kernel_mappings`],
+/// which must be removed by [`clear_kernel_mappings`] before dropping.
+pub fn new_user_aspace(base: VirtAddr, size: usize) -> AxResult<AddrSpace> {
+    let mut aspace = AddrSpace::new_empty(base, size)?;
+    copy_kernel_mappings(&mut aspace)?;
     Ok(aspace)
 }
 
+/// Copies the kernel portion of the page table to a user address space, so
+/// that the kernel is still accessible after switching to it.
+pub fn copy_kernel_mappings(aspace: &mut AddrSpace) -> AxResult {
+    // ARMv8 (aarch64) and LoongArch64 use separate page tables for user space
+    // (TTBR0_EL1 and PGDL), there is no need to copy the kernel portion.
+    if !cfg!(any(target_arch = "aarch64", target_arch = "loongarch64")) {
+        aspace.copy_mappings_from(&KERNEL_ASPACE.lock())?;
+    }
+    Ok(())
+}
+
+/// Removes the kernel portion copied by [`copy_kernel_mappings`], so that the
+/// kernel page table is not affected when dropping the user address space.
+pub fn clear_kernel_mappings(aspace: &mut AddrSpace) {
+    if !cfg!(any(target_arch = "aarch64", target_arch = "loongarch64")) {
+        let kernel = KERNEL_ASPACE.lock();
+        aspace.clear_mappings(VirtAddrRange::from_start_size(kernel.base(), kernel.size()));
+    }
+}
+
 /// Returns the globally unique kernel address space.
 pub fn kernel_aspace() -> &'static SpinNoIrq<AddrSpace> {
     &KERNEL_ASPACE
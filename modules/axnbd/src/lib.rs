@@ -0,0 +1,369 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) network block device client.
+//!
+//! It connects to an export of an [NBD] server, e.g. `nbd-server` or
+//! `qemu-nbd`, over TCP, and exposes it as a block device, so the nodes
+//! without a disk can use the storage on the network as their root
+//! filesystem.
+//!
+//! The fixed newstyle handshake is used, and the export is selected by
+//! `NBD_OPT_EXPORT_NAME`. The requests are sent one at a time, and each
+//! waits for its simple reply. If the connection is broken, or the server
+//! does not reply in time, the device reconnects to the server and retries
+//! the request once, as the reads and the writes of the blocks can be
+//! repeated.
+//!
+//! [NBD]: https://github.com/NetworkBlockDevice/nbd/blob/master/doc/proto.md
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+use alloc::string::String;
+use core::net::SocketAddr;
+use core::time::Duration;
+
+use axdriver::prelude::*;
+use axerrno::{AxError, AxResult, ax_err};
+use axnet::TcpSocket;
+
+/// The default TCP port of the NBD servers.
+pub const DEFAULT_PORT: u16 = 10809;
+
+/// The size of the blocks of the device, which is the size of the sectors
+/// of the NBD protocol.
+const BLOCK_SIZE: usize = 512;
+/// The maximum size of a request, the larger ones are split.
+const MAX_REQUEST: usize = 1 << 20;
+/// How long to wait for the server before reconnecting.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+const NBD_MAGIC: u64 = 0x4e42_444d_4147_4943; // "NBDMAGIC"
+const IHAVEOPT: u64 = 0x4948_4156_454f_5054; // "IHAVEOPT"
+const REQUEST_MAGIC: u32 = 0x2560_9513;
+const SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+const NBD_FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const NBD_FLAG_NO_ZEROES: u16 = 1 << 1;
+const NBD_FLAG_C_FIXED_NEWSTYLE: u32 = 1 << 0;
+const NBD_FLAG_C_NO_ZEROES: u32 = 1 << 1;
+const NBD_OPT_EXPORT_NAME: u32 = 1;
+
+const NBD_FLAG_READ_ONLY: u16 = 1 << 1;
+const NBD_FLAG_SEND_FLUSH: u16 = 1 << 2;
+
+const NBD_CMD_READ: u16 = 0;
+const NBD_CMD_WRITE: u16 = 1;
+const NBD_CMD_DISC: u16 = 2;
+const NBD_CMD_FLUSH: u16 = 3;
+
+/// A block device backed by an export of an NBD server.
+pub struct NbdDevice {
+    addr: SocketAddr,
+    export: String,
+    socket: Option<TcpSocket>,
+    size: u64,
+    flags: u16,
+    /// The handle of the next request.
+    next_handle: u64,
+}
+
+impl NbdDevice {
+    /// Connects to the export `export` of the server at `addr`, where the
+    /// empty name is the default export of the server.
+    ///
+    /// Returns [`AxError::Unsupported`] if the server does not speak the
+    /// fixed newstyle handshake, and [`AxError::InvalidData`] if its export
+    /// is not a whole number of blocks.
+    pub fn connect(addr: SocketAddr, export: &str) -> AxResult<Self> {
+        let mut dev = Self {
+            addr,
+            export: String::from(export),
+            socket: None,
+            size: 0,
+            flags: 0,
+            next_handle: 0,
+        };
+        dev.reconnect()?;
+        info!(
+            "nbd: connected to {} {:?}: {} bytes{}",
+            addr,
+            export,
+            dev.size,
+            if dev.is_read_only() {
+                ", read-only"
+            } else {
+                ""
+            }
+        );
+        Ok(dev)
+    }
+
+    /// Returns `true` if the export can not be written.
+    pub fn is_read_only(&self) -> bool {
+        self.flags & NBD_FLAG_READ_ONLY != 0
+    }
+
+    /// Makes a new connection to the server, and does the handshake.
+    fn reconnect(&mut self) -> AxResult {
+        self.socket = None;
+        let socket = TcpSocket::new();
+        socket.set_connect_timeout(Some(IO_TIMEOUT));
+        socket.set_read_timeout(Some(IO_TIMEOUT));
+        socket.set_write_timeout(Some(IO_TIMEOUT));
+        socket.connect(self.addr)?;
+        // the requests are small and wait for their replies
+        socket.set_nagle_enabled(false)?;
+
+        let mut buf = [0; 18];
+        recv_exact(&socket, &mut buf)?;
+        let magic = u64::from_be_bytes(buf[0..8].try_into().unwrap());
+        let opt_magic = u64::from_be_bytes(buf[8..16].try_into().unwrap());
+        let server_flags = u16::from_be_bytes(buf[16..18].try_into().unwrap());
+        if magic != NBD_MAGIC || opt_magic != IHAVEOPT {
+            return ax_err!(Unsupported, "nbd: not a newstyle server");
+        }
+        if server_flags & NBD_FLAG_FIXED_NEWSTYLE == 0 {
+            return ax_err!(Unsupported, "nbd: not a fixed newstyle server");
+        }
+        let no_zeroes = server_flags & NBD_FLAG_NO_ZEROES != 0;
+        let mut client_flags = NBD_FLAG_C_FIXED_NEWSTYLE;
+        if no_zeroes {
+            client_flags |= NBD_FLAG_C_NO_ZEROES;
+        }
+        send_all(&socket, &client_flags.to_be_bytes())?;
+
+        let mut opt = [0; 16];
+        opt[0..8].copy_from_slice(&IHAVEOPT.to_be_bytes());
+        opt[8..12].copy_from_slice(&NBD_OPT_EXPORT_NAME.to_be_bytes());
+        opt[12..16].copy_from_slice(&(self.export.len() as u32).to_be_bytes());
+        send_all(&socket, &opt)?;
+        send_all(&socket, self.export.as_bytes())?;
+
+        // the server closes the connection if there is no such export
+        let mut reply = [0; 10 + 124];
+        let len = if no_zeroes { 10 } else { reply.len() };
+        recv_exact(&socket, &mut reply[..len]).map_err(|e| match e {
+            AxError::UnexpectedEof => AxError::NotFound,
+            e => e,
+        })?;
+        let size = u64::from_be_bytes(reply[0..8].try_into().unwrap());
+        let flags = u16::from_be_bytes(reply[8..10].try_into().unwrap());
+        if size % BLOCK_SIZE as u64 != 0 {
+            return ax_err!(InvalidData, "nbd: export size not a multiple of blocks");
+        }
+        if self.size != 0 && size != self.size {
+            warn!("nbd: export size changed from {} to {}", self.size, size);
+        }
+        self.size = size;
+        self.flags = flags;
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    /// Sends a request of `cmd` on `len` bytes from `offset`, with the data
+    /// `write` if any, and receives the data of the reply into `read` if
+    /// any. It reconnects and retries once if the connection fails.
+    fn request(
+        &mut self,
+        cmd: u16,
+        offset: u64,
+        len: usize,
+        write: Option<&[u8]>,
+        mut read: Option<&mut [u8]>,
+    ) -> DevResult {
+        match self.try_request(cmd, offset, len, write, read.as_deref_mut()) {
+            Err(RequestError::Net(e)) => {
+                warn!("nbd: request failed: {:?}, reconnecting", e);
+                self.reconnect().map_err(|e| {
+                    error!("nbd: failed to reconnect: {:?}", e);
+                    DevError::Io
+                })?;
+                self.try_request(cmd, offset, len, write, read)
+                    .map_err(RequestError::into_dev_err)
+            }
+            res => res.map_err(RequestError::into_dev_err),
+        }
+    }
+
+    fn try_request(
+        &mut self,
+        cmd: u16,
+        offset: u64,
+        len: usize,
+        write: Option<&[u8]>,
+        read: Option<&mut [u8]>,
+    ) -> Result<(), RequestError> {
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        let Some(socket) = self.socket.as_ref() else {
+            return Err(RequestError::Net(AxError::NotConnected));
+        };
+
+        let mut req = [0; 28];
+        req[0..4].copy_from_slice(&REQUEST_MAGIC.to_be_bytes());
+        req[6..8].copy_from_slice(&cmd.to_be_bytes());
+        req[8..16].copy_from_slice(&handle.to_be_bytes());
+        req[16..24].copy_from_slice(&offset.to_be_bytes());
+        req[24..28].copy_from_slice(&(len as u32).to_be_bytes());
+        send_all(socket, &req)?;
+        if let Some(buf) = write {
+            send_all(socket, buf)?;
+        }
+
+        let mut reply = [0; 16];
+        recv_exact(socket, &mut reply)?;
+        let magic = u32::from_be_bytes(reply[0..4].try_into().unwrap());
+        let error = u32::from_be_bytes(reply[4..8].try_into().unwrap());
+        let reply_handle = u64::from_be_bytes(reply[8..16].try_into().unwrap());
+        if magic != SIMPLE_REPLY_MAGIC || reply_handle != handle {
+            // out of sync, the connection can not be used any more
+            return Err(RequestError::Net(AxError::InvalidData));
+        }
+        if error != 0 {
+            return Err(RequestError::Server(error));
+        }
+        if let Some(buf) = read {
+            recv_exact(socket, buf)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that `len` bytes from the block `block_id` are whole blocks
+    /// within the export, and returns their offset.
+    fn check_range(&self, block_id: u64, len: usize) -> DevResult<u64> {
+        if len % BLOCK_SIZE != 0 {
+            return Err(DevError::InvalidParam);
+        }
+        let offset = block_id
+            .checked_mul(BLOCK_SIZE as u64)
+            .ok_or(DevError::InvalidParam)?;
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= self.size => Ok(offset),
+            _ => Err(DevError::InvalidParam),
+        }
+    }
+}
+
+impl Drop for NbdDevice {
+    fn drop(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            // tell the server to close the connection, without a reply
+            let mut req = [0; 28];
+            req[0..4].copy_from_slice(&REQUEST_MAGIC.to_be_bytes());
+            req[6..8].copy_from_slice(&NBD_CMD_DISC.to_be_bytes());
+            send_all(&socket, &req).ok();
+        }
+    }
+}
+
+impl BaseDriverOps for NbdDevice {
+    fn device_name(&self) -> &str {
+        "nbd"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+}
+
+impl BlockDriverOps for NbdDevice {
+    fn num_blocks(&self) -> u64 {
+        self.size / BLOCK_SIZE as u64
+    }
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let mut offset = self.check_range(block_id, buf.len())?;
+        for chunk in buf.chunks_mut(MAX_REQUEST) {
+            self.request(NBD_CMD_READ, offset, chunk.len(), None, Some(chunk))?;
+            offset += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        if self.is_read_only() {
+            return Err(DevError::Unsupported);
+        }
+        let mut offset = self.check_range(block_id, buf.len())?;
+        for chunk in buf.chunks(MAX_REQUEST) {
+            self.request(NBD_CMD_WRITE, offset, chunk.len(), Some(chunk), None)?;
+            offset += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        if self.flags & NBD_FLAG_SEND_FLUSH == 0 {
+            // the server writes through, or does not cache the writes
+            return Ok(());
+        }
+        self.request(NBD_CMD_FLUSH, 0, 0, None, None)
+    }
+}
+
+// the requests are sent one at a time, there is no request queue
+impl BlockQueueOps for NbdDevice {}
+
+/// The failure of a request.
+enum RequestError {
+    /// The connection fails, the request can be retried on a new one.
+    Net(AxError),
+    /// The server replies with an error, in the errno values of the protocol.
+    Server(u32),
+}
+
+impl RequestError {
+    fn into_dev_err(self) -> DevError {
+        match self {
+            Self::Net(e) => {
+                error!("nbd: request failed: {:?}", e);
+                DevError::Io
+            }
+            Self::Server(errno) => {
+                warn!("nbd: server replied error {}", errno);
+                match errno {
+                    // EPERM, EROFS
+                    1 | 30 => DevError::Unsupported,
+                    // ENOMEM
+                    12 => DevError::NoMemory,
+                    // EINVAL, ENOSPC, EOVERFLOW
+                    22 | 28 | 75 => DevError::InvalidParam,
+                    // EIO and the others
+                    _ => DevError::Io,
+                }
+            }
+        }
+    }
+}
+
+impl From<AxError> for RequestError {
+    fn from(e: AxError) -> Self {
+        Self::Net(e)
+    }
+}
+
+fn send_all(socket: &TcpSocket, mut buf: &[u8]) -> AxResult {
+    while !buf.is_empty() {
+        match socket.send(buf)? {
+            0 => return Err(AxError::WriteZero),
+            n => buf = &buf[n..],
+        }
+    }
+    Ok(())
+}
+
+fn recv_exact(socket: &TcpSocket, mut buf: &mut [u8]) -> AxResult {
+    while !buf.is_empty() {
+        match socket.recv(buf)? {
+            0 => return Err(AxError::UnexpectedEof),
+            n => buf = &mut buf[n..],
+        }
+    }
+    Ok(())
+}
@@ -9,11 +9,18 @@
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`register_hook`]: Function to process received packets before the
+//!   network stack, like XDP.
+//! - [`set_nat_rules`]: Function to forward ports of this host to other hosts,
+//!   enabled by [`set_ip_forward`].
 //!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `dhcp`: Configure the interface by DHCP at boot, see [`dhcp_lease`].
+//! - `irq`: Receive by the interrupt of the NIC if known, so the blocking
+//!   operations sleep instead of polling.
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
@@ -30,24 +37,34 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use self::net_impl::TcpSocket;
+pub use self::net_impl::{ConnectFailure, TcpSocket};
 pub use self::net_impl::UdpSocket;
 pub use self::net_impl::{
-    add_membership, dns_query, from_core_sockaddr, into_core_sockaddr, poll_interfaces,
+    add_membership, dns_query, drop_membership, frame_count, from_core_sockaddr,
+    interrupt_driven, into_core_sockaddr, poll_interfaces, set_event_notifier,
 };
 pub use self::net_impl::{bench_receive, bench_transmit};
+#[cfg(feature = "dhcp")]
+pub use self::net_impl::{dhcp_lease, dhcp_renew, DhcpLease};
+pub use self::net_impl::{
+    hook_stats, register_hook, unregister_hook, HookAction, HookId, HookStats,
+};
+pub use self::net_impl::{
+    ip_forward, nat_rules, set_ip_forward, set_nat_rules, NatProtocol, NatRule,
+};
 pub use smoltcp::time::Duration;
 pub use smoltcp::wire::{
     IpAddress as IpAddr, IpEndpoint as SocketAddr, Ipv4Address as Ipv4Addr, Ipv6Address as Ipv6Addr,
 };
 
-use axdriver::{prelude::*, AxDeviceContainer};
+use axdriver::{prelude::*, AxDeviceContainer, DeviceIrq};
 
-/// Initializes the network subsystem by NIC devices.
-pub fn init_network(mut net_devs: AxDeviceContainer<AxNetDevice>) {
+/// Initializes the network subsystem by NIC devices, where `irq` is the
+/// interrupt of the first one if known.
+pub fn init_network(mut net_devs: AxDeviceContainer<AxNetDevice>, irq: Option<DeviceIrq>) {
     info!("Initialize network subsystem...");
 
     let dev = net_devs.take_one().expect("No NIC device found!");
     info!("  use NIC 0: {:?}", dev.device_name());
-    net_impl::init(dev);
+    net_impl::init(dev, irq);
 }
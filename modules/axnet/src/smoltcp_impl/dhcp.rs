@@ -0,0 +1,242 @@
+//! DHCP client, which configures the IPv4 address, the default gateway and
+//! the DNS servers of `eth0`, instead of the static `AX_IP` and `AX_GW`.
+//!
+//! It runs in a background task, which polls `eth0` with its own socket set
+//! to acquire a lease at boot and renew it before it expires. The frames to
+//! the client are taken from `eth0` before the other sockets see them, and
+//! queued for the task.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+use core::ops::DerefMut;
+use core::time::Duration;
+
+use axerrno::{ax_err, AxResult};
+use axhal::time::{monotonic_time_nanos, NANOS_PER_MICROS};
+use axsync::Mutex;
+use lazy_init::LazyInit;
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{Device, DeviceCapabilities, RxToken};
+use smoltcp::socket::dhcpv4::{self, Event};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    EthernetFrame, EthernetProtocol, IpAddress, IpCidr, IpProtocol, Ipv4Address, Ipv4Packet,
+    UdpPacket, DHCP_CLIENT_PORT,
+};
+
+use super::{nat, sync_loopback_addrs, AxNetTxToken, DeviceWrapper, ETH0};
+
+/// The maximum interval between two polls of the client.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// The maximum number of frames queued for the client.
+const MAX_QUEUED_FRAMES: usize = 16;
+
+/// A lease acquired from a DHCP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpLease {
+    /// The IPv4 address of the interface.
+    pub address: Ipv4Addr,
+    /// The prefix length of the subnet.
+    pub prefix_len: u8,
+    /// The default gateway, if given by the server.
+    pub router: Option<Ipv4Addr>,
+    /// The DNS servers, which are used by [`dns_query`](super::dns_query).
+    pub dns_servers: Vec<Ipv4Addr>,
+    /// The address of the DHCP server.
+    pub server: Ipv4Addr,
+}
+
+struct DhcpClient {
+    sockets: Mutex<SocketSet<'static>>,
+    handle: SocketHandle,
+}
+
+static CLIENT: LazyInit<DhcpClient> = LazyInit::new();
+static LEASE: Mutex<Option<DhcpLease>> = Mutex::new(None);
+static RX_QUEUE: Mutex<VecDeque<Vec<u8>>> = Mutex::new(VecDeque::new());
+
+/// `eth0` seen by the client, which only receives the queued frames.
+struct DhcpDevice<'a>(&'a mut DeviceWrapper);
+
+struct QueuedRxToken(Vec<u8>);
+
+impl RxToken for QueuedRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.0)
+    }
+
+    fn preprocess(&self, _sockets: &mut SocketSet<'_>) {}
+}
+
+impl Device for DhcpDevice<'_> {
+    type RxToken<'a>
+        = QueuedRxToken
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = AxNetTxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = RX_QUEUE.lock().pop_front()?;
+        Some((QueuedRxToken(frame), AxNetTxToken(&self.0.inner)))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.0.transmit(timestamp)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.0.capabilities()
+    }
+}
+
+/// Takes a frame received on `eth0` if it's to the client.
+pub(crate) fn take_frame(frame: &[u8]) -> bool {
+    if !is_to_client(frame).unwrap_or(false) {
+        return false;
+    }
+    let mut queue = RX_QUEUE.lock();
+    if queue.len() < MAX_QUEUED_FRAMES {
+        queue.push_back(frame.to_vec());
+    }
+    true
+}
+
+fn is_to_client(frame: &[u8]) -> smoltcp::wire::Result<bool> {
+    let eth = EthernetFrame::new_checked(frame)?;
+    if eth.ethertype() != EthernetProtocol::Ipv4 {
+        return Ok(false);
+    }
+    let ip = Ipv4Packet::new_checked(eth.payload())?;
+    if ip.next_header() != IpProtocol::Udp {
+        return Ok(false);
+    }
+    let udp = UdpPacket::new_checked(ip.payload())?;
+    Ok(udp.dst_port() == DHCP_CLIENT_PORT)
+}
+
+/// Returns the current lease, or `None` if the address of `eth0` has not
+/// been acquired yet.
+pub fn dhcp_lease() -> Option<DhcpLease> {
+    LEASE.lock().clone()
+}
+
+/// Drops the current lease and acquires a new one.
+///
+/// It returns once the request is made, the new lease is got by
+/// [`dhcp_lease`] when acquired.
+pub fn dhcp_renew() -> AxResult {
+    let Some(client) = CLIENT.try_get() else {
+        return ax_err!(BadState, "DHCP client not started");
+    };
+    let mut sockets = client.sockets.lock();
+    sockets.get_mut::<dhcpv4::Socket>(client.handle).reset();
+    Ok(())
+}
+
+/// Returns the DNS servers of the current lease.
+pub(crate) fn dns_servers() -> Vec<IpAddress> {
+    LEASE.lock().as_ref().map_or_else(Vec::new, |lease| {
+        lease
+            .dns_servers
+            .iter()
+            .map(|addr| IpAddress::Ipv4(Ipv4Address(addr.octets())))
+            .collect()
+    })
+}
+
+/// Starts the DHCP client on `eth0`.
+pub(crate) fn init() {
+    let mut sockets = SocketSet::new(Vec::new());
+    let handle = sockets.add(dhcpv4::Socket::new());
+    CLIENT.init_by(DhcpClient {
+        sockets: Mutex::new(sockets),
+        handle,
+    });
+    axtask::spawn(dhcp_task);
+    info!("  ip:       (DHCP)");
+}
+
+fn current_time() -> Instant {
+    Instant::from_micros((monotonic_time_nanos() / NANOS_PER_MICROS) as i64)
+}
+
+fn dhcp_task() {
+    let client = CLIENT.try_get().unwrap();
+    loop {
+        let timestamp = current_time();
+        let (delay, changed) = {
+            let mut dev = ETH0.dev.lock();
+            let mut iface = ETH0.iface.lock();
+            let mut sockets = client.sockets.lock();
+            iface.poll(timestamp, &mut DhcpDevice(dev.deref_mut()), &mut sockets);
+            let changed = match sockets.get_mut::<dhcpv4::Socket>(client.handle).poll() {
+                Some(Event::Configured(config)) => {
+                    configure(&mut iface, &config);
+                    true
+                }
+                Some(Event::Deconfigured) => {
+                    deconfigure(&mut iface);
+                    true
+                }
+                None => false,
+            };
+            (iface.poll_delay(timestamp, &sockets), changed)
+        };
+        if changed {
+            sync_loopback_addrs();
+        }
+        let delay = delay.map_or(MAX_POLL_INTERVAL, |d| {
+            Duration::from_micros(d.total_micros()).min(MAX_POLL_INTERVAL)
+        });
+        axtask::sleep(delay);
+    }
+}
+
+fn configure(iface: &mut Interface, config: &dhcpv4::Config) {
+    let cidr = config.address;
+    iface.update_ip_addrs(|addrs| {
+        addrs.retain(|addr| !matches!(addr, IpCidr::Ipv4(_)));
+        addrs.push(IpCidr::Ipv4(cidr)).unwrap();
+    });
+    match config.router {
+        Some(router) => {
+            iface.routes_mut().add_default_ipv4_route(router).unwrap();
+        }
+        None => {
+            iface.routes_mut().remove_default_ipv4_route();
+        }
+    }
+    nat::init(ETH0.ether_addr, IpAddress::Ipv4(cidr.address()), cidr.prefix_len());
+
+    let lease = DhcpLease {
+        address: Ipv4Addr::from(cidr.address().0),
+        prefix_len: cidr.prefix_len(),
+        router: config.router.map(|addr| Ipv4Addr::from(addr.0)),
+        dns_servers: config
+            .dns_servers
+            .iter()
+            .map(|addr| Ipv4Addr::from(addr.0))
+            .collect(),
+        server: Ipv4Addr::from(config.server.address.0),
+    };
+    info!("DHCP lease acquired from {}:", lease.server);
+    info!("  ip:       {}/{}", lease.address, lease.prefix_len);
+    info!("  gateway:  {:?}", lease.router);
+    info!("  dns:      {:?}", lease.dns_servers);
+    *LEASE.lock() = Some(lease);
+}
+
+fn deconfigure(iface: &mut Interface) {
+    iface.update_ip_addrs(|addrs| addrs.retain(|addr| !matches!(addr, IpCidr::Ipv4(_))));
+    iface.routes_mut().remove_default_ipv4_route();
+    if LEASE.lock().take().is_some() {
+        warn!("DHCP lease lost");
+    }
+}
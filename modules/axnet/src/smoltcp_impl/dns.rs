@@ -1,13 +1,20 @@
 use alloc::vec::Vec;
-use axerrno::{ax_err_type, AxError, AxResult};
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axhal::time::monotonic_time;
 use core::net::IpAddr;
+use core::time::Duration;
 
 use smoltcp::iface::SocketHandle;
-use smoltcp::socket::dns::{self, GetQueryResultError, StartQueryError};
+use smoltcp::socket::dns::{self, GetQueryResultError, QueryHandle, StartQueryError};
 use smoltcp::wire::DnsQueryType;
 
 use super::addr::into_core_ipaddr;
-use super::{SocketSetWrapper, SOCKET_SET};
+use super::{wait_for_progress, SocketSetWrapper, SOCKET_SET};
+
+/// The time to wait for the response of a query before sending it again.
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+/// The number of times a query is sent before giving up.
+const DNS_ATTEMPTS: usize = 3;
 
 /// A DNS socket.
 struct DnsSocket {
@@ -32,12 +39,57 @@ impl DnsSocket {
     }
 
     /// Query a address with given DNS query type.
+    ///
+    /// The query is sent again if there is no response in [`DNS_TIMEOUT`], as
+    /// the time of the interfaces doesn't advance for the socket to do it. It
+    /// returns [`WouldBlock`](AxError::WouldBlock) if all the attempts time
+    /// out, or [`NotFound`](AxError::NotFound) if the servers fail to resolve
+    /// the name.
     pub fn query(&self, name: &str, query_type: DnsQueryType) -> AxResult<Vec<IpAddr>> {
-        // let local_addr = self.local_addr.unwrap_or_else(f);
         let handle = self.handle.ok_or_else(|| ax_err_type!(InvalidInput))?;
+        for attempt in 1..=DNS_ATTEMPTS {
+            let query_handle = self.start_query(handle, name, query_type)?;
+            let deadline = monotonic_time() + DNS_TIMEOUT;
+            loop {
+                SOCKET_SET.poll_interfaces();
+                match SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
+                    socket.get_query_result(query_handle).map_err(|e| match e {
+                        GetQueryResultError::Pending => AxError::WouldBlock,
+                        GetQueryResultError::Failed => {
+                            ax_err_type!(NotFound, "socket query() failed")
+                        }
+                    })
+                }) {
+                    Ok(n) => {
+                        let mut res = Vec::with_capacity(n.capacity());
+                        for ip in n {
+                            res.push(into_core_ipaddr(ip))
+                        }
+                        return Ok(res);
+                    }
+                    Err(AxError::WouldBlock) if monotonic_time() < deadline => {
+                        wait_for_progress(Some(deadline))
+                    }
+                    Err(AxError::WouldBlock) => {
+                        self.cancel_query(handle, query_handle);
+                        debug!("DNS query for {} timed out, attempt {}", name, attempt);
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        ax_err!(WouldBlock, "socket query() failed: timed out")
+    }
 
+    fn start_query(
+        &self,
+        handle: SocketHandle,
+        name: &str,
+        query_type: DnsQueryType,
+    ) -> AxResult<QueryHandle> {
         let iface = &super::ETH0.iface;
-        let query_handle = SOCKET_SET
+        SOCKET_SET
             .with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
                 socket.start_query(iface.lock().context(), name, query_type)
             })
@@ -51,28 +103,13 @@ impl DnsSocket {
                 StartQueryError::NameTooLong => {
                     ax_err_type!(InvalidInput, "socket query() failed: too long name")
                 }
-            })?;
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
-                socket.get_query_result(query_handle).map_err(|e| match e {
-                    GetQueryResultError::Pending => AxError::WouldBlock,
-                    GetQueryResultError::Failed => {
-                        ax_err_type!(ConnectionRefused, "socket query() failed")
-                    }
-                })
-            }) {
-                Ok(n) => {
-                    let mut res = Vec::with_capacity(n.capacity());
-                    for ip in n {
-                        res.push(into_core_ipaddr(ip))
-                    }
-                    return Ok(res);
-                }
-                Err(AxError::WouldBlock) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
-        }
+            })
+    }
+
+    fn cancel_query(&self, handle: SocketHandle, query_handle: QueryHandle) {
+        SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
+            socket.cancel_query(query_handle)
+        });
     }
 }
 
@@ -85,6 +122,10 @@ impl Drop for DnsSocket {
 }
 
 /// Public function for DNS query.
+///
+/// It sends the query up to 3 times and waits 2 seconds for the response
+/// each time, [`WouldBlock`](AxError::WouldBlock) is returned if there is no
+/// response at all.
 pub fn dns_query(name: &str) -> AxResult<alloc::vec::Vec<IpAddr>> {
     let socket = DnsSocket::new();
     socket.query(name, DnsQueryType::A)
@@ -0,0 +1,139 @@
+//! Early packet processing hooks, like XDP of Linux.
+//!
+//! Hooks run on each frame received from the NIC before it's ingested by
+//! smoltcp, in the order they are registered. A hook can modify the frame in
+//! place and decide its fate by the returned [`HookAction`], so firewalls and
+//! load balancers can be built without the cost of the whole stack.
+//!
+//! Hooks are Rust closures, so filters loaded at runtime, e.g. in WebAssembly,
+//! are run by a closure wrapping their interpreter.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use axerrno::{ax_err, AxResult};
+use spin::RwLock;
+
+/// The verdict of a hook on a received frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    /// Passes the frame to the next hook, or the network stack if it's the last.
+    Pass,
+    /// Drops the frame.
+    Drop,
+    /// Transmits the frame out of the NIC it was received from, like `XDP_TX`,
+    /// e.g. after rewriting its addresses.
+    Redirect,
+}
+
+/// The statistics of a hook.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HookStats {
+    /// The number of frames passed.
+    pub passed: u64,
+    /// The number of frames dropped.
+    pub dropped: u64,
+    /// The number of frames redirected.
+    pub redirected: u64,
+    /// The number of bytes of all frames seen.
+    pub bytes: u64,
+}
+
+/// The identifier of a registered hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookId(usize);
+
+type HookFn = Box<dyn Fn(&mut [u8]) -> HookAction + Send + Sync>;
+
+struct Hook {
+    id: HookId,
+    name: &'static str,
+    func: HookFn,
+    passed: AtomicU64,
+    dropped: AtomicU64,
+    redirected: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Hook {
+    fn stats(&self) -> HookStats {
+        HookStats {
+            passed: self.passed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            redirected: self.redirected.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static HOOKS: RwLock<Vec<Hook>> = RwLock::new(Vec::new());
+/// Whether there is any hook, to skip the lock on the fast path.
+static HAS_HOOKS: AtomicBool = AtomicBool::new(false);
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers a hook named `name`, which runs after all the registered ones.
+///
+/// The hook runs with the NIC locked, so it must not block or use sockets.
+/// The length of the frame cannot be changed.
+pub fn register_hook<F>(name: &'static str, f: F) -> HookId
+where
+    F: Fn(&mut [u8]) -> HookAction + Send + Sync + 'static,
+{
+    let id = HookId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut hooks = HOOKS.write();
+    hooks.push(Hook {
+        id,
+        name,
+        func: Box::new(f),
+        passed: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        redirected: AtomicU64::new(0),
+        bytes: AtomicU64::new(0),
+    });
+    HAS_HOOKS.store(true, Ordering::Release);
+    debug!("net hook {:?} registered: {}", id, name);
+    id
+}
+
+/// Unregisters the hook `id`.
+pub fn unregister_hook(id: HookId) -> AxResult {
+    let mut hooks = HOOKS.write();
+    let Some(index) = hooks.iter().position(|hook| hook.id == id) else {
+        return ax_err!(NotFound, "net hook not registered");
+    };
+    hooks.remove(index);
+    HAS_HOOKS.store(!hooks.is_empty(), Ordering::Release);
+    Ok(())
+}
+
+/// Returns the names and statistics of the registered hooks, in the order
+/// they run.
+pub fn hook_stats() -> Vec<(&'static str, HookStats)> {
+    HOOKS
+        .read()
+        .iter()
+        .map(|hook| (hook.name, hook.stats()))
+        .collect()
+}
+
+/// Runs the hooks on a received frame, until one of them does not pass it.
+pub(crate) fn run_hooks(frame: &mut [u8]) -> HookAction {
+    if !HAS_HOOKS.load(Ordering::Acquire) {
+        return HookAction::Pass;
+    }
+    for hook in HOOKS.read().iter() {
+        hook.bytes.fetch_add(frame.len() as u64, Ordering::Relaxed);
+        let action = (hook.func)(frame);
+        let counter = match action {
+            HookAction::Pass => &hook.passed,
+            HookAction::Drop => &hook.dropped,
+            HookAction::Redirect => &hook.redirected,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if action != HookAction::Pass {
+            return action;
+        }
+    }
+    HookAction::Pass
+}
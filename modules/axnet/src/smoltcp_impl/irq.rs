@@ -0,0 +1,101 @@
+//! Interrupt-driven receiving on `eth0`, with NAPI-like polling under load.
+//!
+//! Without the interrupt of the NIC, the blocking operations poll the
+//! interfaces and yield in a loop. With it, they sleep until the interrupt
+//! tells that frames arrive, or the interfaces make progress by the other
+//! tasks (e.g. the frames on the loopback interface).
+//!
+//! The interrupt is masked when raised, and unmasked once a poll receives
+//! fewer frames than [`NAPI_BUDGET`]. Otherwise more frames are coming, and
+//! the stack stays in the polling mode, where the blocking operations poll
+//! without sleeping, so there is no interrupt per frame under load.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axdriver::DeviceIrq;
+use axhal::time::TimeValue;
+use axtask::WaitQueue;
+use lazy_init::LazyInit;
+
+/// The maximum number of frames received from `eth0` in one poll.
+pub(crate) const NAPI_BUDGET: usize = 64;
+/// The maximum time to sleep without interrupts, in case of a missed wakeup.
+const MAX_IDLE_WAIT: Duration = Duration::from_millis(10);
+
+static IRQ: LazyInit<DeviceIrq> = LazyInit::new();
+/// Set when the interrupt is masked, until the frames are all received.
+static POLLING: AtomicBool = AtomicBool::new(false);
+/// Incremented when the interfaces may have made progress.
+static EVENTS: AtomicUsize = AtomicUsize::new(0);
+static EVENT_WAIT: WaitQueue = WaitQueue::new();
+/// Called when the interfaces may have made progress, for the tasks waiting
+/// for the sockets with other files.
+static EVENT_NOTIFIER: spin::Once<fn()> = spin::Once::new();
+
+/// Receives the interrupts of `eth0`.
+pub(crate) fn init(irq: DeviceIrq) {
+    IRQ.init_by(irq);
+    if axhal::irq::register_handler(irq.irq_num, handle_irq) {
+        info!("  irq:      {}", irq.irq_num);
+    }
+}
+
+fn handle_irq() {
+    let irq = IRQ.try_get().unwrap();
+    if irq.ack() {
+        // masked until the frames are received by `poll_done`
+        axhal::irq::set_enable(irq.irq_num, false);
+        POLLING.store(true, Ordering::Release);
+        notify();
+    }
+}
+
+/// Wakes up the tasks waiting for the interfaces to make progress.
+pub(crate) fn notify() {
+    EVENTS.fetch_add(1, Ordering::AcqRel);
+    EVENT_WAIT.notify_all(false);
+    if let Some(notifier) = EVENT_NOTIFIER.get() {
+        notifier();
+    }
+}
+
+/// Sets the callback invoked by [`notify`], only the first one is kept.
+pub(crate) fn set_event_notifier(notifier: fn()) {
+    EVENT_NOTIFIER.call_once(|| notifier);
+}
+
+/// Returns whether the tasks can sleep until [`notify`], i.e. `eth0` has an
+/// interrupt and is not in the polling mode.
+pub(crate) fn interrupt_driven() -> bool {
+    IRQ.try_get().is_some() && !POLLING.load(Ordering::Acquire)
+}
+
+/// Called after `eth0` is polled, with the number of frames received.
+pub(crate) fn poll_done(received: usize) {
+    let Some(irq) = IRQ.try_get() else {
+        return;
+    };
+    if received < NAPI_BUDGET && POLLING.swap(false, Ordering::AcqRel) {
+        axhal::irq::set_enable(irq.irq_num, true);
+    }
+}
+
+/// Waits for the interfaces to make progress before polling them again, until
+/// the `deadline`.
+///
+/// It only yields if there is no interrupt, or in the polling mode.
+pub(crate) fn wait_for_progress(deadline: Option<TimeValue>) {
+    if !interrupt_driven() {
+        axtask::yield_now();
+        return;
+    }
+    let events = EVENTS.load(Ordering::Acquire);
+    let now = axhal::time::monotonic_time();
+    let timeout = deadline.map_or(MAX_IDLE_WAIT, |d| {
+        d.saturating_sub(now).min(MAX_IDLE_WAIT)
+    });
+    EVENT_WAIT.wait_timeout_until(timeout, || {
+        EVENTS.load(Ordering::Acquire) != events || POLLING.load(Ordering::Acquire)
+    });
+}
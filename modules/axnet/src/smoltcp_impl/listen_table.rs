@@ -1,5 +1,5 @@
-use alloc::{boxed::Box, collections::VecDeque};
-use core::ops::{Deref, DerefMut};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
@@ -11,15 +11,22 @@ use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
 
 const PORT_NUM: usize = 65536;
 
+/// Identifies a listener in the [`ListenTable`].
+pub type ListenerId = usize;
+
 struct ListenTableEntry {
+    id: ListenerId,
     listen_endpoint: IpListenEndpoint,
+    reuse_port: bool,
     syn_queue: VecDeque<SocketHandle>,
 }
 
 impl ListenTableEntry {
-    pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
+    pub fn new(id: ListenerId, listen_endpoint: IpListenEndpoint, reuse_port: bool) -> Self {
         Self {
+            id,
             listen_endpoint,
+            reuse_port,
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
         }
     }
@@ -41,8 +48,15 @@ impl Drop for ListenTableEntry {
     }
 }
 
+/// The TCP listeners of each port.
+///
+/// A port has a single listener, or a group of listeners which all set
+/// `SO_REUSEPORT`. The incoming connections are spread across the group by
+/// the hash of their addresses, so each listener has its own SYN queue and
+/// the threads accepting on them don't contend for the same one.
 pub struct ListenTable {
-    tcp: Box<[Mutex<Option<Box<ListenTableEntry>>>]>,
+    tcp: Box<[Mutex<Vec<ListenTableEntry>>]>,
+    next_id: AtomicUsize,
 }
 
 impl ListenTable {
@@ -50,44 +64,63 @@ impl ListenTable {
         let tcp = unsafe {
             let mut buf = Box::new_uninit_slice(PORT_NUM);
             for i in 0..PORT_NUM {
-                buf[i].write(Mutex::new(None));
+                buf[i].write(Mutex::new(Vec::new()));
             }
             buf.assume_init()
         };
-        Self { tcp }
+        Self {
+            tcp,
+            next_id: AtomicUsize::new(0),
+        }
     }
 
     pub fn can_listen(&self, port: u16) -> bool {
-        self.tcp[port as usize].lock().is_none()
+        self.tcp[port as usize].lock().is_empty()
     }
 
-    pub fn listen(&self, listen_endpoint: IpListenEndpoint) -> AxResult {
+    /// Adds a listener on the port, which joins the listeners already there
+    /// if all of them and the new one set `SO_REUSEPORT`.
+    pub fn listen(
+        &self,
+        listen_endpoint: IpListenEndpoint,
+        reuse_port: bool,
+    ) -> AxResult<ListenerId> {
         let port = listen_endpoint.port;
         assert_ne!(port, 0);
-        let mut entry = self.tcp[port as usize].lock();
-        if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint)));
-            Ok(())
+        let mut entries = self.tcp[port as usize].lock();
+        if entries.iter().all(|entry| reuse_port && entry.reuse_port) {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            entries.push(ListenTableEntry::new(id, listen_endpoint, reuse_port));
+            Ok(id)
         } else {
             ax_err!(AddrInUse, "socket listen() failed")
         }
     }
 
-    pub fn unlisten(&self, port: u16) {
+    pub fn unlisten(&self, port: u16, id: ListenerId) {
         debug!("TCP socket unlisten on {}", port);
-        *self.tcp[port as usize].lock() = None;
+        // The connections not accepted yet are reset, as Linux does.
+        self.tcp[port as usize]
+            .lock()
+            .retain(|entry| entry.id != id);
     }
 
-    pub fn can_accept(&self, port: u16) -> AxResult<bool> {
-        if let Some(entry) = self.tcp[port as usize].lock().deref() {
+    pub fn can_accept(&self, port: u16, id: ListenerId) -> AxResult<bool> {
+        let entries = self.tcp[port as usize].lock();
+        if let Some(entry) = entries.iter().find(|entry| entry.id == id) {
             Ok(entry.syn_queue.iter().any(|&handle| is_connected(handle)))
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
     }
 
-    pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, (IpEndpoint, IpEndpoint))> {
-        if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+    pub fn accept(
+        &self,
+        port: u16,
+        id: ListenerId,
+    ) -> AxResult<(SocketHandle, (IpEndpoint, IpEndpoint))> {
+        let mut entries = self.tcp[port as usize].lock();
+        if let Some(entry) = entries.iter_mut().find(|entry| entry.id == id) {
             let syn_queue: &mut VecDeque<SocketHandle> = &mut entry.syn_queue;
             let idx = syn_queue
                 .iter()
@@ -120,29 +153,54 @@ impl ListenTable {
         dst: IpEndpoint,
         sockets: &mut SocketSet<'_>,
     ) {
-        if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
-            if !entry.can_accept(dst.addr) {
-                // not listening on this address
-                return;
-            }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
-                // SYN queue is full, drop the packet
-                warn!("SYN queue overflow!");
-                return;
-            }
-            let mut socket = SocketSetWrapper::new_tcp_socket();
-            if socket.listen(entry.listen_endpoint).is_ok() {
-                let handle = sockets.add(socket);
-                debug!(
-                    "TCP socket {}: prepare for connection {} -> {}",
-                    handle, src, entry.listen_endpoint
-                );
-                entry.syn_queue.push_back(handle);
-            }
+        let mut entries = self.tcp[dst.port as usize].lock();
+        let group: Vec<usize> = (0..entries.len())
+            .filter(|&i| entries[i].can_accept(dst.addr))
+            .collect();
+        if group.is_empty() {
+            // not listening on this address
+            return;
+        }
+        // Pick a listener of the group by the hash of the addresses, and try
+        // the next ones if its SYN queue is full.
+        let start = hash_endpoints(src, dst) % group.len();
+        let Some(entry) = (0..group.len())
+            .map(|i| group[(start + i) % group.len()])
+            .find(|&i| entries[i].syn_queue.len() < LISTEN_QUEUE_SIZE)
+        else {
+            // SYN queue is full, drop the packet
+            warn!("SYN queue overflow!");
+            return;
+        };
+        let entry = &mut entries[entry];
+        let mut socket = SocketSetWrapper::new_tcp_socket();
+        if socket.listen(entry.listen_endpoint).is_ok() {
+            let handle = sockets.add(socket);
+            debug!(
+                "TCP socket {}: prepare for connection {} -> {}",
+                handle, src, entry.listen_endpoint
+            );
+            entry.syn_queue.push_back(handle);
         }
     }
 }
 
+/// FNV-1a hash of the addresses of a connection.
+fn hash_endpoints(src: IpEndpoint, dst: IpEndpoint) -> usize {
+    let mut hash: u32 = 0x811c_9dc5;
+    let ports = [src.port.to_be_bytes(), dst.port.to_be_bytes()];
+    for &byte in src
+        .addr
+        .as_bytes()
+        .iter()
+        .chain(dst.addr.as_bytes())
+        .chain(ports.iter().flatten())
+    {
+        hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+    }
+    hash as usize
+}
+
 fn is_connected(handle: SocketHandle) -> bool {
     SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
         !matches!(socket.state(), State::Listen | State::SynReceived)
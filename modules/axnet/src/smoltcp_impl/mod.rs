@@ -1,18 +1,28 @@
 mod addr;
 mod bench;
+#[cfg(feature = "dhcp")]
+mod dhcp;
 mod dns;
+mod hook;
+#[cfg(feature = "irq")]
+mod irq;
 mod listen_table;
+mod nat;
 mod tcp;
 mod udp;
 
 use alloc::vec;
-use axerrno::{AxError, AxResult};
+use alloc::vec::Vec;
+use axerrno::{ax_err_type, AxError, AxResult};
 use core::cell::RefCell;
 use core::ops::DerefMut;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
 
 use axdriver::prelude::*;
+use axdriver::DeviceIrq;
 use axdriver_net::{DevError, NetBufPtr};
-use axhal::time::NANOS_PER_MICROS;
+use axhal::time::{TimeValue, NANOS_PER_MICROS};
 use axsync::Mutex;
 use lazy_init::LazyInit;
 use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
@@ -23,8 +33,12 @@ use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 
 use self::listen_table::ListenTable;
 
+#[cfg(feature = "dhcp")]
+pub use self::dhcp::{dhcp_lease, dhcp_renew, DhcpLease};
 pub use self::dns::dns_query;
-pub use self::tcp::TcpSocket;
+pub use self::hook::{hook_stats, register_hook, unregister_hook, HookAction, HookId, HookStats};
+pub use self::nat::{ip_forward, nat_rules, set_ip_forward, set_nat_rules, NatProtocol, NatRule};
+pub use self::tcp::{ConnectFailure, TcpSocket};
 pub use self::udp::UdpSocket;
 pub use addr::{from_core_sockaddr, into_core_sockaddr};
 #[allow(unused)]
@@ -40,31 +54,52 @@ macro_rules! env_or_default {
 const DNS_SEVER: &str = "8.8.8.8";
 
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
+
+/// The number of frames received or transmitted by `eth0`.
+static FRAMES: AtomicU64 = AtomicU64::new(0);
 const STANDARD_MTU: usize = 1500;
 const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
 const LISTEN_QUEUE_SIZE: usize = 512;
+/// The maximum number of frames received from a NIC in one poll, the rest are
+/// received by the next poll.
+const RX_BUDGET: usize = 64;
+/// The time to wait for the peer to close a dropped TCP socket, like the
+/// `tcp_fin_timeout` of Linux.
+const TCP_FIN_TIMEOUT: smoltcp::time::Duration = smoltcp::time::Duration::from_secs(60);
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
+/// The dropped TCP sockets, which are removed once closed.
+static CLOSING_SOCKETS: Mutex<Vec<SocketHandle>> = Mutex::new(Vec::new());
 
 mod loopback;
 static LOOPBACK_DEV: LazyInit<Mutex<LoopbackDev>> = LazyInit::new();
 static LOOPBACK: LazyInit<Mutex<Interface>> = LazyInit::new();
 use self::loopback::LoopbackDev;
 
+#[cfg(not(feature = "dhcp"))]
 const IP: &str = env_or_default!("AX_IP");
+#[cfg(not(feature = "dhcp"))]
 const GATEWAY: &str = env_or_default!("AX_GW");
+#[cfg(not(feature = "dhcp"))]
 const IP_PREFIX: u8 = 24;
 
 static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
 
 struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
 
+/// The smoltcp device of a NIC.
+///
+/// The frames are not copied between the driver and smoltcp: the tokens hold
+/// the DMA buffers of the driver, and smoltcp reads and writes the frames in
+/// place.
 struct DeviceWrapper {
     inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
+    /// The number of frames received in the current poll.
+    received: usize,
 }
 
 struct InterfaceWrapper {
@@ -74,6 +109,32 @@ struct InterfaceWrapper {
     iface: Mutex<Interface>,
 }
 
+/// The timeout of blocking socket operations, they never time out if unset.
+struct SocketTimeout(AtomicU64); // in nanoseconds, 0 if unset
+
+impl SocketTimeout {
+    const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    fn get(&self) -> Option<Duration> {
+        match self.0.load(Ordering::Acquire) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    fn set(&self, timeout: Option<Duration>) {
+        let nanos = timeout.map_or(0, |t| (t.as_nanos() as u64).max(1));
+        self.0.store(nanos, Ordering::Release);
+    }
+
+    /// Returns the deadline of an operation starting now.
+    fn deadline(&self) -> Option<TimeValue> {
+        self.get().map(|t| axhal::time::monotonic_time() + t)
+    }
+}
+
 impl<'a> SocketSetWrapper<'a> {
     fn new() -> Self {
         Self(Mutex::new(SocketSet::new(vec![])))
@@ -98,6 +159,13 @@ impl<'a> SocketSetWrapper<'a> {
     }
 
     pub fn new_dns_socket() -> socket::dns::Socket<'a> {
+        #[cfg(feature = "dhcp")]
+        {
+            let servers = dhcp::dns_servers();
+            if !servers.is_empty() {
+                return socket::dns::Socket::new(&servers, vec![]);
+            }
+        }
         let server_addr = DNS_SEVER.parse().expect("invalid DNS server address");
         socket::dns::Socket::new(&[server_addr], vec![])
     }
@@ -148,17 +216,55 @@ impl<'a> SocketSetWrapper<'a> {
     }
 
     pub fn poll_interfaces(&self) {
-        LOOPBACK.lock().poll(
+        let _changed = LOOPBACK.lock().poll(
             Instant::from_micros_const((0 / NANOS_PER_MICROS) as i64),
             LOOPBACK_DEV.lock().deref_mut(),
             &mut self.0.lock(),
         );
+        // the frames on the loopback interface raise no interrupt
+        #[cfg(feature = "irq")]
+        if _changed {
+            irq::notify();
+        }
+        ETH0.poll(&self.0);
+        self.remove_closed();
     }
 
     pub fn remove(&self, handle: SocketHandle) {
         self.0.lock().remove(handle);
         debug!("socket {}: destroyed", handle);
     }
+
+    /// Removes a dropped TCP socket, or defers it until the connection is
+    /// closed if not yet.
+    pub fn remove_when_closed(&self, handle: SocketHandle) {
+        let mut set = self.0.lock();
+        let socket = set.get_mut::<socket::tcp::Socket>(handle);
+        if tcp::is_closed(socket) {
+            set.remove(handle);
+            debug!("socket {}: destroyed", handle);
+        } else {
+            socket.set_timeout(Some(TCP_FIN_TIMEOUT));
+            CLOSING_SOCKETS.lock().push(handle);
+            debug!("socket {}: closing", handle);
+        }
+    }
+
+    fn remove_closed(&self) {
+        let mut closing = CLOSING_SOCKETS.lock();
+        if closing.is_empty() {
+            return;
+        }
+        let mut set = self.0.lock();
+        closing.retain(|&handle| {
+            let closed = tcp::is_closed(set.get::<socket::tcp::Socket>(handle));
+            if closed {
+                set.remove(handle);
+                debug!("socket {}: destroyed", handle);
+            }
+            !closed
+        });
+    }
 }
 
 #[allow(unused)]
@@ -209,7 +315,15 @@ impl InterfaceWrapper {
         let mut iface = self.iface.lock();
         let mut sockets = sockets.lock();
         let timestamp = Self::current_time();
-        iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        dev.received = 0;
+        let _changed = iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        #[cfg(feature = "irq")]
+        {
+            irq::poll_done(dev.received);
+            if _changed {
+                irq::notify();
+            }
+        }
     }
 }
 
@@ -217,6 +331,7 @@ impl DeviceWrapper {
     fn new(inner: AxNetDevice) -> Self {
         Self {
             inner: RefCell::new(inner),
+            received: 0,
         }
     }
 }
@@ -232,25 +347,58 @@ impl Device for DeviceWrapper {
         Self: 'a;
 
     fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        if self.received >= RX_BUDGET {
+            return None;
+        }
         let mut dev = self.inner.borrow_mut();
         if let Err(e) = dev.recycle_tx_buffers() {
             warn!("recycle_tx_buffers failed: {:?}", e);
             return None;
         }
 
-        if !dev.can_transmit() {
-            return None;
-        }
-        let rx_buf = match dev.receive() {
-            Ok(buf) => buf,
-            Err(err) => {
-                if !matches!(err, DevError::Again) {
-                    warn!("receive failed: {:?}", err);
-                }
+        loop {
+            if !dev.can_transmit() {
                 return None;
             }
-        };
-        Some((AxNetRxToken(&self.inner, rx_buf), AxNetTxToken(&self.inner)))
+            let mut rx_buf = match dev.receive() {
+                Ok(buf) => buf,
+                Err(err) => {
+                    if !matches!(err, DevError::Again) {
+                        warn!("receive failed: {:?}", err);
+                    }
+                    return None;
+                }
+            };
+            #[cfg(feature = "dhcp")]
+            if dhcp::take_frame(rx_buf.packet()) {
+                // Received by the DHCP client instead.
+                dev.recycle_rx_buffer(rx_buf).unwrap();
+                continue;
+            }
+            match hook::run_hooks(rx_buf.packet_mut()) {
+                HookAction::Pass => {
+                    self.received += 1;
+                    FRAMES.fetch_add(1, Ordering::Relaxed);
+                    let rx_token = AxNetRxToken::new(&self.inner, rx_buf);
+                    return Some((rx_token, AxNetTxToken(&self.inner)));
+                }
+                HookAction::Drop => {}
+                HookAction::Redirect => {
+                    let len = rx_buf.packet_len();
+                    match dev.alloc_tx_buffer(len) {
+                        Ok(mut tx_buf) => {
+                            tx_buf.packet_mut().copy_from_slice(rx_buf.packet());
+                            if let Err(e) = dev.transmit(tx_buf) {
+                                warn!("redirect transmit failed: {:?}", e);
+                            }
+                        }
+                        Err(e) => warn!("redirect alloc_tx_buffer failed: {:?}", e),
+                    }
+                }
+            }
+            // The frame is consumed by the hooks, try the next one.
+            dev.recycle_rx_buffer(rx_buf).unwrap();
+        }
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
@@ -275,19 +423,40 @@ impl Device for DeviceWrapper {
     }
 }
 
-struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr);
+/// A received frame in the RX buffer of the driver.
+///
+/// The buffer is given back to the RX queue once consumed, or dropped without
+/// being consumed, so the queue never runs out of buffers.
+struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, Option<NetBufPtr>);
+/// A frame to send, which is written in a TX buffer of the driver.
 struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>);
 
+impl<'a> AxNetRxToken<'a> {
+    fn new(dev: &'a RefCell<AxNetDevice>, rx_buf: NetBufPtr) -> Self {
+        Self(dev, Some(rx_buf))
+    }
+}
+
+impl Drop for AxNetRxToken<'_> {
+    fn drop(&mut self) {
+        if let Some(rx_buf) = self.1.take() {
+            self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
+        }
+    }
+}
+
 impl<'a> RxToken for AxNetRxToken<'a> {
     fn preprocess(&self, sockets: &mut SocketSet<'_>) {
-        snoop_tcp_packet(self.1.packet(), sockets).ok();
+        if let Some(rx_buf) = &self.1 {
+            snoop_tcp_packet(rx_buf.packet(), sockets).ok();
+        }
     }
 
-    fn consume<R, F>(self, f: F) -> R
+    fn consume<R, F>(mut self, f: F) -> R
     where
         F: FnOnce(&mut [u8]) -> R,
     {
-        let mut rx_buf = self.1;
+        let mut rx_buf = self.1.take().unwrap();
         trace!(
             "RECV {} bytes: {:02X?}",
             rx_buf.packet_len(),
@@ -309,6 +478,7 @@ impl<'a> TxToken for AxNetTxToken<'a> {
         let ret = f(tx_buf.packet_mut());
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
         dev.transmit(tx_buf).unwrap();
+        FRAMES.fetch_add(1, Ordering::Relaxed);
         ret
     }
 }
@@ -332,6 +502,84 @@ fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smolt
     Ok(())
 }
 
+/// Returns the interface to reach `addr`: the loopback interface for the
+/// loopback addresses and the addresses of this host, `eth0` otherwise.
+fn route(addr: IpAddress) -> &'static Mutex<Interface> {
+    if addr.is_loopback() || LOOPBACK.lock().has_ip_addr(addr) {
+        &LOOPBACK
+    } else {
+        &ETH0.iface
+    }
+}
+
+/// Makes the addresses of `eth0` also owned by the loopback interface, so
+/// the traffic between the sockets of this host never leaves it.
+fn sync_loopback_addrs() {
+    let addrs: Vec<IpAddress> = ETH0
+        .iface
+        .lock()
+        .ip_addrs()
+        .iter()
+        .map(|cidr| cidr.address())
+        .collect();
+    LOOPBACK.lock().update_ip_addrs(|ip_addrs| {
+        ip_addrs.clear();
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+            .unwrap();
+        for addr in addrs {
+            let prefix_len = match addr {
+                IpAddress::Ipv4(_) => 32,
+                IpAddress::Ipv6(_) => 128,
+            };
+            if ip_addrs.push(IpCidr::new(addr, prefix_len)).is_err() {
+                warn!("too many addresses for the loopback interface: {}", addr);
+            }
+        }
+    });
+}
+
+/// Waits before polling the interfaces again in a blocking operation, until
+/// the `deadline`.
+///
+/// It sleeps until frames arrive if the NIC has an interrupt, or only yields
+/// otherwise.
+fn wait_for_progress(_deadline: Option<TimeValue>) {
+    #[cfg(feature = "irq")]
+    irq::wait_for_progress(_deadline);
+    #[cfg(not(feature = "irq"))]
+    axtask::yield_now();
+}
+
+/// Sets the callback to be invoked when the interfaces may have made progress,
+/// e.g. frames arrive on the interrupt of the NIC.
+///
+/// It is usually used to wake up the tasks waiting for the sockets together
+/// with other files, e.g. in `select`. Only the first callback is kept, and
+/// it is never invoked without the `irq` feature.
+pub fn set_event_notifier(_notifier: fn()) {
+    #[cfg(feature = "irq")]
+    irq::set_event_notifier(_notifier);
+}
+
+/// Returns whether the progress of the interfaces is notified by the
+/// interrupt of the NIC, see [`set_event_notifier`].
+///
+/// Otherwise, the tasks waiting for the sockets must poll the interfaces
+/// without sleeping.
+pub fn interrupt_driven() -> bool {
+    #[cfg(feature = "irq")]
+    if irq::interrupt_driven() {
+        return true;
+    }
+    false
+}
+
+/// Returns the number of frames received or transmitted by `eth0` so far.
+pub fn frame_count() -> u64 {
+    FRAMES.load(Ordering::Relaxed)
+}
+
 /// Poll the network stack.
 ///
 /// It may receive packets from the NIC and process them, and transmit queued
@@ -350,47 +598,75 @@ pub fn bench_receive() {
     ETH0.dev.lock().bench_receive_bandwidth();
 }
 
-/// Add multicast_addr to the loopback device.
-pub fn add_membership(multicast_addr: IpAddress, _interface_addr: IpAddress) {
+/// Joins the multicast group `multicast_addr` on all interfaces.
+pub fn add_membership(multicast_addr: IpAddress, _interface_addr: IpAddress) -> AxResult {
     let timestamp = Instant::from_micros_const((0 / NANOS_PER_MICROS) as i64);
-    let _ = LOOPBACK.lock().join_multicast_group(
-        LOOPBACK_DEV.lock().deref_mut(),
-        multicast_addr,
-        timestamp,
-    );
+    LOOPBACK
+        .lock()
+        .join_multicast_group(LOOPBACK_DEV.lock().deref_mut(), multicast_addr, timestamp)
+        .map_err(|_| ax_err_type!(InvalidInput, "add_membership() failed"))?;
+    let mut dev = ETH0.dev.lock();
+    ETH0.iface
+        .lock()
+        .join_multicast_group(dev.deref_mut(), multicast_addr, timestamp)
+        .map_err(|_| ax_err_type!(InvalidInput, "add_membership() failed"))?;
+    Ok(())
+}
+
+/// Leaves the multicast group `multicast_addr` on all interfaces.
+pub fn drop_membership(multicast_addr: IpAddress, _interface_addr: IpAddress) -> AxResult {
+    let timestamp = Instant::from_micros_const((0 / NANOS_PER_MICROS) as i64);
+    LOOPBACK
+        .lock()
+        .leave_multicast_group(LOOPBACK_DEV.lock().deref_mut(), multicast_addr, timestamp)
+        .map_err(|_| ax_err_type!(InvalidInput, "drop_membership() failed"))?;
+    let mut dev = ETH0.dev.lock();
+    ETH0.iface
+        .lock()
+        .leave_multicast_group(dev.deref_mut(), multicast_addr, timestamp)
+        .map_err(|_| ax_err_type!(InvalidInput, "drop_membership() failed"))?;
+    Ok(())
 }
 
-pub(crate) fn init(_net_dev: AxNetDevice) {
+pub(crate) fn init(_net_dev: AxNetDevice, _irq: Option<DeviceIrq>) {
     let mut device = LoopbackDev::new(Medium::Ip);
     let config = Config::new(smoltcp::wire::HardwareAddress::Ip);
 
-    let mut iface = Interface::new(
+    let iface = Interface::new(
         config,
         &mut device,
         Instant::from_micros_const((0 / NANOS_PER_MICROS) as i64),
     );
-    iface.update_ip_addrs(|ip_addrs| {
-        ip_addrs
-            .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
-            .unwrap();
-    });
     LOOPBACK.init_by(Mutex::new(iface));
     LOOPBACK_DEV.init_by(Mutex::new(device));
+    info!("created net interface \"lo\":");
+    info!("  ip:       127.0.0.1/8");
 
     let ether_addr = EthernetAddress(_net_dev.mac_address().0);
     let eth0 = InterfaceWrapper::new("eth0", _net_dev, ether_addr);
-
-    let ip = IP.parse().expect("invalid IP address");
-    let gateway = GATEWAY.parse().expect("invalid gateway IP address");
-    eth0.setup_ip_addr(ip, IP_PREFIX);
-    eth0.setup_gateway(gateway);
-
     ETH0.init_by(eth0);
     info!("created net interface {:?}:", ETH0.name());
     info!("  ether:    {}", ETH0.ethernet_address());
-    info!("  ip:       {}/{}", ip, IP_PREFIX);
-    info!("  gateway:  {}", gateway);
+
+    #[cfg(feature = "dhcp")]
+    dhcp::init();
+    #[cfg(not(feature = "dhcp"))]
+    {
+        let ip = IP.parse().expect("invalid IP address");
+        let gateway = GATEWAY.parse().expect("invalid gateway IP address");
+        ETH0.setup_ip_addr(ip, IP_PREFIX);
+        ETH0.setup_gateway(gateway);
+        nat::init(ether_addr, ip, IP_PREFIX);
+        info!("  ip:       {}/{}", ip, IP_PREFIX);
+        info!("  gateway:  {}", gateway);
+    }
+    sync_loopback_addrs();
 
     SOCKET_SET.init_by(SocketSetWrapper::new());
     LISTEN_TABLE.init_by(ListenTable::new());
+
+    #[cfg(feature = "irq")]
+    if let Some(irq) = _irq {
+        irq::init(irq);
+    }
 }
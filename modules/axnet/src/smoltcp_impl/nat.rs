@@ -0,0 +1,367 @@
+//! NAT and port forwarding.
+//!
+//! When IP forwarding is enabled, TCP and UDP packets received on a forwarded
+//! port are sent to the target of the [`NatRule`], with the source rewritten
+//! to this host (masquerade), so the replies come back and are sent to the
+//! client. It runs as the `nat` packet hook, before the network stack.
+//!
+//! The MAC addresses of the targets are learned from the frames they send, as
+//! the neighbor cache of smoltcp is not shared, and packets to targets not
+//! seen yet are dropped.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::SocketAddrV4;
+use core::str::FromStr;
+
+use axerrno::{AxError, AxResult};
+use axhal::time::{monotonic_time_nanos, NANOS_PER_SEC};
+use smoltcp::wire::{
+    ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, IpAddress, IpProtocol,
+    Ipv4Address, Ipv4Cidr, Ipv4Packet, TcpPacket, UdpPacket,
+};
+use spin::{Mutex, RwLock};
+
+use super::hook::{register_hook, unregister_hook, HookAction, HookId};
+
+/// The ports of this host to which the clients are masqueraded, below the
+/// ephemeral ports of sockets.
+const NAT_PORT_START: u16 = 0x8000;
+const NAT_PORT_END: u16 = 0xc000;
+
+/// The maximum number of tracked connections.
+const MAX_CONNS: usize = 4096;
+/// Connections idle for this long are expired when the table is full.
+const CONN_TIMEOUT_SECS: u64 = 300;
+
+/// The transport protocol of a [`NatRule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NatProtocol {
+    /// TCP.
+    Tcp,
+    /// UDP.
+    Udp,
+}
+
+/// A port forwarding rule: packets of `protocol` to `port` of this host are
+/// forwarded to `target`.
+///
+/// It's written as `<tcp|udp> <port> <target>`, e.g. `tcp 8080 10.0.2.2:80`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NatRule {
+    /// The transport protocol.
+    pub protocol: NatProtocol,
+    /// The forwarded port of this host.
+    pub port: u16,
+    /// The address the packets are forwarded to.
+    pub target: SocketAddrV4,
+}
+
+impl fmt::Display for NatRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let protocol = match self.protocol {
+            NatProtocol::Tcp => "tcp",
+            NatProtocol::Udp => "udp",
+        };
+        write!(f, "{} {} {}", protocol, self.port, self.target)
+    }
+}
+
+impl FromStr for NatRule {
+    type Err = AxError;
+
+    fn from_str(s: &str) -> AxResult<Self> {
+        let mut fields = s.split_whitespace();
+        let protocol = match fields.next() {
+            Some("tcp") => NatProtocol::Tcp,
+            Some("udp") => NatProtocol::Udp,
+            _ => return Err(AxError::InvalidInput),
+        };
+        let port = fields.next().and_then(|p| p.parse().ok());
+        let target = fields.next().and_then(|t| t.parse().ok());
+        match (port, target, fields.next()) {
+            (Some(port), Some(target), None) if port != 0 => Ok(Self {
+                protocol,
+                port,
+                target,
+            }),
+            _ => Err(AxError::InvalidInput),
+        }
+    }
+}
+
+/// The addresses of this host.
+struct Local {
+    mac: EthernetAddress,
+    cidr: Ipv4Cidr,
+}
+
+/// A connection from a client to a target, masqueraded to `nat_port`.
+struct Conn {
+    client: (Ipv4Address, u16),
+    port: u16,
+    target: SocketAddrV4,
+    last_seen: u64,
+}
+
+struct ConnTable {
+    /// The NAT ports by the protocol, client address and forwarded port.
+    by_client: BTreeMap<(NatProtocol, [u8; 4], u16, u16), u16>,
+    /// The connections by the protocol and NAT port.
+    by_nat: BTreeMap<(NatProtocol, u16), Conn>,
+    next_port: u16,
+}
+
+impl ConnTable {
+    const fn new() -> Self {
+        Self {
+            by_client: BTreeMap::new(),
+            by_nat: BTreeMap::new(),
+            next_port: NAT_PORT_START,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.by_client.clear();
+        self.by_nat.clear();
+    }
+
+    /// Returns the NAT port of the connection from `client` by `rule`, a new
+    /// one is allocated if not tracked yet.
+    fn nat_port(
+        &mut self,
+        protocol: NatProtocol,
+        client: (Ipv4Address, u16),
+        rule: &NatRule,
+        now: u64,
+    ) -> Option<u16> {
+        let key = (protocol, client.0 .0, client.1, rule.port);
+        if let Some(&nat_port) = self.by_client.get(&key) {
+            let conn = self.by_nat.get_mut(&(protocol, nat_port)).unwrap();
+            conn.last_seen = now;
+            return Some(nat_port);
+        }
+
+        if self.by_nat.len() >= MAX_CONNS {
+            self.by_nat
+                .retain(|_, conn| now - conn.last_seen < CONN_TIMEOUT_SECS);
+            let by_nat = &self.by_nat;
+            self.by_client
+                .retain(|&(protocol, ..), nat_port| by_nat.contains_key(&(protocol, *nat_port)));
+            if self.by_nat.len() >= MAX_CONNS {
+                return None;
+            }
+        }
+        for _ in NAT_PORT_START..NAT_PORT_END {
+            let nat_port = self.next_port;
+            self.next_port = if nat_port + 1 == NAT_PORT_END {
+                NAT_PORT_START
+            } else {
+                nat_port + 1
+            };
+            if !self.by_nat.contains_key(&(protocol, nat_port)) {
+                let conn = Conn {
+                    client,
+                    port: rule.port,
+                    target: rule.target,
+                    last_seen: now,
+                };
+                self.by_nat.insert((protocol, nat_port), conn);
+                self.by_client.insert(key, nat_port);
+                return Some(nat_port);
+            }
+        }
+        None
+    }
+}
+
+static LOCAL: RwLock<Option<Local>> = RwLock::new(None);
+static RULES: RwLock<Vec<NatRule>> = RwLock::new(Vec::new());
+static CONNS: Mutex<ConnTable> = Mutex::new(ConnTable::new());
+/// The MAC addresses of the hosts in the local network, by their IP addresses.
+static NEIGHBORS: Mutex<BTreeMap<[u8; 4], EthernetAddress>> = Mutex::new(BTreeMap::new());
+/// The MAC address of the router, from which the packets of other networks
+/// come.
+static GATEWAY_MAC: Mutex<Option<EthernetAddress>> = Mutex::new(None);
+/// The NAT hook, registered if IP forwarding is enabled.
+static HOOK: Mutex<Option<HookId>> = Mutex::new(None);
+
+/// Sets the addresses of this host, called again when they are changed.
+pub(crate) fn init(mac: EthernetAddress, ip: IpAddress, prefix_len: u8) {
+    if let IpAddress::Ipv4(addr) = ip {
+        *LOCAL.write() = Some(Local {
+            mac,
+            cidr: Ipv4Cidr::new(addr, prefix_len),
+        });
+    }
+}
+
+/// Returns whether IP forwarding, i.e., the NAT, is enabled.
+pub fn ip_forward() -> bool {
+    HOOK.lock().is_some()
+}
+
+/// Enables or disables IP forwarding. The tracked connections are dropped
+/// when disabled.
+pub fn set_ip_forward(enabled: bool) {
+    let mut hook = HOOK.lock();
+    match (enabled, *hook) {
+        (true, None) => *hook = Some(register_hook("nat", process)),
+        (false, Some(id)) => {
+            unregister_hook(id).ok();
+            *hook = None;
+            CONNS.lock().clear();
+        }
+        _ => {}
+    }
+}
+
+/// Returns the port forwarding rules.
+pub fn nat_rules() -> Vec<NatRule> {
+    RULES.read().clone()
+}
+
+/// Replaces the port forwarding rules. The tracked connections keep their
+/// targets until expired.
+pub fn set_nat_rules(rules: Vec<NatRule>) {
+    *RULES.write() = rules;
+}
+
+/// Learns the MAC address of the sender of a frame.
+fn learn(local: &Local, ip: Ipv4Address, mac: EthernetAddress) {
+    if !mac.is_unicast() || ip.is_unspecified() {
+        return;
+    }
+    if local.cidr.contains_addr(&ip) {
+        NEIGHBORS.lock().insert(ip.0, mac);
+    } else {
+        *GATEWAY_MAC.lock() = Some(mac);
+    }
+}
+
+fn neighbor_mac(local: &Local, ip: Ipv4Address) -> Option<EthernetAddress> {
+    if local.cidr.contains_addr(&ip) {
+        NEIGHBORS.lock().get(&ip.0).copied()
+    } else {
+        *GATEWAY_MAC.lock()
+    }
+}
+
+/// Returns the source and destination ports of a TCP or UDP packet.
+fn ports(protocol: NatProtocol, payload: &[u8]) -> Option<(u16, u16)> {
+    match protocol {
+        NatProtocol::Tcp => {
+            let packet = TcpPacket::new_checked(payload).ok()?;
+            Some((packet.src_port(), packet.dst_port()))
+        }
+        NatProtocol::Udp => {
+            let packet = UdpPacket::new_checked(payload).ok()?;
+            Some((packet.src_port(), packet.dst_port()))
+        }
+    }
+}
+
+/// Rewrites the ports of a TCP or UDP packet, and updates the checksum.
+fn rewrite_ports(
+    protocol: NatProtocol,
+    payload: &mut [u8],
+    src: (Ipv4Address, u16),
+    dst: (Ipv4Address, u16),
+) {
+    let (src_addr, dst_addr) = (IpAddress::Ipv4(src.0), IpAddress::Ipv4(dst.0));
+    match protocol {
+        NatProtocol::Tcp => {
+            let mut packet = TcpPacket::new_unchecked(payload);
+            packet.set_src_port(src.1);
+            packet.set_dst_port(dst.1);
+            packet.fill_checksum(&src_addr, &dst_addr);
+        }
+        NatProtocol::Udp => {
+            let mut packet = UdpPacket::new_unchecked(payload);
+            packet.set_src_port(src.1);
+            packet.set_dst_port(dst.1);
+            packet.fill_checksum(&src_addr, &dst_addr);
+        }
+    }
+}
+
+/// The NAT hook.
+fn process(frame: &mut [u8]) -> HookAction {
+    translate(frame).unwrap_or(HookAction::Pass)
+}
+
+/// Translates a frame to or from a forwarded port, returns `None` if it's
+/// not forwarded.
+fn translate(frame: &mut [u8]) -> Option<HookAction> {
+    let local = LOCAL.read();
+    let local = local.as_ref()?;
+    let mut eth = EthernetFrame::new_checked(frame).ok()?;
+    let src_mac = eth.src_addr();
+    match eth.ethertype() {
+        EthernetProtocol::Ipv4 => {}
+        EthernetProtocol::Arp => {
+            let packet = ArpPacket::new_checked(eth.payload_mut()).ok()?;
+            if let Ok(ArpRepr::EthernetIpv4 {
+                source_hardware_addr,
+                source_protocol_addr,
+                ..
+            }) = ArpRepr::parse(&packet)
+            {
+                learn(local, source_protocol_addr, source_hardware_addr);
+            }
+            return None;
+        }
+        _ => return None,
+    }
+
+    let mut ip = Ipv4Packet::new_checked(eth.payload_mut()).ok()?;
+    learn(local, ip.src_addr(), src_mac);
+    if ip.dst_addr() != local.cidr.address() || ip.more_frags() || ip.frag_offset() != 0 {
+        return None;
+    }
+    let protocol = match ip.next_header() {
+        IpProtocol::Tcp => NatProtocol::Tcp,
+        IpProtocol::Udp => NatProtocol::Udp,
+        _ => return None,
+    };
+    let (src_port, dst_port) = ports(protocol, ip.payload_mut())?;
+    let now = monotonic_time_nanos() / NANOS_PER_SEC;
+
+    let (src, dst) = {
+        let mut conns = CONNS.lock();
+        let reply = conns
+            .by_nat
+            .get_mut(&(protocol, dst_port))
+            .filter(|conn| conn.target == SocketAddrV4::new(ip.src_addr().0.into(), src_port));
+        if let Some(conn) = reply {
+            // From the target to the client.
+            conn.last_seen = now;
+            ((local.cidr.address(), conn.port), conn.client)
+        } else {
+            // From the client to the target.
+            let rule = *RULES
+                .read()
+                .iter()
+                .find(|rule| rule.protocol == protocol && rule.port == dst_port)?;
+            let Some(nat_port) = conns.nat_port(protocol, (ip.src_addr(), src_port), &rule, now)
+            else {
+                return Some(HookAction::Drop);
+            };
+            let target = (Ipv4Address(rule.target.ip().octets()), rule.target.port());
+            ((local.cidr.address(), nat_port), target)
+        }
+    };
+    let Some(dst_mac) = neighbor_mac(local, dst.0) else {
+        warn!("nat: MAC address of {} unknown", dst.0);
+        return Some(HookAction::Drop);
+    };
+
+    ip.set_src_addr(src.0);
+    ip.set_dst_addr(dst.0);
+    rewrite_ports(protocol, ip.payload_mut(), src, dst);
+    ip.fill_checksum();
+    eth.set_src_addr(local.mac);
+    eth.set_dst_addr(dst_mac);
+    Some(HookAction::Redirect)
+}
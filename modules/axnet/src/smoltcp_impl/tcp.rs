@@ -1,9 +1,10 @@
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
-use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use core::time::Duration;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
-use axhal::time::current_ticks;
+use axhal::time::{current_ticks, TimeValue};
 use axio::{PollState, Read, Write};
 use axsync::Mutex;
 
@@ -13,7 +14,8 @@ use smoltcp::socket::tcp::{self, ConnectError, State};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
-use super::{SocketSetWrapper, LISTEN_TABLE, SOCKET_SET};
+use super::listen_table::ListenerId;
+use super::{wait_for_progress, SocketSetWrapper, SocketTimeout, LISTEN_TABLE, SOCKET_SET};
 
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
@@ -27,6 +29,24 @@ const STATE_CONNECTING: u8 = 2;
 const STATE_CONNECTED: u8 = 3;
 const STATE_LISTENING: u8 = 4;
 
+/// The linger timeout when it's off, see [`TcpSocket::set_linger`].
+const NO_LINGER: u64 = u64::MAX;
+
+// The errors of the last connection attempt.
+const CONNECT_OK: u8 = 0;
+const CONNECT_REFUSED: u8 = 1;
+const CONNECT_TIMED_OUT: u8 = 2;
+
+/// Why a connection attempt of [`TcpSocket::connect`] failed, returned by
+/// [`TcpSocket::take_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectFailure {
+    /// The connection was refused or reset by the peer.
+    Refused,
+    /// The connection was not established before the connect timeout.
+    TimedOut,
+}
+
 /// A TCP socket that provides POSIX-like APIs.
 ///
 /// - [`connect`] is for TCP clients.
@@ -44,6 +64,16 @@ pub struct TcpSocket {
     peer_addr: UnsafeCell<IpEndpoint>,
     nonblock: AtomicBool,
     reuse_addr: AtomicBool,
+    reuse_port: AtomicBool,
+    listener_id: UnsafeCell<ListenerId>,
+    recv_timeout: SocketTimeout,
+    send_timeout: SocketTimeout,
+    connect_timeout: SocketTimeout,
+    connect_deadline: UnsafeCell<Option<TimeValue>>,
+    connect_error: AtomicU8,
+    read_shut: AtomicBool,
+    peer_closed: AtomicBool,
+    linger: AtomicU64, // in nanoseconds, `NO_LINGER` if off
 }
 
 unsafe impl Sync for TcpSocket {}
@@ -58,6 +88,16 @@ impl TcpSocket {
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             nonblock: AtomicBool::new(false),
             reuse_addr: AtomicBool::new(false),
+            reuse_port: AtomicBool::new(false),
+            listener_id: UnsafeCell::new(0),
+            recv_timeout: SocketTimeout::new(),
+            send_timeout: SocketTimeout::new(),
+            connect_timeout: SocketTimeout::new(),
+            connect_deadline: UnsafeCell::new(None),
+            connect_error: AtomicU8::new(CONNECT_OK),
+            read_shut: AtomicBool::new(false),
+            peer_closed: AtomicBool::new(false),
+            linger: AtomicU64::new(NO_LINGER),
         }
     }
 
@@ -74,6 +114,16 @@ impl TcpSocket {
             peer_addr: UnsafeCell::new(peer_addr),
             nonblock: AtomicBool::new(false),
             reuse_addr: AtomicBool::new(false),
+            reuse_port: AtomicBool::new(false),
+            listener_id: UnsafeCell::new(0),
+            recv_timeout: SocketTimeout::new(),
+            send_timeout: SocketTimeout::new(),
+            connect_timeout: SocketTimeout::new(),
+            connect_deadline: UnsafeCell::new(None),
+            connect_error: AtomicU8::new(CONNECT_OK),
+            read_shut: AtomicBool::new(false),
+            peer_closed: AtomicBool::new(false),
+            linger: AtomicU64::new(NO_LINGER),
         }
     }
 
@@ -137,6 +187,102 @@ impl TcpSocket {
         self.reuse_addr.store(reuse_addr, Ordering::Release);
     }
 
+    /// Returns whether this socket is in reuse port mode.
+    #[inline]
+    pub fn is_reuse_port(&self) -> bool {
+        self.reuse_port.load(Ordering::Acquire)
+    }
+
+    /// Moves this TCP socket into or out of reuse port mode.
+    ///
+    /// The `SO_REUSEPORT` option allows multiple sockets to listen on the same
+    /// port if all of them set it, and the incoming connections are balanced
+    /// across them. This option must be set before calling `bind`.
+    #[inline]
+    pub fn set_reuse_port(&self, reuse_port: bool) {
+        self.reuse_port.store(reuse_port, Ordering::Release);
+    }
+
+    /// Returns the timeout of blocking receive operations, or `None` if they
+    /// block forever.
+    #[inline]
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.recv_timeout.get()
+    }
+
+    /// Sets the timeout of blocking receive operations, after which they fail
+    /// with [`Err(WouldBlock)`](AxError::WouldBlock). `None` to block forever.
+    #[inline]
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.recv_timeout.set(timeout);
+    }
+
+    /// Returns the timeout of blocking send operations, or `None` if they
+    /// block forever.
+    #[inline]
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.send_timeout.get()
+    }
+
+    /// Sets the timeout of blocking send and connect operations, after which
+    /// they fail with [`Err(WouldBlock)`](AxError::WouldBlock). `None` to block
+    /// forever.
+    #[inline]
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) {
+        self.send_timeout.set(timeout);
+    }
+
+    /// Returns the timeout of connection attempts, or `None` if they never
+    /// time out.
+    #[inline]
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout.get()
+    }
+
+    /// Sets the timeout of connection attempts, after which the connection is
+    /// aborted and fails with [`ConnectFailure::TimedOut`], even if it's
+    /// nonblocking. `None` to never time out.
+    ///
+    /// It applies to the following calls of [`connect`](Self::connect).
+    #[inline]
+    pub fn set_connect_timeout(&self, timeout: Option<Duration>) {
+        self.connect_timeout.set(timeout);
+    }
+
+    /// Returns the linger timeout, or `None` if lingering is off.
+    #[inline]
+    pub fn linger(&self) -> Option<Duration> {
+        match self.linger.load(Ordering::Acquire) {
+            NO_LINGER => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Sets how the connection is closed when the socket is dropped, like
+    /// `SO_LINGER`.
+    ///
+    /// If `None`, the data not sent yet is sent in the background, which is
+    /// the default. If zero, the connection is reset and the data is
+    /// discarded. Otherwise, dropping blocks until the data is sent and the
+    /// peer acknowledges the close, or the timeout expires.
+    #[inline]
+    pub fn set_linger(&self, linger: Option<Duration>) {
+        let nanos = linger.map_or(NO_LINGER, |t| (t.as_nanos() as u64).min(NO_LINGER - 1));
+        self.linger.store(nanos, Ordering::Release);
+    }
+
+    /// Returns and clears the error of the last [`connect`], which is found
+    /// when the socket becomes writable.
+    ///
+    /// [`connect`]: Self::connect
+    pub fn take_error(&self) -> Option<ConnectFailure> {
+        match self.connect_error.swap(CONNECT_OK, Ordering::AcqRel) {
+            CONNECT_REFUSED => Some(ConnectFailure::Refused),
+            CONNECT_TIMED_OUT => Some(ConnectFailure::TimedOut),
+            _ => None,
+        }
+    }
+
     /// To get the address pair of the socket.
     ///
     /// Returns the local and remote endpoint pair.
@@ -165,8 +311,22 @@ impl TcpSocket {
     /// Connects to the given address and port.
     ///
     /// The local port is generated automatically.
+    ///
+    /// If the socket is nonblocking, it returns
+    /// [`Err(WouldBlock)`](AxError::WouldBlock) once the connection is
+    /// started. The socket becomes writable by [`poll`](Self::poll) when it's
+    /// established or failed, and the failure is got by
+    /// [`take_error`](Self::take_error).
+    ///
+    /// Otherwise, it also returns [`Err(WouldBlock)`](AxError::WouldBlock) if
+    /// the write timeout expires, and the connection goes on as a nonblocking
+    /// one, or if the connect timeout expires, with the failure got by
+    /// [`take_error`](Self::take_error).
     pub fn connect(&self, remote_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CONNECTING, || {
+            self.connect_error.store(CONNECT_OK, Ordering::Release);
+            self.read_shut.store(false, Ordering::Release);
+            self.peer_closed.store(false, Ordering::Release);
             // SAFETY: no other threads can read or write these fields.
             let handle = unsafe { self.handle.get().read() }
                 .unwrap_or_else(|| SOCKET_SET.add(SocketSetWrapper::new_tcp_socket()));
@@ -175,15 +335,9 @@ impl TcpSocket {
             // let (bound_endpoint, remote_endpoint) = self.get_endpoint_pair(remote_addr)?;
             let remote_endpoint = from_core_sockaddr(remote_addr);
             let bound_endpoint = self.bound_endpoint()?;
-            info!("bound endpoint: {:?}", bound_endpoint);
-            info!("remote endpoint: {:?}", remote_endpoint);
-            warn!("Temporarily net bridge used");
-            let iface = if remote_endpoint.addr.as_bytes()[0] == 127 {
-                super::LOOPBACK.try_get().unwrap()
-            } else {
-                info!("Use eth net");
-                &super::ETH0.iface
-            };
+            debug!("bound endpoint: {:?}", bound_endpoint);
+            debug!("remote endpoint: {:?}", remote_endpoint);
+            let iface = super::route(remote_endpoint.addr);
 
             let (local_endpoint, remote_endpoint) = SOCKET_SET
                 .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
@@ -208,6 +362,9 @@ impl TcpSocket {
                 self.local_addr.get().write(local_endpoint);
                 self.peer_addr.get().write(remote_endpoint);
                 self.handle.get().write(Some(handle));
+                self.connect_deadline
+                    .get()
+                    .write(self.connect_timeout.deadline());
             }
             Ok(())
         })
@@ -218,18 +375,25 @@ impl TcpSocket {
 
         // Here our state must be `CONNECTING`, and only one thread can run here.
         if self.is_nonblocking() {
-            Err(AxError::WouldBlock)
+            return Err(AxError::WouldBlock);
+        }
+        let connected = self.block_on(self.send_timeout.deadline(), || {
+            let PollState { writable, .. } = self.poll_connect()?;
+            if writable {
+                Ok(self.get_state() == STATE_CONNECTED)
+            } else {
+                Err(AxError::WouldBlock)
+            }
+        })?;
+        if connected {
+            Ok(())
+        } else if self.connect_error.load(Ordering::Acquire) == CONNECT_REFUSED {
+            // Reported here, instead of by `take_error`.
+            self.connect_error.store(CONNECT_OK, Ordering::Release);
+            ax_err!(ConnectionRefused, "socket connect() failed")
         } else {
-            self.block_on(|| {
-                let PollState { writable, .. } = self.poll_connect()?;
-                if !writable {
-                    Err(AxError::WouldBlock)
-                } else if self.get_state() == STATE_CONNECTED {
-                    Ok(())
-                } else {
-                    ax_err!(ConnectionRefused, "socket connect() failed")
-                }
-            })
+            // There is no `AxError` for timeouts, so it's got by `take_error`.
+            Err(AxError::WouldBlock)
         }
     }
 
@@ -262,7 +426,7 @@ impl TcpSocket {
                 socket.set_bound_endpoint(bound_endpoint);
             });
 
-            if !self.is_reuse_addr() {
+            if !self.is_reuse_addr() && !self.is_reuse_port() {
                 SOCKET_SET.bind_check(local_endpoint.addr, local_endpoint.port)?;
             }
             Ok(())
@@ -280,7 +444,8 @@ impl TcpSocket {
             unsafe {
                 (*self.local_addr.get()).port = bound_endpoint.port;
             }
-            LISTEN_TABLE.listen(bound_endpoint)?;
+            let id = LISTEN_TABLE.listen(bound_endpoint, self.is_reuse_port())?;
+            unsafe { self.listener_id.get().write(id) };
             debug!("TCP socket listening on {}", bound_endpoint);
             Ok(())
         })
@@ -298,10 +463,12 @@ impl TcpSocket {
             return ax_err!(InvalidInput, "socket accept() failed: not listen");
         }
 
-        // SAFETY: `self.local_addr` should be initialized after `bind()`.
+        // SAFETY: `self.local_addr` and `self.listener_id` should be initialized
+        // after `listen()`.
         let local_port = unsafe { self.local_addr.get().read().port };
-        self.block_on(|| {
-            let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
+        let id = unsafe { self.listener_id.get().read() };
+        self.block_on(self.recv_timeout.deadline(), || {
+            let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port, id)?;
             debug!("TCP socket accepted a new connection {}", peer_addr);
             Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
         })
@@ -329,8 +496,9 @@ impl TcpSocket {
             // SAFETY: `self.local_addr` should be initialized in a listening socket,
             // and no other threads can read or write it.
             let local_port = unsafe { self.local_addr.get().read().port };
+            let id = unsafe { self.listener_id.get().read() };
             unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
-            LISTEN_TABLE.unlisten(local_port);
+            LISTEN_TABLE.unlisten(local_port, id);
             SOCKET_SET.poll_interfaces();
             Ok(())
         })
@@ -340,8 +508,20 @@ impl TcpSocket {
         Ok(())
     }
 
+    /// Close the receive half of the tcp socket, the following receive
+    /// operations return the data already received, then `Ok(0)` without
+    /// blocking.
+    ///
+    /// This function is for shutdown(fd, SHUT_RD) syscall.
+    ///
+    /// It won't change TCP state, the peer is not notified.
+    pub fn shutdown_read(&self) {
+        self.read_shut.store(true, Ordering::Release);
+    }
+
     /// Close the transmit half of the tcp socket.
-    /// It will call `close()` on smoltcp::socket::tcp::Socket. It should send FIN to remote half.
+    /// It will call `close()` on smoltcp::socket::tcp::Socket. It should send FIN to remote half
+    /// after the data queued before.
     ///
     /// This function is for shutdown(fd, SHUT_WR) syscall.
     ///
@@ -358,36 +538,15 @@ impl TcpSocket {
 
     /// Receives data from the socket, stores it in the given buffer.
     pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
-        if self.is_connecting() {
-            return Err(AxError::WouldBlock);
-        } else if !self.is_connected() {
-            return ax_err!(NotConnected, "socket recv() failed");
-        }
+        self.recv_impl(buf, false)
+    }
 
-        // SAFETY: `self.handle` should be initialized in a connected socket.
-        let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                if socket.recv_queue() > 0 {
-                    // data available
-                    // TODO: use socket.recv(|buf| {...})
-                    let len = socket
-                        .recv_slice(buf)
-                        .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
-                    Ok(len)
-                } else if !socket.is_active() {
-                    // not open
-                    ax_err!(ConnectionRefused, "socket recv() failed")
-                } else if !socket.may_recv() {
-                    // connection closed
-                    Ok(0)
-                } else {
-                    // no more data
-                    Err(AxError::WouldBlock)
-                }
-            })
-        })
+    /// Receives data from the socket, stores it in the given buffer, without
+    /// removing it from the receive queue.
+    pub fn peek(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.recv_impl(buf, true)
     }
+
     /// Receives data from the socket, stores it in the given buffer.
     ///
     /// It will return [`Err(Timeout)`](AxError::Timeout) if expired.
@@ -402,7 +561,7 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        self.block_on(None, || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if socket.recv_queue() > 0 {
                     // data available
@@ -440,7 +599,7 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
+        self.block_on(self.send_timeout.deadline(), || {
             SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() || !socket.may_send() {
                     // closed by remote
@@ -536,6 +695,44 @@ impl TcpSocket {
 
 /// Private methods
 impl TcpSocket {
+    fn recv_impl(&self, buf: &mut [u8], peek: bool) -> AxResult<usize> {
+        if self.is_connecting() {
+            return Err(AxError::WouldBlock);
+        } else if !self.is_connected() {
+            return ax_err!(NotConnected, "socket recv() failed");
+        }
+
+        // SAFETY: `self.handle` should be initialized in a connected socket.
+        let handle = unsafe { self.handle.get().read().unwrap() };
+        self.block_on(self.recv_timeout.deadline(), || {
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                if socket.recv_queue() > 0 {
+                    // data available
+                    // TODO: use socket.recv(|buf| {...})
+                    let res = if peek {
+                        socket.peek_slice(buf)
+                    } else {
+                        socket.recv_slice(buf)
+                    };
+                    let len = res.map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
+                    Ok(len)
+                } else if self.read_shut.load(Ordering::Acquire) {
+                    // receive half closed by us
+                    Ok(0)
+                } else if self.is_peer_closed(socket) {
+                    // FIN received, end of stream
+                    Ok(0)
+                } else if !socket.is_active() {
+                    // reset by the peer
+                    ax_err!(ConnectionReset, "socket recv() failed")
+                } else {
+                    // no more data
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
     #[inline]
     fn get_state(&self) -> u8 {
         self.state.load(Ordering::Acquire)
@@ -576,7 +773,11 @@ impl TcpSocket {
     }
 
     #[inline]
-    fn is_connecting(&self) -> bool {
+    /// Whether the socket is connecting, i.e., a nonblocking [`connect`] is
+    /// in progress.
+    ///
+    /// [`connect`]: Self::connect
+    pub fn is_connecting(&self) -> bool {
         self.get_state() == STATE_CONNECTING
     }
 
@@ -618,8 +819,18 @@ impl TcpSocket {
         // SAFETY: `self.handle` should be initialized above.
         let handle = unsafe { self.handle.get().read().unwrap() };
         let writable =
-            SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| match socket.state() {
-                State::SynSent => false, // wait for connection
+            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| match socket.state() {
+                State::SynSent => {
+                    // SAFETY: written before the state is changed to `CONNECTING`.
+                    let deadline = unsafe { self.connect_deadline.get().read() };
+                    if deadline.is_some_and(|d| axhal::time::monotonic_time() >= d) {
+                        socket.abort();
+                        self.connect_failed(CONNECT_TIMED_OUT);
+                        true
+                    } else {
+                        false // wait for connection
+                    }
+                }
                 State::Established => {
                     self.set_state(STATE_CONNECTED); // connected
                     debug!(
@@ -630,11 +841,7 @@ impl TcpSocket {
                     true
                 }
                 _ => {
-                    unsafe {
-                        self.local_addr.get().write(UNSPECIFIED_ENDPOINT);
-                        self.peer_addr.get().write(UNSPECIFIED_ENDPOINT);
-                    }
-                    self.set_state(STATE_CLOSED); // connection failed
+                    self.connect_failed(CONNECT_REFUSED);
                     true
                 }
             });
@@ -644,22 +851,49 @@ impl TcpSocket {
         })
     }
 
+    fn connect_failed(&self, error: u8) {
+        unsafe {
+            self.local_addr.get().write(UNSPECIFIED_ENDPOINT);
+            self.peer_addr.get().write(UNSPECIFIED_ENDPOINT);
+        }
+        self.connect_error.store(error, Ordering::Release);
+        self.set_state(STATE_CLOSED); // connection failed
+    }
+
     fn poll_stream(&self) -> AxResult<PollState> {
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
         SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
             Ok(PollState {
-                readable: !socket.may_recv() || socket.can_recv(),
+                readable: !socket.may_recv()
+                    || socket.can_recv()
+                    || self.read_shut.load(Ordering::Acquire),
                 writable: !socket.may_send() || socket.can_send(),
             })
         })
     }
 
+    /// Whether the peer has closed its transmit half, i.e., a FIN is received.
+    ///
+    /// It's remembered, as the socket is closed without telling it from a
+    /// reset once both halves are closed.
+    fn is_peer_closed(&self, socket: &tcp::Socket) -> bool {
+        let closed = matches!(
+            socket.state(),
+            State::CloseWait | State::LastAck | State::Closing | State::TimeWait
+        );
+        if closed {
+            self.peer_closed.store(true, Ordering::Release);
+        }
+        closed || self.peer_closed.load(Ordering::Acquire)
+    }
+
     fn poll_listener(&self) -> AxResult<PollState> {
         // SAFETY: `self.local_addr` should be initialized in a listening socket.
         let local_addr = unsafe { self.local_addr.get().read() };
+        let id = unsafe { self.listener_id.get().read() };
         Ok(PollState {
-            readable: LISTEN_TABLE.can_accept(local_addr.port)?,
+            readable: LISTEN_TABLE.can_accept(local_addr.port, id)?,
             writable: false,
         })
     }
@@ -668,8 +902,9 @@ impl TcpSocket {
     ///
     /// If the socket is non-blocking, it calls the function once and returns
     /// immediately. Otherwise, it may call the function multiple times if it
-    /// returns [`Err(WouldBlock)`](AxError::WouldBlock).
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    /// returns [`Err(WouldBlock)`](AxError::WouldBlock), until the `deadline`
+    /// if given.
+    fn block_on<F, T>(&self, deadline: Option<TimeValue>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
@@ -680,7 +915,12 @@ impl TcpSocket {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if deadline.is_some_and(|d| axhal::time::monotonic_time() >= d) {
+                            return Err(AxError::WouldBlock);
+                        }
+                        wait_for_progress(deadline)
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -706,14 +946,40 @@ impl Write for TcpSocket {
 
 impl Drop for TcpSocket {
     fn drop(&mut self) {
-        self.shutdown().ok();
         // Safe because we have mut reference to `self`.
-        if let Some(handle) = unsafe { self.handle.get().read() } {
-            SOCKET_SET.remove(handle);
+        let handle = unsafe { self.handle.get().read() };
+        let linger = self.linger();
+        if let (Some(handle), Some(Duration::ZERO)) = (handle, linger) {
+            if self.is_connected() {
+                debug!("TCP socket {}: aborting", handle);
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| socket.abort());
+            }
         }
+        self.shutdown().ok();
+        let Some(handle) = handle else {
+            return;
+        };
+        if let Some(timeout) = linger.filter(|t| !t.is_zero()) {
+            let deadline = axhal::time::monotonic_time() + timeout;
+            while !SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, is_closed)
+                && axhal::time::monotonic_time() < deadline
+            {
+                SOCKET_SET.poll_interfaces();
+                wait_for_progress(Some(deadline));
+            }
+        }
+        // The data queued before is still sent, and the socket is removed
+        // once the close is done.
+        SOCKET_SET.remove_when_closed(handle);
     }
 }
 
+/// Whether both halves of the connection are closed, or the socket is never
+/// connected.
+pub(super) fn is_closed(socket: &tcp::Socket) -> bool {
+    matches!(socket.state(), State::Closed | State::TimeWait)
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
@@ -1,8 +1,9 @@
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
-use axhal::time::current_ticks;
+use axhal::time::{current_ticks, TimeValue};
 use axio::{PollState, Read, Write};
 use axsync::Mutex;
 use spin::RwLock;
@@ -12,7 +13,7 @@ use smoltcp::socket::udp::{self, BindError, SendError};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
-use super::{SocketSetWrapper, SOCKET_SET};
+use super::{wait_for_progress, SocketSetWrapper, SocketTimeout, SOCKET_SET};
 
 /// A UDP socket that provides POSIX-like APIs.
 pub struct UdpSocket {
@@ -21,6 +22,8 @@ pub struct UdpSocket {
     peer_addr: RwLock<Option<IpEndpoint>>,
     nonblock: AtomicBool,
     reuse_addr: AtomicBool,
+    recv_timeout: SocketTimeout,
+    send_timeout: SocketTimeout,
 }
 
 impl UdpSocket {
@@ -35,6 +38,8 @@ impl UdpSocket {
             peer_addr: RwLock::new(None),
             nonblock: AtomicBool::new(false),
             reuse_addr: AtomicBool::new(false),
+            recv_timeout: SocketTimeout::new(),
+            send_timeout: SocketTimeout::new(),
         }
     }
 
@@ -97,6 +102,34 @@ impl UdpSocket {
         self.reuse_addr.store(reuse_addr, Ordering::Release);
     }
 
+    /// Returns the timeout of blocking receive operations, or `None` if they
+    /// block forever.
+    #[inline]
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.recv_timeout.get()
+    }
+
+    /// Sets the timeout of blocking receive operations, after which they fail
+    /// with [`Err(WouldBlock)`](AxError::WouldBlock). `None` to block forever.
+    #[inline]
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.recv_timeout.set(timeout);
+    }
+
+    /// Returns the timeout of blocking send operations, or `None` if they
+    /// block forever.
+    #[inline]
+    pub fn write_timeout(&self) -> Option<Duration> {
+        self.send_timeout.get()
+    }
+
+    /// Sets the timeout of blocking send operations, after which they fail
+    /// with [`Err(WouldBlock)`](AxError::WouldBlock). `None` to block forever.
+    #[inline]
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) {
+        self.send_timeout.set(timeout);
+    }
+
     /// Binds an unbound socket to the given address and port.
     ///
     /// It's must be called before [`send_to`](Self::send_to) and
@@ -263,7 +296,7 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
         // info!("send to addr: {:?}", remote_endpoint);
-        self.block_on(|| {
+        self.block_on(self.send_timeout.deadline(), || {
             SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
                 if !socket.is_open() {
                     // not connected
@@ -294,7 +327,7 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
+        self.block_on(self.recv_timeout.deadline(), || {
             SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
                 if !socket.is_open() {
                     // not bound
@@ -310,7 +343,7 @@ impl UdpSocket {
         })
     }
 
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    fn block_on<F, T>(&self, deadline: Option<TimeValue>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
@@ -321,7 +354,12 @@ impl UdpSocket {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if deadline.is_some_and(|d| axhal::time::monotonic_time() >= d) {
+                            return Err(AxError::WouldBlock);
+                        }
+                        wait_for_progress(deadline)
+                    }
                     Err(e) => return Err(e),
                 }
             }
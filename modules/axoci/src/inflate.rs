@@ -0,0 +1,558 @@
+//! A streaming gzip decoder ([RFC 1952]) of the compressed layers.
+//!
+//! The DEFLATE blocks ([RFC 1951]) are decoded on demand as the output is
+//! read, keeping only the last 32 KiB of it for the back references, so the
+//! layers are unpacked without being decompressed to memory first. The
+//! Huffman codes are decoded bit by bit as in zlib's `puff`.
+//!
+//! [RFC 1952]: https://www.rfc-editor.org/rfc/rfc1952
+//! [RFC 1951]: https://www.rfc-editor.org/rfc/rfc1951
+
+use alloc::{vec, vec::Vec};
+
+use axerrno::{AxError, ax_err};
+use axio::{Read, Result};
+
+/// The size of the window of the back references.
+const WINDOW_SIZE: usize = 0x8000;
+const MAX_BITS: usize = 15;
+/// The numbers of the literal/length and the distance codes.
+const MAX_LCODES: usize = 286;
+const MAX_DCODES: usize = 30;
+const FIXED_LCODES: usize = 288;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order of the code lengths of the code length code.
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const FTEXT: u8 = 0x01;
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            k += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// A canonical Huffman code, by the number of the codes of each length and
+/// the symbols ordered by their codes.
+struct Huffman {
+    count: [u16; MAX_BITS + 1],
+    symbol: [u16; FIXED_LCODES],
+}
+
+impl Huffman {
+    const fn empty() -> Self {
+        Self {
+            count: [0; MAX_BITS + 1],
+            symbol: [0; FIXED_LCODES],
+        }
+    }
+
+    /// Builds the code of the symbols with the code lengths `lengths`.
+    ///
+    /// The incomplete codes are accepted, as the encoders emit them when
+    /// there is only one distance code.
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut h = Self::empty();
+        for &len in lengths {
+            h.count[len as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for len in 1..=MAX_BITS {
+            left = (left << 1) - h.count[len] as i32;
+            if left < 0 {
+                return ax_err!(InvalidData, "over-subscribed Huffman code");
+            }
+        }
+        let mut offs = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offs[len + 1] = offs[len] + h.count[len];
+        }
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                h.symbol[offs[len as usize] as usize] = sym as u16;
+                offs[len as usize] += 1;
+            }
+        }
+        Ok(h)
+    }
+}
+
+/// The bits of the compressed stream, from the least significant one of each
+/// byte.
+struct Bits<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    bit_buf: u32,
+    bit_cnt: u32,
+}
+
+impl<R: Read> Bits<R> {
+    fn fill(&mut self) -> Result<bool> {
+        if self.pos == self.len {
+            self.len = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(self.pos < self.len)
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        while self.bit_cnt < n {
+            if !self.fill()? {
+                return Err(AxError::UnexpectedEof);
+            }
+            self.bit_buf |= (self.buf[self.pos] as u32) << self.bit_cnt;
+            self.pos += 1;
+            self.bit_cnt += 8;
+        }
+        let val = self.bit_buf & ((1 << n) - 1);
+        self.bit_buf >>= n;
+        self.bit_cnt -= n;
+        Ok(val)
+    }
+
+    /// Drops the bits up to the next byte boundary.
+    fn align(&mut self) {
+        self.bit_buf >>= self.bit_cnt % 8;
+        self.bit_cnt -= self.bit_cnt % 8;
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        self.bits(8).map(|b| b as u8)
+    }
+
+    fn u16_le(&mut self) -> Result<u16> {
+        self.bits(16).map(|b| b as u16)
+    }
+
+    fn u32_le(&mut self) -> Result<u32> {
+        Ok(self.bits(16)? | (self.bits(16)? << 16))
+    }
+
+    /// Returns whether the stream ends at the current byte boundary.
+    fn at_end(&mut self) -> Result<bool> {
+        Ok(self.bit_cnt == 0 && !self.fill()?)
+    }
+
+    fn decode(&mut self, h: &Huffman) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= self.bits(1)? as i32;
+            let count = h.count[len] as i32;
+            if code - count < first {
+                return Ok(h.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        ax_err!(InvalidData, "invalid Huffman code")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the header of a gzip member.
+    Header,
+    /// Before the header of a DEFLATE block.
+    Block,
+    /// In a stored block, with the number of the bytes left.
+    Stored(u16),
+    /// In a block of Huffman codes.
+    Codes,
+    /// After the last block, before the trailer of the member.
+    Trailer,
+    Done,
+}
+
+/// A reader of the data decompressed from a gzip stream.
+///
+/// The members concatenated are read as one stream, and the CRC and the
+/// size in the trailer of each member are checked.
+pub struct GzDecoder<R> {
+    bits: Bits<R>,
+    state: State,
+    last_block: bool,
+    lcode: Huffman,
+    dcode: Huffman,
+    window: Vec<u8>,
+    /// The position of the next byte in the window.
+    wpos: usize,
+    /// The number of the bytes output by the member, up to the window size.
+    filled: usize,
+    /// The back reference being copied, by its length and distance.
+    copy_len: usize,
+    copy_dist: usize,
+    crc: u32,
+    size: u32,
+}
+
+impl<R: Read> GzDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            bits: Bits {
+                inner,
+                buf: vec![0; 0x2000],
+                pos: 0,
+                len: 0,
+                bit_buf: 0,
+                bit_cnt: 0,
+            },
+            state: State::Header,
+            last_block: false,
+            lcode: Huffman::empty(),
+            dcode: Huffman::empty(),
+            window: vec![0; WINDOW_SIZE],
+            wpos: 0,
+            filled: 0,
+            copy_len: 0,
+            copy_dist: 0,
+            crc: !0,
+            size: 0,
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.bits.inner
+    }
+
+    fn put(&mut self, byte: u8) {
+        self.window[self.wpos] = byte;
+        self.wpos = (self.wpos + 1) % WINDOW_SIZE;
+        self.filled = (self.filled + 1).min(WINDOW_SIZE);
+        self.crc = CRC32_TABLE[((self.crc ^ byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+        self.size = self.size.wrapping_add(1);
+    }
+
+    fn read_header(&mut self) -> Result {
+        let bits = &mut self.bits;
+        if bits.byte()? != 0x1f || bits.byte()? != 0x8b {
+            return ax_err!(InvalidData, "not a gzip stream");
+        }
+        if bits.byte()? != 8 {
+            return ax_err!(InvalidData, "unknown gzip compression method");
+        }
+        let flags = bits.byte()?;
+        if flags & !(FTEXT | FHCRC | FEXTRA | FNAME | FCOMMENT) != 0 {
+            return ax_err!(InvalidData, "reserved gzip flags set");
+        }
+        // MTIME, XFL and OS
+        for _ in 0..6 {
+            bits.byte()?;
+        }
+        if flags & FEXTRA != 0 {
+            for _ in 0..bits.u16_le()? {
+                bits.byte()?;
+            }
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                while bits.byte()? != 0 {}
+            }
+        }
+        if flags & FHCRC != 0 {
+            bits.u16_le()?;
+        }
+        self.last_block = false;
+        self.filled = 0;
+        self.crc = !0;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn read_trailer(&mut self) -> Result {
+        self.bits.align();
+        let crc = self.bits.u32_le()?;
+        let size = self.bits.u32_le()?;
+        if crc != !self.crc || size != self.size {
+            return ax_err!(InvalidData, "gzip checksum mismatch");
+        }
+        Ok(())
+    }
+
+    fn read_block_header(&mut self) -> Result<State> {
+        self.last_block = self.bits.bits(1)? == 1;
+        match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.u16_le()?;
+                if self.bits.u16_le()? != !len {
+                    return ax_err!(InvalidData, "invalid stored block length");
+                }
+                Ok(State::Stored(len))
+            }
+            1 => {
+                let mut lengths = [0u8; FIXED_LCODES + MAX_DCODES];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..FIXED_LCODES].fill(8);
+                lengths[FIXED_LCODES..].fill(5);
+                self.lcode = Huffman::new(&lengths[..FIXED_LCODES])?;
+                self.dcode = Huffman::new(&lengths[FIXED_LCODES..])?;
+                Ok(State::Codes)
+            }
+            2 => {
+                self.read_dynamic_codes()?;
+                Ok(State::Codes)
+            }
+            _ => ax_err!(InvalidData, "invalid block type"),
+        }
+    }
+
+    fn read_dynamic_codes(&mut self) -> Result {
+        let bits = &mut self.bits;
+        let nlen = bits.bits(5)? as usize + 257;
+        let ndist = bits.bits(5)? as usize + 1;
+        let ncode = bits.bits(4)? as usize + 4;
+        if nlen > MAX_LCODES || ndist > MAX_DCODES {
+            return ax_err!(InvalidData, "too many length or distance codes");
+        }
+
+        let mut lengths = [0u8; MAX_LCODES + MAX_DCODES];
+        for &i in &CLEN_ORDER[..ncode] {
+            lengths[i] = bits.bits(3)? as u8;
+        }
+        let clcode = Huffman::new(&lengths[..19])?;
+
+        let mut i = 0;
+        while i < nlen + ndist {
+            let sym = bits.decode(&clcode)?;
+            let (len, repeat) = match sym {
+                0..=15 => (sym as u8, 1),
+                16 => {
+                    if i == 0 {
+                        return ax_err!(InvalidData, "repeat with no first length");
+                    }
+                    (lengths[i - 1], 3 + bits.bits(2)? as usize)
+                }
+                17 => (0, 3 + bits.bits(3)? as usize),
+                _ => (0, 11 + bits.bits(7)? as usize),
+            };
+            if i + repeat > nlen + ndist {
+                return ax_err!(InvalidData, "too many code lengths");
+            }
+            lengths[i..i + repeat].fill(len);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return ax_err!(InvalidData, "no end-of-block code");
+        }
+        self.lcode = Huffman::new(&lengths[..nlen])?;
+        self.dcode = Huffman::new(&lengths[nlen..nlen + ndist])?;
+        Ok(())
+    }
+
+    /// Decodes a symbol of a block, returning the literal byte, or none if
+    /// it starts a back reference or ends the block.
+    fn decode_symbol(&mut self) -> Result<Option<u8>> {
+        let sym = self.bits.decode(&self.lcode)? as usize;
+        if sym < 256 {
+            return Ok(Some(sym as u8));
+        }
+        if sym == 256 {
+            self.state = State::Block;
+            return Ok(None);
+        }
+        let sym = sym - 257;
+        if sym >= LENGTH_BASE.len() {
+            return ax_err!(InvalidData, "invalid length symbol");
+        }
+        let len = LENGTH_BASE[sym] as usize + self.bits.bits(LENGTH_EXTRA[sym] as u32)? as usize;
+        let sym = self.bits.decode(&self.dcode)? as usize;
+        if sym >= DIST_BASE.len() {
+            return ax_err!(InvalidData, "invalid distance symbol");
+        }
+        let dist = DIST_BASE[sym] as usize + self.bits.bits(DIST_EXTRA[sym] as u32)? as usize;
+        if dist > self.filled {
+            return ax_err!(InvalidData, "distance too far back");
+        }
+        self.copy_len = len;
+        self.copy_dist = dist;
+        Ok(None)
+    }
+}
+
+impl<R: Read> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            if self.copy_len > 0 {
+                let byte = self.window[(self.wpos + WINDOW_SIZE - self.copy_dist) % WINDOW_SIZE];
+                self.put(byte);
+                buf[n] = byte;
+                n += 1;
+                self.copy_len -= 1;
+                continue;
+            }
+            match self.state {
+                State::Header => {
+                    self.read_header()?;
+                    self.state = State::Block;
+                }
+                State::Block if self.last_block => self.state = State::Trailer,
+                State::Block => self.state = self.read_block_header()?,
+                State::Stored(0) => self.state = State::Block,
+                State::Stored(left) => {
+                    let byte = self.bits.byte()?;
+                    self.put(byte);
+                    buf[n] = byte;
+                    n += 1;
+                    self.state = State::Stored(left - 1);
+                }
+                State::Codes => {
+                    if let Some(byte) = self.decode_symbol()? {
+                        self.put(byte);
+                        buf[n] = byte;
+                        n += 1;
+                    }
+                }
+                State::Trailer => {
+                    self.read_trailer()?;
+                    self.state = if self.bits.at_end()? {
+                        State::Done
+                    } else {
+                        State::Header
+                    };
+                }
+                State::Done => break,
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Rust is cool!\n` in a stored block, as `gzip -0`.
+    const STORED: [u8; 37] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x0e, 0x00, 0xf1, 0xff,
+        0x52, 0x75, 0x73, 0x74, 0x20, 0x69, 0x73, 0x20, 0x63, 0x6f, 0x6f, 0x6c, 0x21, 0x0a, 0x51,
+        0xe4, 0x15, 0x24, 0x0e, 0x00, 0x00, 0x00,
+    ];
+
+    /// `Rust is cool! Rust is cool!\n` in a block of the fixed codes.
+    const FIXED: [u8; 38] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x0b, 0x2a, 0x2d, 0x2e, 0x51,
+        0xc8, 0x2c, 0x56, 0x48, 0xce, 0xcf, 0xcf, 0x51, 0x54, 0x08, 0x42, 0xe6, 0x71, 0x01, 0x00,
+        0x54, 0x7e, 0x05, 0xbd, 0x1c, 0x00, 0x00, 0x00,
+    ];
+
+    /// `Rust is cool!\n` 5000 times in a block of the dynamic codes, whose back
+    /// references go around the window.
+    const DYNAMIC: [u8; 189] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xed, 0xc7, 0x31, 0x0d, 0x00,
+        0x20, 0x0c, 0x00, 0xb0, 0x1f, 0x15, 0x60, 0x69, 0x16, 0xb8, 0x48, 0x48, 0x76, 0x0c, 0xfc,
+        0x63, 0x82, 0xb3, 0xfd, 0x1a, 0xb7, 0x4e, 0x5f, 0xd5, 0x67, 0xe6, 0x1e, 0x2d, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc,
+        0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xec, 0xc3,
+        0x1e, 0xb9, 0x03, 0xd5, 0x47, 0x70, 0x11, 0x01, 0x00,
+    ];
+
+    /// Decompresses `data`, reading at most `chunk` bytes at a time.
+    fn decompress(data: &[u8], chunk: usize) -> Result<Vec<u8>> {
+        let mut decoder = GzDecoder::new(data);
+        let mut out = Vec::new();
+        let mut buf = vec![0; chunk];
+        loop {
+            let n = decoder.read(&mut buf)?;
+            if n == 0 {
+                return Ok(out);
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        for chunk in [1, 7, 0x1000] {
+            assert_eq!(decompress(&STORED, chunk).unwrap(), b"Rust is cool!\n");
+            assert_eq!(
+                decompress(&FIXED, chunk).unwrap(),
+                b"Rust is cool! Rust is cool!\n"
+            );
+            assert_eq!(
+                decompress(&DYNAMIC, chunk).unwrap(),
+                b"Rust is cool!\n".repeat(5000)
+            );
+        }
+    }
+
+    #[test]
+    fn test_members() {
+        // the file name in the header of the second member is skipped
+        let mut data = STORED.to_vec();
+        data.extend_from_slice(&FIXED[..3]);
+        data.push(FIXED[3] | FNAME);
+        data.extend_from_slice(&FIXED[4..10]);
+        data.extend_from_slice(b"layer.tar\0");
+        data.extend_from_slice(&FIXED[10..]);
+        assert_eq!(
+            decompress(&data, 0x1000).unwrap(),
+            b"Rust is cool!\nRust is cool! Rust is cool!\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            decompress(b"Rust is cool!\n", 16).err(),
+            Some(AxError::InvalidData)
+        );
+        let mut data = FIXED.to_vec();
+        let crc = data.len() - 8;
+        data[crc] ^= 1;
+        assert_eq!(decompress(&data, 16).err(), Some(AxError::InvalidData));
+        assert_eq!(
+            decompress(&DYNAMIC[..100], 16).err(),
+            Some(AxError::UnexpectedEof)
+        );
+        assert_eq!(decompress(&[], 16).err(), Some(AxError::UnexpectedEof));
+    }
+}
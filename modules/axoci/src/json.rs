@@ -0,0 +1,218 @@
+//! A minimal JSON parser for the manifests, the indexes and the configs.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use axerrno::{AxError, AxResult, ax_err};
+
+/// The maximum depth of the nested arrays and objects.
+const MAX_DEPTH: usize = 64;
+
+/// A JSON value.
+#[derive(Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// The numbers are only used as the sizes, so they are kept as integers.
+    Number(i64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Returns the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of an array, or none if it's not an array.
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(elems) => elems,
+            _ => &[],
+        }
+    }
+}
+
+/// Parses a JSON document.
+pub fn parse(text: &[u8]) -> AxResult<Value> {
+    let mut parser = Parser { text, pos: 0 };
+    let value = parser.value(0)?;
+    parser.skip_ws();
+    if parser.pos != text.len() {
+        return ax_err!(InvalidData, "trailing characters in JSON");
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> AxResult<u8> {
+        let c = self.peek().ok_or(AxError::InvalidData)?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, lit: &[u8]) -> AxResult {
+        if !self.text[self.pos..].starts_with(lit) {
+            return ax_err!(InvalidData, "unexpected token in JSON");
+        }
+        self.pos += lit.len();
+        Ok(())
+    }
+
+    fn value(&mut self, depth: usize) -> AxResult<Value> {
+        if depth > MAX_DEPTH {
+            return ax_err!(InvalidData, "JSON nested too deeply");
+        }
+        self.skip_ws();
+        match self.peek() {
+            Some(b'n') => self.expect(b"null").map(|_| Value::Null),
+            Some(b't') => self.expect(b"true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect(b"false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut elems = Vec::new();
+                self.skip_ws();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(elems));
+                }
+                loop {
+                    elems.push(self.value(depth + 1)?);
+                    self.skip_ws();
+                    match self.next()? {
+                        b',' => continue,
+                        b']' => return Ok(Value::Array(elems)),
+                        _ => return ax_err!(InvalidData, "expected ',' or ']' in JSON"),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = BTreeMap::new();
+                self.skip_ws();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(members));
+                }
+                loop {
+                    self.skip_ws();
+                    if self.peek() != Some(b'"') {
+                        return ax_err!(InvalidData, "expected a key in JSON");
+                    }
+                    let key = self.string()?;
+                    self.skip_ws();
+                    self.expect(b":")?;
+                    let value = self.value(depth + 1)?;
+                    members.insert(key, value);
+                    self.skip_ws();
+                    match self.next()? {
+                        b',' => continue,
+                        b'}' => return Ok(Value::Object(members)),
+                        _ => return ax_err!(InvalidData, "expected ',' or '}' in JSON"),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => ax_err!(InvalidData, "unexpected token in JSON"),
+        }
+    }
+
+    fn number(&mut self) -> AxResult<Value> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        let text = core::str::from_utf8(&self.text[start..self.pos]).unwrap();
+        // the fractions are not used by the images, and are truncated
+        let int = text.split(['.', 'e', 'E']).next().unwrap_or("");
+        match int.parse() {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => ax_err!(InvalidData, "invalid number in JSON"),
+        }
+    }
+
+    fn hex4(&mut self) -> AxResult<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = (self.next()? as char)
+                .to_digit(16)
+                .ok_or(AxError::InvalidData)?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn string(&mut self) -> AxResult<String> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\x08',
+                        b'f' => '\x0c',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // a surrogate pair
+                            if (0xd800..0xdc00).contains(&code) {
+                                self.expect(b"\\u")?;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return ax_err!(InvalidData, "invalid surrogate in JSON");
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).ok_or(AxError::InvalidData)?
+                        }
+                        _ => return ax_err!(InvalidData, "invalid escape in JSON"),
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                c => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| AxError::InvalidData)
+    }
+}
@@ -0,0 +1,501 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) OCI image layer store.
+//!
+//! It unpacks the container images in the [OCI image layout], e.g. written by
+//! `skopeo copy docker://alpine oci:alpine:latest` or `docker buildx build
+//! --output type=oci`, so they can be used as the root filesystem of the
+//! applications of the user-space ABI. The layout is read from any mounted
+//! filesystem, e.g. a disk, a shared folder of the host, an NFS export or the
+//! files served by HTTP, where the blobs are addressed by their digests.
+//!
+//! The manifest is selected by its tag in `index.json`, and the one for the
+//! current architecture is chosen from the image indexes. The layers, in
+//! tar or tar.gz, are applied in order to the target directory, so the upper
+//! ones override the lower ones. There is no overlay filesystem, so they are
+//! flattened as `umoci unpack` does: the whiteouts `.wh.<name>` remove the
+//! files of the lower layers, and the opaque whiteouts `.wh..wh..opq` remove
+//! the whole contents of the directories in them.
+//!
+//! The SHA-256 digests of the blobs, and the `diff_ids` of the uncompressed
+//! layers in the config, are checked as the layers are unpacked. The layers
+//! are not held in memory, but the target is left partially unpacked if the
+//! check fails, so the error should be fatal.
+//!
+//! The permissions and the hard links of the files are kept if the target is
+//! in tmpfs, which is enabled by this module. The owners, the timestamps,
+//! the devices and the FIFOs are not unpacked.
+//!
+//! [OCI image layout]: https://github.com/opencontainers/image-spec/blob/main/image-layout.md
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod inflate;
+mod json;
+mod sha256;
+mod tar;
+
+use alloc::{collections::BTreeSet, format, string::String, vec, vec::Vec};
+
+use axerrno::{AxError, AxResult, ax_err};
+use axfs::api::{self as fs, File, Permissions};
+use axio::{Read, Write};
+
+use self::inflate::GzDecoder;
+use self::json::Value;
+use self::sha256::Sha256;
+use self::tar::{Archive, EntryKind};
+
+/// The largest manifest, index or config read.
+const MAX_JSON_SIZE: u64 = 0x40_0000;
+
+/// The annotation of the tags in `index.json`.
+const REF_NAME: &str = "org.opencontainers.image.ref.name";
+
+/// The prefix of the whiteout files.
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// The opaque whiteout, which hides the lower contents of its directory.
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// The name of the current architecture in the platforms of the images.
+const ARCH: &str = if cfg!(target_arch = "x86_64") {
+    "amd64"
+} else if cfg!(target_arch = "aarch64") {
+    "arm64"
+} else if cfg!(target_arch = "riscv64") {
+    "riscv64"
+} else if cfg!(target_arch = "loongarch64") {
+    "loong64"
+} else {
+    "unknown"
+};
+
+/// A reference to a blob.
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+}
+
+impl Descriptor {
+    fn parse(value: &Value) -> AxResult<Self> {
+        let (Some(media_type), Some(digest), Some(size)) = (
+            value.get("mediaType").and_then(Value::as_str),
+            value.get("digest").and_then(Value::as_str),
+            value.get("size").and_then(Value::as_i64),
+        ) else {
+            return ax_err!(InvalidData, "invalid OCI descriptor");
+        };
+        Ok(Self {
+            media_type: media_type.into(),
+            digest: digest.into(),
+            size: u64::try_from(size).map_err(|_| AxError::InvalidData)?,
+        })
+    }
+
+    fn is_index(&self) -> bool {
+        matches!(
+            self.media_type.as_str(),
+            "application/vnd.oci.image.index.v1+json"
+                | "application/vnd.docker.distribution.manifest.list.v2+json"
+        )
+    }
+
+    fn is_manifest(&self) -> bool {
+        matches!(
+            self.media_type.as_str(),
+            "application/vnd.oci.image.manifest.v1+json"
+                | "application/vnd.docker.distribution.manifest.v2+json"
+        )
+    }
+
+    /// Returns whether it's for the current platform, which is assumed if
+    /// the platform is not specified.
+    fn matches_platform(value: &Value) -> bool {
+        let Some(platform) = value.get("platform") else {
+            return true;
+        };
+        platform.get("os").and_then(Value::as_str) == Some("linux")
+            && platform.get("architecture").and_then(Value::as_str) == Some(ARCH)
+    }
+}
+
+/// A reader hashing the data read through it.
+struct Verifier<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<R: Read> Verifier<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    /// Reads the rest of the data, and checks their digest, and their size
+    /// if any.
+    fn finish(mut self, digest: &str, size: Option<u64>) -> AxResult<R> {
+        let mut buf = [0; 512];
+        while self.read(&mut buf)? != 0 {}
+        let Some(hex) = digest.strip_prefix("sha256:") else {
+            return ax_err!(Unsupported, "unsupported digest algorithm");
+        };
+        if size.is_some_and(|size| size != self.len) || self.hasher.finish_hex() != hex {
+            warn!("OCI blob {} is corrupted", digest);
+            return ax_err!(InvalidData, "digest mismatch");
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<R: Read> Read for Verifier<R> {
+    fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
+
+/// Joins a path relative to the root of a layer to the target.
+fn join(target: &str, rel: &str) -> String {
+    if rel.is_empty() {
+        return target.into();
+    }
+    format!("{}/{}", target.trim_end_matches('/'), rel)
+}
+
+/// Removes the file or the directory at `path` with all its contents, if
+/// any.
+fn remove_all(path: &str) -> AxResult {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(AxError::NotFound) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !meta.is_dir() {
+        return fs::remove_file(path);
+    }
+    let children = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<AxResult<Vec<_>>>()?;
+    for child in children {
+        remove_all(&child)?;
+    }
+    fs::remove_dir(path)
+}
+
+/// Creates the directory `path` and its parents if they do not exist, as
+/// [`fs::create_dir_all`] is not supported yet.
+fn create_dir_all(path: &str) -> AxResult {
+    let path = path.trim_end_matches('/');
+    let ends = path.match_indices('/').map(|(i, _)| i).chain([path.len()]);
+    for end in ends.filter(|&end| end > 0) {
+        match fs::create_dir(&path[..end]) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// An OCI image layout, in a directory of a mounted filesystem.
+pub struct Store {
+    root: String,
+}
+
+impl Store {
+    /// Opens the image layout in the directory `root`.
+    pub fn open(root: &str) -> AxResult<Self> {
+        let layout = json::parse(&fs::read(&join(root, "oci-layout"))?)?;
+        match layout.get("imageLayoutVersion").and_then(Value::as_str) {
+            Some(version) if version.starts_with("1.") => {}
+            _ => return ax_err!(Unsupported, "unsupported OCI image layout version"),
+        }
+        Ok(Self {
+            root: root.trim_end_matches('/').into(),
+        })
+    }
+
+    fn blob_path(&self, digest: &str) -> AxResult<String> {
+        let Some((alg, hex)) = digest.split_once(':') else {
+            return ax_err!(InvalidData, "invalid digest");
+        };
+        if alg != "sha256" {
+            return ax_err!(Unsupported, "unsupported digest algorithm");
+        }
+        if hex.len() != 64 || !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return ax_err!(InvalidData, "invalid digest");
+        }
+        Ok(format!("{}/blobs/{}/{}", self.root, alg, hex))
+    }
+
+    fn open_blob(&self, desc: &Descriptor) -> AxResult<Verifier<File>> {
+        Ok(Verifier::new(File::open(&self.blob_path(&desc.digest)?)?))
+    }
+
+    /// Reads and parses a manifest, an index or a config.
+    fn read_json(&self, desc: &Descriptor) -> AxResult<Value> {
+        if desc.size > MAX_JSON_SIZE {
+            return ax_err!(InvalidData, "OCI metadata too large");
+        }
+        let mut blob = self.open_blob(desc)?;
+        let mut bytes = vec![0; desc.size as usize];
+        blob.read_exact(&mut bytes)?;
+        blob.finish(&desc.digest, Some(desc.size))?;
+        json::parse(&bytes)
+    }
+
+    /// Selects the manifest tagged `tag`, or the only image if it's none,
+    /// resolving the image indexes by the current platform.
+    fn resolve(&self, tag: Option<&str>) -> AxResult<Value> {
+        let index = json::parse(&fs::read(&join(&self.root, "index.json"))?)?;
+        let candidates = index
+            .get("manifests")
+            .map(Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter(|m| match tag {
+                Some(tag) => {
+                    let annotations = m.get("annotations");
+                    annotations
+                        .and_then(|a| a.get(REF_NAME))
+                        .and_then(Value::as_str)
+                        == Some(tag)
+                }
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        let mut desc = match candidates.as_slice() {
+            [desc] => Descriptor::parse(desc)?,
+            [] => return ax_err!(NotFound, "no such image in the OCI layout"),
+            _ => {
+                return ax_err!(
+                    InvalidInput,
+                    "several images in the OCI layout, select one by the tag"
+                );
+            }
+        };
+
+        // the indexes may be nested, e.g. an image of several platforms
+        for _ in 0..8 {
+            if desc.is_manifest() {
+                return self.read_json(&desc);
+            }
+            if !desc.is_index() {
+                return ax_err!(Unsupported, "unsupported OCI manifest type");
+            }
+            let index = self.read_json(&desc)?;
+            let Some(next) = index
+                .get("manifests")
+                .map(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .find(|m| Descriptor::matches_platform(m))
+            else {
+                return ax_err!(NotFound, "no image for the current platform");
+            };
+            desc = Descriptor::parse(next)?;
+        }
+        ax_err!(InvalidData, "OCI image indexes nested too deeply")
+    }
+
+    /// Unpacks the image tagged `tag`, or the only image in the layout if
+    /// it's none, to the directory `target`, returning the number of the
+    /// layers.
+    pub fn unpack(&self, tag: Option<&str>, target: &str) -> AxResult<usize> {
+        let manifest = self.resolve(tag)?;
+        let config = Descriptor::parse(manifest.get("config").ok_or(AxError::InvalidData)?)?;
+        let config = self.read_json(&config)?;
+        let diff_ids = config
+            .get("rootfs")
+            .and_then(|r| r.get("diff_ids"))
+            .map(Value::as_array)
+            .unwrap_or_default();
+        let layers = manifest
+            .get("layers")
+            .map(Value::as_array)
+            .unwrap_or_default();
+        if layers.len() != diff_ids.len() {
+            return ax_err!(InvalidData, "layers mismatch the diff_ids of the config");
+        }
+
+        create_dir_all(target)?;
+        for (i, (layer, diff_id)) in layers.iter().zip(diff_ids).enumerate() {
+            let layer = Descriptor::parse(layer)?;
+            let diff_id = diff_id.as_str().ok_or(AxError::InvalidData)?;
+            info!(
+                "  unpack layer {}/{} {} ({} bytes)",
+                i + 1,
+                layers.len(),
+                layer.digest,
+                layer.size
+            );
+            let blob = self.open_blob(&layer)?;
+            let blob = if layer.media_type.ends_with("gzip") {
+                unpack_layer(GzDecoder::new(blob), diff_id, target)?.into_inner()
+            } else if layer.media_type.ends_with(".tar") {
+                if layer.digest != diff_id {
+                    return ax_err!(InvalidData, "layers mismatch the diff_ids of the config");
+                }
+                unpack_layer(blob, diff_id, target)?
+            } else {
+                return ax_err!(Unsupported, "unsupported OCI layer compression");
+            };
+            blob.finish(&layer.digest, Some(layer.size))?;
+        }
+        Ok(layers.len())
+    }
+}
+
+/// Unpacks a layer from the tar archive read from `reader`, checking that
+/// its digest is `diff_id`.
+fn unpack_layer<R: Read>(reader: R, diff_id: &str, target: &str) -> AxResult<R> {
+    let mut archive = Archive::new(Verifier::new(reader));
+    apply_layer(&mut archive, target)?;
+    archive.into_inner().finish(diff_id, None)
+}
+
+/// Returns an error if a component of the directory of `rel` in the target
+/// is a symlink, through which the layer could write out of the target.
+fn check_parents(target: &str, rel: &str) -> AxResult {
+    let mut end = 0;
+    while let Some(i) = rel[end..].find('/') {
+        end += i;
+        match fs::symlink_metadata(&join(target, &rel[..end])) {
+            Ok(meta) if meta.is_symlink() => {
+                return ax_err!(InvalidInput, "OCI layer writes through a symlink");
+            }
+            Ok(_) => {}
+            // it's created with the parents
+            Err(AxError::NotFound) => break,
+            Err(e) => return Err(e),
+        }
+        end += 1;
+    }
+    Ok(())
+}
+
+fn set_mode(path: &str, mode: u32) {
+    match fs::set_permissions(path, Permissions::from_bits_truncate(mode as u16)) {
+        Ok(()) | Err(AxError::Unsupported) => {}
+        Err(e) => warn!("failed to set the permissions of {}: {:?}", path, e),
+    }
+}
+
+/// Copies the contents of the file `src` to `dst`, for the hard links
+/// unsupported by the filesystem.
+fn copy_file(src: &str, dst: &str) -> AxResult {
+    let mut src = File::open(src)?;
+    let mut dst = File::create(dst)?;
+    let mut buf = vec![0; 0x4000];
+    loop {
+        let n = src.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        dst.write_all(&buf[..n])?;
+    }
+}
+
+/// Applies the entries of a layer to the target.
+fn apply_layer<R: Read>(archive: &mut Archive<R>, target: &str) -> AxResult {
+    // the paths added by this layer, which the opaque whiteouts keep
+    let mut added = BTreeSet::new();
+    let mut buf = vec![0; 0x4000];
+    while let Some(entry) = archive.next_entry()? {
+        let rel = entry.normalized_path()?;
+        if rel.is_empty() {
+            // the root of the layer
+            continue;
+        }
+        check_parents(target, &rel)?;
+        let (dir, name) = rel.rsplit_once('/').unwrap_or(("", &rel));
+        let path = join(target, &rel);
+
+        if name == OPAQUE_WHITEOUT {
+            if dir.is_empty() {
+                // it would remove the mount points in the target
+                warn!("ignore the opaque whiteout of the root of the layer");
+                continue;
+            }
+            let dir_path = join(target, dir);
+            let children = match fs::read_dir(&dir_path) {
+                Ok(entries) => entries
+                    .map(|entry| entry.map(|e| e.file_name()))
+                    .collect::<AxResult<Vec<_>>>()?,
+                Err(AxError::NotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            for child in children {
+                if !added.contains(&join(dir, &child)) {
+                    remove_all(&join(&dir_path, &child))?;
+                }
+            }
+            continue;
+        }
+        if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            let hidden = tar::normalize(hidden)?;
+            if hidden.is_empty() {
+                return ax_err!(InvalidData, "invalid whiteout");
+            }
+            remove_all(&join(&join(target, dir), &hidden))?;
+            continue;
+        }
+
+        debug!("  {:?} {}", entry.kind, rel);
+        let existing = fs::symlink_metadata(&path).ok();
+        // a directory is merged with the one of the lower layers, but
+        // anything else replaces what is there
+        match &existing {
+            Some(meta) if meta.is_dir() && entry.kind == EntryKind::Dir => {}
+            Some(_) if entry.kind != EntryKind::Other => remove_all(&path)?,
+            _ => {}
+        }
+        if !dir.is_empty() {
+            create_dir_all(&join(target, dir))?;
+        }
+        match entry.kind {
+            EntryKind::Dir => {
+                if existing.as_ref().is_none_or(|meta| !meta.is_dir()) {
+                    fs::create_dir(&path)?;
+                }
+                set_mode(&path, entry.mode);
+            }
+            EntryKind::File => {
+                let mut file = File::create(&path)?;
+                loop {
+                    let n = archive.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    file.write_all(&buf[..n])?;
+                }
+                drop(file);
+                set_mode(&path, entry.mode);
+            }
+            EntryKind::Symlink => fs::symlink(&entry.link, &path)?,
+            EntryKind::HardLink => {
+                let original_rel = tar::normalize(&entry.link)?;
+                check_parents(target, &original_rel)?;
+                let original = join(target, &original_rel);
+                match fs::hard_link(&original, &path) {
+                    Err(AxError::Unsupported) => copy_file(&original, &path)?,
+                    res => res?,
+                }
+            }
+            EntryKind::Other => {
+                debug!("  skip the special file {}", rel);
+                continue;
+            }
+        }
+        added.insert(rel);
+    }
+    Ok(())
+}
@@ -0,0 +1,425 @@
+//! A streaming reader of the tar archives of the layers.
+//!
+//! The ustar headers are read with the GNU extensions for the long names
+//! (`L` and `K`) and the base-256 numbers, and the PAX extended headers
+//! (`x`) for the paths, the link targets and the sizes, which cover the
+//! archives written by Docker, BuildKit and umoci.
+
+use alloc::{string::String, vec, vec::Vec};
+
+use axerrno::{AxError, AxResult, ax_err};
+use axio::Read;
+
+const BLOCK_SIZE: u64 = 512;
+
+/// The type of an entry of an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    HardLink,
+    /// The devices and the FIFOs, which are not unpacked.
+    Other,
+}
+
+/// The header of an entry of an archive.
+#[derive(Debug)]
+pub struct Entry {
+    pub path: String,
+    pub kind: EntryKind,
+    /// The permission bits.
+    pub mode: u32,
+    pub size: u64,
+    /// The target of a symlink or a hard link.
+    pub link: String,
+}
+
+/// A reader of the entries of an archive, whose data are read from it
+/// between the entries.
+pub struct Archive<R> {
+    inner: R,
+    /// The bytes of the data of the current entry left.
+    remaining: u64,
+    /// The padding after the data of the current entry.
+    padding: u64,
+}
+
+/// The fields overridden by the extended headers of the next entry.
+#[derive(Default)]
+struct Overrides {
+    path: Option<String>,
+    link: Option<String>,
+    size: Option<u64>,
+}
+
+fn field(header: &[u8]) -> &[u8] {
+    let end = header.iter().position(|&b| b == 0).unwrap_or(header.len());
+    &header[..end]
+}
+
+fn field_str(header: &[u8]) -> String {
+    String::from_utf8_lossy(field(header)).into_owned()
+}
+
+/// Parses a number in octal, or in base-256 if the highest bit of the first
+/// byte is set.
+fn number(field: &[u8]) -> AxResult<u64> {
+    if field.first().is_some_and(|&b| b & 0x80 != 0) {
+        let mut n: u64 = (field[0] & 0x3f) as u64;
+        for &b in &field[1..] {
+            n = n
+                .checked_mul(256)
+                .and_then(|n| n.checked_add(b as u64))
+                .ok_or(AxError::InvalidData)?;
+        }
+        return Ok(n);
+    }
+    let mut n: u64 = 0;
+    for &b in field {
+        match b {
+            b'0'..=b'7' => {
+                n = n
+                    .checked_mul(8)
+                    .and_then(|n| n.checked_add((b - b'0') as u64))
+                    .ok_or(AxError::InvalidData)?;
+            }
+            b' ' if n == 0 => {}
+            b' ' | 0 => break,
+            _ => return ax_err!(InvalidData, "invalid number in tar header"),
+        }
+    }
+    Ok(n)
+}
+
+/// Parses the records `<len> <key>=<value>\n` of a PAX extended header.
+fn parse_pax(mut data: &[u8], overrides: &mut Overrides) -> AxResult {
+    while !data.is_empty() {
+        let space = data.iter().position(|&b| b == b' ');
+        let len = space
+            .and_then(|i| core::str::from_utf8(&data[..i]).ok())
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|&len| len <= data.len() && len > space.unwrap() + 1)
+            .ok_or(AxError::InvalidData)?;
+        let record = &data[space.unwrap() + 1..len - 1];
+        data = &data[len..];
+        let Some(eq) = record.iter().position(|&b| b == b'=') else {
+            return ax_err!(InvalidData, "invalid PAX record");
+        };
+        let value = String::from_utf8_lossy(&record[eq + 1..]).into_owned();
+        match &record[..eq] {
+            b"path" => overrides.path = Some(value),
+            b"linkpath" => overrides.link = Some(value),
+            b"size" => overrides.size = Some(value.parse().map_err(|_| AxError::InvalidData)?),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl<R: Read> Archive<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            padding: 0,
+        }
+    }
+
+    /// Returns the underlying reader, to read the data after the archive.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn skip(&mut self, mut len: u64) -> AxResult {
+        let mut buf = [0; 512];
+        while len > 0 {
+            let n = len.min(buf.len() as u64) as usize;
+            self.inner.read_exact(&mut buf[..n])?;
+            len -= n as u64;
+        }
+        Ok(())
+    }
+
+    /// Reads the data of an extended header of `size` bytes.
+    fn read_extension(&mut self, size: u64) -> AxResult<Vec<u8>> {
+        if size > 0x10_0000 {
+            return ax_err!(InvalidData, "tar extended header too large");
+        }
+        let mut data = vec![0; size as usize];
+        self.inner.read_exact(&mut data)?;
+        self.skip(size.next_multiple_of(BLOCK_SIZE) - size)?;
+        Ok(data)
+    }
+
+    /// Reads the header of the next entry, skipping the rest of the data of
+    /// the current one, or returns none at the end of the archive.
+    pub fn next_entry(&mut self) -> AxResult<Option<Entry>> {
+        self.skip(self.remaining + self.padding)?;
+        self.remaining = 0;
+        self.padding = 0;
+
+        let mut overrides = Overrides::default();
+        let mut header = [0u8; BLOCK_SIZE as usize];
+        loop {
+            self.inner.read_exact(&mut header)?;
+            if header.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+            let sum = number(&header[148..156])?;
+            let actual: u64 = header
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+                .sum();
+            if sum != actual {
+                return ax_err!(InvalidData, "tar header checksum mismatch");
+            }
+
+            let size = number(&header[124..136])?;
+            match header[156] {
+                b'L' => {
+                    let data = self.read_extension(size)?;
+                    overrides.path = Some(String::from_utf8_lossy(field(&data)).into_owned());
+                    continue;
+                }
+                b'K' => {
+                    let data = self.read_extension(size)?;
+                    overrides.link = Some(String::from_utf8_lossy(field(&data)).into_owned());
+                    continue;
+                }
+                b'x' => {
+                    let data = self.read_extension(size)?;
+                    parse_pax(&data, &mut overrides)?;
+                    continue;
+                }
+                b'g' => {
+                    // the global headers only carry the comments of the images
+                    self.read_extension(size)?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let path = match overrides.path.take() {
+                Some(path) => path,
+                // the prefix is only in the POSIX ustar headers
+                None if &header[257..263] == b"ustar\0" && header[345] != 0 => {
+                    field_str(&header[345..500]) + "/" + &field_str(&header[..100])
+                }
+                None => field_str(&header[..100]),
+            };
+            let kind = match header[156] {
+                b'0' | b'7' | 0 if path.ends_with('/') => EntryKind::Dir,
+                b'0' | b'7' | 0 => EntryKind::File,
+                b'1' => EntryKind::HardLink,
+                b'2' => EntryKind::Symlink,
+                b'5' => EntryKind::Dir,
+                _ => EntryKind::Other,
+            };
+            // only the regular files have data, whatever the size says
+            let data_size = match kind {
+                EntryKind::File | EntryKind::Other => overrides.size.unwrap_or(size),
+                _ => 0,
+            };
+            self.remaining = data_size;
+            self.padding = data_size.next_multiple_of(BLOCK_SIZE) - data_size;
+            return Ok(Some(Entry {
+                path,
+                kind,
+                mode: number(&header[100..108])? as u32 & 0o7777,
+                size: data_size,
+                link: overrides
+                    .link
+                    .take()
+                    .unwrap_or_else(|| field_str(&header[157..257])),
+            }));
+        }
+    }
+}
+
+impl<R: Read> Read for Archive<R> {
+    /// Reads the data of the current entry.
+    fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
+        let len = (buf.len() as u64).min(self.remaining) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(AxError::UnexpectedEof);
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+impl Entry {
+    /// Returns the path of the entry relative to the root of the layer,
+    /// without `.` and empty components, or an error if it escapes the root
+    /// by `..`.
+    pub fn normalized_path(&self) -> AxResult<String> {
+        normalize(&self.path)
+    }
+}
+
+/// Normalizes a path in an archive, see [`Entry::normalized_path`].
+pub fn normalize(path: &str) -> AxResult<String> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return ax_err!(InvalidInput, "path escapes the layer"),
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::ToString};
+
+    use super::*;
+
+    /// Builds a ustar header, without the checksum.
+    fn header(name: &str, kind: u8, size: u64, link: &str) -> [u8; 512] {
+        let mut header = [0; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header
+    }
+
+    /// Appends the entry of `header` with its checksum and `data` to `tar`.
+    fn push(tar: &mut Vec<u8>, mut header: [u8; 512], data: &[u8]) {
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+        tar.extend_from_slice(&header);
+        tar.extend_from_slice(data);
+        tar.resize(tar.len().next_multiple_of(512), 0);
+    }
+
+    fn pax_record(key: &str, value: &str) -> String {
+        let rest = format!(" {}={}\n", key, value);
+        let mut len = rest.len() + 1;
+        while len.to_string().len() + rest.len() != len {
+            len += 1;
+        }
+        format!("{}{}", len, rest)
+    }
+
+    fn read_data<R: Read>(archive: &mut Archive<R>) -> Vec<u8> {
+        let mut data = Vec::new();
+        archive.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut tar = Vec::new();
+        push(&mut tar, header("dir/", b'5', 0, ""), b"");
+        push(
+            &mut tar,
+            header("dir/a.txt", b'0', 14, ""),
+            b"Rust is cool!\n",
+        );
+        push(&mut tar, header("dir/big", b'0', 600, ""), &[1; 600]);
+        push(&mut tar, header("link", b'2', 0, "dir/a.txt"), b"");
+        push(&mut tar, header("hard", b'1', 0, "dir/a.txt"), b"");
+        tar.extend_from_slice(&[0; 1024]);
+
+        let mut archive = Archive::new(&tar[..]);
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(entry.path, "dir/");
+        assert_eq!(
+            (entry.kind, entry.mode, entry.size),
+            (EntryKind::Dir, 0o644, 0)
+        );
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(entry.path, "dir/a.txt");
+        assert_eq!((entry.kind, entry.size), (EntryKind::File, 14));
+        assert_eq!(read_data(&mut archive), b"Rust is cool!\n");
+        // the data not read is skipped
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!((entry.kind, entry.size), (EntryKind::File, 600));
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(
+            (entry.kind, entry.link.as_str()),
+            (EntryKind::Symlink, "dir/a.txt")
+        );
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!(
+            (entry.kind, entry.link.as_str()),
+            (EntryKind::HardLink, "dir/a.txt")
+        );
+        assert!(archive.next_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_extensions() {
+        let long = "very/".repeat(30) + "long.txt";
+        let mut tar = Vec::new();
+        // the GNU long name
+        let name = format!("{}\0", long);
+        push(
+            &mut tar,
+            header("././@LongLink", b'L', name.len() as u64, ""),
+            name.as_bytes(),
+        );
+        push(&mut tar, header("very/very", b'0', 0, ""), b"");
+        // the PAX path and size override the header
+        let pax = pax_record("path", "pax.txt") + &pax_record("size", "5");
+        push(
+            &mut tar,
+            header("PaxHeaders/pax.txt", b'x', pax.len() as u64, ""),
+            pax.as_bytes(),
+        );
+        push(&mut tar, header("ignored", b'0', 0, ""), b"hello");
+        // the ustar prefix, and a size in base-256
+        let mut prefixed = header("c.txt", b'0', 0, "");
+        prefixed[345..348].copy_from_slice(b"a/b");
+        prefixed[124..136].fill(0);
+        prefixed[124] = 0x80;
+        prefixed[135] = 3;
+        push(&mut tar, prefixed, b"abc");
+        tar.extend_from_slice(&[0; 1024]);
+
+        let mut archive = Archive::new(&tar[..]);
+        assert_eq!(archive.next_entry().unwrap().unwrap().path, long);
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!((entry.path.as_str(), entry.size), ("pax.txt", 5));
+        assert_eq!(read_data(&mut archive), b"hello");
+        let entry = archive.next_entry().unwrap().unwrap();
+        assert_eq!((entry.path.as_str(), entry.size), ("a/b/c.txt", 3));
+        assert_eq!(read_data(&mut archive), b"abc");
+        assert!(archive.next_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut tar = Vec::new();
+        push(&mut tar, header("a.txt", b'0', 5, ""), b"hello");
+        let mut corrupted = tar.clone();
+        corrupted[0] = b'b';
+        let mut archive = Archive::new(&corrupted[..]);
+        assert_eq!(archive.next_entry().err(), Some(AxError::InvalidData));
+
+        // truncated, without the end of the archive
+        let mut archive = Archive::new(&tar[..]);
+        assert!(archive.next_entry().unwrap().is_some());
+        assert_eq!(archive.next_entry().err(), Some(AxError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("./a//b/./c/").unwrap(), "a/b/c");
+        assert_eq!(normalize("/").unwrap(), "");
+        assert_eq!(normalize("a/../b").err(), Some(AxError::InvalidInput));
+    }
+}
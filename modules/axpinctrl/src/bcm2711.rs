@@ -0,0 +1,140 @@
+//! The GPIO controller of BCM2711, the SoC of Raspberry Pi 4, which selects
+//! the functions of its 58 pins and their pull-ups and pull-downs.
+//!
+//! The configuration nodes have the `brcm,pins`, `brcm,function` and
+//! `brcm,pull` properties of the bindings of BCM2835, or the generic `pins`,
+//! `function` and `bias-*` ones, e.g. `pins = "gpio14", "gpio15"` and
+//! `function = "alt0"`.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axclk::Regs;
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+
+use crate::{Bias, PinConf, PinctrlOps};
+
+const NUM_PINS: u32 = 58;
+/// The function select registers, of 10 pins each.
+const GPFSEL0: usize = 0x00;
+const FSEL_MASK: u32 = 0x7;
+/// The pull-up/pull-down registers of BCM2711, of 16 pins each.
+const GPIO_PUP_PDN_CNTRL0: usize = 0xe4;
+const PUD_MASK: u32 = 0x3;
+const PUD_NONE: u32 = 0;
+const PUD_UP: u32 = 1;
+const PUD_DOWN: u32 = 2;
+
+/// The function select values by the names of the functions.
+const FUNCTIONS: &[(&str, u32)] = &[
+    ("gpio_in", 0),
+    ("gpio_out", 1),
+    ("alt0", 4),
+    ("alt1", 5),
+    ("alt2", 6),
+    ("alt3", 7),
+    ("alt4", 3),
+    ("alt5", 2),
+];
+
+/// The GPIO controller of BCM2711.
+pub struct Bcm2711Gpio {
+    regs: Regs,
+}
+
+impl Bcm2711Gpio {
+    /// Creates the controller of the registers `regs`.
+    pub const fn new(regs: Regs) -> Self {
+        Self { regs }
+    }
+
+    /// Selects the function `fsel` of the pin, e.g. 4 for `alt0`.
+    pub fn set_function(&self, pin: u32, fsel: u32) -> AxResult {
+        if pin >= NUM_PINS || fsel > FSEL_MASK {
+            return ax_err!(InvalidInput, "invalid pin function");
+        }
+        let reg = GPFSEL0 + (pin / 10) as usize * 4;
+        let shift = (pin % 10) * 3;
+        self.regs.modify(reg, FSEL_MASK << shift, fsel << shift);
+        Ok(())
+    }
+
+    /// Sets the bias of the pin.
+    pub fn set_bias(&self, pin: u32, bias: Bias) -> AxResult {
+        if pin >= NUM_PINS {
+            return ax_err!(InvalidInput, "invalid pin");
+        }
+        let pud = match bias {
+            Bias::Disable => PUD_NONE,
+            Bias::PullUp => PUD_UP,
+            Bias::PullDown => PUD_DOWN,
+        };
+        let reg = GPIO_PUP_PDN_CNTRL0 + (pin / 16) as usize * 4;
+        let shift = (pin % 16) * 2;
+        self.regs.modify(reg, PUD_MASK << shift, pud << shift);
+        Ok(())
+    }
+
+    /// Applies the `brcm,*` properties, where `brcm,function` and `brcm,pull`
+    /// have either one value for all the pins, or one for each.
+    fn apply_brcm(&self, config: &Node, pins: &[u32]) -> AxResult {
+        let functions: Vec<u32> = config.cells("brcm,function").collect();
+        let pulls: Vec<u32> = config.cells("brcm,pull").collect();
+        for (i, &pin) in pins.iter().enumerate() {
+            if let Some(&fsel) = functions.get(i).or(functions.first()) {
+                self.set_function(pin, fsel)?;
+            }
+            if let Some(&pull) = pulls.get(i).or(pulls.first()) {
+                // the values of the bindings, not the ones of the registers
+                let bias = match pull {
+                    0 => Bias::Disable,
+                    1 => Bias::PullDown,
+                    2 => Bias::PullUp,
+                    _ => return ax_err!(InvalidData, "invalid brcm,pull"),
+                };
+                self.set_bias(pin, bias)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PinctrlOps for Bcm2711Gpio {
+    fn apply(&self, config: &Node) -> AxResult {
+        let pins: Vec<u32> = config.cells("brcm,pins").collect();
+        if !pins.is_empty() {
+            return self.apply_brcm(config, &pins);
+        }
+
+        let fsel = match config.strings("function").next() {
+            Some(name) => Some(
+                FUNCTIONS
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|&(_, fsel)| fsel)
+                    .ok_or(AxError::InvalidData)?,
+            ),
+            None => None,
+        };
+        let conf = PinConf::from_node(config);
+        for name in config.strings("pins") {
+            let pin = name
+                .strip_prefix("gpio")
+                .and_then(|n| n.parse().ok())
+                .ok_or(AxError::InvalidData)?;
+            if let Some(fsel) = fsel {
+                self.set_function(pin, fsel)?;
+            }
+            if let Some(bias) = conf.bias {
+                self.set_bias(pin, bias)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Probes a `brcm,bcm2711-gpio` node.
+pub(crate) fn probe(node: &Node) -> AxResult<Arc<dyn PinctrlOps>> {
+    Ok(Arc::new(Bcm2711Gpio::new(Regs::from_node(node, 0)?)))
+}
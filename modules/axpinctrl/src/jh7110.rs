@@ -0,0 +1,221 @@
+//! The pin controllers of StarFive JH7110, the SoC of VisionFive 2.
+//!
+//! Each GPIO of the SYS (64 pins) and the AON (4 pins) controllers is routed
+//! to the signals of the peripherals by its output, its output enable and an
+//! input signal, given by the `pinmux` cells of the configuration nodes, which
+//! are made by the `GPIOMUX` macro of the device tree bindings of Linux
+//! (`dt-bindings/pinctrl/starfive,jh7110-pinctrl.h`). The pads of them and of
+//! the `pins` cells are configured by the generic properties.
+//!
+//! The configuration nodes of a device are the groups of the pins in the
+//! children of the nodes referred to by `pinctrl-<n>`.
+
+use alloc::sync::Arc;
+
+use axclk::Regs;
+use axerrno::{AxResult, ax_err};
+use axhal::dtb::Node;
+
+use crate::{Bias, PinConf, PinctrlOps};
+
+/// The fields of a `pinmux` cell: the pin, the output enable, the output and
+/// the input signal.
+const PINMUX_PIN_MASK: u32 = 0x3ff;
+const PINMUX_DOEN_SHIFT: u32 = 10;
+const PINMUX_DOEN_MASK: u32 = 0x3f;
+const PINMUX_DOUT_SHIFT: u32 = 16;
+const PINMUX_DOUT_MASK: u32 = 0xff;
+const PINMUX_DIN_SHIFT: u32 = 24;
+/// No input signal is routed from the pin.
+const GPI_NONE: u32 = 0xff;
+/// The value of the input signal selecting the pin 0, after the constant low
+/// and high.
+const GPI_PIN_BASE: u32 = 2;
+
+const PADCFG_IE: u32 = 1 << 0;
+const PADCFG_DS_SHIFT: u32 = 1;
+const PADCFG_DS_MASK: u32 = 0x3 << PADCFG_DS_SHIFT;
+const PADCFG_PU: u32 = 1 << 3;
+const PADCFG_PD: u32 = 1 << 4;
+const PADCFG_SLEW: u32 = 1 << 5;
+const PADCFG_SMT: u32 = 1 << 6;
+
+/// The registers of a controller, where the output, the output enable and
+/// the input signal registers have the fields of 8 bits for 4 pins or
+/// signals each.
+struct Layout {
+    ngpio: u32,
+    doen: usize,
+    doen_mask: u32,
+    dout: usize,
+    dout_mask: u32,
+    gpi: usize,
+    gpi_mask: u32,
+    /// The ranges of the pads with the configuration registers: the first
+    /// and the last pad, and the register of the first one.
+    pads: &'static [(u32, u32, usize)],
+}
+
+const SYS: Layout = Layout {
+    ngpio: 64,
+    doen: 0x0,
+    doen_mask: 0x3f,
+    dout: 0x40,
+    dout_mask: 0x7f,
+    gpi: 0x80,
+    gpi_mask: 0x7f,
+    pads: &[(0, 74, 0x120), (89, 94, 0x284)],
+};
+
+const AON: Layout = Layout {
+    ngpio: 4,
+    doen: 0x0,
+    doen_mask: 0x7,
+    dout: 0x4,
+    dout_mask: 0xf,
+    gpi: 0x8,
+    gpi_mask: 0xf,
+    pads: &[(0, 3, 0x1c)],
+};
+
+/// A pin controller of JH7110.
+pub struct Jh7110Pinctrl {
+    regs: Regs,
+    layout: &'static Layout,
+}
+
+impl Jh7110Pinctrl {
+    /// Routes the output `dout` and the output enable `doen` to the GPIO
+    /// `pin`, and the pin to the input signal `din` unless it's
+    /// [`GPI_NONE`].
+    fn set_mux(&self, pin: u32, dout: u32, doen: u32, din: u32) -> AxResult {
+        let layout = self.layout;
+        if pin >= layout.ngpio {
+            return ax_err!(InvalidInput, "invalid GPIO");
+        }
+        let offset = (pin / 4) as usize * 4;
+        let shift = (pin % 4) * 8;
+        self.regs.modify(
+            layout.dout + offset,
+            layout.dout_mask << shift,
+            (dout & layout.dout_mask) << shift,
+        );
+        self.regs.modify(
+            layout.doen + offset,
+            layout.doen_mask << shift,
+            (doen & layout.doen_mask) << shift,
+        );
+        if din != GPI_NONE {
+            let offset = (din / 4) as usize * 4;
+            let shift = (din % 4) * 8;
+            self.regs.modify(
+                layout.gpi + offset,
+                layout.gpi_mask << shift,
+                ((pin + GPI_PIN_BASE) & layout.gpi_mask) << shift,
+            );
+        }
+        Ok(())
+    }
+
+    /// Configures the pad `pad`.
+    fn set_conf(&self, pad: u32, conf: &PinConf) -> AxResult {
+        let mut clear = 0;
+        let mut set = 0;
+        if let Some(bias) = conf.bias {
+            clear |= PADCFG_PU | PADCFG_PD;
+            set |= match bias {
+                Bias::Disable => 0,
+                Bias::PullUp => PADCFG_PU,
+                Bias::PullDown => PADCFG_PD,
+            };
+        }
+        if let Some(ma) = conf.drive_strength {
+            // 2, 4, 8 or 12 mA
+            let ds = match ma {
+                0..=2 => 0,
+                3..=4 => 1,
+                5..=8 => 2,
+                _ => 3,
+            };
+            clear |= PADCFG_DS_MASK;
+            set |= ds << PADCFG_DS_SHIFT;
+        }
+        for (value, bit) in [
+            (conf.input_enable, PADCFG_IE),
+            (conf.input_schmitt, PADCFG_SMT),
+            (conf.slew_rate.map(|rate| rate != 0), PADCFG_SLEW),
+        ] {
+            if let Some(value) = value {
+                clear |= bit;
+                set |= if value { bit } else { 0 };
+            }
+        }
+        if clear == 0 {
+            return Ok(());
+        }
+        let Some(reg) = self
+            .layout
+            .pads
+            .iter()
+            .find(|&&(first, last, _)| (first..=last).contains(&pad))
+            .map(|&(first, _, reg)| reg + (pad - first) as usize * 4)
+        else {
+            return ax_err!(InvalidInput, "invalid pad");
+        };
+        self.regs.modify(reg, clear, set);
+        Ok(())
+    }
+
+    /// Applies a group of the pins.
+    fn apply_group(&self, group: &Node) -> AxResult {
+        let conf = PinConf::from_node(group);
+        for mux in group.cells("pinmux") {
+            let pin = mux & PINMUX_PIN_MASK;
+            let doen = (mux >> PINMUX_DOEN_SHIFT) & PINMUX_DOEN_MASK;
+            let dout = (mux >> PINMUX_DOUT_SHIFT) & PINMUX_DOUT_MASK;
+            let din = mux >> PINMUX_DIN_SHIFT;
+            self.set_mux(pin, dout, doen, din)?;
+            self.set_conf(pin, &conf)?;
+        }
+        for pad in group.cells("pins") {
+            self.set_conf(pad, &conf)?;
+        }
+        Ok(())
+    }
+}
+
+impl PinctrlOps for Jh7110Pinctrl {
+    fn apply(&self, config: &Node) -> AxResult {
+        self.apply_group(config)?;
+        let mut res = Ok(());
+        config.for_each_child(|group| {
+            if res.is_ok() {
+                res = self.apply_group(&group);
+            }
+        });
+        res
+    }
+}
+
+/// Probes a controller, and enables its clock and takes it out of reset if
+/// they are provided.
+fn probe(node: &Node, layout: &'static Layout) -> AxResult<Arc<dyn PinctrlOps>> {
+    let regs = Regs::from_node(node, 0)?;
+    if let Ok(clk) = axclk::get(node, None) {
+        clk.enable()?;
+    }
+    if let Ok(reset) = axclk::reset::get(node, None) {
+        reset.deassert()?;
+    }
+    Ok(Arc::new(Jh7110Pinctrl { regs, layout }))
+}
+
+/// Probes a `starfive,jh7110-sys-pinctrl` node.
+pub(crate) fn probe_sys(node: &Node) -> AxResult<Arc<dyn PinctrlOps>> {
+    probe(node, &SYS)
+}
+
+/// Probes a `starfive,jh7110-aon-pinctrl` node.
+pub(crate) fn probe_aon(node: &Node) -> AxResult<Arc<dyn PinctrlOps>> {
+    probe(node, &AON)
+}
@@ -0,0 +1,203 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) pin controller module.
+//!
+//! The pins of a SoC are multiplexed between the functions of its
+//! peripherals, e.g. a pin may be a GPIO, the TX of a UART or the clock of an
+//! SD card, and each of them has its bias, drive strength and so on. The
+//! states of the pins of a device are given by the `pinctrl-names` and the
+//! `pinctrl-<n>` properties of its device tree node, which refer to the
+//! configuration nodes under the pin controllers.
+//!
+//! The pin controllers are probed from the device tree by [`init`], which
+//! then selects the `default` state of each enabled device, instead of
+//! relying on the state left by the bootloader. The drivers may switch the
+//! states of their devices by [`select_state`].
+//!
+//! The pin controllers supported are:
+//!
+//! - [`bcm2711`]: the GPIO controller of BCM2711 (Raspberry Pi 4).
+//! - [`jh7110`]: the SYS and the AON pin controllers of StarFive JH7110
+//!   (VisionFive 2).
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+pub mod bcm2711;
+pub mod jh7110;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::sync::Arc;
+
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::dtb::Node;
+use axsync::Mutex;
+
+/// The bias of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bias {
+    /// Neither pulled up nor down.
+    Disable,
+    /// Pulled up.
+    PullUp,
+    /// Pulled down.
+    PullDown,
+}
+
+/// The generic configuration of pins, by the properties of the device tree
+/// bindings of the pin controllers, `None` for the ones not changed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PinConf {
+    /// `bias-disable`, `bias-pull-up` or `bias-pull-down`.
+    pub bias: Option<Bias>,
+    /// `drive-strength`, in mA.
+    pub drive_strength: Option<u32>,
+    /// `input-enable` or `input-disable`.
+    pub input_enable: Option<bool>,
+    /// `input-schmitt-enable` or `input-schmitt-disable`.
+    pub input_schmitt: Option<bool>,
+    /// `slew-rate`, 0 for the slow one.
+    pub slew_rate: Option<u32>,
+}
+
+impl PinConf {
+    /// Parses the generic configuration of the configuration node `node`.
+    pub fn from_node(node: &Node) -> Self {
+        let has = |name| node.property(name).is_some();
+        let flag = |enable, disable| {
+            if has(enable) {
+                Some(true)
+            } else if has(disable) {
+                Some(false)
+            } else {
+                None
+            }
+        };
+        let bias = if has("bias-disable") {
+            Some(Bias::Disable)
+        } else if has("bias-pull-up") {
+            Some(Bias::PullUp)
+        } else if has("bias-pull-down") {
+            Some(Bias::PullDown)
+        } else {
+            None
+        };
+        Self {
+            bias,
+            drive_strength: node.property_u32("drive-strength"),
+            input_enable: flag("input-enable", "input-disable"),
+            input_schmitt: flag("input-schmitt-enable", "input-schmitt-disable"),
+            slew_rate: node.property_u32("slew-rate"),
+        }
+    }
+}
+
+/// The operations of a pin controller.
+pub trait PinctrlOps: Send + Sync {
+    /// Applies the configuration node `config`, which is under the node of
+    /// the controller.
+    fn apply(&self, config: &Node) -> AxResult;
+}
+
+/// The pin controllers by the phandles of their nodes.
+static PROVIDERS: Mutex<BTreeMap<u32, Arc<dyn PinctrlOps>>> = Mutex::new(BTreeMap::new());
+
+/// Registers the pin controller of the device tree node with the phandle
+/// `phandle`.
+pub fn register_provider(phandle: u32, ops: Arc<dyn PinctrlOps>) {
+    PROVIDERS.lock().insert(phandle, ops);
+}
+
+/// Applies the configuration node `config` by the controller of its nearest
+/// ancestor.
+fn apply_config(config: &Node) -> AxResult {
+    let mut node = config.parent();
+    while let Some(parent) = node {
+        let ops = parent
+            .phandle()
+            .and_then(|phandle| PROVIDERS.lock().get(&phandle).cloned());
+        if let Some(ops) = ops {
+            return ops.apply(config);
+        }
+        node = parent.parent();
+    }
+    ax_err!(NotFound, "no pin controller of the configuration")
+}
+
+/// Selects the state `name` in the `pinctrl-names` of the device tree node
+/// `node`, by applying the configuration nodes of the state.
+///
+/// A node without `pinctrl-names` has only the `default` state, in
+/// `pinctrl-0`.
+pub fn select_state(node: &Node, name: &str) -> AxResult {
+    let index = match node.strings("pinctrl-names").position(|n| n == name) {
+        Some(index) => index,
+        None if name == "default" && node.property("pinctrl-names").is_none() => 0,
+        None => return ax_err!(NotFound, "no such pin state"),
+    };
+    for phandle in node.cells(&format!("pinctrl-{}", index)) {
+        let config = axhal::dtb::find_by_phandle(phandle).ok_or(AxError::NotFound)?;
+        apply_config(&config)?;
+    }
+    Ok(())
+}
+
+/// Selects the `default` state of the pins of the device tree node `node`.
+pub fn select_default(node: &Node) -> AxResult {
+    select_state(node, "default")
+}
+
+/// Probes a pin controller of a device tree node.
+type ProbeFn = fn(&Node) -> AxResult<Arc<dyn PinctrlOps>>;
+
+/// The pin controllers by their compatible strings.
+const DRIVERS: &[(&str, ProbeFn)] = &[
+    ("brcm,bcm2711-gpio", bcm2711::probe),
+    ("starfive,jh7110-sys-pinctrl", jh7110::probe_sys),
+    ("starfive,jh7110-aon-pinctrl", jh7110::probe_aon),
+];
+
+/// Probes the pin controllers in the device tree, and selects the `default`
+/// state of the pins of each enabled device.
+///
+/// It must be called after [`axclk::init`], as the controllers may need
+/// their clocks.
+pub fn init() {
+    info!("Initialize pin controllers...");
+    axhal::dtb::for_each_node(|node| {
+        let Some(phandle) = node.phandle() else {
+            return;
+        };
+        if !node.is_enabled() {
+            return;
+        }
+        let Some(&(compatible, probe)) = DRIVERS.iter().find(|(c, _)| node.is_compatible(c)) else {
+            return;
+        };
+        match probe(&node) {
+            Ok(ops) => {
+                debug!("  {} ({})", node.name(), compatible);
+                register_provider(phandle, ops);
+            }
+            Err(e) => warn!("failed to probe the pins of {}: {:?}", node.name(), e),
+        }
+    });
+    if PROVIDERS.lock().is_empty() {
+        return;
+    }
+
+    let mut count = 0;
+    axhal::dtb::for_each_node(|node| {
+        if !node.is_enabled() || node.property("pinctrl-0").is_none() {
+            return;
+        }
+        match select_default(&node) {
+            Ok(()) => count += 1,
+            Err(AxError::NotFound) => {}
+            Err(e) => warn!("failed to select the pins of {}: {:?}", node.name(), e),
+        }
+    });
+    info!("  default pins of {} devices selected", count);
+}
@@ -0,0 +1,170 @@
+//! The ACPI control method batteries (`PNP0C0A`) and AC adapters (`ACPI0003`).
+//!
+//! ArceOS has no AML interpreter, so the control methods `_STA`, `_BIF`,
+//! `_BST` and `_PSR` of the devices are evaluated by an [`AcpiEvaluator`] of
+//! the platform, e.g. one backed by an AML interpreter or by the firmware.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use axerrno::{AxResult, ax_err};
+
+use crate::{PowerSupply, Properties, Status, SupplyType};
+
+/// The value of an unknown field in the packages of `_BIF` and `_BST`.
+const UNKNOWN: u64 = 0xffff_ffff;
+/// The bit of `_STA` set if the battery is present.
+const STA_BATTERY_PRESENT: u64 = 1 << 4;
+/// The bit of the battery state of `_BST` set if it's discharging.
+const BST_DISCHARGING: u64 = 1 << 0;
+/// The bit of the battery state of `_BST` set if it's charging.
+const BST_CHARGING: u64 = 1 << 1;
+
+/// Evaluates the control methods of the ACPI devices.
+pub trait AcpiEvaluator: Send {
+    /// Evaluates the method `method` (e.g. `_STA`) of the device at `path`
+    /// (e.g. `\_SB.BAT0`), whose result is an integer.
+    fn evaluate_integer(&mut self, path: &str, method: &str) -> AxResult<u64>;
+
+    /// Evaluates the method `method` (e.g. `_BST`) of the device at `path`,
+    /// whose result is a package, and returns its integer elements, with the
+    /// other elements (e.g. the strings of `_BIF`) skipped.
+    fn evaluate_package(&mut self, path: &str, method: &str) -> AxResult<Vec<u64>>;
+}
+
+fn known(value: u64) -> Option<u32> {
+    (value != UNKNOWN).then_some(value as u32)
+}
+
+/// An ACPI control method battery.
+pub struct AcpiBattery<E: AcpiEvaluator> {
+    name: String,
+    path: String,
+    eval: E,
+}
+
+impl<E: AcpiEvaluator> AcpiBattery<E> {
+    /// Creates the battery `name` (e.g. `BAT0`) of the device at `path`.
+    pub fn new(name: impl Into<String>, path: impl Into<String>, eval: E) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            eval,
+        }
+    }
+}
+
+impl<E: AcpiEvaluator> PowerSupply for AcpiBattery<E> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supply_type(&self) -> SupplyType {
+        SupplyType::Battery
+    }
+
+    fn properties(&mut self) -> AxResult<Properties> {
+        // `_STA` is optional, the battery is present without it
+        let sta = self.eval.evaluate_integer(&self.path, "_STA").unwrap_or(!0);
+        if sta & STA_BATTERY_PRESENT == 0 {
+            return Ok(Properties {
+                present: Some(false),
+                ..Properties::EMPTY
+            });
+        }
+
+        // power unit, design capacity, last full capacity, technology,
+        // design voltage, ...
+        let bif = self.eval.evaluate_package(&self.path, "_BIF")?;
+        // state, present rate, remaining capacity, present voltage
+        let bst = self.eval.evaluate_package(&self.path, "_BST")?;
+        if bif.len() < 5 || bst.len() < 4 {
+            return ax_err!(InvalidData, "bad battery information");
+        }
+
+        let full = known(bif[2]).or(known(bif[1]));
+        let remaining = known(bst[2]);
+        let state = bst[0];
+        let status = if state & BST_CHARGING != 0 {
+            Status::Charging
+        } else if state & BST_DISCHARGING != 0 {
+            Status::Discharging
+        } else if remaining.is_some() && remaining >= full {
+            Status::Full
+        } else {
+            Status::NotCharging
+        };
+        let capacity = match (remaining, full) {
+            (Some(remaining), Some(full)) if full > 0 => {
+                Some((remaining as u64 * 100 / full as u64).min(100) as u8)
+            }
+            _ => None,
+        };
+        // the rate is always positive, and negative in Linux if discharging
+        let rate = known(bst[1]).map(|rate| {
+            let rate = rate as i32 * 1000;
+            if status == Status::Discharging {
+                -rate
+            } else {
+                rate
+            }
+        });
+
+        let mut props = Properties {
+            present: Some(true),
+            status: Some(status),
+            capacity,
+            voltage_now: known(bst[3]).or(known(bif[4])).map(|mv| mv * 1000),
+            ..Properties::EMPTY
+        };
+        let to_micro = |v: Option<u32>| v.map(|v| v * 1000);
+        if bif[0] == 0 {
+            // in mW and mWh
+            props.power_now = rate;
+            props.energy_now = to_micro(remaining);
+            props.energy_full = to_micro(full);
+        } else {
+            // in mA and mAh
+            props.current_now = rate;
+            props.charge_now = to_micro(remaining);
+            props.charge_full = to_micro(full);
+        }
+        Ok(props)
+    }
+}
+
+/// An ACPI AC adapter.
+pub struct AcpiAc<E: AcpiEvaluator> {
+    name: String,
+    path: String,
+    eval: E,
+}
+
+impl<E: AcpiEvaluator> AcpiAc<E> {
+    /// Creates the AC adapter `name` (e.g. `AC`) of the device at `path`.
+    pub fn new(name: impl Into<String>, path: impl Into<String>, eval: E) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            eval,
+        }
+    }
+}
+
+impl<E: AcpiEvaluator> PowerSupply for AcpiAc<E> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supply_type(&self) -> SupplyType {
+        SupplyType::Mains
+    }
+
+    fn properties(&mut self) -> AxResult<Properties> {
+        let psr = self.eval.evaluate_integer(&self.path, "_PSR")?;
+        Ok(Properties {
+            online: Some(psr != 0),
+            ..Properties::EMPTY
+        })
+    }
+}
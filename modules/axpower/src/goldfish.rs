@@ -0,0 +1,146 @@
+//! The goldfish battery of the emulators, e.g. the Android emulator.
+//!
+//! The device has a battery and an AC adapter, whose states are in the 32-bit
+//! MMIO registers.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::ptr::NonNull;
+
+use axerrno::AxResult;
+
+use crate::{Health, PowerSupply, Properties, Status, SupplyType};
+
+const INT_STATUS: usize = 0x00;
+const INT_ENABLE: usize = 0x04;
+const AC_ONLINE: usize = 0x08;
+const STATUS: usize = 0x0c;
+const HEALTH: usize = 0x10;
+const PRESENT: usize = 0x14;
+const CAPACITY: usize = 0x18;
+const VOLTAGE: usize = 0x1c;
+const TEMP: usize = 0x20;
+const CHARGE_COUNTER: usize = 0x24;
+const CURRENT_NOW: usize = 0x30;
+const CHARGE_FULL_UAH: usize = 0x38;
+const CYCLE_COUNT: usize = 0x40;
+
+/// The registers of a goldfish battery.
+struct Regs {
+    base: NonNull<u8>,
+}
+
+unsafe impl Send for Regs {}
+unsafe impl Sync for Regs {}
+
+impl Regs {
+    fn read(&self, reg: usize) -> u32 {
+        unsafe { self.base.as_ptr().add(reg).cast::<u32>().read_volatile() }
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        unsafe {
+            self.base
+                .as_ptr()
+                .add(reg)
+                .cast::<u32>()
+                .write_volatile(value)
+        }
+    }
+}
+
+/// The battery of a goldfish battery device.
+pub struct GoldfishBattery {
+    regs: Arc<Regs>,
+}
+
+/// The AC adapter of a goldfish battery device.
+pub struct GoldfishAc {
+    regs: Arc<Regs>,
+}
+
+/// Probes the goldfish battery device whose registers are mapped at `base`,
+/// and returns its battery and AC adapter.
+///
+/// # Safety
+///
+/// `base` must be the virtual address of the registers of the device.
+pub unsafe fn probe(base: NonNull<u8>) -> (GoldfishBattery, GoldfishAc) {
+    let regs = Arc::new(Regs { base });
+    // the properties are polled, no interrupt is needed
+    regs.write(INT_ENABLE, 0);
+    let _ = regs.read(INT_STATUS);
+    (GoldfishBattery { regs: regs.clone() }, GoldfishAc { regs })
+}
+
+/// Probes the goldfish battery device whose registers are mapped at `base`,
+/// and registers its battery `battery` and AC adapter `ac`.
+///
+/// # Safety
+///
+/// `base` must be the virtual address of the registers of the device.
+pub unsafe fn init(base: NonNull<u8>) -> AxResult {
+    let (battery, ac) = unsafe { probe(base) };
+    crate::register(Box::new(battery))?;
+    crate::register(Box::new(ac))
+}
+
+impl PowerSupply for GoldfishBattery {
+    fn name(&self) -> &str {
+        "battery"
+    }
+
+    fn supply_type(&self) -> SupplyType {
+        SupplyType::Battery
+    }
+
+    fn properties(&mut self) -> AxResult<Properties> {
+        let regs = &self.regs;
+        let status = match regs.read(STATUS) {
+            1 => Status::Charging,
+            2 => Status::Discharging,
+            3 => Status::NotCharging,
+            4 => Status::Full,
+            _ => Status::Unknown,
+        };
+        let health = match regs.read(HEALTH) {
+            1 => Health::Good,
+            2 => Health::Overheat,
+            3 => Health::Dead,
+            4 => Health::OverVoltage,
+            5 => Health::Failure,
+            6 => Health::Cold,
+            _ => Health::Unknown,
+        };
+        Ok(Properties {
+            present: Some(regs.read(PRESENT) != 0),
+            status: Some(status),
+            health: Some(health),
+            capacity: Some(regs.read(CAPACITY).min(100) as u8),
+            voltage_now: Some(regs.read(VOLTAGE)),
+            current_now: Some(regs.read(CURRENT_NOW) as i32),
+            charge_now: Some(regs.read(CHARGE_COUNTER)),
+            charge_full: Some(regs.read(CHARGE_FULL_UAH)),
+            temp: Some(regs.read(TEMP) as i32),
+            cycle_count: Some(regs.read(CYCLE_COUNT)),
+            ..Properties::EMPTY
+        })
+    }
+}
+
+impl PowerSupply for GoldfishAc {
+    fn name(&self) -> &str {
+        "ac"
+    }
+
+    fn supply_type(&self) -> SupplyType {
+        SupplyType::Mains
+    }
+
+    fn properties(&mut self) -> AxResult<Properties> {
+        Ok(Properties {
+            online: Some(self.regs.read(AC_ONLINE) != 0),
+            ..Properties::EMPTY
+        })
+    }
+}
@@ -0,0 +1,300 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) power supply module.
+//!
+//! Batteries and AC adapters are registered as power supplies by
+//! [`register`], and their properties are read by [`properties`] and
+//! [`read_attribute`], in the units and the formats of the power supply class
+//! of Linux, so that they can be exported as the files in
+//! `/sys/class/power_supply/<name>`.
+//!
+//! The drivers of the supplies are:
+//!
+//! - [`goldfish`]: the goldfish battery of the emulators, e.g. the Android
+//!   emulator.
+//! - [`sbs`]: the Smart Battery System batteries on an SMBus (I2C).
+//! - [`acpi`]: the ACPI control method batteries, whose methods are evaluated
+//!   by the platform.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+pub mod acpi;
+pub mod goldfish;
+pub mod sbs;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use axerrno::{AxResult, ax_err};
+use axsync::Mutex;
+
+/// The type of a power supply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupplyType {
+    /// A battery.
+    Battery,
+    /// An AC adapter.
+    Mains,
+}
+
+impl SupplyType {
+    /// Returns the name of the type in the `type` file of Linux.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Battery => "Battery",
+            Self::Mains => "Mains",
+        }
+    }
+}
+
+/// The charging status of a battery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The status is unknown.
+    Unknown,
+    /// The battery is charging.
+    Charging,
+    /// The battery is discharging.
+    Discharging,
+    /// The battery is neither charging nor discharging, but not full.
+    NotCharging,
+    /// The battery is full.
+    Full,
+}
+
+impl Status {
+    /// Returns the name of the status in the `status` file of Linux.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Charging => "Charging",
+            Self::Discharging => "Discharging",
+            Self::NotCharging => "Not charging",
+            Self::Full => "Full",
+        }
+    }
+}
+
+/// The health of a battery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// The health is unknown.
+    Unknown,
+    /// The battery is good.
+    Good,
+    /// The battery is overheated.
+    Overheat,
+    /// The battery is dead.
+    Dead,
+    /// The voltage of the battery is too high.
+    OverVoltage,
+    /// The battery failed for other reasons.
+    Failure,
+    /// The battery is too cold.
+    Cold,
+}
+
+impl Health {
+    /// Returns the name of the health in the `health` file of Linux.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Good => "Good",
+            Self::Overheat => "Overheat",
+            Self::Dead => "Dead",
+            Self::OverVoltage => "Over voltage",
+            Self::Failure => "Unspecified failure",
+            Self::Cold => "Cold",
+        }
+    }
+}
+
+/// The properties of a power supply, `None` for the ones not supported.
+///
+/// The units are the same as Linux: µV, µA, µAh, µW, µWh, and 0.1 °C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Properties {
+    /// Whether the AC adapter is plugged in, only for the mains.
+    pub online: Option<bool>,
+    /// Whether the battery is present.
+    pub present: Option<bool>,
+    /// The charging status.
+    pub status: Option<Status>,
+    /// The health.
+    pub health: Option<Health>,
+    /// The remaining capacity in percent.
+    pub capacity: Option<u8>,
+    /// The voltage.
+    pub voltage_now: Option<u32>,
+    /// The current, positive when charging and negative when discharging.
+    pub current_now: Option<i32>,
+    /// The power, positive when charging and negative when discharging.
+    pub power_now: Option<i32>,
+    /// The remaining charge.
+    pub charge_now: Option<u32>,
+    /// The charge when the battery is full.
+    pub charge_full: Option<u32>,
+    /// The remaining energy.
+    pub energy_now: Option<u32>,
+    /// The energy when the battery is full.
+    pub energy_full: Option<u32>,
+    /// The temperature.
+    pub temp: Option<i32>,
+    /// The number of charge cycles.
+    pub cycle_count: Option<u32>,
+}
+
+impl Properties {
+    /// No property is supported.
+    pub const EMPTY: Self = Self {
+        online: None,
+        present: None,
+        status: None,
+        health: None,
+        capacity: None,
+        voltage_now: None,
+        current_now: None,
+        power_now: None,
+        charge_now: None,
+        charge_full: None,
+        energy_now: None,
+        energy_full: None,
+        temp: None,
+        cycle_count: None,
+    };
+
+    /// Returns the value of the attribute `attr` as the content of its file
+    /// in Linux without the newline, or `None` if it's not supported.
+    pub fn attribute(&self, attr: &str) -> Option<String> {
+        fn num(v: Option<impl ToString>) -> Option<String> {
+            v.map(|v| v.to_string())
+        }
+        match attr {
+            "online" => num(self.online.map(u8::from)),
+            "present" => num(self.present.map(u8::from)),
+            "status" => self.status.map(|s| s.as_str().into()),
+            "health" => self.health.map(|h| h.as_str().into()),
+            "capacity" => num(self.capacity),
+            "voltage_now" => num(self.voltage_now),
+            "current_now" => num(self.current_now),
+            "power_now" => num(self.power_now),
+            "charge_now" => num(self.charge_now),
+            "charge_full" => num(self.charge_full),
+            "energy_now" => num(self.energy_now),
+            "energy_full" => num(self.energy_full),
+            "temp" => num(self.temp),
+            "cycle_count" => num(self.cycle_count),
+            _ => None,
+        }
+    }
+}
+
+/// The attributes of the properties, which are the names of their files.
+pub const ATTRIBUTES: &[&str] = &[
+    "online",
+    "present",
+    "status",
+    "health",
+    "capacity",
+    "voltage_now",
+    "current_now",
+    "power_now",
+    "charge_now",
+    "charge_full",
+    "energy_now",
+    "energy_full",
+    "temp",
+    "cycle_count",
+];
+
+/// A driver of a power supply.
+pub trait PowerSupply: Send {
+    /// The name of the supply, e.g. `BAT0`.
+    fn name(&self) -> &str;
+
+    /// The type of the supply.
+    fn supply_type(&self) -> SupplyType;
+
+    /// Reads the properties from the hardware.
+    fn properties(&mut self) -> AxResult<Properties>;
+}
+
+struct Supply {
+    name: String,
+    supply_type: SupplyType,
+    driver: Mutex<Box<dyn PowerSupply>>,
+}
+
+static SUPPLIES: Mutex<Vec<Arc<Supply>>> = Mutex::new(Vec::new());
+
+fn find(name: &str) -> AxResult<Arc<Supply>> {
+    let supplies = SUPPLIES.lock();
+    match supplies.iter().find(|s| s.name == name) {
+        Some(supply) => Ok(supply.clone()),
+        None => ax_err!(NotFound),
+    }
+}
+
+/// Registers a power supply, whose name must be unique.
+pub fn register(driver: Box<dyn PowerSupply>) -> AxResult {
+    let name = driver.name().to_string();
+    let mut supplies = SUPPLIES.lock();
+    if supplies.iter().any(|s| s.name == name) {
+        return ax_err!(AlreadyExists, "power supply already registered");
+    }
+    info!("power supply {}: {}", name, driver.supply_type().as_str());
+    supplies.push(Arc::new(Supply {
+        name,
+        supply_type: driver.supply_type(),
+        driver: Mutex::new(driver),
+    }));
+    Ok(())
+}
+
+/// Returns the names of all power supplies, in the order they are registered.
+pub fn supplies() -> Vec<String> {
+    SUPPLIES.lock().iter().map(|s| s.name.clone()).collect()
+}
+
+/// Returns the type of the power supply `name`.
+pub fn supply_type(name: &str) -> AxResult<SupplyType> {
+    Ok(find(name)?.supply_type)
+}
+
+/// Reads the properties of the power supply `name`.
+pub fn properties(name: &str) -> AxResult<Properties> {
+    find(name)?.driver.lock().properties()
+}
+
+/// Reads the attribute `attr` of the power supply `name`, formatted as the
+/// content of its file in `/sys/class/power_supply/<name>`.
+///
+/// Besides the ones in [`ATTRIBUTES`], `type` is the type of the supply, and
+/// `uevent` is all the properties supported as `POWER_SUPPLY_*` variables.
+pub fn read_attribute(name: &str, attr: &str) -> AxResult<String> {
+    let supply = find(name)?;
+    if attr == "type" {
+        return Ok(format!("{}\n", supply.supply_type.as_str()));
+    }
+    let props = supply.driver.lock().properties()?;
+    if attr == "uevent" {
+        let mut out = format!("POWER_SUPPLY_NAME={}\n", name);
+        let _ = writeln!(out, "POWER_SUPPLY_TYPE={}", supply.supply_type.as_str());
+        for attr in ATTRIBUTES {
+            if let Some(value) = props.attribute(attr) {
+                let _ = writeln!(out, "POWER_SUPPLY_{}={}", attr.to_uppercase(), value);
+            }
+        }
+        return Ok(out);
+    }
+    match props.attribute(attr) {
+        Some(value) => Ok(value + "\n"),
+        None => ax_err!(Unsupported),
+    }
+}
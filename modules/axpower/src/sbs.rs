@@ -0,0 +1,105 @@
+//! The Smart Battery System (SBS) batteries, which are read by the SMBus
+//! commands of the Smart Battery Data Specification.
+
+use alloc::string::String;
+
+use axerrno::AxResult;
+
+use crate::{PowerSupply, Properties, Status, SupplyType};
+
+/// The SMBus address of a smart battery.
+pub const SBS_ADDR: u8 = 0x0b;
+
+const TEMPERATURE: u8 = 0x08;
+const VOLTAGE: u8 = 0x09;
+const CURRENT: u8 = 0x0a;
+const RELATIVE_STATE_OF_CHARGE: u8 = 0x0d;
+const REMAINING_CAPACITY: u8 = 0x0f;
+const FULL_CHARGE_CAPACITY: u8 = 0x10;
+const BATTERY_STATUS: u8 = 0x16;
+const CYCLE_COUNT: u8 = 0x17;
+
+const STATUS_DISCHARGING: u16 = 1 << 6;
+const STATUS_FULLY_CHARGED: u16 = 1 << 5;
+
+/// An SMBus (I2C) controller, to be implemented by the platform.
+pub trait SmbusOps: Send {
+    /// Reads the word of the command `cmd` from the device at `addr`.
+    fn read_word(&mut self, addr: u8, cmd: u8) -> AxResult<u16>;
+}
+
+/// A smart battery on an SMBus.
+///
+/// The capacities are read in mAh, the default `CAPACITY_MODE` of the
+/// `BatteryMode` of the battery.
+pub struct SbsBattery<B: SmbusOps> {
+    name: String,
+    bus: B,
+    addr: u8,
+}
+
+impl<B: SmbusOps> SbsBattery<B> {
+    /// Creates the smart battery `name` at the default address of `bus`.
+    pub fn new(name: impl Into<String>, bus: B) -> Self {
+        Self::with_addr(name, bus, SBS_ADDR)
+    }
+
+    /// Creates the smart battery `name` at `addr` of `bus`, e.g. a battery
+    /// behind a selector.
+    pub fn with_addr(name: impl Into<String>, bus: B, addr: u8) -> Self {
+        Self {
+            name: name.into(),
+            bus,
+            addr,
+        }
+    }
+
+    fn read(&mut self, cmd: u8) -> AxResult<u16> {
+        self.bus.read_word(self.addr, cmd)
+    }
+}
+
+impl<B: SmbusOps> PowerSupply for SbsBattery<B> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supply_type(&self) -> SupplyType {
+        SupplyType::Battery
+    }
+
+    fn properties(&mut self) -> AxResult<Properties> {
+        // a missing battery doesn't respond
+        let Ok(status) = self.read(BATTERY_STATUS) else {
+            return Ok(Properties {
+                present: Some(false),
+                ..Properties::EMPTY
+            });
+        };
+        // the current is signed, positive when charging
+        let current = self.read(CURRENT)? as i16 as i32;
+        let status = if status & STATUS_FULLY_CHARGED != 0 {
+            Status::Full
+        } else if status & STATUS_DISCHARGING != 0 {
+            Status::Discharging
+        } else if current > 0 {
+            Status::Charging
+        } else {
+            Status::NotCharging
+        };
+        // the temperature is in 0.1 K
+        let temp = self.read(TEMPERATURE)? as i32 - 2731;
+        Ok(Properties {
+            present: Some(true),
+            status: Some(status),
+            capacity: Some(self.read(RELATIVE_STATE_OF_CHARGE)?.min(100) as u8),
+            voltage_now: Some(self.read(VOLTAGE)? as u32 * 1000),
+            current_now: Some(current * 1000),
+            charge_now: Some(self.read(REMAINING_CAPACITY)? as u32 * 1000),
+            charge_full: Some(self.read(FULL_CHARGE_CAPACITY)? as u32 * 1000),
+            temp: Some(temp),
+            cycle_count: Some(self.read(CYCLE_COUNT)? as u32),
+            ..Properties::EMPTY
+        })
+    }
+}
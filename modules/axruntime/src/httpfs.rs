@@ -0,0 +1,16 @@
+//! The HTTP server whose files are mounted at boot.
+
+/// The mount point of the files.
+const MOUNT_PATH: &str = "/mnt/http";
+
+/// Mounts the files under the URL set by `AX_HTTPFS` on `/mnt/http`, if any.
+pub(crate) fn mount() {
+    let url = option_env!("AX_HTTPFS").unwrap_or("");
+    if url.is_empty() {
+        return;
+    }
+    info!("Mount the files under {} on {}...", url, MOUNT_PATH);
+    if let Err(e) = axfs::api::mount_http(url, MOUNT_PATH) {
+        warn!("failed to mount the files under {}: {:?}", url, e);
+    }
+}
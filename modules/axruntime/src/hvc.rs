@@ -0,0 +1,35 @@
+//! The character devices as the hvc ports of the console.
+
+use alloc::{boxed::Box, string::String};
+
+use axdriver::{AxCharDevice, AxDeviceContainer, prelude::*};
+use axhal::console::{ConsoleBackend, HvcPort};
+
+struct CharDevicePort(AxCharDevice);
+
+impl HvcPort for CharDevicePort {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.write_bytes(bytes).ok();
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> usize {
+        self.0.read_bytes(bytes).unwrap_or(0)
+    }
+}
+
+/// Registers the character devices as `hvc0`, `hvc1`, ..., and switches the
+/// console to `hvc0` if there is any.
+pub(crate) fn init_hvc(mut char_devs: AxDeviceContainer<AxCharDevice>) {
+    while let Some(dev) = char_devs.take_one() {
+        let name = String::from(dev.device_name());
+        let port = Box::leak(Box::new(CharDevicePort(dev)));
+        match axhal::console::register_hvc_port(port) {
+            Some(idx) => info!("  hvc{}: {}", idx, name),
+            None => warn!("too many hvc ports, {} is ignored", name),
+        }
+    }
+    if axhal::console::num_hvc_ports() > 0 {
+        info!("switch the console to hvc0");
+        axhal::console::set_backend(ConsoleBackend::Hvc(0));
+    }
+}
@@ -3,5 +3,10 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     error!("{}", info);
+    #[cfg(feature = "ksyms")]
+    {
+        error!("Backtrace:");
+        axhal::ksyms::backtrace(|addr| error!("  {}", axhal::ksyms::symbolize(addr)));
+    }
     axhal::misc::terminate()
 }
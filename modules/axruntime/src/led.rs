@@ -0,0 +1,98 @@
+//! The LEDs of the platform, driven by the timer interrupt, whose attributes
+//! are exported to `/sys/class/leds` if `fs` is enabled.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use core::ptr::NonNull;
+
+use axhal::mem::{PhysAddr, phys_to_virt};
+use axled::Trigger;
+use axled::gpio::{GpioLed, Pl061};
+use kspin::SpinNoIrq;
+
+/// The triggers of the LEDs in the platform config, in order.
+const TRIGGERS: [Trigger; 3] = [Trigger::Heartbeat, Trigger::Disk, Trigger::Netdev];
+
+/// Probes the LEDs of the platform, and exports their attributes.
+pub(crate) fn init() {
+    if axconfig::devices::LED_GPIO_PADDR != 0 {
+        let paddr = PhysAddr::from(axconfig::devices::LED_GPIO_PADDR);
+        let base = NonNull::new(phys_to_virt(paddr).as_mut_ptr()).unwrap();
+        // the address is in the MMIO regions, which are mapped
+        let gpio = Arc::new(SpinNoIrq::new(unsafe { Pl061::new(base) }));
+        for (i, &(pin, active_low)) in axconfig::devices::LED_GPIO_PINS.iter().enumerate() {
+            let led = GpioLed::new(format!("led{}", i), gpio.clone(), pin as _, active_low != 0);
+            let trigger = TRIGGERS.get(i).copied().unwrap_or(Trigger::None);
+            if let Err(e) = axled::register(Box::new(led), trigger) {
+                warn!("failed to register led{}: {:?}", i, e);
+            }
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    axled::set_activity_source(axled::Activity::Disk, axfs::io_count);
+    #[cfg(feature = "net")]
+    axled::set_activity_source(axled::Activity::Netdev, axnet::frame_count);
+
+    #[cfg(feature = "fs")]
+    for name in axled::leds() {
+        export(name);
+    }
+}
+
+/// Exports the attributes `brightness`, `max_brightness` and `trigger` of the
+/// LED `name`.
+#[cfg(feature = "fs")]
+fn export(name: alloc::string::String) {
+    use alloc::string::String;
+    use axerrno::ax_err;
+
+    let dir = format!("class/leds/{}", name);
+    let (led_r, led_w) = (name.clone(), name.clone());
+    axfs::add_sys_attr(
+        &format!("{}/brightness", dir),
+        move || format!("{}\n", axled::brightness(&led_r).unwrap_or(0)),
+        move |s| match s.trim().parse() {
+            Ok(brightness) => axled::set_brightness(&led_w, brightness),
+            Err(_) => ax_err!(InvalidInput),
+        },
+    );
+
+    let led = name.clone();
+    axfs::add_sys_file(&format!("{}/max_brightness", dir), move || {
+        format!("{}\n", axled::max_brightness(&led).unwrap_or(0))
+    });
+
+    let (led_r, led_w) = (name.clone(), name);
+    axfs::add_sys_attr(
+        &format!("{}/trigger", dir),
+        // all the triggers, with the current one in brackets
+        move || {
+            let current = axled::trigger(&led_r).ok();
+            let mut out = String::new();
+            for t in Trigger::ALL {
+                if !out.is_empty() {
+                    out.push(' ');
+                }
+                if Some(t) == current {
+                    out += &format!("[{}]", t.as_str());
+                } else {
+                    out += t.as_str();
+                }
+            }
+            out + "\n"
+        },
+        move |s| match Trigger::from_name(s.trim()) {
+            Some(trigger) => axled::set_trigger(&led_w, trigger),
+            None => ax_err!(InvalidInput),
+        },
+    );
+}
+
+/// Drives the triggers of the LEDs, called by the timer interrupt.
+pub(crate) fn on_timer_tick() {
+    if axhal::cpu::this_cpu_is_bsp() {
+        axled::update(axhal::time::monotonic_time());
+    }
+}
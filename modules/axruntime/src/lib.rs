@@ -9,10 +9,45 @@
 //! - `paging`: Enable page table manipulation support.
 //! - `irq`: Enable interrupt handling support.
 //! - `multitask`: Enable multi-threading support.
+//! - `sched_trace`: Enable scheduler tracing, the trace is exported to
+//!   `/proc/sched_trace` in the Chrome trace event format if `fs` is enabled.
+//! - `mcount`: Record the call graph of the modules built with the `mcount`
+//!   instrumentation (`MCOUNT=y`), exported to `/proc/mcount` if `fs` is
+//!   enabled.
+//! - `ksyms`: Embed the symbol table in the kernel image, to show the
+//!   backtraces of the panics and the addresses in `/proc/mcount` by the
+//!   function names.
+//! - `qos`: Enable the partitioning of the caches and the memory bandwidth
+//!   between the classes of service of the tasks.
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
-//! - `fs`: Enable filesystem support.
+//! - `fs`: Enable filesystem support. The dirty blocks of the disk are written
+//!   back periodically if `multitask` is enabled.
+//! - `fs-irq`: Complete the requests of the disk by its interrupt.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
+//! - `hvc`: Use the virtio-console devices as the hvc ports, and the first
+//!   one as the console.
+//! - `input`: Enable input devices support, e.g. the keyboards.
+//! - `rng`: Seed the kernel entropy pool by the hardware random number
+//!   generator, e.g. a virtio-rng device.
+//! - `power`: Enable the power supplies, e.g. the batteries, and export them
+//!   to `/sys/class/power_supply` if `fs` is enabled.
+//! - `led`: Enable the LEDs of the platform, driven by the heartbeat, disk
+//!   and network triggers, and export them to `/sys/class/leds` if `fs` is
+//!   enabled.
+//! - `clk`: Probe the clock and reset controllers in the device tree, for
+//!   the drivers to enable the clocks of their devices.
+//! - `pinctrl`: Probe the pin controllers in the device tree, and route the
+//!   pins of the devices by their `default` states.
+//! - `mbox`: Probe the mailboxes in the device tree, and the RPMsg devices to
+//!   talk to the firmware of the co-processors.
+//! - `ivshmem`: Use the inter-VM shared memory devices as byte streams to the
+//!   host or other VMs, and register them in `/dev` if `fs` is enabled.
+//! - `host`: Run in a user-space process of the Linux host, initialized by
+//!   [`init_host`] instead of booting. The kernel page table is not created
+//!   even if `paging` is enabled.
+//! - `vtime`: Use the virtual clock advanced by the tests, the idle task
+//!   skips to the next timer event.
 //!
 //! All the features are optional and disabled by default.
 
@@ -22,12 +57,51 @@
 #[macro_use]
 extern crate axlog;
 
+#[cfg(any(
+    feature = "fs",
+    feature = "hvc",
+    feature = "rng",
+    feature = "power",
+    feature = "led"
+))]
+extern crate alloc;
+
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
 
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "fs")]
+mod procfs;
+
+#[cfg(feature = "mcount")]
+mod mcount;
+
+#[cfg(feature = "hvc")]
+mod hvc;
+
+#[cfg(feature = "rng")]
+mod rng;
+
+#[cfg(feature = "nbd")]
+mod nbd;
+
+#[cfg(feature = "nfs")]
+mod nfs;
+
+#[cfg(feature = "httpfs")]
+mod httpfs;
+
+#[cfg(feature = "oci")]
+mod oci;
+
+#[cfg(feature = "power")]
+mod power;
+
+#[cfg(feature = "led")]
+mod led;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
@@ -100,7 +174,7 @@ fn is_init_ok() -> bool {
 ///
 /// In multi-core environment, this function is called on the primary CPU,
 /// and the secondary CPUs call [`rust_main_secondary`].
-#[cfg_attr(not(test), unsafe(no_mangle))]
+#[cfg_attr(not(any(test, feature = "host")), unsafe(no_mangle))]
 pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     ax_println!("{}", LOGO);
     ax_println!(
@@ -125,10 +199,46 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         chrono::DateTime::from_timestamp_nanos(axhal::time::wall_time_nanos() as _),
     );
 
+    init_primary(cpu_id, dtb);
+
+    unsafe { main() };
+
+    // the blocks written are cached, write them back before terminating
+    #[cfg(feature = "fs")]
+    axfs::api::sync().ok();
+
+    #[cfg(feature = "multitask")]
+    axtask::exit(0);
+    #[cfg(not(feature = "multitask"))]
+    {
+        debug!("main task exited: exit_code={}", 0);
+        axhal::misc::terminate();
+    }
+}
+
+/// Initializes the runtime in a user-space process of the Linux host (see
+/// the `linux-host` platform of [axhal]), with the calling thread as the
+/// primary CPU.
+///
+/// It does the same initialization work as [`rust_main`], but returns to the
+/// caller instead of calling the application's `main` function, so the
+/// kernel can be driven by the host program afterwards. The calling thread
+/// becomes the main task, so the kernel must be called only by it. It must
+/// be called only once.
+#[cfg(feature = "host")]
+pub fn init_host() {
+    axhal::init_early();
+    init_primary(0, 0);
+}
+
+/// Initializes all the enabled modules on the primary CPU, and waits for the
+/// secondary CPUs.
+fn init_primary(cpu_id: usize, dtb: usize) {
     axlog::init();
     axlog::set_max_level(option_env!("AX_LOG").unwrap_or("")); // no effect if set `log-level-*` features
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
+    axhal::dtb::init(dtb);
 
     info!("Found physcial memory regions:");
     for r in axhal::mem::memory_regions() {
@@ -144,30 +254,98 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(feature = "alloc")]
     init_allocator();
 
-    #[cfg(feature = "paging")]
+    // the page tables of a host process are not managed by the kernel
+    #[cfg(all(feature = "paging", not(feature = "host")))]
     axmm::init_memory_management();
 
     info!("Initialize platform devices...");
     axhal::platform_init();
+    axhal::cpu::mark_online();
 
     #[cfg(feature = "multitask")]
     axtask::init_scheduler();
 
-    #[cfg(any(feature = "fs", feature = "net", feature = "display"))]
+    #[cfg(feature = "clk")]
+    axclk::init();
+
+    #[cfg(feature = "pinctrl")]
+    axpinctrl::init();
+
+    #[cfg(feature = "mbox")]
+    axmbox::init();
+
+    #[cfg(any(
+        feature = "fs",
+        feature = "net",
+        feature = "display",
+        feature = "input",
+        feature = "uio",
+        feature = "ivshmem",
+        feature = "hvc",
+        feature = "rng"
+    ))]
     {
         #[allow(unused_variables)]
         let all_devices = axdriver::init_drivers();
 
+        #[cfg(feature = "hvc")]
+        self::hvc::init_hvc(all_devices.char);
+
+        #[cfg(feature = "rng")]
+        self::rng::init_rng(all_devices.rng);
+
+        // the network block device, the NFS exports and the HTTP servers are
+        // reached through the network
+        #[cfg(any(feature = "nbd", feature = "nfs", feature = "httpfs"))]
+        axnet::init_network(all_devices.net, all_devices.net_irq);
+
         #[cfg(feature = "fs")]
-        axfs::init_filesystems(all_devices.block);
+        {
+            #[cfg(feature = "nbd")]
+            let block_devs = self::nbd::root_device(all_devices.block);
+            #[cfg(not(feature = "nbd"))]
+            let block_devs = all_devices.block;
+            axfs::init_filesystems(block_devs);
+            #[cfg(feature = "fs-irq")]
+            if let Some(irq) = all_devices.block_irq {
+                axfs::init_block_irq(irq);
+            }
+            self::procfs::init();
+            #[cfg(feature = "ninep")]
+            axfs::init_shared_folders(all_devices.ninep);
+            #[cfg(feature = "nfs")]
+            self::nfs::mount();
+            #[cfg(feature = "httpfs")]
+            self::httpfs::mount();
+            #[cfg(feature = "oci")]
+            self::oci::unpack();
+        }
 
-        #[cfg(feature = "net")]
-        axnet::init_network(all_devices.net);
+        #[cfg(all(
+            feature = "net",
+            not(any(feature = "nbd", feature = "nfs", feature = "httpfs"))
+        ))]
+        axnet::init_network(all_devices.net, all_devices.net_irq);
 
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
+
+        #[cfg(feature = "input")]
+        axinput::init_input(all_devices.input);
+
+        #[cfg(feature = "uio")]
+        axuio::init_uio(all_devices.uio);
+
+        #[cfg(feature = "ivshmem")]
+        axivshmem::init(all_devices.ivshmem);
     }
 
+    #[cfg(feature = "power")]
+    self::power::init();
+
+    #[cfg(feature = "led")]
+    self::led::init();
+
     #[cfg(feature = "smp")]
     self::mp::start_secondary_cpus(cpu_id);
 
@@ -183,23 +361,15 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         init_tls();
     }
 
+    // the constructors of a host process are called by the loader
+    #[cfg(not(feature = "host"))]
     ctor_bare::call_ctors();
 
     info!("Primary CPU {} init OK.", cpu_id);
     INITED_CPUS.fetch_add(1, Ordering::Relaxed);
 
     while !is_init_ok() {
-        core::hint::spin_loop();
-    }
-
-    unsafe { main() };
-
-    #[cfg(feature = "multitask")]
-    axtask::exit(0);
-    #[cfg(not(feature = "multitask"))]
-    {
-        debug!("main task exited: exit_code={}", 0);
-        axhal::misc::terminate();
+        axhal::arch::cpu_relax();
     }
 }
 
@@ -240,24 +410,49 @@ fn init_interrupt() {
     const PERIODIC_INTERVAL_NANOS: u64 =
         axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
 
+    /// The deadline of the next periodic tick.
     #[percpu::def_percpu]
     static NEXT_DEADLINE: u64 = 0;
 
-    fn update_timer() {
+    /// Advances the deadline of the next periodic tick if it has passed,
+    /// returns whether it has, otherwise the interrupt is triggered by an
+    /// earlier timer event.
+    fn update_timer() -> bool {
         let now_ns = axhal::time::monotonic_time_nanos();
         // Safety: we have disabled preemption in IRQ handler.
-        let mut deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
-        if now_ns >= deadline {
-            deadline = now_ns + PERIODIC_INTERVAL_NANOS;
+        let deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
+        if now_ns < deadline {
+            return false;
         }
-        unsafe { NEXT_DEADLINE.write_current_raw(deadline + PERIODIC_INTERVAL_NANOS) };
-        axhal::time::set_oneshot_timer(deadline);
+        let mut next = deadline + PERIODIC_INTERVAL_NANOS;
+        if now_ns >= next {
+            next = now_ns + PERIODIC_INTERVAL_NANOS;
+        }
+        unsafe { NEXT_DEADLINE.write_current_raw(next) };
+        true
     }
 
     axhal::irq::register_handler(TIMER_IRQ_NUM, || {
-        update_timer();
+        #[cfg_attr(
+            not(any(feature = "multitask", feature = "led")),
+            allow(unused_variables)
+        )]
+        let tick = update_timer();
+        #[cfg(feature = "multitask")]
+        if tick {
+            axtask::on_timer_tick();
+        } else {
+            axtask::on_timer_event();
+        }
+        #[cfg(feature = "led")]
+        if tick {
+            self::led::on_timer_tick();
+        }
+        let deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
         #[cfg(feature = "multitask")]
-        axtask::on_timer_tick();
+        axtask::program_timer(deadline);
+        #[cfg(not(feature = "multitask"))]
+        axhal::time::set_oneshot_timer(deadline);
     });
 
     // Enable IRQs before starting app
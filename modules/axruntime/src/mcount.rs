@@ -0,0 +1,198 @@
+//! Call graph profiling with the `mcount` instrumentation of the compiler.
+//!
+//! With `-Z instrument-mcount`, every function of the instrumented crates
+//! calls `mcount` at its entry, after its frame has been set up. The stub
+//! below reads the return address of the function from its frame, i.e. the
+//! call site in the caller, and the return address of `mcount`, i.e. a
+//! location in the callee, and records the pair as an arc of the call graph.
+//!
+//! This crate is not instrumented (see `MCOUNT` in the top-level Makefile),
+//! and the recorder does not call any other function, so that `mcount`
+//! never recurses. The arcs are kept in a fixed-size lock-free hash table,
+//! the interrupt handlers and other CPUs may record them concurrently.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(feature = "fs")]
+use alloc::{collections::BTreeMap, format, string::String};
+#[cfg(feature = "fs")]
+use core::fmt::Write;
+
+#[cfg(feature = "fs")]
+use axerrno::{AxError, AxResult};
+
+/// The maximum number of distinct arcs that can be recorded.
+const MAX_ARCS: usize = 1 << 13;
+
+/// The number of slots to probe before giving up recording an arc.
+const MAX_PROBES: usize = 16;
+
+struct CallArc {
+    /// The call site in the caller.
+    from: AtomicUsize,
+    /// The location in the callee, `0` if the slot is free.
+    to: AtomicUsize,
+    count: AtomicU64,
+}
+
+impl CallArc {
+    const fn new() -> Self {
+        Self {
+            from: AtomicUsize::new(0),
+            to: AtomicUsize::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+static ARCS: [CallArc; MAX_ARCS] = [const { CallArc::new() }; MAX_ARCS];
+
+/// Whether the calls are being recorded, from the boot by default.
+static RECORDING: AtomicBool = AtomicBool::new(true);
+
+/// The number of calls not recorded because the table is full.
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    ".globl mcount, _mcount",
+    "mcount:",
+    "_mcount:",
+    "mov rdi, [rbp + 8]",
+    "mov rsi, [rsp]",
+    "jmp {record}",
+    record = sym mcount_record,
+);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".globl mcount, _mcount",
+    "mcount:",
+    "_mcount:",
+    "ldr x0, [x29, #8]",
+    "mov x1, x30",
+    "b {record}",
+    record = sym mcount_record,
+);
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".globl mcount, _mcount",
+    "mcount:",
+    "_mcount:",
+    "ld a0, -8(s0)",
+    "mv a1, ra",
+    "j {record}",
+    record = sym mcount_record,
+);
+
+#[cfg(target_arch = "loongarch64")]
+core::arch::global_asm!(
+    ".globl mcount, _mcount",
+    "mcount:",
+    "_mcount:",
+    "ld.d $a0, $fp, -8",
+    "move $a1, $ra",
+    "b {record}",
+    record = sym mcount_record,
+);
+
+/// Records a call from the call site `from` to the function that `to` is in.
+extern "C" fn mcount_record(from: usize, to: usize) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    let hash = (from ^ to.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    let start = hash >> (usize::BITS - MAX_ARCS.trailing_zeros());
+    for i in 0..MAX_PROBES {
+        let arc = &ARCS[(start + i) % MAX_ARCS];
+        let mut arc_to = arc.to.load(Ordering::Acquire);
+        if arc_to == 0 {
+            match arc
+                .to
+                .compare_exchange(0, to, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    arc.from.store(from, Ordering::Release);
+                    arc.count.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                Err(current) => arc_to = current,
+            }
+        }
+        // A slot just claimed by others may not have its `from` yet, then the
+        // same arc is recorded in another slot, and merged when read.
+        if arc_to == to && arc.from.load(Ordering::Acquire) == from {
+            arc.count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+    DROPPED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Clears all the recorded arcs.
+///
+/// The calls being recorded at the same time on other CPUs may be lost or
+/// left in the table.
+#[cfg(feature = "fs")]
+fn clear() {
+    for arc in &ARCS {
+        arc.to.store(0, Ordering::Relaxed);
+        arc.from.store(0, Ordering::Relaxed);
+        arc.count.store(0, Ordering::Relaxed);
+    }
+    DROPPED.store(0, Ordering::Relaxed);
+}
+
+/// Lists the arcs of the call graph, one per line as `<from> <to> <count>`,
+/// where `<from>` is the call site in the caller and `<to>` is a location
+/// near the entry of the callee.
+///
+/// With the `ksyms` feature, the caller and the callee are resolved by the
+/// symbol table and appended as `<caller>+<offset> -> <callee>`, otherwise
+/// they can be resolved by the symbols of the kernel image on the host (e.g.
+/// with `addr2line -f`).
+#[cfg(feature = "fs")]
+pub(crate) fn gen_mcount() -> String {
+    let mut arcs = BTreeMap::new();
+    for arc in &ARCS {
+        let to = arc.to.load(Ordering::Acquire);
+        let from = arc.from.load(Ordering::Acquire);
+        if to != 0 && from != 0 {
+            *arcs.entry((from, to)).or_insert(0) += arc.count.load(Ordering::Relaxed);
+        }
+    }
+    let mut out = format!("# dropped {}\n", DROPPED.load(Ordering::Relaxed));
+    for ((from, to), count) in arcs {
+        write!(out, "{from:#x} {to:#x} {count}").ok();
+        if let (Some((caller, offset)), Some((callee, _))) =
+            (axhal::ksyms::lookup(from), axhal::ksyms::lookup(to))
+        {
+            write!(out, " {caller}+{offset:#x} -> {callee}").ok();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(feature = "fs")]
+pub(crate) fn gen_recording() -> String {
+    format!("{}\n", RECORDING.load(Ordering::Relaxed) as u8)
+}
+
+/// Stops recording with `0`, or starts a new recording with `1`, which
+/// clears the arcs recorded before.
+#[cfg(feature = "fs")]
+pub(crate) fn set_recording(value: &str) -> AxResult {
+    match value.trim() {
+        "0" => RECORDING.store(false, Ordering::Relaxed),
+        "1" => {
+            if !RECORDING.load(Ordering::Relaxed) {
+                clear();
+                RECORDING.store(true, Ordering::Relaxed);
+            }
+        }
+        _ => return Err(AxError::InvalidInput),
+    }
+    Ok(())
+}
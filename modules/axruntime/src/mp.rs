@@ -22,7 +22,7 @@ pub fn start_secondary_cpus(primary_cpu_id: usize) {
             logic_cpu_id += 1;
 
             while ENTERED_CPUS.load(Ordering::Acquire) <= logic_cpu_id {
-                core::hint::spin_loop();
+                axhal::arch::cpu_relax();
             }
         }
     }
@@ -40,6 +40,7 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
     axmm::init_memory_management_secondary();
 
     axhal::platform_init_secondary();
+    axhal::cpu::mark_online();
 
     #[cfg(feature = "multitask")]
     axtask::init_scheduler_secondary();
@@ -48,7 +49,7 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
     super::INITED_CPUS.fetch_add(1, Ordering::Relaxed);
 
     while !super::is_init_ok() {
-        core::hint::spin_loop();
+        axhal::arch::cpu_relax();
     }
 
     #[cfg(feature = "irq")]
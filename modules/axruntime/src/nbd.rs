@@ -0,0 +1,44 @@
+//! The network block device as the root disk of the diskless nodes.
+
+use alloc::boxed::Box;
+use core::net::{IpAddr, SocketAddr};
+
+use axdriver::{AxBlockDevice, AxDeviceContainer};
+use axnbd::NbdDevice;
+
+/// Parses the NBD server and the export in `AX_NBD`, in the format of
+/// `<ip>[:<port>][/<export>]`.
+fn parse_target(target: &str) -> Option<(SocketAddr, &str)> {
+    let (addr, export) = target.split_once('/').unwrap_or((target, ""));
+    let addr = match addr.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => SocketAddr::new(addr.parse::<IpAddr>().ok()?, axnbd::DEFAULT_PORT),
+    };
+    Some((addr, export))
+}
+
+/// Returns the block devices found, or the export set by `AX_NBD` if there
+/// is none.
+pub(crate) fn root_device(
+    block_devs: AxDeviceContainer<AxBlockDevice>,
+) -> AxDeviceContainer<AxBlockDevice> {
+    let target = option_env!("AX_NBD").unwrap_or("");
+    if !block_devs.is_empty() || target.is_empty() {
+        return block_devs;
+    }
+    let Some((addr, export)) = parse_target(target) else {
+        warn!("invalid AX_NBD {:?}", target);
+        return block_devs;
+    };
+    info!(
+        "Connect to the network block device {} {:?}...",
+        addr, export
+    );
+    match NbdDevice::connect(addr, export) {
+        Ok(dev) => AxDeviceContainer::from_one(Box::new(dev)),
+        Err(e) => {
+            warn!("failed to connect to the network block device: {:?}", e);
+            block_devs
+        }
+    }
+}
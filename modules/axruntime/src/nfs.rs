@@ -0,0 +1,44 @@
+//! The NFS export mounted at boot.
+
+use core::net::IpAddr;
+
+use axfs::NfsTransport;
+
+/// The mount point of the export.
+const MOUNT_PATH: &str = "/mnt/nfs";
+
+/// Parses the server, the export and the transport in `AX_NFS`, in the format
+/// of `<ip>:<export>[,udp|,tcp]`.
+fn parse_source(source: &str) -> Option<(IpAddr, &str, NfsTransport)> {
+    let (source, transport) = match source.rsplit_once(',') {
+        Some((source, "udp")) => (source, NfsTransport::Udp),
+        Some((source, "tcp")) => (source, NfsTransport::Tcp),
+        Some(_) => return None,
+        None => (source, NfsTransport::Tcp),
+    };
+    // the IPv6 addresses are in brackets, as they contain colons
+    let (server, export) = match source.strip_prefix('[') {
+        Some(rest) => rest.split_once("]:")?,
+        None => source.split_once(':')?,
+    };
+    Some((server.parse().ok()?, export, transport))
+}
+
+/// Mounts the export set by `AX_NFS` on `/mnt/nfs`, if any.
+pub(crate) fn mount() {
+    let source = option_env!("AX_NFS").unwrap_or("");
+    if source.is_empty() {
+        return;
+    }
+    let Some((server, export, transport)) = parse_source(source) else {
+        warn!("invalid AX_NFS {:?}", source);
+        return;
+    };
+    info!(
+        "Mount the NFS export {}:{} on {} by {:?}...",
+        server, export, MOUNT_PATH, transport
+    );
+    if let Err(e) = axfs::api::mount_nfs(server, export, MOUNT_PATH, transport) {
+        warn!("failed to mount the NFS export: {:?}", e);
+    }
+}
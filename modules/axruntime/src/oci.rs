@@ -0,0 +1,23 @@
+//! The container image unpacked as the root filesystem at boot.
+
+/// The directory the image is unpacked to.
+const TARGET_PATH: &str = "/";
+
+/// Unpacks the image set by `AX_OCI` to `/`, if any, in the format of
+/// `<layout-dir>[:<tag>]`.
+pub(crate) fn unpack() {
+    let source = option_env!("AX_OCI").unwrap_or("");
+    if source.is_empty() {
+        return;
+    }
+    let (layout, tag) = match source.rsplit_once(':') {
+        Some((layout, tag)) => (layout, Some(tag)),
+        None => (source, None),
+    };
+    info!("Unpack the OCI image {} to {}...", source, TARGET_PATH);
+    let res = axoci::Store::open(layout).and_then(|store| store.unpack(tag, TARGET_PATH));
+    match res {
+        Ok(layers) => info!("  {} layers unpacked", layers),
+        Err(e) => error!("failed to unpack the OCI image {}: {:?}", source, e),
+    }
+}
@@ -0,0 +1,37 @@
+//! The power supplies, whose attributes are exported to
+//! `/sys/class/power_supply` if `fs` is enabled.
+
+use core::ptr::NonNull;
+
+use axhal::mem::{PhysAddr, phys_to_virt};
+
+/// Probes the power supplies of the platform, and exports their attributes.
+pub(crate) fn init() {
+    if axconfig::devices::BATTERY_PADDR != 0 {
+        let paddr = PhysAddr::from(axconfig::devices::BATTERY_PADDR);
+        let base = NonNull::new(phys_to_virt(paddr).as_mut_ptr()).unwrap();
+        // the address is in the MMIO regions, which are mapped
+        if let Err(e) = unsafe { axpower::goldfish::init(base) } {
+            warn!("failed to initialize the goldfish battery: {:?}", e);
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    for name in axpower::supplies() {
+        use alloc::{format, string::String};
+        let attrs = ["type", "uevent"]
+            .into_iter()
+            .chain(axpower::ATTRIBUTES.iter().copied());
+        for attr in attrs {
+            // only the attributes supported at the time are exported
+            if axpower::read_attribute(&name, attr).is_err() {
+                continue;
+            }
+            let supply = name.clone();
+            axfs::add_sys_file(
+                &format!("class/power_supply/{}/{}", name, attr),
+                move || axpower::read_attribute(&supply, attr).unwrap_or_else(|_| String::new()),
+            );
+        }
+    }
+}
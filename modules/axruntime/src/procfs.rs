@@ -0,0 +1,381 @@
+//! CPU, IRQ, memory, scheduler and network hook statistics, the tasks, and
+//! devices for user-space drivers, exported to `/proc`, and the network
+//! sysctls in `/proc/sys/net`.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+#[cfg(feature = "net")]
+use axerrno::{AxError, AxResult};
+use axhal::time::{NANOS_PER_SEC, monotonic_time_nanos};
+
+/// The clock ticks per second used in `/proc` files, the same as `USER_HZ`
+/// of Linux.
+const USER_HZ: u64 = 100;
+
+const NANOS_PER_USER_TICK: u64 = NANOS_PER_SEC / USER_HZ;
+
+/// Time that a CPU has spent since boot, in nanoseconds.
+struct CpuTime {
+    system: u64,
+    idle: u64,
+    irq: u64,
+}
+
+impl CpuTime {
+    fn of(cpu_id: usize) -> Self {
+        #[cfg(feature = "irq")]
+        let irq = axhal::irq::irq_time_nanos(cpu_id);
+        #[cfg(not(feature = "irq"))]
+        let irq = 0;
+
+        #[cfg(all(feature = "multitask", feature = "irq"))]
+        let (busy, idle) = {
+            const NANOS_PER_TICK: u64 = NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+            let ticks = axtask::cpu_ticks(cpu_id);
+            (ticks.busy * NANOS_PER_TICK, ticks.idle * NANOS_PER_TICK)
+        };
+        // Without the idle task, idle time cannot be told from busy time.
+        #[cfg(not(all(feature = "multitask", feature = "irq")))]
+        let (busy, idle) = (monotonic_time_nanos(), 0);
+
+        Self {
+            system: busy.saturating_sub(irq),
+            idle,
+            irq,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.system += other.system;
+        self.idle += other.idle;
+        self.irq += other.irq;
+    }
+
+    /// Writes a line of `/proc/stat` in clock ticks, fields are `user`, `nice`,
+    /// `system`, `idle`, `iowait`, `irq`, `softirq`, `steal`, `guest` and
+    /// `guest_nice`.
+    fn write_stat_line(&self, out: &mut String, name: &str) {
+        let ticks = |ns: u64| ns / NANOS_PER_USER_TICK;
+        writeln!(
+            out,
+            "{name} 0 0 {} {} 0 {} 0 0 0 0",
+            ticks(self.system),
+            ticks(self.idle),
+            ticks(self.irq),
+        )
+        .ok();
+    }
+}
+
+fn gen_stat() -> String {
+    let mut out = String::new();
+    let cpus: Vec<_> = (0..axconfig::SMP).map(CpuTime::of).collect();
+    let mut total = CpuTime {
+        system: 0,
+        idle: 0,
+        irq: 0,
+    };
+    for t in &cpus {
+        total.add(t);
+    }
+    total.write_stat_line(&mut out, "cpu ");
+    for (cpu_id, t) in cpus.iter().enumerate() {
+        t.write_stat_line(&mut out, &format!("cpu{cpu_id}"));
+    }
+
+    #[cfg(feature = "irq")]
+    {
+        use axhal::irq::{MAX_IRQ_COUNT, irq_count};
+        let counts: Vec<usize> = (0..MAX_IRQ_COUNT)
+            .map(|irq| {
+                (0..axconfig::SMP)
+                    .map(|cpu_id| irq_count(irq, cpu_id))
+                    .sum()
+            })
+            .collect();
+        write!(out, "intr {}", counts.iter().sum::<usize>()).ok();
+        for count in counts {
+            write!(out, " {count}").ok();
+        }
+        writeln!(out).ok();
+    }
+
+    let boot_time = axhal::time::epochoffset_nanos() / NANOS_PER_SEC;
+    writeln!(out, "btime {boot_time}").ok();
+    out
+}
+
+#[cfg(feature = "irq")]
+fn gen_interrupts() -> String {
+    use axhal::irq::{MAX_IRQ_COUNT, irq_count};
+
+    let mut out = String::from("    ");
+    for cpu_id in 0..axconfig::SMP {
+        write!(out, " {:>10}", format!("CPU{cpu_id}")).ok();
+    }
+    writeln!(out).ok();
+    for irq in 0..MAX_IRQ_COUNT {
+        let counts: Vec<_> = (0..axconfig::SMP)
+            .map(|cpu_id| irq_count(irq, cpu_id))
+            .collect();
+        if counts.iter().all(|&c| c == 0) {
+            continue;
+        }
+        write!(out, "{irq:>3}:").ok();
+        for count in counts {
+            write!(out, " {count:>10}").ok();
+        }
+        writeln!(out).ok();
+    }
+    out
+}
+
+fn gen_uptime() -> String {
+    let uptime = monotonic_time_nanos();
+    let idle: u64 = (0..axconfig::SMP)
+        .map(|cpu_id| CpuTime::of(cpu_id).idle)
+        .sum();
+    let secs = |ns: u64| (ns / NANOS_PER_SEC, ns % NANOS_PER_SEC / NANOS_PER_USER_TICK);
+    let (up_secs, up_frac) = secs(uptime);
+    let (idle_secs, idle_frac) = secs(idle);
+    format!("{up_secs}.{up_frac:02} {idle_secs}.{idle_frac:02}\n")
+}
+
+/// Generates `/proc/meminfo` from the global allocator, where the free pages
+/// and the free bytes in the heap are available.
+#[cfg(feature = "alloc")]
+fn gen_meminfo() -> String {
+    use axhal::mem::PAGE_SIZE_4K;
+
+    let allocator = axalloc::global_allocator();
+    let kb = |bytes: usize| bytes / 1024;
+    let free = allocator.available_pages() * PAGE_SIZE_4K;
+    let total = allocator.used_pages() * PAGE_SIZE_4K + free;
+    let available = free + allocator.available_bytes();
+    let mut out = String::new();
+    for (name, bytes) in [
+        ("MemTotal", total),
+        ("MemFree", free),
+        ("MemAvailable", available),
+        ("Buffers", 0),
+        ("Cached", 0),
+        ("SwapTotal", 0),
+        ("SwapFree", 0),
+    ] {
+        writeln!(out, "{:<16}{:>8} kB", format!("{name}:"), kb(bytes)).ok();
+    }
+    out
+}
+
+/// The ID of the `main` task without `multitask`, the same as `getpid`.
+#[cfg(not(feature = "multitask"))]
+const MAIN_PID: u64 = 2;
+
+/// The state of a task shown in `/proc/<pid>`.
+struct TaskInfo {
+    name: String,
+    /// The state letter of Linux, `R`, `S` or `Z`.
+    state: char,
+}
+
+impl TaskInfo {
+    #[cfg(feature = "multitask")]
+    fn of(pid: u64) -> Option<Self> {
+        use axtask::TaskState;
+
+        let task = axtask::init_pid_ns().find_task(pid)?;
+        let state = match task.state() {
+            TaskState::Running | TaskState::Ready => 'R',
+            TaskState::Blocked => 'S',
+            TaskState::Exited => 'Z',
+        };
+        Some(Self {
+            name: task.name().into(),
+            state,
+        })
+    }
+
+    #[cfg(not(feature = "multitask"))]
+    fn of(pid: u64) -> Option<Self> {
+        (pid == MAIN_PID).then(|| Self {
+            name: "main".into(),
+            state: 'R',
+        })
+    }
+
+    /// Generates `/proc/<pid>/stat`, the 52 fields of Linux, where those
+    /// not tracked are 0.
+    fn stat(&self, pid: u64) -> String {
+        // the name is truncated to 15 bytes like `comm` of Linux
+        let name = self.name.get(..15).unwrap_or(&self.name);
+        let mut out = format!(
+            "{pid} ({name}) {} 0 {pid} {pid} 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 0 0 0",
+            self.state
+        );
+        for _ in 25..=52 {
+            out.push_str(" 0");
+        }
+        out.push('\n');
+        out
+    }
+
+    fn status(&self, pid: u64) -> String {
+        let state = match self.state {
+            'R' => "R (running)",
+            'S' => "S (sleeping)",
+            _ => "Z (zombie)",
+        };
+        format!(
+            "Name:\t{}\nState:\t{state}\nTgid:\t{pid}\nPid:\t{pid}\nPPid:\t0\nThreads:\t1\n",
+            self.name
+        )
+    }
+}
+
+/// The tasks shown in `/proc/<pid>`.
+struct Tasks;
+
+impl axfs::ProcTasks for Tasks {
+    fn current(&self) -> u64 {
+        #[cfg(feature = "multitask")]
+        {
+            axtask::current().id().as_u64()
+        }
+        #[cfg(not(feature = "multitask"))]
+        {
+            MAIN_PID
+        }
+    }
+
+    fn pids(&self) -> Vec<u64> {
+        #[cfg(feature = "multitask")]
+        {
+            axtask::init_pid_ns().task_ids()
+        }
+        #[cfg(not(feature = "multitask"))]
+        {
+            alloc::vec![MAIN_PID]
+        }
+    }
+
+    fn generate(&self, pid: u64, name: &str) -> Option<String> {
+        let task = TaskInfo::of(pid)?;
+        match name {
+            "comm" => Some(format!("{}\n", task.name)),
+            "stat" => Some(task.stat(pid)),
+            "status" => Some(task.status(pid)),
+            _ => None,
+        }
+    }
+}
+
+/// Lists the devices left to user-space drivers, with their memory regions.
+#[cfg(feature = "uio")]
+fn gen_uio() -> String {
+    let mut out = String::new();
+    for (i, dev) in axuio::devices().enumerate() {
+        write!(
+            out,
+            "uio{i} {:02x}:{:02x}.{} {:04x}:{:04x} {:02x}{:02x}",
+            dev.bus,
+            dev.device,
+            dev.function,
+            dev.vendor_id,
+            dev.device_id,
+            dev.class,
+            dev.subclass
+        )
+        .ok();
+        for map in &dev.maps {
+            write!(out, " {:#x}+{:#x}", map.paddr, map.size).ok();
+        }
+        writeln!(out).ok();
+    }
+    out
+}
+
+/// Lists the packet hooks of the network stack in the order they run, with
+/// the frames passed, dropped and redirected, and the bytes seen by each.
+#[cfg(feature = "net")]
+fn gen_net_hooks() -> String {
+    let mut out = String::from("name passed dropped redirected bytes\n");
+    for (name, stats) in axnet::hook_stats() {
+        writeln!(
+            out,
+            "{name} {} {} {} {}",
+            stats.passed, stats.dropped, stats.redirected, stats.bytes
+        )
+        .ok();
+    }
+    out
+}
+
+#[cfg(feature = "net")]
+fn gen_ip_forward() -> String {
+    format!("{}\n", axnet::ip_forward() as u8)
+}
+
+#[cfg(feature = "net")]
+fn set_ip_forward(value: &str) -> AxResult {
+    match value.trim() {
+        "0" => axnet::set_ip_forward(false),
+        "1" => axnet::set_ip_forward(true),
+        _ => return Err(AxError::InvalidInput),
+    }
+    Ok(())
+}
+
+/// Lists the port forwarding rules, one per line.
+#[cfg(feature = "net")]
+fn gen_nat_rules() -> String {
+    let mut out = String::new();
+    for rule in axnet::nat_rules() {
+        writeln!(out, "{rule}").ok();
+    }
+    out
+}
+
+/// Replaces the port forwarding rules by the written ones, one per line.
+#[cfg(feature = "net")]
+fn set_nat_rules(value: &str) -> AxResult {
+    let rules = value
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect::<AxResult<_>>()?;
+    axnet::set_nat_rules(rules);
+    Ok(())
+}
+
+/// Adds the statistics files and the tasks to `/proc`, and the sysctls to
+/// `/proc/sys`.
+pub(crate) fn init() {
+    axfs::add_proc_file("stat", gen_stat);
+    #[cfg(feature = "irq")]
+    axfs::add_proc_file("interrupts", gen_interrupts);
+    axfs::add_proc_file("uptime", gen_uptime);
+    #[cfg(feature = "alloc")]
+    axfs::add_proc_file("meminfo", gen_meminfo);
+    axfs::set_proc_tasks(&Tasks);
+    #[cfg(feature = "uio")]
+    axfs::add_proc_file("uio", gen_uio);
+    #[cfg(feature = "net")]
+    axfs::add_proc_file("net_hooks", gen_net_hooks);
+    #[cfg(feature = "net")]
+    {
+        axfs::add_sysctl("net/ipv4/ip_forward", gen_ip_forward, set_ip_forward);
+        axfs::add_sysctl("net/ipv4/nat_rules", gen_nat_rules, set_nat_rules);
+    }
+    #[cfg(feature = "sched_trace")]
+    axfs::add_proc_file("sched_trace", axtask::sched_trace_to_chrome_json);
+    #[cfg(feature = "mcount")]
+    {
+        axfs::add_proc_file("mcount", crate::mcount::gen_mcount);
+        axfs::add_sysctl(
+            "kernel/mcount",
+            crate::mcount::gen_recording,
+            crate::mcount::set_recording,
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//! The hardware random number generator as the source of the entropy pool.
+
+use alloc::{boxed::Box, string::String};
+
+use axdriver::{AxDeviceContainer, AxRngDevice, prelude::*};
+use axhal::entropy::EntropySource;
+
+struct RngSource(AxRngDevice);
+
+impl EntropySource for RngSource {
+    fn fill(&mut self, buf: &mut [u8]) -> usize {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.0.read_entropy(&mut buf[filled..]) {
+                Ok(0) | Err(_) => break,
+                Ok(len) => filled += len,
+            }
+        }
+        filled
+    }
+}
+
+/// Registers the first random number generator as the source of the entropy
+/// pool, which seeds the pool.
+pub(crate) fn init_rng(mut rng_devs: AxDeviceContainer<AxRngDevice>) {
+    let Some(dev) = rng_devs.take_one() else {
+        warn!("no random number generator, the entropy pool is not seeded");
+        return;
+    };
+    let name = String::from(dev.device_name());
+    axhal::entropy::set_source(Box::leak(Box::new(RngSource(dev))));
+    if axhal::entropy::is_seeded() {
+        info!("  entropy pool seeded by {}", name);
+    } else {
+        warn!("failed to seed the entropy pool by {}", name);
+    }
+}
@@ -0,0 +1,28 @@
+//! Memory ordering helpers.
+//!
+//! These are thin wrappers of [`core::sync::atomic::fence`], named after the
+//! SMP barriers commonly used in kernels, so that the intended ordering is
+//! clear at the call site.
+
+use core::sync::atomic::{Ordering, fence};
+
+/// A full memory barrier: orders all memory accesses before it against all
+/// memory accesses after it.
+#[inline(always)]
+pub fn smp_mb() {
+    fence(Ordering::SeqCst);
+}
+
+/// A read memory barrier: loads before it are not reordered with loads and
+/// stores after it.
+#[inline(always)]
+pub fn smp_rmb() {
+    fence(Ordering::Acquire);
+}
+
+/// A write memory barrier: stores after it are not reordered with loads and
+/// stores before it.
+#[inline(always)]
+pub fn smp_wmb() {
+    fence(Ordering::Release);
+}
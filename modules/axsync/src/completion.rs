@@ -0,0 +1,144 @@
+//! Completions, for waiting on events signaled by other tasks or interrupt
+//! handlers.
+
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use axtask::WaitQueue;
+use kspin::SpinNoIrq;
+
+/// The value of `done` after [`Completion::complete_all`].
+const COMPLETE_ALL: usize = usize::MAX;
+
+/// A completion, which lets tasks wait until an event happens.
+///
+/// Each [`complete`](Completion::complete) lets one waiter pass, either
+/// waiting now or in the future, while [`complete_all`] lets all waiters
+/// pass until [`reinit`](Completion::reinit) is called. They never block, so
+/// can be called in interrupt handlers, e.g. when a device finishes a request.
+///
+/// Tasks can wait by blocking with [`wait`](Completion::wait), or in async
+/// code by awaiting [`wait_async`](Completion::wait_async).
+///
+/// [`complete_all`]: Completion::complete_all
+pub struct Completion {
+    done: AtomicUsize,
+    wq: WaitQueue,
+    wakers: SpinNoIrq<Vec<Waker>>,
+}
+
+impl Completion {
+    /// Creates a new [`Completion`], which is not completed.
+    pub const fn new() -> Self {
+        Self {
+            done: AtomicUsize::new(0),
+            wq: WaitQueue::new(),
+            wakers: SpinNoIrq::new(Vec::new()),
+        }
+    }
+
+    /// Lets one waiter pass.
+    pub fn complete(&self) {
+        let _ = self
+            .done
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |done| {
+                (done != COMPLETE_ALL).then(|| done + 1)
+            });
+        self.wq.notify_one(false);
+        // Futures may not be polled in order, wake up all of them to retry.
+        self.wake_all_futures();
+    }
+
+    /// Lets all waiters pass, until [`reinit`](Completion::reinit) is called.
+    pub fn complete_all(&self) {
+        self.done.store(COMPLETE_ALL, Ordering::Release);
+        self.wq.notify_all(false);
+        self.wake_all_futures();
+    }
+
+    /// Resets to the uncompleted state.
+    pub fn reinit(&self) {
+        self.done.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if there is any pending completion, without consuming
+    /// it.
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire) != 0
+    }
+
+    /// Consumes one completion without blocking.
+    ///
+    /// Returns `true` if a completion is consumed.
+    pub fn try_wait(&self) -> bool {
+        self.done
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |done| match done {
+                0 => None,
+                COMPLETE_ALL => Some(COMPLETE_ALL),
+                done => Some(done - 1),
+            })
+            .is_ok()
+    }
+
+    /// Blocks the current task until a completion is consumed.
+    pub fn wait(&self) {
+        self.wq.wait_until(|| self.try_wait());
+    }
+
+    /// Blocks the current task until a completion is consumed, or the given
+    /// duration has elapsed.
+    ///
+    /// Returns `true` if a completion is consumed, or `false` on timeout.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout(&self, dur: core::time::Duration) -> bool {
+        !self.wq.wait_timeout_until(dur, || self.try_wait())
+    }
+
+    /// Returns a future that is ready when a completion is consumed.
+    pub fn wait_async(&self) -> CompletionFuture<'_> {
+        CompletionFuture { completion: self }
+    }
+
+    fn wake_all_futures(&self) {
+        let wakers = core::mem::take(&mut *self.wakers.lock());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Completion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The future returned by [`Completion::wait_async`].
+pub struct CompletionFuture<'a> {
+    completion: &'a Completion,
+}
+
+impl Future for CompletionFuture<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let completion = self.completion;
+        if completion.try_wait() {
+            return Poll::Ready(());
+        }
+        let mut wakers = completion.wakers.lock();
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        drop(wakers);
+        // Check again, in case it is completed before the waker is registered.
+        if completion.try_wait() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
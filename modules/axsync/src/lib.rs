@@ -3,6 +3,10 @@
 //! Currently supported primitives:
 //!
 //! - [`Mutex`]: A mutual exclusion primitive.
+//! - [`SeqLock`]: A sequence lock for small read-mostly data.
+//! - [`Completion`]: Waiting for events signaled by other tasks or interrupt
+//!   handlers, blocking or in async code.
+//! - mod [`barrier`]: memory ordering helpers (`smp_mb`, `smp_rmb`, `smp_wmb`).
 //! - mod [`spin`]: spinlocks imported from the [`kspin`] crate.
 //!
 //! # Cargo Features
@@ -10,18 +14,45 @@
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
 //!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
 //!   feature is enabled by default.
+//! - `smp`: For use in the multi-core environments. If the feature is enabled,
+//!   [`Mutex`] spins for a while before blocking if the owner is running on
+//!   another CPU.
+//! - `lockdep`: Track the acquisition order of [`Mutex`]es, and warn on orders
+//!   that may deadlock, or sleeping on a [`Mutex`] with IRQs disabled. For
+//!   debugging only, as every lock operation updates a global graph.
+//! - `irq`: Interrupts are enabled, used by `lockdep` to detect sleeping in
+//!   atomic contexts, and by `Completion::wait_timeout`.
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
 
+#[cfg(feature = "multitask")]
+extern crate alloc;
+#[cfg(feature = "lockdep")]
+#[macro_use]
+extern crate log;
+
 pub use kspin as spin;
 
+pub mod barrier;
+
+mod seqlock;
+pub use self::seqlock::SeqLock;
+
+#[cfg(feature = "multitask")]
+mod completion;
+#[cfg(feature = "lockdep")]
+mod lockdep;
 #[cfg(feature = "multitask")]
 mod mutex;
 
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
-pub use self::mutex::{Mutex, MutexGuard, RawMutex};
+pub use self::mutex::{Mutex, MutexGuard, MutexStats, RawMutex, mutex_stats};
+
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::completion::{Completion, CompletionFuture};
 
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
@@ -0,0 +1,135 @@
+//! Lock dependency tracking, for detecting potential deadlocks.
+//!
+//! Every time a task acquires a [`Mutex`](crate::Mutex) while holding others,
+//! the order "held → acquiring" is recorded in a global dependency graph. If
+//! an order closes a cycle, the locks may be acquired in reverse orders by
+//! different tasks, which can deadlock, even if it did not happen this time.
+//!
+//! A lock class is identified by the address of the lock, and is forgotten
+//! when the lock is dropped.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use kspin::SpinNoIrq;
+
+struct LockGraph {
+    /// Edges from a lock to the locks acquired after it.
+    after: BTreeMap<usize, BTreeSet<usize>>,
+    /// Locks held by each task, in the acquisition order.
+    held: BTreeMap<u64, Vec<usize>>,
+}
+
+impl LockGraph {
+    /// Finds a path from `from` to `to` in the dependency graph.
+    fn find_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut visited = BTreeSet::new();
+        let mut path = Vec::new();
+        self.dfs(from, to, &mut visited, &mut path).then_some(path)
+    }
+
+    fn dfs(
+        &self,
+        node: usize,
+        to: usize,
+        visited: &mut BTreeSet<usize>,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        path.push(node);
+        if node == to {
+            return true;
+        }
+        if visited.insert(node) {
+            for &next in self.after.get(&node).into_iter().flatten() {
+                if self.dfs(next, to, visited, path) {
+                    return true;
+                }
+            }
+        }
+        path.pop();
+        false
+    }
+}
+
+static GRAPH: SpinNoIrq<LockGraph> = SpinNoIrq::new(LockGraph {
+    after: BTreeMap::new(),
+    held: BTreeMap::new(),
+});
+
+fn current_id() -> u64 {
+    axtask::current().id().as_u64()
+}
+
+/// Records that the current task is going to acquire the lock at `lock`, and
+/// warns if it may deadlock with the locks already held.
+pub(crate) fn lock_acquire(lock: usize) {
+    let task_id = current_id();
+    let mut graph = GRAPH.lock();
+    let held = graph.held.get(&task_id).cloned().unwrap_or_default();
+    for prev in held {
+        if graph.after.get(&prev).is_some_and(|s| s.contains(&lock)) {
+            continue;
+        }
+        if let Some(path) = graph.find_path(lock, prev) {
+            warn!(
+                "lockdep: possible deadlock: {} acquires mutex {:#x} while holding \
+                 {:#x}, but they were acquired in the reverse order: {:x?}",
+                axtask::current().id_name(),
+                lock,
+                prev,
+                path
+            );
+        }
+        graph.after.entry(prev).or_default().insert(lock);
+    }
+}
+
+/// Records that the current task has acquired the lock at `lock`.
+pub(crate) fn lock_acquired(lock: usize) {
+    GRAPH
+        .lock()
+        .held
+        .entry(current_id())
+        .or_default()
+        .push(lock);
+}
+
+/// Records that the current task has released the lock at `lock`.
+pub(crate) fn lock_release(lock: usize) {
+    let task_id = current_id();
+    let mut graph = GRAPH.lock();
+    if let Some(held) = graph.held.get_mut(&task_id) {
+        if let Some(pos) = held.iter().rposition(|&l| l == lock) {
+            held.remove(pos);
+        }
+        if held.is_empty() {
+            graph.held.remove(&task_id);
+        }
+    }
+}
+
+/// Forgets the lock at `lock`, as it is dropped and the address may be
+/// reused by another lock.
+pub(crate) fn lock_destroy(lock: usize) {
+    let mut graph = GRAPH.lock();
+    graph.after.remove(&lock);
+    for after in graph.after.values_mut() {
+        after.remove(&lock);
+    }
+}
+
+/// Warns if the current task is going to sleep in an atomic context, which
+/// usually means a spinlock is held.
+pub(crate) fn might_sleep(lock: usize) {
+    #[cfg(feature = "irq")]
+    if !axhal::arch::irqs_enabled() {
+        warn!(
+            "lockdep: {} sleeps on mutex {:#x} with IRQs disabled, \
+             is a spinlock held?",
+            axtask::current().id_name(),
+            lock
+        );
+    }
+    #[cfg(not(feature = "irq"))]
+    let _ = lock;
+}
@@ -1,17 +1,54 @@
-//! A naïve sleeping mutex.
+//! An adaptive sleeping mutex.
 
+#[cfg(feature = "smp")]
+use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::{AtomicU64, Ordering};
 
 use axtask::{WaitQueue, current};
 
+/// The maximum number of iterations to spin on a locked mutex before parking.
+#[cfg(feature = "smp")]
+const MAX_SPIN_COUNT: usize = 1000;
+
+static CONTENDED: AtomicU64 = AtomicU64::new(0);
+static SPIN_ACQUIRED: AtomicU64 = AtomicU64::new(0);
+static PARKED: AtomicU64 = AtomicU64::new(0);
+
+/// Contention statistics of all mutexes, returned by [`mutex_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MutexStats {
+    /// Number of `lock` calls that found the mutex locked.
+    pub contended: u64,
+    /// Number of contended `lock` calls that acquired the mutex by spinning,
+    /// without parking.
+    pub spin_acquired: u64,
+    /// Number of times a task parked on the wait queue of a mutex.
+    pub parked: u64,
+}
+
+/// Returns the contention statistics of all mutexes since boot.
+pub fn mutex_stats() -> MutexStats {
+    MutexStats {
+        contended: CONTENDED.load(Ordering::Relaxed),
+        spin_acquired: SPIN_ACQUIRED.load(Ordering::Relaxed),
+        parked: PARKED.load(Ordering::Relaxed),
+    }
+}
+
 /// A [`lock_api::RawMutex`] implementation.
 ///
-/// When the mutex is locked, the current task will block and be put into the
-/// wait queue. When the mutex is unlocked, all tasks waiting on the queue
-/// will be woken up.
+/// When the mutex is locked, the current task first spins for a while if the
+/// owner is running on another CPU, as it is likely to release the mutex soon.
+/// If the mutex is still not acquired, the current task will block and be put
+/// into the wait queue. When the mutex is unlocked, one task waiting on the
+/// queue will be woken up.
 pub struct RawMutex {
     wq: WaitQueue,
     owner_id: AtomicU64,
+    /// The CPU on which the owner acquired the mutex, where the spinning
+    /// tasks check whether it is still running.
+    #[cfg(feature = "smp")]
+    owner_cpu: AtomicUsize,
 }
 
 impl RawMutex {
@@ -21,8 +58,55 @@ impl RawMutex {
         Self {
             wq: WaitQueue::new(),
             owner_id: AtomicU64::new(0),
+            #[cfg(feature = "smp")]
+            owner_cpu: AtomicUsize::new(0),
         }
     }
+
+    #[cfg(feature = "lockdep")]
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Records the CPU of the new owner, called after the mutex is acquired.
+    #[inline(always)]
+    fn acquired(&self) {
+        #[cfg(feature = "smp")]
+        self.owner_cpu
+            .store(axhal::cpu::this_cpu_id(), Ordering::Relaxed);
+    }
+
+    /// Spins while the owner is running on another CPU.
+    ///
+    /// Returns `true` if the mutex is acquired.
+    #[cfg(feature = "smp")]
+    fn spin_on_owner(&self, current_id: u64) -> bool {
+        for _ in 0..MAX_SPIN_COUNT {
+            let owner_id = self.owner_id.load(Ordering::Relaxed);
+            if owner_id == 0 {
+                if self
+                    .owner_id
+                    .compare_exchange_weak(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return true;
+                }
+                continue;
+            }
+            if owner_id == current_id {
+                break; // Recursive locking, reported in the slow path.
+            }
+            // The owner can not release the mutex soon if it is not running.
+            // It may also have migrated, or `owner_cpu` is not updated by the
+            // new owner yet, then stop spinning as well.
+            let owner_cpu = self.owner_cpu.load(Ordering::Relaxed);
+            if !axtask::is_task_running_on(owner_cpu, owner_id) {
+                break;
+            }
+            axhal::arch::cpu_relax();
+        }
+        false
+    }
 }
 
 unsafe impl lock_api::RawMutex for RawMutex {
@@ -31,7 +115,33 @@ unsafe impl lock_api::RawMutex for RawMutex {
     type GuardMarker = lock_api::GuardSend;
 
     fn lock(&self) {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::lock_acquire(self.addr());
+
         let current_id = current().id().as_u64();
+        if self
+            .owner_id
+            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.acquired();
+            #[cfg(feature = "lockdep")]
+            crate::lockdep::lock_acquired(self.addr());
+            return;
+        }
+        CONTENDED.fetch_add(1, Ordering::Relaxed);
+
+        // Spinning is useless without other CPUs, where the owner is blocked
+        // or preempted as we are running.
+        #[cfg(feature = "smp")]
+        if self.spin_on_owner(current_id) {
+            self.acquired();
+            SPIN_ACQUIRED.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "lockdep")]
+            crate::lockdep::lock_acquired(self.addr());
+            return;
+        }
+
         loop {
             // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
             // when called in a loop.
@@ -49,20 +159,36 @@ unsafe impl lock_api::RawMutex for RawMutex {
                         "{} tried to acquire mutex it already owns.",
                         current().id_name()
                     );
+                    PARKED.fetch_add(1, Ordering::Relaxed);
+                    #[cfg(feature = "lockdep")]
+                    crate::lockdep::might_sleep(self.addr());
                     // Wait until the lock looks unlocked before retrying
                     self.wq.wait_until(|| !self.is_locked());
                 }
             }
         }
+        self.acquired();
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::lock_acquired(self.addr());
     }
 
     fn try_lock(&self) -> bool {
         let current_id = current().id().as_u64();
         // The reason for using a strong compare_exchange is explained here:
         // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
-        self.owner_id
+        let locked = self
+            .owner_id
             .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+            .is_ok();
+        if locked {
+            self.acquired();
+        }
+        // `try_lock` never blocks and can not deadlock, so the order is not checked.
+        #[cfg(feature = "lockdep")]
+        if locked {
+            crate::lockdep::lock_acquired(self.addr());
+        }
+        locked
     }
 
     unsafe fn unlock(&self) {
@@ -73,6 +199,8 @@ unsafe impl lock_api::RawMutex for RawMutex {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::lock_release(self.addr());
         self.wq.notify_one(true);
     }
 
@@ -81,6 +209,13 @@ unsafe impl lock_api::RawMutex for RawMutex {
     }
 }
 
+#[cfg(feature = "lockdep")]
+impl Drop for RawMutex {
+    fn drop(&mut self) {
+        crate::lockdep::lock_destroy(self.addr());
+    }
+}
+
 /// An alias of [`lock_api::Mutex`].
 pub type Mutex<T> = lock_api::Mutex<RawMutex, T>;
 /// An alias of [`lock_api::MutexGuard`].
@@ -138,4 +273,34 @@ mod tests {
         assert_eq!(*M.lock(), NUM_ITERS * NUM_TASKS * 3);
         println!("Mutex test OK");
     }
+
+    #[test]
+    fn contended() {
+        INIT.call_once(thread::init_scheduler);
+
+        const NUM_TASKS: u32 = 10;
+        static M: Mutex<u32> = Mutex::new(0);
+
+        let before = crate::mutex_stats();
+        let guard = M.lock();
+        let tasks: Vec<_> = (0..NUM_TASKS)
+            .map(|_| thread::spawn(|| *M.lock() += 1))
+            .collect();
+        // Let all tasks find the mutex locked and park, as the owner is not
+        // running.
+        for _ in 0..NUM_TASKS {
+            thread::yield_now();
+        }
+        drop(guard);
+        for task in tasks {
+            task.join();
+        }
+        assert_eq!(*M.lock(), NUM_TASKS);
+
+        // Other tests may lock mutexes concurrently.
+        let after = crate::mutex_stats();
+        assert!(after.contended - before.contended >= NUM_TASKS as u64);
+        assert!(after.parked - before.parked >= NUM_TASKS as u64);
+        println!("Mutex contention test OK");
+    }
 }
@@ -0,0 +1,121 @@
+//! Sequence locks.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+use crate::barrier::{smp_rmb, smp_wmb};
+
+/// A sequence lock, for small read-mostly data.
+///
+/// Readers never block writers nor each other: a reader copies the data out
+/// and retries if a writer was active meanwhile, which is detected by the
+/// sequence number being odd or changed. Writers are serialized by a spinlock.
+///
+/// As a torn copy may be observed before retrying, `T` must be [`Copy`] and
+/// valid for any bit pattern read concurrently with a write, e.g. plain
+/// integers or structs of them.
+pub struct SeqLock<T> {
+    seq: AtomicUsize,
+    writer: SpinNoIrq<()>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    /// Creates a new [`SeqLock`] with the given data.
+    pub const fn new(data: T) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            writer: SpinNoIrq::new(()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Reads a consistent copy of the data, without blocking writers.
+    pub fn read(&self) -> T {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 != 0 {
+                axhal::arch::wait_for_change(&self.seq, seq);
+                continue;
+            }
+            // Safety: a torn value is never returned, see the type docs.
+            let data = unsafe { core::ptr::read_volatile(self.data.get()) };
+            smp_rmb();
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return data;
+            }
+        }
+    }
+
+    /// Updates the data with the given function.
+    ///
+    /// Concurrent readers will retry until the update is done.
+    pub fn write<F: FnOnce(&mut T)>(&self, f: F) {
+        let _guard = self.writer.lock();
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        smp_wmb();
+        // Safety: writers are serialized by the spinlock.
+        let mut data = unsafe { core::ptr::read_volatile(self.data.get()) };
+        f(&mut data);
+        unsafe { core::ptr::write_volatile(self.data.get(), data) };
+        self.seq.fetch_add(1, Ordering::Release);
+    }
+
+    /// Replaces the data with `data`.
+    pub fn set(&self, data: T) {
+        self.write(|d| *d = data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SeqLock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn read_write() {
+        let lock = SeqLock::new((1u64, 2u64));
+        assert_eq!(lock.read(), (1, 2));
+        lock.write(|data| data.0 += 10);
+        assert_eq!(lock.read(), (11, 2));
+        lock.set((3, 4));
+        assert_eq!(lock.read(), (3, 4));
+    }
+
+    #[test]
+    fn no_torn_reads() {
+        const NUM_READERS: usize = 4;
+        const NUM_WRITES: u64 = 100_000;
+        static LOCK: SeqLock<[u64; 4]> = SeqLock::new([0; 4]);
+        static DONE: AtomicBool = AtomicBool::new(false);
+
+        let readers = (0..NUM_READERS)
+            .map(|_| {
+                thread::spawn(|| {
+                    let mut last = 0;
+                    while !DONE.load(Ordering::Acquire) {
+                        // the words are written at once, in increasing values
+                        let data = LOCK.read();
+                        assert!(data.iter().all(|&x| x == data[0]));
+                        assert!(data[0] >= last);
+                        last = data[0];
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 1..=NUM_WRITES {
+            LOCK.set([i; 4]);
+        }
+        DONE.store(true, Ordering::Release);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(LOCK.read(), [NUM_WRITES; 4]);
+    }
+}
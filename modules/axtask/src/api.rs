@@ -9,10 +9,24 @@ use kernel_guard::NoPreemptIrqSave;
 
 pub(crate) use crate::run_queue::{current_run_queue, select_run_queue};
 
+#[doc(cfg(feature = "multitask"))]
+pub use crate::id::{MAX_TASK_ID, PidNamespace, init_pid_ns};
+#[cfg(feature = "sched_trace")]
+#[doc(cfg(feature = "sched_trace"))]
+pub use crate::sched_trace::{
+    SCHED_TRACE_CAPACITY, SchedEvent, SchedTraceRecord, clear_sched_trace, sched_trace_records,
+    sched_trace_to_chrome_json, set_sched_trace_enabled,
+};
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub use crate::stat::{CpuTicks, cpu_ticks};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task::{CurrentTask, TaskId, TaskInner};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub use crate::timers::TimerHandle;
 #[doc(cfg(feature = "multitask"))]
 pub use crate::wait_queue::WaitQueue;
 
@@ -107,6 +121,42 @@ pub fn on_timer_tick() {
     current_run_queue::<NoOp>().scheduler_timer_tick();
 }
 
+/// Programs the one-shot timer of the current CPU for the next periodic tick
+/// at `deadline_ns` (in monotonic nanoseconds), or for the earliest timer
+/// event if it's earlier.
+///
+/// It should be called in the timer interrupt handler, after
+/// [`on_timer_tick`] or [`on_timer_event`].
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub fn program_timer(deadline_ns: u64) {
+    crate::timers::program_timer(deadline_ns);
+}
+
+/// Handles the timer interrupts that are not periodic ticks, which are
+/// triggered for the timer events earlier than the next tick.
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub fn on_timer_event() {
+    crate::timers::check_events();
+}
+
+/// Sets a timer that calls `callback` with the current time at `deadline`,
+/// which is in the wall time as [`axhal::time::wall_time`].
+///
+/// The callback is called in the timer interrupt handler of the current CPU,
+/// so it must not block. The timers are kept in a hierarchical timer wheel,
+/// and the timer interrupt is triggered at the earliest deadline, instead of
+/// the next periodic tick.
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub fn set_timer<F>(deadline: axhal::time::TimeValue, callback: F) -> TimerHandle
+where
+    F: FnOnce(axhal::time::TimeValue) + Send + 'static,
+{
+    crate::timers::set_timer(deadline, callback)
+}
+
 /// Adds the given task to the run queue, returns the task reference.
 pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     let task_ref = task.into_arc();
@@ -124,6 +174,15 @@ where
     spawn_task(TaskInner::new(f, name, stack_size))
 }
 
+/// Spawns a new task with the given parameters, or returns [`None`] if all
+/// task IDs are in use.
+pub fn try_spawn_raw<F>(f: F, name: String, stack_size: usize) -> Option<AxTaskRef>
+where
+    F: FnOnce() + Send + 'static,
+{
+    Some(spawn_task(TaskInner::try_new(f, name, stack_size)?))
+}
+
 /// Spawns a new task with the default parameters.
 ///
 /// The default task name is an empty string. The default task stack size is
@@ -150,11 +209,20 @@ pub fn set_priority(prio: isize) -> bool {
     current_run_queue::<NoPreemptIrqSave>().set_current_priority(prio)
 }
 
+/// Set the priority for the given task, e.g. to boost the owner of a lock
+/// that a higher priority task is waiting for.
+///
+/// Returns `true` if the priority is set successfully. See [`set_priority`]
+/// for the range of the priority.
+pub fn set_task_priority(task: &AxTaskRef, prio: isize) -> bool {
+    // The priority is a property of the task itself, so the scheduler of any
+    // run queue can set it.
+    select_run_queue::<NoPreemptIrqSave>(task).set_task_priority(task, prio)
+}
+
 /// Set the affinity for the current task.
 /// [`AxCpuMask`] is used to specify the CPU affinity.
 /// Returns `true` if the affinity is set successfully.
-///
-/// TODO: support set the affinity for other tasks.
 pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
     if cpumask.is_empty() {
         false
@@ -184,6 +252,75 @@ pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
     }
 }
 
+/// Set the affinity for the given task.
+///
+/// The current task is migrated immediately as [`set_current_affinity`]. For
+/// other tasks, a blocked task moves to a CPU in the new affinity when it is
+/// woken up, and a running or ready task moves when it next yields or is
+/// preempted.
+///
+/// Returns `true` if the affinity is set successfully.
+pub fn set_task_affinity(task: &AxTaskRef, cpumask: AxCpuMask) -> bool {
+    if current().ptr_eq(task) {
+        set_current_affinity(cpumask)
+    } else if cpumask.is_empty() {
+        false
+    } else {
+        task.set_cpumask(cpumask);
+        true
+    }
+}
+
+/// Set the class of service for the given task, which limits its use of the
+/// caches and the memory bandwidth, see [`axhal::qos`].
+///
+/// It takes effect immediately for the current task, and the next time it is
+/// switched to for other tasks. Returns `false` if `class` is not available
+/// for any of the resources.
+#[cfg(feature = "qos")]
+#[doc(cfg(feature = "qos"))]
+pub fn set_task_qos_class(task: &AxTaskRef, class: usize) -> bool {
+    use axhal::qos::{Resource, num_classes};
+
+    let classes = [
+        Resource::L2Cache,
+        Resource::L3Cache,
+        Resource::MemoryBandwidth,
+    ]
+    .into_iter()
+    .map(num_classes)
+    .max()
+    .unwrap_or(0);
+    if class != 0 && class >= classes {
+        return false;
+    }
+    let _guard = NoPreemptIrqSave::new();
+    task.set_qos_class(class);
+    if current().ptr_eq(task) {
+        axhal::qos::switch_class(class);
+    }
+    true
+}
+
+/// Returns whether the task with ID `id` is running on the CPU `cpu_id`.
+///
+/// It does not look up the task, for a spinning mutex to check whether its
+/// owner is still running cheaply.
+#[cfg(feature = "smp")]
+#[doc(cfg(feature = "smp"))]
+pub fn is_task_running_on(cpu_id: usize, id: u64) -> bool {
+    crate::run_queue::is_task_running_on(cpu_id, id)
+}
+
+/// Returns the CPU time all tasks except the idle tasks have been running.
+///
+/// It includes the current run of the current task, but not the current runs
+/// of the tasks on other CPUs. See [`TaskInner::cpu_time`] for the CPU time
+/// of a single task.
+pub fn tasks_cpu_time() -> core::time::Duration {
+    crate::task::tasks_cpu_time()
+}
+
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
@@ -199,12 +336,15 @@ pub fn sleep(dur: core::time::Duration) {
 
 /// Current task is going to sleep, it will be woken up at the given deadline.
 ///
-/// If the feature `irq` is not enabled, it uses busy-wait instead.
+/// If the feature `irq` is not enabled, it keeps yielding the CPU until the
+/// deadline instead, as no timer can wake it up.
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
     #[cfg(feature = "irq")]
     current_run_queue::<NoPreemptIrqSave>().sleep_until(deadline);
     #[cfg(not(feature = "irq"))]
-    axhal::time::busy_wait_until(deadline);
+    while axhal::time::wall_time() < deadline {
+        yield_now();
+    }
 }
 
 /// Exits the current task.
@@ -214,12 +354,20 @@ pub fn exit(exit_code: i32) -> ! {
 
 /// The idle task routine.
 ///
-/// It runs an infinite loop that keeps calling [`yield_now()`].
+/// It runs an infinite loop that keeps calling [`yield_now()`], and waits for
+/// IRQs (or relaxes the CPU without IRQs) in between. With the `vtime`
+/// feature, it advances the virtual clock to the next timer instead, if any.
 pub fn run_idle() -> ! {
     loop {
         yield_now();
+        #[cfg(all(feature = "irq", feature = "vtime"))]
+        if axhal::time::advance_to_next_timer() {
+            continue;
+        }
         debug!("idle task: waiting for IRQs...");
         #[cfg(feature = "irq")]
         axhal::arch::wait_for_irqs();
+        #[cfg(not(feature = "irq"))]
+        axhal::arch::cpu_relax();
     }
 }
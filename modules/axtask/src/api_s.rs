@@ -6,7 +6,7 @@ pub fn yield_now() {
     if cfg!(feature = "irq") {
         axhal::arch::wait_for_irqs();
     } else {
-        core::hint::spin_loop();
+        axhal::arch::cpu_relax();
     }
 }
 
@@ -0,0 +1,241 @@
+//! Task ID allocation and the task ID table.
+
+use alloc::{collections::BTreeMap, sync::Arc};
+
+use kspin::SpinNoIrq;
+
+use crate::{AxTaskRef, WeakAxTaskRef};
+
+/// The maximum task ID (exclusive), like `pid_max` of Linux.
+pub const MAX_TASK_ID: u64 = 32768;
+
+/// The minimum task ID. ID 0 is reserved to represent "no task".
+const MIN_TASK_ID: usize = 1;
+
+const BITS: usize = u64::BITS as usize;
+const NUM_WORDS: usize = MAX_TASK_ID as usize / BITS;
+const NUM_SUMMARY_WORDS: usize = NUM_WORDS.div_ceil(BITS);
+
+/// A two-level bitmap ID allocator.
+///
+/// IDs are allocated cyclically: the search for a free ID starts right after
+/// the last allocated one and wraps around at [`MAX_TASK_ID`]. So a freed ID
+/// is not reused until the whole ID space has been gone through, which avoids
+/// confusing a new task with a recently exited one, the same as Linux PIDs.
+struct IdAllocator {
+    /// One bit for each ID, set if the ID is allocated.
+    words: [u64; NUM_WORDS],
+    /// One bit for each word in `words`, set if all IDs in the word are
+    /// allocated, used to skip full words quickly.
+    full: [u64; NUM_SUMMARY_WORDS],
+    /// The last allocated ID.
+    last: usize,
+}
+
+impl IdAllocator {
+    const fn new() -> Self {
+        Self {
+            words: [0; NUM_WORDS],
+            full: [0; NUM_SUMMARY_WORDS],
+            last: MIN_TASK_ID - 1,
+        }
+    }
+
+    fn is_allocated(&self, id: usize) -> bool {
+        self.words[id / BITS] & (1 << (id % BITS)) != 0
+    }
+
+    fn set(&mut self, id: usize) {
+        let w = id / BITS;
+        self.words[w] |= 1 << (id % BITS);
+        if self.words[w] == u64::MAX {
+            self.full[w / BITS] |= 1 << (w % BITS);
+        }
+    }
+
+    fn clear(&mut self, id: usize) {
+        let w = id / BITS;
+        self.words[w] &= !(1 << (id % BITS));
+        self.full[w / BITS] &= !(1 << (w % BITS));
+    }
+
+    /// Finds the first free ID in `[start, end)`.
+    fn find_free(&self, start: usize, end: usize) -> Option<usize> {
+        let mut id = start;
+        while id < end {
+            let first_word = id / BITS;
+            let s = first_word / BITS;
+            // Non-full words in the current summary word, starting from `first_word`.
+            let candidates = !self.full[s] & (u64::MAX << (first_word % BITS));
+            if candidates == 0 {
+                id = (s + 1) * BITS * BITS;
+                continue;
+            }
+            let w = s * BITS + candidates.trailing_zeros() as usize;
+            let mask = if w == first_word {
+                u64::MAX << (id % BITS)
+            } else {
+                u64::MAX
+            };
+            let free = !self.words[w] & mask;
+            if free != 0 {
+                let found = w * BITS + free.trailing_zeros() as usize;
+                return (found < end).then_some(found);
+            }
+            id = (w + 1) * BITS;
+        }
+        None
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        let max = MAX_TASK_ID as usize;
+        let id = self
+            .find_free(self.last + 1, max)
+            .or_else(|| self.find_free(MIN_TASK_ID, self.last + 1))?;
+        self.set(id);
+        self.last = id;
+        Some(id)
+    }
+
+    fn free(&mut self, id: usize) -> bool {
+        if id >= MAX_TASK_ID as usize || !self.is_allocated(id) {
+            return false;
+        }
+        self.clear(id);
+        true
+    }
+}
+
+struct PidTable {
+    ids: IdAllocator,
+    tasks: BTreeMap<u64, WeakAxTaskRef>,
+}
+
+/// A task ID namespace.
+///
+/// Each namespace has its own ID allocator and a table to look up tasks by
+/// ID. Currently only the initial namespace ([`init_pid_ns`]) exists, the
+/// `level` and `parent` fields are reserved for nested namespaces, where a
+/// task will have an ID in each namespace from its own up to the initial one.
+pub struct PidNamespace {
+    level: u32,
+    parent: Option<Arc<PidNamespace>>,
+    table: SpinNoIrq<PidTable>,
+}
+
+static INIT_PID_NS: PidNamespace = PidNamespace {
+    level: 0,
+    parent: None,
+    table: SpinNoIrq::new(PidTable {
+        ids: IdAllocator::new(),
+        tasks: BTreeMap::new(),
+    }),
+};
+
+/// Returns the initial task ID namespace, which all tasks belong to.
+pub fn init_pid_ns() -> &'static PidNamespace {
+    &INIT_PID_NS
+}
+
+impl PidNamespace {
+    /// The nesting level of the namespace, 0 for the initial namespace.
+    pub const fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// The parent namespace, or [`None`] for the initial namespace.
+    pub fn parent(&self) -> Option<&Arc<PidNamespace>> {
+        self.parent.as_ref()
+    }
+
+    /// Finds a task by its ID in this namespace.
+    ///
+    /// Returns [`None`] if there is no such task, or it has been dropped.
+    pub fn find_task(&self, id: u64) -> Option<AxTaskRef> {
+        let task = self.table.lock().tasks.get(&id)?.clone();
+        task.upgrade()
+    }
+
+    /// Returns the IDs of the tasks in this namespace, in ascending order.
+    pub fn task_ids(&self) -> alloc::vec::Vec<u64> {
+        self.table.lock().tasks.keys().copied().collect()
+    }
+
+    /// Returns the number of allocated IDs in this namespace.
+    pub fn count(&self) -> usize {
+        let table = self.table.lock();
+        table
+            .ids
+            .words
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum()
+    }
+
+    pub(crate) fn alloc_id(&self) -> Option<u64> {
+        self.table.lock().ids.alloc().map(|id| id as u64)
+    }
+
+    /// Releases the ID and removes the task from the table.
+    ///
+    /// It is called when the task is dropped, or when it is reaped if its ID
+    /// is kept by [`TaskInner::keep_id_until_reaped`].
+    ///
+    /// [`TaskInner::keep_id_until_reaped`]: crate::TaskInner::keep_id_until_reaped
+    pub fn free_id(&self, id: u64) {
+        let mut table = self.table.lock();
+        table.tasks.remove(&id);
+        table.ids.free(id as usize);
+    }
+
+    pub(crate) fn register(&self, task: &AxTaskRef) {
+        let id = task.id().as_u64();
+        self.table.lock().tasks.insert(id, Arc::downgrade(task));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_cyclic() {
+        let mut ids = IdAllocator::new();
+        assert_eq!(ids.alloc(), Some(1));
+        assert_eq!(ids.alloc(), Some(2));
+        assert_eq!(ids.alloc(), Some(3));
+        assert!(ids.free(1));
+        // a freed ID is not reused until the others are gone through
+        assert_eq!(ids.alloc(), Some(4));
+
+        assert!(!ids.free(1));
+        assert!(!ids.free(0));
+        assert!(!ids.free(MAX_TASK_ID as usize));
+    }
+
+    #[test]
+    fn test_alloc_wrap_around() {
+        let max = MAX_TASK_ID as usize;
+        let mut ids = IdAllocator::new();
+        for id in MIN_TASK_ID..max {
+            assert_eq!(ids.alloc(), Some(id));
+        }
+        assert_eq!(ids.alloc(), None);
+
+        // the search wraps around after the last allocated ID
+        assert!(ids.free(5000));
+        assert!(ids.free(100));
+        assert_eq!(ids.alloc(), Some(100));
+        assert_eq!(ids.alloc(), Some(5000));
+        assert_eq!(ids.alloc(), None);
+
+        // the full words are skipped, and found again once an ID is freed
+        for id in 128..192 {
+            assert!(ids.free(id));
+        }
+        assert!(ids.free(max - 1));
+        assert_eq!(ids.alloc(), Some(max - 1));
+        assert_eq!(ids.alloc(), Some(128));
+        assert_eq!(ids.alloc(), Some(129));
+    }
+}
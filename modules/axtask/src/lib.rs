@@ -13,6 +13,9 @@
 //!    APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!    [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `smp`: Each CPU has its own run queue, and a CPU going idle steals ready
+//!   tasks from the run queues of other CPUs. Otherwise, a single run queue
+//!   is used.
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -20,6 +23,17 @@
 //!   the `multitask` and `preempt` features if it is enabled.
 //! - `sched_cfs`: Use the [Completely Fair Scheduler][3]. It also enables the
 //!   the `multitask` and `preempt` features if it is enabled.
+//! - `sched_global_rq`: With `smp`, the tasks allowed to run on any CPU are
+//!   put into a single run queue shared by all CPUs instead, which balances
+//!   the load without stealing, for small systems where the lock of the
+//!   shared run queue is not contended. The run queue of each CPU only holds
+//!   the tasks pinned to some CPUs.
+//! - `sched_trace`: Record context switches, wakeups and migrations to a ring
+//!   buffer, which can be exported by [`sched_trace_to_chrome_json`]. It also
+//!   enables the `multitask` feature.
+//! - `qos`: Assign the tasks to the classes of service partitioning the
+//!   caches and the memory bandwidth, by [`set_task_qos_class`]. It also
+//!   enables the `multitask` feature.
 //!
 //! [1]: scheduler::FifoScheduler
 //! [2]: scheduler::RRScheduler
@@ -41,11 +55,18 @@ cfg_if::cfg_if! {
 
         #[macro_use]
         mod run_queue;
+        mod id;
         mod task;
         mod task_ext;
         mod api;
         mod wait_queue;
 
+        #[cfg(feature = "sched_trace")]
+        mod sched_trace;
+        #[cfg(feature = "irq")]
+        mod stat;
+        #[cfg(feature = "irq")]
+        mod timer_wheel;
         #[cfg(feature = "irq")]
         mod timers;
 
@@ -4,6 +4,8 @@ use core::mem::MaybeUninit;
 
 #[cfg(feature = "smp")]
 use alloc::sync::Weak;
+#[cfg(feature = "smp")]
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use kernel_guard::BaseGuard;
 use kspin::SpinRaw;
@@ -53,6 +55,31 @@ static mut RUN_QUEUES: [MaybeUninit<&'static mut AxRunQueue>; axconfig::SMP] =
 #[allow(clippy::declare_interior_mutable_const)] // It's ok because it's used only for initialization `RUN_QUEUES`.
 const ARRAY_REPEAT_VALUE: MaybeUninit<&'static mut AxRunQueue> = MaybeUninit::uninit();
 
+/// The scheduler shared by all CPUs with the `sched_global_rq` feature, for
+/// the tasks allowed to run on any CPU. The run queue of each CPU only holds
+/// the tasks pinned to some CPUs then, and no task is stolen.
+#[cfg(all(feature = "smp", feature = "sched_global_rq"))]
+static GLOBAL_SCHEDULER: LazyInit<SpinRaw<Scheduler>> = LazyInit::new();
+
+/// Whether the run queue of each CPU in [`RUN_QUEUES`] is initialized, for
+/// stealing tasks only from the initialized ones.
+#[cfg(feature = "smp")]
+static RUN_QUEUE_READY: [AtomicBool; axconfig::SMP] =
+    [const { AtomicBool::new(false) }; axconfig::SMP];
+
+/// The ID of the task running on each CPU, to check whether a task is
+/// running without looking it up.
+#[cfg(feature = "smp")]
+static RUNNING_TASK_IDS: [AtomicU64; axconfig::SMP] = [const { AtomicU64::new(0) }; axconfig::SMP];
+
+/// Returns whether the task `id` is running on the CPU `cpu_id`.
+#[cfg(feature = "smp")]
+pub(crate) fn is_task_running_on(cpu_id: usize, id: u64) -> bool {
+    RUNNING_TASK_IDS
+        .get(cpu_id)
+        .is_some_and(|running| running.load(Ordering::Acquire) == id)
+}
+
 /// Returns a reference to the current run queue in [`CurrentRunQueueRef`].
 ///
 /// ## Safety
@@ -237,7 +264,7 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
             self.inner.cpu_id
         );
         assert!(task.is_ready());
-        self.inner.scheduler.lock().add_task(task);
+        self.inner.scheduler_of(&task).lock().add_task(task);
     }
 
     /// Unblock one task by inserting it into the run queue.
@@ -246,6 +273,8 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
     /// which means the task is already unblocked by other cores.
     pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
         let task_id_name = task.id_name();
+        #[cfg(feature = "sched_trace")]
+        let task_id = task.id().as_u64();
         // Try to change the state of the task from `Blocked` to `Ready`,
         // if successful, the task will be put into this run queue,
         // otherwise, the task is already unblocked by other cores.
@@ -258,6 +287,11 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
             // Since now, the task to be unblocked is in the `Ready` state.
             let cpu_id = self.inner.cpu_id;
             debug!("task unblock: {} on run_queue {}", task_id_name, cpu_id);
+            #[cfg(feature = "sched_trace")]
+            crate::sched_trace::record(crate::sched_trace::SchedEvent::Wakeup {
+                task: task_id,
+                target_cpu: cpu_id,
+            });
             // Note: when the task is unblocked on another CPU's run queue,
             // we just ingiore the `resched` flag.
             if resched && cpu_id == this_cpu_id() {
@@ -266,6 +300,19 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
             }
         }
     }
+
+    /// Sets the priority of a task, which may be in any state.
+    pub fn set_task_priority(&mut self, task: &AxTaskRef, prio: isize) -> bool {
+        let ok = self
+            .inner
+            .scheduler_of(task)
+            .lock()
+            .set_priority(task, prio);
+        if ok {
+            task.store_priority(prio);
+        }
+        ok
+    }
 }
 
 /// Core functions of run queue.
@@ -273,7 +320,14 @@ impl<G: BaseGuard> CurrentRunQueueRef<'_, G> {
     #[cfg(feature = "irq")]
     pub fn scheduler_timer_tick(&mut self) {
         let curr = &self.current_task;
-        if !curr.is_idle() && self.inner.scheduler.lock().task_tick(curr.as_task_ref()) {
+        crate::stat::account_tick(curr.is_idle());
+        if !curr.is_idle()
+            && self
+                .inner
+                .scheduler_of(curr.as_task_ref())
+                .lock()
+                .task_tick(curr.as_task_ref())
+        {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
@@ -435,10 +489,16 @@ impl<G: BaseGuard> CurrentRunQueueRef<'_, G> {
     }
 
     pub fn set_current_priority(&mut self, prio: isize) -> bool {
-        self.inner
-            .scheduler
+        let curr = self.current_task.as_task_ref();
+        let ok = self
+            .inner
+            .scheduler_of(curr)
             .lock()
-            .set_priority(self.current_task.as_task_ref(), prio)
+            .set_priority(curr, prio);
+        if ok {
+            curr.store_priority(prio);
+        }
+        ok
     }
 }
 
@@ -458,12 +518,27 @@ impl AxRunQueue {
         }
     }
 
+    /// Returns the scheduler to put `task` into, i.e. the one of this run
+    /// queue, or [`GLOBAL_SCHEDULER`] for a task allowed to run on any CPU
+    /// with the `sched_global_rq` feature.
+    fn scheduler_of(&self, task: &AxTaskRef) -> &SpinRaw<Scheduler> {
+        #[cfg(all(feature = "smp", feature = "sched_global_rq"))]
+        if task.cpumask().is_full() {
+            return &GLOBAL_SCHEDULER;
+        }
+        let _ = task;
+        &self.scheduler
+    }
+
     /// Puts target task into current run queue with `Ready` state
     /// if its state matches `current_state` (except idle task).
     ///
     /// If `preempt`, keep current task's time slice, otherwise reset it.
     ///
-    /// Returns `true` if the target task is put into this run queue successfully,
+    /// A running task whose CPU affinity no longer contains this CPU is put
+    /// into the run queue of another CPU in its affinity instead.
+    ///
+    /// Returns `true` if the target task is put into a run queue successfully,
     /// otherwise `false`.
     fn put_task_with_state(
         &mut self,
@@ -490,11 +565,27 @@ impl AxRunQueue {
                 #[cfg(feature = "smp")]
                 while task.on_cpu() {
                     // Wait for the task to finish its scheduling process.
-                    core::hint::spin_loop();
+                    axhal::arch::cpu_relax();
                 }
             }
+            // The affinity of the running task may have been changed by other
+            // tasks, move it to a CPU in the new affinity.
+            #[cfg(feature = "smp")]
+            if current_state == TaskState::Running && !task.cpumask().get(self.cpu_id) {
+                let index = select_run_queue_index(task.cpumask());
+                #[cfg(feature = "sched_trace")]
+                crate::sched_trace::record(crate::sched_trace::SchedEvent::Migrate {
+                    task: task.id().as_u64(),
+                    target_cpu: index,
+                });
+                get_run_queue(index)
+                    .scheduler_of(&task)
+                    .lock()
+                    .put_prev_task(task, preempt);
+                return true;
+            }
             // TODO: priority
-            self.scheduler.lock().put_prev_task(task, preempt);
+            self.scheduler_of(&task).lock().put_prev_task(task, preempt);
             true
         } else {
             false
@@ -504,14 +595,16 @@ impl AxRunQueue {
     /// Core reschedule subroutine.
     /// Pick the next task to run and switch to it.
     fn resched(&mut self) {
-        let next = self
-            .scheduler
-            .lock()
-            .pick_next_task()
-            .unwrap_or_else(|| unsafe {
-                // Safety: IRQs must be disabled at this time.
-                IDLE_TASK.current_ref_raw().get_unchecked().clone()
-            });
+        let next = self.scheduler.lock().pick_next_task();
+        #[cfg(all(feature = "smp", feature = "sched_global_rq"))]
+        let next = next.or_else(|| GLOBAL_SCHEDULER.lock().pick_next_task());
+        // Steal a task from other CPUs instead of going idle.
+        #[cfg(all(feature = "smp", not(feature = "sched_global_rq")))]
+        let next = next.or_else(|| self.steal_task());
+        let next = next.unwrap_or_else(|| unsafe {
+            // Safety: IRQs must be disabled at this time.
+            IDLE_TASK.current_ref_raw().get_unchecked().clone()
+        });
         assert!(
             next.is_ready(),
             "next {} is not ready: {:?}",
@@ -521,6 +614,49 @@ impl AxRunQueue {
         self.switch_to(crate::current(), next);
     }
 
+    /// Steals a ready task allowed to run on this CPU from the run queues of
+    /// other CPUs, which are tried in turn starting from the next CPU.
+    ///
+    /// Only the task to run next on the other CPU is taken, so the run queue
+    /// is not scanned. If it is pinned to other CPUs, it is put back as if it
+    /// were preempted, which keeps its place and time slice in the RR and CFS
+    /// schedulers (the FIFO scheduler moves it to the tail), and the next CPU
+    /// is tried.
+    ///
+    /// It is called when this CPU is going to be idle, including on each
+    /// timer tick of the idle task, which balances the load periodically.
+    #[cfg(all(feature = "smp", not(feature = "sched_global_rq")))]
+    fn steal_task(&self) -> Option<AxTaskRef> {
+        for i in 1..axconfig::SMP {
+            let index = (self.cpu_id + i) % axconfig::SMP;
+            if !RUN_QUEUE_READY[index].load(Ordering::Acquire) {
+                continue;
+            }
+            let mut scheduler = get_run_queue(index).scheduler.lock();
+            let Some(task) = scheduler.pick_next_task() else {
+                continue;
+            };
+            if !task.cpumask().get(self.cpu_id) {
+                scheduler.put_prev_task(task, true);
+                continue;
+            }
+            drop(scheduler);
+            debug!(
+                "task steal: {} from run_queue {} to {}",
+                task.id_name(),
+                index,
+                self.cpu_id
+            );
+            #[cfg(feature = "sched_trace")]
+            crate::sched_trace::record(crate::sched_trace::SchedEvent::Migrate {
+                task: task.id().as_u64(),
+                target_cpu: self.cpu_id,
+            });
+            return Some(task);
+        }
+        None
+    }
+
     fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
         // Make sure that IRQs are disabled by kernel guard or other means.
         #[cfg(all(not(test), feature = "irq"))] // Note: irq is faked under unit tests.
@@ -539,11 +675,35 @@ impl AxRunQueue {
         if prev_task.ptr_eq(&next_task) {
             return;
         }
+        #[cfg(feature = "sched_trace")]
+        crate::sched_trace::record(crate::sched_trace::SchedEvent::Switch {
+            prev: prev_task.id().as_u64(),
+            next: next_task.id().as_u64(),
+        });
+
+        // The task may have been moved here by `put_task_with_state()` of
+        // another CPU, wait for it to be switched out there.
+        //
+        // Pairs with the `clear_prev_task_on_cpu()`.
+        #[cfg(feature = "smp")]
+        while next_task.on_cpu() {
+            axhal::arch::cpu_relax();
+        }
 
         // Claim the task as running, we do this before switching to it
         // such that any running task will have this set.
         #[cfg(feature = "smp")]
-        next_task.set_on_cpu(true);
+        {
+            next_task.set_on_cpu(true);
+            RUNNING_TASK_IDS[self.cpu_id].store(next_task.id().as_u64(), Ordering::Release);
+        }
+
+        #[cfg(feature = "qos")]
+        axhal::qos::switch_class(next_task.qos_class());
+
+        let now = axhal::time::monotonic_time_nanos();
+        prev_task.stop_running(now);
+        next_task.start_running(now);
 
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
@@ -603,9 +763,14 @@ fn gc_entry() {
 /// then puts the task to the scheduler of target run queue.
 #[cfg(feature = "smp")]
 pub(crate) fn migrate_entry(migrated_task: AxTaskRef) {
-    select_run_queue::<kernel_guard::NoPreemptIrqSave>(&migrated_task)
-        .inner
-        .scheduler
+    let rq = select_run_queue::<kernel_guard::NoPreemptIrqSave>(&migrated_task);
+    #[cfg(feature = "sched_trace")]
+    crate::sched_trace::record(crate::sched_trace::SchedEvent::Migrate {
+        task: migrated_task.id().as_u64(),
+        target_cpu: rq.inner.cpu_id,
+    });
+    rq.inner
+        .scheduler_of(&migrated_task)
         .lock()
         .put_prev_task(migrated_task, false)
 }
@@ -636,14 +801,20 @@ pub(crate) fn init() {
     // Put the subsequent execution into the `main` task.
     let main_task = TaskInner::new_init("main".into()).into_arc();
     main_task.set_state(TaskState::Running);
+    #[cfg(feature = "smp")]
+    RUNNING_TASK_IDS[cpu_id].store(main_task.id().as_u64(), Ordering::Release);
     unsafe { CurrentTask::init_current(main_task) }
 
+    #[cfg(all(feature = "smp", feature = "sched_global_rq"))]
+    GLOBAL_SCHEDULER.init_once(SpinRaw::new(Scheduler::new()));
     RUN_QUEUE.with_current(|rq| {
         rq.init_once(AxRunQueue::new(cpu_id));
     });
     unsafe {
         RUN_QUEUES[cpu_id].write(RUN_QUEUE.current_ref_mut_raw());
     }
+    #[cfg(feature = "smp")]
+    RUN_QUEUE_READY[cpu_id].store(true, Ordering::Release);
 }
 
 pub(crate) fn init_secondary() {
@@ -652,6 +823,8 @@ pub(crate) fn init_secondary() {
     // Put the subsequent execution into the `idle` task.
     let idle_task = TaskInner::new_init("idle".into()).into_arc();
     idle_task.set_state(TaskState::Running);
+    #[cfg(feature = "smp")]
+    RUNNING_TASK_IDS[cpu_id].store(idle_task.id().as_u64(), Ordering::Release);
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.clone());
     });
@@ -663,4 +836,6 @@ pub(crate) fn init_secondary() {
     unsafe {
         RUN_QUEUES[cpu_id].write(RUN_QUEUE.current_ref_mut_raw());
     }
+    #[cfg(feature = "smp")]
+    RUN_QUEUE_READY[cpu_id].store(true, Ordering::Release);
 }
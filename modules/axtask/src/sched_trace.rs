@@ -0,0 +1,196 @@
+//! Scheduler tracing.
+//!
+//! Context switches, wakeups and migrations are recorded to a ring buffer,
+//! which can be exported in the [Chrome trace event format][1] and inspected
+//! with `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+//!
+//! [1]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// The maximum number of records kept in the trace buffer. The oldest record
+/// is dropped when a new one arrives at a full buffer.
+pub const SCHED_TRACE_CAPACITY: usize = 8192;
+
+/// A scheduler event.
+#[derive(Debug, Clone, Copy)]
+pub enum SchedEvent {
+    /// The CPU switched from task `prev` to task `next`.
+    Switch {
+        /// ID of the task switched out.
+        prev: u64,
+        /// ID of the task switched in.
+        next: u64,
+    },
+    /// A blocked task was woken up and put into the run queue of `target_cpu`.
+    Wakeup {
+        /// ID of the woken task.
+        task: u64,
+        /// The CPU whose run queue the task was put into.
+        target_cpu: usize,
+    },
+    /// A task was migrated to the run queue of `target_cpu`.
+    Migrate {
+        /// ID of the migrated task.
+        task: u64,
+        /// The CPU whose run queue the task was put into.
+        target_cpu: usize,
+    },
+}
+
+/// A recorded scheduler event.
+#[derive(Debug, Clone, Copy)]
+pub struct SchedTraceRecord {
+    /// Monotonic time when the event happened, in nanoseconds.
+    pub time_ns: u64,
+    /// The CPU where the event happened.
+    pub cpu_id: usize,
+    /// The event.
+    pub event: SchedEvent,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+static BUFFER: SpinNoIrq<VecDeque<SchedTraceRecord>> = SpinNoIrq::new(VecDeque::new());
+
+pub(crate) fn record(event: SchedEvent) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let record = SchedTraceRecord {
+        time_ns: axhal::time::monotonic_time_nanos(),
+        cpu_id: axhal::cpu::this_cpu_id(),
+        event,
+    };
+    let mut buf = BUFFER.lock();
+    if buf.len() >= SCHED_TRACE_CAPACITY {
+        buf.pop_front();
+    }
+    buf.push_back(record);
+}
+
+/// Starts or stops recording scheduler events. Recording is enabled by
+/// default.
+pub fn set_sched_trace_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns a copy of the recorded events, from the oldest to the newest.
+pub fn sched_trace_records() -> Vec<SchedTraceRecord> {
+    BUFFER.lock().iter().copied().collect()
+}
+
+/// Discards all recorded events.
+pub fn clear_sched_trace() {
+    BUFFER.lock().clear();
+}
+
+fn task_name(id: u64) -> String {
+    match crate::init_pid_ns().find_task(id) {
+        Some(task) => alloc::format!("{} ({})", task.name(), id),
+        None => alloc::format!("task {}", id),
+    }
+}
+
+fn write_json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).ok();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes the time in microseconds, the unit of timestamps in the format.
+fn write_us(out: &mut String, ns: u64) {
+    write!(out, "{}.{:03}", ns / 1000, ns % 1000).ok();
+}
+
+fn begin_event(out: &mut String, name: &str, ph: &str, cpu_id: usize, ts_ns: u64) {
+    if !out.ends_with('[') {
+        out.push(',');
+    }
+    out.push_str("\n{\"name\":");
+    write_json_str(out, name);
+    write!(out, ",\"ph\":\"{ph}\",\"pid\":0,\"tid\":{cpu_id},\"ts\":").ok();
+    write_us(out, ts_ns);
+}
+
+fn write_slice(out: &mut String, task: u64, cpu_id: usize, start_ns: u64, end_ns: u64) {
+    begin_event(out, &task_name(task), "X", cpu_id, start_ns);
+    out.push_str(",\"dur\":");
+    write_us(out, end_ns.saturating_sub(start_ns));
+    out.push('}');
+}
+
+fn write_instant(
+    out: &mut String,
+    name: &str,
+    record: &SchedTraceRecord,
+    task: u64,
+    target: usize,
+) {
+    begin_event(out, name, "i", record.cpu_id, record.time_ns);
+    out.push_str(",\"s\":\"t\",\"args\":{\"task\":");
+    write_json_str(out, &task_name(task));
+    write!(out, ",\"target_cpu\":{target}}}}}").ok();
+}
+
+/// Exports the recorded events in the Chrome trace event format (JSON).
+///
+/// Each CPU is shown as a thread, where the tasks running on it are shown as
+/// slices between context switches, and wakeups and migrations issued on it
+/// are shown as instant events.
+pub fn sched_trace_to_chrome_json() -> String {
+    let records = sched_trace_records();
+    let now = axhal::time::monotonic_time_nanos();
+
+    let mut out = String::from("{\"displayTimeUnit\":\"ns\",\"traceEvents\":[");
+    for cpu_id in 0..axconfig::SMP {
+        if !out.ends_with('[') {
+            out.push(',');
+        }
+        write!(
+            out,
+            "\n{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{cpu_id},\
+             \"args\":{{\"name\":\"CPU {cpu_id}\"}}}}"
+        )
+        .ok();
+    }
+
+    // The running task on each CPU and when it was switched in.
+    let mut running: [Option<(u64, u64)>; axconfig::SMP] = [None; axconfig::SMP];
+    for record in &records {
+        match record.event {
+            SchedEvent::Switch { prev, next } => {
+                if let Some((_, start)) = running[record.cpu_id].filter(|&(t, _)| t == prev) {
+                    write_slice(&mut out, prev, record.cpu_id, start, record.time_ns);
+                }
+                running[record.cpu_id] = Some((next, record.time_ns));
+            }
+            SchedEvent::Wakeup { task, target_cpu } => {
+                write_instant(&mut out, "wakeup", record, task, target_cpu);
+            }
+            SchedEvent::Migrate { task, target_cpu } => {
+                write_instant(&mut out, "migrate", record, task, target_cpu);
+            }
+        }
+    }
+    for (cpu_id, slot) in running.iter().enumerate() {
+        if let Some((task, start)) = *slot {
+            write_slice(&mut out, task, cpu_id, start, now);
+        }
+    }
+    out.push_str("\n]}\n");
+    out
+}
@@ -0,0 +1,46 @@
+//! Per-CPU time accounting based on timer ticks.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+struct TickCounters {
+    busy: AtomicU64,
+    idle: AtomicU64,
+}
+
+static CPU_TICKS: [TickCounters; axconfig::SMP] = [const {
+    TickCounters {
+        busy: AtomicU64::new(0),
+        idle: AtomicU64::new(0),
+    }
+}; axconfig::SMP];
+
+/// Number of timer ticks a CPU has spent in each state.
+///
+/// A tick lasts `1 / axconfig::TICKS_PER_SEC` seconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuTicks {
+    /// Ticks that interrupted a task other than the idle task.
+    pub busy: u64,
+    /// Ticks that interrupted the idle task.
+    pub idle: u64,
+}
+
+/// Charges the current timer tick to the current CPU.
+pub(crate) fn account_tick(idle: bool) {
+    let ticks = &CPU_TICKS[axhal::cpu::this_cpu_id()];
+    if idle {
+        ticks.idle.fetch_add(1, Ordering::Relaxed);
+    } else {
+        ticks.busy.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of timer ticks the given CPU has spent since boot.
+pub fn cpu_ticks(cpu_id: usize) -> CpuTicks {
+    CPU_TICKS
+        .get(cpu_id)
+        .map_or(CpuTicks::default(), |t| CpuTicks {
+            busy: t.busy.load(Ordering::Relaxed),
+            idle: t.idle.load(Ordering::Relaxed),
+        })
+}
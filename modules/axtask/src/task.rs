@@ -1,8 +1,10 @@
 use alloc::{boxed::Box, string::String, sync::Arc};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, Ordering};
-use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicIsize, AtomicU8, AtomicU64, Ordering};
+use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull, time::Duration};
 
+#[cfg(feature = "qos")]
+use core::sync::atomic::AtomicU32;
 #[cfg(feature = "preempt")]
 use core::sync::atomic::AtomicUsize;
 
@@ -13,9 +15,28 @@ use axhal::arch::TaskContext;
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
 
+use crate::id::init_pid_ns;
 use crate::task_ext::AxTaskExt;
 use crate::{AxCpuMask, AxTask, AxTaskRef, WaitQueue};
 
+/// The CPU time in nanoseconds of all tasks except the idle tasks, until
+/// they were switched out last time.
+static TASKS_CPU_TIME_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the CPU time of all tasks except the idle tasks, including the
+/// current run of the current task.
+///
+/// The current runs of the tasks on other CPUs are not included.
+pub(crate) fn tasks_cpu_time() -> Duration {
+    let mut nanos = TASKS_CPU_TIME_NANOS.load(Ordering::Relaxed);
+    if let Some(curr) = crate::current_may_uninit() {
+        if !curr.is_idle() {
+            nanos += curr.running_nanos();
+        }
+    }
+    Duration::from_nanos(nanos)
+}
+
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct TaskId(u64);
@@ -42,15 +63,36 @@ pub struct TaskInner {
     is_idle: bool,
     is_init: bool,
 
-    entry: Option<*mut dyn FnOnce()>,
+    /// The entry function, taken by the task on its first run, or dropped
+    /// with the task if it never runs.
+    entry: UnsafeCell<Option<Box<dyn FnOnce()>>>,
     state: AtomicU8,
 
     /// CPU affinity mask.
     cpumask: SpinNoIrq<AxCpuMask>,
 
+    /// The priority last set successfully, see [`crate::set_priority`].
+    priority: AtomicIsize,
+
+    /// The class of service partitioning the caches and the memory
+    /// bandwidth, see [`axhal::qos`].
+    #[cfg(feature = "qos")]
+    qos_class: AtomicU32,
+
+    /// The CPU time in nanoseconds the task has been running, until it was
+    /// switched in last time.
+    cpu_time_nanos: AtomicU64,
+    /// The monotonic time in nanoseconds the task was switched in, or 0 if it
+    /// is not running.
+    run_start_nanos: AtomicU64,
+
     /// Mark whether the task is in the wait queue.
     in_wait_queue: AtomicBool,
 
+    /// Whether the ID is kept after the task is dropped, see
+    /// [`TaskInner::keep_id_until_reaped`].
+    keep_id: AtomicBool,
+
     /// Used to indicate whether the task is running on a CPU.
     #[cfg(feature = "smp")]
     on_cpu: AtomicBool,
@@ -78,9 +120,13 @@ pub struct TaskInner {
 }
 
 impl TaskId {
-    fn new() -> Self {
-        static ID_COUNTER: AtomicU64 = AtomicU64::new(1);
-        Self(ID_COUNTER.fetch_add(1, Ordering::Relaxed))
+    /// Allocates a new ID from the initial task ID namespace, or returns
+    /// [`None`] if all IDs are in use.
+    ///
+    /// The ID is released when the task is dropped, unless it is kept by
+    /// [`TaskInner::keep_id_until_reaped`].
+    fn new() -> Option<Self> {
+        init_pid_ns().alloc_id().map(Self)
     }
 
     /// Convert the task ID to a `u64`.
@@ -107,11 +153,24 @@ unsafe impl Sync for TaskInner {}
 
 impl TaskInner {
     /// Create a new task with the given entry function and stack size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all task IDs are in use, see [`TaskInner::try_new`].
     pub fn new<F>(entry: F, name: String, stack_size: usize) -> Self
     where
         F: FnOnce() + Send + 'static,
     {
-        let mut t = Self::new_common(TaskId::new(), name);
+        Self::try_new(entry, name, stack_size).expect("no task IDs available, too many tasks")
+    }
+
+    /// Create a new task with the given entry function and stack size, or
+    /// returns [`None`] if all task IDs are in use.
+    pub fn try_new<F>(entry: F, name: String, stack_size: usize) -> Option<Self>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut t = Self::new_common(TaskId::new()?, name);
         debug!("new task: {}", t.id_name());
         let kstack = TaskStack::alloc(align_up_4k(stack_size));
 
@@ -120,13 +179,19 @@ impl TaskInner {
         #[cfg(not(feature = "tls"))]
         let tls = VirtAddr::from(0);
 
-        t.entry = Some(Box::into_raw(Box::new(entry)));
+        // The new task is in the same class of service as its creator.
+        #[cfg(feature = "qos")]
+        if let Some(curr) = crate::current_may_uninit() {
+            t.set_qos_class(curr.qos_class());
+        }
+
+        t.entry = UnsafeCell::new(Some(Box::new(entry)));
         t.ctx_mut().init(task_entry as usize, kstack.top(), tls);
         t.kstack = Some(kstack);
         if t.name() == "idle" {
             t.is_idle = true;
         }
-        t
+        Some(t)
     }
 
     /// Gets the ID of the task.
@@ -146,6 +211,15 @@ impl TaskInner {
         }
     }
 
+    /// Keeps the ID of the task allocated after the task is dropped, until it
+    /// is released by [`PidNamespace::free_id`] when the task is reaped, like
+    /// a process waited by its parent.
+    ///
+    /// [`PidNamespace::free_id`]: crate::PidNamespace::free_id
+    pub fn keep_id_until_reaped(&self) {
+        self.keep_id.store(true, Ordering::Release);
+    }
+
     /// Get a combined string of the task ID and name.
     pub fn id_name(&self) -> alloc::string::String {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name())
@@ -217,6 +291,49 @@ impl TaskInner {
         *self.cpumask.lock() = cpumask
     }
 
+    /// Gets the priority of the task, which is 0 if it has never been set.
+    ///
+    /// The meaning of the priority is dependent on the scheduler, see
+    /// [`crate::set_priority`].
+    #[inline]
+    pub fn priority(&self) -> isize {
+        self.priority.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn store_priority(&self, prio: isize) {
+        self.priority.store(prio, Ordering::Release)
+    }
+
+    /// Gets the CPU time the task has been running, including the current
+    /// run if it is running now.
+    pub fn cpu_time(&self) -> Duration {
+        Duration::from_nanos(self.cpu_time_nanos.load(Ordering::Acquire) + self.running_nanos())
+    }
+
+    /// Returns the nanoseconds since the task was switched in, or 0 if it is
+    /// not running.
+    fn running_nanos(&self) -> u64 {
+        match self.run_start_nanos.load(Ordering::Acquire) {
+            0 => 0,
+            start => axhal::time::monotonic_time_nanos().saturating_sub(start),
+        }
+    }
+
+    /// Gets the class of service of the task, see [`axhal::qos`].
+    #[cfg(feature = "qos")]
+    #[inline]
+    pub fn qos_class(&self) -> usize {
+        self.qos_class.load(Ordering::Acquire) as usize
+    }
+
+    /// Sets the class of service of the task, which takes effect the next
+    /// time the task is switched to. See [`crate::set_task_qos_class`].
+    #[cfg(feature = "qos")]
+    #[inline]
+    pub fn set_qos_class(&self, class: usize) {
+        self.qos_class.store(class as u32, Ordering::Release)
+    }
+
     /// Read the top address of the kernel stack for the task.
     #[inline]
     pub fn get_kernel_stack_top(&self) -> Option<usize> {
@@ -240,11 +357,17 @@ impl TaskInner {
             name: UnsafeCell::new(name),
             is_idle: false,
             is_init: false,
-            entry: None,
+            entry: UnsafeCell::new(None),
             state: AtomicU8::new(TaskState::Ready as u8),
             // By default, the task is allowed to run on all CPUs.
             cpumask: SpinNoIrq::new(AxCpuMask::full()),
+            priority: AtomicIsize::new(0),
+            #[cfg(feature = "qos")]
+            qos_class: AtomicU32::new(0),
+            cpu_time_nanos: AtomicU64::new(0),
+            run_start_nanos: AtomicU64::new(0),
             in_wait_queue: AtomicBool::new(false),
+            keep_id: AtomicBool::new(false),
             #[cfg(feature = "irq")]
             timer_ticket_id: AtomicU64::new(0),
             #[cfg(feature = "smp")]
@@ -272,8 +395,10 @@ impl TaskInner {
     /// And there is no need to set the `entry`, `kstack` or `tls` fields, as
     /// they will be filled automatically when the task is switches out.
     pub(crate) fn new_init(name: String) -> Self {
-        let mut t = Self::new_common(TaskId::new(), name);
+        let id = TaskId::new().expect("no task IDs available, too many tasks");
+        let mut t = Self::new_common(id, name);
         t.is_init = true;
+        t.start_running(axhal::time::monotonic_time_nanos());
         #[cfg(feature = "smp")]
         t.set_on_cpu(true);
         if t.name() == "idle" {
@@ -283,7 +408,9 @@ impl TaskInner {
     }
 
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let task = Arc::new(AxTask::new(self));
+        init_pid_ns().register(&task);
+        task
     }
 
     /// Returns the task's current state.
@@ -328,8 +455,9 @@ impl TaskInner {
         self.is_init
     }
 
+    /// Whether the task is the idle task of a CPU.
     #[inline]
-    pub(crate) const fn is_idle(&self) -> bool {
+    pub const fn is_idle(&self) -> bool {
         self.is_idle
     }
 
@@ -369,6 +497,26 @@ impl TaskInner {
         self.timer_ticket_id.store(0, Ordering::Release);
     }
 
+    /// Starts charging the CPU time to the task, as it is switched in at
+    /// `now` (monotonic nanoseconds).
+    #[inline]
+    pub(crate) fn start_running(&self, now: u64) {
+        self.run_start_nanos.store(now.max(1), Ordering::Release);
+    }
+
+    /// Stops charging the CPU time to the task, as it is switched out at
+    /// `now` (monotonic nanoseconds).
+    pub(crate) fn stop_running(&self, now: u64) {
+        let start = self.run_start_nanos.swap(0, Ordering::AcqRel);
+        if start != 0 {
+            let nanos = now.saturating_sub(start);
+            self.cpu_time_nanos.fetch_add(nanos, Ordering::AcqRel);
+            if !self.is_idle {
+                TASKS_CPU_TIME_NANOS.fetch_add(nanos, Ordering::Relaxed);
+            }
+        }
+    }
+
     #[inline]
     #[cfg(feature = "preempt")]
     pub(crate) fn set_preempt_pending(&self, pending: bool) {
@@ -416,8 +564,15 @@ impl TaskInner {
         self.wait_for_exit.notify_all(false);
     }
 
+    /// Returns a mutable pointer to the task context.
+    ///
+    /// # Safety
+    ///
+    /// The context is saved and restored on context switches, so it can only
+    /// be modified by the current task itself, and only the fields that are
+    /// not changed by the switches, e.g. the page table root.
     #[inline]
-    pub(crate) const unsafe fn ctx_mut_ptr(&self) -> *mut TaskContext {
+    pub const unsafe fn ctx_mut_ptr(&self) -> *mut TaskContext {
         self.ctx.get()
     }
 
@@ -454,6 +609,9 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        if !self.keep_id.load(Ordering::Acquire) {
+            init_pid_ns().free_id(self.id.as_u64());
+        }
     }
 }
 
@@ -555,8 +713,9 @@ extern "C" fn task_entry() -> ! {
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
-    if let Some(entry) = task.entry {
-        unsafe { Box::from_raw(entry)() };
+    // SAFETY: only the task itself takes its entry, on its first run.
+    if let Some(entry) = unsafe { (*task.entry.get()).take() } {
+        entry();
     }
     crate::exit(0);
 }
@@ -128,3 +128,48 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[cfg(all(feature = "irq", feature = "vtime"))]
+#[test]
+fn test_vtime_timeout() {
+    use axhal::time::{Duration, advance, monotonic_time, wall_time};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+    // the timer IRQ handler of `axruntime`, without the periodic ticks
+    static TIMER_HANDLER: Once = Once::new();
+    TIMER_HANDLER.call_once(|| {
+        axhal::irq::register_handler(axhal::time::TIMER_IRQ_NUM, || {
+            axtask::on_timer_event();
+            axtask::program_timer(u64::MAX);
+        });
+        // arm the timer for the events from now on
+        axtask::program_timer(u64::MAX);
+    });
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    // nothing else is runnable, so the idle task skips to the deadline
+    let start = monotonic_time();
+    assert!(WQ.wait_timeout(Duration::from_secs(10)));
+    assert_eq!(monotonic_time() - start, Duration::from_secs(10));
+
+    // notified before the timeout, by a task sleeping for a shorter time
+    let start = monotonic_time();
+    axtask::spawn(|| {
+        axtask::sleep(Duration::from_millis(500));
+        WQ.notify_one(true);
+    });
+    assert!(!WQ.wait_timeout(Duration::from_secs(10)));
+    assert_eq!(monotonic_time() - start, Duration::from_millis(500));
+
+    // the timers are fired as the clock is advanced past their deadlines
+    static FIRED: AtomicUsize = AtomicUsize::new(0);
+    axtask::set_timer(wall_time() + Duration::from_secs(1), |_| {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    });
+    advance(Duration::from_millis(999));
+    assert_eq!(FIRED.load(Ordering::Relaxed), 0);
+    advance(Duration::from_millis(1));
+    assert_eq!(FIRED.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,214 @@
+//! A hierarchical timer wheel, to manage a large number of timer events in
+//! constant time for most operations.
+//!
+//! The deadlines are in nanoseconds, and the wheel has `LEVELS` levels of
+//! `SLOTS` slots. The slots of level `L` are `SLOTS ^ L` nanoseconds wide,
+//! so an event is put in the lowest level whose slots can distinguish its
+//! deadline from the current time of the wheel. As the time advances to the
+//! start of a slot of a higher level, the events in it are cascaded down to
+//! the lower levels, until they expire in level 0.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use timer_list::TimeValue;
+
+const LEVEL_BITS: u32 = 6;
+const SLOTS: usize = 1 << LEVEL_BITS;
+/// The number of levels to cover all 64-bit deadlines.
+const LEVELS: usize = u64::BITS.div_ceil(LEVEL_BITS) as usize;
+
+/// A hierarchical timer wheel of events of type `E`.
+pub(crate) struct TimerWheel<E> {
+    /// The current time of the wheel, all events before it have expired.
+    now: u64,
+    /// The events in the slots, indexed by `level * SLOTS + slot`.
+    slots: Vec<Vec<(u64, E)>>,
+    /// The bitmaps of the nonempty slots of each level.
+    occupied: [u64; LEVELS],
+    /// The events that have expired but are not taken yet, in the order of
+    /// their deadlines.
+    expired: VecDeque<(u64, E)>,
+}
+
+/// Returns the level and the slot of `deadline` in a wheel at time `now`.
+fn position(now: u64, deadline: u64) -> (usize, usize) {
+    let diff = deadline ^ now;
+    let level = (u64::BITS - 1 - diff.leading_zeros()) / LEVEL_BITS;
+    let slot = (deadline >> (level * LEVEL_BITS)) as usize & (SLOTS - 1);
+    (level as usize, slot)
+}
+
+/// Returns the start time of `slot` of the level `level`, in the current
+/// round of the level at time `now`.
+fn slot_start(now: u64, level: usize, slot: usize) -> u64 {
+    let shift = level as u32 * LEVEL_BITS;
+    let round_shift = shift + LEVEL_BITS;
+    let round = if round_shift < u64::BITS {
+        now >> round_shift << round_shift
+    } else {
+        0
+    };
+    round | (slot as u64) << shift
+}
+
+impl<E> TimerWheel<E> {
+    /// Creates an empty timer wheel at time 0.
+    pub fn new() -> Self {
+        Self {
+            now: 0,
+            slots: (0..LEVELS * SLOTS).map(|_| Vec::new()).collect(),
+            occupied: [0; LEVELS],
+            expired: VecDeque::new(),
+        }
+    }
+
+    fn insert(&mut self, deadline: u64, event: E) {
+        if deadline <= self.now {
+            self.expired.push_back((deadline, event));
+            return;
+        }
+        let (level, slot) = position(self.now, deadline);
+        self.slots[level * SLOTS + slot].push((deadline, event));
+        self.occupied[level] |= 1 << slot;
+    }
+
+    /// Sets a timer event that expires at `deadline`.
+    pub fn set(&mut self, deadline: TimeValue, event: E) {
+        let deadline = deadline.as_nanos().min(u64::MAX as u128) as u64;
+        self.insert(deadline, event);
+    }
+
+    /// Returns the lowest level that has pending slots, and the first
+    /// pending slot of it.
+    ///
+    /// The events of a lower level always expire before the events of a
+    /// higher level.
+    fn first_pending(&self) -> Option<(usize, usize)> {
+        (0..LEVELS).find_map(|level| {
+            let bits = self.occupied[level];
+            (bits != 0).then(|| (level, bits.trailing_zeros() as usize))
+        })
+    }
+
+    /// Returns the earliest deadline of the events, or [`None`] if there are
+    /// no events.
+    pub fn next_deadline(&self) -> Option<TimeValue> {
+        if let Some(&(deadline, _)) = self.expired.front() {
+            return Some(TimeValue::from_nanos(deadline));
+        }
+        let (level, slot) = self.first_pending()?;
+        let deadline = self.slots[level * SLOTS + slot]
+            .iter()
+            .map(|(deadline, _)| *deadline)
+            .min()?;
+        Some(TimeValue::from_nanos(deadline))
+    }
+
+    /// Advances the time of the wheel to `now`, moving the events that have
+    /// expired to the expired queue.
+    fn advance(&mut self, now: u64) {
+        while let Some((level, slot)) = self.first_pending() {
+            let start = slot_start(self.now, level, slot);
+            if start > now {
+                break;
+            }
+            self.now = start;
+            // The events are re-inserted to the lower levels, or expired if
+            // they are in level 0.
+            self.occupied[level] &= !(1 << slot);
+            let events = core::mem::take(&mut self.slots[level * SLOTS + slot]);
+            for (deadline, event) in events {
+                self.insert(deadline, event);
+            }
+        }
+        self.now = self.now.max(now);
+    }
+
+    /// Removes one expired event at time `now`, returns its deadline and the
+    /// event, or [`None`] if no events have expired.
+    pub fn expire_one(&mut self, now: TimeValue) -> Option<(TimeValue, E)> {
+        if self.expired.is_empty() {
+            self.advance(now.as_nanos().min(u64::MAX as u128) as u64);
+        }
+        self.expired
+            .pop_front()
+            .map(|(deadline, event)| (TimeValue::from_nanos(deadline), event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nanos(ns: u64) -> TimeValue {
+        TimeValue::from_nanos(ns)
+    }
+
+    /// Takes all the events expired at `now`, with their deadlines.
+    fn expire_all(wheel: &mut TimerWheel<u32>, now: u64) -> Vec<(u64, u32)> {
+        core::iter::from_fn(|| wheel.expire_one(nanos(now)))
+            .map(|(deadline, event)| (deadline.as_nanos() as u64, event))
+            .collect()
+    }
+
+    #[test]
+    fn test_position() {
+        assert_eq!(position(0, 1), (0, 1));
+        assert_eq!(position(0, 63), (0, 63));
+        assert_eq!(position(0, 64), (1, 1));
+        assert_eq!(position(100, 127), (0, 63));
+        assert_eq!(position(100, 128), (1, 2));
+        assert_eq!(position(0, u64::MAX), (LEVELS - 1, 15));
+        assert_eq!(slot_start(100, 1, 2), 128);
+        assert_eq!(slot_start(4096, 1, 14), 4992);
+    }
+
+    #[test]
+    fn test_expire_in_order() {
+        let mut wheel = TimerWheel::new();
+        let deadlines = [5000, 3, 64, 1 << 40, 63, 4096, 65, 1_000_000];
+        for (i, &deadline) in deadlines.iter().enumerate() {
+            wheel.set(nanos(deadline), i as u32);
+        }
+        assert_eq!(wheel.next_deadline(), Some(nanos(3)));
+
+        assert!(expire_all(&mut wheel, 2).is_empty());
+        assert_eq!(expire_all(&mut wheel, 64), [(3, 1), (63, 4), (64, 2)]);
+        assert_eq!(wheel.next_deadline(), Some(nanos(65)));
+        // cascaded from the higher levels
+        assert_eq!(
+            expire_all(&mut wheel, 5000),
+            [(65, 6), (4096, 5), (5000, 0)]
+        );
+        assert_eq!(wheel.next_deadline(), Some(nanos(1_000_000)));
+        assert_eq!(
+            expire_all(&mut wheel, u64::MAX),
+            [(1_000_000, 7), (1 << 40, 3)]
+        );
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_expire_past_and_same_deadlines() {
+        let mut wheel = TimerWheel::new();
+        for i in 0..3 {
+            wheel.set(nanos(1000), i);
+        }
+        assert!(expire_all(&mut wheel, 999).is_empty());
+        assert_eq!(
+            expire_all(&mut wheel, 2000),
+            [(1000, 0), (1000, 1), (1000, 2)]
+        );
+
+        // the deadlines passed expire at once
+        wheel.set(nanos(1500), 3);
+        assert_eq!(wheel.next_deadline(), Some(nanos(1500)));
+        assert_eq!(expire_all(&mut wheel, 2000), [(1500, 3)]);
+
+        // clamped to the end of the wheel
+        wheel.set(TimeValue::MAX, 4);
+        assert!(expire_all(&mut wheel, u64::MAX - 1).is_empty());
+        assert_eq!(expire_all(&mut wheel, u64::MAX), [(u64::MAX, 4)]);
+    }
+}
@@ -1,17 +1,36 @@
-use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::{boxed::Box, sync::Arc};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
-use kernel_guard::NoOp;
+use kernel_guard::{NoOp, NoPreemptIrqSave};
 use lazyinit::LazyInit;
-use timer_list::{TimeValue, TimerEvent, TimerList};
+use timer_list::{TimeValue, TimerEvent};
 
-use axhal::time::wall_time;
+use axhal::time::{epochoffset_nanos, wall_time};
 
+use crate::timer_wheel::TimerWheel;
 use crate::{AxTaskRef, select_run_queue};
 
 static TIMER_TICKET_ID: AtomicU64 = AtomicU64::new(1);
 
 percpu_static! {
-    TIMER_LIST: LazyInit<TimerList<TaskWakeupEvent>> = LazyInit::new(),
+    TIMER_WHEEL: LazyInit<TimerWheel<TimerKind>> = LazyInit::new(),
+    /// The monotonic deadline in nanoseconds that the one-shot timer of the
+    /// CPU is programmed to.
+    TIMER_DEADLINE: u64 = 0,
+}
+
+enum TimerKind {
+    Wakeup(TaskWakeupEvent),
+    Callback(CallbackEvent),
+}
+
+impl TimerEvent for TimerKind {
+    fn callback(self, now: TimeValue) {
+        match self {
+            Self::Wakeup(event) => event.callback(now),
+            Self::Callback(event) => event.callback(now),
+        }
+    }
 }
 
 struct TaskWakeupEvent {
@@ -35,12 +54,74 @@ impl TimerEvent for TaskWakeupEvent {
     }
 }
 
+struct CallbackEvent {
+    cancelled: Arc<AtomicBool>,
+    callback: Box<dyn FnOnce(TimeValue) + Send>,
+}
+
+impl TimerEvent for CallbackEvent {
+    fn callback(self, now: TimeValue) {
+        // Cancelled timers are left in the wheel, and ignored on expiry.
+        if !self.cancelled.load(Ordering::Acquire) {
+            (self.callback)(now)
+        }
+    }
+}
+
+/// A timer set by [`set_timer`](crate::set_timer), which can be cancelled
+/// before it expires.
+pub struct TimerHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TimerHandle {
+    /// Cancels the timer, its callback will not be called if it has not
+    /// expired yet.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// Converts a wall time deadline to the monotonic time in nanoseconds.
+fn to_monotonic_nanos(deadline: TimeValue) -> u64 {
+    (deadline.as_nanos() as u64).saturating_sub(epochoffset_nanos())
+}
+
+/// Adds an event to the timer wheel of the current CPU, and reprograms the
+/// one-shot timer if it is earlier than the programmed deadline.
+fn add_event(deadline: TimeValue, event: TimerKind) {
+    let _guard = NoPreemptIrqSave::new();
+    unsafe {
+        // Safety: IRQs are disabled by the guard.
+        TIMER_WHEEL.current_ref_mut_raw().set(deadline, event);
+        let deadline_ns = to_monotonic_nanos(deadline);
+        if deadline_ns < TIMER_DEADLINE.read_current_raw() {
+            TIMER_DEADLINE.write_current_raw(deadline_ns);
+            axhal::time::set_oneshot_timer(deadline_ns);
+        }
+    }
+}
+
 pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
-    TIMER_LIST.with_current(|timer_list| {
-        let ticket_id = TIMER_TICKET_ID.fetch_add(1, Ordering::AcqRel);
-        task.set_timer_ticket(ticket_id);
-        timer_list.set(deadline, TaskWakeupEvent { ticket_id, task });
-    })
+    let ticket_id = TIMER_TICKET_ID.fetch_add(1, Ordering::AcqRel);
+    task.set_timer_ticket(ticket_id);
+    add_event(
+        deadline,
+        TimerKind::Wakeup(TaskWakeupEvent { ticket_id, task }),
+    );
+}
+
+pub fn set_timer<F>(deadline: TimeValue, callback: F) -> TimerHandle
+where
+    F: FnOnce(TimeValue) + Send + 'static,
+{
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let event = CallbackEvent {
+        cancelled: cancelled.clone(),
+        callback: Box::new(callback),
+    };
+    add_event(deadline, TimerKind::Callback(event));
+    TimerHandle { cancelled }
 }
 
 pub fn check_events() {
@@ -48,7 +129,7 @@ pub fn check_events() {
         let now = wall_time();
         let event = unsafe {
             // Safety: IRQs are disabled at this time.
-            TIMER_LIST.current_ref_mut_raw()
+            TIMER_WHEEL.current_ref_mut_raw()
         }
         .expire_one(now);
         if let Some((_deadline, event)) = event {
@@ -59,8 +140,25 @@ pub fn check_events() {
     }
 }
 
+/// Programs the one-shot timer of the current CPU to `deadline_ns`, or the
+/// deadline of the earliest timer event if it's earlier.
+///
+/// IRQs must be disabled.
+pub fn program_timer(deadline_ns: u64) {
+    let deadline_ns = unsafe {
+        // Safety: IRQs are disabled at this time.
+        TIMER_WHEEL.current_ref_mut_raw()
+    }
+    .next_deadline()
+    .map_or(deadline_ns, |next| {
+        deadline_ns.min(to_monotonic_nanos(next))
+    });
+    unsafe { TIMER_DEADLINE.write_current_raw(deadline_ns) };
+    axhal::time::set_oneshot_timer(deadline_ns);
+}
+
 pub fn init() {
-    TIMER_LIST.with_current(|timer_list| {
-        timer_list.init_once(TimerList::new());
+    TIMER_WHEEL.with_current(|timer_wheel| {
+        timer_wheel.init_once(TimerWheel::new());
     });
 }
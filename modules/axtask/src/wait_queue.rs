@@ -165,6 +165,48 @@ impl WaitQueue {
         timeout
     }
 
+    /// Blocks the current task and put it into the wait queue, until the given
+    /// `condition` becomes true, or the `deadline` (in the wall time as
+    /// [`axhal::time::wall_time`]) has passed if it is not [`None`].
+    ///
+    /// Returns `true` if it timed out, i.e. the deadline has passed before
+    /// the condition becomes true.
+    ///
+    /// Without the `irq` feature, no timer can wake up the task at the
+    /// deadline, so it yields in a loop until either is met, instead of
+    /// blocking.
+    pub fn wait_until_deadline<F>(
+        &self,
+        deadline: Option<axhal::time::TimeValue>,
+        condition: F,
+    ) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        let Some(deadline) = deadline else {
+            self.wait_until(condition);
+            return false;
+        };
+        #[cfg(feature = "irq")]
+        {
+            if condition() {
+                return false;
+            }
+            let now = axhal::time::wall_time();
+            now >= deadline || self.wait_timeout_until(deadline - now, condition)
+        }
+        #[cfg(not(feature = "irq"))]
+        loop {
+            if condition() {
+                return false;
+            }
+            if axhal::time::wall_time() >= deadline {
+                return true;
+            }
+            crate::yield_now();
+        }
+    }
+
     /// Wakes up one task in the wait queue, usually the first one.
     ///
     /// If `resched` is true, the current task will be preempted when the
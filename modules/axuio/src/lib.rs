@@ -0,0 +1,216 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) user-space device access
+//! module.
+//!
+//! PCI devices not claimed by any kernel driver can be opened here and driven
+//! by the application, like [UIO] and [VFIO] of Linux: the memory BARs of an
+//! opened device are described by [`UioDevice::maps`] to be mapped, and its
+//! interrupts are delivered as events with [`UioHandle::wait_irq`].
+//!
+//! There is no IOMMU in ArceOS, so the DMA of an opened device is not
+//! confined, and opening a device is as privileged as the kernel. Each device
+//! can only be opened by one owner at a time.
+//!
+//! [UIO]: https://www.kernel.org/doc/html/latest/driver-api/uio-howto.html
+//! [VFIO]: https://www.kernel.org/doc/html/latest/driver-api/vfio.html
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+#[doc(no_inline)]
+pub use axdriver::{UioDevice, UioMap};
+use axerrno::{AxError, AxResult, ax_err};
+use axhal::irq::IrqHandler;
+use axsync::Completion;
+use lazyinit::LazyInit;
+
+/// The maximum number of devices whose interrupts can be delivered, as each
+/// of them has its own IRQ handler.
+const MAX_IRQ_DEVICES: usize = 8;
+
+/// The IRQ number of a device which is not bound to any IRQ.
+const NO_IRQ: usize = usize::MAX;
+
+struct UioSlot {
+    dev: UioDevice,
+    opened: AtomicBool,
+}
+
+static DEVICES: LazyInit<Vec<UioSlot>> = LazyInit::new();
+
+/// The interrupt state of a device.
+struct IrqState {
+    irq: AtomicUsize,
+    count: AtomicU32,
+    event: Completion,
+}
+
+impl IrqState {
+    const fn new() -> Self {
+        Self {
+            irq: AtomicUsize::new(NO_IRQ),
+            count: AtomicU32::new(0),
+            event: Completion::new(),
+        }
+    }
+}
+
+/// The interrupt states, indexed by the device index.
+static IRQ_STATES: [IrqState; MAX_IRQ_DEVICES] = [const { IrqState::new() }; MAX_IRQ_DEVICES];
+
+fn irq_handler<const INDEX: usize>() {
+    let state = &IRQ_STATES[INDEX];
+    // The device may keep the interrupt asserted until the driver handles
+    // it, so it is masked until the driver re-enables it.
+    axhal::irq::set_enable(state.irq.load(Ordering::Relaxed), false);
+    state.count.fetch_add(1, Ordering::Release);
+    state.event.complete();
+}
+
+const IRQ_HANDLERS: [IrqHandler; MAX_IRQ_DEVICES] = [
+    irq_handler::<0>,
+    irq_handler::<1>,
+    irq_handler::<2>,
+    irq_handler::<3>,
+    irq_handler::<4>,
+    irq_handler::<5>,
+    irq_handler::<6>,
+    irq_handler::<7>,
+];
+
+/// Initializes the user-space device access by the unclaimed devices.
+pub fn init_uio(uio_devs: Vec<UioDevice>) {
+    info!("Initialize user-space device access...");
+    for (i, dev) in uio_devs.iter().enumerate() {
+        info!(
+            "  uio{}: {:02x}:{:02x}.{} {:04x}:{:04x}",
+            i, dev.bus, dev.device, dev.function, dev.vendor_id, dev.device_id
+        );
+    }
+    DEVICES.init_once(
+        uio_devs
+            .into_iter()
+            .map(|dev| UioSlot {
+                dev,
+                opened: AtomicBool::new(false),
+            })
+            .collect(),
+    );
+}
+
+/// Returns the devices that can be opened, the index of each device is the
+/// argument of [`open`].
+pub fn devices() -> impl Iterator<Item = &'static UioDevice> {
+    DEVICES.iter().map(|slot| &slot.dev)
+}
+
+/// Opens the device at `index` exclusively.
+///
+/// Returns [`AxError::ResourceBusy`] if it is already opened.
+pub fn open(index: usize) -> AxResult<UioHandle> {
+    let slot = DEVICES.as_slice().get(index).ok_or(AxError::NotFound)?;
+    if slot.opened.swap(true, Ordering::Acquire) {
+        return ax_err!(ResourceBusy, "uio: device already opened");
+    }
+    if let Some(state) = IRQ_STATES.get(index) {
+        state.event.reinit();
+    }
+    Ok(UioHandle { index })
+}
+
+/// An opened device, which is released when dropped.
+pub struct UioHandle {
+    index: usize,
+}
+
+impl UioHandle {
+    /// Returns the index of the device.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the information of the device.
+    pub fn device(&self) -> &'static UioDevice {
+        &DEVICES[self.index].dev
+    }
+
+    fn irq_state(&self) -> AxResult<&'static IrqState> {
+        IRQ_STATES
+            .get(self.index)
+            .filter(|state| state.irq.load(Ordering::Relaxed) != NO_IRQ)
+            .ok_or(AxError::BadState)
+    }
+
+    /// Delivers the interrupts of `irq` to this device, and enables it.
+    ///
+    /// As the routing of PCI interrupts is platform-specific, the IRQ number
+    /// is given by the driver. A device can only be bound to one IRQ, which
+    /// cannot be shared with kernel drivers.
+    pub fn bind_irq(&self, irq: usize) -> AxResult {
+        let state = IRQ_STATES.get(self.index).ok_or(AxError::Unsupported)?;
+        match state.irq.load(Ordering::Relaxed) {
+            // Bound by a previous owner, the handler is still registered.
+            bound if bound == irq => {
+                axhal::irq::set_enable(irq, true);
+                Ok(())
+            }
+            NO_IRQ => {
+                // Set before registering, as the handler masks the IRQ by it.
+                state.irq.store(irq, Ordering::Relaxed);
+                if axhal::irq::register_handler(irq, IRQ_HANDLERS[self.index]) {
+                    Ok(())
+                } else {
+                    state.irq.store(NO_IRQ, Ordering::Relaxed);
+                    ax_err!(ResourceBusy, "uio: IRQ already in use")
+                }
+            }
+            _ => ax_err!(AlreadyExists, "uio: device bound to another IRQ"),
+        }
+    }
+
+    /// Unmasks or masks the IRQ of the device.
+    ///
+    /// The IRQ is masked after each interrupt, and should be unmasked after
+    /// the interrupt is handled.
+    pub fn enable_irq(&self, enabled: bool) -> AxResult {
+        let state = self.irq_state()?;
+        axhal::irq::set_enable(state.irq.load(Ordering::Relaxed), enabled);
+        Ok(())
+    }
+
+    /// Returns `true` if there is an interrupt not waited yet.
+    pub fn irq_pending(&self) -> bool {
+        self.irq_state().is_ok_and(|state| state.event.is_done())
+    }
+
+    /// Waits for an interrupt of the device, and returns the total number of
+    /// interrupts since the device is bound.
+    ///
+    /// Returns [`AxError::WouldBlock`] if `nonblocking` is set and there is no
+    /// interrupt pending.
+    pub fn wait_irq(&self, nonblocking: bool) -> AxResult<u32> {
+        let state = self.irq_state()?;
+        if nonblocking {
+            if !state.event.try_wait() {
+                return Err(AxError::WouldBlock);
+            }
+        } else {
+            state.event.wait();
+        }
+        Ok(state.count.load(Ordering::Acquire))
+    }
+}
+
+impl Drop for UioHandle {
+    fn drop(&mut self) {
+        if let Ok(state) = self.irq_state() {
+            axhal::irq::set_enable(state.irq.load(Ordering::Relaxed), false);
+        }
+        DEVICES[self.index].opened.store(false, Ordering::Release);
+    }
+}
@@ -49,7 +49,60 @@ endif
 $(OUT_DIR):
 	$(call run_cmd,mkdir,-p $@)
 
+ifneq ($(filter ksyms,$(FEATURES)),)
+  out_ksyms := $(patsubst %.elf,%.ksyms,$(OUT_ELF))
+
+  # Fill the `.ksyms` section reserved in the image with the text symbols, see
+  # `axhal::ksyms` for the format.
+  define embed_ksyms
+    @printf "    $(GREEN_C)Embedding$(END_C) symbol table into $(OUT_ELF)\n"
+    $(call run_cmd,$(OBJCOPY),$(OUT_ELF) --dump-section .ksyms=$(out_ksyms).reserved)
+    @$(NM) -n --defined-only --demangle $(OUT_ELF) \
+      | sed -n -e 's/::h[0-9a-f]\{16\}$$//' -e 's/^\([0-9a-f]\{16\}\) [tTwW] /\1 /p' > $(out_ksyms)
+    @test $$(wc -c < $(out_ksyms)) -lt $$(wc -c < $(out_ksyms).reserved) \
+      || (echo "The symbol table is larger than the .ksyms section" && exit 1)
+    @truncate -r $(out_ksyms).reserved $(out_ksyms)
+    $(call run_cmd,$(OBJCOPY),$(OUT_ELF) --update-section .ksyms=$(out_ksyms))
+  endef
+endif
+
+ifneq ($(filter kaslr,$(FEATURES)),)
+  out_krelocs := $(patsubst %.elf,%.krelocs,$(OUT_ELF))
+  kernel_sym = $$($(NM) $(OUT_ELF) | sed -n 's/^\([0-9a-f]\{16\}\) . $(1)$$/\1/p')
+
+  # The relocations embedded into the image by the build script of `axhal`.
+  export AX_KRELOCS_PATH := $(abspath $(out_krelocs))
+
+  # Write the locations of the absolute addresses to the image to $(1), i.e.
+  # the `R_RISCV_64` or `R_AARCH64_ABS64` relocations whose locations and
+  # symbols are both in the image, one per line as the link-time address in
+  # hex. The addresses are compared as strings of the same length, as they
+  # are too large for `awk`.
+  define dump_krelocs
+    @$(READELF) -r --wide $(OUT_ELF) \
+      | awk -v start=$(call kernel_sym,_skernel) -v end=$(call kernel_sym,_ekernel) \
+        '($$3 == "R_RISCV_64" || $$3 == "R_AARCH64_ABS64") && $$1"" >= start"" && $$1"" < end"" && $$4"" >= start"" && $$4"" <= end"" { print $$1 }' \
+      > $(1)
+  endef
+
+  # Build the image again with the relocations embedded, if they are not the
+  # ones in it, see the `kaslr` module of `axhal`. The table is the last
+  # section of the loaded data, so embedding it must not change the others.
+  define embed_krelocs
+    $(call dump_krelocs,$(out_krelocs).new)
+    @if ! cmp -s $(out_krelocs).new $(out_krelocs); then \
+      printf "    $(GREEN_C)Embedding$(END_C) relocations into $(OUT_ELF)\n"; \
+      mv $(out_krelocs).new $(out_krelocs) && $(MAKE) --no-print-directory _cargo_build $(OUT_ELF); \
+    fi
+    $(call dump_krelocs,$(out_krelocs).new)
+    @cmp -s $(out_krelocs).new $(out_krelocs) \
+      || (echo "The relocations are changed by embedding them" && exit 1)
+  endef
+endif
+
 $(OUT_BIN): _cargo_build $(OUT_ELF)
+	$(call embed_krelocs)
+	$(call embed_ksyms)
 	$(call run_cmd,$(OBJCOPY),$(OUT_ELF) --strip-all -O binary $@)
 
 ifeq ($(ARCH), aarch64)
@@ -20,9 +20,14 @@ CFLAGS += -DAX_LOG_$(shell echo $(LOG) | tr 'a-z' 'A-Z')
 CFLAGS += -nostdinc -fno-builtin -ffreestanding -Wall
 CFLAGS += -I$(CURDIR)/$(inc_dir)
 LDFLAGS += -nostdlib -static -no-pie --gc-sections -znostart-stop-gc -T$(LD_SCRIPT)
+ifneq ($(filter kaslr,$(FEATURES)),)
+  LDFLAGS += --emit-relocs
+endif
 
 ifeq ($(MODE), release)
   CFLAGS += -O3
+else ifeq ($(MODE), tiny)
+  CFLAGS += -Os
 endif
 
 ifeq ($(ARCH), riscv64)
@@ -9,6 +9,7 @@ else
 endif
 
 build_args-release := --release
+build_args-tiny := --profile tiny
 
 build_args := \
   -Z unstable-options \
@@ -17,8 +18,33 @@ build_args := \
   $(build_args-$(MODE)) \
   $(verbose)
 
+ifeq ($(MCOUNT), y)
+  # Instrument the modules except `axruntime`, which records the calls.
+  mcount_packages := $(filter-out axruntime,$(shell ls $(CURDIR)/modules))
+  mcount_profile := $(if $(filter debug,$(MODE)),dev,$(MODE))
+  mcount_flags := ["-Zinstrument-mcount", "-Cforce-frame-pointers=yes"]
+  build_args += -Z profile-rustflags \
+    $(foreach p,$(mcount_packages),--config 'profile.$(mcount_profile).package.$(p).rustflags=$(mcount_flags)')
+endif
+
 RUSTFLAGS:= -A unsafe_op_in_unsafe_fn
+ifneq ($(filter ksyms,$(FEATURES)),)
+  # Keep the frame pointers to walk the backtraces.
+  RUSTFLAGS += -C force-frame-pointers=yes
+endif
 RUSTFLAGS_LINK_ARGS := -C link-arg=-T$(LD_SCRIPT) -C link-arg=-no-pie -C link-arg=-znostart-stop-gc
+ifneq ($(filter kaslr,$(FEATURES)),)
+  ifeq ($(filter $(ARCH),riscv64 aarch64),)
+    $(error "kaslr" is only supported on riscv64 and aarch64)
+  endif
+  ifeq ($(ARCH), riscv64)
+    # The image is relocated before the MMU is enabled, where the absolute
+    # addresses in the jump tables can not be used yet.
+    RUSTFLAGS += -Z no-jump-tables
+  endif
+  # Keep the relocations to find the addresses to be fixed up at boot.
+  RUSTFLAGS_LINK_ARGS += -C link-arg=--emit-relocs
+endif
 RUSTDOCFLAGS := -Z unstable-options --enable-index-page -D rustdoc::broken_intra_doc_links
 
 ifeq ($(MAKECMDGOALS), doc_check_missing)
@@ -51,5 +77,9 @@ endef
 define unit_test
   $(call run_cmd,cargo test,-p axfs $(1) $(verbose) -- --nocapture)
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "tmpfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "ext2" $(verbose) -- --nocapture)
   $(call run_cmd,cargo test,--workspace --exclude axfs $(1) $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p arceos_posix_api $(1) --features "fs multitask" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "irq vtime axhal/irq" $(verbose) -- --nocapture)
 endef
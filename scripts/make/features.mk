@@ -13,7 +13,7 @@
 
 ifeq ($(APP_TYPE),c)
   ax_feat_prefix := axfeat/
-  lib_features := fp_simd irq alloc multitask fs net fd pipe select epoll
+  lib_features := fp_simd irq console-irq alloc multitask fs net fd pipe mqueue sysvipc signal select epoll mmap hugetlbfs shm quota uio fb input inotify timerfd
 else
   ifeq ($(NO_AXSTD),y)
     ax_feat_prefix := axfeat/
@@ -31,9 +31,12 @@ ifeq ($(APP_TYPE), c)
   ifneq ($(wildcard $(APP)/features.txt),)    # check features.txt exists
     override FEATURES += $(shell cat $(APP)/features.txt)
   endif
-  ifneq ($(filter fs net pipe select epoll,$(FEATURES)),)
+  ifneq ($(filter fs net pipe mqueue select epoll shm quota uio fb input inotify timerfd,$(FEATURES)),)
     override FEATURES += fd
   endif
+  ifneq ($(filter mqueue sysvipc signal uio fb input inotify timerfd,$(FEATURES)),)
+    override FEATURES += multitask
+  endif
 endif
 
 override FEATURES := $(strip $(FEATURES))
@@ -49,6 +52,12 @@ endif
 
 ifeq ($(BUS),mmio)
   ax_feat += bus-mmio
+else ifeq ($(BUS),both)
+  ax_feat += bus-both
+endif
+
+ifeq ($(MCOUNT),y)
+  ax_feat += mcount
 endif
 
 ifeq ($(shell test $(SMP) -gt 1; echo $$?),0)
@@ -4,10 +4,16 @@ QEMU := qemu-system-$(ARCH)
 
 ifeq ($(BUS), mmio)
   vdev-suffix := device
+  net-vdev-suffix := device
 else ifeq ($(BUS), pci)
   vdev-suffix := pci
+  net-vdev-suffix := pci
+else ifeq ($(BUS), both)
+  # the NIC is on PCIe, and other devices are on MMIO
+  vdev-suffix := device
+  net-vdev-suffix := pci
 else
-  $(error "BUS" must be one of "mmio" or "pci")
+  $(error "BUS" must be one of "mmio", "pci" or "both")
 endif
 
 ifeq ($(ARCH), x86_64)
@@ -17,13 +23,19 @@ else ifeq ($(ARCH), riscv64)
 else ifeq ($(ARCH), aarch64)
   ifeq ($(PLAT_NAME), aarch64-raspi4)
     machine := raspi4b
+    # the RAM size of the QEMU raspi4b machine is fixed
     override MEM := 2G
   else
     machine := virt
   endif
 else ifeq ($(ARCH), loongarch64)
   machine := virt
-  override MEM := 1G
+endif
+
+ifeq ($(MEM),)
+  # the physical memory size in the platform config, in MiB
+  phys_memory_size := $(subst _,,$(patsubst "%",%,$(shell axconfig-gen $(PLAT_CONFIG) -r plat.phys-memory-size)))
+  MEM := $(shell echo $$(($(phys_memory_size) / 0x100000)))M
 endif
 
 qemu_args-x86_64 := \
@@ -50,8 +62,14 @@ qemu_args-$(BLK) += \
   -device virtio-blk-$(vdev-suffix),drive=disk0 \
   -drive id=disk0,if=none,format=raw,file=$(DISK_IMG)
 
+ifneq ($(SHARED_DIR),)
+  qemu_args-y += \
+    -device virtio-9p-$(vdev-suffix),fsdev=fs0,mount_tag=host \
+    -fsdev local,id=fs0,path=$(SHARED_DIR),security_model=none
+endif
+
 qemu_args-$(NET) += \
-  -device virtio-net-$(vdev-suffix),netdev=net0
+  -device virtio-net-$(net-vdev-suffix),netdev=net0
 
 ifeq ($(NET_DEV), user)
   qemu_args-$(NET) += -netdev user,id=net0,hostfwd=tcp::5555-:5555,hostfwd=udp::5555-:5555
@@ -76,6 +94,7 @@ endif
 
 qemu_args-$(GRAPHIC) += \
   -device virtio-gpu-$(vdev-suffix) -vga none \
+  -device virtio-keyboard-$(vdev-suffix) \
   -serial mon:stdio
 
 ifeq ($(GRAPHIC), n)
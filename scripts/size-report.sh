@@ -0,0 +1,62 @@
+#!/bin/bash
+#
+# Report the size of the kernel image, and the bytes of the code and data
+# attributed to each crate by the symbols.
+#
+# NM=rust-nm ./size-report.sh <elf> <bin> [limit]
+#
+# The symbols are attributed by the first component of their demangled paths,
+# the others (e.g. of the C code and the assembly) are counted as `[other]`.
+# If `limit` is given (e.g. 200K), exit with an error if the image is larger.
+
+ELF=$1
+BIN=$2
+LIMIT=$3
+NM=${NM:-nm}
+
+set -o pipefail
+
+if [ -z "$ELF" ] || [ -z "$BIN" ]; then
+    echo "Usage: $0 <elf> <bin> [limit]"
+    exit 1
+fi
+
+$NM --print-size --size-sort --radix=d --defined-only --demangle "$ELF" | awk '
+NF >= 4 {
+    size = $2 + 0
+    type = tolower($3)
+    name = $4
+    for (i = 5; i <= NF; i++) name = name " " $i
+    # `<T as Trait>::f` is attributed to the crate of `T`.
+    sub(/^(<|&|\*const |\*mut |mut |dyn |impl )+/, "", name)
+    crate = "[other]"
+    if (index(name, "::") > 0) crate = substr(name, 1, index(name, "::") - 1)
+    if (type == "t" || type == "w") sec = "text"
+    else if (type == "r") sec = "rodata"
+    else if (type == "d" || type == "g" || type == "v") sec = "data"
+    else if (type == "b" || type == "s") sec = "bss"
+    else next
+    bytes[crate, sec] += size
+    total[crate] += size
+}
+END {
+    printf "%-24s %10s %10s %10s %10s %10s\n", "crate", "total", "text", "rodata", "data", "bss"
+    fflush()
+    for (c in total) {
+        printf "%-24s %10d %10d %10d %10d %10d\n", c, total[c], \
+            bytes[c, "text"], bytes[c, "rodata"], bytes[c, "data"], bytes[c, "bss"] | "sort -k2 -n -r"
+    }
+    close("sort -k2 -n -r")
+}' || exit 1
+
+SIZE=$(wc -c < "$BIN")
+echo
+echo "Image size of $BIN: $SIZE bytes"
+
+if [ -n "$LIMIT" ]; then
+    MAX=$(numfmt --from=iec "$LIMIT") || exit 1
+    if [ "$SIZE" -gt "$MAX" ]; then
+        echo "Error: the image is larger than $LIMIT ($MAX bytes)"
+        exit 1
+    fi
+fi
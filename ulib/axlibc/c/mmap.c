@@ -2,6 +2,8 @@
 #include <stdio.h>
 #include <sys/mman.h>
 
+#ifndef AX_CONFIG_MMAP
+
 // TODO:
 void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off)
 {
@@ -16,19 +18,21 @@ int munmap(void *addr, size_t length)
     return 0;
 }
 
-// TODO:
-void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
-             ... /* void *new_address */)
+// TODO
+int mprotect(void *addr, size_t len, int prot)
 {
     unimplemented();
-    return NULL;
+    return 0;
 }
 
-// TODO
-int mprotect(void *addr, size_t len, int prot)
+#endif // AX_CONFIG_MMAP
+
+// TODO:
+void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
+             ... /* void *new_address */)
 {
     unimplemented();
-    return 0;
+    return NULL;
 }
 
 // TODO
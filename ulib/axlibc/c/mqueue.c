@@ -0,0 +1,25 @@
+#include <mqueue.h>
+#include <stdarg.h>
+
+#ifdef AX_CONFIG_MQUEUE
+
+// TODO: remove this function in future work
+mqd_t ax_mq_open(const char *name, int flags, mode_t mode, struct mq_attr *attr);
+
+mqd_t mq_open(const char *name, int flags, ...)
+{
+    mode_t mode = 0;
+    struct mq_attr *attr = NULL;
+
+    if (flags & O_CREAT) {
+        va_list ap;
+        va_start(ap, flags);
+        mode = va_arg(ap, mode_t);
+        attr = va_arg(ap, struct mq_attr *);
+        va_end(ap);
+    }
+
+    return ax_mq_open(name, flags, mode, attr);
+}
+
+#endif // AX_CONFIG_MQUEUE
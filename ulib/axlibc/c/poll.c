@@ -1,5 +1,66 @@
+#include <errno.h>
 #include <poll.h>
 #include <stdio.h>
+#include <sys/select.h>
+#include <sys/time.h>
+
+#ifdef AX_CONFIG_SELECT
+
+// Implemented by `select`, so `fd` must be less than `FD_SETSIZE`.
+int poll(struct pollfd *__fds, nfds_t __nfds, int __timeout)
+{
+    fd_set rfds, wfds, efds;
+    int maxfd = -1;
+
+    FD_ZERO(&rfds);
+    FD_ZERO(&wfds);
+    FD_ZERO(&efds);
+    for (nfds_t i = 0; i < __nfds; i++) {
+        int fd = __fds[i].fd;
+        __fds[i].revents = 0;
+        if (fd < 0)
+            continue;
+        if (fd >= FD_SETSIZE) {
+            errno = EINVAL;
+            return -1;
+        }
+        if (__fds[i].events & (POLLIN | POLLPRI))
+            FD_SET(fd, &rfds);
+        if (__fds[i].events & POLLOUT)
+            FD_SET(fd, &wfds);
+        FD_SET(fd, &efds);
+        if (fd > maxfd)
+            maxfd = fd;
+    }
+
+    struct timeval tv, *ptv = NULL;
+    if (__timeout >= 0) {
+        tv.tv_sec = __timeout / 1000;
+        tv.tv_usec = __timeout % 1000 * 1000;
+        ptv = &tv;
+    }
+    int ret = select(maxfd + 1, &rfds, &wfds, &efds, ptv);
+    if (ret < 0)
+        return ret;
+
+    int n = 0;
+    for (nfds_t i = 0; i < __nfds; i++) {
+        int fd = __fds[i].fd;
+        if (fd < 0)
+            continue;
+        if (FD_ISSET(fd, &rfds))
+            __fds[i].revents |= __fds[i].events & (POLLIN | POLLPRI);
+        if (FD_ISSET(fd, &wfds))
+            __fds[i].revents |= POLLOUT;
+        if (FD_ISSET(fd, &efds))
+            __fds[i].revents |= POLLERR;
+        if (__fds[i].revents)
+            n++;
+    }
+    return n;
+}
+
+#else
 
 // TODO
 int poll(struct pollfd *__fds, nfds_t __nfds, int __timeout)
@@ -7,3 +68,5 @@ int poll(struct pollfd *__fds, nfds_t __nfds, int __timeout)
     unimplemented();
     return 0;
 }
+
+#endif // AX_CONFIG_SELECT
@@ -0,0 +1,31 @@
+#include <stdarg.h>
+#include <sys/sem.h>
+
+#ifdef AX_CONFIG_SYSVIPC
+
+// TODO: remove this function in future work
+int ax_semctl(int semid, int semnum, int cmd, unsigned long arg);
+
+int semctl(int semid, int semnum, int cmd, ...)
+{
+    unsigned long arg = 0;
+
+    switch (cmd) {
+    case SETVAL:
+    case GETALL:
+    case SETALL:
+    case IPC_STAT:
+    case IPC_SET:
+    case IPC_INFO: {
+        va_list ap;
+        va_start(ap, cmd);
+        arg = va_arg(ap, unsigned long);
+        va_end(ap);
+        break;
+    }
+    }
+
+    return ax_semctl(semid, semnum, cmd, arg);
+}
+
+#endif // AX_CONFIG_SYSVIPC
@@ -3,6 +3,7 @@
 #include <stddef.h>
 #include <stdio.h>
 
+#ifndef AX_CONFIG_SIGNAL
 int sigaction_helper(int signum, const struct sigaction *act, struct sigaction *oldact,
                      size_t sigsetsize)
 {
@@ -15,30 +16,54 @@ int sigaction_helper(int signum, const struct sigaction *act, struct sigaction *
     return 0;
 }
 
-void (*signal(int signum, void (*handler)(int)))(int)
+int sigaction(int sig, const struct sigaction *restrict act, struct sigaction *restrict oact)
 {
-    struct sigaction old;
-    struct sigaction act = {
-        .sa_handler = handler, .sa_flags = SA_RESTART, /* BSD signal semantics */
-    };
+    return sigaction_helper(sig, act, oact, sizeof(sigset_t));
+}
 
-    if (sigaction_helper(signum, &act, &old, sizeof(sigset_t)) < 0)
-        return SIG_ERR;
+// TODO
+int kill(pid_t __pid, int __sig)
+{
+    unimplemented();
+    return 0;
+}
 
-    return (old.sa_flags & SA_SIGINFO) ? NULL : old.sa_handler;
+// TODO
+int raise(int __sig)
+{
+    unimplemented();
+    return 0;
 }
 
-int sigaction(int sig, const struct sigaction *restrict act, struct sigaction *restrict oact)
+// TODO
+int pthread_sigmask(int __how, const sigset_t *restrict __newmask, sigset_t *restrict __oldmask)
 {
-    return sigaction_helper(sig, act, oact, sizeof(sigset_t));
+    unimplemented();
+    return 0;
 }
 
+#ifdef AX_CONFIG_MULTITASK
 // TODO
-int kill(pid_t __pid, int __sig)
+int pthread_kill(pthread_t t, int sig)
 {
     unimplemented();
     return 0;
 }
+#endif
+#endif // AX_CONFIG_SIGNAL
+
+void (*signal(int signum, void (*handler)(int)))(int)
+{
+    struct sigaction old;
+    struct sigaction act = {
+        .sa_handler = handler, .sa_flags = SA_RESTART, /* BSD signal semantics */
+    };
+
+    if (sigaction(signum, &act, &old) < 0)
+        return SIG_ERR;
+
+    return (old.sa_flags & SA_SIGINFO) ? NULL : old.sa_handler;
+}
 
 int sigemptyset(sigset_t *set)
 {
@@ -52,13 +77,6 @@ int sigemptyset(sigset_t *set)
     return 0;
 }
 
-// TODO
-int raise(int __sig)
-{
-    unimplemented();
-    return 0;
-}
-
 int sigaddset(sigset_t *set, int sig)
 {
     unsigned s = sig - 1;
@@ -69,19 +87,3 @@ int sigaddset(sigset_t *set, int sig)
     set->__bits[s / 8 / sizeof *set->__bits] |= 1UL << (s & (8 * sizeof *set->__bits - 1));
     return 0;
 }
-
-// TODO
-int pthread_sigmask(int __how, const sigset_t *restrict __newmask, sigset_t *restrict __oldmask)
-{
-    unimplemented();
-    return 0;
-}
-
-#ifdef AX_CONFIG_MULTITASK
-// TODO
-int pthread_kill(pthread_t t, int sig)
-{
-    unimplemented();
-    return 0;
-}
-#endif
@@ -24,24 +24,4 @@ int accept4(int fd, struct sockaddr *restrict addr, socklen_t *restrict len, int
     return ret;
 }
 
-int getsockopt(int fd, int level, int optname, void *restrict optval, socklen_t *restrict optlen)
-{
-    unimplemented();
-    return -1;
-}
-
-int setsockopt(int fd, int level, int optname, const void *optval, socklen_t optlen)
-{
-    unimplemented("fd: %d, level: %d, optname: %d, optval: %d, optlen: %d", fd, level, optname,
-                  *(int *)optval, optlen);
-    return 0;
-}
-
-// TODO
-ssize_t sendmsg(int fd, const struct msghdr *msg, int flags)
-{
-    unimplemented();
-    return 0;
-}
-
 #endif // AX_CONFIG_NET
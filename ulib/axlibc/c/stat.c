@@ -9,13 +9,6 @@ int fchmod(int fd, mode_t mode)
     return 0;
 }
 
-// TODO:
-int mkdir(const char *path, mode_t mode)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO
 int chmod(const char *path, mode_t mode)
 {
@@ -29,10 +22,3 @@ mode_t umask(mode_t mask)
     unimplemented("mask: %d", mask);
     return 0;
 }
-
-// TODO
-int fstatat(int fd, const char *restrict path, struct stat *restrict st, int flag)
-{
-    unimplemented();
-    return 0;
-}
@@ -174,13 +174,6 @@ void tzset()
     return;
 }
 
-// TODO
-int setitimer(int _which, const struct itimerval *restrict _new, struct itimerval *restrict _old)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO
 char *ctime_r(const time_t *t, char *buf)
 {
@@ -188,11 +181,13 @@ char *ctime_r(const time_t *t, char *buf)
     return NULL;
 }
 
-// TODO
 clock_t clock(void)
 {
-    unimplemented();
-    return 0;
+    struct timespec ts;
+
+    if (clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &ts))
+        return -1;
+    return ts.tv_sec * CLOCKS_PER_SEC + ts.tv_nsec / (1000000000 / CLOCKS_PER_SEC);
 }
 
 #ifdef AX_CONFIG_FP_SIMD
@@ -97,13 +97,21 @@
 #define POSIX_FADV_NOREUSE  5
 #endif
 
-#define AT_FDCWD      (-100)
-#define AT_EMPTY_PATH 0x1000
+#define AT_FDCWD            (-100)
+#define AT_SYMLINK_NOFOLLOW 0x100
+#define AT_REMOVEDIR        0x200
+#define AT_SYMLINK_FOLLOW   0x400
+#define AT_EMPTY_PATH       0x1000
 
 #define SYNC_FILE_RANGE_WAIT_BEFORE 1
 #define SYNC_FILE_RANGE_WRITE       2
 #define SYNC_FILE_RANGE_WAIT_AFTER  4
 
+#define SPLICE_F_MOVE     1
+#define SPLICE_F_NONBLOCK 2
+#define SPLICE_F_MORE     4
+#define SPLICE_F_GIFT     8
+
 #define loff_t off_t
 
 struct flock {
@@ -119,5 +127,13 @@ int posix_fadvise(int __fd, unsigned long __offset, unsigned long __len, int __a
 int sync_file_range(int, off_t, off_t, unsigned);
 
 int open(const char *filename, int flags, ...);
+int openat(int dirfd, const char *filename, int flags, ...);
+
+#ifdef AX_CONFIG_PIPE
+struct iovec;
+ssize_t splice(int, off_t *, int, off_t *, size_t, unsigned);
+ssize_t tee(int, int, size_t, unsigned);
+ssize_t vmsplice(int, const struct iovec *, size_t, unsigned);
+#endif
 
 #endif
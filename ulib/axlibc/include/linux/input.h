@@ -0,0 +1,43 @@
+#ifndef _LINUX_INPUT_H
+#define _LINUX_INPUT_H
+
+#include <stdint.h>
+#include <sys/time.h>
+
+struct input_event {
+    struct timeval time;
+    uint16_t type;
+    uint16_t code;
+    int32_t value;
+};
+
+#define EV_VERSION 0x010001
+
+#define EVIOCGVERSION 0x80044501
+#define EVIOCGREP     0x80084503
+#define EVIOCSREP     0x40084503
+/* EVIOCGLED(len), as _IOC(_IOC_READ, 'E', 0x19, len) */
+#define EVIOCGLED_BASE  0x80004519
+#define EVIOCGLED(len)  (EVIOCGLED_BASE | ((len) << 16))
+
+#define EV_SYN 0x00
+#define EV_KEY 0x01
+#define EV_REL 0x02
+#define EV_ABS 0x03
+#define EV_MSC 0x04
+#define EV_LED 0x11
+#define EV_REP 0x14
+
+#define SYN_REPORT  0
+#define SYN_DROPPED 3
+
+#define LED_NUML    0x00
+#define LED_CAPSL   0x01
+#define LED_SCROLLL 0x02
+#define LED_MAX     0x0f
+
+#define REP_DELAY  0x00
+#define REP_PERIOD 0x01
+#define REP_MAX    0x01
+
+#endif // _LINUX_INPUT_H
@@ -0,0 +1,37 @@
+#ifndef _MQUEUE_H
+#define _MQUEUE_H
+
+#include <fcntl.h>
+#include <signal.h>
+#include <sys/types.h>
+#include <time.h>
+
+typedef int mqd_t;
+
+struct mq_attr {
+    long mq_flags;
+    long mq_maxmsg;
+    long mq_msgsize;
+    long mq_curmsgs;
+    long __unused[4];
+};
+
+#define MQ_PRIO_MAX 32768
+
+#ifdef AX_CONFIG_MQUEUE
+
+mqd_t mq_open(const char *, int, ...);
+int mq_close(mqd_t);
+int mq_unlink(const char *);
+int mq_send(mqd_t, const char *, size_t, unsigned);
+int mq_timedsend(mqd_t, const char *, size_t, unsigned, const struct timespec *);
+ssize_t mq_receive(mqd_t, char *, size_t, unsigned *);
+ssize_t mq_timedreceive(mqd_t, char *__restrict, size_t, unsigned *__restrict,
+                        const struct timespec *__restrict);
+int mq_getattr(mqd_t, struct mq_attr *);
+int mq_setattr(mqd_t, const struct mq_attr *__restrict, struct mq_attr *__restrict);
+int mq_notify(mqd_t, const struct sigevent *);
+
+#endif // AX_CONFIG_MQUEUE
+
+#endif // _MQUEUE_H
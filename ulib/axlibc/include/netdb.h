@@ -51,6 +51,9 @@ struct address {
 #define NI_DGRAM        0x10
 #define NI_NUMERICSCOPE 0x100
 
+#define NI_MAXHOST 255
+#define NI_MAXSERV 32
+
 #define EAI_BADFLAGS -1
 #define EAI_NONAME   -2
 #define EAI_AGAIN    -3
@@ -78,6 +81,8 @@ const char *hstrerror(int ecode);
 
 int getaddrinfo(const char *, const char *, const struct addrinfo *, struct addrinfo **);
 void freeaddrinfo(struct addrinfo *);
+int getnameinfo(const struct sockaddr *__restrict, socklen_t, char *__restrict, socklen_t,
+                char *__restrict, socklen_t, int);
 const char *gai_strerror(int __ecode);
 
 #endif // AX_CONFIG_NET
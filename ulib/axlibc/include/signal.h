@@ -4,6 +4,7 @@
 #include <pthread.h>
 #include <stddef.h>
 #include <stdint.h>
+#include <sys/types.h>
 
 typedef int sig_atomic_t;
 
@@ -14,6 +15,29 @@ union sigval {
 
 typedef union sigval __sigval_t;
 
+#define SIGEV_SIGNAL    0
+#define SIGEV_NONE      1
+#define SIGEV_THREAD    2
+#define SIGEV_THREAD_ID 4
+
+struct sigevent {
+    union sigval sigev_value;
+    int sigev_signo;
+    int sigev_notify;
+    union {
+        char __pad[64 - 2 * sizeof(int) - sizeof(union sigval)];
+        pid_t sigev_notify_thread_id;
+        struct {
+            void (*sigev_notify_function)(union sigval);
+            pthread_attr_t *sigev_notify_attributes;
+        } __sev_thread;
+    } __sev_fields;
+};
+
+#define sigev_notify_thread_id  __sev_fields.sigev_notify_thread_id
+#define sigev_notify_function   __sev_fields.__sev_thread.sigev_notify_function
+#define sigev_notify_attributes __sev_fields.__sev_thread.sigev_notify_attributes
+
 #define SA_NOCLDSTOP 1
 #define SA_NOCLDWAIT 2
 #define SA_SIGINFO   4
@@ -169,6 +193,7 @@ int sigemptyset(sigset_t *);
 int raise(int);
 int sigaddset(sigset_t *, int);
 int pthread_sigmask(int, const sigset_t *__restrict, sigset_t *__restrict);
+int sigprocmask(int, const sigset_t *__restrict, sigset_t *__restrict);
 
 int kill(pid_t, int);
 
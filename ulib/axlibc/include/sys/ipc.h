@@ -0,0 +1,34 @@
+#ifndef _SYS_IPC_H
+#define _SYS_IPC_H
+
+#include <sys/time.h>
+#include <sys/types.h>
+
+typedef int key_t;
+
+struct ipc_perm {
+    key_t __key;
+    uid_t uid;
+    gid_t gid;
+    uid_t cuid;
+    gid_t cgid;
+    mode_t mode;
+    int __seq;
+    long __unused1;
+    long __unused2;
+};
+
+#define IPC_PRIVATE ((key_t)0)
+
+#define IPC_CREAT  01000
+#define IPC_EXCL   02000
+#define IPC_NOWAIT 04000
+
+#define IPC_RMID 0
+#define IPC_SET  1
+#define IPC_STAT 2
+#define IPC_INFO 3
+
+key_t ftok(const char *, int);
+
+#endif // _SYS_IPC_H
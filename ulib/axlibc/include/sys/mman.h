@@ -30,7 +30,11 @@
 #else
 #define MAP_ANONYMOUS 0x20 /* Don't use a file.  */
 #endif
-#define MAP_ANON MAP_ANONYMOUS
+#define MAP_ANON            MAP_ANONYMOUS
+#define MAP_NORESERVE       0x4000   /* Don't check for reservations.  */
+#define MAP_POPULATE        0x8000   /* Populate (prefault) pagetables.  */
+#define MAP_STACK           0x20000  /* Allocation is for a stack.  */
+#define MAP_FIXED_NOREPLACE 0x100000 /* MAP_FIXED but do not unmap underlying mapping.  */
 /* When MAP_HUGETLB is set bits [26:31] encode the log2 of the huge page size.  */
 #define MAP_HUGE_SHIFT 26
 #define MAP_HUGE_MASK  0x3f
@@ -49,4 +53,11 @@ void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
 int mprotect(void *addr, size_t len, int prot);
 int madvise(void *addr, size_t length, int advice);
 
+#ifdef AX_CONFIG_SHM
+
+int shm_open(const char *name, int flag, mode_t mode);
+int shm_unlink(const char *name);
+
+#endif // AX_CONFIG_SHM
+
 #endif
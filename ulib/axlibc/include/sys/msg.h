@@ -0,0 +1,34 @@
+#ifndef _SYS_MSG_H
+#define _SYS_MSG_H
+
+#include <sys/ipc.h>
+
+typedef unsigned long msgqnum_t;
+typedef unsigned long msglen_t;
+
+#define MSG_NOERROR 010000
+#define MSG_EXCEPT  020000
+
+struct msqid_ds {
+    struct ipc_perm msg_perm;
+    time_t msg_stime;
+    time_t msg_rtime;
+    time_t msg_ctime;
+    unsigned long __msg_cbytes;
+    msgqnum_t msg_qnum;
+    msglen_t msg_qbytes;
+    pid_t msg_lspid;
+    pid_t msg_lrpid;
+    unsigned long __unused[2];
+};
+
+#ifdef AX_CONFIG_SYSVIPC
+
+int msgget(key_t, int);
+int msgctl(int, int, struct msqid_ds *);
+int msgsnd(int, const void *, size_t, int);
+ssize_t msgrcv(int, void *, size_t, long, int);
+
+#endif // AX_CONFIG_SYSVIPC
+
+#endif // _SYS_MSG_H
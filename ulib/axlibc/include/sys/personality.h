@@ -0,0 +1,9 @@
+#ifndef _SYS_PERSONALITY_H
+#define _SYS_PERSONALITY_H
+
+#define PER_LINUX       0x0000
+#define PER_MASK        0x00ff
+
+int personality(unsigned long);
+
+#endif // _SYS_PERSONALITY_H
@@ -1,4 +1,18 @@
 #ifndef _SYS_PRCTL_H
 #define _SYS_PRCTL_H
 
+#define PR_SET_PDEATHSIG    1
+#define PR_GET_PDEATHSIG    2
+#define PR_GET_DUMPABLE     3
+#define PR_SET_DUMPABLE     4
+#define PR_SET_NAME         15
+#define PR_GET_NAME         16
+#define PR_SET_NO_NEW_PRIVS 38
+#define PR_GET_NO_NEW_PRIVS 39
+
+/* The maximum length of a task name, including the trailing NUL */
+#define PR_NAME_LEN 16
+
+int prctl(int, ...);
+
 #endif // _SYS_PRCTL_H
@@ -0,0 +1,40 @@
+#ifndef _SYS_SEM_H
+#define _SYS_SEM_H
+
+#include <sys/ipc.h>
+
+#define SEM_UNDO 0x1000
+
+#define GETPID  11
+#define GETVAL  12
+#define GETALL  13
+#define GETNCNT 14
+#define GETZCNT 15
+#define SETVAL  16
+#define SETALL  17
+
+struct semid_ds {
+    struct ipc_perm sem_perm;
+    time_t sem_otime;
+    time_t sem_ctime;
+    unsigned long sem_nsems;
+    unsigned long __unused3;
+    unsigned long __unused4;
+};
+
+struct sembuf {
+    unsigned short sem_num;
+    short sem_op;
+    short sem_flg;
+};
+
+#ifdef AX_CONFIG_SYSVIPC
+
+int semget(key_t, int, int);
+int semctl(int, int, int, ...);
+int semop(int, struct sembuf *, size_t);
+int semtimedop(int, struct sembuf *, size_t, const struct timespec *);
+
+#endif // AX_CONFIG_SYSVIPC
+
+#endif // _SYS_SEM_H
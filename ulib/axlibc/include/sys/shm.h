@@ -0,0 +1,37 @@
+#ifndef _SYS_SHM_H
+#define _SYS_SHM_H
+
+#include <sys/ipc.h>
+
+#define SHMLBA 4096
+
+#define SHM_RDONLY 010000
+#define SHM_RND    020000
+#define SHM_REMAP  040000
+#define SHM_EXEC   0100000
+
+typedef unsigned long shmatt_t;
+
+struct shmid_ds {
+    struct ipc_perm shm_perm;
+    size_t shm_segsz;
+    time_t shm_atime;
+    time_t shm_dtime;
+    time_t shm_ctime;
+    pid_t shm_cpid;
+    pid_t shm_lpid;
+    shmatt_t shm_nattch;
+    unsigned long __pad1;
+    unsigned long __pad2;
+};
+
+#if defined(AX_CONFIG_SYSVIPC) && defined(AX_CONFIG_SHM)
+
+int shmget(key_t, size_t, int);
+void *shmat(int, const void *, int);
+int shmdt(const void *);
+int shmctl(int, int, struct shmid_ds *);
+
+#endif // AX_CONFIG_SYSVIPC && AX_CONFIG_SHM
+
+#endif // _SYS_SHM_H
@@ -53,6 +53,11 @@ struct sockaddr_storage {
     unsigned long __ss_align;
 };
 
+struct linger {
+    int l_onoff;
+    int l_linger;
+};
+
 int socket(int, int, int);
 int shutdown(int, int);
 
@@ -68,6 +73,7 @@ ssize_t sendto(int, const void *, size_t, int, const struct sockaddr *, socklen_
 ssize_t recvfrom(int, void *__restrict, size_t, int, struct sockaddr *__restrict,
                  socklen_t *__restrict);
 ssize_t sendmsg(int, const struct msghdr *, int);
+ssize_t recvmsg(int, struct msghdr *, int);
 
 int getsockopt(int, int, int, void *__restrict, socklen_t *__restrict);
 int setsockopt(int, int, int, const void *, socklen_t);
@@ -114,7 +120,15 @@ int getpeername(int sockfd, struct sockaddr *restrict addr, socklen_t *restrict
 #define SO_PREFER_BUSY_POLL        69
 #define SO_BUSY_POLL_BUDGET        70
 
-#define MSG_NOSIGNAL 0x4000
+#define MSG_OOB       0x0001
+#define MSG_PEEK      0x0002
+#define MSG_DONTROUTE 0x0004
+#define MSG_CTRUNC    0x0008
+#define MSG_TRUNC     0x0020
+#define MSG_DONTWAIT  0x0040
+#define MSG_EOR       0x0080
+#define MSG_WAITALL   0x0100
+#define MSG_NOSIGNAL  0x4000
 
 #define SHUT_RD   0
 #define SHUT_WR   1
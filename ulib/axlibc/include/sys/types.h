@@ -17,4 +17,6 @@ typedef int pid_t;
 typedef unsigned uid_t;
 typedef unsigned gid_t;
 
+typedef void *timer_t;
+
 #endif // __SYS_TYPES_H__
@@ -3,10 +3,19 @@
 
 #include <stddef.h>
 #include <sys/time.h>
+#include <sys/types.h>
 
-#define CLOCK_REALTIME  0
-#define CLOCK_MONOTONIC 1
-#define CLOCKS_PER_SEC  1000000L
+#define CLOCK_REALTIME           0
+#define CLOCK_MONOTONIC          1
+#define CLOCK_PROCESS_CPUTIME_ID 2
+#define CLOCK_THREAD_CPUTIME_ID  3
+#define CLOCK_MONOTONIC_RAW      4
+#define CLOCK_REALTIME_COARSE    5
+#define CLOCK_MONOTONIC_COARSE   6
+#define CLOCK_BOOTTIME           7
+#define CLOCKS_PER_SEC           1000000L
+
+#define TIMER_ABSTIME 1
 
 struct tm {
     int tm_sec;   /* seconds of minute */
@@ -22,6 +31,13 @@ struct tm {
     const char *__tm_zone;
 };
 
+struct itimerspec {
+    struct timespec it_interval;
+    struct timespec it_value;
+};
+
+struct sigevent;
+
 clock_t clock(void);
 time_t time(time_t *);
 double difftime(time_t, time_t);
@@ -38,6 +54,16 @@ char *ctime_r(const time_t *, char *);
 void tzset(void);
 
 int nanosleep(const struct timespec *requested_time, struct timespec *remaining);
+int clock_nanosleep(clockid_t _clk, int flags, const struct timespec *requested_time,
+                    struct timespec *remaining);
 int clock_gettime(clockid_t _clk, struct timespec *ts);
+int clock_settime(clockid_t _clk, const struct timespec *ts);
+int clock_getres(clockid_t _clk, struct timespec *res);
+
+int timer_create(clockid_t, struct sigevent *__restrict, timer_t *__restrict);
+int timer_delete(timer_t);
+int timer_settime(timer_t, int, const struct itimerspec *__restrict, struct itimerspec *__restrict);
+int timer_gettime(timer_t, struct itimerspec *);
+int timer_getoverrun(timer_t);
 
 #endif // __TIME_H__
@@ -0,0 +1,12 @@
+#ifndef _UIO_H
+#define _UIO_H
+
+#include <fcntl.h>
+
+#ifdef AX_CONFIG_UIO
+
+int uio_open(int index, int irq, int flags);
+
+#endif // AX_CONFIG_UIO
+
+#endif // _UIO_H
@@ -63,12 +63,16 @@ char *getcwd(char *, size_t);
 unsigned alarm(unsigned);
 unsigned sleep(unsigned);
 int pause(void);
+int nice(int);
 int usleep(unsigned);
 
 pid_t fork(void);
 int execve(const char *, char *const[], char *const[]);
 _Noreturn void _exit(int);
 
+int brk(void *);
+void *sbrk(intptr_t);
+
 pid_t getpid(void);
 pid_t getppid(void);
 pid_t getpgrp(void);
@@ -1,6 +1,5 @@
-use crate::{ctypes, utils::e};
-use arceos_posix_api::{sys_close, sys_dup, sys_dup2, sys_fcntl};
-use axerrno::LinuxError;
+use crate::utils::e;
+use arceos_posix_api::{sys_close, sys_dup, sys_dup2, sys_dup3, sys_fcntl, sys_ioctl};
 use core::ffi::c_int;
 
 /// Close a file by `fd`.
@@ -27,28 +26,17 @@ pub unsafe extern "C" fn dup2(old_fd: c_int, new_fd: c_int) -> c_int {
 /// If oldfd equals newfd, then `dup3()` fails with the error `EINVAL`.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn dup3(old_fd: c_int, new_fd: c_int, flags: c_int) -> c_int {
-    if old_fd == new_fd {
-        return e((LinuxError::EINVAL as c_int).wrapping_neg());
-    }
-    let r = e(sys_dup2(old_fd, new_fd));
-    if r < 0 {
-        r
-    } else {
-        if flags as u32 & ctypes::O_CLOEXEC != 0 {
-            e(sys_fcntl(
-                new_fd,
-                ctypes::F_SETFD as c_int,
-                ctypes::FD_CLOEXEC as usize,
-            ));
-        }
-        new_fd
-    }
+    e(sys_dup3(old_fd, new_fd, flags))
 }
 
 /// Manipulate file descriptor.
-///
-/// TODO: `SET/GET` command is ignored
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn ax_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
     e(sys_fcntl(fd, cmd, arg))
 }
+
+/// Perform a device-specific request on a file descriptor.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_ioctl(fd: c_int, request: c_int, arg: usize) -> c_int {
+    e(sys_ioctl(fd, request, arg))
+}
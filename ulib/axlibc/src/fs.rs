@@ -1,7 +1,10 @@
 use core::ffi::{c_char, c_int};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_fdatasync, sys_flock, sys_fstat, sys_fstatat, sys_fstatfs, sys_fsync, sys_getcwd, sys_link,
+    sys_linkat, sys_lseek, sys_lstat, sys_mkdir, sys_mkdirat, sys_open, sys_openat, sys_quotactl,
+    sys_readlink, sys_readlinkat, sys_rename, sys_renameat, sys_rmdir, sys_sendfile, sys_stat,
+    sys_statfs, sys_symlink, sys_symlinkat, sys_unlink, sys_unlinkat,
 };
 
 use crate::{ctypes, utils::e};
@@ -19,6 +22,20 @@ pub unsafe extern "C" fn ax_open(
     e(sys_open(filename, flags, mode))
 }
 
+/// Open a file by `filename` relative to the directory `dirfd`, and insert it
+/// into the file descriptor table.
+///
+/// Return its index in the file table (`fd`).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    e(sys_openat(dirfd, filename, flags, mode))
+}
+
 /// Set the position of the file indicated by `fd`.
 ///
 /// Return its position after seek.
@@ -27,6 +44,28 @@ pub unsafe extern "C" fn lseek(fd: c_int, offset: ctypes::off_t, whence: c_int)
     e(sys_lseek(fd, offset, whence) as _) as _
 }
 
+/// Apply or remove an advisory lock on the open file `fd`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn flock(fd: c_int, operation: c_int) -> c_int {
+    e(sys_flock(fd, operation))
+}
+
+/// Write the data and the metadata of the file `fd` to the disk.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fsync(fd: c_int) -> c_int {
+    e(sys_fsync(fd))
+}
+
+/// Write the data of the file `fd` to the disk.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fdatasync(fd: c_int) -> c_int {
+    e(sys_fdatasync(fd))
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -51,6 +90,82 @@ pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut ctypes::stat) -> c
     e(sys_lstat(path, buf) as _)
 }
 
+/// Get the metadata of the file `path` relative to the directory `dirfd`, and
+/// write into `buf`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    e(sys_fstatat(dirfd, path, buf, flags))
+}
+
+/// Get the statistics of the filesystem holding the file `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_statfs(path, buf))
+}
+
+/// Get the statistics of the filesystem holding the file `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_fstatfs(fd, buf))
+}
+
+/// Create a symbolic link `linkpath` whose content is `target`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    e(sys_symlink(target, linkpath))
+}
+
+/// Create a symbolic link `linkpath` relative to the directory `newdirfd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn symlinkat(
+    target: *const c_char,
+    newdirfd: c_int,
+    linkpath: *const c_char,
+) -> c_int {
+    e(sys_symlinkat(target, newdirfd, linkpath))
+}
+
+/// Read the content of the symbolic link `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlink(path, buf, bufsiz) as _) as _
+}
+
+/// Read the content of the symbolic link `path` relative to the directory
+/// `dirfd` into `buf`.
+///
+/// Return the number of bytes placed in `buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlinkat(dirfd, path, buf, bufsiz) as _) as _
+}
+
 /// Get the path of the current directory.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
@@ -65,3 +180,107 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    e(sys_renameat(olddirfd, old, newdirfd, new))
+}
+
+/// Create a directory `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdir(path, mode))
+}
+
+/// Create a directory `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(dirfd, path, mode))
+}
+
+/// Remove the file `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unlink(path: *const c_char) -> c_int {
+    e(sys_unlink(path))
+}
+
+/// Remove the file or the directory `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    e(sys_unlinkat(dirfd, path, flags))
+}
+
+/// Remove the empty directory `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmdir(path: *const c_char) -> c_int {
+    e(sys_rmdir(path))
+}
+
+/// Create a hard link `new` to the file `old`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn link(old: *const c_char, new: *const c_char) -> c_int {
+    e(sys_link(old, new))
+}
+
+/// Create a hard link `new` relative to the directory `newdirfd` to the file
+/// `old` relative to the directory `olddirfd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn linkat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_int,
+) -> c_int {
+    e(sys_linkat(olddirfd, old, newdirfd, new, flags))
+}
+
+/// Manipulate disk quotas.
+///
+/// Quotas are not supported, it always fails with `ENOSYS`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quotactl(
+    cmd: c_int,
+    special: *const c_char,
+    id: c_int,
+    addr: *mut c_char,
+) -> c_int {
+    e(sys_quotactl(cmd, special, id, addr))
+}
+
+/// Copy data from the file `in_fd` to `out_fd`, e.g. a socket, inside the
+/// kernel.
+///
+/// Return the number of bytes written to `out_fd`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sendfile(
+    out_fd: c_int,
+    in_fd: c_int,
+    offset: *mut ctypes::off_t,
+    count: usize,
+) -> ctypes::ssize_t {
+    e(sys_sendfile(out_fd, in_fd, offset, count) as _) as _
+}
@@ -0,0 +1,32 @@
+use core::ffi::{c_char, c_int};
+
+use arceos_posix_api::{sys_inotify_add_watch, sys_inotify_init1, sys_inotify_rm_watch};
+
+use crate::utils::e;
+
+/// Create an inotify instance.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_init() -> c_int {
+    e(sys_inotify_init1(0))
+}
+
+/// Create an inotify instance with `flags`, which may contain `IN_NONBLOCK`
+/// and `IN_CLOEXEC`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_init1(flags: c_int) -> c_int {
+    e(sys_inotify_init1(flags))
+}
+
+/// Watch the events in `mask` of the file at `pathname`.
+///
+/// Return the watch descriptor
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int {
+    e(sys_inotify_add_watch(fd, pathname, mask))
+}
+
+/// Remove the watch `wd` from an inotify instance.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    e(sys_inotify_rm_watch(fd, wd))
+}
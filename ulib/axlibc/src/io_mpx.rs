@@ -1,11 +1,13 @@
 use crate::{ctypes, utils::e};
 
 use core::ffi::c_int;
-
 #[cfg(feature = "select")]
-use arceos_posix_api::sys_select;
+use core::ffi::c_void;
+
 #[cfg(feature = "epoll")]
 use arceos_posix_api::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
+#[cfg(feature = "select")]
+use arceos_posix_api::{sys_pselect6, sys_select};
 
 /// Creates a new epoll instance.
 ///
@@ -52,3 +54,20 @@ pub unsafe extern "C" fn select(
 ) -> c_int {
     e(sys_select(nfds, readfds, writefds, exceptfds, timeout))
 }
+
+/// Like `select`, but with a `timespec` timeout and a signal mask to be set
+/// during the call.
+#[cfg(feature = "select")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pselect(
+    nfds: c_int,
+    readfds: *mut ctypes::fd_set,
+    writefds: *mut ctypes::fd_set,
+    exceptfds: *mut ctypes::fd_set,
+    timeout: *const ctypes::timespec,
+    sigmask: *const c_void,
+) -> c_int {
+    e(sys_pselect6(
+        nfds, readfds, writefds, exceptfds, timeout, sigmask,
+    ))
+}
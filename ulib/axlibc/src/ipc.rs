@@ -0,0 +1,113 @@
+use core::ffi::{c_int, c_long, c_void};
+
+use arceos_posix_api::{
+    sys_msgctl, sys_msgget, sys_msgrcv, sys_msgsnd, sys_semctl, sys_semget, sys_semtimedop,
+};
+#[cfg(feature = "shm")]
+use arceos_posix_api::{sys_shmat, sys_shmctl, sys_shmdt, sys_shmget};
+
+use crate::{ctypes, utils::e};
+
+/// Get a System V semaphore set identifier.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn semget(key: ctypes::key_t, nsems: c_int, semflg: c_int) -> c_int {
+    e(sys_semget(key, nsems, semflg))
+}
+
+/// System V semaphore control operations.
+///
+/// TODO: remove this function in future work
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_semctl(semid: c_int, semnum: c_int, cmd: c_int, arg: usize) -> c_int {
+    e(sys_semctl(semid, semnum, cmd, arg))
+}
+
+/// Perform operations on selected semaphores in a System V semaphore set.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn semop(semid: c_int, sops: *mut ctypes::sembuf, nsops: usize) -> c_int {
+    e(sys_semtimedop(semid, sops, nsops, core::ptr::null()))
+}
+
+/// Perform operations on selected semaphores in a System V semaphore set,
+/// with a relative timeout.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn semtimedop(
+    semid: c_int,
+    sops: *mut ctypes::sembuf,
+    nsops: usize,
+    timeout: *const ctypes::timespec,
+) -> c_int {
+    e(sys_semtimedop(semid, sops, nsops, timeout))
+}
+
+/// Get a System V message queue identifier.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn msgget(key: ctypes::key_t, msgflg: c_int) -> c_int {
+    e(sys_msgget(key, msgflg))
+}
+
+/// System V message queue control operations.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn msgctl(msqid: c_int, cmd: c_int, buf: *mut ctypes::msqid_ds) -> c_int {
+    e(sys_msgctl(msqid, cmd, buf))
+}
+
+/// Send a message to a System V message queue.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn msgsnd(
+    msqid: c_int,
+    msgp: *const c_void,
+    msgsz: usize,
+    msgflg: c_int,
+) -> c_int {
+    e(sys_msgsnd(msqid, msgp, msgsz, msgflg))
+}
+
+/// Receive a message from a System V message queue.
+///
+/// Return the size of the received message text if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn msgrcv(
+    msqid: c_int,
+    msgp: *mut c_void,
+    msgsz: usize,
+    msgtyp: c_long,
+    msgflg: c_int,
+) -> ctypes::ssize_t {
+    e(sys_msgrcv(msqid, msgp, msgsz, msgtyp, msgflg) as _) as _
+}
+
+/// Get a System V shared memory segment identifier.
+#[cfg(feature = "shm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shmget(key: ctypes::key_t, size: usize, shmflg: c_int) -> c_int {
+    e(sys_shmget(key, size, shmflg))
+}
+
+/// Attach a System V shared memory segment.
+///
+/// Return the address of the attached segment, or `(void *)-1` on error.
+#[cfg(feature = "shm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shmat(shmid: c_int, shmaddr: *const c_void, shmflg: c_int) -> *mut c_void {
+    let ret = sys_shmat(shmid, shmaddr, shmflg) as isize;
+    if (-4095..0).contains(&ret) {
+        e(ret as c_int) as isize as *mut c_void
+    } else {
+        ret as *mut c_void
+    }
+}
+
+/// Detach a System V shared memory segment.
+#[cfg(feature = "shm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shmdt(shmaddr: *const c_void) -> c_int {
+    e(sys_shmdt(shmaddr))
+}
+
+/// System V shared memory control operations.
+#[cfg(feature = "shm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shmctl(shmid: c_int, cmd: c_int, buf: *mut ctypes::shmid_ds) -> c_int {
+    e(sys_shmctl(shmid, cmd, buf))
+}
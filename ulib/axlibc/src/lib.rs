@@ -18,12 +18,26 @@
 //! - Lib C functions
 //!     - `fd`: Enable file descriptor table.
 //!     - `pipe`: Enable pipe support.
+//!     - `mqueue`: Enable POSIX message queue support.
+//!     - `sysvipc`: Enable System V semaphore and message queue support.
+//!     - `signal`: Enable POSIX signal support.
 //!     - `select`: Enable synchronous I/O multiplexing ([select]) support.
 //!     - `epoll`: Enable event polling ([epoll]) support.
+//!     - `mmap`: Enable memory mapping ([mmap]) and program break (`brk`) support.
+//!     - `shm`: Enable POSIX shared memory objects (`shm_open`) in `/dev/shm`, and
+//!       System V shared memory segments (`shmget`/`shmat`) with `sysvipc`.
+//!     - `uio`: Enable user-space drivers of the PCI devices not claimed by the kernel.
+//!     - `fb`: Enable the framebuffer device `/dev/fb0` to be mapped by [mmap].
+//!     - `input`: Enable the input device `/dev/input/event0` with the Linux evdev interface.
+//!     - `inotify`: Enable watching the changes of the files ([inotify]).
+//!     - `timerfd`: Enable the timers notified through file descriptors ([timerfd]).
 //!
 //! [ArceOS]: https://github.com/arceos-org/arceos
 //! [select]: https://man7.org/linux/man-pages/man2/select.2.html
 //! [epoll]: https://man7.org/linux/man-pages/man7/epoll.7.html
+//! [mmap]: https://man7.org/linux/man-pages/man2/mmap.2.html
+//! [inotify]: https://man7.org/linux/man-pages/man7/inotify.7.html
+//! [timerfd]: https://man7.org/linux/man-pages/man2/timerfd_create.2.html
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
 #![feature(doc_cfg)]
@@ -53,20 +67,36 @@ mod utils;
 mod fd_ops;
 #[cfg(feature = "fs")]
 mod fs;
+#[cfg(feature = "inotify")]
+mod inotify;
 #[cfg(any(feature = "select", feature = "epoll"))]
 mod io_mpx;
+#[cfg(feature = "sysvipc")]
+mod ipc;
 #[cfg(feature = "alloc")]
 mod malloc;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mqueue")]
+mod mqueue;
 #[cfg(feature = "net")]
 mod net;
 #[cfg(feature = "pipe")]
 mod pipe;
 #[cfg(feature = "multitask")]
 mod pthread;
+#[cfg(feature = "multitask")]
+mod sched;
+#[cfg(feature = "signal")]
+mod signal;
 #[cfg(feature = "alloc")]
 mod strftime;
 #[cfg(feature = "fp_simd")]
 mod strtod;
+#[cfg(feature = "timerfd")]
+mod timerfd;
+#[cfg(feature = "uio")]
+mod uio;
 
 mod errno;
 mod io;
@@ -84,42 +114,87 @@ pub use self::io::{read, writev};
 
 pub use self::errno::strerror;
 pub use self::mktime::mktime;
-pub use self::rand::{rand, random, srand};
-pub use self::resource::{getrlimit, setrlimit};
+pub use self::rand::{getrandom, rand, random, srand};
+pub use self::resource::{getrlimit, prlimit, setrlimit};
 pub use self::setjmp::{longjmp, setjmp};
-pub use self::sys::sysconf;
-pub use self::time::{clock_gettime, nanosleep};
+pub use self::sys::{ax_prctl, personality, sysconf};
+pub use self::time::{clock_getres, clock_gettime, clock_nanosleep, clock_settime, nanosleep};
 pub use self::unistd::{abort, exit, getpid};
 
+#[cfg(feature = "multitask")]
+pub use self::resource::{getpriority, setpriority};
+#[cfg(feature = "multitask")]
+pub use self::unistd::nice;
+
 #[cfg(feature = "alloc")]
 pub use self::malloc::{free, malloc};
 #[cfg(feature = "alloc")]
 pub use self::strftime::strftime;
 
 #[cfg(feature = "fd")]
-pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
+pub use self::fd_ops::{ax_fcntl, ax_ioctl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{
+    ax_open, ax_openat, flock, fstat, fstatat, fstatfs, getcwd, link, linkat, lseek, lstat, mkdir,
+    mkdirat, quotactl, readlink, readlinkat, rename, renameat, rmdir, sendfile, stat, statfs,
+    symlink, symlinkat, unlink, unlinkat,
+};
+
+#[cfg(feature = "sysvipc")]
+pub use self::ipc::{ax_semctl, msgctl, msgget, msgrcv, msgsnd, semget, semop, semtimedop};
+#[cfg(all(feature = "sysvipc", feature = "shm"))]
+pub use self::ipc::{shmat, shmctl, shmdt, shmget};
+
+#[cfg(feature = "mmap")]
+pub use self::mmap::{brk, mmap, mprotect, munmap, sbrk};
+#[cfg(feature = "shm")]
+pub use self::mmap::{shm_open, shm_unlink};
+
+#[cfg(feature = "mqueue")]
+pub use self::mqueue::{
+    ax_mq_open, mq_close, mq_getattr, mq_notify, mq_receive, mq_send, mq_setattr, mq_timedreceive,
+    mq_timedsend, mq_unlink,
+};
 
 #[cfg(feature = "net")]
 pub use self::net::{
-    accept, bind, connect, freeaddrinfo, getaddrinfo, getpeername, getsockname, listen, recv,
-    recvfrom, send, sendto, shutdown, socket,
+    accept, bind, connect, freeaddrinfo, getaddrinfo, getnameinfo, getpeername, getsockname,
+    getsockopt, listen, recv, recvfrom, recvmsg, send, sendmsg, sendto, setsockopt, shutdown,
+    socket,
 };
 
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_create, pthread_exit, pthread_join, pthread_self};
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_mutex_init, pthread_mutex_lock, pthread_mutex_unlock};
+#[cfg(feature = "multitask")]
+pub use self::sched::{sched_getaffinity, sched_setaffinity};
+
+#[cfg(feature = "signal")]
+pub use self::signal::{kill, pthread_kill, pthread_sigmask, raise, sigaction, sigprocmask};
+#[cfg(all(feature = "signal", feature = "irq"))]
+pub use self::time::{
+    alarm, getitimer, setitimer, timer_create, timer_delete, timer_getoverrun, timer_gettime,
+    timer_settime,
+};
 
 #[cfg(feature = "pipe")]
-pub use self::pipe::pipe;
+pub use self::pipe::{pipe, splice, tee, vmsplice};
+
+#[cfg(feature = "inotify")]
+pub use self::inotify::{inotify_add_watch, inotify_init, inotify_init1, inotify_rm_watch};
+
+#[cfg(feature = "timerfd")]
+pub use self::timerfd::{timerfd_create, timerfd_gettime, timerfd_settime};
 
-#[cfg(feature = "select")]
-pub use self::io_mpx::select;
 #[cfg(feature = "epoll")]
 pub use self::io_mpx::{epoll_create, epoll_ctl, epoll_wait};
+#[cfg(feature = "select")]
+pub use self::io_mpx::{pselect, select};
 
 #[cfg(feature = "fp_simd")]
 pub use self::strtod::{strtod, strtof};
+
+#[cfg(feature = "uio")]
+pub use self::uio::uio_open;
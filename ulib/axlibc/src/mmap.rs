@@ -0,0 +1,86 @@
+use core::ffi::{c_int, c_void};
+
+use arceos_posix_api::{sys_brk, sys_mmap, sys_mprotect, sys_munmap, sys_sbrk};
+#[cfg(feature = "shm")]
+use arceos_posix_api::{sys_shm_open, sys_shm_unlink};
+
+use crate::{ctypes, utils::e};
+
+/// Map files or devices into memory
+///
+/// Return the start address of the mapping, or `MAP_FAILED` on error
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mmap(
+    addr: *mut c_void,
+    len: ctypes::size_t,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    let ret = sys_mmap(addr, len, prot, flags, fd, off) as isize;
+    // Error codes are returned as addresses in `[-4095, -1]`.
+    if (-4095..0).contains(&ret) {
+        e(ret as c_int) as isize as *mut c_void
+    } else {
+        ret as *mut c_void
+    }
+}
+
+/// Unmap a range of memory
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn munmap(addr: *mut c_void, len: ctypes::size_t) -> c_int {
+    e(sys_munmap(addr, len))
+}
+
+/// Set protection on a region of memory
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mprotect(addr: *mut c_void, len: ctypes::size_t, prot: c_int) -> c_int {
+    e(sys_mprotect(addr, len, prot))
+}
+
+/// Set the program break
+///
+/// Return 0 if succeed
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn brk(addr: *mut c_void) -> c_int {
+    if sys_brk(addr) == addr {
+        0
+    } else {
+        crate::errno::set_errno(axerrno::LinuxError::ENOMEM.code());
+        -1
+    }
+}
+
+/// Change the program break by `increment` bytes
+///
+/// Return the previous program break, or `(void *)-1` on error
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sbrk(increment: isize) -> *mut c_void {
+    let ret = sys_sbrk(increment) as isize;
+    if (-4095..0).contains(&ret) {
+        e(ret as c_int) as isize as *mut c_void
+    } else {
+        ret as *mut c_void
+    }
+}
+
+/// Create or open a POSIX shared memory object
+///
+/// Return the file descriptor of the object
+#[cfg(feature = "shm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shm_open(
+    name: *const core::ffi::c_char,
+    flag: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    e(sys_shm_open(name, flag, mode))
+}
+
+/// Remove a POSIX shared memory object
+#[cfg(feature = "shm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shm_unlink(name: *const core::ffi::c_char) -> c_int {
+    e(sys_shm_unlink(name))
+}
@@ -0,0 +1,106 @@
+use core::ffi::{c_char, c_int, c_uint};
+use core::ptr;
+
+use arceos_posix_api::{
+    sys_close, sys_mq_getsetattr, sys_mq_notify, sys_mq_open, sys_mq_timedreceive,
+    sys_mq_timedsend, sys_mq_unlink,
+};
+
+use crate::{ctypes, utils::e};
+
+/// Open a message queue.
+///
+/// TODO: remove this function in future work
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_mq_open(
+    name: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+    attr: *const ctypes::mq_attr,
+) -> c_int {
+    e(sys_mq_open(name, flags, mode, attr))
+}
+
+/// Close a message queue descriptor.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_close(mqdes: c_int) -> c_int {
+    e(sys_close(mqdes))
+}
+
+/// Remove a message queue.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_unlink(name: *const c_char) -> c_int {
+    e(sys_mq_unlink(name))
+}
+
+/// Send a message to a message queue.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_send(
+    mqdes: c_int,
+    msg_ptr: *const c_char,
+    msg_len: usize,
+    msg_prio: c_uint,
+) -> c_int {
+    e(sys_mq_timedsend(mqdes, msg_ptr, msg_len, msg_prio, ptr::null()))
+}
+
+/// Send a message to a message queue, with an absolute timeout.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_timedsend(
+    mqdes: c_int,
+    msg_ptr: *const c_char,
+    msg_len: usize,
+    msg_prio: c_uint,
+    abs_timeout: *const ctypes::timespec,
+) -> c_int {
+    e(sys_mq_timedsend(mqdes, msg_ptr, msg_len, msg_prio, abs_timeout))
+}
+
+/// Receive a message from a message queue.
+///
+/// Return the size of the received message if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_receive(
+    mqdes: c_int,
+    msg_ptr: *mut c_char,
+    msg_len: usize,
+    msg_prio: *mut c_uint,
+) -> ctypes::ssize_t {
+    e(sys_mq_timedreceive(mqdes, msg_ptr, msg_len, msg_prio, ptr::null()) as _) as _
+}
+
+/// Receive a message from a message queue, with an absolute timeout.
+///
+/// Return the size of the received message if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_timedreceive(
+    mqdes: c_int,
+    msg_ptr: *mut c_char,
+    msg_len: usize,
+    msg_prio: *mut c_uint,
+    abs_timeout: *const ctypes::timespec,
+) -> ctypes::ssize_t {
+    e(sys_mq_timedreceive(mqdes, msg_ptr, msg_len, msg_prio, abs_timeout) as _) as _
+}
+
+/// Get the attributes of a message queue.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_getattr(mqdes: c_int, attr: *mut ctypes::mq_attr) -> c_int {
+    e(sys_mq_getsetattr(mqdes, ptr::null(), attr))
+}
+
+/// Set the attributes of a message queue.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_setattr(
+    mqdes: c_int,
+    newattr: *const ctypes::mq_attr,
+    oldattr: *mut ctypes::mq_attr,
+) -> c_int {
+    e(sys_mq_getsetattr(mqdes, newattr, oldattr))
+}
+
+/// Register for notification when a message is available.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mq_notify(mqdes: c_int, sevp: *const ctypes::sigevent) -> c_int {
+    e(sys_mq_notify(mqdes, sevp))
+}
@@ -1,8 +1,9 @@
 use arceos_posix_api::{
-    sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getpeername,
-    sys_getsockname, sys_listen, sys_recv, sys_recvfrom, sys_send, sys_sendto, sys_shutdown,
-    sys_socket,
+    sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getnameinfo,
+    sys_getpeername, sys_getsockname, sys_getsockopt, sys_listen, sys_recv, sys_recvfrom,
+    sys_recvmsg, sys_send, sys_sendmsg, sys_sendto, sys_setsockopt, sys_shutdown, sys_socket,
 };
+use axerrno::LinuxError;
 use core::ffi::{c_char, c_int, c_void};
 
 use crate::{ctypes, utils::e};
@@ -47,7 +48,7 @@ pub unsafe extern "C" fn sendto(
     socket_fd: c_int,
     buf_ptr: *const c_void,
     len: ctypes::size_t,
-    flag: c_int, // currently not used
+    flag: c_int,
     socket_addr: *const ctypes::sockaddr,
     addrlen: ctypes::socklen_t,
 ) -> ctypes::ssize_t {
@@ -65,7 +66,7 @@ pub unsafe extern "C" fn send(
     socket_fd: c_int,
     buf_ptr: *const c_void,
     len: ctypes::size_t,
-    flag: c_int, // currently not used
+    flag: c_int,
 ) -> ctypes::ssize_t {
     e(sys_send(socket_fd, buf_ptr, len, flag) as _) as _
 }
@@ -78,7 +79,7 @@ pub unsafe extern "C" fn recvfrom(
     socket_fd: c_int,
     buf_ptr: *mut c_void,
     len: ctypes::size_t,
-    flag: c_int, // currently not used
+    flag: c_int,
     socket_addr: *mut ctypes::sockaddr,
     addrlen: *mut ctypes::socklen_t,
 ) -> ctypes::ssize_t {
@@ -96,11 +97,35 @@ pub unsafe extern "C" fn recv(
     socket_fd: c_int,
     buf_ptr: *mut c_void,
     len: ctypes::size_t,
-    flag: c_int, // currently not used
+    flag: c_int,
 ) -> ctypes::ssize_t {
     e(sys_recv(socket_fd, buf_ptr, len, flag) as _) as _
 }
 
+/// Send a message gathered from multiple buffers on a socket.
+///
+/// Return the number of bytes sent if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sendmsg(
+    socket_fd: c_int,
+    msg: *const ctypes::msghdr,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    e(sys_sendmsg(socket_fd, msg, flags) as _) as _
+}
+
+/// Receive a message on a socket into multiple buffers.
+///
+/// Return the number of bytes received if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn recvmsg(
+    socket_fd: c_int,
+    msg: *mut ctypes::msghdr,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    e(sys_recvmsg(socket_fd, msg, flags) as _) as _
+}
+
 /// Listen for connections on a socket
 ///
 /// Return 0 if success.
@@ -128,11 +153,36 @@ pub unsafe extern "C" fn accept(
 ///
 /// Return 0 if success.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn shutdown(
+pub unsafe extern "C" fn shutdown(socket_fd: c_int, how: c_int) -> c_int {
+    e(sys_shutdown(socket_fd, how))
+}
+
+/// Set an option of a socket.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setsockopt(
     socket_fd: c_int,
-    flag: c_int, // currently not used
+    level: c_int,
+    optname: c_int,
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
 ) -> c_int {
-    e(sys_shutdown(socket_fd, flag))
+    e(sys_setsockopt(socket_fd, level, optname, optval, optlen))
+}
+
+/// Get an option of a socket.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> c_int {
+    e(sys_getsockopt(socket_fd, level, optname, optval, optlen))
 }
 
 /// Query addresses for a domain name.
@@ -145,14 +195,47 @@ pub unsafe extern "C" fn getaddrinfo(
     hints: *const ctypes::addrinfo,
     res: *mut *mut ctypes::addrinfo,
 ) -> c_int {
-    let ret = e(sys_getaddrinfo(nodename, servname, hints, res));
-    match ret {
-        r if r < 0 => ctypes::EAI_FAIL,
+    match sys_getaddrinfo(nodename, servname, hints, res) {
+        r if r < 0 => gai_error(r),
         0 => ctypes::EAI_NONAME,
         _ => 0,
     }
 }
 
+/// Get the name of a host and a service from a socket address.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getnameinfo(
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+    host: *mut c_char,
+    hostlen: ctypes::socklen_t,
+    serv: *mut c_char,
+    servlen: ctypes::socklen_t,
+    flags: c_int,
+) -> c_int {
+    match sys_getnameinfo(addr, addrlen, host, hostlen, serv, servlen, flags) {
+        r if r < 0 => gai_error(r),
+        _ => 0,
+    }
+}
+
+/// Converts the error of name resolution to the `EAI_*` code.
+fn gai_error(ret: c_int) -> c_int {
+    match LinuxError::try_from(-ret) {
+        Ok(LinuxError::ENOENT) => ctypes::EAI_NONAME,
+        Ok(LinuxError::EAGAIN) => ctypes::EAI_AGAIN,
+        Ok(LinuxError::EAFNOSUPPORT) => ctypes::EAI_FAMILY,
+        Ok(LinuxError::ESOCKTNOSUPPORT) => ctypes::EAI_SOCKTYPE,
+        Ok(LinuxError::EOVERFLOW) => ctypes::EAI_OVERFLOW,
+        _ => {
+            e(ret);
+            ctypes::EAI_SYSTEM
+        }
+    }
+}
+
 /// Free queried `addrinfo` struct
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn freeaddrinfo(res: *mut ctypes::addrinfo) {
@@ -1,8 +1,8 @@
-use core::ffi::c_int;
+use core::ffi::{c_int, c_uint};
 
-use arceos_posix_api::sys_pipe;
+use arceos_posix_api::{sys_pipe, sys_splice, sys_tee, sys_vmsplice};
 
-use crate::utils::e;
+use crate::{ctypes, utils::e};
 
 /// Create a pipe
 ///
@@ -12,3 +12,44 @@ pub unsafe extern "C" fn pipe(fd: *mut c_int) -> c_int {
     let fds = unsafe { core::slice::from_raw_parts_mut(fd, 2) };
     e(sys_pipe(fds))
 }
+
+/// Splice data to/from a pipe
+///
+/// Return the number of bytes spliced
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn splice(
+    fd_in: c_int,
+    off_in: *mut ctypes::off_t,
+    fd_out: c_int,
+    off_out: *mut ctypes::off_t,
+    len: usize,
+    flags: c_uint,
+) -> ctypes::ssize_t {
+    e(sys_splice(fd_in, off_in, fd_out, off_out, len, flags) as _) as _
+}
+
+/// Duplicate pipe content
+///
+/// Return the number of bytes duplicated
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tee(
+    fd_in: c_int,
+    fd_out: c_int,
+    len: usize,
+    flags: c_uint,
+) -> ctypes::ssize_t {
+    e(sys_tee(fd_in, fd_out, len, flags) as _) as _
+}
+
+/// Splice user pages to/from a pipe
+///
+/// Return the number of bytes transferred
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vmsplice(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    nr_segs: usize,
+    flags: c_uint,
+) -> ctypes::ssize_t {
+    e(sys_vmsplice(fd, iov, nr_segs, flags) as _) as _
+}
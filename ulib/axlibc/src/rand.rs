@@ -1,10 +1,14 @@
 //! Random number generator.
 
 use core::{
-    ffi::{c_int, c_long, c_uint},
+    ffi::{c_int, c_long, c_uint, c_void},
     sync::atomic::{AtomicU64, Ordering::SeqCst},
 };
 
+use arceos_posix_api::sys_getrandom;
+
+use crate::{ctypes, utils::e};
+
 static SEED: AtomicU64 = AtomicU64::new(0xa2ce_a2ce);
 
 /// Sets the seed for the random number generator.
@@ -28,3 +32,15 @@ pub unsafe extern "C" fn random() -> c_long {
     SEED.store(new_seed, SeqCst);
     new_seed as c_long
 }
+
+/// Fills `buf` with up to `buflen` random bytes from the kernel entropy pool.
+///
+/// Return the number of bytes filled if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getrandom(
+    buf: *mut c_void,
+    buflen: usize,
+    flags: c_uint,
+) -> ctypes::ssize_t {
+    e(sys_getrandom(buf, buflen, flags) as _) as _
+}
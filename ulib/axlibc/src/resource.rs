@@ -1,6 +1,8 @@
 use core::ffi::c_int;
 
-use arceos_posix_api::{sys_getrlimit, sys_setrlimit};
+#[cfg(feature = "multitask")]
+use arceos_posix_api::{sys_getpriority, sys_setpriority};
+use arceos_posix_api::{sys_getrlimit, sys_prlimit64, sys_setrlimit};
 
 use crate::utils::e;
 
@@ -15,3 +17,31 @@ pub unsafe extern "C" fn getrlimit(resource: c_int, rlimits: *mut crate::ctypes:
 pub unsafe extern "C" fn setrlimit(resource: c_int, rlimits: *mut crate::ctypes::rlimit) -> c_int {
     e(sys_setrlimit(resource, rlimits))
 }
+
+/// Get and set resource limitations of a process
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn prlimit(
+    pid: crate::ctypes::pid_t,
+    resource: c_int,
+    new_limit: *const crate::ctypes::rlimit,
+    old_limit: *mut crate::ctypes::rlimit,
+) -> c_int {
+    e(sys_prlimit64(pid, resource, new_limit, old_limit))
+}
+
+/// Get the scheduling priority, i.e. the nice value
+///
+/// Return -1 and set `errno` on failure, where -1 is also a valid nice value
+#[cfg(feature = "multitask")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getpriority(which: c_int, who: c_int) -> c_int {
+    let ret = sys_getpriority(which, who);
+    if ret < 0 { e(ret) } else { 20 - ret }
+}
+
+/// Set the scheduling priority, i.e. the nice value
+#[cfg(feature = "multitask")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setpriority(which: c_int, who: c_int, prio: c_int) -> c_int {
+    e(sys_setpriority(which, who, prio))
+}
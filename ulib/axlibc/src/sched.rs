@@ -0,0 +1,33 @@
+use core::ffi::c_int;
+
+use arceos_posix_api::{sys_sched_getaffinity, sys_sched_setaffinity};
+
+use crate::{ctypes, utils::e};
+
+/// Set the CPU affinity of a thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_setaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    mask: *const ctypes::cpu_set_t,
+) -> c_int {
+    e(unsafe { sys_sched_setaffinity(pid, cpusetsize, mask) })
+}
+
+/// Get the CPU affinity of a thread.
+///
+/// The bytes of `mask` beyond the CPUs of the system are zeroed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_getaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    mask: *mut ctypes::cpu_set_t,
+) -> c_int {
+    let ret = e(unsafe { sys_sched_getaffinity(pid, cpusetsize, mask) });
+    if ret < 0 {
+        return ret;
+    }
+    let len = ret as usize;
+    unsafe { (mask as *mut u8).add(len).write_bytes(0, cpusetsize - len) };
+    0
+}
@@ -0,0 +1,63 @@
+use core::ffi::c_int;
+
+use arceos_posix_api::{
+    sys_getpid, sys_kill, sys_pthread_kill, sys_rt_sigaction, sys_rt_sigprocmask, sys_tgkill,
+};
+
+use crate::{ctypes, utils::e};
+
+/// Examine and change a signal action.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sigaction(
+    signum: c_int,
+    act: *const ctypes::sigaction,
+    oldact: *mut ctypes::sigaction,
+) -> c_int {
+    e(sys_rt_sigaction(signum, act, oldact))
+}
+
+/// Examine and change blocked signals.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sigprocmask(
+    how: c_int,
+    set: *const ctypes::sigset_t,
+    oldset: *mut ctypes::sigset_t,
+) -> c_int {
+    e(sys_rt_sigprocmask(how, set, oldset))
+}
+
+/// Examine and change blocked signals of the calling thread.
+///
+/// Return 0 if succeed, or the error number on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pthread_sigmask(
+    how: c_int,
+    set: *const ctypes::sigset_t,
+    oldset: *mut ctypes::sigset_t,
+) -> c_int {
+    sys_rt_sigprocmask(how, set, oldset).wrapping_neg()
+}
+
+/// Send a signal to a process.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kill(pid: ctypes::pid_t, sig: c_int) -> c_int {
+    e(sys_kill(pid, sig))
+}
+
+/// Send a signal to the calling thread.
+///
+/// If the signal causes a handler to be called, `raise` returns after the
+/// handler has returned.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raise(sig: c_int) -> c_int {
+    let tid = sys_getpid();
+    e(sys_tgkill(tid, tid, sig))
+}
+
+/// Send a signal to a thread.
+///
+/// Return 0 if succeed, or the error number on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pthread_kill(thread: ctypes::pthread_t, sig: c_int) -> c_int {
+    unsafe { sys_pthread_kill(thread, sig) }.wrapping_neg()
+}
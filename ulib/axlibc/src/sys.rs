@@ -1,5 +1,7 @@
-use arceos_posix_api::sys_sysconf;
-use core::ffi::{c_int, c_long};
+use arceos_posix_api::{sys_personality, sys_prctl, sys_sysconf};
+use core::ffi::{c_int, c_long, c_ulong};
+
+use crate::utils::e;
 
 /// Return system configuration infomation
 ///
@@ -8,3 +10,23 @@ use core::ffi::{c_int, c_long};
 pub unsafe extern "C" fn sysconf(name: c_int) -> c_long {
     sys_sysconf(name)
 }
+
+/// Operations on a process or thread.
+///
+/// TODO: remove this function in future work
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_prctl(
+    option: c_int,
+    arg2: c_ulong,
+    arg3: c_ulong,
+    arg4: c_ulong,
+    arg5: c_ulong,
+) -> c_int {
+    e(sys_prctl(option, arg2, arg3, arg4, arg5))
+}
+
+/// Set the process execution domain.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn personality(persona: c_ulong) -> c_int {
+    e(sys_personality(persona))
+}
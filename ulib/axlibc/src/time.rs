@@ -1,5 +1,14 @@
-use arceos_posix_api::{sys_clock_gettime, sys_nanosleep};
+use arceos_posix_api::{
+    sys_clock_getres, sys_clock_gettime, sys_clock_nanosleep, sys_clock_settime, sys_nanosleep,
+};
+#[cfg(all(feature = "signal", feature = "irq"))]
+use arceos_posix_api::{
+    sys_getitimer, sys_setitimer, sys_timer_create, sys_timer_delete, sys_timer_getoverrun,
+    sys_timer_gettime, sys_timer_settime,
+};
 use core::ffi::c_int;
+#[cfg(all(feature = "signal", feature = "irq"))]
+use core::ffi::c_uint;
 
 use crate::{ctypes, utils::e};
 
@@ -9,6 +18,21 @@ pub unsafe extern "C" fn clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::
     e(sys_clock_gettime(clk, ts))
 }
 
+/// Get the resolution of a clock
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clock_getres(clk: ctypes::clockid_t, res: *mut ctypes::timespec) -> c_int {
+    e(unsafe { sys_clock_getres(clk, res) })
+}
+
+/// Set clock time
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clock_settime(
+    clk: ctypes::clockid_t,
+    ts: *const ctypes::timespec,
+) -> c_int {
+    e(sys_clock_settime(clk, ts))
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
@@ -19,3 +43,103 @@ pub unsafe extern "C" fn nanosleep(
 ) -> c_int {
     e(sys_nanosleep(req, rem))
 }
+
+/// Sleep on a clock, for a duration or until an absolute time if `flags`
+/// contains `TIMER_ABSTIME`
+///
+/// Return 0 if succeed, or the error number on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clock_nanosleep(
+    clk: ctypes::clockid_t,
+    flags: c_int,
+    req: *const ctypes::timespec,
+    rem: *mut ctypes::timespec,
+) -> c_int {
+    unsafe { sys_clock_nanosleep(clk, flags, req, rem) }.wrapping_neg()
+}
+
+/// Create a POSIX per-process timer.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn timer_create(
+    clockid: ctypes::clockid_t,
+    sevp: *mut ctypes::sigevent,
+    timerid: *mut ctypes::timer_t,
+) -> c_int {
+    e(unsafe { sys_timer_create(clockid, sevp, timerid) })
+}
+
+/// Delete a POSIX per-process timer.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn timer_delete(timerid: ctypes::timer_t) -> c_int {
+    e(sys_timer_delete(timerid))
+}
+
+/// Arm or disarm a POSIX per-process timer.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn timer_settime(
+    timerid: ctypes::timer_t,
+    flags: c_int,
+    new_value: *const ctypes::itimerspec,
+    old_value: *mut ctypes::itimerspec,
+) -> c_int {
+    e(unsafe { sys_timer_settime(timerid, flags, new_value, old_value) })
+}
+
+/// Get the setting of a POSIX per-process timer.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn timer_gettime(
+    timerid: ctypes::timer_t,
+    curr_value: *mut ctypes::itimerspec,
+) -> c_int {
+    e(unsafe { sys_timer_gettime(timerid, curr_value) })
+}
+
+/// Get the overrun count of a POSIX per-process timer.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn timer_getoverrun(timerid: ctypes::timer_t) -> c_int {
+    e(sys_timer_getoverrun(timerid))
+}
+
+/// Set an interval timer, only `ITIMER_REAL` is supported.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn setitimer(
+    which: c_int,
+    new_value: *const ctypes::itimerval,
+    old_value: *mut ctypes::itimerval,
+) -> c_int {
+    e(unsafe { sys_setitimer(which, new_value, old_value) })
+}
+
+/// Get an interval timer, only `ITIMER_REAL` is supported.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getitimer(which: c_int, curr_value: *mut ctypes::itimerval) -> c_int {
+    e(unsafe { sys_getitimer(which, curr_value) })
+}
+
+/// Send `SIGALRM` to the process after `seconds`, or cancel the alarm if it
+/// is zero.
+///
+/// Returns the seconds remaining of the previous alarm, rounded up, or zero
+/// if there was none.
+#[cfg(all(feature = "signal", feature = "irq"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn alarm(seconds: c_uint) -> c_uint {
+    let new_value = ctypes::itimerval {
+        it_interval: ctypes::timeval::default(),
+        it_value: ctypes::timeval {
+            tv_sec: seconds as _,
+            tv_usec: 0,
+        },
+    };
+    let mut old_value = ctypes::itimerval::default();
+    unsafe { sys_setitimer(ctypes::ITIMER_REAL as c_int, &new_value, &mut old_value) };
+    let old = old_value.it_value;
+    (old.tv_sec + (old.tv_usec > 0) as ctypes::time_t) as c_uint
+}
@@ -0,0 +1,14 @@
+use core::ffi::c_int;
+
+use arceos_posix_api::sys_uio_open;
+
+use crate::utils::e;
+
+/// Open a device listed in `/proc/uio` for user-space drivers, and deliver
+/// the interrupts of `irq` to it if `irq` is not negative.
+///
+/// Return the file descriptor of the device
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn uio_open(index: c_int, irq: c_int, flags: c_int) -> c_int {
+    e(sys_uio_open(index, irq, flags))
+}
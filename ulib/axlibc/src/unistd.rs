@@ -1,4 +1,6 @@
 use arceos_posix_api::{sys_exit, sys_getpid};
+#[cfg(feature = "multitask")]
+use arceos_posix_api::{sys_getpriority, sys_nice};
 use core::ffi::c_int;
 
 /// Get current thread ID.
@@ -18,3 +20,16 @@ pub unsafe extern "C" fn abort() -> ! {
 pub unsafe extern "C" fn exit(exit_code: c_int) -> ! {
     sys_exit(exit_code)
 }
+
+/// Add `inc` to the nice value of the current thread.
+///
+/// Return the new nice value, or -1 and set `errno` on failure
+#[cfg(feature = "multitask")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nice(inc: c_int) -> c_int {
+    let ret = sys_nice(inc);
+    if ret < 0 {
+        return crate::utils::e(ret);
+    }
+    20 - sys_getpriority(crate::ctypes::PRIO_PROCESS as _, 0)
+}
@@ -162,6 +162,17 @@ impl File {
     pub fn metadata(&self) -> Result<Metadata> {
         api::ax_file_attr(&self.inner).map(Metadata)
     }
+
+    /// Attempts to sync all data and metadata of the file to the disk.
+    pub fn sync_all(&self) -> Result<()> {
+        api::ax_sync_file(&self.inner, false)
+    }
+
+    /// Attempts to sync the data of the file to the disk, but not
+    /// necessarily the metadata.
+    pub fn sync_data(&self) -> Result<()> {
+        api::ax_sync_file(&self.inner, true)
+    }
 }
 
 impl Read for File {
@@ -29,16 +29,42 @@
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
+//!     - `fs-irq`: Wait for the disk by its interrupt instead of polling.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext2`: Use ext2 as the root filesystem instead of FAT.
+//!     - `tmpfs`: Use tmpfs on `/tmp`, and as the root filesystem without a disk.
+//!     - `ninep`: Mount the folders shared by the host through virtio-9p on `/mnt/<tag>`.
 //!     - `net`: Enable networking support.
+//!     - `dhcp`: Configure the network interface by DHCP.
+//!     - `net-irq`: Receive by the interrupt of the NIC instead of polling.
+//!     - `cluster`: Coordinate with other ArceOS instances by heartbeats and RPCs.
+//!     - `nbd`: Use a network block device as the root disk if there is no block device.
+//!     - `nfs`: Mount a directory exported by an NFS server on `/mnt/nfs`.
+//!     - `httpfs`: Mount the files served by an HTTP server read-only on `/mnt/http`.
+//!     - `oci`: Unpack a container image in the OCI image layout to `/` at boot.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.
+//!     - `input`: Enable input devices support.
+//!     - `hvc`: Use the virtio-console device as the console instead of the UART.
+//!     - `rng`: Seed the kernel entropy pool by the virtio-rng device.
+//!     - `power`: Enable the power supplies, e.g. the batteries.
+//!     - `led`: Enable the LEDs, e.g. the heartbeat LED.
+//!     - `clk`: Enable the clock and reset controllers.
+//!     - `pinctrl`: Route the pins of the devices by the pin controllers.
+//!     - `mbox`: Enable the mailboxes and the RPMsg devices of the co-processors.
+//!     - `ivshmem`: Enable the shared-memory rings to the host or other VMs.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
 //!     - `bus-pci`: Use PCI bus to probe all PCI devices.
+//!     - `bus-both`: Probe both the MMIO devices and the PCI devices.
 //!     - `driver-ramdisk`: Use the RAM disk to emulate the block device.
 //!     - `driver-ixgbe`: Enable the Intel 82599 10Gbit NIC driver.
 //!     - `driver-bcm2835-sdhci`: Enable the BCM2835 SDHCI driver (Raspberry Pi SD card).
+//! - Time
+//!     - `vtime`: Use a virtual clock advanced only by [`time::advance`], for the tests.
+//! - Profiling
+//!     - `mcount`: Record the call graph of the modules built with `MCOUNT=y`.
+//!     - `ksyms`: Show the backtraces of the panics by the function names.
 //! - Logging
 //!     - `log-level-off`: Disable all logging.
 //!     - `log-level-error`, `log-level-warn`, `log-level-info`, `log-level-debug`,
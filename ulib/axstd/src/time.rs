@@ -95,3 +95,12 @@ impl Sub<Instant> for Instant {
         self.duration_since(other)
     }
 }
+
+/// Advances the virtual clock by `dur`, which is otherwise frozen except
+/// when all the tasks are waiting for the timers.
+///
+/// The timers expired in `dur` are fired in order, before it returns.
+#[cfg(feature = "vtime")]
+pub fn advance(dur: Duration) {
+    arceos_api::time::ax_advance_time(dur)
+}
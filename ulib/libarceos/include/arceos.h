@@ -0,0 +1,76 @@
+#ifndef _ARCEOS_H
+#define _ARCEOS_H
+
+/*
+ * Embedding API of ArceOS, to run the kernel in a user-space process of the
+ * Linux host, e.g. for the unit tests and fuzzing of the kernel modules.
+ *
+ * The kernel is initialized by `arceos_init`, and then driven only by the
+ * calling thread, which becomes its main task. The other threads of the
+ * host must not call into it. The scheduling is cooperative, so the kernel
+ * threads run only when the main task blocks, sleeps or yields.
+ *
+ * The functions return the non-negative results on success, and the
+ * negative error numbers (e.g. `-ENOENT`) on failure, like the system calls
+ * of Linux. The flags and the structures are the same as on Linux.
+ */
+
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The version of this API, bumped on any incompatible change. */
+#define ARCEOS_ABI_VERSION 1
+
+struct arceos_config {
+    /* Size in bytes of the physical memory of the kernel, 0 for 64 MiB. */
+    size_t memory_size;
+    /* Name of the TAP interface used as the NIC, NULL for "tap0". */
+    const char *tap_ifname;
+};
+
+typedef void *arceos_thread_t;
+
+/* Returns `ARCEOS_ABI_VERSION` that the library is built with. */
+unsigned int arceos_abi_version(void);
+
+/* Initializes the kernel, with the default configuration if `config` is
+ * NULL. Returns `-EBUSY` if it is already initialized. */
+int arceos_init(const struct arceos_config *config);
+
+/* Threads */
+int arceos_thread_create(arceos_thread_t *thread, void *(*entry)(void *), void *arg);
+int arceos_thread_join(arceos_thread_t thread, void **retval);
+int arceos_yield(void);
+int arceos_sleep(uint64_t nanos);
+
+/* Virtual clock, only if built with the `vtime` feature */
+int arceos_advance_time(uint64_t nanos);
+
+/* Files, on a tmpfs as the root filesystem */
+int arceos_open(const char *path, int flags, mode_t mode);
+ssize_t arceos_read(int fd, void *buf, size_t count);
+ssize_t arceos_write(int fd, const void *buf, size_t count);
+int arceos_close(int fd);
+int arceos_mkdir(const char *path, mode_t mode);
+int arceos_unlink(const char *path);
+
+/* Sockets, only if built with the `net` feature */
+int arceos_socket(int domain, int type, int protocol);
+int arceos_bind(int fd, const struct sockaddr *addr, socklen_t addrlen);
+int arceos_connect(int fd, const struct sockaddr *addr, socklen_t addrlen);
+int arceos_listen(int fd, int backlog);
+int arceos_accept(int fd, struct sockaddr *addr, socklen_t *addrlen);
+ssize_t arceos_send(int fd, const void *buf, size_t len, int flags);
+ssize_t arceos_recv(int fd, void *buf, size_t len, int flags);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* _ARCEOS_H */
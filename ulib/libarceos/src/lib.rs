@@ -0,0 +1,250 @@
+//! The embedding API of [ArceOS], to run the kernel as a user-space process on
+//! Linux, for fast unit testing and fuzzing of the kernel modules without an
+//! emulator, like the user-mode Linux.
+//!
+//! It is built as a static library with a stable C ABI, declared in
+//! `include/arceos.h`, whose version is [`ARCEOS_ABI_VERSION`]. The kernel is
+//! built for the `linux-host` platform of `axhal`:
+//!
+//! - The physical memory is a block of the heap of the process.
+//! - The console is the standard input and output of the process.
+//! - There are no interrupts and only one CPU, so the scheduling is
+//!   cooperative: the kernel threads run only when the main task blocks,
+//!   sleeps or yields.
+//! - The root filesystem is a tmpfs, as there are no disks.
+//! - The NIC is a TAP device of the host, with the `net` feature. The address
+//!   of the kernel is set by `AX_IP` and `AX_GW` at build time, as usual.
+//!
+//! The functions return the non-negative results on success, and the negative
+//! error numbers on failure, like the system calls of Linux.
+//!
+//! # Cargo Features
+//!
+//! - `fs`: Enable the file operations (enabled by default).
+//! - `net`: Enable the socket operations, the TAP interface must be created
+//!   on the host before [`arceos_init`].
+//! - `vtime`: Use a virtual clock starting at 0, which advances only by
+//!   [`arceos_advance_time`], for the deterministic tests of the timeouts. A
+//!   sleep never ends if no one advances the clock.
+//!
+//! [ArceOS]: https://github.com/arceos-org/arceos
+
+use core::ffi::{c_char, c_int, c_uint, c_void};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::LinuxError;
+
+/// The version of the C ABI, bumped on any incompatible change.
+pub const ARCEOS_ABI_VERSION: c_uint = 1;
+
+/// The configuration of the kernel, `struct arceos_config`.
+#[repr(C)]
+pub struct ArceosConfig {
+    /// The size in bytes of the physical memory, or 0 for the default.
+    pub memory_size: usize,
+    /// The name of the TAP interface, or NULL for `tap0`.
+    pub tap_ifname: *const c_char,
+}
+
+static INITED: AtomicBool = AtomicBool::new(false);
+
+/// Returns the version of the C ABI that the library is built with.
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_abi_version() -> c_uint {
+    ARCEOS_ABI_VERSION
+}
+
+/// Initializes the kernel, with the default configuration if `config` is
+/// NULL.
+///
+/// The calling thread becomes the main task of the kernel, and must be the
+/// only thread of the host calling into it afterwards. Returns `-EBUSY` if
+/// the kernel is already initialized.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_init(config: *const ArceosConfig) -> c_int {
+    if INITED.swap(true, Ordering::AcqRel) {
+        return -LinuxError::EBUSY.code();
+    }
+    if let Some(config) = unsafe { config.as_ref() } {
+        // SAFETY: the kernel, which reads them, is not running yet.
+        unsafe {
+            if config.memory_size != 0 {
+                std::env::set_var("AX_HOST_MEMORY", config.memory_size.to_string());
+            }
+            if !config.tap_ifname.is_null() {
+                let ifname = core::ffi::CStr::from_ptr(config.tap_ifname);
+                match ifname.to_str() {
+                    Ok(ifname) => std::env::set_var("AX_TAP_IFNAME", ifname),
+                    Err(_) => {
+                        INITED.store(false, Ordering::Release);
+                        return -LinuxError::EINVAL.code();
+                    }
+                }
+            }
+        }
+    }
+    axruntime::init_host();
+    0
+}
+
+/// Creates a kernel thread running `entry(arg)`, and stores its handle in
+/// `thread`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_thread_create(
+    thread: *mut ctypes::pthread_t,
+    entry: extern "C" fn(arg: *mut c_void) -> *mut c_void,
+    arg: *mut c_void,
+) -> c_int {
+    unsafe { api::sys_pthread_create(thread, core::ptr::null(), entry, arg) }
+}
+
+/// Waits for the kernel thread `thread` to exit, and stores its return value
+/// in `retval` if it is not NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_thread_join(
+    thread: ctypes::pthread_t,
+    retval: *mut *mut c_void,
+) -> c_int {
+    unsafe { api::sys_pthread_join(thread, retval) }
+}
+
+/// Yields the CPU to the other kernel threads.
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_yield() -> c_int {
+    api::sys_sched_yield()
+}
+
+/// Sleeps for `nanos` nanoseconds, running the other kernel threads.
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_sleep(nanos: u64) -> c_int {
+    let req = ctypes::timespec {
+        tv_sec: (nanos / 1_000_000_000) as _,
+        tv_nsec: (nanos % 1_000_000_000) as _,
+    };
+    unsafe { api::sys_nanosleep(&req, core::ptr::null_mut()) }
+}
+
+/// Advances the virtual clock by `nanos` nanoseconds, for the kernel threads
+/// sleeping or waiting with timeouts to see the new time.
+#[cfg(feature = "vtime")]
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_advance_time(nanos: u64) -> c_int {
+    axhal::time::advance(core::time::Duration::from_nanos(nanos));
+    0
+}
+
+/// Opens the file `path`.
+#[cfg(feature = "fs")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_open(
+    path: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    api::sys_open(path, flags, mode)
+}
+
+/// Reads at most `count` bytes from the file descriptor `fd` into `buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_read(fd: c_int, buf: *mut c_void, count: usize) -> isize {
+    api::sys_read(fd, buf, count) as _
+}
+
+/// Writes `count` bytes in `buf` to the file descriptor `fd`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_write(fd: c_int, buf: *const c_void, count: usize) -> isize {
+    api::sys_write(fd, buf, count) as _
+}
+
+/// Closes the file descriptor `fd`.
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_close(fd: c_int) -> c_int {
+    api::sys_close(fd)
+}
+
+/// Creates the directory `path`.
+#[cfg(feature = "fs")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    api::sys_mkdir(path, mode)
+}
+
+/// Removes the file `path`.
+#[cfg(feature = "fs")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_unlink(path: *const c_char) -> c_int {
+    api::sys_unlink(path)
+}
+
+/// Creates a socket.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
+    api::sys_socket(domain, socktype, protocol)
+}
+
+/// Binds the socket `fd` to the address `addr`.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_bind(
+    fd: c_int,
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> c_int {
+    api::sys_bind(fd, addr, addrlen)
+}
+
+/// Connects the socket `fd` to the address `addr`.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_connect(
+    fd: c_int,
+    addr: *const ctypes::sockaddr,
+    addrlen: ctypes::socklen_t,
+) -> c_int {
+    api::sys_connect(fd, addr, addrlen)
+}
+
+/// Listens for the connections on the socket `fd`.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub extern "C" fn arceos_listen(fd: c_int, backlog: c_int) -> c_int {
+    api::sys_listen(fd, backlog)
+}
+
+/// Accepts a connection on the listening socket `fd`, and stores the address
+/// of the peer in `addr` if it is not NULL.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_accept(
+    fd: c_int,
+    addr: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) -> c_int {
+    unsafe { api::sys_accept(fd, addr, addrlen) }
+}
+
+/// Sends `len` bytes in `buf` on the socket `fd`.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_send(
+    fd: c_int,
+    buf: *const c_void,
+    len: usize,
+    flags: c_int,
+) -> isize {
+    api::sys_send(fd, buf, len, flags) as _
+}
+
+/// Receives at most `len` bytes from the socket `fd` into `buf`.
+#[cfg(feature = "net")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn arceos_recv(
+    fd: c_int,
+    buf: *mut c_void,
+    len: usize,
+    flags: c_int,
+) -> isize {
+    api::sys_recv(fd, buf, len, flags) as _
+}